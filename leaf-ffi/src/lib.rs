@@ -128,7 +128,8 @@ pub unsafe extern "C" fn leaf_run_with_config_string(rt_id: u16, config: *const
     }
 }
 
-/// Reloads DNS servers, outbounds and routing rules from the config file.
+/// Reloads DNS servers, outbounds, routing rules and inbounds from the config
+/// file, only the parts which changed are rebuilt.
 ///
 /// @param rt_id The ID of the leaf instance to reload.
 ///
@@ -141,7 +142,37 @@ pub extern "C" fn leaf_reload(rt_id: u16) -> i32 {
     ERR_OK
 }
 
-/// Shuts down leaf.
+/// Reloads DNS servers, outbounds, routing rules and inbounds from the config
+/// file, only the parts which changed are rebuilt.
+///
+/// @param rt_id The ID of the leaf instance to reload.
+/// @param full Pointer to store whether sessions were closed by the reload,
+///             those through outbounds which were replaced or removed, or the
+///             UDP ones of inbounds which were restarted. May be null.
+/// @return Returns ERR_OK on success.
+#[no_mangle]
+pub unsafe extern "C" fn leaf_reload_ex(rt_id: u16, full: *mut bool) -> i32 {
+    match leaf::reload(rt_id) {
+        Ok(kind) => {
+            if !full.is_null() {
+                unsafe { *full = kind == leaf::ReloadKind::Full };
+            }
+            ERR_OK
+        }
+        Err(e) => to_errno(e),
+    }
+}
+
+/// Notifies leaf that the network has changed, e.g. the device switched from
+/// Wi-Fi to cellular, so QUIC connections migrate to a new socket.
+#[no_mangle]
+pub extern "C" fn leaf_notify_network_change() {
+    leaf::notify_network_change();
+}
+
+/// Shuts down leaf. New sessions are refused and the active ones have up to
+/// SHUTDOWN_TIMEOUT seconds to end, calling it again closes them without
+/// waiting.
 ///
 /// @param rt_id The ID of the leaf instance to reload.
 ///
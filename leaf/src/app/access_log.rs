@@ -0,0 +1,451 @@
+//! Access log, a line for each session relayed.
+//!
+//! Disabled unless the `accessLog` log option is `console` or a file. Lines
+//! are handed to a writer thread, and dropped if it falls behind, so that
+//! logging never stalls the sessions.
+
+use std::fmt::Write as _;
+use std::fs::OpenOptions;
+use std::io::{self, Write};
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use futures::task::{Context, Poll};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tracing::{info, warn};
+use tracing_appender::non_blocking::{NonBlocking, WorkerGuard};
+
+use crate::config;
+use crate::proxy::*;
+use crate::session::{Session, SocksAddr};
+
+lazy_static::lazy_static! {
+    static ref ACCESS_LOG: RwLock<Option<Arc<AccessLog>>> = RwLock::new(None);
+}
+
+// The placeholders of a plain format.
+const FIELDS: [&str; 12] = [
+    "time",
+    "event",
+    "network",
+    "inbound",
+    "source",
+    "destination",
+    "sniffed",
+    "rule",
+    "outbound",
+    "up",
+    "down",
+    "duration",
+];
+
+#[derive(Debug, Clone, PartialEq)]
+enum Format {
+    Json,
+    // A template of placeholders such as `{destination}`.
+    Plain(String),
+}
+
+impl Format {
+    fn parse(format: &str) -> Self {
+        match format {
+            "" | "json" => Format::Json,
+            _ => Format::Plain(format.to_string()),
+        }
+    }
+}
+
+enum Value {
+    Null,
+    Number(u64),
+    String(String),
+}
+
+fn json_string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+// A session as logged.
+struct Entry<'a> {
+    time: chrono::DateTime<chrono::Utc>,
+    sess: &'a Session,
+    rule: Option<usize>,
+    // The bytes sent and received and the duration, once the session ended.
+    end: Option<(u64, u64, Duration)>,
+}
+
+impl Entry<'_> {
+    fn value(&self, field: &str) -> Value {
+        let sess = self.sess;
+        match field {
+            "time" => Value::String(
+                self.time
+                    .to_rfc3339_opts(chrono::SecondsFormat::Millis, true),
+            ),
+            "event" => Value::String(if self.end.is_some() { "end" } else { "start" }.to_string()),
+            "network" => Value::String(sess.network.to_string()),
+            "inbound" => Value::String(sess.inbound_tag.clone()),
+            "source" => Value::String(sess.source.to_string()),
+            "destination" => Value::String(sess.destination.to_string()),
+            "sniffed" => sess
+                .tls_sniffed_domain
+                .as_ref()
+                .or(sess.http_sniffed_domain.as_ref())
+                .or(sess.dns_sniffed_domain.as_ref())
+                .map_or(Value::Null, |x| Value::String(x.clone())),
+            "rule" => self.rule.map_or(Value::Null, |x| Value::Number(x as u64)),
+            "outbound" => Value::String(match sess.group_actor.lock().unwrap().as_ref() {
+                Some(actor) => format!("{}({})", &sess.outbound_tag, actor),
+                None => sess.outbound_tag.clone(),
+            }),
+            "up" => self.end.map_or(Value::Null, |x| Value::Number(x.0)),
+            "down" => self.end.map_or(Value::Null, |x| Value::Number(x.1)),
+            "duration" => self
+                .end
+                .map_or(Value::Null, |x| Value::Number(x.2.as_millis() as u64)),
+            _ => Value::Null,
+        }
+    }
+
+    fn format(&self, format: &Format) -> String {
+        match format {
+            Format::Json => {
+                let fields: Vec<String> = FIELDS
+                    .iter()
+                    .map(|field| {
+                        let value = match self.value(field) {
+                            Value::Null => "null".to_string(),
+                            Value::Number(n) => n.to_string(),
+                            Value::String(s) => json_string(&s),
+                        };
+                        format!("\"{}\":{}", field, value)
+                    })
+                    .collect();
+                format!("{{{}}}", fields.join(","))
+            }
+            Format::Plain(template) => {
+                let mut line = template.clone();
+                for field in FIELDS {
+                    let placeholder = format!("{{{}}}", field);
+                    if line.contains(&placeholder) {
+                        let value = match self.value(field) {
+                            Value::Null => "-".to_string(),
+                            Value::Number(n) => n.to_string(),
+                            Value::String(s) => s,
+                        };
+                        line = line.replace(&placeholder, &value);
+                    }
+                }
+                line
+            }
+        }
+    }
+}
+
+fn open_writer(output: &str) -> io::Result<(NonBlocking, WorkerGuard)> {
+    if output == "console" {
+        return Ok(tracing_appender::non_blocking(io::stdout()));
+    }
+    let file = OpenOptions::new().create(true).append(true).open(output)?;
+    Ok(tracing_appender::non_blocking(file))
+}
+
+pub struct AccessLog {
+    output: String,
+    format: Format,
+    log_start: bool,
+    writer: Mutex<(NonBlocking, WorkerGuard)>,
+}
+
+impl AccessLog {
+    fn new(config: &config::Log) -> io::Result<Self> {
+        Ok(AccessLog {
+            output: config.access_log.clone(),
+            format: Format::parse(&config.access_log_format),
+            log_start: config.access_log_start,
+            writer: Mutex::new(open_writer(&config.access_log)?),
+        })
+    }
+
+    fn is_same(&self, config: &config::Log) -> bool {
+        self.output == config.access_log
+            && self.format == Format::parse(&config.access_log_format)
+            && self.log_start == config.access_log_start
+    }
+
+    fn write(&self, entry: &Entry) {
+        let mut line = entry.format(&self.format);
+        line.push('\n');
+        let _ = self.writer.lock().unwrap().0.write_all(line.as_bytes());
+    }
+
+    fn reopen(&self) {
+        if self.output == "console" {
+            return;
+        }
+        match open_writer(&self.output) {
+            // The previous writer is flushed as it's dropped.
+            Ok(writer) => *self.writer.lock().unwrap() = writer,
+            Err(e) => warn!("reopen access log {} failed: {}", &self.output, e),
+        }
+    }
+
+    fn session(self: &Arc<Self>, sess: &Session, rule: Option<usize>) -> Arc<SessionLog> {
+        if self.log_start {
+            self.write(&Entry {
+                time: chrono::Utc::now(),
+                sess,
+                rule,
+                end: None,
+            });
+        }
+        Arc::new(SessionLog {
+            log: self.clone(),
+            sess: sess.clone(),
+            rule,
+            start: Instant::now(),
+            up: AtomicU64::new(0),
+            down: AtomicU64::new(0),
+        })
+    }
+
+    /// Wraps the outbound stream of a session, to log the session when the
+    /// stream is dropped.
+    pub fn stream(
+        self: &Arc<Self>,
+        stream: AnyStream,
+        sess: &Session,
+        rule: Option<usize>,
+    ) -> AnyStream {
+        Box::new(LoggedStream {
+            inner: stream,
+            log: self.session(sess, rule),
+        })
+    }
+
+    /// Wraps the outbound datagram of a session, to log the session when
+    /// both halves of the datagram are dropped.
+    pub fn datagram(
+        self: &Arc<Self>,
+        datagram: AnyOutboundDatagram,
+        sess: &Session,
+        rule: Option<usize>,
+    ) -> AnyOutboundDatagram {
+        Box::new(LoggedDatagram {
+            inner: datagram,
+            log: self.session(sess, rule),
+        })
+    }
+}
+
+/// Enables the access log of a log config, or disables it if none is
+/// configured. A running access log of the same settings is kept.
+pub fn setup(config: &config::Log) {
+    let mut access_log = ACCESS_LOG.write().unwrap();
+    if config.access_log.is_empty() {
+        *access_log = None;
+        return;
+    }
+    if access_log.as_ref().is_some_and(|x| x.is_same(config)) {
+        return;
+    }
+    *access_log = match AccessLog::new(config) {
+        Ok(x) => {
+            info!("access log to {}", &config.access_log);
+            Some(Arc::new(x))
+        }
+        Err(e) => {
+            warn!(
+                "open access log {} failed, access logging disabled: {}",
+                &config.access_log, e
+            );
+            None
+        }
+    };
+}
+
+/// Returns the access log, if enabled.
+pub fn get() -> Option<Arc<AccessLog>> {
+    ACCESS_LOG.read().unwrap().clone()
+}
+
+/// Reopens the file of the access log, after it has been rotated.
+pub fn reopen() {
+    if let Some(access_log) = get() {
+        access_log.reopen();
+    }
+}
+
+// Logs a session when it ends.
+struct SessionLog {
+    log: Arc<AccessLog>,
+    sess: Session,
+    rule: Option<usize>,
+    start: Instant,
+    up: AtomicU64,
+    down: AtomicU64,
+}
+
+impl Drop for SessionLog {
+    fn drop(&mut self) {
+        self.log.write(&Entry {
+            time: chrono::Utc::now(),
+            sess: &self.sess,
+            rule: self.rule,
+            end: Some((
+                self.up.load(Ordering::Relaxed),
+                self.down.load(Ordering::Relaxed),
+                self.start.elapsed(),
+            )),
+        });
+    }
+}
+
+struct LoggedStream {
+    inner: AnyStream,
+    log: Arc<SessionLog>,
+}
+
+impl AsyncRead for LoggedStream {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context,
+        buf: &mut ReadBuf,
+    ) -> Poll<io::Result<()>> {
+        let filled = buf.filled().len();
+        let res = Pin::new(&mut self.inner).poll_read(cx, buf);
+        let n = buf.filled().len() - filled;
+        self.log.down.fetch_add(n as u64, Ordering::Relaxed);
+        res
+    }
+}
+
+impl AsyncWrite for LoggedStream {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let res = Pin::new(&mut self.inner).poll_write(cx, buf);
+        if let Poll::Ready(Ok(n)) = res {
+            self.log.up.fetch_add(n as u64, Ordering::Relaxed);
+        }
+        res
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+}
+
+struct LoggedDatagram {
+    inner: AnyOutboundDatagram,
+    log: Arc<SessionLog>,
+}
+
+impl OutboundDatagram for LoggedDatagram {
+    fn split(
+        self: Box<Self>,
+    ) -> (
+        Box<dyn OutboundDatagramRecvHalf>,
+        Box<dyn OutboundDatagramSendHalf>,
+    ) {
+        let (r, s) = self.inner.split();
+        (
+            Box::new(LoggedRecvHalf(r, self.log.clone())),
+            Box::new(LoggedSendHalf(s, self.log)),
+        )
+    }
+}
+
+struct LoggedRecvHalf(Box<dyn OutboundDatagramRecvHalf>, Arc<SessionLog>);
+
+#[async_trait]
+impl OutboundDatagramRecvHalf for LoggedRecvHalf {
+    async fn recv_from(&mut self, buf: &mut [u8]) -> io::Result<(usize, SocksAddr)> {
+        let res = self.0.recv_from(buf).await;
+        if let Ok((n, _)) = res.as_ref() {
+            self.1.down.fetch_add(*n as u64, Ordering::Relaxed);
+        }
+        res
+    }
+}
+
+struct LoggedSendHalf(Box<dyn OutboundDatagramSendHalf>, Arc<SessionLog>);
+
+#[async_trait]
+impl OutboundDatagramSendHalf for LoggedSendHalf {
+    async fn send_to(&mut self, buf: &[u8], target: &SocksAddr) -> io::Result<usize> {
+        let res = self.0.send_to(buf, target).await;
+        if let Ok(n) = res.as_ref() {
+            self.1.up.fetch_add(*n as u64, Ordering::Relaxed);
+        }
+        res
+    }
+
+    async fn close(&mut self) -> io::Result<()> {
+        self.0.close().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use crate::session::Network;
+
+    use super::*;
+
+    #[test]
+    fn test_format() {
+        let sess = Session {
+            network: Network::Tcp,
+            source: "127.0.0.1:5000".parse().unwrap(),
+            destination: SocksAddr::try_from(("1.2.3.4", 443)).unwrap(),
+            inbound_tag: "socks".to_string(),
+            outbound_tag: "proxy\"1".to_string(),
+            tls_sniffed_domain: Some("example.com".to_string()),
+            ..Default::default()
+        };
+        let mut entry = Entry {
+            time: chrono::Utc.with_ymd_and_hms(2024, 1, 2, 3, 4, 5).unwrap(),
+            sess: &sess,
+            rule: Some(2),
+            end: None,
+        };
+        assert_eq!(
+            entry.format(&Format::Json),
+            "{\"time\":\"2024-01-02T03:04:05.000Z\",\"event\":\"start\",\"network\":\"tcp\",\
+             \"inbound\":\"socks\",\"source\":\"127.0.0.1:5000\",\"destination\":\"1.2.3.4:443\",\
+             \"sniffed\":\"example.com\",\"rule\":2,\"outbound\":\"proxy\\\"1\",\"up\":null,\
+             \"down\":null,\"duration\":null}"
+        );
+        entry.rule = None;
+        entry.end = Some((10, 20, Duration::from_millis(1500)));
+        let plain = Format::parse("{inbound} {destination} {rule} {up}/{down} {duration}ms {x}");
+        assert_eq!(entry.format(&plain), "socks 1.2.3.4:443 - 10/20 1500ms {x}");
+    }
+}
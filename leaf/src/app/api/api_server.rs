@@ -1,4 +1,4 @@
-use std::collections::HashSet;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::convert::Infallible;
 use std::iter::FromIterator;
 use std::net::{IpAddr, SocketAddr};
@@ -7,18 +7,20 @@ use std::sync::Arc;
 use chrono::{Local, TimeZone};
 
 use axum::{
-    extract::{Path, State},
-    http::StatusCode,
-    response::{Html, Json},
-    routing::{get, post},
+    extract::{Path, Query, Request, State},
+    http::{header, HeaderValue, Method, StatusCode},
+    middleware::{self, Next},
+    response::{Html, IntoResponse, Json, Response},
+    routing::{delete, get, post},
     Router,
 };
 use tracing::info;
 
-#[cfg(feature = "outbound-select")]
-use axum::extract::Query;
-
-use crate::RuntimeManager;
+use crate::{
+    app::stat_manager::Traffic,
+    session::{Network, Session, SocksAddr},
+    RuntimeManager,
+};
 
 mod models {
     use serde_derive::{Deserialize, Serialize};
@@ -34,6 +36,30 @@ mod models {
     #[derive(Debug, Serialize, Deserialize)]
     pub struct SelectReply {
         pub selected: Option<String>,
+        // The outbound in use, which differs from the selected one while the
+        // group falls back from it.
+        pub active: Option<String>,
+    }
+
+    #[cfg(feature = "outbound-urltest")]
+    #[derive(Debug, Deserialize)]
+    pub struct UrlTestOptions {
+        pub outbound: Option<String>,
+    }
+
+    #[cfg(feature = "outbound-urltest")]
+    #[derive(Debug, Serialize, Deserialize)]
+    pub struct UrlTestLatency {
+        pub tag: String,
+        // None if the check failed or hasn't run yet.
+        pub latency_ms: Option<u64>,
+    }
+
+    #[cfg(feature = "outbound-urltest")]
+    #[derive(Debug, Serialize, Deserialize)]
+    pub struct UrlTestReply {
+        pub selected: Option<String>,
+        pub latencies: Vec<UrlTestLatency>,
     }
 
     #[derive(Debug, Serialize, Deserialize)]
@@ -52,6 +78,37 @@ mod models {
         pub dns_sniffed_domain: Option<String>,
         pub tls_sniffed_domain: Option<String>,
         pub http_sniffed_domain: Option<String>,
+        pub sniffed_protocol: Option<String>,
+    }
+
+    #[derive(Debug, Serialize, Deserialize)]
+    pub struct Connection {
+        pub id: u64,
+        pub network: String,
+        pub inbound_tag: String,
+        pub source: String,
+        pub destination: String,
+        pub outbound_tag: String,
+        pub bytes_up: u64,
+        pub bytes_down: u64,
+        pub start_time: u32,
+    }
+
+    #[derive(Debug, Serialize, Deserialize)]
+    pub struct QuicPathStat {
+        pub tag: String,
+        pub remote_addr: String,
+        pub rtt_ms: u64,
+        pub lost_packets: u64,
+        pub congestion_events: u64,
+        pub cwnd: u64,
+        pub update_time: u32,
+    }
+
+    #[derive(Debug, Serialize, Deserialize)]
+    pub struct Traffic {
+        pub up: u64,
+        pub down: u64,
     }
 
     #[derive(Debug, Serialize, Deserialize)]
@@ -72,6 +129,97 @@ mod models {
         pub tcp_ms: Option<u128>,
         pub udp_ms: Option<u128>,
     }
+
+    #[cfg(feature = "outbound-failover")]
+    #[derive(Debug, Deserialize)]
+    pub struct DelayOptions {
+        // An http:// or https:// URL, or a tcp:// or tls:// host and port, a
+        // bare host and port is connected to with a TLS handshake.
+        pub url: Option<String>,
+        // In milliseconds.
+        pub timeout: Option<u64>,
+    }
+
+    #[cfg(feature = "outbound-failover")]
+    #[derive(Debug, Serialize, Deserialize)]
+    pub struct Delay {
+        pub tag: String,
+        pub delay_ms: u64,
+        // The actor a group outbound tested.
+        pub actor: Option<String>,
+    }
+
+    #[derive(Debug, Serialize, Deserialize)]
+    pub struct RuleProviderRefresh {
+        pub name: String,
+        pub rules: usize,
+    }
+
+    #[derive(Debug, Serialize, Deserialize)]
+    pub struct GeoipUpdate {
+        pub bytes: usize,
+    }
+
+    #[derive(Debug, Serialize, Deserialize)]
+    pub struct RuleHits {
+        pub index: usize,
+        pub target: String,
+        pub hits: u64,
+    }
+
+    #[derive(Debug, Serialize, Deserialize)]
+    pub struct RouterRules {
+        // The rules section of a JSON config, none if the rules are from a
+        // config of another format.
+        pub rules: Option<serde_json::Value>,
+        // Whether the rules are those of the config file.
+        pub persistent: bool,
+        pub hits: Vec<RuleHits>,
+    }
+
+    #[cfg(feature = "config-json")]
+    #[derive(Debug, Serialize, Deserialize)]
+    pub struct ReplaceRulesOptions {
+        pub persist: Option<bool>,
+    }
+
+    #[cfg(feature = "config-json")]
+    #[derive(Debug, Serialize, Deserialize)]
+    pub struct ReplaceRules {
+        pub rules: usize,
+        pub persistent: bool,
+    }
+
+    #[derive(Debug, Default, Serialize, Deserialize)]
+    pub struct ExplainOptions {
+        // The destination as host:port.
+        pub dest: Option<String>,
+        pub src: Option<String>,
+        // tcp or udp, tcp if not given.
+        pub network: Option<String>,
+        pub inbound: Option<String>,
+        pub process: Option<String>,
+        // A sniffed protocol the rules see.
+        pub protocol: Option<String>,
+    }
+
+    #[derive(Debug, Serialize, Deserialize)]
+    pub struct Explain {
+        // The destination after the fake IP and DNS sniffed domains.
+        pub destination: String,
+        // The position of the rule, none if no rule matched.
+        pub rule: Option<usize>,
+        pub outbound: String,
+    }
+
+    #[cfg(feature = "outbound-failover")]
+    #[derive(Debug, Serialize, Deserialize)]
+    pub struct DelayError {
+        pub tag: String,
+        // One of not_found, invalid_url, timeout and failed.
+        pub error: String,
+        pub message: String,
+    }
 }
 
 mod handlers {
@@ -107,10 +255,14 @@ mod handlers {
             if let Ok(selected) = rm.get_outbound_selected(&outbound).await {
                 return Ok(Json(models::SelectReply {
                     selected: Some(selected),
+                    active: rm.get_outbound_active(&outbound).await.ok(),
                 }));
             }
         }
-        Ok(Json(models::SelectReply { selected: None }))
+        Ok(Json(models::SelectReply {
+            selected: None,
+            active: None,
+        }))
     }
 
     #[cfg(feature = "outbound-select")]
@@ -130,6 +282,31 @@ mod handlers {
         Ok(Json(Vec::new()))
     }
 
+    #[cfg(feature = "outbound-urltest")]
+    pub async fn url_test_get(
+        Query(opts): Query<models::UrlTestOptions>,
+        State(rm): State<Arc<RuntimeManager>>,
+    ) -> Result<Json<models::UrlTestReply>, Infallible> {
+        if let Some(outbound) = opts.outbound {
+            if let Ok((selected, latencies)) = rm.get_outbound_url_test(&outbound).await {
+                return Ok(Json(models::UrlTestReply {
+                    selected: Some(selected),
+                    latencies: latencies
+                        .into_iter()
+                        .map(|(tag, latency)| models::UrlTestLatency {
+                            tag,
+                            latency_ms: latency.map(|x| x.as_millis() as u64),
+                        })
+                        .collect(),
+                }));
+            }
+        }
+        Ok(Json(models::UrlTestReply {
+            selected: None,
+            latencies: Vec::new(),
+        }))
+    }
+
     pub async fn runtime_reload(
         State(rm): State<Arc<RuntimeManager>>,
     ) -> Result<StatusCode, Infallible> {
@@ -172,6 +349,7 @@ mod handlers {
                 dns_sniffed_domain: c.sess.dns_sniffed_domain.clone(),
                 tls_sniffed_domain: c.sess.tls_sniffed_domain.clone(),
                 http_sniffed_domain: c.sess.http_sniffed_domain.clone(),
+                sniffed_protocol: c.sess.sniffed_protocol.map(str::to_owned),
             });
         }
         Ok(Json(stats))
@@ -199,11 +377,196 @@ mod handlers {
                 dns_sniffed_domain: c.sess.dns_sniffed_domain.clone(),
                 tls_sniffed_domain: c.sess.tls_sniffed_domain.clone(),
                 http_sniffed_domain: c.sess.http_sniffed_domain.clone(),
+                sniffed_protocol: c.sess.sniffed_protocol.map(str::to_owned),
             });
         }
         Ok(Json(stats))
     }
 
+    pub async fn connections(
+        State(rm): State<Arc<RuntimeManager>>,
+    ) -> Result<Json<Vec<models::Connection>>, Infallible> {
+        let sm = rm.stat_manager();
+        let sm = sm.read().await;
+        let mut conns: Vec<_> = sm
+            .counters
+            .values()
+            .map(|c| models::Connection {
+                id: c.id,
+                network: c.sess.network.to_string(),
+                inbound_tag: c.sess.inbound_tag.to_owned(),
+                source: c.sess.source.to_string(),
+                destination: c.sess.destination.to_string(),
+                outbound_tag: c.sess.outbound_tag.to_owned(),
+                bytes_up: c.bytes_sent(),
+                bytes_down: c.bytes_recvd(),
+                start_time: c.start_time(),
+            })
+            .collect();
+        conns.sort_by_key(|c| c.id);
+        Ok(Json(conns))
+    }
+
+    pub async fn connection_kill(
+        Path(id): Path<u64>,
+        State(rm): State<Arc<RuntimeManager>>,
+    ) -> Result<StatusCode, Infallible> {
+        if rm.stat_manager().read().await.kill(id) {
+            Ok(StatusCode::OK)
+        } else {
+            Ok(StatusCode::NOT_FOUND)
+        }
+    }
+
+    pub async fn stat_quic_json(
+        State(rm): State<Arc<RuntimeManager>>,
+    ) -> Result<Json<Vec<models::QuicPathStat>>, Infallible> {
+        let sm = rm.stat_manager();
+        let stats = sm
+            .read()
+            .await
+            .quic_path_stats()
+            .into_iter()
+            .map(|s| models::QuicPathStat {
+                tag: s.tag,
+                remote_addr: s.remote_addr.to_string(),
+                rtt_ms: s.rtt_ms,
+                lost_packets: s.lost_packets,
+                congestion_events: s.congestion_events,
+                cwnd: s.cwnd,
+                update_time: s.update_time,
+            })
+            .collect();
+        Ok(Json(stats))
+    }
+
+    fn traffic_reply(traffic: &HashMap<String, Arc<Traffic>>) -> BTreeMap<String, models::Traffic> {
+        traffic
+            .iter()
+            .map(|(tag, t)| {
+                (
+                    tag.clone(),
+                    models::Traffic {
+                        up: t.up(),
+                        down: t.down(),
+                    },
+                )
+            })
+            .collect()
+    }
+
+    pub async fn stat_inbound_traffic(
+        State(rm): State<Arc<RuntimeManager>>,
+    ) -> Result<Json<BTreeMap<String, models::Traffic>>, Infallible> {
+        let sm = rm.stat_manager();
+        let sm = sm.read().await;
+        Ok(Json(traffic_reply(&sm.inbound_traffic)))
+    }
+
+    pub async fn stat_outbound_traffic(
+        State(rm): State<Arc<RuntimeManager>>,
+    ) -> Result<Json<BTreeMap<String, models::Traffic>>, Infallible> {
+        let sm = rm.stat_manager();
+        let sm = sm.read().await;
+        Ok(Json(traffic_reply(&sm.outbound_traffic)))
+    }
+
+    pub async fn stat_traffic_reset(
+        State(rm): State<Arc<RuntimeManager>>,
+    ) -> Result<StatusCode, Infallible> {
+        rm.stat_manager().read().await.reset_traffic();
+        Ok(StatusCode::OK)
+    }
+
+    pub async fn dns_cache_flush(
+        State(rm): State<Arc<RuntimeManager>>,
+    ) -> Result<StatusCode, Infallible> {
+        rm.flush_dns_cache().await;
+        Ok(StatusCode::OK)
+    }
+
+    pub async fn rule_provider_refresh(
+        Path(name): Path<String>,
+        State(rm): State<Arc<RuntimeManager>>,
+    ) -> Response {
+        match rm.refresh_rule_provider(&name).await {
+            Ok(Ok(rules)) => Json(models::RuleProviderRefresh { name, rules }).into_response(),
+            Ok(Err(e)) => (StatusCode::BAD_GATEWAY, e.to_string()).into_response(),
+            Err(e) => (StatusCode::NOT_FOUND, e.to_string()).into_response(),
+        }
+    }
+
+    pub async fn geoip_update(State(rm): State<Arc<RuntimeManager>>) -> Response {
+        match rm.update_geoip().await {
+            Ok(Ok(bytes)) => Json(models::GeoipUpdate { bytes }).into_response(),
+            Ok(Err(e)) => (StatusCode::BAD_GATEWAY, e.to_string()).into_response(),
+            Err(e) => (StatusCode::NOT_FOUND, e.to_string()).into_response(),
+        }
+    }
+
+    pub async fn router_rules(
+        State(rm): State<Arc<RuntimeManager>>,
+    ) -> Result<Json<models::RouterRules>, Infallible> {
+        let hits = rm
+            .rule_hits()
+            .await
+            .into_iter()
+            .map(|x| models::RuleHits {
+                index: x.index,
+                target: x.target,
+                hits: x.hits,
+            })
+            .collect();
+        #[cfg(feature = "config-json")]
+        let (rules, persistent) = rm.router_rules();
+        #[cfg(not(feature = "config-json"))]
+        let (rules, persistent) = (None, true);
+        Ok(Json(models::RouterRules {
+            rules,
+            persistent,
+            hits,
+        }))
+    }
+
+    #[cfg(feature = "config-json")]
+    pub async fn router_rules_replace(
+        Query(opts): Query<models::ReplaceRulesOptions>,
+        State(rm): State<Arc<RuntimeManager>>,
+        Json(rules): Json<serde_json::Value>,
+    ) -> Response {
+        let persist = opts.persist.unwrap_or(false);
+        match rm.replace_rules(rules, persist).await {
+            Ok(n) => Json(models::ReplaceRules {
+                rules: n,
+                persistent: persist,
+            })
+            .into_response(),
+            Err(e @ (crate::Error::Config(_) | crate::Error::NoConfigFile)) => {
+                (StatusCode::BAD_REQUEST, e.to_string()).into_response()
+            }
+            Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+        }
+    }
+
+    pub async fn router_explain(
+        Query(opts): Query<models::ExplainOptions>,
+        State(rm): State<Arc<RuntimeManager>>,
+    ) -> Response {
+        let sess = match explain_session(opts) {
+            Ok(sess) => sess,
+            Err(e) => return (StatusCode::BAD_REQUEST, e).into_response(),
+        };
+        match rm.explain_route(sess).await {
+            Ok((sess, outbound, rule)) => Json(models::Explain {
+                destination: sess.destination.to_string(),
+                rule,
+                outbound,
+            })
+            .into_response(),
+            Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+        }
+    }
+
     pub async fn stat_html(
         State(rm): State<Arc<RuntimeManager>>,
     ) -> Result<Html<String>, Infallible> {
@@ -362,15 +725,153 @@ table, th, td {
             udp_ms,
         }))
     }
+
+    #[cfg(feature = "outbound-failover")]
+    fn delay_error(tag: String, status: StatusCode, error: &str, message: String) -> Response {
+        let reply = models::DelayError {
+            tag,
+            error: error.to_string(),
+            message,
+        };
+        (status, Json(reply)).into_response()
+    }
+
+    // Tests are run concurrently, each on a handler of its own.
+    #[cfg(feature = "outbound-failover")]
+    pub async fn outbound_delay(
+        Path(tag): Path<String>,
+        Query(opts): Query<models::DelayOptions>,
+        State(rm): State<Arc<RuntimeManager>>,
+    ) -> Response {
+        use crate::proxy::failover::HealthCheckTarget;
+
+        let url = opts.url.unwrap_or_default();
+        let target = if url.trim().is_empty() || url.contains("://") {
+            HealthCheckTarget::parse(&url)
+        } else {
+            HealthCheckTarget::parse(&format!("tls://{}", url))
+        };
+        let Some(target) = target else {
+            let message = format!("invalid url {}", url);
+            return delay_error(tag, StatusCode::BAD_REQUEST, "invalid_url", message);
+        };
+        let to = std::time::Duration::from_millis(opts.timeout.unwrap_or(5000));
+        match tokio::time::timeout(to, rm.test_outbound_delay(&tag, &target)).await {
+            Ok(Ok(Ok((delay, actor)))) => Json(models::Delay {
+                tag,
+                delay_ms: delay.as_millis() as u64,
+                actor,
+            })
+            .into_response(),
+            Ok(Ok(Err(e))) => delay_error(tag, StatusCode::BAD_GATEWAY, "failed", e.to_string()),
+            Ok(Err(e)) => delay_error(tag, StatusCode::NOT_FOUND, "not_found", e.to_string()),
+            Err(_) => {
+                let message = format!("no reply in {} ms", to.as_millis());
+                delay_error(tag, StatusCode::GATEWAY_TIMEOUT, "timeout", message)
+            }
+        }
+    }
+}
+
+// The made up session an explain request routes.
+fn explain_session(opts: models::ExplainOptions) -> Result<Session, String> {
+    let dest = opts.dest.ok_or("missing dest")?;
+    let (host, port) = dest
+        .rsplit_once(':')
+        .and_then(|(host, port)| Some((host, port.parse::<u16>().ok()?)))
+        .ok_or_else(|| format!("invalid dest {}", dest))?;
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    let destination =
+        SocksAddr::try_from((host, port)).map_err(|e| format!("invalid dest {}: {}", dest, e))?;
+    let mut sess = Session {
+        destination,
+        ..Default::default()
+    };
+    if let Some(src) = opts.src {
+        sess.source = src.parse().map_err(|_| format!("invalid src {}", src))?;
+    }
+    sess.network = match opts.network.as_deref() {
+        None | Some("tcp") => Network::Tcp,
+        Some("udp") => Network::Udp,
+        Some(x) => return Err(format!("invalid network {}", x)),
+    };
+    if let Some(inbound) = opts.inbound {
+        sess.inbound_tag = inbound;
+    }
+    sess.process_name = opts.process;
+    if let Some(protocol) = opts.protocol {
+        let protocol = protocol.to_lowercase();
+        sess.sniffed_protocol = Some(
+            crate::common::sniff::PROTOCOLS
+                .iter()
+                .find(|x| **x == protocol)
+                .copied()
+                .ok_or_else(|| format!("invalid protocol {}", protocol))?,
+        );
+    }
+    Ok(sess)
+}
+
+// Whether a request carries the secret, as a bearer token or as the token
+// parameter, which browsers use where they can't set headers.
+fn authorized(secret: &str, authorization: Option<&str>, query: Option<&str>) -> bool {
+    if secret.is_empty() {
+        return true;
+    }
+    if authorization.and_then(|x| x.strip_prefix("Bearer ")) == Some(secret) {
+        return true;
+    }
+    query
+        .unwrap_or_default()
+        .split('&')
+        .filter_map(|x| x.split_once('='))
+        .any(|(k, v)| k == "token" && v == secret)
+}
+
+// Checks the secret and allows the requests of the web dashboards from other
+// origins.
+async fn auth_and_cors(State(secret): State<Arc<String>>, req: Request, next: Next) -> Response {
+    let mut resp = if req.method() == Method::OPTIONS {
+        StatusCode::NO_CONTENT.into_response()
+    } else if authorized(
+        &secret,
+        req.headers()
+            .get(header::AUTHORIZATION)
+            .and_then(|x| x.to_str().ok()),
+        req.uri().query(),
+    ) {
+        next.run(req).await
+    } else {
+        StatusCode::UNAUTHORIZED.into_response()
+    };
+    let headers = resp.headers_mut();
+    headers.insert(
+        header::ACCESS_CONTROL_ALLOW_ORIGIN,
+        HeaderValue::from_static("*"),
+    );
+    headers.insert(
+        header::ACCESS_CONTROL_ALLOW_METHODS,
+        HeaderValue::from_static("GET, POST, PUT, PATCH, DELETE, OPTIONS"),
+    );
+    headers.insert(
+        header::ACCESS_CONTROL_ALLOW_HEADERS,
+        HeaderValue::from_static("Authorization, Content-Type"),
+    );
+    resp
 }
 
 pub struct ApiServer {
     runtime_manager: Arc<RuntimeManager>,
+    // The bearer token the requests must carry, none if empty.
+    secret: Arc<String>,
 }
 
 impl ApiServer {
-    pub fn new(runtime_manager: Arc<RuntimeManager>) -> Self {
-        Self { runtime_manager }
+    pub fn new(runtime_manager: Arc<RuntimeManager>, secret: String) -> Self {
+        Self {
+            runtime_manager,
+            secret: Arc::new(secret),
+        }
     }
 
     pub fn serve(&self, listen_addr: SocketAddr) -> crate::Runner {
@@ -386,6 +887,11 @@ impl ApiServer {
                 .route("/api/v1/app/outbound/selects", get(handlers::select_list));
         }
 
+        #[cfg(feature = "outbound-urltest")]
+        {
+            app = app.route("/api/v1/app/outbound/urltest", get(handlers::url_test_get));
+        }
+
         app = app
             .route("/api/v1/runtime/stat/html", get(handlers::stat_html))
             .route("/api/v1/runtime/stat/json", get(handlers::stat_json))
@@ -397,6 +903,41 @@ impl ApiServer {
                 "/api/v1/runtime/stat/recent/json",
                 get(handlers::stat_recent_json),
             )
+            .route(
+                "/api/v1/runtime/stat/quic/json",
+                get(handlers::stat_quic_json),
+            )
+            .route("/api/v1/runtime/connections", get(handlers::connections))
+            .route(
+                "/api/v1/runtime/connections/{id}",
+                delete(handlers::connection_kill),
+            )
+            .route(
+                "/api/v1/runtime/stat/traffic/inbound/json",
+                get(handlers::stat_inbound_traffic),
+            )
+            .route(
+                "/api/v1/runtime/stat/traffic/outbound/json",
+                get(handlers::stat_outbound_traffic),
+            )
+            .route(
+                "/api/v1/runtime/stat/traffic/reset",
+                post(handlers::stat_traffic_reset),
+            )
+            .route(
+                "/api/v1/runtime/dns/cache/flush",
+                post(handlers::dns_cache_flush),
+            )
+            .route(
+                "/api/v1/runtime/rule_providers/{name}/refresh",
+                post(handlers::rule_provider_refresh),
+            )
+            .route("/api/v1/runtime/geoip/update", post(handlers::geoip_update))
+            .route("/api/v1/runtime/router/rules", get(handlers::router_rules))
+            .route(
+                "/api/v1/runtime/router/explain",
+                get(handlers::router_explain),
+            )
             .route(
                 "/api/v1/runtime/outbound/{tag}/last_peer_active",
                 get(handlers::last_peer_active),
@@ -410,7 +951,29 @@ impl ApiServer {
                 get(handlers::outbound_health),
             );
 
-        let app = app.with_state(self.runtime_manager.clone());
+        #[cfg(feature = "config-json")]
+        {
+            app = app.route(
+                "/api/v1/runtime/router/rules",
+                axum::routing::put(handlers::router_rules_replace),
+            );
+        }
+
+        #[cfg(feature = "outbound-failover")]
+        {
+            app = app.route(
+                "/api/v1/runtime/outbound/{tag}/delay",
+                get(handlers::outbound_delay),
+            );
+        }
+
+        let app = app
+            .merge(super::clash::routes())
+            .layer(middleware::from_fn_with_state(
+                self.secret.clone(),
+                auth_and_cors,
+            ))
+            .with_state(self.runtime_manager.clone());
 
         info!("api server listening tcp {}", &listen_addr);
 
@@ -420,3 +983,96 @@ impl ApiServer {
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Sends a request without a body to the app served by a local listener,
+    // returns the status code.
+    async fn request_status(app: Router, request: &str) -> u16 {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await });
+        let mut stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+        stream
+            .write_all(
+                format!(
+                    "{}\r\nHost: localhost\r\nConnection: close\r\n\r\n",
+                    request
+                )
+                .as_bytes(),
+            )
+            .await
+            .unwrap();
+        let mut resp = String::new();
+        stream.read_to_string(&mut resp).await.unwrap();
+        resp.split(' ').nth(1).unwrap().parse().unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_auth_and_cors() {
+        let app =
+            |secret: &str| {
+                Router::new().route("/", get(|| async { "ok" })).layer(
+                    middleware::from_fn_with_state(Arc::new(secret.to_string()), auth_and_cors),
+                )
+            };
+        assert_eq!(request_status(app("s3"), "GET / HTTP/1.1").await, 401);
+        assert_eq!(
+            request_status(app("s3"), "GET /?token=s4 HTTP/1.1").await,
+            401
+        );
+        assert_eq!(
+            request_status(app("s3"), "GET /?token=s3 HTTP/1.1").await,
+            200
+        );
+        assert_eq!(
+            request_status(app("s3"), "GET / HTTP/1.1\r\nAuthorization: Bearer s3").await,
+            200
+        );
+        assert_eq!(request_status(app("s3"), "OPTIONS / HTTP/1.1").await, 204);
+        assert_eq!(request_status(app(""), "GET / HTTP/1.1").await, 200);
+    }
+
+    #[test]
+    fn test_authorized() {
+        assert!(authorized("", None, None));
+        assert!(authorized("s3", Some("Bearer s3"), None));
+        assert!(authorized("s3", None, Some("level=info&token=s3")));
+        assert!(!authorized("s3", None, None));
+        assert!(!authorized("s3", Some("Bearer s4"), Some("token=s4")));
+        assert!(!authorized("s3", Some("s3"), Some("s3")));
+    }
+
+    #[test]
+    fn test_explain_session() {
+        let opts = |dest: &str| models::ExplainOptions {
+            dest: Some(dest.to_string()),
+            ..Default::default()
+        };
+        let sess = explain_session(opts("example.com:443")).unwrap();
+        assert_eq!(sess.destination.to_string(), "example.com:443");
+        assert_eq!(sess.network, Network::Tcp);
+        let sess = explain_session(models::ExplainOptions {
+            src: Some("10.0.0.2:5000".to_string()),
+            network: Some("udp".to_string()),
+            protocol: Some("QUIC".to_string()),
+            ..opts("[::1]:53")
+        })
+        .unwrap();
+        assert_eq!(sess.destination.to_string(), "[::1]:53");
+        assert_eq!(sess.source.to_string(), "10.0.0.2:5000");
+        assert_eq!(sess.network, Network::Udp);
+        assert_eq!(sess.sniffed_protocol, Some("quic"));
+        assert!(explain_session(opts("example.com")).is_err());
+        assert!(explain_session(models::ExplainOptions::default()).is_err());
+        assert!(explain_session(models::ExplainOptions {
+            protocol: Some("ftp".to_string()),
+            ..opts("example.com:21")
+        })
+        .is_err());
+    }
+}
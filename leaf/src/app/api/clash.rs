@@ -0,0 +1,479 @@
+//! A subset of the external controller API of Clash, so that its dashboards
+//! can be used with leaf. Those endpoints without a counterpart in leaf return
+//! empty replies.
+
+use std::collections::BTreeMap;
+use std::convert::Infallible;
+use std::sync::Arc;
+use std::time::Duration;
+
+use axum::{
+    body::Body,
+    extract::{Path, Query, State},
+    http::{header, StatusCode},
+    response::{IntoResponse, Json, Response},
+    routing::{delete, get},
+    Router,
+};
+use chrono::{DateTime, SecondsFormat};
+use tokio::sync::broadcast::error::RecvError;
+
+use crate::{
+    app::{logger, stat_manager::StatManager},
+    RuntimeManager,
+};
+
+mod models {
+    use serde_derive::{Deserialize, Serialize};
+
+    #[derive(Debug, Serialize)]
+    pub struct Version {
+        pub version: String,
+        pub premium: bool,
+    }
+
+    #[derive(Debug, Serialize)]
+    pub struct Configs {
+        pub port: u16,
+        #[serde(rename = "socks-port")]
+        pub socks_port: u16,
+        #[serde(rename = "redir-port")]
+        pub redir_port: u16,
+        #[serde(rename = "allow-lan")]
+        pub allow_lan: bool,
+        pub mode: String,
+        #[serde(rename = "log-level")]
+        pub log_level: String,
+    }
+
+    #[derive(Debug, Serialize)]
+    pub struct Rules {
+        pub rules: Vec<String>,
+    }
+
+    #[derive(Debug, Serialize)]
+    pub struct Delay {
+        pub delay: u64,
+    }
+
+    #[derive(Debug, Serialize)]
+    pub struct Proxy {
+        pub name: String,
+        #[serde(rename = "type")]
+        pub type_: String,
+        pub udp: bool,
+        pub history: Vec<Delay>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub now: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub all: Option<Vec<String>>,
+    }
+
+    #[derive(Debug, Serialize)]
+    pub struct Proxies {
+        pub proxies: std::collections::BTreeMap<String, Proxy>,
+    }
+
+    #[derive(Debug, Deserialize)]
+    pub struct SelectOptions {
+        pub name: String,
+    }
+
+    #[derive(Debug, Deserialize)]
+    pub struct DelayOptions {
+        // In milliseconds.
+        pub timeout: Option<u64>,
+    }
+
+    #[derive(Debug, Serialize)]
+    pub struct Metadata {
+        pub network: String,
+        #[serde(rename = "type")]
+        pub type_: String,
+        #[serde(rename = "sourceIP")]
+        pub source_ip: String,
+        #[serde(rename = "sourcePort")]
+        pub source_port: String,
+        #[serde(rename = "destinationIP")]
+        pub destination_ip: String,
+        #[serde(rename = "destinationPort")]
+        pub destination_port: String,
+        pub host: String,
+    }
+
+    #[derive(Debug, Serialize)]
+    pub struct Connection {
+        pub id: String,
+        pub metadata: Metadata,
+        pub upload: u64,
+        pub download: u64,
+        pub start: String,
+        pub chains: Vec<String>,
+        pub rule: String,
+        #[serde(rename = "rulePayload")]
+        pub rule_payload: String,
+    }
+
+    #[derive(Debug, Serialize)]
+    pub struct Connections {
+        #[serde(rename = "downloadTotal")]
+        pub download_total: u64,
+        #[serde(rename = "uploadTotal")]
+        pub upload_total: u64,
+        pub connections: Vec<Connection>,
+    }
+
+    #[derive(Debug, Serialize)]
+    pub struct Traffic {
+        pub up: u64,
+        pub down: u64,
+    }
+
+    #[derive(Debug, Deserialize)]
+    pub struct LogsOptions {
+        pub level: Option<String>,
+    }
+
+    #[derive(Debug, Serialize)]
+    pub struct Log {
+        #[serde(rename = "type")]
+        pub type_: String,
+        pub payload: String,
+    }
+}
+
+// The Clash type of an outbound protocol.
+fn proxy_type(protocol: &str) -> &str {
+    match protocol {
+        "direct" => "Direct",
+        "drop" => "Reject",
+        "select" => "Selector",
+        "urltest" => "URLTest",
+        "failover" | "tryall" => "Fallback",
+        "static" => "LoadBalance",
+        "chain" => "Relay",
+        "socks" => "Socks5",
+        "http" => "Http",
+        "shadowsocks" => "Shadowsocks",
+        "vmess" => "Vmess",
+        "vless" => "Vless",
+        "trojan" => "Trojan",
+        "hysteria2" => "Hysteria2",
+        "tuic" => "Tuic",
+        "wireguard" => "WireGuard",
+        "ssh" => "Ssh",
+        _ => protocol,
+    }
+}
+
+// The Clash type of a log level.
+fn log_type(level: &tracing::Level) -> &'static str {
+    match *level {
+        tracing::Level::ERROR => "error",
+        tracing::Level::WARN => "warning",
+        tracing::Level::INFO => "info",
+        _ => "debug",
+    }
+}
+
+// The most verbose level of a Clash log level, none for silent.
+fn max_log_level(level: &str) -> Option<tracing::Level> {
+    match level {
+        "error" => Some(tracing::Level::ERROR),
+        "warning" => Some(tracing::Level::WARN),
+        "debug" => Some(tracing::Level::TRACE),
+        "silent" => None,
+        _ => Some(tracing::Level::INFO),
+    }
+}
+
+// Returns the bytes sent and received through all outbounds.
+fn total_traffic(sm: &StatManager) -> (u64, u64) {
+    sm.outbound_traffic
+        .values()
+        .fold((0, 0), |(up, down), t| (up + t.up(), down + t.down()))
+}
+
+// Streams a JSON object per line, the way Clash does without a websocket.
+fn json_lines<S>(stream: S) -> Response
+where
+    S: futures::Stream<Item = String> + Send + 'static,
+{
+    use futures::StreamExt;
+
+    let body = Body::from_stream(stream.map(|mut line| {
+        line.push('\n');
+        Ok::<_, Infallible>(line)
+    }));
+    ([(header::CONTENT_TYPE, "application/json")], body).into_response()
+}
+
+async fn version() -> Json<models::Version> {
+    Json(models::Version {
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        premium: false,
+    })
+}
+
+async fn configs() -> Json<models::Configs> {
+    Json(models::Configs {
+        port: 0,
+        socks_port: 0,
+        redir_port: 0,
+        allow_lan: false,
+        mode: "rule".to_string(),
+        log_level: "info".to_string(),
+    })
+}
+
+async fn configs_update() -> StatusCode {
+    StatusCode::NO_CONTENT
+}
+
+async fn rules() -> Json<models::Rules> {
+    Json(models::Rules { rules: Vec::new() })
+}
+
+#[cfg_attr(
+    not(any(feature = "outbound-select", feature = "outbound-urltest")),
+    allow(unused_variables, unused_mut)
+)]
+async fn proxy(rm: &RuntimeManager, name: &str, protocol: &str) -> models::Proxy {
+    let mut proxy = models::Proxy {
+        name: name.to_string(),
+        type_: proxy_type(protocol).to_string(),
+        udp: true,
+        history: Vec::new(),
+        now: None,
+        all: None,
+    };
+    match protocol {
+        #[cfg(feature = "outbound-select")]
+        "select" => {
+            proxy.now = rm.get_outbound_selected(name).await.ok();
+            proxy.all = rm.get_outbound_selects(name).await.ok();
+        }
+        #[cfg(feature = "outbound-urltest")]
+        "urltest" => {
+            if let Ok((selected, latencies)) = rm.get_outbound_url_test(name).await {
+                if let Some(delay) = latencies.iter().find(|x| x.0 == selected).and_then(|x| x.1) {
+                    proxy.history.push(models::Delay {
+                        delay: delay.as_millis() as u64,
+                    });
+                }
+                proxy.now = Some(selected);
+                proxy.all = Some(latencies.into_iter().map(|x| x.0).collect());
+            }
+        }
+        _ => (),
+    }
+    proxy
+}
+
+async fn proxies(State(rm): State<Arc<RuntimeManager>>) -> Json<models::Proxies> {
+    let mut proxies = BTreeMap::new();
+    for (tag, protocol) in rm.get_outbound_protocols().await {
+        let p = proxy(&rm, &tag, &protocol).await;
+        proxies.insert(tag, p);
+    }
+    Json(models::Proxies { proxies })
+}
+
+async fn proxy_get(
+    Path(name): Path<String>,
+    State(rm): State<Arc<RuntimeManager>>,
+) -> Result<Json<models::Proxy>, StatusCode> {
+    let protocols = rm.get_outbound_protocols().await;
+    let (_, protocol) = protocols
+        .iter()
+        .find(|x| x.0 == name)
+        .ok_or(StatusCode::NOT_FOUND)?;
+    Ok(Json(proxy(&rm, &name, protocol).await))
+}
+
+#[cfg(feature = "outbound-select")]
+async fn proxy_select(
+    Path(name): Path<String>,
+    State(rm): State<Arc<RuntimeManager>>,
+    Json(opts): Json<models::SelectOptions>,
+) -> StatusCode {
+    if rm.get_outbound_selected(&name).await.is_err() {
+        return StatusCode::NOT_FOUND;
+    }
+    if rm.set_outbound_selected(&name, &opts.name).await.is_err() {
+        return StatusCode::BAD_REQUEST;
+    }
+    StatusCode::NO_CONTENT
+}
+
+async fn proxy_delay(
+    Path(name): Path<String>,
+    Query(opts): Query<models::DelayOptions>,
+    State(rm): State<Arc<RuntimeManager>>,
+) -> Result<Json<models::Delay>, StatusCode> {
+    let timeout = Duration::from_millis(opts.timeout.unwrap_or(5000));
+    let (tcp_res, _) = rm
+        .health_check_outbound(&name, Some(timeout))
+        .await
+        .map_err(|_| StatusCode::NOT_FOUND)?;
+    let delay = tcp_res.map_err(|_| StatusCode::REQUEST_TIMEOUT)?;
+    Ok(Json(models::Delay {
+        delay: delay.as_millis() as u64,
+    }))
+}
+
+async fn connections(State(rm): State<Arc<RuntimeManager>>) -> Json<models::Connections> {
+    let sm = rm.stat_manager();
+    let sm = sm.read().await;
+    let (upload_total, download_total) = total_traffic(&sm);
+    let mut counters: Vec<_> = sm.counters.values().collect();
+    counters.sort_by_key(|c| c.id);
+    let connections = counters
+        .into_iter()
+        .map(|c| {
+            let sess = &c.sess;
+            let mut chains = vec![sess.outbound_tag.clone()];
+            if let Some(actor) = sess.group_actor.lock().unwrap().clone() {
+                chains.insert(0, actor);
+            }
+            models::Connection {
+                id: c.id.to_string(),
+                metadata: models::Metadata {
+                    network: sess.network.to_string(),
+                    type_: sess.inbound_tag.clone(),
+                    source_ip: sess.source.ip().to_string(),
+                    source_port: sess.source.port().to_string(),
+                    destination_ip: sess
+                        .destination
+                        .ip()
+                        .map(|x| x.to_string())
+                        .unwrap_or_default(),
+                    destination_port: sess.destination.port().to_string(),
+                    host: sess.destination.domain().cloned().unwrap_or_default(),
+                },
+                upload: c.bytes_sent(),
+                download: c.bytes_recvd(),
+                start: DateTime::from_timestamp(c.start_time() as i64, 0)
+                    .unwrap_or_default()
+                    .to_rfc3339_opts(SecondsFormat::Secs, true),
+                chains,
+                rule: String::new(),
+                rule_payload: String::new(),
+            }
+        })
+        .collect();
+    Json(models::Connections {
+        download_total,
+        upload_total,
+        connections,
+    })
+}
+
+async fn connections_kill(State(rm): State<Arc<RuntimeManager>>) -> StatusCode {
+    let sm = rm.stat_manager();
+    let sm = sm.read().await;
+    for id in sm.counters.keys() {
+        sm.kill(*id);
+    }
+    StatusCode::NO_CONTENT
+}
+
+async fn connection_kill(Path(id): Path<u64>, State(rm): State<Arc<RuntimeManager>>) -> StatusCode {
+    if rm.stat_manager().read().await.kill(id) {
+        StatusCode::NO_CONTENT
+    } else {
+        StatusCode::NOT_FOUND
+    }
+}
+
+// Streams the bytes sent and received through all outbounds each second.
+async fn traffic(State(rm): State<Arc<RuntimeManager>>) -> Response {
+    let interval = tokio::time::interval(Duration::from_secs(1));
+    let stream = futures::stream::unfold(
+        (rm, interval, None),
+        |(rm, mut interval, last)| async move {
+            interval.tick().await;
+            let (up, down) = total_traffic(&*rm.stat_manager().read().await);
+            let (last_up, last_down) = last.unwrap_or((up, down));
+            // The totals go down when they are reset.
+            let line = serde_json::to_string(&models::Traffic {
+                up: up.saturating_sub(last_up),
+                down: down.saturating_sub(last_down),
+            })
+            .unwrap_or_default();
+            Some((line, (rm, interval, Some((up, down)))))
+        },
+    );
+    json_lines(stream)
+}
+
+// Streams the log events from now on.
+async fn logs(Query(opts): Query<models::LogsOptions>) -> Response {
+    let max_level = max_log_level(opts.level.as_deref().unwrap_or("info"));
+    let stream = futures::stream::unfold(logger::subscribe(), move |mut rx| async move {
+        let max_level = max_level?;
+        loop {
+            match rx.recv().await {
+                Ok(record) if record.level <= max_level => {
+                    let line = serde_json::to_string(&models::Log {
+                        type_: log_type(&record.level).to_string(),
+                        payload: record.message,
+                    })
+                    .unwrap_or_default();
+                    return Some((line, rx));
+                }
+                Ok(_) | Err(RecvError::Lagged(_)) => continue,
+                Err(RecvError::Closed) => return None,
+            }
+        }
+    });
+    json_lines(stream)
+}
+
+pub fn routes() -> Router<Arc<RuntimeManager>> {
+    let mut app = Router::new()
+        .route("/version", get(version))
+        .route("/configs", get(configs).patch(configs_update))
+        .route("/rules", get(rules))
+        .route("/proxies", get(proxies))
+        .route("/proxies/{name}/delay", get(proxy_delay))
+        .route("/connections", get(connections).delete(connections_kill))
+        .route("/connections/{id}", delete(connection_kill))
+        .route("/traffic", get(traffic))
+        .route("/logs", get(logs));
+
+    #[cfg(feature = "outbound-select")]
+    {
+        app = app.route("/proxies/{name}", get(proxy_get).put(proxy_select));
+    }
+    #[cfg(not(feature = "outbound-select"))]
+    {
+        app = app.route("/proxies/{name}", get(proxy_get));
+    }
+
+    app
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_proxy_type() {
+        assert_eq!(proxy_type("select"), "Selector");
+        assert_eq!(proxy_type("failover"), "Fallback");
+        assert_eq!(proxy_type("drop"), "Reject");
+        assert_eq!(proxy_type("obfs"), "obfs");
+    }
+
+    #[test]
+    fn test_log_level() {
+        let info = max_log_level("info").unwrap();
+        assert!(tracing::Level::WARN <= info);
+        assert!(tracing::Level::DEBUG > info);
+        assert!(tracing::Level::TRACE <= max_log_level("debug").unwrap());
+        assert!(max_log_level("silent").is_none());
+        assert_eq!(log_type(&tracing::Level::WARN), "warning");
+        assert_eq!(log_type(&tracing::Level::TRACE), "debug");
+    }
+}
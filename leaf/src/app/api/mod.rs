@@ -1 +1,2 @@
 pub mod api_server;
+mod clash;
@@ -1,4 +1,6 @@
 use std::io::{self};
+use std::net::IpAddr;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use std::time::Duration;
 
@@ -8,7 +10,7 @@ use tokio::sync::RwLock;
 use tracing::{debug, info, warn, Instrument};
 
 use crate::{
-    app::SyncDnsClient,
+    app::{access_log, fake_dns::FakeDns, SyncDnsClient},
     common::{
         self,
         dns_sniff::{DnsSniffer, SniffingDatagram},
@@ -88,7 +90,16 @@ impl OutboundDatagram for HealthcheckUdpDatagram {
 #[inline]
 fn log_request(sess: &Session, outbound_tag: &str, handshake_time: Option<u128>) {
     let hs = handshake_time.map_or("failed".to_string(), |hs| format!("{}ms", hs));
+    let outbound_tag = match sess.group_actor.lock().unwrap().as_ref() {
+        Some(actor) => format!("{}({})", outbound_tag, actor),
+        None => outbound_tag.to_string(),
+    };
     let network = sess.network.to_string();
+    let user = sess
+        .user
+        .as_ref()
+        .map(|x| format!(" user={}", x))
+        .unwrap_or_default();
 
     #[cfg(feature = "rule-process-name")]
     {
@@ -103,9 +114,10 @@ fn log_request(sess: &Session, outbound_tag: &str, handshake_time: Option<u128>)
             })
             .unwrap_or("");
         info!(
-            "handled process={} src={} proto={} in={} out={} connect={} dst={}",
+            "handled process={} src={}{} proto={} in={} out={} connect={} dst={}",
             process_name,
             sess.forwarded_source.unwrap_or_else(|| sess.source.ip()),
+            user,
             network,
             &sess.inbound_tag,
             outbound_tag,
@@ -117,8 +129,9 @@ fn log_request(sess: &Session, outbound_tag: &str, handshake_time: Option<u128>)
     #[cfg(not(feature = "rule-process-name"))]
     {
         info!(
-            "handled src={} proto={} in={} out={} connect={} dst={}",
+            "handled src={}{} proto={} in={} out={} connect={} dst={}",
             sess.forwarded_source.unwrap_or_else(|| sess.source.ip()),
+            user,
             network,
             &sess.inbound_tag,
             outbound_tag,
@@ -131,9 +144,14 @@ fn log_request(sess: &Session, outbound_tag: &str, handshake_time: Option<u128>)
 pub struct Dispatcher {
     pub(crate) outbound_manager: Arc<RwLock<OutboundManager>>,
     pub(crate) router: Arc<RwLock<Router>>,
-    dns_client: SyncDnsClient,
+    pub(crate) dns_client: SyncDnsClient,
     stat_manager: SyncStatManager,
     dns_sniffer: DnsSniffer,
+    // Fake DNS of inbounds answering queries of other clients, whose fake
+    // IPs reach the dispatcher unmapped.
+    fake_dns: std::sync::RwLock<Vec<Arc<FakeDns>>>,
+    // Refuses the streams of inbounds while shutting down.
+    draining: AtomicBool,
 }
 
 impl Dispatcher {
@@ -149,9 +167,129 @@ impl Dispatcher {
             dns_client,
             stat_manager,
             dns_sniffer: DnsSniffer::new(),
+            fake_dns: std::sync::RwLock::new(Vec::new()),
+            draining: AtomicBool::new(false),
         }
     }
 
+    /// Registers a fake DNS, streams to its fake IPs from any inbound are
+    /// dispatched to the paired domains.
+    pub fn add_fake_dns(&self, fake_dns: Arc<FakeDns>) {
+        self.fake_dns.write().unwrap().push(fake_dns);
+    }
+
+    /// Refuses the streams of inbounds from now on, the sessions already
+    /// dispatched keep relaying.
+    pub fn drain(&self) {
+        self.draining.store(true, Ordering::Relaxed);
+    }
+
+    async fn fake_ip_domain(&self, ip: &IpAddr) -> Option<String> {
+        let fake_dns = self.fake_dns.read().unwrap().clone();
+        for f in fake_dns {
+            if f.is_fake_ip(ip).await {
+                return f.query_domain(ip).await;
+            }
+        }
+        None
+    }
+
+    // Finds the local process of a session, when the rules match processes
+    // and the inbound didn't tell it. Left unknown if it can't be found, the
+    // session then matches none of those rules.
+    #[cfg(feature = "rule-process-name")]
+    async fn find_process(&self, sess: &mut Session) {
+        if sess.process_name.is_some() || !self.router.read().await.match_process() {
+            return;
+        }
+        let (network, source) = (sess.network, sess.source);
+        sess.process_name = tokio::task::spawn_blocking(move || {
+            crate::common::process::find_process_path(network, source)
+        })
+        .await
+        .ok()
+        .flatten();
+    }
+
+    // Puts back the domain of a fake IP, and the domain a DNS query of the
+    // client resolved to the IP.
+    async fn restore_domain(&self, sess: &mut Session) {
+        if let Some(ip) = sess.destination.ip() {
+            if let Some(domain) = self.fake_ip_domain(&ip).await {
+                debug!("fake ip {} paired with domain={}", &ip, &domain);
+                sess.destination = SocksAddr::Domain(domain, sess.destination.port());
+            }
+        }
+
+        if option::DNS_DOMAIN_SNIFFING.load(std::sync::atomic::Ordering::Relaxed) {
+            if let Some(ip) = sess.destination.ip() {
+                if let Some(domain) = self.dns_sniffer.get(&ip).await {
+                    debug!("dns sniffed domain={}", &domain);
+                    sess.dns_sniffed_domain = Some(domain);
+                }
+            }
+        }
+    }
+
+    // The outbound of a session no rule matches, the final outbound of its
+    // inbound if it has one, or the default outbound.
+    async fn final_outbound(&self, router: &Router, sess: &Session) -> Option<String> {
+        if let Some(tag) = router.inbound_final(&sess.inbound_tag) {
+            return Some(tag.to_owned());
+        }
+        self.outbound_manager.read().await.default_handler()
+    }
+
+    // Picks the outbound of a session along with the position of the rule
+    // picking it. The rule the session matches comes first, then the final
+    // outbound of its inbound, then the default outbound, which is the first
+    // one or the one of the FINAL rule. An explained session isn't counted
+    // in the hits of the rule.
+    async fn pick_outbound(
+        &self,
+        sess: &Session,
+        explain: bool,
+    ) -> io::Result<(String, Option<usize>)> {
+        let router = self.router.read().await;
+        let picked = if explain {
+            router.explain_rule(sess).await
+        } else {
+            router.pick_rule(sess).await
+        };
+        match picked {
+            Ok(Some((rule, tag))) => {
+                debug!(
+                    "picked route out={} src={} dst={}",
+                    tag, &sess.source, &sess.destination
+                );
+                Ok((tag, Some(rule)))
+            }
+            Ok(None) => match self.final_outbound(&router, sess).await {
+                Some(tag) => {
+                    debug!("picked final out={}", &tag);
+                    Ok((tag, None))
+                }
+                None => {
+                    warn!("no outbound found");
+                    Err(io::Error::other("no outbound found"))
+                }
+            },
+            Err(e) => Err(io::Error::other(format!("pick route failed: {}", e))),
+        }
+    }
+
+    /// Routes a stream session the way it's dispatched, without connecting
+    /// it. Returns the session as the rules saw it, the outbound and the
+    /// position of the rule picking it. The session isn't sniffed, there
+    /// are no bytes of it.
+    pub async fn explain(&self, mut sess: Session) -> io::Result<(Session, String, Option<usize>)> {
+        self.restore_domain(&mut sess).await;
+        #[cfg(feature = "rule-process-name")]
+        self.find_process(&mut sess).await;
+        let (outbound, rule) = self.pick_outbound(&sess, true).await?;
+        Ok((sess, outbound, rule))
+    }
+
     pub async fn dispatch_stream<T>(&self, sess: Session, lhs: T)
     where
         T: 'static + AsyncRead + AsyncWrite + Unpin + Send + Sync,
@@ -164,19 +302,17 @@ impl Dispatcher {
     where
         T: 'static + AsyncRead + AsyncWrite + Unpin + Send + Sync,
     {
+        if self.draining.load(Ordering::Relaxed) {
+            debug!("refused stream while shutting down");
+            return;
+        }
+
         debug!(
             "dispatch proto={} in={} src={} dst={}",
             &sess.network, &sess.inbound_tag, &sess.source, &sess.destination
         );
 
-        if option::DNS_DOMAIN_SNIFFING.load(std::sync::atomic::Ordering::Relaxed) {
-            if let Some(ip) = sess.destination.ip() {
-                if let Some(domain) = self.dns_sniffer.get(&ip).await {
-                    debug!("dns sniffed domain={}", &domain);
-                    sess.dns_sniffed_domain = Some(domain);
-                }
-            }
-        }
+        self.restore_domain(&mut sess).await;
 
         if let Some(domain) = sess.destination.domain() {
             if domain == "healthcheck.leaf" {
@@ -200,11 +336,18 @@ impl Dispatcher {
         let do_tls = (tls_sniff && is_tls_port) || tls_sniff_all;
         let do_http = (http_sniff && is_http_port) || http_sniff_all;
 
-        let mut lhs: Box<dyn ProxyStream> = if (do_tls || do_http) && sniff::should_sniff(&sess) {
+        let sniff_domain = (do_tls || do_http) && sniff::should_sniff(&sess);
+        // Waits for the client only when a protocol rule may pick the route,
+        // the sessions matching a rule before it are routed right away.
+        let sniff_protocol = self.router.read().await.needs_protocol(&sess);
+
+        let mut lhs: Box<dyn ProxyStream> = if sniff_domain || sniff_protocol {
             let mut lhs = sniff::SniffingStream::new(lhs);
             match lhs.sniff(&sess).await {
                 Ok(res) => {
-                    if let Some((kind, domain)) = res {
+                    sess.sniffed_protocol = Some(lhs.protocol());
+                    debug!("sniffed protocol={}", lhs.protocol());
+                    if let Some((kind, domain)) = res.filter(|_| sniff_domain) {
                         debug!("sniffed domain={}", &domain);
                         match kind {
                             sniff::SniffKind::Tls => {
@@ -238,33 +381,20 @@ impl Dispatcher {
             Box::new(lhs)
         };
 
-        let outbound = {
-            let router = self.router.read().await;
-            match router.pick_route(&sess).await {
-                Ok(Some(tag)) => {
-                    debug!(
-                        "picked route out={} src={} dst={}",
-                        tag, &sess.source, &sess.destination
-                    );
-                    tag.to_owned()
-                }
-                Ok(None) => {
-                    if let Some(tag) = self.outbound_manager.read().await.default_handler() {
-                        debug!("picked default out={}", &tag);
-                        tag
-                    } else {
-                        warn!("no outbound found");
-                        return;
-                    }
-                }
-                Err(err) => {
-                    debug!("pick route err={}", err);
-                    return;
-                }
+        #[cfg(feature = "rule-process-name")]
+        self.find_process(&mut sess).await;
+
+        let (outbound, rule) = match self.pick_outbound(&sess, false).await {
+            Ok(picked) => picked,
+            Err(e) => {
+                debug!("pick route err={}", e);
+                return;
             }
         };
 
         sess.outbound_tag = outbound.clone();
+        // Not shared with the sessions this one was cloned from.
+        sess.group_actor = Default::default();
 
         let h = if let Some(h) = self.outbound_manager.read().await.get(&outbound) {
             h
@@ -287,6 +417,7 @@ impl Dispatcher {
                         e
                     );
                     log_request(&sess, h.tag(), None);
+                    self.stat_manager.write().await.count_connect_error(h.tag());
                     return;
                 }
             };
@@ -324,6 +455,10 @@ impl Dispatcher {
                         .stat_stream(rhs, sess.clone());
                 }
 
+                if let Some(access_log) = access_log::get() {
+                    rhs = access_log.stream(rhs, &sess, rule);
+                }
+
                 match common::io::copy_buf_bidirectional_with_timeout(
                     &mut lhs,
                     &mut rhs,
@@ -344,6 +479,7 @@ impl Dispatcher {
             Err(e) => {
                 debug!("outbound handle err={}", e);
                 log_request(&sess, h.tag(), None);
+                self.stat_manager.write().await.count_connect_error(h.tag());
             }
         }
     }
@@ -352,9 +488,9 @@ impl Dispatcher {
         let outbound = {
             let router = self.router.read().await;
             match router.pick_route(&sess).await {
-                Ok(Some(tag)) => tag.to_owned(),
+                Ok(Some(tag)) => tag,
                 Ok(None) => {
-                    if let Some(tag) = self.outbound_manager.read().await.default_handler() {
+                    if let Some(tag) = self.final_outbound(&router, &sess).await {
                         tag
                     } else {
                         return Err(io::Error::other("no outbound found"));
@@ -363,10 +499,21 @@ impl Dispatcher {
                 Err(_) => return Err(io::Error::other("pick route failed")),
             }
         };
+        self.dispatch_stream_outbound_via(sess, &outbound).await
+    }
 
-        sess.outbound_tag = outbound.clone();
+    /// Connects a stream through the outbound `tag` rather than the one
+    /// picked by the router.
+    pub async fn dispatch_stream_outbound_via(
+        &self,
+        mut sess: Session,
+        tag: &str,
+    ) -> io::Result<AnyStream> {
+        sess.outbound_tag = tag.to_owned();
+        // Not shared with the sessions this one was cloned from.
+        sess.group_actor = Default::default();
 
-        let h = if let Some(h) = self.outbound_manager.read().await.get(&outbound) {
+        let h = if let Some(h) = self.outbound_manager.read().await.get(tag) {
             h
         } else {
             return Err(io::Error::other("handler not found"));
@@ -409,33 +556,39 @@ impl Dispatcher {
             }
         }
 
-        let outbound = {
-            let router = self.router.read().await;
-            match router.pick_route(&sess).await {
-                Ok(Some(tag)) => {
-                    debug!(
-                        "picked route out={} src={} dst={}",
-                        tag, &sess.source, &sess.destination
-                    );
-                    tag.to_owned()
-                }
-                Ok(None) => {
-                    if let Some(tag) = self.outbound_manager.read().await.default_handler() {
-                        debug!("picked default out={}", &tag);
-                        tag
-                    } else {
-                        warn!("no outbound found");
-                        return Err(io::Error::other("no outbound found"));
-                    }
-                }
-                Err(err) => {
-                    debug!("pick route err={}", err);
-                    return Err(io::Error::other("pick route failed"));
-                }
-            }
-        };
+        #[cfg(feature = "rule-process-name")]
+        self.find_process(&mut sess).await;
+
+        let (outbound, rule) = self.pick_outbound(&sess, false).await.map_err(|e| {
+            debug!("pick route err={}", e);
+            e
+        })?;
+        self.connect_datagram(sess, outbound, rule).await
+    }
 
+    /// Connects a datagram through the outbound `tag` rather than the one
+    /// picked by the router.
+    pub async fn dispatch_datagram_via(
+        &self,
+        sess: Session,
+        tag: &str,
+    ) -> io::Result<Box<dyn OutboundDatagram>> {
+        debug!(
+            "dispatch proto={} in={} src={} dst={} out={}",
+            &sess.network, &sess.inbound_tag, &sess.source, &sess.destination, tag
+        );
+        self.connect_datagram(sess, tag.to_owned(), None).await
+    }
+
+    async fn connect_datagram(
+        &self,
+        mut sess: Session,
+        outbound: String,
+        rule: Option<usize>,
+    ) -> io::Result<Box<dyn OutboundDatagram>> {
         sess.outbound_tag = outbound.clone();
+        // Not shared with the sessions this one was cloned from.
+        sess.group_actor = Default::default();
 
         let h = if let Some(h) = self.outbound_manager.read().await.get(&outbound) {
             h
@@ -448,7 +601,14 @@ impl Dispatcher {
 
         debug!("connect datagram outbound={}", h.tag());
         let transport =
-            crate::proxy::connect_datagram_outbound(&sess, self.dns_client.clone(), &h).await?;
+            match crate::proxy::connect_datagram_outbound(&sess, self.dns_client.clone(), &h).await
+            {
+                Ok(t) => t,
+                Err(e) => {
+                    self.stat_manager.write().await.count_connect_error(h.tag());
+                    return Err(e);
+                }
+            };
 
         match h.datagram()?.handle(&sess, transport).await {
             Ok(mut d) => {
@@ -462,6 +622,13 @@ impl Dispatcher {
                     .await
                     .stat_outbound_datagram(d, sess.clone());
 
+                // The queries of the DNS client are no sessions of clients.
+                if sess.inbound_tag != "dnsclient" {
+                    if let Some(access_log) = access_log::get() {
+                        d = access_log.datagram(d, &sess, rule);
+                    }
+                }
+
                 if option::DNS_DOMAIN_SNIFFING.load(std::sync::atomic::Ordering::Relaxed)
                     && sess.destination.port() == 53
                 {
@@ -473,6 +640,7 @@ impl Dispatcher {
             Err(e) => {
                 debug!("outbound handle err={}", e);
                 log_request(&sess, h.tag(), None);
+                self.stat_manager.write().await.count_connect_error(h.tag());
                 Err(e)
             }
         }
@@ -485,4 +653,12 @@ impl Dispatcher {
             false
         }
     }
+
+    pub async fn is_drop_outbound(&self, tag: &str) -> bool {
+        if let Some(h) = self.outbound_manager.read().await.get(tag) {
+            h.is_drop()
+        } else {
+            false
+        }
+    }
 }
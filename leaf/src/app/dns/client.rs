@@ -3,17 +3,26 @@ use std::fmt;
 use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, ToSocketAddrs};
 use std::num::NonZeroUsize;
 use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::{Arc, Mutex, Weak};
-use std::time::{Duration, Instant};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
 use anyhow::{anyhow, Result};
 use async_recursion::async_recursion;
+use cidr::{IpCidr, IpInet};
 use futures::future::select_ok;
+use futures::stream::{FuturesUnordered, StreamExt};
 use hickory_proto::{
     op::{
-        header::MessageType, op_code::OpCode, query::Query, response_code::ResponseCode, Message,
+        header::MessageType, op_code::OpCode, query::Query, response_code::ResponseCode, Edns,
+        Message,
+    },
+    rr::{
+        rdata::opt::{ClientSubnet, EdnsCode, EdnsOption},
+        record_data::RData,
+        record_type::RecordType,
+        Name,
     },
-    rr::{record_data::RData, record_type::RecordType, Name},
 };
 use lru::LruCache;
 use rand::{rngs::StdRng, Rng, SeedableRng};
@@ -23,6 +32,11 @@ use tokio::sync::Mutex as TokioMutex;
 use tokio::time::timeout;
 use tracing::{debug, trace, warn, Instrument};
 
+#[cfg(all(
+    feature = "rustls-tls",
+    any(feature = "outbound-tls", feature = "outbound-quic")
+))]
+use crate::common::cert::SpkiPinVerifier;
 #[cfg(feature = "rustls-tls")]
 use {
     std::sync::Arc as SyncArc,
@@ -40,14 +54,48 @@ use {
     tokio_openssl::SslStream,
 };
 
+#[cfg(feature = "doh-h2")]
+use {
+    ::h2::client as h2_client,
+    ::http::{header, Method, Request, StatusCode},
+    bytes::Bytes,
+};
+
+use super::hosts::Hosts;
 use crate::{app::dispatcher::Dispatcher, option, proxy::*, session::*};
 include!("client/types.rs");
 
+const DOH_DEFAULT_PATH: &str = "/dns-query";
+
+// The TTL of the answers from the static hosts if none is configured.
+const DEFAULT_HOSTS_TTL: u32 = 60;
+
+// How long a name without addresses is cached if no TTL is configured.
+const DEFAULT_NEGATIVE_TTL: u32 = 30;
+
+// Larger responses aren't DNS messages.
+const DOH_MAX_RESPONSE_SIZE: usize = 65535;
+
 impl DnsClient {
     fn load_servers(dns: &crate::config::Dns) -> Result<Vec<Resolver>> {
+        let servers = Self::parse_servers(&dns.servers, &dns.outbound);
+        if servers.is_empty() {
+            return Err(anyhow!("no dns servers"));
+        }
+        Ok(servers)
+    }
+
+    // The servers are queried through the outbound if the tag isn't empty.
+    fn parse_servers(values: &[String], outbound: &str) -> Vec<Resolver> {
         let mut servers = Vec::new();
-        for server in dns.servers.iter() {
-            match Self::parse_server(server) {
+        for server in values.iter() {
+            let parsed = Self::parse_server(server).and_then(|mut parsed| {
+                if !outbound.is_empty() {
+                    parsed.set_outbound(outbound)?;
+                }
+                Ok(parsed)
+            });
+            match parsed {
                 Ok(parsed) => servers.push(parsed),
                 Err(err) => warn!("skip invalid dns server [{}]: {}", server, err),
             }
@@ -55,13 +103,49 @@ impl DnsClient {
         for server in &servers {
             debug!("loaded dns server: {}", server);
         }
-        if servers.is_empty() {
-            return Err(anyhow!("no dns servers"));
+        servers
+    }
+
+    fn load_groups(dns: &crate::config::Dns) -> Result<HashMap<String, ServerGroup>> {
+        let mut groups = HashMap::new();
+        for (name, servers) in dns.groups.iter() {
+            let servers = Self::parse_servers(&servers.values, &servers.outbound);
+            if servers.is_empty() {
+                return Err(anyhow!("no dns servers in group {}", name));
+            }
+            let group = ServerGroup {
+                servers,
+                selector_state: Mutex::new(ServerSelectorState::default()),
+            };
+            groups.insert(name.to_owned(), group);
         }
-        Ok(servers)
+        Ok(groups)
+    }
+
+    fn load_rules(
+        dns: &crate::config::Dns,
+        groups: &HashMap<String, ServerGroup>,
+    ) -> Result<Vec<ServerRule>> {
+        let mut rules = Vec::new();
+        for rule in dns.rules.iter() {
+            if !groups.contains_key(&rule.group) {
+                return Err(anyhow!("unknown dns group {} in rules", rule.group));
+            }
+            rules.push(ServerRule {
+                domains: crate::app::router::DomainSet::new(&rule.domains),
+                group: rule.group.clone(),
+            });
+        }
+        Ok(rules)
     }
 
     fn parse_server(server: &str) -> Result<Resolver> {
+        // The queries to a server after noecs: leave the client subnet out.
+        if server.to_ascii_lowercase().starts_with("noecs:") {
+            let mut parsed = Self::parse_server(&server[6..])?;
+            parsed.disable_client_subnet();
+            return Ok(parsed);
+        }
         let server_lower = server.to_ascii_lowercase();
         let (server, is_direct) = if server_lower.starts_with("direct:") {
             (&server[7..], true)
@@ -74,10 +158,24 @@ impl DnsClient {
         if server.to_ascii_lowercase().starts_with("doh:") {
             return Self::parse_doh_server(server, is_direct);
         }
+        if server.to_ascii_lowercase().starts_with("https://") {
+            return Self::parse_doh_url(server, is_direct);
+        }
+        for transport in [Transport::Tcp, Transport::Tls, Transport::Quic] {
+            let prefix = format!("{}://", transport.scheme());
+            if server.to_ascii_lowercase().starts_with(&prefix) {
+                return Self::parse_stream_server(server, &prefix, transport, is_direct);
+            }
+        }
         let ip = server
             .parse::<IpAddr>()
             .map_err(|e| anyhow!("invalid dns server [{}]: {}", server, e))?;
-        Ok(Resolver::Server(SocketAddr::new(ip, 53), is_direct))
+        Ok(Resolver::Server(
+            SocketAddr::new(ip, 53),
+            is_direct,
+            None,
+            true,
+        ))
     }
 
     fn parse_doh_server(server: &str, is_direct: bool) -> Result<Resolver> {
@@ -112,22 +210,173 @@ impl DnsClient {
         };
         Ok(Resolver::DoH(DohResolver {
             domain: domain.to_string(),
+            port: 443,
+            path: DOH_DEFAULT_PATH.to_string(),
+            bootstrap_ip,
+            is_direct,
+            outbound: None,
+            ecs: true,
+        }))
+    }
+
+    // Parses https://host[:port][/path], the path defaults to /dns-query.
+    // A host which is an IP needs no bootstrap.
+    fn parse_doh_url(server: &str, is_direct: bool) -> Result<Resolver> {
+        let rest = &server[8..];
+        let (authority, path) = match rest.find('/') {
+            Some(i) => (&rest[..i], &rest[i..]),
+            None => (rest, DOH_DEFAULT_PATH),
+        };
+        let (host, port) = Self::parse_host_port(server, authority, 443)?;
+        Ok(Resolver::DoH(DohResolver {
+            domain: host.to_string(),
+            port,
+            path: path.to_string(),
+            bootstrap_ip: host.parse::<IpAddr>().ok(),
+            is_direct,
+            outbound: None,
+            ecs: true,
+        }))
+    }
+
+    // Parses tls://host[:port][@ip][?sni=name&pin=digest], the same with
+    // quic://, and tcp://host[:port][@ip]. The IP after @ is the one of a
+    // host which isn't an IP, pins can be repeated.
+    fn parse_stream_server(
+        server: &str,
+        prefix: &str,
+        transport: Transport,
+        is_direct: bool,
+    ) -> Result<Resolver> {
+        #[cfg(not(feature = "doq"))]
+        if transport == Transport::Quic {
+            return Err(anyhow!(
+                "invalid dns server [{}]: dns over quic needs the doq feature",
+                server
+            ));
+        }
+        let rest = &server[prefix.len()..];
+        let (rest, options) = match rest.split_once('?') {
+            Some((rest, options)) => (rest, Some(options)),
+            None => (rest, None),
+        };
+        let (authority, ip) = match rest.split_once('@') {
+            Some((authority, ip)) => (authority, Some(ip)),
+            None => (rest, None),
+        };
+        let (host, port) = Self::parse_host_port(server, authority, transport.default_port())?;
+        let bootstrap_ip = match ip {
+            Some(ip) => Some(
+                ip.parse::<IpAddr>()
+                    .map_err(|e| anyhow!("invalid dns server [{}]: {}", server, e))?,
+            ),
+            None => host.parse::<IpAddr>().ok(),
+        };
+        let tls = transport != Transport::Tcp;
+        let mut sni = None;
+        let mut pins = Vec::new();
+        for option in options.into_iter().flat_map(|x| x.split('&')) {
+            match option.split_once('=') {
+                Some(("sni", v)) if tls && !v.is_empty() => sni = Some(v.to_string()),
+                Some(("pin", v)) if tls && !v.is_empty() => pins.push(v.to_string()),
+                _ => {
+                    return Err(anyhow!(
+                        "invalid dns server [{}]: invalid option {}",
+                        server,
+                        option
+                    ))
+                }
+            }
+        }
+        Ok(Resolver::Stream(StreamResolver {
+            host: host.to_string(),
+            port,
             bootstrap_ip,
+            transport,
+            sni,
+            pins,
             is_direct,
+            outbound: None,
+            ecs: true,
         }))
     }
 
-    async fn resolve_doh_bootstrap_addr(
+    // Parses host[:port], where an IPv6 host is in brackets.
+    fn parse_host_port<'a>(
+        server: &str,
+        authority: &'a str,
+        default_port: u16,
+    ) -> Result<(&'a str, u16)> {
+        let (host, port) = if let Some(rest) = authority.strip_prefix('[') {
+            let (host, port) = rest
+                .split_once(']')
+                .ok_or_else(|| anyhow!("invalid dns server [{}]: invalid host", server))?;
+            (host, port.strip_prefix(':'))
+        } else {
+            match authority.rsplit_once(':') {
+                Some((host, port)) => (host, Some(port)),
+                None => (authority, None),
+            }
+        };
+        if host.is_empty() {
+            return Err(anyhow!("invalid dns server [{}]: empty host", server));
+        }
+        let port = match port {
+            Some(port) => port
+                .parse::<u16>()
+                .map_err(|e| anyhow!("invalid dns server [{}]: {}", server, e))?,
+            None => default_port,
+        };
+        if host.parse::<IpAddr>().is_err() {
+            Name::from_str(&format!("{}.", host))
+                .map_err(|e| anyhow!("invalid dns server [{}]: {}", server, e))?;
+        }
+        Ok((host, port))
+    }
+
+    // The host of a DoH, TCP, TLS or QUIC server is resolved by the plain
+    // servers, so that it doesn't recurse through these servers, or by the
+    // system resolver if there are none.
+    #[async_recursion]
+    async fn resolve_bootstrap_addr(
         &self,
-        domain: &str,
+        host: &str,
+        port: u16,
         bootstrap_ip: Option<IpAddr>,
+        is_direct: bool,
     ) -> Result<SocketAddr> {
         if let Some(ip) = bootstrap_ip {
-            return Ok(SocketAddr::new(ip, 443));
+            return Ok(SocketAddr::new(ip, port));
         }
-        let domain = domain.to_owned();
+        let name = Name::from_str(&format!("{}.", host))?;
+        let request = Self::new_query(name, RecordType::A).to_vec()?;
+        let plain_servers = self
+            .servers
+            .iter()
+            .filter(|x| matches!(x, Resolver::Server(..) | Resolver::System(_)));
+        let mut has_plain_servers = false;
+        for server in plain_servers {
+            has_plain_servers = true;
+            let res = timeout(
+                Duration::from_secs(*option::DNS_TIMEOUT),
+                self.resolve_with_server(is_direct, request.clone(), host, server),
+            )
+            .await;
+            match res {
+                Ok(Ok(entry)) if !entry.ips.is_empty() => {
+                    return Ok(SocketAddr::new(entry.ips[0], port));
+                }
+                Ok(Ok(_)) => (),
+                Ok(Err(e)) => debug!("bootstrap {} with {} failed: {}", host, server, e),
+                Err(_) => debug!("bootstrap {} with {} timeout", host, server),
+            }
+        }
+        if has_plain_servers {
+            return Err(anyhow!("bootstrap failed: no resolved address"));
+        }
+        let domain = host.to_owned();
         let addr = tokio::task::spawn_blocking(move || {
-            (domain.as_str(), 443)
+            (domain.as_str(), port)
                 .to_socket_addrs()
                 .ok()
                 .and_then(|mut addrs| addrs.next())
@@ -138,12 +387,12 @@ impl DnsClient {
         Ok(addr)
     }
 
-    async fn connect_doh_tcp_stream(
+    async fn connect_tcp_stream(
         &self,
-        doh: &DohResolver,
+        is_direct: bool,
         bootstrap_addr: SocketAddr,
     ) -> Result<AnyStream> {
-        if doh.is_direct {
+        if is_direct {
             let stream = TcpStream::connect(bootstrap_addr).await?;
             return Ok(Box::new(stream));
         }
@@ -170,13 +419,105 @@ impl DnsClient {
         Err(anyhow!("no dispatcher"))
     }
 
+    // Dials a DoH, TCP or TLS server. Through an outbound, a host without
+    // a bootstrap IP is left to the outbound to resolve.
+    async fn connect_server_stream(
+        &self,
+        host: &str,
+        port: u16,
+        bootstrap_ip: Option<IpAddr>,
+        is_direct: bool,
+        outbound: Option<&str>,
+    ) -> Result<AnyStream> {
+        let Some(tag) = outbound else {
+            let bootstrap_addr = self
+                .resolve_bootstrap_addr(host, port, bootstrap_ip, is_direct)
+                .await
+                .map_err(|e| anyhow!("resolve bootstrap failed: {}", e))?;
+            return self.connect_tcp_stream(is_direct, bootstrap_addr).await;
+        };
+        let destination = match bootstrap_ip {
+            Some(ip) => SocksAddr::from(SocketAddr::new(ip, port)),
+            None => SocksAddr::try_from((host.to_owned(), port))
+                .map_err(|e| anyhow!("invalid host {}: {}", host, e))?,
+        };
+        let source = match destination.ip() {
+            Some(IpAddr::V6(_)) => SocketAddr::new(IpAddr::V6(Ipv6Addr::UNSPECIFIED), 0),
+            _ => SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), 0),
+        };
+        let sess = Session {
+            network: Network::Tcp,
+            source,
+            destination,
+            inbound_tag: "dnsclient".to_string(),
+            ..Default::default()
+        };
+        let dispatcher = self
+            .dispatcher
+            .as_ref()
+            .ok_or_else(|| anyhow!("no dispatcher"))?
+            .upgrade()
+            .ok_or_else(|| anyhow!("dispatcher is gone"))?;
+        dispatcher
+            .dispatch_stream_outbound_via(sess, tag)
+            .await
+            .map_err(|e| anyhow!("dispatch stream to {} failed: {}", tag, e))
+    }
+
+    // HTTP/2 is offered when it's supported.
+    fn doh_alpns() -> Vec<&'static [u8]> {
+        if cfg!(feature = "doh-h2") {
+            vec![&b"h2"[..], &b"http/1.1"[..]]
+        } else {
+            vec![&b"http/1.1"[..]]
+        }
+    }
+
+    // Certificates are checked against the pins only if there are pins.
+    #[cfg(all(
+        feature = "rustls-tls",
+        any(feature = "outbound-tls", feature = "outbound-quic")
+    ))]
+    fn pinned_tls_config(pins: &[String]) -> Result<ClientConfig> {
+        #[cfg(feature = "rustls-tls-aws-lc")]
+        let provider = tokio_rustls::rustls::crypto::aws_lc_rs::default_provider();
+        #[cfg(not(feature = "rustls-tls-aws-lc"))]
+        let provider = tokio_rustls::rustls::crypto::ring::default_provider();
+        let provider = SyncArc::new(provider);
+        let verifier = SpkiPinVerifier::new(pins.to_vec(), None, provider.clone());
+        Ok(ClientConfig::builder_with_provider(provider)
+            .with_safe_default_protocol_versions()?
+            .dangerous()
+            .with_custom_certificate_verifier(SyncArc::new(verifier))
+            .with_no_client_auth())
+    }
+
+    #[cfg(all(
+        feature = "rustls-tls",
+        not(any(feature = "outbound-tls", feature = "outbound-quic"))
+    ))]
+    fn pinned_tls_config(_pins: &[String]) -> Result<ClientConfig> {
+        Err(anyhow!("certificate pins need the outbound-tls feature"))
+    }
+
+    // Returns the stream and the protocol the server chose with ALPN.
     #[cfg(feature = "rustls-tls")]
-    async fn wrap_doh_tls_stream(stream: AnyStream, server_name: &str) -> Result<AnyStream> {
-        let mut roots = RootCertStore::empty();
-        roots.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned());
-        let config = ClientConfig::builder()
-            .with_root_certificates(roots)
-            .with_no_client_auth();
+    async fn wrap_tls_stream(
+        stream: AnyStream,
+        server_name: &str,
+        alpns: &[&[u8]],
+        pins: &[String],
+    ) -> Result<(AnyStream, Option<Vec<u8>>)> {
+        let mut config = if pins.is_empty() {
+            let mut roots = RootCertStore::empty();
+            roots.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned());
+            ClientConfig::builder()
+                .with_root_certificates(roots)
+                .with_no_client_auth()
+        } else {
+            Self::pinned_tls_config(pins)?
+        };
+        config.alpn_protocols = alpns.iter().map(|x| x.to_vec()).collect();
         let connector = TlsConnector::from(SyncArc::new(config));
         let domain = ServerName::try_from(server_name.to_owned())
             .map_err(|e| anyhow!("invalid tls server name {}: {}", server_name, e))?;
@@ -184,14 +525,32 @@ impl DnsClient {
             .connect(domain, stream)
             .await
             .map_err(|e| anyhow!("connect tls failed: {}", e))?;
-        Ok(Box::new(tls_stream))
+        let alpn = tls_stream.get_ref().1.alpn_protocol().map(|x| x.to_vec());
+        Ok((Box::new(tls_stream), alpn))
     }
 
     #[cfg(all(not(feature = "rustls-tls"), feature = "openssl-tls"))]
-    async fn wrap_doh_tls_stream(stream: AnyStream, server_name: &str) -> Result<AnyStream> {
-        let ssl_connector = SslConnector::builder(SslMethod::tls())
-            .map_err(|e| anyhow!("create ssl connector failed: {}", e))?
-            .build();
+    async fn wrap_tls_stream(
+        stream: AnyStream,
+        server_name: &str,
+        alpns: &[&[u8]],
+        pins: &[String],
+    ) -> Result<(AnyStream, Option<Vec<u8>>)> {
+        if !pins.is_empty() {
+            return Err(anyhow!("certificate pins need rustls"));
+        }
+        let mut builder = SslConnector::builder(SslMethod::tls())
+            .map_err(|e| anyhow!("create ssl connector failed: {}", e))?;
+        if !alpns.is_empty() {
+            let wire: Vec<u8> = alpns
+                .iter()
+                .flat_map(|x| std::iter::once(x.len() as u8).chain(x.iter().copied()))
+                .collect();
+            builder
+                .set_alpn_protos(&wire)
+                .map_err(|e| anyhow!("set alpn failed: {}", e))?;
+        }
+        let ssl_connector = builder.build();
         let mut ssl =
             Ssl::new(ssl_connector.context()).map_err(|e| anyhow!("new ssl failed: {}", e))?;
         ssl.set_hostname(server_name)
@@ -202,18 +561,24 @@ impl DnsClient {
             .connect()
             .map_err(|e| anyhow!("connect ssl stream failed: {}", e))
             .await?;
-        Ok(Box::new(stream))
+        let alpn = stream.ssl().selected_alpn_protocol().map(|x| x.to_vec());
+        Ok((Box::new(stream), alpn))
     }
 
     #[cfg(not(any(feature = "rustls-tls", feature = "openssl-tls")))]
-    async fn wrap_doh_tls_stream(_stream: AnyStream, _server_name: &str) -> Result<AnyStream> {
+    async fn wrap_tls_stream(
+        _stream: AnyStream,
+        _server_name: &str,
+        _alpns: &[&[u8]],
+        _pins: &[String],
+    ) -> Result<(AnyStream, Option<Vec<u8>>)> {
         Err(anyhow!("no tls backend available"))
     }
 
-    fn build_doh_http_request(domain: &str, body_len: usize) -> String {
+    fn build_doh_http_request(doh: &DohResolver, body_len: usize) -> String {
         format!(
-            "POST /dns-query HTTP/1.1\r\nHost: {}\r\nContent-Type: application/dns-message\r\nAccept: application/dns-message\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
-            domain, body_len
+            "POST {} HTTP/1.1\r\nHost: {}\r\nContent-Type: application/dns-message\r\nAccept: application/dns-message\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+            doh.path, doh.authority(), body_len
         )
     }
 
@@ -293,12 +658,270 @@ impl DnsClient {
         Ok(body.to_vec())
     }
 
-    async fn query_doh_message(
+    // Sends a DNS message to a DoH server and returns the DNS message of the
+    // response. The HTTP/2 connections are kept for the following queries,
+    // a new one is dialed when the last is gone.
+    async fn doh_exchange(
         &self,
         request: &[u8],
-        host: &str,
         resolver: &Resolver,
         doh: &DohResolver,
+    ) -> Result<Vec<u8>> {
+        #[cfg(feature = "doh-h2")]
+        {
+            let key = resolver.to_string();
+            let conn = self.doh_connections.lock().await.get(&key).cloned();
+            if let Some(conn) = conn.filter(|x| !x.closed.load(Ordering::Relaxed)) {
+                return Self::doh_h2_exchange(&conn, request, doh).await;
+            }
+        }
+        #[cfg(not(feature = "doh-h2"))]
+        let _ = resolver;
+        let stream = self
+            .connect_server_stream(
+                &doh.domain,
+                doh.port,
+                doh.bootstrap_ip,
+                doh.is_direct,
+                doh.outbound.as_deref(),
+            )
+            .await
+            .map_err(|e| anyhow!("connect doh stream failed: {}", e))?;
+        let (mut stream, alpn) =
+            Self::wrap_tls_stream(stream, &doh.domain, &Self::doh_alpns(), &[])
+                .await
+                .map_err(|e| anyhow!("connect doh tls failed: {}", e))?;
+        let is_h2 = alpn.as_deref() == Some(&b"h2"[..]);
+        #[cfg(feature = "doh-h2")]
+        if is_h2 {
+            let conn = Self::doh_h2_handshake(stream, resolver.to_string()).await?;
+            self.doh_connections
+                .lock()
+                .await
+                .insert(resolver.to_string(), conn.clone());
+            return Self::doh_h2_exchange(&conn, request, doh).await;
+        }
+        #[cfg(not(feature = "doh-h2"))]
+        let _ = is_h2;
+        let request_header = Self::build_doh_http_request(doh, request.len());
+        stream
+            .write_all(request_header.as_bytes())
+            .await
+            .map_err(|e| anyhow!("write doh http header failed: {}", e))?;
+        stream
+            .write_all(request)
+            .await
+            .map_err(|e| anyhow!("write doh message body failed: {}", e))?;
+        stream
+            .flush()
+            .await
+            .map_err(|e| anyhow!("flush doh request failed: {}", e))?;
+        let mut resp = Vec::new();
+        stream
+            .read_to_end(&mut resp)
+            .await
+            .map_err(|e| anyhow!("read doh response failed: {}", e))?;
+        Self::parse_doh_http_body(&resp)
+            .map_err(|e| anyhow!("parse doh http response failed: {}", e))
+    }
+
+    // Starts an HTTP/2 connection over `stream`, the connection is driven by
+    // a task until it's closed.
+    #[cfg(feature = "doh-h2")]
+    async fn doh_h2_handshake(stream: AnyStream, server: String) -> Result<DohConnection> {
+        let (sender, conn) = h2_client::Builder::new()
+            .enable_push(false)
+            .handshake(stream)
+            .await
+            .map_err(|e| anyhow!("doh http2 handshake failed: {}", e))?;
+        let closed = Arc::new(std::sync::atomic::AtomicBool::new(false));
+        let conn_closed = closed.clone();
+        tokio::spawn(async move {
+            if let Err(e) = conn.await {
+                debug!("doh connection to {} failed: {}", server, e);
+            }
+            conn_closed.store(true, Ordering::Relaxed);
+        });
+        Ok(DohConnection { sender, closed })
+    }
+
+    #[cfg(feature = "doh-h2")]
+    async fn doh_h2_exchange(
+        conn: &DohConnection,
+        request: &[u8],
+        doh: &DohResolver,
+    ) -> Result<Vec<u8>> {
+        // A failure of the connection rather than the stream, the next
+        // query dials again.
+        let check = |e: ::h2::Error| {
+            if e.is_go_away() || e.is_io() {
+                conn.closed.store(true, Ordering::Relaxed);
+            }
+            anyhow!("doh http2 request failed: {}", e)
+        };
+        let req = Request::builder()
+            .method(Method::POST)
+            .uri(format!("https://{}{}", doh.authority(), doh.path))
+            .header(header::CONTENT_TYPE, "application/dns-message")
+            .header(header::ACCEPT, "application/dns-message")
+            .header(header::CONTENT_LENGTH, request.len())
+            .body(())?;
+        let mut sender = conn.sender.clone().ready().await.map_err(check)?;
+        let (resp, mut send) = sender.send_request(req, false).map_err(check)?;
+        send.send_data(Bytes::copy_from_slice(request), true)
+            .map_err(check)?;
+        let resp = resp.await.map_err(check)?;
+        if resp.status() != StatusCode::OK {
+            return Err(anyhow!(
+                "doh server returned http status {}",
+                resp.status().as_u16()
+            ));
+        }
+        let mut body = resp.into_body();
+        let mut payload = Vec::new();
+        while let Some(data) = body.data().await {
+            let data = data.map_err(check)?;
+            let _ = body.flow_control().release_capacity(data.len());
+            payload.extend_from_slice(&data);
+            if payload.len() > DOH_MAX_RESPONSE_SIZE {
+                return Err(anyhow!("doh response too large"));
+            }
+        }
+        Ok(payload)
+    }
+
+    // Sends a DNS message to a TCP or TLS server and returns the response.
+    // Queries share the connection to the server, which is dialed again
+    // once it's closed, with a backoff if the dials keep failing.
+    async fn tcp_exchange(
+        &self,
+        request: &[u8],
+        resolver: &Resolver,
+        tcp: &StreamResolver,
+    ) -> Result<Vec<u8>> {
+        let state = self
+            .tcp_connections
+            .lock()
+            .await
+            .entry(resolver.to_string())
+            .or_default()
+            .clone();
+        // Held while dialing, the queries meanwhile wait for the new
+        // connection instead of dialing their own.
+        let mut state = state.lock().await;
+        let conn = match state.conn.as_ref() {
+            Some(conn) if !conn.is_closed() => conn.clone(),
+            _ => {
+                if let Some(conn) = state.conn.take() {
+                    if !conn.answered() {
+                        state.backoff.failed();
+                    }
+                }
+                state.backoff.check()?;
+                match self.dial_tcp(resolver, tcp).await {
+                    Ok(conn) => {
+                        state.backoff.succeeded();
+                        let conn = Arc::new(conn);
+                        state.conn = Some(conn.clone());
+                        conn
+                    }
+                    Err(e) => {
+                        state.backoff.failed();
+                        return Err(e);
+                    }
+                }
+            }
+        };
+        drop(state);
+        conn.exchange(request).await
+    }
+
+    async fn dial_tcp(
+        &self,
+        resolver: &Resolver,
+        tcp: &StreamResolver,
+    ) -> Result<super::tcp::Connection> {
+        let stream = self
+            .connect_server_stream(
+                &tcp.host,
+                tcp.port,
+                tcp.bootstrap_ip,
+                tcp.is_direct,
+                tcp.outbound.as_deref(),
+            )
+            .await
+            .map_err(|e| anyhow!("connect stream failed: {}", e))?;
+        let stream = if tcp.transport == Transport::Tls {
+            let server_name = tcp.sni.as_deref().unwrap_or(&tcp.host);
+            Self::wrap_tls_stream(stream, server_name, &[], &tcp.pins)
+                .await
+                .map_err(|e| anyhow!("connect tls failed: {}", e))?
+                .0
+        } else {
+            stream
+        };
+        Ok(super::tcp::Connection::new(stream, resolver.to_string()))
+    }
+
+    // Sends a DNS message to a QUIC server and returns the response. QUIC
+    // needs a socket of its own, so the server is always reached directly.
+    #[cfg(feature = "doq")]
+    async fn quic_exchange(
+        &self,
+        request: &[u8],
+        resolver: &Resolver,
+        quic: &StreamResolver,
+    ) -> Result<Vec<u8>> {
+        let key = resolver.to_string();
+        let server = {
+            let mut servers = self.quic_servers.lock().await;
+            match servers.get(&key) {
+                Some(server) => server.clone(),
+                None => {
+                    let server_name = quic.sni.as_deref().unwrap_or(&quic.host);
+                    let config = Self::quic_tls_config(&quic.pins)?;
+                    let server =
+                        Arc::new(super::quic::Server::new(config, server_name, key.clone())?);
+                    servers.insert(key, server.clone());
+                    server
+                }
+            }
+        };
+        let dial = async {
+            let addr = self
+                .resolve_bootstrap_addr(&quic.host, quic.port, quic.bootstrap_ip, true)
+                .await
+                .map_err(|e| anyhow!("resolve bootstrap failed: {}", e))?;
+            let socket = self.new_udp_socket(&addr).await?;
+            Ok((addr, socket))
+        };
+        server.exchange(request, dial).await
+    }
+
+    // QUIC is TLS 1.3 only.
+    #[cfg(feature = "doq")]
+    fn quic_tls_config(pins: &[String]) -> Result<ClientConfig> {
+        if !pins.is_empty() {
+            return Self::pinned_tls_config(pins);
+        }
+        #[cfg(feature = "rustls-tls-aws-lc")]
+        let provider = tokio_rustls::rustls::crypto::aws_lc_rs::default_provider();
+        #[cfg(not(feature = "rustls-tls-aws-lc"))]
+        let provider = tokio_rustls::rustls::crypto::ring::default_provider();
+        let mut roots = RootCertStore::empty();
+        roots.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned());
+        Ok(ClientConfig::builder_with_provider(SyncArc::new(provider))
+            .with_protocol_versions(&[&tokio_rustls::rustls::version::TLS13])?
+            .with_root_certificates(roots)
+            .with_no_client_auth())
+    }
+
+    // Queries a DoH, TCP, TLS or QUIC server.
+    async fn query_stream_message(
+        &self,
+        request: &[u8],
+        host: &str,
+        resolver: &Resolver,
     ) -> Result<(Message, Duration)> {
         for i in 0..*option::MAX_DNS_RETRIES {
             let start = tokio::time::Instant::now();
@@ -309,63 +932,40 @@ impl DnsClient {
                 i + 1,
                 *option::MAX_DNS_RETRIES
             );
-            let bootstrap_addr = match self
-                .resolve_doh_bootstrap_addr(&doh.domain, doh.bootstrap_ip)
-                .await
-            {
-                Ok(addr) => addr,
-                Err(err) => {
-                    debug!("resolve doh bootstrap failed: {}", err);
-                    continue;
+            let exchange = async {
+                match resolver {
+                    Resolver::DoH(doh) => self.doh_exchange(request, resolver, doh).await,
+                    #[cfg(feature = "doq")]
+                    Resolver::Stream(quic) if quic.transport == Transport::Quic => {
+                        self.quic_exchange(request, resolver, quic).await
+                    }
+                    Resolver::Stream(tcp) => self.tcp_exchange(request, resolver, tcp).await,
+                    _ => Err(anyhow!("not a stream server")),
                 }
             };
-            let stream = match self.connect_doh_tcp_stream(doh, bootstrap_addr).await {
-                Ok(stream) => stream,
-                Err(err) => {
-                    debug!("connect doh stream failed: {}", err);
+            let res = timeout(Duration::from_secs(*option::DNS_REQUEST_TIMEOUT), exchange).await;
+            let dns_payload = match res {
+                Ok(Ok(payload)) => payload,
+                Ok(Err(err)) => {
+                    debug!("request to {} failed: {}", resolver, err);
                     continue;
                 }
-            };
-            let mut stream = match Self::wrap_doh_tls_stream(stream, &doh.domain).await {
-                Ok(stream) => stream,
-                Err(err) => {
-                    debug!("connect doh tls failed: {}", err);
-                    continue;
-                }
-            };
-            let request_header = Self::build_doh_http_request(&doh.domain, request.len());
-            if let Err(err) = stream.write_all(request_header.as_bytes()).await {
-                debug!("write doh http header failed: {}", err);
-                continue;
-            }
-            if let Err(err) = stream.write_all(request).await {
-                debug!("write doh message body failed: {}", err);
-                continue;
-            }
-            if let Err(err) = stream.flush().await {
-                debug!("flush doh request failed: {}", err);
-                continue;
-            }
-            let mut resp = Vec::new();
-            if let Err(err) = stream.read_to_end(&mut resp).await {
-                debug!("read doh response failed: {}", err);
-                continue;
-            }
-            let dns_payload = match Self::parse_doh_http_body(&resp) {
-                Ok(body) => body,
-                Err(err) => {
-                    debug!("parse doh http response failed: {}", err);
+                Err(_) => {
+                    debug!("request to {} timeout", resolver);
                     continue;
                 }
             };
             let message = match Message::from_vec(&dns_payload) {
                 Ok(message) => message,
                 Err(err) => {
-                    debug!("parse doh dns payload failed: {}", err);
+                    debug!("parse dns payload from {} failed: {}", resolver, err);
                     continue;
                 }
             };
-            if message.response_code() != ResponseCode::NoError {
+            if !matches!(
+                message.response_code(),
+                ResponseCode::NoError | ResponseCode::NXDomain
+            ) {
                 debug!(
                     "error DNS response from {} for {}: {}",
                     resolver,
@@ -377,19 +977,16 @@ impl DnsClient {
             let elapsed = tokio::time::Instant::now().duration_since(start);
             return Ok((message, elapsed));
         }
-        Err(anyhow!("all doh lookup attempts failed"))
+        Err(anyhow!("all lookup attempts failed"))
     }
 
-    async fn query_with_doh(
+    async fn query_with_stream(
         &self,
         request: Vec<u8>,
         host: &str,
         resolver: &Resolver,
-        doh: &DohResolver,
     ) -> Result<CacheEntry> {
-        let (resp, elapsed) = self
-            .query_doh_message(&request, host, resolver, doh)
-            .await?;
+        let (resp, elapsed) = self.query_stream_message(&request, host, resolver).await?;
         let mut ips = Vec::new();
         for ans in resp.answers() {
             if let Some(data) = ans.data() {
@@ -401,13 +998,15 @@ impl DnsClient {
             }
         }
         if ips.is_empty() {
-            return Err(anyhow!(
-                "no records in DNS response from {} for {}",
+            debug!(
+                "no records in DNS response from {} for {}: {}",
                 resolver,
-                host
-            ));
+                host,
+                resp.response_code()
+            );
+            return Ok(self.negative_entry());
         }
-        let ttl = resp.answers().iter().next().unwrap().ttl();
+        let ttl = self.clamp_ttl(resp.answers().iter().next().unwrap().ttl());
         let Some(deadline) = Instant::now().checked_add(Duration::from_secs(ttl.into())) else {
             return Err(anyhow!("invalid ttl"));
         };
@@ -418,20 +1017,21 @@ impl DnsClient {
             elapsed.as_millis(),
             &ips,
         );
-        Ok(CacheEntry { ips, deadline })
+        Ok(CacheEntry {
+            ips,
+            deadline,
+            scope: Self::answer_scope(&resp),
+        })
     }
 
-    async fn query_ech_with_doh(
+    async fn query_ech_with_stream(
         &self,
         request: Vec<u8>,
         host: &str,
         resolver: &Resolver,
-        doh: &DohResolver,
         ty: RecordType,
     ) -> Result<EchCacheEntry> {
-        let (resp, elapsed) = self
-            .query_doh_message(&request, host, resolver, doh)
-            .await?;
+        let (resp, elapsed) = self.query_stream_message(&request, host, resolver).await?;
         let mut last_ttl = None;
         for ans in resp.answers() {
             if ans.record_type() != ty {
@@ -441,7 +1041,7 @@ impl DnsClient {
                 last_ttl = Some(ans.ttl());
                 let value = data.to_string();
                 if let Some(ech_config_list) = Self::extract_ech_config_list(&value) {
-                    let ttl = ans.ttl();
+                    let ttl = self.clamp_ttl(ans.ttl());
                     let Some(deadline) =
                         Instant::now().checked_add(Duration::from_secs(ttl.into()))
                     else {
@@ -473,22 +1073,91 @@ impl DnsClient {
         Err(anyhow!("no {} records for {} from {}", ty, host, resolver))
     }
 
-    fn load_hosts(dns: &crate::config::Dns) -> HashMap<String, Vec<IpAddr>> {
-        let mut hosts = HashMap::new();
+    fn load_hosts(dns: &crate::config::Dns) -> Hosts {
+        let mut hosts = Hosts::default();
         for (name, ips) in dns.hosts.iter() {
-            hosts.insert(name.to_owned(), ips.values.to_vec());
-        }
-        let mut parsed_hosts = HashMap::new();
-        for (name, static_ips) in hosts.iter() {
-            let mut ips = Vec::new();
-            for ip in static_ips {
-                if let Ok(parsed_ip) = ip.parse::<IpAddr>() {
-                    ips.push(parsed_ip);
+            let mut parsed_ips = Vec::new();
+            for ip in ips.values.iter() {
+                match ip.parse::<IpAddr>() {
+                    Ok(ip) => parsed_ips.push(ip),
+                    Err(e) => warn!("skip invalid ip [{}] of host {}: {}", ip, name, e),
                 }
             }
-            parsed_hosts.insert(name.to_owned(), ips);
+            hosts.insert(name, parsed_ips);
+        }
+        hosts
+    }
+
+    fn load_strategy(dns: &crate::config::Dns) -> Result<Strategy> {
+        if dns.strategy.is_empty() {
+            return Ok(Strategy::from_options());
+        }
+        dns.strategy.parse()
+    }
+
+    fn load_fake_ip_filter(dns: &crate::config::Dns) -> Result<FakeIpFilter> {
+        let whitelist = match dns.fake_ip_filter_mode.as_str() {
+            "" | "blacklist" => false,
+            "whitelist" => true,
+            mode => return Err(anyhow!("invalid fake ip filter mode {}", mode)),
+        };
+        Ok(FakeIpFilter {
+            whitelist,
+            domains: crate::app::router::DomainSet::new(&dns.fake_ip_filter),
+        })
+    }
+
+    fn hosts_ttl(dns: &crate::config::Dns) -> u32 {
+        if dns.hosts_ttl > 0 {
+            dns.hosts_ttl
+        } else {
+            DEFAULT_HOSTS_TTL
+        }
+    }
+
+    fn negative_ttl(dns: &crate::config::Dns) -> u32 {
+        if dns.cache_negative_ttl > 0 {
+            dns.cache_negative_ttl
+        } else {
+            DEFAULT_NEGATIVE_TTL
+        }
+    }
+
+    fn cache_size(dns: &crate::config::Dns) -> NonZeroUsize {
+        NonZeroUsize::new(dns.cache_size as usize)
+            .unwrap_or_else(|| NonZeroUsize::new(*option::DNS_CACHE_SIZE).unwrap())
+    }
+
+    // A prefix such as 1.2.3.0/24, or auto for the network of the public IP.
+    fn load_client_subnet(dns: &crate::config::Dns) -> Result<Option<IpCidr>> {
+        match dns.edns_client_subnet.as_str() {
+            "" => Ok(None),
+            "auto" => {
+                let ip = option::PUBLIC_IP
+                    .parse::<IpAddr>()
+                    .map_err(|_| anyhow!("auto edns client subnet needs a valid PUBLIC_IP"))?;
+                Ok(Some(Self::public_subnet(ip)))
+            }
+            subnet => subnet
+                .parse::<IpInet>()
+                .map(|x| Some(x.network()))
+                .map_err(|e| anyhow!("invalid edns client subnet {}: {}", subnet, e)),
+        }
+    }
+
+    // The /24 or /56 network of an address, about as much of it as the
+    // servers need to pick the close addresses.
+    fn public_subnet(ip: IpAddr) -> IpCidr {
+        let len = if ip.is_ipv4() { 24 } else { 56 };
+        IpInet::new(ip, len).unwrap().network()
+    }
+
+    fn cache_file(dns: &crate::config::Dns) -> Option<String> {
+        if dns.cache_file.is_empty() {
+            None
+        } else {
+            Some(dns.cache_file.clone())
         }
-        parsed_hosts
     }
 
     pub fn new(dns: &protobuf::MessageField<crate::config::Dns>) -> Result<Self> {
@@ -498,26 +1167,65 @@ impl DnsClient {
             return Err(anyhow!("empty dns config"));
         };
         let servers = Self::load_servers(dns)?;
+        let groups = Self::load_groups(dns)?;
+        let rules = Self::load_rules(dns, &groups)?;
         let hosts = Self::load_hosts(dns);
-        let ipv4_cache = Arc::new(TokioMutex::new(LruCache::<String, CacheEntry>::new(
-            NonZeroUsize::new(*option::DNS_CACHE_SIZE).unwrap(),
-        )));
-        let ipv6_cache = Arc::new(TokioMutex::new(LruCache::<String, CacheEntry>::new(
-            NonZeroUsize::new(*option::DNS_CACHE_SIZE).unwrap(),
-        )));
+        let hosts_ttl = Self::hosts_ttl(dns);
+        let strategy = Self::load_strategy(dns)?;
+        let fake_ip_filter = Self::load_fake_ip_filter(dns)?;
+        let client_subnet = Self::load_client_subnet(dns)?;
+        let cache_size = Self::cache_size(dns);
+        let cache_file = Self::cache_file(dns);
+        let mut ipv4_cache = LruCache::<String, CacheEntry>::new(cache_size);
+        let mut ipv6_cache = LruCache::<String, CacheEntry>::new(cache_size);
+        if let Some(path) = cache_file.as_ref() {
+            match std::fs::read_to_string(path) {
+                Ok(data) => {
+                    for (ty, host, entry) in Self::parse_cache_file(&data, SystemTime::now()) {
+                        if ty == RecordType::AAAA {
+                            ipv6_cache.put(host, entry);
+                        } else {
+                            ipv4_cache.put(host, entry);
+                        }
+                    }
+                }
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => (),
+                Err(e) => warn!("read dns cache file {} failed: {}", path, e),
+            }
+        }
+        let ipv4_cache = Arc::new(TokioMutex::new(ipv4_cache));
+        let ipv6_cache = Arc::new(TokioMutex::new(ipv6_cache));
         let ech_cache = Arc::new(TokioMutex::new(LruCache::<String, EchCacheEntry>::new(
-            NonZeroUsize::new(*option::DNS_CACHE_SIZE).unwrap(),
+            cache_size,
         )));
 
         Ok(Self {
             dispatcher: None,
             servers,
+            groups,
+            rules,
             hosts,
+            hosts_ttl,
+            strategy,
+            fake_ip_filter,
+            concurrent_queries: dns.concurrent_queries,
+            client_subnet,
+            cache_min_ttl: dns.cache_min_ttl,
+            cache_max_ttl: dns.cache_max_ttl,
+            negative_ttl: Self::negative_ttl(dns),
+            cache_file,
             ipv4_cache,
             ipv6_cache,
             ech_cache,
             ech_query_locks: Arc::new(TokioMutex::new(HashMap::new())),
             selector_state: Arc::new(Mutex::new(ServerSelectorState::default())),
+            cache_hits: AtomicU64::new(0),
+            cache_misses: AtomicU64::new(0),
+            #[cfg(feature = "doh-h2")]
+            doh_connections: Arc::new(TokioMutex::new(HashMap::new())),
+            tcp_connections: Arc::new(TokioMutex::new(HashMap::new())),
+            #[cfg(feature = "doq")]
+            quic_servers: Arc::new(TokioMutex::new(HashMap::new())),
         })
     }
 
@@ -532,9 +1240,46 @@ impl DnsClient {
             return Err(anyhow!("empty dns config"));
         };
         let servers = Self::load_servers(dns)?;
+        let groups = Self::load_groups(dns)?;
+        let rules = Self::load_rules(dns, &groups)?;
         let hosts = Self::load_hosts(dns);
+        let strategy = Self::load_strategy(dns)?;
+        let fake_ip_filter = Self::load_fake_ip_filter(dns)?;
+        let client_subnet = Self::load_client_subnet(dns)?;
         self.servers = servers;
+        self.groups = groups;
+        self.rules = rules;
         self.hosts = hosts;
+        self.hosts_ttl = Self::hosts_ttl(dns);
+        self.strategy = strategy;
+        self.fake_ip_filter = fake_ip_filter;
+        self.concurrent_queries = dns.concurrent_queries;
+        self.client_subnet = client_subnet;
+        self.cache_min_ttl = dns.cache_min_ttl;
+        self.cache_max_ttl = dns.cache_max_ttl;
+        self.negative_ttl = Self::negative_ttl(dns);
+        self.cache_file = Self::cache_file(dns);
+        // The lookups, which hold the caches, can't run alongside.
+        let cache_size = Self::cache_size(dns);
+        if let Ok(mut cache) = self.ipv4_cache.try_lock() {
+            cache.resize(cache_size);
+        }
+        if let Ok(mut cache) = self.ipv6_cache.try_lock() {
+            cache.resize(cache_size);
+        }
+        if let Ok(mut cache) = self.ech_cache.try_lock() {
+            cache.resize(cache_size);
+        }
+        // The connections still in use close after their queries.
+        #[cfg(feature = "doh-h2")]
+        {
+            self.doh_connections = Arc::new(TokioMutex::new(HashMap::new()));
+        }
+        self.tcp_connections = Arc::new(TokioMutex::new(HashMap::new()));
+        #[cfg(feature = "doq")]
+        {
+            self.quic_servers = Arc::new(TokioMutex::new(HashMap::new()));
+        }
         if let Ok(mut selector) = self.selector_state.lock() {
             selector.primary_server = None;
             selector.last_reselect_at = None;
@@ -614,7 +1359,7 @@ impl DnsClient {
         resolver: &Resolver,
     ) -> Result<CacheEntry> {
         let resolver_addr = match resolver {
-            Resolver::Server(addr, _) => SocksAddr::from(*addr),
+            Resolver::Server(addr, ..) => SocksAddr::from(*addr),
             _ => SocksAddr::any_ipv4(),
         };
         async move {
@@ -660,6 +1405,11 @@ impl DnsClient {
                     }
                 };
 
+                if resp.response_code() == ResponseCode::NXDomain {
+                    debug!("no such domain from {} for {}", resolver, host);
+                    return Ok(self.negative_entry());
+                }
+
                 if resp.response_code() != ResponseCode::NoError {
                     debug!(
                         "error DNS response from {} for {}: {}",
@@ -687,11 +1437,11 @@ impl DnsClient {
 
                 if ips.is_empty() {
                     debug!("no records in DNS response from {} for {}", resolver, host);
-                    break;
+                    return Ok(self.negative_entry());
                 }
 
                 let elapsed = tokio::time::Instant::now().duration_since(start);
-                let ttl = resp.answers().iter().next().unwrap().ttl();
+                let ttl = self.clamp_ttl(resp.answers().iter().next().unwrap().ttl());
                 debug!(
                     "received from server={} ttl={} elapsed={}ms ips={:?}",
                     resolver,
@@ -706,7 +1456,11 @@ impl DnsClient {
                     break;
                 };
 
-                let entry = CacheEntry { ips, deadline };
+                let entry = CacheEntry {
+                    ips,
+                    deadline,
+                    scope: Self::answer_scope(&resp),
+                };
                 return Ok(entry);
             }
             Err(anyhow!("all lookup attempts failed"))
@@ -760,7 +1514,7 @@ impl DnsClient {
         ty: RecordType,
     ) -> Result<EchCacheEntry> {
         let resolver_addr = match resolver {
-            Resolver::Server(addr, _) => SocksAddr::from(*addr),
+            Resolver::Server(addr, ..) => SocksAddr::from(*addr),
             _ => SocksAddr::any_ipv4(),
         };
         async move {
@@ -826,7 +1580,7 @@ impl DnsClient {
                         last_ttl = Some(ans.ttl());
                         let value = data.to_string();
                         if let Some(ech_config_list) = Self::extract_ech_config_list(&value) {
-                            let ttl = ans.ttl();
+                            let ttl = self.clamp_ttl(ans.ttl());
                             let elapsed = tokio::time::Instant::now().duration_since(start);
                             debug!(
                                 "received ech from server={} type={} ttl={} elapsed={}ms len={}",
@@ -871,6 +1625,19 @@ impl DnsClient {
         .await
     }
 
+    // Sends the queries of a session through the outbound of the server,
+    // or the one the router picks.
+    async fn dispatch_datagram(
+        dispatcher: &Dispatcher,
+        sess: Session,
+        outbound: Option<&str>,
+    ) -> std::io::Result<Box<dyn OutboundDatagram>> {
+        match outbound {
+            Some(tag) => dispatcher.dispatch_datagram_via(sess, tag).await,
+            None => dispatcher.dispatch_datagram(sess).await,
+        }
+    }
+
     async fn resolve_with_server(
         &self,
         is_direct: bool,
@@ -879,7 +1646,7 @@ impl DnsClient {
         resolver: &Resolver,
     ) -> Result<CacheEntry> {
         let (socket, span) = match resolver {
-            Resolver::Server(server, _) if is_direct => {
+            Resolver::Server(server, ..) if is_direct => {
                 debug!("direct lookup");
                 let socket = self.new_udp_socket(server).await?;
                 (
@@ -887,7 +1654,7 @@ impl DnsClient {
                     tracing::Span::current(),
                 )
             }
-            Resolver::Server(server, _) => {
+            Resolver::Server(server, _, outbound, _) => {
                 debug!("dispatched lookup");
                 if let Some(dispatcher_weak) = self.dispatcher.as_ref() {
                     // The source address will be used to determine which address the
@@ -906,8 +1673,7 @@ impl DnsClient {
                     let span = sess.span();
                     if let Some(dispatcher) = dispatcher_weak.upgrade() {
                         (
-                            dispatcher
-                                .dispatch_datagram(sess)
+                            Self::dispatch_datagram(&dispatcher, sess, outbound.as_deref())
                                 .instrument(span.clone())
                                 .await?,
                             span,
@@ -946,10 +1712,11 @@ impl DnsClient {
                 return Ok(CacheEntry {
                     ips,
                     deadline: Instant::now() + Duration::from_secs(60),
+                    scope: None,
                 });
             }
-            Resolver::DoH(doh) => {
-                return self.query_with_doh(request, host, resolver, doh).await;
+            Resolver::DoH(_) | Resolver::Stream(_) => {
+                return self.query_with_stream(request, host, resolver).await;
             }
         };
 
@@ -966,14 +1733,14 @@ impl DnsClient {
         ty: RecordType,
     ) -> Result<EchCacheEntry> {
         let (socket, span) = match resolver {
-            Resolver::Server(server, _) if is_direct => {
+            Resolver::Server(server, ..) if is_direct => {
                 let socket = self.new_udp_socket(server).await?;
                 (
                     Box::new(StdOutboundDatagram::new(socket)) as Box<dyn OutboundDatagram>,
                     tracing::Span::current(),
                 )
             }
-            Resolver::Server(server, _) => {
+            Resolver::Server(server, _, outbound, _) => {
                 if let Some(dispatcher_weak) = self.dispatcher.as_ref() {
                     let source = match server {
                         SocketAddr::V4(_) => SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), 0),
@@ -989,8 +1756,7 @@ impl DnsClient {
                     let span = sess.span();
                     if let Some(dispatcher) = dispatcher_weak.upgrade() {
                         (
-                            dispatcher
-                                .dispatch_datagram(sess)
+                            Self::dispatch_datagram(&dispatcher, sess, outbound.as_deref())
                                 .instrument(span.clone())
                                 .await?,
                             span,
@@ -1005,9 +1771,9 @@ impl DnsClient {
             Resolver::System(_) => {
                 return Err(anyhow!("system resolver does not support {} query", ty));
             }
-            Resolver::DoH(doh) => {
+            Resolver::DoH(_) | Resolver::Stream(_) => {
                 return self
-                    .query_ech_with_doh(request, host, resolver, doh, ty)
+                    .query_ech_with_stream(request, host, resolver, ty)
                     .await;
             }
         };
@@ -1016,16 +1782,23 @@ impl DnsClient {
             .await
     }
 
-    fn select_preferred_server_index(&self, servers: &[&Resolver]) -> usize {
-        if let Ok(mut selector) = self.selector_state.lock() {
+    fn select_preferred_server_index(
+        selector: &Mutex<ServerSelectorState>,
+        servers: &[&Resolver],
+    ) -> usize {
+        if let Ok(mut selector) = selector.lock() {
             selector.select_primary_index(servers)
         } else {
             0
         }
     }
 
-    fn fallback_server_indices(&self, servers: &[&Resolver], preferred_idx: usize) -> Vec<usize> {
-        if let Ok(selector) = self.selector_state.lock() {
+    fn fallback_server_indices(
+        selector: &Mutex<ServerSelectorState>,
+        servers: &[&Resolver],
+        preferred_idx: usize,
+    ) -> Vec<usize> {
+        if let Ok(selector) = selector.lock() {
             selector.fallback_indices(servers, preferred_idx)
         } else {
             (0..servers.len())
@@ -1034,33 +1807,49 @@ impl DnsClient {
         }
     }
 
-    fn mark_server_success(&self, resolver: &Resolver, elapsed: Duration) {
-        if let Ok(mut selector) = self.selector_state.lock() {
+    fn mark_server_success(
+        selector: &Mutex<ServerSelectorState>,
+        resolver: &Resolver,
+        elapsed: Duration,
+    ) {
+        if let Ok(mut selector) = selector.lock() {
             selector.mark_success(&resolver.to_string(), elapsed);
         }
     }
 
-    fn mark_server_failure(&self, resolver: &Resolver, is_timeout: bool) {
-        if let Ok(mut selector) = self.selector_state.lock() {
+    fn mark_server_failure(
+        selector: &Mutex<ServerSelectorState>,
+        resolver: &Resolver,
+        is_timeout: bool,
+    ) {
+        if let Ok(mut selector) = selector.lock() {
             selector.mark_failure(&resolver.to_string(), is_timeout);
         }
     }
 
-    fn switch_primary_server(&self, resolver: &Resolver) {
-        if let Ok(mut selector) = self.selector_state.lock() {
+    fn mark_server_lost(selector: &Mutex<ServerSelectorState>, resolver: &Resolver) {
+        if let Ok(mut selector) = selector.lock() {
+            selector.mark_lost(&resolver.to_string());
+        }
+    }
+
+    fn switch_primary_server(selector: &Mutex<ServerSelectorState>, resolver: &Resolver) {
+        if let Ok(mut selector) = selector.lock() {
             selector.set_primary(&resolver.to_string());
         }
     }
 
     async fn query_task(
         &self,
+        selector: &Mutex<ServerSelectorState>,
         is_direct: bool,
-        request: Vec<u8>,
+        requests: &Requests,
         host: &str,
         resolver: &Resolver,
         ty: RecordType,
     ) -> Result<(CacheEntry, Duration)> {
         let start = tokio::time::Instant::now();
+        let request = requests.for_server(resolver);
         let res = match timeout(
             Duration::from_secs(*option::DNS_TIMEOUT),
             self.resolve_with_server(is_direct, request, host, resolver),
@@ -1074,12 +1863,12 @@ impl DnsClient {
             Ok(entry) => {
                 trace!("query {} {} success with server {}", host, ty, resolver);
                 let elapsed = start.elapsed();
-                self.mark_server_success(resolver, elapsed);
+                Self::mark_server_success(selector, resolver, elapsed);
                 Ok((entry, elapsed))
             }
             Err(e) => {
                 let is_timeout = e.to_string().contains("timeout");
-                self.mark_server_failure(resolver, is_timeout);
+                Self::mark_server_failure(selector, resolver, is_timeout);
                 debug!(
                     "query {} {} failed with server {}: {}",
                     host, ty, resolver, e
@@ -1091,13 +1880,15 @@ impl DnsClient {
 
     async fn query_ech_task(
         &self,
+        selector: &Mutex<ServerSelectorState>,
         is_direct: bool,
-        request: Vec<u8>,
+        requests: &Requests,
         host: &str,
         resolver: &Resolver,
         ty: RecordType,
     ) -> Result<(EchCacheEntry, Duration)> {
         let start = tokio::time::Instant::now();
+        let request = requests.for_server(resolver);
         let res = match timeout(
             Duration::from_secs(*option::DNS_TIMEOUT),
             self.resolve_ech_with_server(is_direct, request, host, resolver, ty),
@@ -1110,12 +1901,12 @@ impl DnsClient {
         match res {
             Ok(entry) => {
                 let elapsed = start.elapsed();
-                self.mark_server_success(resolver, elapsed);
+                Self::mark_server_success(selector, resolver, elapsed);
                 Ok((entry, elapsed))
             }
             Err(e) => {
                 let is_timeout = e.to_string().contains("timeout");
-                self.mark_server_failure(resolver, is_timeout);
+                Self::mark_server_failure(selector, resolver, is_timeout);
                 debug!(
                     "query ech {} {} failed with server {}: {}",
                     host, ty, resolver, e
@@ -1139,58 +1930,72 @@ impl DnsClient {
                     ..Default::default()
                 };
                 if let Ok(Some(tag)) = dispatcher.router.read().await.pick_route(&sess).await {
-                    is_direct_outbound = dispatcher.is_direct_outbound(tag).await;
+                    is_direct_outbound = dispatcher.is_direct_outbound(&tag).await;
                 }
             }
         }
         Ok(is_direct_outbound)
     }
 
-    fn collect_servers(&self, is_direct_outbound: bool) -> Vec<&Resolver> {
-        let mut servers = Vec::new();
-        if is_direct_outbound {
-            for server in &self.servers {
-                match server {
-                    Resolver::Server(_, true) | Resolver::System(true) => {
-                        servers.push(server);
-                    }
-                    Resolver::DoH(doh) if doh.is_direct => {
-                        servers.push(server);
-                    }
-                    _ => (),
+    // Returns the servers of the group of the first rule the host matches,
+    // or the default servers, along with the state their primary server is
+    // picked with.
+    fn select_group(&self, host: &str) -> (&[Resolver], &Mutex<ServerSelectorState>) {
+        for rule in &self.rules {
+            if let Some(value) = rule.domains.find(host) {
+                if let Some(group) = self.groups.get(&rule.group) {
+                    trace!("{} matches [{}] of dns group {}", host, value, rule.group);
+                    return (&group.servers, &group.selector_state);
                 }
             }
+        }
+        (&self.servers, &*self.selector_state)
+    }
+
+    /// Returns the name of the group resolving the host if it matches a rule.
+    pub fn server_group(&self, host: &str) -> Option<&str> {
+        self.rules
+            .iter()
+            .find(|x| x.domains.find(host).is_some())
+            .map(|x| x.group.as_str())
+    }
+
+    /// Returns whether the fake DNS leaves the host to its real addresses.
+    pub fn bypasses_fake_ip(&self, host: &str) -> bool {
+        let listed = self.fake_ip_filter.domains.find(host).is_some();
+        listed != self.fake_ip_filter.whitelist
+    }
+
+    // The servers keep their configured order whatever their kinds. The
+    // direct lookups, which the outbounds resolve their own servers with,
+    // skip the servers queried through outbounds, since these could need
+    // the lookups themselves, and are left with the system resolver if
+    // there's nothing else.
+    fn collect_servers(
+        all: &[Resolver],
+        is_direct_outbound: bool,
+        is_direct: bool,
+    ) -> Vec<&Resolver> {
+        let all: Vec<&Resolver> = all
+            .iter()
+            .filter(|x| !is_direct || x.outbound().is_none())
+            .collect();
+        if all.is_empty() {
+            debug!("no dns servers for direct lookup, fallback to system resolver");
+            return vec![&SYSTEM_RESOLVER];
+        }
+        let mut servers = Vec::new();
+        if is_direct_outbound {
+            servers.extend(all.iter().copied().filter(|x| x.is_direct()));
             if servers.is_empty() {
                 debug!("no direct dns servers for direct outbound, fallback to normal servers");
-                for server in &self.servers {
-                    match server {
-                        Resolver::Server(_, false) | Resolver::System(false) => {
-                            servers.push(server);
-                        }
-                        Resolver::DoH(doh) if !doh.is_direct => {
-                            servers.push(server);
-                        }
-                        _ => (),
-                    }
-                }
+                servers.extend(all.iter().copied().filter(|x| !x.is_direct()));
             }
         } else {
-            for server in &self.servers {
-                match server {
-                    Resolver::Server(_, false) | Resolver::System(false) => {
-                        servers.push(server);
-                    }
-                    Resolver::DoH(doh) if !doh.is_direct => {
-                        servers.push(server);
-                    }
-                    _ => (),
-                }
-            }
+            servers.extend(all.iter().copied().filter(|x| !x.is_direct()));
         }
         if servers.is_empty() {
-            for server in &self.servers {
-                servers.push(server);
-            }
+            servers = all;
         }
         servers
     }
@@ -1203,36 +2008,38 @@ impl DnsClient {
         host: &str,
         ty: RecordType,
     ) -> Result<CacheEntry> {
-        let msg = Self::new_query(name.clone(), ty);
-        let msg_buf = match msg.to_vec() {
-            Ok(b) => b,
-            Err(e) => return Err(anyhow!("encode message to buffer failed: {}", e)),
-        };
+        let requests = &self.new_requests(name, ty)?;
 
         let is_direct_outbound = self.is_direct_outbound(host).await?;
-        let servers = self.collect_servers(is_direct_outbound);
+        let (servers, selector) = self.select_group(host);
+        let servers = Self::collect_servers(servers, is_direct_outbound, is_direct);
         if servers.is_empty() {
             return Err(anyhow!("no dns servers available for query"));
         }
         if servers.len() == 1 {
             return self
-                .query_task(is_direct, msg_buf, host, servers[0], ty)
+                .query_task(selector, is_direct, requests, host, servers[0], ty)
                 .await
                 .map(|(entry, _)| entry);
         }
-        let preferred_idx = self.select_preferred_server_index(&servers);
+        if self.concurrent_queries {
+            return self
+                .race_query(selector, is_direct, requests, host, &servers, ty)
+                .await;
+        }
+        let preferred_idx = Self::select_preferred_server_index(selector, &servers);
         let preferred = servers[preferred_idx];
         let mut errors = Vec::new();
 
         match self
-            .query_task(is_direct, msg_buf.clone(), host, preferred, ty)
+            .query_task(selector, is_direct, requests, host, preferred, ty)
             .await
         {
             Ok((entry, _)) => return Ok(entry),
             Err(err) => errors.push(format!("{}: {}", preferred, err)),
         }
 
-        let fallback_indices = self.fallback_server_indices(&servers, preferred_idx);
+        let fallback_indices = Self::fallback_server_indices(selector, &servers, preferred_idx);
         let fallback_concurrency = (*option::DNS_SERVER_FALLBACK_CONCURRENCY).max(1);
         let mut cursor = 0usize;
         while cursor < fallback_indices.len() {
@@ -1244,11 +2051,11 @@ impl DnsClient {
             if batch.len() == 1 {
                 let idx = batch[0];
                 match self
-                    .query_task(is_direct, msg_buf.clone(), host, servers[idx], ty)
+                    .query_task(selector, is_direct, requests, host, servers[idx], ty)
                     .await
                 {
                     Ok((entry, _)) => {
-                        self.switch_primary_server(servers[idx]);
+                        Self::switch_primary_server(selector, servers[idx]);
                         return Ok(entry);
                     }
                     Err(err) => errors.push(format!("{}: {}", servers[idx], err)),
@@ -1257,9 +2064,8 @@ impl DnsClient {
                 let mut tasks = Vec::new();
                 for idx in batch {
                     let resolver = servers[*idx];
-                    let request = msg_buf.clone();
                     let t = async move {
-                        self.query_task(is_direct, request, host, resolver, ty)
+                        self.query_task(selector, is_direct, requests, host, resolver, ty)
                             .await
                             .map(|(entry, _)| (*idx, entry))
                     };
@@ -1267,7 +2073,7 @@ impl DnsClient {
                 }
                 match select_ok(tasks.into_iter()).await {
                     Ok(((idx, entry), _)) => {
-                        self.switch_primary_server(servers[idx]);
+                        Self::switch_primary_server(selector, servers[idx]);
                         return Ok(entry);
                     }
                     Err(err) => errors.push(format!("fallback batch failed: {}", err)),
@@ -1279,6 +2085,61 @@ impl DnsClient {
         Err(anyhow!("all dns queries failed: {}", errors.join("; ")))
     }
 
+    // Sends the query to the servers with the best scores at once. The
+    // first answer with addresses wins, an empty one only if none of the
+    // others has any. The queries still running are then dropped, and
+    // their servers counted as having lost, which lowers their scores.
+    async fn race_query(
+        &self,
+        selector: &Mutex<ServerSelectorState>,
+        is_direct: bool,
+        requests: &Requests,
+        host: &str,
+        servers: &[&Resolver],
+        ty: RecordType,
+    ) -> Result<CacheEntry> {
+        let preferred_idx = Self::select_preferred_server_index(selector, servers);
+        let mut pending = vec![preferred_idx];
+        pending.extend(Self::fallback_server_indices(
+            selector,
+            servers,
+            preferred_idx,
+        ));
+        pending.truncate((*option::DNS_CONCURRENT_QUERY_SERVERS).max(1));
+        let mut tasks = pending
+            .clone()
+            .into_iter()
+            .map(|idx| async move {
+                let res = self
+                    .query_task(selector, is_direct, requests, host, servers[idx], ty)
+                    .await;
+                (idx, res)
+            })
+            .collect::<FuturesUnordered<_>>();
+        let mut empty = None;
+        let mut errors = Vec::new();
+        while let Some((idx, res)) = tasks.next().await {
+            match res {
+                Ok((entry, _)) if !entry.ips.is_empty() => {
+                    for lost in pending.iter().filter(|x| **x != idx) {
+                        Self::mark_server_lost(selector, servers[*lost]);
+                    }
+                    Self::switch_primary_server(selector, servers[idx]);
+                    return Ok(entry);
+                }
+                Ok((entry, _)) => {
+                    empty.get_or_insert(entry);
+                }
+                Err(err) => errors.push(format!("{}: {}", servers[idx], err)),
+            }
+            pending.retain(|x| *x != idx);
+        }
+        if let Some(entry) = empty {
+            return Ok(entry);
+        }
+        Err(anyhow!("all dns queries failed: {}", errors.join("; ")))
+    }
+
     async fn query_ech_record_type(
         &self,
         is_direct: bool,
@@ -1286,35 +2147,32 @@ impl DnsClient {
         host: &str,
         ty: RecordType,
     ) -> Result<EchCacheEntry> {
-        let msg = Self::new_query(name.clone(), ty);
-        let msg_buf = match msg.to_vec() {
-            Ok(b) => b,
-            Err(e) => return Err(anyhow!("encode message to buffer failed: {}", e)),
-        };
+        let requests = &self.new_requests(name, ty)?;
         let is_direct_outbound = self.is_direct_outbound(host).await?;
-        let servers = self.collect_servers(is_direct_outbound);
+        let (servers, selector) = self.select_group(host);
+        let servers = Self::collect_servers(servers, is_direct_outbound, is_direct);
         if servers.is_empty() {
             return Err(anyhow!("no dns servers available for query"));
         }
         if servers.len() == 1 {
             return self
-                .query_ech_task(is_direct, msg_buf, host, servers[0], ty)
+                .query_ech_task(selector, is_direct, requests, host, servers[0], ty)
                 .await
                 .map(|(entry, _)| entry);
         }
-        let preferred_idx = self.select_preferred_server_index(&servers);
+        let preferred_idx = Self::select_preferred_server_index(selector, &servers);
         let preferred = servers[preferred_idx];
         let mut errors = Vec::new();
 
         match self
-            .query_ech_task(is_direct, msg_buf.clone(), host, preferred, ty)
+            .query_ech_task(selector, is_direct, requests, host, preferred, ty)
             .await
         {
             Ok((entry, _)) => return Ok(entry),
             Err(err) => errors.push(format!("{}: {}", preferred, err)),
         }
 
-        let fallback_indices = self.fallback_server_indices(&servers, preferred_idx);
+        let fallback_indices = Self::fallback_server_indices(selector, &servers, preferred_idx);
         let fallback_concurrency = (*option::DNS_SERVER_FALLBACK_CONCURRENCY).max(1);
         let mut cursor = 0usize;
         while cursor < fallback_indices.len() {
@@ -1326,11 +2184,11 @@ impl DnsClient {
             if batch.len() == 1 {
                 let idx = batch[0];
                 match self
-                    .query_ech_task(is_direct, msg_buf.clone(), host, servers[idx], ty)
+                    .query_ech_task(selector, is_direct, requests, host, servers[idx], ty)
                     .await
                 {
                     Ok((entry, _)) => {
-                        self.switch_primary_server(servers[idx]);
+                        Self::switch_primary_server(selector, servers[idx]);
                         return Ok(entry);
                     }
                     Err(err) => errors.push(format!("{}: {}", servers[idx], err)),
@@ -1339,9 +2197,8 @@ impl DnsClient {
                 let mut tasks = Vec::new();
                 for idx in batch {
                     let resolver = servers[*idx];
-                    let request = msg_buf.clone();
                     let t = async move {
-                        self.query_ech_task(is_direct, request, host, resolver, ty)
+                        self.query_ech_task(selector, is_direct, requests, host, resolver, ty)
                             .await
                             .map(|(entry, _)| (*idx, entry))
                     };
@@ -1349,7 +2206,7 @@ impl DnsClient {
                 }
                 match select_ok(tasks.into_iter()).await {
                     Ok(((idx, entry), _)) => {
-                        self.switch_primary_server(servers[idx]);
+                        Self::switch_primary_server(selector, servers[idx]);
                         return Ok(entry);
                     }
                     Err(err) => errors.push(format!("fallback batch failed: {}", err)),
@@ -1361,12 +2218,15 @@ impl DnsClient {
         Err(anyhow!("all ech queries failed: {}", errors.join("; ")))
     }
 
+    // Returns the answers of the preferred and fallback families. One which
+    // is empty or failed waits for the other one, which is only waited for
+    // up to `delay` otherwise.
     async fn dualstack_query<P, F>(
         &self,
         preferred: &mut P,
         fallback: &mut F,
         delay: Duration,
-    ) -> Result<(CacheEntry, Option<CacheEntry>)>
+    ) -> Result<(Option<CacheEntry>, Option<CacheEntry>)>
     where
         P: std::future::Future<Output = Result<CacheEntry>> + Unpin,
         F: std::future::Future<Output = Result<CacheEntry>> + Unpin,
@@ -1388,32 +2248,28 @@ impl DnsClient {
             },
         };
 
-        match first_res {
-            Ok(entry) => {
-                let other = if first_is_preferred {
-                    match timeout(Duration::from_millis(0), &mut *fallback).await {
-                        Ok(Ok(e)) => Some(e),
-                        _ => None,
-                    }
-                } else {
-                    match timeout(Duration::from_millis(0), &mut *preferred).await {
-                        Ok(Ok(e)) => Some(e),
-                        _ => None,
-                    }
-                };
-                Ok((entry, other))
-            }
-            Err(err1) => {
-                let second_res = if first_is_preferred {
-                    (&mut *fallback).await
-                } else {
-                    (&mut *preferred).await
-                };
-                match second_res {
-                    Ok(entry) => Ok((entry, None)),
-                    Err(err2) => Err(anyhow!("all dns queries failed: {}; {}", err1, err2)),
-                }
-            }
+        let has_ips = matches!(&first_res, Ok(entry) if !entry.ips.is_empty());
+        let second_res = if has_ips {
+            let res = if first_is_preferred {
+                timeout(Duration::from_millis(0), &mut *fallback).await
+            } else {
+                timeout(Duration::from_millis(0), &mut *preferred).await
+            };
+            res.unwrap_or_else(|_| Err(anyhow!("not answered in time")))
+        } else if first_is_preferred {
+            (&mut *fallback).await
+        } else {
+            (&mut *preferred).await
+        };
+
+        let (preferred_res, fallback_res) = if first_is_preferred {
+            (first_res, second_res)
+        } else {
+            (second_res, first_res)
+        };
+        match (preferred_res, fallback_res) {
+            (Err(err1), Err(err2)) => Err(anyhow!("all dns queries failed: {}; {}", err1, err2)),
+            (preferred_res, fallback_res) => Ok((preferred_res.ok(), fallback_res.ok())),
         }
     }
 
@@ -1429,14 +2285,159 @@ impl DnsClient {
         msg
     }
 
-    async fn cache_insert(&self, host: &str, entry: CacheEntry) {
-        if entry.ips.is_empty() {
-            return;
+    // Encodes the query, and a copy of it which carries the client subnet
+    // if there's one.
+    fn new_requests(&self, name: &Name, ty: RecordType) -> Result<Requests> {
+        let mut msg = Self::new_query(name.clone(), ty);
+        let plain = msg
+            .to_vec()
+            .map_err(|e| anyhow!("encode message to buffer failed: {}", e))?;
+        let Some(subnet) = self.client_subnet.as_ref() else {
+            return Ok(Requests {
+                plain,
+                with_subnet: None,
+            });
+        };
+        let mut edns = Edns::new();
+        // The size of the buffer the UDP responses are read into.
+        edns.set_max_payload(512);
+        let option = ClientSubnet::new(subnet.first_address(), subnet.network_length(), 0);
+        edns.options_mut().insert(EdnsOption::Subnet(option));
+        msg.set_edns(edns);
+        let with_subnet = msg
+            .to_vec()
+            .map_err(|e| anyhow!("encode message to buffer failed: {}", e))?;
+        Ok(Requests {
+            plain,
+            with_subnet: Some(with_subnet),
+        })
+    }
+
+    // The network of the clients the answer is for, none if the server
+    // didn't scope it.
+    fn answer_scope(resp: &Message) -> Option<IpCidr> {
+        let edns = resp.extensions().as_ref()?;
+        let EdnsOption::Subnet(subnet) = edns.option(EdnsCode::Subnet)? else {
+            return None;
+        };
+        if subnet.scope_prefix() == 0 {
+            return None;
         }
-        match entry.ips[0] {
-            IpAddr::V4(..) => self.ipv4_cache.lock().await.put(host.to_owned(), entry),
-            IpAddr::V6(..) => self.ipv6_cache.lock().await.put(host.to_owned(), entry),
+        IpInet::new(subnet.addr(), subnet.scope_prefix())
+            .ok()
+            .map(|x| x.network())
+    }
+
+    fn cache_of(&self, ty: RecordType) -> &TokioMutex<LruCache<String, CacheEntry>> {
+        if ty == RecordType::AAAA {
+            &self.ipv6_cache
+        } else {
+            &self.ipv4_cache
+        }
+    }
+
+    async fn cache_insert(&self, host: &str, ty: RecordType, entry: CacheEntry) {
+        self.cache_of(ty).lock().await.put(host.to_owned(), entry);
+    }
+
+    // Bounds the TTL of an answer as configured.
+    fn clamp_ttl(&self, ttl: u32) -> u32 {
+        let ttl = ttl.max(self.cache_min_ttl);
+        if self.cache_max_ttl > 0 {
+            ttl.min(self.cache_max_ttl)
+        } else {
+            ttl
+        }
+    }
+
+    // The entry of a name which doesn't exist, or has no addresses of the
+    // family queried.
+    fn negative_entry(&self) -> CacheEntry {
+        CacheEntry {
+            ips: Vec::new(),
+            deadline: Instant::now() + Duration::from_secs(self.negative_ttl.into()),
+            scope: None,
+        }
+    }
+
+    /// Drops all the cached answers.
+    pub async fn flush_cache(&self) {
+        self.ipv4_cache.lock().await.clear();
+        self.ipv6_cache.lock().await.clear();
+        self.ech_cache.lock().await.clear();
+    }
+
+    // The lines of the cache file are the record type, the host, the time
+    // the entry expires at in seconds since the epoch, then the addresses,
+    // none for a negative entry. The least recently used entries come
+    // first, so that they're the first evicted again once loaded.
+    async fn format_cache_file(&self, now: SystemTime) -> String {
+        let instant = Instant::now();
+        let mut data = String::new();
+        for ty in [RecordType::A, RecordType::AAAA] {
+            let cache = self.cache_of(ty).lock().await;
+            for (host, entry) in cache.iter().rev() {
+                // The subnet may have changed by the next start.
+                if entry.scope.is_some() {
+                    continue;
+                }
+                let Some(ttl) = entry.deadline.checked_duration_since(instant) else {
+                    continue;
+                };
+                let Ok(expiry) = (now + ttl).duration_since(UNIX_EPOCH) else {
+                    continue;
+                };
+                data.push_str(&format!("{} {} {}", ty, host, expiry.as_secs()));
+                for ip in &entry.ips {
+                    data.push_str(&format!(" {}", ip));
+                }
+                data.push('\n');
+            }
+        }
+        data
+    }
+
+    // Skips the lines which are invalid or expired.
+    fn parse_cache_file(data: &str, now: SystemTime) -> Vec<(RecordType, String, CacheEntry)> {
+        let instant = Instant::now();
+        data.lines()
+            .filter_map(|line| {
+                let mut fields = line.split_whitespace();
+                let ty = match fields.next()? {
+                    "A" => RecordType::A,
+                    "AAAA" => RecordType::AAAA,
+                    _ => return None,
+                };
+                let host = fields.next()?.to_string();
+                let expiry = UNIX_EPOCH + Duration::from_secs(fields.next()?.parse().ok()?);
+                let ttl = expiry.duration_since(now).ok()?;
+                let ips = fields
+                    .map(|x| x.parse::<IpAddr>())
+                    .collect::<Result<Vec<_>, _>>()
+                    .ok()?;
+                let deadline = instant.checked_add(ttl)?;
+                let entry = CacheEntry {
+                    ips,
+                    deadline,
+                    scope: None,
+                };
+                Some((ty, host, entry))
+            })
+            .collect()
+    }
+
+    /// Writes the cached answers to the cache file if there's one, they're
+    /// loaded back on the next start.
+    pub async fn save_cache(&self) -> Result<()> {
+        let Some(path) = self.cache_file.as_ref() else {
+            return Ok(());
         };
+        let data = self.format_cache_file(SystemTime::now()).await;
+        // Replaced at once, a write cut short leaves the previous file.
+        let tmp_path = format!("{}.tmp", path);
+        std::fs::write(&tmp_path, data)?;
+        std::fs::rename(&tmp_path, path)?;
+        Ok(())
     }
 
     async fn get_cached_ech(&self, host: &str) -> Option<String> {
@@ -1517,33 +2518,46 @@ impl DnsClient {
     async fn get_cached(&self, host: &String) -> Result<Vec<IpAddr>> {
         let mut cached_ips = Vec::new();
 
-        let fetch_order = match (*crate::option::ENABLE_IPV6, *crate::option::PREFER_IPV6) {
-            (true, true) => vec![&self.ipv6_cache, &self.ipv4_cache],
-            (true, false) => vec![&self.ipv4_cache, &self.ipv6_cache],
-            _ => vec![&self.ipv4_cache],
-        };
-
-        // Query caches in priority order
-        for cache in fetch_order {
-            if let Some(entry) = cache.lock().await.get(host) {
-                if entry
-                    .deadline
-                    .checked_duration_since(Instant::now())
-                    .is_none()
-                {
-                    return Err(anyhow!("entry expired"));
-                }
-                let mut ips = entry.ips.to_vec();
-                cached_ips.append(&mut ips);
+        // All the families of the strategy are needed, in its order. The
+        // names cached as having no addresses give none.
+        for ty in self.strategy.record_types() {
+            let mut cache = self.cache_of(*ty).lock().await;
+            let Some(entry) = cache.get(host) else {
+                return Err(anyhow!("no {} entry", ty));
+            };
+            if entry
+                .deadline
+                .checked_duration_since(Instant::now())
+                .is_none()
+            {
+                return Err(anyhow!("entry expired"));
             }
+            if !entry.is_for(self.client_subnet.as_ref()) {
+                return Err(anyhow!("entry for another subnet"));
+            }
+            cached_ips.extend_from_slice(&entry.ips);
         }
+        Ok(cached_ips)
+    }
 
-        // Return results or error if no cached IPs found
-        if !cached_ips.is_empty() {
-            Ok(cached_ips)
-        } else {
-            Err(anyhow!("empty result"))
-        }
+    /// Returns the resolution strategy.
+    pub fn strategy(&self) -> Strategy {
+        self.strategy
+    }
+
+    /// Returns the numbers of lookups answered from the cache and of those
+    /// which weren't.
+    pub fn cache_stats(&self) -> (u64, u64) {
+        (
+            self.cache_hits.load(Ordering::Relaxed),
+            self.cache_misses.load(Ordering::Relaxed),
+        )
+    }
+
+    /// Returns the addresses of the host in the static hosts, in rotated
+    /// order, along with the TTL to answer them with.
+    pub fn lookup_hosts(&self, host: &str) -> Option<(Vec<IpAddr>, u32)> {
+        self.hosts.get(host).map(|ips| (ips, self.hosts_ttl))
     }
 
     pub async fn lookup(&self, host: &String) -> Result<Vec<IpAddr>> {
@@ -1564,33 +2578,18 @@ impl DnsClient {
             return Ok(vec![ip]);
         }
 
-        if let Ok(ips) = self.get_cached(host).await {
+        if let Some(ips) = self.hosts.get(host) {
             return Ok(ips);
         }
 
-        // Making cache lookup a priority rather than static hosts lookup
-        // and insert the static IPs to the cache because there's a chance
-        // for the IPs in the cache to be re-ordered.
-        if !self.hosts.is_empty() {
-            if let Some(ips) = self.hosts.get(host) {
-                if !ips.is_empty() {
-                    if ips.len() > 1 {
-                        let deadline = Instant::now()
-                            .checked_add(Duration::from_secs(6000))
-                            .unwrap();
-                        self.cache_insert(
-                            host,
-                            CacheEntry {
-                                ips: ips.clone(),
-                                deadline,
-                            },
-                        )
-                        .await;
-                    }
-                    return Ok(ips.to_vec());
-                }
+        if let Ok(ips) = self.get_cached(host).await {
+            self.cache_hits.fetch_add(1, Ordering::Relaxed);
+            if ips.is_empty() {
+                return Err(anyhow!("no addresses for {} (cached)", host));
             }
+            return Ok(ips);
         }
+        self.cache_misses.fetch_add(1, Ordering::Relaxed);
 
         let mut fqdn = host.to_owned();
         fqdn.push('.');
@@ -1599,25 +2598,37 @@ impl DnsClient {
             Err(e) => return Err(anyhow!("invalid domain name [{}]: {}", host, e)),
         };
 
-        if *crate::option::ENABLE_IPV6 {
+        let types = self.strategy.record_types();
+        if let [preferred_ty, fallback_ty] = types {
             let delay = Duration::from_millis(*crate::option::DNS_DUALSTACK_DELAY_MS);
-            let mut a_fut = Box::pin(self.query_record_type(is_direct, &name, host, RecordType::A));
-            let mut aaaa_fut =
-                Box::pin(self.query_record_type(is_direct, &name, host, RecordType::AAAA));
-
-            let (first, second) = if *crate::option::PREFER_IPV6 {
-                self.dualstack_query(&mut aaaa_fut, &mut a_fut, delay)
-                    .await?
-            } else {
-                self.dualstack_query(&mut a_fut, &mut aaaa_fut, delay)
-                    .await?
+            let mut preferred_fut =
+                Box::pin(self.query_record_type(is_direct, &name, host, *preferred_ty));
+            let mut fallback_fut =
+                Box::pin(self.query_record_type(is_direct, &name, host, *fallback_ty));
+            let (preferred, fallback) = self
+                .dualstack_query(&mut preferred_fut, &mut fallback_fut, delay)
+                .await?;
+
+            // The addresses of the preferred family come first, whichever
+            // family answered first. A family without an answer is cached
+            // as empty for as long as the other one, and for the same
+            // subnet.
+            let Some((deadline, scope)) = preferred
+                .as_ref()
+                .or(fallback.as_ref())
+                .map(|x| (x.deadline, x.scope))
+            else {
+                return Err(anyhow!("could not resolve to any address"));
             };
-
-            let mut ips = first.ips.clone();
-            self.cache_insert(host, first).await;
-            if let Some(second) = second {
-                ips.extend_from_slice(&second.ips);
-                self.cache_insert(host, second).await;
+            let mut ips = Vec::new();
+            for (ty, entry) in [(*preferred_ty, preferred), (*fallback_ty, fallback)] {
+                let entry = entry.unwrap_or(CacheEntry {
+                    ips: Vec::new(),
+                    deadline,
+                    scope,
+                });
+                ips.extend_from_slice(&entry.ips);
+                self.cache_insert(host, ty, entry).await;
             }
             if !ips.is_empty() {
                 return Ok(ips);
@@ -1626,10 +2637,10 @@ impl DnsClient {
         }
 
         let entry = self
-            .query_record_type(is_direct, &name, host, RecordType::A)
+            .query_record_type(is_direct, &name, host, types[0])
             .await?;
         let ips = entry.ips.clone();
-        self.cache_insert(host, entry).await;
+        self.cache_insert(host, types[0], entry).await;
         if !ips.is_empty() {
             return Ok(ips);
         }
@@ -2,6 +2,36 @@
 struct CacheEntry {
     pub ips: Vec<IpAddr>,
     pub deadline: Instant,
+    // The network of the client subnet the answer was given for, as scoped
+    // by the server, none if it's the same for all.
+    pub scope: Option<IpCidr>,
+}
+
+impl CacheEntry {
+    // Whether the answer is good for the lookups sent with the subnet.
+    fn is_for(&self, subnet: Option<&IpCidr>) -> bool {
+        match (self.scope.as_ref(), subnet) {
+            (None, _) => true,
+            (Some(scope), Some(subnet)) => scope.contains(&subnet.first_address()),
+            (Some(_), None) => false,
+        }
+    }
+}
+
+// A query with and without the client subnet, the servers it's disabled
+// for are sent the latter.
+struct Requests {
+    plain: Vec<u8>,
+    with_subnet: Option<Vec<u8>>,
+}
+
+impl Requests {
+    fn for_server(&self, resolver: &Resolver) -> Vec<u8> {
+        match self.with_subnet.as_ref() {
+            Some(request) if resolver.sends_client_subnet() => request.clone(),
+            _ => self.plain.clone(),
+        }
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -10,42 +40,232 @@ pub struct EchCacheEntry {
     pub deadline: Instant,
 }
 
+/// Which address families hosts are resolved to, and in which order their
+/// addresses are tried.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Strategy {
+    Ipv4Only,
+    Ipv6Only,
+    PreferIpv4,
+    PreferIpv6,
+    /// Both families, the dials race the connects to the IPv4 addresses
+    /// against those to the IPv6 ones, which get a head start.
+    DualStack,
+}
+
+impl Strategy {
+    // The one of the ENABLE_IPV6 and PREFER_IPV6 options.
+    fn from_options() -> Self {
+        match (*option::ENABLE_IPV6, *option::PREFER_IPV6) {
+            (true, true) => Self::PreferIpv6,
+            (true, false) => Self::PreferIpv4,
+            _ => Self::Ipv4Only,
+        }
+    }
+
+    // The record types queried, the preferred one first.
+    fn record_types(&self) -> &'static [RecordType] {
+        match self {
+            Self::Ipv4Only => &[RecordType::A],
+            Self::Ipv6Only => &[RecordType::AAAA],
+            Self::PreferIpv4 => &[RecordType::A, RecordType::AAAA],
+            Self::PreferIpv6 | Self::DualStack => &[RecordType::AAAA, RecordType::A],
+        }
+    }
+}
+
+impl FromStr for Strategy {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "ipv4_only" => Ok(Self::Ipv4Only),
+            "ipv6_only" => Ok(Self::Ipv6Only),
+            "prefer_ipv4" => Ok(Self::PreferIpv4),
+            "prefer_ipv6" => Ok(Self::PreferIpv6),
+            "dual_stack" => Ok(Self::DualStack),
+            _ => Err(anyhow!("invalid dns strategy {}", s)),
+        }
+    }
+}
+
 #[derive(Clone, Debug)]
 struct DohResolver {
+    // The host of the URL, which may be an IP.
     domain: String,
+    port: u16,
+    path: String,
     bootstrap_ip: Option<IpAddr>,
     is_direct: bool,
+    // The tag of the outbound the queries are sent through.
+    outbound: Option<String>,
+    // Whether the queries carry the client subnet.
+    ecs: bool,
+}
+
+// The host and the port of a server URL, the port is left out if it's the
+// default one.
+fn authority(host: &str, port: u16, default_port: u16) -> String {
+    let host = if host.contains(':') {
+        format!("[{}]", host)
+    } else {
+        host.to_string()
+    };
+    if port == default_port {
+        host
+    } else {
+        format!("{}:{}", host, port)
+    }
+}
+
+impl DohResolver {
+    fn authority(&self) -> String {
+        authority(&self.domain, self.port, 443)
+    }
+
+    // Whether it can be written as doh:domain[@ip].
+    fn is_short_form(&self) -> bool {
+        self.port == 443 && self.path == DOH_DEFAULT_PATH && self.domain.parse::<IpAddr>().is_err()
+    }
+}
+
+// A connection to a DoH server which speaks HTTP/2, shared by the queries.
+#[cfg(feature = "doh-h2")]
+#[derive(Clone)]
+struct DohConnection {
+    sender: ::h2::client::SendRequest<bytes::Bytes>,
+    // Set once the connection is gone or the server sent GOAWAY.
+    closed: Arc<std::sync::atomic::AtomicBool>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Transport {
+    Tcp,
+    Tls,
+    Quic,
+}
+
+impl Transport {
+    fn scheme(&self) -> &'static str {
+        match self {
+            Self::Tcp => "tcp",
+            Self::Tls => "tls",
+            Self::Quic => "quic",
+        }
+    }
+
+    fn default_port(&self) -> u16 {
+        match self {
+            Self::Tcp => 53,
+            Self::Tls | Self::Quic => 853,
+        }
+    }
+}
+
+// A server queried over TCP, TLS or QUIC, where the messages are framed
+// with their lengths.
+#[derive(Clone, Debug)]
+struct StreamResolver {
+    // May be an IP.
+    host: String,
+    port: u16,
+    bootstrap_ip: Option<IpAddr>,
+    transport: Transport,
+    // The name the certificate is verified for, the host by default.
+    sni: Option<String>,
+    // Base64 encoded SHA-256 digests of SPKIs, which are checked instead
+    // of the certificate chain.
+    pins: Vec<String>,
+    is_direct: bool,
+    outbound: Option<String>,
+    ecs: bool,
+}
+
+// The connection to a TCP or TLS server, and the backoff of its dials.
+#[derive(Default)]
+struct TcpServerState {
+    conn: Option<Arc<super::tcp::Connection>>,
+    backoff: super::tcp::Backoff,
 }
 
 #[derive(Clone, Debug)]
 enum Resolver {
-    Server(SocketAddr, bool),
+    // The address, whether it's direct, the outbound tag and whether the
+    // queries carry the client subnet.
+    Server(SocketAddr, bool, Option<String>, bool),
     DoH(DohResolver),
+    Stream(StreamResolver),
     System(bool),
 }
 
-#[derive(Clone, Debug, Default)]
-struct ServerRuntimeStats {
-    avg_latency_ms: f64,
-    samples: u64,
-    successes: u64,
-    failures: u64,
-    timeouts: u64,
-    consecutive_slow: u32,
-    consecutive_failures: u32,
-}
+// What the direct lookups fall back to when all the servers are queried
+// through outbounds.
+static SYSTEM_RESOLVER: Resolver = Resolver::System(true);
 
-#[derive(Clone, Debug, Default)]
-struct ServerSelectorState {
-    primary_server: Option<String>,
-    stats: HashMap<String, ServerRuntimeStats>,
-    last_reselect_at: Option<Instant>,
-}
+impl Resolver {
+    // Whether it's queried directly rather than through the router.
+    fn is_direct(&self) -> bool {
+        match self {
+            Self::Server(_, direct, ..) | Self::System(direct) => *direct,
+            Self::DoH(doh) => doh.is_direct,
+            Self::Stream(stream) => stream.is_direct,
+        }
+    }
 
-impl fmt::Display for Resolver {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    // The tag of the outbound it's queried through instead of the router.
+    fn outbound(&self) -> Option<&str> {
+        match self {
+            Self::Server(_, _, outbound, _) => outbound.as_deref(),
+            Self::DoH(doh) => doh.outbound.as_deref(),
+            Self::Stream(stream) => stream.outbound.as_deref(),
+            Self::System(_) => None,
+        }
+    }
+
+    // The direct servers are left as they are, the others which can't be
+    // reached through an outbound are errors rather than bypassing it.
+    fn set_outbound(&mut self, tag: &str) -> Result<()> {
+        if self.is_direct() {
+            return Ok(());
+        }
+        let outbound = match self {
+            Self::Server(_, _, outbound, _) => outbound,
+            Self::DoH(doh) => &mut doh.outbound,
+            Self::Stream(stream) if stream.transport == Transport::Quic => {
+                return Err(anyhow!("dns over quic can't use an outbound"));
+            }
+            Self::Stream(stream) => &mut stream.outbound,
+            Self::System(_) => return Err(anyhow!("the system resolver can't use an outbound")),
+        };
+        *outbound = Some(tag.to_string());
+        Ok(())
+    }
+
+    // The system resolver never sends it.
+    fn sends_client_subnet(&self) -> bool {
         match self {
-            Self::Server(addr, direct) => {
+            Self::Server(_, _, _, ecs) => *ecs,
+            Self::DoH(doh) => doh.ecs,
+            Self::Stream(stream) => stream.ecs,
+            Self::System(_) => false,
+        }
+    }
+
+    fn disable_client_subnet(&mut self) {
+        match self {
+            Self::Server(_, _, _, ecs) => *ecs = false,
+            Self::DoH(doh) => doh.ecs = false,
+            Self::Stream(stream) => stream.ecs = false,
+            Self::System(_) => (),
+        }
+    }
+
+    fn fmt_server(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if !self.sends_client_subnet() && !matches!(self, Self::System(_)) {
+            write!(f, "noecs:")?;
+        }
+        match self {
+            Self::Server(addr, direct, ..) => {
                 if *direct {
                     write!(f, "direct:{}", addr)
                 } else {
@@ -54,15 +274,29 @@ impl fmt::Display for Resolver {
             }
             Self::DoH(doh) => {
                 if doh.is_direct {
-                    write!(f, "direct:doh:{}", doh.domain)?;
-                } else {
-                    write!(f, "doh:{}", doh.domain)?;
+                    write!(f, "direct:")?;
                 }
+                if !doh.is_short_form() {
+                    return write!(f, "https://{}{}", doh.authority(), doh.path);
+                }
+                write!(f, "doh:{}", doh.domain)?;
                 if let Some(ip) = doh.bootstrap_ip {
                     write!(f, "@{}", ip)?;
                 }
                 Ok(())
             }
+            Self::Stream(stream) => {
+                if stream.is_direct {
+                    write!(f, "direct:")?;
+                }
+                let transport = stream.transport;
+                let authority = authority(&stream.host, stream.port, transport.default_port());
+                write!(f, "{}://{}", transport.scheme(), authority)?;
+                match stream.bootstrap_ip {
+                    Some(ip) if stream.host.parse::<IpAddr>().is_err() => write!(f, "@{}", ip),
+                    _ => Ok(()),
+                }
+            }
             Self::System(direct) => {
                 if *direct {
                     write!(f, "direct:system")
@@ -74,6 +308,57 @@ impl fmt::Display for Resolver {
     }
 }
 
+// Servers which resolve the domains some rules match, the group picks its
+// primary server on its own.
+struct ServerGroup {
+    servers: Vec<Resolver>,
+    selector_state: Mutex<ServerSelectorState>,
+}
+
+// The domains of a rule, whose lookups go to a group rather than the
+// default servers.
+struct ServerRule {
+    domains: crate::app::router::DomainSet,
+    group: String,
+}
+
+// The domains the fake DNS leaves to their real addresses, those listed in
+// the blacklist mode, or those not listed in the whitelist mode.
+struct FakeIpFilter {
+    whitelist: bool,
+    domains: crate::app::router::DomainSet,
+}
+
+#[derive(Clone, Debug, Default)]
+struct ServerRuntimeStats {
+    avg_latency_ms: f64,
+    samples: u64,
+    successes: u64,
+    failures: u64,
+    timeouts: u64,
+    consecutive_slow: u32,
+    consecutive_failures: u32,
+    // The races to answer a lookup it lost in a row.
+    consecutive_losses: u32,
+}
+
+#[derive(Clone, Debug, Default)]
+struct ServerSelectorState {
+    primary_server: Option<String>,
+    stats: HashMap<String, ServerRuntimeStats>,
+    last_reselect_at: Option<Instant>,
+}
+
+impl fmt::Display for Resolver {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.fmt_server(f)?;
+        if let Some(tag) = self.outbound() {
+            write!(f, " via {}", tag)?;
+        }
+        Ok(())
+    }
+}
+
 impl ServerSelectorState {
     fn score_of(&self, server: &str) -> f64 {
         if let Some(stat) = self.stats.get(server) {
@@ -87,6 +372,7 @@ impl ServerSelectorState {
                 + (stat.timeouts as f64 * 900.0)
                 + (stat.consecutive_failures as f64 * 1200.0)
                 + (stat.consecutive_slow as f64 * 300.0)
+                + (stat.consecutive_losses as f64 * 300.0)
         } else {
             (*option::DNS_SERVER_SLOW_RESPONSE_MS as f64) / 2.0
         }
@@ -179,6 +465,7 @@ impl ServerSelectorState {
             stat.consecutive_slow = 0;
         }
         stat.consecutive_failures = 0;
+        stat.consecutive_losses = 0;
         if self.primary_server.is_none() {
             self.primary_server = Some(server.to_owned());
         }
@@ -199,6 +486,12 @@ impl ServerSelectorState {
         }
     }
 
+    // Another server answered first, the query to this one was dropped.
+    fn mark_lost(&mut self, server: &str) {
+        let stat = self.stats.entry(server.to_owned()).or_default();
+        stat.consecutive_losses = stat.consecutive_losses.saturating_add(1);
+    }
+
     fn set_primary(&mut self, server: &str) {
         self.primary_server = Some(server.to_owned());
         self.last_reselect_at = Some(Instant::now());
@@ -208,10 +501,43 @@ impl ServerSelectorState {
 pub struct DnsClient {
     dispatcher: Option<Weak<Dispatcher>>,
     servers: Vec<Resolver>,
-    hosts: HashMap<String, Vec<IpAddr>>,
+    groups: HashMap<String, ServerGroup>,
+    // Evaluated in order before falling back to the default servers.
+    rules: Vec<ServerRule>,
+    // Answered before the cache and the servers.
+    hosts: Hosts,
+    hosts_ttl: u32,
+    strategy: Strategy,
+    fake_ip_filter: FakeIpFilter,
+    // Whether the lookups are sent to several servers at once rather than
+    // one after the other.
+    concurrent_queries: bool,
+    // Sent along with the queries to the servers, which may answer with
+    // the addresses close to it.
+    client_subnet: Option<IpCidr>,
+    // The bounds of the TTLs the answers are cached for, zero for none.
+    cache_min_ttl: u32,
+    cache_max_ttl: u32,
+    // How long a name without addresses is cached.
+    negative_ttl: u32,
+    // Where the cache is kept across restarts.
+    cache_file: Option<String>,
+    // By family, so that the one which wasn't queried is a miss rather than
+    // no addresses once the strategy changes.
     ipv4_cache: Arc<TokioMutex<LruCache<String, CacheEntry>>>,
     ipv6_cache: Arc<TokioMutex<LruCache<String, CacheEntry>>>,
     ech_cache: Arc<TokioMutex<LruCache<String, EchCacheEntry>>>,
     ech_query_locks: Arc<TokioMutex<HashMap<String, Arc<TokioMutex<()>>>>>,
     selector_state: Arc<Mutex<ServerSelectorState>>,
+    // Lookups answered from the cache and those which weren't.
+    cache_hits: AtomicU64,
+    cache_misses: AtomicU64,
+    // The HTTP/2 connections to the DoH servers, by server.
+    #[cfg(feature = "doh-h2")]
+    doh_connections: Arc<TokioMutex<HashMap<String, DohConnection>>>,
+    // The connections to the TCP and TLS servers, by server.
+    tcp_connections: Arc<TokioMutex<HashMap<String, Arc<TokioMutex<TcpServerState>>>>>,
+    // The QUIC servers, by server.
+    #[cfg(feature = "doq")]
+    quic_servers: Arc<TokioMutex<HashMap<String, Arc<super::quic::Server>>>>,
 }
@@ -0,0 +1,106 @@
+//! Static hosts, names mapped to addresses which are answered without
+//! asking any server. A name starting with `*.` maps the subdomains of the
+//! rest, the longest such suffix of a host wins over the shorter ones, an
+//! exact name over all of them.
+
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+struct Entry {
+    ips: Vec<IpAddr>,
+    // Rotates the addresses from one lookup to the next, so that the
+    // connections are spread over them.
+    next: AtomicUsize,
+}
+
+#[derive(Default)]
+pub struct Hosts {
+    names: HashMap<String, Entry>,
+    // By the suffix after `*.`.
+    suffixes: HashMap<String, Entry>,
+}
+
+fn normalize(name: &str) -> String {
+    name.trim_end_matches('.').to_ascii_lowercase()
+}
+
+impl Hosts {
+    pub fn insert(&mut self, name: &str, ips: Vec<IpAddr>) {
+        if ips.is_empty() {
+            return;
+        }
+        let entry = Entry {
+            ips,
+            next: AtomicUsize::new(0),
+        };
+        let name = normalize(name);
+        match name.strip_prefix("*.") {
+            Some(suffix) => self.suffixes.insert(suffix.to_string(), entry),
+            None => self.names.insert(name, entry),
+        };
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty() && self.suffixes.is_empty()
+    }
+
+    /// Returns the addresses of the host, starting one further on each call.
+    pub fn get(&self, host: &str) -> Option<Vec<IpAddr>> {
+        if self.is_empty() {
+            return None;
+        }
+        let host = normalize(host);
+        let entry = self.names.get(&host).or_else(|| {
+            host.match_indices('.')
+                .find_map(|(i, _)| self.suffixes.get(&host[i + 1..]))
+        })?;
+        let mut ips = entry.ips.clone();
+        let n = entry.next.fetch_add(1, Ordering::Relaxed);
+        ips.rotate_left(n % ips.len());
+        Some(ips)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ip(s: &str) -> IpAddr {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn test_get() {
+        let mut hosts = Hosts::default();
+        hosts.insert("router.lan", vec![ip("192.168.1.1")]);
+        hosts.insert("*.lan", vec![ip("192.168.1.2")]);
+        hosts.insert("*.nas.lan", vec![ip("192.168.1.3"), ip("fd00::3")]);
+        assert_eq!(hosts.get("Router.lan."), Some(vec![ip("192.168.1.1")]));
+        assert_eq!(hosts.get("printer.lan"), Some(vec![ip("192.168.1.2")]));
+        assert_eq!(
+            hosts.get("a.nas.lan"),
+            Some(vec![ip("192.168.1.3"), ip("fd00::3")])
+        );
+        assert_eq!(hosts.get("lan"), None);
+        assert_eq!(hosts.get("example.com"), None);
+    }
+
+    #[test]
+    fn test_rotation() {
+        let mut hosts = Hosts::default();
+        hosts.insert("lb.example", vec![ip("10.0.0.1"), ip("10.0.0.2")]);
+        assert_eq!(
+            hosts.get("lb.example"),
+            Some(vec![ip("10.0.0.1"), ip("10.0.0.2")])
+        );
+        assert_eq!(
+            hosts.get("lb.example"),
+            Some(vec![ip("10.0.0.2"), ip("10.0.0.1")])
+        );
+        assert_eq!(
+            hosts.get("lb.example"),
+            Some(vec![ip("10.0.0.1"), ip("10.0.0.2")])
+        );
+    }
+}
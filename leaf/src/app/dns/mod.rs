@@ -1,3 +1,11 @@
 mod client;
+mod hosts;
+#[cfg(feature = "doq")]
+mod quic;
+#[cfg(feature = "inbound-dns")]
+mod server;
+mod tcp;
 
 pub use client::*;
+#[cfg(feature = "inbound-dns")]
+pub use server::DnsServer;
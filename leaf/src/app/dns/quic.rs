@@ -0,0 +1,218 @@
+//! DNS over QUIC, RFC 9250. The queries to a server share a connection,
+//! each is sent on a bidirectional stream of its own, with the two-byte
+//! length framing and an ID of zero.
+
+use std::future::Future;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use anyhow::{anyhow, Result};
+use tokio::net::UdpSocket;
+use tokio::sync::Mutex;
+use tracing::debug;
+
+use super::tcp::Backoff;
+
+const ALPN: &[u8] = b"doq";
+
+struct Connection {
+    // Kept with the connection it drives.
+    _endpoint: quinn::Endpoint,
+    conn: quinn::Connection,
+    // Whether the server answered anything, a connection closed before is
+    // counted as a failure to connect.
+    answered: AtomicBool,
+}
+
+#[derive(Default)]
+struct State {
+    conn: Option<Arc<Connection>>,
+    backoff: Backoff,
+}
+
+enum QueryError {
+    ZeroRttRejected,
+    // The server reset or stopped the stream.
+    Reset(quinn::VarInt),
+    Other(anyhow::Error),
+}
+
+fn other_error<E: Into<anyhow::Error>>(e: E) -> QueryError {
+    QueryError::Other(e.into())
+}
+
+pub struct Server {
+    // Built once, so that the session tickets it keeps let the connections
+    // after the first one send their queries in 0-RTT.
+    client_config: quinn::ClientConfig,
+    server_name: String,
+    // The server as configured, for logging.
+    name: String,
+    state: Mutex<State>,
+}
+
+impl Server {
+    pub fn new(mut crypto: rustls::ClientConfig, server_name: &str, name: String) -> Result<Self> {
+        crypto.alpn_protocols = vec![ALPN.to_vec()];
+        crypto.enable_early_data = true;
+        let crypto = quinn::crypto::rustls::QuicClientConfig::try_from(crypto)
+            .map_err(|e| anyhow!("invalid tls config: {}", e))?;
+        Ok(Self {
+            client_config: quinn::ClientConfig::new(Arc::new(crypto)),
+            server_name: server_name.to_string(),
+            name,
+            state: Mutex::new(State::default()),
+        })
+    }
+
+    /// Sends a DNS message and returns the response. `dial` gives the
+    /// address of the server and the socket to reach it with, it's only
+    /// awaited if there's no connection to reuse.
+    pub async fn exchange<D>(&self, request: &[u8], dial: D) -> Result<Vec<u8>>
+    where
+        D: Future<Output = Result<(SocketAddr, UdpSocket)>>,
+    {
+        if request.len() < 12 || request.len() > u16::MAX as usize {
+            return Err(anyhow!("invalid dns message"));
+        }
+        let conn = self.connection(dial).await?;
+        let mut res = query(&conn.conn, request).await;
+        if matches!(res, Err(QueryError::ZeroRttRejected)) {
+            // Sent again in 1-RTT.
+            debug!("0-rtt rejected by {}", self.name);
+            res = query(&conn.conn, request).await;
+        }
+        match res {
+            Ok(mut response) => {
+                conn.answered.store(true, Ordering::Relaxed);
+                response[..2].copy_from_slice(&request[..2]);
+                Ok(response)
+            }
+            Err(QueryError::Reset(code)) => {
+                conn.answered.store(true, Ordering::Relaxed);
+                debug!("dns stream to {} reset: {}", self.name, code);
+                Ok(servfail(request))
+            }
+            Err(QueryError::ZeroRttRejected) => Err(anyhow!("0-rtt rejected")),
+            Err(QueryError::Other(e)) => Err(e),
+        }
+    }
+
+    async fn connection<D>(&self, dial: D) -> Result<Arc<Connection>>
+    where
+        D: Future<Output = Result<(SocketAddr, UdpSocket)>>,
+    {
+        // Held while connecting, the queries meanwhile wait for the new
+        // connection instead of making their own.
+        let mut state = self.state.lock().await;
+        if let Some(conn) = state.conn.as_ref() {
+            if conn.conn.close_reason().is_none() {
+                return Ok(conn.clone());
+            }
+        }
+        // A connection in 0-RTT is handed out before its handshake is
+        // done, so it's only known to have worked once it answered.
+        if let Some(conn) = state.conn.take() {
+            if conn.answered.load(Ordering::Relaxed) {
+                state.backoff.succeeded();
+            } else {
+                state.backoff.failed();
+            }
+        }
+        state.backoff.check()?;
+        match self.connect(dial).await {
+            Ok(conn) => {
+                let conn = Arc::new(conn);
+                state.conn = Some(conn.clone());
+                Ok(conn)
+            }
+            Err(e) => {
+                state.backoff.failed();
+                Err(e)
+            }
+        }
+    }
+
+    async fn connect<D>(&self, dial: D) -> Result<Connection>
+    where
+        D: Future<Output = Result<(SocketAddr, UdpSocket)>>,
+    {
+        let (addr, socket) = dial.await?;
+        let mut endpoint = quinn::Endpoint::new(
+            quinn::EndpointConfig::default(),
+            None,
+            socket.into_std()?,
+            Arc::new(quinn::TokioRuntime),
+        )?;
+        endpoint.set_default_client_config(self.client_config.clone());
+        let connecting = endpoint.connect(addr, &self.server_name)?;
+        let conn = match connecting.into_0rtt() {
+            Ok((conn, _)) => {
+                debug!("connecting {} in 0-rtt", self.name);
+                conn
+            }
+            Err(connecting) => connecting
+                .await
+                .map_err(|e| anyhow!("connect quic failed: {}", e))?,
+        };
+        Ok(Connection {
+            _endpoint: endpoint,
+            conn,
+            answered: AtomicBool::new(false),
+        })
+    }
+}
+
+async fn query(conn: &quinn::Connection, request: &[u8]) -> Result<Vec<u8>, QueryError> {
+    let (mut send, mut recv) = conn.open_bi().await.map_err(other_error)?;
+    let mut frame = Vec::with_capacity(request.len() + 2);
+    frame.extend_from_slice(&(request.len() as u16).to_be_bytes());
+    frame.extend_from_slice(&[0, 0]);
+    frame.extend_from_slice(&request[2..]);
+    send.write_all(&frame).await.map_err(|e| match e {
+        quinn::WriteError::ZeroRttRejected => QueryError::ZeroRttRejected,
+        quinn::WriteError::Stopped(code) => QueryError::Reset(code),
+        e => other_error(e),
+    })?;
+    send.finish().map_err(other_error)?;
+    let frame = recv
+        .read_to_end(2 + u16::MAX as usize)
+        .await
+        .map_err(|e| match e {
+            quinn::ReadToEndError::Read(quinn::ReadError::ZeroRttRejected) => {
+                QueryError::ZeroRttRejected
+            }
+            quinn::ReadToEndError::Read(quinn::ReadError::Reset(code)) => QueryError::Reset(code),
+            e => other_error(e),
+        })?;
+    if frame.len() < 4 || u16::from_be_bytes([frame[0], frame[1]]) as usize != frame.len() - 2 {
+        return Err(other_error(anyhow!("invalid dns response")));
+    }
+    Ok(frame[2..].to_vec())
+}
+
+// The answer to a query whose stream was reset, a server failure.
+fn servfail(request: &[u8]) -> Vec<u8> {
+    let mut response = request.to_vec();
+    // QR
+    response[2] |= 0x80;
+    // RCODE
+    response[3] = (response[3] & 0xf0) | 2;
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_servfail() {
+        let request = [0x12, 0x34, 0x01, 0x20, 0, 1, 0, 0, 0, 0, 0, 0, b'q'];
+        let response = servfail(&request);
+        assert_eq!(&response[..2], &[0x12, 0x34]);
+        assert_eq!(response[2], 0x81);
+        assert_eq!(response[3], 0x22);
+        assert_eq!(&response[4..], &request[4..]);
+    }
+}
@@ -0,0 +1,284 @@
+//! Answers DNS queries of clients. A queried domain is routed like a
+//! connection to it: domains routed to a drop outbound are blocked, domains
+//! routed to a proxy are answered with fake IPs if fake DNS is enabled, and
+//! the others are resolved with the DNS client.
+
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::sync::Arc;
+
+use anyhow::Result;
+use hickory_proto::op::{
+    header::MessageType, op_code::OpCode, response_code::ResponseCode, Edns, Message,
+};
+use hickory_proto::rr::{
+    dns_class::DNSClass, rdata, record_data::RData, record_type::RecordType, resource::Record, Name,
+};
+use tracing::debug;
+
+use crate::app::dispatcher::Dispatcher;
+use crate::app::fake_dns::FakeDns;
+use crate::session::{Network, Session, SocksAddr};
+
+// The largest UDP response to clients without EDNS.
+const MIN_UDP_PAYLOAD: usize = 512;
+
+enum Route {
+    Direct,
+    Proxy,
+    Drop,
+}
+
+pub struct DnsServer {
+    inbound_tag: String,
+    dispatcher: Arc<Dispatcher>,
+    fake_dns: Option<Arc<FakeDns>>,
+    ttl: u32,
+    // Answers blocked domains with NXDOMAIN rather than unspecified addresses.
+    blocked_nxdomain: bool,
+}
+
+impl DnsServer {
+    pub fn new(
+        inbound_tag: String,
+        dispatcher: Arc<Dispatcher>,
+        fake_dns: Option<Arc<FakeDns>>,
+        ttl: u32,
+        blocked_nxdomain: bool,
+    ) -> Self {
+        Self {
+            inbound_tag,
+            dispatcher,
+            fake_dns,
+            ttl,
+            blocked_nxdomain,
+        }
+    }
+
+    async fn route(&self, source: SocketAddr, domain: &str) -> Route {
+        let Ok(destination) = SocksAddr::try_from((domain, 0)) else {
+            return Route::Direct;
+        };
+        let sess = Session {
+            network: Network::Tcp,
+            source,
+            destination,
+            inbound_tag: self.inbound_tag.clone(),
+            skip_resolve: true,
+            ..Default::default()
+        };
+        let tag = match self.dispatcher.router.read().await.pick_route(&sess).await {
+            Ok(Some(tag)) => tag,
+            _ => match self
+                .dispatcher
+                .outbound_manager
+                .read()
+                .await
+                .default_handler()
+            {
+                Some(tag) => tag,
+                None => return Route::Direct,
+            },
+        };
+        if self.dispatcher.is_drop_outbound(&tag).await {
+            Route::Drop
+        } else if self.dispatcher.is_direct_outbound(&tag).await {
+            Route::Direct
+        } else {
+            Route::Proxy
+        }
+    }
+
+    /// Returns the response to `request` from `source`, it is truncated to
+    /// fit the UDP payload size of the client if `udp` is set.
+    pub async fn handle(&self, source: SocketAddr, request: &[u8], udp: bool) -> Result<Vec<u8>> {
+        let req = Message::from_vec(request)?;
+        let max_size = if udp {
+            max_udp_payload(&req)
+        } else {
+            u16::MAX as usize
+        };
+        let mut resp = new_response(&req);
+        if req.op_code() != OpCode::Query || req.queries().len() != 1 {
+            resp.set_response_code(ResponseCode::NotImp);
+            return encode(resp, max_size);
+        }
+        let query = &req.queries()[0];
+        if query.query_class() != DNSClass::IN {
+            resp.set_response_code(ResponseCode::NotImp);
+            return encode(resp, max_size);
+        }
+        let name = query.name().clone();
+        let domain = name.to_ascii().trim_end_matches('.').to_lowercase();
+        let ty = query.query_type();
+
+        match self.route(source, &domain).await {
+            Route::Drop => {
+                debug!("blocked dns query {} {}", &domain, ty);
+                if self.blocked_nxdomain {
+                    resp.set_response_code(ResponseCode::NXDomain);
+                } else {
+                    let ips = [
+                        IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+                        IpAddr::V6(Ipv6Addr::UNSPECIFIED),
+                    ];
+                    add_answers(&mut resp, &name, ty, &ips, self.ttl);
+                }
+                return encode(resp, max_size);
+            }
+            Route::Proxy => {
+                if let Some(fake_dns) = &self.fake_dns {
+                    match fake_dns.generate_fake_response(request).await {
+                        Ok(resp) => return Ok(resp),
+                        Err(e) => debug!("generate fake response failed: {}", e),
+                    }
+                }
+            }
+            Route::Direct => (),
+        }
+
+        if ty == RecordType::A || ty == RecordType::AAAA {
+            let dns_client = self.dispatcher.dns_client.read().await;
+            // The static hosts come with their own TTL.
+            let (res, ttl) = match dns_client.lookup_hosts(&domain) {
+                Some((ips, ttl)) => (Ok(ips), ttl),
+                None => (dns_client.lookup(&domain).await, self.ttl),
+            };
+            match res {
+                Ok(ips) => add_answers(&mut resp, &name, ty, &ips, ttl),
+                Err(e) => {
+                    debug!("lookup {} failed: {}", &domain, e);
+                    resp.set_response_code(ResponseCode::ServFail);
+                }
+            }
+        }
+        // Other types are answered with no data.
+        encode(resp, max_size)
+    }
+}
+
+// Returns the UDP payload size the client accepts.
+fn max_udp_payload(req: &Message) -> usize {
+    req.extensions()
+        .as_ref()
+        .map(|edns| edns.max_payload() as usize)
+        .unwrap_or(MIN_UDP_PAYLOAD)
+        .max(MIN_UDP_PAYLOAD)
+}
+
+fn new_response(req: &Message) -> Message {
+    let mut resp = Message::new();
+    resp.set_id(req.id())
+        .set_message_type(MessageType::Response)
+        .set_op_code(req.op_code())
+        .set_recursion_desired(req.recursion_desired())
+        .set_recursion_available(true)
+        .set_checking_disabled(req.checking_disabled())
+        .set_response_code(ResponseCode::NoError);
+    resp.add_queries(req.queries().to_vec());
+    if let Some(edns) = req.extensions() {
+        let mut resp_edns = Edns::new();
+        resp_edns.set_max_payload(edns.max_payload().max(MIN_UDP_PAYLOAD as u16));
+        resp.set_edns(resp_edns);
+    }
+    resp
+}
+
+// Adds the addresses of the queried family as answers.
+fn add_answers(resp: &mut Message, name: &Name, ty: RecordType, ips: &[IpAddr], ttl: u32) {
+    for ip in ips {
+        let rdata = match (ty, ip) {
+            (RecordType::A, IpAddr::V4(ip)) => RData::A(rdata::A(*ip)),
+            (RecordType::AAAA, IpAddr::V6(ip)) => RData::AAAA(rdata::AAAA(*ip)),
+            _ => continue,
+        };
+        resp.add_answer(Record::from_rdata(name.clone(), ttl, rdata));
+    }
+}
+
+// Encodes the response, it is truncated with the TC bit set if larger than
+// `max_size`, so that the client retries over TCP.
+fn encode(mut resp: Message, max_size: usize) -> Result<Vec<u8>> {
+    let buf = resp.to_vec()?;
+    if buf.len() <= max_size {
+        return Ok(buf);
+    }
+    resp.take_answers();
+    resp.take_name_servers();
+    resp.take_additionals();
+    resp.set_truncated(true);
+    Ok(resp.to_vec()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use hickory_proto::op::Query;
+
+    use super::*;
+
+    fn request(edns_payload: Option<u16>) -> Message {
+        let mut req = Message::new();
+        req.set_id(42)
+            .set_recursion_desired(true)
+            .add_query(Query::query(
+                Name::from_str("example.com.").unwrap(),
+                RecordType::A,
+            ));
+        if let Some(payload) = edns_payload {
+            let mut edns = Edns::new();
+            edns.set_max_payload(payload);
+            req.set_edns(edns);
+        }
+        req
+    }
+
+    #[test]
+    fn test_max_udp_payload() {
+        assert_eq!(max_udp_payload(&request(None)), 512);
+        assert_eq!(max_udp_payload(&request(Some(256))), 512);
+        assert_eq!(max_udp_payload(&request(Some(1232))), 1232);
+    }
+
+    #[test]
+    fn test_add_answers() {
+        let req = request(None);
+        let mut resp = new_response(&req);
+        let name = req.queries()[0].name().clone();
+        let ips = [
+            IpAddr::V4(Ipv4Addr::new(1, 2, 3, 4)),
+            IpAddr::V6(Ipv6Addr::LOCALHOST),
+        ];
+        add_answers(&mut resp, &name, RecordType::A, &ips, 60);
+        assert_eq!(resp.id(), 42);
+        assert!(resp.recursion_available());
+        assert_eq!(resp.answers().len(), 1);
+        assert_eq!(resp.answers()[0].ttl(), 60);
+        assert_eq!(
+            resp.answers()[0].data(),
+            Some(&RData::A(rdata::A(Ipv4Addr::new(1, 2, 3, 4))))
+        );
+    }
+
+    #[test]
+    fn test_encode_truncated() {
+        let req = request(None);
+        let mut resp = new_response(&req);
+        let name = req.queries()[0].name().clone();
+        let ips: Vec<IpAddr> = (0..64)
+            .map(|i| IpAddr::V4(Ipv4Addr::new(10, 0, 0, i)))
+            .collect();
+        add_answers(&mut resp, &name, RecordType::A, &ips, 60);
+
+        let full = Message::from_vec(&encode(resp.clone(), u16::MAX as usize).unwrap()).unwrap();
+        assert!(!full.truncated());
+        assert_eq!(full.answers().len(), 64);
+
+        let buf = encode(resp, max_udp_payload(&req)).unwrap();
+        assert!(buf.len() <= 512);
+        let truncated = Message::from_vec(&buf).unwrap();
+        assert!(truncated.truncated());
+        assert!(truncated.answers().is_empty());
+        assert_eq!(truncated.queries().len(), 1);
+    }
+}
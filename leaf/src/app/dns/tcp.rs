@@ -0,0 +1,234 @@
+//! DNS over a TCP or TLS connection, with the two-byte length framing of
+//! RFC 7766 and RFC 7858. Queries are pipelined over a kept-alive
+//! connection, the responses are matched to them by ID.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, AtomicU16, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, Result};
+use tokio::io::{AsyncReadExt, AsyncWriteExt, ReadHalf, WriteHalf};
+use tokio::sync::{mpsc, oneshot};
+use tracing::debug;
+
+use crate::proxy::AnyStream;
+
+// The queries waiting for responses, by the ID they were sent with.
+type Pending = Arc<Mutex<HashMap<u16, oneshot::Sender<Vec<u8>>>>>;
+
+// Frames written ahead of the socket.
+const WRITE_QUEUE_SIZE: usize = 64;
+
+pub struct Connection {
+    // Frames are written by a task, so that a query given up halfway
+    // doesn't leave half a frame on the connection.
+    frame_tx: mpsc::Sender<Vec<u8>>,
+    pending: Pending,
+    next_id: AtomicU16,
+    // Set once the connection is gone, the next query dials again.
+    closed: Arc<AtomicBool>,
+    // Whether the server answered anything, a connection closed before is
+    // counted as a failure to connect.
+    answered: Arc<AtomicBool>,
+}
+
+// Forgets a query however it ends.
+struct PendingGuard<'a> {
+    pending: &'a Pending,
+    id: u16,
+}
+
+impl Drop for PendingGuard<'_> {
+    fn drop(&mut self) {
+        self.pending.lock().unwrap().remove(&self.id);
+    }
+}
+
+impl Connection {
+    pub fn new(stream: AnyStream, server: String) -> Self {
+        let (r, w) = tokio::io::split(stream);
+        let (frame_tx, frame_rx) = mpsc::channel(WRITE_QUEUE_SIZE);
+        let pending = Pending::default();
+        let closed = Arc::new(AtomicBool::new(false));
+        let answered = Arc::new(AtomicBool::new(false));
+        tokio::spawn(write_frames(w, frame_rx, closed.clone()));
+        tokio::spawn(read_frames(
+            r,
+            pending.clone(),
+            closed.clone(),
+            answered.clone(),
+            server,
+        ));
+        Self {
+            frame_tx,
+            pending,
+            next_id: AtomicU16::new(rand::random()),
+            closed,
+            answered,
+        }
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.closed.load(Ordering::Relaxed)
+    }
+
+    pub fn answered(&self) -> bool {
+        self.answered.load(Ordering::Relaxed)
+    }
+
+    /// Sends a DNS message and returns the response.
+    pub async fn exchange(&self, request: &[u8]) -> Result<Vec<u8>> {
+        if request.len() < 2 || request.len() > u16::MAX as usize {
+            return Err(anyhow!("invalid dns message"));
+        }
+        // The queries of all lookups share the connection, their IDs are
+        // replaced with ones unique to it, then restored in the responses.
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let (tx, rx) = oneshot::channel();
+        self.pending.lock().unwrap().insert(id, tx);
+        let _guard = PendingGuard {
+            pending: &self.pending,
+            id,
+        };
+        let mut frame = Vec::with_capacity(request.len() + 2);
+        frame.extend_from_slice(&(request.len() as u16).to_be_bytes());
+        frame.extend_from_slice(&id.to_be_bytes());
+        frame.extend_from_slice(&request[2..]);
+        self.frame_tx
+            .send(frame)
+            .await
+            .map_err(|_| anyhow!("connection closed"))?;
+        let mut response = rx.await.map_err(|_| anyhow!("connection closed"))?;
+        response[..2].copy_from_slice(&request[..2]);
+        Ok(response)
+    }
+}
+
+async fn write_frames(
+    mut w: WriteHalf<AnyStream>,
+    mut frame_rx: mpsc::Receiver<Vec<u8>>,
+    closed: Arc<AtomicBool>,
+) {
+    while let Some(frame) = frame_rx.recv().await {
+        if w.write_all(&frame).await.is_err() || w.flush().await.is_err() {
+            closed.store(true, Ordering::Relaxed);
+            return;
+        }
+    }
+    // The connection is dropped.
+    let _ = w.shutdown().await;
+}
+
+async fn read_frames(
+    mut r: ReadHalf<AnyStream>,
+    pending: Pending,
+    closed: Arc<AtomicBool>,
+    answered: Arc<AtomicBool>,
+    server: String,
+) {
+    loop {
+        let mut len = [0u8; 2];
+        if let Err(e) = r.read_exact(&mut len).await {
+            debug!("dns connection to {} closed: {}", server, e);
+            break;
+        }
+        let len = u16::from_be_bytes(len) as usize;
+        if len < 2 {
+            debug!("invalid dns response from {}", server);
+            break;
+        }
+        let mut buf = vec![0u8; len];
+        if let Err(e) = r.read_exact(&mut buf).await {
+            debug!("dns connection to {} closed: {}", server, e);
+            break;
+        }
+        answered.store(true, Ordering::Relaxed);
+        let id = u16::from_be_bytes([buf[0], buf[1]]);
+        if let Some(tx) = pending.lock().unwrap().remove(&id) {
+            let _ = tx.send(buf);
+        }
+    }
+    closed.store(true, Ordering::Relaxed);
+    // Fails the queries still waiting.
+    pending.lock().unwrap().clear();
+}
+
+/// Spaces out the dials to a server which keep failing, the delay doubles
+/// from `MIN_DELAY` up to `MAX_DELAY`.
+#[derive(Default)]
+pub struct Backoff {
+    failures: u32,
+    next_dial: Option<Instant>,
+}
+
+impl Backoff {
+    const MIN_DELAY: Duration = Duration::from_millis(500);
+    const MAX_DELAY: Duration = Duration::from_secs(30);
+
+    /// Fails if it's too early to dial again.
+    pub fn check(&self) -> Result<()> {
+        if let Some(next_dial) = self.next_dial {
+            let now = Instant::now();
+            if now < next_dial {
+                return Err(anyhow!(
+                    "reconnecting in {} ms",
+                    (next_dial - now).as_millis()
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn failed(&mut self) {
+        let delay = Self::MIN_DELAY
+            .saturating_mul(1 << self.failures.min(6))
+            .min(Self::MAX_DELAY);
+        self.failures += 1;
+        self.next_dial = Some(Instant::now() + delay);
+    }
+
+    pub fn succeeded(&mut self) {
+        *self = Self::default();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_pipelined_exchange() {
+        let (client, server) = tokio::io::duplex(1024);
+        let conn = Connection::new(Box::new(client), "test".to_string());
+        // Answers two queries in reverse order, echoing them.
+        tokio::spawn(async move {
+            let (mut r, mut w) = tokio::io::split(server);
+            let mut frames = Vec::new();
+            for _ in 0..2 {
+                let mut len = [0u8; 2];
+                r.read_exact(&mut len).await.unwrap();
+                let mut buf = vec![0u8; u16::from_be_bytes(len) as usize];
+                r.read_exact(&mut buf).await.unwrap();
+                frames.push([&len[..], &buf[..]].concat());
+            }
+            for frame in frames.iter().rev() {
+                w.write_all(frame).await.unwrap();
+            }
+        });
+        let (a, b) = tokio::join!(conn.exchange(&[0, 1, b'a']), conn.exchange(&[0, 2, b'b']));
+        assert_eq!(a.unwrap(), vec![0, 1, b'a']);
+        assert_eq!(b.unwrap(), vec![0, 2, b'b']);
+        assert!(conn.answered());
+    }
+
+    #[test]
+    fn test_backoff() {
+        let mut backoff = Backoff::default();
+        assert!(backoff.check().is_ok());
+        backoff.failed();
+        assert!(backoff.check().is_err());
+        backoff.succeeded();
+        assert!(backoff.check().is_ok());
+    }
+}
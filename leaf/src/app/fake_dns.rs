@@ -1,54 +1,212 @@
 use std::collections::HashMap;
-use std::net::{IpAddr, Ipv4Addr};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
 
 use anyhow::{anyhow, Result};
+use cidr::Ipv6Cidr;
 use hickory_proto::op::{
     header::MessageType, op_code::OpCode, response_code::ResponseCode, Message,
 };
 use hickory_proto::rr::{
-    dns_class::DNSClass, rdata, record_data::RData, record_type::RecordType, resource::Record,
+    dns_class::DNSClass, rdata, record_data::RData, record_type::RecordType, resource::Record, Name,
 };
 use tokio::sync::RwLock;
-use tracing::debug;
+use tracing::{debug, warn};
+
+use crate::app::SyncDnsClient;
+
+// The allocations are written at most this often, and once more when the
+// fake DNS is dropped.
+const SAVE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Returns the file the allocations of the fake DNS of an inbound are kept
+/// in across restarts, in the cache location set by the environment, none
+/// if there's none.
+pub fn cache_file(tag: &str) -> Option<PathBuf> {
+    if crate::option::CACHE_LOCATION.is_empty() {
+        return None;
+    }
+    let tag: String = tag
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    Some(Path::new(&*crate::option::CACHE_LOCATION).join(format!("fake_dns.{}.cache", tag)))
+}
+
+fn write_cache_file(path: &Path, data: &str) -> Result<()> {
+    if let Some(dir) = path.parent() {
+        if !dir.as_os_str().is_empty() && !dir.exists() {
+            std::fs::create_dir_all(dir)?;
+        }
+    }
+    // Written aside and renamed, so that a crash never leaves a truncated
+    // file behind.
+    let tmp = path.with_extension("tmp");
+    std::fs::write(&tmp, data)?;
+    std::fs::rename(&tmp, path)?;
+    Ok(())
+}
 
 pub enum FakeDnsMode {
     Include,
     Exclude,
 }
 
-pub struct FakeDns(RwLock<FakeDnsImpl>);
+pub struct FakeDns {
+    inner: RwLock<FakeDnsImpl>,
+    // The domains in the static hosts are answered with their addresses,
+    // those the DNS client resolves with a group of servers or filters out
+    // of the fake IPs are left to their real addresses.
+    dns_client: SyncDnsClient,
+    // Where the allocations are kept across restarts, so that the fake IPs
+    // the applications still have keep their domains.
+    cache_file: Option<PathBuf>,
+}
 
 impl FakeDns {
-    pub fn new(mode: FakeDnsMode, filters: Vec<String>) -> Self {
-        Self(RwLock::new(FakeDnsImpl::new(mode, filters)))
+    pub fn new(
+        mode: FakeDnsMode,
+        filters: Vec<String>,
+        dns_client: SyncDnsClient,
+        cache_file: Option<PathBuf>,
+    ) -> Self {
+        let mut inner = FakeDnsImpl::new(mode, filters);
+        if let Some(path) = cache_file.as_ref() {
+            match std::fs::read_to_string(path) {
+                Ok(data) => inner.restore(&data),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => (),
+                Err(e) => warn!("read fake dns cache {} failed: {}", path.display(), e),
+            }
+        }
+        Self {
+            inner: RwLock::new(inner),
+            dns_client,
+            cache_file,
+        }
     }
 
     pub async fn query_domain(&self, ip: &IpAddr) -> Option<String> {
-        self.0.read().await.query_domain(ip)
+        self.inner.read().await.query_domain(ip)
     }
 
-    pub async fn query_fake_ip(&self, domain: &str) -> Option<IpAddr> {
-        self.0.read().await.query_fake_ip(domain)
+    /// Returns the fake IP of the domain, of the IPv6 pool if `ipv6`.
+    pub async fn query_fake_ip(&self, domain: &str, ipv6: bool) -> Option<IpAddr> {
+        self.inner.read().await.query_fake_ip(domain, ipv6)
     }
 
     pub async fn generate_fake_response(&self, request: &[u8]) -> Result<Vec<u8>> {
-        self.0.write().await.generate_fake_response(request)
+        let req = Message::from_vec(request)?;
+        if let Some(query) = req.queries().first() {
+            let domain = domain_of(query.name());
+            let dns_client = self.dns_client.read().await;
+            if let Some((ips, ttl)) = dns_client.lookup_hosts(&domain) {
+                return hosts_response(&req, &ips, ttl);
+            }
+            if let Some(group) = dns_client.server_group(&domain) {
+                return Err(anyhow!("domain {} resolved by dns group {}", domain, group));
+            }
+            if dns_client.bypasses_fake_ip(&domain) {
+                return Err(anyhow!("domain {} filtered from fake ips", domain));
+            }
+        }
+        let mut inner = self.inner.write().await;
+        let resp = inner.generate_fake_response(&req);
+        if let Some(path) = self.cache_file.as_ref() {
+            if inner.dirty && inner.saved_at.elapsed() >= SAVE_INTERVAL {
+                let data = inner.dump();
+                inner.dirty = false;
+                inner.saved_at = Instant::now();
+                let path = path.clone();
+                tokio::task::spawn_blocking(move || {
+                    if let Err(e) = write_cache_file(&path, &data) {
+                        warn!("write fake dns cache {} failed: {}", path.display(), e);
+                    }
+                });
+            }
+        }
+        resp
     }
 
     pub async fn is_fake_ip(&self, ip: &IpAddr) -> bool {
-        self.0.read().await.is_fake_ip(ip)
+        self.inner.read().await.is_fake_ip(ip)
+    }
+}
+
+impl Drop for FakeDns {
+    fn drop(&mut self) {
+        let Some(path) = self.cache_file.as_ref() else {
+            return;
+        };
+        let inner = self.inner.get_mut();
+        if inner.dirty {
+            if let Err(e) = write_cache_file(path, &inner.dump()) {
+                warn!("write fake dns cache {} failed: {}", path.display(), e);
+            }
+        }
+    }
+}
+
+fn domain_of(name: &Name) -> String {
+    // TODO check if a valid domain
+    if name.is_fqdn() {
+        let fqdn = name.to_ascii();
+        fqdn[..fqdn.len() - 1].to_string()
+    } else {
+        name.to_ascii()
     }
 }
 
+// Sets the response according to the request.
+// https://github.com/miekg/dns/blob/f515aa579d28efa1af67d9a62cc57f2dfe59da76/defaults.go#L15
+fn new_response(req: &Message) -> Message {
+    let mut resp = Message::new();
+    resp.set_id(req.id())
+        .set_message_type(MessageType::Response)
+        .set_op_code(req.op_code());
+    if resp.op_code() == OpCode::Query {
+        resp.set_recursion_desired(req.recursion_desired())
+            .set_checking_disabled(req.checking_disabled());
+    }
+    resp.set_response_code(ResponseCode::NoError);
+    if let Some(query) = req.queries().first() {
+        resp.add_query(query.clone());
+    }
+    resp
+}
+
+// Answers with the addresses of the queried family, other types with no
+// data.
+fn hosts_response(req: &Message, ips: &[IpAddr], ttl: u32) -> Result<Vec<u8>> {
+    let mut resp = new_response(req);
+    let query = &req.queries()[0];
+    for ip in ips {
+        let rdata = match (query.query_type(), ip) {
+            (RecordType::A, IpAddr::V4(ip)) => RData::A(rdata::A(*ip)),
+            (RecordType::AAAA, IpAddr::V6(ip)) => RData::AAAA(rdata::AAAA(*ip)),
+            _ => continue,
+        };
+        resp.add_answer(Record::from_rdata(query.name().clone(), ttl, rdata));
+    }
+    Ok(resp.to_vec()?)
+}
+
 struct FakeDnsImpl {
     ip_to_domain: HashMap<u32, String>,
     domain_to_ip: HashMap<String, u32>,
     cursor: u32,
     min_cursor: u32,
     max_cursor: u32,
+    // The IPv6 address of a domain is the one at the offset of its IPv4
+    // address in this pool.
+    ipv6_pool: Ipv6Cidr,
     ttl: u32,
     filters: Vec<String>,
     mode: FakeDnsMode,
+    // Whether there are allocations not written to the cache file yet, and
+    // when it was last written.
+    dirty: bool,
+    saved_at: Instant,
 }
 
 impl FakeDnsImpl {
@@ -61,29 +219,58 @@ impl FakeDnsImpl {
             cursor: min_cursor,
             min_cursor,
             max_cursor,
+            ipv6_pool: *crate::option::FAKE_IPV6_CIDR,
             ttl: 1,
             filters,
             mode,
+            dirty: false,
+            saved_at: Instant::now(),
         }
     }
 
     pub(self) fn query_domain(&self, ip: &IpAddr) -> Option<String> {
         let ip = match ip {
-            IpAddr::V4(ip) => ip,
-            _ => return None,
+            IpAddr::V4(ip) => Self::ip_to_u32(ip),
+            IpAddr::V6(ip) => self.ipv6_to_u32(ip)?,
         };
-        self.ip_to_domain.get(&Self::ip_to_u32(ip)).cloned()
+        self.ip_to_domain.get(&ip).cloned()
     }
 
-    pub(self) fn query_fake_ip(&self, domain: &str) -> Option<IpAddr> {
-        self.domain_to_ip
-            .get(domain)
-            .map(|v| IpAddr::V4(Self::u32_to_ip(v.to_owned())))
+    pub(self) fn query_fake_ip(&self, domain: &str, ipv6: bool) -> Option<IpAddr> {
+        let ip = *self.domain_to_ip.get(domain)?;
+        if ipv6 {
+            self.u32_to_ipv6(ip).map(IpAddr::V6)
+        } else {
+            Some(IpAddr::V4(Self::u32_to_ip(ip)))
+        }
     }
 
-    pub(self) fn generate_fake_response(&mut self, request: &[u8]) -> Result<Vec<u8>> {
-        let req = Message::from_vec(request)?;
+    // The address at the offset of `ip` in the IPv6 pool, none if the pool
+    // is too small.
+    fn u32_to_ipv6(&self, ip: u32) -> Option<Ipv6Addr> {
+        let offset = u128::from(ip - self.min_cursor);
+        let host_bits = 128 - u32::from(self.ipv6_pool.network_length());
+        if host_bits < 128 && offset >> host_bits != 0 {
+            return None;
+        }
+        Some(Ipv6Addr::from(
+            u128::from(self.ipv6_pool.first_address()) + offset,
+        ))
+    }
+
+    fn ipv6_to_u32(&self, ip: &Ipv6Addr) -> Option<u32> {
+        if !self.ipv6_pool.contains(ip) {
+            return None;
+        }
+        let offset = u128::from(*ip) - u128::from(self.ipv6_pool.first_address());
+        let ip = u128::from(self.min_cursor) + offset;
+        if ip > u128::from(self.max_cursor) {
+            return None;
+        }
+        Some(ip as u32)
+    }
 
+    pub(self) fn generate_fake_response(&mut self, req: &Message) -> Result<Vec<u8>> {
         if req.queries().is_empty() {
             return Err(anyhow!("no queries in this DNS request"));
         }
@@ -102,46 +289,22 @@ impl FakeDnsImpl {
         }
 
         let raw_name = query.name();
-
-        // TODO check if a valid domain
-        let domain = if raw_name.is_fqdn() {
-            let fqdn = raw_name.to_ascii();
-            fqdn[..fqdn.len() - 1].to_string()
-        } else {
-            raw_name.to_ascii()
-        };
+        let domain = domain_of(raw_name);
 
         if !self.accept(&domain) {
             return Err(anyhow!("domain {} not accepted", domain));
         }
 
-        let ip = if let Some(ip) = self.query_fake_ip(&domain) {
-            match ip {
-                IpAddr::V4(a) => a,
-                _ => return Err(anyhow!("unexpected Ipv6 fake IP")),
-            }
+        // The queries of both families share the allocation of the domain.
+        let ip = if let Some(ip) = self.domain_to_ip.get(&domain) {
+            Self::u32_to_ip(*ip)
         } else {
             let ip = self.allocate_ip(&domain)?;
             debug!("allocate {} for {}", &ip, &domain);
             ip
         };
 
-        let mut resp = Message::new();
-
-        // sets the response according to request
-        // https://github.com/miekg/dns/blob/f515aa579d28efa1af67d9a62cc57f2dfe59da76/defaults.go#L15
-        resp.set_id(req.id())
-            .set_message_type(MessageType::Response)
-            .set_op_code(req.op_code());
-
-        if resp.op_code() == OpCode::Query {
-            resp.set_recursion_desired(req.recursion_desired())
-                .set_checking_disabled(req.checking_disabled());
-        }
-        resp.set_response_code(ResponseCode::NoError);
-        if !req.queries().is_empty() {
-            resp.add_query(query.clone());
-        }
+        let mut resp = new_response(req);
 
         if query.query_type() == RecordType::A {
             let mut ans = Record::new();
@@ -151,6 +314,16 @@ impl FakeDnsImpl {
                 .set_dns_class(DNSClass::IN)
                 .set_data(Some(RData::A(rdata::A(ip))));
             resp.add_answer(ans);
+        } else if query.query_type() == RecordType::AAAA {
+            if let Some(ip) = self.u32_to_ipv6(Self::ip_to_u32(&ip)) {
+                let mut ans = Record::new();
+                ans.set_name(raw_name.clone())
+                    .set_rr_type(RecordType::AAAA)
+                    .set_ttl(self.ttl)
+                    .set_dns_class(DNSClass::IN)
+                    .set_data(Some(RData::AAAA(rdata::AAAA(ip))));
+                resp.add_answer(ans);
+            }
         }
 
         Ok(resp.to_vec()?)
@@ -159,7 +332,7 @@ impl FakeDnsImpl {
     pub(self) fn is_fake_ip(&self, ip: &IpAddr) -> bool {
         let ip = match ip {
             IpAddr::V4(ip) => ip,
-            _ => return false,
+            IpAddr::V6(ip) => return self.ipv6_pool.contains(ip),
         };
         let ip = Self::ip_to_u32(ip);
         ip >= self.min_cursor && ip <= self.max_cursor
@@ -172,11 +345,60 @@ impl FakeDnsImpl {
             self.domain_to_ip.remove(&prev_domain);
         }
         self.domain_to_ip.insert(domain.to_owned(), self.cursor);
+        self.dirty = true;
         let ip = Self::u32_to_ip(self.cursor);
         self.prepare_next_cursor()?;
         Ok(ip)
     }
 
+    // A line for each allocation, its address then its domain, from the
+    // next to be reused to the last one made.
+    fn dump(&self) -> String {
+        let size = self.max_cursor - self.min_cursor + 1;
+        let mut data = String::new();
+        for i in 0..size {
+            let ip = self.min_cursor + (self.cursor - self.min_cursor + i) % size;
+            if let Some(domain) = self.ip_to_domain.get(&ip) {
+                data.push_str(&format!("{} {}\n", Self::u32_to_ip(ip), domain));
+            }
+        }
+        data
+    }
+
+    // Loads the allocations dumped, those out of the pool are discarded. The
+    // next ones are made after the last one loaded, so that the oldest are
+    // reused first as before.
+    fn restore(&mut self, data: &str) {
+        let mut last = None;
+        for line in data.lines() {
+            let Some((ip, domain)) = line.split_once(' ') else {
+                continue;
+            };
+            let Ok(ip) = ip.parse::<Ipv4Addr>() else {
+                continue;
+            };
+            let ip = Self::ip_to_u32(&ip);
+            if domain.is_empty() || ip < self.min_cursor || ip > self.max_cursor {
+                continue;
+            }
+            if let Some(prev_domain) = self.ip_to_domain.insert(ip, domain.to_owned()) {
+                self.domain_to_ip.remove(&prev_domain);
+            }
+            if let Some(prev_ip) = self.domain_to_ip.insert(domain.to_owned(), ip) {
+                if prev_ip != ip {
+                    self.ip_to_domain.remove(&prev_ip);
+                }
+            }
+            last = Some(ip);
+        }
+        if let Some(last) = last {
+            self.cursor = last;
+            if self.prepare_next_cursor().is_err() {
+                self.cursor = self.min_cursor;
+            }
+        }
+    }
+
     // Make sure `self.cursor` is valid and can be used immediately for next fake IP.
     fn prepare_next_cursor(&mut self) -> Result<()> {
         for _ in 0..3 {
@@ -244,4 +466,63 @@ mod tests {
         let ip2 = 2130706433u32;
         assert_eq!(ip1, ip2);
     }
+
+    #[test]
+    fn test_dump_and_restore() {
+        let mut fake_dns = FakeDnsImpl::new(FakeDnsMode::Exclude, Vec::new());
+        let a = fake_dns.allocate_ip("a.com").unwrap();
+        let b = fake_dns.allocate_ip("b.com").unwrap();
+        let mut data = fake_dns.dump();
+        // Out of the pool.
+        data.push_str("10.0.0.1 c.com\n");
+
+        let mut restored = FakeDnsImpl::new(FakeDnsMode::Exclude, Vec::new());
+        restored.restore(&data);
+        assert_eq!(restored.query_domain(&a.into()), Some("a.com".to_string()));
+        assert_eq!(restored.query_fake_ip("b.com", false), Some(b.into()));
+        assert_eq!(restored.query_fake_ip("c.com", false), None);
+        assert!(!restored.dirty);
+        // Allocated after the restored ones.
+        let c = restored.allocate_ip("c.com").unwrap();
+        assert_eq!(FakeDnsImpl::ip_to_u32(&c), FakeDnsImpl::ip_to_u32(&b) + 1);
+    }
+
+    #[test]
+    fn test_ipv6_pool() {
+        let mut fake_dns = FakeDnsImpl::new(FakeDnsMode::Exclude, Vec::new());
+        let ip = fake_dns.allocate_ip("a.com").unwrap();
+        let ipv6 = fake_dns.query_fake_ip("a.com", true).unwrap();
+        assert_eq!(ipv6, "fc00::".parse::<IpAddr>().unwrap());
+        assert!(fake_dns.is_fake_ip(&ipv6));
+        assert_eq!(fake_dns.query_domain(&ipv6), Some("a.com".to_string()));
+        assert_eq!(fake_dns.query_domain(&ip.into()), Some("a.com".to_string()));
+        // Past the offsets of the IPv4 pool.
+        let outside = "fc00::1:0".parse().unwrap();
+        assert!(fake_dns.is_fake_ip(&outside));
+        assert_eq!(fake_dns.query_domain(&outside), None);
+        assert!(!fake_dns.is_fake_ip(&"fd00::1".parse().unwrap()));
+
+        fake_dns.ipv6_pool = "fc00::/120".parse().unwrap();
+        assert_eq!(fake_dns.query_fake_ip("a.com", true), None);
+    }
+
+    #[test]
+    fn test_hosts_response() {
+        use hickory_proto::op::Query;
+
+        let mut req = Message::new();
+        req.set_id(7).add_query(Query::query(
+            Name::from_ascii("nas.lan.").unwrap(),
+            RecordType::AAAA,
+        ));
+        let ips = ["10.0.0.1".parse().unwrap(), "fd00::1".parse().unwrap()];
+        let resp = Message::from_vec(&hosts_response(&req, &ips, 300).unwrap()).unwrap();
+        assert_eq!(resp.id(), 7);
+        assert_eq!(resp.answers().len(), 1);
+        assert_eq!(resp.answers()[0].ttl(), 300);
+        assert_eq!(
+            resp.answers()[0].data(),
+            Some(&RData::AAAA(rdata::AAAA("fd00::1".parse().unwrap())))
+        );
+    }
 }
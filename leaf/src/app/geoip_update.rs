@@ -0,0 +1,148 @@
+//! Keeps the geoip database of the rules up to date. It's downloaded when
+//! the file is missing or older than the interval, and only replaces the
+//! file once it's read as a database. The rules then match with it right
+//! away, a download which fails leaves the database in use as it was.
+
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
+
+use anyhow::{anyhow, Result};
+use tokio::sync::RwLock;
+use tracing::{info, warn};
+
+use crate::app::outbound::manager::OutboundManager;
+use crate::app::router::Router;
+use crate::app::rule_provider::download;
+use crate::app::SyncDnsClient;
+use crate::config;
+
+// The update interval if the config doesn't set one.
+const DEFAULT_INTERVAL: Duration = Duration::from_secs(7 * 86400);
+// How long after a failed update it's tried again.
+const RETRY_INTERVAL: Duration = Duration::from_secs(300);
+// How often the database is checked for an update.
+const CHECK_INTERVAL: Duration = Duration::from_secs(60);
+const DOWNLOAD_TIMEOUT: Duration = Duration::from_secs(300);
+
+struct State {
+    // When the file was written.
+    updated: Option<SystemTime>,
+    attempted: Option<Instant>,
+}
+
+pub struct GeoipUpdater {
+    url: String,
+    interval: u32,
+    outbound: String,
+    path: PathBuf,
+    state: std::sync::Mutex<State>,
+    // Held by an update, a forced update waits for the one running.
+    update_lock: tokio::sync::Mutex<()>,
+}
+
+impl GeoipUpdater {
+    pub fn new(config: &config::Router) -> Self {
+        let path = Path::new(&*crate::option::ASSET_LOCATION).join("geo.mmdb");
+        let updated = std::fs::metadata(&path).and_then(|x| x.modified()).ok();
+        GeoipUpdater {
+            url: config.geoip_url.clone(),
+            interval: config.geoip_update_interval,
+            outbound: config.geoip_outbound.clone(),
+            path,
+            state: std::sync::Mutex::new(State {
+                updated,
+                attempted: None,
+            }),
+            update_lock: tokio::sync::Mutex::new(()),
+        }
+    }
+
+    pub fn is_configured_by(&self, config: &config::Router) -> bool {
+        self.url == config.geoip_url
+            && self.interval == config.geoip_update_interval
+            && self.outbound == config.geoip_outbound
+    }
+
+    fn interval(&self) -> Duration {
+        match self.interval {
+            0 => DEFAULT_INTERVAL,
+            secs => Duration::from_secs(secs as u64),
+        }
+    }
+
+    /// Whether the file is missing or older than the interval, a failed
+    /// update is retried after a while.
+    pub fn is_due(&self) -> bool {
+        let state = self.state.lock().unwrap();
+        if state
+            .attempted
+            .is_some_and(|x| x.elapsed() < RETRY_INTERVAL)
+        {
+            return false;
+        }
+        match state.updated {
+            Some(x) => x.elapsed().unwrap_or_default() >= self.interval(),
+            None => true,
+        }
+    }
+
+    /// Downloads the database and has the router match with it, returns
+    /// its size in bytes.
+    pub async fn update(
+        &self,
+        router: &RwLock<Router>,
+        outbound_manager: &Arc<RwLock<OutboundManager>>,
+        dns_client: SyncDnsClient,
+    ) -> Result<usize> {
+        let _guard = self.update_lock.lock().await;
+        self.state.lock().unwrap().attempted = Some(Instant::now());
+        let handler = if self.outbound.is_empty() {
+            None
+        } else {
+            let handler = outbound_manager.read().await.get(&self.outbound);
+            Some(handler.ok_or_else(|| anyhow!("outbound {} not found", self.outbound))?)
+        };
+        let body = tokio::time::timeout(DOWNLOAD_TIMEOUT, download(&self.url, handler, dns_client))
+            .await
+            .map_err(|_| anyhow!("download timed out"))??;
+        maxminddb::Reader::from_source(body.as_slice())
+            .map_err(|e| anyhow!("invalid geoip database: {}", e))?;
+        let tmp = self.path.with_extension("tmp");
+        tokio::fs::write(&tmp, &body).await?;
+        tokio::fs::rename(&tmp, &self.path).await?;
+        let mut state = self.state.lock().unwrap();
+        state.updated = Some(SystemTime::now());
+        state.attempted = None;
+        drop(state);
+        router
+            .read()
+            .await
+            .reload_mmdb(&self.path.to_string_lossy())?;
+        Ok(body.len())
+    }
+}
+
+/// Updates the geoip database of the router when it's due.
+pub fn update_task(
+    router: Arc<RwLock<Router>>,
+    outbound_manager: Arc<RwLock<OutboundManager>>,
+    dns_client: SyncDnsClient,
+) -> crate::Runner {
+    Box::pin(async move {
+        loop {
+            // Read on each round, the updater changes with a reload.
+            let updater = router.read().await.geoip_updater();
+            if let Some(updater) = updater.filter(|x| x.is_due()) {
+                match updater
+                    .update(&router, &outbound_manager, dns_client.clone())
+                    .await
+                {
+                    Ok(n) => info!("updated geoip database, {} bytes", n),
+                    Err(e) => warn!("update geoip database failed: {}", e),
+                }
+            }
+            tokio::time::sleep(CHECK_INTERVAL).await;
+        }
+    })
+}
@@ -0,0 +1,102 @@
+use std::io;
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use anyhow::Result;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::UdpSocket;
+use tracing::{debug, info, warn};
+
+use crate::app::dns::DnsServer;
+use crate::Runner;
+
+// Serves queries on a TCP connection, each one prefixed with its length,
+// until the client closes it.
+async fn handle_tcp_stream<S>(
+    mut stream: S,
+    source: SocketAddr,
+    server: Arc<DnsServer>,
+) -> io::Result<()>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    loop {
+        let len = match stream.read_u16().await {
+            Ok(len) => len as usize,
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(()),
+            Err(e) => return Err(e),
+        };
+        let mut request = vec![0u8; len];
+        stream.read_exact(&mut request).await?;
+        let response = server
+            .handle(source, &request, false)
+            .await
+            .map_err(io::Error::other)?;
+        let mut buf = Vec::with_capacity(2 + response.len());
+        buf.extend_from_slice(&(response.len() as u16).to_be_bytes());
+        buf.extend_from_slice(&response);
+        stream.write_all(&buf).await?;
+    }
+}
+
+async fn handle_tcp_listen(listen_addr: SocketAddr, server: Arc<DnsServer>) -> io::Result<()> {
+    let listener = crate::proxy::TcpListener::bind(&listen_addr).await?;
+    info!("listening tcp {} (dns)", &listen_addr);
+    loop {
+        let (stream, source) = listener.accept().await?;
+        let server = server.clone();
+        tokio::spawn(async move {
+            if let Err(e) = handle_tcp_stream(stream, source, server).await {
+                debug!("serve dns over tcp for {} failed: {}", &source, e);
+            }
+        });
+    }
+}
+
+async fn handle_udp_listen(listen_addr: SocketAddr, server: Arc<DnsServer>) -> io::Result<()> {
+    let socket = Arc::new(UdpSocket::bind(&listen_addr).await?);
+    info!("listening udp {} (dns)", &listen_addr);
+    let mut buf = vec![0u8; u16::MAX as usize];
+    loop {
+        let (n, source) = socket.recv_from(&mut buf).await?;
+        let request = buf[..n].to_vec();
+        let socket = socket.clone();
+        let server = server.clone();
+        tokio::spawn(async move {
+            match server.handle(source, &request, true).await {
+                Ok(response) => {
+                    if let Err(e) = socket.send_to(&response, &source).await {
+                        debug!("send dns response to {} failed: {}", &source, e);
+                    }
+                }
+                Err(e) => debug!("handle dns query from {} failed: {}", &source, e),
+            }
+        });
+    }
+}
+
+pub struct DnsInboundListener {
+    pub address: String,
+    pub port: u16,
+    pub server: Arc<DnsServer>,
+}
+
+impl DnsInboundListener {
+    pub fn listen(&self) -> Result<Vec<Runner>> {
+        let listen_addr = SocketAddr::new(self.address.parse()?, self.port);
+        let tcp_server = self.server.clone();
+        let udp_server = self.server.clone();
+        Ok(vec![
+            Box::pin(async move {
+                if let Err(e) = handle_tcp_listen(listen_addr, tcp_server).await {
+                    warn!("handler tcp listen failed: {}", e);
+                }
+            }),
+            Box::pin(async move {
+                if let Err(e) = handle_udp_listen(listen_addr, udp_server).await {
+                    warn!("handler udp listen failed: {}", e);
+                }
+            }),
+        ])
+    }
+}
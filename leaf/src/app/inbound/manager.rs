@@ -1,8 +1,10 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
 
 use anyhow::{anyhow, Result};
+use futures::future::AbortHandle;
 use protobuf::Message;
+use tracing::info;
 
 use crate::app::dispatcher::Dispatcher;
 use crate::app::nat_manager::NatManager;
@@ -13,10 +15,16 @@ use crate::Runner;
 
 #[cfg(feature = "inbound-amux")]
 use crate::proxy::amux;
+#[cfg(feature = "inbound-grpc")]
+use crate::proxy::grpc;
 #[cfg(feature = "inbound-hc")]
 use crate::proxy::hc;
 #[cfg(feature = "inbound-http")]
 use crate::proxy::http;
+#[cfg(feature = "inbound-httpupgrade")]
+use crate::proxy::httpupgrade;
+#[cfg(feature = "inbound-mixed")]
+use crate::proxy::mixed;
 #[cfg(feature = "inbound-mptp")]
 use crate::proxy::mptp;
 #[cfg(all(feature = "inbound-nf", windows))]
@@ -31,6 +39,10 @@ use crate::proxy::socks;
 use crate::proxy::tls;
 #[cfg(feature = "inbound-trojan")]
 use crate::proxy::trojan;
+#[cfg(feature = "inbound-tunnel")]
+use crate::proxy::tunnel;
+#[cfg(feature = "inbound-vless")]
+use crate::proxy::vless;
 #[cfg(feature = "inbound-ws")]
 use crate::proxy::ws;
 
@@ -45,12 +57,69 @@ use super::cat_listener::CatInboundListener;
 #[cfg(feature = "inbound-tun")]
 use super::tun_listener::TunInboundListener;
 
+#[cfg(all(feature = "inbound-redirect", target_os = "linux"))]
+use super::redirect_listener::RedirectInboundListener;
+
+#[cfg(feature = "inbound-dns")]
+use super::dns_listener::DnsInboundListener;
+
+// Returns the accounts and whether SOCKS4 is served, from the settings of a
+// socks inbound or a mixed one.
+#[cfg(feature = "inbound-socks")]
+fn socks_settings(inbound: &config::Inbound) -> Result<(HashMap<String, String>, bool)> {
+    let mut users = HashMap::new();
+    let mut socks4 = true;
+    if !inbound.settings.is_empty() {
+        let settings = config::SocksInboundSettings::parse_from_bytes(&inbound.settings)
+            .map_err(|e| anyhow!("invalid [{}] inbound settings: {}", &inbound.tag, e))?;
+        if !settings.username.is_empty() {
+            users.insert(settings.username, settings.password);
+        }
+        for user in settings.users {
+            users.insert(user.name, user.password);
+        }
+        socks4 = settings.socks4.unwrap_or(true);
+    }
+    Ok((users, socks4))
+}
+
+// Returns the configured certificate and key, or a self-signed pair for the
+// SAN, which defaults to the listen address, if none is configured.
+#[cfg(any(feature = "inbound-quic", feature = "inbound-tls"))]
+fn certificate_or_self_signed(
+    inbound: &config::Inbound,
+    certificate: &str,
+    certificate_key: &str,
+    self_signed_san: &str,
+    persist_self_signed: bool,
+) -> Result<(String, String)> {
+    if !certificate.is_empty() {
+        return Ok((certificate.to_string(), certificate_key.to_string()));
+    }
+    let san = if self_signed_san.is_empty() {
+        &inbound.address
+    } else {
+        self_signed_san
+    };
+    let persist_as = if persist_self_signed {
+        Some(format!("{}.self-signed", &inbound.tag))
+    } else {
+        None
+    };
+    crate::common::cert::self_signed_certificate(san, persist_as.as_deref())
+        .map_err(|e| anyhow!("generate self-signed certificate failed: {}", e))
+}
+
 pub struct InboundManager {
     network_listeners: HashMap<String, NetworkInboundListener>,
     #[cfg(feature = "inbound-tun")]
     tun_listener: Option<TunInboundListener>,
     #[cfg(feature = "inbound-cat")]
     cat_listener: Option<CatInboundListener>,
+    #[cfg(all(feature = "inbound-redirect", target_os = "linux"))]
+    redirect_listeners: Vec<RedirectInboundListener>,
+    #[cfg(feature = "inbound-dns")]
+    dns_listeners: Vec<DnsInboundListener>,
     tun_auto: bool,
 }
 
@@ -67,44 +136,101 @@ impl InboundManager {
             match inbound.protocol.as_str() {
                 #[cfg(feature = "inbound-socks")]
                 "socks" => {
-                    let mut username = None;
-                    let mut password = None;
+                    let (users, socks4) = socks_settings(inbound)?;
+                    let associations =
+                        Arc::new(socks::inbound::UdpAssociations::new(!users.is_empty()));
+                    let stream = Arc::new(socks::inbound::StreamHandler::new(
+                        users,
+                        socks4,
+                        associations.clone(),
+                    ));
+                    let datagram = Arc::new(socks::inbound::DatagramHandler::new(associations));
+                    let handler = Arc::new(proxy::inbound::Handler::new(
+                        tag.clone(),
+                        Some(stream),
+                        Some(datagram),
+                    ));
+                    handlers.insert(tag.clone(), handler);
+                }
+                #[cfg(feature = "inbound-http")]
+                "http" => {
+                    let mut users = HashMap::new();
                     if !inbound.settings.is_empty() {
                         let settings =
-                            config::SocksInboundSettings::parse_from_bytes(&inbound.settings)
+                            config::HttpInboundSettings::parse_from_bytes(&inbound.settings)
                                 .map_err(|e| {
                                     anyhow!("invalid [{}] inbound settings: {}", &tag, e)
                                 })?;
-                        username = if settings.username.is_empty() {
-                            None
-                        } else {
-                            Some(settings.username)
-                        };
-                        password = if settings.password.is_empty() {
-                            None
-                        } else {
-                            Some(settings.password)
-                        };
+                        for user in settings.users {
+                            users.insert(user.name, user.password);
+                        }
                     }
-                    let stream = Arc::new(socks::inbound::StreamHandler { username, password });
-                    let datagram = Arc::new(socks::inbound::DatagramHandler);
+                    let stream = Arc::new(http::inbound::StreamHandler::new(users));
                     let handler = Arc::new(proxy::inbound::Handler::new(
                         tag.clone(),
                         Some(stream),
-                        Some(datagram),
+                        None,
                     ));
                     handlers.insert(tag.clone(), handler);
                 }
-                #[cfg(feature = "inbound-http")]
-                "http" => {
-                    let stream = Arc::new(http::inbound::StreamHandler);
+                #[cfg(feature = "inbound-mixed")]
+                "mixed" => {
+                    let (users, socks4) = socks_settings(inbound)?;
+                    let associations =
+                        Arc::new(socks::inbound::UdpAssociations::new(!users.is_empty()));
+                    let socks_stream = Arc::new(socks::inbound::StreamHandler::new(
+                        users.clone(),
+                        socks4,
+                        associations.clone(),
+                    ));
+                    let http_stream = Arc::new(http::inbound::StreamHandler::new(users));
+                    let stream = Arc::new(mixed::inbound::StreamHandler::new(
+                        socks_stream,
+                        http_stream,
+                    ));
+                    let datagram = Arc::new(socks::inbound::DatagramHandler::new(associations));
                     let handler = Arc::new(proxy::inbound::Handler::new(
                         tag.clone(),
                         Some(stream),
-                        None,
+                        Some(datagram),
                     ));
                     handlers.insert(tag.clone(), handler);
                 }
+                #[cfg(feature = "inbound-tunnel")]
+                "tunnel" => {
+                    let settings =
+                        config::TunnelInboundSettings::parse_from_bytes(&inbound.settings)
+                            .map_err(|e| anyhow!("invalid [{}] inbound settings: {}", &tag, e))?;
+                    let destination = tunnel::inbound::parse_destination(&settings.destination)
+                        .map_err(|e| anyhow!("invalid [{}] inbound settings: {}", &tag, e))?;
+                    let (tcp, udp) = match settings.network.as_str() {
+                        "" | "both" => (true, true),
+                        "tcp" => (true, false),
+                        "udp" => (false, true),
+                        network => {
+                            return Err(anyhow!(
+                                "invalid [{}] inbound settings: unknown network {}",
+                                &tag,
+                                network
+                            ));
+                        }
+                    };
+                    let stream: Option<proxy::AnyInboundStreamHandler> = if tcp {
+                        Some(Arc::new(tunnel::inbound::StreamHandler::new(
+                            destination.clone(),
+                        )))
+                    } else {
+                        None
+                    };
+                    let datagram: Option<proxy::AnyInboundDatagramHandler> = if udp {
+                        Some(Arc::new(tunnel::inbound::DatagramHandler::new(destination)))
+                    } else {
+                        None
+                    };
+                    let handler =
+                        Arc::new(proxy::inbound::Handler::new(tag.clone(), stream, datagram));
+                    handlers.insert(tag.clone(), handler);
+                }
                 #[cfg(feature = "inbound-mptp")]
                 "mptp" => {
                     let stream = Arc::new(mptp::inbound::stream::Handler::new());
@@ -144,7 +270,12 @@ impl InboundManager {
                     } else {
                         (FakeDnsMode::Exclude, fake_dns_exclude)
                     };
-                    let fake_dns = Arc::new(FakeDns::new(mode, filters));
+                    let fake_dns = Arc::new(FakeDns::new(
+                        mode,
+                        filters,
+                        dispatcher.dns_client.clone(),
+                        crate::app::fake_dns::cache_file(&tag),
+                    ));
                     let manager = Arc::new(nf::inbound::NfManager::new(
                         settings.driver_name.clone(),
                         settings.nfapi.clone(),
@@ -166,10 +297,10 @@ impl InboundManager {
                     let settings =
                         config::ShadowsocksInboundSettings::parse_from_bytes(&inbound.settings)
                             .map_err(|e| anyhow!("invalid [{}] inbound settings: {}", &tag, e))?;
-                    let stream = Arc::new(shadowsocks::inbound::StreamHandler {
-                        cipher: settings.method.clone(),
-                        password: settings.password.clone(),
-                    });
+                    let stream = Arc::new(shadowsocks::inbound::StreamHandler::new(
+                        settings.method.clone(),
+                        settings.password.clone(),
+                    )?);
                     let datagram = Arc::new(shadowsocks::inbound::DatagramHandler {
                         cipher: settings.method.clone(),
                         password: settings.password.clone(),
@@ -186,8 +317,14 @@ impl InboundManager {
                     let settings =
                         config::TrojanInboundSettings::parse_from_bytes(&inbound.settings)
                             .map_err(|e| anyhow!("invalid [{}] inbound settings: {}", &tag, e))?;
+                    let users = settings
+                        .users
+                        .into_iter()
+                        .map(|x| (x.name, x.password))
+                        .collect();
                     let stream = Arc::new(trojan::inbound::StreamHandler::new(
-                        settings.passwords.to_vec(),
+                        settings.passwords,
+                        users,
                     ));
                     let handler = Arc::new(proxy::inbound::Handler::new(
                         tag.clone(),
@@ -196,12 +333,92 @@ impl InboundManager {
                     ));
                     handlers.insert(tag.clone(), handler);
                 }
+                #[cfg(feature = "inbound-vless")]
+                "vless" => {
+                    let settings =
+                        config::VlessInboundSettings::parse_from_bytes(&inbound.settings)
+                            .map_err(|e| anyhow!("invalid [{}] inbound settings: {}", &tag, e))?;
+                    let fallback = if settings.fallback.is_empty() {
+                        None
+                    } else {
+                        Some(settings.fallback.clone())
+                    };
+                    let stream = Arc::new(
+                        vless::inbound::StreamHandler::new(settings.uuids.to_vec(), fallback)
+                            .map_err(|e| anyhow!("invalid [{}] inbound settings: {}", &tag, e))?,
+                    );
+                    let handler = Arc::new(proxy::inbound::Handler::new(
+                        tag.clone(),
+                        Some(stream),
+                        None,
+                    ));
+                    handlers.insert(tag.clone(), handler);
+                }
                 #[cfg(feature = "inbound-ws")]
                 "ws" => {
                     let settings =
                         config::WebSocketInboundSettings::parse_from_bytes(&inbound.settings)
                             .map_err(|e| anyhow!("invalid [{}] inbound settings: {}", &tag, e))?;
-                    let stream = Arc::new(ws::inbound::StreamHandler::new(settings.path.clone()));
+                    let header = if settings.header_name.is_empty() {
+                        None
+                    } else {
+                        Some((settings.header_name.clone(), settings.header_value.clone()))
+                    };
+                    let fallback = if !settings.fallback.is_empty() {
+                        Some(ws::inbound::Fallback::Address(settings.fallback.clone()))
+                    } else if settings.fallback_status != 0 || !settings.fallback_file.is_empty() {
+                        let status = if settings.fallback_status == 0 {
+                            200
+                        } else {
+                            settings.fallback_status
+                        };
+                        let file = if settings.fallback_file.is_empty() {
+                            None
+                        } else {
+                            Some(settings.fallback_file.as_str())
+                        };
+                        Some(
+                            ws::inbound::Fallback::response(status, file).map_err(|e| {
+                                anyhow!("invalid [{}] inbound settings: {}", &tag, e)
+                            })?,
+                        )
+                    } else {
+                        None
+                    };
+                    let stream = Arc::new(ws::inbound::StreamHandler::new(
+                        settings.path.clone(),
+                        header,
+                        fallback,
+                    ));
+                    let handler = Arc::new(proxy::inbound::Handler::new(
+                        tag.clone(),
+                        Some(stream),
+                        None,
+                    ));
+                    handlers.insert(tag.clone(), handler);
+                }
+                #[cfg(feature = "inbound-grpc")]
+                "grpc" => {
+                    let settings = config::GrpcInboundSettings::parse_from_bytes(&inbound.settings)
+                        .map_err(|e| anyhow!("invalid [{}] inbound settings: {}", &tag, e))?;
+                    let stream = Arc::new(grpc::inbound::StreamHandler::new(
+                        settings.service_name.clone(),
+                    ));
+                    let handler = Arc::new(proxy::inbound::Handler::new(
+                        tag.clone(),
+                        Some(stream),
+                        None,
+                    ));
+                    handlers.insert(tag.clone(), handler);
+                }
+                #[cfg(feature = "inbound-httpupgrade")]
+                "httpupgrade" => {
+                    let settings =
+                        config::HttpUpgradeInboundSettings::parse_from_bytes(&inbound.settings)
+                            .map_err(|e| anyhow!("invalid [{}] inbound settings: {}", &tag, e))?;
+                    let stream = Arc::new(httpupgrade::inbound::StreamHandler::new(
+                        settings.path.clone(),
+                    ));
                     let handler = Arc::new(proxy::inbound::Handler::new(
                         tag.clone(),
                         Some(stream),
@@ -213,10 +430,64 @@ impl InboundManager {
                 "quic" => {
                     let settings = config::QuicInboundSettings::parse_from_bytes(&inbound.settings)
                         .map_err(|e| anyhow!("invalid [{}] inbound settings: {}", &tag, e))?;
+                    let max_concurrent_bidi_streams = if settings.max_concurrent_bidi_streams == 0 {
+                        None
+                    } else {
+                        Some(settings.max_concurrent_bidi_streams)
+                    };
+                    let max_idle_timeout_ms = if settings.max_idle_timeout_ms == 0 {
+                        None
+                    } else {
+                        Some(settings.max_idle_timeout_ms)
+                    };
+                    let keep_alive_interval_ms = if settings.keep_alive_interval_ms == 0 {
+                        None
+                    } else {
+                        Some(settings.keep_alive_interval_ms)
+                    };
+                    let congestion_controller = if settings.congestion_controller.is_empty() {
+                        None
+                    } else {
+                        Some(settings.congestion_controller.clone())
+                    };
+                    let certificate_reload_interval = if settings.certificate_reload_interval == 0 {
+                        None
+                    } else {
+                        Some(settings.certificate_reload_interval)
+                    };
+                    let client_ca_certificate = if settings.client_ca_certificate.is_empty() {
+                        None
+                    } else {
+                        Some(settings.client_ca_certificate.clone())
+                    };
+                    let fallback = if settings.fallback.is_empty() {
+                        None
+                    } else {
+                        Some(settings.fallback.clone())
+                    };
+                    let (certificate, certificate_key) = certificate_or_self_signed(
+                        inbound,
+                        &settings.certificate,
+                        &settings.certificate_key,
+                        &settings.self_signed_san,
+                        settings.persist_self_signed,
+                    )
+                    .map_err(|e| anyhow!("invalid [{}] inbound settings: {}", &tag, e))?;
                     let datagram = Arc::new(quic::inbound::DatagramHandler::new(
-                        settings.certificate.clone(),
-                        settings.certificate_key.clone(),
+                        tag.clone(),
+                        certificate,
+                        certificate_key,
                         settings.alpn.clone(),
+                        max_concurrent_bidi_streams,
+                        max_idle_timeout_ms,
+                        keep_alive_interval_ms,
+                        congestion_controller,
+                        certificate_reload_interval,
+                        client_ca_certificate,
+                        settings.udp_over_stream,
+                        settings.accept_uni_streams,
+                        settings.fallback_alpns.clone(),
+                        fallback,
                     )?);
                     let handler = Arc::new(proxy::inbound::Handler::new(
                         tag.clone(),
@@ -239,12 +510,26 @@ impl InboundManager {
                     } else {
                         Some(settings.ech_key.clone())
                     };
+                    let default_sni = if settings.default_sni.is_empty() {
+                        None
+                    } else {
+                        Some(settings.default_sni.clone())
+                    };
+                    let (certificate, certificate_key) = certificate_or_self_signed(
+                        inbound,
+                        &settings.certificate,
+                        &settings.certificate_key,
+                        &settings.self_signed_san,
+                        settings.persist_self_signed,
+                    )
+                    .map_err(|e| anyhow!("invalid [{}] inbound settings: {}", &tag, e))?;
                     let stream = Arc::new(
                         tls::inbound::StreamHandler::new(
-                            settings.certificate.clone(),
-                            settings.certificate_key.clone(),
+                            certificate,
+                            certificate_key,
                             ech_config,
                             ech_key,
+                            default_sni,
                         )
                         .map_err(|e| anyhow!("invalid [{}] inbound tls capability: {}", &tag, e))?,
                     );
@@ -279,6 +564,8 @@ impl InboundManager {
                         }
                         let stream = Arc::new(amux::inbound::StreamHandler {
                             actors: actors.clone(),
+                            max_sessions_per_connection: settings.max_sessions_per_connection
+                                as usize,
                         });
                         let handler = Arc::new(proxy::inbound::Handler::new(
                             tag.clone(),
@@ -334,6 +621,12 @@ impl InboundManager {
         #[cfg(feature = "inbound-cat")]
         let mut cat_listener: Option<CatInboundListener> = None;
 
+        #[cfg(all(feature = "inbound-redirect", target_os = "linux"))]
+        let mut redirect_listeners: Vec<RedirectInboundListener> = Vec::new();
+
+        #[cfg(feature = "inbound-dns")]
+        let mut dns_listeners: Vec<DnsInboundListener> = Vec::new();
+
         let mut tun_auto = false;
 
         for inbound in inbounds.iter() {
@@ -360,6 +653,80 @@ impl InboundManager {
                     };
                     cat_listener.replace(listener);
                 }
+                #[cfg(all(feature = "inbound-redirect", target_os = "linux"))]
+                "redirect" | "tproxy" => {
+                    redirect_listeners.push(RedirectInboundListener {
+                        inbound: inbound.clone(),
+                        tproxy: inbound.protocol == "tproxy",
+                        dispatcher: dispatcher.clone(),
+                        nat_manager: nat_manager.clone(),
+                    });
+                }
+                #[cfg(all(feature = "inbound-redirect", not(target_os = "linux")))]
+                "redirect" | "tproxy" => {
+                    return Err(anyhow!(
+                        "[{}] {} inbound is only supported on Linux",
+                        &tag,
+                        &inbound.protocol
+                    ));
+                }
+                #[cfg(feature = "inbound-dns")]
+                "dns" => {
+                    use crate::app::fake_dns::{FakeDns, FakeDnsMode};
+                    let settings = config::DnsInboundSettings::parse_from_bytes(&inbound.settings)
+                        .map_err(|e| anyhow!("invalid [{}] inbound settings: {}", &tag, e))?;
+                    if !settings.fake_dns_exclude.is_empty()
+                        && !settings.fake_dns_include.is_empty()
+                    {
+                        return Err(anyhow!(
+                            "invalid [{}] inbound settings: fake DNS run in either include mode or exclude mode",
+                            &tag
+                        ));
+                    }
+                    let fake_dns = if !settings.fake_dns_include.is_empty() {
+                        Some(Arc::new(FakeDns::new(
+                            FakeDnsMode::Include,
+                            settings.fake_dns_include,
+                            dispatcher.dns_client.clone(),
+                            crate::app::fake_dns::cache_file(&tag),
+                        )))
+                    } else if !settings.fake_dns_exclude.is_empty() {
+                        Some(Arc::new(FakeDns::new(
+                            FakeDnsMode::Exclude,
+                            settings.fake_dns_exclude,
+                            dispatcher.dns_client.clone(),
+                            crate::app::fake_dns::cache_file(&tag),
+                        )))
+                    } else {
+                        None
+                    };
+                    if let Some(fake_dns) = &fake_dns {
+                        dispatcher.add_fake_dns(fake_dns.clone());
+                    }
+                    let blocked_nxdomain = match settings.blocked_response.as_str() {
+                        "" | "nxdomain" => true,
+                        "zero" => false,
+                        v => {
+                            return Err(anyhow!(
+                                "invalid [{}] inbound settings: unknown blocked response {}",
+                                &tag,
+                                v
+                            ));
+                        }
+                    };
+                    let ttl = if settings.ttl == 0 { 60 } else { settings.ttl };
+                    dns_listeners.push(DnsInboundListener {
+                        address: inbound.address.clone(),
+                        port: inbound.port as u16,
+                        server: Arc::new(crate::app::dns::DnsServer::new(
+                            tag.clone(),
+                            dispatcher.clone(),
+                            fake_dns,
+                            ttl,
+                            blocked_nxdomain,
+                        )),
+                    });
+                }
                 _ => {
                     if let Some(h) = handlers.get(&tag) {
                         let listener = NetworkInboundListener {
@@ -381,6 +748,10 @@ impl InboundManager {
             tun_listener,
             #[cfg(feature = "inbound-cat")]
             cat_listener,
+            #[cfg(all(feature = "inbound-redirect", target_os = "linux"))]
+            redirect_listeners,
+            #[cfg(feature = "inbound-dns")]
+            dns_listeners,
             tun_auto,
         })
     }
@@ -390,6 +761,14 @@ impl InboundManager {
         for (_, listener) in self.network_listeners.iter() {
             runners.append(&mut listener.listen()?);
         }
+        #[cfg(all(feature = "inbound-redirect", target_os = "linux"))]
+        for listener in self.redirect_listeners.iter() {
+            runners.append(&mut listener.listen()?);
+        }
+        #[cfg(feature = "inbound-dns")]
+        for listener in self.dns_listeners.iter() {
+            runners.append(&mut listener.listen()?);
+        }
         Ok(runners)
     }
 
@@ -412,3 +791,204 @@ impl InboundManager {
         self.tun_auto
     }
 }
+
+/// Whether an inbound is served by a network listener. The other inbounds
+/// have listeners of their own, which run for the lifetime of the runtime.
+pub fn is_network_inbound(inbound: &config::Inbound) -> bool {
+    !matches!(
+        inbound.protocol.as_str(),
+        "tun" | "cat" | "redirect" | "tproxy" | "dns"
+    )
+}
+
+// Returns the inbounds an inbound goes through.
+fn inbound_actors(inbound: &config::Inbound) -> Vec<String> {
+    match inbound.protocol.as_str() {
+        "chain" => config::ChainInboundSettings::parse_from_bytes(&inbound.settings)
+            .map(|x| x.actors)
+            .unwrap_or_default(),
+        "amux" => config::AMuxInboundSettings::parse_from_bytes(&inbound.settings)
+            .map(|x| x.actors)
+            .unwrap_or_default(),
+        _ => Vec::new(),
+    }
+}
+
+// Returns the tags of the inbounds which are new, changed or removed, along
+// with those going through them.
+fn changed_inbounds(old: &[config::Inbound], new: &[config::Inbound]) -> HashSet<String> {
+    // The final outbound is up to the router, the listener stays when only
+    // it changes.
+    let same = |a: &config::Inbound, b: &config::Inbound| {
+        a.tag == b.tag
+            && a.protocol == b.protocol
+            && a.address == b.address
+            && a.port == b.port
+            && a.settings == b.settings
+    };
+    let mut changed: HashSet<String> = new
+        .iter()
+        .filter(|x| !old.iter().any(|y| same(x, y)))
+        .chain(old.iter().filter(|x| !new.iter().any(|y| same(x, y))))
+        .map(|x| x.tag.clone())
+        .collect();
+    loop {
+        let more: Vec<String> = new
+            .iter()
+            .filter(|x| !changed.contains(&x.tag))
+            .filter(|x| inbound_actors(x).iter().any(|a| changed.contains(a)))
+            .map(|x| x.tag.clone())
+            .collect();
+        if more.is_empty() {
+            return changed;
+        }
+        changed.extend(more);
+    }
+}
+
+// The listener tasks of an inbound.
+#[derive(Default)]
+struct ListenerTasks {
+    tcp: Option<AbortHandle>,
+    udp: Option<AbortHandle>,
+}
+
+impl ListenerTasks {
+    fn abort(&self) {
+        self.tcp
+            .iter()
+            .chain(self.udp.iter())
+            .for_each(|x| x.abort());
+    }
+}
+
+fn spawn_listener(runner: Option<Runner>) -> Option<AbortHandle> {
+    runner.map(|x| {
+        let (task, abort_handle) = futures::future::abortable(x);
+        tokio::spawn(task);
+        abort_handle
+    })
+}
+
+/// The network listeners, each of which runs in a task of its own, so that a
+/// reload only restarts the listeners of the inbounds which changed. The TCP
+/// sessions accepted by a listener outlive it, the UDP ones don't.
+pub struct NetworkListeners {
+    dispatcher: Arc<Dispatcher>,
+    nat_manager: Arc<NatManager>,
+    inbounds: Vec<config::Inbound>,
+    tasks: HashMap<String, ListenerTasks>,
+}
+
+impl NetworkListeners {
+    pub fn new(dispatcher: Arc<Dispatcher>, nat_manager: Arc<NatManager>) -> Self {
+        NetworkListeners {
+            dispatcher,
+            nat_manager,
+            inbounds: Vec::new(),
+            tasks: HashMap::new(),
+        }
+    }
+
+    /// Starts the listeners of the inbounds which are new or changed and
+    /// stops those of the inbounds which changed or are gone. Returns whether
+    /// any running listener was stopped.
+    pub fn reload(&mut self, inbounds: &[config::Inbound]) -> Result<bool> {
+        let inbounds: Vec<config::Inbound> = inbounds
+            .iter()
+            .filter(|x| is_network_inbound(x))
+            .cloned()
+            .collect();
+        let changed = changed_inbounds(&self.inbounds, &inbounds);
+        if changed.is_empty() {
+            return Ok(false);
+        }
+        // The handlers of all inbounds, for those going through others.
+        let manager =
+            InboundManager::new(&inbounds, self.dispatcher.clone(), self.nat_manager.clone())?;
+        let mut runners = Vec::new();
+        for tag in changed.iter() {
+            if let Some(listener) = manager.network_listeners.get(tag) {
+                runners.push((tag.clone(), listener.listen_tcp()?, listener.listen_udp()?));
+            }
+        }
+        let mut stopped = false;
+        for tag in changed.iter() {
+            if let Some(tasks) = self.tasks.remove(tag) {
+                info!("stopping listeners of [{}]", tag);
+                tasks.abort();
+                stopped = true;
+            }
+        }
+        for (tag, tcp, udp) in runners {
+            let tasks = ListenerTasks {
+                tcp: spawn_listener(tcp),
+                udp: spawn_listener(udp),
+            };
+            self.tasks.insert(tag, tasks);
+        }
+        self.inbounds = inbounds;
+        Ok(stopped)
+    }
+
+    /// Stops accepting sessions, from any inbound, for shutting down. The TCP
+    /// listeners are stopped, the UDP ones keep running since they relay the
+    /// datagrams of the existing sessions as well.
+    pub fn drain(&mut self) {
+        self.dispatcher.drain();
+        self.nat_manager.drain();
+        for tasks in self.tasks.values_mut() {
+            if let Some(task) = tasks.tcp.take() {
+                task.abort();
+            }
+        }
+    }
+}
+
+impl Drop for NetworkListeners {
+    fn drop(&mut self) {
+        for tasks in self.tasks.values() {
+            tasks.abort();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn inbound(tag: &str, port: u32) -> config::Inbound {
+        config::Inbound {
+            tag: tag.to_string(),
+            protocol: "socks".to_string(),
+            address: "127.0.0.1".to_string(),
+            port,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_changed_inbounds() {
+        let chain = config::Inbound {
+            tag: "chain".to_string(),
+            protocol: "chain".to_string(),
+            settings: config::ChainInboundSettings {
+                actors: vec!["b".to_string()],
+                ..Default::default()
+            }
+            .write_to_bytes()
+            .unwrap(),
+            ..Default::default()
+        };
+        let old = vec![inbound("a", 1080), inbound("b", 1081), inbound("c", 1082)];
+        assert!(changed_inbounds(&old, &old).is_empty());
+        let mut new = vec![inbound("a", 1080), inbound("b", 1091), chain];
+        let changed = changed_inbounds(&old, &new);
+        let expected: HashSet<String> = ["b", "c", "chain"].iter().map(|x| x.to_string()).collect();
+        assert_eq!(changed, expected);
+        new.truncate(1);
+        assert!(!changed_inbounds(&old, &new).contains("a"));
+        new[0].final_outbound = "proxy".to_string();
+        assert!(!changed_inbounds(&old, &new).contains("a"));
+    }
+}
@@ -6,6 +6,12 @@ mod tun_listener;
 #[cfg(feature = "inbound-cat")]
 mod cat_listener;
 
+#[cfg(all(feature = "inbound-redirect", target_os = "linux"))]
+mod redirect_listener;
+
+#[cfg(feature = "inbound-dns")]
+mod dns_listener;
+
 pub mod manager;
 
 #[cfg(feature = "inbound-nf")]
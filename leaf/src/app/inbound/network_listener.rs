@@ -288,46 +288,43 @@ pub struct NetworkInboundListener {
 
 impl NetworkInboundListener {
     pub fn listen(&self) -> Result<Vec<Runner>> {
-        let mut runners: Vec<Runner> = Vec::new();
+        Ok(self
+            .listen_tcp()?
+            .into_iter()
+            .chain(self.listen_udp()?)
+            .collect())
+    }
+
+    /// Returns the runner listening on TCP, none if the inbound doesn't.
+    pub fn listen_tcp(&self) -> Result<Option<Runner>> {
         let listen_addr = SocketAddr::new(self.address.parse()?, self.port);
-        // Check whether this inbound listens on TCP.
-        if self.handler.stream().is_ok() {
-            let listen_addr_cloned = listen_addr;
-            let handler_cloned = self.handler.clone();
-            let dispatcher_cloned = self.dispatcher.clone();
-            let nat_manager_cloned = self.nat_manager.clone();
-            runners.push(Box::pin(async move {
-                if let Err(e) = handle_tcp_listen(
-                    listen_addr_cloned,
-                    handler_cloned,
-                    dispatcher_cloned,
-                    nat_manager_cloned,
-                )
-                .await
-                {
-                    warn!("handler tcp listen failed: {}", e);
-                }
-            }));
+        if self.handler.stream().is_err() {
+            return Ok(None);
         }
-        // Check whether this inbound binds on UDP.
-        if self.handler.datagram().is_ok() {
-            let listen_addr_cloned = listen_addr;
-            let handler_cloned = self.handler.clone();
-            let dispatcher_cloned = self.dispatcher.clone();
-            let nat_manager_cloned = self.nat_manager.clone();
-            runners.push(Box::pin(async move {
-                if let Err(e) = handle_udp_listen(
-                    listen_addr_cloned,
-                    handler_cloned,
-                    dispatcher_cloned,
-                    nat_manager_cloned,
-                )
-                .await
-                {
-                    warn!("handler udp listen failed: {}", e);
-                }
-            }));
+        let handler = self.handler.clone();
+        let dispatcher = self.dispatcher.clone();
+        let nat_manager = self.nat_manager.clone();
+        Ok(Some(Box::pin(async move {
+            if let Err(e) = handle_tcp_listen(listen_addr, handler, dispatcher, nat_manager).await {
+                warn!("handler tcp listen failed: {}", e);
+            }
+        })))
+    }
+
+    /// Returns the runner bound on UDP, none if the inbound doesn't. The
+    /// runner relays the datagrams of the sessions as well.
+    pub fn listen_udp(&self) -> Result<Option<Runner>> {
+        let listen_addr = SocketAddr::new(self.address.parse()?, self.port);
+        if self.handler.datagram().is_err() {
+            return Ok(None);
         }
-        Ok(runners)
+        let handler = self.handler.clone();
+        let dispatcher = self.dispatcher.clone();
+        let nat_manager = self.nat_manager.clone();
+        Ok(Some(Box::pin(async move {
+            if let Err(e) = handle_udp_listen(listen_addr, handler, dispatcher, nat_manager).await {
+                warn!("handler udp listen failed: {}", e);
+            }
+        })))
     }
 }
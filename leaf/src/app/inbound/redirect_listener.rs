@@ -0,0 +1,397 @@
+//! Transparent proxy inbounds for traffic diverted by netfilter, with the
+//! REDIRECT target (`redirect`, TCP only) or the TPROXY target (`tproxy`, TCP
+//! and UDP).
+
+use std::io;
+use std::mem;
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4, SocketAddrV6};
+use std::os::fd::AsRawFd;
+use std::sync::Arc;
+
+use anyhow::Result;
+use socket2::{Domain, SockAddr, Socket, Type};
+use tokio::io::Interest;
+use tokio::net::{TcpStream, UdpSocket};
+use tokio::sync::mpsc::channel as tokio_channel;
+use tokio::sync::mpsc::{Receiver as TokioReceiver, Sender as TokioSender};
+use tracing::{debug, info, trace, warn, Instrument};
+
+use crate::app::dispatcher::Dispatcher;
+use crate::app::nat_manager::{NatManager, UdpPacket};
+use crate::config::Inbound;
+use crate::proxy::TcpListener;
+use crate::session::{DatagramSource, Network, Session, SocksAddr};
+use crate::Runner;
+
+// From linux/netfilter_ipv4.h and linux/netfilter_ipv6/ip6_tables.h.
+const SO_ORIGINAL_DST: libc::c_int = 80;
+const IP6T_SO_ORIGINAL_DST: libc::c_int = 80;
+
+unsafe fn getsockopt<T>(fd: libc::c_int, level: libc::c_int, name: libc::c_int) -> io::Result<T> {
+    let mut value: T = mem::zeroed();
+    let mut len = mem::size_of::<T>() as libc::socklen_t;
+    let ret = libc::getsockopt(fd, level, name, &mut value as *mut T as *mut _, &mut len);
+    if ret == -1 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(value)
+}
+
+fn setsockopt_int(
+    fd: libc::c_int,
+    level: libc::c_int,
+    name: libc::c_int,
+    value: libc::c_int,
+) -> io::Result<()> {
+    let ret = unsafe {
+        libc::setsockopt(
+            fd,
+            level,
+            name,
+            &value as *const _ as *const libc::c_void,
+            mem::size_of::<libc::c_int>() as libc::socklen_t,
+        )
+    };
+    if ret == -1 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+// Returns the destination of a connection before REDIRECT rewrote it.
+fn original_dst(stream: &TcpStream) -> io::Result<SocketAddr> {
+    let fd = stream.as_raw_fd();
+    let ipv4 = match stream.local_addr()? {
+        SocketAddr::V4(_) => true,
+        // IPv4 connections accepted on a dual-stack socket.
+        SocketAddr::V6(addr) => addr.ip().to_ipv4_mapped().is_some(),
+    };
+    if ipv4 {
+        let addr: libc::sockaddr_in = unsafe { getsockopt(fd, libc::SOL_IP, SO_ORIGINAL_DST)? };
+        Ok(SocketAddr::V4(SocketAddrV4::new(
+            Ipv4Addr::from(u32::from_be(addr.sin_addr.s_addr)),
+            u16::from_be(addr.sin_port),
+        )))
+    } else {
+        let addr: libc::sockaddr_in6 =
+            unsafe { getsockopt(fd, libc::SOL_IPV6, IP6T_SO_ORIGINAL_DST)? };
+        Ok(SocketAddr::V6(SocketAddrV6::new(
+            Ipv6Addr::from(addr.sin6_addr.s6_addr),
+            u16::from_be(addr.sin6_port),
+            addr.sin6_flowinfo,
+            addr.sin6_scope_id,
+        )))
+    }
+}
+
+// Allows binding to and accepting for non-local addresses, which needs
+// CAP_NET_ADMIN.
+fn set_transparent(socket: &Socket, addr: &SocketAddr) -> io::Result<()> {
+    match addr {
+        SocketAddr::V4(_) => {
+            setsockopt_int(socket.as_raw_fd(), libc::SOL_IP, libc::IP_TRANSPARENT, 1)
+        }
+        SocketAddr::V6(_) => setsockopt_int(
+            socket.as_raw_fd(),
+            libc::SOL_IPV6,
+            libc::IPV6_TRANSPARENT,
+            1,
+        ),
+    }
+}
+
+fn bind_tcp(listen_addr: &SocketAddr, tproxy: bool) -> io::Result<TcpListener> {
+    let socket = Socket::new(Domain::for_address(*listen_addr), Type::STREAM, None)?;
+    socket.set_reuse_address(true)?;
+    if tproxy {
+        set_transparent(&socket, listen_addr)?;
+    }
+    socket.bind(&(*listen_addr).into())?;
+    socket.listen(1024)?;
+    TcpListener::from_std(socket.into())
+}
+
+fn bind_udp(listen_addr: &SocketAddr) -> io::Result<UdpSocket> {
+    let socket = Socket::new(Domain::for_address(*listen_addr), Type::DGRAM, None)?;
+    socket.set_reuse_address(true)?;
+    set_transparent(&socket, listen_addr)?;
+    // Has the original destination of each datagram passed along with it.
+    match listen_addr {
+        SocketAddr::V4(_) => setsockopt_int(
+            socket.as_raw_fd(),
+            libc::SOL_IP,
+            libc::IP_RECVORIGDSTADDR,
+            1,
+        )?,
+        SocketAddr::V6(_) => setsockopt_int(
+            socket.as_raw_fd(),
+            libc::SOL_IPV6,
+            libc::IPV6_RECVORIGDSTADDR,
+            1,
+        )?,
+    }
+    socket.bind(&(*listen_addr).into())?;
+    socket.set_nonblocking(true)?;
+    UdpSocket::from_std(socket.into())
+}
+
+// IPv4 addresses show up mapped to IPv6 on dual-stack sockets.
+fn canonical(addr: SocketAddr) -> SocketAddr {
+    SocketAddr::new(addr.ip().to_canonical(), addr.port())
+}
+
+// Receives a datagram, returns its length, source and original destination.
+fn recv_with_original_dst(
+    fd: libc::c_int,
+    buf: &mut [u8],
+) -> io::Result<(usize, SocketAddr, SocketAddr)> {
+    unsafe {
+        let mut src: libc::sockaddr_storage = mem::zeroed();
+        let mut control = [0u8; 128];
+        let mut iov = libc::iovec {
+            iov_base: buf.as_mut_ptr() as *mut libc::c_void,
+            iov_len: buf.len(),
+        };
+        let mut msg: libc::msghdr = mem::zeroed();
+        msg.msg_name = &mut src as *mut _ as *mut libc::c_void;
+        msg.msg_namelen = mem::size_of::<libc::sockaddr_storage>() as libc::socklen_t;
+        msg.msg_iov = &mut iov;
+        msg.msg_iovlen = 1;
+        msg.msg_control = control.as_mut_ptr() as *mut libc::c_void;
+        msg.msg_controllen = control.len() as _;
+        let n = libc::recvmsg(fd, &mut msg, 0);
+        if n == -1 {
+            return Err(io::Error::last_os_error());
+        }
+        let src = SockAddr::new(src, msg.msg_namelen)
+            .as_socket()
+            .ok_or_else(|| io::Error::other("invalid source address"))?;
+
+        let mut original_dst = None;
+        let mut cmsg = libc::CMSG_FIRSTHDR(&msg);
+        while !cmsg.is_null() {
+            let len = match ((*cmsg).cmsg_level, (*cmsg).cmsg_type) {
+                (libc::SOL_IP, libc::IP_ORIGDSTADDR) => mem::size_of::<libc::sockaddr_in>(),
+                (libc::SOL_IPV6, libc::IPV6_ORIGDSTADDR) => mem::size_of::<libc::sockaddr_in6>(),
+                _ => 0,
+            };
+            if len > 0 {
+                let mut storage: libc::sockaddr_storage = mem::zeroed();
+                std::ptr::copy_nonoverlapping(
+                    libc::CMSG_DATA(cmsg),
+                    &mut storage as *mut _ as *mut u8,
+                    len,
+                );
+                original_dst = SockAddr::new(storage, len as libc::socklen_t).as_socket();
+                break;
+            }
+            cmsg = libc::CMSG_NXTHDR(&msg, cmsg);
+        }
+        let original_dst =
+            original_dst.ok_or_else(|| io::Error::other("no original destination"))?;
+        Ok((n as usize, canonical(src), canonical(original_dst)))
+    }
+}
+
+// Sends a reply from a transient socket bound to `src`, the address the
+// client sent the datagram to.
+fn send_from(src: SocketAddr, dst: SocketAddr, data: &[u8]) -> io::Result<()> {
+    let socket = Socket::new(Domain::for_address(src), Type::DGRAM, None)?;
+    socket.set_reuse_address(true)?;
+    set_transparent(&socket, &src)?;
+    socket.bind(&src.into())?;
+    socket.send_to(data, &dst.into())?;
+    Ok(())
+}
+
+async fn handle_tcp_listen(
+    listen_addr: SocketAddr,
+    tproxy: bool,
+    inbound_tag: String,
+    dispatcher: Arc<Dispatcher>,
+) -> io::Result<()> {
+    let listener = bind_tcp(&listen_addr, tproxy)?;
+    info!(
+        "listening tcp {} ({})",
+        &listen_addr,
+        if tproxy { "tproxy" } else { "redirect" }
+    );
+    loop {
+        let (stream, _) = listener.accept().await?;
+        let source = stream
+            .peer_addr()
+            .unwrap_or_else(|_| *crate::option::UNSPECIFIED_BIND_ADDR);
+        let local_addr = stream
+            .local_addr()
+            .unwrap_or_else(|_| *crate::option::UNSPECIFIED_BIND_ADDR);
+        let destination = if tproxy {
+            Ok(local_addr)
+        } else {
+            original_dst(&stream)
+        };
+        let destination = match destination {
+            // A connection made to the listener itself rather than
+            // redirected to it would loop back here.
+            Ok(addr) if !tproxy && addr == local_addr => {
+                debug!("reject connection to the listener from {}", &source);
+                continue;
+            }
+            Ok(addr) => addr,
+            Err(e) => {
+                debug!("get original destination from {} failed: {}", &source, e);
+                continue;
+            }
+        };
+        let sess = Session {
+            network: Network::Tcp,
+            source,
+            local_addr,
+            destination: SocksAddr::from(destination),
+            inbound_tag: inbound_tag.clone(),
+            ..Default::default()
+        };
+        let dispatcher = dispatcher.clone();
+        let span = sess.span();
+        tokio::spawn(
+            async move { dispatcher.dispatch_stream(sess, stream).await }.instrument(span),
+        );
+    }
+}
+
+async fn handle_udp_listen(
+    listen_addr: SocketAddr,
+    inbound_tag: String,
+    nat_manager: Arc<NatManager>,
+) -> io::Result<()> {
+    let socket = bind_udp(&listen_addr)?;
+    info!("listening udp {} (tproxy)", &listen_addr);
+
+    // Replies from the NAT manager, with the original destination as the
+    // source.
+    let (l_tx, mut l_rx): (TokioSender<UdpPacket>, TokioReceiver<UdpPacket>) =
+        tokio_channel(*crate::option::UDP_DOWNLINK_CHANNEL_SIZE);
+    tokio::spawn(async move {
+        while let Some(pkt) = l_rx.recv().await {
+            let src_addr = match &pkt.src_addr {
+                SocksAddr::Ip(addr) => *addr,
+                _ => {
+                    debug!("drop reply from non-IP address {}", &pkt.src_addr);
+                    continue;
+                }
+            };
+            let dst_addr = *pkt.dst_addr.must_ip();
+            trace!("send udp packet src={} dst={}", &src_addr, &dst_addr);
+            if let Err(e) = send_from(src_addr, dst_addr, &pkt.data) {
+                debug!(
+                    "send reply from {} to {} failed: {}",
+                    &src_addr, &dst_addr, e
+                );
+            }
+        }
+    });
+
+    let mut buf = vec![0u8; *crate::option::DATAGRAM_BUFFER_SIZE * 1024];
+    loop {
+        let (n, src_addr, original_dst) = match socket
+            .async_io(Interest::READABLE, || {
+                recv_with_original_dst(socket.as_raw_fd(), &mut buf)
+            })
+            .await
+        {
+            Ok(v) => v,
+            Err(e) => {
+                debug!("receive datagram failed: {}", e);
+                continue;
+            }
+        };
+        trace!(
+            "received udp packet src={} dst={} len={}",
+            &src_addr,
+            &original_dst,
+            n
+        );
+        // Sessions are kept per destination, so that the replies of each
+        // come from where the client expects.
+        let dgram_src = DatagramSource {
+            original_dst: Some(original_dst),
+            ..DatagramSource::new(src_addr, None)
+        };
+        let pkt = UdpPacket::new(
+            buf[..n].to_vec(),
+            SocksAddr::from(src_addr),
+            SocksAddr::from(original_dst),
+        );
+        nat_manager
+            .send(None, &dgram_src, &inbound_tag, &l_tx, pkt)
+            .await;
+    }
+}
+
+pub struct RedirectInboundListener {
+    pub inbound: Inbound,
+    // The TPROXY target keeps the destination as the local address, the
+    // REDIRECT target needs it to be looked up.
+    pub tproxy: bool,
+    pub dispatcher: Arc<Dispatcher>,
+    pub nat_manager: Arc<NatManager>,
+}
+
+impl RedirectInboundListener {
+    pub fn listen(&self) -> Result<Vec<Runner>> {
+        let mut runners: Vec<Runner> = Vec::new();
+        let listen_addr = SocketAddr::new(self.inbound.address.parse()?, self.inbound.port as u16);
+        let inbound_tag = self.inbound.tag.clone();
+        let tproxy = self.tproxy;
+        let dispatcher = self.dispatcher.clone();
+        let tcp_inbound_tag = inbound_tag.clone();
+        runners.push(Box::pin(async move {
+            if let Err(e) =
+                handle_tcp_listen(listen_addr, tproxy, tcp_inbound_tag, dispatcher).await
+            {
+                warn!("handler tcp listen failed: {}", e);
+            }
+        }));
+        // REDIRECT doesn't work for UDP.
+        if tproxy {
+            let nat_manager = self.nat_manager.clone();
+            runners.push(Box::pin(async move {
+                if let Err(e) = handle_udp_listen(listen_addr, inbound_tag, nat_manager).await {
+                    warn!("handler udp listen failed: {}", e);
+                }
+            }));
+        }
+        Ok(runners)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_recv_with_original_dst() {
+        // Without TPROXY the original destination is the local address, which
+        // needs no privilege to see.
+        let socket = Socket::new(Domain::IPV4, Type::DGRAM, None).unwrap();
+        setsockopt_int(
+            socket.as_raw_fd(),
+            libc::SOL_IP,
+            libc::IP_RECVORIGDSTADDR,
+            1,
+        )
+        .unwrap();
+        socket
+            .bind(&"127.0.0.1:0".parse::<SocketAddr>().unwrap().into())
+            .unwrap();
+        let local_addr = socket.local_addr().unwrap().as_socket().unwrap();
+
+        let client = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        client.send_to(b"hello", local_addr).unwrap();
+
+        let mut buf = [0u8; 16];
+        let (n, src, original_dst) = recv_with_original_dst(socket.as_raw_fd(), &mut buf).unwrap();
+        assert_eq!(&buf[..n], b"hello");
+        assert_eq!(src, client.local_addr().unwrap());
+        assert_eq!(original_dst, local_addr);
+    }
+}
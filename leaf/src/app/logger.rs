@@ -1,8 +1,9 @@
-use std::fs::OpenOptions;
 use std::path::Path;
 use std::sync::RwLock;
+use std::time::Duration;
 
 use anyhow::Result;
+use tokio::sync::broadcast;
 use tracing::field::Visit;
 use tracing_appender::non_blocking::WorkerGuard;
 use tracing_subscriber::{
@@ -15,6 +16,7 @@ use tracing_subscriber::{
     reload::Handle,
 };
 
+use crate::common::rotating_file::RotatingFile;
 use crate::config;
 
 type FilterHandle = Handle<LevelFilter, Registry>;
@@ -30,6 +32,75 @@ struct LogEventFormat {
     mode: LogFormatMode,
 }
 
+struct MessageVisitor {
+    message: Option<String>,
+}
+
+impl Visit for MessageVisitor {
+    fn record_debug(&mut self, field: &tracing::field::Field, value: &dyn std::fmt::Debug) {
+        if field.name() == "message" {
+            self.message = Some(format!("{value:?}"));
+        }
+    }
+
+    fn record_str(&mut self, field: &tracing::field::Field, value: &str) {
+        if field.name() == "message" {
+            self.message = Some(value.to_string());
+        }
+    }
+}
+
+impl MessageVisitor {
+    fn message(event: &tracing::Event<'_>) -> Option<String> {
+        let mut visitor = MessageVisitor { message: None };
+        event.record(&mut visitor);
+        let mut message = visitor.message?;
+        if message.starts_with('\"') && message.ends_with('\"') && message.len() >= 2 {
+            message = message[1..message.len() - 1].to_string();
+        }
+        Some(message)
+    }
+}
+
+/// A log event for the subscribers of the API.
+#[derive(Clone, Debug)]
+pub struct LogRecord {
+    pub level: tracing::Level,
+    pub message: String,
+}
+
+lazy_static::lazy_static! {
+    static ref LOG_TX: broadcast::Sender<LogRecord> = broadcast::channel(256).0;
+}
+
+/// Receives the log events from now on. Slow receivers miss events rather
+/// than hold up the logging.
+pub fn subscribe() -> broadcast::Receiver<LogRecord> {
+    LOG_TX.subscribe()
+}
+
+// Sends the events to the subscribers, it costs nothing while there are
+// none.
+struct BroadcastLayer;
+
+impl<S: tracing::Subscriber> Layer<S> for BroadcastLayer {
+    fn on_event(
+        &self,
+        event: &tracing::Event<'_>,
+        _ctx: tracing_subscriber::layer::Context<'_, S>,
+    ) {
+        if LOG_TX.receiver_count() == 0 {
+            return;
+        }
+        if let Some(message) = MessageVisitor::message(event) {
+            let _ = LOG_TX.send(LogRecord {
+                level: *event.metadata().level(),
+                message,
+            });
+        }
+    }
+}
+
 impl<S, N> tracing_subscriber::fmt::format::FormatEvent<S, N> for LogEventFormat
 where
     S: tracing::Subscriber + for<'a> tracing_subscriber::registry::LookupSpan<'a>,
@@ -46,35 +117,7 @@ where
                 tracing_subscriber::fmt::format::Format::default().format_event(ctx, writer, event)
             }
             LogFormatMode::Compact => {
-                struct MessageVisitor {
-                    message: Option<String>,
-                }
-
-                impl Visit for MessageVisitor {
-                    fn record_debug(
-                        &mut self,
-                        field: &tracing::field::Field,
-                        value: &dyn std::fmt::Debug,
-                    ) {
-                        if field.name() == "message" {
-                            self.message = Some(format!("{value:?}"));
-                        }
-                    }
-
-                    fn record_str(&mut self, field: &tracing::field::Field, value: &str) {
-                        if field.name() == "message" {
-                            self.message = Some(value.to_string());
-                        }
-                    }
-                }
-
-                let mut visitor = MessageVisitor { message: None };
-                event.record(&mut visitor);
-
-                if let Some(mut message) = visitor.message {
-                    if message.starts_with('\"') && message.ends_with('\"') && message.len() >= 2 {
-                        message = message[1..message.len() - 1].to_string();
-                    }
+                if let Some(message) = MessageVisitor::message(event) {
                     std::fmt::Write::write_str(&mut writer, &message)?;
                 } else {
                     std::fmt::Write::write_str(&mut writer, "")?;
@@ -172,7 +215,14 @@ fn get_writer(config: &config::Log) -> Result<(WriterLayer, WorkerGuard)> {
         }
         config::log::Output::FILE => {
             let p = Path::new(&config.output_file);
-            let writer = OpenOptions::new().append(true).create(true).open(p)?;
+            // The writer runs on the worker of the non-blocking appender
+            // only, so the file is rotated between lines.
+            let writer = RotatingFile::open(
+                p,
+                config.max_size_mb as u64 * 1024 * 1024,
+                config.max_backups as usize,
+                Duration::from_secs(config.max_age_days as u64 * 24 * 3600),
+            )?;
             let (writer, writer_guard) = tracing_appender::non_blocking(writer);
             let writer = fmt::Layer::default()
                 .with_ansi(false)
@@ -200,9 +250,11 @@ pub fn setup_logger(config: &config::Log) -> Result<()> {
         let (filter, filter_handle) = reload::Layer::new(filter);
         let (writer, writer_handle) = reload::Layer::new(writer);
         let leaf_filter = filter_fn(|metadata| metadata.target().starts_with("leaf"));
+        let broadcast_filter = filter_fn(|metadata| metadata.target().starts_with("leaf"));
         tracing_subscriber::registry()
             .with(filter)
             .with(writer.with_filter(leaf_filter))
+            .with(BroadcastLayer.with_filter(broadcast_filter))
             .init();
         *h = Some(HandleController::new(
             filter_handle,
@@ -0,0 +1,229 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use axum::{extract::State, http::header, response::IntoResponse, routing::get, Router};
+use tracing::info;
+
+use crate::app::{
+    nat_manager::NatManager,
+    stat_manager::{StatManager, LATENCY_BUCKETS},
+    SyncDnsClient, SyncStatManager,
+};
+
+// Escapes a label value of the text format.
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+fn write_header(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+}
+
+fn render_stats(out: &mut String, sm: &StatManager) {
+    let active = sm
+        .counters
+        .values()
+        .filter(|x| !x.send_completed() || !x.recv_completed())
+        .count();
+    write_header(
+        out,
+        "leaf_active_sessions",
+        "gauge",
+        "Sessions relaying data.",
+    );
+    let _ = writeln!(out, "leaf_active_sessions {}", active);
+
+    write_header(
+        out,
+        "leaf_outbound_bytes_total",
+        "counter",
+        "Bytes relayed through an outbound.",
+    );
+    let traffic: BTreeMap<_, _> = sm.outbound_traffic.iter().collect();
+    for (tag, t) in traffic {
+        for (direction, n) in [("up", t.up()), ("down", t.down())] {
+            let _ = writeln!(
+                out,
+                "leaf_outbound_bytes_total{{tag=\"{}\",direction=\"{}\"}} {}",
+                escape(tag),
+                direction,
+                n
+            );
+        }
+    }
+
+    write_header(
+        out,
+        "leaf_outbound_connect_errors_total",
+        "counter",
+        "Sessions which failed to connect through an outbound.",
+    );
+    let errors: BTreeMap<_, _> = sm.connect_errors.iter().collect();
+    for (tag, n) in errors {
+        let _ = writeln!(
+            out,
+            "leaf_outbound_connect_errors_total{{tag=\"{}\"}} {}",
+            escape(tag),
+            n
+        );
+    }
+
+    write_header(
+        out,
+        "leaf_outbound_health_check_seconds",
+        "histogram",
+        "Latencies of the successful health checks of an outbound.",
+    );
+    let mut latencies = sm.health_check_latencies();
+    latencies.sort_by_key(|x| (x.0.clone(), x.1.to_string()));
+    for (tag, network, h) in latencies {
+        let labels = format!("tag=\"{}\",network=\"{}\"", escape(&tag), network);
+        let mut cumulative = 0;
+        for (bound, n) in LATENCY_BUCKETS.iter().zip(h.buckets.iter()) {
+            cumulative += n;
+            let _ = writeln!(
+                out,
+                "leaf_outbound_health_check_seconds_bucket{{{},le=\"{}\"}} {}",
+                labels, bound, cumulative
+            );
+        }
+        let _ = writeln!(
+            out,
+            "leaf_outbound_health_check_seconds_bucket{{{},le=\"+Inf\"}} {}",
+            labels, h.count
+        );
+        let _ = writeln!(
+            out,
+            "leaf_outbound_health_check_seconds_sum{{{}}} {}",
+            labels,
+            h.sum.as_secs_f64()
+        );
+        let _ = writeln!(
+            out,
+            "leaf_outbound_health_check_seconds_count{{{}}} {}",
+            labels, h.count
+        );
+    }
+}
+
+/// Serves the metrics in the Prometheus text format. The metrics are only
+/// read when scraped.
+pub struct MetricsServer {
+    stat_manager: SyncStatManager,
+    dns_client: SyncDnsClient,
+    nat_manager: Arc<NatManager>,
+}
+
+impl MetricsServer {
+    pub fn new(
+        stat_manager: SyncStatManager,
+        dns_client: SyncDnsClient,
+        nat_manager: Arc<NatManager>,
+    ) -> Self {
+        Self {
+            stat_manager,
+            dns_client,
+            nat_manager,
+        }
+    }
+
+    async fn render(&self) -> String {
+        let mut out = String::new();
+        render_stats(&mut out, &*self.stat_manager.read().await);
+
+        let (hits, misses) = self.dns_client.read().await.cache_stats();
+        write_header(
+            &mut out,
+            "leaf_dns_cache_hits_total",
+            "counter",
+            "Lookups answered from the DNS cache.",
+        );
+        let _ = writeln!(out, "leaf_dns_cache_hits_total {}", hits);
+        write_header(
+            &mut out,
+            "leaf_dns_cache_misses_total",
+            "counter",
+            "Lookups not answered from the DNS cache.",
+        );
+        let _ = writeln!(out, "leaf_dns_cache_misses_total {}", misses);
+
+        write_header(
+            &mut out,
+            "leaf_nat_sessions",
+            "gauge",
+            "Entries of the UDP NAT table.",
+        );
+        let _ = writeln!(out, "leaf_nat_sessions {}", self.nat_manager.size().await);
+        out
+    }
+
+    pub fn serve(self, listen_addr: SocketAddr) -> crate::Runner {
+        async fn metrics(State(server): State<Arc<MetricsServer>>) -> impl IntoResponse {
+            (
+                [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+                server.render().await,
+            )
+        }
+
+        let app = Router::new()
+            .route("/metrics", get(metrics))
+            .with_state(Arc::new(self));
+
+        info!("metrics server listening tcp {}", &listen_addr);
+
+        Box::pin(async move {
+            let listener = tokio::net::TcpListener::bind(listen_addr).await.unwrap();
+            axum::serve(listener, app).await.unwrap();
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::app::stat_manager;
+    use crate::session::{Network, Session};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_render_stats() {
+        use tokio::io::AsyncWriteExt;
+
+        let mut sm = StatManager::new();
+        let sess = Session {
+            outbound_tag: "a\"b".to_string(),
+            ..Default::default()
+        };
+        let (client, _server) = tokio::io::duplex(1024);
+        let mut stream = sm.stat_stream(Box::new(client), sess);
+        stream.write_all(&[0; 7]).await.unwrap();
+        sm.count_connect_error("c");
+        let tag = "test_render_stats";
+        stat_manager::observe_health_check(tag, Network::Tcp, Duration::from_millis(80));
+
+        let mut out = String::new();
+        render_stats(&mut out, &sm);
+        let lines: Vec<&str> = out.lines().collect();
+        for line in [
+            "# TYPE leaf_active_sessions gauge",
+            "leaf_active_sessions 1",
+            "leaf_outbound_bytes_total{tag=\"a\\\"b\",direction=\"up\"} 7",
+            "leaf_outbound_bytes_total{tag=\"a\\\"b\",direction=\"down\"} 0",
+            "leaf_outbound_connect_errors_total{tag=\"c\"} 1",
+            "leaf_outbound_health_check_seconds_bucket{tag=\"test_render_stats\",network=\"tcp\",le=\"0.05\"} 0",
+            "leaf_outbound_health_check_seconds_bucket{tag=\"test_render_stats\",network=\"tcp\",le=\"0.1\"} 1",
+            "leaf_outbound_health_check_seconds_bucket{tag=\"test_render_stats\",network=\"tcp\",le=\"+Inf\"} 1",
+            "leaf_outbound_health_check_seconds_count{tag=\"test_render_stats\",network=\"tcp\"} 1",
+        ] {
+            assert!(lines.contains(&line), "missing {}", line);
+        }
+    }
+}
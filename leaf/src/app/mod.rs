@@ -2,19 +2,25 @@ use std::sync::Arc;
 
 use tokio::sync::RwLock;
 
+pub mod access_log;
 pub mod dispatcher;
 pub mod dns;
+pub mod geoip_update;
 pub mod healthcheck;
 pub mod inbound;
 pub mod logger;
 pub mod nat_manager;
 pub mod outbound;
 pub mod router;
+pub mod rule_provider;
 pub mod stat_manager;
 
 #[cfg(feature = "api")]
 pub mod api;
 
+#[cfg(feature = "metrics")]
+pub mod metrics;
+
 pub mod fake_dns;
 
 pub mod dns_client {
@@ -1,4 +1,5 @@
 use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 
@@ -10,6 +11,7 @@ use tokio::sync::{
 use tracing::{debug, error, trace, Instrument};
 
 use crate::app::dispatcher::Dispatcher;
+use crate::common::sniff;
 use crate::option;
 use crate::session::{DatagramSource, Network, Session, SocksAddr};
 
@@ -48,6 +50,8 @@ pub struct NatManager {
     sessions: Arc<Mutex<SessionMap>>,
     dispatcher: Arc<Dispatcher>,
     timeout_check_task: Mutex<Option<BoxFuture<'static, ()>>>,
+    // No sessions are added while shutting down.
+    draining: AtomicBool,
 }
 
 impl NatManager {
@@ -99,9 +103,21 @@ impl NatManager {
             sessions,
             dispatcher,
             timeout_check_task: Mutex::new(Some(timeout_check_task)),
+            draining: AtomicBool::new(false),
         }
     }
 
+    /// Drops the packets of new sessions from now on, the existing sessions
+    /// keep flowing until they time out.
+    pub fn drain(&self) {
+        self.draining.store(true, Ordering::Relaxed);
+    }
+
+    /// Returns the number of active sessions.
+    pub async fn size(&self) -> usize {
+        self.sessions.lock().await.len()
+    }
+
     fn _send(&self, guard: &mut MutexGuard<'_, SessionMap>, key: &DatagramSource, pkt: UdpPacket) {
         if let Some(sess) = guard.get_mut(key) {
             if let Err(err) = sess.0.try_send(pkt) {
@@ -128,6 +144,11 @@ impl NatManager {
             return;
         }
 
+        if self.draining.load(Ordering::Relaxed) {
+            trace!("dropped packet of new session while shutting down {}", pkt);
+            return;
+        }
+
         let mut sess = sess.cloned().unwrap_or_else(|| Session {
             network: Network::Udp,
             source: dgram_src.address,
@@ -135,6 +156,7 @@ impl NatManager {
             destination: pkt.dst_addr.clone(),
             inbound_tag: inbound_tag.to_string(),
             process_name: dgram_src.process_name.clone(),
+            user: dgram_src.user.clone(),
             ..Default::default()
         });
 
@@ -149,6 +171,7 @@ impl NatManager {
         // Always update destination to the packet's destination, because the session passed
         // from inbound listener might have a default (empty) destination.
         sess.destination = pkt.dst_addr.clone();
+        sess.sniffed_protocol = Some(sniff::datagram_protocol(&pkt.data));
 
         self.add_session(sess, dgram_src.clone(), client_ch_tx.clone(), &mut guard)
             .await;
@@ -227,9 +250,15 @@ impl NatManager {
                             }
                             Ok((n, addr)) => {
                                 trace!("outbound received udp packet src={} len={}", &addr, n);
+                                // Replies to transparently proxied datagrams
+                                // must come from the original destination.
+                                let src_addr = match raddr_downlink.original_dst {
+                                    Some(original_dst) => SocksAddr::from(original_dst),
+                                    None => addr.clone(),
+                                };
                                 let pkt = UdpPacket::new(
                                     buf[..n].to_vec(),
-                                    addr.clone(),
+                                    src_addr,
                                     SocksAddr::from(raddr_downlink.address),
                                 );
                                 if let Err(err) = client_ch_tx.send(pkt).await {
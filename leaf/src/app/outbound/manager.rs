@@ -1,5 +1,5 @@
 use std::{
-    collections::{hash_map, HashMap},
+    collections::{hash_map, HashMap, HashSet},
     convert::From,
     sync::Arc,
 };
@@ -24,6 +24,8 @@ use crate::proxy::r#static;
 use crate::proxy::select;
 #[cfg(feature = "outbound-tryall")]
 use crate::proxy::tryall;
+#[cfg(feature = "outbound-urltest")]
+use crate::proxy::urltest;
 
 #[cfg(feature = "outbound-amux")]
 use crate::proxy::amux;
@@ -31,6 +33,16 @@ use crate::proxy::amux;
 use crate::proxy::direct;
 #[cfg(feature = "outbound-drop")]
 use crate::proxy::drop;
+#[cfg(feature = "outbound-grpc")]
+use crate::proxy::grpc;
+#[cfg(feature = "outbound-h2")]
+use crate::proxy::h2;
+#[cfg(feature = "outbound-http")]
+use crate::proxy::http;
+#[cfg(feature = "outbound-httpupgrade")]
+use crate::proxy::httpupgrade;
+#[cfg(feature = "outbound-hysteria2")]
+use crate::proxy::hysteria2;
 #[cfg(feature = "outbound-obfs")]
 use crate::proxy::obfs;
 #[cfg(feature = "outbound-quic")]
@@ -41,16 +53,24 @@ use crate::proxy::reality;
 use crate::proxy::redirect;
 #[cfg(feature = "outbound-shadowsocks")]
 use crate::proxy::shadowsocks;
+#[cfg(feature = "outbound-shadowtls")]
+use crate::proxy::shadowtls;
 #[cfg(feature = "outbound-socks")]
 use crate::proxy::socks;
+#[cfg(feature = "outbound-ssh")]
+use crate::proxy::ssh;
 #[cfg(feature = "outbound-tls")]
 use crate::proxy::tls;
 #[cfg(feature = "outbound-trojan")]
 use crate::proxy::trojan;
+#[cfg(feature = "outbound-tuic")]
+use crate::proxy::tuic;
 #[cfg(feature = "outbound-vless")]
 use crate::proxy::vless;
 #[cfg(feature = "outbound-vmess")]
 use crate::proxy::vmess;
+#[cfg(feature = "outbound-wireguard")]
+use crate::proxy::wireguard;
 #[cfg(feature = "outbound-ws")]
 use crate::proxy::ws;
 
@@ -69,8 +89,12 @@ pub struct OutboundManager {
     external_handlers: super::plugin::ExternalHandlers,
     #[cfg(feature = "outbound-select")]
     selectors: Arc<super::Selectors>,
+    #[cfg(feature = "outbound-urltest")]
+    url_tests: super::UrlTests,
     default_handler: Option<String>,
-    abort_handles: Vec<AbortHandle>,
+    // The tasks spawned by the handlers, by the tag of the handler.
+    abort_handles: Vec<(String, AbortHandle)>,
+    outbounds: Vec<Outbound>,
 }
 
 struct HandlerCacheEntry<'a> {
@@ -87,8 +111,9 @@ impl OutboundManager {
         dns_client: SyncDnsClient,
         handlers: &mut HashMap<String, AnyOutboundHandler>,
         #[cfg(feature = "plugin")] external_handlers: &mut super::plugin::ExternalHandlers,
+        #[cfg(feature = "outbound-urltest")] url_tests: &mut super::UrlTests,
         default_handler: &mut Option<String>,
-        abort_handles: &mut Vec<AbortHandle>,
+        abort_handles: &mut Vec<(String, AbortHandle)>,
     ) -> Result<()> {
         // If there are multiple outbounds with the same setting, we would want
         // a shared one to reduce memory usage. This vector is used as a cache for
@@ -127,6 +152,7 @@ impl OutboundManager {
                     .tag(tag.clone())
                     .stream_handler(Arc::new(drop::StreamHandler))
                     .datagram_handler(Arc::new(drop::DatagramHandler))
+                    .is_drop(true)
                     .build(),
                 #[cfg(feature = "outbound-redirect")]
                 "redirect" => {
@@ -171,24 +197,64 @@ impl OutboundManager {
                         .datagram_handler(datagram)
                         .build()
                 }
+                #[cfg(feature = "outbound-http")]
+                "http" => {
+                    let settings =
+                        config::HttpOutboundSettings::parse_from_bytes(&outbound.settings)
+                            .map_err(|e| anyhow!("invalid [{}] outbound settings: {}", &tag, e))?;
+                    let stream = Arc::new(http::outbound::StreamHandler {
+                        address: settings.address,
+                        port: settings.port as u16,
+                        username: settings.username,
+                        password: settings.password,
+                        headers: settings.headers,
+                    });
+                    HandlerBuilder::default()
+                        .tag(tag.clone())
+                        .stream_handler(stream)
+                        .build()
+                }
                 #[cfg(feature = "outbound-shadowsocks")]
                 "shadowsocks" => {
                     let settings =
                         config::ShadowsocksOutboundSettings::parse_from_bytes(&outbound.settings)
                             .map_err(|e| anyhow!("invalid [{}] outbound settings: {}", &tag, e))?;
-                    let stream = Arc::new(shadowsocks::outbound::StreamHandler::new(
+                    let mut stream = shadowsocks::outbound::StreamHandler::new(
                         settings.address.clone(),
                         settings.port as u16,
                         settings.method.clone(),
                         settings.password.clone(),
                         settings.prefix.as_ref().cloned(),
-                    )?);
-                    let datagram = Arc::new(shadowsocks::outbound::DatagramHandler {
-                        address: settings.address,
+                    )?;
+                    let mut datagram = shadowsocks::outbound::DatagramHandler {
+                        address: settings.address.clone(),
                         port: settings.port as u16,
-                        cipher: settings.method,
-                        password: settings.password,
-                    });
+                        cipher: settings.method.clone(),
+                        password: settings.password.clone(),
+                        udp_over_tcp: settings.udp_over_tcp,
+                    };
+                    // With a SIP003 plugin, only TCP goes through the plugin,
+                    // which includes UDP over TCP.
+                    if let Some(plugin) = settings.plugin.as_ref().filter(|x| !x.is_empty()) {
+                        let (port, abort_handle) = shadowsocks::outbound::plugin::start(
+                            plugin,
+                            settings.plugin_opts.as_deref(),
+                            &settings.address,
+                            settings.port as u16,
+                        )
+                        .map_err(|e| {
+                            anyhow!("start plugin {} for [{}] failed: {}", plugin, &tag, e)
+                        })?;
+                        abort_handles.push((tag.clone(), abort_handle));
+                        stream.address = "127.0.0.1".to_string();
+                        stream.port = port;
+                        if datagram.udp_over_tcp {
+                            datagram.address = "127.0.0.1".to_string();
+                            datagram.port = port;
+                        }
+                    }
+                    let stream = Arc::new(stream);
+                    let datagram = Arc::new(datagram);
                     HandlerBuilder::default()
                         .tag(tag.clone())
                         .stream_handler(stream)
@@ -270,10 +336,16 @@ impl OutboundManager {
                     let settings =
                         config::VlessOutboundSettings::parse_from_bytes(&outbound.settings)
                             .map_err(|e| anyhow!("invalid [{}] outbound settings: {}", &tag, e))?;
+                    let flow = if settings.flow.is_empty() {
+                        None
+                    } else {
+                        Some(settings.flow.clone())
+                    };
                     let stream = Arc::new(vless::outbound::StreamHandler {
                         address: settings.address.clone(),
                         port: settings.port as u16,
                         uuid: settings.uuid.clone(),
+                        flow,
                     });
                     let datagram = Arc::new(vless::outbound::DatagramHandler {
                         address: settings.address.clone(),
@@ -301,6 +373,106 @@ impl OutboundManager {
                         .stream_handler(stream)
                         .build()
                 }
+                #[cfg(feature = "outbound-h2")]
+                "h2" => {
+                    let settings = config::H2OutboundSettings::parse_from_bytes(&outbound.settings)
+                        .map_err(|e| anyhow!("invalid [{}] outbound settings: {}", &tag, e))?;
+                    let tls_settings = settings.tls.get_or_default();
+                    let server_name = if tls_settings.server_name.is_empty() {
+                        settings.address.clone()
+                    } else {
+                        tls_settings.server_name.clone()
+                    };
+                    let certificate = if tls_settings.certificate.is_empty() {
+                        None
+                    } else {
+                        Some(tls_settings.certificate.clone())
+                    };
+                    let certificate_key = if tls_settings.certificate_key.is_empty() {
+                        None
+                    } else {
+                        Some(tls_settings.certificate_key.clone())
+                    };
+                    let client_certificate = if tls_settings.client_certificate.is_empty() {
+                        None
+                    } else {
+                        Some(tls_settings.client_certificate.clone())
+                    };
+                    let client_certificate_key = if tls_settings.client_certificate_key.is_empty() {
+                        None
+                    } else {
+                        Some(tls_settings.client_certificate_key.clone())
+                    };
+                    let ech_config_list = if tls_settings.ech_config_list.is_empty() {
+                        None
+                    } else {
+                        Some(tls_settings.ech_config_list.clone())
+                    };
+                    let certificate_fingerprint = if tls_settings.certificate_fingerprint.is_empty()
+                    {
+                        None
+                    } else {
+                        Some(tls_settings.certificate_fingerprint.clone())
+                    };
+                    // The connection speaks HTTP/2 whatever the alpn setting.
+                    let tls_handler = tls::outbound::StreamHandler::new(
+                        server_name,
+                        vec!["h2".to_string()],
+                        certificate,
+                        certificate_key,
+                        client_certificate,
+                        client_certificate_key,
+                        tls_settings.insecure,
+                        tls_settings.allow_name_mismatch,
+                        certificate_fingerprint,
+                        tls_settings.certificate_pins.clone(),
+                        tls_settings.pin_only,
+                        tls_settings.ech,
+                        tls_settings.ech_disable_dns_lookup,
+                        tls_settings.ech_required,
+                        ech_config_list,
+                        dns_client.clone(),
+                    )?;
+                    let username = if settings.username.is_empty() {
+                        None
+                    } else {
+                        Some(settings.username.clone())
+                    };
+                    let password = if settings.password.is_empty() {
+                        None
+                    } else {
+                        Some(settings.password.clone())
+                    };
+                    let stream = Arc::new(h2::outbound::StreamHandler::new(
+                        settings.address.clone(),
+                        settings.port as u16,
+                        username,
+                        password,
+                        settings.padding,
+                        tls_handler,
+                        dns_client.clone(),
+                    ));
+                    HandlerBuilder::default()
+                        .tag(tag.clone())
+                        .stream_handler(stream)
+                        .build()
+                }
+                #[cfg(feature = "outbound-shadowtls")]
+                "shadowtls" => {
+                    let settings =
+                        config::ShadowTlsOutboundSettings::parse_from_bytes(&outbound.settings)
+                            .map_err(|e| anyhow!("invalid [{}] outbound settings: {}", &tag, e))?;
+                    let stream = Arc::new(shadowtls::outbound::StreamHandler {
+                        address: settings.address.clone(),
+                        port: settings.port as u16,
+                        password: settings.password.clone(),
+                        handshake_server: settings.handshake_server.clone(),
+                    });
+                    HandlerBuilder::default()
+                        .tag(tag.clone())
+                        .stream_handler(stream)
+                        .build()
+                }
                 #[cfg(feature = "outbound-tls")]
                 "tls" => {
                     let settings =
@@ -316,19 +488,41 @@ impl OutboundManager {
                     } else {
                         Some(settings.certificate_key.clone())
                     };
+                    let client_certificate = if settings.client_certificate.is_empty() {
+                        None
+                    } else {
+                        Some(settings.client_certificate.clone())
+                    };
+                    let client_certificate_key = if settings.client_certificate_key.is_empty() {
+                        None
+                    } else {
+                        Some(settings.client_certificate_key.clone())
+                    };
                     let ech_config_list = if settings.ech_config_list.is_empty() {
                         None
                     } else {
                         Some(settings.ech_config_list.clone())
                     };
+                    let certificate_fingerprint = if settings.certificate_fingerprint.is_empty() {
+                        None
+                    } else {
+                        Some(settings.certificate_fingerprint.clone())
+                    };
                     let stream = Arc::new(tls::outbound::StreamHandler::new(
                         settings.server_name.clone(),
                         settings.alpn.clone(),
                         certificate,
                         certificate_key,
+                        client_certificate,
+                        client_certificate_key,
                         settings.insecure,
+                        settings.allow_name_mismatch,
+                        certificate_fingerprint,
+                        settings.certificate_pins.clone(),
+                        settings.pin_only,
                         settings.ech,
                         settings.ech_disable_dns_lookup,
+                        settings.ech_required,
                         ech_config_list,
                         dns_client.clone(),
                     )?);
@@ -345,6 +539,22 @@ impl OutboundManager {
                     let stream = Arc::new(ws::outbound::StreamHandler {
                         path: settings.path.clone(),
                         headers: settings.headers.clone(),
+                        early_data_size: settings.early_data_size as usize,
+                        early_data_header_name: settings.early_data_header_name.clone(),
+                    });
+                    HandlerBuilder::default()
+                        .tag(tag.clone())
+                        .stream_handler(stream)
+                        .build()
+                }
+                #[cfg(feature = "outbound-httpupgrade")]
+                "httpupgrade" => {
+                    let settings =
+                        config::HttpUpgradeOutboundSettings::parse_from_bytes(&outbound.settings)
+                            .map_err(|e| anyhow!("invalid [{}] outbound settings: {}", &tag, e))?;
+                    let stream = Arc::new(httpupgrade::outbound::StreamHandler {
+                        path: settings.path.clone(),
+                        headers: settings.headers.clone(),
                     });
                     HandlerBuilder::default()
                         .tag(tag.clone())
@@ -371,18 +581,186 @@ impl OutboundManager {
                     } else {
                         Some(settings.certificate_key.clone())
                     };
+                    let congestion_controller = if settings.congestion_controller.is_empty() {
+                        None
+                    } else {
+                        Some(settings.congestion_controller.clone())
+                    };
+                    let max_sessions_per_connection = if settings.max_sessions_per_connection == 0 {
+                        None
+                    } else {
+                        Some(settings.max_sessions_per_connection)
+                    };
+                    let certificate_fingerprint = if settings.certificate_fingerprint.is_empty() {
+                        None
+                    } else {
+                        Some(settings.certificate_fingerprint.clone())
+                    };
                     let stream = Arc::new(quic::outbound::StreamHandler::new(
+                        tag.clone(),
                         settings.address.clone(),
                         settings.port as u16,
                         server_name,
                         settings.alpn.clone(),
                         certificate,
                         certificate_key,
+                        congestion_controller,
+                        max_sessions_per_connection,
+                        settings.udp_over_stream,
+                        certificate_fingerprint,
+                        settings.certificate_pins.clone(),
+                        settings.pin_only,
+                        settings.allow_name_mismatch,
+                        settings.zero_rtt,
+                        settings.reconnect_on_network_change,
                         dns_client.clone(),
-                    ));
+                    )?);
                     HandlerBuilder::default()
                         .tag(tag.clone())
-                        .stream_handler(stream)
+                        .stream_handler(stream.clone())
+                        .datagram_handler(stream)
+                        .build()
+                }
+                #[cfg(feature = "outbound-hysteria2")]
+                "hysteria2" => {
+                    let settings =
+                        config::Hysteria2OutboundSettings::parse_from_bytes(&outbound.settings)
+                            .map_err(|e| anyhow!("invalid [{}] outbound settings: {}", &tag, e))?;
+                    let server_name = if settings.sni.is_empty() {
+                        None
+                    } else {
+                        Some(settings.sni.clone())
+                    };
+                    let stream = Arc::new(hysteria2::outbound::Handler::new(
+                        settings.address.clone(),
+                        settings.port as u16,
+                        settings.password.clone(),
+                        settings.up_mbps,
+                        settings.down_mbps,
+                        server_name,
+                        settings.insecure,
+                        dns_client.clone(),
+                    )?);
+                    HandlerBuilder::default()
+                        .tag(tag.clone())
+                        .stream_handler(stream.clone())
+                        .datagram_handler(stream)
+                        .build()
+                }
+                #[cfg(feature = "outbound-tuic")]
+                "tuic" => {
+                    let settings =
+                        config::TuicOutboundSettings::parse_from_bytes(&outbound.settings)
+                            .map_err(|e| anyhow!("invalid [{}] outbound settings: {}", &tag, e))?;
+                    let server_name = if settings.sni.is_empty() {
+                        None
+                    } else {
+                        Some(settings.sni.clone())
+                    };
+                    let udp_relay_mode = if settings.udp_relay_mode.is_empty() {
+                        None
+                    } else {
+                        Some(settings.udp_relay_mode.as_str())
+                    };
+                    let stream = Arc::new(tuic::outbound::Handler::new(
+                        settings.address.clone(),
+                        settings.port as u16,
+                        &settings.uuid,
+                        settings.password.clone(),
+                        server_name,
+                        settings.alpn.clone(),
+                        settings.insecure,
+                        udp_relay_mode,
+                        dns_client.clone(),
+                    )?);
+                    HandlerBuilder::default()
+                        .tag(tag.clone())
+                        .stream_handler(stream.clone())
+                        .datagram_handler(stream)
+                        .build()
+                }
+                #[cfg(feature = "outbound-wireguard")]
+                "wireguard" => {
+                    let settings =
+                        config::WireGuardOutboundSettings::parse_from_bytes(&outbound.settings)
+                            .map_err(|e| anyhow!("invalid [{}] outbound settings: {}", &tag, e))?;
+                    let preshared_key = if settings.preshared_key.is_empty() {
+                        None
+                    } else {
+                        Some(settings.preshared_key.as_str())
+                    };
+                    let mtu = if settings.mtu == 0 {
+                        None
+                    } else {
+                        Some(settings.mtu)
+                    };
+                    let stream = Arc::new(wireguard::Handler::new(
+                        &settings.private_key,
+                        &settings.peer_public_key,
+                        preshared_key,
+                        &settings.endpoint,
+                        &settings.allowed_ips,
+                        &settings.local_address,
+                        mtu,
+                        dns_client.clone(),
+                    )?);
+                    HandlerBuilder::default()
+                        .tag(tag.clone())
+                        .stream_handler(stream.clone())
+                        .datagram_handler(stream)
+                        .build()
+                }
+                #[cfg(feature = "outbound-ssh")]
+                "ssh" => {
+                    let settings =
+                        config::SshOutboundSettings::parse_from_bytes(&outbound.settings)
+                            .map_err(|e| anyhow!("invalid [{}] outbound settings: {}", &tag, e))?;
+                    let password = if settings.password.is_empty() {
+                        None
+                    } else {
+                        Some(settings.password.clone())
+                    };
+                    let private_key = if settings.private_key.is_empty() {
+                        None
+                    } else {
+                        Some(settings.private_key.as_str())
+                    };
+                    let private_key_passphrase = if settings.private_key_passphrase.is_empty() {
+                        None
+                    } else {
+                        Some(settings.private_key_passphrase.as_str())
+                    };
+                    let host_key_fingerprint = if settings.host_key_fingerprint.is_empty() {
+                        None
+                    } else {
+                        Some(settings.host_key_fingerprint.as_str())
+                    };
+                    let known_hosts = if settings.known_hosts.is_empty() {
+                        None
+                    } else {
+                        Some(settings.known_hosts.as_str())
+                    };
+                    let max_channels_per_conn = if settings.max_channels_per_conn == 0 {
+                        None
+                    } else {
+                        Some(settings.max_channels_per_conn)
+                    };
+                    let stream = Arc::new(ssh::Handler::new(
+                        settings.address.clone(),
+                        settings.port as u16,
+                        settings.username.clone(),
+                        password,
+                        private_key,
+                        private_key_passphrase,
+                        host_key_fingerprint,
+                        known_hosts,
+                        max_channels_per_conn,
+                        dns_client.clone(),
+                    )?);
+                    HandlerBuilder::default()
+                        .tag(tag.clone())
+                        .stream_handler(stream.clone())
+                        .datagram_handler(stream)
                         .build()
                 }
                 _ => continue,
@@ -428,11 +806,69 @@ impl OutboundManager {
                         let stream = Arc::new(tryall::StreamHandler {
                             actors: actors.clone(),
                             delay_base: settings.delay_base,
+                            max_concurrent_attempts: settings.max_concurrent_attempts,
                             dns_client: dns_client.clone(),
                         });
                         let datagram = Arc::new(tryall::DatagramHandler {
                             actors,
                             delay_base: settings.delay_base,
+                            max_concurrent_attempts: settings.max_concurrent_attempts,
+                            dns_client: dns_client.clone(),
+                        });
+                        let handler = HandlerBuilder::default()
+                            .tag(tag.clone())
+                            .stream_handler(stream)
+                            .datagram_handler(datagram)
+                            .build();
+                        handlers.insert(tag.clone(), handler);
+                        trace!(
+                            "added handler [{}] with actors: {}",
+                            &tag,
+                            settings.actors.join(",")
+                        );
+                    }
+                    #[cfg(feature = "outbound-urltest")]
+                    "urltest" => {
+                        let settings = config::UrlTestOutboundSettings::parse_from_bytes(
+                            &outbound.settings,
+                        )
+                        .map_err(|e| anyhow!("invalid [{}] outbound settings: {}", &tag, e))?;
+                        let mut actors = Vec::new();
+                        for actor in settings.actors.iter() {
+                            if let Some(a) = handlers.get(actor) {
+                                actors.push(a.clone());
+                            } else {
+                                continue 'outbounds;
+                            }
+                        }
+                        if actors.is_empty() {
+                            continue;
+                        }
+                        let target =
+                            failover::HealthCheckTarget::parse(&settings.url).ok_or_else(|| {
+                                anyhow!(
+                                    "invalid [{}] outbound settings: invalid url {}",
+                                    &tag,
+                                    &settings.url
+                                )
+                            })?;
+                        let (status, abort_handle) = urltest::start(
+                            actors.clone(),
+                            target,
+                            std::time::Duration::from_secs(settings.interval.max(1) as u64),
+                            std::time::Duration::from_millis(settings.tolerance as u64),
+                            dns_client.clone(),
+                        );
+                        abort_handles.push((tag.clone(), abort_handle));
+                        url_tests.insert(tag.clone(), status.clone());
+                        let stream = Arc::new(urltest::StreamHandler {
+                            actors: actors.clone(),
+                            status: status.clone(),
+                            dns_client: dns_client.clone(),
+                        });
+                        let datagram = Arc::new(urltest::DatagramHandler {
+                            actors,
+                            status,
                             dns_client: dns_client.clone(),
                         });
                         let handler = HandlerBuilder::default()
@@ -465,12 +901,19 @@ impl OutboundManager {
                         if actors.is_empty() {
                             continue;
                         }
+                        let sessions = Arc::new(r#static::Sessions::new(actors.len()));
                         let stream = Arc::new(r#static::StreamHandler::new(
                             actors.clone(),
                             &settings.method,
+                            sessions.clone(),
+                            dns_client.clone(),
+                        )?);
+                        let datagram = Arc::new(r#static::DatagramHandler::new(
+                            actors,
+                            &settings.method,
+                            sessions,
+                            dns_client.clone(),
                         )?);
-                        let datagram =
-                            Arc::new(r#static::DatagramHandler::new(actors, &settings.method)?);
                         let handler = HandlerBuilder::default()
                             .tag(tag.clone())
                             .stream_handler(stream)
@@ -507,7 +950,16 @@ impl OutboundManager {
                             } else {
                                 None
                             };
-                        let (stream, mut stream_abort_handles) = failover::StreamHandler::new(
+                        let health_check_target =
+                            failover::HealthCheckTarget::parse(&settings.health_check_url)
+                                .ok_or_else(|| {
+                                    anyhow!(
+                                "invalid [{}] outbound settings: invalid health check url {}",
+                                &tag,
+                                &settings.health_check_url
+                            )
+                                })?;
+                        let (stream, stream_abort_handles) = failover::StreamHandler::new(
                             actors.clone(),
                             settings.fail_timeout,
                             settings.health_check,
@@ -525,9 +977,12 @@ impl OutboundManager {
                             settings.health_check_wait,
                             settings.health_check_attempts,
                             settings.health_check_success_percentage,
+                            health_check_target,
+                            settings.failures_before_down,
+                            settings.successes_before_up,
                             dns_client.clone(),
                         );
-                        let (datagram, mut datagram_abort_handles) = failover::DatagramHandler::new(
+                        let (datagram, datagram_abort_handles) = failover::DatagramHandler::new(
                             actors,
                             settings.fail_timeout,
                             settings.health_check,
@@ -542,6 +997,8 @@ impl OutboundManager {
                             settings.health_check_wait,
                             settings.health_check_attempts,
                             settings.health_check_success_percentage,
+                            settings.failures_before_down,
+                            settings.successes_before_up,
                             dns_client.clone(),
                         );
                         let handler = HandlerBuilder::default()
@@ -550,8 +1007,46 @@ impl OutboundManager {
                             .datagram_handler(Arc::new(datagram))
                             .build();
                         handlers.insert(tag.clone(), handler);
-                        abort_handles.append(&mut stream_abort_handles);
-                        abort_handles.append(&mut datagram_abort_handles);
+                        abort_handles.extend(
+                            stream_abort_handles
+                                .into_iter()
+                                .chain(datagram_abort_handles)
+                                .map(|x| (tag.clone(), x)),
+                        );
+                        trace!(
+                            "added handler [{}] with actors: {}",
+                            &tag,
+                            settings.actors.join(",")
+                        );
+                    }
+                    #[cfg(feature = "outbound-grpc")]
+                    "grpc" => {
+                        let settings =
+                            config::GrpcOutboundSettings::parse_from_bytes(&outbound.settings)
+                                .map_err(|e| {
+                                    anyhow!("invalid [{}] outbound settings: {}", &tag, e)
+                                })?;
+                        let mut actors = Vec::new();
+                        for actor in settings.actors.iter() {
+                            if let Some(a) = handlers.get(actor) {
+                                actors.push(a.clone());
+                            } else {
+                                continue 'outbounds;
+                            }
+                        }
+                        let stream = Arc::new(grpc::outbound::StreamHandler::new(
+                            settings.service_name.clone(),
+                            settings.host.clone(),
+                            settings.address.clone(),
+                            settings.port as u16,
+                            actors,
+                            dns_client.clone(),
+                        ));
+                        let handler = HandlerBuilder::default()
+                            .tag(tag.clone())
+                            .stream_handler(stream)
+                            .build();
+                        handlers.insert(tag.clone(), handler);
                         trace!(
                             "added handler [{}] with actors: {}",
                             &tag,
@@ -573,7 +1068,28 @@ impl OutboundManager {
                                 continue 'outbounds;
                             }
                         }
-                        let (stream, mut stream_abort_handles) = amux::outbound::StreamHandler::new(
+                        let keep_alive = amux::KeepAlive {
+                            interval: std::time::Duration::from_secs(
+                                settings.keep_alive_interval as u64,
+                            ),
+                            max_missed: if settings.keep_alive_max_missed == 0 {
+                                3
+                            } else {
+                                settings.keep_alive_max_missed as usize
+                            },
+                            open_timeout: std::time::Duration::from_secs(
+                                settings.open_timeout as u64,
+                            ),
+                        };
+                        let padding = amux::Padding {
+                            enabled: settings.padding,
+                            max_overhead_pct: if settings.padding_max_overhead_pct == 0 {
+                                25
+                            } else {
+                                settings.padding_max_overhead_pct.min(u16::MAX as u32) as u16
+                            },
+                        };
+                        let (stream, stream_abort_handles) = amux::outbound::StreamHandler::new(
                             settings.address.clone(),
                             settings.port as u16,
                             actors.clone(),
@@ -581,6 +1097,10 @@ impl OutboundManager {
                             settings.concurrency as usize,
                             settings.max_recv_bytes as usize,
                             settings.max_lifetime,
+                            settings.max_connections as usize,
+                            settings.connection_idle_timeout as u64,
+                            keep_alive,
+                            padding,
                             dns_client.clone(),
                         );
                         let handler = HandlerBuilder::default()
@@ -588,7 +1108,8 @@ impl OutboundManager {
                             .stream_handler(Arc::new(stream))
                             .build();
                         handlers.insert(tag.clone(), handler);
-                        abort_handles.append(&mut stream_abort_handles);
+                        abort_handles
+                            .extend(stream_abort_handles.into_iter().map(|x| (tag.clone(), x)));
                         trace!(
                             "added handler [{}] with actors: {}",
                             &tag,
@@ -615,6 +1136,7 @@ impl OutboundManager {
                         }
                         let stream = Arc::new(chain::outbound::StreamHandler {
                             actors: actors.clone(),
+                            dns_client: dns_client.clone(),
                         });
                         let datagram = Arc::new(chain::outbound::DatagramHandler {
                             actors: actors.clone(),
@@ -707,7 +1229,7 @@ impl OutboundManager {
         outbounds: &[Outbound],
         handlers: &mut HashMap<String, AnyOutboundHandler>,
         #[cfg(feature = "plugin")] external_handlers: &mut super::plugin::ExternalHandlers,
-
+        dns_client: SyncDnsClient,
         #[cfg(feature = "outbound-select")] selectors: &mut super::Selectors,
     ) -> Result<()> {
         // FIXME a better way to find outbound deps?
@@ -751,21 +1273,66 @@ impl OutboundManager {
                         use std::sync::atomic::AtomicUsize;
                         let selected = Arc::new(AtomicUsize::new(0));
 
-                        let mut selector =
-                            OutboundSelector::new(tag.clone(), actors_tags, selected.clone());
-                        if let Ok(Some(selected)) = super::selector::get_selected_from_cache(&tag) {
-                            // FIXME handle error
-                            let _ = selector.set_selected(&selected);
+                        let cache_file = if !settings.persist {
+                            None
+                        } else if !settings.cache_file.is_empty() {
+                            Some(std::path::PathBuf::from(&settings.cache_file))
                         } else {
-                            let _ = selector.set_selected(&settings.actors[0]);
-                        }
+                            match super::selector::get_cache_file_path() {
+                                Ok(p) => Some(p),
+                                Err(e) => {
+                                    tracing::warn!("selection of [{}] won't persist: {}", &tag, e);
+                                    None
+                                }
+                            }
+                        };
+                        let mut selector = OutboundSelector::new(
+                            tag.clone(),
+                            actors_tags,
+                            selected.clone(),
+                            cache_file,
+                        );
+                        selector.restore_selected();
+
+                        let fallback = if settings.fallback_on_failure {
+                            let target =
+                                failover::HealthCheckTarget::parse(&settings.health_check_url)
+                                    .ok_or_else(|| {
+                                        anyhow!(
+                                    "invalid [{}] outbound settings: invalid health check url {}",
+                                    &tag,
+                                    &settings.health_check_url
+                                )
+                                    })?;
+                            let fallback = Arc::new(select::Fallback::new(
+                                actors.clone(),
+                                selected.clone(),
+                                settings.failures_before_down,
+                                target,
+                                std::time::Duration::from_secs(
+                                    settings.check_interval.max(1) as u64
+                                ),
+                                dns_client.clone(),
+                            ));
+                            selector = selector.with_fallback(fallback.clone());
+                            Some(fallback)
+                        } else {
+                            None
+                        };
                         let selector = Arc::new(RwLock::new(selector));
 
                         let stream = Arc::new(select::StreamHandler {
                             actors: actors.clone(),
                             selected: selected.clone(),
+                            fallback: fallback.clone(),
+                            dns_client: dns_client.clone(),
+                        });
+                        let datagram = Arc::new(select::DatagramHandler {
+                            actors,
+                            selected,
+                            fallback,
+                            dns_client: dns_client.clone(),
                         });
-                        let datagram = Arc::new(select::DatagramHandler { actors, selected });
 
                         #[cfg(feature = "outbound-select")]
                         {
@@ -792,12 +1359,98 @@ impl OutboundManager {
         Ok(())
     }
 
+    // Returns the outbounds a group goes through, none if the settings are
+    // invalid.
+    fn dependencies(outbound: &Outbound) -> Option<Vec<String>> {
+        let settings = &outbound.settings;
+        let deps = match outbound.protocol.as_str() {
+            "tryall" => {
+                config::TryAllOutboundSettings::parse_from_bytes(settings)
+                    .ok()?
+                    .actors
+            }
+            "static" => {
+                config::StaticOutboundSettings::parse_from_bytes(settings)
+                    .ok()?
+                    .actors
+            }
+            "failover" => {
+                let settings = config::FailOverOutboundSettings::parse_from_bytes(settings).ok()?;
+                let mut deps = settings.actors;
+                deps.extend(settings.last_resort);
+                deps
+            }
+            "select" => {
+                config::SelectOutboundSettings::parse_from_bytes(settings)
+                    .ok()?
+                    .actors
+            }
+            "urltest" => {
+                config::UrlTestOutboundSettings::parse_from_bytes(settings)
+                    .ok()?
+                    .actors
+            }
+            "amux" => {
+                config::AMuxOutboundSettings::parse_from_bytes(settings)
+                    .ok()?
+                    .actors
+            }
+            "chain" => {
+                config::ChainOutboundSettings::parse_from_bytes(settings)
+                    .ok()?
+                    .actors
+            }
+            "mptp" => {
+                config::MptpOutboundSettings::parse_from_bytes(settings)
+                    .ok()?
+                    .actors
+            }
+            "grpc" => {
+                config::GrpcOutboundSettings::parse_from_bytes(settings)
+                    .ok()?
+                    .actors
+            }
+            _ => Vec::new(),
+        };
+        Some(deps)
+    }
+
+    // Returns the tags of the outbounds which are the same in both configs,
+    // along with all the outbounds they go through. Plugins are always
+    // loaded again.
+    fn unchanged(old: &[Outbound], new: &[Outbound]) -> HashSet<String> {
+        let mut unchanged: HashMap<&str, Vec<String>> = new
+            .iter()
+            .filter(|x| x.protocol != "plugin" && old.contains(x))
+            .filter_map(|x| Some((x.tag.as_str(), Self::dependencies(x)?)))
+            .collect();
+        loop {
+            let changed: Vec<&str> = unchanged
+                .iter()
+                .filter(|(_, deps)| deps.iter().any(|x| !unchanged.contains_key(x.as_str())))
+                .map(|(tag, _)| *tag)
+                .collect();
+            if changed.is_empty() {
+                break;
+            }
+            for tag in changed {
+                unchanged.remove(tag);
+            }
+        }
+        unchanged.into_keys().map(str::to_string).collect()
+    }
+
+    /// Loads the outbounds which changed, the others keep their handlers and
+    /// the state of them. Returns the tags of the outbounds which were
+    /// replaced or removed.
     // TODO make this non-async?
     pub async fn reload(
         &mut self,
         outbounds: &[Outbound],
         dns_client: SyncDnsClient,
-    ) -> Result<()> {
+    ) -> Result<HashSet<String>> {
+        let unchanged = Self::unchanged(&self.outbounds, outbounds);
+
         // Save outound select states.
         #[cfg(feature = "outbound-select")]
         let selected_outbounds: HashMap<String, String> = {
@@ -808,35 +1461,64 @@ impl OutboundManager {
             m
         };
 
-        // Load new outbounds.
-        let mut handlers: HashMap<String, AnyOutboundHandler> = HashMap::new();
+        // Load new outbounds, the handlers already there are kept.
+        let mut handlers: HashMap<String, AnyOutboundHandler> = self
+            .handlers
+            .iter()
+            .filter(|(tag, _)| unchanged.contains(*tag))
+            .map(|(tag, h)| (tag.clone(), h.clone()))
+            .collect();
 
         #[cfg(feature = "plugin")]
         let mut external_handlers = super::plugin::ExternalHandlers::new();
-        let mut default_handler: Option<String> = None;
-        let mut abort_handles: Vec<AbortHandle> = Vec::new();
+        let mut default_handler: Option<String> = outbounds.first().map(|x| x.tag.clone());
+        let mut abort_handles: Vec<(String, AbortHandle)> = Vec::new();
 
         #[cfg(feature = "outbound-select")]
-        let mut selectors: super::Selectors = HashMap::new();
+        let mut selectors: super::Selectors = self
+            .selectors
+            .iter()
+            .filter(|(tag, _)| unchanged.contains(*tag))
+            .map(|(tag, x)| (tag.clone(), x.clone()))
+            .collect();
+        #[cfg(feature = "outbound-urltest")]
+        let mut url_tests: super::UrlTests = self
+            .url_tests
+            .iter()
+            .filter(|(tag, _)| unchanged.contains(*tag))
+            .map(|(tag, x)| (tag.clone(), x.clone()))
+            .collect();
 
         for _i in 0..4 {
-            Self::load_handlers(
+            let res = Self::load_handlers(
                 outbounds,
                 dns_client.clone(),
                 &mut handlers,
                 #[cfg(feature = "plugin")]
                 &mut external_handlers,
+                #[cfg(feature = "outbound-urltest")]
+                &mut url_tests,
                 &mut default_handler,
                 &mut abort_handles,
-            )?;
-            Self::load_selectors(
-                outbounds,
-                &mut handlers,
-                #[cfg(feature = "plugin")]
-                &mut external_handlers,
-                #[cfg(feature = "outbound-select")]
-                &mut selectors,
-            )?;
+            )
+            .and_then(|_| {
+                Self::load_selectors(
+                    outbounds,
+                    &mut handlers,
+                    #[cfg(feature = "plugin")]
+                    &mut external_handlers,
+                    dns_client.clone(),
+                    #[cfg(feature = "outbound-select")]
+                    &mut selectors,
+                )
+            });
+            if let Err(e) = res {
+                // Don't leave tasks of the failed handlers running.
+                for (_, abort_handle) in abort_handles.iter() {
+                    abort_handle.abort();
+                }
+                return Err(e);
+            }
         }
 
         // Restore outbound select states.
@@ -851,11 +1533,29 @@ impl OutboundManager {
             }
         }
 
-        // Abort spawned tasks inside handlers.
-        for abort_handle in self.abort_handles.iter() {
-            abort_handle.abort();
+        // Abort the tasks of the handlers which are gone, a handler may be
+        // shared by outbounds of the same settings.
+        for (tag, abort_handle) in std::mem::take(&mut self.abort_handles) {
+            let kept = self.handlers.get(&tag).is_some_and(|h| {
+                unchanged
+                    .iter()
+                    .filter_map(|x| self.handlers.get(x))
+                    .any(|x| Arc::ptr_eq(h, x))
+            });
+            if kept {
+                abort_handles.push((tag, abort_handle));
+            } else {
+                abort_handle.abort();
+            }
         }
 
+        let replaced = self
+            .outbounds
+            .iter()
+            .filter(|x| !unchanged.contains(&x.tag))
+            .map(|x| x.tag.clone())
+            .collect();
+
         self.handlers = handlers;
 
         #[cfg(feature = "plugin")]
@@ -866,10 +1566,15 @@ impl OutboundManager {
         {
             self.selectors = Arc::new(selectors);
         }
+        #[cfg(feature = "outbound-urltest")]
+        {
+            self.url_tests = url_tests;
+        }
 
         self.default_handler = default_handler;
         self.abort_handles = abort_handles;
-        Ok(())
+        self.outbounds = outbounds.to_vec();
+        Ok(replaced)
     }
 
     pub fn new(outbounds: &[Outbound], dns_client: SyncDnsClient) -> Result<Self> {
@@ -877,27 +1582,41 @@ impl OutboundManager {
         #[cfg(feature = "plugin")]
         let mut external_handlers = super::plugin::ExternalHandlers::new();
         let mut default_handler: Option<String> = None;
-        let mut abort_handles: Vec<AbortHandle> = Vec::new();
+        let mut abort_handles: Vec<(String, AbortHandle)> = Vec::new();
         #[cfg(feature = "outbound-select")]
         let mut selectors: super::Selectors = HashMap::new();
+        #[cfg(feature = "outbound-urltest")]
+        let mut url_tests: super::UrlTests = HashMap::new();
         for _i in 0..4 {
-            Self::load_handlers(
+            let res = Self::load_handlers(
                 outbounds,
                 dns_client.clone(),
                 &mut handlers,
                 #[cfg(feature = "plugin")]
                 &mut external_handlers,
+                #[cfg(feature = "outbound-urltest")]
+                &mut url_tests,
                 &mut default_handler,
                 &mut abort_handles,
-            )?;
-            Self::load_selectors(
-                outbounds,
-                &mut handlers,
-                #[cfg(feature = "plugin")]
-                &mut external_handlers,
-                #[cfg(feature = "outbound-select")]
-                &mut selectors,
-            )?;
+            )
+            .and_then(|_| {
+                Self::load_selectors(
+                    outbounds,
+                    &mut handlers,
+                    #[cfg(feature = "plugin")]
+                    &mut external_handlers,
+                    dns_client.clone(),
+                    #[cfg(feature = "outbound-select")]
+                    &mut selectors,
+                )
+            });
+            if let Err(e) = res {
+                // Don't leave tasks of the failed handlers running.
+                for (_, abort_handle) in abort_handles.iter() {
+                    abort_handle.abort();
+                }
+                return Err(e);
+            }
         }
 
         Ok(OutboundManager {
@@ -907,8 +1626,11 @@ impl OutboundManager {
 
             #[cfg(feature = "outbound-select")]
             selectors: Arc::new(selectors),
+            #[cfg(feature = "outbound-urltest")]
+            url_tests,
             default_handler,
             abort_handles,
+            outbounds: outbounds.to_vec(),
         })
     }
 
@@ -924,6 +1646,14 @@ impl OutboundManager {
         self.default_handler.clone()
     }
 
+    /// The tag and protocol of each outbound, in the order of the config.
+    pub fn protocols(&self) -> Vec<(String, String)> {
+        self.outbounds
+            .iter()
+            .map(|x| (x.tag.clone(), x.protocol.clone()))
+            .collect()
+    }
+
     pub fn handlers(&self) -> Handlers<'_> {
         Handlers {
             inner: self.handlers.values(),
@@ -934,6 +1664,21 @@ impl OutboundManager {
     pub fn get_selector(&self, tag: &str) -> Option<Arc<RwLock<OutboundSelector>>> {
         self.selectors.get(tag).map(Clone::clone)
     }
+
+    #[cfg(feature = "outbound-urltest")]
+    pub fn get_url_test(&self, tag: &str) -> Option<Arc<urltest::Status>> {
+        self.url_tests.get(tag).map(Clone::clone)
+    }
+}
+
+impl Drop for OutboundManager {
+    fn drop(&mut self) {
+        // Abort spawned tasks inside handlers, which also stops any plugin
+        // processes they supervise.
+        for (_, abort_handle) in self.abort_handles.iter() {
+            abort_handle.abort();
+        }
+    }
 }
 
 pub struct Handlers<'a> {
@@ -947,3 +1692,47 @@ impl<'a> Iterator for Handlers<'a> {
         self.inner.next()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn outbound(tag: &str, protocol: &str, settings: Vec<u8>) -> Outbound {
+        Outbound {
+            tag: tag.to_string(),
+            protocol: protocol.to_string(),
+            settings,
+            ..Default::default()
+        }
+    }
+
+    fn chain(tag: &str, actors: &[&str]) -> Outbound {
+        let settings = config::ChainOutboundSettings {
+            actors: actors.iter().map(|x| x.to_string()).collect(),
+            ..Default::default()
+        };
+        outbound(tag, "chain", settings.write_to_bytes().unwrap())
+    }
+
+    #[test]
+    fn test_unchanged() {
+        let old = vec![
+            outbound("a", "direct", Vec::new()),
+            outbound("b", "direct", Vec::new()),
+            chain("ab", &["a", "b"]),
+            chain("a2", &["a"]),
+            outbound("c", "direct", Vec::new()),
+        ];
+        let new = vec![
+            outbound("a", "direct", Vec::new()),
+            outbound("b", "drop", Vec::new()),
+            chain("ab", &["a", "b"]),
+            chain("a2", &["a"]),
+            outbound("d", "direct", Vec::new()),
+        ];
+        let unchanged = OutboundManager::unchanged(&old, &new);
+        // The chain through the changed outbound changes too.
+        let expected: HashSet<String> = ["a", "a2"].iter().map(|x| x.to_string()).collect();
+        assert_eq!(unchanged, expected);
+    }
+}
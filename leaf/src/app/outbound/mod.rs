@@ -16,3 +16,7 @@ pub mod plugin;
 
 #[cfg(feature = "outbound-select")]
 pub type Selectors = HashMap<String, Arc<RwLock<selector::OutboundSelector>>>;
+
+#[cfg(feature = "outbound-urltest")]
+pub type UrlTests =
+    std::collections::HashMap<String, std::sync::Arc<crate::proxy::urltest::Status>>;
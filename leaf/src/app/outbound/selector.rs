@@ -1,13 +1,20 @@
 use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicUsize, Ordering};
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 
 use protobuf::Message;
 use tracing::warn;
 
 use anyhow::{anyhow, Result};
 
-fn get_cache_file_path() -> Result<PathBuf> {
+use crate::proxy::select::Fallback;
+
+// Serializes the updates of a cache file, which selectors share.
+static CACHE_LOCK: Mutex<()> = Mutex::new(());
+
+/// Returns the default cache file, in the cache location set by the
+/// environment or else the cache directory of the user.
+pub fn get_cache_file_path() -> Result<PathBuf> {
     let cache_loc = if !(&*crate::option::CACHE_LOCATION).is_empty() {
         Path::new(&*crate::option::CACHE_LOCATION).to_owned()
     } else {
@@ -24,24 +31,37 @@ fn get_cache_file_path() -> Result<PathBuf> {
     Ok(cache_loc.join("selector.cache"))
 }
 
-pub fn get_selected_from_cache(id: &str) -> Result<Option<String>> {
-    let cache_file = get_cache_file_path()?;
-    let content = std::fs::read(&cache_file)?;
+pub fn get_selected_from_cache(cache_file: &Path, id: &str) -> Result<Option<String>> {
+    if !cache_file.exists() {
+        return Ok(None);
+    }
+    let content = std::fs::read(cache_file)?;
     let cache = super::selector_cache::SelectorCache::parse_from_bytes(&content)?;
     Ok(cache.items.get(id).map(Clone::clone))
 }
 
-pub fn persist_selected_to_cache(id: String, selected: String) -> Result<()> {
-    let cache_file = get_cache_file_path()?;
-    let mut cache = if cache_file.exists() {
-        let content = std::fs::read(&cache_file)?;
-        super::selector_cache::SelectorCache::parse_from_bytes(&content)?
-    } else {
-        super::selector_cache::SelectorCache::new()
-    };
+pub fn persist_selected_to_cache(cache_file: &Path, id: String, selected: String) -> Result<()> {
+    let _g = CACHE_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(dir) = cache_file.parent() {
+        if !dir.as_os_str().is_empty() && !dir.exists() {
+            std::fs::create_dir_all(dir)?;
+        }
+    }
+    // A broken cache is replaced rather than blocking the selection.
+    let mut cache = std::fs::read(cache_file)
+        .ok()
+        .and_then(|x| super::selector_cache::SelectorCache::parse_from_bytes(&x).ok())
+        .unwrap_or_default();
+    if cache.items.get(&id) == Some(&selected) {
+        return Ok(());
+    }
     cache.items.insert(id, selected);
     let content = cache.write_to_bytes()?;
-    std::fs::write(&cache_file, content)?;
+    // Written aside and renamed, so that a crash never leaves a truncated
+    // cache behind.
+    let tmp = cache_file.with_extension("tmp");
+    std::fs::write(&tmp, content)?;
+    std::fs::rename(&tmp, cache_file)?;
     Ok(())
 }
 
@@ -53,14 +73,48 @@ pub struct OutboundSelector {
     id: String,
     handlers: OutboundList,
     selected: OutboundIndex,
+    // Where the selection persists, none if it doesn't.
+    cache_file: Option<PathBuf>,
+    fallback: Option<Arc<Fallback>>,
 }
 
 impl OutboundSelector {
-    pub fn new(id: String, handlers: OutboundList, selected: OutboundIndex) -> Self {
+    pub fn new(
+        id: String,
+        handlers: OutboundList,
+        selected: OutboundIndex,
+        cache_file: Option<PathBuf>,
+    ) -> Self {
         Self {
             id,
             handlers,
             selected,
+            cache_file,
+            fallback: None,
+        }
+    }
+
+    pub fn with_fallback(mut self, fallback: Arc<Fallback>) -> Self {
+        self.fallback = Some(fallback);
+        self
+    }
+
+    /// Restores the selection from the cache file, the first handler is
+    /// kept if there's none or it's no longer in the list.
+    pub fn restore_selected(&mut self) {
+        let Some(cache_file) = self.cache_file.as_ref() else {
+            return;
+        };
+        match get_selected_from_cache(cache_file, &self.id) {
+            Ok(Some(tag)) => {
+                if let Some(i) = self.handlers.iter().position(|x| *x == tag) {
+                    self.selected.store(i, Ordering::Relaxed);
+                } else {
+                    warn!("cached selection [{}] of [{}] not found", tag, self.id);
+                }
+            }
+            Ok(None) => (),
+            Err(e) => warn!("load selector state failed: {}", e),
         }
     }
 
@@ -72,11 +126,27 @@ impl OutboundSelector {
         self.handlers[self.selected.load(Ordering::Relaxed)].to_owned()
     }
 
+    /// Returns the handler in use, which differs from the selected one
+    /// while the group falls back from it.
+    pub fn get_active_tag(&self) -> String {
+        match self.fallback.as_ref() {
+            Some(fallback) => self.handlers[fallback.active()].to_owned(),
+            None => self.get_selected_tag(),
+        }
+    }
+
     pub fn set_selected(&mut self, tag: &str) -> Result<()> {
         if let Some(i) = self.handlers.iter().position(|x| x == tag) {
             self.selected.store(i, Ordering::Relaxed);
-            if let Err(e) = persist_selected_to_cache(self.id.clone(), tag.to_string()) {
-                warn!("persist selector state failed: {}", e);
+            if let Some(fallback) = self.fallback.as_ref() {
+                fallback.reset();
+            }
+            if let Some(cache_file) = self.cache_file.as_ref() {
+                if let Err(e) =
+                    persist_selected_to_cache(cache_file, self.id.clone(), tag.to_string())
+                {
+                    warn!("persist selector state failed: {}", e);
+                }
             }
             Ok(())
         } else {
@@ -84,3 +154,48 @@ impl OutboundSelector {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn selector(id: &str, cache_file: &Path) -> OutboundSelector {
+        OutboundSelector::new(
+            id.to_string(),
+            vec!["a".to_string(), "b".to_string(), "c".to_string()],
+            Arc::new(AtomicUsize::new(0)),
+            Some(cache_file.to_owned()),
+        )
+    }
+
+    #[test]
+    fn test_persist_selected() {
+        let cache_file = std::env::temp_dir().join("leaf-test-selector.cache");
+        let _ = std::fs::remove_file(&cache_file);
+
+        let mut s1 = selector("s1", &cache_file);
+        s1.restore_selected();
+        assert_eq!(s1.get_selected_tag(), "a");
+        s1.set_selected("c").unwrap();
+        selector("s2", &cache_file).set_selected("b").unwrap();
+
+        let mut s1 = selector("s1", &cache_file);
+        s1.restore_selected();
+        assert_eq!(s1.get_selected_tag(), "c");
+        let mut s2 = selector("s2", &cache_file);
+        s2.restore_selected();
+        assert_eq!(s2.get_selected_tag(), "b");
+
+        // A cached tag which is gone falls back to the first handler.
+        let mut s1 = OutboundSelector::new(
+            "s1".to_string(),
+            vec!["a".to_string(), "b".to_string()],
+            Arc::new(AtomicUsize::new(0)),
+            Some(cache_file.clone()),
+        );
+        s1.restore_selected();
+        assert_eq!(s1.get_selected_tag(), "a");
+
+        std::fs::remove_file(&cache_file).unwrap();
+    }
+}
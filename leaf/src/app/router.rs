@@ -1,18 +1,27 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::net::IpAddr;
+#[cfg(feature = "rule-process-name")]
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
 
+use aho_corasick::AhoCorasick;
 use anyhow::anyhow;
 use anyhow::Result;
+use arc_swap::ArcSwap;
 use async_recursion::async_recursion;
 use cidr::IpCidr;
 use futures::TryFutureExt;
-use maxminddb::geoip2::Country;
+use maxminddb::geoip2::{Asn, Country};
 use maxminddb::Mmap;
-#[cfg(feature = "rule-process-name")]
+#[cfg(feature = "regex")]
 use regex::Regex;
 use tracing::{debug, warn};
 
+use crate::app::geoip_update::GeoipUpdater;
+use crate::app::rule_provider::RuleProvider;
 use crate::app::SyncDnsClient;
+use crate::common::net::PortSet;
 use crate::config;
 use crate::session::{Network, Session, SocksAddr};
 
@@ -21,29 +30,116 @@ pub trait Condition: Send + Sync + Unpin {
 }
 
 struct Rule {
+    // The position of the rule in the config.
+    index: usize,
     target: String,
     condition: Box<dyn Condition>,
+    // Whether the rule matches the sniffed protocol.
+    protocol: bool,
+    // The sessions routed by the rule.
+    hits: AtomicU64,
 }
 
 impl Rule {
-    fn new(target: String, condition: Box<dyn Condition>) -> Self {
-        Rule { target, condition }
+    fn new(index: usize, target: String, condition: Box<dyn Condition>) -> Self {
+        Rule {
+            index,
+            target,
+            condition,
+            protocol: false,
+            hits: AtomicU64::new(0),
+        }
+    }
+}
+
+// The rules along with the index of their domains. A rule with domains is
+// only tried if the index finds it for the domain of the session, so the
+// rules a session is tried against are the ones without domains and the
+// ones the index found, in the order of the rules.
+#[derive(Default)]
+struct RuleSet {
+    rules: Vec<Rule>,
+    domains: DomainIndex,
+    // The positions of the rules without domains.
+    others: Vec<usize>,
+}
+
+impl RuleSet {
+    fn candidates(&self, sess: &Session) -> Candidates<'_> {
+        let destination = sess
+            .destination_for_routing()
+            .unwrap_or_else(|_| std::borrow::Cow::Borrowed(&sess.destination));
+        let found = match destination.domain() {
+            Some(domain) if destination.is_domain() => self.domains.find(domain),
+            _ => Vec::new(),
+        };
+        Candidates {
+            rules: self,
+            found,
+            next_found: 0,
+            next_other: 0,
+        }
+    }
+}
+
+// The rules a session may match, in order.
+struct Candidates<'a> {
+    rules: &'a RuleSet,
+    found: Vec<usize>,
+    next_found: usize,
+    next_other: usize,
+}
+
+impl<'a> Iterator for Candidates<'a> {
+    type Item = &'a Rule;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let found = self.found.get(self.next_found).copied();
+        let other = self.rules.others.get(self.next_other).copied();
+        let i = match (found, other) {
+            (Some(f), Some(o)) if f < o => {
+                self.next_found += 1;
+                f
+            }
+            (_, Some(o)) => {
+                self.next_other += 1;
+                o
+            }
+            (Some(f), None) => {
+                self.next_found += 1;
+                f
+            }
+            (None, None) => return None,
+        };
+        Some(&self.rules.rules[i])
     }
 }
 
+/// A rule and the number of sessions it routed since it was loaded.
+pub struct RuleHits {
+    /// The position of the rule in the config.
+    pub index: usize,
+    pub target: String,
+    pub hits: u64,
+}
+
 impl Condition for Rule {
     fn apply(&self, sess: &Session) -> bool {
         self.condition.apply(sess)
     }
 }
 
+// A database shared by the matchers, which is empty if the file couldn't be
+// opened and is replaced when the file is updated.
+type MmdbReader = Arc<std::sync::RwLock<Option<Arc<maxminddb::Reader<Mmap>>>>>;
+
 struct MmdbMatcher {
-    reader: Arc<maxminddb::Reader<Mmap>>,
+    reader: MmdbReader,
     country_code: String,
 }
 
 impl MmdbMatcher {
-    fn new(reader: Arc<maxminddb::Reader<Mmap>>, country_code: String) -> Self {
+    fn new(reader: MmdbReader, country_code: String) -> Self {
         MmdbMatcher {
             reader,
             country_code,
@@ -56,9 +152,12 @@ impl Condition for MmdbMatcher {
         let destination = sess
             .destination_for_routing()
             .unwrap_or_else(|_| std::borrow::Cow::Borrowed(&sess.destination));
+        let Some(reader) = self.reader.read().unwrap().clone() else {
+            return false;
+        };
         if !destination.is_domain() {
             if let Some(ip) = destination.ip() {
-                if let Ok(country) = self.reader.lookup::<Country>(ip) {
+                if let Ok(country) = reader.lookup::<Country>(ip) {
                     if let Some(country) = country.country {
                         if let Some(iso_code) = country.iso_code {
                             if iso_code.to_lowercase() == self.country_code.to_lowercase() {
@@ -74,8 +173,109 @@ impl Condition for MmdbMatcher {
     }
 }
 
+// Matches the autonomous system announcing the destination IP.
+struct AsnMatcher {
+    reader: MmdbReader,
+    numbers: HashSet<u32>,
+}
+
+impl Condition for AsnMatcher {
+    fn apply(&self, sess: &Session) -> bool {
+        let destination = sess
+            .destination_for_routing()
+            .unwrap_or_else(|_| std::borrow::Cow::Borrowed(&sess.destination));
+        if destination.is_domain() {
+            return false;
+        }
+        let Some(ip) = destination.ip() else {
+            return false;
+        };
+        let Some(reader) = self.reader.read().unwrap().clone() else {
+            return false;
+        };
+        let Ok(asn) = reader.lookup::<Asn>(ip) else {
+            return false;
+        };
+        match asn.autonomous_system_number {
+            Some(n) if self.numbers.contains(&n) => {
+                debug!("[{}] matches asn [{}]", ip, n);
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+struct ProtocolMatcher {
+    values: Vec<String>,
+}
+
+impl Condition for ProtocolMatcher {
+    fn apply(&self, sess: &Session) -> bool {
+        let Some(protocol) = sess.sniffed_protocol else {
+            return false;
+        };
+        if self.values.iter().any(|x| x == protocol) {
+            debug!("[{}] matches protocol", protocol);
+            return true;
+        }
+        false
+    }
+}
+
+/// CIDRs merged into sorted ranges of addresses, an address is looked up
+/// by a binary search.
+#[derive(Default)]
+pub struct IpSet {
+    v4: Vec<(u32, u32)>,
+    v6: Vec<(u128, u128)>,
+}
+
+impl IpSet {
+    pub fn new(cidrs: &[IpCidr]) -> Self {
+        let mut v4 = Vec::new();
+        let mut v6 = Vec::new();
+        for cidr in cidrs {
+            match (cidr.first_address(), cidr.last_address()) {
+                (IpAddr::V4(first), IpAddr::V4(last)) => v4.push((first.into(), last.into())),
+                (IpAddr::V6(first), IpAddr::V6(last)) => v6.push((first.into(), last.into())),
+                _ => (),
+            }
+        }
+        IpSet {
+            v4: Self::merge(v4),
+            v6: Self::merge(v6),
+        }
+    }
+
+    fn merge<T: Ord + Copy>(mut ranges: Vec<(T, T)>) -> Vec<(T, T)> {
+        ranges.sort_unstable();
+        let mut merged: Vec<(T, T)> = Vec::with_capacity(ranges.len());
+        for (first, last) in ranges {
+            match merged.last_mut() {
+                // Overlapping ranges are joined, adjacent ones are left apart.
+                Some(prev) if first <= prev.1 => prev.1 = prev.1.max(last),
+                _ => merged.push((first, last)),
+            }
+        }
+        merged
+    }
+
+    fn find<T: Ord + Copy>(ranges: &[(T, T)], addr: T) -> bool {
+        let i = ranges.partition_point(|x| x.0 <= addr);
+        i > 0 && addr <= ranges[i - 1].1
+    }
+
+    pub fn contains(&self, ip: &IpAddr) -> bool {
+        match ip {
+            IpAddr::V4(ip) => Self::find(&self.v4, u32::from(*ip)),
+            IpAddr::V6(ip) => Self::find(&self.v6, u128::from(*ip)),
+        }
+    }
+}
+
 struct IpCidrMatcher {
-    values: Vec<IpCidr>,
+    values: IpSet,
 }
 
 impl IpCidrMatcher {
@@ -91,7 +291,9 @@ impl IpCidrMatcher {
             }
             drop(ip);
         }
-        IpCidrMatcher { values: cidrs }
+        IpCidrMatcher {
+            values: IpSet::new(&cidrs),
+        }
     }
 }
 
@@ -101,12 +303,10 @@ impl Condition for IpCidrMatcher {
             .destination_for_routing()
             .unwrap_or_else(|_| std::borrow::Cow::Borrowed(&sess.destination));
         if !destination.is_domain() {
-            for cidr in &self.values {
-                if let Some(ip) = destination.ip() {
-                    if cidr.contains(&ip) {
-                        debug!("[{}] matches ip-cidr [{}]", ip, &cidr);
-                        return true;
-                    }
+            if let Some(ip) = destination.ip() {
+                if self.values.contains(&ip) {
+                    debug!("[{}] matches ip-cidr", ip);
+                    return true;
                 }
             }
         }
@@ -114,6 +314,64 @@ impl Condition for IpCidrMatcher {
     }
 }
 
+// Matches the source of a session, the client of the inbound.
+struct SourceIpMatcher {
+    values: Vec<IpCidr>,
+}
+
+impl SourceIpMatcher {
+    fn new(ips: &[String]) -> Self {
+        let mut values = Vec::new();
+        for ip in ips {
+            // A single address is a host CIDR.
+            let cidr = match ip.parse::<IpAddr>() {
+                Ok(addr) => Ok(IpCidr::new_host(addr)),
+                Err(_) => ip.parse::<IpCidr>(),
+            };
+            match cidr {
+                Ok(cidr) => values.push(cidr),
+                Err(e) => warn!("invalid source ip {}: {}", ip, e),
+            }
+        }
+        SourceIpMatcher { values }
+    }
+}
+
+impl Condition for SourceIpMatcher {
+    fn apply(&self, sess: &Session) -> bool {
+        // IPv4 clients of a dual-stack listener come with mapped addresses.
+        let ip = sess.source.ip().to_canonical();
+        if let Some(cidr) = self.values.iter().find(|x| x.contains(&ip)) {
+            debug!("[{}] matches source ip [{}]", ip, cidr);
+            return true;
+        }
+        false
+    }
+}
+
+struct SourcePortMatcher {
+    ports: PortSet,
+}
+
+impl SourcePortMatcher {
+    fn new(ports: &[String]) -> Self {
+        SourcePortMatcher {
+            ports: port_set(ports),
+        }
+    }
+}
+
+impl Condition for SourcePortMatcher {
+    fn apply(&self, sess: &Session) -> bool {
+        let port = sess.source.port();
+        if self.ports.contains(port) {
+            debug!("[{}] matches source port", port);
+            return true;
+        }
+        false
+    }
+}
+
 struct InboundTagMatcher {
     values: Vec<String>,
 }
@@ -140,6 +398,100 @@ impl Condition for InboundTagMatcher {
     }
 }
 
+struct AlpnMatcher {
+    values: Vec<String>,
+}
+
+impl AlpnMatcher {
+    fn new(alpns: &mut [String]) -> Self {
+        let mut values = Vec::new();
+        for a in alpns.iter_mut() {
+            values.push(std::mem::take(a));
+        }
+        Self { values }
+    }
+}
+
+impl Condition for AlpnMatcher {
+    fn apply(&self, sess: &Session) -> bool {
+        if let Some(alpn) = sess.alpn.as_ref() {
+            for v in &self.values {
+                if v == alpn {
+                    debug!("[{}] matches alpn [{}]", alpn, v);
+                    return true;
+                }
+            }
+        }
+        false
+    }
+}
+
+// Matches the server name received by a TLS inbound, exactly for FULL
+// values and including subdomains for DOMAIN values.
+struct SniMatcher {
+    values: Vec<(config::router::rule::domain::Type, String)>,
+}
+
+impl SniMatcher {
+    fn new(snis: &mut [config::router::rule::Domain]) -> Self {
+        let mut values = Vec::new();
+        for sni in snis.iter_mut() {
+            values.push((sni.type_.unwrap(), std::mem::take(&mut sni.value)));
+        }
+        Self { values }
+    }
+}
+
+impl Condition for SniMatcher {
+    fn apply(&self, sess: &Session) -> bool {
+        if let Some(sni) = sess.sni.as_ref() {
+            for (type_, v) in &self.values {
+                let matched = match type_ {
+                    config::router::rule::domain::Type::FULL => sni == v,
+                    config::router::rule::domain::Type::DOMAIN => is_sub_domain(sni, v),
+                    config::router::rule::domain::Type::PLAIN => sni.contains(v.as_str()),
+                    // Only the domain lists have them.
+                    config::router::rule::domain::Type::REGEX => false,
+                };
+                if matched {
+                    debug!("[{}] matches sni [{}]", sni, v);
+                    return true;
+                }
+            }
+        }
+        false
+    }
+}
+
+// Matches the user an inbound authenticated the session as.
+struct UserMatcher {
+    values: Vec<String>,
+}
+
+impl UserMatcher {
+    fn new(users: &mut [String]) -> Self {
+        let mut values = Vec::new();
+        for u in users.iter_mut() {
+            values.push(std::mem::take(u));
+        }
+        Self { values }
+    }
+}
+
+impl Condition for UserMatcher {
+    fn apply(&self, sess: &Session) -> bool {
+        if let Some(user) = sess.user.as_ref() {
+            for v in &self.values {
+                if v == user {
+                    debug!("[{}] matches user [{}]", user, v);
+                    return true;
+                }
+            }
+        }
+        false
+    }
+}
+
 struct NetworkMatcher {
     values: Vec<Network>,
 }
@@ -170,74 +522,40 @@ impl Condition for NetworkMatcher {
     }
 }
 
+// The ports of the lists, the invalid ones are left out.
+fn port_set(lists: &[String]) -> PortSet {
+    let mut ports = PortSet::default();
+    for list in lists {
+        if let Err(e) = ports.add(list) {
+            warn!("invalid port list {}: {}", list, e);
+        }
+    }
+    ports
+}
+
 struct PortMatcher {
-    condition: Box<dyn Condition>,
+    ports: PortSet,
 }
 
 impl PortMatcher {
     fn new(port_ranges: &[String]) -> Self {
-        let mut cond_or = ConditionOr::new();
-        for pr in port_ranges.iter() {
-            match PortRangeMatcher::new(pr) {
-                Ok(m) => cond_or.add(Box::new(m)),
-                Err(e) => warn!("failed to add port range matcher: {}", e),
-            }
-        }
         PortMatcher {
-            condition: Box::new(cond_or),
+            ports: port_set(port_ranges),
         }
     }
 }
 
 impl Condition for PortMatcher {
-    fn apply(&self, sess: &Session) -> bool {
-        self.condition.apply(sess)
-    }
-}
-
-struct PortRangeMatcher {
-    start: u16,
-    end: u16,
-}
-
-impl PortRangeMatcher {
-    fn new(port_range: &str) -> Result<Self> {
-        let parts: Vec<&str> = port_range.split('-').collect();
-        if parts.len() != 2 {
-            return Err(anyhow!("invalid port range"));
-        }
-        let start = if let Ok(v) = parts[0].parse::<u16>() {
-            v
-        } else {
-            return Err(anyhow!("invalid port range"));
-        };
-        let end = if let Ok(v) = parts[1].parse::<u16>() {
-            v
-        } else {
-            return Err(anyhow!("invalid port range"));
-        };
-        if start > end {
-            return Err(anyhow!("invalid port range"));
-        }
-        Ok(PortRangeMatcher { start, end })
-    }
-}
-
-impl Condition for PortRangeMatcher {
     fn apply(&self, sess: &Session) -> bool {
         let port = sess
             .destination_for_routing()
             .unwrap_or_else(|_| std::borrow::Cow::Borrowed(&sess.destination))
             .port();
-        if port >= self.start && port <= self.end {
-            debug!(
-                "[{}] matches port range [{}-{}]",
-                port, self.start, self.end
-            );
-            true
-        } else {
-            false
+        if self.ports.contains(port) {
+            debug!("[{}] matches port", port);
+            return true;
         }
+        false
     }
 }
 
@@ -268,6 +586,36 @@ impl Condition for DomainKeywordMatcher {
     }
 }
 
+#[cfg(feature = "regex")]
+struct DomainRegexMatcher {
+    regex: Regex,
+}
+
+#[cfg(feature = "regex")]
+impl Condition for DomainRegexMatcher {
+    fn apply(&self, sess: &Session) -> bool {
+        let destination = sess
+            .destination_for_routing()
+            .unwrap_or_else(|_| std::borrow::Cow::Borrowed(&sess.destination));
+        if destination.is_domain() {
+            if let Some(domain) = destination.domain() {
+                if self.regex.is_match(domain) {
+                    debug!("[{}] matches domain regex [{}]", domain, self.regex);
+                    return true;
+                }
+            }
+        }
+        false
+    }
+}
+
+#[cfg(feature = "regex")]
+fn domain_regex(value: &str) -> Option<Regex> {
+    Regex::new(value)
+        .map_err(|e| warn!("invalid domain regex {}: {}", value, e))
+        .ok()
+}
+
 struct DomainSuffixMatcher {
     value: String,
 }
@@ -342,12 +690,35 @@ impl Condition for DomainFullMatcher {
     }
 }
 
+// Matches the domains of domain sets.
+struct DomainSetMatcher {
+    set: DomainSet,
+}
+
+impl Condition for DomainSetMatcher {
+    fn apply(&self, sess: &Session) -> bool {
+        let destination = sess
+            .destination_for_routing()
+            .unwrap_or_else(|_| std::borrow::Cow::Borrowed(&sess.destination));
+        if let Some(domain) = destination.domain() {
+            if let Some(value) = self.set.find(domain) {
+                debug!("[{}] matches domain set [{}]", domain, value);
+                return true;
+            }
+        }
+        false
+    }
+}
+
 struct DomainMatcher {
     condition: Box<dyn Condition>,
 }
 
 impl DomainMatcher {
-    fn new(domains: &mut [config::router::rule::Domain]) -> Self {
+    fn new(
+        domains: &mut [config::router::rule::Domain],
+        set_domains: &[config::router::rule::Domain],
+    ) -> Self {
         let mut cond_or = ConditionOr::new();
         for rr_domain in domains.iter_mut() {
             let filter = std::mem::take(&mut rr_domain.value);
@@ -361,8 +732,21 @@ impl DomainMatcher {
                 config::router::rule::domain::Type::FULL => {
                     cond_or.add(Box::new(DomainFullMatcher::new(filter)));
                 }
+                config::router::rule::domain::Type::REGEX => {
+                    #[cfg(feature = "regex")]
+                    if let Some(regex) = domain_regex(&filter) {
+                        cond_or.add(Box::new(DomainRegexMatcher { regex }));
+                    }
+                    #[cfg(not(feature = "regex"))]
+                    warn!("domain regex {} needs the regex feature", filter);
+                }
             }
         }
+        if !set_domains.is_empty() {
+            cond_or.add(Box::new(DomainSetMatcher {
+                set: DomainSet::new(set_domains),
+            }));
+        }
         DomainMatcher {
             condition: Box::new(cond_or),
         }
@@ -375,36 +759,290 @@ impl Condition for DomainMatcher {
     }
 }
 
-#[cfg(feature = "rule-process-name")]
-pub struct ProcessNameMatcher {
+/// Domains matched the way the domain conditions of the rules match them,
+/// for matching a domain against many values. Full domains and suffixes
+/// are looked up in sets, once per label of the domain, keywords are
+/// searched one by one.
+#[derive(Default)]
+pub struct DomainSet {
+    keywords: Vec<String>,
+    suffixes: HashSet<String>,
+    fulls: HashSet<String>,
+    #[cfg(feature = "regex")]
     regexes: Vec<Regex>,
 }
 
-#[cfg(feature = "rule-process-name")]
-impl ProcessNameMatcher {
-    pub fn new(patterns: Vec<String>) -> Self {
-        let mut regexes = Vec::new();
-        for pattern in patterns {
-            if let Ok(regex) = Regex::new(&pattern) {
-                regexes.push(regex);
-            } else {
-                warn!("Invalid regex pattern: {}", pattern);
+impl DomainSet {
+    pub fn new(domains: &[config::router::rule::Domain]) -> Self {
+        let mut set = Self::default();
+        for domain in domains {
+            let value = domain.value.to_lowercase();
+            match domain.type_.unwrap() {
+                config::router::rule::domain::Type::PLAIN => set.keywords.push(value),
+                config::router::rule::domain::Type::DOMAIN => {
+                    set.suffixes.insert(value);
+                }
+                config::router::rule::domain::Type::FULL => {
+                    set.fulls.insert(value);
+                }
+                config::router::rule::domain::Type::REGEX => {
+                    // Not lowercased, which would change the classes.
+                    #[cfg(feature = "regex")]
+                    set.regexes.extend(domain_regex(&domain.value));
+                    #[cfg(not(feature = "regex"))]
+                    warn!("domain regex {} needs the regex feature", domain.value);
+                }
             }
         }
-        Self { regexes }
+        set
     }
-}
 
-#[cfg(feature = "rule-process-name")]
-impl Condition for ProcessNameMatcher {
-    fn apply(&self, sess: &Session) -> bool {
-        if let Some(process_name) = sess.process_name.as_ref() {
-            for regex in &self.regexes {
-                if regex.is_match(process_name) {
-                    debug!("Matched process_name={} with regex", process_name);
-                    return true;
-                }
-            }
+    /// Returns the value `domain` matches.
+    pub fn find(&self, domain: &str) -> Option<&str> {
+        let domain = domain.trim_end_matches('.').to_lowercase();
+        if let Some(v) = self.fulls.get(&domain) {
+            return Some(v);
+        }
+        let mut suffix = domain.as_str();
+        loop {
+            if let Some(v) = self.suffixes.get(suffix) {
+                return Some(v);
+            }
+            match suffix.split_once('.') {
+                Some((_, rest)) => suffix = rest,
+                None => break,
+            }
+        }
+        if let Some(v) = self.keywords.iter().find(|x| domain.contains(x.as_str())) {
+            return Some(v);
+        }
+        #[cfg(feature = "regex")]
+        if let Some(v) = self.regexes.iter().find(|x| x.is_match(&domain)) {
+            return Some(v.as_str());
+        }
+        None
+    }
+}
+
+// A node of the suffix trie, by the labels of the domains from the end.
+#[derive(Default)]
+struct SuffixNode {
+    children: HashMap<Box<str>, usize>,
+    // The rules with the suffix ending at the node.
+    rules: Vec<usize>,
+}
+
+/// The domains of many rules, for finding the rules a domain matches
+/// without trying them one by one. Full domains are looked up in a map,
+/// suffixes in a trie of their labels from the end and keywords with an
+/// Aho-Corasick automaton, each of which has the positions of the rules
+/// with the value. Domains are matched in lowercase.
+#[derive(Default)]
+pub struct DomainIndex {
+    fulls: HashMap<String, Vec<usize>>,
+    // The root is the first node.
+    suffixes: Vec<SuffixNode>,
+    keywords: Option<AhoCorasick>,
+    // The rules with a keyword, by the pattern of the keyword.
+    keyword_rules: Vec<Vec<usize>>,
+    #[cfg(feature = "regex")]
+    regexes: Vec<(Regex, usize)>,
+}
+
+#[derive(Default)]
+pub struct DomainIndexBuilder {
+    fulls: HashMap<String, Vec<usize>>,
+    suffixes: Vec<SuffixNode>,
+    keywords: HashMap<String, usize>,
+    keyword_rules: Vec<Vec<usize>>,
+    #[cfg(feature = "regex")]
+    regexes: Vec<(Regex, usize)>,
+}
+
+impl DomainIndexBuilder {
+    /// Adds a domain of the rule at `rule`.
+    pub fn add(&mut self, rule: usize, domain: &config::router::rule::Domain) {
+        let value = domain.value.to_lowercase();
+        match domain.type_.unwrap() {
+            config::router::rule::domain::Type::PLAIN => {
+                let next = self.keyword_rules.len();
+                let pattern = *self.keywords.entry(value).or_insert(next);
+                if pattern == next {
+                    self.keyword_rules.push(Vec::new());
+                }
+                self.keyword_rules[pattern].push(rule);
+            }
+            config::router::rule::domain::Type::DOMAIN => {
+                if self.suffixes.is_empty() {
+                    self.suffixes.push(SuffixNode::default());
+                }
+                let mut node = 0;
+                for label in value.rsplit('.') {
+                    node = match self.suffixes[node].children.get(label) {
+                        Some(&child) => child,
+                        None => {
+                            let child = self.suffixes.len();
+                            self.suffixes.push(SuffixNode::default());
+                            self.suffixes[node].children.insert(label.into(), child);
+                            child
+                        }
+                    };
+                }
+                self.suffixes[node].rules.push(rule);
+            }
+            config::router::rule::domain::Type::FULL => {
+                self.fulls.entry(value).or_default().push(rule);
+            }
+            config::router::rule::domain::Type::REGEX => {
+                // Not lowercased, which would change the classes.
+                #[cfg(feature = "regex")]
+                if let Some(regex) = domain_regex(&domain.value) {
+                    self.regexes.push((regex, rule));
+                }
+                #[cfg(not(feature = "regex"))]
+                warn!("domain regex {} needs the regex feature", domain.value);
+            }
+        }
+    }
+
+    pub fn build(self) -> DomainIndex {
+        let keywords = if self.keywords.is_empty() {
+            None
+        } else {
+            let mut patterns: Vec<&str> = vec![""; self.keyword_rules.len()];
+            for (keyword, &pattern) in self.keywords.iter() {
+                patterns[pattern] = keyword.as_str();
+            }
+            AhoCorasick::new(patterns)
+                .map_err(|e| warn!("build domain keyword matcher failed: {}", e))
+                .ok()
+        };
+        DomainIndex {
+            fulls: self.fulls,
+            suffixes: self.suffixes,
+            keywords,
+            keyword_rules: self.keyword_rules,
+            #[cfg(feature = "regex")]
+            regexes: self.regexes,
+        }
+    }
+}
+
+impl DomainIndex {
+    /// Returns the positions of the rules `domain` matches, in order.
+    pub fn find(&self, domain: &str) -> Vec<usize> {
+        let domain = domain.trim_end_matches('.');
+        let domain = if domain.bytes().any(|x| x.is_ascii_uppercase()) {
+            std::borrow::Cow::Owned(domain.to_lowercase())
+        } else {
+            std::borrow::Cow::Borrowed(domain)
+        };
+        let mut rules = Vec::new();
+        if let Some(v) = self.fulls.get(domain.as_ref()) {
+            rules.extend_from_slice(v);
+        }
+        if let Some(root) = self.suffixes.first() {
+            let mut node = root;
+            for label in domain.rsplit('.') {
+                match node.children.get(label) {
+                    Some(&child) => node = &self.suffixes[child],
+                    None => break,
+                }
+                rules.extend_from_slice(&node.rules);
+            }
+        }
+        if let Some(keywords) = &self.keywords {
+            for m in keywords.find_overlapping_iter(domain.as_ref()) {
+                rules.extend_from_slice(&self.keyword_rules[m.pattern().as_usize()]);
+            }
+        }
+        #[cfg(feature = "regex")]
+        for (regex, rule) in self.regexes.iter() {
+            if regex.is_match(&domain) {
+                rules.push(*rule);
+            }
+        }
+        rules.sort_unstable();
+        rules.dedup();
+        rules
+    }
+}
+
+// Matches the rules a provider has at the time.
+struct ProviderMatcher {
+    provider: Arc<RuleProvider>,
+}
+
+impl Condition for ProviderMatcher {
+    fn apply(&self, sess: &Session) -> bool {
+        if self.provider.rules().matches(sess) {
+            debug!(
+                "[{}] matches rule provider [{}]",
+                sess.destination,
+                self.provider.name()
+            );
+            return true;
+        }
+        false
+    }
+}
+
+#[cfg(feature = "rule-process-name")]
+pub struct ProcessNameMatcher {
+    regexes: Vec<Regex>,
+}
+
+#[cfg(feature = "rule-process-name")]
+impl ProcessNameMatcher {
+    pub fn new(patterns: Vec<String>) -> Self {
+        let mut regexes = Vec::new();
+        for pattern in patterns {
+            if let Ok(regex) = Regex::new(&pattern) {
+                regexes.push(regex);
+            } else {
+                warn!("Invalid regex pattern: {}", pattern);
+            }
+        }
+        Self { regexes }
+    }
+}
+
+// Matches the name or the full path of the process case-insensitively.
+#[cfg(feature = "rule-process-name")]
+struct ProcessMatcher {
+    values: Vec<String>,
+    full_path: bool,
+}
+
+#[cfg(feature = "rule-process-name")]
+impl Condition for ProcessMatcher {
+    fn apply(&self, sess: &Session) -> bool {
+        let Some(path) = sess.process_name.as_ref() else {
+            return false;
+        };
+        let value = if self.full_path {
+            path.as_str()
+        } else {
+            path.rsplit(['/', '\\']).next().unwrap_or(path)
+        };
+        if let Some(v) = self.values.iter().find(|x| x.eq_ignore_ascii_case(value)) {
+            debug!("[{}] matches process [{}]", path, v);
+            return true;
+        }
+        false
+    }
+}
+
+#[cfg(feature = "rule-process-name")]
+impl Condition for ProcessNameMatcher {
+    fn apply(&self, sess: &Session) -> bool {
+        if let Some(process_name) = sess.process_name.as_ref() {
+            for regex in &self.regexes {
+                if regex.is_match(process_name) {
+                    debug!("Matched process_name={} with regex", process_name);
+                    return true;
+                }
+            }
         }
         false
     }
@@ -468,109 +1106,493 @@ impl Condition for ConditionOr {
     }
 }
 
+struct ConditionNot {
+    condition: Box<dyn Condition>,
+}
+
+impl Condition for ConditionNot {
+    fn apply(&self, sess: &Session) -> bool {
+        !self.condition.apply(sess)
+    }
+}
+
+/// The rules of a config loaded for a router, see `Router::prepare_reload`.
+pub struct RouterReload {
+    rules: RuleSet,
+    domain_resolve: bool,
+    providers: HashMap<String, Arc<RuleProvider>>,
+    mmdb_readers: HashMap<String, MmdbReader>,
+    geoip_updater: Option<Arc<GeoipUpdater>>,
+    #[cfg(feature = "rule-process-name")]
+    match_process: bool,
+}
+
 pub struct Router {
-    rules: Vec<Rule>,
+    // Swapped as a whole when the rules are replaced, the sessions being
+    // routed keep the rules they started with.
+    rules: ArcSwap<RuleSet>,
     domain_resolve: bool,
     dns_client: SyncDnsClient,
+    providers: HashMap<String, Arc<RuleProvider>>,
+    // Shared by the rules replacing each other, a database updated is seen
+    // by all of them.
+    mmdb_readers: std::sync::Mutex<HashMap<String, MmdbReader>>,
+    geoip_updater: Option<Arc<GeoipUpdater>>,
+    // The final outbounds of the inbounds by their tags.
+    inbound_finals: HashMap<String, String>,
+    // Whether there are rules matching the process of a session.
+    #[cfg(feature = "rule-process-name")]
+    match_process: AtomicBool,
 }
 
 impl Router {
-    fn load_rules(rules: &mut Vec<Rule>, routing_rules: &mut [config::router::Rule]) {
-        let mut mmdb_readers: HashMap<String, Arc<maxminddb::Reader<Mmap>>> = HashMap::new();
-        for rr in routing_rules.iter_mut() {
-            let mut cond_and = ConditionAnd::new();
+    // Keeps the providers whose config didn't change, along with the rules
+    // they downloaded.
+    fn load_providers(
+        providers: &mut HashMap<String, Arc<RuleProvider>>,
+        configs: &[config::router::RuleProvider],
+    ) {
+        let mut old = std::mem::take(providers);
+        for config in configs {
+            let provider = match old.remove(&config.name) {
+                Some(p) if p.config() == config => p,
+                _ => Arc::new(RuleProvider::new(config)),
+            };
+            providers.insert(config.name.clone(), provider);
+        }
+    }
 
-            if !rr.domains.is_empty() {
-                cond_and.add(Box::new(DomainMatcher::new(&mut rr.domains)));
-            }
+    // Keeps the updater if its config didn't change.
+    fn load_geoip_updater(updater: &mut Option<Arc<GeoipUpdater>>, config: &config::Router) {
+        if config.geoip_url.is_empty() {
+            *updater = None;
+            return;
+        }
+        if updater.as_ref().is_some_and(|x| x.is_configured_by(config)) {
+            return;
+        }
+        *updater = Some(Arc::new(GeoipUpdater::new(config)));
+    }
 
-            if !rr.ip_cidrs.is_empty() {
-                cond_and.add(Box::new(IpCidrMatcher::new(&mut rr.ip_cidrs)));
+    // Opens a file once for all the rules.
+    fn open_mmdb(readers: &mut HashMap<String, MmdbReader>, file: &str) -> MmdbReader {
+        if let Some(r) = readers.get(file) {
+            return r.clone();
+        }
+        let reader = match maxminddb::Reader::open_mmap(file) {
+            Ok(r) => Some(Arc::new(r)),
+            Err(e) => {
+                warn!("open mmdb file {} failed: {:?}", file, e);
+                None
             }
+        };
+        let reader = Arc::new(std::sync::RwLock::new(reader));
+        readers.insert(file.to_owned(), reader.clone());
+        reader
+    }
 
-            if !rr.mmdbs.is_empty() {
-                for mmdb in rr.mmdbs.iter() {
-                    let reader = match mmdb_readers.get(&mmdb.file) {
-                        Some(r) => r.clone(),
-                        None => match maxminddb::Reader::open_mmap(&mmdb.file) {
-                            Ok(r) => {
-                                let r = Arc::new(r);
-                                mmdb_readers.insert(mmdb.file.to_owned(), r.clone());
-                                r
-                            }
-                            Err(e) => {
-                                warn!("open mmdb file {} failed: {:?}", mmdb.file, e);
-                                continue;
-                            }
-                        },
-                    };
-                    cond_and.add(Box::new(MmdbMatcher::new(
-                        reader,
-                        mmdb.country_code.clone(),
-                    )));
-                }
-            }
+    // The fields of a rule, and its sub rules if it's a logical one, all of
+    // which have to match.
+    fn rule_condition(
+        rr: &mut config::router::Rule,
+        providers: &HashMap<String, Arc<RuleProvider>>,
+        mmdb_readers: &mut HashMap<String, MmdbReader>,
+    ) -> ConditionAnd {
+        let mut cond_and = ConditionAnd::new();
+
+        if !rr.domains.is_empty() || !rr.set_domains.is_empty() {
+            let set_domains = std::mem::take(&mut rr.set_domains);
+            cond_and.add(Box::new(DomainMatcher::new(&mut rr.domains, &set_domains)));
+        }
+
+        if !rr.ip_cidrs.is_empty() {
+            cond_and.add(Box::new(IpCidrMatcher::new(&mut rr.ip_cidrs)));
+        }
 
-            if !rr.port_ranges.is_empty() {
-                cond_and.add(Box::new(PortMatcher::new(&rr.port_ranges)));
+        if !rr.mmdbs.is_empty() {
+            for mmdb in rr.mmdbs.iter() {
+                let reader = Self::open_mmdb(mmdb_readers, &mmdb.file);
+                cond_and.add(Box::new(MmdbMatcher::new(
+                    reader,
+                    mmdb.country_code.clone(),
+                )));
             }
+        }
 
-            if !rr.networks.is_empty() {
-                cond_and.add(Box::new(NetworkMatcher::new(&mut rr.networks)));
+        if !rr.asns.is_empty() {
+            let mut numbers: HashMap<&str, HashSet<u32>> = HashMap::new();
+            for asn in rr.asns.iter() {
+                numbers.entry(&asn.file).or_default().insert(asn.number);
+            }
+            let mut cond_or = ConditionOr::new();
+            for (file, numbers) in numbers {
+                let reader = Self::open_mmdb(mmdb_readers, file);
+                cond_or.add(Box::new(AsnMatcher { reader, numbers }));
             }
+            cond_and.add(Box::new(cond_or));
+        }
+
+        if !rr.port_ranges.is_empty() {
+            cond_and.add(Box::new(PortMatcher::new(&rr.port_ranges)));
+        }
+
+        if !rr.source_ip_cidrs.is_empty() {
+            cond_and.add(Box::new(SourceIpMatcher::new(&rr.source_ip_cidrs)));
+        }
+
+        if !rr.source_ports.is_empty() {
+            cond_and.add(Box::new(SourcePortMatcher::new(&rr.source_ports)));
+        }
+
+        if !rr.networks.is_empty() {
+            cond_and.add(Box::new(NetworkMatcher::new(&mut rr.networks)));
+        }
+
+        if !rr.inbound_tags.is_empty() {
+            cond_and.add(Box::new(InboundTagMatcher::new(&mut rr.inbound_tags)));
+        }
+
+        if !rr.alpns.is_empty() {
+            cond_and.add(Box::new(AlpnMatcher::new(&mut rr.alpns)));
+        }
+
+        if !rr.snis.is_empty() {
+            cond_and.add(Box::new(SniMatcher::new(&mut rr.snis)));
+        }
 
-            if !rr.inbound_tags.is_empty() {
-                cond_and.add(Box::new(InboundTagMatcher::new(&mut rr.inbound_tags)));
+        if !rr.users.is_empty() {
+            cond_and.add(Box::new(UserMatcher::new(&mut rr.users)));
+        }
+
+        #[cfg(feature = "rule-process-name")]
+        if !rr.process_names.is_empty() {
+            cond_and.add(Box::new(ProcessNameMatcher::new(rr.process_names.clone())));
+        }
+
+        #[cfg(feature = "rule-process-name")]
+        if !rr.processes.is_empty() {
+            cond_and.add(Box::new(ProcessMatcher {
+                values: std::mem::take(&mut rr.processes),
+                full_path: false,
+            }));
+        }
+
+        #[cfg(feature = "rule-process-name")]
+        if !rr.process_paths.is_empty() {
+            cond_and.add(Box::new(ProcessMatcher {
+                values: std::mem::take(&mut rr.process_paths),
+                full_path: true,
+            }));
+        }
+
+        if !rr.protocols.is_empty() {
+            cond_and.add(Box::new(ProtocolMatcher {
+                values: std::mem::take(&mut rr.protocols),
+            }));
+        }
+
+        if !rr.rule_providers.is_empty() {
+            let mut cond_or = ConditionOr::new();
+            for name in rr.rule_providers.iter() {
+                match providers.get(name) {
+                    Some(provider) => cond_or.add(Box::new(ProviderMatcher {
+                        provider: provider.clone(),
+                    })),
+                    None => warn!("unknown rule provider {}", name),
+                }
             }
+            cond_and.add(Box::new(cond_or));
+        }
 
-            #[cfg(feature = "rule-process-name")]
-            if !rr.process_names.is_empty() {
-                cond_and.add(Box::new(ProcessNameMatcher::new(rr.process_names.clone())));
+        if !rr.logic.is_empty() {
+            let mut sub_conds = Vec::new();
+            for sub_rule in rr.sub_rules.iter_mut() {
+                sub_conds.push(Self::rule_condition(sub_rule, providers, mmdb_readers));
+            }
+            // Evaluated in order, stopping at the first one deciding the result.
+            match rr.logic.as_str() {
+                "and" => {
+                    for sub_cond in sub_conds {
+                        cond_and.add(Box::new(sub_cond));
+                    }
+                }
+                "or" => {
+                    let mut cond = ConditionOr::new();
+                    for sub_cond in sub_conds {
+                        cond.add(Box::new(sub_cond));
+                    }
+                    cond_and.add(Box::new(cond));
+                }
+                "not" => {
+                    let mut cond = ConditionAnd::new();
+                    for sub_cond in sub_conds {
+                        cond.add(Box::new(sub_cond));
+                    }
+                    cond_and.add(Box::new(ConditionNot {
+                        condition: Box::new(cond),
+                    }));
+                }
+                logic => warn!("unknown rule logic {}", logic),
             }
+        }
 
-            if cond_and.is_empty() {
+        cond_and
+    }
+
+    // The domains of the rules go to the domain index, the rest of a rule
+    // is its condition. The domains of sub rules stay in their conditions.
+    fn load_rules(
+        routing_rules: &mut [config::router::Rule],
+        providers: &HashMap<String, Arc<RuleProvider>>,
+        mmdb_readers: &mut HashMap<String, MmdbReader>,
+    ) -> RuleSet {
+        let mut rules = Vec::new();
+        let mut domains = DomainIndexBuilder::default();
+        let mut others = Vec::new();
+        for (index, rr) in routing_rules.iter_mut().enumerate() {
+            let protocol = Self::matches_protocol(rr);
+            let mut rr_domains = std::mem::take(&mut rr.domains);
+            rr_domains.append(&mut rr.set_domains);
+            let cond_and = Self::rule_condition(rr, providers, mmdb_readers);
+
+            if cond_and.is_empty() && rr_domains.is_empty() {
                 warn!("empty rule at target {}", rr.target_tag);
                 continue;
             }
 
+            let position = rules.len();
+            if rr_domains.is_empty() {
+                others.push(position);
+            }
+            for domain in rr_domains.iter() {
+                domains.add(position, domain);
+            }
+
             let tag = std::mem::take(&mut rr.target_tag);
-            rules.push(Rule::new(tag, Box::new(cond_and)));
+            let mut rule = Rule::new(index, tag, Box::new(cond_and));
+            rule.protocol = protocol;
+            rules.push(rule);
+        }
+        RuleSet {
+            rules,
+            domains: domains.build(),
+            others,
         }
     }
 
-    pub fn new(
+    // Keeps the providers and the geoip updater of the router being
+    // reloaded, if their configs didn't change.
+    fn load(
         router: &mut protobuf::MessageField<config::Router>,
-        dns_client: SyncDnsClient,
-    ) -> Self {
-        let mut rules: Vec<Rule> = Vec::new();
+        mut providers: HashMap<String, Arc<RuleProvider>>,
+        mut geoip_updater: Option<Arc<GeoipUpdater>>,
+    ) -> RouterReload {
+        let mut rules = RuleSet::default();
         let mut domain_resolve = false;
-        if let Some(router) = router.as_mut() {
-            Self::load_rules(&mut rules, &mut router.rules);
-            domain_resolve = router.domain_resolve;
+        let mut mmdb_readers = HashMap::new();
+        #[cfg(feature = "rule-process-name")]
+        let mut match_process = false;
+        match router.as_mut() {
+            Some(router) => {
+                #[cfg(feature = "rule-process-name")]
+                {
+                    match_process = Self::matches_process(&router.rules);
+                }
+                Self::load_providers(&mut providers, &router.rule_providers);
+                rules = Self::load_rules(&mut router.rules, &providers, &mut mmdb_readers);
+                Self::load_geoip_updater(&mut geoip_updater, router);
+                domain_resolve = router.domain_resolve;
+            }
+            None => {
+                providers.clear();
+                geoip_updater = None;
+            }
         }
-        Router {
+        RouterReload {
             rules,
             domain_resolve,
+            providers,
+            mmdb_readers,
+            geoip_updater,
+            #[cfg(feature = "rule-process-name")]
+            match_process,
+        }
+    }
+
+    pub fn new(
+        router: &mut protobuf::MessageField<config::Router>,
+        dns_client: SyncDnsClient,
+    ) -> Self {
+        let load = Self::load(router, HashMap::new(), None);
+        Router {
+            rules: ArcSwap::from_pointee(load.rules),
+            domain_resolve: load.domain_resolve,
             dns_client,
+            providers: load.providers,
+            mmdb_readers: std::sync::Mutex::new(load.mmdb_readers),
+            geoip_updater: load.geoip_updater,
+            inbound_finals: HashMap::new(),
+            #[cfg(feature = "rule-process-name")]
+            match_process: AtomicBool::new(load.match_process),
         }
     }
 
-    pub fn reload(&mut self, router: &mut protobuf::MessageField<config::Router>) -> Result<()> {
-        self.rules.clear();
-        if let Some(router) = router.as_mut() {
-            Self::load_rules(&mut self.rules, &mut router.rules);
-            self.domain_resolve = router.domain_resolve;
+    #[cfg(feature = "rule-process-name")]
+    fn matches_process(rules: &[config::router::Rule]) -> bool {
+        rules.iter().any(|x| {
+            !x.process_names.is_empty()
+                || !x.processes.is_empty()
+                || !x.process_paths.is_empty()
+                || Self::matches_process(&x.sub_rules)
+        })
+    }
+
+    fn matches_protocol(rule: &config::router::Rule) -> bool {
+        !rule.protocols.is_empty() || rule.sub_rules.iter().any(Self::matches_protocol)
+    }
+
+    /// Whether the protocol of the session has to be sniffed to pick its
+    /// route, that is a rule matching protocols comes before any other rule
+    /// matching the session.
+    pub fn needs_protocol(&self, sess: &Session) -> bool {
+        if sess.sniffed_protocol.is_some() {
+            return false;
         }
+        let rules = self.rules.load();
+        for rule in rules.candidates(sess) {
+            if rule.protocol {
+                return true;
+            }
+            if rule.apply(sess) {
+                return false;
+            }
+        }
+        false
+    }
+
+    /// Whether the rules need the process of the sessions.
+    #[cfg(feature = "rule-process-name")]
+    pub fn match_process(&self) -> bool {
+        self.match_process.load(Ordering::Relaxed)
+    }
+
+    /// Loads the rules of a config for `apply_reload`, which with many
+    /// rules takes a while. Only the router is read, the sessions being
+    /// routed meanwhile aren't held up.
+    pub fn prepare_reload(
+        &self,
+        router: &mut protobuf::MessageField<config::Router>,
+    ) -> RouterReload {
+        Self::load(router, self.providers.clone(), self.geoip_updater.clone())
+    }
+
+    /// Swaps in the rules loaded by `prepare_reload`.
+    pub fn apply_reload(&mut self, reload: RouterReload) {
+        self.rules.store(Arc::new(reload.rules));
+        self.domain_resolve = reload.domain_resolve;
+        self.providers = reload.providers;
+        *self.mmdb_readers.get_mut().unwrap() = reload.mmdb_readers;
+        self.geoip_updater = reload.geoip_updater;
+        #[cfg(feature = "rule-process-name")]
+        {
+            *self.match_process.get_mut() = reload.match_process;
+        }
+    }
+
+    /// Replaces the rules and nothing else of the router. The rules may
+    /// use the rule providers and the databases of the router, the
+    /// sessions being routed aren't held up by the replacement.
+    pub fn replace_rules(&self, routing_rules: &mut [config::router::Rule]) {
+        let rules = Self::load_rules(
+            routing_rules,
+            &self.providers,
+            &mut self.mmdb_readers.lock().unwrap(),
+        );
+        #[cfg(feature = "rule-process-name")]
+        self.match_process
+            .store(Self::matches_process(routing_rules), Ordering::Relaxed);
+        self.rules.store(Arc::new(rules));
+    }
+
+    /// Loads the final outbounds of the inbounds, which route the sessions
+    /// of an inbound no rule matches.
+    pub fn load_inbound_finals(&mut self, inbounds: &[config::Inbound]) {
+        self.inbound_finals = inbounds
+            .iter()
+            .filter(|x| !x.final_outbound.is_empty())
+            .map(|x| (x.tag.clone(), x.final_outbound.clone()))
+            .collect();
+    }
+
+    /// The final outbound of an inbound.
+    pub fn inbound_final(&self, inbound_tag: &str) -> Option<&String> {
+        self.inbound_finals.get(inbound_tag)
+    }
+
+    pub fn rule_providers(&self) -> Vec<Arc<RuleProvider>> {
+        self.providers.values().cloned().collect()
+    }
+
+    pub fn rule_provider(&self, name: &str) -> Option<Arc<RuleProvider>> {
+        self.providers.get(name).cloned()
+    }
+
+    pub fn geoip_updater(&self) -> Option<Arc<GeoipUpdater>> {
+        self.geoip_updater.clone()
+    }
+
+    /// Reopens a database the rules match with, after the file was
+    /// replaced. The rules keep the database in use if it fails.
+    pub fn reload_mmdb(&self, file: &str) -> Result<()> {
+        let Some(reader) = self.mmdb_readers.lock().unwrap().get(file).cloned() else {
+            return Ok(());
+        };
+        let new_reader = maxminddb::Reader::open_mmap(file)?;
+        *reader.write().unwrap() = Some(Arc::new(new_reader));
         Ok(())
     }
 
+    pub async fn pick_route<'a>(&'a self, sess: &'a Session) -> Result<Option<String>> {
+        Ok(self.pick_rule(sess).await?.map(|(_, target)| target))
+    }
+
+    /// Returns the position in the config of the rule a session matches,
+    /// along with its target.
     #[async_recursion]
-    pub async fn pick_route<'a>(&'a self, sess: &'a Session) -> Result<Option<&'a String>> {
+    pub async fn pick_rule<'a>(&'a self, sess: &'a Session) -> Result<Option<(usize, String)>> {
+        let rules = self.rules.load_full();
+        let rule = self.match_rule(&rules, sess).await?;
+        if let Some(rule) = rule {
+            rule.hits.fetch_add(1, Ordering::Relaxed);
+        }
+        Ok(rule.map(|x| (x.index, x.target.clone())))
+    }
+
+    /// Like `pick_rule`, without counting the session in the hits of the
+    /// rule, for sessions which aren't dispatched.
+    pub async fn explain_rule(&self, sess: &Session) -> Result<Option<(usize, String)>> {
+        let rules = self.rules.load_full();
+        let rule = self.match_rule(&rules, sess).await?;
+        Ok(rule.map(|x| (x.index, x.target.clone())))
+    }
+
+    pub fn rule_hits(&self) -> Vec<RuleHits> {
+        self.rules
+            .load()
+            .rules
+            .iter()
+            .map(|x| RuleHits {
+                index: x.index,
+                target: x.target.clone(),
+                hits: x.hits.load(Ordering::Relaxed),
+            })
+            .collect()
+    }
+
+    async fn match_rule<'a>(&self, rules: &'a RuleSet, sess: &Session) -> Result<Option<&'a Rule>> {
         let effective_dest = &sess.destination;
-        for rule in &self.rules {
-            if rule.apply(sess) {
-                return Ok(Some(&rule.target));
-            }
+        if let Some(rule) = rules.candidates(sess).find(|x| x.apply(sess)) {
+            return Ok(Some(rule));
         }
         if effective_dest.is_domain() && self.domain_resolve && !sess.skip_resolve {
             debug!("resolve routing domain={:?}", effective_dest.domain());
@@ -593,10 +1615,8 @@ impl Router {
                 new_sess.tls_sniffed_domain = None;
                 new_sess.http_sniffed_domain = None;
                 debug!("re-matching with resolved ip={}", ips[0]);
-                for rule in &self.rules {
-                    if rule.apply(&new_sess) {
-                        return Ok(Some(&rule.target));
-                    }
+                if let Some(rule) = rules.candidates(&new_sess).find(|x| x.apply(&new_sess)) {
+                    return Ok(Some(rule));
                 }
             }
         }
@@ -642,19 +1662,199 @@ mod tests {
         sess.destination = SocksAddr::Domain("www.google.com".to_string(), 22);
         assert!(m.apply(&sess));
 
-        // test invalid port ranges
-        let m = PortRangeMatcher::new("22-21");
-        assert!(m.is_err());
-        let m = PortRangeMatcher::new("22");
-        assert!(m.is_err());
-        let m = PortRangeMatcher::new("22-");
-        assert!(m.is_err());
-        let m = PortRangeMatcher::new("-22");
-        assert!(m.is_err());
-        let m = PortRangeMatcher::new("22-abc");
-        assert!(m.is_err());
-        let m = PortRangeMatcher::new("22-23-24");
-        assert!(m.is_err());
+        // test port lists
+        let m = PortMatcher::new(&vec!["80,443,6881-6889".to_string(), "22".to_string()]);
+        for port in [22, 80, 443, 6881, 6885, 6889] {
+            sess.destination = SocksAddr::Domain("www.google.com".to_string(), port);
+            assert!(m.apply(&sess));
+        }
+        for port in [81, 6880, 6890] {
+            sess.destination = SocksAddr::Domain("www.google.com".to_string(), port);
+            assert!(!m.apply(&sess));
+        }
+
+        // test invalid port lists, which are left out
+        let m = PortMatcher::new(&vec!["22-21".to_string(), "443,x".to_string()]);
+        sess.destination = SocksAddr::Domain("www.google.com".to_string(), 443);
+        assert!(!m.apply(&sess));
+    }
+
+    #[test]
+    fn test_source_matchers() {
+        let mut sess = Session {
+            source: "192.168.1.50:40000".parse().unwrap(),
+            ..Default::default()
+        };
+        let ip = SourceIpMatcher::new(&["192.168.1.50".to_string(), "fd00::/8".to_string()]);
+        let port = SourcePortMatcher::new(&["22".to_string(), "40000-40010".to_string()]);
+        assert!(ip.apply(&sess));
+        assert!(port.apply(&sess));
+        sess.source = "[::ffff:192.168.1.50]:22".parse().unwrap();
+        assert!(ip.apply(&sess));
+        assert!(port.apply(&sess));
+        sess.source = "[fd00::1]:41000".parse().unwrap();
+        assert!(ip.apply(&sess));
+        assert!(!port.apply(&sess));
+        sess.source = "192.168.1.51:22".parse().unwrap();
+        assert!(!ip.apply(&sess));
+    }
+
+    #[test]
+    fn test_logical_rules() {
+        let mut domain = config::router::rule::Domain::new();
+        domain.type_ = protobuf::EnumOrUnknown::new(config::router::rule::domain::Type::DOMAIN);
+        domain.value = "example.com".to_string();
+        let mut source = config::router::Rule::new();
+        source.source_ip_cidrs.push("192.168.1.50".to_string());
+        let mut not = config::router::Rule::new();
+        not.logic = "not".to_string();
+        not.sub_rules.push(source);
+        let mut rr = config::router::Rule::new();
+        rr.domains.push(domain);
+        rr.logic = "and".to_string();
+        rr.sub_rules.push(not);
+        let cond = Router::rule_condition(&mut rr, &HashMap::new(), &mut HashMap::new());
+
+        let mut sess = Session {
+            source: "192.168.1.51:40000".parse().unwrap(),
+            destination: SocksAddr::Domain("www.example.com".to_string(), 443),
+            ..Default::default()
+        };
+        assert!(cond.apply(&sess));
+        sess.source = "192.168.1.50:40000".parse().unwrap();
+        assert!(!cond.apply(&sess));
+        sess.source = "192.168.1.51:40000".parse().unwrap();
+        sess.destination = SocksAddr::Domain("www.google.com".to_string(), 443);
+        assert!(!cond.apply(&sess));
+
+        let mut rr = config::router::Rule::new();
+        rr.logic = "or".to_string();
+        for port in ["22", "443"] {
+            let mut sub_rule = config::router::Rule::new();
+            sub_rule.port_ranges.push(port.to_string());
+            rr.sub_rules.push(sub_rule);
+        }
+        let cond = Router::rule_condition(&mut rr, &HashMap::new(), &mut HashMap::new());
+        assert!(cond.apply(&sess));
+        sess.destination = SocksAddr::Domain("www.google.com".to_string(), 80);
+        assert!(!cond.apply(&sess));
+    }
+
+    #[test]
+    fn test_protocol_matcher() {
+        let mut sess = Session::default();
+        let m = ProtocolMatcher {
+            values: vec!["bittorrent".to_string(), "quic".to_string()],
+        };
+        assert!(!m.apply(&sess));
+        sess.sniffed_protocol = Some("quic");
+        assert!(m.apply(&sess));
+        sess.sniffed_protocol = Some("tls");
+        assert!(!m.apply(&sess));
+
+        let mut rr = config::router::Rule::new();
+        rr.logic = "not".to_string();
+        rr.sub_rules.push(config::router::Rule::new());
+        assert!(!Router::matches_protocol(&rr));
+        rr.sub_rules[0].protocols.push("tls".to_string());
+        assert!(Router::matches_protocol(&rr));
+    }
+
+    #[test]
+    fn test_alpn_matcher() {
+        let mut sess = Session::default();
+        let m = AlpnMatcher::new(&mut ["h3".to_string()]);
+        assert!(!m.apply(&sess));
+        sess.alpn = Some("h3".to_string());
+        assert!(m.apply(&sess));
+        sess.alpn = Some("leaf".to_string());
+        assert!(!m.apply(&sess));
+    }
+
+    #[cfg(feature = "rule-process-name")]
+    #[test]
+    fn test_process_matcher() {
+        let mut sess = Session::default();
+        let name = ProcessMatcher {
+            values: vec!["Firefox.exe".to_string()],
+            full_path: false,
+        };
+        let path = ProcessMatcher {
+            values: vec!["/usr/bin/curl".to_string()],
+            full_path: true,
+        };
+        assert!(!name.apply(&sess));
+        sess.process_name = Some(r"C:\Program Files\Mozilla Firefox\firefox.exe".to_string());
+        assert!(name.apply(&sess));
+        assert!(!path.apply(&sess));
+        sess.process_name = Some("/usr/bin/curl".to_string());
+        assert!(!name.apply(&sess));
+        assert!(path.apply(&sess));
+    }
+
+    #[test]
+    fn test_domain_set() {
+        use config::router::rule::{domain::Type, Domain};
+
+        let domains: Vec<Domain> = [
+            (Type::FULL, "internal.example.com"),
+            (Type::DOMAIN, "corp.example"),
+            (Type::PLAIN, "intranet"),
+        ]
+        .into_iter()
+        .map(|(type_, value)| {
+            let mut domain = Domain::new();
+            domain.type_ = protobuf::EnumOrUnknown::new(type_);
+            domain.value = value.to_string();
+            domain
+        })
+        .collect();
+        let set = DomainSet::new(&domains);
+        assert_eq!(
+            set.find("internal.example.com"),
+            Some("internal.example.com")
+        );
+        assert_eq!(set.find("www.internal.example.com"), None);
+        assert_eq!(set.find("corp.example"), Some("corp.example"));
+        assert_eq!(set.find("Git.Corp.Example."), Some("corp.example"));
+        assert_eq!(set.find("xcorp.example"), None);
+        assert_eq!(set.find("intranet.example.org"), Some("intranet"));
+        assert_eq!(set.find("example.org"), None);
+    }
+
+    #[test]
+    fn test_sni_matcher() {
+        use config::router::rule::{domain::Type, Domain};
+
+        let mut full = Domain::new();
+        full.type_ = protobuf::EnumOrUnknown::new(Type::FULL);
+        full.value = "internal.example.com".to_string();
+        let mut suffix = Domain::new();
+        suffix.type_ = protobuf::EnumOrUnknown::new(Type::DOMAIN);
+        suffix.value = "corp.example.org".to_string();
+        let m = SniMatcher::new(&mut [full, suffix]);
+
+        let mut sess = Session::default();
+        assert!(!m.apply(&sess));
+        sess.sni = Some("internal.example.com".to_string());
+        assert!(m.apply(&sess));
+        sess.sni = Some("www.internal.example.com".to_string());
+        assert!(!m.apply(&sess));
+        sess.sni = Some("git.corp.example.org".to_string());
+        assert!(m.apply(&sess));
+        sess.sni = Some("example.org".to_string());
+        assert!(!m.apply(&sess));
+    }
+
+    #[test]
+    fn test_user_matcher() {
+        let m = UserMatcher::new(&mut ["alice".to_string()]);
+        let mut sess = Session::default();
+        assert!(!m.apply(&sess));
+        sess.user = Some("alice".to_string());
+        assert!(m.apply(&sess));
+        sess.user = Some("bob".to_string());
+        assert!(!m.apply(&sess));
     }
 
     #[test]
@@ -708,4 +1908,174 @@ mod tests {
         sess.destination = SocksAddr::from(("10.0.0.2".parse::<IpAddr>().unwrap(), 80));
         assert!(!m.apply(&sess));
     }
+
+    #[test]
+    fn test_ip_set() {
+        let cidrs: Vec<IpCidr> = ["10.0.0.0/8", "10.1.0.0/16", "11.0.0.0/8", "2001:db8::/32"]
+            .iter()
+            .map(|x| x.parse().unwrap())
+            .collect();
+        let set = IpSet::new(&cidrs);
+        assert_eq!(
+            set.v4,
+            vec![(0x0a000000, 0x0affffff), (0x0b000000, 0x0bffffff)]
+        );
+        for ip in ["10.0.0.0", "10.1.2.3", "11.255.255.255", "2001:db8::1"] {
+            assert!(set.contains(&ip.parse().unwrap()), "{}", ip);
+        }
+        for ip in ["9.255.255.255", "12.0.0.0", "2001:db9::1", "::"] {
+            assert!(!set.contains(&ip.parse().unwrap()), "{}", ip);
+        }
+        assert!(!IpSet::default().contains(&"10.0.0.1".parse().unwrap()));
+    }
+
+    #[test]
+    fn test_domain_set_matcher() {
+        use config::router::rule::domain::Type;
+
+        let domain = |ty, value: &str| {
+            let mut domain = config::router::rule::Domain::new();
+            domain.type_ = protobuf::EnumOrUnknown::new(ty);
+            domain.value = value.to_string();
+            domain
+        };
+        let mut domains = vec![domain(Type::FULL, "example.org")];
+        let set_domains = vec![
+            domain(Type::DOMAIN, "ads.com"),
+            domain(Type::PLAIN, "tracker"),
+        ];
+        let m = DomainMatcher::new(&mut domains, &set_domains);
+        for (host, matched) in [
+            ("example.org", true),
+            ("x.ads.com", true),
+            ("tracker.net", true),
+            ("badads.com", false),
+        ] {
+            let sess = Session {
+                destination: SocksAddr::Domain(host.to_string(), 443),
+                ..Default::default()
+            };
+            assert_eq!(m.apply(&sess), matched, "{}", host);
+        }
+    }
+
+    fn domain(ty: config::router::rule::domain::Type, value: &str) -> config::router::rule::Domain {
+        let mut domain = config::router::rule::Domain::new();
+        domain.type_ = protobuf::EnumOrUnknown::new(ty);
+        domain.value = value.to_string();
+        domain
+    }
+
+    #[test]
+    fn test_domain_index() {
+        use config::router::rule::domain::Type;
+
+        let mut builder = DomainIndexBuilder::default();
+        builder.add(0, &domain(Type::DOMAIN, "google.com"));
+        builder.add(1, &domain(Type::FULL, "www.google.com"));
+        builder.add(2, &domain(Type::PLAIN, "oogle"));
+        builder.add(2, &domain(Type::PLAIN, "goo"));
+        builder.add(3, &domain(Type::DOMAIN, "com"));
+        builder.add(4, &domain(Type::PLAIN, "goo"));
+        builder.add(5, &domain(Type::DOMAIN, "Example.ORG"));
+        let index = builder.build();
+        assert_eq!(index.find("www.google.com"), vec![0, 1, 2, 3, 4]);
+        assert_eq!(index.find("google.com."), vec![0, 2, 3, 4]);
+        assert_eq!(index.find("gle.com"), vec![3]);
+        assert_eq!(index.find("mail.EXAMPLE.org"), vec![5]);
+        assert!(index.find("example.net").is_empty());
+        assert!(DomainIndex::default().find("google.com").is_empty());
+    }
+
+    #[test]
+    fn test_rule_set() {
+        use config::router::rule::domain::Type;
+
+        let mut port = config::router::Rule::new();
+        port.port_ranges.push("22".to_string());
+        port.target_tag = "ssh".to_string();
+        let mut suffix = config::router::Rule::new();
+        suffix.domains.push(domain(Type::DOMAIN, "google.com"));
+        suffix.target_tag = "suffix".to_string();
+        let mut keyword = config::router::Rule::new();
+        keyword.domains.push(domain(Type::PLAIN, "google"));
+        keyword.port_ranges.push("443".to_string());
+        keyword.target_tag = "keyword".to_string();
+        let mut network = config::router::Rule::new();
+        network.networks.push("tcp".to_string());
+        network.target_tag = "tcp".to_string();
+        let mut rules = vec![port, suffix, keyword, network];
+        let rules = Router::load_rules(&mut rules, &HashMap::new(), &mut HashMap::new());
+        assert_eq!(rules.others, vec![0, 3]);
+
+        let target = |host: &str, port: u16| {
+            let sess = Session {
+                destination: SocksAddr::Domain(host.to_string(), port),
+                ..Default::default()
+            };
+            rules
+                .candidates(&sess)
+                .find(|x| x.apply(&sess))
+                .map(|x| x.target.clone())
+        };
+        assert_eq!(target("www.google.com", 22).unwrap(), "ssh");
+        assert_eq!(target("www.google.com", 443).unwrap(), "suffix");
+        assert_eq!(target("google.net", 443).unwrap(), "keyword");
+        assert_eq!(target("google.net", 80).unwrap(), "tcp");
+        let sess = Session {
+            destination: SocksAddr::from(("1.1.1.1".parse::<IpAddr>().unwrap(), 443)),
+            ..Default::default()
+        };
+        let candidates: Vec<usize> = rules.candidates(&sess).map(|x| x.index).collect();
+        assert_eq!(candidates, vec![0, 3]);
+    }
+
+    fn large_domain_index() -> DomainIndex {
+        use config::router::rule::domain::Type;
+
+        let mut builder = DomainIndexBuilder::default();
+        for i in 0..50000 {
+            let (ty, value) = match i % 5 {
+                0 => (Type::PLAIN, format!("kw{}x", i)),
+                1 => (Type::FULL, format!("www.site{}.net", i)),
+                _ => (Type::DOMAIN, format!("site{}.com", i)),
+            };
+            builder.add(i, &domain(ty, &value));
+        }
+        builder.build()
+    }
+
+    #[test]
+    fn test_large_domain_index() {
+        let index = large_domain_index();
+        assert_eq!(index.find("cdn.site49999.com"), vec![49999]);
+        assert_eq!(index.find("www.site1.net"), vec![1]);
+        assert_eq!(index.find("a.kw10x.org"), vec![10]);
+        assert!(index.find("nomatch.example.org").is_empty());
+    }
+
+    // Matches domains against a large index and checks a match takes under
+    // a microsecond, run it with
+    // `cargo test --release -- --ignored --nocapture bench_domain_index`.
+    #[test]
+    #[ignore]
+    fn bench_domain_index() {
+        let index = large_domain_index();
+        let hosts = [
+            "cdn.site12347.com",
+            "www.site1.net",
+            "a.kw10x.org",
+            "nomatch.example.org",
+        ];
+        let rounds = 1000000;
+        let start = std::time::Instant::now();
+        for i in 0..rounds {
+            std::hint::black_box(index.find(hosts[i % hosts.len()]));
+        }
+        let per_find = start.elapsed() / rounds as u32;
+        println!("{:?} per find", per_find);
+        if !cfg!(debug_assertions) {
+            assert!(per_find < std::time::Duration::from_micros(1));
+        }
+    }
 }
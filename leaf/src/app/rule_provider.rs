@@ -0,0 +1,586 @@
+//! Rule providers, lists of domains and IPs downloaded from a URL which the
+//! rules reference by name. A list is kept in a cache file, loaded from it
+//! at start and refreshed on an interval, a refresh which fails leaves the
+//! rules in use as they were.
+//!
+//! The lists are either text, an entry per line, or the payload of a clash
+//! provider in yaml. An entry is a domain, matched fully, a domain starting
+//! with `+.`, `.` or `*.`, matched as a suffix, an IP or a CIDR, or a clash
+//! classical rule of the types `DOMAIN`, `DOMAIN-SUFFIX`, `DOMAIN-KEYWORD`,
+//! `DOMAIN-REGEX`, `IP-CIDR` and `IP-CIDR6`.
+
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
+
+use anyhow::{anyhow, Result};
+use cidr::IpCidr;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::sync::RwLock;
+use tracing::{debug, info, warn};
+
+use crate::app::outbound::manager::OutboundManager;
+use crate::app::router::{DomainSet, Router};
+use crate::app::SyncDnsClient;
+use crate::config::router::rule::{domain::Type, Domain};
+use crate::config::router::RuleProvider as Config;
+use crate::proxy::AnyStream;
+use crate::session::{Session, SocksAddr};
+
+// The refresh interval of a provider which doesn't set one.
+const DEFAULT_INTERVAL: Duration = Duration::from_secs(86400);
+// How long after a failed refresh it's tried again.
+const RETRY_INTERVAL: Duration = Duration::from_secs(300);
+// How often the providers are checked for a refresh.
+const CHECK_INTERVAL: Duration = Duration::from_secs(60);
+const DOWNLOAD_TIMEOUT: Duration = Duration::from_secs(60);
+const MAX_RESPONSE_SIZE: u64 = 64 * 1024 * 1024;
+
+/// The domains and IPs of a list.
+#[derive(Default)]
+pub struct ProviderRules {
+    domains: DomainSet,
+    ips: Vec<IpCidr>,
+    len: usize,
+}
+
+impl ProviderRules {
+    pub fn parse(text: &str, yaml: bool) -> Self {
+        let entries: Vec<&str> = if yaml {
+            yaml_payload(text)
+        } else {
+            text.lines().map(|x| strip_comment(x).trim()).collect()
+        };
+        let mut domains = Vec::new();
+        let mut ips = Vec::new();
+        for entry in entries.into_iter().filter(|x| !x.is_empty()) {
+            match parse_entry(entry) {
+                Some(Entry::Domain(ty, value)) => {
+                    let mut domain = Domain::new();
+                    domain.type_ = protobuf::EnumOrUnknown::new(ty);
+                    domain.value = value.to_string();
+                    domains.push(domain);
+                }
+                Some(Entry::Ip(cidr)) => ips.push(cidr),
+                None => debug!("unsupported rule provider entry {}", entry),
+            }
+        }
+        ProviderRules {
+            domains: DomainSet::new(&domains),
+            len: domains.len() + ips.len(),
+            ips,
+        }
+    }
+
+    /// The number of entries.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn matches(&self, sess: &Session) -> bool {
+        let destination = sess
+            .destination_for_routing()
+            .unwrap_or_else(|_| std::borrow::Cow::Borrowed(&sess.destination));
+        if let Some(domain) = destination.domain() {
+            return self.domains.find(domain).is_some();
+        }
+        match destination.ip() {
+            Some(ip) => self.ips.iter().any(|x| x.contains(&ip)),
+            None => false,
+        }
+    }
+}
+
+enum Entry<'a> {
+    Domain(Type, &'a str),
+    Ip(IpCidr),
+}
+
+fn strip_comment(line: &str) -> &str {
+    line.split_once('#').map_or(line, |(x, _)| x)
+}
+
+fn parse_entry(entry: &str) -> Option<Entry<'_>> {
+    if let Some((ty, rest)) = entry.split_once(',') {
+        // The options after the value, such as no-resolve, are ignored.
+        let value = rest.split(',').next().unwrap_or_default().trim();
+        return match ty.trim().to_ascii_uppercase().as_str() {
+            "DOMAIN" => Some(Entry::Domain(Type::FULL, value)),
+            "DOMAIN-SUFFIX" => Some(Entry::Domain(Type::DOMAIN, value)),
+            "DOMAIN-KEYWORD" => Some(Entry::Domain(Type::PLAIN, value)),
+            "DOMAIN-REGEX" => Some(Entry::Domain(Type::REGEX, value)),
+            "IP-CIDR" | "IP-CIDR6" => parse_cidr(value).map(Entry::Ip),
+            _ => None,
+        };
+    }
+    if let Some(cidr) = parse_cidr(entry) {
+        return Some(Entry::Ip(cidr));
+    }
+    // A wildcard for a single label in clash, matched as a suffix here.
+    for prefix in ["+.", "*.", "."] {
+        if let Some(suffix) = entry.strip_prefix(prefix) {
+            return Some(Entry::Domain(Type::DOMAIN, suffix));
+        }
+    }
+    Some(Entry::Domain(Type::FULL, entry))
+}
+
+fn parse_cidr(value: &str) -> Option<IpCidr> {
+    match value.parse::<IpAddr>() {
+        Ok(ip) => Some(IpCidr::new_host(ip)),
+        Err(_) => value.parse::<IpCidr>().ok(),
+    }
+}
+
+// The items of the payload list of a clash provider, the other keys are
+// ignored.
+fn yaml_payload(text: &str) -> Vec<&str> {
+    let mut items = Vec::new();
+    let mut in_payload = false;
+    for line in text.lines() {
+        let trimmed = line.trim();
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            continue;
+        }
+        if !line.starts_with([' ', '\t', '-']) {
+            in_payload = trimmed.strip_suffix(':').map(str::trim_end) == Some("payload");
+            continue;
+        }
+        if !in_payload {
+            continue;
+        }
+        if let Some(item) = trimmed.strip_prefix('-') {
+            let item = item.trim();
+            let item = match item.chars().next() {
+                Some(q @ ('\'' | '"')) => item[1..].split(q).next().unwrap_or_default(),
+                _ => strip_comment(item).trim(),
+            };
+            items.push(item);
+        }
+    }
+    items
+}
+
+struct State {
+    // When the rules in use were downloaded.
+    updated: Option<SystemTime>,
+    attempted: Option<Instant>,
+}
+
+pub struct RuleProvider {
+    config: Config,
+    interval: Duration,
+    yaml: bool,
+    path: PathBuf,
+    // Replaced as a whole by a refresh, the sessions being matched keep the
+    // rules they started with.
+    rules: std::sync::RwLock<Arc<ProviderRules>>,
+    state: std::sync::Mutex<State>,
+    // Held by a refresh, a forced refresh waits for the one running.
+    refresh_lock: tokio::sync::Mutex<()>,
+}
+
+impl RuleProvider {
+    /// Creates a provider with the rules of its cache file, if there's one.
+    pub fn new(config: &Config) -> Self {
+        let yaml = match config.format.as_str() {
+            "yaml" => true,
+            "text" => false,
+            "" => [&config.path, &config.url]
+                .iter()
+                .any(|x| x.ends_with(".yaml") || x.ends_with(".yml")),
+            format => {
+                warn!("unknown rule provider format {}, read as text", format);
+                false
+            }
+        };
+        let asset_loc = Path::new(&*crate::option::ASSET_LOCATION);
+        let path = if config.path.is_empty() {
+            asset_loc.join(format!("{}.rules", config.name))
+        } else {
+            asset_loc.join(&config.path)
+        };
+        let interval = match config.interval {
+            0 => DEFAULT_INTERVAL,
+            secs => Duration::from_secs(secs as u64),
+        };
+        let mut rules = ProviderRules::default();
+        let mut updated = None;
+        match std::fs::read_to_string(&path) {
+            Ok(text) => {
+                rules = ProviderRules::parse(&text, yaml);
+                updated = std::fs::metadata(&path).and_then(|x| x.modified()).ok();
+                debug!(
+                    "loaded {} rules of provider {} from {}",
+                    rules.len(),
+                    config.name,
+                    path.display()
+                );
+            }
+            Err(e) => debug!("no cache of rule provider {}: {}", config.name, e),
+        }
+        RuleProvider {
+            config: config.clone(),
+            interval,
+            yaml,
+            path,
+            rules: std::sync::RwLock::new(Arc::new(rules)),
+            state: std::sync::Mutex::new(State {
+                updated,
+                attempted: None,
+            }),
+            refresh_lock: tokio::sync::Mutex::new(()),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.config.name
+    }
+
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    pub fn rules(&self) -> Arc<ProviderRules> {
+        self.rules.read().unwrap().clone()
+    }
+
+    /// Whether the rules are older than the interval, a failed refresh is
+    /// retried after a while.
+    pub fn is_due(&self) -> bool {
+        let state = self.state.lock().unwrap();
+        if state
+            .attempted
+            .is_some_and(|x| x.elapsed() < RETRY_INTERVAL)
+        {
+            return false;
+        }
+        match state.updated {
+            Some(x) => x.elapsed().unwrap_or_default() >= self.interval,
+            None => true,
+        }
+    }
+
+    /// Downloads the list, returns the number of entries in it. The rules
+    /// and the cache are only replaced if it succeeds.
+    pub async fn refresh(
+        &self,
+        outbound_manager: &Arc<RwLock<OutboundManager>>,
+        dns_client: SyncDnsClient,
+    ) -> Result<usize> {
+        let _guard = self.refresh_lock.lock().await;
+        self.state.lock().unwrap().attempted = Some(Instant::now());
+        let handler = if self.config.outbound.is_empty() {
+            None
+        } else {
+            let handler = outbound_manager.read().await.get(&self.config.outbound);
+            Some(handler.ok_or_else(|| anyhow!("outbound {} not found", self.config.outbound))?)
+        };
+        let body = tokio::time::timeout(
+            DOWNLOAD_TIMEOUT,
+            download(&self.config.url, handler, dns_client),
+        )
+        .await
+        .map_err(|_| anyhow!("download timed out"))??;
+        let text = String::from_utf8(body).map_err(|_| anyhow!("invalid utf-8 in the list"))?;
+        let rules = ProviderRules::parse(&text, self.yaml);
+        // More likely an error page than a list emptied on purpose.
+        if rules.is_empty() {
+            return Err(anyhow!("no rules in the list"));
+        }
+        let tmp = self.path.with_extension("tmp");
+        tokio::fs::write(&tmp, &text).await?;
+        tokio::fs::rename(&tmp, &self.path).await?;
+        let len = rules.len();
+        *self.rules.write().unwrap() = Arc::new(rules);
+        let mut state = self.state.lock().unwrap();
+        state.updated = Some(SystemTime::now());
+        state.attempted = None;
+        Ok(len)
+    }
+}
+
+/// Refreshes the providers of the router when they're due.
+pub fn refresh_task(
+    router: Arc<RwLock<Router>>,
+    outbound_manager: Arc<RwLock<OutboundManager>>,
+    dns_client: SyncDnsClient,
+) -> crate::Runner {
+    Box::pin(async move {
+        loop {
+            // Read on each round, the providers change with a reload.
+            let providers = router.read().await.rule_providers();
+            for provider in providers.iter().filter(|x| x.is_due()) {
+                match provider
+                    .refresh(&outbound_manager, dns_client.clone())
+                    .await
+                {
+                    Ok(n) => info!("refreshed rule provider {}, {} rules", provider.name(), n),
+                    Err(e) => warn!("refresh rule provider {} failed: {}", provider.name(), e),
+                }
+            }
+            tokio::time::sleep(CHECK_INTERVAL).await;
+        }
+    })
+}
+
+struct Url<'a> {
+    tls: bool,
+    host: &'a str,
+    port: u16,
+    path: &'a str,
+}
+
+fn default_port(tls: bool) -> u16 {
+    if tls {
+        443
+    } else {
+        80
+    }
+}
+
+fn parse_url(url: &str) -> Result<Url<'_>> {
+    let (tls, rest) = if let Some(rest) = url.strip_prefix("https://") {
+        (true, rest)
+    } else if let Some(rest) = url.strip_prefix("http://") {
+        (false, rest)
+    } else {
+        return Err(anyhow!("unsupported url {}", url));
+    };
+    let (authority, path) = match rest.find('/') {
+        Some(i) => rest.split_at(i),
+        None => (rest, "/"),
+    };
+    let (host, port) = if let Some(v6) = authority.strip_prefix('[') {
+        let (host, port) = v6
+            .split_once(']')
+            .ok_or_else(|| anyhow!("invalid url {}", url))?;
+        (host, port.strip_prefix(':'))
+    } else {
+        match authority.rsplit_once(':') {
+            Some((host, port)) => (host, Some(port)),
+            None => (authority, None),
+        }
+    };
+    let port = match port {
+        Some(port) => port.parse().map_err(|_| anyhow!("invalid url {}", url))?,
+        None => default_port(tls),
+    };
+    if host.is_empty() {
+        return Err(anyhow!("invalid url {}", url));
+    }
+    Ok(Url {
+        tls,
+        host,
+        port,
+        path,
+    })
+}
+
+// Fetches the body of the URL, through the outbound if there is one.
+pub(crate) async fn download(
+    url: &str,
+    handler: Option<crate::proxy::AnyOutboundHandler>,
+    dns_client: SyncDnsClient,
+) -> Result<Vec<u8>> {
+    let url = parse_url(url)?;
+    let sess = Session {
+        destination: SocksAddr::try_from((url.host, url.port))?,
+        new_conn_once: true,
+        ..Default::default()
+    };
+    let stream: AnyStream = match handler {
+        Some(h) => {
+            let stream =
+                crate::proxy::connect_stream_outbound(&sess, dns_client.clone(), &h).await?;
+            h.stream()?.handle(&sess, None, stream).await?
+        }
+        None => {
+            crate::proxy::new_tcp_stream(dns_client.clone(), &url.host.to_string(), &url.port)
+                .await?
+        }
+    };
+    let mut stream = if url.tls {
+        tls_connect(&sess, stream, dns_client).await?
+    } else {
+        stream
+    };
+    let host = if url.port == default_port(url.tls) {
+        url.host.to_string()
+    } else {
+        format!("{}:{}", url.host, url.port)
+    };
+    let request = format!(
+        "GET {} HTTP/1.1\r\nHost: {}\r\nUser-Agent: leaf\r\nAccept: */*\r\nAccept-Encoding: identity\r\nConnection: close\r\n\r\n",
+        url.path, host
+    );
+    stream.write_all(request.as_bytes()).await?;
+    let mut response = Vec::new();
+    (&mut stream)
+        .take(MAX_RESPONSE_SIZE)
+        .read_to_end(&mut response)
+        .await?;
+    let _ = stream.shutdown().await;
+    parse_response(&response)
+}
+
+#[cfg(feature = "outbound-tls")]
+async fn tls_connect(
+    sess: &Session,
+    stream: AnyStream,
+    dns_client: SyncDnsClient,
+) -> Result<AnyStream> {
+    use crate::proxy::OutboundStreamHandler;
+
+    let tls_handler = crate::proxy::tls::outbound::StreamHandler::new(
+        String::from(""),
+        vec![],
+        None,
+        None,
+        None,
+        None,
+        false,
+        false,
+        None,
+        Vec::new(),
+        false,
+        false,
+        false,
+        false,
+        None,
+        dns_client,
+    )?;
+    Ok(tls_handler.handle(sess, None, Some(stream)).await?)
+}
+
+#[cfg(not(feature = "outbound-tls"))]
+async fn tls_connect(
+    _sess: &Session,
+    _stream: AnyStream,
+    _dns_client: SyncDnsClient,
+) -> Result<AnyStream> {
+    Err(anyhow!("https needs the outbound-tls feature"))
+}
+
+// Returns the body of a complete HTTP/1.1 response.
+fn parse_response(response: &[u8]) -> Result<Vec<u8>> {
+    let end = response
+        .windows(4)
+        .position(|x| x == b"\r\n\r\n")
+        .ok_or_else(|| anyhow!("incomplete http response"))?;
+    let head = std::str::from_utf8(&response[..end])?;
+    let body = &response[end + 4..];
+    let mut lines = head.split("\r\n");
+    let status = lines
+        .next()
+        .and_then(|x| x.split_whitespace().nth(1))
+        .ok_or_else(|| anyhow!("invalid http response"))?;
+    if status != "200" {
+        return Err(anyhow!("http status {}", status));
+    }
+    let chunked = lines.filter_map(|x| x.split_once(':')).any(|(k, v)| {
+        k.trim().eq_ignore_ascii_case("transfer-encoding")
+            && v.to_ascii_lowercase().contains("chunked")
+    });
+    if chunked {
+        decode_chunked(body)
+    } else {
+        Ok(body.to_vec())
+    }
+}
+
+fn decode_chunked(mut body: &[u8]) -> Result<Vec<u8>> {
+    let mut data = Vec::new();
+    loop {
+        let end = body
+            .windows(2)
+            .position(|x| x == b"\r\n")
+            .ok_or_else(|| anyhow!("incomplete chunked body"))?;
+        let size = std::str::from_utf8(&body[..end])?;
+        // Chunk extensions follow a semicolon.
+        let size = size.split(';').next().unwrap_or_default().trim();
+        let size = usize::from_str_radix(size, 16).map_err(|_| anyhow!("invalid chunk size"))?;
+        body = &body[end + 2..];
+        if size == 0 {
+            return Ok(data);
+        }
+        if body.len() < size + 2 {
+            return Err(anyhow!("incomplete chunked body"));
+        }
+        data.extend_from_slice(&body[..size]);
+        body = &body[size + 2..];
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn session(host: &str) -> Session {
+        Session {
+            destination: SocksAddr::try_from((host, 443)).unwrap(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_parse_text() {
+        let text = "# ads\nads.example.com\n+.tracker.example\n.cdn.example # cdn\n\n\
+                    10.0.0.0/8\n2001:db8::1\nDOMAIN-KEYWORD,analytics\nIP-CIDR,192.0.2.0/24,no-resolve\n\
+                    PROCESS-NAME,curl\n";
+        let rules = ProviderRules::parse(text, false);
+        assert_eq!(rules.len(), 7);
+        assert!(rules.matches(&session("ads.example.com")));
+        assert!(!rules.matches(&session("www.example.com")));
+        assert!(rules.matches(&session("tracker.example")));
+        assert!(rules.matches(&session("a.b.tracker.example")));
+        assert!(rules.matches(&session("img.cdn.example")));
+        assert!(rules.matches(&session("google-analytics.com")));
+        assert!(rules.matches(&session("10.1.2.3")));
+        assert!(rules.matches(&session("192.0.2.7")));
+        assert!(rules.matches(&session("2001:db8::1")));
+        assert!(!rules.matches(&session("2001:db8::2")));
+    }
+
+    #[test]
+    fn test_parse_yaml() {
+        let text = "# clash provider\npayload:\n  - '+.example.org'\n  - \"full.example.net\"\n  \
+                    - 198.51.100.0/24 # range\nother:\n  - ignored.example\n";
+        let rules = ProviderRules::parse(text, true);
+        assert_eq!(rules.len(), 3);
+        assert!(rules.matches(&session("www.example.org")));
+        assert!(rules.matches(&session("full.example.net")));
+        assert!(rules.matches(&session("198.51.100.1")));
+        assert!(!rules.matches(&session("ignored.example")));
+    }
+
+    #[test]
+    fn test_parse_url() {
+        let url = parse_url("https://example.com/lists/ads.txt").unwrap();
+        assert!(url.tls);
+        assert_eq!(
+            (url.host, url.port, url.path),
+            ("example.com", 443, "/lists/ads.txt")
+        );
+        let url = parse_url("http://[::1]:8080").unwrap();
+        assert!(!url.tls);
+        assert_eq!((url.host, url.port, url.path), ("::1", 8080, "/"));
+        assert!(parse_url("ftp://example.com/list").is_err());
+    }
+
+    #[test]
+    fn test_parse_response() {
+        let response = b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\nhello";
+        assert_eq!(parse_response(response).unwrap(), b"hello");
+        let response =
+            b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n5\r\nhello\r\n1;x=y\r\n!\r\n0\r\n\r\n";
+        assert_eq!(parse_response(response).unwrap(), b"hello!");
+        let response = b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\n\r\n";
+        assert!(parse_response(response).is_err());
+    }
+}
@@ -1,7 +1,9 @@
-use std::collections::{HashMap, VecDeque};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::net::SocketAddr;
 use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering};
 use std::sync::Arc;
-use std::{io, pin::Pin};
+use std::time::Duration;
+use std::{future::Future, io, pin::Pin};
 
 use async_trait::async_trait;
 use futures::{
@@ -9,13 +11,95 @@ use futures::{
     task::{Context, Poll},
 };
 use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
-use tokio::sync::{mpsc, RwLock};
+use tokio::sync::{mpsc, Notify, RwLock};
 use tracing::debug;
 
 use crate::{option, proxy::*, session::*};
 
 pub type SyncStatManager = Arc<RwLock<StatManager>>;
 
+/// Bytes relayed through a handler, totalled over all its sessions.
+#[derive(Default)]
+pub struct Traffic {
+    up: AtomicU64,
+    down: AtomicU64,
+}
+
+impl Traffic {
+    pub fn up(&self) -> u64 {
+        self.up.load(Ordering::Relaxed)
+    }
+
+    pub fn down(&self) -> u64 {
+        self.down.load(Ordering::Relaxed)
+    }
+
+    fn reset(&self) {
+        self.up.store(0, Ordering::Relaxed);
+        self.down.store(0, Ordering::Relaxed);
+    }
+}
+
+/// The totals a session adds to, those of its inbound and its outbound.
+#[derive(Clone)]
+pub struct SessionTraffic {
+    inbound: Arc<Traffic>,
+    outbound: Arc<Traffic>,
+}
+
+impl SessionTraffic {
+    fn add_up(&self, n: u64) {
+        self.inbound.up.fetch_add(n, Ordering::Relaxed);
+        self.outbound.up.fetch_add(n, Ordering::Relaxed);
+    }
+
+    fn add_down(&self, n: u64) {
+        self.inbound.down.fetch_add(n, Ordering::Relaxed);
+        self.outbound.down.fetch_add(n, Ordering::Relaxed);
+    }
+}
+
+/// Aborts the relay of a session. The wrappers of the session fail their
+/// reads and writes, and the halves are closed as the relay drops them.
+#[derive(Default)]
+pub struct KillSwitch {
+    killed: AtomicBool,
+    notify: Notify,
+}
+
+impl KillSwitch {
+    pub fn kill(&self) {
+        self.killed.store(true, Ordering::Relaxed);
+        self.notify.notify_waiters();
+    }
+
+    pub fn is_killed(&self) -> bool {
+        self.killed.load(Ordering::Relaxed)
+    }
+
+    async fn killed(&self) {
+        loop {
+            // Registered before the check, so that a kill in between isn't
+            // missed.
+            let notified = self.notify.notified();
+            if self.is_killed() {
+                return;
+            }
+            notified.await;
+        }
+    }
+}
+
+fn killed_error() -> io::Error {
+    io::Error::new(io::ErrorKind::ConnectionAborted, "session killed")
+}
+
+type Killed = Pin<Box<dyn Future<Output = ()> + Send + Sync>>;
+
+fn killed(kill: Arc<KillSwitch>) -> Killed {
+    Box::pin(async move { kill.killed().await })
+}
+
 pub struct Stream {
     pub inner: AnyStream,
     pub bytes_recvd: Arc<AtomicU64>,
@@ -23,10 +107,27 @@ pub struct Stream {
     pub recv_completed: Arc<AtomicBool>,
     pub send_completed: Arc<AtomicBool>,
     pub last_peer_active: Arc<AtomicU32>,
+    pub traffic: SessionTraffic,
+    // Whether it's the stream of the inbound side, from which reads go up
+    // rather than down.
+    pub inbound: bool,
+    pub kill: Arc<KillSwitch>,
+    // Wakes the relay on a kill, never polled once it's done, since the
+    // switch is checked first.
+    pub killed: Killed,
     pub id: u64,
     pub tx: mpsc::UnboundedSender<u64>,
 }
 
+impl Stream {
+    fn poll_killed(&mut self, cx: &mut Context) -> io::Result<()> {
+        if self.kill.is_killed() || self.killed.as_mut().poll(cx).is_ready() {
+            return Err(killed_error());
+        }
+        Ok(())
+    }
+}
+
 impl Drop for Stream {
     fn drop(&mut self) {
         // In case of abnormal shutdown.
@@ -42,13 +143,19 @@ impl AsyncRead for Stream {
         cx: &mut Context,
         buf: &mut ReadBuf,
     ) -> Poll<io::Result<()>> {
+        self.poll_killed(cx)?;
         let len = buf.filled().len();
         let remaining = buf.remaining();
         ready!(Pin::new(&mut self.inner).poll_read(cx, buf))?;
         let new_len = buf.filled().len();
         if new_len > len {
-            self.bytes_recvd
-                .fetch_add((new_len - len) as u64, Ordering::Relaxed);
+            let n = (new_len - len) as u64;
+            self.bytes_recvd.fetch_add(n, Ordering::Relaxed);
+            if self.inbound {
+                self.traffic.add_up(n);
+            } else {
+                self.traffic.add_down(n);
+            }
             self.last_peer_active
                 .store(get_unix_timestamp(), Ordering::Relaxed);
         } else if remaining > 0 {
@@ -64,8 +171,14 @@ impl AsyncWrite for Stream {
         cx: &mut Context,
         buf: &[u8],
     ) -> Poll<io::Result<usize>> {
+        self.poll_killed(cx)?;
         let n = ready!(Pin::new(&mut self.inner).poll_write(cx, buf))?;
         self.bytes_sent.fetch_add(n as u64, Ordering::Relaxed);
+        if self.inbound {
+            self.traffic.add_down(n as u64);
+        } else {
+            self.traffic.add_up(n as u64);
+        }
         Poll::Ready(Ok(n))
     }
 
@@ -87,6 +200,8 @@ pub struct Datagram {
     pub recv_completed: Arc<AtomicBool>,
     pub send_completed: Arc<AtomicBool>,
     pub last_peer_active: Arc<AtomicU32>,
+    pub traffic: SessionTraffic,
+    pub kill: Arc<KillSwitch>,
     pub id: u64,
     pub tx: mpsc::UnboundedSender<u64>,
 }
@@ -111,11 +226,15 @@ impl OutboundDatagram for Datagram {
                 self.bytes_recvd.clone(),
                 self.recv_completed.clone(),
                 self.last_peer_active.clone(),
+                self.traffic.clone(),
+                self.kill.clone(),
             )),
             Box::new(DatagramSendHalf(
                 s,
                 self.bytes_sent.clone(),
                 self.send_completed.clone(),
+                self.traffic.clone(),
+                self.kill.clone(),
             )),
         )
     }
@@ -126,6 +245,8 @@ pub struct DatagramRecvHalf(
     Arc<AtomicU64>,
     Arc<AtomicBool>,
     Arc<AtomicU32>,
+    SessionTraffic,
+    Arc<KillSwitch>,
 );
 
 impl Drop for DatagramRecvHalf {
@@ -137,11 +258,14 @@ impl Drop for DatagramRecvHalf {
 #[async_trait]
 impl OutboundDatagramRecvHalf for DatagramRecvHalf {
     async fn recv_from(&mut self, buf: &mut [u8]) -> io::Result<(usize, SocksAddr)> {
-        self.0.recv_from(buf).await.map(|(n, a)| {
-            self.1.fetch_add(n as u64, Ordering::Relaxed);
-            self.3.store(get_unix_timestamp(), Ordering::Relaxed);
-            (n, a)
-        })
+        let (n, a) = tokio::select! {
+            res = self.0.recv_from(buf) => res?,
+            _ = self.5.killed() => return Err(killed_error()),
+        };
+        self.1.fetch_add(n as u64, Ordering::Relaxed);
+        self.3.store(get_unix_timestamp(), Ordering::Relaxed);
+        self.4.add_down(n as u64);
+        Ok((n, a))
     }
 }
 
@@ -149,6 +273,8 @@ pub struct DatagramSendHalf(
     Box<dyn OutboundDatagramSendHalf>,
     Arc<AtomicU64>,
     Arc<AtomicBool>,
+    SessionTraffic,
+    Arc<KillSwitch>,
 );
 
 impl Drop for DatagramSendHalf {
@@ -160,9 +286,13 @@ impl Drop for DatagramSendHalf {
 #[async_trait]
 impl OutboundDatagramSendHalf for DatagramSendHalf {
     async fn send_to(&mut self, buf: &[u8], target: &SocksAddr) -> io::Result<usize> {
-        self.0.send_to(buf, target).await.inspect(|&n| {
-            self.1.fetch_add(n as u64, Ordering::Relaxed);
-        })
+        let n = tokio::select! {
+            res = self.0.send_to(buf, target) => res?,
+            _ = self.4.killed() => return Err(killed_error()),
+        };
+        self.1.fetch_add(n as u64, Ordering::Relaxed);
+        self.3.add_up(n as u64);
+        Ok(n)
     }
 
     async fn close(&mut self) -> io::Result<()> {
@@ -180,6 +310,7 @@ pub struct Counter {
     pub send_completed: Arc<AtomicBool>,
     pub last_peer_active: Arc<AtomicU32>,
     pub logged: Arc<AtomicBool>,
+    pub kill: Arc<KillSwitch>,
 }
 
 impl Counter {
@@ -234,6 +365,82 @@ fn get_unix_timestamp() -> u32 {
         .unwrap_or(0)
 }
 
+/// The latest path statistics sampled from a QUIC connection.
+#[derive(Debug, Clone)]
+pub struct QuicPathStat {
+    pub tag: String,
+    pub remote_addr: SocketAddr,
+    pub rtt_ms: u64,
+    pub lost_packets: u64,
+    pub congestion_events: u64,
+    pub cwnd: u64,
+    pub update_time: u32,
+}
+
+lazy_static::lazy_static! {
+    // Keyed by tag and remote address. The QUIC handlers hold no reference to
+    // the stat manager, so the samples are kept here and read through it.
+    static ref QUIC_PATH_STATS: std::sync::RwLock<HashMap<(String, SocketAddr), QuicPathStat>> =
+        std::sync::RwLock::new(HashMap::new());
+}
+
+/// Records a sample, `update_time` is set to the current time.
+pub fn update_quic_path_stat(mut stat: QuicPathStat) {
+    stat.update_time = get_unix_timestamp();
+    QUIC_PATH_STATS
+        .write()
+        .unwrap()
+        .insert((stat.tag.clone(), stat.remote_addr), stat);
+}
+
+pub fn remove_quic_path_stat(tag: &str, remote_addr: &SocketAddr) {
+    QUIC_PATH_STATS
+        .write()
+        .unwrap()
+        .remove(&(tag.to_string(), *remote_addr));
+}
+
+/// Upper bounds of the buckets of health check latencies, in seconds.
+pub const LATENCY_BUCKETS: [f64; 8] = [0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0];
+
+/// Latencies of the successful health checks of an outbound.
+#[derive(Debug, Clone, Default)]
+pub struct LatencyHistogram {
+    /// Checks per bucket, not cumulative, the last one for those above all
+    /// bounds.
+    pub buckets: [u64; LATENCY_BUCKETS.len() + 1],
+    pub sum: Duration,
+    pub count: u64,
+}
+
+impl LatencyHistogram {
+    fn observe(&mut self, latency: Duration) {
+        let secs = latency.as_secs_f64();
+        let i = LATENCY_BUCKETS
+            .iter()
+            .position(|x| secs <= *x)
+            .unwrap_or(LATENCY_BUCKETS.len());
+        self.buckets[i] += 1;
+        self.sum += latency;
+        self.count += 1;
+    }
+}
+
+lazy_static::lazy_static! {
+    // Keyed by tag and network, recorded by the health checks of the groups.
+    static ref HEALTH_CHECK_LATENCIES: std::sync::RwLock<HashMap<(String, Network), LatencyHistogram>> =
+        std::sync::RwLock::new(HashMap::new());
+}
+
+pub fn observe_health_check(tag: &str, network: Network, latency: Duration) {
+    HEALTH_CHECK_LATENCIES
+        .write()
+        .unwrap()
+        .entry((tag.to_string(), network))
+        .or_default()
+        .observe(latency);
+}
+
 pub struct StatManager {
     pub counters: HashMap<u64, Counter>,
     pub recent_counters: VecDeque<Counter>,
@@ -241,6 +448,11 @@ pub struct StatManager {
     pub next_id: u64,
     pub tx: mpsc::UnboundedSender<u64>,
     pub rx: Option<mpsc::UnboundedReceiver<u64>>,
+    // Keyed by handler tag, they outlive the sessions and config reloads.
+    pub inbound_traffic: HashMap<String, Arc<Traffic>>,
+    pub outbound_traffic: HashMap<String, Arc<Traffic>>,
+    // Failed connects of each outbound.
+    pub connect_errors: HashMap<String, u64>,
 }
 
 impl Default for StatManager {
@@ -253,6 +465,9 @@ impl Default for StatManager {
             next_id: 1,
             tx,
             rx: Some(rx),
+            inbound_traffic: HashMap::new(),
+            outbound_traffic: HashMap::new(),
+            connect_errors: HashMap::new(),
         }
     }
 }
@@ -339,6 +554,43 @@ impl StatManager {
         })
     }
 
+    fn session_traffic(&mut self, sess: &Session) -> SessionTraffic {
+        SessionTraffic {
+            inbound: self
+                .inbound_traffic
+                .entry(sess.inbound_tag.clone())
+                .or_default()
+                .clone(),
+            outbound: self
+                .outbound_traffic
+                .entry(sess.outbound_tag.clone())
+                .or_default()
+                .clone(),
+        }
+    }
+
+    /// Resets the traffic totals of all handlers.
+    pub fn reset_traffic(&self) {
+        for t in self
+            .inbound_traffic
+            .values()
+            .chain(self.outbound_traffic.values())
+        {
+            t.reset();
+        }
+    }
+
+    pub fn count_connect_error(&mut self, tag: &str) {
+        *self.connect_errors.entry(tag.to_owned()).or_default() += 1;
+    }
+
+    /// Drops the totals of the outbounds which are gone, to be called after a
+    /// reload.
+    pub fn retain_outbounds<F: Fn(&str) -> bool>(&mut self, f: F) {
+        self.outbound_traffic.retain(|tag, _| f(tag));
+        self.connect_errors.retain(|tag, _| f(tag));
+    }
+
     pub fn stat_stream(&mut self, stream: AnyStream, sess: Session) -> AnyStream {
         let bytes_recvd = Arc::new(AtomicU64::new(0));
         let bytes_sent = Arc::new(AtomicU64::new(0));
@@ -347,6 +599,8 @@ impl StatManager {
         let logged = Arc::new(AtomicBool::new(false));
         let ts = get_unix_timestamp();
         let last_peer_active = Arc::new(AtomicU32::new(ts));
+        let traffic = self.session_traffic(&sess);
+        let kill = Arc::new(KillSwitch::default());
         let id = self.next_id;
         self.next_id += 1;
         self.counters.insert(
@@ -361,6 +615,7 @@ impl StatManager {
                 send_completed: send_completed.clone(),
                 last_peer_active: last_peer_active.clone(),
                 logged,
+                kill: kill.clone(),
             },
         );
         Box::new(Stream {
@@ -370,6 +625,10 @@ impl StatManager {
             recv_completed,
             send_completed,
             last_peer_active,
+            traffic,
+            inbound: false,
+            killed: killed(kill.clone()),
+            kill,
             id,
             tx: self.tx.clone(),
         })
@@ -383,6 +642,8 @@ impl StatManager {
         let logged = Arc::new(AtomicBool::new(false));
         let ts = get_unix_timestamp();
         let last_peer_active = Arc::new(AtomicU32::new(ts));
+        let traffic = self.session_traffic(&sess);
+        let kill = Arc::new(KillSwitch::default());
         let id = self.next_id;
         self.next_id += 1;
         self.counters.insert(
@@ -397,6 +658,7 @@ impl StatManager {
                 send_completed: send_completed.clone(),
                 last_peer_active: last_peer_active.clone(),
                 logged,
+                kill: kill.clone(),
             },
         );
         Box::new(Stream {
@@ -406,6 +668,10 @@ impl StatManager {
             recv_completed: send_completed,
             send_completed: recv_completed,
             last_peer_active,
+            traffic,
+            inbound: true,
+            killed: killed(kill.clone()),
+            kill,
             id,
             tx: self.tx.clone(),
         })
@@ -423,6 +689,8 @@ impl StatManager {
         let logged = Arc::new(AtomicBool::new(false));
         let ts = get_unix_timestamp();
         let last_peer_active = Arc::new(AtomicU32::new(ts));
+        let traffic = self.session_traffic(&sess);
+        let kill = Arc::new(KillSwitch::default());
         let id = self.next_id;
         self.next_id += 1;
         self.counters.insert(
@@ -437,6 +705,7 @@ impl StatManager {
                 send_completed: send_completed.clone(),
                 last_peer_active: last_peer_active.clone(),
                 logged,
+                kill: kill.clone(),
             },
         );
         Box::new(Datagram {
@@ -446,11 +715,41 @@ impl StatManager {
             recv_completed,
             send_completed,
             last_peer_active,
+            traffic,
+            kill,
             id,
             tx: self.tx.clone(),
         })
     }
 
+    /// Kills a session, returns false if there's no such session.
+    pub fn kill(&self, id: u64) -> bool {
+        match self.counters.get(&id) {
+            Some(c) => {
+                c.kill.kill();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Kills the sessions going through any of the outbounds, returns the
+    /// number of them.
+    pub fn kill_outbounds(&self, tags: &HashSet<String>) -> usize {
+        let mut n = 0;
+        for c in self.counters.values() {
+            if c.send_completed() && c.recv_completed() {
+                continue;
+            }
+            let actor = c.sess.group_actor.lock().unwrap().clone();
+            if tags.contains(&c.sess.outbound_tag) || actor.is_some_and(|x| tags.contains(&x)) {
+                c.kill.kill();
+                n += 1;
+            }
+        }
+        n
+    }
+
     pub fn get_last_peer_active(&self, outbound_tag: &str) -> Option<u32> {
         self.counters
             .values()
@@ -463,6 +762,19 @@ impl StatManager {
         self.get_last_peer_active(outbound_tag)
             .map(|ts| get_unix_timestamp().saturating_sub(ts))
     }
+
+    pub fn quic_path_stats(&self) -> Vec<QuicPathStat> {
+        QUIC_PATH_STATS.read().unwrap().values().cloned().collect()
+    }
+
+    pub fn health_check_latencies(&self) -> Vec<(String, Network, LatencyHistogram)> {
+        HEALTH_CHECK_LATENCIES
+            .read()
+            .unwrap()
+            .iter()
+            .map(|((tag, network), h)| (tag.clone(), *network, h.clone()))
+            .collect()
+    }
 }
 
 #[cfg(test)]
@@ -508,6 +820,54 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_quic_path_stats() {
+        let remote_addr: SocketAddr = "127.0.0.1:4433".parse().unwrap();
+        let stat = QuicPathStat {
+            tag: "test_quic_path_stats".to_string(),
+            remote_addr,
+            rtt_ms: 10,
+            lost_packets: 1,
+            congestion_events: 0,
+            cwnd: 12000,
+            update_time: 0,
+        };
+        update_quic_path_stat(stat.clone());
+        update_quic_path_stat(QuicPathStat { rtt_ms: 20, ..stat });
+        let stats: Vec<_> = StatManager::new()
+            .quic_path_stats()
+            .into_iter()
+            .filter(|s| s.tag == "test_quic_path_stats")
+            .collect();
+        assert_eq!(stats.len(), 1);
+        assert_eq!(stats[0].rtt_ms, 20);
+        remove_quic_path_stat("test_quic_path_stats", &remote_addr);
+        assert!(StatManager::new()
+            .quic_path_stats()
+            .iter()
+            .all(|s| s.tag != "test_quic_path_stats"));
+    }
+
+    #[test]
+    fn test_health_check_latencies() {
+        let tag = "test_health_check_latencies";
+        for ms in [10, 50, 300, 20_000] {
+            observe_health_check(tag, Network::Tcp, Duration::from_millis(ms));
+        }
+        observe_health_check(tag, Network::Udp, Duration::from_millis(10));
+        let latencies: Vec<_> = StatManager::new()
+            .health_check_latencies()
+            .into_iter()
+            .filter(|x| x.0 == tag && x.1 == Network::Tcp)
+            .collect();
+        assert_eq!(latencies.len(), 1);
+        let h = &latencies[0].2;
+        // A bound is the largest latency of its bucket.
+        assert_eq!(h.buckets, [2, 0, 0, 1, 0, 0, 0, 0, 1]);
+        assert_eq!(h.count, 4);
+        assert_eq!(h.sum, Duration::from_millis(20_360));
+    }
+
     #[tokio::test]
     async fn test_stat_stream_non_empty_buf() {
         let mock = MockStream {
@@ -530,6 +890,13 @@ mod tests {
             recv_completed: recv_completed.clone(),
             send_completed: send_completed.clone(),
             last_peer_active: last_peer_active.clone(),
+            traffic: SessionTraffic {
+                inbound: Arc::new(Traffic::default()),
+                outbound: Arc::new(Traffic::default()),
+            },
+            inbound: false,
+            kill: Arc::new(KillSwitch::default()),
+            killed: Box::pin(futures::future::pending()),
             id: 0,
             tx,
         };
@@ -549,4 +916,82 @@ mod tests {
         let received = bytes_recvd.load(Ordering::Relaxed);
         assert_eq!(received, 5, "Expected 5 bytes received, got {}", received);
     }
+
+    #[tokio::test]
+    async fn test_traffic() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let mut sm = StatManager::new();
+        let sess = Session {
+            inbound_tag: "in".to_string(),
+            outbound_tag: "out".to_string(),
+            ..Default::default()
+        };
+        let mock = |n| MockStream {
+            data: vec![0; n],
+            read_pos: 0,
+        };
+        // Reads of the outbound stream go down, those of the inbound one up.
+        let mut rhs = sm.stat_stream(Box::new(mock(5)), sess.clone());
+        let mut lhs = sm.stat_inbound_stream(Box::new(mock(3)), sess.clone());
+        let mut buf = Vec::new();
+        rhs.read_to_end(&mut buf).await.unwrap();
+        lhs.read_to_end(&mut buf).await.unwrap();
+        rhs.write_all(&[0; 3]).await.unwrap();
+        lhs.write_all(&[0; 5]).await.unwrap();
+        for t in [&sm.inbound_traffic["in"], &sm.outbound_traffic["out"]] {
+            assert_eq!(t.up(), 3 + 3);
+            assert_eq!(t.down(), 5 + 5);
+        }
+
+        // A later session of the same tags adds to the totals.
+        let mut rhs = sm.stat_stream(Box::new(mock(2)), sess);
+        rhs.read_to_end(&mut buf).await.unwrap();
+        assert_eq!(sm.outbound_traffic["out"].down(), 12);
+
+        sm.reset_traffic();
+        assert_eq!(sm.inbound_traffic["in"].up(), 0);
+        assert_eq!(sm.outbound_traffic["out"].down(), 0);
+        rhs.write_all(&[0; 4]).await.unwrap();
+        assert_eq!(sm.outbound_traffic["out"].up(), 4);
+
+        sm.retain_outbounds(|tag| tag != "out");
+        assert!(sm.outbound_traffic.is_empty());
+        assert_eq!(sm.inbound_traffic.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_kill() {
+        use tokio::io::AsyncReadExt;
+
+        let mut sm = StatManager::new();
+        let (client, _server) = tokio::io::duplex(64);
+        let mut stream = sm.stat_stream(Box::new(client), Session::default());
+        let id = *sm.counters.keys().next().unwrap();
+        let read = tokio::spawn(async move {
+            let mut buf = [0u8; 1];
+            stream.read(&mut buf).await
+        });
+        // The read is pending by now, the kill has to wake it.
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(sm.kill(id));
+        let err = read.await.unwrap().unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::ConnectionAborted);
+        assert!(!sm.kill(id + 1));
+
+        let sess = Session {
+            outbound_tag: "group".to_string(),
+            ..Default::default()
+        };
+        sess.group_actor
+            .lock()
+            .unwrap()
+            .replace("actor".to_string());
+        let (client, _server) = tokio::io::duplex(64);
+        let _stream = sm.stat_stream(Box::new(client), sess);
+        let tags: HashSet<String> = ["other".to_string()].into();
+        assert_eq!(sm.kill_outbounds(&tags), 0);
+        let tags: HashSet<String> = ["actor".to_string()].into();
+        assert_eq!(sm.kill_outbounds(&tags), 1);
+    }
 }
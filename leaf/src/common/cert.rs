@@ -0,0 +1,685 @@
+use sha2::{Digest, Sha256};
+
+#[cfg(any(feature = "inbound-quic", feature = "inbound-tls"))]
+use {
+    anyhow::Result,
+    rustls::pki_types::pem::PemObject,
+    std::{fs, path::Path},
+    tracing::info,
+};
+
+#[cfg(feature = "rustls")]
+use {
+    rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier},
+    rustls::crypto::CryptoProvider,
+    rustls::pki_types::{CertificateDer, PrivateKeyDer, ServerName, UnixTime},
+    rustls::{CertificateError, DigitallySignedStruct, Error, SignatureScheme},
+    std::sync::Arc,
+};
+
+#[cfg(all(
+    feature = "rustls",
+    any(feature = "outbound-tls", feature = "outbound-quic")
+))]
+use {
+    base64::{engine::general_purpose::STANDARD, Engine},
+    rustls::client::{VerifierBuilderError, WebPkiServerVerifier},
+    rustls::RootCertStore,
+    std::collections::HashSet,
+    std::net::IpAddr,
+    std::sync::Mutex,
+    tracing::warn,
+    x509_parser::prelude::GeneralName,
+};
+
+/// Returns the hex encoded SHA-256 digest of a DER encoded certificate.
+pub fn sha256_fingerprint(der: &[u8]) -> String {
+    hex::encode(Sha256::digest(der))
+}
+
+/// Converts a fingerprint as users write it, e.g. `AB:CD:...`, to the form
+/// returned by `sha256_fingerprint`.
+pub fn normalize_fingerprint(fingerprint: &str) -> String {
+    fingerprint
+        .chars()
+        .filter(|c| *c != ':')
+        .collect::<String>()
+        .to_ascii_lowercase()
+}
+
+/// Loads a PEM bundle, given inline or as a path, or a single DER encoded
+/// certificate from a `.der` file. Fails if there are no certificates.
+#[cfg(feature = "rustls")]
+pub fn load_certs(certificate: &str) -> anyhow::Result<Vec<CertificateDer<'static>>> {
+    use rustls::pki_types::pem::PemObject;
+
+    let certs = if certificate.contains("-----BEGIN") {
+        CertificateDer::pem_slice_iter(certificate.as_bytes()).collect::<Result<Vec<_>, _>>()?
+    } else {
+        let data = std::fs::read(certificate)
+            .map_err(|e| anyhow::anyhow!("load certificates from {} failed: {}", certificate, e))?;
+        if std::path::Path::new(certificate)
+            .extension()
+            .and_then(|ext| ext.to_str())
+            == Some("der")
+        {
+            vec![CertificateDer::from(data)]
+        } else {
+            CertificateDer::pem_slice_iter(&data).collect::<Result<Vec<_>, _>>()?
+        }
+    };
+    if certs.is_empty() {
+        return Err(anyhow::anyhow!(
+            "no certificates found in {}",
+            if certificate.contains("-----BEGIN") {
+                "inline pem"
+            } else {
+                certificate
+            }
+        ));
+    }
+    Ok(certs)
+}
+
+/// Loads a PEM encoded private key, given inline or as a path, or a PKCS #8
+/// DER encoded key from a `.der` file.
+#[cfg(feature = "rustls")]
+pub fn load_private_key(key: &str) -> anyhow::Result<PrivateKeyDer<'static>> {
+    use rustls::pki_types::pem::PemObject;
+
+    if key.contains("-----BEGIN") {
+        return PrivateKeyDer::from_pem_slice(key.as_bytes())
+            .map_err(|e| anyhow::anyhow!("no private keys found in inline pem: {}", e));
+    }
+    let data = std::fs::read(key)
+        .map_err(|e| anyhow::anyhow!("load private key from {} failed: {}", key, e))?;
+    if std::path::Path::new(key)
+        .extension()
+        .and_then(|ext| ext.to_str())
+        == Some("der")
+    {
+        Ok(PrivateKeyDer::Pkcs8(data.into()))
+    } else {
+        PrivateKeyDer::from_pem_slice(&data)
+            .map_err(|e| anyhow::anyhow!("no private keys found in {}: {}", key, e))
+    }
+}
+
+/// Returns a PEM encoded self-signed certificate and key for `san`.
+///
+/// With `persist_as` the pair is stored as `<persist_as>.crt` and
+/// `<persist_as>.key` under the asset location, and loaded from there on
+/// later calls so the identity survives restarts.
+#[cfg(any(feature = "inbound-quic", feature = "inbound-tls"))]
+pub fn self_signed_certificate(san: &str, persist_as: Option<&str>) -> Result<(String, String)> {
+    let paths = persist_as.map(|name| {
+        let asset_loc = Path::new(&*crate::option::ASSET_LOCATION);
+        (
+            asset_loc.join(format!("{}.crt", name)),
+            asset_loc.join(format!("{}.key", name)),
+        )
+    });
+    if let Some((cert_path, key_path)) = &paths {
+        if cert_path.exists() && key_path.exists() {
+            let cert = fs::read_to_string(cert_path)?;
+            let key = fs::read_to_string(key_path)?;
+            let der = CertificateDer::from_pem_slice(cert.as_bytes())
+                .map_err(|e| anyhow::anyhow!("load {} failed: {}", cert_path.display(), e))?;
+            info!(
+                "loaded self-signed certificate {}, sha256 fingerprint {}",
+                cert_path.display(),
+                sha256_fingerprint(&der)
+            );
+            return Ok((cert, key));
+        }
+    }
+    let rcgen::CertifiedKey { cert, key_pair } =
+        rcgen::generate_simple_self_signed(vec![san.to_string()])?;
+    let cert_pem = cert.pem();
+    let key_pem = key_pair.serialize_pem();
+    info!(
+        "generated self-signed certificate for {}, sha256 fingerprint {}",
+        san,
+        sha256_fingerprint(cert.der())
+    );
+    if let Some((cert_path, key_path)) = &paths {
+        fs::write(cert_path, &cert_pem)?;
+        fs::write(key_path, &key_pem)?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(key_path, fs::Permissions::from_mode(0o600))?;
+        }
+    }
+    Ok((cert_pem, key_pem))
+}
+
+/// Accepts exactly the server certificate with the pinned SHA-256
+/// fingerprint, regardless of its issuer and names.
+#[cfg(feature = "rustls")]
+#[derive(Debug)]
+pub struct FingerprintVerifier {
+    fingerprint: String,
+    provider: Arc<CryptoProvider>,
+}
+
+#[cfg(feature = "rustls")]
+impl FingerprintVerifier {
+    pub fn new(fingerprint: &str, provider: Arc<CryptoProvider>) -> Self {
+        Self {
+            fingerprint: normalize_fingerprint(fingerprint),
+            provider,
+        }
+    }
+}
+
+#[cfg(feature = "rustls")]
+impl ServerCertVerifier for FingerprintVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer,
+        _intermediates: &[CertificateDer],
+        _server_name: &ServerName,
+        _ocsp_response: &[u8],
+        _now: UnixTime,
+    ) -> Result<ServerCertVerified, Error> {
+        if sha256_fingerprint(end_entity.as_ref()) == self.fingerprint {
+            Ok(ServerCertVerified::assertion())
+        } else {
+            Err(Error::InvalidCertificate(
+                CertificateError::ApplicationVerificationFailure,
+            ))
+        }
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, Error> {
+        rustls::crypto::verify_tls12_signature(
+            message,
+            cert,
+            dss,
+            &self.provider.signature_verification_algorithms,
+        )
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, Error> {
+        rustls::crypto::verify_tls13_signature(
+            message,
+            cert,
+            dss,
+            &self.provider.signature_verification_algorithms,
+        )
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.provider
+            .signature_verification_algorithms
+            .supported_schemes()
+    }
+}
+
+/// Accepts any server certificate, handshake signatures are still checked.
+#[cfg(feature = "rustls")]
+#[derive(Debug)]
+pub struct InsecureVerifier(pub Arc<CryptoProvider>);
+
+#[cfg(feature = "rustls")]
+impl ServerCertVerifier for InsecureVerifier {
+    fn verify_server_cert(
+        &self,
+        _end_entity: &CertificateDer,
+        _intermediates: &[CertificateDer],
+        _server_name: &ServerName,
+        _ocsp_response: &[u8],
+        _now: UnixTime,
+    ) -> Result<ServerCertVerified, Error> {
+        Ok(ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, Error> {
+        rustls::crypto::verify_tls12_signature(
+            message,
+            cert,
+            dss,
+            &self.0.signature_verification_algorithms,
+        )
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, Error> {
+        rustls::crypto::verify_tls13_signature(
+            message,
+            cert,
+            dss,
+            &self.0.signature_verification_algorithms,
+        )
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.0.signature_verification_algorithms.supported_schemes()
+    }
+}
+
+/// Returns the base64 encoded SHA-256 digest of the SubjectPublicKeyInfo of
+/// a DER encoded certificate, the form certificate pins are written in.
+#[cfg(all(
+    feature = "rustls",
+    any(feature = "outbound-tls", feature = "outbound-quic")
+))]
+pub fn spki_sha256(der: &[u8]) -> Option<String> {
+    let (_, cert) = x509_parser::parse_x509_certificate(der).ok()?;
+    Some(STANDARD.encode(Sha256::digest(cert.public_key().raw)))
+}
+
+/// Accepts a server certificate chain if the SPKI digest of any of its
+/// certificates is pinned. With an `inner` verifier the chain must pass it as
+/// well, without one pins are all that's checked.
+#[cfg(all(
+    feature = "rustls",
+    any(feature = "outbound-tls", feature = "outbound-quic")
+))]
+#[derive(Debug)]
+pub struct SpkiPinVerifier {
+    pins: Vec<String>,
+    inner: Option<Arc<dyn ServerCertVerifier>>,
+    provider: Arc<CryptoProvider>,
+}
+
+#[cfg(all(
+    feature = "rustls",
+    any(feature = "outbound-tls", feature = "outbound-quic")
+))]
+impl SpkiPinVerifier {
+    pub fn new(
+        pins: Vec<String>,
+        inner: Option<Arc<dyn ServerCertVerifier>>,
+        provider: Arc<CryptoProvider>,
+    ) -> Self {
+        Self {
+            pins,
+            inner,
+            provider,
+        }
+    }
+}
+
+#[cfg(all(
+    feature = "rustls",
+    any(feature = "outbound-tls", feature = "outbound-quic")
+))]
+impl ServerCertVerifier for SpkiPinVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer,
+        intermediates: &[CertificateDer],
+        server_name: &ServerName,
+        ocsp_response: &[u8],
+        now: UnixTime,
+    ) -> Result<ServerCertVerified, Error> {
+        let observed = std::iter::once(end_entity)
+            .chain(intermediates)
+            .filter_map(|cert| spki_sha256(cert.as_ref()))
+            .collect::<Vec<_>>();
+        if !observed.iter().any(|digest| self.pins.contains(digest)) {
+            // Named so it can be copied into the config.
+            return Err(Error::General(format!(
+                "no certificate pin matches, observed spki sha256 digests: {}",
+                observed.join(", ")
+            )));
+        }
+        if let Some(inner) = &self.inner {
+            inner.verify_server_cert(end_entity, intermediates, server_name, ocsp_response, now)?;
+        }
+        Ok(ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, Error> {
+        rustls::crypto::verify_tls12_signature(
+            message,
+            cert,
+            dss,
+            &self.provider.signature_verification_algorithms,
+        )
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, Error> {
+        rustls::crypto::verify_tls13_signature(
+            message,
+            cert,
+            dss,
+            &self.provider.signature_verification_algorithms,
+        )
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.provider
+            .signature_verification_algorithms
+            .supported_schemes()
+    }
+}
+
+/// Returns a verifier checking server certificates against `roots`, and with
+/// `allow_name_mismatch` accepting them whatever names they're for.
+#[cfg(all(
+    feature = "rustls",
+    any(feature = "outbound-tls", feature = "outbound-quic")
+))]
+pub fn root_verifier(
+    roots: RootCertStore,
+    provider: Arc<CryptoProvider>,
+    allow_name_mismatch: bool,
+) -> Result<Arc<dyn ServerCertVerifier>, VerifierBuilderError> {
+    let verifier =
+        WebPkiServerVerifier::builder_with_provider(Arc::new(roots), provider).build()?;
+    if allow_name_mismatch {
+        Ok(Arc::new(NameMismatchVerifier {
+            inner: verifier,
+            warned: Mutex::new(HashSet::new()),
+        }))
+    } else {
+        Ok(verifier)
+    }
+}
+
+// The DNS names and IP addresses a certificate is for.
+#[cfg(all(
+    feature = "rustls",
+    any(feature = "outbound-tls", feature = "outbound-quic")
+))]
+fn presented_names(der: &[u8]) -> Vec<String> {
+    let Ok((_, cert)) = x509_parser::parse_x509_certificate(der) else {
+        return Vec::new();
+    };
+    let Ok(Some(san)) = cert.subject_alternative_name() else {
+        return Vec::new();
+    };
+    san.value
+        .general_names
+        .iter()
+        .filter_map(|name| match name {
+            GeneralName::DNSName(x) => Some(x.to_string()),
+            GeneralName::IPAddress(x) => <[u8; 4]>::try_from(*x)
+                .map(IpAddr::from)
+                .or_else(|_| <[u8; 16]>::try_from(*x).map(IpAddr::from))
+                .map(|ip| ip.to_string())
+                .ok(),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Verifies the chain and validity of server certificates like `inner`, but
+/// accepts certificates which are for other names than the server's.
+#[cfg(all(
+    feature = "rustls",
+    any(feature = "outbound-tls", feature = "outbound-quic")
+))]
+#[derive(Debug)]
+struct NameMismatchVerifier {
+    inner: Arc<WebPkiServerVerifier>,
+    // Servers a mismatch has been logged for.
+    warned: Mutex<HashSet<String>>,
+}
+
+#[cfg(all(
+    feature = "rustls",
+    any(feature = "outbound-tls", feature = "outbound-quic")
+))]
+impl ServerCertVerifier for NameMismatchVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer,
+        intermediates: &[CertificateDer],
+        server_name: &ServerName,
+        ocsp_response: &[u8],
+        now: UnixTime,
+    ) -> Result<ServerCertVerified, Error> {
+        let err = match self.inner.verify_server_cert(
+            end_entity,
+            intermediates,
+            server_name,
+            ocsp_response,
+            now,
+        ) {
+            Ok(verified) => return Ok(verified),
+            Err(e) => e,
+        };
+        // If the certificate verifies for a name it's for, the name was the
+        // only problem.
+        let names = presented_names(end_entity.as_ref());
+        let Some(presented) = names.iter().find_map(|name| {
+            let name = match name.strip_prefix("*.") {
+                Some(domain) => format!("wildcard.{}", domain),
+                None => name.clone(),
+            };
+            ServerName::try_from(name).ok()
+        }) else {
+            return Err(err);
+        };
+        self.inner
+            .verify_server_cert(end_entity, intermediates, &presented, ocsp_response, now)
+            .map_err(|_| err)?;
+        let server = server_name.to_str().into_owned();
+        if self.warned.lock().unwrap().insert(server.clone()) {
+            warn!(
+                "certificate of {} is for {}, accepted as name mismatches are allowed",
+                server,
+                names.join(", ")
+            );
+        }
+        Ok(ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, Error> {
+        self.inner.verify_tls12_signature(message, cert, dss)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, Error> {
+        self.inner.verify_tls13_signature(message, cert, dss)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.inner.supported_verify_schemes()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_fingerprint() {
+        assert_eq!(normalize_fingerprint("AB:cd:01"), "abcd01");
+        assert_eq!(normalize_fingerprint("abcd01"), "abcd01");
+    }
+
+    #[cfg(feature = "rustls")]
+    #[test]
+    fn test_load_certs() {
+        let bundle = (0..2)
+            .map(|_| {
+                rcgen::generate_simple_self_signed(vec!["localhost".to_string()])
+                    .unwrap()
+                    .cert
+                    .pem()
+            })
+            .collect::<String>();
+        assert_eq!(load_certs(&bundle).unwrap().len(), 2);
+
+        let path = std::env::temp_dir().join("leaf-test-load-certs.pem");
+        std::fs::write(&path, &bundle).unwrap();
+        assert_eq!(load_certs(path.to_str().unwrap()).unwrap().len(), 2);
+
+        std::fs::write(&path, "no certificates here").unwrap();
+        let err = load_certs(path.to_str().unwrap()).unwrap_err();
+        assert!(err.to_string().contains("no certificates found"));
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[cfg(feature = "rustls")]
+    #[test]
+    fn test_load_private_key() {
+        let key = rcgen::KeyPair::generate().unwrap();
+        assert!(matches!(
+            load_private_key(&key.serialize_pem()).unwrap(),
+            PrivateKeyDer::Pkcs8(_)
+        ));
+
+        let path = std::env::temp_dir().join("leaf-test-load-private-key.der");
+        std::fs::write(&path, key.serialize_der()).unwrap();
+        assert!(load_private_key(path.to_str().unwrap()).is_ok());
+        std::fs::remove_file(&path).unwrap();
+
+        let err = load_private_key("/nonexistent/leaf-key.pem").unwrap_err();
+        assert!(err.to_string().contains("/nonexistent/leaf-key.pem"));
+    }
+
+    #[cfg(all(
+        feature = "rustls",
+        any(feature = "outbound-tls", feature = "outbound-quic")
+    ))]
+    mod verifier {
+        use super::*;
+
+        fn provider() -> Arc<CryptoProvider> {
+            #[cfg(feature = "rustls-tls-aws-lc")]
+            let provider = rustls::crypto::aws_lc_rs::default_provider();
+            #[cfg(not(feature = "rustls-tls-aws-lc"))]
+            let provider = rustls::crypto::ring::default_provider();
+            provider.into()
+        }
+
+        fn certificate(expired: bool) -> CertificateDer<'static> {
+            let mut params = rcgen::CertificateParams::new(vec!["localhost".to_string()]).unwrap();
+            if expired {
+                params.not_before = rcgen::date_time_ymd(2000, 1, 1);
+                params.not_after = rcgen::date_time_ymd(2001, 1, 1);
+            }
+            let key_pair = rcgen::KeyPair::generate().unwrap();
+            params.self_signed(&key_pair).unwrap().der().clone()
+        }
+
+        fn verifier(
+            cert: &CertificateDer<'static>,
+            pins: Vec<String>,
+            pin_only: bool,
+        ) -> SpkiPinVerifier {
+            let inner: Option<Arc<dyn ServerCertVerifier>> = if pin_only {
+                None
+            } else {
+                let mut roots = rustls::RootCertStore::empty();
+                roots.add(cert.clone()).unwrap();
+                Some(
+                    rustls::client::WebPkiServerVerifier::builder_with_provider(
+                        Arc::new(roots),
+                        provider(),
+                    )
+                    .build()
+                    .unwrap(),
+                )
+            };
+            SpkiPinVerifier::new(pins, inner, provider())
+        }
+
+        fn verify(verifier: &dyn ServerCertVerifier, cert: &CertificateDer) -> Result<(), Error> {
+            verify_for(verifier, cert, "localhost")
+        }
+
+        fn verify_for(
+            verifier: &dyn ServerCertVerifier,
+            cert: &CertificateDer,
+            name: &str,
+        ) -> Result<(), Error> {
+            let server_name = ServerName::try_from(name.to_string()).unwrap();
+            verifier
+                .verify_server_cert(cert, &[], &server_name, &[], UnixTime::now())
+                .map(|_| ())
+        }
+
+        #[test]
+        fn test_matching_pin() {
+            let cert = certificate(false);
+            let pin = spki_sha256(cert.as_ref()).unwrap();
+            assert!(verify(&verifier(&cert, vec![pin.clone()], false), &cert).is_ok());
+            assert!(verify(&verifier(&cert, vec![pin], true), &cert).is_ok());
+        }
+
+        #[test]
+        fn test_mismatched_pin() {
+            let cert = certificate(false);
+            let other = spki_sha256(certificate(false).as_ref()).unwrap();
+            let err = verify(&verifier(&cert, vec![other], false), &cert).unwrap_err();
+            let observed = spki_sha256(cert.as_ref()).unwrap();
+            assert!(err.to_string().contains(&observed));
+        }
+
+        #[test]
+        fn test_expired_pinned_certificate() {
+            let cert = certificate(true);
+            let pin = spki_sha256(cert.as_ref()).unwrap();
+            assert!(verify(&verifier(&cert, vec![pin.clone()], false), &cert).is_err());
+            assert!(verify(&verifier(&cert, vec![pin], true), &cert).is_ok());
+        }
+
+        fn roots(cert: &CertificateDer<'static>) -> RootCertStore {
+            let mut roots = RootCertStore::empty();
+            roots.add(cert.clone()).unwrap();
+            roots
+        }
+
+        #[test]
+        fn test_name_mismatch() {
+            let cert = certificate(false);
+            let strict = root_verifier(roots(&cert), provider(), false).unwrap();
+            let relaxed = root_verifier(roots(&cert), provider(), true).unwrap();
+            assert!(verify_for(strict.as_ref(), &cert, "example.com").is_err());
+            assert!(verify_for(relaxed.as_ref(), &cert, "example.com").is_ok());
+            assert!(verify_for(relaxed.as_ref(), &cert, "localhost").is_ok());
+
+            // Other failures are still reported.
+            let expired = certificate(true);
+            let relaxed = root_verifier(roots(&expired), provider(), true).unwrap();
+            assert!(verify_for(relaxed.as_ref(), &expired, "example.com").is_err());
+            let other = root_verifier(roots(&certificate(false)), provider(), true).unwrap();
+            assert!(verify_for(other.as_ref(), &cert, "example.com").is_err());
+        }
+    }
+}
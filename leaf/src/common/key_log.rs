@@ -0,0 +1,111 @@
+//! NSS key log output for decrypting captured TLS and QUIC traffic.
+//!
+//! Disabled unless `SSLKEYLOGFILE` or the `keyLogFile` log option names a
+//! file. Handlers install `key_log()` on the rustls configs they build, so it
+//! has to be set up before they're created.
+
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::sync::{Arc, Mutex, RwLock};
+
+use rustls::KeyLog;
+use tracing::warn;
+
+lazy_static::lazy_static! {
+    static ref KEY_LOG: RwLock<Option<Arc<KeyLogFile>>> = RwLock::new(None);
+}
+
+#[derive(Debug)]
+pub struct KeyLogFile {
+    path: String,
+    file: Mutex<Option<File>>,
+}
+
+impl KeyLogFile {
+    fn open(path: &str) -> Option<Self> {
+        match OpenOptions::new().create(true).append(true).open(path) {
+            Ok(file) => Some(Self {
+                path: path.to_string(),
+                file: Mutex::new(Some(file)),
+            }),
+            Err(e) => {
+                warn!(
+                    "open tls key log {} failed, key logging disabled: {}",
+                    path, e
+                );
+                None
+            }
+        }
+    }
+}
+
+impl KeyLog for KeyLogFile {
+    fn log(&self, label: &str, client_random: &[u8], secret: &[u8]) {
+        let mut file = self.file.lock().unwrap();
+        let Some(f) = file.as_mut() else {
+            return;
+        };
+        let line = format!("{} {} {}\n", label, to_hex(client_random), to_hex(secret));
+        if let Err(e) = f.write_all(line.as_bytes()) {
+            warn!(
+                "write tls key log {} failed, key logging disabled: {}",
+                self.path, e
+            );
+            *file = None;
+        }
+    }
+}
+
+fn to_hex(data: &[u8]) -> String {
+    data.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Enables key logging to `path`, or to `SSLKEYLOGFILE` if `path` is empty,
+/// and disables it if neither is set.
+pub fn setup(path: &str) {
+    let path = if path.is_empty() {
+        std::env::var("SSLKEYLOGFILE").unwrap_or_default()
+    } else {
+        path.to_string()
+    };
+    let key_log = if path.is_empty() {
+        None
+    } else {
+        warn!(
+            "tls key logging to {} is enabled, anyone who can read it can decrypt the traffic",
+            path
+        );
+        KeyLogFile::open(&path).map(Arc::new)
+    };
+    *KEY_LOG.write().unwrap() = key_log;
+}
+
+/// Returns the key log to install on rustls configs, if enabled.
+pub fn key_log() -> Option<Arc<dyn KeyLog>> {
+    KEY_LOG
+        .read()
+        .unwrap()
+        .clone()
+        .map(|key_log| key_log as Arc<dyn KeyLog>)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_key_log_file() {
+        let path = std::env::temp_dir().join("leaf-test-key-log.txt");
+        let _ = std::fs::remove_file(&path);
+        let key_log = KeyLogFile::open(path.to_str().unwrap()).unwrap();
+        key_log.log("CLIENT_RANDOM", &[0x01, 0xab], &[0xff]);
+        key_log.log("CLIENT_RANDOM", &[0x02], &[0x00]);
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "CLIENT_RANDOM 01ab ff\nCLIENT_RANDOM 02 00\n"
+        );
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(KeyLogFile::open("/nonexistent/leaf-key-log.txt").is_none());
+    }
+}
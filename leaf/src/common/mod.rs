@@ -3,8 +3,32 @@ pub mod dns_sniff;
 pub mod io;
 pub mod net;
 pub mod resolver;
+pub mod rotating_file;
 pub mod sniff;
 
+#[cfg(any(
+    feature = "inbound-quic",
+    feature = "inbound-tls",
+    feature = "outbound-quic",
+    feature = "outbound-tls",
+    feature = "outbound-hysteria2",
+    feature = "outbound-tuic"
+))]
+pub mod cert;
+
+#[cfg(feature = "rustls")]
+pub mod key_log;
+
+#[cfg(any(
+    feature = "outbound-quic",
+    feature = "outbound-hysteria2",
+    feature = "outbound-tuic"
+))]
+pub mod udp_session;
+
+#[cfg(feature = "rule-process-name")]
+pub mod process;
+
 #[cfg(target_os = "macos")]
 pub mod cmd_macos;
 #[cfg(target_os = "macos")]
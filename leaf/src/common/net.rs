@@ -21,3 +21,101 @@ pub fn parse_bind_addr(bind: &str) -> Result<SocketAddr> {
         None => Ok(SocketAddr::new(ip_addr.parse()?, 0)),
     }
 }
+
+/// A set of ports, parsed from lists of single ports and inclusive ranges
+/// such as `80,443,8000-8100`.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct PortSet {
+    // Sorted, neither overlapping nor adjacent, for a binary search.
+    ranges: Vec<(u16, u16)>,
+}
+
+impl PortSet {
+    pub fn parse(list: &str) -> Result<Self> {
+        let mut set = Self::default();
+        set.add(list)?;
+        Ok(set)
+    }
+
+    /// Adds the ports of a list, none of them if it's invalid.
+    pub fn add(&mut self, list: &str) -> Result<()> {
+        let mut ranges = Vec::new();
+        for item in list.split(',') {
+            let (start, end) = match item.split_once('-') {
+                Some((start, end)) => (parse_port(start)?, parse_port(end)?),
+                None => (parse_port(item)?, parse_port(item)?),
+            };
+            if start > end {
+                return Err(anyhow!("invalid port range {}", item.trim()));
+            }
+            ranges.push((start, end));
+        }
+        self.ranges.append(&mut ranges);
+        self.ranges.sort_unstable();
+        let mut merged: Vec<(u16, u16)> = Vec::with_capacity(self.ranges.len());
+        for (start, end) in self.ranges.drain(..) {
+            match merged.last_mut() {
+                Some(last) if start <= last.1.saturating_add(1) => last.1 = last.1.max(end),
+                _ => merged.push((start, end)),
+            }
+        }
+        self.ranges = merged;
+        Ok(())
+    }
+
+    pub fn contains(&self, port: u16) -> bool {
+        let i = self.ranges.partition_point(|&(_, end)| end < port);
+        self.ranges.get(i).is_some_and(|&(start, _)| start <= port)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ranges.is_empty()
+    }
+}
+
+fn parse_port(s: &str) -> Result<u16> {
+    s.trim()
+        .parse::<u16>()
+        .map_err(|_| anyhow!("invalid port {}", s.trim()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_port_set() {
+        let set = PortSet::parse("80, 443,8000-8100").unwrap();
+        assert!(set.contains(80));
+        assert!(set.contains(443));
+        assert!(set.contains(8000));
+        assert!(set.contains(8050));
+        assert!(set.contains(8100));
+        assert!(!set.contains(81));
+        assert!(!set.contains(8101));
+        assert!(!set.contains(0));
+        assert!(!set.contains(u16::MAX));
+    }
+
+    #[test]
+    fn test_port_set_merge() {
+        let mut set = PortSet::parse("6881-6885,6884-6889").unwrap();
+        set.add("6890,100-200,150-160,6870-6880").unwrap();
+        assert_eq!(set.ranges, vec![(100, 200), (6870, 6890)]);
+        let set = PortSet::parse("22-22,0,65535,65535").unwrap();
+        assert_eq!(set.ranges, vec![(0, 0), (22, 22), (65535, 65535)]);
+        assert!(set.contains(0) && set.contains(22) && set.contains(65535));
+    }
+
+    #[test]
+    fn test_port_set_invalid() {
+        for list in [
+            "", "22-21", "22-", "-22", "22-abc", "22-23-24", "65536", "80,", "1-65536",
+        ] {
+            assert!(PortSet::parse(list).is_err(), "{}", list);
+        }
+        let mut set = PortSet::parse("80").unwrap();
+        assert!(set.add("443,x").is_err());
+        assert!(!set.contains(443));
+    }
+}
@@ -0,0 +1,99 @@
+//! Looks the socket up in /proc/net, then the process holding its inode
+//! among the file descriptors in /proc.
+
+use std::fs;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+
+use crate::session::Network;
+
+pub fn find_process_path(network: Network, local: SocketAddr) -> Option<String> {
+    let inode = find_inode(network, local)?;
+    let pid = find_pid(inode)?;
+    let path = fs::read_link(format!("/proc/{}/exe", pid)).ok()?;
+    Some(path.to_string_lossy().into_owned())
+}
+
+fn find_inode(network: Network, local: SocketAddr) -> Option<u64> {
+    let tables: &[&str] = match network {
+        Network::Tcp => &["/proc/net/tcp", "/proc/net/tcp6"],
+        Network::Udp => &["/proc/net/udp", "/proc/net/udp6"],
+    };
+    let mut sockets = Vec::new();
+    for table in tables {
+        let Ok(text) = fs::read_to_string(table) else {
+            continue;
+        };
+        for (addr, inode) in text.lines().skip(1).filter_map(parse_line) {
+            if addr.port() == local.port() && inode != 0 {
+                sockets.push((addr.ip().to_canonical(), inode));
+            }
+        }
+    }
+    // An IPv4 socket may as well be an IPv6 one accepting mapped addresses.
+    super::match_socket(local.ip().to_canonical(), sockets)
+}
+
+// The local address and the inode of a line of a socket table.
+fn parse_line(line: &str) -> Option<(SocketAddr, u64)> {
+    let fields: Vec<&str> = line.split_whitespace().collect();
+    let (ip, port) = fields.get(1)?.split_once(':')?;
+    let port = u16::from_str_radix(port, 16).ok()?;
+    let inode = fields.get(9)?.parse().ok()?;
+    Some((SocketAddr::new(parse_ip(ip)?, port), inode))
+}
+
+// The addresses are printed as 32-bit words in host order.
+fn parse_ip(hex: &str) -> Option<IpAddr> {
+    let mut bytes = Vec::with_capacity(16);
+    for i in (0..hex.len()).step_by(8) {
+        let word = u32::from_str_radix(hex.get(i..i + 8)?, 16).ok()?;
+        bytes.extend_from_slice(&word.to_ne_bytes());
+    }
+    if let Ok(bytes) = <[u8; 4]>::try_from(bytes.as_slice()) {
+        return Some(IpAddr::V4(Ipv4Addr::from(bytes)));
+    }
+    let bytes = <[u8; 16]>::try_from(bytes.as_slice()).ok()?;
+    Some(IpAddr::V6(Ipv6Addr::from(bytes)))
+}
+
+fn find_pid(inode: u64) -> Option<u32> {
+    let target = format!("socket:[{}]", inode);
+    for entry in fs::read_dir("/proc").ok()?.flatten() {
+        let Some(pid) = entry.file_name().to_str().and_then(|x| x.parse().ok()) else {
+            continue;
+        };
+        let Ok(fds) = fs::read_dir(entry.path().join("fd")) else {
+            continue;
+        };
+        for fd in fds.flatten() {
+            if fs::read_link(fd.path()).is_ok_and(|x| x.as_os_str() == target.as_str()) {
+                return Some(pid);
+            }
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_line() {
+        let line = "   0: 0100007F:1F90 00000000:0000 0A 00000000:00000000 00:00000000 00000000  1000        0 12345 1 0000000000000000 100 0 0 10 0";
+        let (addr, inode) = parse_line(line).unwrap();
+        assert_eq!(addr.port(), 8080);
+        assert_eq!(inode, 12345);
+        if cfg!(target_endian = "little") {
+            assert_eq!(addr.ip(), "127.0.0.1".parse::<IpAddr>().unwrap());
+        }
+    }
+
+    #[test]
+    fn test_find_own_socket() {
+        let socket = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let path = find_process_path(Network::Tcp, socket.local_addr().unwrap());
+        let exe = std::env::current_exe().unwrap();
+        assert_eq!(path, Some(exe.to_string_lossy().into_owned()));
+    }
+}
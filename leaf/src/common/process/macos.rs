@@ -0,0 +1,105 @@
+//! Looks the socket up in the PCB list of the kernel, which has the PID of
+//! the process which last used it, then the path of the process by libproc.
+//! The offsets are of the xinpcb_n and xsocket_n structures of xnu, the
+//! entries are padded to 8 bytes.
+
+use std::ffi::CString;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::sync::LazyLock;
+
+use crate::session::Network;
+
+// The size of an entry of the list, without the TCP control block.
+static ITEM_SIZE: LazyLock<usize> = LazyLock::new(|| {
+    let major = sysctl("kern.osrelease")
+        .and_then(|x| {
+            let release = String::from_utf8_lossy(&x).into_owned();
+            release.split('.').next()?.parse::<u32>().ok()
+        })
+        .unwrap_or(0);
+    // Darwin 22 is macOS 13.
+    if major >= 22 {
+        408
+    } else {
+        384
+    }
+});
+
+// rup8(sizeof(xtcpcb_n))
+const TCPCB_SIZE: usize = 208;
+// sizeof(xinpgen), ahead of the entries.
+const HEADER_SIZE: usize = 24;
+
+pub fn find_process_path(network: Network, local: SocketAddr) -> Option<String> {
+    let (name, item_size) = match network {
+        Network::Tcp => ("net.inet.tcp.pcblist_n", *ITEM_SIZE + TCPCB_SIZE),
+        Network::Udp => ("net.inet.udp.pcblist_n", *ITEM_SIZE),
+    };
+    let buf = sysctl(name)?;
+    let ip = local.ip().to_canonical();
+    let mut sockets = Vec::new();
+    let mut i = HEADER_SIZE;
+    while i + item_size <= buf.len() {
+        let (inp, so) = (i, i + 104);
+        i += item_size;
+        // xinpcb_n.inp_lport
+        if u16::from_be_bytes([buf[inp + 18], buf[inp + 19]]) != local.port() {
+            continue;
+        }
+        // xinpcb_n.inp_vflag
+        let flag = buf[inp + 44];
+        let addr = if flag & 0x1 != 0 && ip.is_ipv4() {
+            let bytes: [u8; 4] = buf[inp + 76..inp + 80].try_into().ok()?;
+            IpAddr::V4(Ipv4Addr::from(bytes))
+        } else if flag & 0x2 != 0 && ip.is_ipv6() {
+            let bytes: [u8; 16] = buf[inp + 64..inp + 80].try_into().ok()?;
+            IpAddr::V6(Ipv6Addr::from(bytes))
+        } else {
+            continue;
+        };
+        // xsocket_n.so_last_pid
+        let pid = u32::from_ne_bytes(buf[so + 68..so + 72].try_into().ok()?);
+        sockets.push((addr, pid));
+    }
+    super::match_socket(ip, sockets).and_then(pid_path)
+}
+
+fn sysctl(name: &str) -> Option<Vec<u8>> {
+    let name = CString::new(name).ok()?;
+    let mut len = 0;
+    unsafe {
+        if libc::sysctlbyname(
+            name.as_ptr(),
+            std::ptr::null_mut(),
+            &mut len,
+            std::ptr::null_mut(),
+            0,
+        ) != 0
+        {
+            return None;
+        }
+        let mut buf = vec![0u8; len];
+        if libc::sysctlbyname(
+            name.as_ptr(),
+            buf.as_mut_ptr() as _,
+            &mut len,
+            std::ptr::null_mut(),
+            0,
+        ) != 0
+        {
+            return None;
+        }
+        buf.truncate(len);
+        Some(buf)
+    }
+}
+
+fn pid_path(pid: u32) -> Option<String> {
+    let mut buf = vec![0u8; libc::PROC_PIDPATHINFO_MAXSIZE as usize];
+    let n = unsafe { libc::proc_pidpath(pid as _, buf.as_mut_ptr() as _, buf.len() as _) };
+    if n <= 0 {
+        return None;
+    }
+    buf.truncate(n as usize);
+    String::from_utf8(buf).ok()
+}
@@ -0,0 +1,81 @@
+//! Finds the local process owning a socket by its address, for the rules
+//! matching the process of a session. Lookups scan the socket tables of
+//! the system, so their results are kept for a little while.
+
+use std::collections::HashMap;
+#[cfg(any(target_os = "linux", target_os = "macos", target_os = "windows"))]
+use std::net::IpAddr;
+use std::net::SocketAddr;
+use std::sync::{LazyLock, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::session::Network;
+
+#[cfg(target_os = "linux")]
+mod linux;
+#[cfg(target_os = "linux")]
+use linux as os;
+
+#[cfg(target_os = "macos")]
+mod macos;
+#[cfg(target_os = "macos")]
+use macos as os;
+
+#[cfg(target_os = "windows")]
+mod windows;
+#[cfg(target_os = "windows")]
+use windows as os;
+
+const CACHE_TTL: Duration = Duration::from_secs(5);
+// Expired entries are dropped once the cache grows past this.
+const CACHE_SIZE: usize = 1024;
+
+type Cache = HashMap<(Network, SocketAddr), (Instant, Option<String>)>;
+
+static CACHE: LazyLock<Mutex<Cache>> = LazyLock::new(Default::default);
+
+/// Returns the path of the executable of the process with a socket bound
+/// to `local`, none if there isn't one or it can't be told. Blocks while
+/// scanning the sockets.
+pub fn find_process_path(network: Network, local: SocketAddr) -> Option<String> {
+    let key = (network, local);
+    if let Some((time, path)) = CACHE.lock().unwrap().get(&key) {
+        if time.elapsed() < CACHE_TTL {
+            return path.clone();
+        }
+    }
+    let path = lookup(network, local);
+    let mut cache = CACHE.lock().unwrap();
+    if cache.len() >= CACHE_SIZE {
+        cache.retain(|_, (time, _)| time.elapsed() < CACHE_TTL);
+    }
+    cache.insert(key, (Instant::now(), path.clone()));
+    path
+}
+
+// Picks the socket bound to `ip` among the sockets on the port, or else the
+// first one bound to any address. An unconnected UDP socket is bound to any
+// address, and so may be sending from `ip` too.
+#[cfg(any(target_os = "linux", target_os = "macos", target_os = "windows"))]
+fn match_socket<T>(ip: IpAddr, sockets: Vec<(IpAddr, T)>) -> Option<T> {
+    let mut wildcard = None;
+    for (addr, socket) in sockets {
+        if addr == ip {
+            return Some(socket);
+        }
+        if addr.is_unspecified() && wildcard.is_none() {
+            wildcard = Some(socket);
+        }
+    }
+    wildcard
+}
+
+#[cfg(any(target_os = "linux", target_os = "macos", target_os = "windows"))]
+fn lookup(network: Network, local: SocketAddr) -> Option<String> {
+    os::find_process_path(network, local)
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", target_os = "windows")))]
+fn lookup(_network: Network, _local: SocketAddr) -> Option<String> {
+    None
+}
@@ -0,0 +1,159 @@
+//! Looks the socket up in the extended TCP and UDP tables of the IP helper,
+//! which have the PID of the owner, then the image of the process.
+
+#![allow(non_snake_case)]
+
+use std::ffi::{c_void, OsString};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::os::windows::ffi::OsStringExt;
+
+use crate::session::Network;
+
+const AF_INET: u32 = 2;
+const AF_INET6: u32 = 23;
+const TCP_TABLE_OWNER_PID_ALL: i32 = 5;
+const UDP_TABLE_OWNER_PID: i32 = 1;
+const NO_ERROR: u32 = 0;
+const ERROR_INSUFFICIENT_BUFFER: u32 = 122;
+const PROCESS_QUERY_LIMITED_INFORMATION: u32 = 0x1000;
+const MAX_PATH: usize = 260;
+
+#[link(name = "iphlpapi")]
+extern "system" {
+    fn GetExtendedTcpTable(
+        pTcpTable: *mut c_void,
+        pdwSize: *mut u32,
+        bOrder: i32,
+        ulAf: u32,
+        TableClass: i32,
+        Reserved: u32,
+    ) -> u32;
+    fn GetExtendedUdpTable(
+        pUdpTable: *mut c_void,
+        pdwSize: *mut u32,
+        bOrder: i32,
+        ulAf: u32,
+        TableClass: i32,
+        Reserved: u32,
+    ) -> u32;
+}
+
+#[link(name = "kernel32")]
+extern "system" {
+    fn OpenProcess(dwDesiredAccess: u32, bInheritHandle: i32, dwProcessId: u32) -> *mut c_void;
+    fn QueryFullProcessImageNameW(
+        hProcess: *mut c_void,
+        dwFlags: u32,
+        lpExeName: *mut u16,
+        lpdwSize: *mut u32,
+    ) -> i32;
+    fn CloseHandle(hObject: *mut c_void) -> i32;
+}
+
+// The layout of a row of a table.
+struct Layout {
+    size: usize,
+    addr: usize,
+    port: usize,
+    pid: usize,
+}
+
+impl Layout {
+    // MIB_TCPROW_OWNER_PID, MIB_TCP6ROW_OWNER_PID, MIB_UDPROW_OWNER_PID and
+    // MIB_UDP6ROW_OWNER_PID.
+    fn of(network: Network, v6: bool) -> Self {
+        let (size, addr, port, pid) = match (network, v6) {
+            (Network::Tcp, false) => (24, 4, 8, 20),
+            (Network::Tcp, true) => (56, 0, 20, 52),
+            (Network::Udp, false) => (12, 0, 4, 8),
+            (Network::Udp, true) => (28, 0, 20, 24),
+        };
+        Layout {
+            size,
+            addr,
+            port,
+            pid,
+        }
+    }
+}
+
+pub fn find_process_path(network: Network, local: SocketAddr) -> Option<String> {
+    let ip = local.ip().to_canonical();
+    let v6 = ip.is_ipv6();
+    let table = get_table(network, v6)?;
+    let layout = Layout::of(network, v6);
+    let count = u32::from_ne_bytes(table.get(..4)?.try_into().ok()?) as usize;
+    let mut sockets = Vec::new();
+    for i in 0..count {
+        let row = table.get(4 + i * layout.size..4 + (i + 1) * layout.size)?;
+        let port = u16::from_be_bytes([row[layout.port], row[layout.port + 1]]);
+        if port != local.port() {
+            continue;
+        }
+        let addr = if v6 {
+            let bytes: [u8; 16] = row[layout.addr..layout.addr + 16].try_into().ok()?;
+            IpAddr::V6(Ipv6Addr::from(bytes))
+        } else {
+            let bytes: [u8; 4] = row[layout.addr..layout.addr + 4].try_into().ok()?;
+            IpAddr::V4(Ipv4Addr::from(bytes))
+        };
+        let pid = u32::from_ne_bytes(row[layout.pid..layout.pid + 4].try_into().ok()?);
+        sockets.push((addr, pid));
+    }
+    super::match_socket(ip, sockets).and_then(pid_path)
+}
+
+fn get_table(network: Network, v6: bool) -> Option<Vec<u8>> {
+    let af = if v6 { AF_INET6 } else { AF_INET };
+    let get = |buf: &mut Vec<u8>, size: &mut u32| unsafe {
+        match network {
+            Network::Tcp => GetExtendedTcpTable(
+                buf.as_mut_ptr() as _,
+                size,
+                0,
+                af,
+                TCP_TABLE_OWNER_PID_ALL,
+                0,
+            ),
+            Network::Udp => {
+                GetExtendedUdpTable(buf.as_mut_ptr() as _, size, 0, af, UDP_TABLE_OWNER_PID, 0)
+            }
+        }
+    };
+    let mut size = 0u32;
+    let mut buf = Vec::new();
+    // The table may grow between the calls.
+    for _ in 0..3 {
+        match get(&mut buf, &mut size) {
+            NO_ERROR => {
+                buf.truncate(size as usize);
+                return Some(buf);
+            }
+            ERROR_INSUFFICIENT_BUFFER => buf.resize(size as usize, 0),
+            _ => return None,
+        }
+    }
+    None
+}
+
+fn pid_path(pid: u32) -> Option<String> {
+    // The idle and system processes.
+    if pid == 0 || pid == 4 {
+        return None;
+    }
+    unsafe {
+        let handle = OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, 0, pid);
+        if handle.is_null() {
+            return None;
+        }
+        let mut buf = vec![0u16; MAX_PATH * 4];
+        let mut len = buf.len() as u32;
+        let ok = QueryFullProcessImageNameW(handle, 0, buf.as_mut_ptr(), &mut len);
+        CloseHandle(handle);
+        if ok == 0 {
+            return None;
+        }
+        let path = OsString::from_wide(&buf[..len as usize]);
+        Some(path.to_string_lossy().into_owned())
+    }
+}
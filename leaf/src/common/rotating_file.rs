@@ -0,0 +1,179 @@
+//! A log file rotated by size.
+//!
+//! When a write would take the file over its size limit, the file is renamed
+//! with a timestamp suffix and a new one is started, then the backups beyond
+//! the count or age limits are deleted. Writes are expected to come from a
+//! single writer, such as the worker of a non-blocking appender, so that a
+//! line never spans two files.
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+pub struct RotatingFile {
+    path: PathBuf,
+    file: File,
+    size: u64,
+    // Zero for no limit, as for the others.
+    max_size: u64,
+    max_backups: usize,
+    max_age: Duration,
+}
+
+// Whether a file name is the one of a backup of the file, which is the
+// file name followed by a timestamp and maybe a counter.
+fn is_backup(name: &str, file_name: &str) -> bool {
+    let Some(suffix) = name
+        .strip_prefix(file_name)
+        .and_then(|x| x.strip_prefix('.'))
+    else {
+        return false;
+    };
+    let timestamp = suffix.split('-').take(2).collect::<Vec<_>>().join("-");
+    chrono::NaiveDateTime::parse_from_str(&timestamp, "%Y%m%d-%H%M%S%.3f").is_ok()
+}
+
+impl RotatingFile {
+    pub fn open(
+        path: &Path,
+        max_size: u64,
+        max_backups: usize,
+        max_age: Duration,
+    ) -> io::Result<Self> {
+        let file = OpenOptions::new().append(true).create(true).open(path)?;
+        let size = file.metadata()?.len();
+        let f = RotatingFile {
+            path: path.to_path_buf(),
+            file,
+            size,
+            max_size,
+            max_backups,
+            max_age,
+        };
+        f.prune();
+        Ok(f)
+    }
+
+    fn file_name(&self) -> String {
+        self.path
+            .file_name()
+            .map(|x| x.to_string_lossy().to_string())
+            .unwrap_or_default()
+    }
+
+    fn backup_path(&self) -> PathBuf {
+        let timestamp = chrono::Local::now().format("%Y%m%d-%H%M%S%.3f");
+        let base = format!("{}.{}", self.file_name(), timestamp);
+        let mut path = self.path.with_file_name(&base);
+        let mut n = 1;
+        while path.exists() {
+            path = self.path.with_file_name(format!("{}-{}", base, n));
+            n += 1;
+        }
+        path
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        self.file.flush()?;
+        fs::rename(&self.path, self.backup_path())?;
+        self.file = OpenOptions::new()
+            .append(true)
+            .create(true)
+            .open(&self.path)?;
+        self.size = 0;
+        self.prune();
+        Ok(())
+    }
+
+    // Returns the backups, the newest first.
+    fn backups(&self) -> io::Result<Vec<(PathBuf, SystemTime)>> {
+        let dir = match self.path.parent() {
+            Some(x) if !x.as_os_str().is_empty() => x,
+            _ => Path::new("."),
+        };
+        let file_name = self.file_name();
+        let mut backups = Vec::new();
+        for entry in fs::read_dir(dir)? {
+            let entry = entry?;
+            if !is_backup(&entry.file_name().to_string_lossy(), &file_name) {
+                continue;
+            }
+            let modified = entry.metadata()?.modified()?;
+            backups.push((entry.path(), modified));
+        }
+        backups.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| b.0.cmp(&a.0)));
+        Ok(backups)
+    }
+
+    // Deletes the backups beyond the limits. Failures are ignored, this
+    // is the logger, and the next rotation tries again.
+    fn prune(&self) {
+        let Ok(backups) = self.backups() else {
+            return;
+        };
+        let now = SystemTime::now();
+        for (i, (path, modified)) in backups.iter().enumerate() {
+            let too_many = self.max_backups > 0 && i >= self.max_backups;
+            let too_old = !self.max_age.is_zero()
+                && now.duration_since(*modified).unwrap_or_default() > self.max_age;
+            if too_many || too_old {
+                let _ = fs::remove_file(path);
+            }
+        }
+    }
+}
+
+impl Write for RotatingFile {
+    // Takes a whole line, which isn't split between files.
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.max_size > 0 && self.size > 0 && self.size + buf.len() as u64 > self.max_size {
+            // Keeps writing to the file if it can't be rotated.
+            let _ = self.rotate();
+        }
+        self.file.write_all(buf)?;
+        self.size += buf.len() as u64;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_backup() {
+        assert!(is_backup("leaf.log.20240102-030405.678", "leaf.log"));
+        assert!(is_backup("leaf.log.20240102-030405.678-2", "leaf.log"));
+        assert!(!is_backup("leaf.log", "leaf.log"));
+        assert!(!is_backup("leaf.log.old", "leaf.log"));
+        assert!(!is_backup("other.log.20240102-030405.678", "leaf.log"));
+    }
+
+    #[test]
+    fn test_rotate() {
+        let dir = std::env::temp_dir().join("leaf-test-rotating-file");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("leaf.log");
+        let mut f = RotatingFile::open(&path, 10, 2, Duration::ZERO).unwrap();
+        for line in ["line1\n", "line2\n", "line3\n", "line4\n"] {
+            f.write_all(line.as_bytes()).unwrap();
+        }
+        f.flush().unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "line4\n");
+        let backups = f.backups().unwrap();
+        assert_eq!(backups.len(), 2);
+        let mut lines: Vec<String> = backups
+            .iter()
+            .map(|x| fs::read_to_string(&x.0).unwrap())
+            .collect();
+        lines.sort();
+        assert_eq!(lines, vec!["line2\n", "line3\n"]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
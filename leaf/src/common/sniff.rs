@@ -14,6 +14,64 @@ pub fn should_sniff(sess: &Session) -> bool {
     !sess.destination.is_domain()
 }
 
+/// The protocols a session is told to be of by the protocol rules.
+pub const PROTOCOLS: &[&str] = &["tls", "http", "quic", "bittorrent", "dns", "unknown"];
+
+const HTTP_METHODS: &[&[u8]] = &[
+    b"GET", b"POST", b"HEAD", b"PUT", b"DELETE", b"OPTIONS", b"CONNECT", b"PATCH", b"TRACE", b"PRI",
+];
+
+/// Tells the protocol of a stream by the bytes the client sent first.
+pub fn stream_protocol(buf: &[u8]) -> &'static str {
+    if buf.len() >= 3 && buf[0] == 0x16 && buf[1] == 0x3 && buf[2] <= 0x4 {
+        return "tls";
+    }
+    let is_method = |m: &&[u8]| buf.starts_with(m) && buf.get(m.len()) == Some(&b' ');
+    if HTTP_METHODS.iter().any(is_method) {
+        return "http";
+    }
+    if buf.starts_with(b"\x13BitTorrent protocol") {
+        return "bittorrent";
+    }
+    // DNS over TCP, each message prefixed with its length.
+    if buf.len() >= 2 && u16::from_be_bytes([buf[0], buf[1]]) >= 12 && is_dns_query(&buf[2..]) {
+        return "dns";
+    }
+    "unknown"
+}
+
+/// Tells the protocol of a UDP session by its first datagram.
+pub fn datagram_protocol(buf: &[u8]) -> &'static str {
+    // A long header packet of QUIC v1, v2 or the drafts.
+    if buf.len() >= 5 && buf[0] & 0xc0 == 0xc0 {
+        let version = u32::from_be_bytes([buf[1], buf[2], buf[3], buf[4]]);
+        if version == 0x1 || version == 0x6b3343cf || version >> 8 == 0xff0000 {
+            return "quic";
+        }
+    }
+    if is_dns_query(buf) {
+        return "dns";
+    }
+    // A query of the DHT, the keys of the dictionary are sorted, or the
+    // SYN of uTP.
+    let is_krpc = [b"1:y1:qe", b"1:y1:re", b"1:y1:ee"]
+        .iter()
+        .any(|x| buf.ends_with(*x));
+    if (buf.starts_with(b"d1:") && is_krpc) || (buf.len() >= 20 && buf[0] == 0x41 && buf[1] <= 2) {
+        return "bittorrent";
+    }
+    "unknown"
+}
+
+// A standard query with one question and no answers.
+fn is_dns_query(buf: &[u8]) -> bool {
+    if buf.len() < 12 {
+        return false;
+    }
+    let count = |i: usize| u16::from_be_bytes([buf[i], buf[i + 1]]);
+    buf[2] & 0xf8 == 0 && count(4) == 1 && count(6) == 0 && count(8) == 0 && count(10) <= 1
+}
+
 pub enum SniffKind {
     Tls,
     Http,
@@ -193,6 +251,11 @@ where
         SniffResult::NotEnoughData
     }
 
+    /// The protocol of the bytes read while sniffing.
+    pub fn protocol(&self) -> &'static str {
+        stream_protocol(&self.buf)
+    }
+
     pub async fn sniff(&mut self, _sess: &Session) -> io::Result<Option<(SniffKind, String)>> {
         for _ in 0..3 {
             match timeout(
@@ -269,3 +332,34 @@ impl<T: AsyncWrite + Unpin> AsyncWrite for SniffingStream<T> {
         AsyncWrite::poll_shutdown(Pin::new(&mut self.inner), cx)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stream_protocol() {
+        assert_eq!(stream_protocol(b"\x16\x03\x01\x02\x00\x01"), "tls");
+        assert_eq!(stream_protocol(b"GET / HTTP/1.1\r\n"), "http");
+        assert_eq!(stream_protocol(b"GETTING"), "unknown");
+        assert_eq!(
+            stream_protocol(b"\x13BitTorrent protocol\0\0"),
+            "bittorrent"
+        );
+        let query = b"\x00\x1d\x12\x34\x01\x00\x00\x01\x00\x00\x00\x00\x00\x00\x07example\x03com\x00\x00\x01\x00\x01";
+        assert_eq!(stream_protocol(query), "dns");
+        assert_eq!(stream_protocol(b"SSH-2.0-OpenSSH_9.6\r\n"), "unknown");
+        assert_eq!(stream_protocol(b""), "unknown");
+    }
+
+    #[test]
+    fn test_datagram_protocol() {
+        assert_eq!(datagram_protocol(b"\xc3\x00\x00\x00\x01\x08"), "quic");
+        assert_eq!(datagram_protocol(b"\xd3\x6b\x33\x43\xcf\x08"), "quic");
+        let query = b"d1:ad2:id20:abcdefghij0123456789e1:q4:ping1:t2:aa1:y1:qe";
+        assert_eq!(datagram_protocol(query), "bittorrent");
+        let query = b"\x12\x34\x01\x00\x00\x01\x00\x00\x00\x00\x00\x00\x07example\x03com\x00\x00\x01\x00\x01";
+        assert_eq!(datagram_protocol(query), "dns");
+        assert_eq!(datagram_protocol(b"\x00\x01\x02"), "unknown");
+    }
+}
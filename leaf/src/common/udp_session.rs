@@ -0,0 +1,154 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::io;
+use std::sync::{Mutex, MutexGuard};
+
+use bytes::{BufMut, Bytes, BytesMut};
+
+/// The id of a UDP session relayed over a multiplexed connection.
+pub trait SessionId: Copy + Eq + Hash {
+    /// How many distinct ids there are.
+    const COUNT: u64;
+
+    fn random() -> Self;
+
+    fn next(self) -> Self;
+}
+
+macro_rules! impl_session_id {
+    ($($t:ty),*) => {
+        $(
+            impl SessionId for $t {
+                const COUNT: u64 = <$t>::MAX as u64 + 1;
+
+                fn random() -> Self {
+                    rand::random()
+                }
+
+                fn next(self) -> Self {
+                    self.wrapping_add(1)
+                }
+            }
+        )*
+    };
+}
+
+impl_session_id!(u16, u32);
+
+/// The UDP sessions relayed over a connection, keyed by session id.
+pub struct SessionMap<K, V>(Mutex<HashMap<K, V>>);
+
+impl<K, V> Default for SessionMap<K, V> {
+    fn default() -> Self {
+        Self(Mutex::new(HashMap::new()))
+    }
+}
+
+impl<K: SessionId, V> SessionMap<K, V> {
+    /// Registers a session under an unused id, starting from a random one
+    /// and taking the next free id after it. Fails once all ids are taken.
+    pub fn register(&self, session: V) -> io::Result<K> {
+        let mut sessions = self.0.lock().unwrap();
+        if sessions.len() as u64 >= K::COUNT {
+            return Err(io::Error::other("no free udp session id"));
+        }
+        let mut id = K::random();
+        while sessions.contains_key(&id) {
+            id = id.next();
+        }
+        sessions.insert(id, session);
+        Ok(id)
+    }
+
+    pub fn lock(&self) -> MutexGuard<'_, HashMap<K, V>> {
+        self.0.lock().unwrap()
+    }
+
+    pub fn remove(&self, id: &K) -> Option<V> {
+        self.0.lock().unwrap().remove(id)
+    }
+}
+
+/// A fragment of a UDP packet.
+pub trait Fragment: Sized {
+    fn packet_id(&self) -> u16;
+
+    fn fragment_id(&self) -> u8;
+
+    fn fragment_count(&self) -> u8;
+
+    fn payload(&self) -> &Bytes;
+
+    /// Turns the first fragment into the whole packet carrying `payload`.
+    fn into_packet(self, payload: Bytes) -> Self;
+}
+
+/// Reassembles the fragmented packets of a UDP session, keeping the
+/// fragments of one packet at a time like the reference implementations do.
+pub struct Defragger<F> {
+    packet_id: u16,
+    fragments: Vec<Option<F>>,
+    received: usize,
+}
+
+impl<F> Default for Defragger<F> {
+    fn default() -> Self {
+        Self {
+            packet_id: 0,
+            fragments: Vec::new(),
+            received: 0,
+        }
+    }
+}
+
+impl<F: Fragment> Defragger<F> {
+    /// Returns the packet once all its fragments have arrived.
+    pub fn feed(&mut self, fragment: F) -> Option<F> {
+        if fragment.fragment_count() == 1 {
+            return Some(fragment);
+        }
+        if fragment.packet_id() != self.packet_id
+            || self.fragments.len() != fragment.fragment_count() as usize
+        {
+            self.packet_id = fragment.packet_id();
+            self.fragments = (0..fragment.fragment_count()).map(|_| None).collect();
+            self.received = 0;
+        }
+        let id = fragment.fragment_id() as usize;
+        let slot = self.fragments.get_mut(id)?;
+        if slot.is_none() {
+            *slot = Some(fragment);
+            self.received += 1;
+        }
+        if self.received < self.fragments.len() {
+            return None;
+        }
+        let fragments = std::mem::take(&mut self.fragments);
+        self.received = 0;
+        let mut fragments = fragments.into_iter().flatten();
+        let first = fragments.next()?;
+        let mut payload = BytesMut::from(&first.payload()[..]);
+        for fragment in fragments {
+            payload.put_slice(fragment.payload());
+        }
+        Some(first.into_packet(payload.freeze()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_register_exhausted() {
+        let sessions = SessionMap::<u16, ()>::default();
+        let free = rand::random::<u16>();
+        sessions
+            .lock()
+            .extend((0..=u16::MAX).filter(|id| *id != free).map(|id| (id, ())));
+        assert_eq!(sessions.register(()).unwrap(), free);
+        assert!(sessions.register(()).is_err());
+        sessions.remove(&7);
+        assert_eq!(sessions.register(()).unwrap(), 7);
+    }
+}
@@ -5,12 +5,70 @@ use anyhow::Result;
 use protobuf::Message;
 use serde_derive::{Deserialize, Serialize};
 
+use crate::common::net::PortSet;
 use crate::config::{external_rule, internal};
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Dns {
     pub servers: Option<Vec<String>>,
     pub hosts: Option<HashMap<String, Vec<String>>>,
+    pub groups: Option<HashMap<String, DnsGroup>>,
+    pub rules: Option<Vec<DnsRule>>,
+    pub outbound: Option<String>,
+    #[serde(rename = "hostsTtl", alias = "hosts_ttl")]
+    pub hosts_ttl: Option<u32>,
+    pub strategy: Option<String>,
+    #[serde(rename = "cacheMinTtl", alias = "cache_min_ttl")]
+    pub cache_min_ttl: Option<u32>,
+    #[serde(rename = "cacheMaxTtl", alias = "cache_max_ttl")]
+    pub cache_max_ttl: Option<u32>,
+    #[serde(rename = "cacheNegativeTtl", alias = "cache_negative_ttl")]
+    pub cache_negative_ttl: Option<u32>,
+    #[serde(rename = "cacheSize", alias = "cache_size")]
+    pub cache_size: Option<u32>,
+    #[serde(rename = "cacheFile", alias = "cache_file")]
+    pub cache_file: Option<String>,
+    #[serde(rename = "fakeIpFilter", alias = "fake_ip_filter")]
+    pub fake_ip_filter: Option<FakeIpFilter>,
+    #[serde(rename = "ednsClientSubnet", alias = "edns_client_subnet")]
+    pub edns_client_subnet: Option<String>,
+    #[serde(rename = "concurrentQueries", alias = "concurrent_queries")]
+    pub concurrent_queries: Option<bool>,
+}
+
+// A group is its list of servers, or the list along with the tag of the
+// outbound they're queried through.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(untagged)]
+pub enum DnsGroup {
+    Servers(Vec<String>),
+    WithOutbound {
+        servers: Vec<String>,
+        outbound: Option<String>,
+    },
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DnsRule {
+    pub domain: Option<Vec<String>>,
+    #[serde(rename = "domainKeyword", alias = "domain_keyword")]
+    pub domain_keyword: Option<Vec<String>>,
+    #[serde(rename = "domainSuffix", alias = "domain_suffix")]
+    pub domain_suffix: Option<Vec<String>>,
+    pub group: String,
+}
+
+// The domains the fake DNS answers with real addresses in the blacklist
+// mode, the default, or the only ones it answers with fake ones in the
+// whitelist mode.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct FakeIpFilter {
+    pub mode: Option<String>,
+    pub domain: Option<Vec<String>>,
+    #[serde(rename = "domainKeyword", alias = "domain_keyword")]
+    pub domain_keyword: Option<Vec<String>>,
+    #[serde(rename = "domainSuffix", alias = "domain_suffix")]
+    pub domain_suffix: Option<Vec<String>>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -18,6 +76,33 @@ pub struct Log {
     pub level: Option<String>,
     pub output: Option<String>,
     pub format: Option<String>,
+    #[serde(rename = "keyLogFile", alias = "key_log_file")]
+    pub key_log_file: Option<String>,
+    #[serde(rename = "accessLog", alias = "access_log")]
+    pub access_log: Option<String>,
+    #[serde(rename = "accessLogFormat", alias = "access_log_format")]
+    pub access_log_format: Option<String>,
+    #[serde(rename = "accessLogStart", alias = "access_log_start")]
+    pub access_log_start: Option<bool>,
+    #[serde(rename = "maxSizeMb", alias = "max_size_mb")]
+    pub max_size_mb: Option<u32>,
+    #[serde(rename = "maxBackups", alias = "max_backups")]
+    pub max_backups: Option<u32>,
+    #[serde(rename = "maxAgeDays", alias = "max_age_days")]
+    pub max_age_days: Option<u32>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Metrics {
+    pub address: Option<String>,
+    pub port: u16,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Api {
+    pub address: Option<String>,
+    pub port: u16,
+    pub secret: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -44,6 +129,47 @@ pub struct NfInboundSettings {
 pub struct SocksInboundSettings {
     pub username: Option<String>,
     pub password: Option<String>,
+    // Accounts accepted with username/password authentication.
+    pub users: Option<Vec<InboundUser>>,
+    // SOCKS4 and SOCKS4a are accepted unless set to false.
+    pub socks4: Option<bool>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct InboundUser {
+    pub name: String,
+    pub password: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct HttpInboundSettings {
+    // Accounts accepted with Basic proxy authentication.
+    pub users: Option<Vec<InboundUser>>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct DnsInboundSettings {
+    #[serde(rename = "fakeDnsExclude", alias = "fake_dns_exclude")]
+    pub fake_dns_exclude: Option<Vec<String>>,
+    #[serde(rename = "fakeDnsInclude", alias = "fake_dns_include")]
+    pub fake_dns_include: Option<Vec<String>>,
+    // TTL of the answers, in seconds.
+    pub ttl: Option<u32>,
+    // The answer for domains routed to a drop outbound, nxdomain or zero.
+    #[serde(rename = "blockedResponse", alias = "blocked_response")]
+    pub blocked_response: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct TunnelInboundSettings {
+    // The host:port all traffic is forwarded to.
+    pub destination: Option<String>,
+    // One of tcp, udp or both, defaults to both.
+    pub network: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -57,12 +183,47 @@ pub struct ShadowsocksInboundSettings {
 #[serde(deny_unknown_fields)]
 pub struct TrojanInboundSettings {
     pub passwords: Option<Vec<String>>,
+    // Accounts whose sessions carry the name of the user, for the rules.
+    pub users: Option<Vec<InboundUser>>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct VlessInboundSettings {
+    pub uuids: Option<Vec<String>>,
+    pub fallback: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 #[serde(deny_unknown_fields)]
 pub struct WebSocketInboundSettings {
     pub path: Option<String>,
+    // Requests without this header, or with another value, are answered
+    // with 404 as for a wrong path.
+    #[serde(rename = "headerName", alias = "header_name")]
+    pub header_name: Option<String>,
+    #[serde(rename = "headerValue", alias = "header_value")]
+    pub header_value: Option<String>,
+    // The host:port requests that don't upgrade are relayed to.
+    pub fallback: Option<String>,
+    // Or the response they get, with the content of the file as the body.
+    #[serde(rename = "fallbackStatus", alias = "fallback_status")]
+    pub fallback_status: Option<u16>,
+    #[serde(rename = "fallbackFile", alias = "fallback_file")]
+    pub fallback_file: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct GrpcInboundSettings {
+    #[serde(rename = "serviceName", alias = "service_name")]
+    pub service_name: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct HttpUpgradeInboundSettings {
+    pub path: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -77,6 +238,11 @@ pub struct HcInboundSettings {
 #[serde(deny_unknown_fields)]
 pub struct AMuxInboundSettings {
     pub actors: Option<Vec<String>>,
+    #[serde(
+        rename = "maxSessionsPerConnection",
+        alias = "max_sessions_per_connection"
+    )]
+    pub max_sessions_per_connection: Option<u32>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -89,6 +255,35 @@ pub struct QuicInboundSettings {
     #[serde(rename = "rawCertificateKey", alias = "raw_certificate_key")]
     pub raw_certificate_key: Option<Vec<String>>,
     pub alpn: Option<Vec<String>>,
+    #[serde(
+        rename = "maxConcurrentBidiStreams",
+        alias = "max_concurrent_bidi_streams"
+    )]
+    pub max_concurrent_bidi_streams: Option<u32>,
+    #[serde(rename = "maxIdleTimeoutMs", alias = "max_idle_timeout_ms")]
+    pub max_idle_timeout_ms: Option<u32>,
+    #[serde(rename = "keepAliveIntervalMs", alias = "keep_alive_interval_ms")]
+    pub keep_alive_interval_ms: Option<u32>,
+    #[serde(rename = "congestionController", alias = "congestion_controller")]
+    pub congestion_controller: Option<String>,
+    #[serde(
+        rename = "certificateReloadInterval",
+        alias = "certificate_reload_interval"
+    )]
+    pub certificate_reload_interval: Option<u32>,
+    #[serde(rename = "clientCaCertificate", alias = "client_ca_certificate")]
+    pub client_ca_certificate: Option<String>,
+    #[serde(rename = "udpOverStream", alias = "udp_over_stream")]
+    pub udp_over_stream: Option<bool>,
+    #[serde(rename = "selfSignedSan", alias = "self_signed_san")]
+    pub self_signed_san: Option<String>,
+    #[serde(rename = "persistSelfSigned", alias = "persist_self_signed")]
+    pub persist_self_signed: Option<bool>,
+    #[serde(rename = "acceptUniStreams", alias = "accept_uni_streams")]
+    pub accept_uni_streams: Option<bool>,
+    #[serde(rename = "fallbackAlpn", alias = "fallback_alpn")]
+    pub fallback_alpn: Option<Vec<String>>,
+    pub fallback: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -104,6 +299,12 @@ pub struct TlsInboundSettings {
     pub ech_config: Option<String>,
     #[serde(rename = "echKey", alias = "ech_key")]
     pub ech_key: Option<String>,
+    #[serde(rename = "selfSignedSan", alias = "self_signed_san")]
+    pub self_signed_san: Option<String>,
+    #[serde(rename = "persistSelfSigned", alias = "persist_self_signed")]
+    pub persist_self_signed: Option<bool>,
+    #[serde(rename = "defaultSni", alias = "default_sni")]
+    pub default_sni: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -149,6 +350,16 @@ pub struct SocksOutboundSettings {
     pub password: Option<String>,
 }
 
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct HttpOutboundSettings {
+    pub address: Option<String>,
+    pub port: Option<u16>,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    // Additional headers sent with the CONNECT request.
+    pub headers: Option<HashMap<String, String>>,
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct ShadowsocksOutboundSettings {
     pub address: Option<String>,
@@ -156,10 +367,19 @@ pub struct ShadowsocksOutboundSettings {
     pub method: Option<String>,
     pub password: Option<String>,
     pub prefix: Option<String>,
+    // Path to a SIP003 plugin, e.g. v2ray-plugin.
+    pub plugin: Option<String>,
+    #[serde(rename = "pluginOpts", alias = "plugin_opts")]
+    pub plugin_opts: Option<String>,
+    // Sends UDP over TCP with the UoT v2 protocol.
+    #[serde(rename = "udpOverTcp", alias = "udp_over_tcp")]
+    pub udp_over_tcp: Option<bool>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct ObfsOutboundSettings {
+    // The mode of simple-obfs.
+    #[serde(alias = "mode")]
     pub method: Option<String>,
     pub host: Option<String>,
     pub path: Option<String>,
@@ -186,6 +406,92 @@ pub struct VlessOutboundSettings {
     pub address: Option<String>,
     pub port: Option<u16>,
     pub uuid: Option<String>,
+    pub flow: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct Hysteria2OutboundSettings {
+    pub address: Option<String>,
+    pub port: Option<u16>,
+    pub password: Option<String>,
+    #[serde(rename = "upMbps", alias = "up_mbps")]
+    pub up_mbps: Option<u32>,
+    #[serde(rename = "downMbps", alias = "down_mbps")]
+    pub down_mbps: Option<u32>,
+    pub sni: Option<String>,
+    pub insecure: Option<bool>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct TuicOutboundSettings {
+    pub address: Option<String>,
+    pub port: Option<u16>,
+    pub uuid: Option<String>,
+    pub password: Option<String>,
+    pub sni: Option<String>,
+    pub alpn: Option<Vec<String>>,
+    pub insecure: Option<bool>,
+    #[serde(rename = "udpRelayMode", alias = "udp_relay_mode")]
+    pub udp_relay_mode: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct WireGuardOutboundSettings {
+    #[serde(rename = "privateKey", alias = "private_key")]
+    pub private_key: Option<String>,
+    #[serde(rename = "peerPublicKey", alias = "peer_public_key")]
+    pub peer_public_key: Option<String>,
+    #[serde(rename = "presharedKey", alias = "preshared_key")]
+    pub preshared_key: Option<String>,
+    pub endpoint: Option<String>,
+    #[serde(rename = "allowedIps", alias = "allowed_ips")]
+    pub allowed_ips: Option<Vec<String>>,
+    #[serde(rename = "localAddress", alias = "local_address")]
+    pub local_address: Option<Vec<String>>,
+    pub mtu: Option<u32>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct SshOutboundSettings {
+    pub address: Option<String>,
+    pub port: Option<u16>,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    #[serde(rename = "privateKey", alias = "private_key")]
+    pub private_key: Option<String>,
+    #[serde(rename = "privateKeyPassphrase", alias = "private_key_passphrase")]
+    pub private_key_passphrase: Option<String>,
+    #[serde(rename = "hostKeyFingerprint", alias = "host_key_fingerprint")]
+    pub host_key_fingerprint: Option<String>,
+    #[serde(rename = "knownHosts", alias = "known_hosts")]
+    pub known_hosts: Option<String>,
+    #[serde(rename = "maxChannelsPerConn", alias = "max_channels_per_conn")]
+    pub max_channels_per_conn: Option<u32>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct H2OutboundSettings {
+    pub address: Option<String>,
+    pub port: Option<u16>,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    pub padding: Option<bool>,
+    pub tls: Option<TlsOutboundSettings>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct ShadowTlsOutboundSettings {
+    pub address: Option<String>,
+    pub port: Option<u16>,
+    pub password: Option<String>,
+    #[serde(rename = "handshakeServer", alias = "handshake_server")]
+    pub handshake_server: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -203,11 +509,14 @@ pub struct TryAllOutboundSettings {
     pub actors: Option<Vec<String>>,
     #[serde(rename = "delayBase", alias = "delay_base")]
     pub delay_base: Option<u32>,
+    #[serde(rename = "maxConcurrentAttempts", alias = "max_concurrent_attempts")]
+    pub max_concurrent_attempts: Option<u32>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct StaticOutboundSettings {
     pub actors: Option<Vec<String>>,
+    #[serde(alias = "strategy")]
     pub method: Option<String>,
 }
 
@@ -230,12 +539,53 @@ pub struct TlsOutboundSettings {
     pub ech_disable_dns_lookup: Option<bool>,
     #[serde(rename = "echConfigList", alias = "ech_config_list")]
     pub ech_config_list: Option<String>,
+    #[serde(rename = "echRequired", alias = "ech_required")]
+    pub ech_required: Option<bool>,
+    #[serde(rename = "certificateFingerprint", alias = "certificate_fingerprint")]
+    pub certificate_fingerprint: Option<String>,
+    #[serde(rename = "certificatePins", alias = "certificate_pins")]
+    pub certificate_pins: Option<Vec<String>>,
+    #[serde(rename = "pinOnly", alias = "pin_only")]
+    pub pin_only: Option<bool>,
+    #[serde(rename = "allowNameMismatch", alias = "allow_name_mismatch")]
+    pub allow_name_mismatch: Option<bool>,
+    #[serde(rename = "clientCertificate", alias = "client_certificate")]
+    pub client_certificate: Option<String>,
+    #[serde(rename = "clientCertificateKey", alias = "client_certificate_key")]
+    pub client_certificate_key: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct WebSocketOutboundSettings {
     pub path: Option<String>,
     pub headers: Option<HashMap<String, String>>,
+    // Up to this many bytes of the first write go in a header of the
+    // upgrade request, the "ed" of V2Ray and Xray.
+    #[serde(rename = "earlyDataSize", alias = "early_data_size")]
+    pub early_data_size: Option<u32>,
+    // Defaults to Sec-WebSocket-Protocol.
+    #[serde(rename = "earlyDataHeaderName", alias = "early_data_header_name")]
+    pub early_data_header_name: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct HttpUpgradeOutboundSettings {
+    pub path: Option<String>,
+    // A Host header replaces the host of the destination.
+    pub headers: Option<HashMap<String, String>>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct GrpcOutboundSettings {
+    #[serde(rename = "serviceName", alias = "service_name")]
+    pub service_name: Option<String>,
+    pub host: Option<String>,
+    // With an address, the outbound dials it through the actors and shares
+    // the connection across sessions, rather than running over the stream
+    // of a chain.
+    pub address: Option<String>,
+    pub port: Option<u16>,
+    pub actors: Option<Vec<String>>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -245,9 +595,26 @@ pub struct AMuxOutboundSettings {
     pub actors: Option<Vec<String>>,
     #[serde(rename = "maxAccepts", alias = "max_accepts")]
     pub max_accepts: Option<u32>,
+    #[serde(
+        alias = "maxSessionsPerConnection",
+        alias = "max_sessions_per_connection"
+    )]
     pub concurrency: Option<u32>,
     pub max_recv_bytes: Option<u64>,
     pub max_lifetime: Option<u64>,
+    #[serde(rename = "keepAliveInterval", alias = "keep_alive_interval")]
+    pub keep_alive_interval: Option<u32>,
+    #[serde(rename = "keepAliveMaxMissed", alias = "keep_alive_max_missed")]
+    pub keep_alive_max_missed: Option<u32>,
+    #[serde(rename = "openTimeout", alias = "open_timeout")]
+    pub open_timeout: Option<u32>,
+    #[serde(rename = "maxConnections", alias = "max_connections")]
+    pub max_connections: Option<u32>,
+    #[serde(rename = "connectionIdleTimeout", alias = "connection_idle_timeout")]
+    pub connection_idle_timeout: Option<u32>,
+    pub padding: Option<bool>,
+    #[serde(rename = "paddingMaxOverheadPct", alias = "padding_max_overhead_pct")]
+    pub padding_max_overhead_pct: Option<u32>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -264,6 +631,30 @@ pub struct QuicOutboundSettings {
     #[serde(rename = "rawCertificateKey", alias = "raw_certificate_key")]
     pub raw_certificate_key: Option<Vec<String>>,
     pub alpn: Option<Vec<String>>,
+    #[serde(rename = "congestionController", alias = "congestion_controller")]
+    pub congestion_controller: Option<String>,
+    #[serde(
+        rename = "maxSessionsPerConnection",
+        alias = "max_sessions_per_connection"
+    )]
+    pub max_sessions_per_connection: Option<u32>,
+    #[serde(rename = "udpOverStream", alias = "udp_over_stream")]
+    pub udp_over_stream: Option<bool>,
+    #[serde(rename = "certificateFingerprint", alias = "certificate_fingerprint")]
+    pub certificate_fingerprint: Option<String>,
+    #[serde(rename = "zeroRtt", alias = "zero_rtt")]
+    pub zero_rtt: Option<bool>,
+    #[serde(
+        rename = "reconnectOnNetworkChange",
+        alias = "reconnect_on_network_change"
+    )]
+    pub reconnect_on_network_change: Option<bool>,
+    #[serde(rename = "certificatePins", alias = "certificate_pins")]
+    pub certificate_pins: Option<Vec<String>>,
+    #[serde(rename = "pinOnly", alias = "pin_only")]
+    pub pin_only: Option<bool>,
+    #[serde(rename = "allowNameMismatch", alias = "allow_name_mismatch")]
+    pub allow_name_mismatch: Option<bool>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -307,6 +698,12 @@ pub struct FailOverOutboundSettings {
         alias = "health_check_success_percentage"
     )]
     pub health_check_success_percentage: Option<u32>,
+    #[serde(rename = "healthCheckUrl", alias = "health_check_url")]
+    pub health_check_url: Option<String>,
+    #[serde(rename = "failuresBeforeDown", alias = "failures_before_down")]
+    pub failures_before_down: Option<u32>,
+    #[serde(rename = "successesBeforeUp", alias = "successes_before_up")]
+    pub successes_before_up: Option<u32>,
     pub failover: Option<bool>,
     #[serde(rename = "fallbackCache", alias = "fallback_cache")]
     pub fallback_cache: Option<bool>,
@@ -319,6 +716,25 @@ pub struct FailOverOutboundSettings {
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct SelectOutboundSettings {
     pub actors: Option<Vec<String>>,
+    pub persist: Option<bool>,
+    #[serde(rename = "cacheFile", alias = "cache_file")]
+    pub cache_file: Option<String>,
+    #[serde(rename = "fallbackOnFailure", alias = "fallback_on_failure")]
+    pub fallback_on_failure: Option<bool>,
+    #[serde(rename = "failuresBeforeDown", alias = "failures_before_down")]
+    pub failures_before_down: Option<u32>,
+    #[serde(rename = "healthCheckUrl", alias = "health_check_url")]
+    pub health_check_url: Option<String>,
+    #[serde(rename = "checkInterval", alias = "check_interval")]
+    pub check_interval: Option<u32>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct UrlTestOutboundSettings {
+    pub actors: Option<Vec<String>>,
+    pub url: Option<String>,
+    pub interval: Option<u32>,
+    pub tolerance: Option<u32>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -332,6 +748,10 @@ pub struct Inbound {
     pub tag: Option<String>,
     pub address: Option<String>,
     pub port: Option<u16>,
+    // The outbound of the sessions no rule matches, in place of the default
+    // one.
+    #[serde(rename = "final")]
+    pub final_outbound: Option<String>,
     #[serde(flatten)]
     pub settings: InboundSettings,
 }
@@ -355,11 +775,23 @@ pub enum InboundSettings {
         #[serde(default)]
         settings: Option<TrojanInboundSettings>,
     },
+    Vless {
+        #[serde(default)]
+        settings: Option<VlessInboundSettings>,
+    },
     #[serde(rename = "websocket", alias = "ws")]
     WebSocket {
         #[serde(default)]
         settings: Option<WebSocketInboundSettings>,
     },
+    Grpc {
+        #[serde(default)]
+        settings: Option<GrpcInboundSettings>,
+    },
+    HttpUpgrade {
+        #[serde(default)]
+        settings: Option<HttpUpgradeInboundSettings>,
+    },
     Hc {
         #[serde(default)]
         settings: Option<HcInboundSettings>,
@@ -392,7 +824,27 @@ pub enum InboundSettings {
         #[serde(default)]
         settings: Option<SocksInboundSettings>,
     },
-    Http,
+    Http {
+        #[serde(default)]
+        settings: Option<HttpInboundSettings>,
+    },
+    // SOCKS and HTTP on the same port, the socks settings apply to both.
+    Mixed {
+        #[serde(default)]
+        settings: Option<SocksInboundSettings>,
+    },
+    Tunnel {
+        #[serde(default)]
+        settings: Option<TunnelInboundSettings>,
+    },
+    Dns {
+        #[serde(default)]
+        settings: Option<DnsInboundSettings>,
+    },
+    // Transparent proxy for TCP diverted with the REDIRECT target, Linux only.
+    Redirect,
+    // Transparent proxy for TCP diverted with the TPROXY target, Linux only.
+    Tproxy,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -413,6 +865,10 @@ pub enum OutboundSettings {
         #[serde(default)]
         settings: Option<SocksOutboundSettings>,
     },
+    Http {
+        #[serde(default)]
+        settings: Option<HttpOutboundSettings>,
+    },
     Shadowsocks {
         #[serde(default)]
         settings: Option<ShadowsocksOutboundSettings>,
@@ -433,6 +889,30 @@ pub enum OutboundSettings {
         #[serde(default)]
         settings: Option<VlessOutboundSettings>,
     },
+    Hysteria2 {
+        #[serde(default)]
+        settings: Option<Hysteria2OutboundSettings>,
+    },
+    Tuic {
+        #[serde(default)]
+        settings: Option<TuicOutboundSettings>,
+    },
+    WireGuard {
+        #[serde(default)]
+        settings: Option<WireGuardOutboundSettings>,
+    },
+    Ssh {
+        #[serde(default)]
+        settings: Option<SshOutboundSettings>,
+    },
+    H2 {
+        #[serde(default)]
+        settings: Option<H2OutboundSettings>,
+    },
+    ShadowTls {
+        #[serde(default)]
+        settings: Option<ShadowTlsOutboundSettings>,
+    },
     Reality {
         #[serde(default)]
         settings: Option<RealityOutboundSettings>,
@@ -454,6 +934,14 @@ pub enum OutboundSettings {
         #[serde(default)]
         settings: Option<WebSocketOutboundSettings>,
     },
+    Grpc {
+        #[serde(default)]
+        settings: Option<GrpcOutboundSettings>,
+    },
+    HttpUpgrade {
+        #[serde(default)]
+        settings: Option<HttpUpgradeOutboundSettings>,
+    },
     AMux {
         #[serde(default)]
         settings: Option<AMuxOutboundSettings>,
@@ -478,6 +966,11 @@ pub enum OutboundSettings {
         #[serde(default)]
         settings: Option<SelectOutboundSettings>,
     },
+    #[serde(alias = "auto")]
+    UrlTest {
+        #[serde(default)]
+        settings: Option<UrlTestOutboundSettings>,
+    },
     Plugin {
         #[serde(default)]
         settings: Option<PluginOutboundSettings>,
@@ -497,22 +990,91 @@ pub struct Rule {
     #[serde(rename = "domainSuffix", alias = "domain_suffix")]
     pub domain_suffix: Option<Vec<String>>,
     pub geoip: Option<Vec<String>>,
+    pub asn: Option<Vec<Asn>>,
     pub external: Option<Vec<String>>,
     #[serde(rename = "portRange", alias = "port_range")]
     pub port_range: Option<Vec<String>>,
+    #[serde(rename = "srcIp", alias = "src_ip")]
+    pub src_ip: Option<Vec<String>>,
+    #[serde(rename = "srcPort", alias = "src_port")]
+    pub src_port: Option<Vec<String>>,
     pub network: Option<Vec<String>>,
     #[serde(rename = "inboundTag", alias = "inbound_tag")]
     pub inbound_tag: Option<Vec<String>>,
     #[serde(rename = "processName", alias = "process_name")]
     pub process_name: Option<Vec<String>>,
+    pub process: Option<Vec<String>>,
+    #[serde(rename = "processPath", alias = "process_path")]
+    pub process_path: Option<Vec<String>>,
+    pub alpn: Option<Vec<String>>,
+    pub sni: Option<Vec<String>>,
+    #[serde(rename = "sniSuffix", alias = "sni_suffix")]
+    pub sni_suffix: Option<Vec<String>>,
+    pub user: Option<Vec<String>>,
+    // tls, http, quic, bittorrent, dns or unknown.
+    pub protocol: Option<Vec<String>>,
+    #[serde(rename = "ruleProvider", alias = "rule_provider")]
+    pub rule_provider: Option<Vec<String>>,
+    // The rules of an and, or or not rule, which have no target.
+    pub rules: Option<Vec<Rule>>,
+    #[serde(default)]
     pub target: String,
 }
 
+// An autonomous system, 13335 or "AS13335".
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(untagged)]
+pub enum Asn {
+    Number(u32),
+    Name(String),
+}
+
+impl Asn {
+    fn number(&self) -> Result<u32> {
+        match self {
+            Asn::Number(n) => Ok(*n),
+            Asn::Name(name) => {
+                let n = name.trim();
+                let n = n
+                    .strip_prefix("AS")
+                    .or_else(|| n.strip_prefix("as"))
+                    .unwrap_or(n);
+                n.parse()
+                    .map_err(|_| anyhow::anyhow!("invalid asn {}", name))
+            }
+        }
+    }
+}
+
+// A list of domains and IPs downloaded from the URL, which is refreshed
+// every `interval` seconds. The format is `text` or `yaml`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RuleProvider {
+    pub url: String,
+    pub interval: Option<u32>,
+    pub format: Option<String>,
+    pub path: Option<String>,
+    pub outbound: Option<String>,
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Router {
     pub rules: Option<Vec<Rule>>,
     #[serde(rename = "domainResolve", alias = "domain_resolve")]
     pub domain_resolve: Option<bool>,
+    #[serde(rename = "ruleProviders", alias = "rule_providers")]
+    pub rule_providers: Option<HashMap<String, RuleProvider>>,
+    // The mmdb of the asn rules, asn.mmdb in the assets by default.
+    #[serde(rename = "asnDatabase", alias = "asn_database")]
+    pub asn_database: Option<String>,
+    // Where geo.mmdb is downloaded from, every `geoipUpdateInterval`
+    // seconds through `geoipOutbound`.
+    #[serde(rename = "geoipUrl", alias = "geoip_url")]
+    pub geoip_url: Option<String>,
+    #[serde(rename = "geoipUpdateInterval", alias = "geoip_update_interval")]
+    pub geoip_update_interval: Option<u32>,
+    #[serde(rename = "geoipOutbound", alias = "geoip_outbound")]
+    pub geoip_outbound: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, Default)]
@@ -523,12 +1085,114 @@ pub struct Config {
     pub outbounds: Option<Vec<Outbound>>,
     pub router: Option<Router>,
     pub dns: Option<Dns>,
+    pub metrics: Option<Metrics>,
+    pub api: Option<Api>,
 }
 
 fn is_inline_certificate(certificate: &str) -> bool {
     certificate.contains("-----BEGIN")
 }
 
+fn to_internal_tls_outbound_settings(
+    ext_settings: &TlsOutboundSettings,
+) -> Result<internal::TlsOutboundSettings> {
+    if let Some(ext_ech_config_list) = &ext_settings.ech_config_list {
+        validate_non_empty_str(ext_ech_config_list, "echConfigList", "tls outbound")?;
+    }
+    let mut settings = internal::TlsOutboundSettings::new();
+    if let Some(ext_server_name) = &ext_settings.server_name {
+        settings.server_name = ext_server_name.clone();
+    }
+    if let Some(ext_alpn) = &ext_settings.alpn {
+        settings.alpn = ext_alpn.clone();
+    }
+    if let Some(ext_raw_certificate) = &ext_settings.raw_certificate {
+        settings.certificate = ext_raw_certificate.join("\n");
+    } else if let Some(ext_certificate) = &ext_settings.certificate {
+        if is_inline_certificate(ext_certificate) {
+            settings.certificate = ext_certificate.clone();
+        } else {
+            let cert = Path::new(&ext_certificate);
+            if cert.is_absolute() {
+                settings.certificate = cert.to_string_lossy().to_string();
+            } else {
+                let asset_loc = Path::new(&*crate::option::ASSET_LOCATION);
+                let path = asset_loc.join(cert).to_string_lossy().to_string();
+                settings.certificate = path;
+            }
+        }
+    }
+    if let Some(ext_raw_certificate_key) = &ext_settings.raw_certificate_key {
+        settings.certificate_key = ext_raw_certificate_key.join("\n");
+    } else if let Some(ext_certificate_key) = &ext_settings.certificate_key {
+        let key = Path::new(&ext_certificate_key);
+        if key.is_absolute() {
+            settings.certificate_key = key.to_string_lossy().to_string();
+        } else {
+            let asset_loc = Path::new(&*crate::option::ASSET_LOCATION);
+            let path = asset_loc.join(key).to_string_lossy().to_string();
+            settings.certificate_key = path;
+        }
+    }
+    match (
+        &ext_settings.client_certificate,
+        &ext_settings.client_certificate_key,
+    ) {
+        (Some(ext_cert), Some(ext_key)) => {
+            settings.client_certificate =
+                resolve_pem_file(ext_cert, "clientCertificate", "tls outbound")?;
+            settings.client_certificate_key =
+                resolve_pem_file(ext_key, "clientCertificateKey", "tls outbound")?;
+        }
+        (None, None) => {}
+        _ => {
+            return Err(anyhow::anyhow!(
+                "invalid [tls outbound] settings: clientCertificate and clientCertificateKey must be set together"
+            ));
+        }
+    }
+    if let Some(ext_insecure) = ext_settings.insecure {
+        settings.insecure = ext_insecure;
+    }
+    if let Some(ext_allow_name_mismatch) = ext_settings.allow_name_mismatch {
+        if settings.insecure && ext_allow_name_mismatch {
+            return Err(anyhow::anyhow!(
+                "invalid [tls outbound] settings: insecure and allowNameMismatch can't be set together"
+            ));
+        }
+        settings.allow_name_mismatch = ext_allow_name_mismatch;
+    }
+    if let Some(ext_ech) = ext_settings.ech {
+        settings.ech = ext_ech;
+    }
+    if let Some(ext_ech_disable_dns_lookup) = ext_settings.ech_disable_dns_lookup {
+        settings.ech_disable_dns_lookup = ext_ech_disable_dns_lookup;
+    }
+    if let Some(ext_ech_config_list) = &ext_settings.ech_config_list {
+        settings.ech_config_list = ext_ech_config_list.clone();
+    }
+    if let Some(ext_ech_required) = ext_settings.ech_required {
+        if ext_ech_required && !settings.ech {
+            return Err(anyhow::anyhow!(
+                "invalid [tls outbound] settings: echRequired requires ech"
+            ));
+        }
+        settings.ech_required = ext_ech_required;
+    }
+    if let Some(ext_fingerprint) = &ext_settings.certificate_fingerprint {
+        settings.certificate_fingerprint = validate_fingerprint(ext_fingerprint, "tls outbound")?;
+    }
+    settings.certificate_pins = validate_certificate_pins(
+        ext_settings.certificate_pins.as_deref().unwrap_or_default(),
+        ext_settings.pin_only,
+        "tls outbound",
+    )?;
+    if let Some(ext_pin_only) = ext_settings.pin_only {
+        settings.pin_only = ext_pin_only;
+    }
+    Ok(settings)
+}
+
 fn validate_non_empty_str(value: &str, field_name: &str, protocol: &str) -> Result<()> {
     if value.trim().is_empty() {
         return Err(anyhow::anyhow!(
@@ -540,41 +1204,402 @@ fn validate_non_empty_str(value: &str, field_name: &str, protocol: &str) -> Resu
     Ok(())
 }
 
-pub fn to_internal(mut config: Config) -> Result<internal::Config> {
-    let mut log = internal::Log::new();
-    if let Some(ext_log) = &config.log {
-        if let Some(ext_level) = &ext_log.level {
-            match ext_level.to_lowercase().as_str() {
-                "trace" => log.level = protobuf::EnumOrUnknown::new(internal::log::Level::TRACE),
-                "debug" => log.level = protobuf::EnumOrUnknown::new(internal::log::Level::DEBUG),
-                "info" => log.level = protobuf::EnumOrUnknown::new(internal::log::Level::INFO),
-                "warn" => log.level = protobuf::EnumOrUnknown::new(internal::log::Level::WARN),
-                "error" => log.level = protobuf::EnumOrUnknown::new(internal::log::Level::ERROR),
-                "none" => log.level = protobuf::EnumOrUnknown::new(internal::log::Level::NONE),
-                _ => log.level = protobuf::EnumOrUnknown::new(internal::log::Level::WARN),
-            }
-        }
-
-        if let Some(ext_output) = &ext_log.output {
-            match ext_output.as_str() {
-                "console" => {
-                    log.output = protobuf::EnumOrUnknown::new(internal::log::Output::CONSOLE)
-                }
-                _ => {
-                    log.output = protobuf::EnumOrUnknown::new(internal::log::Output::FILE);
-                    log.output_file = ext_output.clone();
-                }
-            }
-        }
+fn validate_congestion_controller(value: &str, protocol: &str) -> Result<()> {
+    match value {
+        "bbr" | "cubic" | "newreno" => Ok(()),
+        _ => Err(anyhow::anyhow!(
+            "invalid [{}] settings: unknown congestionController \"{}\", expected one of bbr, cubic, newreno",
+            protocol,
+            value
+        )),
+    }
+}
 
-        if let Some(ext_format) = &ext_log.format {
-            match ext_format.to_lowercase().as_str() {
+fn validate_non_zero(value: u32, field_name: &str, protocol: &str) -> Result<()> {
+    if value == 0 {
+        return Err(anyhow::anyhow!(
+            "invalid [{}] settings: {} must be greater than 0",
+            protocol,
+            field_name
+        ));
+    }
+    Ok(())
+}
+
+// Returns the fingerprint in lowercase hex without separators.
+fn validate_fingerprint(value: &str, protocol: &str) -> Result<String> {
+    let fingerprint = value.replace(':', "").to_ascii_lowercase();
+    if fingerprint.len() != 64 || !fingerprint.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(anyhow::anyhow!(
+            "invalid [{}] settings: certificateFingerprint must be a hex encoded SHA-256 digest",
+            protocol
+        ));
+    }
+    Ok(fingerprint)
+}
+
+// Inline PEM is kept as is, paths are resolved against the asset location
+// and have to exist.
+fn resolve_pem_file(value: &str, field: &str, protocol: &str) -> Result<String> {
+    if is_inline_certificate(value) {
+        return Ok(value.to_string());
+    }
+    let path = Path::new(&*crate::option::ASSET_LOCATION).join(value);
+    if !path.exists() {
+        return Err(anyhow::anyhow!(
+            "invalid [{}] settings: {} {} not found",
+            protocol,
+            field,
+            path.display()
+        ));
+    }
+    Ok(path.to_string_lossy().to_string())
+}
+
+// Pins are base64 encoded SHA-256 digests of a SubjectPublicKeyInfo.
+fn validate_certificate_pins(
+    pins: &[String],
+    pin_only: Option<bool>,
+    protocol: &str,
+) -> Result<Vec<String>> {
+    for pin in pins {
+        let valid = pin.len() == 44
+            && pin.ends_with('=')
+            && pin[..43]
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '+' || c == '/');
+        if !valid {
+            return Err(anyhow::anyhow!(
+                "invalid [{}] settings: certificatePins must be base64 encoded SHA-256 digests, got {}",
+                protocol,
+                pin
+            ));
+        }
+    }
+    if pin_only == Some(true) && pins.is_empty() {
+        return Err(anyhow::anyhow!(
+            "invalid [{}] settings: pinOnly requires certificatePins",
+            protocol
+        ));
+    }
+    Ok(pins.to_vec())
+}
+
+fn to_internal_domains(
+    fulls: &Option<Vec<String>>,
+    keywords: &Option<Vec<String>>,
+    suffixes: &Option<Vec<String>>,
+) -> Vec<internal::router::rule::Domain> {
+    let types = [
+        (fulls, internal::router::rule::domain::Type::FULL),
+        (keywords, internal::router::rule::domain::Type::PLAIN),
+        (suffixes, internal::router::rule::domain::Type::DOMAIN),
+    ];
+    let mut domains = Vec::new();
+    for (values, type_) in types {
+        for value in values.iter().flatten() {
+            let mut domain = internal::router::rule::Domain::new();
+            domain.type_ = protobuf::EnumOrUnknown::new(type_);
+            domain.value = value.to_owned();
+            domains.push(domain);
+        }
+    }
+    domains
+}
+
+/// Converts rules for replacing the rules of a running router. `ext_router`
+/// is the router of its config, with the rule providers and the ASN database
+/// the rules may use. A FINAL rule isn't accepted, the default outbound is
+/// picked as the config is loaded.
+pub fn to_internal_rules(
+    ext_rules: &mut [Rule],
+    ext_router: Option<&Router>,
+) -> Result<Vec<internal::router::Rule>> {
+    let asn_database = ext_router.and_then(|x| x.asn_database.as_ref());
+    let rule_providers = ext_router.and_then(|x| x.rule_providers.as_ref());
+    let mut rules = Vec::new();
+    for ext_rule in ext_rules.iter_mut() {
+        if ext_rule.type_field.as_deref() == Some("FINAL") {
+            return Err(anyhow::anyhow!("FINAL rule in rules replacing others"));
+        }
+        rules.push(to_internal_routing_rule(
+            ext_rule,
+            asn_database,
+            rule_providers,
+        )?);
+    }
+    Ok(rules)
+}
+
+// A rule of the router, which has a target.
+fn to_internal_routing_rule(
+    ext_rule: &mut Rule,
+    asn_database: Option<&String>,
+    rule_providers: Option<&HashMap<String, RuleProvider>>,
+) -> Result<internal::router::Rule> {
+    let target_tag = std::mem::take(&mut ext_rule.target);
+    if target_tag.is_empty() {
+        return Err(anyhow::anyhow!("rule without target"));
+    }
+    let mut rule = to_internal_rule(ext_rule, asn_database, rule_providers, 0)?;
+    rule.target_tag = target_tag;
+    Ok(rule)
+}
+
+// How deep the and, or and not rules may nest.
+const MAX_RULE_DEPTH: usize = 8;
+
+fn to_internal_rule(
+    ext_rule: &mut Rule,
+    asn_database: Option<&String>,
+    rule_providers: Option<&HashMap<String, RuleProvider>>,
+    depth: usize,
+) -> Result<internal::router::Rule> {
+    let mut rule = internal::router::Rule::new();
+    match ext_rule.type_field.as_deref() {
+        Some(logic @ ("and" | "or" | "not")) => {
+            if depth >= MAX_RULE_DEPTH {
+                return Err(anyhow::anyhow!(
+                    "rules nested deeper than {}",
+                    MAX_RULE_DEPTH
+                ));
+            }
+            let ext_sub_rules = ext_rule.rules.take().unwrap_or_default();
+            if ext_sub_rules.is_empty() {
+                return Err(anyhow::anyhow!("{} rule without rules", logic));
+            }
+            if logic == "not" && ext_sub_rules.len() != 1 {
+                return Err(anyhow::anyhow!("not rule with more than one rule"));
+            }
+            rule.logic = logic.to_owned();
+            for mut ext_sub_rule in ext_sub_rules {
+                if !ext_sub_rule.target.is_empty() {
+                    return Err(anyhow::anyhow!("target in a {} rule", logic));
+                }
+                rule.sub_rules.push(to_internal_rule(
+                    &mut ext_sub_rule,
+                    asn_database,
+                    rule_providers,
+                    depth + 1,
+                )?);
+            }
+        }
+        _ => {
+            if ext_rule.rules.is_some() {
+                return Err(anyhow::anyhow!("rules in a rule not of and, or or not"));
+            }
+        }
+    }
+    if let Some(ext_ips) = ext_rule.ip.as_mut() {
+        for ext_ip in ext_ips.drain(0..) {
+            rule.ip_cidrs.push(ext_ip);
+        }
+    }
+    if let Some(ext_domains) = ext_rule.domain.as_mut() {
+        for ext_domain in ext_domains.drain(0..) {
+            let mut domain = internal::router::rule::Domain::new();
+            domain.type_ = protobuf::EnumOrUnknown::new(internal::router::rule::domain::Type::FULL);
+            domain.value = ext_domain;
+            rule.domains.push(domain);
+        }
+    }
+    if let Some(ext_domain_keywords) = ext_rule.domain_keyword.as_mut() {
+        for ext_domain_keyword in ext_domain_keywords.drain(0..) {
+            let mut domain = internal::router::rule::Domain::new();
+            domain.type_ =
+                protobuf::EnumOrUnknown::new(internal::router::rule::domain::Type::PLAIN);
+            domain.value = ext_domain_keyword;
+            rule.domains.push(domain);
+        }
+    }
+    if let Some(ext_domain_suffixes) = ext_rule.domain_suffix.as_mut() {
+        for ext_domain_suffix in ext_domain_suffixes.drain(0..) {
+            let mut domain = internal::router::rule::Domain::new();
+            domain.type_ =
+                protobuf::EnumOrUnknown::new(internal::router::rule::domain::Type::DOMAIN);
+            domain.value = ext_domain_suffix;
+            rule.domains.push(domain);
+        }
+    }
+    if let Some(ext_geoips) = ext_rule.geoip.as_mut() {
+        for ext_geoip in ext_geoips.drain(0..) {
+            let mut mmdb = internal::router::rule::Mmdb::new();
+            let asset_loc = Path::new(&*crate::option::ASSET_LOCATION);
+            mmdb.file = asset_loc.join("geo.mmdb").to_string_lossy().to_string();
+            mmdb.country_code = ext_geoip;
+            rule.mmdbs.push(mmdb)
+        }
+    }
+    if let Some(ext_asns) = ext_rule.asn.as_ref() {
+        let asset_loc = Path::new(&*crate::option::ASSET_LOCATION);
+        let file = match asn_database {
+            Some(file) => asset_loc.join(file),
+            None => asset_loc.join("asn.mmdb"),
+        };
+        if !file.exists() {
+            return Err(anyhow::anyhow!("asn database {} not found", file.display()));
+        }
+        for ext_asn in ext_asns {
+            let mut asn = internal::router::rule::Asn::new();
+            asn.file = file.to_string_lossy().to_string();
+            asn.number = ext_asn.number()?;
+            rule.asns.push(asn);
+        }
+    }
+    if let Some(ext_externals) = ext_rule.external.as_mut() {
+        for ext_external in ext_externals.drain(0..) {
+            external_rule::add_external_rule(&mut rule, &ext_external)?;
+        }
+    }
+    if let Some(ext_port_ranges) = ext_rule.port_range.as_mut() {
+        for ext_port_range in ext_port_ranges.drain(0..) {
+            PortSet::parse(&ext_port_range)?;
+            rule.port_ranges.push(ext_port_range);
+        }
+    }
+    if let Some(ext_src_ips) = ext_rule.src_ip.as_mut() {
+        rule.source_ip_cidrs.append(ext_src_ips);
+    }
+    if let Some(ext_src_ports) = ext_rule.src_port.as_mut() {
+        for ext_src_port in ext_src_ports.drain(0..) {
+            PortSet::parse(&ext_src_port)?;
+            rule.source_ports.push(ext_src_port);
+        }
+    }
+    if let Some(ext_networks) = ext_rule.network.as_mut() {
+        for ext_network in ext_networks.drain(0..) {
+            rule.networks.push(ext_network);
+        }
+    }
+    if let Some(ext_its) = ext_rule.inbound_tag.as_mut() {
+        for it in ext_its.drain(0..) {
+            rule.inbound_tags.push(it);
+        }
+    }
+    if let Some(ext_alpns) = ext_rule.alpn.as_mut() {
+        for alpn in ext_alpns.drain(0..) {
+            rule.alpns.push(alpn);
+        }
+    }
+    if let Some(ext_snis) = ext_rule.sni.as_mut() {
+        for ext_sni in ext_snis.drain(0..) {
+            let mut sni = internal::router::rule::Domain::new();
+            sni.type_ = protobuf::EnumOrUnknown::new(internal::router::rule::domain::Type::FULL);
+            sni.value = ext_sni;
+            rule.snis.push(sni);
+        }
+    }
+    if let Some(ext_sni_suffixes) = ext_rule.sni_suffix.as_mut() {
+        for ext_sni_suffix in ext_sni_suffixes.drain(0..) {
+            let mut sni = internal::router::rule::Domain::new();
+            sni.type_ = protobuf::EnumOrUnknown::new(internal::router::rule::domain::Type::DOMAIN);
+            sni.value = ext_sni_suffix;
+            rule.snis.push(sni);
+        }
+    }
+    if let Some(ext_users) = ext_rule.user.as_mut() {
+        for user in ext_users.drain(0..) {
+            rule.users.push(user);
+        }
+    }
+    #[cfg(feature = "rule-process-name")]
+    if let Some(ext_process_names) = ext_rule.process_name.as_mut() {
+        for process_name in ext_process_names.drain(0..) {
+            rule.process_names.push(process_name);
+        }
+    }
+    #[cfg(feature = "rule-process-name")]
+    if let Some(ext_processes) = ext_rule.process.as_mut() {
+        rule.processes.append(ext_processes);
+    }
+    #[cfg(feature = "rule-process-name")]
+    if let Some(ext_process_paths) = ext_rule.process_path.as_mut() {
+        rule.process_paths.append(ext_process_paths);
+    }
+    if let Some(ext_protocols) = ext_rule.protocol.as_mut() {
+        for protocol in ext_protocols.drain(0..) {
+            let protocol = protocol.to_lowercase();
+            if !crate::common::sniff::PROTOCOLS.contains(&protocol.as_str()) {
+                return Err(anyhow::anyhow!("unknown protocol {}", protocol));
+            }
+            rule.protocols.push(protocol);
+        }
+    }
+    if let Some(ext_providers) = ext_rule.rule_provider.as_mut() {
+        for name in ext_providers.drain(0..) {
+            let defined = rule_providers.is_some_and(|x| x.contains_key(&name));
+            if !defined {
+                return Err(anyhow::anyhow!("unknown rule provider {}", name));
+            }
+            rule.rule_providers.push(name);
+        }
+    }
+    Ok(rule)
+}
+
+pub fn to_internal(mut config: Config) -> Result<internal::Config> {
+    let mut log = internal::Log::new();
+    if let Some(ext_log) = &config.log {
+        if let Some(ext_level) = &ext_log.level {
+            match ext_level.to_lowercase().as_str() {
+                "trace" => log.level = protobuf::EnumOrUnknown::new(internal::log::Level::TRACE),
+                "debug" => log.level = protobuf::EnumOrUnknown::new(internal::log::Level::DEBUG),
+                "info" => log.level = protobuf::EnumOrUnknown::new(internal::log::Level::INFO),
+                "warn" => log.level = protobuf::EnumOrUnknown::new(internal::log::Level::WARN),
+                "error" => log.level = protobuf::EnumOrUnknown::new(internal::log::Level::ERROR),
+                "none" => log.level = protobuf::EnumOrUnknown::new(internal::log::Level::NONE),
+                _ => log.level = protobuf::EnumOrUnknown::new(internal::log::Level::WARN),
+            }
+        }
+
+        if let Some(ext_output) = &ext_log.output {
+            match ext_output.as_str() {
+                "console" => {
+                    log.output = protobuf::EnumOrUnknown::new(internal::log::Output::CONSOLE)
+                }
+                _ => {
+                    log.output = protobuf::EnumOrUnknown::new(internal::log::Output::FILE);
+                    log.output_file = ext_output.clone();
+                }
+            }
+        }
+
+        if let Some(ext_format) = &ext_log.format {
+            match ext_format.to_lowercase().as_str() {
                 "compact" => {
                     log.format = protobuf::EnumOrUnknown::new(internal::log::Format::COMPACT)
                 }
                 _ => log.format = protobuf::EnumOrUnknown::new(internal::log::Format::FULL),
             }
         }
+
+        if let Some(ext_key_log_file) = &ext_log.key_log_file {
+            log.key_log_file = ext_key_log_file.clone();
+        }
+
+        if let Some(ext_access_log) = &ext_log.access_log {
+            log.access_log = ext_access_log.clone();
+        }
+
+        if let Some(ext_access_log_format) = &ext_log.access_log_format {
+            log.access_log_format = ext_access_log_format.clone();
+        }
+
+        if let Some(ext_access_log_start) = ext_log.access_log_start {
+            log.access_log_start = ext_access_log_start;
+        }
+
+        if let Some(ext_max_size_mb) = ext_log.max_size_mb {
+            log.max_size_mb = ext_max_size_mb;
+        }
+
+        if let Some(ext_max_backups) = ext_log.max_backups {
+            log.max_backups = ext_max_backups;
+        }
+
+        if let Some(ext_max_age_days) = ext_log.max_age_days {
+            log.max_age_days = ext_max_age_days;
+        }
     }
 
     let mut inbounds = Vec::new();
@@ -592,6 +1617,20 @@ pub fn to_internal(mut config: Config) -> Result<internal::Config> {
             if let Some(ext_port) = ext_inbound.port {
                 inbound.port = ext_port as u32;
             }
+            if let Some(ext_final) = &ext_inbound.final_outbound {
+                if inbound.tag.is_empty() {
+                    return Err(anyhow::anyhow!("final outbound of an inbound without tag"));
+                }
+                let defined = config
+                    .outbounds
+                    .iter()
+                    .flatten()
+                    .any(|x| x.tag.as_ref() == Some(ext_final));
+                if !defined {
+                    return Err(anyhow::anyhow!("unknown final outbound {}", ext_final));
+                }
+                inbound.final_outbound = ext_final.clone();
+            }
 
             match &ext_inbound.settings {
                 #[cfg(any(
@@ -748,8 +1787,16 @@ pub fn to_internal(mut config: Config) -> Result<internal::Config> {
                 }
                 InboundSettings::Socks {
                     settings: ext_settings,
+                }
+                | InboundSettings::Mixed {
+                    settings: ext_settings,
                 } => {
-                    inbound.protocol = "socks".to_string();
+                    inbound.protocol =
+                        if matches!(ext_inbound.settings, InboundSettings::Mixed { .. }) {
+                            "mixed".to_string()
+                        } else {
+                            "socks".to_string()
+                        };
                     if let Some(ext_settings) = ext_settings {
                         let mut settings = internal::SocksInboundSettings::new();
                         if let Some(ext_username) = &ext_settings.username {
@@ -758,13 +1805,87 @@ pub fn to_internal(mut config: Config) -> Result<internal::Config> {
                         if let Some(ext_password) = &ext_settings.password {
                             settings.password = ext_password.clone();
                         }
+                        if let Some(ext_users) = &ext_settings.users {
+                            for ext_user in ext_users {
+                                let mut user = internal::socks_inbound_settings::User::new();
+                                user.name = ext_user.name.clone();
+                                user.password = ext_user.password.clone();
+                                settings.users.push(user);
+                            }
+                        }
+                        if let Some(ext_socks4) = ext_settings.socks4 {
+                            settings.socks4 = Some(ext_socks4);
+                        }
+                        let settings = settings.write_to_bytes().unwrap();
+                        inbound.settings = settings;
+                    }
+                    inbounds.push(inbound);
+                }
+                InboundSettings::Tunnel {
+                    settings: ext_settings,
+                } => {
+                    inbound.protocol = "tunnel".to_string();
+                    if let Some(ext_settings) = ext_settings {
+                        let mut settings = internal::TunnelInboundSettings::new();
+                        if let Some(ext_destination) = &ext_settings.destination {
+                            settings.destination = ext_destination.clone();
+                        }
+                        if let Some(ext_network) = &ext_settings.network {
+                            settings.network = ext_network.clone();
+                        }
+                        let settings = settings.write_to_bytes().unwrap();
+                        inbound.settings = settings;
+                    }
+                    inbounds.push(inbound);
+                }
+                InboundSettings::Dns {
+                    settings: ext_settings,
+                } => {
+                    inbound.protocol = "dns".to_string();
+                    if let Some(ext_settings) = ext_settings {
+                        let mut settings = internal::DnsInboundSettings::new();
+                        if let Some(ext_excludes) = &ext_settings.fake_dns_exclude {
+                            settings.fake_dns_exclude = ext_excludes.clone();
+                        }
+                        if let Some(ext_includes) = &ext_settings.fake_dns_include {
+                            settings.fake_dns_include = ext_includes.clone();
+                        }
+                        if let Some(ext_ttl) = ext_settings.ttl {
+                            settings.ttl = ext_ttl;
+                        }
+                        if let Some(ext_blocked_response) = &ext_settings.blocked_response {
+                            settings.blocked_response = ext_blocked_response.clone();
+                        }
                         let settings = settings.write_to_bytes().unwrap();
                         inbound.settings = settings;
                     }
                     inbounds.push(inbound);
                 }
-                InboundSettings::Http => {
+                InboundSettings::Redirect => {
+                    inbound.protocol = "redirect".to_string();
+                    inbounds.push(inbound);
+                }
+                InboundSettings::Tproxy => {
+                    inbound.protocol = "tproxy".to_string();
+                    inbounds.push(inbound);
+                }
+                InboundSettings::Http {
+                    settings: ext_settings,
+                } => {
                     inbound.protocol = "http".to_string();
+                    if let Some(ext_settings) = ext_settings {
+                        let mut settings = internal::HttpInboundSettings::new();
+                        if let Some(ext_users) = &ext_settings.users {
+                            for ext_user in ext_users {
+                                let mut user = internal::http_inbound_settings::User::new();
+                                user.name = ext_user.name.clone();
+                                user.password = ext_user.password.clone();
+                                settings.users.push(user);
+                            }
+                        }
+                        let settings = settings.write_to_bytes().unwrap();
+                        inbound.settings = settings;
+                    }
                     inbounds.push(inbound);
                 }
                 InboundSettings::Shadowsocks {
@@ -795,6 +1916,32 @@ pub fn to_internal(mut config: Config) -> Result<internal::Config> {
                                 settings.passwords.push(ext_pass.clone());
                             }
                         }
+                        if let Some(ext_users) = &ext_settings.users {
+                            for ext_user in ext_users {
+                                let mut user = internal::trojan_inbound_settings::User::new();
+                                user.name = ext_user.name.clone();
+                                user.password = ext_user.password.clone();
+                                settings.users.push(user);
+                            }
+                        }
+                        let settings = settings.write_to_bytes().unwrap();
+                        inbound.settings = settings;
+                    }
+                    inbounds.push(inbound);
+                }
+                InboundSettings::Vless {
+                    settings: ext_settings,
+                } => {
+                    inbound.protocol = "vless".to_string();
+                    if let Some(ext_settings) = ext_settings {
+                        let mut settings = internal::VlessInboundSettings::new();
+                        if let Some(ext_uuids) = &ext_settings.uuids {
+                            settings.uuids = ext_uuids.clone();
+                        }
+                        if let Some(ext_fallback) = &ext_settings.fallback {
+                            validate_non_empty_str(ext_fallback, "fallback", "vless inbound")?;
+                            settings.fallback = ext_fallback.clone();
+                        }
                         let settings = settings.write_to_bytes().unwrap();
                         inbound.settings = settings;
                     }
@@ -814,6 +1961,49 @@ pub fn to_internal(mut config: Config) -> Result<internal::Config> {
                                 settings.path = "/".to_string();
                             }
                         };
+                        if let Some(ext_header_name) = &ext_settings.header_name {
+                            settings.header_name = ext_header_name.clone();
+                        }
+                        if let Some(ext_header_value) = &ext_settings.header_value {
+                            settings.header_value = ext_header_value.clone();
+                        }
+                        if let Some(ext_fallback) = &ext_settings.fallback {
+                            settings.fallback = ext_fallback.clone();
+                        }
+                        if let Some(ext_fallback_status) = ext_settings.fallback_status {
+                            settings.fallback_status = ext_fallback_status as u32;
+                        }
+                        if let Some(ext_fallback_file) = &ext_settings.fallback_file {
+                            settings.fallback_file = ext_fallback_file.clone();
+                        }
+                        let settings = settings.write_to_bytes().unwrap();
+                        inbound.settings = settings;
+                    }
+                    inbounds.push(inbound);
+                }
+                InboundSettings::Grpc {
+                    settings: ext_settings,
+                } => {
+                    inbound.protocol = "grpc".to_string();
+                    if let Some(ext_settings) = ext_settings {
+                        let mut settings = internal::GrpcInboundSettings::new();
+                        if let Some(ext_service_name) = &ext_settings.service_name {
+                            settings.service_name = ext_service_name.clone();
+                        }
+                        let settings = settings.write_to_bytes().unwrap();
+                        inbound.settings = settings;
+                    }
+                    inbounds.push(inbound);
+                }
+                InboundSettings::HttpUpgrade {
+                    settings: ext_settings,
+                } => {
+                    inbound.protocol = "httpupgrade".to_string();
+                    if let Some(ext_settings) = ext_settings {
+                        let mut settings = internal::HttpUpgradeInboundSettings::new();
+                        if let Some(ext_path) = &ext_settings.path {
+                            settings.path = ext_path.clone();
+                        }
                         let settings = settings.write_to_bytes().unwrap();
                         inbound.settings = settings;
                     }
@@ -830,6 +2020,8 @@ pub fn to_internal(mut config: Config) -> Result<internal::Config> {
                                 settings.actors.push(ext_actor.clone());
                             }
                         }
+                        settings.max_sessions_per_connection =
+                            ext_settings.max_sessions_per_connection.unwrap_or_default();
                         let settings = settings.write_to_bytes().unwrap();
                         inbound.settings = settings;
                     }
@@ -874,6 +2066,87 @@ pub fn to_internal(mut config: Config) -> Result<internal::Config> {
                                 settings.alpn.push(ext_alpn.clone());
                             }
                         }
+                        if let Some(ext_streams) = ext_settings.max_concurrent_bidi_streams {
+                            validate_non_zero(
+                                ext_streams,
+                                "maxConcurrentBidiStreams",
+                                "quic inbound",
+                            )?;
+                            settings.max_concurrent_bidi_streams = ext_streams;
+                        }
+                        if let Some(ext_idle_timeout) = ext_settings.max_idle_timeout_ms {
+                            validate_non_zero(
+                                ext_idle_timeout,
+                                "maxIdleTimeoutMs",
+                                "quic inbound",
+                            )?;
+                            settings.max_idle_timeout_ms = ext_idle_timeout;
+                        }
+                        if let Some(ext_keep_alive) = ext_settings.keep_alive_interval_ms {
+                            validate_non_zero(
+                                ext_keep_alive,
+                                "keepAliveIntervalMs",
+                                "quic inbound",
+                            )?;
+                            let idle_timeout = ext_settings
+                                .max_idle_timeout_ms
+                                .unwrap_or(*crate::option::QUIC_MAX_IDLE_TIMEOUT_MS);
+                            if ext_keep_alive >= idle_timeout {
+                                return Err(anyhow::anyhow!(
+                                    "invalid [quic inbound] settings: keepAliveIntervalMs ({}) must be less than maxIdleTimeoutMs ({})",
+                                    ext_keep_alive,
+                                    idle_timeout
+                                ));
+                            }
+                            settings.keep_alive_interval_ms = ext_keep_alive;
+                        }
+                        if let Some(ext_cc) = &ext_settings.congestion_controller {
+                            validate_congestion_controller(ext_cc, "quic inbound")?;
+                            settings.congestion_controller = ext_cc.clone();
+                        }
+                        if let Some(ext_interval) = ext_settings.certificate_reload_interval {
+                            settings.certificate_reload_interval = ext_interval;
+                        }
+                        if let Some(ext_client_ca) = &ext_settings.client_ca_certificate {
+                            validate_non_empty_str(
+                                ext_client_ca,
+                                "clientCaCertificate",
+                                "quic inbound",
+                            )?;
+                            if is_inline_certificate(ext_client_ca) {
+                                settings.client_ca_certificate = ext_client_ca.clone();
+                            } else {
+                                let cert = Path::new(&ext_client_ca);
+                                if cert.is_absolute() {
+                                    settings.client_ca_certificate =
+                                        cert.to_string_lossy().to_string();
+                                } else {
+                                    let asset_loc = Path::new(&*crate::option::ASSET_LOCATION);
+                                    let path = asset_loc.join(cert).to_string_lossy().to_string();
+                                    settings.client_ca_certificate = path;
+                                }
+                            }
+                        }
+                        if let Some(ext_udp_over_stream) = ext_settings.udp_over_stream {
+                            settings.udp_over_stream = ext_udp_over_stream;
+                        }
+                        if let Some(ext_san) = &ext_settings.self_signed_san {
+                            validate_non_empty_str(ext_san, "selfSignedSan", "quic inbound")?;
+                            settings.self_signed_san = ext_san.clone();
+                        }
+                        if let Some(ext_persist) = ext_settings.persist_self_signed {
+                            settings.persist_self_signed = ext_persist;
+                        }
+                        if let Some(ext_accept_uni) = ext_settings.accept_uni_streams {
+                            settings.accept_uni_streams = ext_accept_uni;
+                        }
+                        if let Some(ext_fallback_alpn) = &ext_settings.fallback_alpn {
+                            settings.fallback_alpns = ext_fallback_alpn.clone();
+                        }
+                        if let Some(ext_fallback) = &ext_settings.fallback {
+                            validate_non_empty_str(ext_fallback, "fallback", "quic inbound")?;
+                            settings.fallback = ext_fallback.clone();
+                        }
                         let settings = settings.write_to_bytes().unwrap();
                         inbound.settings = settings;
                     }
@@ -935,6 +2208,16 @@ pub fn to_internal(mut config: Config) -> Result<internal::Config> {
                         if let Some(ext_ech_key) = &ext_settings.ech_key {
                             settings.ech_key = ext_ech_key.clone();
                         }
+                        if let Some(ext_san) = &ext_settings.self_signed_san {
+                            validate_non_empty_str(ext_san, "selfSignedSan", "tls inbound")?;
+                            settings.self_signed_san = ext_san.clone();
+                        }
+                        if let Some(ext_persist) = ext_settings.persist_self_signed {
+                            settings.persist_self_signed = ext_persist;
+                        }
+                        if let Some(ext_default_sni) = &ext_settings.default_sni {
+                            settings.default_sni = ext_default_sni.clone();
+                        }
                         let settings = settings.write_to_bytes().unwrap();
                         inbound.settings = settings;
                     }
@@ -1027,29 +2310,69 @@ pub fn to_internal(mut config: Config) -> Result<internal::Config> {
                     }
                     outbounds.push(outbound);
                 }
-                OutboundSettings::Shadowsocks {
+                OutboundSettings::Http {
                     settings: ext_settings,
                 } => {
-                    outbound.protocol = "shadowsocks".to_string();
+                    outbound.protocol = "http".to_string();
                     if let Some(ext_settings) = ext_settings {
-                        let mut settings = internal::ShadowsocksOutboundSettings::new();
+                        let mut settings = internal::HttpOutboundSettings::new();
                         if let Some(ext_address) = &ext_settings.address {
                             settings.address = ext_address.clone();
                         }
                         if let Some(ext_port) = ext_settings.port {
                             settings.port = ext_port as u32;
                         }
-                        if let Some(ext_method) = &ext_settings.method {
-                            settings.method = ext_method.clone();
-                        } else {
-                            settings.method = "chacha20-ietf-poly1305".to_string();
+                        if let Some(ext_username) = &ext_settings.username {
+                            settings.username = ext_username.clone();
                         }
                         if let Some(ext_password) = &ext_settings.password {
                             settings.password = ext_password.clone();
                         }
-                        if let Some(ext_prefix) = &ext_settings.prefix {
+                        if let Some(ext_headers) = &ext_settings.headers {
+                            settings.headers = ext_headers.clone();
+                        }
+                        let settings = settings.write_to_bytes().unwrap();
+                        outbound.settings = settings;
+                    }
+                    outbounds.push(outbound);
+                }
+                OutboundSettings::Shadowsocks {
+                    settings: ext_settings,
+                } => {
+                    outbound.protocol = "shadowsocks".to_string();
+                    if let Some(ext_settings) = ext_settings {
+                        let mut settings = internal::ShadowsocksOutboundSettings::new();
+                        if let Some(ext_address) = &ext_settings.address {
+                            settings.address = ext_address.clone();
+                        }
+                        if let Some(ext_port) = ext_settings.port {
+                            settings.port = ext_port as u32;
+                        }
+                        if let Some(ext_method) = &ext_settings.method {
+                            settings.method = ext_method.clone();
+                        } else {
+                            settings.method = "chacha20-ietf-poly1305".to_string();
+                        }
+                        if let Some(ext_password) = &ext_settings.password {
+                            settings.password = ext_password.clone();
+                        }
+                        if let Some(ext_prefix) = &ext_settings.prefix {
                             settings.prefix = Some(ext_prefix.clone());
                         }
+                        if let Some(ext_plugin) = &ext_settings.plugin {
+                            settings.plugin = Some(ext_plugin.clone());
+                        }
+                        if let Some(ext_plugin_opts) = &ext_settings.plugin_opts {
+                            if ext_settings.plugin.is_none() {
+                                return Err(anyhow::anyhow!(
+                                    "shadowsocks pluginOpts requires plugin"
+                                ));
+                            }
+                            settings.plugin_opts = Some(ext_plugin_opts.clone());
+                        }
+                        if let Some(ext_udp_over_tcp) = ext_settings.udp_over_tcp {
+                            settings.udp_over_tcp = ext_udp_over_tcp;
+                        }
                         let settings = settings.write_to_bytes().unwrap();
                         outbound.settings = settings;
                     }
@@ -1062,14 +2385,19 @@ pub fn to_internal(mut config: Config) -> Result<internal::Config> {
                     if let Some(ext_settings) = ext_settings {
                         let mut settings = internal::ObfsOutboundSettings::new();
                         if let Some(ext_method) = &ext_settings.method {
+                            if ext_method != "http" && ext_method != "tls" {
+                                return Err(anyhow::anyhow!(
+                                    "invalid [obfs outbound] settings: unknown mode {}",
+                                    ext_method
+                                ));
+                            }
                             settings.method = ext_method.clone();
                         }
                         if let Some(ext_host) = &ext_settings.host {
                             settings.host = ext_host.clone();
                         }
-                        if let Some(ext_path) = &ext_settings.path {
-                            settings.path = ext_path.clone();
-                        }
+                        // As obfs-uri of simple-obfs defaults to.
+                        settings.path = ext_settings.path.as_deref().unwrap_or("/").to_string();
                         let settings = settings.write_to_bytes().unwrap();
                         outbound.settings = settings;
                     }
@@ -1133,97 +2461,285 @@ pub fn to_internal(mut config: Config) -> Result<internal::Config> {
                         if let Some(ext_uuid) = &ext_settings.uuid {
                             settings.uuid = ext_uuid.clone();
                         }
+                        // Vision is the default for compatibility, "none" disables it.
+                        settings.flow = match ext_settings.flow.as_deref() {
+                            None | Some("xtls-rprx-vision") => "xtls-rprx-vision".to_string(),
+                            Some("") | Some("none") => String::new(),
+                            Some(flow) => {
+                                return Err(anyhow::anyhow!(
+                                    "invalid [vless outbound] settings: unknown flow {}",
+                                    flow
+                                ));
+                            }
+                        };
                         let settings = settings.write_to_bytes().unwrap();
                         outbound.settings = settings;
                     }
                     outbounds.push(outbound);
                 }
-                OutboundSettings::Reality {
+                OutboundSettings::Hysteria2 {
                     settings: ext_settings,
                 } => {
-                    outbound.protocol = "reality".to_string();
+                    outbound.protocol = "hysteria2".to_string();
                     if let Some(ext_settings) = ext_settings {
-                        let mut settings = internal::RealityOutboundSettings::new();
-                        if let Some(ext_server_name) = &ext_settings.server_name {
-                            settings.server_name = ext_server_name.clone();
+                        let mut settings = internal::Hysteria2OutboundSettings::new();
+                        if let Some(ext_address) = &ext_settings.address {
+                            settings.address = ext_address.clone();
                         }
-                        if let Some(ext_public_key) = &ext_settings.public_key {
-                            settings.public_key = ext_public_key.clone();
+                        if let Some(ext_port) = ext_settings.port {
+                            settings.port = ext_port as u32;
                         }
-                        if let Some(ext_short_id) = &ext_settings.short_id {
-                            settings.short_id = ext_short_id.clone();
+                        if let Some(ext_password) = &ext_settings.password {
+                            validate_non_empty_str(ext_password, "password", "hysteria2 outbound")?;
+                            settings.password = ext_password.clone();
+                        }
+                        if let Some(ext_up_mbps) = ext_settings.up_mbps {
+                            settings.up_mbps = ext_up_mbps;
                         }
+                        if let Some(ext_down_mbps) = ext_settings.down_mbps {
+                            settings.down_mbps = ext_down_mbps;
+                        }
+                        if let Some(ext_sni) = &ext_settings.sni {
+                            settings.sni = ext_sni.clone();
+                        }
+                        settings.insecure = ext_settings.insecure.unwrap_or(false);
                         let settings = settings.write_to_bytes().unwrap();
                         outbound.settings = settings;
                     }
                     outbounds.push(outbound);
                 }
-                OutboundSettings::Tls {
+                OutboundSettings::Tuic {
                     settings: ext_settings,
                 } => {
-                    outbound.protocol = "tls".to_string();
+                    outbound.protocol = "tuic".to_string();
                     if let Some(ext_settings) = ext_settings {
-                        if let Some(ext_ech_config_list) = &ext_settings.ech_config_list {
+                        let mut settings = internal::TuicOutboundSettings::new();
+                        if let Some(ext_address) = &ext_settings.address {
+                            settings.address = ext_address.clone();
+                        }
+                        if let Some(ext_port) = ext_settings.port {
+                            settings.port = ext_port as u32;
+                        }
+                        if let Some(ext_uuid) = &ext_settings.uuid {
+                            validate_non_empty_str(ext_uuid, "uuid", "tuic outbound")?;
+                            settings.uuid = ext_uuid.clone();
+                        }
+                        if let Some(ext_password) = &ext_settings.password {
+                            settings.password = ext_password.clone();
+                        }
+                        if let Some(ext_sni) = &ext_settings.sni {
+                            settings.sni = ext_sni.clone();
+                        }
+                        if let Some(ext_alpns) = &ext_settings.alpn {
+                            for ext_alpn in ext_alpns {
+                                settings.alpn.push(ext_alpn.clone());
+                            }
+                        }
+                        settings.insecure = ext_settings.insecure.unwrap_or(false);
+                        if let Some(ext_udp_relay_mode) = &ext_settings.udp_relay_mode {
+                            settings.udp_relay_mode = ext_udp_relay_mode.clone();
+                        }
+                        let settings = settings.write_to_bytes().unwrap();
+                        outbound.settings = settings;
+                    }
+                    outbounds.push(outbound);
+                }
+                OutboundSettings::WireGuard {
+                    settings: ext_settings,
+                } => {
+                    outbound.protocol = "wireguard".to_string();
+                    if let Some(ext_settings) = ext_settings {
+                        let mut settings = internal::WireGuardOutboundSettings::new();
+                        if let Some(ext_private_key) = &ext_settings.private_key {
                             validate_non_empty_str(
-                                ext_ech_config_list,
-                                "echConfigList",
-                                "tls outbound",
+                                ext_private_key,
+                                "privateKey",
+                                "wireguard outbound",
                             )?;
+                            settings.private_key = ext_private_key.clone();
                         }
-                        let mut settings = internal::TlsOutboundSettings::new();
-                        if let Some(ext_server_name) = &ext_settings.server_name {
-                            settings.server_name = ext_server_name.clone();
+                        if let Some(ext_peer_public_key) = &ext_settings.peer_public_key {
+                            validate_non_empty_str(
+                                ext_peer_public_key,
+                                "peerPublicKey",
+                                "wireguard outbound",
+                            )?;
+                            settings.peer_public_key = ext_peer_public_key.clone();
                         }
-                        if let Some(ext_alpn) = &ext_settings.alpn {
-                            settings.alpn = ext_alpn.clone();
+                        if let Some(ext_preshared_key) = &ext_settings.preshared_key {
+                            settings.preshared_key = ext_preshared_key.clone();
                         }
-                        if let Some(ext_raw_certificate) = &ext_settings.raw_certificate {
-                            settings.certificate = ext_raw_certificate.join("\n");
-                        } else if let Some(ext_certificate) = &ext_settings.certificate {
-                            if is_inline_certificate(ext_certificate) {
-                                settings.certificate = ext_certificate.clone();
-                            } else {
-                                let cert = Path::new(&ext_certificate);
-                                if cert.is_absolute() {
-                                    settings.certificate = cert.to_string_lossy().to_string();
-                                } else {
-                                    let asset_loc = Path::new(&*crate::option::ASSET_LOCATION);
-                                    let path = asset_loc.join(cert).to_string_lossy().to_string();
-                                    settings.certificate = path;
-                                }
+                        if let Some(ext_endpoint) = &ext_settings.endpoint {
+                            validate_non_empty_str(ext_endpoint, "endpoint", "wireguard outbound")?;
+                            settings.endpoint = ext_endpoint.clone();
+                        }
+                        if let Some(ext_allowed_ips) = &ext_settings.allowed_ips {
+                            for ext_allowed_ip in ext_allowed_ips {
+                                settings.allowed_ips.push(ext_allowed_ip.clone());
                             }
                         }
-                        if let Some(ext_raw_certificate_key) = &ext_settings.raw_certificate_key {
-                            settings.certificate_key = ext_raw_certificate_key.join("\n");
-                        } else if let Some(ext_certificate_key) = &ext_settings.certificate_key {
-                            let key = Path::new(&ext_certificate_key);
-                            if key.is_absolute() {
-                                settings.certificate_key = key.to_string_lossy().to_string();
-                            } else {
-                                let asset_loc = Path::new(&*crate::option::ASSET_LOCATION);
-                                let path = asset_loc.join(key).to_string_lossy().to_string();
-                                settings.certificate_key = path;
+                        if let Some(ext_local_addresses) = &ext_settings.local_address {
+                            for ext_local_address in ext_local_addresses {
+                                settings.local_address.push(ext_local_address.clone());
                             }
                         }
-                        if let Some(ext_insecure) = ext_settings.insecure {
-                            settings.insecure = ext_insecure;
+                        if let Some(ext_mtu) = ext_settings.mtu {
+                            settings.mtu = ext_mtu;
                         }
-                        if let Some(ext_ech) = ext_settings.ech {
-                            settings.ech = ext_ech;
+                        let settings = settings.write_to_bytes().unwrap();
+                        outbound.settings = settings;
+                    }
+                    outbounds.push(outbound);
+                }
+                OutboundSettings::Ssh {
+                    settings: ext_settings,
+                } => {
+                    outbound.protocol = "ssh".to_string();
+                    if let Some(ext_settings) = ext_settings {
+                        let mut settings = internal::SshOutboundSettings::new();
+                        if let Some(ext_address) = &ext_settings.address {
+                            settings.address = ext_address.clone();
                         }
-                        if let Some(ext_ech_disable_dns_lookup) =
-                            ext_settings.ech_disable_dns_lookup
-                        {
-                            settings.ech_disable_dns_lookup = ext_ech_disable_dns_lookup;
+                        if let Some(ext_port) = ext_settings.port {
+                            settings.port = ext_port as u32;
+                        }
+                        if let Some(ext_username) = &ext_settings.username {
+                            validate_non_empty_str(ext_username, "username", "ssh outbound")?;
+                            settings.username = ext_username.clone();
+                        }
+                        if ext_settings.password.is_none() && ext_settings.private_key.is_none() {
+                            return Err(anyhow::anyhow!(
+                                "invalid [ssh outbound] settings: password or privateKey required"
+                            ));
+                        }
+                        if let Some(ext_password) = &ext_settings.password {
+                            settings.password = ext_password.clone();
+                        }
+                        if let Some(ext_private_key) = &ext_settings.private_key {
+                            validate_non_empty_str(ext_private_key, "privateKey", "ssh outbound")?;
+                            settings.private_key = ext_private_key.clone();
+                        }
+                        if let Some(ext_passphrase) = &ext_settings.private_key_passphrase {
+                            settings.private_key_passphrase = ext_passphrase.clone();
+                        }
+                        if let Some(ext_fingerprint) = &ext_settings.host_key_fingerprint {
+                            settings.host_key_fingerprint = ext_fingerprint.clone();
+                        }
+                        if let Some(ext_known_hosts) = &ext_settings.known_hosts {
+                            settings.known_hosts = ext_known_hosts.clone();
+                        }
+                        if let Some(ext_max_channels) = ext_settings.max_channels_per_conn {
+                            settings.max_channels_per_conn = ext_max_channels;
+                        }
+                        let settings = settings.write_to_bytes().unwrap();
+                        outbound.settings = settings;
+                    }
+                    outbounds.push(outbound);
+                }
+                OutboundSettings::H2 {
+                    settings: ext_settings,
+                } => {
+                    outbound.protocol = "h2".to_string();
+                    if let Some(ext_settings) = ext_settings {
+                        let mut settings = internal::H2OutboundSettings::new();
+                        if let Some(ext_address) = &ext_settings.address {
+                            settings.address = ext_address.clone();
+                        }
+                        if let Some(ext_port) = ext_settings.port {
+                            settings.port = ext_port as u32;
+                        }
+                        match (&ext_settings.username, &ext_settings.password) {
+                            (Some(ext_username), Some(ext_password)) => {
+                                validate_non_empty_str(ext_username, "username", "h2 outbound")?;
+                                settings.username = ext_username.clone();
+                                settings.password = ext_password.clone();
+                            }
+                            (None, None) => {}
+                            _ => {
+                                return Err(anyhow::anyhow!(
+                                    "invalid [h2 outbound] settings: username and password must be set together"
+                                ));
+                            }
+                        }
+                        if let Some(ext_padding) = ext_settings.padding {
+                            settings.padding = ext_padding;
                         }
-                        if let Some(ext_ech_config_list) = &ext_settings.ech_config_list {
-                            settings.ech_config_list = ext_ech_config_list.clone();
+                        if let Some(ext_tls) = &ext_settings.tls {
+                            settings.tls = protobuf::MessageField::some(
+                                to_internal_tls_outbound_settings(ext_tls)?,
+                            );
                         }
                         let settings = settings.write_to_bytes().unwrap();
                         outbound.settings = settings;
                     }
                     outbounds.push(outbound);
                 }
+                OutboundSettings::ShadowTls {
+                    settings: ext_settings,
+                } => {
+                    outbound.protocol = "shadowtls".to_string();
+                    if let Some(ext_settings) = ext_settings {
+                        let mut settings = internal::ShadowTlsOutboundSettings::new();
+                        if let Some(ext_address) = &ext_settings.address {
+                            settings.address = ext_address.clone();
+                        }
+                        if let Some(ext_port) = ext_settings.port {
+                            settings.port = ext_port as u32;
+                        }
+                        let Some(ext_password) = &ext_settings.password else {
+                            return Err(anyhow::anyhow!(
+                                "invalid [shadowtls outbound] settings: password required"
+                            ));
+                        };
+                        validate_non_empty_str(ext_password, "password", "shadowtls outbound")?;
+                        settings.password = ext_password.clone();
+                        let Some(ext_handshake_server) = &ext_settings.handshake_server else {
+                            return Err(anyhow::anyhow!(
+                                "invalid [shadowtls outbound] settings: handshakeServer required"
+                            ));
+                        };
+                        validate_non_empty_str(
+                            ext_handshake_server,
+                            "handshakeServer",
+                            "shadowtls outbound",
+                        )?;
+                        settings.handshake_server = ext_handshake_server.clone();
+                        let settings = settings.write_to_bytes().unwrap();
+                        outbound.settings = settings;
+                    }
+                    outbounds.push(outbound);
+                }
+                OutboundSettings::Reality {
+                    settings: ext_settings,
+                } => {
+                    outbound.protocol = "reality".to_string();
+                    if let Some(ext_settings) = ext_settings {
+                        let mut settings = internal::RealityOutboundSettings::new();
+                        if let Some(ext_server_name) = &ext_settings.server_name {
+                            settings.server_name = ext_server_name.clone();
+                        }
+                        if let Some(ext_public_key) = &ext_settings.public_key {
+                            settings.public_key = ext_public_key.clone();
+                        }
+                        if let Some(ext_short_id) = &ext_settings.short_id {
+                            settings.short_id = ext_short_id.clone();
+                        }
+                        let settings = settings.write_to_bytes().unwrap();
+                        outbound.settings = settings;
+                    }
+                    outbounds.push(outbound);
+                }
+                OutboundSettings::Tls {
+                    settings: ext_settings,
+                } => {
+                    outbound.protocol = "tls".to_string();
+                    if let Some(ext_settings) = ext_settings {
+                        let settings = to_internal_tls_outbound_settings(ext_settings)?;
+                        let settings = settings.write_to_bytes().unwrap();
+                        outbound.settings = settings;
+                    }
+                    outbounds.push(outbound);
+                }
                 OutboundSettings::WebSocket {
                     settings: ext_settings,
                 } => {
@@ -1236,6 +2752,14 @@ pub fn to_internal(mut config: Config) -> Result<internal::Config> {
                         if let Some(ext_headers) = &ext_settings.headers {
                             settings.headers = ext_headers.clone();
                         }
+                        if let Some(ext_early_data_size) = ext_settings.early_data_size {
+                            settings.early_data_size = ext_early_data_size;
+                        }
+                        if let Some(ext_early_data_header_name) =
+                            &ext_settings.early_data_header_name
+                        {
+                            settings.early_data_header_name = ext_early_data_header_name.clone();
+                        }
                         let settings = settings.write_to_bytes().unwrap();
                         outbound.settings = settings;
                     }
@@ -1257,6 +2781,8 @@ pub fn to_internal(mut config: Config) -> Result<internal::Config> {
                         } else {
                             settings.delay_base = 0;
                         }
+                        settings.max_concurrent_attempts =
+                            ext_settings.max_concurrent_attempts.unwrap_or(0);
                         let settings = settings.write_to_bytes().unwrap();
                         outbound.settings = settings;
                     }
@@ -1313,6 +2839,13 @@ pub fn to_internal(mut config: Config) -> Result<internal::Config> {
                             ext_settings.health_check_attempts.unwrap_or(1);
                         settings.health_check_success_percentage =
                             ext_settings.health_check_success_percentage.unwrap_or(50);
+                        if let Some(ext_health_check_url) = &ext_settings.health_check_url {
+                            settings.health_check_url = ext_health_check_url.clone();
+                        }
+                        settings.failures_before_down =
+                            ext_settings.failures_before_down.unwrap_or(1);
+                        settings.successes_before_up =
+                            ext_settings.successes_before_up.unwrap_or(1);
                         settings.check_interval = ext_settings.check_interval.unwrap_or(300); // 300 secs
                         settings.failover = ext_settings.failover.unwrap_or(true);
                         settings.fallback_cache = ext_settings.fallback_cache.unwrap_or(false);
@@ -1323,6 +2856,51 @@ pub fn to_internal(mut config: Config) -> Result<internal::Config> {
                     }
                     outbounds.push(outbound);
                 }
+                OutboundSettings::HttpUpgrade {
+                    settings: ext_settings,
+                } => {
+                    outbound.protocol = "httpupgrade".to_string();
+                    if let Some(ext_settings) = ext_settings {
+                        let mut settings = internal::HttpUpgradeOutboundSettings::new();
+                        if let Some(ext_path) = &ext_settings.path {
+                            settings.path = ext_path.clone();
+                        }
+                        if let Some(ext_headers) = &ext_settings.headers {
+                            settings.headers = ext_headers.clone();
+                        }
+                        let settings = settings.write_to_bytes().unwrap();
+                        outbound.settings = settings;
+                    }
+                    outbounds.push(outbound);
+                }
+                OutboundSettings::Grpc {
+                    settings: ext_settings,
+                } => {
+                    outbound.protocol = "grpc".to_string();
+                    if let Some(ext_settings) = ext_settings {
+                        let mut settings = internal::GrpcOutboundSettings::new();
+                        if let Some(ext_service_name) = &ext_settings.service_name {
+                            settings.service_name = ext_service_name.clone();
+                        }
+                        if let Some(ext_host) = &ext_settings.host {
+                            settings.host = ext_host.clone();
+                        }
+                        if let Some(ext_address) = &ext_settings.address {
+                            settings.address = ext_address.clone();
+                        }
+                        if let Some(ext_port) = ext_settings.port {
+                            settings.port = ext_port as u32;
+                        }
+                        if let Some(ext_actors) = &ext_settings.actors {
+                            for ext_actor in ext_actors {
+                                settings.actors.push(ext_actor.clone());
+                            }
+                        }
+                        let settings = settings.write_to_bytes().unwrap();
+                        outbound.settings = settings;
+                    }
+                    outbounds.push(outbound);
+                }
                 OutboundSettings::AMux {
                     settings: ext_settings,
                 } => {
@@ -1344,6 +2922,17 @@ pub fn to_internal(mut config: Config) -> Result<internal::Config> {
                         settings.concurrency = ext_settings.concurrency.unwrap_or(2);
                         settings.max_recv_bytes = ext_settings.max_recv_bytes.unwrap_or_default();
                         settings.max_lifetime = ext_settings.max_lifetime.unwrap_or_default();
+                        settings.keep_alive_interval =
+                            ext_settings.keep_alive_interval.unwrap_or_default();
+                        settings.keep_alive_max_missed =
+                            ext_settings.keep_alive_max_missed.unwrap_or(3);
+                        settings.open_timeout = ext_settings.open_timeout.unwrap_or_default();
+                        settings.max_connections = ext_settings.max_connections.unwrap_or_default();
+                        settings.connection_idle_timeout =
+                            ext_settings.connection_idle_timeout.unwrap_or_default();
+                        settings.padding = ext_settings.padding.unwrap_or_default();
+                        settings.padding_max_overhead_pct =
+                            ext_settings.padding_max_overhead_pct.unwrap_or(25);
                         let settings = settings.write_to_bytes().unwrap();
                         outbound.settings = settings;
                     }
@@ -1395,6 +2984,42 @@ pub fn to_internal(mut config: Config) -> Result<internal::Config> {
                         if let Some(ext_alpns) = &ext_settings.alpn {
                             settings.alpn = ext_alpns.clone();
                         }
+                        if let Some(ext_cc) = &ext_settings.congestion_controller {
+                            validate_congestion_controller(ext_cc, "quic outbound")?;
+                            settings.congestion_controller = ext_cc.clone();
+                        }
+                        if let Some(ext_max_sessions) = ext_settings.max_sessions_per_connection {
+                            validate_non_zero(
+                                ext_max_sessions,
+                                "maxSessionsPerConnection",
+                                "quic outbound",
+                            )?;
+                            settings.max_sessions_per_connection = ext_max_sessions;
+                        }
+                        if let Some(ext_udp_over_stream) = ext_settings.udp_over_stream {
+                            settings.udp_over_stream = ext_udp_over_stream;
+                        }
+                        if let Some(ext_fingerprint) = &ext_settings.certificate_fingerprint {
+                            settings.certificate_fingerprint =
+                                validate_fingerprint(ext_fingerprint, "quic outbound")?;
+                        }
+                        if let Some(ext_zero_rtt) = ext_settings.zero_rtt {
+                            settings.zero_rtt = ext_zero_rtt;
+                        }
+                        if let Some(ext_reconnect) = ext_settings.reconnect_on_network_change {
+                            settings.reconnect_on_network_change = ext_reconnect;
+                        }
+                        settings.certificate_pins = validate_certificate_pins(
+                            ext_settings.certificate_pins.as_deref().unwrap_or_default(),
+                            ext_settings.pin_only,
+                            "quic outbound",
+                        )?;
+                        if let Some(ext_pin_only) = ext_settings.pin_only {
+                            settings.pin_only = ext_pin_only;
+                        }
+                        if let Some(ext_allow_name_mismatch) = ext_settings.allow_name_mismatch {
+                            settings.allow_name_mismatch = ext_allow_name_mismatch;
+                        }
                         let settings = settings.write_to_bytes().unwrap();
                         outbound.settings = settings;
                     }
@@ -1449,6 +3074,37 @@ pub fn to_internal(mut config: Config) -> Result<internal::Config> {
                                 settings.actors.push(ext_actor.clone());
                             }
                         }
+                        settings.persist = ext_settings.persist.unwrap_or(true);
+                        if let Some(ext_cache_file) = &ext_settings.cache_file {
+                            settings.cache_file = ext_cache_file.clone();
+                        }
+                        settings.fallback_on_failure =
+                            ext_settings.fallback_on_failure.unwrap_or(false);
+                        settings.failures_before_down =
+                            ext_settings.failures_before_down.unwrap_or(3);
+                        if let Some(ext_health_check_url) = &ext_settings.health_check_url {
+                            settings.health_check_url = ext_health_check_url.clone();
+                        }
+                        settings.check_interval = ext_settings.check_interval.unwrap_or(30);
+                        let settings = settings.write_to_bytes().unwrap();
+                        outbound.settings = settings;
+                    }
+                    outbounds.push(outbound);
+                }
+                OutboundSettings::UrlTest {
+                    settings: ext_settings,
+                } => {
+                    outbound.protocol = "urltest".to_string();
+                    if let Some(ext_settings) = ext_settings {
+                        let mut settings = internal::UrlTestOutboundSettings::new();
+                        if let Some(ext_actors) = &ext_settings.actors {
+                            settings.actors.extend_from_slice(ext_actors);
+                        }
+                        if let Some(ext_url) = &ext_settings.url {
+                            settings.url = ext_url.clone();
+                        }
+                        settings.interval = ext_settings.interval.unwrap_or(300); // 300 secs
+                        settings.tolerance = ext_settings.tolerance.unwrap_or(50); // 50ms
                         let settings = settings.write_to_bytes().unwrap();
                         outbound.settings = settings;
                     }
@@ -1481,17 +3137,13 @@ pub fn to_internal(mut config: Config) -> Result<internal::Config> {
         let mut rules = Vec::new();
         if let Some(ext_rules) = ext_router.rules.as_mut() {
             for ext_rule in ext_rules.iter_mut() {
-                let mut rule = internal::router::Rule::new();
-                let target_tag = std::mem::take(&mut ext_rule.target);
-                rule.target_tag = target_tag;
-
                 // handle FINAL rule first
                 if let Some(type_field) = &ext_rule.type_field {
                     if type_field == "FINAL" {
                         // reorder outbounds to make the FINAL one first
                         let mut idx = None;
                         for (i, v) in outbounds.iter().enumerate() {
-                            if v.tag == rule.target_tag {
+                            if v.tag == ext_rule.target {
                                 idx = Some(i);
                             }
                         }
@@ -1503,88 +3155,40 @@ pub fn to_internal(mut config: Config) -> Result<internal::Config> {
                     }
                 }
 
-                if let Some(ext_ips) = ext_rule.ip.as_mut() {
-                    for ext_ip in ext_ips.drain(0..) {
-                        rule.ip_cidrs.push(ext_ip);
-                    }
-                }
-                if let Some(ext_domains) = ext_rule.domain.as_mut() {
-                    for ext_domain in ext_domains.drain(0..) {
-                        let mut domain = internal::router::rule::Domain::new();
-                        domain.type_ = protobuf::EnumOrUnknown::new(
-                            internal::router::rule::domain::Type::FULL,
-                        );
-                        domain.value = ext_domain;
-                        rule.domains.push(domain);
-                    }
-                }
-                if let Some(ext_domain_keywords) = ext_rule.domain_keyword.as_mut() {
-                    for ext_domain_keyword in ext_domain_keywords.drain(0..) {
-                        let mut domain = internal::router::rule::Domain::new();
-                        domain.type_ = protobuf::EnumOrUnknown::new(
-                            internal::router::rule::domain::Type::PLAIN,
-                        );
-                        domain.value = ext_domain_keyword;
-                        rule.domains.push(domain);
-                    }
-                }
-                if let Some(ext_domain_suffixes) = ext_rule.domain_suffix.as_mut() {
-                    for ext_domain_suffix in ext_domain_suffixes.drain(0..) {
-                        let mut domain = internal::router::rule::Domain::new();
-                        domain.type_ = protobuf::EnumOrUnknown::new(
-                            internal::router::rule::domain::Type::DOMAIN,
-                        );
-                        domain.value = ext_domain_suffix;
-                        rule.domains.push(domain);
-                    }
-                }
-                if let Some(ext_geoips) = ext_rule.geoip.as_mut() {
-                    for ext_geoip in ext_geoips.drain(0..) {
-                        let mut mmdb = internal::router::rule::Mmdb::new();
-                        let asset_loc = Path::new(&*crate::option::ASSET_LOCATION);
-                        mmdb.file = asset_loc.join("geo.mmdb").to_string_lossy().to_string();
-                        mmdb.country_code = ext_geoip;
-                        rule.mmdbs.push(mmdb)
-                    }
-                }
-                if let Some(ext_externals) = ext_rule.external.as_mut() {
-                    for ext_external in ext_externals.drain(0..) {
-                        match external_rule::add_external_rule(&mut rule, &ext_external) {
-                            Ok(_) => (),
-                            Err(e) => {
-                                println!("load external rule failed: {}", e);
-                            }
-                        }
-                    }
-                }
-                if let Some(ext_port_ranges) = ext_rule.port_range.as_mut() {
-                    for ext_port_range in ext_port_ranges.drain(0..) {
-                        rule.port_ranges.push(ext_port_range);
-                    }
-                }
-                if let Some(ext_networks) = ext_rule.network.as_mut() {
-                    for ext_network in ext_networks.drain(0..) {
-                        rule.networks.push(ext_network);
-                    }
-                }
-                if let Some(ext_its) = ext_rule.inbound_tag.as_mut() {
-                    for it in ext_its.drain(0..) {
-                        rule.inbound_tags.push(it);
-                    }
-                }
-                #[cfg(feature = "rule-process-name")]
-                if let Some(ext_process_names) = ext_rule.process_name.as_mut() {
-                    for process_name in ext_process_names.drain(0..) {
-                        rule.process_names.push(process_name);
-                    }
-                }
-                rules.push(rule);
+                rules.push(to_internal_routing_rule(
+                    ext_rule,
+                    ext_router.asn_database.as_ref(),
+                    ext_router.rule_providers.as_ref(),
+                )?);
             }
         }
         int_router.rules = rules;
         if let Some(ext_domain_resolve) = ext_router.domain_resolve {
             int_router.domain_resolve = ext_domain_resolve;
         }
+        if let Some(ext_geoip_url) = ext_router.geoip_url.as_ref() {
+            if !ext_geoip_url.starts_with("http://") && !ext_geoip_url.starts_with("https://") {
+                return Err(anyhow::anyhow!("invalid geoip url {}", ext_geoip_url));
+            }
+            int_router.geoip_url = ext_geoip_url.clone();
+            int_router.geoip_update_interval = ext_router.geoip_update_interval.unwrap_or(0);
+            int_router.geoip_outbound = ext_router.geoip_outbound.clone().unwrap_or_default();
+        }
+        if let Some(ext_providers) = ext_router.rule_providers.as_ref() {
+            let mut names: Vec<&String> = ext_providers.keys().collect();
+            names.sort();
+            for name in names {
+                let ext_provider = &ext_providers[name];
+                let mut provider = internal::router::RuleProvider::new();
+                provider.name = name.to_owned();
+                provider.url = ext_provider.url.clone();
+                provider.interval = ext_provider.interval.unwrap_or(0);
+                provider.format = ext_provider.format.clone().unwrap_or_default();
+                provider.path = ext_provider.path.clone().unwrap_or_default();
+                provider.outbound = ext_provider.outbound.clone().unwrap_or_default();
+                int_router.rule_providers.push(provider);
+            }
+        }
         router = protobuf::MessageField::some(int_router);
     }
 
@@ -1608,6 +3212,74 @@ pub fn to_internal(mut config: Config) -> Result<internal::Config> {
                 hosts.insert(name.to_owned(), ips);
             }
         }
+        if let Some(ext_groups) = ext_dns.groups.as_ref() {
+            for (name, ext_group) in ext_groups.iter() {
+                let mut servers = internal::dns::Servers::new();
+                match ext_group {
+                    DnsGroup::Servers(values) => servers.values = values.clone(),
+                    DnsGroup::WithOutbound {
+                        servers: values,
+                        outbound,
+                    } => {
+                        servers.values = values.clone();
+                        servers.outbound = outbound.clone().unwrap_or_default();
+                    }
+                }
+                dns.groups.insert(name.to_owned(), servers);
+            }
+        }
+        if let Some(ext_rules) = ext_dns.rules.as_ref() {
+            for ext_rule in ext_rules {
+                let mut rule = internal::dns::Rule::new();
+                rule.domains = to_internal_domains(
+                    &ext_rule.domain,
+                    &ext_rule.domain_keyword,
+                    &ext_rule.domain_suffix,
+                );
+                rule.group = ext_rule.group.clone();
+                dns.rules.push(rule);
+            }
+        }
+        if let Some(ext_outbound) = ext_dns.outbound.as_ref() {
+            dns.outbound = ext_outbound.to_owned();
+        }
+        if let Some(ext_hosts_ttl) = ext_dns.hosts_ttl {
+            dns.hosts_ttl = ext_hosts_ttl;
+        }
+        if let Some(ext_strategy) = ext_dns.strategy.as_ref() {
+            dns.strategy = ext_strategy.to_owned();
+        }
+        if let Some(ext_cache_min_ttl) = ext_dns.cache_min_ttl {
+            dns.cache_min_ttl = ext_cache_min_ttl;
+        }
+        if let Some(ext_cache_max_ttl) = ext_dns.cache_max_ttl {
+            dns.cache_max_ttl = ext_cache_max_ttl;
+        }
+        if let Some(ext_cache_negative_ttl) = ext_dns.cache_negative_ttl {
+            dns.cache_negative_ttl = ext_cache_negative_ttl;
+        }
+        if let Some(ext_cache_size) = ext_dns.cache_size {
+            dns.cache_size = ext_cache_size;
+        }
+        if let Some(ext_cache_file) = ext_dns.cache_file.as_ref() {
+            dns.cache_file = ext_cache_file.to_owned();
+        }
+        if let Some(ext_filter) = ext_dns.fake_ip_filter.as_ref() {
+            if let Some(ext_mode) = ext_filter.mode.as_ref() {
+                dns.fake_ip_filter_mode = ext_mode.to_owned();
+            }
+            dns.fake_ip_filter = to_internal_domains(
+                &ext_filter.domain,
+                &ext_filter.domain_keyword,
+                &ext_filter.domain_suffix,
+            );
+        }
+        if let Some(ext_subnet) = ext_dns.edns_client_subnet.as_ref() {
+            dns.edns_client_subnet = ext_subnet.to_owned();
+        }
+        if let Some(ext_concurrent_queries) = ext_dns.concurrent_queries {
+            dns.concurrent_queries = ext_concurrent_queries;
+        }
     }
     if servers.is_empty() {
         servers.push("1.1.1.1".to_string());
@@ -1617,11 +3289,33 @@ pub fn to_internal(mut config: Config) -> Result<internal::Config> {
         dns.hosts = hosts;
     }
 
+    let mut metrics = protobuf::MessageField::none();
+    if let Some(ext_metrics) = &config.metrics {
+        let mut int_metrics = internal::Metrics::new();
+        int_metrics.address = ext_metrics
+            .address
+            .clone()
+            .unwrap_or("127.0.0.1".to_string());
+        int_metrics.port = ext_metrics.port as u32;
+        metrics = protobuf::MessageField::some(int_metrics);
+    }
+
+    let mut api = protobuf::MessageField::none();
+    if let Some(ext_api) = &config.api {
+        let mut int_api = internal::Api::new();
+        int_api.address = ext_api.address.clone().unwrap_or("127.0.0.1".to_string());
+        int_api.port = ext_api.port as u32;
+        int_api.secret = ext_api.secret.clone().unwrap_or_default();
+        api = protobuf::MessageField::some(int_api);
+    }
+
     let mut config = internal::Config::new();
     config.log = protobuf::MessageField::some(log);
     config.inbounds = inbounds;
     config.outbounds = outbounds;
     config.router = router;
     config.dns = protobuf::MessageField::some(dns);
+    config.metrics = metrics;
+    config.api = api;
     Ok(config)
 }
@@ -34,6 +34,12 @@ pub struct General {
     pub loglevel: Option<String>,
     pub logoutput: Option<String>,
     pub logformat: Option<String>,
+    pub access_log: Option<String>,
+    pub access_log_format: Option<String>,
+    pub access_log_start: Option<bool>,
+    pub log_max_size_mb: Option<u32>,
+    pub log_max_backups: Option<u32>,
+    pub log_max_age_days: Option<u32>,
     pub dns_server: Option<Vec<String>>,
     pub dns_interface: Option<String>,
     pub always_real_ip: Option<Vec<String>>,
@@ -42,9 +48,22 @@ pub struct General {
     pub http_port: Option<u16>,
     pub socks_interface: Option<String>,
     pub socks_port: Option<u16>,
+    pub mixed_interface: Option<String>,
+    pub mixed_port: Option<u16>,
+    // The final outbounds of the inbounds, for the sessions no rule matches.
+    pub http_final: Option<String>,
+    pub socks_final: Option<String>,
+    pub mixed_final: Option<String>,
+    pub tun_final: Option<String>,
     pub api_interface: Option<String>,
     pub api_port: Option<u16>,
+    pub api_secret: Option<String>,
+    pub metrics_interface: Option<String>,
+    pub metrics_port: Option<u16>,
     pub routing_domain_resolve: Option<bool>,
+    pub geoip_url: Option<String>,
+    pub geoip_update_interval: Option<u32>,
+    pub geoip_outbound: Option<String>,
     pub wintun: Option<String>,
     pub tun_dns_server: Option<Vec<String>>,
 }
@@ -71,13 +90,22 @@ pub struct Proxy {
     pub obfs_host: Option<String>,
     pub obfs_path: Option<String>,
 
+    // shadow-tls
+    pub shadow_tls_password: Option<String>,
+    pub shadow_tls_sni: Option<String>,
+
     pub ws: Option<bool>,
     pub tls: Option<bool>,
     pub tls_cert: Option<String>,
+    pub tls_cert_fingerprint: Option<String>,
+    pub tls_client_cert: Option<String>,
+    pub tls_client_key: Option<String>,
     pub tls_insecure: Option<bool>,
+    pub tls_allow_name_mismatch: Option<bool>,
     pub tls_ech: Option<bool>,
     pub tls_ech_disable_dns_lookup: Option<bool>,
     pub tls_ech_config_list: Option<String>,
+    pub tls_ech_required: Option<bool>,
     pub ws_path: Option<String>,
     pub ws_host: Option<String>,
 
@@ -88,13 +116,37 @@ pub struct Proxy {
     pub username: Option<String>,
     pub uuid: Option<String>,
 
+    // vless
+    pub flow: Option<String>,
+
+    // hysteria2
+    pub up_mbps: Option<u32>,
+    pub down_mbps: Option<u32>,
+
+    // tuic
+    pub udp_relay_mode: Option<String>,
+
+    // ssh
+    pub private_key: Option<String>,
+    pub private_key_passphrase: Option<String>,
+    pub host_key_fingerprint: Option<String>,
+    pub known_hosts: Option<String>,
+    pub max_channels_per_conn: Option<u32>,
+
     pub amux: Option<bool>,
     pub amux_max: Option<i32>,
     pub amux_con: Option<i32>,
     pub amux_max_recv: Option<u64>,
     pub amux_max_lifetime: Option<u64>,
+    pub amux_keep_alive: Option<u32>,
+    pub amux_open_timeout: Option<u32>,
 
     pub quic: Option<bool>,
+    pub quic_congestion_controller: Option<String>,
+    pub quic_max_sessions_per_connection: Option<u32>,
+    pub quic_udp_over_stream: Option<bool>,
+    pub quic_zero_rtt: Option<bool>,
+    pub quic_reconnect_on_network_change: Option<bool>,
 
     // reality
     pub reality: Option<bool>,
@@ -116,24 +168,47 @@ impl Default for Proxy {
             obfs_type: None,
             obfs_host: None,
             obfs_path: None,
+            shadow_tls_password: None,
+            shadow_tls_sni: None,
             ws: Some(false),
             tls: Some(false),
             tls_cert: None,
+            tls_cert_fingerprint: None,
+            tls_client_cert: None,
+            tls_client_key: None,
             tls_insecure: Some(false),
+            tls_allow_name_mismatch: None,
             tls_ech: Some(false),
             tls_ech_disable_dns_lookup: Some(false),
             tls_ech_config_list: None,
+            tls_ech_required: None,
             ws_path: None,
             ws_host: None,
             sni: None,
             username: None,
             uuid: None,
+            flow: None,
+            up_mbps: None,
+            down_mbps: None,
+            udp_relay_mode: None,
+            private_key: None,
+            private_key_passphrase: None,
+            host_key_fingerprint: None,
+            known_hosts: None,
+            max_channels_per_conn: None,
             amux: Some(false),
             amux_max: Some(8),
             amux_con: Some(2),
             amux_max_recv: Some(0),
             amux_max_lifetime: Some(0),
+            amux_keep_alive: None,
+            amux_open_timeout: None,
             quic: Some(false),
+            quic_congestion_controller: None,
+            quic_max_sessions_per_connection: None,
+            quic_udp_over_stream: None,
+            quic_zero_rtt: None,
+            quic_reconnect_on_network_change: None,
             reality: Some(false),
             reality_public_key: None,
             reality_short_id: None,
@@ -167,12 +242,21 @@ pub struct ProxyGroup {
     pub health_check_wait: Option<bool>,
     pub health_check_attempts: Option<u32>,
     pub health_check_success_percentage: Option<u32>,
+    pub health_check_url: Option<String>,
+    pub failures_before_down: Option<u32>,
+    pub successes_before_up: Option<u32>,
 
     // tryall
     pub delay_base: Option<u32>,
+    pub max_concurrent_attempts: Option<u32>,
 
     // static
     pub method: Option<String>,
+
+    // select
+    pub persist: Option<bool>,
+    pub cache_file: Option<String>,
+    pub fallback_on_failure: Option<bool>,
 }
 
 impl Default for ProxyGroup {
@@ -199,8 +283,15 @@ impl Default for ProxyGroup {
             health_check_wait: None,
             health_check_attempts: None,
             health_check_success_percentage: None,
+            health_check_url: None,
+            failures_before_down: None,
+            successes_before_up: None,
             delay_base: None,
+            max_concurrent_attempts: None,
             method: None,
+            persist: None,
+            cache_file: None,
+            fallback_on_failure: None,
         }
     }
 }
@@ -526,6 +617,24 @@ pub fn from_lines(lines: Vec<io::Result<String>>) -> Result<Config> {
             "logformat" => {
                 general.logformat = Some(parts[1].to_string());
             }
+            "access-log" => {
+                general.access_log = get_string(parts[1]);
+            }
+            "access-log-format" => {
+                general.access_log_format = get_string(parts[1]);
+            }
+            "access-log-start" => {
+                general.access_log_start = Some(parts[1] == "true");
+            }
+            "log-max-size-mb" => {
+                general.log_max_size_mb = get_value::<u32>(parts[1]);
+            }
+            "log-max-backups" => {
+                general.log_max_backups = get_value::<u32>(parts[1]);
+            }
+            "log-max-age-days" => {
+                general.log_max_age_days = get_value::<u32>(parts[1]);
+            }
             "dns-server" => {
                 general.dns_server = get_char_sep_slice(parts[1], ',');
             }
@@ -545,6 +654,15 @@ pub fn from_lines(lines: Vec<io::Result<String>>) -> Result<Config> {
                     Some(false)
                 };
             }
+            "geoip-url" => {
+                general.geoip_url = get_string(parts[1]);
+            }
+            "geoip-update-interval" => {
+                general.geoip_update_interval = get_value::<u32>(parts[1]);
+            }
+            "geoip-outbound" => {
+                general.geoip_outbound = get_string(parts[1]);
+            }
             "http-interface" | "interface" => {
                 general.http_interface = get_string(parts[1]);
             }
@@ -557,12 +675,39 @@ pub fn from_lines(lines: Vec<io::Result<String>>) -> Result<Config> {
             "socks-port" => {
                 general.socks_port = get_value::<u16>(parts[1]);
             }
+            "mixed-interface" => {
+                general.mixed_interface = get_string(parts[1]);
+            }
+            "mixed-port" => {
+                general.mixed_port = get_value::<u16>(parts[1]);
+            }
+            "http-final" => {
+                general.http_final = get_string(parts[1]);
+            }
+            "socks-final" => {
+                general.socks_final = get_string(parts[1]);
+            }
+            "mixed-final" => {
+                general.mixed_final = get_string(parts[1]);
+            }
+            "tun-final" => {
+                general.tun_final = get_string(parts[1]);
+            }
             "api-interface" => {
                 general.api_interface = get_string(parts[1]);
             }
             "api-port" => {
                 general.api_port = get_value::<u16>(parts[1]);
             }
+            "api-secret" => {
+                general.api_secret = get_string(parts[1]);
+            }
+            "metrics-interface" => {
+                general.metrics_interface = get_string(parts[1]);
+            }
+            "metrics-port" => {
+                general.metrics_port = get_value::<u16>(parts[1]);
+            }
             "wintun" => {
                 general.wintun = get_string(parts[1]);
             }
@@ -629,14 +774,32 @@ pub fn from_lines(lines: Vec<io::Result<String>>) -> Result<Config> {
                 "obfs-path" => {
                     proxy.obfs_path = Some(v.to_string());
                 }
+                "shadow-tls-password" => {
+                    proxy.shadow_tls_password = Some(v.to_string());
+                }
+                "shadow-tls-sni" => {
+                    proxy.shadow_tls_sni = Some(v.to_string());
+                }
                 "ws" => proxy.ws = if v == "true" { Some(true) } else { Some(false) },
                 "tls" => proxy.tls = if v == "true" { Some(true) } else { Some(false) },
                 "tls-cert" => {
                     proxy.tls_cert = Some(v.to_string());
                 }
+                "tls-cert-fingerprint" => {
+                    proxy.tls_cert_fingerprint = Some(v.to_string());
+                }
+                "tls-client-cert" => {
+                    proxy.tls_client_cert = Some(v.to_string());
+                }
+                "tls-client-key" => {
+                    proxy.tls_client_key = Some(v.to_string());
+                }
                 "tls-insecure" => {
                     proxy.tls_insecure = if v == "true" { Some(true) } else { Some(false) }
                 }
+                "tls-allow-name-mismatch" => {
+                    proxy.tls_allow_name_mismatch = Some(v == "true");
+                }
                 "tls-ech" => proxy.tls_ech = if v == "true" { Some(true) } else { Some(false) },
                 "tls-ech-disable-dns-lookup" => {
                     proxy.tls_ech_disable_dns_lookup =
@@ -645,6 +808,9 @@ pub fn from_lines(lines: Vec<io::Result<String>>) -> Result<Config> {
                 "tls-ech-config-list" | "ech-config-list" => {
                     proxy.tls_ech_config_list = Some(v.to_string());
                 }
+                "tls-ech-required" => {
+                    proxy.tls_ech_required = Some(v == "true");
+                }
                 "ws-path" => {
                     proxy.ws_path = Some(v.to_string());
                 }
@@ -660,6 +826,33 @@ pub fn from_lines(lines: Vec<io::Result<String>>) -> Result<Config> {
                 "uuid" => {
                     proxy.uuid = Some(v.to_string());
                 }
+                "flow" => {
+                    proxy.flow = Some(v.to_string());
+                }
+                "up-mbps" => {
+                    proxy.up_mbps = v.parse::<u32>().ok();
+                }
+                "down-mbps" => {
+                    proxy.down_mbps = v.parse::<u32>().ok();
+                }
+                "udp-relay-mode" => {
+                    proxy.udp_relay_mode = Some(v.to_string());
+                }
+                "private-key" => {
+                    proxy.private_key = Some(v.to_string());
+                }
+                "private-key-passphrase" => {
+                    proxy.private_key_passphrase = Some(v.to_string());
+                }
+                "host-key-fingerprint" => {
+                    proxy.host_key_fingerprint = Some(v.to_string());
+                }
+                "known-hosts" => {
+                    proxy.known_hosts = Some(v.to_string());
+                }
+                "max-channels-per-conn" => {
+                    proxy.max_channels_per_conn = v.parse::<u32>().ok();
+                }
                 "amux" => proxy.amux = if v == "true" { Some(true) } else { Some(false) },
                 "amux-max" => {
                     let i = v.parse::<i32>().ok();
@@ -677,7 +870,32 @@ pub fn from_lines(lines: Vec<io::Result<String>>) -> Result<Config> {
                     let i = v.parse::<u64>().ok();
                     proxy.amux_max_lifetime = i;
                 }
+                "amux-keep-alive" => {
+                    let i = v.parse::<u32>().ok();
+                    proxy.amux_keep_alive = i;
+                }
+                "amux-open-timeout" => {
+                    let i = v.parse::<u32>().ok();
+                    proxy.amux_open_timeout = i;
+                }
                 "quic" => proxy.quic = if v == "true" { Some(true) } else { Some(false) },
+                "quic-congestion-controller" => {
+                    proxy.quic_congestion_controller = Some(v.to_string());
+                }
+                "quic-max-sessions-per-connection" => {
+                    let i = v.parse::<u32>().ok();
+                    proxy.quic_max_sessions_per_connection = i;
+                }
+                "quic-udp-over-stream" => {
+                    proxy.quic_udp_over_stream = if v == "true" { Some(true) } else { Some(false) }
+                }
+                "quic-zero-rtt" => {
+                    proxy.quic_zero_rtt = if v == "true" { Some(true) } else { Some(false) }
+                }
+                "quic-reconnect-on-network-change" => {
+                    proxy.quic_reconnect_on_network_change =
+                        if v == "true" { Some(true) } else { Some(false) }
+                }
                 "reality" => proxy.reality = if v == "true" { Some(true) } else { Some(false) },
                 "reality-public-key" => {
                     proxy.reality_public_key = Some(v.to_string());
@@ -737,6 +955,13 @@ pub fn from_lines(lines: Vec<io::Result<String>>) -> Result<Config> {
                 ("trojan", 0) => proxy.password = Some(param.clone()),
                 ("vmess", 0) => proxy.username = Some(param.clone()),
                 ("vless", 0) => proxy.password = Some(param.clone()),
+                ("hysteria2", 0) => proxy.password = Some(param.clone()),
+                ("tuic", 0) => proxy.uuid = Some(param.clone()),
+                ("tuic", 1) => proxy.password = Some(param.clone()),
+                ("ssh", 0) => proxy.username = Some(param.clone()),
+                ("ssh", 1) => proxy.password = Some(param.clone()),
+                ("http", 0) => proxy.username = Some(param.clone()),
+                ("http", 1) => proxy.password = Some(param.clone()),
                 _ => (),
             }
         }
@@ -794,7 +1019,8 @@ pub fn from_lines(lines: Vec<io::Result<String>>) -> Result<Config> {
 
         for param in params {
             if param.contains('=') {
-                let parts: Vec<&str> = param.split('=').map(str::trim).collect();
+                // A value may hold '=', e.g. the query of a URL.
+                let parts: Vec<&str> = param.splitn(2, '=').map(str::trim).collect();
                 if parts.len() != 2 {
                     continue;
                 }
@@ -879,17 +1105,46 @@ pub fn from_lines(lines: Vec<io::Result<String>>) -> Result<Config> {
                         let i = v.parse().ok();
                         group.health_check_success_percentage = i;
                     }
+                    "health-check-url" => {
+                        group.health_check_url = Some(v.to_owned());
+                    }
+                    "failures-before-down" => {
+                        let i = v.parse().ok();
+                        group.failures_before_down = i;
+                    }
+                    "successes-before-up" => {
+                        let i = v.parse().ok();
+                        group.successes_before_up = i;
+                    }
                     "delay-base" => {
                         let i = v.parse().ok();
                         group.delay_base = i;
                     }
-                    "method" => {
+                    "max-concurrent-attempts" => {
+                        let i = v.parse().ok();
+                        group.max_concurrent_attempts = i;
+                    }
+                    "method" | "strategy" => {
                         group.method = if !v.is_empty() {
                             Some(v.to_owned())
                         } else {
                             None
                         };
                     }
+                    "persist" => {
+                        group.persist = if v == "true" { Some(true) } else { Some(false) };
+                    }
+                    "fallback-on-failure" => {
+                        group.fallback_on_failure =
+                            if v == "true" { Some(true) } else { Some(false) };
+                    }
+                    "cache-file" => {
+                        group.cache_file = if !v.is_empty() {
+                            Some(v.to_owned())
+                        } else {
+                            None
+                        };
+                    }
                     _ => {}
                 }
             }
@@ -939,12 +1194,28 @@ pub fn from_lines(lines: Vec<io::Result<String>>) -> Result<Config> {
             continue; // at lease 3 params except the FINAL rule
         }
 
+        // A list of ports spans the params up to the target.
+        if matches!(rule.type_field.as_str(), "PORT-RANGE" | "SRC-PORT") {
+            let n = params[1..]
+                .iter()
+                .take_while(|x| x.chars().all(|c| c.is_ascii_digit() || c == '-'))
+                .count();
+            if n > 0 && n < params.len() - 1 {
+                rule.filter = Some(params[1..=n].join(","));
+                rule.target = params[n + 1].to_string();
+                rules.push(rule);
+            }
+            continue;
+        }
+
         // the 3th must be the target
         rule.target = params[2].to_string();
 
         match rule.type_field.as_str() {
             "IP-CIDR" | "DOMAIN" | "DOMAIN-SUFFIX" | "DOMAIN-KEYWORD" | "GEOIP" | "EXTERNAL"
-            | "PORT-RANGE" | "NETWORK" | "INBOUND-TAG" | "PROCESS-NAME" => {
+            | "ASN" | "IP-ASN" | "SRC-IP" | "SRC-IP-CIDR" | "NETWORK" | "INBOUND-TAG"
+            | "PROCESS-NAME" | "PROCESS" | "PROCESS-PATH" | "ALPN" | "SNI" | "SNI-SUFFIX"
+            | "USER" | "PROTOCOL" | "DOMAIN-SET" | "IP-SET" => {
                 rule.filter = Some(params[1].to_string());
             }
             _ => {}
@@ -996,6 +1267,13 @@ pub fn to_common(conf: &Config) -> Result<common::Config> {
             level: ext_general.loglevel.clone(),
             output: ext_general.logoutput.clone(),
             format: ext_general.logformat.clone(),
+            key_log_file: None,
+            access_log: ext_general.access_log.clone(),
+            access_log_format: ext_general.access_log_format.clone(),
+            access_log_start: ext_general.access_log_start,
+            max_size_mb: ext_general.log_max_size_mb,
+            max_backups: ext_general.log_max_backups,
+            max_age_days: ext_general.log_max_age_days,
         };
         common_config.log = Some(log);
 
@@ -1009,7 +1287,8 @@ pub fn to_common(conf: &Config) -> Result<common::Config> {
                 tag: Some("http".to_string()),
                 address: Some(interface.clone()),
                 port: Some(*port),
-                settings: common::InboundSettings::Http,
+                final_outbound: ext_general.http_final.clone(),
+                settings: common::InboundSettings::Http { settings: None },
             });
         }
 
@@ -1021,15 +1300,30 @@ pub fn to_common(conf: &Config) -> Result<common::Config> {
                 tag: Some("socks".to_string()),
                 address: Some(interface.clone()),
                 port: Some(*port),
+                final_outbound: ext_general.socks_final.clone(),
                 settings: common::InboundSettings::Socks { settings: None },
             });
         }
 
+        if let (Some(interface), Some(port)) = (
+            ext_general.mixed_interface.as_ref(),
+            ext_general.mixed_port.as_ref(),
+        ) {
+            inbounds.push(common::Inbound {
+                tag: Some("mixed".to_string()),
+                address: Some(interface.clone()),
+                port: Some(*port),
+                final_outbound: ext_general.mixed_final.clone(),
+                settings: common::InboundSettings::Mixed { settings: None },
+            });
+        }
+
         if let Some(nf) = &ext_general.nf {
             inbounds.push(common::Inbound {
                 tag: Some("nf".to_string()),
                 address: Some("127.0.0.1".to_string()),
                 port: Some(0),
+                final_outbound: None,
                 settings: common::InboundSettings::Nf {
                     settings: Some(common::NfInboundSettings {
                         driver_name: nf.driver_name.clone(),
@@ -1081,6 +1375,7 @@ pub fn to_common(conf: &Config) -> Result<common::Config> {
                 tag: Some("tun".to_string()),
                 address: None,
                 port: None,
+                final_outbound: ext_general.tun_final.clone(),
                 settings: common::InboundSettings::Tun {
                     settings: Some(settings),
                 },
@@ -1168,6 +1463,20 @@ pub fn to_common(conf: &Config) -> Result<common::Config> {
                         },
                     });
                 }
+                "http" => {
+                    outbounds.push(common::Outbound {
+                        tag: Some(ext_proxy.tag.clone()),
+                        settings: common::OutboundSettings::Http {
+                            settings: Some(common::HttpOutboundSettings {
+                                address: ext_proxy.address.clone(),
+                                port: ext_proxy.port,
+                                username: ext_proxy.username.clone(),
+                                password: ext_proxy.password.clone(),
+                                headers: None,
+                            }),
+                        },
+                    });
+                }
                 "shadowsocks" => {
                     let settings = common::ShadowsocksOutboundSettings {
                         address: ext_proxy.address.clone(),
@@ -1175,9 +1484,43 @@ pub fn to_common(conf: &Config) -> Result<common::Config> {
                         method: ext_proxy.encrypt_method.clone(),
                         password: ext_proxy.password.clone(),
                         prefix: ext_proxy.prefix.clone(),
+                        plugin: None,
+                        plugin_opts: None,
+                        udp_over_tcp: None,
                     };
 
-                    if let Some(obfs) = &ext_proxy.obfs_type {
+                    if let Some(shadow_tls_password) = &ext_proxy.shadow_tls_password {
+                        let ss_tag = format!("{}_ss_xxx", ext_proxy.tag);
+                        let shadow_tls_tag = format!("{}_shadowtls_xxx", ext_proxy.tag);
+
+                        outbounds.push(common::Outbound {
+                            tag: Some(ext_proxy.tag.clone()),
+                            settings: common::OutboundSettings::Chain {
+                                settings: Some(common::ChainOutboundSettings {
+                                    actors: Some(vec![shadow_tls_tag.clone(), ss_tag.clone()]),
+                                }),
+                            },
+                        });
+
+                        outbounds.push(common::Outbound {
+                            tag: Some(shadow_tls_tag),
+                            settings: common::OutboundSettings::ShadowTls {
+                                settings: Some(common::ShadowTlsOutboundSettings {
+                                    address: ext_proxy.address.clone(),
+                                    port: ext_proxy.port,
+                                    password: Some(shadow_tls_password.clone()),
+                                    handshake_server: ext_proxy.shadow_tls_sni.clone(),
+                                }),
+                            },
+                        });
+
+                        outbounds.push(common::Outbound {
+                            tag: Some(ss_tag),
+                            settings: common::OutboundSettings::Shadowsocks {
+                                settings: Some(settings),
+                            },
+                        });
+                    } else if let Some(obfs) = &ext_proxy.obfs_type {
                         let ss_tag = format!("{}_ss_xxx", ext_proxy.tag);
                         let obfs_tag = format!("{}_obfs_xxx", ext_proxy.tag);
 
@@ -1226,6 +1569,7 @@ pub fn to_common(conf: &Config) -> Result<common::Config> {
                             .uuid
                             .clone()
                             .or_else(|| ext_proxy.password.clone()), // prioritize uuid, then password
+                        flow: ext_proxy.flow.clone(),
                     };
 
                     let mut next_tag = ext_proxy.tag.clone();
@@ -1265,6 +1609,57 @@ pub fn to_common(conf: &Config) -> Result<common::Config> {
                         },
                     });
                 }
+                "hysteria2" => {
+                    outbounds.push(common::Outbound {
+                        tag: Some(ext_proxy.tag.clone()),
+                        settings: common::OutboundSettings::Hysteria2 {
+                            settings: Some(common::Hysteria2OutboundSettings {
+                                address: ext_proxy.address.clone(),
+                                port: ext_proxy.port,
+                                password: ext_proxy.password.clone(),
+                                up_mbps: ext_proxy.up_mbps,
+                                down_mbps: ext_proxy.down_mbps,
+                                sni: ext_proxy.sni.clone(),
+                                insecure: ext_proxy.tls_insecure,
+                            }),
+                        },
+                    });
+                }
+                "tuic" => {
+                    outbounds.push(common::Outbound {
+                        tag: Some(ext_proxy.tag.clone()),
+                        settings: common::OutboundSettings::Tuic {
+                            settings: Some(common::TuicOutboundSettings {
+                                address: ext_proxy.address.clone(),
+                                port: ext_proxy.port,
+                                uuid: ext_proxy.uuid.clone(),
+                                password: ext_proxy.password.clone(),
+                                sni: ext_proxy.sni.clone(),
+                                alpn: None,
+                                insecure: ext_proxy.tls_insecure,
+                                udp_relay_mode: ext_proxy.udp_relay_mode.clone(),
+                            }),
+                        },
+                    });
+                }
+                "ssh" => {
+                    outbounds.push(common::Outbound {
+                        tag: Some(ext_proxy.tag.clone()),
+                        settings: common::OutboundSettings::Ssh {
+                            settings: Some(common::SshOutboundSettings {
+                                address: ext_proxy.address.clone(),
+                                port: ext_proxy.port,
+                                username: ext_proxy.username.clone(),
+                                password: ext_proxy.password.clone(),
+                                private_key: ext_proxy.private_key.clone(),
+                                private_key_passphrase: ext_proxy.private_key_passphrase.clone(),
+                                host_key_fingerprint: ext_proxy.host_key_fingerprint.clone(),
+                                known_hosts: ext_proxy.known_hosts.clone(),
+                                max_channels_per_conn: ext_proxy.max_channels_per_conn,
+                            }),
+                        },
+                    });
+                }
                 "trojan" | "vmess" => {
                     let mut actors = Vec::new();
                     let mut component_outbounds = Vec::new();
@@ -1289,6 +1684,13 @@ pub fn to_common(conf: &Config) -> Result<common::Config> {
                                 ech: ext_proxy.tls_ech,
                                 ech_disable_dns_lookup: ext_proxy.tls_ech_disable_dns_lookup,
                                 ech_config_list: resolve_ech(&ext_proxy.tls_ech_config_list),
+                                ech_required: ext_proxy.tls_ech_required,
+                                certificate_fingerprint: ext_proxy.tls_cert_fingerprint.clone(),
+                                certificate_pins: None,
+                                pin_only: None,
+                                allow_name_mismatch: ext_proxy.tls_allow_name_mismatch,
+                                client_certificate: ext_proxy.tls_client_cert.clone(),
+                                client_certificate_key: ext_proxy.tls_client_key.clone(),
                             }),
                         },
                     });
@@ -1309,6 +1711,8 @@ pub fn to_common(conf: &Config) -> Result<common::Config> {
                                 } else {
                                     Some(ws_headers)
                                 },
+                                early_data_size: None,
+                                early_data_header_name: None,
                             }),
                         },
                     });
@@ -1331,6 +1735,13 @@ pub fn to_common(conf: &Config) -> Result<common::Config> {
                                     concurrency: ext_proxy.amux_con.map(|x| x as u32),
                                     max_recv_bytes: ext_proxy.amux_max_recv,
                                     max_lifetime: ext_proxy.amux_max_lifetime,
+                                    keep_alive_interval: ext_proxy.amux_keep_alive,
+                                    keep_alive_max_missed: None,
+                                    open_timeout: ext_proxy.amux_open_timeout,
+                                    max_connections: None,
+                                    connection_idle_timeout: None,
+                                    padding: None,
+                                    padding_max_overhead_pct: None,
                                 }),
                             },
                         });
@@ -1349,6 +1760,19 @@ pub fn to_common(conf: &Config) -> Result<common::Config> {
                                     raw_certificate: None,
                                     raw_certificate_key: None,
                                     alpn: Some(vec!["http/1.1".to_string()]),
+                                    congestion_controller: ext_proxy
+                                        .quic_congestion_controller
+                                        .clone(),
+                                    max_sessions_per_connection: ext_proxy
+                                        .quic_max_sessions_per_connection,
+                                    udp_over_stream: ext_proxy.quic_udp_over_stream,
+                                    zero_rtt: ext_proxy.quic_zero_rtt,
+                                    reconnect_on_network_change: ext_proxy
+                                        .quic_reconnect_on_network_change,
+                                    certificate_fingerprint: ext_proxy.tls_cert_fingerprint.clone(),
+                                    certificate_pins: None,
+                                    pin_only: None,
+                                    allow_name_mismatch: ext_proxy.tls_allow_name_mismatch,
                                 }),
                             },
                         });
@@ -1450,6 +1874,7 @@ pub fn to_common(conf: &Config) -> Result<common::Config> {
                             settings: Some(common::TryAllOutboundSettings {
                                 actors: ext_proxy_group.actors.clone(),
                                 delay_base: ext_proxy_group.delay_base,
+                                max_concurrent_attempts: ext_proxy_group.max_concurrent_attempts,
                             }),
                         },
                     });
@@ -1483,6 +1908,9 @@ pub fn to_common(conf: &Config) -> Result<common::Config> {
                                 health_check_attempts: ext_proxy_group.health_check_attempts,
                                 health_check_success_percentage: ext_proxy_group
                                     .health_check_success_percentage,
+                                health_check_url: ext_proxy_group.health_check_url.clone(),
+                                failures_before_down: ext_proxy_group.failures_before_down,
+                                successes_before_up: ext_proxy_group.successes_before_up,
                                 failover: ext_proxy_group.failover,
                                 fallback_cache: ext_proxy_group.fallback_cache,
                                 cache_size: ext_proxy_group.cache_size,
@@ -1497,6 +1925,12 @@ pub fn to_common(conf: &Config) -> Result<common::Config> {
                         settings: common::OutboundSettings::Select {
                             settings: Some(common::SelectOutboundSettings {
                                 actors: ext_proxy_group.actors.clone(),
+                                persist: ext_proxy_group.persist,
+                                cache_file: ext_proxy_group.cache_file.clone(),
+                                fallback_on_failure: ext_proxy_group.fallback_on_failure,
+                                failures_before_down: ext_proxy_group.failures_before_down,
+                                health_check_url: ext_proxy_group.health_check_url.clone(),
+                                check_interval: ext_proxy_group.check_interval,
                             }),
                         },
                     });
@@ -1529,11 +1963,23 @@ pub fn to_common(conf: &Config) -> Result<common::Config> {
                 domain_keyword: None,
                 domain_suffix: None,
                 geoip: None,
+                asn: None,
                 external: None,
                 port_range: None,
+                src_ip: None,
+                src_port: None,
                 network: None,
                 inbound_tag: None,
                 process_name: None,
+                process: None,
+                process_path: None,
+                alpn: None,
+                sni: None,
+                sni_suffix: None,
+                user: None,
+                protocol: None,
+                rule_provider: None,
+                rules: None,
                 target: ext_rule.target.clone(),
             };
 
@@ -1544,11 +1990,23 @@ pub fn to_common(conf: &Config) -> Result<common::Config> {
                     "DOMAIN-KEYWORD" => rule.domain_keyword = Some(vec![filter.clone()]),
                     "DOMAIN-SUFFIX" => rule.domain_suffix = Some(vec![filter.clone()]),
                     "GEOIP" => rule.geoip = Some(vec![filter.clone()]),
+                    "ASN" | "IP-ASN" => rule.asn = Some(vec![common::Asn::Name(filter.clone())]),
                     "EXTERNAL" => rule.external = Some(vec![filter.clone()]),
+                    "DOMAIN-SET" => rule.external = Some(vec![format!("domain-set:{}", filter)]),
+                    "IP-SET" => rule.external = Some(vec![format!("ip-set:{}", filter)]),
                     "PORT-RANGE" => rule.port_range = Some(vec![filter.clone()]),
+                    "SRC-IP" | "SRC-IP-CIDR" => rule.src_ip = Some(vec![filter.clone()]),
+                    "SRC-PORT" => rule.src_port = Some(vec![filter.clone()]),
                     "NETWORK" => rule.network = Some(vec![filter.clone()]),
                     "INBOUND-TAG" => rule.inbound_tag = Some(vec![filter.clone()]),
                     "PROCESS-NAME" => rule.process_name = Some(vec![filter.clone()]),
+                    "PROCESS" => rule.process = Some(vec![filter.clone()]),
+                    "PROCESS-PATH" => rule.process_path = Some(vec![filter.clone()]),
+                    "ALPN" => rule.alpn = Some(vec![filter.clone()]),
+                    "SNI" => rule.sni = Some(vec![filter.clone()]),
+                    "SNI-SUFFIX" => rule.sni_suffix = Some(vec![filter.clone()]),
+                    "USER" => rule.user = Some(vec![filter.clone()]),
+                    "PROTOCOL" => rule.protocol = Some(vec![filter.clone()]),
                     _ => {}
                 }
             }
@@ -1558,11 +2016,29 @@ pub fn to_common(conf: &Config) -> Result<common::Config> {
     common_config.router = Some(common::Router {
         rules: Some(rules),
         domain_resolve: conf.general.as_ref().and_then(|g| g.routing_domain_resolve),
+        rule_providers: None,
+        asn_database: None,
+        geoip_url: conf.general.as_ref().and_then(|g| g.geoip_url.clone()),
+        geoip_update_interval: conf.general.as_ref().and_then(|g| g.geoip_update_interval),
+        geoip_outbound: conf.general.as_ref().and_then(|g| g.geoip_outbound.clone()),
     });
 
     let mut dns = common::Dns {
         servers: None,
         hosts: None,
+        groups: None,
+        rules: None,
+        outbound: None,
+        hosts_ttl: None,
+        strategy: None,
+        cache_min_ttl: None,
+        cache_max_ttl: None,
+        cache_negative_ttl: None,
+        cache_size: None,
+        cache_file: None,
+        fake_ip_filter: None,
+        edns_client_subnet: None,
+        concurrent_queries: None,
     };
     if let Some(ext_general) = &conf.general {
         dns.servers = ext_general.dns_server.clone();
@@ -1570,6 +2046,22 @@ pub fn to_common(conf: &Config) -> Result<common::Config> {
     dns.hosts = conf.host.clone();
     common_config.dns = Some(dns);
 
+    if let Some(ext_general) = &conf.general {
+        if let Some(port) = ext_general.metrics_port {
+            common_config.metrics = Some(common::Metrics {
+                address: ext_general.metrics_interface.clone(),
+                port,
+            });
+        }
+        if let Some(port) = ext_general.api_port {
+            common_config.api = Some(common::Api {
+                address: ext_general.api_interface.clone(),
+                port,
+                secret: ext_general.api_secret.clone(),
+            });
+        }
+    }
+
     Ok(common_config)
 }
 
@@ -1589,6 +2081,146 @@ mod tests {
     use super::*;
     use protobuf::Message;
 
+    #[test]
+    fn test_hysteria2_outbound() {
+        let conf = r#"
+[Proxy]
+Hy2 = hysteria2, 1.2.3.4, 443, password, up-mbps=50, down-mbps=200, sni=www.example.com, tls-insecure=true
+"#;
+        let internal = from_string(conf).unwrap();
+        assert_eq!(internal.outbounds[0].protocol, "hysteria2");
+        let settings =
+            internal::Hysteria2OutboundSettings::parse_from_bytes(&internal.outbounds[0].settings)
+                .unwrap();
+        assert_eq!(settings.address, "1.2.3.4");
+        assert_eq!(settings.port, 443);
+        assert_eq!(settings.password, "password");
+        assert_eq!(settings.up_mbps, 50);
+        assert_eq!(settings.down_mbps, 200);
+        assert_eq!(settings.sni, "www.example.com");
+        assert!(settings.insecure);
+    }
+
+    #[test]
+    fn test_tuic_outbound() {
+        let conf = r#"
+[Proxy]
+Tuic = tuic, 1.2.3.4, 443, b831381d-6324-4d53-ad4f-8cda48b30811, password, sni=www.example.com, udp-relay-mode=quic
+"#;
+        let internal = from_string(conf).unwrap();
+        assert_eq!(internal.outbounds[0].protocol, "tuic");
+        let settings =
+            internal::TuicOutboundSettings::parse_from_bytes(&internal.outbounds[0].settings)
+                .unwrap();
+        assert_eq!(settings.address, "1.2.3.4");
+        assert_eq!(settings.port, 443);
+        assert_eq!(settings.uuid, "b831381d-6324-4d53-ad4f-8cda48b30811");
+        assert_eq!(settings.password, "password");
+        assert_eq!(settings.sni, "www.example.com");
+        assert_eq!(settings.udp_relay_mode, "quic");
+        assert!(!settings.insecure);
+    }
+
+    #[test]
+    fn test_http_outbound() {
+        let conf = r#"
+[Proxy]
+Http = http, 1.2.3.4, 8080, user, pass
+"#;
+        let internal = from_string(conf).unwrap();
+        assert_eq!(internal.outbounds[0].protocol, "http");
+        let settings =
+            internal::HttpOutboundSettings::parse_from_bytes(&internal.outbounds[0].settings)
+                .unwrap();
+        assert_eq!(settings.address, "1.2.3.4");
+        assert_eq!(settings.port, 8080);
+        assert_eq!(settings.username, "user");
+        assert_eq!(settings.password, "pass");
+    }
+
+    #[test]
+    fn test_ssh_outbound() {
+        let conf = r#"
+[Proxy]
+Ssh = ssh, 1.2.3.4, 22, user, private-key=/etc/leaf/id_ed25519, known-hosts=/etc/leaf/known_hosts, max-channels-per-conn=4
+"#;
+        let internal = from_string(conf).unwrap();
+        assert_eq!(internal.outbounds[0].protocol, "ssh");
+        let settings =
+            internal::SshOutboundSettings::parse_from_bytes(&internal.outbounds[0].settings)
+                .unwrap();
+        assert_eq!(settings.address, "1.2.3.4");
+        assert_eq!(settings.port, 22);
+        assert_eq!(settings.username, "user");
+        assert!(settings.password.is_empty());
+        assert_eq!(settings.private_key, "/etc/leaf/id_ed25519");
+        assert_eq!(settings.known_hosts, "/etc/leaf/known_hosts");
+        assert_eq!(settings.max_channels_per_conn, 4);
+    }
+
+    #[test]
+    fn test_port_rules() {
+        let conf = r#"
+[Rule]
+PORT-RANGE, 80, 443, 8000-8100, Proxy
+SRC-PORT, 6881-6889, Reject
+"#;
+        let internal = from_string(conf).unwrap();
+        let rules = &internal.router.rules;
+        assert_eq!(rules[0].port_ranges, vec!["80,443,8000-8100"]);
+        assert_eq!(rules[0].target_tag, "Proxy");
+        assert_eq!(rules[1].source_ports, vec!["6881-6889"]);
+        assert_eq!(rules[1].target_tag, "Reject");
+
+        let conf = "[Rule]\nPORT-RANGE, 22-21, Direct\n";
+        assert!(from_string(conf).is_err());
+    }
+
+    #[test]
+    fn test_set_rules() {
+        let dir = std::env::temp_dir();
+        let domains = dir.join("leaf-test-conf-domains.txt");
+        let ips = dir.join("leaf-test-conf-ips.txt");
+        std::fs::write(&domains, "# ads\nads.com\nkeyword:tracker\n").unwrap();
+        std::fs::write(&ips, "10.0.0.0/8\n2001:db8::/32\n").unwrap();
+        let conf = format!(
+            "[Rule]\nDOMAIN-SET, {}, Reject\nIP-SET, {}, Direct\n",
+            domains.display(),
+            ips.display()
+        );
+        let internal = from_string(&conf).unwrap();
+        let rules = &internal.router.rules;
+        assert_eq!(rules[0].set_domains.len(), 2);
+        assert_eq!(rules[0].target_tag, "Reject");
+        assert_eq!(rules[1].ip_cidrs, vec!["10.0.0.0/8", "2001:db8::/32"]);
+        assert_eq!(rules[1].target_tag, "Direct");
+        std::fs::remove_file(domains).unwrap();
+        std::fs::remove_file(ips).unwrap();
+    }
+
+    #[test]
+    fn test_shadowsocks_shadow_tls_outbound() {
+        let conf = r#"
+[Proxy]
+SS = ss, 1.2.3.4, 443, encrypt-method=chacha20-ietf-poly1305, password=password, shadow-tls-password=secret, shadow-tls-sni=www.example.com
+"#;
+        let internal = from_string(conf).unwrap();
+        assert_eq!(internal.outbounds[0].protocol, "chain");
+        assert_eq!(internal.outbounds[1].protocol, "shadowtls");
+        let settings =
+            internal::ShadowTlsOutboundSettings::parse_from_bytes(&internal.outbounds[1].settings)
+                .unwrap();
+        assert_eq!(settings.address, "1.2.3.4");
+        assert_eq!(settings.port, 443);
+        assert_eq!(settings.password, "secret");
+        assert_eq!(settings.handshake_server, "www.example.com");
+        assert_eq!(internal.outbounds[2].protocol, "shadowsocks");
+
+        // The site to mirror is required.
+        let conf = conf.replace(", shadow-tls-sni=www.example.com", "");
+        assert!(from_string(&conf).is_err());
+    }
+
     #[test]
     fn test_trojan_tls_outbound_order() {
         let conf = r#"
@@ -1785,6 +2417,36 @@ tun-dns-server = 8.8.8.8, 8.8.4.4
         }
     }
 
+    #[test]
+    fn test_metrics_conf() {
+        let conf = r#"
+[General]
+metrics-interface = 0.0.0.0
+metrics-port = 9100
+"#;
+        let lines: Vec<io::Result<String>> = conf.lines().map(|s| Ok(s.to_string())).collect();
+        let config = from_lines(lines).unwrap();
+        let metrics = to_internal(&config).unwrap().metrics.unwrap();
+        assert_eq!(metrics.address, "0.0.0.0");
+        assert_eq!(metrics.port, 9100);
+    }
+
+    #[test]
+    fn test_api_conf() {
+        let conf = r#"
+[General]
+api-interface = 0.0.0.0
+api-port = 9999
+api-secret = s3cret
+"#;
+        let lines: Vec<io::Result<String>> = conf.lines().map(|s| Ok(s.to_string())).collect();
+        let config = from_lines(lines).unwrap();
+        let api = to_internal(&config).unwrap().api.unwrap();
+        assert_eq!(api.address, "0.0.0.0");
+        assert_eq!(api.port, 9999);
+        assert_eq!(api.secret, "s3cret");
+    }
+
     #[test]
     fn test_tls_ech_fallback_mapping() {
         let conf = r#"
@@ -1970,6 +2632,43 @@ CERT4
         assert_eq!(certs.get("MyThirdCert").unwrap(), "CERT3\n");
         assert_eq!(certs.get("NoSpaceCert").unwrap(), "CERT4\n");
     }
+
+    #[test]
+    fn test_inbound_final() {
+        let conf = r#"
+[General]
+socks-interface = 127.0.0.1
+socks-port = 1080
+socks-final = Proxy
+http-interface = 127.0.0.1
+http-port = 1087
+
+[Proxy]
+Direct = direct
+Proxy = drop
+"#;
+        let lines: Vec<io::Result<String>> = conf.lines().map(|s| Ok(s.to_string())).collect();
+        let config = from_lines(lines).unwrap();
+        let internal = to_internal(&config).unwrap();
+        let socks = internal.inbounds.iter().find(|x| x.tag == "socks").unwrap();
+        assert_eq!(socks.final_outbound, "Proxy");
+        let http = internal.inbounds.iter().find(|x| x.tag == "http").unwrap();
+        assert!(http.final_outbound.is_empty());
+
+        let conf = conf.replace("socks-final = Proxy", "socks-final = Nowhere");
+        let lines: Vec<io::Result<String>> = conf.lines().map(|s| Ok(s.to_string())).collect();
+        let config = from_lines(lines).unwrap();
+        assert!(to_internal(&config).is_err());
+    }
+}
+
+pub fn common_from_file<P>(path: P) -> Result<common::Config>
+where
+    P: AsRef<Path>,
+{
+    let lines = read_lines(path)?.collect();
+    let config = from_lines(lines)?;
+    to_common(&config)
 }
 
 pub fn from_file<P>(path: P) -> Result<internal::Config>
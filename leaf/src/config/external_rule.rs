@@ -1,9 +1,13 @@
 use std::fs::File;
 use std::io::BufReader;
+use std::net::IpAddr;
 use std::path::Path;
+use std::time::Instant;
 
 use anyhow::anyhow;
 use anyhow::Result;
+use cidr::IpCidr;
+use tracing::info;
 
 use super::{geosite, internal};
 
@@ -32,10 +36,161 @@ pub fn load_mmdb_rule(filter: &str) -> Result<(String, String)> {
 }
 
 pub fn load_site_rule(filter: &str) -> Result<(String, String)> {
-    load_file_or_default(filter, "site.dat")
+    load_file_or_default(filter, &crate::option::SITE_FILE)
+}
+
+// Reads the domains of a category, given as the tag optionally followed by
+// attributes such as `google@cn`, in which case only the domains which
+// have all of them are taken.
+fn load_site_domains(file: &str, code: &str) -> Result<Vec<internal::router::rule::Domain>> {
+    let mut parts = code.split('@');
+    let tag = parts.next().unwrap_or_default().to_uppercase();
+    let attrs: Vec<&str> = parts.collect();
+
+    // Loads SiteGroup objects one by one instead of loading the whole list.
+    let f = File::open(file)
+        .map_err(|e| anyhow!("open {} for site category {} failed: {}", file, code, e))?;
+    let mut reader = BufReader::with_capacity(2048, f);
+    let mut input = protobuf::CodedInputStream::new(&mut reader);
+    while !input.eof()? {
+        let _ = input.read_raw_byte()?; // skip
+        let mut site_group = input.read_message::<geosite::SiteGroup>()?;
+        if site_group.tag.to_uppercase() != tag {
+            continue;
+        }
+        let mut domains = Vec::new();
+        for domain in site_group.domain.iter_mut() {
+            let has_attrs = attrs.iter().all(|attr| {
+                domain
+                    .attribute
+                    .iter()
+                    .any(|x| x.key.eq_ignore_ascii_case(attr))
+            });
+            if !has_attrs {
+                continue;
+            }
+            let type_ = match domain.type_.enum_value() {
+                Ok(geosite::domain::Type::Plain) => internal::router::rule::domain::Type::PLAIN,
+                Ok(geosite::domain::Type::Regex) => internal::router::rule::domain::Type::REGEX,
+                Ok(geosite::domain::Type::Domain) => internal::router::rule::domain::Type::DOMAIN,
+                Ok(geosite::domain::Type::Full) => internal::router::rule::domain::Type::FULL,
+                Err(_) => continue,
+            };
+            let mut domain_rule = internal::router::rule::Domain::new();
+            domain_rule.type_ = protobuf::EnumOrUnknown::new(type_);
+            domain_rule.value = std::mem::take(&mut domain.value);
+            domains.push(domain_rule);
+        }
+        return Ok(domains); // assume at most 1 matched tag
+    }
+    Err(anyhow!("site category {} not found in {}", code, file))
+}
+
+// An absolute path, or a path in the assets.
+fn asset_path(path: &str) -> String {
+    if Path::new(path).is_absolute() {
+        return path.to_string();
+    }
+    let asset_loc = Path::new(&*crate::option::ASSET_LOCATION);
+    asset_loc.join(path).to_string_lossy().to_string()
+}
+
+// Parses a domain set, a domain per line which is matched as a suffix, or
+// fully, as a keyword or as a regex with the prefixes `full:`, `keyword:`
+// and `regex:`. Lines starting with `#` are comments.
+fn parse_domain_set(file: &str, text: &str) -> Result<Vec<internal::router::rule::Domain>> {
+    use internal::router::rule::domain::Type;
+
+    let mut domains = Vec::new();
+    for (i, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (type_, value) = match line.split_once(':') {
+            Some(("domain", value)) => (Type::DOMAIN, value),
+            Some(("full", value)) => (Type::FULL, value),
+            Some(("keyword", value)) => (Type::PLAIN, value),
+            Some(("regex", value)) => (Type::REGEX, value),
+            Some((prefix, _)) => {
+                return Err(anyhow!("{}:{}: unknown prefix {}", file, i + 1, prefix));
+            }
+            None => (Type::DOMAIN, line),
+        };
+        let value = value.trim();
+        if value.is_empty() || (type_ != Type::REGEX && value.contains(char::is_whitespace)) {
+            return Err(anyhow!("{}:{}: invalid domain {}", file, i + 1, line));
+        }
+        #[cfg(feature = "regex")]
+        if type_ == Type::REGEX {
+            regex::Regex::new(value)
+                .map_err(|e| anyhow!("{}:{}: invalid regex {}: {}", file, i + 1, value, e))?;
+        }
+        let mut domain = internal::router::rule::Domain::new();
+        domain.type_ = protobuf::EnumOrUnknown::new(type_);
+        domain.value = value.to_string();
+        domains.push(domain);
+    }
+    Ok(domains)
+}
+
+// Parses an IP set, a CIDR or an IP per line. Lines starting with `#` are
+// comments.
+fn parse_ip_set(file: &str, text: &str) -> Result<Vec<String>> {
+    let mut cidrs = Vec::new();
+    for (i, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let cidr = match line.parse::<IpAddr>() {
+            Ok(ip) => IpCidr::new_host(ip),
+            Err(_) => line
+                .parse::<IpCidr>()
+                .map_err(|e| anyhow!("{}:{}: invalid cidr {}: {}", file, i + 1, line, e))?,
+        };
+        cidrs.push(format!(
+            "{}/{}",
+            cidr.first_address(),
+            cidr.network_length()
+        ));
+    }
+    Ok(cidrs)
+}
+
+fn read_set(file: &str) -> Result<String> {
+    std::fs::read_to_string(file).map_err(|e| anyhow!("read {} failed: {}", file, e))
 }
 
 pub fn add_external_rule(rule: &mut internal::router::Rule, ext_external: &str) -> Result<()> {
+    if let Some(path) = ext_external.strip_prefix("domain-set:") {
+        let start = Instant::now();
+        let file = asset_path(path);
+        let domains = parse_domain_set(&file, &read_set(&file)?)?;
+        info!(
+            "loaded {} domains from domain set {} in {} ms",
+            domains.len(),
+            file,
+            start.elapsed().as_millis()
+        );
+        rule.set_domains.extend(domains);
+        return Ok(());
+    }
+
+    if let Some(path) = ext_external.strip_prefix("ip-set:") {
+        let start = Instant::now();
+        let file = asset_path(path);
+        let cidrs = parse_ip_set(&file, &read_set(&file)?)?;
+        info!(
+            "loaded {} cidrs from ip set {} in {} ms",
+            cidrs.len(),
+            file,
+            start.elapsed().as_millis()
+        );
+        rule.ip_cidrs.extend(cidrs);
+        return Ok(());
+    }
+
     if ext_external.starts_with("mmdb") {
         let (file, code) = match load_mmdb_rule(ext_external) {
             Ok((f, c)) => (f, c),
@@ -49,61 +204,106 @@ pub fn add_external_rule(rule: &mut internal::router::Rule, ext_external: &str)
         rule.mmdbs.push(mmdb)
     }
 
-    if ext_external.starts_with("site") {
+    if ext_external.starts_with("site") || ext_external.starts_with("geosite") {
         let (file, code) = match load_site_rule(ext_external) {
             Ok((f, c)) => (f, c),
             Err(e) => {
                 return Err(anyhow!("load site rule failed: {}", e));
             }
         };
+        let domains = load_site_domains(&file, &code)?;
+        println!(
+            "loaded {} domain rules from [{}] for tag [{}]",
+            domains.len(),
+            file,
+            code
+        );
+        rule.domains.extend(domains);
+    }
+    Ok(())
+}
 
-        // Loads SiteGroup objects one by one instead of loading the whole list.
-        let mut reader = BufReader::with_capacity(2048, File::open(&file)?);
-        let mut input = protobuf::CodedInputStream::new(&mut reader);
-        while !input.eof()? {
-            let _ = input.read_raw_byte()?; // skip
-            let mut site_group = input.read_message::<geosite::SiteGroup>()?;
-            if site_group.tag == code.to_uppercase() {
-                for domain in site_group.domain.iter_mut() {
-                    let mut domain_rule = match domain.type_.unwrap() {
-                        geosite::domain::Type::Plain => {
-                            let mut d = internal::router::rule::Domain::new();
-                            d.type_ = protobuf::EnumOrUnknown::new(
-                                internal::router::rule::domain::Type::PLAIN,
-                            );
-                            d
-                        }
-                        geosite::domain::Type::Domain => {
-                            let mut d = internal::router::rule::Domain::new();
-                            d.type_ = protobuf::EnumOrUnknown::new(
-                                internal::router::rule::domain::Type::DOMAIN,
-                            );
-                            d
-                        }
-                        geosite::domain::Type::Full => {
-                            let mut d = internal::router::rule::Domain::new();
-                            d.type_ = protobuf::EnumOrUnknown::new(
-                                internal::router::rule::domain::Type::FULL,
-                            );
-                            d
-                        }
-                        _ => {
-                            continue;
-                        }
-                    };
-                    let value = std::mem::take(&mut domain.value);
-                    domain_rule.value = value;
-                    rule.domains.push(domain_rule);
-                }
-                println!(
-                    "loaded {} domain rules from [{}] for tag [{}]",
-                    rule.domains.len(),
-                    file,
-                    code
-                );
-                break; // assume at most 1 matched tag
-            }
+#[cfg(test)]
+mod tests {
+    use protobuf::Message;
+
+    use super::*;
+
+    fn site_domain(type_: geosite::domain::Type, value: &str, attrs: &[&str]) -> geosite::Domain {
+        let mut domain = geosite::Domain::new();
+        domain.type_ = protobuf::EnumOrUnknown::new(type_);
+        domain.value = value.to_string();
+        for key in attrs {
+            let mut attr = geosite::domain::Attribute::new();
+            attr.key = key.to_string();
+            domain.attribute.push(attr);
         }
+        domain
+    }
+
+    #[test]
+    fn test_parse_domain_set() {
+        use internal::router::rule::domain::Type;
+
+        let text =
+            "# ads\nexample.com\n\nfull:ads.example.org\nkeyword:tracker\nregex:^ad[0-9]+\\.\n";
+        let domains = parse_domain_set("ads.txt", text).unwrap();
+        let domains: Vec<(Type, &str)> = domains
+            .iter()
+            .map(|x| (x.type_.unwrap(), x.value.as_str()))
+            .collect();
+        assert_eq!(
+            domains,
+            vec![
+                (Type::DOMAIN, "example.com"),
+                (Type::FULL, "ads.example.org"),
+                (Type::PLAIN, "tracker"),
+                (Type::REGEX, "^ad[0-9]+\\."),
+            ]
+        );
+        let err = parse_domain_set("ads.txt", "example.com\nsuffix:example.org\n").unwrap_err();
+        assert!(err.to_string().starts_with("ads.txt:2:"));
+        let err = parse_domain_set("ads.txt", "example .com\n").unwrap_err();
+        assert!(err.to_string().starts_with("ads.txt:1:"));
+    }
+
+    #[test]
+    fn test_parse_ip_set() {
+        let text = "# lan\n10.0.0.0/8\n192.168.1.1\n\n2001:db8::/32\n";
+        let cidrs = parse_ip_set("lan.txt", text).unwrap();
+        assert_eq!(cidrs, vec!["10.0.0.0/8", "192.168.1.1/32", "2001:db8::/32"]);
+        let err = parse_ip_set("lan.txt", "10.0.0.0/8\n10.0.0.1/8\n").unwrap_err();
+        assert!(err.to_string().starts_with("lan.txt:2:"));
+    }
+
+    #[test]
+    fn test_load_site_domains() {
+        let mut group = geosite::SiteGroup::new();
+        group.tag = "NETFLIX".to_string();
+        group.domain = vec![
+            site_domain(geosite::domain::Type::Domain, "netflix.com", &[]),
+            site_domain(geosite::domain::Type::Regex, "^nflx.*\\.net$", &["cdn"]),
+            site_domain(geosite::domain::Type::Full, "ads.netflix.com", &["ads"]),
+        ];
+        let mut list = geosite::SiteGroupList::new();
+        list.site_group.push(group);
+        let path = std::env::temp_dir().join("leaf-test-geosite.dat");
+        std::fs::write(&path, list.write_to_bytes().unwrap()).unwrap();
+        let path = path.to_string_lossy().to_string();
+
+        let domains = load_site_domains(&path, "netflix").unwrap();
+        assert_eq!(domains.len(), 3);
+        assert_eq!(
+            domains[1].type_.unwrap(),
+            internal::router::rule::domain::Type::REGEX
+        );
+        let domains = load_site_domains(&path, "netflix@ads").unwrap();
+        assert_eq!(domains.len(), 1);
+        assert_eq!(domains[0].value, "ads.netflix.com");
+        let err = load_site_domains(&path, "hulu").unwrap_err();
+        assert!(err.to_string().contains("hulu"));
+        std::fs::remove_file(&path).unwrap();
+        let err = load_site_domains(&path, "netflix").unwrap_err();
+        assert!(err.to_string().contains("netflix"));
     }
-    Ok(())
 }
@@ -33,6 +33,34 @@ pub struct Dns {
     pub servers: ::std::vec::Vec<::std::string::String>,
     // @@protoc_insertion_point(field:Dns.hosts)
     pub hosts: ::std::collections::HashMap<::std::string::String, dns::Ips>,
+    // @@protoc_insertion_point(field:Dns.groups)
+    pub groups: ::std::collections::HashMap<::std::string::String, dns::Servers>,
+    // @@protoc_insertion_point(field:Dns.rules)
+    pub rules: ::std::vec::Vec<dns::Rule>,
+    // @@protoc_insertion_point(field:Dns.outbound)
+    pub outbound: ::std::string::String,
+    // @@protoc_insertion_point(field:Dns.hosts_ttl)
+    pub hosts_ttl: u32,
+    // @@protoc_insertion_point(field:Dns.strategy)
+    pub strategy: ::std::string::String,
+    // @@protoc_insertion_point(field:Dns.cache_min_ttl)
+    pub cache_min_ttl: u32,
+    // @@protoc_insertion_point(field:Dns.cache_max_ttl)
+    pub cache_max_ttl: u32,
+    // @@protoc_insertion_point(field:Dns.cache_negative_ttl)
+    pub cache_negative_ttl: u32,
+    // @@protoc_insertion_point(field:Dns.cache_size)
+    pub cache_size: u32,
+    // @@protoc_insertion_point(field:Dns.cache_file)
+    pub cache_file: ::std::string::String,
+    // @@protoc_insertion_point(field:Dns.fake_ip_filter_mode)
+    pub fake_ip_filter_mode: ::std::string::String,
+    // @@protoc_insertion_point(field:Dns.fake_ip_filter)
+    pub fake_ip_filter: ::std::vec::Vec<router::rule::Domain>,
+    // @@protoc_insertion_point(field:Dns.edns_client_subnet)
+    pub edns_client_subnet: ::std::string::String,
+    // @@protoc_insertion_point(field:Dns.concurrent_queries)
+    pub concurrent_queries: bool,
     // special fields
     // @@protoc_insertion_point(special_field:Dns.special_fields)
     pub special_fields: ::protobuf::SpecialFields,
@@ -78,6 +106,60 @@ impl ::protobuf::Message for Dns {
                     is.pop_limit(old_limit);
                     self.hosts.insert(key, value);
                 },
+                34 => {
+                    let len = is.read_raw_varint32()?;
+                    let old_limit = is.push_limit(len as u64)?;
+                    let mut key = ::std::default::Default::default();
+                    let mut value = ::std::default::Default::default();
+                    while let Some(tag) = is.read_raw_tag_or_eof()? {
+                        match tag {
+                            10 => key = is.read_string()?,
+                            18 => value = is.read_message()?,
+                            _ => ::protobuf::rt::skip_field_for_tag(tag, is)?,
+                        };
+                    }
+                    is.pop_limit(old_limit);
+                    self.groups.insert(key, value);
+                },
+                42 => {
+                    self.rules.push(is.read_message()?);
+                },
+                50 => {
+                    self.outbound = is.read_string()?;
+                },
+                56 => {
+                    self.hosts_ttl = is.read_uint32()?;
+                },
+                66 => {
+                    self.strategy = is.read_string()?;
+                },
+                72 => {
+                    self.cache_min_ttl = is.read_uint32()?;
+                },
+                80 => {
+                    self.cache_max_ttl = is.read_uint32()?;
+                },
+                88 => {
+                    self.cache_negative_ttl = is.read_uint32()?;
+                },
+                96 => {
+                    self.cache_size = is.read_uint32()?;
+                },
+                106 => {
+                    self.cache_file = is.read_string()?;
+                },
+                114 => {
+                    self.fake_ip_filter_mode = is.read_string()?;
+                },
+                122 => {
+                    self.fake_ip_filter.push(is.read_message()?);
+                },
+                130 => {
+                    self.edns_client_subnet = is.read_string()?;
+                },
+                136 => {
+                    self.concurrent_queries = is.read_bool()?;
+                },
                 tag => {
                     ::protobuf::rt::read_unknown_or_skip_group(tag, is, self.special_fields.mut_unknown_fields())?;
                 },
@@ -100,6 +182,54 @@ impl ::protobuf::Message for Dns {
             entry_size += 1 + ::protobuf::rt::compute_raw_varint64_size(len) + len;
             my_size += 1 + ::protobuf::rt::compute_raw_varint64_size(entry_size) + entry_size
         };
+        for (k, v) in &self.groups {
+            let mut entry_size = 0;
+            entry_size += ::protobuf::rt::string_size(1, &k);
+            let len = v.compute_size();
+            entry_size += 1 + ::protobuf::rt::compute_raw_varint64_size(len) + len;
+            my_size += 1 + ::protobuf::rt::compute_raw_varint64_size(entry_size) + entry_size
+        };
+        for value in &self.rules {
+            let len = value.compute_size();
+            my_size += 1 + ::protobuf::rt::compute_raw_varint64_size(len) + len;
+        };
+        if !self.outbound.is_empty() {
+            my_size += ::protobuf::rt::string_size(6, &self.outbound);
+        }
+        if self.hosts_ttl != 0 {
+            my_size += ::protobuf::rt::uint32_size(7, self.hosts_ttl);
+        }
+        if !self.strategy.is_empty() {
+            my_size += ::protobuf::rt::string_size(8, &self.strategy);
+        }
+        if self.cache_min_ttl != 0 {
+            my_size += ::protobuf::rt::uint32_size(9, self.cache_min_ttl);
+        }
+        if self.cache_max_ttl != 0 {
+            my_size += ::protobuf::rt::uint32_size(10, self.cache_max_ttl);
+        }
+        if self.cache_negative_ttl != 0 {
+            my_size += ::protobuf::rt::uint32_size(11, self.cache_negative_ttl);
+        }
+        if self.cache_size != 0 {
+            my_size += ::protobuf::rt::uint32_size(12, self.cache_size);
+        }
+        if !self.cache_file.is_empty() {
+            my_size += ::protobuf::rt::string_size(13, &self.cache_file);
+        }
+        if !self.fake_ip_filter_mode.is_empty() {
+            my_size += ::protobuf::rt::string_size(14, &self.fake_ip_filter_mode);
+        }
+        for value in &self.fake_ip_filter {
+            let len = value.compute_size();
+            my_size += 1 + ::protobuf::rt::compute_raw_varint64_size(len) + len;
+        };
+        if !self.edns_client_subnet.is_empty() {
+            my_size += ::protobuf::rt::string_size(16, &self.edns_client_subnet);
+        }
+        if self.concurrent_queries != false {
+            my_size += 2 + 1;
+        }
         my_size += ::protobuf::rt::unknown_fields_size(self.special_fields.unknown_fields());
         self.special_fields.cached_size().set(my_size as u32);
         my_size
@@ -119,6 +249,55 @@ impl ::protobuf::Message for Dns {
             os.write_string(1, &k)?;
             ::protobuf::rt::write_message_field_with_cached_size(2, v, os)?;
         };
+        for (k, v) in &self.groups {
+            let mut entry_size = 0;
+            entry_size += ::protobuf::rt::string_size(1, &k);
+            let len = v.cached_size() as u64;
+            entry_size += 1 + ::protobuf::rt::compute_raw_varint64_size(len) + len;
+            os.write_raw_varint32(34)?; // Tag.
+            os.write_raw_varint32(entry_size as u32)?;
+            os.write_string(1, &k)?;
+            ::protobuf::rt::write_message_field_with_cached_size(2, v, os)?;
+        };
+        for v in &self.rules {
+            ::protobuf::rt::write_message_field_with_cached_size(5, v, os)?;
+        };
+        if !self.outbound.is_empty() {
+            os.write_string(6, &self.outbound)?;
+        }
+        if self.hosts_ttl != 0 {
+            os.write_uint32(7, self.hosts_ttl)?;
+        }
+        if !self.strategy.is_empty() {
+            os.write_string(8, &self.strategy)?;
+        }
+        if self.cache_min_ttl != 0 {
+            os.write_uint32(9, self.cache_min_ttl)?;
+        }
+        if self.cache_max_ttl != 0 {
+            os.write_uint32(10, self.cache_max_ttl)?;
+        }
+        if self.cache_negative_ttl != 0 {
+            os.write_uint32(11, self.cache_negative_ttl)?;
+        }
+        if self.cache_size != 0 {
+            os.write_uint32(12, self.cache_size)?;
+        }
+        if !self.cache_file.is_empty() {
+            os.write_string(13, &self.cache_file)?;
+        }
+        if !self.fake_ip_filter_mode.is_empty() {
+            os.write_string(14, &self.fake_ip_filter_mode)?;
+        }
+        for v in &self.fake_ip_filter {
+            ::protobuf::rt::write_message_field_with_cached_size(15, v, os)?;
+        };
+        if !self.edns_client_subnet.is_empty() {
+            os.write_string(16, &self.edns_client_subnet)?;
+        }
+        if self.concurrent_queries != false {
+            os.write_bool(17, self.concurrent_queries)?;
+        }
         os.write_unknown_fields(self.special_fields.unknown_fields())?;
         ::std::result::Result::Ok(())
     }
@@ -138,6 +317,20 @@ impl ::protobuf::Message for Dns {
     fn clear(&mut self) {
         self.servers.clear();
         self.hosts.clear();
+        self.groups.clear();
+        self.rules.clear();
+        self.outbound.clear();
+        self.hosts_ttl = 0;
+        self.strategy.clear();
+        self.cache_min_ttl = 0;
+        self.cache_max_ttl = 0;
+        self.cache_negative_ttl = 0;
+        self.cache_size = 0;
+        self.cache_file.clear();
+        self.fake_ip_filter_mode.clear();
+        self.fake_ip_filter.clear();
+        self.edns_client_subnet.clear();
+        self.concurrent_queries = false;
         self.special_fields.clear();
     }
 
@@ -238,124 +431,415 @@ pub mod dns {
             &instance
         }
     }
-}
-
-// @@protoc_insertion_point(message:Log)
-#[derive(PartialEq,Clone,Default,Debug)]
-pub struct Log {
-    // message fields
-    // @@protoc_insertion_point(field:Log.level)
-    pub level: ::protobuf::EnumOrUnknown<log::Level>,
-    // @@protoc_insertion_point(field:Log.output)
-    pub output: ::protobuf::EnumOrUnknown<log::Output>,
-    // @@protoc_insertion_point(field:Log.output_file)
-    pub output_file: ::std::string::String,
-    // @@protoc_insertion_point(field:Log.format)
-    pub format: ::protobuf::EnumOrUnknown<log::Format>,
-    // special fields
-    // @@protoc_insertion_point(special_field:Log.special_fields)
-    pub special_fields: ::protobuf::SpecialFields,
-}
 
-impl<'a> ::std::default::Default for &'a Log {
-    fn default() -> &'a Log {
-        <Log as ::protobuf::Message>::default_instance()
+    // @@protoc_insertion_point(message:Dns.Servers)
+    #[derive(PartialEq,Clone,Default,Debug)]
+    pub struct Servers {
+        // message fields
+        // @@protoc_insertion_point(field:Dns.Servers.values)
+        pub values: ::std::vec::Vec<::std::string::String>,
+        // @@protoc_insertion_point(field:Dns.Servers.outbound)
+        pub outbound: ::std::string::String,
+        // special fields
+        // @@protoc_insertion_point(special_field:Dns.Servers.special_fields)
+        pub special_fields: ::protobuf::SpecialFields,
     }
-}
 
-impl Log {
-    pub fn new() -> Log {
-        ::std::default::Default::default()
+    impl<'a> ::std::default::Default for &'a Servers {
+        fn default() -> &'a Servers {
+            <Servers as ::protobuf::Message>::default_instance()
+        }
     }
-}
-
-impl ::protobuf::Message for Log {
-    const NAME: &'static str = "Log";
 
-    fn is_initialized(&self) -> bool {
-        true
+    impl Servers {
+        pub fn new() -> Servers {
+            ::std::default::Default::default()
+        }
     }
 
-    fn merge_from(&mut self, is: &mut ::protobuf::CodedInputStream<'_>) -> ::protobuf::Result<()> {
-        while let Some(tag) = is.read_raw_tag_or_eof()? {
-            match tag {
-                8 => {
-                    self.level = is.read_enum_or_unknown()?;
-                },
-                16 => {
-                    self.output = is.read_enum_or_unknown()?;
-                },
-                26 => {
-                    self.output_file = is.read_string()?;
-                },
-                32 => {
-                    self.format = is.read_enum_or_unknown()?;
-                },
-                tag => {
-                    ::protobuf::rt::read_unknown_or_skip_group(tag, is, self.special_fields.mut_unknown_fields())?;
-                },
-            };
+    impl ::protobuf::Message for Servers {
+        const NAME: &'static str = "Servers";
+
+        fn is_initialized(&self) -> bool {
+            true
         }
-        ::std::result::Result::Ok(())
-    }
 
-    // Compute sizes of nested messages
-    #[allow(unused_variables)]
-    fn compute_size(&self) -> u64 {
-        let mut my_size = 0;
-        if self.level != ::protobuf::EnumOrUnknown::new(log::Level::INFO) {
-            my_size += ::protobuf::rt::int32_size(1, self.level.value());
+        fn merge_from(&mut self, is: &mut ::protobuf::CodedInputStream<'_>) -> ::protobuf::Result<()> {
+            while let Some(tag) = is.read_raw_tag_or_eof()? {
+                match tag {
+                    10 => {
+                        self.values.push(is.read_string()?);
+                    },
+                    18 => {
+                        self.outbound = is.read_string()?;
+                    },
+                    tag => {
+                        ::protobuf::rt::read_unknown_or_skip_group(tag, is, self.special_fields.mut_unknown_fields())?;
+                    },
+                };
+            }
+            ::std::result::Result::Ok(())
         }
-        if self.output != ::protobuf::EnumOrUnknown::new(log::Output::CONSOLE) {
-            my_size += ::protobuf::rt::int32_size(2, self.output.value());
+
+        // Compute sizes of nested messages
+        #[allow(unused_variables)]
+        fn compute_size(&self) -> u64 {
+            let mut my_size = 0;
+            for value in &self.values {
+                my_size += ::protobuf::rt::string_size(1, &value);
+            };
+            if !self.outbound.is_empty() {
+                my_size += ::protobuf::rt::string_size(2, &self.outbound);
+            }
+            my_size += ::protobuf::rt::unknown_fields_size(self.special_fields.unknown_fields());
+            self.special_fields.cached_size().set(my_size as u32);
+            my_size
         }
-        if !self.output_file.is_empty() {
-            my_size += ::protobuf::rt::string_size(3, &self.output_file);
+
+        fn write_to_with_cached_sizes(&self, os: &mut ::protobuf::CodedOutputStream<'_>) -> ::protobuf::Result<()> {
+            for v in &self.values {
+                os.write_string(1, &v)?;
+            };
+            if !self.outbound.is_empty() {
+                os.write_string(2, &self.outbound)?;
+            }
+            os.write_unknown_fields(self.special_fields.unknown_fields())?;
+            ::std::result::Result::Ok(())
         }
-        if self.format != ::protobuf::EnumOrUnknown::new(log::Format::FULL) {
-            my_size += ::protobuf::rt::int32_size(4, self.format.value());
+
+        fn special_fields(&self) -> &::protobuf::SpecialFields {
+            &self.special_fields
         }
-        my_size += ::protobuf::rt::unknown_fields_size(self.special_fields.unknown_fields());
-        self.special_fields.cached_size().set(my_size as u32);
-        my_size
-    }
 
-    fn write_to_with_cached_sizes(&self, os: &mut ::protobuf::CodedOutputStream<'_>) -> ::protobuf::Result<()> {
-        if self.level != ::protobuf::EnumOrUnknown::new(log::Level::INFO) {
-            os.write_enum(1, ::protobuf::EnumOrUnknown::value(&self.level))?;
+        fn mut_special_fields(&mut self) -> &mut ::protobuf::SpecialFields {
+            &mut self.special_fields
         }
-        if self.output != ::protobuf::EnumOrUnknown::new(log::Output::CONSOLE) {
-            os.write_enum(2, ::protobuf::EnumOrUnknown::value(&self.output))?;
+
+        fn new() -> Servers {
+            Servers::new()
         }
-        if !self.output_file.is_empty() {
-            os.write_string(3, &self.output_file)?;
+
+        fn clear(&mut self) {
+            self.values.clear();
+            self.outbound.clear();
+            self.special_fields.clear();
         }
-        if self.format != ::protobuf::EnumOrUnknown::new(log::Format::FULL) {
-            os.write_enum(4, ::protobuf::EnumOrUnknown::value(&self.format))?;
+
+        fn default_instance() -> &'static Servers {
+            static instance: Servers = Servers {
+                values: ::std::vec::Vec::new(),
+                outbound: ::std::string::String::new(),
+                special_fields: ::protobuf::SpecialFields::new(),
+            };
+            &instance
         }
-        os.write_unknown_fields(self.special_fields.unknown_fields())?;
-        ::std::result::Result::Ok(())
     }
 
-    fn special_fields(&self) -> &::protobuf::SpecialFields {
-        &self.special_fields
+    // @@protoc_insertion_point(message:Dns.Rule)
+    #[derive(PartialEq,Clone,Default,Debug)]
+    pub struct Rule {
+        // message fields
+        // @@protoc_insertion_point(field:Dns.Rule.domains)
+        pub domains: ::std::vec::Vec<super::router::rule::Domain>,
+        // @@protoc_insertion_point(field:Dns.Rule.group)
+        pub group: ::std::string::String,
+        // special fields
+        // @@protoc_insertion_point(special_field:Dns.Rule.special_fields)
+        pub special_fields: ::protobuf::SpecialFields,
     }
 
-    fn mut_special_fields(&mut self) -> &mut ::protobuf::SpecialFields {
-        &mut self.special_fields
+    impl<'a> ::std::default::Default for &'a Rule {
+        fn default() -> &'a Rule {
+            <Rule as ::protobuf::Message>::default_instance()
+        }
     }
 
-    fn new() -> Log {
-        Log::new()
+    impl Rule {
+        pub fn new() -> Rule {
+            ::std::default::Default::default()
+        }
     }
 
-    fn clear(&mut self) {
-        self.level = ::protobuf::EnumOrUnknown::new(log::Level::INFO);
-        self.output = ::protobuf::EnumOrUnknown::new(log::Output::CONSOLE);
-        self.output_file.clear();
-        self.format = ::protobuf::EnumOrUnknown::new(log::Format::FULL);
-        self.special_fields.clear();
-    }
+    impl ::protobuf::Message for Rule {
+        const NAME: &'static str = "Rule";
+
+        fn is_initialized(&self) -> bool {
+            true
+        }
+
+        fn merge_from(&mut self, is: &mut ::protobuf::CodedInputStream<'_>) -> ::protobuf::Result<()> {
+            while let Some(tag) = is.read_raw_tag_or_eof()? {
+                match tag {
+                    10 => {
+                        self.domains.push(is.read_message()?);
+                    },
+                    18 => {
+                        self.group = is.read_string()?;
+                    },
+                    tag => {
+                        ::protobuf::rt::read_unknown_or_skip_group(tag, is, self.special_fields.mut_unknown_fields())?;
+                    },
+                };
+            }
+            ::std::result::Result::Ok(())
+        }
+
+        // Compute sizes of nested messages
+        #[allow(unused_variables)]
+        fn compute_size(&self) -> u64 {
+            let mut my_size = 0;
+            for value in &self.domains {
+                let len = value.compute_size();
+                my_size += 1 + ::protobuf::rt::compute_raw_varint64_size(len) + len;
+            };
+            if !self.group.is_empty() {
+                my_size += ::protobuf::rt::string_size(2, &self.group);
+            }
+            my_size += ::protobuf::rt::unknown_fields_size(self.special_fields.unknown_fields());
+            self.special_fields.cached_size().set(my_size as u32);
+            my_size
+        }
+
+        fn write_to_with_cached_sizes(&self, os: &mut ::protobuf::CodedOutputStream<'_>) -> ::protobuf::Result<()> {
+            for v in &self.domains {
+                ::protobuf::rt::write_message_field_with_cached_size(1, v, os)?;
+            };
+            if !self.group.is_empty() {
+                os.write_string(2, &self.group)?;
+            }
+            os.write_unknown_fields(self.special_fields.unknown_fields())?;
+            ::std::result::Result::Ok(())
+        }
+
+        fn special_fields(&self) -> &::protobuf::SpecialFields {
+            &self.special_fields
+        }
+
+        fn mut_special_fields(&mut self) -> &mut ::protobuf::SpecialFields {
+            &mut self.special_fields
+        }
+
+        fn new() -> Rule {
+            Rule::new()
+        }
+
+        fn clear(&mut self) {
+            self.domains.clear();
+            self.group.clear();
+            self.special_fields.clear();
+        }
+
+        fn default_instance() -> &'static Rule {
+            static instance: Rule = Rule {
+                domains: ::std::vec::Vec::new(),
+                group: ::std::string::String::new(),
+                special_fields: ::protobuf::SpecialFields::new(),
+            };
+            &instance
+        }
+    }
+}
+
+// @@protoc_insertion_point(message:Log)
+#[derive(PartialEq,Clone,Default,Debug)]
+pub struct Log {
+    // message fields
+    // @@protoc_insertion_point(field:Log.level)
+    pub level: ::protobuf::EnumOrUnknown<log::Level>,
+    // @@protoc_insertion_point(field:Log.output)
+    pub output: ::protobuf::EnumOrUnknown<log::Output>,
+    // @@protoc_insertion_point(field:Log.output_file)
+    pub output_file: ::std::string::String,
+    // @@protoc_insertion_point(field:Log.format)
+    pub format: ::protobuf::EnumOrUnknown<log::Format>,
+    // @@protoc_insertion_point(field:Log.key_log_file)
+    pub key_log_file: ::std::string::String,
+    // @@protoc_insertion_point(field:Log.access_log)
+    pub access_log: ::std::string::String,
+    // @@protoc_insertion_point(field:Log.access_log_format)
+    pub access_log_format: ::std::string::String,
+    // @@protoc_insertion_point(field:Log.access_log_start)
+    pub access_log_start: bool,
+    // @@protoc_insertion_point(field:Log.max_size_mb)
+    pub max_size_mb: u32,
+    // @@protoc_insertion_point(field:Log.max_backups)
+    pub max_backups: u32,
+    // @@protoc_insertion_point(field:Log.max_age_days)
+    pub max_age_days: u32,
+    // special fields
+    // @@protoc_insertion_point(special_field:Log.special_fields)
+    pub special_fields: ::protobuf::SpecialFields,
+}
+
+impl<'a> ::std::default::Default for &'a Log {
+    fn default() -> &'a Log {
+        <Log as ::protobuf::Message>::default_instance()
+    }
+}
+
+impl Log {
+    pub fn new() -> Log {
+        ::std::default::Default::default()
+    }
+}
+
+impl ::protobuf::Message for Log {
+    const NAME: &'static str = "Log";
+
+    fn is_initialized(&self) -> bool {
+        true
+    }
+
+    fn merge_from(&mut self, is: &mut ::protobuf::CodedInputStream<'_>) -> ::protobuf::Result<()> {
+        while let Some(tag) = is.read_raw_tag_or_eof()? {
+            match tag {
+                8 => {
+                    self.level = is.read_enum_or_unknown()?;
+                },
+                16 => {
+                    self.output = is.read_enum_or_unknown()?;
+                },
+                26 => {
+                    self.output_file = is.read_string()?;
+                },
+                32 => {
+                    self.format = is.read_enum_or_unknown()?;
+                },
+                42 => {
+                    self.key_log_file = is.read_string()?;
+                },
+                50 => {
+                    self.access_log = is.read_string()?;
+                },
+                58 => {
+                    self.access_log_format = is.read_string()?;
+                },
+                64 => {
+                    self.access_log_start = is.read_bool()?;
+                },
+                72 => {
+                    self.max_size_mb = is.read_uint32()?;
+                },
+                80 => {
+                    self.max_backups = is.read_uint32()?;
+                },
+                88 => {
+                    self.max_age_days = is.read_uint32()?;
+                },
+                tag => {
+                    ::protobuf::rt::read_unknown_or_skip_group(tag, is, self.special_fields.mut_unknown_fields())?;
+                },
+            };
+        }
+        ::std::result::Result::Ok(())
+    }
+
+    // Compute sizes of nested messages
+    #[allow(unused_variables)]
+    fn compute_size(&self) -> u64 {
+        let mut my_size = 0;
+        if self.level != ::protobuf::EnumOrUnknown::new(log::Level::INFO) {
+            my_size += ::protobuf::rt::int32_size(1, self.level.value());
+        }
+        if self.output != ::protobuf::EnumOrUnknown::new(log::Output::CONSOLE) {
+            my_size += ::protobuf::rt::int32_size(2, self.output.value());
+        }
+        if !self.output_file.is_empty() {
+            my_size += ::protobuf::rt::string_size(3, &self.output_file);
+        }
+        if self.format != ::protobuf::EnumOrUnknown::new(log::Format::FULL) {
+            my_size += ::protobuf::rt::int32_size(4, self.format.value());
+        }
+        if !self.key_log_file.is_empty() {
+            my_size += ::protobuf::rt::string_size(5, &self.key_log_file);
+        }
+        if !self.access_log.is_empty() {
+            my_size += ::protobuf::rt::string_size(6, &self.access_log);
+        }
+        if !self.access_log_format.is_empty() {
+            my_size += ::protobuf::rt::string_size(7, &self.access_log_format);
+        }
+        if self.access_log_start != false {
+            my_size += 1 + 1;
+        }
+        if self.max_size_mb != 0 {
+            my_size += ::protobuf::rt::uint32_size(9, self.max_size_mb);
+        }
+        if self.max_backups != 0 {
+            my_size += ::protobuf::rt::uint32_size(10, self.max_backups);
+        }
+        if self.max_age_days != 0 {
+            my_size += ::protobuf::rt::uint32_size(11, self.max_age_days);
+        }
+        my_size += ::protobuf::rt::unknown_fields_size(self.special_fields.unknown_fields());
+        self.special_fields.cached_size().set(my_size as u32);
+        my_size
+    }
+
+    fn write_to_with_cached_sizes(&self, os: &mut ::protobuf::CodedOutputStream<'_>) -> ::protobuf::Result<()> {
+        if self.level != ::protobuf::EnumOrUnknown::new(log::Level::INFO) {
+            os.write_enum(1, ::protobuf::EnumOrUnknown::value(&self.level))?;
+        }
+        if self.output != ::protobuf::EnumOrUnknown::new(log::Output::CONSOLE) {
+            os.write_enum(2, ::protobuf::EnumOrUnknown::value(&self.output))?;
+        }
+        if !self.output_file.is_empty() {
+            os.write_string(3, &self.output_file)?;
+        }
+        if self.format != ::protobuf::EnumOrUnknown::new(log::Format::FULL) {
+            os.write_enum(4, ::protobuf::EnumOrUnknown::value(&self.format))?;
+        }
+        if !self.key_log_file.is_empty() {
+            os.write_string(5, &self.key_log_file)?;
+        }
+        if !self.access_log.is_empty() {
+            os.write_string(6, &self.access_log)?;
+        }
+        if !self.access_log_format.is_empty() {
+            os.write_string(7, &self.access_log_format)?;
+        }
+        if self.access_log_start != false {
+            os.write_bool(8, self.access_log_start)?;
+        }
+        if self.max_size_mb != 0 {
+            os.write_uint32(9, self.max_size_mb)?;
+        }
+        if self.max_backups != 0 {
+            os.write_uint32(10, self.max_backups)?;
+        }
+        if self.max_age_days != 0 {
+            os.write_uint32(11, self.max_age_days)?;
+        }
+        os.write_unknown_fields(self.special_fields.unknown_fields())?;
+        ::std::result::Result::Ok(())
+    }
+
+    fn special_fields(&self) -> &::protobuf::SpecialFields {
+        &self.special_fields
+    }
+
+    fn mut_special_fields(&mut self) -> &mut ::protobuf::SpecialFields {
+        &mut self.special_fields
+    }
+
+    fn new() -> Log {
+        Log::new()
+    }
+
+    fn clear(&mut self) {
+        self.level = ::protobuf::EnumOrUnknown::new(log::Level::INFO);
+        self.output = ::protobuf::EnumOrUnknown::new(log::Output::CONSOLE);
+        self.output_file.clear();
+        self.format = ::protobuf::EnumOrUnknown::new(log::Format::FULL);
+        self.key_log_file.clear();
+        self.access_log.clear();
+        self.access_log_format.clear();
+        self.access_log_start = false;
+        self.max_size_mb = 0;
+        self.max_backups = 0;
+        self.max_age_days = 0;
+        self.special_fields.clear();
+    }
 
     fn default_instance() -> &'static Log {
         static instance: Log = Log {
@@ -363,6 +847,13 @@ impl ::protobuf::Message for Log {
             output: ::protobuf::EnumOrUnknown::from_i32(0),
             output_file: ::std::string::String::new(),
             format: ::protobuf::EnumOrUnknown::from_i32(0),
+            key_log_file: ::std::string::String::new(),
+            access_log: ::std::string::String::new(),
+            access_log_format: ::std::string::String::new(),
+            access_log_start: false,
+            max_size_mb: 0,
+            max_backups: 0,
+            max_age_days: 0,
             special_fields: ::protobuf::SpecialFields::new(),
         };
         &instance
@@ -884,6 +1375,10 @@ pub struct SocksInboundSettings {
     pub username: ::std::string::String,
     // @@protoc_insertion_point(field:SocksInboundSettings.password)
     pub password: ::std::string::String,
+    // @@protoc_insertion_point(field:SocksInboundSettings.users)
+    pub users: ::std::vec::Vec<socks_inbound_settings::User>,
+    // @@protoc_insertion_point(field:SocksInboundSettings.socks4)
+    pub socks4: ::std::option::Option<bool>,
     // special fields
     // @@protoc_insertion_point(special_field:SocksInboundSettings.special_fields)
     pub special_fields: ::protobuf::SpecialFields,
@@ -917,6 +1412,12 @@ impl ::protobuf::Message for SocksInboundSettings {
                 18 => {
                     self.password = is.read_string()?;
                 },
+                26 => {
+                    self.users.push(is.read_message()?);
+                },
+                32 => {
+                    self.socks4 = ::std::option::Option::Some(is.read_bool()?);
+                },
                 tag => {
                     ::protobuf::rt::read_unknown_or_skip_group(tag, is, self.special_fields.mut_unknown_fields())?;
                 },
@@ -935,6 +1436,13 @@ impl ::protobuf::Message for SocksInboundSettings {
         if !self.password.is_empty() {
             my_size += ::protobuf::rt::string_size(2, &self.password);
         }
+        for value in &self.users {
+            let len = value.compute_size();
+            my_size += 1 + ::protobuf::rt::compute_raw_varint64_size(len) + len;
+        };
+        if let Some(v) = self.socks4 {
+            my_size += 1 + 1;
+        }
         my_size += ::protobuf::rt::unknown_fields_size(self.special_fields.unknown_fields());
         self.special_fields.cached_size().set(my_size as u32);
         my_size
@@ -947,6 +1455,12 @@ impl ::protobuf::Message for SocksInboundSettings {
         if !self.password.is_empty() {
             os.write_string(2, &self.password)?;
         }
+        for v in &self.users {
+            ::protobuf::rt::write_message_field_with_cached_size(3, v, os)?;
+        };
+        if let Some(v) = self.socks4 {
+            os.write_bool(4, v)?;
+        }
         os.write_unknown_fields(self.special_fields.unknown_fields())?;
         ::std::result::Result::Ok(())
     }
@@ -966,6 +1480,8 @@ impl ::protobuf::Message for SocksInboundSettings {
     fn clear(&mut self) {
         self.username.clear();
         self.password.clear();
+        self.users.clear();
+        self.socks4 = ::std::option::Option::None;
         self.special_fields.clear();
     }
 
@@ -973,39 +1489,145 @@ impl ::protobuf::Message for SocksInboundSettings {
         static instance: SocksInboundSettings = SocksInboundSettings {
             username: ::std::string::String::new(),
             password: ::std::string::String::new(),
+            users: ::std::vec::Vec::new(),
+            socks4: ::std::option::Option::None,
             special_fields: ::protobuf::SpecialFields::new(),
         };
         &instance
     }
 }
 
-// @@protoc_insertion_point(message:ShadowsocksInboundSettings)
-#[derive(PartialEq,Clone,Default,Debug)]
-pub struct ShadowsocksInboundSettings {
-    // message fields
-    // @@protoc_insertion_point(field:ShadowsocksInboundSettings.method)
-    pub method: ::std::string::String,
-    // @@protoc_insertion_point(field:ShadowsocksInboundSettings.password)
-    pub password: ::std::string::String,
-    // special fields
-    // @@protoc_insertion_point(special_field:ShadowsocksInboundSettings.special_fields)
-    pub special_fields: ::protobuf::SpecialFields,
-}
-
-impl<'a> ::std::default::Default for &'a ShadowsocksInboundSettings {
-    fn default() -> &'a ShadowsocksInboundSettings {
-        <ShadowsocksInboundSettings as ::protobuf::Message>::default_instance()
+/// Nested message and enums of message `SocksInboundSettings`
+pub mod socks_inbound_settings {
+    // @@protoc_insertion_point(message:SocksInboundSettings.User)
+    #[derive(PartialEq,Clone,Default,Debug)]
+    pub struct User {
+        // message fields
+        // @@protoc_insertion_point(field:SocksInboundSettings.User.name)
+        pub name: ::std::string::String,
+        // @@protoc_insertion_point(field:SocksInboundSettings.User.password)
+        pub password: ::std::string::String,
+        // special fields
+        // @@protoc_insertion_point(special_field:SocksInboundSettings.User.special_fields)
+        pub special_fields: ::protobuf::SpecialFields,
     }
-}
 
-impl ShadowsocksInboundSettings {
-    pub fn new() -> ShadowsocksInboundSettings {
-        ::std::default::Default::default()
+    impl<'a> ::std::default::Default for &'a User {
+        fn default() -> &'a User {
+            <User as ::protobuf::Message>::default_instance()
+        }
     }
-}
 
-impl ::protobuf::Message for ShadowsocksInboundSettings {
-    const NAME: &'static str = "ShadowsocksInboundSettings";
+    impl User {
+        pub fn new() -> User {
+            ::std::default::Default::default()
+        }
+    }
+
+    impl ::protobuf::Message for User {
+        const NAME: &'static str = "User";
+
+        fn is_initialized(&self) -> bool {
+            true
+        }
+
+        fn merge_from(&mut self, is: &mut ::protobuf::CodedInputStream<'_>) -> ::protobuf::Result<()> {
+            while let Some(tag) = is.read_raw_tag_or_eof()? {
+                match tag {
+                    10 => {
+                        self.name = is.read_string()?;
+                    },
+                    18 => {
+                        self.password = is.read_string()?;
+                    },
+                    tag => {
+                        ::protobuf::rt::read_unknown_or_skip_group(tag, is, self.special_fields.mut_unknown_fields())?;
+                    },
+                };
+            }
+            ::std::result::Result::Ok(())
+        }
+
+        // Compute sizes of nested messages
+        #[allow(unused_variables)]
+        fn compute_size(&self) -> u64 {
+            let mut my_size = 0;
+            if !self.name.is_empty() {
+                my_size += ::protobuf::rt::string_size(1, &self.name);
+            }
+            if !self.password.is_empty() {
+                my_size += ::protobuf::rt::string_size(2, &self.password);
+            }
+            my_size += ::protobuf::rt::unknown_fields_size(self.special_fields.unknown_fields());
+            self.special_fields.cached_size().set(my_size as u32);
+            my_size
+        }
+
+        fn write_to_with_cached_sizes(&self, os: &mut ::protobuf::CodedOutputStream<'_>) -> ::protobuf::Result<()> {
+            if !self.name.is_empty() {
+                os.write_string(1, &self.name)?;
+            }
+            if !self.password.is_empty() {
+                os.write_string(2, &self.password)?;
+            }
+            os.write_unknown_fields(self.special_fields.unknown_fields())?;
+            ::std::result::Result::Ok(())
+        }
+
+        fn special_fields(&self) -> &::protobuf::SpecialFields {
+            &self.special_fields
+        }
+
+        fn mut_special_fields(&mut self) -> &mut ::protobuf::SpecialFields {
+            &mut self.special_fields
+        }
+
+        fn new() -> User {
+            User::new()
+        }
+
+        fn clear(&mut self) {
+            self.name.clear();
+            self.password.clear();
+            self.special_fields.clear();
+        }
+
+        fn default_instance() -> &'static User {
+            static instance: User = User {
+                name: ::std::string::String::new(),
+                password: ::std::string::String::new(),
+                special_fields: ::protobuf::SpecialFields::new(),
+            };
+            &instance
+        }
+    }
+}
+
+// @@protoc_insertion_point(message:HttpInboundSettings)
+#[derive(PartialEq,Clone,Default,Debug)]
+pub struct HttpInboundSettings {
+    // message fields
+    // @@protoc_insertion_point(field:HttpInboundSettings.users)
+    pub users: ::std::vec::Vec<http_inbound_settings::User>,
+    // special fields
+    // @@protoc_insertion_point(special_field:HttpInboundSettings.special_fields)
+    pub special_fields: ::protobuf::SpecialFields,
+}
+
+impl<'a> ::std::default::Default for &'a HttpInboundSettings {
+    fn default() -> &'a HttpInboundSettings {
+        <HttpInboundSettings as ::protobuf::Message>::default_instance()
+    }
+}
+
+impl HttpInboundSettings {
+    pub fn new() -> HttpInboundSettings {
+        ::std::default::Default::default()
+    }
+}
+
+impl ::protobuf::Message for HttpInboundSettings {
+    const NAME: &'static str = "HttpInboundSettings";
 
     fn is_initialized(&self) -> bool {
         true
@@ -1015,10 +1637,7 @@ impl ::protobuf::Message for ShadowsocksInboundSettings {
         while let Some(tag) = is.read_raw_tag_or_eof()? {
             match tag {
                 10 => {
-                    self.method = is.read_string()?;
-                },
-                18 => {
-                    self.password = is.read_string()?;
+                    self.users.push(is.read_message()?);
                 },
                 tag => {
                     ::protobuf::rt::read_unknown_or_skip_group(tag, is, self.special_fields.mut_unknown_fields())?;
@@ -1032,24 +1651,19 @@ impl ::protobuf::Message for ShadowsocksInboundSettings {
     #[allow(unused_variables)]
     fn compute_size(&self) -> u64 {
         let mut my_size = 0;
-        if !self.method.is_empty() {
-            my_size += ::protobuf::rt::string_size(1, &self.method);
-        }
-        if !self.password.is_empty() {
-            my_size += ::protobuf::rt::string_size(2, &self.password);
-        }
+        for value in &self.users {
+            let len = value.compute_size();
+            my_size += 1 + ::protobuf::rt::compute_raw_varint64_size(len) + len;
+        };
         my_size += ::protobuf::rt::unknown_fields_size(self.special_fields.unknown_fields());
         self.special_fields.cached_size().set(my_size as u32);
         my_size
     }
 
     fn write_to_with_cached_sizes(&self, os: &mut ::protobuf::CodedOutputStream<'_>) -> ::protobuf::Result<()> {
-        if !self.method.is_empty() {
-            os.write_string(1, &self.method)?;
-        }
-        if !self.password.is_empty() {
-            os.write_string(2, &self.password)?;
-        }
+        for v in &self.users {
+            ::protobuf::rt::write_message_field_with_cached_size(1, v, os)?;
+        };
         os.write_unknown_fields(self.special_fields.unknown_fields())?;
         ::std::result::Result::Ok(())
     }
@@ -1062,51 +1676,161 @@ impl ::protobuf::Message for ShadowsocksInboundSettings {
         &mut self.special_fields
     }
 
-    fn new() -> ShadowsocksInboundSettings {
-        ShadowsocksInboundSettings::new()
+    fn new() -> HttpInboundSettings {
+        HttpInboundSettings::new()
     }
 
     fn clear(&mut self) {
-        self.method.clear();
-        self.password.clear();
+        self.users.clear();
         self.special_fields.clear();
     }
 
-    fn default_instance() -> &'static ShadowsocksInboundSettings {
-        static instance: ShadowsocksInboundSettings = ShadowsocksInboundSettings {
-            method: ::std::string::String::new(),
-            password: ::std::string::String::new(),
+    fn default_instance() -> &'static HttpInboundSettings {
+        static instance: HttpInboundSettings = HttpInboundSettings {
+            users: ::std::vec::Vec::new(),
             special_fields: ::protobuf::SpecialFields::new(),
         };
         &instance
     }
 }
 
-// @@protoc_insertion_point(message:TrojanInboundSettings)
+/// Nested message and enums of message `HttpInboundSettings`
+pub mod http_inbound_settings {
+    // @@protoc_insertion_point(message:HttpInboundSettings.User)
+    #[derive(PartialEq,Clone,Default,Debug)]
+    pub struct User {
+        // message fields
+        // @@protoc_insertion_point(field:HttpInboundSettings.User.name)
+        pub name: ::std::string::String,
+        // @@protoc_insertion_point(field:HttpInboundSettings.User.password)
+        pub password: ::std::string::String,
+        // special fields
+        // @@protoc_insertion_point(special_field:HttpInboundSettings.User.special_fields)
+        pub special_fields: ::protobuf::SpecialFields,
+    }
+
+    impl<'a> ::std::default::Default for &'a User {
+        fn default() -> &'a User {
+            <User as ::protobuf::Message>::default_instance()
+        }
+    }
+
+    impl User {
+        pub fn new() -> User {
+            ::std::default::Default::default()
+        }
+    }
+
+    impl ::protobuf::Message for User {
+        const NAME: &'static str = "User";
+
+        fn is_initialized(&self) -> bool {
+            true
+        }
+
+        fn merge_from(&mut self, is: &mut ::protobuf::CodedInputStream<'_>) -> ::protobuf::Result<()> {
+            while let Some(tag) = is.read_raw_tag_or_eof()? {
+                match tag {
+                    10 => {
+                        self.name = is.read_string()?;
+                    },
+                    18 => {
+                        self.password = is.read_string()?;
+                    },
+                    tag => {
+                        ::protobuf::rt::read_unknown_or_skip_group(tag, is, self.special_fields.mut_unknown_fields())?;
+                    },
+                };
+            }
+            ::std::result::Result::Ok(())
+        }
+
+        // Compute sizes of nested messages
+        #[allow(unused_variables)]
+        fn compute_size(&self) -> u64 {
+            let mut my_size = 0;
+            if !self.name.is_empty() {
+                my_size += ::protobuf::rt::string_size(1, &self.name);
+            }
+            if !self.password.is_empty() {
+                my_size += ::protobuf::rt::string_size(2, &self.password);
+            }
+            my_size += ::protobuf::rt::unknown_fields_size(self.special_fields.unknown_fields());
+            self.special_fields.cached_size().set(my_size as u32);
+            my_size
+        }
+
+        fn write_to_with_cached_sizes(&self, os: &mut ::protobuf::CodedOutputStream<'_>) -> ::protobuf::Result<()> {
+            if !self.name.is_empty() {
+                os.write_string(1, &self.name)?;
+            }
+            if !self.password.is_empty() {
+                os.write_string(2, &self.password)?;
+            }
+            os.write_unknown_fields(self.special_fields.unknown_fields())?;
+            ::std::result::Result::Ok(())
+        }
+
+        fn special_fields(&self) -> &::protobuf::SpecialFields {
+            &self.special_fields
+        }
+
+        fn mut_special_fields(&mut self) -> &mut ::protobuf::SpecialFields {
+            &mut self.special_fields
+        }
+
+        fn new() -> User {
+            User::new()
+        }
+
+        fn clear(&mut self) {
+            self.name.clear();
+            self.password.clear();
+            self.special_fields.clear();
+        }
+
+        fn default_instance() -> &'static User {
+            static instance: User = User {
+                name: ::std::string::String::new(),
+                password: ::std::string::String::new(),
+                special_fields: ::protobuf::SpecialFields::new(),
+            };
+            &instance
+        }
+    }
+}
+
+// @@protoc_insertion_point(message:DnsInboundSettings)
 #[derive(PartialEq,Clone,Default,Debug)]
-pub struct TrojanInboundSettings {
+pub struct DnsInboundSettings {
     // message fields
-    // @@protoc_insertion_point(field:TrojanInboundSettings.passwords)
-    pub passwords: ::std::vec::Vec<::std::string::String>,
+    // @@protoc_insertion_point(field:DnsInboundSettings.fake_dns_exclude)
+    pub fake_dns_exclude: ::std::vec::Vec<::std::string::String>,
+    // @@protoc_insertion_point(field:DnsInboundSettings.fake_dns_include)
+    pub fake_dns_include: ::std::vec::Vec<::std::string::String>,
+    // @@protoc_insertion_point(field:DnsInboundSettings.ttl)
+    pub ttl: u32,
+    // @@protoc_insertion_point(field:DnsInboundSettings.blocked_response)
+    pub blocked_response: ::std::string::String,
     // special fields
-    // @@protoc_insertion_point(special_field:TrojanInboundSettings.special_fields)
+    // @@protoc_insertion_point(special_field:DnsInboundSettings.special_fields)
     pub special_fields: ::protobuf::SpecialFields,
 }
 
-impl<'a> ::std::default::Default for &'a TrojanInboundSettings {
-    fn default() -> &'a TrojanInboundSettings {
-        <TrojanInboundSettings as ::protobuf::Message>::default_instance()
+impl<'a> ::std::default::Default for &'a DnsInboundSettings {
+    fn default() -> &'a DnsInboundSettings {
+        <DnsInboundSettings as ::protobuf::Message>::default_instance()
     }
 }
 
-impl TrojanInboundSettings {
-    pub fn new() -> TrojanInboundSettings {
+impl DnsInboundSettings {
+    pub fn new() -> DnsInboundSettings {
         ::std::default::Default::default()
     }
 }
 
-impl ::protobuf::Message for TrojanInboundSettings {
-    const NAME: &'static str = "TrojanInboundSettings";
+impl ::protobuf::Message for DnsInboundSettings {
+    const NAME: &'static str = "DnsInboundSettings";
 
     fn is_initialized(&self) -> bool {
         true
@@ -1116,7 +1840,16 @@ impl ::protobuf::Message for TrojanInboundSettings {
         while let Some(tag) = is.read_raw_tag_or_eof()? {
             match tag {
                 10 => {
-                    self.passwords.push(is.read_string()?);
+                    self.fake_dns_exclude.push(is.read_string()?);
+                },
+                18 => {
+                    self.fake_dns_include.push(is.read_string()?);
+                },
+                24 => {
+                    self.ttl = is.read_uint32()?;
+                },
+                34 => {
+                    self.blocked_response = is.read_string()?;
                 },
                 tag => {
                     ::protobuf::rt::read_unknown_or_skip_group(tag, is, self.special_fields.mut_unknown_fields())?;
@@ -1130,18 +1863,36 @@ impl ::protobuf::Message for TrojanInboundSettings {
     #[allow(unused_variables)]
     fn compute_size(&self) -> u64 {
         let mut my_size = 0;
-        for value in &self.passwords {
+        for value in &self.fake_dns_exclude {
             my_size += ::protobuf::rt::string_size(1, &value);
         };
+        for value in &self.fake_dns_include {
+            my_size += ::protobuf::rt::string_size(2, &value);
+        };
+        if self.ttl != 0 {
+            my_size += ::protobuf::rt::uint32_size(3, self.ttl);
+        }
+        if !self.blocked_response.is_empty() {
+            my_size += ::protobuf::rt::string_size(4, &self.blocked_response);
+        }
         my_size += ::protobuf::rt::unknown_fields_size(self.special_fields.unknown_fields());
         self.special_fields.cached_size().set(my_size as u32);
         my_size
     }
 
     fn write_to_with_cached_sizes(&self, os: &mut ::protobuf::CodedOutputStream<'_>) -> ::protobuf::Result<()> {
-        for v in &self.passwords {
+        for v in &self.fake_dns_exclude {
             os.write_string(1, &v)?;
         };
+        for v in &self.fake_dns_include {
+            os.write_string(2, &v)?;
+        };
+        if self.ttl != 0 {
+            os.write_uint32(3, self.ttl)?;
+        }
+        if !self.blocked_response.is_empty() {
+            os.write_string(4, &self.blocked_response)?;
+        }
         os.write_unknown_fields(self.special_fields.unknown_fields())?;
         ::std::result::Result::Ok(())
     }
@@ -1154,49 +1905,57 @@ impl ::protobuf::Message for TrojanInboundSettings {
         &mut self.special_fields
     }
 
-    fn new() -> TrojanInboundSettings {
-        TrojanInboundSettings::new()
+    fn new() -> DnsInboundSettings {
+        DnsInboundSettings::new()
     }
 
     fn clear(&mut self) {
-        self.passwords.clear();
+        self.fake_dns_exclude.clear();
+        self.fake_dns_include.clear();
+        self.ttl = 0;
+        self.blocked_response.clear();
         self.special_fields.clear();
     }
 
-    fn default_instance() -> &'static TrojanInboundSettings {
-        static instance: TrojanInboundSettings = TrojanInboundSettings {
-            passwords: ::std::vec::Vec::new(),
+    fn default_instance() -> &'static DnsInboundSettings {
+        static instance: DnsInboundSettings = DnsInboundSettings {
+            fake_dns_exclude: ::std::vec::Vec::new(),
+            fake_dns_include: ::std::vec::Vec::new(),
+            ttl: 0,
+            blocked_response: ::std::string::String::new(),
             special_fields: ::protobuf::SpecialFields::new(),
         };
         &instance
     }
 }
 
-// @@protoc_insertion_point(message:WebSocketInboundSettings)
+// @@protoc_insertion_point(message:TunnelInboundSettings)
 #[derive(PartialEq,Clone,Default,Debug)]
-pub struct WebSocketInboundSettings {
+pub struct TunnelInboundSettings {
     // message fields
-    // @@protoc_insertion_point(field:WebSocketInboundSettings.path)
-    pub path: ::std::string::String,
+    // @@protoc_insertion_point(field:TunnelInboundSettings.destination)
+    pub destination: ::std::string::String,
+    // @@protoc_insertion_point(field:TunnelInboundSettings.network)
+    pub network: ::std::string::String,
     // special fields
-    // @@protoc_insertion_point(special_field:WebSocketInboundSettings.special_fields)
+    // @@protoc_insertion_point(special_field:TunnelInboundSettings.special_fields)
     pub special_fields: ::protobuf::SpecialFields,
 }
 
-impl<'a> ::std::default::Default for &'a WebSocketInboundSettings {
-    fn default() -> &'a WebSocketInboundSettings {
-        <WebSocketInboundSettings as ::protobuf::Message>::default_instance()
+impl<'a> ::std::default::Default for &'a TunnelInboundSettings {
+    fn default() -> &'a TunnelInboundSettings {
+        <TunnelInboundSettings as ::protobuf::Message>::default_instance()
     }
 }
 
-impl WebSocketInboundSettings {
-    pub fn new() -> WebSocketInboundSettings {
+impl TunnelInboundSettings {
+    pub fn new() -> TunnelInboundSettings {
         ::std::default::Default::default()
     }
 }
 
-impl ::protobuf::Message for WebSocketInboundSettings {
-    const NAME: &'static str = "WebSocketInboundSettings";
+impl ::protobuf::Message for TunnelInboundSettings {
+    const NAME: &'static str = "TunnelInboundSettings";
 
     fn is_initialized(&self) -> bool {
         true
@@ -1206,7 +1965,10 @@ impl ::protobuf::Message for WebSocketInboundSettings {
         while let Some(tag) = is.read_raw_tag_or_eof()? {
             match tag {
                 10 => {
-                    self.path = is.read_string()?;
+                    self.destination = is.read_string()?;
+                },
+                18 => {
+                    self.network = is.read_string()?;
                 },
                 tag => {
                     ::protobuf::rt::read_unknown_or_skip_group(tag, is, self.special_fields.mut_unknown_fields())?;
@@ -1220,8 +1982,11 @@ impl ::protobuf::Message for WebSocketInboundSettings {
     #[allow(unused_variables)]
     fn compute_size(&self) -> u64 {
         let mut my_size = 0;
-        if !self.path.is_empty() {
-            my_size += ::protobuf::rt::string_size(1, &self.path);
+        if !self.destination.is_empty() {
+            my_size += ::protobuf::rt::string_size(1, &self.destination);
+        }
+        if !self.network.is_empty() {
+            my_size += ::protobuf::rt::string_size(2, &self.network);
         }
         my_size += ::protobuf::rt::unknown_fields_size(self.special_fields.unknown_fields());
         self.special_fields.cached_size().set(my_size as u32);
@@ -1229,8 +1994,11 @@ impl ::protobuf::Message for WebSocketInboundSettings {
     }
 
     fn write_to_with_cached_sizes(&self, os: &mut ::protobuf::CodedOutputStream<'_>) -> ::protobuf::Result<()> {
-        if !self.path.is_empty() {
-            os.write_string(1, &self.path)?;
+        if !self.destination.is_empty() {
+            os.write_string(1, &self.destination)?;
+        }
+        if !self.network.is_empty() {
+            os.write_string(2, &self.network)?;
         }
         os.write_unknown_fields(self.special_fields.unknown_fields())?;
         ::std::result::Result::Ok(())
@@ -1244,49 +2012,53 @@ impl ::protobuf::Message for WebSocketInboundSettings {
         &mut self.special_fields
     }
 
-    fn new() -> WebSocketInboundSettings {
-        WebSocketInboundSettings::new()
+    fn new() -> TunnelInboundSettings {
+        TunnelInboundSettings::new()
     }
 
     fn clear(&mut self) {
-        self.path.clear();
+        self.destination.clear();
+        self.network.clear();
         self.special_fields.clear();
     }
 
-    fn default_instance() -> &'static WebSocketInboundSettings {
-        static instance: WebSocketInboundSettings = WebSocketInboundSettings {
-            path: ::std::string::String::new(),
+    fn default_instance() -> &'static TunnelInboundSettings {
+        static instance: TunnelInboundSettings = TunnelInboundSettings {
+            destination: ::std::string::String::new(),
+            network: ::std::string::String::new(),
             special_fields: ::protobuf::SpecialFields::new(),
         };
         &instance
     }
 }
 
-// @@protoc_insertion_point(message:AMuxInboundSettings)
+// @@protoc_insertion_point(message:ShadowsocksInboundSettings)
 #[derive(PartialEq,Clone,Default,Debug)]
-pub struct AMuxInboundSettings {
+pub struct ShadowsocksInboundSettings {
     // message fields
-    // @@protoc_insertion_point(field:AMuxInboundSettings.actors)
-    pub actors: ::std::vec::Vec<::std::string::String>,
+    // @@protoc_insertion_point(field:ShadowsocksInboundSettings.method)
+    pub method: ::std::string::String,
+    // @@protoc_insertion_point(field:ShadowsocksInboundSettings.password)
+    pub password: ::std::string::String,
     // special fields
-    // @@protoc_insertion_point(special_field:AMuxInboundSettings.special_fields)
+    // @@protoc_insertion_point(special_field:ShadowsocksInboundSettings.special_fields)
     pub special_fields: ::protobuf::SpecialFields,
 }
 
-impl<'a> ::std::default::Default for &'a AMuxInboundSettings {
-    fn default() -> &'a AMuxInboundSettings {
-        <AMuxInboundSettings as ::protobuf::Message>::default_instance()
+impl<'a> ::std::default::Default for &'a ShadowsocksInboundSettings {
+    fn default() -> &'a ShadowsocksInboundSettings {
+        <ShadowsocksInboundSettings as ::protobuf::Message>::default_instance()
     }
 }
 
-impl AMuxInboundSettings {
-    pub fn new() -> AMuxInboundSettings {
+impl ShadowsocksInboundSettings {
+    pub fn new() -> ShadowsocksInboundSettings {
         ::std::default::Default::default()
     }
 }
 
-impl ::protobuf::Message for AMuxInboundSettings {
-    const NAME: &'static str = "AMuxInboundSettings";
+impl ::protobuf::Message for ShadowsocksInboundSettings {
+    const NAME: &'static str = "ShadowsocksInboundSettings";
 
     fn is_initialized(&self) -> bool {
         true
@@ -1296,7 +2068,10 @@ impl ::protobuf::Message for AMuxInboundSettings {
         while let Some(tag) = is.read_raw_tag_or_eof()? {
             match tag {
                 10 => {
-                    self.actors.push(is.read_string()?);
+                    self.method = is.read_string()?;
+                },
+                18 => {
+                    self.password = is.read_string()?;
                 },
                 tag => {
                     ::protobuf::rt::read_unknown_or_skip_group(tag, is, self.special_fields.mut_unknown_fields())?;
@@ -1310,18 +2085,24 @@ impl ::protobuf::Message for AMuxInboundSettings {
     #[allow(unused_variables)]
     fn compute_size(&self) -> u64 {
         let mut my_size = 0;
-        for value in &self.actors {
-            my_size += ::protobuf::rt::string_size(1, &value);
-        };
+        if !self.method.is_empty() {
+            my_size += ::protobuf::rt::string_size(1, &self.method);
+        }
+        if !self.password.is_empty() {
+            my_size += ::protobuf::rt::string_size(2, &self.password);
+        }
         my_size += ::protobuf::rt::unknown_fields_size(self.special_fields.unknown_fields());
         self.special_fields.cached_size().set(my_size as u32);
         my_size
     }
 
     fn write_to_with_cached_sizes(&self, os: &mut ::protobuf::CodedOutputStream<'_>) -> ::protobuf::Result<()> {
-        for v in &self.actors {
-            os.write_string(1, &v)?;
-        };
+        if !self.method.is_empty() {
+            os.write_string(1, &self.method)?;
+        }
+        if !self.password.is_empty() {
+            os.write_string(2, &self.password)?;
+        }
         os.write_unknown_fields(self.special_fields.unknown_fields())?;
         ::std::result::Result::Ok(())
     }
@@ -1334,55 +2115,53 @@ impl ::protobuf::Message for AMuxInboundSettings {
         &mut self.special_fields
     }
 
-    fn new() -> AMuxInboundSettings {
-        AMuxInboundSettings::new()
+    fn new() -> ShadowsocksInboundSettings {
+        ShadowsocksInboundSettings::new()
     }
 
     fn clear(&mut self) {
-        self.actors.clear();
+        self.method.clear();
+        self.password.clear();
         self.special_fields.clear();
     }
 
-    fn default_instance() -> &'static AMuxInboundSettings {
-        static instance: AMuxInboundSettings = AMuxInboundSettings {
-            actors: ::std::vec::Vec::new(),
+    fn default_instance() -> &'static ShadowsocksInboundSettings {
+        static instance: ShadowsocksInboundSettings = ShadowsocksInboundSettings {
+            method: ::std::string::String::new(),
+            password: ::std::string::String::new(),
             special_fields: ::protobuf::SpecialFields::new(),
         };
         &instance
     }
 }
 
-// @@protoc_insertion_point(message:NfInboundSettings)
+// @@protoc_insertion_point(message:TrojanInboundSettings)
 #[derive(PartialEq,Clone,Default,Debug)]
-pub struct NfInboundSettings {
+pub struct TrojanInboundSettings {
     // message fields
-    // @@protoc_insertion_point(field:NfInboundSettings.driver_name)
-    pub driver_name: ::std::string::String,
-    // @@protoc_insertion_point(field:NfInboundSettings.nfapi)
-    pub nfapi: ::std::string::String,
-    // @@protoc_insertion_point(field:NfInboundSettings.fake_dns_exclude)
-    pub fake_dns_exclude: ::std::vec::Vec<::std::string::String>,
-    // @@protoc_insertion_point(field:NfInboundSettings.fake_dns_include)
-    pub fake_dns_include: ::std::vec::Vec<::std::string::String>,
+    // @@protoc_insertion_point(field:TrojanInboundSettings.passwords)
+    pub passwords: ::std::vec::Vec<::std::string::String>,
+    // @@protoc_insertion_point(field:TrojanInboundSettings.users)
+    pub users: ::std::vec::Vec<trojan_inbound_settings::User>,
     // special fields
-    // @@protoc_insertion_point(special_field:NfInboundSettings.special_fields)
+    // @@protoc_insertion_point(special_field:TrojanInboundSettings.special_fields)
     pub special_fields: ::protobuf::SpecialFields,
 }
 
-impl<'a> ::std::default::Default for &'a NfInboundSettings {
-    fn default() -> &'a NfInboundSettings {
-        <NfInboundSettings as ::protobuf::Message>::default_instance()
+impl<'a> ::std::default::Default for &'a TrojanInboundSettings {
+    fn default() -> &'a TrojanInboundSettings {
+        <TrojanInboundSettings as ::protobuf::Message>::default_instance()
     }
 }
 
-impl NfInboundSettings {
-    pub fn new() -> NfInboundSettings {
+impl TrojanInboundSettings {
+    pub fn new() -> TrojanInboundSettings {
         ::std::default::Default::default()
     }
 }
 
-impl ::protobuf::Message for NfInboundSettings {
-    const NAME: &'static str = "NfInboundSettings";
+impl ::protobuf::Message for TrojanInboundSettings {
+    const NAME: &'static str = "TrojanInboundSettings";
 
     fn is_initialized(&self) -> bool {
         true
@@ -1392,16 +2171,10 @@ impl ::protobuf::Message for NfInboundSettings {
         while let Some(tag) = is.read_raw_tag_or_eof()? {
             match tag {
                 10 => {
-                    self.driver_name = is.read_string()?;
+                    self.passwords.push(is.read_string()?);
                 },
                 18 => {
-                    self.nfapi = is.read_string()?;
-                },
-                26 => {
-                    self.fake_dns_exclude.push(is.read_string()?);
-                },
-                34 => {
-                    self.fake_dns_include.push(is.read_string()?);
+                    self.users.push(is.read_message()?);
                 },
                 tag => {
                     ::protobuf::rt::read_unknown_or_skip_group(tag, is, self.special_fields.mut_unknown_fields())?;
@@ -1415,17 +2188,12 @@ impl ::protobuf::Message for NfInboundSettings {
     #[allow(unused_variables)]
     fn compute_size(&self) -> u64 {
         let mut my_size = 0;
-        if !self.driver_name.is_empty() {
-            my_size += ::protobuf::rt::string_size(1, &self.driver_name);
-        }
-        if !self.nfapi.is_empty() {
-            my_size += ::protobuf::rt::string_size(2, &self.nfapi);
-        }
-        for value in &self.fake_dns_exclude {
-            my_size += ::protobuf::rt::string_size(3, &value);
+        for value in &self.passwords {
+            my_size += ::protobuf::rt::string_size(1, &value);
         };
-        for value in &self.fake_dns_include {
-            my_size += ::protobuf::rt::string_size(4, &value);
+        for value in &self.users {
+            let len = value.compute_size();
+            my_size += 1 + ::protobuf::rt::compute_raw_varint64_size(len) + len;
         };
         my_size += ::protobuf::rt::unknown_fields_size(self.special_fields.unknown_fields());
         self.special_fields.cached_size().set(my_size as u32);
@@ -1433,17 +2201,11 @@ impl ::protobuf::Message for NfInboundSettings {
     }
 
     fn write_to_with_cached_sizes(&self, os: &mut ::protobuf::CodedOutputStream<'_>) -> ::protobuf::Result<()> {
-        if !self.driver_name.is_empty() {
-            os.write_string(1, &self.driver_name)?;
-        }
-        if !self.nfapi.is_empty() {
-            os.write_string(2, &self.nfapi)?;
-        }
-        for v in &self.fake_dns_exclude {
-            os.write_string(3, &v)?;
+        for v in &self.passwords {
+            os.write_string(1, &v)?;
         };
-        for v in &self.fake_dns_include {
-            os.write_string(4, &v)?;
+        for v in &self.users {
+            ::protobuf::rt::write_message_field_with_cached_size(2, v, os)?;
         };
         os.write_unknown_fields(self.special_fields.unknown_fields())?;
         ::std::result::Result::Ok(())
@@ -1457,59 +2219,159 @@ impl ::protobuf::Message for NfInboundSettings {
         &mut self.special_fields
     }
 
-    fn new() -> NfInboundSettings {
-        NfInboundSettings::new()
+    fn new() -> TrojanInboundSettings {
+        TrojanInboundSettings::new()
     }
 
     fn clear(&mut self) {
-        self.driver_name.clear();
-        self.nfapi.clear();
-        self.fake_dns_exclude.clear();
-        self.fake_dns_include.clear();
+        self.passwords.clear();
+        self.users.clear();
         self.special_fields.clear();
     }
 
-    fn default_instance() -> &'static NfInboundSettings {
-        static instance: NfInboundSettings = NfInboundSettings {
-            driver_name: ::std::string::String::new(),
-            nfapi: ::std::string::String::new(),
-            fake_dns_exclude: ::std::vec::Vec::new(),
-            fake_dns_include: ::std::vec::Vec::new(),
+    fn default_instance() -> &'static TrojanInboundSettings {
+        static instance: TrojanInboundSettings = TrojanInboundSettings {
+            passwords: ::std::vec::Vec::new(),
+            users: ::std::vec::Vec::new(),
             special_fields: ::protobuf::SpecialFields::new(),
         };
         &instance
     }
 }
 
-// @@protoc_insertion_point(message:QuicInboundSettings)
+/// Nested message and enums of message `TrojanInboundSettings`
+pub mod trojan_inbound_settings {
+    // @@protoc_insertion_point(message:TrojanInboundSettings.User)
+    #[derive(PartialEq,Clone,Default,Debug)]
+    pub struct User {
+        // message fields
+        // @@protoc_insertion_point(field:TrojanInboundSettings.User.name)
+        pub name: ::std::string::String,
+        // @@protoc_insertion_point(field:TrojanInboundSettings.User.password)
+        pub password: ::std::string::String,
+        // special fields
+        // @@protoc_insertion_point(special_field:TrojanInboundSettings.User.special_fields)
+        pub special_fields: ::protobuf::SpecialFields,
+    }
+
+    impl<'a> ::std::default::Default for &'a User {
+        fn default() -> &'a User {
+            <User as ::protobuf::Message>::default_instance()
+        }
+    }
+
+    impl User {
+        pub fn new() -> User {
+            ::std::default::Default::default()
+        }
+    }
+
+    impl ::protobuf::Message for User {
+        const NAME: &'static str = "User";
+
+        fn is_initialized(&self) -> bool {
+            true
+        }
+
+        fn merge_from(&mut self, is: &mut ::protobuf::CodedInputStream<'_>) -> ::protobuf::Result<()> {
+            while let Some(tag) = is.read_raw_tag_or_eof()? {
+                match tag {
+                    10 => {
+                        self.name = is.read_string()?;
+                    },
+                    18 => {
+                        self.password = is.read_string()?;
+                    },
+                    tag => {
+                        ::protobuf::rt::read_unknown_or_skip_group(tag, is, self.special_fields.mut_unknown_fields())?;
+                    },
+                };
+            }
+            ::std::result::Result::Ok(())
+        }
+
+        // Compute sizes of nested messages
+        #[allow(unused_variables)]
+        fn compute_size(&self) -> u64 {
+            let mut my_size = 0;
+            if !self.name.is_empty() {
+                my_size += ::protobuf::rt::string_size(1, &self.name);
+            }
+            if !self.password.is_empty() {
+                my_size += ::protobuf::rt::string_size(2, &self.password);
+            }
+            my_size += ::protobuf::rt::unknown_fields_size(self.special_fields.unknown_fields());
+            self.special_fields.cached_size().set(my_size as u32);
+            my_size
+        }
+
+        fn write_to_with_cached_sizes(&self, os: &mut ::protobuf::CodedOutputStream<'_>) -> ::protobuf::Result<()> {
+            if !self.name.is_empty() {
+                os.write_string(1, &self.name)?;
+            }
+            if !self.password.is_empty() {
+                os.write_string(2, &self.password)?;
+            }
+            os.write_unknown_fields(self.special_fields.unknown_fields())?;
+            ::std::result::Result::Ok(())
+        }
+
+        fn special_fields(&self) -> &::protobuf::SpecialFields {
+            &self.special_fields
+        }
+
+        fn mut_special_fields(&mut self) -> &mut ::protobuf::SpecialFields {
+            &mut self.special_fields
+        }
+
+        fn new() -> User {
+            User::new()
+        }
+
+        fn clear(&mut self) {
+            self.name.clear();
+            self.password.clear();
+            self.special_fields.clear();
+        }
+
+        fn default_instance() -> &'static User {
+            static instance: User = User {
+                name: ::std::string::String::new(),
+                password: ::std::string::String::new(),
+                special_fields: ::protobuf::SpecialFields::new(),
+            };
+            &instance
+        }
+    }
+}
+
+// @@protoc_insertion_point(message:VlessInboundSettings)
 #[derive(PartialEq,Clone,Default,Debug)]
-pub struct QuicInboundSettings {
+pub struct VlessInboundSettings {
     // message fields
-    // @@protoc_insertion_point(field:QuicInboundSettings.certificate)
-    pub certificate: ::std::string::String,
-    // @@protoc_insertion_point(field:QuicInboundSettings.certificate_key)
-    pub certificate_key: ::std::string::String,
-    // @@protoc_insertion_point(field:QuicInboundSettings.alpn)
-    pub alpn: ::std::vec::Vec<::std::string::String>,
+    // @@protoc_insertion_point(field:VlessInboundSettings.uuids)
+    pub uuids: ::std::vec::Vec<::std::string::String>,
+    // @@protoc_insertion_point(field:VlessInboundSettings.fallback)
+    pub fallback: ::std::string::String,
     // special fields
-    // @@protoc_insertion_point(special_field:QuicInboundSettings.special_fields)
+    // @@protoc_insertion_point(special_field:VlessInboundSettings.special_fields)
     pub special_fields: ::protobuf::SpecialFields,
 }
 
-impl<'a> ::std::default::Default for &'a QuicInboundSettings {
-    fn default() -> &'a QuicInboundSettings {
-        <QuicInboundSettings as ::protobuf::Message>::default_instance()
+impl<'a> ::std::default::Default for &'a VlessInboundSettings {
+    fn default() -> &'a VlessInboundSettings {
+        <VlessInboundSettings as ::protobuf::Message>::default_instance()
     }
 }
 
-impl QuicInboundSettings {
-    pub fn new() -> QuicInboundSettings {
+impl VlessInboundSettings {
+    pub fn new() -> VlessInboundSettings {
         ::std::default::Default::default()
     }
 }
 
-impl ::protobuf::Message for QuicInboundSettings {
-    const NAME: &'static str = "QuicInboundSettings";
+impl ::protobuf::Message for VlessInboundSettings {
+    const NAME: &'static str = "VlessInboundSettings";
 
     fn is_initialized(&self) -> bool {
         true
@@ -1519,13 +2381,10 @@ impl ::protobuf::Message for QuicInboundSettings {
         while let Some(tag) = is.read_raw_tag_or_eof()? {
             match tag {
                 10 => {
-                    self.certificate = is.read_string()?;
+                    self.uuids.push(is.read_string()?);
                 },
                 18 => {
-                    self.certificate_key = is.read_string()?;
-                },
-                26 => {
-                    self.alpn.push(is.read_string()?);
+                    self.fallback = is.read_string()?;
                 },
                 tag => {
                     ::protobuf::rt::read_unknown_or_skip_group(tag, is, self.special_fields.mut_unknown_fields())?;
@@ -1539,30 +2398,24 @@ impl ::protobuf::Message for QuicInboundSettings {
     #[allow(unused_variables)]
     fn compute_size(&self) -> u64 {
         let mut my_size = 0;
-        if !self.certificate.is_empty() {
-            my_size += ::protobuf::rt::string_size(1, &self.certificate);
-        }
-        if !self.certificate_key.is_empty() {
-            my_size += ::protobuf::rt::string_size(2, &self.certificate_key);
-        }
-        for value in &self.alpn {
-            my_size += ::protobuf::rt::string_size(3, &value);
+        for value in &self.uuids {
+            my_size += ::protobuf::rt::string_size(1, &value);
         };
+        if !self.fallback.is_empty() {
+            my_size += ::protobuf::rt::string_size(2, &self.fallback);
+        }
         my_size += ::protobuf::rt::unknown_fields_size(self.special_fields.unknown_fields());
         self.special_fields.cached_size().set(my_size as u32);
         my_size
     }
 
     fn write_to_with_cached_sizes(&self, os: &mut ::protobuf::CodedOutputStream<'_>) -> ::protobuf::Result<()> {
-        if !self.certificate.is_empty() {
-            os.write_string(1, &self.certificate)?;
+        for v in &self.uuids {
+            os.write_string(1, &v)?;
+        };
+        if !self.fallback.is_empty() {
+            os.write_string(2, &self.fallback)?;
         }
-        if !self.certificate_key.is_empty() {
-            os.write_string(2, &self.certificate_key)?;
-        }
-        for v in &self.alpn {
-            os.write_string(3, &v)?;
-        };
         os.write_unknown_fields(self.special_fields.unknown_fields())?;
         ::std::result::Result::Ok(())
     }
@@ -1575,59 +2428,61 @@ impl ::protobuf::Message for QuicInboundSettings {
         &mut self.special_fields
     }
 
-    fn new() -> QuicInboundSettings {
-        QuicInboundSettings::new()
+    fn new() -> VlessInboundSettings {
+        VlessInboundSettings::new()
     }
 
     fn clear(&mut self) {
-        self.certificate.clear();
-        self.certificate_key.clear();
-        self.alpn.clear();
+        self.uuids.clear();
+        self.fallback.clear();
         self.special_fields.clear();
     }
 
-    fn default_instance() -> &'static QuicInboundSettings {
-        static instance: QuicInboundSettings = QuicInboundSettings {
-            certificate: ::std::string::String::new(),
-            certificate_key: ::std::string::String::new(),
-            alpn: ::std::vec::Vec::new(),
+    fn default_instance() -> &'static VlessInboundSettings {
+        static instance: VlessInboundSettings = VlessInboundSettings {
+            uuids: ::std::vec::Vec::new(),
+            fallback: ::std::string::String::new(),
             special_fields: ::protobuf::SpecialFields::new(),
         };
         &instance
     }
 }
 
-// @@protoc_insertion_point(message:TlsInboundSettings)
+// @@protoc_insertion_point(message:WebSocketInboundSettings)
 #[derive(PartialEq,Clone,Default,Debug)]
-pub struct TlsInboundSettings {
+pub struct WebSocketInboundSettings {
     // message fields
-    // @@protoc_insertion_point(field:TlsInboundSettings.certificate)
-    pub certificate: ::std::string::String,
-    // @@protoc_insertion_point(field:TlsInboundSettings.certificate_key)
-    pub certificate_key: ::std::string::String,
-    // @@protoc_insertion_point(field:TlsInboundSettings.ech_config)
-    pub ech_config: ::std::string::String,
-    // @@protoc_insertion_point(field:TlsInboundSettings.ech_key)
-    pub ech_key: ::std::string::String,
+    // @@protoc_insertion_point(field:WebSocketInboundSettings.path)
+    pub path: ::std::string::String,
+    // @@protoc_insertion_point(field:WebSocketInboundSettings.header_name)
+    pub header_name: ::std::string::String,
+    // @@protoc_insertion_point(field:WebSocketInboundSettings.header_value)
+    pub header_value: ::std::string::String,
+    // @@protoc_insertion_point(field:WebSocketInboundSettings.fallback)
+    pub fallback: ::std::string::String,
+    // @@protoc_insertion_point(field:WebSocketInboundSettings.fallback_status)
+    pub fallback_status: u32,
+    // @@protoc_insertion_point(field:WebSocketInboundSettings.fallback_file)
+    pub fallback_file: ::std::string::String,
     // special fields
-    // @@protoc_insertion_point(special_field:TlsInboundSettings.special_fields)
+    // @@protoc_insertion_point(special_field:WebSocketInboundSettings.special_fields)
     pub special_fields: ::protobuf::SpecialFields,
 }
 
-impl<'a> ::std::default::Default for &'a TlsInboundSettings {
-    fn default() -> &'a TlsInboundSettings {
-        <TlsInboundSettings as ::protobuf::Message>::default_instance()
+impl<'a> ::std::default::Default for &'a WebSocketInboundSettings {
+    fn default() -> &'a WebSocketInboundSettings {
+        <WebSocketInboundSettings as ::protobuf::Message>::default_instance()
     }
 }
 
-impl TlsInboundSettings {
-    pub fn new() -> TlsInboundSettings {
+impl WebSocketInboundSettings {
+    pub fn new() -> WebSocketInboundSettings {
         ::std::default::Default::default()
     }
 }
 
-impl ::protobuf::Message for TlsInboundSettings {
-    const NAME: &'static str = "TlsInboundSettings";
+impl ::protobuf::Message for WebSocketInboundSettings {
+    const NAME: &'static str = "WebSocketInboundSettings";
 
     fn is_initialized(&self) -> bool {
         true
@@ -1637,16 +2492,22 @@ impl ::protobuf::Message for TlsInboundSettings {
         while let Some(tag) = is.read_raw_tag_or_eof()? {
             match tag {
                 10 => {
-                    self.certificate = is.read_string()?;
+                    self.path = is.read_string()?;
                 },
                 18 => {
-                    self.certificate_key = is.read_string()?;
+                    self.header_name = is.read_string()?;
                 },
                 26 => {
-                    self.ech_config = is.read_string()?;
+                    self.header_value = is.read_string()?;
                 },
                 34 => {
-                    self.ech_key = is.read_string()?;
+                    self.fallback = is.read_string()?;
+                },
+                40 => {
+                    self.fallback_status = is.read_uint32()?;
+                },
+                50 => {
+                    self.fallback_file = is.read_string()?;
                 },
                 tag => {
                     ::protobuf::rt::read_unknown_or_skip_group(tag, is, self.special_fields.mut_unknown_fields())?;
@@ -1660,17 +2521,23 @@ impl ::protobuf::Message for TlsInboundSettings {
     #[allow(unused_variables)]
     fn compute_size(&self) -> u64 {
         let mut my_size = 0;
-        if !self.certificate.is_empty() {
-            my_size += ::protobuf::rt::string_size(1, &self.certificate);
+        if !self.path.is_empty() {
+            my_size += ::protobuf::rt::string_size(1, &self.path);
         }
-        if !self.certificate_key.is_empty() {
-            my_size += ::protobuf::rt::string_size(2, &self.certificate_key);
+        if !self.header_name.is_empty() {
+            my_size += ::protobuf::rt::string_size(2, &self.header_name);
         }
-        if !self.ech_config.is_empty() {
-            my_size += ::protobuf::rt::string_size(3, &self.ech_config);
+        if !self.header_value.is_empty() {
+            my_size += ::protobuf::rt::string_size(3, &self.header_value);
         }
-        if !self.ech_key.is_empty() {
-            my_size += ::protobuf::rt::string_size(4, &self.ech_key);
+        if !self.fallback.is_empty() {
+            my_size += ::protobuf::rt::string_size(4, &self.fallback);
+        }
+        if self.fallback_status != 0 {
+            my_size += ::protobuf::rt::uint32_size(5, self.fallback_status);
+        }
+        if !self.fallback_file.is_empty() {
+            my_size += ::protobuf::rt::string_size(6, &self.fallback_file);
         }
         my_size += ::protobuf::rt::unknown_fields_size(self.special_fields.unknown_fields());
         self.special_fields.cached_size().set(my_size as u32);
@@ -1678,17 +2545,23 @@ impl ::protobuf::Message for TlsInboundSettings {
     }
 
     fn write_to_with_cached_sizes(&self, os: &mut ::protobuf::CodedOutputStream<'_>) -> ::protobuf::Result<()> {
-        if !self.certificate.is_empty() {
-            os.write_string(1, &self.certificate)?;
+        if !self.path.is_empty() {
+            os.write_string(1, &self.path)?;
         }
-        if !self.certificate_key.is_empty() {
-            os.write_string(2, &self.certificate_key)?;
+        if !self.header_name.is_empty() {
+            os.write_string(2, &self.header_name)?;
         }
-        if !self.ech_config.is_empty() {
-            os.write_string(3, &self.ech_config)?;
+        if !self.header_value.is_empty() {
+            os.write_string(3, &self.header_value)?;
         }
-        if !self.ech_key.is_empty() {
-            os.write_string(4, &self.ech_key)?;
+        if !self.fallback.is_empty() {
+            os.write_string(4, &self.fallback)?;
+        }
+        if self.fallback_status != 0 {
+            os.write_uint32(5, self.fallback_status)?;
+        }
+        if !self.fallback_file.is_empty() {
+            os.write_string(6, &self.fallback_file)?;
         }
         os.write_unknown_fields(self.special_fields.unknown_fields())?;
         ::std::result::Result::Ok(())
@@ -1702,55 +2575,59 @@ impl ::protobuf::Message for TlsInboundSettings {
         &mut self.special_fields
     }
 
-    fn new() -> TlsInboundSettings {
-        TlsInboundSettings::new()
+    fn new() -> WebSocketInboundSettings {
+        WebSocketInboundSettings::new()
     }
 
     fn clear(&mut self) {
-        self.certificate.clear();
-        self.certificate_key.clear();
-        self.ech_config.clear();
-        self.ech_key.clear();
+        self.path.clear();
+        self.header_name.clear();
+        self.header_value.clear();
+        self.fallback.clear();
+        self.fallback_status = 0;
+        self.fallback_file.clear();
         self.special_fields.clear();
     }
 
-    fn default_instance() -> &'static TlsInboundSettings {
-        static instance: TlsInboundSettings = TlsInboundSettings {
-            certificate: ::std::string::String::new(),
-            certificate_key: ::std::string::String::new(),
-            ech_config: ::std::string::String::new(),
-            ech_key: ::std::string::String::new(),
+    fn default_instance() -> &'static WebSocketInboundSettings {
+        static instance: WebSocketInboundSettings = WebSocketInboundSettings {
+            path: ::std::string::String::new(),
+            header_name: ::std::string::String::new(),
+            header_value: ::std::string::String::new(),
+            fallback: ::std::string::String::new(),
+            fallback_status: 0,
+            fallback_file: ::std::string::String::new(),
             special_fields: ::protobuf::SpecialFields::new(),
         };
         &instance
     }
 }
 
-// @@protoc_insertion_point(message:ChainInboundSettings)
+// @@protoc_insertion_point(message:GrpcInboundSettings)
 #[derive(PartialEq,Clone,Default,Debug)]
-pub struct ChainInboundSettings {
+pub struct GrpcInboundSettings {
     // message fields
-    // @@protoc_insertion_point(field:ChainInboundSettings.actors)
-    pub actors: ::std::vec::Vec<::std::string::String>,
+    // @@protoc_insertion_point(field:GrpcInboundSettings.service_name)
+    pub service_name: ::std::string::String,
     // special fields
-    // @@protoc_insertion_point(special_field:ChainInboundSettings.special_fields)
+    // @@protoc_insertion_point(special_field:GrpcInboundSettings.special_fields)
     pub special_fields: ::protobuf::SpecialFields,
 }
 
-impl<'a> ::std::default::Default for &'a ChainInboundSettings {
-    fn default() -> &'a ChainInboundSettings {
-        <ChainInboundSettings as ::protobuf::Message>::default_instance()
+impl<'a> ::std::default::Default for &'a GrpcInboundSettings {
+    fn default() -> &'a GrpcInboundSettings {
+        <GrpcInboundSettings as ::protobuf::Message>::default_instance()
     }
 }
 
-impl ChainInboundSettings {
-    pub fn new() -> ChainInboundSettings {
+impl GrpcInboundSettings {
+    pub fn new() -> GrpcInboundSettings {
         ::std::default::Default::default()
     }
 }
 
-impl ::protobuf::Message for ChainInboundSettings {
-    const NAME: &'static str = "ChainInboundSettings";
+impl ::protobuf::Message for GrpcInboundSettings {
+    const NAME: &'static str = "GrpcInboundSettings";
 
     fn is_initialized(&self) -> bool {
         true
@@ -1760,7 +2637,7 @@ impl ::protobuf::Message for ChainInboundSettings {
         while let Some(tag) = is.read_raw_tag_or_eof()? {
             match tag {
                 10 => {
-                    self.actors.push(is.read_string()?);
+                    self.service_name = is.read_string()?;
                 },
                 tag => {
                     ::protobuf::rt::read_unknown_or_skip_group(tag, is, self.special_fields.mut_unknown_fields())?;
@@ -1774,18 +2651,18 @@ impl ::protobuf::Message for ChainInboundSettings {
     #[allow(unused_variables)]
     fn compute_size(&self) -> u64 {
         let mut my_size = 0;
-        for value in &self.actors {
-            my_size += ::protobuf::rt::string_size(1, &value);
-        };
+        if !self.service_name.is_empty() {
+            my_size += ::protobuf::rt::string_size(1, &self.service_name);
+        }
         my_size += ::protobuf::rt::unknown_fields_size(self.special_fields.unknown_fields());
         self.special_fields.cached_size().set(my_size as u32);
         my_size
     }
 
     fn write_to_with_cached_sizes(&self, os: &mut ::protobuf::CodedOutputStream<'_>) -> ::protobuf::Result<()> {
-        for v in &self.actors {
-            os.write_string(1, &v)?;
-        };
+        if !self.service_name.is_empty() {
+            os.write_string(1, &self.service_name)?;
+        }
         os.write_unknown_fields(self.special_fields.unknown_fields())?;
         ::std::result::Result::Ok(())
     }
@@ -1798,46 +2675,49 @@ impl ::protobuf::Message for ChainInboundSettings {
         &mut self.special_fields
     }
 
-    fn new() -> ChainInboundSettings {
-        ChainInboundSettings::new()
+    fn new() -> GrpcInboundSettings {
+        GrpcInboundSettings::new()
     }
 
     fn clear(&mut self) {
-        self.actors.clear();
+        self.service_name.clear();
         self.special_fields.clear();
     }
 
-    fn default_instance() -> &'static ChainInboundSettings {
-        static instance: ChainInboundSettings = ChainInboundSettings {
-            actors: ::std::vec::Vec::new(),
+    fn default_instance() -> &'static GrpcInboundSettings {
+        static instance: GrpcInboundSettings = GrpcInboundSettings {
+            service_name: ::std::string::String::new(),
             special_fields: ::protobuf::SpecialFields::new(),
         };
         &instance
     }
 }
 
-// @@protoc_insertion_point(message:MptpInboundSettings)
+// @@protoc_insertion_point(message:HttpUpgradeInboundSettings)
 #[derive(PartialEq,Clone,Default,Debug)]
-pub struct MptpInboundSettings {
+pub struct HttpUpgradeInboundSettings {
+    // message fields
+    // @@protoc_insertion_point(field:HttpUpgradeInboundSettings.path)
+    pub path: ::std::string::String,
     // special fields
-    // @@protoc_insertion_point(special_field:MptpInboundSettings.special_fields)
+    // @@protoc_insertion_point(special_field:HttpUpgradeInboundSettings.special_fields)
     pub special_fields: ::protobuf::SpecialFields,
 }
 
-impl<'a> ::std::default::Default for &'a MptpInboundSettings {
-    fn default() -> &'a MptpInboundSettings {
-        <MptpInboundSettings as ::protobuf::Message>::default_instance()
+impl<'a> ::std::default::Default for &'a HttpUpgradeInboundSettings {
+    fn default() -> &'a HttpUpgradeInboundSettings {
+        <HttpUpgradeInboundSettings as ::protobuf::Message>::default_instance()
     }
 }
 
-impl MptpInboundSettings {
-    pub fn new() -> MptpInboundSettings {
+impl HttpUpgradeInboundSettings {
+    pub fn new() -> HttpUpgradeInboundSettings {
         ::std::default::Default::default()
     }
 }
 
-impl ::protobuf::Message for MptpInboundSettings {
-    const NAME: &'static str = "MptpInboundSettings";
+impl ::protobuf::Message for HttpUpgradeInboundSettings {
+    const NAME: &'static str = "HttpUpgradeInboundSettings";
 
     fn is_initialized(&self) -> bool {
         true
@@ -1846,6 +2726,9 @@ impl ::protobuf::Message for MptpInboundSettings {
     fn merge_from(&mut self, is: &mut ::protobuf::CodedInputStream<'_>) -> ::protobuf::Result<()> {
         while let Some(tag) = is.read_raw_tag_or_eof()? {
             match tag {
+                10 => {
+                    self.path = is.read_string()?;
+                },
                 tag => {
                     ::protobuf::rt::read_unknown_or_skip_group(tag, is, self.special_fields.mut_unknown_fields())?;
                 },
@@ -1858,12 +2741,18 @@ impl ::protobuf::Message for MptpInboundSettings {
     #[allow(unused_variables)]
     fn compute_size(&self) -> u64 {
         let mut my_size = 0;
+        if !self.path.is_empty() {
+            my_size += ::protobuf::rt::string_size(1, &self.path);
+        }
         my_size += ::protobuf::rt::unknown_fields_size(self.special_fields.unknown_fields());
         self.special_fields.cached_size().set(my_size as u32);
         my_size
     }
 
     fn write_to_with_cached_sizes(&self, os: &mut ::protobuf::CodedOutputStream<'_>) -> ::protobuf::Result<()> {
+        if !self.path.is_empty() {
+            os.write_string(1, &self.path)?;
+        }
         os.write_unknown_fields(self.special_fields.unknown_fields())?;
         ::std::result::Result::Ok(())
     }
@@ -1876,51 +2765,51 @@ impl ::protobuf::Message for MptpInboundSettings {
         &mut self.special_fields
     }
 
-    fn new() -> MptpInboundSettings {
-        MptpInboundSettings::new()
+    fn new() -> HttpUpgradeInboundSettings {
+        HttpUpgradeInboundSettings::new()
     }
 
     fn clear(&mut self) {
+        self.path.clear();
         self.special_fields.clear();
     }
 
-    fn default_instance() -> &'static MptpInboundSettings {
-        static instance: MptpInboundSettings = MptpInboundSettings {
+    fn default_instance() -> &'static HttpUpgradeInboundSettings {
+        static instance: HttpUpgradeInboundSettings = HttpUpgradeInboundSettings {
+            path: ::std::string::String::new(),
             special_fields: ::protobuf::SpecialFields::new(),
         };
         &instance
     }
 }
 
-// @@protoc_insertion_point(message:HcInboundSettings)
+// @@protoc_insertion_point(message:AMuxInboundSettings)
 #[derive(PartialEq,Clone,Default,Debug)]
-pub struct HcInboundSettings {
+pub struct AMuxInboundSettings {
     // message fields
-    // @@protoc_insertion_point(field:HcInboundSettings.path)
-    pub path: ::std::string::String,
-    // @@protoc_insertion_point(field:HcInboundSettings.request)
-    pub request: ::std::string::String,
-    // @@protoc_insertion_point(field:HcInboundSettings.response)
-    pub response: ::std::string::String,
+    // @@protoc_insertion_point(field:AMuxInboundSettings.actors)
+    pub actors: ::std::vec::Vec<::std::string::String>,
+    // @@protoc_insertion_point(field:AMuxInboundSettings.max_sessions_per_connection)
+    pub max_sessions_per_connection: u32,
     // special fields
-    // @@protoc_insertion_point(special_field:HcInboundSettings.special_fields)
+    // @@protoc_insertion_point(special_field:AMuxInboundSettings.special_fields)
     pub special_fields: ::protobuf::SpecialFields,
 }
 
-impl<'a> ::std::default::Default for &'a HcInboundSettings {
-    fn default() -> &'a HcInboundSettings {
-        <HcInboundSettings as ::protobuf::Message>::default_instance()
+impl<'a> ::std::default::Default for &'a AMuxInboundSettings {
+    fn default() -> &'a AMuxInboundSettings {
+        <AMuxInboundSettings as ::protobuf::Message>::default_instance()
     }
 }
 
-impl HcInboundSettings {
-    pub fn new() -> HcInboundSettings {
+impl AMuxInboundSettings {
+    pub fn new() -> AMuxInboundSettings {
         ::std::default::Default::default()
     }
 }
 
-impl ::protobuf::Message for HcInboundSettings {
-    const NAME: &'static str = "HcInboundSettings";
+impl ::protobuf::Message for AMuxInboundSettings {
+    const NAME: &'static str = "AMuxInboundSettings";
 
     fn is_initialized(&self) -> bool {
         true
@@ -1930,13 +2819,10 @@ impl ::protobuf::Message for HcInboundSettings {
         while let Some(tag) = is.read_raw_tag_or_eof()? {
             match tag {
                 10 => {
-                    self.path = is.read_string()?;
-                },
-                18 => {
-                    self.request = is.read_string()?;
+                    self.actors.push(is.read_string()?);
                 },
-                26 => {
-                    self.response = is.read_string()?;
+                16 => {
+                    self.max_sessions_per_connection = is.read_uint32()?;
                 },
                 tag => {
                     ::protobuf::rt::read_unknown_or_skip_group(tag, is, self.special_fields.mut_unknown_fields())?;
@@ -1950,14 +2836,11 @@ impl ::protobuf::Message for HcInboundSettings {
     #[allow(unused_variables)]
     fn compute_size(&self) -> u64 {
         let mut my_size = 0;
-        if !self.path.is_empty() {
-            my_size += ::protobuf::rt::string_size(1, &self.path);
-        }
-        if !self.request.is_empty() {
-            my_size += ::protobuf::rt::string_size(2, &self.request);
-        }
-        if !self.response.is_empty() {
-            my_size += ::protobuf::rt::string_size(3, &self.response);
+        for value in &self.actors {
+            my_size += ::protobuf::rt::string_size(1, &value);
+        };
+        if self.max_sessions_per_connection != 0 {
+            my_size += ::protobuf::rt::uint32_size(2, self.max_sessions_per_connection);
         }
         my_size += ::protobuf::rt::unknown_fields_size(self.special_fields.unknown_fields());
         self.special_fields.cached_size().set(my_size as u32);
@@ -1965,14 +2848,11 @@ impl ::protobuf::Message for HcInboundSettings {
     }
 
     fn write_to_with_cached_sizes(&self, os: &mut ::protobuf::CodedOutputStream<'_>) -> ::protobuf::Result<()> {
-        if !self.path.is_empty() {
-            os.write_string(1, &self.path)?;
-        }
-        if !self.request.is_empty() {
-            os.write_string(2, &self.request)?;
-        }
-        if !self.response.is_empty() {
-            os.write_string(3, &self.response)?;
+        for v in &self.actors {
+            os.write_string(1, &v)?;
+        };
+        if self.max_sessions_per_connection != 0 {
+            os.write_uint32(2, self.max_sessions_per_connection)?;
         }
         os.write_unknown_fields(self.special_fields.unknown_fields())?;
         ::std::result::Result::Ok(())
@@ -1986,61 +2866,57 @@ impl ::protobuf::Message for HcInboundSettings {
         &mut self.special_fields
     }
 
-    fn new() -> HcInboundSettings {
-        HcInboundSettings::new()
+    fn new() -> AMuxInboundSettings {
+        AMuxInboundSettings::new()
     }
 
     fn clear(&mut self) {
-        self.path.clear();
-        self.request.clear();
-        self.response.clear();
+        self.actors.clear();
+        self.max_sessions_per_connection = 0;
         self.special_fields.clear();
     }
 
-    fn default_instance() -> &'static HcInboundSettings {
-        static instance: HcInboundSettings = HcInboundSettings {
-            path: ::std::string::String::new(),
-            request: ::std::string::String::new(),
-            response: ::std::string::String::new(),
+    fn default_instance() -> &'static AMuxInboundSettings {
+        static instance: AMuxInboundSettings = AMuxInboundSettings {
+            actors: ::std::vec::Vec::new(),
+            max_sessions_per_connection: 0,
             special_fields: ::protobuf::SpecialFields::new(),
         };
         &instance
     }
 }
 
-// @@protoc_insertion_point(message:Inbound)
+// @@protoc_insertion_point(message:NfInboundSettings)
 #[derive(PartialEq,Clone,Default,Debug)]
-pub struct Inbound {
+pub struct NfInboundSettings {
     // message fields
-    // @@protoc_insertion_point(field:Inbound.tag)
-    pub tag: ::std::string::String,
-    // @@protoc_insertion_point(field:Inbound.protocol)
-    pub protocol: ::std::string::String,
-    // @@protoc_insertion_point(field:Inbound.address)
-    pub address: ::std::string::String,
-    // @@protoc_insertion_point(field:Inbound.port)
-    pub port: u32,
-    // @@protoc_insertion_point(field:Inbound.settings)
-    pub settings: ::std::vec::Vec<u8>,
+    // @@protoc_insertion_point(field:NfInboundSettings.driver_name)
+    pub driver_name: ::std::string::String,
+    // @@protoc_insertion_point(field:NfInboundSettings.nfapi)
+    pub nfapi: ::std::string::String,
+    // @@protoc_insertion_point(field:NfInboundSettings.fake_dns_exclude)
+    pub fake_dns_exclude: ::std::vec::Vec<::std::string::String>,
+    // @@protoc_insertion_point(field:NfInboundSettings.fake_dns_include)
+    pub fake_dns_include: ::std::vec::Vec<::std::string::String>,
     // special fields
-    // @@protoc_insertion_point(special_field:Inbound.special_fields)
+    // @@protoc_insertion_point(special_field:NfInboundSettings.special_fields)
     pub special_fields: ::protobuf::SpecialFields,
 }
 
-impl<'a> ::std::default::Default for &'a Inbound {
-    fn default() -> &'a Inbound {
-        <Inbound as ::protobuf::Message>::default_instance()
+impl<'a> ::std::default::Default for &'a NfInboundSettings {
+    fn default() -> &'a NfInboundSettings {
+        <NfInboundSettings as ::protobuf::Message>::default_instance()
     }
 }
 
-impl Inbound {
-    pub fn new() -> Inbound {
+impl NfInboundSettings {
+    pub fn new() -> NfInboundSettings {
         ::std::default::Default::default()
     }
 }
 
-impl ::protobuf::Message for Inbound {
-    const NAME: &'static str = "Inbound";
+impl ::protobuf::Message for NfInboundSettings {
+    const NAME: &'static str = "NfInboundSettings";
 
     fn is_initialized(&self) -> bool {
         true
@@ -2050,19 +2926,16 @@ impl ::protobuf::Message for Inbound {
         while let Some(tag) = is.read_raw_tag_or_eof()? {
             match tag {
                 10 => {
-                    self.tag = is.read_string()?;
+                    self.driver_name = is.read_string()?;
                 },
                 18 => {
-                    self.protocol = is.read_string()?;
+                    self.nfapi = is.read_string()?;
                 },
                 26 => {
-                    self.address = is.read_string()?;
-                },
-                32 => {
-                    self.port = is.read_uint32()?;
+                    self.fake_dns_exclude.push(is.read_string()?);
                 },
-                42 => {
-                    self.settings = is.read_bytes()?;
+                34 => {
+                    self.fake_dns_include.push(is.read_string()?);
                 },
                 tag => {
                     ::protobuf::rt::read_unknown_or_skip_group(tag, is, self.special_fields.mut_unknown_fields())?;
@@ -2076,42 +2949,36 @@ impl ::protobuf::Message for Inbound {
     #[allow(unused_variables)]
     fn compute_size(&self) -> u64 {
         let mut my_size = 0;
-        if !self.tag.is_empty() {
-            my_size += ::protobuf::rt::string_size(1, &self.tag);
-        }
-        if !self.protocol.is_empty() {
-            my_size += ::protobuf::rt::string_size(2, &self.protocol);
-        }
-        if !self.address.is_empty() {
-            my_size += ::protobuf::rt::string_size(3, &self.address);
-        }
-        if self.port != 0 {
-            my_size += ::protobuf::rt::uint32_size(4, self.port);
+        if !self.driver_name.is_empty() {
+            my_size += ::protobuf::rt::string_size(1, &self.driver_name);
         }
-        if !self.settings.is_empty() {
-            my_size += ::protobuf::rt::bytes_size(5, &self.settings);
+        if !self.nfapi.is_empty() {
+            my_size += ::protobuf::rt::string_size(2, &self.nfapi);
         }
+        for value in &self.fake_dns_exclude {
+            my_size += ::protobuf::rt::string_size(3, &value);
+        };
+        for value in &self.fake_dns_include {
+            my_size += ::protobuf::rt::string_size(4, &value);
+        };
         my_size += ::protobuf::rt::unknown_fields_size(self.special_fields.unknown_fields());
         self.special_fields.cached_size().set(my_size as u32);
         my_size
     }
 
     fn write_to_with_cached_sizes(&self, os: &mut ::protobuf::CodedOutputStream<'_>) -> ::protobuf::Result<()> {
-        if !self.tag.is_empty() {
-            os.write_string(1, &self.tag)?;
-        }
-        if !self.protocol.is_empty() {
-            os.write_string(2, &self.protocol)?;
-        }
-        if !self.address.is_empty() {
-            os.write_string(3, &self.address)?;
-        }
-        if self.port != 0 {
-            os.write_uint32(4, self.port)?;
+        if !self.driver_name.is_empty() {
+            os.write_string(1, &self.driver_name)?;
         }
-        if !self.settings.is_empty() {
-            os.write_bytes(5, &self.settings)?;
+        if !self.nfapi.is_empty() {
+            os.write_string(2, &self.nfapi)?;
         }
+        for v in &self.fake_dns_exclude {
+            os.write_string(3, &v)?;
+        };
+        for v in &self.fake_dns_include {
+            os.write_string(4, &v)?;
+        };
         os.write_unknown_fields(self.special_fields.unknown_fields())?;
         ::std::result::Result::Ok(())
     }
@@ -2124,59 +2991,83 @@ impl ::protobuf::Message for Inbound {
         &mut self.special_fields
     }
 
-    fn new() -> Inbound {
-        Inbound::new()
+    fn new() -> NfInboundSettings {
+        NfInboundSettings::new()
     }
 
     fn clear(&mut self) {
-        self.tag.clear();
-        self.protocol.clear();
-        self.address.clear();
-        self.port = 0;
-        self.settings.clear();
+        self.driver_name.clear();
+        self.nfapi.clear();
+        self.fake_dns_exclude.clear();
+        self.fake_dns_include.clear();
         self.special_fields.clear();
     }
 
-    fn default_instance() -> &'static Inbound {
-        static instance: Inbound = Inbound {
-            tag: ::std::string::String::new(),
-            protocol: ::std::string::String::new(),
-            address: ::std::string::String::new(),
-            port: 0,
-            settings: ::std::vec::Vec::new(),
+    fn default_instance() -> &'static NfInboundSettings {
+        static instance: NfInboundSettings = NfInboundSettings {
+            driver_name: ::std::string::String::new(),
+            nfapi: ::std::string::String::new(),
+            fake_dns_exclude: ::std::vec::Vec::new(),
+            fake_dns_include: ::std::vec::Vec::new(),
             special_fields: ::protobuf::SpecialFields::new(),
         };
         &instance
     }
 }
 
-// @@protoc_insertion_point(message:RedirectOutboundSettings)
+// @@protoc_insertion_point(message:QuicInboundSettings)
 #[derive(PartialEq,Clone,Default,Debug)]
-pub struct RedirectOutboundSettings {
+pub struct QuicInboundSettings {
     // message fields
-    // @@protoc_insertion_point(field:RedirectOutboundSettings.address)
-    pub address: ::std::string::String,
-    // @@protoc_insertion_point(field:RedirectOutboundSettings.port)
-    pub port: u32,
+    // @@protoc_insertion_point(field:QuicInboundSettings.certificate)
+    pub certificate: ::std::string::String,
+    // @@protoc_insertion_point(field:QuicInboundSettings.certificate_key)
+    pub certificate_key: ::std::string::String,
+    // @@protoc_insertion_point(field:QuicInboundSettings.alpn)
+    pub alpn: ::std::vec::Vec<::std::string::String>,
+    // @@protoc_insertion_point(field:QuicInboundSettings.max_concurrent_bidi_streams)
+    pub max_concurrent_bidi_streams: u32,
+    // @@protoc_insertion_point(field:QuicInboundSettings.max_idle_timeout_ms)
+    pub max_idle_timeout_ms: u32,
+    // @@protoc_insertion_point(field:QuicInboundSettings.keep_alive_interval_ms)
+    pub keep_alive_interval_ms: u32,
+    // @@protoc_insertion_point(field:QuicInboundSettings.congestion_controller)
+    pub congestion_controller: ::std::string::String,
+    // @@protoc_insertion_point(field:QuicInboundSettings.certificate_reload_interval)
+    pub certificate_reload_interval: u32,
+    // @@protoc_insertion_point(field:QuicInboundSettings.client_ca_certificate)
+    pub client_ca_certificate: ::std::string::String,
+    // @@protoc_insertion_point(field:QuicInboundSettings.udp_over_stream)
+    pub udp_over_stream: bool,
+    // @@protoc_insertion_point(field:QuicInboundSettings.self_signed_san)
+    pub self_signed_san: ::std::string::String,
+    // @@protoc_insertion_point(field:QuicInboundSettings.persist_self_signed)
+    pub persist_self_signed: bool,
+    // @@protoc_insertion_point(field:QuicInboundSettings.accept_uni_streams)
+    pub accept_uni_streams: bool,
+    // @@protoc_insertion_point(field:QuicInboundSettings.fallback_alpns)
+    pub fallback_alpns: ::std::vec::Vec<::std::string::String>,
+    // @@protoc_insertion_point(field:QuicInboundSettings.fallback)
+    pub fallback: ::std::string::String,
     // special fields
-    // @@protoc_insertion_point(special_field:RedirectOutboundSettings.special_fields)
+    // @@protoc_insertion_point(special_field:QuicInboundSettings.special_fields)
     pub special_fields: ::protobuf::SpecialFields,
 }
 
-impl<'a> ::std::default::Default for &'a RedirectOutboundSettings {
-    fn default() -> &'a RedirectOutboundSettings {
-        <RedirectOutboundSettings as ::protobuf::Message>::default_instance()
+impl<'a> ::std::default::Default for &'a QuicInboundSettings {
+    fn default() -> &'a QuicInboundSettings {
+        <QuicInboundSettings as ::protobuf::Message>::default_instance()
     }
 }
 
-impl RedirectOutboundSettings {
-    pub fn new() -> RedirectOutboundSettings {
+impl QuicInboundSettings {
+    pub fn new() -> QuicInboundSettings {
         ::std::default::Default::default()
     }
 }
 
-impl ::protobuf::Message for RedirectOutboundSettings {
-    const NAME: &'static str = "RedirectOutboundSettings";
+impl ::protobuf::Message for QuicInboundSettings {
+    const NAME: &'static str = "QuicInboundSettings";
 
     fn is_initialized(&self) -> bool {
         true
@@ -2186,10 +3077,49 @@ impl ::protobuf::Message for RedirectOutboundSettings {
         while let Some(tag) = is.read_raw_tag_or_eof()? {
             match tag {
                 10 => {
-                    self.address = is.read_string()?;
+                    self.certificate = is.read_string()?;
                 },
-                16 => {
-                    self.port = is.read_uint32()?;
+                18 => {
+                    self.certificate_key = is.read_string()?;
+                },
+                26 => {
+                    self.alpn.push(is.read_string()?);
+                },
+                32 => {
+                    self.max_concurrent_bidi_streams = is.read_uint32()?;
+                },
+                40 => {
+                    self.max_idle_timeout_ms = is.read_uint32()?;
+                },
+                48 => {
+                    self.keep_alive_interval_ms = is.read_uint32()?;
+                },
+                58 => {
+                    self.congestion_controller = is.read_string()?;
+                },
+                64 => {
+                    self.certificate_reload_interval = is.read_uint32()?;
+                },
+                74 => {
+                    self.client_ca_certificate = is.read_string()?;
+                },
+                80 => {
+                    self.udp_over_stream = is.read_bool()?;
+                },
+                90 => {
+                    self.self_signed_san = is.read_string()?;
+                },
+                96 => {
+                    self.persist_self_signed = is.read_bool()?;
+                },
+                104 => {
+                    self.accept_uni_streams = is.read_bool()?;
+                },
+                114 => {
+                    self.fallback_alpns.push(is.read_string()?);
+                },
+                122 => {
+                    self.fallback = is.read_string()?;
                 },
                 tag => {
                     ::protobuf::rt::read_unknown_or_skip_group(tag, is, self.special_fields.mut_unknown_fields())?;
@@ -2203,11 +3133,50 @@ impl ::protobuf::Message for RedirectOutboundSettings {
     #[allow(unused_variables)]
     fn compute_size(&self) -> u64 {
         let mut my_size = 0;
-        if !self.address.is_empty() {
-            my_size += ::protobuf::rt::string_size(1, &self.address);
+        if !self.certificate.is_empty() {
+            my_size += ::protobuf::rt::string_size(1, &self.certificate);
         }
-        if self.port != 0 {
-            my_size += ::protobuf::rt::uint32_size(2, self.port);
+        if !self.certificate_key.is_empty() {
+            my_size += ::protobuf::rt::string_size(2, &self.certificate_key);
+        }
+        for value in &self.alpn {
+            my_size += ::protobuf::rt::string_size(3, &value);
+        };
+        if self.max_concurrent_bidi_streams != 0 {
+            my_size += ::protobuf::rt::uint32_size(4, self.max_concurrent_bidi_streams);
+        }
+        if self.max_idle_timeout_ms != 0 {
+            my_size += ::protobuf::rt::uint32_size(5, self.max_idle_timeout_ms);
+        }
+        if self.keep_alive_interval_ms != 0 {
+            my_size += ::protobuf::rt::uint32_size(6, self.keep_alive_interval_ms);
+        }
+        if !self.congestion_controller.is_empty() {
+            my_size += ::protobuf::rt::string_size(7, &self.congestion_controller);
+        }
+        if self.certificate_reload_interval != 0 {
+            my_size += ::protobuf::rt::uint32_size(8, self.certificate_reload_interval);
+        }
+        if !self.client_ca_certificate.is_empty() {
+            my_size += ::protobuf::rt::string_size(9, &self.client_ca_certificate);
+        }
+        if self.udp_over_stream != false {
+            my_size += 1 + 1;
+        }
+        if !self.self_signed_san.is_empty() {
+            my_size += ::protobuf::rt::string_size(11, &self.self_signed_san);
+        }
+        if self.persist_self_signed != false {
+            my_size += 1 + 1;
+        }
+        if self.accept_uni_streams != false {
+            my_size += 1 + 1;
+        }
+        for value in &self.fallback_alpns {
+            my_size += ::protobuf::rt::string_size(14, &value);
+        };
+        if !self.fallback.is_empty() {
+            my_size += ::protobuf::rt::string_size(15, &self.fallback);
         }
         my_size += ::protobuf::rt::unknown_fields_size(self.special_fields.unknown_fields());
         self.special_fields.cached_size().set(my_size as u32);
@@ -2215,11 +3184,50 @@ impl ::protobuf::Message for RedirectOutboundSettings {
     }
 
     fn write_to_with_cached_sizes(&self, os: &mut ::protobuf::CodedOutputStream<'_>) -> ::protobuf::Result<()> {
-        if !self.address.is_empty() {
-            os.write_string(1, &self.address)?;
+        if !self.certificate.is_empty() {
+            os.write_string(1, &self.certificate)?;
         }
-        if self.port != 0 {
-            os.write_uint32(2, self.port)?;
+        if !self.certificate_key.is_empty() {
+            os.write_string(2, &self.certificate_key)?;
+        }
+        for v in &self.alpn {
+            os.write_string(3, &v)?;
+        };
+        if self.max_concurrent_bidi_streams != 0 {
+            os.write_uint32(4, self.max_concurrent_bidi_streams)?;
+        }
+        if self.max_idle_timeout_ms != 0 {
+            os.write_uint32(5, self.max_idle_timeout_ms)?;
+        }
+        if self.keep_alive_interval_ms != 0 {
+            os.write_uint32(6, self.keep_alive_interval_ms)?;
+        }
+        if !self.congestion_controller.is_empty() {
+            os.write_string(7, &self.congestion_controller)?;
+        }
+        if self.certificate_reload_interval != 0 {
+            os.write_uint32(8, self.certificate_reload_interval)?;
+        }
+        if !self.client_ca_certificate.is_empty() {
+            os.write_string(9, &self.client_ca_certificate)?;
+        }
+        if self.udp_over_stream != false {
+            os.write_bool(10, self.udp_over_stream)?;
+        }
+        if !self.self_signed_san.is_empty() {
+            os.write_string(11, &self.self_signed_san)?;
+        }
+        if self.persist_self_signed != false {
+            os.write_bool(12, self.persist_self_signed)?;
+        }
+        if self.accept_uni_streams != false {
+            os.write_bool(13, self.accept_uni_streams)?;
+        }
+        for v in &self.fallback_alpns {
+            os.write_string(14, &v)?;
+        };
+        if !self.fallback.is_empty() {
+            os.write_string(15, &self.fallback)?;
         }
         os.write_unknown_fields(self.special_fields.unknown_fields())?;
         ::std::result::Result::Ok(())
@@ -2233,57 +3241,89 @@ impl ::protobuf::Message for RedirectOutboundSettings {
         &mut self.special_fields
     }
 
-    fn new() -> RedirectOutboundSettings {
-        RedirectOutboundSettings::new()
+    fn new() -> QuicInboundSettings {
+        QuicInboundSettings::new()
     }
 
     fn clear(&mut self) {
-        self.address.clear();
-        self.port = 0;
+        self.certificate.clear();
+        self.certificate_key.clear();
+        self.alpn.clear();
+        self.max_concurrent_bidi_streams = 0;
+        self.max_idle_timeout_ms = 0;
+        self.keep_alive_interval_ms = 0;
+        self.congestion_controller.clear();
+        self.certificate_reload_interval = 0;
+        self.client_ca_certificate.clear();
+        self.udp_over_stream = false;
+        self.self_signed_san.clear();
+        self.persist_self_signed = false;
+        self.accept_uni_streams = false;
+        self.fallback_alpns.clear();
+        self.fallback.clear();
         self.special_fields.clear();
     }
 
-    fn default_instance() -> &'static RedirectOutboundSettings {
-        static instance: RedirectOutboundSettings = RedirectOutboundSettings {
-            address: ::std::string::String::new(),
-            port: 0,
+    fn default_instance() -> &'static QuicInboundSettings {
+        static instance: QuicInboundSettings = QuicInboundSettings {
+            certificate: ::std::string::String::new(),
+            certificate_key: ::std::string::String::new(),
+            alpn: ::std::vec::Vec::new(),
+            max_concurrent_bidi_streams: 0,
+            max_idle_timeout_ms: 0,
+            keep_alive_interval_ms: 0,
+            congestion_controller: ::std::string::String::new(),
+            certificate_reload_interval: 0,
+            client_ca_certificate: ::std::string::String::new(),
+            udp_over_stream: false,
+            self_signed_san: ::std::string::String::new(),
+            persist_self_signed: false,
+            accept_uni_streams: false,
+            fallback_alpns: ::std::vec::Vec::new(),
+            fallback: ::std::string::String::new(),
             special_fields: ::protobuf::SpecialFields::new(),
         };
         &instance
     }
 }
 
-// @@protoc_insertion_point(message:SocksOutboundSettings)
+// @@protoc_insertion_point(message:TlsInboundSettings)
 #[derive(PartialEq,Clone,Default,Debug)]
-pub struct SocksOutboundSettings {
+pub struct TlsInboundSettings {
     // message fields
-    // @@protoc_insertion_point(field:SocksOutboundSettings.address)
-    pub address: ::std::string::String,
-    // @@protoc_insertion_point(field:SocksOutboundSettings.port)
-    pub port: u32,
-    // @@protoc_insertion_point(field:SocksOutboundSettings.username)
-    pub username: ::std::string::String,
-    // @@protoc_insertion_point(field:SocksOutboundSettings.password)
-    pub password: ::std::string::String,
+    // @@protoc_insertion_point(field:TlsInboundSettings.certificate)
+    pub certificate: ::std::string::String,
+    // @@protoc_insertion_point(field:TlsInboundSettings.certificate_key)
+    pub certificate_key: ::std::string::String,
+    // @@protoc_insertion_point(field:TlsInboundSettings.ech_config)
+    pub ech_config: ::std::string::String,
+    // @@protoc_insertion_point(field:TlsInboundSettings.ech_key)
+    pub ech_key: ::std::string::String,
+    // @@protoc_insertion_point(field:TlsInboundSettings.self_signed_san)
+    pub self_signed_san: ::std::string::String,
+    // @@protoc_insertion_point(field:TlsInboundSettings.persist_self_signed)
+    pub persist_self_signed: bool,
+    // @@protoc_insertion_point(field:TlsInboundSettings.default_sni)
+    pub default_sni: ::std::string::String,
     // special fields
-    // @@protoc_insertion_point(special_field:SocksOutboundSettings.special_fields)
+    // @@protoc_insertion_point(special_field:TlsInboundSettings.special_fields)
     pub special_fields: ::protobuf::SpecialFields,
 }
 
-impl<'a> ::std::default::Default for &'a SocksOutboundSettings {
-    fn default() -> &'a SocksOutboundSettings {
-        <SocksOutboundSettings as ::protobuf::Message>::default_instance()
+impl<'a> ::std::default::Default for &'a TlsInboundSettings {
+    fn default() -> &'a TlsInboundSettings {
+        <TlsInboundSettings as ::protobuf::Message>::default_instance()
     }
 }
 
-impl SocksOutboundSettings {
-    pub fn new() -> SocksOutboundSettings {
+impl TlsInboundSettings {
+    pub fn new() -> TlsInboundSettings {
         ::std::default::Default::default()
     }
 }
 
-impl ::protobuf::Message for SocksOutboundSettings {
-    const NAME: &'static str = "SocksOutboundSettings";
+impl ::protobuf::Message for TlsInboundSettings {
+    const NAME: &'static str = "TlsInboundSettings";
 
     fn is_initialized(&self) -> bool {
         true
@@ -2293,16 +3333,25 @@ impl ::protobuf::Message for SocksOutboundSettings {
         while let Some(tag) = is.read_raw_tag_or_eof()? {
             match tag {
                 10 => {
-                    self.address = is.read_string()?;
+                    self.certificate = is.read_string()?;
                 },
-                16 => {
-                    self.port = is.read_uint32()?;
+                18 => {
+                    self.certificate_key = is.read_string()?;
                 },
                 26 => {
-                    self.username = is.read_string()?;
+                    self.ech_config = is.read_string()?;
                 },
                 34 => {
-                    self.password = is.read_string()?;
+                    self.ech_key = is.read_string()?;
+                },
+                42 => {
+                    self.self_signed_san = is.read_string()?;
+                },
+                48 => {
+                    self.persist_self_signed = is.read_bool()?;
+                },
+                58 => {
+                    self.default_sni = is.read_string()?;
                 },
                 tag => {
                     ::protobuf::rt::read_unknown_or_skip_group(tag, is, self.special_fields.mut_unknown_fields())?;
@@ -2316,17 +3365,26 @@ impl ::protobuf::Message for SocksOutboundSettings {
     #[allow(unused_variables)]
     fn compute_size(&self) -> u64 {
         let mut my_size = 0;
-        if !self.address.is_empty() {
-            my_size += ::protobuf::rt::string_size(1, &self.address);
+        if !self.certificate.is_empty() {
+            my_size += ::protobuf::rt::string_size(1, &self.certificate);
         }
-        if self.port != 0 {
-            my_size += ::protobuf::rt::uint32_size(2, self.port);
+        if !self.certificate_key.is_empty() {
+            my_size += ::protobuf::rt::string_size(2, &self.certificate_key);
         }
-        if !self.username.is_empty() {
-            my_size += ::protobuf::rt::string_size(3, &self.username);
+        if !self.ech_config.is_empty() {
+            my_size += ::protobuf::rt::string_size(3, &self.ech_config);
         }
-        if !self.password.is_empty() {
-            my_size += ::protobuf::rt::string_size(4, &self.password);
+        if !self.ech_key.is_empty() {
+            my_size += ::protobuf::rt::string_size(4, &self.ech_key);
+        }
+        if !self.self_signed_san.is_empty() {
+            my_size += ::protobuf::rt::string_size(5, &self.self_signed_san);
+        }
+        if self.persist_self_signed != false {
+            my_size += 1 + 1;
+        }
+        if !self.default_sni.is_empty() {
+            my_size += ::protobuf::rt::string_size(7, &self.default_sni);
         }
         my_size += ::protobuf::rt::unknown_fields_size(self.special_fields.unknown_fields());
         self.special_fields.cached_size().set(my_size as u32);
@@ -2334,17 +3392,26 @@ impl ::protobuf::Message for SocksOutboundSettings {
     }
 
     fn write_to_with_cached_sizes(&self, os: &mut ::protobuf::CodedOutputStream<'_>) -> ::protobuf::Result<()> {
-        if !self.address.is_empty() {
-            os.write_string(1, &self.address)?;
+        if !self.certificate.is_empty() {
+            os.write_string(1, &self.certificate)?;
         }
-        if self.port != 0 {
-            os.write_uint32(2, self.port)?;
+        if !self.certificate_key.is_empty() {
+            os.write_string(2, &self.certificate_key)?;
         }
-        if !self.username.is_empty() {
-            os.write_string(3, &self.username)?;
+        if !self.ech_config.is_empty() {
+            os.write_string(3, &self.ech_config)?;
         }
-        if !self.password.is_empty() {
-            os.write_string(4, &self.password)?;
+        if !self.ech_key.is_empty() {
+            os.write_string(4, &self.ech_key)?;
+        }
+        if !self.self_signed_san.is_empty() {
+            os.write_string(5, &self.self_signed_san)?;
+        }
+        if self.persist_self_signed != false {
+            os.write_bool(6, self.persist_self_signed)?;
+        }
+        if !self.default_sni.is_empty() {
+            os.write_string(7, &self.default_sni)?;
         }
         os.write_unknown_fields(self.special_fields.unknown_fields())?;
         ::std::result::Result::Ok(())
@@ -2358,63 +3425,61 @@ impl ::protobuf::Message for SocksOutboundSettings {
         &mut self.special_fields
     }
 
-    fn new() -> SocksOutboundSettings {
-        SocksOutboundSettings::new()
+    fn new() -> TlsInboundSettings {
+        TlsInboundSettings::new()
     }
 
     fn clear(&mut self) {
-        self.address.clear();
-        self.port = 0;
-        self.username.clear();
-        self.password.clear();
+        self.certificate.clear();
+        self.certificate_key.clear();
+        self.ech_config.clear();
+        self.ech_key.clear();
+        self.self_signed_san.clear();
+        self.persist_self_signed = false;
+        self.default_sni.clear();
         self.special_fields.clear();
     }
 
-    fn default_instance() -> &'static SocksOutboundSettings {
-        static instance: SocksOutboundSettings = SocksOutboundSettings {
-            address: ::std::string::String::new(),
-            port: 0,
-            username: ::std::string::String::new(),
-            password: ::std::string::String::new(),
+    fn default_instance() -> &'static TlsInboundSettings {
+        static instance: TlsInboundSettings = TlsInboundSettings {
+            certificate: ::std::string::String::new(),
+            certificate_key: ::std::string::String::new(),
+            ech_config: ::std::string::String::new(),
+            ech_key: ::std::string::String::new(),
+            self_signed_san: ::std::string::String::new(),
+            persist_self_signed: false,
+            default_sni: ::std::string::String::new(),
             special_fields: ::protobuf::SpecialFields::new(),
         };
         &instance
     }
 }
 
-// @@protoc_insertion_point(message:ShadowsocksOutboundSettings)
+// @@protoc_insertion_point(message:ChainInboundSettings)
 #[derive(PartialEq,Clone,Default,Debug)]
-pub struct ShadowsocksOutboundSettings {
+pub struct ChainInboundSettings {
     // message fields
-    // @@protoc_insertion_point(field:ShadowsocksOutboundSettings.address)
-    pub address: ::std::string::String,
-    // @@protoc_insertion_point(field:ShadowsocksOutboundSettings.port)
-    pub port: u32,
-    // @@protoc_insertion_point(field:ShadowsocksOutboundSettings.method)
-    pub method: ::std::string::String,
-    // @@protoc_insertion_point(field:ShadowsocksOutboundSettings.password)
-    pub password: ::std::string::String,
-    // @@protoc_insertion_point(field:ShadowsocksOutboundSettings.prefix)
-    pub prefix: ::std::option::Option<::std::string::String>,
+    // @@protoc_insertion_point(field:ChainInboundSettings.actors)
+    pub actors: ::std::vec::Vec<::std::string::String>,
     // special fields
-    // @@protoc_insertion_point(special_field:ShadowsocksOutboundSettings.special_fields)
+    // @@protoc_insertion_point(special_field:ChainInboundSettings.special_fields)
     pub special_fields: ::protobuf::SpecialFields,
 }
 
-impl<'a> ::std::default::Default for &'a ShadowsocksOutboundSettings {
-    fn default() -> &'a ShadowsocksOutboundSettings {
-        <ShadowsocksOutboundSettings as ::protobuf::Message>::default_instance()
+impl<'a> ::std::default::Default for &'a ChainInboundSettings {
+    fn default() -> &'a ChainInboundSettings {
+        <ChainInboundSettings as ::protobuf::Message>::default_instance()
     }
 }
 
-impl ShadowsocksOutboundSettings {
-    pub fn new() -> ShadowsocksOutboundSettings {
+impl ChainInboundSettings {
+    pub fn new() -> ChainInboundSettings {
         ::std::default::Default::default()
     }
 }
 
-impl ::protobuf::Message for ShadowsocksOutboundSettings {
-    const NAME: &'static str = "ShadowsocksOutboundSettings";
+impl ::protobuf::Message for ChainInboundSettings {
+    const NAME: &'static str = "ChainInboundSettings";
 
     fn is_initialized(&self) -> bool {
         true
@@ -2424,19 +3489,7 @@ impl ::protobuf::Message for ShadowsocksOutboundSettings {
         while let Some(tag) = is.read_raw_tag_or_eof()? {
             match tag {
                 10 => {
-                    self.address = is.read_string()?;
-                },
-                16 => {
-                    self.port = is.read_uint32()?;
-                },
-                26 => {
-                    self.method = is.read_string()?;
-                },
-                34 => {
-                    self.password = is.read_string()?;
-                },
-                42 => {
-                    self.prefix = ::std::option::Option::Some(is.read_string()?);
+                    self.actors.push(is.read_string()?);
                 },
                 tag => {
                     ::protobuf::rt::read_unknown_or_skip_group(tag, is, self.special_fields.mut_unknown_fields())?;
@@ -2450,42 +3503,18 @@ impl ::protobuf::Message for ShadowsocksOutboundSettings {
     #[allow(unused_variables)]
     fn compute_size(&self) -> u64 {
         let mut my_size = 0;
-        if !self.address.is_empty() {
-            my_size += ::protobuf::rt::string_size(1, &self.address);
-        }
-        if self.port != 0 {
-            my_size += ::protobuf::rt::uint32_size(2, self.port);
-        }
-        if !self.method.is_empty() {
-            my_size += ::protobuf::rt::string_size(3, &self.method);
-        }
-        if !self.password.is_empty() {
-            my_size += ::protobuf::rt::string_size(4, &self.password);
-        }
-        if let Some(v) = self.prefix.as_ref() {
-            my_size += ::protobuf::rt::string_size(5, &v);
-        }
+        for value in &self.actors {
+            my_size += ::protobuf::rt::string_size(1, &value);
+        };
         my_size += ::protobuf::rt::unknown_fields_size(self.special_fields.unknown_fields());
         self.special_fields.cached_size().set(my_size as u32);
         my_size
     }
 
     fn write_to_with_cached_sizes(&self, os: &mut ::protobuf::CodedOutputStream<'_>) -> ::protobuf::Result<()> {
-        if !self.address.is_empty() {
-            os.write_string(1, &self.address)?;
-        }
-        if self.port != 0 {
-            os.write_uint32(2, self.port)?;
-        }
-        if !self.method.is_empty() {
-            os.write_string(3, &self.method)?;
-        }
-        if !self.password.is_empty() {
-            os.write_string(4, &self.password)?;
-        }
-        if let Some(v) = self.prefix.as_ref() {
-            os.write_string(5, v)?;
-        }
+        for v in &self.actors {
+            os.write_string(1, &v)?;
+        };
         os.write_unknown_fields(self.special_fields.unknown_fields())?;
         ::std::result::Result::Ok(())
     }
@@ -2498,61 +3527,46 @@ impl ::protobuf::Message for ShadowsocksOutboundSettings {
         &mut self.special_fields
     }
 
-    fn new() -> ShadowsocksOutboundSettings {
-        ShadowsocksOutboundSettings::new()
+    fn new() -> ChainInboundSettings {
+        ChainInboundSettings::new()
     }
 
     fn clear(&mut self) {
-        self.address.clear();
-        self.port = 0;
-        self.method.clear();
-        self.password.clear();
-        self.prefix = ::std::option::Option::None;
+        self.actors.clear();
         self.special_fields.clear();
     }
 
-    fn default_instance() -> &'static ShadowsocksOutboundSettings {
-        static instance: ShadowsocksOutboundSettings = ShadowsocksOutboundSettings {
-            address: ::std::string::String::new(),
-            port: 0,
-            method: ::std::string::String::new(),
-            password: ::std::string::String::new(),
-            prefix: ::std::option::Option::None,
+    fn default_instance() -> &'static ChainInboundSettings {
+        static instance: ChainInboundSettings = ChainInboundSettings {
+            actors: ::std::vec::Vec::new(),
             special_fields: ::protobuf::SpecialFields::new(),
         };
         &instance
     }
 }
 
-// @@protoc_insertion_point(message:ObfsOutboundSettings)
+// @@protoc_insertion_point(message:MptpInboundSettings)
 #[derive(PartialEq,Clone,Default,Debug)]
-pub struct ObfsOutboundSettings {
-    // message fields
-    // @@protoc_insertion_point(field:ObfsOutboundSettings.method)
-    pub method: ::std::string::String,
-    // @@protoc_insertion_point(field:ObfsOutboundSettings.host)
-    pub host: ::std::string::String,
-    // @@protoc_insertion_point(field:ObfsOutboundSettings.path)
-    pub path: ::std::string::String,
+pub struct MptpInboundSettings {
     // special fields
-    // @@protoc_insertion_point(special_field:ObfsOutboundSettings.special_fields)
+    // @@protoc_insertion_point(special_field:MptpInboundSettings.special_fields)
     pub special_fields: ::protobuf::SpecialFields,
 }
 
-impl<'a> ::std::default::Default for &'a ObfsOutboundSettings {
-    fn default() -> &'a ObfsOutboundSettings {
-        <ObfsOutboundSettings as ::protobuf::Message>::default_instance()
+impl<'a> ::std::default::Default for &'a MptpInboundSettings {
+    fn default() -> &'a MptpInboundSettings {
+        <MptpInboundSettings as ::protobuf::Message>::default_instance()
     }
 }
 
-impl ObfsOutboundSettings {
-    pub fn new() -> ObfsOutboundSettings {
+impl MptpInboundSettings {
+    pub fn new() -> MptpInboundSettings {
         ::std::default::Default::default()
     }
 }
 
-impl ::protobuf::Message for ObfsOutboundSettings {
-    const NAME: &'static str = "ObfsOutboundSettings";
+impl ::protobuf::Message for MptpInboundSettings {
+    const NAME: &'static str = "MptpInboundSettings";
 
     fn is_initialized(&self) -> bool {
         true
@@ -2561,15 +3575,6 @@ impl ::protobuf::Message for ObfsOutboundSettings {
     fn merge_from(&mut self, is: &mut ::protobuf::CodedInputStream<'_>) -> ::protobuf::Result<()> {
         while let Some(tag) = is.read_raw_tag_or_eof()? {
             match tag {
-                10 => {
-                    self.method = is.read_string()?;
-                },
-                18 => {
-                    self.host = is.read_string()?;
-                },
-                26 => {
-                    self.path = is.read_string()?;
-                },
                 tag => {
                     ::protobuf::rt::read_unknown_or_skip_group(tag, is, self.special_fields.mut_unknown_fields())?;
                 },
@@ -2582,29 +3587,2211 @@ impl ::protobuf::Message for ObfsOutboundSettings {
     #[allow(unused_variables)]
     fn compute_size(&self) -> u64 {
         let mut my_size = 0;
-        if !self.method.is_empty() {
-            my_size += ::protobuf::rt::string_size(1, &self.method);
-        }
-        if !self.host.is_empty() {
-            my_size += ::protobuf::rt::string_size(2, &self.host);
-        }
-        if !self.path.is_empty() {
-            my_size += ::protobuf::rt::string_size(3, &self.path);
-        }
         my_size += ::protobuf::rt::unknown_fields_size(self.special_fields.unknown_fields());
         self.special_fields.cached_size().set(my_size as u32);
         my_size
     }
 
     fn write_to_with_cached_sizes(&self, os: &mut ::protobuf::CodedOutputStream<'_>) -> ::protobuf::Result<()> {
-        if !self.method.is_empty() {
-            os.write_string(1, &self.method)?;
-        }
-        if !self.host.is_empty() {
-            os.write_string(2, &self.host)?;
+        os.write_unknown_fields(self.special_fields.unknown_fields())?;
+        ::std::result::Result::Ok(())
+    }
+
+    fn special_fields(&self) -> &::protobuf::SpecialFields {
+        &self.special_fields
+    }
+
+    fn mut_special_fields(&mut self) -> &mut ::protobuf::SpecialFields {
+        &mut self.special_fields
+    }
+
+    fn new() -> MptpInboundSettings {
+        MptpInboundSettings::new()
+    }
+
+    fn clear(&mut self) {
+        self.special_fields.clear();
+    }
+
+    fn default_instance() -> &'static MptpInboundSettings {
+        static instance: MptpInboundSettings = MptpInboundSettings {
+            special_fields: ::protobuf::SpecialFields::new(),
+        };
+        &instance
+    }
+}
+
+// @@protoc_insertion_point(message:HcInboundSettings)
+#[derive(PartialEq,Clone,Default,Debug)]
+pub struct HcInboundSettings {
+    // message fields
+    // @@protoc_insertion_point(field:HcInboundSettings.path)
+    pub path: ::std::string::String,
+    // @@protoc_insertion_point(field:HcInboundSettings.request)
+    pub request: ::std::string::String,
+    // @@protoc_insertion_point(field:HcInboundSettings.response)
+    pub response: ::std::string::String,
+    // special fields
+    // @@protoc_insertion_point(special_field:HcInboundSettings.special_fields)
+    pub special_fields: ::protobuf::SpecialFields,
+}
+
+impl<'a> ::std::default::Default for &'a HcInboundSettings {
+    fn default() -> &'a HcInboundSettings {
+        <HcInboundSettings as ::protobuf::Message>::default_instance()
+    }
+}
+
+impl HcInboundSettings {
+    pub fn new() -> HcInboundSettings {
+        ::std::default::Default::default()
+    }
+}
+
+impl ::protobuf::Message for HcInboundSettings {
+    const NAME: &'static str = "HcInboundSettings";
+
+    fn is_initialized(&self) -> bool {
+        true
+    }
+
+    fn merge_from(&mut self, is: &mut ::protobuf::CodedInputStream<'_>) -> ::protobuf::Result<()> {
+        while let Some(tag) = is.read_raw_tag_or_eof()? {
+            match tag {
+                10 => {
+                    self.path = is.read_string()?;
+                },
+                18 => {
+                    self.request = is.read_string()?;
+                },
+                26 => {
+                    self.response = is.read_string()?;
+                },
+                tag => {
+                    ::protobuf::rt::read_unknown_or_skip_group(tag, is, self.special_fields.mut_unknown_fields())?;
+                },
+            };
+        }
+        ::std::result::Result::Ok(())
+    }
+
+    // Compute sizes of nested messages
+    #[allow(unused_variables)]
+    fn compute_size(&self) -> u64 {
+        let mut my_size = 0;
+        if !self.path.is_empty() {
+            my_size += ::protobuf::rt::string_size(1, &self.path);
+        }
+        if !self.request.is_empty() {
+            my_size += ::protobuf::rt::string_size(2, &self.request);
+        }
+        if !self.response.is_empty() {
+            my_size += ::protobuf::rt::string_size(3, &self.response);
+        }
+        my_size += ::protobuf::rt::unknown_fields_size(self.special_fields.unknown_fields());
+        self.special_fields.cached_size().set(my_size as u32);
+        my_size
+    }
+
+    fn write_to_with_cached_sizes(&self, os: &mut ::protobuf::CodedOutputStream<'_>) -> ::protobuf::Result<()> {
+        if !self.path.is_empty() {
+            os.write_string(1, &self.path)?;
+        }
+        if !self.request.is_empty() {
+            os.write_string(2, &self.request)?;
+        }
+        if !self.response.is_empty() {
+            os.write_string(3, &self.response)?;
+        }
+        os.write_unknown_fields(self.special_fields.unknown_fields())?;
+        ::std::result::Result::Ok(())
+    }
+
+    fn special_fields(&self) -> &::protobuf::SpecialFields {
+        &self.special_fields
+    }
+
+    fn mut_special_fields(&mut self) -> &mut ::protobuf::SpecialFields {
+        &mut self.special_fields
+    }
+
+    fn new() -> HcInboundSettings {
+        HcInboundSettings::new()
+    }
+
+    fn clear(&mut self) {
+        self.path.clear();
+        self.request.clear();
+        self.response.clear();
+        self.special_fields.clear();
+    }
+
+    fn default_instance() -> &'static HcInboundSettings {
+        static instance: HcInboundSettings = HcInboundSettings {
+            path: ::std::string::String::new(),
+            request: ::std::string::String::new(),
+            response: ::std::string::String::new(),
+            special_fields: ::protobuf::SpecialFields::new(),
+        };
+        &instance
+    }
+}
+
+// @@protoc_insertion_point(message:Inbound)
+#[derive(PartialEq,Clone,Default,Debug)]
+pub struct Inbound {
+    // message fields
+    // @@protoc_insertion_point(field:Inbound.tag)
+    pub tag: ::std::string::String,
+    // @@protoc_insertion_point(field:Inbound.protocol)
+    pub protocol: ::std::string::String,
+    // @@protoc_insertion_point(field:Inbound.address)
+    pub address: ::std::string::String,
+    // @@protoc_insertion_point(field:Inbound.port)
+    pub port: u32,
+    // @@protoc_insertion_point(field:Inbound.settings)
+    pub settings: ::std::vec::Vec<u8>,
+    // @@protoc_insertion_point(field:Inbound.final_outbound)
+    pub final_outbound: ::std::string::String,
+    // special fields
+    // @@protoc_insertion_point(special_field:Inbound.special_fields)
+    pub special_fields: ::protobuf::SpecialFields,
+}
+
+impl<'a> ::std::default::Default for &'a Inbound {
+    fn default() -> &'a Inbound {
+        <Inbound as ::protobuf::Message>::default_instance()
+    }
+}
+
+impl Inbound {
+    pub fn new() -> Inbound {
+        ::std::default::Default::default()
+    }
+}
+
+impl ::protobuf::Message for Inbound {
+    const NAME: &'static str = "Inbound";
+
+    fn is_initialized(&self) -> bool {
+        true
+    }
+
+    fn merge_from(&mut self, is: &mut ::protobuf::CodedInputStream<'_>) -> ::protobuf::Result<()> {
+        while let Some(tag) = is.read_raw_tag_or_eof()? {
+            match tag {
+                10 => {
+                    self.tag = is.read_string()?;
+                },
+                18 => {
+                    self.protocol = is.read_string()?;
+                },
+                26 => {
+                    self.address = is.read_string()?;
+                },
+                32 => {
+                    self.port = is.read_uint32()?;
+                },
+                42 => {
+                    self.settings = is.read_bytes()?;
+                },
+                50 => {
+                    self.final_outbound = is.read_string()?;
+                },
+                tag => {
+                    ::protobuf::rt::read_unknown_or_skip_group(tag, is, self.special_fields.mut_unknown_fields())?;
+                },
+            };
+        }
+        ::std::result::Result::Ok(())
+    }
+
+    // Compute sizes of nested messages
+    #[allow(unused_variables)]
+    fn compute_size(&self) -> u64 {
+        let mut my_size = 0;
+        if !self.tag.is_empty() {
+            my_size += ::protobuf::rt::string_size(1, &self.tag);
+        }
+        if !self.protocol.is_empty() {
+            my_size += ::protobuf::rt::string_size(2, &self.protocol);
+        }
+        if !self.address.is_empty() {
+            my_size += ::protobuf::rt::string_size(3, &self.address);
+        }
+        if self.port != 0 {
+            my_size += ::protobuf::rt::uint32_size(4, self.port);
+        }
+        if !self.settings.is_empty() {
+            my_size += ::protobuf::rt::bytes_size(5, &self.settings);
+        }
+        if !self.final_outbound.is_empty() {
+            my_size += ::protobuf::rt::string_size(6, &self.final_outbound);
+        }
+        my_size += ::protobuf::rt::unknown_fields_size(self.special_fields.unknown_fields());
+        self.special_fields.cached_size().set(my_size as u32);
+        my_size
+    }
+
+    fn write_to_with_cached_sizes(&self, os: &mut ::protobuf::CodedOutputStream<'_>) -> ::protobuf::Result<()> {
+        if !self.tag.is_empty() {
+            os.write_string(1, &self.tag)?;
+        }
+        if !self.protocol.is_empty() {
+            os.write_string(2, &self.protocol)?;
+        }
+        if !self.address.is_empty() {
+            os.write_string(3, &self.address)?;
+        }
+        if self.port != 0 {
+            os.write_uint32(4, self.port)?;
+        }
+        if !self.settings.is_empty() {
+            os.write_bytes(5, &self.settings)?;
+        }
+        if !self.final_outbound.is_empty() {
+            os.write_string(6, &self.final_outbound)?;
+        }
+        os.write_unknown_fields(self.special_fields.unknown_fields())?;
+        ::std::result::Result::Ok(())
+    }
+
+    fn special_fields(&self) -> &::protobuf::SpecialFields {
+        &self.special_fields
+    }
+
+    fn mut_special_fields(&mut self) -> &mut ::protobuf::SpecialFields {
+        &mut self.special_fields
+    }
+
+    fn new() -> Inbound {
+        Inbound::new()
+    }
+
+    fn clear(&mut self) {
+        self.tag.clear();
+        self.protocol.clear();
+        self.address.clear();
+        self.port = 0;
+        self.settings.clear();
+        self.final_outbound.clear();
+        self.special_fields.clear();
+    }
+
+    fn default_instance() -> &'static Inbound {
+        static instance: Inbound = Inbound {
+            tag: ::std::string::String::new(),
+            protocol: ::std::string::String::new(),
+            address: ::std::string::String::new(),
+            port: 0,
+            settings: ::std::vec::Vec::new(),
+            final_outbound: ::std::string::String::new(),
+            special_fields: ::protobuf::SpecialFields::new(),
+        };
+        &instance
+    }
+}
+
+// @@protoc_insertion_point(message:RedirectOutboundSettings)
+#[derive(PartialEq,Clone,Default,Debug)]
+pub struct RedirectOutboundSettings {
+    // message fields
+    // @@protoc_insertion_point(field:RedirectOutboundSettings.address)
+    pub address: ::std::string::String,
+    // @@protoc_insertion_point(field:RedirectOutboundSettings.port)
+    pub port: u32,
+    // special fields
+    // @@protoc_insertion_point(special_field:RedirectOutboundSettings.special_fields)
+    pub special_fields: ::protobuf::SpecialFields,
+}
+
+impl<'a> ::std::default::Default for &'a RedirectOutboundSettings {
+    fn default() -> &'a RedirectOutboundSettings {
+        <RedirectOutboundSettings as ::protobuf::Message>::default_instance()
+    }
+}
+
+impl RedirectOutboundSettings {
+    pub fn new() -> RedirectOutboundSettings {
+        ::std::default::Default::default()
+    }
+}
+
+impl ::protobuf::Message for RedirectOutboundSettings {
+    const NAME: &'static str = "RedirectOutboundSettings";
+
+    fn is_initialized(&self) -> bool {
+        true
+    }
+
+    fn merge_from(&mut self, is: &mut ::protobuf::CodedInputStream<'_>) -> ::protobuf::Result<()> {
+        while let Some(tag) = is.read_raw_tag_or_eof()? {
+            match tag {
+                10 => {
+                    self.address = is.read_string()?;
+                },
+                16 => {
+                    self.port = is.read_uint32()?;
+                },
+                tag => {
+                    ::protobuf::rt::read_unknown_or_skip_group(tag, is, self.special_fields.mut_unknown_fields())?;
+                },
+            };
+        }
+        ::std::result::Result::Ok(())
+    }
+
+    // Compute sizes of nested messages
+    #[allow(unused_variables)]
+    fn compute_size(&self) -> u64 {
+        let mut my_size = 0;
+        if !self.address.is_empty() {
+            my_size += ::protobuf::rt::string_size(1, &self.address);
+        }
+        if self.port != 0 {
+            my_size += ::protobuf::rt::uint32_size(2, self.port);
+        }
+        my_size += ::protobuf::rt::unknown_fields_size(self.special_fields.unknown_fields());
+        self.special_fields.cached_size().set(my_size as u32);
+        my_size
+    }
+
+    fn write_to_with_cached_sizes(&self, os: &mut ::protobuf::CodedOutputStream<'_>) -> ::protobuf::Result<()> {
+        if !self.address.is_empty() {
+            os.write_string(1, &self.address)?;
+        }
+        if self.port != 0 {
+            os.write_uint32(2, self.port)?;
+        }
+        os.write_unknown_fields(self.special_fields.unknown_fields())?;
+        ::std::result::Result::Ok(())
+    }
+
+    fn special_fields(&self) -> &::protobuf::SpecialFields {
+        &self.special_fields
+    }
+
+    fn mut_special_fields(&mut self) -> &mut ::protobuf::SpecialFields {
+        &mut self.special_fields
+    }
+
+    fn new() -> RedirectOutboundSettings {
+        RedirectOutboundSettings::new()
+    }
+
+    fn clear(&mut self) {
+        self.address.clear();
+        self.port = 0;
+        self.special_fields.clear();
+    }
+
+    fn default_instance() -> &'static RedirectOutboundSettings {
+        static instance: RedirectOutboundSettings = RedirectOutboundSettings {
+            address: ::std::string::String::new(),
+            port: 0,
+            special_fields: ::protobuf::SpecialFields::new(),
+        };
+        &instance
+    }
+}
+
+// @@protoc_insertion_point(message:SocksOutboundSettings)
+#[derive(PartialEq,Clone,Default,Debug)]
+pub struct SocksOutboundSettings {
+    // message fields
+    // @@protoc_insertion_point(field:SocksOutboundSettings.address)
+    pub address: ::std::string::String,
+    // @@protoc_insertion_point(field:SocksOutboundSettings.port)
+    pub port: u32,
+    // @@protoc_insertion_point(field:SocksOutboundSettings.username)
+    pub username: ::std::string::String,
+    // @@protoc_insertion_point(field:SocksOutboundSettings.password)
+    pub password: ::std::string::String,
+    // special fields
+    // @@protoc_insertion_point(special_field:SocksOutboundSettings.special_fields)
+    pub special_fields: ::protobuf::SpecialFields,
+}
+
+impl<'a> ::std::default::Default for &'a SocksOutboundSettings {
+    fn default() -> &'a SocksOutboundSettings {
+        <SocksOutboundSettings as ::protobuf::Message>::default_instance()
+    }
+}
+
+impl SocksOutboundSettings {
+    pub fn new() -> SocksOutboundSettings {
+        ::std::default::Default::default()
+    }
+}
+
+impl ::protobuf::Message for SocksOutboundSettings {
+    const NAME: &'static str = "SocksOutboundSettings";
+
+    fn is_initialized(&self) -> bool {
+        true
+    }
+
+    fn merge_from(&mut self, is: &mut ::protobuf::CodedInputStream<'_>) -> ::protobuf::Result<()> {
+        while let Some(tag) = is.read_raw_tag_or_eof()? {
+            match tag {
+                10 => {
+                    self.address = is.read_string()?;
+                },
+                16 => {
+                    self.port = is.read_uint32()?;
+                },
+                26 => {
+                    self.username = is.read_string()?;
+                },
+                34 => {
+                    self.password = is.read_string()?;
+                },
+                tag => {
+                    ::protobuf::rt::read_unknown_or_skip_group(tag, is, self.special_fields.mut_unknown_fields())?;
+                },
+            };
+        }
+        ::std::result::Result::Ok(())
+    }
+
+    // Compute sizes of nested messages
+    #[allow(unused_variables)]
+    fn compute_size(&self) -> u64 {
+        let mut my_size = 0;
+        if !self.address.is_empty() {
+            my_size += ::protobuf::rt::string_size(1, &self.address);
+        }
+        if self.port != 0 {
+            my_size += ::protobuf::rt::uint32_size(2, self.port);
+        }
+        if !self.username.is_empty() {
+            my_size += ::protobuf::rt::string_size(3, &self.username);
+        }
+        if !self.password.is_empty() {
+            my_size += ::protobuf::rt::string_size(4, &self.password);
+        }
+        my_size += ::protobuf::rt::unknown_fields_size(self.special_fields.unknown_fields());
+        self.special_fields.cached_size().set(my_size as u32);
+        my_size
+    }
+
+    fn write_to_with_cached_sizes(&self, os: &mut ::protobuf::CodedOutputStream<'_>) -> ::protobuf::Result<()> {
+        if !self.address.is_empty() {
+            os.write_string(1, &self.address)?;
+        }
+        if self.port != 0 {
+            os.write_uint32(2, self.port)?;
+        }
+        if !self.username.is_empty() {
+            os.write_string(3, &self.username)?;
+        }
+        if !self.password.is_empty() {
+            os.write_string(4, &self.password)?;
+        }
+        os.write_unknown_fields(self.special_fields.unknown_fields())?;
+        ::std::result::Result::Ok(())
+    }
+
+    fn special_fields(&self) -> &::protobuf::SpecialFields {
+        &self.special_fields
+    }
+
+    fn mut_special_fields(&mut self) -> &mut ::protobuf::SpecialFields {
+        &mut self.special_fields
+    }
+
+    fn new() -> SocksOutboundSettings {
+        SocksOutboundSettings::new()
+    }
+
+    fn clear(&mut self) {
+        self.address.clear();
+        self.port = 0;
+        self.username.clear();
+        self.password.clear();
+        self.special_fields.clear();
+    }
+
+    fn default_instance() -> &'static SocksOutboundSettings {
+        static instance: SocksOutboundSettings = SocksOutboundSettings {
+            address: ::std::string::String::new(),
+            port: 0,
+            username: ::std::string::String::new(),
+            password: ::std::string::String::new(),
+            special_fields: ::protobuf::SpecialFields::new(),
+        };
+        &instance
+    }
+}
+
+// @@protoc_insertion_point(message:HttpOutboundSettings)
+#[derive(PartialEq,Clone,Default,Debug)]
+pub struct HttpOutboundSettings {
+    // message fields
+    // @@protoc_insertion_point(field:HttpOutboundSettings.address)
+    pub address: ::std::string::String,
+    // @@protoc_insertion_point(field:HttpOutboundSettings.port)
+    pub port: u32,
+    // @@protoc_insertion_point(field:HttpOutboundSettings.username)
+    pub username: ::std::string::String,
+    // @@protoc_insertion_point(field:HttpOutboundSettings.password)
+    pub password: ::std::string::String,
+    // @@protoc_insertion_point(field:HttpOutboundSettings.headers)
+    pub headers: ::std::collections::HashMap<::std::string::String, ::std::string::String>,
+    // special fields
+    // @@protoc_insertion_point(special_field:HttpOutboundSettings.special_fields)
+    pub special_fields: ::protobuf::SpecialFields,
+}
+
+impl<'a> ::std::default::Default for &'a HttpOutboundSettings {
+    fn default() -> &'a HttpOutboundSettings {
+        <HttpOutboundSettings as ::protobuf::Message>::default_instance()
+    }
+}
+
+impl HttpOutboundSettings {
+    pub fn new() -> HttpOutboundSettings {
+        ::std::default::Default::default()
+    }
+}
+
+impl ::protobuf::Message for HttpOutboundSettings {
+    const NAME: &'static str = "HttpOutboundSettings";
+
+    fn is_initialized(&self) -> bool {
+        true
+    }
+
+    fn merge_from(&mut self, is: &mut ::protobuf::CodedInputStream<'_>) -> ::protobuf::Result<()> {
+        while let Some(tag) = is.read_raw_tag_or_eof()? {
+            match tag {
+                10 => {
+                    self.address = is.read_string()?;
+                },
+                16 => {
+                    self.port = is.read_uint32()?;
+                },
+                26 => {
+                    self.username = is.read_string()?;
+                },
+                34 => {
+                    self.password = is.read_string()?;
+                },
+                42 => {
+                    let len = is.read_raw_varint32()?;
+                    let old_limit = is.push_limit(len as u64)?;
+                    let mut key = ::std::default::Default::default();
+                    let mut value = ::std::default::Default::default();
+                    while let Some(tag) = is.read_raw_tag_or_eof()? {
+                        match tag {
+                            10 => key = is.read_string()?,
+                            18 => value = is.read_string()?,
+                            _ => ::protobuf::rt::skip_field_for_tag(tag, is)?,
+                        };
+                    }
+                    is.pop_limit(old_limit);
+                    self.headers.insert(key, value);
+                },
+                tag => {
+                    ::protobuf::rt::read_unknown_or_skip_group(tag, is, self.special_fields.mut_unknown_fields())?;
+                },
+            };
+        }
+        ::std::result::Result::Ok(())
+    }
+
+    // Compute sizes of nested messages
+    #[allow(unused_variables)]
+    fn compute_size(&self) -> u64 {
+        let mut my_size = 0;
+        if !self.address.is_empty() {
+            my_size += ::protobuf::rt::string_size(1, &self.address);
+        }
+        if self.port != 0 {
+            my_size += ::protobuf::rt::uint32_size(2, self.port);
+        }
+        if !self.username.is_empty() {
+            my_size += ::protobuf::rt::string_size(3, &self.username);
+        }
+        if !self.password.is_empty() {
+            my_size += ::protobuf::rt::string_size(4, &self.password);
+        }
+        for (k, v) in &self.headers {
+            let mut entry_size = 0;
+            entry_size += ::protobuf::rt::string_size(1, &k);
+            entry_size += ::protobuf::rt::string_size(2, &v);
+            my_size += 1 + ::protobuf::rt::compute_raw_varint64_size(entry_size) + entry_size
+        };
+        my_size += ::protobuf::rt::unknown_fields_size(self.special_fields.unknown_fields());
+        self.special_fields.cached_size().set(my_size as u32);
+        my_size
+    }
+
+    fn write_to_with_cached_sizes(&self, os: &mut ::protobuf::CodedOutputStream<'_>) -> ::protobuf::Result<()> {
+        if !self.address.is_empty() {
+            os.write_string(1, &self.address)?;
+        }
+        if self.port != 0 {
+            os.write_uint32(2, self.port)?;
+        }
+        if !self.username.is_empty() {
+            os.write_string(3, &self.username)?;
+        }
+        if !self.password.is_empty() {
+            os.write_string(4, &self.password)?;
+        }
+        for (k, v) in &self.headers {
+            let mut entry_size = 0;
+            entry_size += ::protobuf::rt::string_size(1, &k);
+            entry_size += ::protobuf::rt::string_size(2, &v);
+            os.write_raw_varint32(42)?; // Tag.
+            os.write_raw_varint32(entry_size as u32)?;
+            os.write_string(1, &k)?;
+            os.write_string(2, &v)?;
+        };
+        os.write_unknown_fields(self.special_fields.unknown_fields())?;
+        ::std::result::Result::Ok(())
+    }
+
+    fn special_fields(&self) -> &::protobuf::SpecialFields {
+        &self.special_fields
+    }
+
+    fn mut_special_fields(&mut self) -> &mut ::protobuf::SpecialFields {
+        &mut self.special_fields
+    }
+
+    fn new() -> HttpOutboundSettings {
+        HttpOutboundSettings::new()
+    }
+
+    fn clear(&mut self) {
+        self.address.clear();
+        self.port = 0;
+        self.username.clear();
+        self.password.clear();
+        self.headers.clear();
+        self.special_fields.clear();
+    }
+
+    fn default_instance() -> &'static HttpOutboundSettings {
+        static instance: ::protobuf::rt::Lazy<HttpOutboundSettings> = ::protobuf::rt::Lazy::new();
+        instance.get(HttpOutboundSettings::new)
+    }
+}
+
+// @@protoc_insertion_point(message:ShadowsocksOutboundSettings)
+#[derive(PartialEq,Clone,Default,Debug)]
+pub struct ShadowsocksOutboundSettings {
+    // message fields
+    // @@protoc_insertion_point(field:ShadowsocksOutboundSettings.address)
+    pub address: ::std::string::String,
+    // @@protoc_insertion_point(field:ShadowsocksOutboundSettings.port)
+    pub port: u32,
+    // @@protoc_insertion_point(field:ShadowsocksOutboundSettings.method)
+    pub method: ::std::string::String,
+    // @@protoc_insertion_point(field:ShadowsocksOutboundSettings.password)
+    pub password: ::std::string::String,
+    // @@protoc_insertion_point(field:ShadowsocksOutboundSettings.prefix)
+    pub prefix: ::std::option::Option<::std::string::String>,
+    // @@protoc_insertion_point(field:ShadowsocksOutboundSettings.plugin)
+    pub plugin: ::std::option::Option<::std::string::String>,
+    // @@protoc_insertion_point(field:ShadowsocksOutboundSettings.plugin_opts)
+    pub plugin_opts: ::std::option::Option<::std::string::String>,
+    // @@protoc_insertion_point(field:ShadowsocksOutboundSettings.udp_over_tcp)
+    pub udp_over_tcp: bool,
+    // special fields
+    // @@protoc_insertion_point(special_field:ShadowsocksOutboundSettings.special_fields)
+    pub special_fields: ::protobuf::SpecialFields,
+}
+
+impl<'a> ::std::default::Default for &'a ShadowsocksOutboundSettings {
+    fn default() -> &'a ShadowsocksOutboundSettings {
+        <ShadowsocksOutboundSettings as ::protobuf::Message>::default_instance()
+    }
+}
+
+impl ShadowsocksOutboundSettings {
+    pub fn new() -> ShadowsocksOutboundSettings {
+        ::std::default::Default::default()
+    }
+}
+
+impl ::protobuf::Message for ShadowsocksOutboundSettings {
+    const NAME: &'static str = "ShadowsocksOutboundSettings";
+
+    fn is_initialized(&self) -> bool {
+        true
+    }
+
+    fn merge_from(&mut self, is: &mut ::protobuf::CodedInputStream<'_>) -> ::protobuf::Result<()> {
+        while let Some(tag) = is.read_raw_tag_or_eof()? {
+            match tag {
+                10 => {
+                    self.address = is.read_string()?;
+                },
+                16 => {
+                    self.port = is.read_uint32()?;
+                },
+                26 => {
+                    self.method = is.read_string()?;
+                },
+                34 => {
+                    self.password = is.read_string()?;
+                },
+                42 => {
+                    self.prefix = ::std::option::Option::Some(is.read_string()?);
+                },
+                50 => {
+                    self.plugin = ::std::option::Option::Some(is.read_string()?);
+                },
+                58 => {
+                    self.plugin_opts = ::std::option::Option::Some(is.read_string()?);
+                },
+                64 => {
+                    self.udp_over_tcp = is.read_bool()?;
+                },
+                tag => {
+                    ::protobuf::rt::read_unknown_or_skip_group(tag, is, self.special_fields.mut_unknown_fields())?;
+                },
+            };
+        }
+        ::std::result::Result::Ok(())
+    }
+
+    // Compute sizes of nested messages
+    #[allow(unused_variables)]
+    fn compute_size(&self) -> u64 {
+        let mut my_size = 0;
+        if !self.address.is_empty() {
+            my_size += ::protobuf::rt::string_size(1, &self.address);
+        }
+        if self.port != 0 {
+            my_size += ::protobuf::rt::uint32_size(2, self.port);
+        }
+        if !self.method.is_empty() {
+            my_size += ::protobuf::rt::string_size(3, &self.method);
+        }
+        if !self.password.is_empty() {
+            my_size += ::protobuf::rt::string_size(4, &self.password);
+        }
+        if let Some(v) = self.prefix.as_ref() {
+            my_size += ::protobuf::rt::string_size(5, &v);
+        }
+        if let Some(v) = self.plugin.as_ref() {
+            my_size += ::protobuf::rt::string_size(6, &v);
+        }
+        if let Some(v) = self.plugin_opts.as_ref() {
+            my_size += ::protobuf::rt::string_size(7, &v);
+        }
+        if self.udp_over_tcp != false {
+            my_size += 1 + 1;
+        }
+        my_size += ::protobuf::rt::unknown_fields_size(self.special_fields.unknown_fields());
+        self.special_fields.cached_size().set(my_size as u32);
+        my_size
+    }
+
+    fn write_to_with_cached_sizes(&self, os: &mut ::protobuf::CodedOutputStream<'_>) -> ::protobuf::Result<()> {
+        if !self.address.is_empty() {
+            os.write_string(1, &self.address)?;
+        }
+        if self.port != 0 {
+            os.write_uint32(2, self.port)?;
+        }
+        if !self.method.is_empty() {
+            os.write_string(3, &self.method)?;
+        }
+        if !self.password.is_empty() {
+            os.write_string(4, &self.password)?;
+        }
+        if let Some(v) = self.prefix.as_ref() {
+            os.write_string(5, v)?;
+        }
+        if let Some(v) = self.plugin.as_ref() {
+            os.write_string(6, v)?;
+        }
+        if let Some(v) = self.plugin_opts.as_ref() {
+            os.write_string(7, v)?;
+        }
+        if self.udp_over_tcp != false {
+            os.write_bool(8, self.udp_over_tcp)?;
+        }
+        os.write_unknown_fields(self.special_fields.unknown_fields())?;
+        ::std::result::Result::Ok(())
+    }
+
+    fn special_fields(&self) -> &::protobuf::SpecialFields {
+        &self.special_fields
+    }
+
+    fn mut_special_fields(&mut self) -> &mut ::protobuf::SpecialFields {
+        &mut self.special_fields
+    }
+
+    fn new() -> ShadowsocksOutboundSettings {
+        ShadowsocksOutboundSettings::new()
+    }
+
+    fn clear(&mut self) {
+        self.address.clear();
+        self.port = 0;
+        self.method.clear();
+        self.password.clear();
+        self.prefix = ::std::option::Option::None;
+        self.plugin = ::std::option::Option::None;
+        self.plugin_opts = ::std::option::Option::None;
+        self.udp_over_tcp = false;
+        self.special_fields.clear();
+    }
+
+    fn default_instance() -> &'static ShadowsocksOutboundSettings {
+        static instance: ShadowsocksOutboundSettings = ShadowsocksOutboundSettings {
+            address: ::std::string::String::new(),
+            port: 0,
+            method: ::std::string::String::new(),
+            password: ::std::string::String::new(),
+            prefix: ::std::option::Option::None,
+            plugin: ::std::option::Option::None,
+            plugin_opts: ::std::option::Option::None,
+            udp_over_tcp: false,
+            special_fields: ::protobuf::SpecialFields::new(),
+        };
+        &instance
+    }
+}
+
+// @@protoc_insertion_point(message:ObfsOutboundSettings)
+#[derive(PartialEq,Clone,Default,Debug)]
+pub struct ObfsOutboundSettings {
+    // message fields
+    // @@protoc_insertion_point(field:ObfsOutboundSettings.method)
+    pub method: ::std::string::String,
+    // @@protoc_insertion_point(field:ObfsOutboundSettings.host)
+    pub host: ::std::string::String,
+    // @@protoc_insertion_point(field:ObfsOutboundSettings.path)
+    pub path: ::std::string::String,
+    // special fields
+    // @@protoc_insertion_point(special_field:ObfsOutboundSettings.special_fields)
+    pub special_fields: ::protobuf::SpecialFields,
+}
+
+impl<'a> ::std::default::Default for &'a ObfsOutboundSettings {
+    fn default() -> &'a ObfsOutboundSettings {
+        <ObfsOutboundSettings as ::protobuf::Message>::default_instance()
+    }
+}
+
+impl ObfsOutboundSettings {
+    pub fn new() -> ObfsOutboundSettings {
+        ::std::default::Default::default()
+    }
+}
+
+impl ::protobuf::Message for ObfsOutboundSettings {
+    const NAME: &'static str = "ObfsOutboundSettings";
+
+    fn is_initialized(&self) -> bool {
+        true
+    }
+
+    fn merge_from(&mut self, is: &mut ::protobuf::CodedInputStream<'_>) -> ::protobuf::Result<()> {
+        while let Some(tag) = is.read_raw_tag_or_eof()? {
+            match tag {
+                10 => {
+                    self.method = is.read_string()?;
+                },
+                18 => {
+                    self.host = is.read_string()?;
+                },
+                26 => {
+                    self.path = is.read_string()?;
+                },
+                tag => {
+                    ::protobuf::rt::read_unknown_or_skip_group(tag, is, self.special_fields.mut_unknown_fields())?;
+                },
+            };
+        }
+        ::std::result::Result::Ok(())
+    }
+
+    // Compute sizes of nested messages
+    #[allow(unused_variables)]
+    fn compute_size(&self) -> u64 {
+        let mut my_size = 0;
+        if !self.method.is_empty() {
+            my_size += ::protobuf::rt::string_size(1, &self.method);
+        }
+        if !self.host.is_empty() {
+            my_size += ::protobuf::rt::string_size(2, &self.host);
+        }
+        if !self.path.is_empty() {
+            my_size += ::protobuf::rt::string_size(3, &self.path);
+        }
+        my_size += ::protobuf::rt::unknown_fields_size(self.special_fields.unknown_fields());
+        self.special_fields.cached_size().set(my_size as u32);
+        my_size
+    }
+
+    fn write_to_with_cached_sizes(&self, os: &mut ::protobuf::CodedOutputStream<'_>) -> ::protobuf::Result<()> {
+        if !self.method.is_empty() {
+            os.write_string(1, &self.method)?;
+        }
+        if !self.host.is_empty() {
+            os.write_string(2, &self.host)?;
+        }
+        if !self.path.is_empty() {
+            os.write_string(3, &self.path)?;
+        }
+        os.write_unknown_fields(self.special_fields.unknown_fields())?;
+        ::std::result::Result::Ok(())
+    }
+
+    fn special_fields(&self) -> &::protobuf::SpecialFields {
+        &self.special_fields
+    }
+
+    fn mut_special_fields(&mut self) -> &mut ::protobuf::SpecialFields {
+        &mut self.special_fields
+    }
+
+    fn new() -> ObfsOutboundSettings {
+        ObfsOutboundSettings::new()
+    }
+
+    fn clear(&mut self) {
+        self.method.clear();
+        self.host.clear();
+        self.path.clear();
+        self.special_fields.clear();
+    }
+
+    fn default_instance() -> &'static ObfsOutboundSettings {
+        static instance: ObfsOutboundSettings = ObfsOutboundSettings {
+            method: ::std::string::String::new(),
+            host: ::std::string::String::new(),
+            path: ::std::string::String::new(),
+            special_fields: ::protobuf::SpecialFields::new(),
+        };
+        &instance
+    }
+}
+
+// @@protoc_insertion_point(message:TrojanOutboundSettings)
+#[derive(PartialEq,Clone,Default,Debug)]
+pub struct TrojanOutboundSettings {
+    // message fields
+    // @@protoc_insertion_point(field:TrojanOutboundSettings.address)
+    pub address: ::std::string::String,
+    // @@protoc_insertion_point(field:TrojanOutboundSettings.port)
+    pub port: u32,
+    // @@protoc_insertion_point(field:TrojanOutboundSettings.password)
+    pub password: ::std::string::String,
+    // special fields
+    // @@protoc_insertion_point(special_field:TrojanOutboundSettings.special_fields)
+    pub special_fields: ::protobuf::SpecialFields,
+}
+
+impl<'a> ::std::default::Default for &'a TrojanOutboundSettings {
+    fn default() -> &'a TrojanOutboundSettings {
+        <TrojanOutboundSettings as ::protobuf::Message>::default_instance()
+    }
+}
+
+impl TrojanOutboundSettings {
+    pub fn new() -> TrojanOutboundSettings {
+        ::std::default::Default::default()
+    }
+}
+
+impl ::protobuf::Message for TrojanOutboundSettings {
+    const NAME: &'static str = "TrojanOutboundSettings";
+
+    fn is_initialized(&self) -> bool {
+        true
+    }
+
+    fn merge_from(&mut self, is: &mut ::protobuf::CodedInputStream<'_>) -> ::protobuf::Result<()> {
+        while let Some(tag) = is.read_raw_tag_or_eof()? {
+            match tag {
+                10 => {
+                    self.address = is.read_string()?;
+                },
+                16 => {
+                    self.port = is.read_uint32()?;
+                },
+                26 => {
+                    self.password = is.read_string()?;
+                },
+                tag => {
+                    ::protobuf::rt::read_unknown_or_skip_group(tag, is, self.special_fields.mut_unknown_fields())?;
+                },
+            };
+        }
+        ::std::result::Result::Ok(())
+    }
+
+    // Compute sizes of nested messages
+    #[allow(unused_variables)]
+    fn compute_size(&self) -> u64 {
+        let mut my_size = 0;
+        if !self.address.is_empty() {
+            my_size += ::protobuf::rt::string_size(1, &self.address);
+        }
+        if self.port != 0 {
+            my_size += ::protobuf::rt::uint32_size(2, self.port);
+        }
+        if !self.password.is_empty() {
+            my_size += ::protobuf::rt::string_size(3, &self.password);
+        }
+        my_size += ::protobuf::rt::unknown_fields_size(self.special_fields.unknown_fields());
+        self.special_fields.cached_size().set(my_size as u32);
+        my_size
+    }
+
+    fn write_to_with_cached_sizes(&self, os: &mut ::protobuf::CodedOutputStream<'_>) -> ::protobuf::Result<()> {
+        if !self.address.is_empty() {
+            os.write_string(1, &self.address)?;
+        }
+        if self.port != 0 {
+            os.write_uint32(2, self.port)?;
+        }
+        if !self.password.is_empty() {
+            os.write_string(3, &self.password)?;
+        }
+        os.write_unknown_fields(self.special_fields.unknown_fields())?;
+        ::std::result::Result::Ok(())
+    }
+
+    fn special_fields(&self) -> &::protobuf::SpecialFields {
+        &self.special_fields
+    }
+
+    fn mut_special_fields(&mut self) -> &mut ::protobuf::SpecialFields {
+        &mut self.special_fields
+    }
+
+    fn new() -> TrojanOutboundSettings {
+        TrojanOutboundSettings::new()
+    }
+
+    fn clear(&mut self) {
+        self.address.clear();
+        self.port = 0;
+        self.password.clear();
+        self.special_fields.clear();
+    }
+
+    fn default_instance() -> &'static TrojanOutboundSettings {
+        static instance: TrojanOutboundSettings = TrojanOutboundSettings {
+            address: ::std::string::String::new(),
+            port: 0,
+            password: ::std::string::String::new(),
+            special_fields: ::protobuf::SpecialFields::new(),
+        };
+        &instance
+    }
+}
+
+// @@protoc_insertion_point(message:TlsOutboundSettings)
+#[derive(PartialEq,Clone,Default,Debug)]
+pub struct TlsOutboundSettings {
+    // message fields
+    // @@protoc_insertion_point(field:TlsOutboundSettings.server_name)
+    pub server_name: ::std::string::String,
+    // @@protoc_insertion_point(field:TlsOutboundSettings.alpn)
+    pub alpn: ::std::vec::Vec<::std::string::String>,
+    // @@protoc_insertion_point(field:TlsOutboundSettings.certificate)
+    pub certificate: ::std::string::String,
+    // @@protoc_insertion_point(field:TlsOutboundSettings.insecure)
+    pub insecure: bool,
+    // @@protoc_insertion_point(field:TlsOutboundSettings.certificate_key)
+    pub certificate_key: ::std::string::String,
+    // @@protoc_insertion_point(field:TlsOutboundSettings.ech_config_list)
+    pub ech_config_list: ::std::string::String,
+    // @@protoc_insertion_point(field:TlsOutboundSettings.ech)
+    pub ech: bool,
+    // @@protoc_insertion_point(field:TlsOutboundSettings.ech_disable_dns_lookup)
+    pub ech_disable_dns_lookup: bool,
+    // @@protoc_insertion_point(field:TlsOutboundSettings.certificate_fingerprint)
+    pub certificate_fingerprint: ::std::string::String,
+    // @@protoc_insertion_point(field:TlsOutboundSettings.certificate_pins)
+    pub certificate_pins: ::std::vec::Vec<::std::string::String>,
+    // @@protoc_insertion_point(field:TlsOutboundSettings.pin_only)
+    pub pin_only: bool,
+    // @@protoc_insertion_point(field:TlsOutboundSettings.allow_name_mismatch)
+    pub allow_name_mismatch: bool,
+    // @@protoc_insertion_point(field:TlsOutboundSettings.ech_required)
+    pub ech_required: bool,
+    // @@protoc_insertion_point(field:TlsOutboundSettings.client_certificate)
+    pub client_certificate: ::std::string::String,
+    // @@protoc_insertion_point(field:TlsOutboundSettings.client_certificate_key)
+    pub client_certificate_key: ::std::string::String,
+    // special fields
+    // @@protoc_insertion_point(special_field:TlsOutboundSettings.special_fields)
+    pub special_fields: ::protobuf::SpecialFields,
+}
+
+impl<'a> ::std::default::Default for &'a TlsOutboundSettings {
+    fn default() -> &'a TlsOutboundSettings {
+        <TlsOutboundSettings as ::protobuf::Message>::default_instance()
+    }
+}
+
+impl TlsOutboundSettings {
+    pub fn new() -> TlsOutboundSettings {
+        ::std::default::Default::default()
+    }
+}
+
+impl ::protobuf::Message for TlsOutboundSettings {
+    const NAME: &'static str = "TlsOutboundSettings";
+
+    fn is_initialized(&self) -> bool {
+        true
+    }
+
+    fn merge_from(&mut self, is: &mut ::protobuf::CodedInputStream<'_>) -> ::protobuf::Result<()> {
+        while let Some(tag) = is.read_raw_tag_or_eof()? {
+            match tag {
+                10 => {
+                    self.server_name = is.read_string()?;
+                },
+                18 => {
+                    self.alpn.push(is.read_string()?);
+                },
+                26 => {
+                    self.certificate = is.read_string()?;
+                },
+                32 => {
+                    self.insecure = is.read_bool()?;
+                },
+                42 => {
+                    self.certificate_key = is.read_string()?;
+                },
+                50 => {
+                    self.ech_config_list = is.read_string()?;
+                },
+                56 => {
+                    self.ech = is.read_bool()?;
+                },
+                64 => {
+                    self.ech_disable_dns_lookup = is.read_bool()?;
+                },
+                74 => {
+                    self.certificate_fingerprint = is.read_string()?;
+                },
+                82 => {
+                    self.certificate_pins.push(is.read_string()?);
+                },
+                88 => {
+                    self.pin_only = is.read_bool()?;
+                },
+                104 => {
+                    self.allow_name_mismatch = is.read_bool()?;
+                },
+                112 => {
+                    self.ech_required = is.read_bool()?;
+                },
+                122 => {
+                    self.client_certificate = is.read_string()?;
+                },
+                130 => {
+                    self.client_certificate_key = is.read_string()?;
+                },
+                tag => {
+                    ::protobuf::rt::read_unknown_or_skip_group(tag, is, self.special_fields.mut_unknown_fields())?;
+                },
+            };
+        }
+        ::std::result::Result::Ok(())
+    }
+
+    // Compute sizes of nested messages
+    #[allow(unused_variables)]
+    fn compute_size(&self) -> u64 {
+        let mut my_size = 0;
+        if !self.server_name.is_empty() {
+            my_size += ::protobuf::rt::string_size(1, &self.server_name);
+        }
+        for value in &self.alpn {
+            my_size += ::protobuf::rt::string_size(2, &value);
+        };
+        if !self.certificate.is_empty() {
+            my_size += ::protobuf::rt::string_size(3, &self.certificate);
+        }
+        if self.insecure != false {
+            my_size += 1 + 1;
+        }
+        if !self.certificate_key.is_empty() {
+            my_size += ::protobuf::rt::string_size(5, &self.certificate_key);
+        }
+        if !self.ech_config_list.is_empty() {
+            my_size += ::protobuf::rt::string_size(6, &self.ech_config_list);
+        }
+        if self.ech != false {
+            my_size += 1 + 1;
+        }
+        if self.ech_disable_dns_lookup != false {
+            my_size += 1 + 1;
+        }
+        if !self.certificate_fingerprint.is_empty() {
+            my_size += ::protobuf::rt::string_size(9, &self.certificate_fingerprint);
+        }
+        for value in &self.certificate_pins {
+            my_size += ::protobuf::rt::string_size(10, &value);
+        };
+        if self.pin_only != false {
+            my_size += 1 + 1;
+        }
+        if self.allow_name_mismatch != false {
+            my_size += 1 + 1;
+        }
+        if self.ech_required != false {
+            my_size += 1 + 1;
+        }
+        if !self.client_certificate.is_empty() {
+            my_size += ::protobuf::rt::string_size(15, &self.client_certificate);
+        }
+        if !self.client_certificate_key.is_empty() {
+            my_size += ::protobuf::rt::string_size(16, &self.client_certificate_key);
+        }
+        my_size += ::protobuf::rt::unknown_fields_size(self.special_fields.unknown_fields());
+        self.special_fields.cached_size().set(my_size as u32);
+        my_size
+    }
+
+    fn write_to_with_cached_sizes(&self, os: &mut ::protobuf::CodedOutputStream<'_>) -> ::protobuf::Result<()> {
+        if !self.server_name.is_empty() {
+            os.write_string(1, &self.server_name)?;
+        }
+        for v in &self.alpn {
+            os.write_string(2, &v)?;
+        };
+        if !self.certificate.is_empty() {
+            os.write_string(3, &self.certificate)?;
+        }
+        if self.insecure != false {
+            os.write_bool(4, self.insecure)?;
+        }
+        if !self.certificate_key.is_empty() {
+            os.write_string(5, &self.certificate_key)?;
+        }
+        if !self.ech_config_list.is_empty() {
+            os.write_string(6, &self.ech_config_list)?;
+        }
+        if self.ech != false {
+            os.write_bool(7, self.ech)?;
+        }
+        if self.ech_disable_dns_lookup != false {
+            os.write_bool(8, self.ech_disable_dns_lookup)?;
+        }
+        if !self.certificate_fingerprint.is_empty() {
+            os.write_string(9, &self.certificate_fingerprint)?;
+        }
+        for v in &self.certificate_pins {
+            os.write_string(10, &v)?;
+        };
+        if self.pin_only != false {
+            os.write_bool(11, self.pin_only)?;
+        }
+        if self.allow_name_mismatch != false {
+            os.write_bool(13, self.allow_name_mismatch)?;
+        }
+        if self.ech_required != false {
+            os.write_bool(14, self.ech_required)?;
+        }
+        if !self.client_certificate.is_empty() {
+            os.write_string(15, &self.client_certificate)?;
+        }
+        if !self.client_certificate_key.is_empty() {
+            os.write_string(16, &self.client_certificate_key)?;
+        }
+        os.write_unknown_fields(self.special_fields.unknown_fields())?;
+        ::std::result::Result::Ok(())
+    }
+
+    fn special_fields(&self) -> &::protobuf::SpecialFields {
+        &self.special_fields
+    }
+
+    fn mut_special_fields(&mut self) -> &mut ::protobuf::SpecialFields {
+        &mut self.special_fields
+    }
+
+    fn new() -> TlsOutboundSettings {
+        TlsOutboundSettings::new()
+    }
+
+    fn clear(&mut self) {
+        self.server_name.clear();
+        self.alpn.clear();
+        self.certificate.clear();
+        self.insecure = false;
+        self.certificate_key.clear();
+        self.ech_config_list.clear();
+        self.ech = false;
+        self.ech_disable_dns_lookup = false;
+        self.certificate_fingerprint.clear();
+        self.certificate_pins.clear();
+        self.pin_only = false;
+        self.allow_name_mismatch = false;
+        self.ech_required = false;
+        self.client_certificate.clear();
+        self.client_certificate_key.clear();
+        self.special_fields.clear();
+    }
+
+    fn default_instance() -> &'static TlsOutboundSettings {
+        static instance: TlsOutboundSettings = TlsOutboundSettings {
+            server_name: ::std::string::String::new(),
+            alpn: ::std::vec::Vec::new(),
+            certificate: ::std::string::String::new(),
+            insecure: false,
+            certificate_key: ::std::string::String::new(),
+            ech_config_list: ::std::string::String::new(),
+            ech: false,
+            ech_disable_dns_lookup: false,
+            certificate_fingerprint: ::std::string::String::new(),
+            certificate_pins: ::std::vec::Vec::new(),
+            pin_only: false,
+            allow_name_mismatch: false,
+            ech_required: false,
+            client_certificate: ::std::string::String::new(),
+            client_certificate_key: ::std::string::String::new(),
+            special_fields: ::protobuf::SpecialFields::new(),
+        };
+        &instance
+    }
+}
+
+// @@protoc_insertion_point(message:WebSocketOutboundSettings)
+#[derive(PartialEq,Clone,Default,Debug)]
+pub struct WebSocketOutboundSettings {
+    // message fields
+    // @@protoc_insertion_point(field:WebSocketOutboundSettings.path)
+    pub path: ::std::string::String,
+    // @@protoc_insertion_point(field:WebSocketOutboundSettings.headers)
+    pub headers: ::std::collections::HashMap<::std::string::String, ::std::string::String>,
+    // @@protoc_insertion_point(field:WebSocketOutboundSettings.early_data_size)
+    pub early_data_size: u32,
+    // @@protoc_insertion_point(field:WebSocketOutboundSettings.early_data_header_name)
+    pub early_data_header_name: ::std::string::String,
+    // special fields
+    // @@protoc_insertion_point(special_field:WebSocketOutboundSettings.special_fields)
+    pub special_fields: ::protobuf::SpecialFields,
+}
+
+impl<'a> ::std::default::Default for &'a WebSocketOutboundSettings {
+    fn default() -> &'a WebSocketOutboundSettings {
+        <WebSocketOutboundSettings as ::protobuf::Message>::default_instance()
+    }
+}
+
+impl WebSocketOutboundSettings {
+    pub fn new() -> WebSocketOutboundSettings {
+        ::std::default::Default::default()
+    }
+}
+
+impl ::protobuf::Message for WebSocketOutboundSettings {
+    const NAME: &'static str = "WebSocketOutboundSettings";
+
+    fn is_initialized(&self) -> bool {
+        true
+    }
+
+    fn merge_from(&mut self, is: &mut ::protobuf::CodedInputStream<'_>) -> ::protobuf::Result<()> {
+        while let Some(tag) = is.read_raw_tag_or_eof()? {
+            match tag {
+                10 => {
+                    self.path = is.read_string()?;
+                },
+                18 => {
+                    let len = is.read_raw_varint32()?;
+                    let old_limit = is.push_limit(len as u64)?;
+                    let mut key = ::std::default::Default::default();
+                    let mut value = ::std::default::Default::default();
+                    while let Some(tag) = is.read_raw_tag_or_eof()? {
+                        match tag {
+                            10 => key = is.read_string()?,
+                            18 => value = is.read_string()?,
+                            _ => ::protobuf::rt::skip_field_for_tag(tag, is)?,
+                        };
+                    }
+                    is.pop_limit(old_limit);
+                    self.headers.insert(key, value);
+                },
+                24 => {
+                    self.early_data_size = is.read_uint32()?;
+                },
+                34 => {
+                    self.early_data_header_name = is.read_string()?;
+                },
+                tag => {
+                    ::protobuf::rt::read_unknown_or_skip_group(tag, is, self.special_fields.mut_unknown_fields())?;
+                },
+            };
+        }
+        ::std::result::Result::Ok(())
+    }
+
+    // Compute sizes of nested messages
+    #[allow(unused_variables)]
+    fn compute_size(&self) -> u64 {
+        let mut my_size = 0;
+        if !self.path.is_empty() {
+            my_size += ::protobuf::rt::string_size(1, &self.path);
+        }
+        for (k, v) in &self.headers {
+            let mut entry_size = 0;
+            entry_size += ::protobuf::rt::string_size(1, &k);
+            entry_size += ::protobuf::rt::string_size(2, &v);
+            my_size += 1 + ::protobuf::rt::compute_raw_varint64_size(entry_size) + entry_size
+        };
+        if self.early_data_size != 0 {
+            my_size += ::protobuf::rt::uint32_size(3, self.early_data_size);
+        }
+        if !self.early_data_header_name.is_empty() {
+            my_size += ::protobuf::rt::string_size(4, &self.early_data_header_name);
+        }
+        my_size += ::protobuf::rt::unknown_fields_size(self.special_fields.unknown_fields());
+        self.special_fields.cached_size().set(my_size as u32);
+        my_size
+    }
+
+    fn write_to_with_cached_sizes(&self, os: &mut ::protobuf::CodedOutputStream<'_>) -> ::protobuf::Result<()> {
+        if !self.path.is_empty() {
+            os.write_string(1, &self.path)?;
+        }
+        for (k, v) in &self.headers {
+            let mut entry_size = 0;
+            entry_size += ::protobuf::rt::string_size(1, &k);
+            entry_size += ::protobuf::rt::string_size(2, &v);
+            os.write_raw_varint32(18)?; // Tag.
+            os.write_raw_varint32(entry_size as u32)?;
+            os.write_string(1, &k)?;
+            os.write_string(2, &v)?;
+        };
+        if self.early_data_size != 0 {
+            os.write_uint32(3, self.early_data_size)?;
+        }
+        if !self.early_data_header_name.is_empty() {
+            os.write_string(4, &self.early_data_header_name)?;
+        }
+        os.write_unknown_fields(self.special_fields.unknown_fields())?;
+        ::std::result::Result::Ok(())
+    }
+
+    fn special_fields(&self) -> &::protobuf::SpecialFields {
+        &self.special_fields
+    }
+
+    fn mut_special_fields(&mut self) -> &mut ::protobuf::SpecialFields {
+        &mut self.special_fields
+    }
+
+    fn new() -> WebSocketOutboundSettings {
+        WebSocketOutboundSettings::new()
+    }
+
+    fn clear(&mut self) {
+        self.path.clear();
+        self.headers.clear();
+        self.early_data_size = 0;
+        self.early_data_header_name.clear();
+        self.special_fields.clear();
+    }
+
+    fn default_instance() -> &'static WebSocketOutboundSettings {
+        static instance: ::protobuf::rt::Lazy<WebSocketOutboundSettings> = ::protobuf::rt::Lazy::new();
+        instance.get(WebSocketOutboundSettings::new)
+    }
+}
+
+// @@protoc_insertion_point(message:HttpUpgradeOutboundSettings)
+#[derive(PartialEq,Clone,Default,Debug)]
+pub struct HttpUpgradeOutboundSettings {
+    // message fields
+    // @@protoc_insertion_point(field:HttpUpgradeOutboundSettings.path)
+    pub path: ::std::string::String,
+    // @@protoc_insertion_point(field:HttpUpgradeOutboundSettings.headers)
+    pub headers: ::std::collections::HashMap<::std::string::String, ::std::string::String>,
+    // special fields
+    // @@protoc_insertion_point(special_field:HttpUpgradeOutboundSettings.special_fields)
+    pub special_fields: ::protobuf::SpecialFields,
+}
+
+impl<'a> ::std::default::Default for &'a HttpUpgradeOutboundSettings {
+    fn default() -> &'a HttpUpgradeOutboundSettings {
+        <HttpUpgradeOutboundSettings as ::protobuf::Message>::default_instance()
+    }
+}
+
+impl HttpUpgradeOutboundSettings {
+    pub fn new() -> HttpUpgradeOutboundSettings {
+        ::std::default::Default::default()
+    }
+}
+
+impl ::protobuf::Message for HttpUpgradeOutboundSettings {
+    const NAME: &'static str = "HttpUpgradeOutboundSettings";
+
+    fn is_initialized(&self) -> bool {
+        true
+    }
+
+    fn merge_from(&mut self, is: &mut ::protobuf::CodedInputStream<'_>) -> ::protobuf::Result<()> {
+        while let Some(tag) = is.read_raw_tag_or_eof()? {
+            match tag {
+                10 => {
+                    self.path = is.read_string()?;
+                },
+                18 => {
+                    let len = is.read_raw_varint32()?;
+                    let old_limit = is.push_limit(len as u64)?;
+                    let mut key = ::std::default::Default::default();
+                    let mut value = ::std::default::Default::default();
+                    while let Some(tag) = is.read_raw_tag_or_eof()? {
+                        match tag {
+                            10 => key = is.read_string()?,
+                            18 => value = is.read_string()?,
+                            _ => ::protobuf::rt::skip_field_for_tag(tag, is)?,
+                        };
+                    }
+                    is.pop_limit(old_limit);
+                    self.headers.insert(key, value);
+                },
+                tag => {
+                    ::protobuf::rt::read_unknown_or_skip_group(tag, is, self.special_fields.mut_unknown_fields())?;
+                },
+            };
+        }
+        ::std::result::Result::Ok(())
+    }
+
+    // Compute sizes of nested messages
+    #[allow(unused_variables)]
+    fn compute_size(&self) -> u64 {
+        let mut my_size = 0;
+        if !self.path.is_empty() {
+            my_size += ::protobuf::rt::string_size(1, &self.path);
+        }
+        for (k, v) in &self.headers {
+            let mut entry_size = 0;
+            entry_size += ::protobuf::rt::string_size(1, &k);
+            entry_size += ::protobuf::rt::string_size(2, &v);
+            my_size += 1 + ::protobuf::rt::compute_raw_varint64_size(entry_size) + entry_size
+        };
+        my_size += ::protobuf::rt::unknown_fields_size(self.special_fields.unknown_fields());
+        self.special_fields.cached_size().set(my_size as u32);
+        my_size
+    }
+
+    fn write_to_with_cached_sizes(&self, os: &mut ::protobuf::CodedOutputStream<'_>) -> ::protobuf::Result<()> {
+        if !self.path.is_empty() {
+            os.write_string(1, &self.path)?;
+        }
+        for (k, v) in &self.headers {
+            let mut entry_size = 0;
+            entry_size += ::protobuf::rt::string_size(1, &k);
+            entry_size += ::protobuf::rt::string_size(2, &v);
+            os.write_raw_varint32(18)?; // Tag.
+            os.write_raw_varint32(entry_size as u32)?;
+            os.write_string(1, &k)?;
+            os.write_string(2, &v)?;
+        };
+        os.write_unknown_fields(self.special_fields.unknown_fields())?;
+        ::std::result::Result::Ok(())
+    }
+
+    fn special_fields(&self) -> &::protobuf::SpecialFields {
+        &self.special_fields
+    }
+
+    fn mut_special_fields(&mut self) -> &mut ::protobuf::SpecialFields {
+        &mut self.special_fields
+    }
+
+    fn new() -> HttpUpgradeOutboundSettings {
+        HttpUpgradeOutboundSettings::new()
+    }
+
+    fn clear(&mut self) {
+        self.path.clear();
+        self.headers.clear();
+        self.special_fields.clear();
+    }
+
+    fn default_instance() -> &'static HttpUpgradeOutboundSettings {
+        static instance: ::protobuf::rt::Lazy<HttpUpgradeOutboundSettings> = ::protobuf::rt::Lazy::new();
+        instance.get(HttpUpgradeOutboundSettings::new)
+    }
+}
+
+// @@protoc_insertion_point(message:GrpcOutboundSettings)
+#[derive(PartialEq,Clone,Default,Debug)]
+pub struct GrpcOutboundSettings {
+    // message fields
+    // @@protoc_insertion_point(field:GrpcOutboundSettings.service_name)
+    pub service_name: ::std::string::String,
+    // @@protoc_insertion_point(field:GrpcOutboundSettings.host)
+    pub host: ::std::string::String,
+    // @@protoc_insertion_point(field:GrpcOutboundSettings.address)
+    pub address: ::std::string::String,
+    // @@protoc_insertion_point(field:GrpcOutboundSettings.port)
+    pub port: u32,
+    // @@protoc_insertion_point(field:GrpcOutboundSettings.actors)
+    pub actors: ::std::vec::Vec<::std::string::String>,
+    // special fields
+    // @@protoc_insertion_point(special_field:GrpcOutboundSettings.special_fields)
+    pub special_fields: ::protobuf::SpecialFields,
+}
+
+impl<'a> ::std::default::Default for &'a GrpcOutboundSettings {
+    fn default() -> &'a GrpcOutboundSettings {
+        <GrpcOutboundSettings as ::protobuf::Message>::default_instance()
+    }
+}
+
+impl GrpcOutboundSettings {
+    pub fn new() -> GrpcOutboundSettings {
+        ::std::default::Default::default()
+    }
+}
+
+impl ::protobuf::Message for GrpcOutboundSettings {
+    const NAME: &'static str = "GrpcOutboundSettings";
+
+    fn is_initialized(&self) -> bool {
+        true
+    }
+
+    fn merge_from(&mut self, is: &mut ::protobuf::CodedInputStream<'_>) -> ::protobuf::Result<()> {
+        while let Some(tag) = is.read_raw_tag_or_eof()? {
+            match tag {
+                10 => {
+                    self.service_name = is.read_string()?;
+                },
+                18 => {
+                    self.host = is.read_string()?;
+                },
+                26 => {
+                    self.address = is.read_string()?;
+                },
+                32 => {
+                    self.port = is.read_uint32()?;
+                },
+                42 => {
+                    self.actors.push(is.read_string()?);
+                },
+                tag => {
+                    ::protobuf::rt::read_unknown_or_skip_group(tag, is, self.special_fields.mut_unknown_fields())?;
+                },
+            };
+        }
+        ::std::result::Result::Ok(())
+    }
+
+    // Compute sizes of nested messages
+    #[allow(unused_variables)]
+    fn compute_size(&self) -> u64 {
+        let mut my_size = 0;
+        if !self.service_name.is_empty() {
+            my_size += ::protobuf::rt::string_size(1, &self.service_name);
+        }
+        if !self.host.is_empty() {
+            my_size += ::protobuf::rt::string_size(2, &self.host);
+        }
+        if !self.address.is_empty() {
+            my_size += ::protobuf::rt::string_size(3, &self.address);
+        }
+        if self.port != 0 {
+            my_size += ::protobuf::rt::uint32_size(4, self.port);
+        }
+        for value in &self.actors {
+            my_size += ::protobuf::rt::string_size(5, &value);
+        };
+        my_size += ::protobuf::rt::unknown_fields_size(self.special_fields.unknown_fields());
+        self.special_fields.cached_size().set(my_size as u32);
+        my_size
+    }
+
+    fn write_to_with_cached_sizes(&self, os: &mut ::protobuf::CodedOutputStream<'_>) -> ::protobuf::Result<()> {
+        if !self.service_name.is_empty() {
+            os.write_string(1, &self.service_name)?;
+        }
+        if !self.host.is_empty() {
+            os.write_string(2, &self.host)?;
+        }
+        if !self.address.is_empty() {
+            os.write_string(3, &self.address)?;
+        }
+        if self.port != 0 {
+            os.write_uint32(4, self.port)?;
+        }
+        for v in &self.actors {
+            os.write_string(5, &v)?;
+        };
+        os.write_unknown_fields(self.special_fields.unknown_fields())?;
+        ::std::result::Result::Ok(())
+    }
+
+    fn special_fields(&self) -> &::protobuf::SpecialFields {
+        &self.special_fields
+    }
+
+    fn mut_special_fields(&mut self) -> &mut ::protobuf::SpecialFields {
+        &mut self.special_fields
+    }
+
+    fn new() -> GrpcOutboundSettings {
+        GrpcOutboundSettings::new()
+    }
+
+    fn clear(&mut self) {
+        self.service_name.clear();
+        self.host.clear();
+        self.address.clear();
+        self.port = 0;
+        self.actors.clear();
+        self.special_fields.clear();
+    }
+
+    fn default_instance() -> &'static GrpcOutboundSettings {
+        static instance: GrpcOutboundSettings = GrpcOutboundSettings {
+            service_name: ::std::string::String::new(),
+            host: ::std::string::String::new(),
+            address: ::std::string::String::new(),
+            port: 0,
+            actors: ::std::vec::Vec::new(),
+            special_fields: ::protobuf::SpecialFields::new(),
+        };
+        &instance
+    }
+}
+
+// @@protoc_insertion_point(message:TryAllOutboundSettings)
+#[derive(PartialEq,Clone,Default,Debug)]
+pub struct TryAllOutboundSettings {
+    // message fields
+    // @@protoc_insertion_point(field:TryAllOutboundSettings.actors)
+    pub actors: ::std::vec::Vec<::std::string::String>,
+    // @@protoc_insertion_point(field:TryAllOutboundSettings.delay_base)
+    pub delay_base: u32,
+    // @@protoc_insertion_point(field:TryAllOutboundSettings.max_concurrent_attempts)
+    pub max_concurrent_attempts: u32,
+    // special fields
+    // @@protoc_insertion_point(special_field:TryAllOutboundSettings.special_fields)
+    pub special_fields: ::protobuf::SpecialFields,
+}
+
+impl<'a> ::std::default::Default for &'a TryAllOutboundSettings {
+    fn default() -> &'a TryAllOutboundSettings {
+        <TryAllOutboundSettings as ::protobuf::Message>::default_instance()
+    }
+}
+
+impl TryAllOutboundSettings {
+    pub fn new() -> TryAllOutboundSettings {
+        ::std::default::Default::default()
+    }
+}
+
+impl ::protobuf::Message for TryAllOutboundSettings {
+    const NAME: &'static str = "TryAllOutboundSettings";
+
+    fn is_initialized(&self) -> bool {
+        true
+    }
+
+    fn merge_from(&mut self, is: &mut ::protobuf::CodedInputStream<'_>) -> ::protobuf::Result<()> {
+        while let Some(tag) = is.read_raw_tag_or_eof()? {
+            match tag {
+                10 => {
+                    self.actors.push(is.read_string()?);
+                },
+                16 => {
+                    self.delay_base = is.read_uint32()?;
+                },
+                24 => {
+                    self.max_concurrent_attempts = is.read_uint32()?;
+                },
+                tag => {
+                    ::protobuf::rt::read_unknown_or_skip_group(tag, is, self.special_fields.mut_unknown_fields())?;
+                },
+            };
+        }
+        ::std::result::Result::Ok(())
+    }
+
+    // Compute sizes of nested messages
+    #[allow(unused_variables)]
+    fn compute_size(&self) -> u64 {
+        let mut my_size = 0;
+        for value in &self.actors {
+            my_size += ::protobuf::rt::string_size(1, &value);
+        };
+        if self.delay_base != 0 {
+            my_size += ::protobuf::rt::uint32_size(2, self.delay_base);
+        }
+        if self.max_concurrent_attempts != 0 {
+            my_size += ::protobuf::rt::uint32_size(3, self.max_concurrent_attempts);
+        }
+        my_size += ::protobuf::rt::unknown_fields_size(self.special_fields.unknown_fields());
+        self.special_fields.cached_size().set(my_size as u32);
+        my_size
+    }
+
+    fn write_to_with_cached_sizes(&self, os: &mut ::protobuf::CodedOutputStream<'_>) -> ::protobuf::Result<()> {
+        for v in &self.actors {
+            os.write_string(1, &v)?;
+        };
+        if self.delay_base != 0 {
+            os.write_uint32(2, self.delay_base)?;
+        }
+        if self.max_concurrent_attempts != 0 {
+            os.write_uint32(3, self.max_concurrent_attempts)?;
+        }
+        os.write_unknown_fields(self.special_fields.unknown_fields())?;
+        ::std::result::Result::Ok(())
+    }
+
+    fn special_fields(&self) -> &::protobuf::SpecialFields {
+        &self.special_fields
+    }
+
+    fn mut_special_fields(&mut self) -> &mut ::protobuf::SpecialFields {
+        &mut self.special_fields
+    }
+
+    fn new() -> TryAllOutboundSettings {
+        TryAllOutboundSettings::new()
+    }
+
+    fn clear(&mut self) {
+        self.actors.clear();
+        self.delay_base = 0;
+        self.max_concurrent_attempts = 0;
+        self.special_fields.clear();
+    }
+
+    fn default_instance() -> &'static TryAllOutboundSettings {
+        static instance: TryAllOutboundSettings = TryAllOutboundSettings {
+            actors: ::std::vec::Vec::new(),
+            delay_base: 0,
+            max_concurrent_attempts: 0,
+            special_fields: ::protobuf::SpecialFields::new(),
+        };
+        &instance
+    }
+}
+
+// @@protoc_insertion_point(message:StaticOutboundSettings)
+#[derive(PartialEq,Clone,Default,Debug)]
+pub struct StaticOutboundSettings {
+    // message fields
+    // @@protoc_insertion_point(field:StaticOutboundSettings.actors)
+    pub actors: ::std::vec::Vec<::std::string::String>,
+    // @@protoc_insertion_point(field:StaticOutboundSettings.method)
+    pub method: ::std::string::String,
+    // special fields
+    // @@protoc_insertion_point(special_field:StaticOutboundSettings.special_fields)
+    pub special_fields: ::protobuf::SpecialFields,
+}
+
+impl<'a> ::std::default::Default for &'a StaticOutboundSettings {
+    fn default() -> &'a StaticOutboundSettings {
+        <StaticOutboundSettings as ::protobuf::Message>::default_instance()
+    }
+}
+
+impl StaticOutboundSettings {
+    pub fn new() -> StaticOutboundSettings {
+        ::std::default::Default::default()
+    }
+}
+
+impl ::protobuf::Message for StaticOutboundSettings {
+    const NAME: &'static str = "StaticOutboundSettings";
+
+    fn is_initialized(&self) -> bool {
+        true
+    }
+
+    fn merge_from(&mut self, is: &mut ::protobuf::CodedInputStream<'_>) -> ::protobuf::Result<()> {
+        while let Some(tag) = is.read_raw_tag_or_eof()? {
+            match tag {
+                10 => {
+                    self.actors.push(is.read_string()?);
+                },
+                18 => {
+                    self.method = is.read_string()?;
+                },
+                tag => {
+                    ::protobuf::rt::read_unknown_or_skip_group(tag, is, self.special_fields.mut_unknown_fields())?;
+                },
+            };
+        }
+        ::std::result::Result::Ok(())
+    }
+
+    // Compute sizes of nested messages
+    #[allow(unused_variables)]
+    fn compute_size(&self) -> u64 {
+        let mut my_size = 0;
+        for value in &self.actors {
+            my_size += ::protobuf::rt::string_size(1, &value);
+        };
+        if !self.method.is_empty() {
+            my_size += ::protobuf::rt::string_size(2, &self.method);
+        }
+        my_size += ::protobuf::rt::unknown_fields_size(self.special_fields.unknown_fields());
+        self.special_fields.cached_size().set(my_size as u32);
+        my_size
+    }
+
+    fn write_to_with_cached_sizes(&self, os: &mut ::protobuf::CodedOutputStream<'_>) -> ::protobuf::Result<()> {
+        for v in &self.actors {
+            os.write_string(1, &v)?;
+        };
+        if !self.method.is_empty() {
+            os.write_string(2, &self.method)?;
+        }
+        os.write_unknown_fields(self.special_fields.unknown_fields())?;
+        ::std::result::Result::Ok(())
+    }
+
+    fn special_fields(&self) -> &::protobuf::SpecialFields {
+        &self.special_fields
+    }
+
+    fn mut_special_fields(&mut self) -> &mut ::protobuf::SpecialFields {
+        &mut self.special_fields
+    }
+
+    fn new() -> StaticOutboundSettings {
+        StaticOutboundSettings::new()
+    }
+
+    fn clear(&mut self) {
+        self.actors.clear();
+        self.method.clear();
+        self.special_fields.clear();
+    }
+
+    fn default_instance() -> &'static StaticOutboundSettings {
+        static instance: StaticOutboundSettings = StaticOutboundSettings {
+            actors: ::std::vec::Vec::new(),
+            method: ::std::string::String::new(),
+            special_fields: ::protobuf::SpecialFields::new(),
+        };
+        &instance
+    }
+}
+
+// @@protoc_insertion_point(message:AMuxOutboundSettings)
+#[derive(PartialEq,Clone,Default,Debug)]
+pub struct AMuxOutboundSettings {
+    // message fields
+    // @@protoc_insertion_point(field:AMuxOutboundSettings.address)
+    pub address: ::std::string::String,
+    // @@protoc_insertion_point(field:AMuxOutboundSettings.port)
+    pub port: u32,
+    // @@protoc_insertion_point(field:AMuxOutboundSettings.actors)
+    pub actors: ::std::vec::Vec<::std::string::String>,
+    // @@protoc_insertion_point(field:AMuxOutboundSettings.max_accepts)
+    pub max_accepts: u32,
+    // @@protoc_insertion_point(field:AMuxOutboundSettings.concurrency)
+    pub concurrency: u32,
+    // @@protoc_insertion_point(field:AMuxOutboundSettings.max_recv_bytes)
+    pub max_recv_bytes: u64,
+    // @@protoc_insertion_point(field:AMuxOutboundSettings.max_lifetime)
+    pub max_lifetime: u64,
+    // @@protoc_insertion_point(field:AMuxOutboundSettings.keep_alive_interval)
+    pub keep_alive_interval: u32,
+    // @@protoc_insertion_point(field:AMuxOutboundSettings.keep_alive_max_missed)
+    pub keep_alive_max_missed: u32,
+    // @@protoc_insertion_point(field:AMuxOutboundSettings.open_timeout)
+    pub open_timeout: u32,
+    // @@protoc_insertion_point(field:AMuxOutboundSettings.max_connections)
+    pub max_connections: u32,
+    // @@protoc_insertion_point(field:AMuxOutboundSettings.connection_idle_timeout)
+    pub connection_idle_timeout: u32,
+    // @@protoc_insertion_point(field:AMuxOutboundSettings.padding)
+    pub padding: bool,
+    // @@protoc_insertion_point(field:AMuxOutboundSettings.padding_max_overhead_pct)
+    pub padding_max_overhead_pct: u32,
+    // special fields
+    // @@protoc_insertion_point(special_field:AMuxOutboundSettings.special_fields)
+    pub special_fields: ::protobuf::SpecialFields,
+}
+
+impl<'a> ::std::default::Default for &'a AMuxOutboundSettings {
+    fn default() -> &'a AMuxOutboundSettings {
+        <AMuxOutboundSettings as ::protobuf::Message>::default_instance()
+    }
+}
+
+impl AMuxOutboundSettings {
+    pub fn new() -> AMuxOutboundSettings {
+        ::std::default::Default::default()
+    }
+}
+
+impl ::protobuf::Message for AMuxOutboundSettings {
+    const NAME: &'static str = "AMuxOutboundSettings";
+
+    fn is_initialized(&self) -> bool {
+        true
+    }
+
+    fn merge_from(&mut self, is: &mut ::protobuf::CodedInputStream<'_>) -> ::protobuf::Result<()> {
+        while let Some(tag) = is.read_raw_tag_or_eof()? {
+            match tag {
+                10 => {
+                    self.address = is.read_string()?;
+                },
+                16 => {
+                    self.port = is.read_uint32()?;
+                },
+                26 => {
+                    self.actors.push(is.read_string()?);
+                },
+                32 => {
+                    self.max_accepts = is.read_uint32()?;
+                },
+                40 => {
+                    self.concurrency = is.read_uint32()?;
+                },
+                48 => {
+                    self.max_recv_bytes = is.read_uint64()?;
+                },
+                56 => {
+                    self.max_lifetime = is.read_uint64()?;
+                },
+                64 => {
+                    self.keep_alive_interval = is.read_uint32()?;
+                },
+                72 => {
+                    self.keep_alive_max_missed = is.read_uint32()?;
+                },
+                80 => {
+                    self.open_timeout = is.read_uint32()?;
+                },
+                88 => {
+                    self.max_connections = is.read_uint32()?;
+                },
+                96 => {
+                    self.connection_idle_timeout = is.read_uint32()?;
+                },
+                104 => {
+                    self.padding = is.read_bool()?;
+                },
+                112 => {
+                    self.padding_max_overhead_pct = is.read_uint32()?;
+                },
+                tag => {
+                    ::protobuf::rt::read_unknown_or_skip_group(tag, is, self.special_fields.mut_unknown_fields())?;
+                },
+            };
+        }
+        ::std::result::Result::Ok(())
+    }
+
+    // Compute sizes of nested messages
+    #[allow(unused_variables)]
+    fn compute_size(&self) -> u64 {
+        let mut my_size = 0;
+        if !self.address.is_empty() {
+            my_size += ::protobuf::rt::string_size(1, &self.address);
+        }
+        if self.port != 0 {
+            my_size += ::protobuf::rt::uint32_size(2, self.port);
+        }
+        for value in &self.actors {
+            my_size += ::protobuf::rt::string_size(3, &value);
+        };
+        if self.max_accepts != 0 {
+            my_size += ::protobuf::rt::uint32_size(4, self.max_accepts);
+        }
+        if self.concurrency != 0 {
+            my_size += ::protobuf::rt::uint32_size(5, self.concurrency);
+        }
+        if self.max_recv_bytes != 0 {
+            my_size += ::protobuf::rt::uint64_size(6, self.max_recv_bytes);
+        }
+        if self.max_lifetime != 0 {
+            my_size += ::protobuf::rt::uint64_size(7, self.max_lifetime);
+        }
+        if self.keep_alive_interval != 0 {
+            my_size += ::protobuf::rt::uint32_size(8, self.keep_alive_interval);
+        }
+        if self.keep_alive_max_missed != 0 {
+            my_size += ::protobuf::rt::uint32_size(9, self.keep_alive_max_missed);
+        }
+        if self.open_timeout != 0 {
+            my_size += ::protobuf::rt::uint32_size(10, self.open_timeout);
+        }
+        if self.max_connections != 0 {
+            my_size += ::protobuf::rt::uint32_size(11, self.max_connections);
+        }
+        if self.connection_idle_timeout != 0 {
+            my_size += ::protobuf::rt::uint32_size(12, self.connection_idle_timeout);
+        }
+        if self.padding != false {
+            my_size += 1 + 1;
+        }
+        if self.padding_max_overhead_pct != 0 {
+            my_size += ::protobuf::rt::uint32_size(14, self.padding_max_overhead_pct);
+        }
+        my_size += ::protobuf::rt::unknown_fields_size(self.special_fields.unknown_fields());
+        self.special_fields.cached_size().set(my_size as u32);
+        my_size
+    }
+
+    fn write_to_with_cached_sizes(&self, os: &mut ::protobuf::CodedOutputStream<'_>) -> ::protobuf::Result<()> {
+        if !self.address.is_empty() {
+            os.write_string(1, &self.address)?;
+        }
+        if self.port != 0 {
+            os.write_uint32(2, self.port)?;
+        }
+        for v in &self.actors {
+            os.write_string(3, &v)?;
+        };
+        if self.max_accepts != 0 {
+            os.write_uint32(4, self.max_accepts)?;
+        }
+        if self.concurrency != 0 {
+            os.write_uint32(5, self.concurrency)?;
+        }
+        if self.max_recv_bytes != 0 {
+            os.write_uint64(6, self.max_recv_bytes)?;
+        }
+        if self.max_lifetime != 0 {
+            os.write_uint64(7, self.max_lifetime)?;
+        }
+        if self.keep_alive_interval != 0 {
+            os.write_uint32(8, self.keep_alive_interval)?;
+        }
+        if self.keep_alive_max_missed != 0 {
+            os.write_uint32(9, self.keep_alive_max_missed)?;
+        }
+        if self.open_timeout != 0 {
+            os.write_uint32(10, self.open_timeout)?;
+        }
+        if self.max_connections != 0 {
+            os.write_uint32(11, self.max_connections)?;
+        }
+        if self.connection_idle_timeout != 0 {
+            os.write_uint32(12, self.connection_idle_timeout)?;
+        }
+        if self.padding != false {
+            os.write_bool(13, self.padding)?;
         }
-        if !self.path.is_empty() {
-            os.write_string(3, &self.path)?;
+        if self.padding_max_overhead_pct != 0 {
+            os.write_uint32(14, self.padding_max_overhead_pct)?;
         }
         os.write_unknown_fields(self.special_fields.unknown_fields())?;
         ::std::result::Result::Ok(())
@@ -2618,57 +5805,103 @@ impl ::protobuf::Message for ObfsOutboundSettings {
         &mut self.special_fields
     }
 
-    fn new() -> ObfsOutboundSettings {
-        ObfsOutboundSettings::new()
+    fn new() -> AMuxOutboundSettings {
+        AMuxOutboundSettings::new()
     }
 
     fn clear(&mut self) {
-        self.method.clear();
-        self.host.clear();
-        self.path.clear();
+        self.address.clear();
+        self.port = 0;
+        self.actors.clear();
+        self.max_accepts = 0;
+        self.concurrency = 0;
+        self.max_recv_bytes = 0;
+        self.max_lifetime = 0;
+        self.keep_alive_interval = 0;
+        self.keep_alive_max_missed = 0;
+        self.open_timeout = 0;
+        self.max_connections = 0;
+        self.connection_idle_timeout = 0;
+        self.padding = false;
+        self.padding_max_overhead_pct = 0;
         self.special_fields.clear();
     }
 
-    fn default_instance() -> &'static ObfsOutboundSettings {
-        static instance: ObfsOutboundSettings = ObfsOutboundSettings {
-            method: ::std::string::String::new(),
-            host: ::std::string::String::new(),
-            path: ::std::string::String::new(),
+    fn default_instance() -> &'static AMuxOutboundSettings {
+        static instance: AMuxOutboundSettings = AMuxOutboundSettings {
+            address: ::std::string::String::new(),
+            port: 0,
+            actors: ::std::vec::Vec::new(),
+            max_accepts: 0,
+            concurrency: 0,
+            max_recv_bytes: 0,
+            max_lifetime: 0,
+            keep_alive_interval: 0,
+            keep_alive_max_missed: 0,
+            open_timeout: 0,
+            max_connections: 0,
+            connection_idle_timeout: 0,
+            padding: false,
+            padding_max_overhead_pct: 0,
             special_fields: ::protobuf::SpecialFields::new(),
         };
         &instance
     }
 }
 
-// @@protoc_insertion_point(message:TrojanOutboundSettings)
+// @@protoc_insertion_point(message:QuicOutboundSettings)
 #[derive(PartialEq,Clone,Default,Debug)]
-pub struct TrojanOutboundSettings {
+pub struct QuicOutboundSettings {
     // message fields
-    // @@protoc_insertion_point(field:TrojanOutboundSettings.address)
+    // @@protoc_insertion_point(field:QuicOutboundSettings.address)
     pub address: ::std::string::String,
-    // @@protoc_insertion_point(field:TrojanOutboundSettings.port)
+    // @@protoc_insertion_point(field:QuicOutboundSettings.port)
     pub port: u32,
-    // @@protoc_insertion_point(field:TrojanOutboundSettings.password)
-    pub password: ::std::string::String,
+    // @@protoc_insertion_point(field:QuicOutboundSettings.server_name)
+    pub server_name: ::std::string::String,
+    // @@protoc_insertion_point(field:QuicOutboundSettings.certificate)
+    pub certificate: ::std::string::String,
+    // @@protoc_insertion_point(field:QuicOutboundSettings.alpn)
+    pub alpn: ::std::vec::Vec<::std::string::String>,
+    // @@protoc_insertion_point(field:QuicOutboundSettings.certificate_key)
+    pub certificate_key: ::std::string::String,
+    // @@protoc_insertion_point(field:QuicOutboundSettings.congestion_controller)
+    pub congestion_controller: ::std::string::String,
+    // @@protoc_insertion_point(field:QuicOutboundSettings.max_sessions_per_connection)
+    pub max_sessions_per_connection: u32,
+    // @@protoc_insertion_point(field:QuicOutboundSettings.udp_over_stream)
+    pub udp_over_stream: bool,
+    // @@protoc_insertion_point(field:QuicOutboundSettings.certificate_fingerprint)
+    pub certificate_fingerprint: ::std::string::String,
+    // @@protoc_insertion_point(field:QuicOutboundSettings.zero_rtt)
+    pub zero_rtt: bool,
+    // @@protoc_insertion_point(field:QuicOutboundSettings.reconnect_on_network_change)
+    pub reconnect_on_network_change: bool,
+    // @@protoc_insertion_point(field:QuicOutboundSettings.certificate_pins)
+    pub certificate_pins: ::std::vec::Vec<::std::string::String>,
+    // @@protoc_insertion_point(field:QuicOutboundSettings.pin_only)
+    pub pin_only: bool,
+    // @@protoc_insertion_point(field:QuicOutboundSettings.allow_name_mismatch)
+    pub allow_name_mismatch: bool,
     // special fields
-    // @@protoc_insertion_point(special_field:TrojanOutboundSettings.special_fields)
+    // @@protoc_insertion_point(special_field:QuicOutboundSettings.special_fields)
     pub special_fields: ::protobuf::SpecialFields,
 }
 
-impl<'a> ::std::default::Default for &'a TrojanOutboundSettings {
-    fn default() -> &'a TrojanOutboundSettings {
-        <TrojanOutboundSettings as ::protobuf::Message>::default_instance()
+impl<'a> ::std::default::Default for &'a QuicOutboundSettings {
+    fn default() -> &'a QuicOutboundSettings {
+        <QuicOutboundSettings as ::protobuf::Message>::default_instance()
     }
 }
 
-impl TrojanOutboundSettings {
-    pub fn new() -> TrojanOutboundSettings {
+impl QuicOutboundSettings {
+    pub fn new() -> QuicOutboundSettings {
         ::std::default::Default::default()
     }
 }
 
-impl ::protobuf::Message for TrojanOutboundSettings {
-    const NAME: &'static str = "TrojanOutboundSettings";
+impl ::protobuf::Message for QuicOutboundSettings {
+    const NAME: &'static str = "QuicOutboundSettings";
 
     fn is_initialized(&self) -> bool {
         true
@@ -2684,7 +5917,43 @@ impl ::protobuf::Message for TrojanOutboundSettings {
                     self.port = is.read_uint32()?;
                 },
                 26 => {
-                    self.password = is.read_string()?;
+                    self.server_name = is.read_string()?;
+                },
+                34 => {
+                    self.certificate = is.read_string()?;
+                },
+                42 => {
+                    self.alpn.push(is.read_string()?);
+                },
+                50 => {
+                    self.certificate_key = is.read_string()?;
+                },
+                58 => {
+                    self.congestion_controller = is.read_string()?;
+                },
+                64 => {
+                    self.max_sessions_per_connection = is.read_uint32()?;
+                },
+                72 => {
+                    self.udp_over_stream = is.read_bool()?;
+                },
+                82 => {
+                    self.certificate_fingerprint = is.read_string()?;
+                },
+                88 => {
+                    self.zero_rtt = is.read_bool()?;
+                },
+                96 => {
+                    self.reconnect_on_network_change = is.read_bool()?;
+                },
+                106 => {
+                    self.certificate_pins.push(is.read_string()?);
+                },
+                112 => {
+                    self.pin_only = is.read_bool()?;
+                },
+                120 => {
+                    self.allow_name_mismatch = is.read_bool()?;
                 },
                 tag => {
                     ::protobuf::rt::read_unknown_or_skip_group(tag, is, self.special_fields.mut_unknown_fields())?;
@@ -2704,8 +5973,44 @@ impl ::protobuf::Message for TrojanOutboundSettings {
         if self.port != 0 {
             my_size += ::protobuf::rt::uint32_size(2, self.port);
         }
-        if !self.password.is_empty() {
-            my_size += ::protobuf::rt::string_size(3, &self.password);
+        if !self.server_name.is_empty() {
+            my_size += ::protobuf::rt::string_size(3, &self.server_name);
+        }
+        if !self.certificate.is_empty() {
+            my_size += ::protobuf::rt::string_size(4, &self.certificate);
+        }
+        for value in &self.alpn {
+            my_size += ::protobuf::rt::string_size(5, &value);
+        };
+        if !self.certificate_key.is_empty() {
+            my_size += ::protobuf::rt::string_size(6, &self.certificate_key);
+        }
+        if !self.congestion_controller.is_empty() {
+            my_size += ::protobuf::rt::string_size(7, &self.congestion_controller);
+        }
+        if self.max_sessions_per_connection != 0 {
+            my_size += ::protobuf::rt::uint32_size(8, self.max_sessions_per_connection);
+        }
+        if self.udp_over_stream != false {
+            my_size += 1 + 1;
+        }
+        if !self.certificate_fingerprint.is_empty() {
+            my_size += ::protobuf::rt::string_size(10, &self.certificate_fingerprint);
+        }
+        if self.zero_rtt != false {
+            my_size += 1 + 1;
+        }
+        if self.reconnect_on_network_change != false {
+            my_size += 1 + 1;
+        }
+        for value in &self.certificate_pins {
+            my_size += ::protobuf::rt::string_size(13, &value);
+        };
+        if self.pin_only != false {
+            my_size += 1 + 1;
+        }
+        if self.allow_name_mismatch != false {
+            my_size += 1 + 1;
         }
         my_size += ::protobuf::rt::unknown_fields_size(self.special_fields.unknown_fields());
         self.special_fields.cached_size().set(my_size as u32);
@@ -2719,8 +6024,44 @@ impl ::protobuf::Message for TrojanOutboundSettings {
         if self.port != 0 {
             os.write_uint32(2, self.port)?;
         }
-        if !self.password.is_empty() {
-            os.write_string(3, &self.password)?;
+        if !self.server_name.is_empty() {
+            os.write_string(3, &self.server_name)?;
+        }
+        if !self.certificate.is_empty() {
+            os.write_string(4, &self.certificate)?;
+        }
+        for v in &self.alpn {
+            os.write_string(5, &v)?;
+        };
+        if !self.certificate_key.is_empty() {
+            os.write_string(6, &self.certificate_key)?;
+        }
+        if !self.congestion_controller.is_empty() {
+            os.write_string(7, &self.congestion_controller)?;
+        }
+        if self.max_sessions_per_connection != 0 {
+            os.write_uint32(8, self.max_sessions_per_connection)?;
+        }
+        if self.udp_over_stream != false {
+            os.write_bool(9, self.udp_over_stream)?;
+        }
+        if !self.certificate_fingerprint.is_empty() {
+            os.write_string(10, &self.certificate_fingerprint)?;
+        }
+        if self.zero_rtt != false {
+            os.write_bool(11, self.zero_rtt)?;
+        }
+        if self.reconnect_on_network_change != false {
+            os.write_bool(12, self.reconnect_on_network_change)?;
+        }
+        for v in &self.certificate_pins {
+            os.write_string(13, &v)?;
+        };
+        if self.pin_only != false {
+            os.write_bool(14, self.pin_only)?;
+        }
+        if self.allow_name_mismatch != false {
+            os.write_bool(15, self.allow_name_mismatch)?;
         }
         os.write_unknown_fields(self.special_fields.unknown_fields())?;
         ::std::result::Result::Ok(())
@@ -2734,67 +6075,83 @@ impl ::protobuf::Message for TrojanOutboundSettings {
         &mut self.special_fields
     }
 
-    fn new() -> TrojanOutboundSettings {
-        TrojanOutboundSettings::new()
+    fn new() -> QuicOutboundSettings {
+        QuicOutboundSettings::new()
     }
 
     fn clear(&mut self) {
         self.address.clear();
         self.port = 0;
-        self.password.clear();
+        self.server_name.clear();
+        self.certificate.clear();
+        self.alpn.clear();
+        self.certificate_key.clear();
+        self.congestion_controller.clear();
+        self.max_sessions_per_connection = 0;
+        self.udp_over_stream = false;
+        self.certificate_fingerprint.clear();
+        self.zero_rtt = false;
+        self.reconnect_on_network_change = false;
+        self.certificate_pins.clear();
+        self.pin_only = false;
+        self.allow_name_mismatch = false;
         self.special_fields.clear();
     }
 
-    fn default_instance() -> &'static TrojanOutboundSettings {
-        static instance: TrojanOutboundSettings = TrojanOutboundSettings {
+    fn default_instance() -> &'static QuicOutboundSettings {
+        static instance: QuicOutboundSettings = QuicOutboundSettings {
             address: ::std::string::String::new(),
             port: 0,
-            password: ::std::string::String::new(),
+            server_name: ::std::string::String::new(),
+            certificate: ::std::string::String::new(),
+            alpn: ::std::vec::Vec::new(),
+            certificate_key: ::std::string::String::new(),
+            congestion_controller: ::std::string::String::new(),
+            max_sessions_per_connection: 0,
+            udp_over_stream: false,
+            certificate_fingerprint: ::std::string::String::new(),
+            zero_rtt: false,
+            reconnect_on_network_change: false,
+            certificate_pins: ::std::vec::Vec::new(),
+            pin_only: false,
+            allow_name_mismatch: false,
             special_fields: ::protobuf::SpecialFields::new(),
         };
         &instance
     }
 }
 
-// @@protoc_insertion_point(message:TlsOutboundSettings)
+// @@protoc_insertion_point(message:VMessOutboundSettings)
 #[derive(PartialEq,Clone,Default,Debug)]
-pub struct TlsOutboundSettings {
+pub struct VMessOutboundSettings {
     // message fields
-    // @@protoc_insertion_point(field:TlsOutboundSettings.server_name)
-    pub server_name: ::std::string::String,
-    // @@protoc_insertion_point(field:TlsOutboundSettings.alpn)
-    pub alpn: ::std::vec::Vec<::std::string::String>,
-    // @@protoc_insertion_point(field:TlsOutboundSettings.certificate)
-    pub certificate: ::std::string::String,
-    // @@protoc_insertion_point(field:TlsOutboundSettings.insecure)
-    pub insecure: bool,
-    // @@protoc_insertion_point(field:TlsOutboundSettings.certificate_key)
-    pub certificate_key: ::std::string::String,
-    // @@protoc_insertion_point(field:TlsOutboundSettings.ech_config_list)
-    pub ech_config_list: ::std::string::String,
-    // @@protoc_insertion_point(field:TlsOutboundSettings.ech)
-    pub ech: bool,
-    // @@protoc_insertion_point(field:TlsOutboundSettings.ech_disable_dns_lookup)
-    pub ech_disable_dns_lookup: bool,
+    // @@protoc_insertion_point(field:VMessOutboundSettings.address)
+    pub address: ::std::string::String,
+    // @@protoc_insertion_point(field:VMessOutboundSettings.port)
+    pub port: u32,
+    // @@protoc_insertion_point(field:VMessOutboundSettings.uuid)
+    pub uuid: ::std::string::String,
+    // @@protoc_insertion_point(field:VMessOutboundSettings.security)
+    pub security: ::std::string::String,
     // special fields
-    // @@protoc_insertion_point(special_field:TlsOutboundSettings.special_fields)
+    // @@protoc_insertion_point(special_field:VMessOutboundSettings.special_fields)
     pub special_fields: ::protobuf::SpecialFields,
 }
 
-impl<'a> ::std::default::Default for &'a TlsOutboundSettings {
-    fn default() -> &'a TlsOutboundSettings {
-        <TlsOutboundSettings as ::protobuf::Message>::default_instance()
+impl<'a> ::std::default::Default for &'a VMessOutboundSettings {
+    fn default() -> &'a VMessOutboundSettings {
+        <VMessOutboundSettings as ::protobuf::Message>::default_instance()
     }
 }
 
-impl TlsOutboundSettings {
-    pub fn new() -> TlsOutboundSettings {
+impl VMessOutboundSettings {
+    pub fn new() -> VMessOutboundSettings {
         ::std::default::Default::default()
     }
 }
 
-impl ::protobuf::Message for TlsOutboundSettings {
-    const NAME: &'static str = "TlsOutboundSettings";
+impl ::protobuf::Message for VMessOutboundSettings {
+    const NAME: &'static str = "VMessOutboundSettings";
 
     fn is_initialized(&self) -> bool {
         true
@@ -2804,28 +6161,16 @@ impl ::protobuf::Message for TlsOutboundSettings {
         while let Some(tag) = is.read_raw_tag_or_eof()? {
             match tag {
                 10 => {
-                    self.server_name = is.read_string()?;
+                    self.address = is.read_string()?;
                 },
-                18 => {
-                    self.alpn.push(is.read_string()?);
+                16 => {
+                    self.port = is.read_uint32()?;
                 },
                 26 => {
-                    self.certificate = is.read_string()?;
-                },
-                32 => {
-                    self.insecure = is.read_bool()?;
-                },
-                42 => {
-                    self.certificate_key = is.read_string()?;
-                },
-                50 => {
-                    self.ech_config_list = is.read_string()?;
-                },
-                56 => {
-                    self.ech = is.read_bool()?;
+                    self.uuid = is.read_string()?;
                 },
-                64 => {
-                    self.ech_disable_dns_lookup = is.read_bool()?;
+                34 => {
+                    self.security = is.read_string()?;
                 },
                 tag => {
                     ::protobuf::rt::read_unknown_or_skip_group(tag, is, self.special_fields.mut_unknown_fields())?;
@@ -2839,29 +6184,17 @@ impl ::protobuf::Message for TlsOutboundSettings {
     #[allow(unused_variables)]
     fn compute_size(&self) -> u64 {
         let mut my_size = 0;
-        if !self.server_name.is_empty() {
-            my_size += ::protobuf::rt::string_size(1, &self.server_name);
-        }
-        for value in &self.alpn {
-            my_size += ::protobuf::rt::string_size(2, &value);
-        };
-        if !self.certificate.is_empty() {
-            my_size += ::protobuf::rt::string_size(3, &self.certificate);
-        }
-        if self.insecure != false {
-            my_size += 1 + 1;
-        }
-        if !self.certificate_key.is_empty() {
-            my_size += ::protobuf::rt::string_size(5, &self.certificate_key);
+        if !self.address.is_empty() {
+            my_size += ::protobuf::rt::string_size(1, &self.address);
         }
-        if !self.ech_config_list.is_empty() {
-            my_size += ::protobuf::rt::string_size(6, &self.ech_config_list);
+        if self.port != 0 {
+            my_size += ::protobuf::rt::uint32_size(2, self.port);
         }
-        if self.ech != false {
-            my_size += 1 + 1;
+        if !self.uuid.is_empty() {
+            my_size += ::protobuf::rt::string_size(3, &self.uuid);
         }
-        if self.ech_disable_dns_lookup != false {
-            my_size += 1 + 1;
+        if !self.security.is_empty() {
+            my_size += ::protobuf::rt::string_size(4, &self.security);
         }
         my_size += ::protobuf::rt::unknown_fields_size(self.special_fields.unknown_fields());
         self.special_fields.cached_size().set(my_size as u32);
@@ -2869,29 +6202,17 @@ impl ::protobuf::Message for TlsOutboundSettings {
     }
 
     fn write_to_with_cached_sizes(&self, os: &mut ::protobuf::CodedOutputStream<'_>) -> ::protobuf::Result<()> {
-        if !self.server_name.is_empty() {
-            os.write_string(1, &self.server_name)?;
-        }
-        for v in &self.alpn {
-            os.write_string(2, &v)?;
-        };
-        if !self.certificate.is_empty() {
-            os.write_string(3, &self.certificate)?;
-        }
-        if self.insecure != false {
-            os.write_bool(4, self.insecure)?;
-        }
-        if !self.certificate_key.is_empty() {
-            os.write_string(5, &self.certificate_key)?;
+        if !self.address.is_empty() {
+            os.write_string(1, &self.address)?;
         }
-        if !self.ech_config_list.is_empty() {
-            os.write_string(6, &self.ech_config_list)?;
+        if self.port != 0 {
+            os.write_uint32(2, self.port)?;
         }
-        if self.ech != false {
-            os.write_bool(7, self.ech)?;
+        if !self.uuid.is_empty() {
+            os.write_string(3, &self.uuid)?;
         }
-        if self.ech_disable_dns_lookup != false {
-            os.write_bool(8, self.ech_disable_dns_lookup)?;
+        if !self.security.is_empty() {
+            os.write_string(4, &self.security)?;
         }
         os.write_unknown_fields(self.special_fields.unknown_fields())?;
         ::std::result::Result::Ok(())
@@ -2905,65 +6226,61 @@ impl ::protobuf::Message for TlsOutboundSettings {
         &mut self.special_fields
     }
 
-    fn new() -> TlsOutboundSettings {
-        TlsOutboundSettings::new()
+    fn new() -> VMessOutboundSettings {
+        VMessOutboundSettings::new()
     }
 
     fn clear(&mut self) {
-        self.server_name.clear();
-        self.alpn.clear();
-        self.certificate.clear();
-        self.insecure = false;
-        self.certificate_key.clear();
-        self.ech_config_list.clear();
-        self.ech = false;
-        self.ech_disable_dns_lookup = false;
+        self.address.clear();
+        self.port = 0;
+        self.uuid.clear();
+        self.security.clear();
         self.special_fields.clear();
     }
 
-    fn default_instance() -> &'static TlsOutboundSettings {
-        static instance: TlsOutboundSettings = TlsOutboundSettings {
-            server_name: ::std::string::String::new(),
-            alpn: ::std::vec::Vec::new(),
-            certificate: ::std::string::String::new(),
-            insecure: false,
-            certificate_key: ::std::string::String::new(),
-            ech_config_list: ::std::string::String::new(),
-            ech: false,
-            ech_disable_dns_lookup: false,
+    fn default_instance() -> &'static VMessOutboundSettings {
+        static instance: VMessOutboundSettings = VMessOutboundSettings {
+            address: ::std::string::String::new(),
+            port: 0,
+            uuid: ::std::string::String::new(),
+            security: ::std::string::String::new(),
             special_fields: ::protobuf::SpecialFields::new(),
         };
         &instance
     }
 }
 
-// @@protoc_insertion_point(message:WebSocketOutboundSettings)
+// @@protoc_insertion_point(message:VlessOutboundSettings)
 #[derive(PartialEq,Clone,Default,Debug)]
-pub struct WebSocketOutboundSettings {
+pub struct VlessOutboundSettings {
     // message fields
-    // @@protoc_insertion_point(field:WebSocketOutboundSettings.path)
-    pub path: ::std::string::String,
-    // @@protoc_insertion_point(field:WebSocketOutboundSettings.headers)
-    pub headers: ::std::collections::HashMap<::std::string::String, ::std::string::String>,
+    // @@protoc_insertion_point(field:VlessOutboundSettings.address)
+    pub address: ::std::string::String,
+    // @@protoc_insertion_point(field:VlessOutboundSettings.port)
+    pub port: u32,
+    // @@protoc_insertion_point(field:VlessOutboundSettings.uuid)
+    pub uuid: ::std::string::String,
+    // @@protoc_insertion_point(field:VlessOutboundSettings.flow)
+    pub flow: ::std::string::String,
     // special fields
-    // @@protoc_insertion_point(special_field:WebSocketOutboundSettings.special_fields)
+    // @@protoc_insertion_point(special_field:VlessOutboundSettings.special_fields)
     pub special_fields: ::protobuf::SpecialFields,
 }
 
-impl<'a> ::std::default::Default for &'a WebSocketOutboundSettings {
-    fn default() -> &'a WebSocketOutboundSettings {
-        <WebSocketOutboundSettings as ::protobuf::Message>::default_instance()
+impl<'a> ::std::default::Default for &'a VlessOutboundSettings {
+    fn default() -> &'a VlessOutboundSettings {
+        <VlessOutboundSettings as ::protobuf::Message>::default_instance()
     }
 }
 
-impl WebSocketOutboundSettings {
-    pub fn new() -> WebSocketOutboundSettings {
+impl VlessOutboundSettings {
+    pub fn new() -> VlessOutboundSettings {
         ::std::default::Default::default()
     }
 }
 
-impl ::protobuf::Message for WebSocketOutboundSettings {
-    const NAME: &'static str = "WebSocketOutboundSettings";
+impl ::protobuf::Message for VlessOutboundSettings {
+    const NAME: &'static str = "VlessOutboundSettings";
 
     fn is_initialized(&self) -> bool {
         true
@@ -2973,22 +6290,16 @@ impl ::protobuf::Message for WebSocketOutboundSettings {
         while let Some(tag) = is.read_raw_tag_or_eof()? {
             match tag {
                 10 => {
-                    self.path = is.read_string()?;
+                    self.address = is.read_string()?;
                 },
-                18 => {
-                    let len = is.read_raw_varint32()?;
-                    let old_limit = is.push_limit(len as u64)?;
-                    let mut key = ::std::default::Default::default();
-                    let mut value = ::std::default::Default::default();
-                    while let Some(tag) = is.read_raw_tag_or_eof()? {
-                        match tag {
-                            10 => key = is.read_string()?,
-                            18 => value = is.read_string()?,
-                            _ => ::protobuf::rt::skip_field_for_tag(tag, is)?,
-                        };
-                    }
-                    is.pop_limit(old_limit);
-                    self.headers.insert(key, value);
+                16 => {
+                    self.port = is.read_uint32()?;
+                },
+                26 => {
+                    self.uuid = is.read_string()?;
+                },
+                34 => {
+                    self.flow = is.read_string()?;
                 },
                 tag => {
                     ::protobuf::rt::read_unknown_or_skip_group(tag, is, self.special_fields.mut_unknown_fields())?;
@@ -3002,33 +6313,36 @@ impl ::protobuf::Message for WebSocketOutboundSettings {
     #[allow(unused_variables)]
     fn compute_size(&self) -> u64 {
         let mut my_size = 0;
-        if !self.path.is_empty() {
-            my_size += ::protobuf::rt::string_size(1, &self.path);
+        if !self.address.is_empty() {
+            my_size += ::protobuf::rt::string_size(1, &self.address);
+        }
+        if self.port != 0 {
+            my_size += ::protobuf::rt::uint32_size(2, self.port);
+        }
+        if !self.uuid.is_empty() {
+            my_size += ::protobuf::rt::string_size(3, &self.uuid);
+        }
+        if !self.flow.is_empty() {
+            my_size += ::protobuf::rt::string_size(4, &self.flow);
         }
-        for (k, v) in &self.headers {
-            let mut entry_size = 0;
-            entry_size += ::protobuf::rt::string_size(1, &k);
-            entry_size += ::protobuf::rt::string_size(2, &v);
-            my_size += 1 + ::protobuf::rt::compute_raw_varint64_size(entry_size) + entry_size
-        };
         my_size += ::protobuf::rt::unknown_fields_size(self.special_fields.unknown_fields());
         self.special_fields.cached_size().set(my_size as u32);
         my_size
     }
 
     fn write_to_with_cached_sizes(&self, os: &mut ::protobuf::CodedOutputStream<'_>) -> ::protobuf::Result<()> {
-        if !self.path.is_empty() {
-            os.write_string(1, &self.path)?;
+        if !self.address.is_empty() {
+            os.write_string(1, &self.address)?;
+        }
+        if self.port != 0 {
+            os.write_uint32(2, self.port)?;
+        }
+        if !self.uuid.is_empty() {
+            os.write_string(3, &self.uuid)?;
+        }
+        if !self.flow.is_empty() {
+            os.write_string(4, &self.flow)?;
         }
-        for (k, v) in &self.headers {
-            let mut entry_size = 0;
-            entry_size += ::protobuf::rt::string_size(1, &k);
-            entry_size += ::protobuf::rt::string_size(2, &v);
-            os.write_raw_varint32(18)?; // Tag.
-            os.write_raw_varint32(entry_size as u32)?;
-            os.write_string(1, &k)?;
-            os.write_string(2, &v)?;
-        };
         os.write_unknown_fields(self.special_fields.unknown_fields())?;
         ::std::result::Result::Ok(())
     }
@@ -3041,49 +6355,67 @@ impl ::protobuf::Message for WebSocketOutboundSettings {
         &mut self.special_fields
     }
 
-    fn new() -> WebSocketOutboundSettings {
-        WebSocketOutboundSettings::new()
+    fn new() -> VlessOutboundSettings {
+        VlessOutboundSettings::new()
     }
 
     fn clear(&mut self) {
-        self.path.clear();
-        self.headers.clear();
+        self.address.clear();
+        self.port = 0;
+        self.uuid.clear();
+        self.flow.clear();
         self.special_fields.clear();
     }
 
-    fn default_instance() -> &'static WebSocketOutboundSettings {
-        static instance: ::protobuf::rt::Lazy<WebSocketOutboundSettings> = ::protobuf::rt::Lazy::new();
-        instance.get(WebSocketOutboundSettings::new)
+    fn default_instance() -> &'static VlessOutboundSettings {
+        static instance: VlessOutboundSettings = VlessOutboundSettings {
+            address: ::std::string::String::new(),
+            port: 0,
+            uuid: ::std::string::String::new(),
+            flow: ::std::string::String::new(),
+            special_fields: ::protobuf::SpecialFields::new(),
+        };
+        &instance
     }
 }
 
-// @@protoc_insertion_point(message:TryAllOutboundSettings)
+// @@protoc_insertion_point(message:Hysteria2OutboundSettings)
 #[derive(PartialEq,Clone,Default,Debug)]
-pub struct TryAllOutboundSettings {
+pub struct Hysteria2OutboundSettings {
     // message fields
-    // @@protoc_insertion_point(field:TryAllOutboundSettings.actors)
-    pub actors: ::std::vec::Vec<::std::string::String>,
-    // @@protoc_insertion_point(field:TryAllOutboundSettings.delay_base)
-    pub delay_base: u32,
+    // @@protoc_insertion_point(field:Hysteria2OutboundSettings.address)
+    pub address: ::std::string::String,
+    // @@protoc_insertion_point(field:Hysteria2OutboundSettings.port)
+    pub port: u32,
+    // @@protoc_insertion_point(field:Hysteria2OutboundSettings.password)
+    pub password: ::std::string::String,
+    // @@protoc_insertion_point(field:Hysteria2OutboundSettings.up_mbps)
+    pub up_mbps: u32,
+    // @@protoc_insertion_point(field:Hysteria2OutboundSettings.down_mbps)
+    pub down_mbps: u32,
+    // @@protoc_insertion_point(field:Hysteria2OutboundSettings.sni)
+    pub sni: ::std::string::String,
+    // @@protoc_insertion_point(field:Hysteria2OutboundSettings.insecure)
+    pub insecure: bool,
     // special fields
-    // @@protoc_insertion_point(special_field:TryAllOutboundSettings.special_fields)
+    // @@protoc_insertion_point(special_field:Hysteria2OutboundSettings.special_fields)
     pub special_fields: ::protobuf::SpecialFields,
 }
 
-impl<'a> ::std::default::Default for &'a TryAllOutboundSettings {
-    fn default() -> &'a TryAllOutboundSettings {
-        <TryAllOutboundSettings as ::protobuf::Message>::default_instance()
+impl<'a> ::std::default::Default for &'a Hysteria2OutboundSettings {
+    fn default() -> &'a Hysteria2OutboundSettings {
+        <Hysteria2OutboundSettings as ::protobuf::Message>::default_instance()
     }
 }
 
-impl TryAllOutboundSettings {
-    pub fn new() -> TryAllOutboundSettings {
+impl Hysteria2OutboundSettings {
+    pub fn new() -> Hysteria2OutboundSettings {
         ::std::default::Default::default()
     }
 }
 
-impl ::protobuf::Message for TryAllOutboundSettings {
-    const NAME: &'static str = "TryAllOutboundSettings";
+impl ::protobuf::Message for Hysteria2OutboundSettings {
+    const NAME: &'static str = "Hysteria2OutboundSettings";
 
     fn is_initialized(&self) -> bool {
         true
@@ -3093,10 +6425,25 @@ impl ::protobuf::Message for TryAllOutboundSettings {
         while let Some(tag) = is.read_raw_tag_or_eof()? {
             match tag {
                 10 => {
-                    self.actors.push(is.read_string()?);
+                    self.address = is.read_string()?;
                 },
                 16 => {
-                    self.delay_base = is.read_uint32()?;
+                    self.port = is.read_uint32()?;
+                },
+                26 => {
+                    self.password = is.read_string()?;
+                },
+                32 => {
+                    self.up_mbps = is.read_uint32()?;
+                },
+                40 => {
+                    self.down_mbps = is.read_uint32()?;
+                },
+                50 => {
+                    self.sni = is.read_string()?;
+                },
+                56 => {
+                    self.insecure = is.read_bool()?;
                 },
                 tag => {
                     ::protobuf::rt::read_unknown_or_skip_group(tag, is, self.special_fields.mut_unknown_fields())?;
@@ -3110,11 +6457,26 @@ impl ::protobuf::Message for TryAllOutboundSettings {
     #[allow(unused_variables)]
     fn compute_size(&self) -> u64 {
         let mut my_size = 0;
-        for value in &self.actors {
-            my_size += ::protobuf::rt::string_size(1, &value);
-        };
-        if self.delay_base != 0 {
-            my_size += ::protobuf::rt::uint32_size(2, self.delay_base);
+        if !self.address.is_empty() {
+            my_size += ::protobuf::rt::string_size(1, &self.address);
+        }
+        if self.port != 0 {
+            my_size += ::protobuf::rt::uint32_size(2, self.port);
+        }
+        if !self.password.is_empty() {
+            my_size += ::protobuf::rt::string_size(3, &self.password);
+        }
+        if self.up_mbps != 0 {
+            my_size += ::protobuf::rt::uint32_size(4, self.up_mbps);
+        }
+        if self.down_mbps != 0 {
+            my_size += ::protobuf::rt::uint32_size(5, self.down_mbps);
+        }
+        if !self.sni.is_empty() {
+            my_size += ::protobuf::rt::string_size(6, &self.sni);
+        }
+        if self.insecure != false {
+            my_size += 1 + 1;
         }
         my_size += ::protobuf::rt::unknown_fields_size(self.special_fields.unknown_fields());
         self.special_fields.cached_size().set(my_size as u32);
@@ -3122,11 +6484,26 @@ impl ::protobuf::Message for TryAllOutboundSettings {
     }
 
     fn write_to_with_cached_sizes(&self, os: &mut ::protobuf::CodedOutputStream<'_>) -> ::protobuf::Result<()> {
-        for v in &self.actors {
-            os.write_string(1, &v)?;
-        };
-        if self.delay_base != 0 {
-            os.write_uint32(2, self.delay_base)?;
+        if !self.address.is_empty() {
+            os.write_string(1, &self.address)?;
+        }
+        if self.port != 0 {
+            os.write_uint32(2, self.port)?;
+        }
+        if !self.password.is_empty() {
+            os.write_string(3, &self.password)?;
+        }
+        if self.up_mbps != 0 {
+            os.write_uint32(4, self.up_mbps)?;
+        }
+        if self.down_mbps != 0 {
+            os.write_uint32(5, self.down_mbps)?;
+        }
+        if !self.sni.is_empty() {
+            os.write_string(6, &self.sni)?;
+        }
+        if self.insecure != false {
+            os.write_bool(7, self.insecure)?;
         }
         os.write_unknown_fields(self.special_fields.unknown_fields())?;
         ::std::result::Result::Ok(())
@@ -3140,53 +6517,75 @@ impl ::protobuf::Message for TryAllOutboundSettings {
         &mut self.special_fields
     }
 
-    fn new() -> TryAllOutboundSettings {
-        TryAllOutboundSettings::new()
+    fn new() -> Hysteria2OutboundSettings {
+        Hysteria2OutboundSettings::new()
     }
 
     fn clear(&mut self) {
-        self.actors.clear();
-        self.delay_base = 0;
+        self.address.clear();
+        self.port = 0;
+        self.password.clear();
+        self.up_mbps = 0;
+        self.down_mbps = 0;
+        self.sni.clear();
+        self.insecure = false;
         self.special_fields.clear();
     }
 
-    fn default_instance() -> &'static TryAllOutboundSettings {
-        static instance: TryAllOutboundSettings = TryAllOutboundSettings {
-            actors: ::std::vec::Vec::new(),
-            delay_base: 0,
+    fn default_instance() -> &'static Hysteria2OutboundSettings {
+        static instance: Hysteria2OutboundSettings = Hysteria2OutboundSettings {
+            address: ::std::string::String::new(),
+            port: 0,
+            password: ::std::string::String::new(),
+            up_mbps: 0,
+            down_mbps: 0,
+            sni: ::std::string::String::new(),
+            insecure: false,
             special_fields: ::protobuf::SpecialFields::new(),
         };
         &instance
     }
 }
 
-// @@protoc_insertion_point(message:StaticOutboundSettings)
+// @@protoc_insertion_point(message:TuicOutboundSettings)
 #[derive(PartialEq,Clone,Default,Debug)]
-pub struct StaticOutboundSettings {
+pub struct TuicOutboundSettings {
     // message fields
-    // @@protoc_insertion_point(field:StaticOutboundSettings.actors)
-    pub actors: ::std::vec::Vec<::std::string::String>,
-    // @@protoc_insertion_point(field:StaticOutboundSettings.method)
-    pub method: ::std::string::String,
+    // @@protoc_insertion_point(field:TuicOutboundSettings.address)
+    pub address: ::std::string::String,
+    // @@protoc_insertion_point(field:TuicOutboundSettings.port)
+    pub port: u32,
+    // @@protoc_insertion_point(field:TuicOutboundSettings.uuid)
+    pub uuid: ::std::string::String,
+    // @@protoc_insertion_point(field:TuicOutboundSettings.password)
+    pub password: ::std::string::String,
+    // @@protoc_insertion_point(field:TuicOutboundSettings.sni)
+    pub sni: ::std::string::String,
+    // @@protoc_insertion_point(field:TuicOutboundSettings.alpn)
+    pub alpn: ::std::vec::Vec<::std::string::String>,
+    // @@protoc_insertion_point(field:TuicOutboundSettings.insecure)
+    pub insecure: bool,
+    // @@protoc_insertion_point(field:TuicOutboundSettings.udp_relay_mode)
+    pub udp_relay_mode: ::std::string::String,
     // special fields
-    // @@protoc_insertion_point(special_field:StaticOutboundSettings.special_fields)
+    // @@protoc_insertion_point(special_field:TuicOutboundSettings.special_fields)
     pub special_fields: ::protobuf::SpecialFields,
 }
 
-impl<'a> ::std::default::Default for &'a StaticOutboundSettings {
-    fn default() -> &'a StaticOutboundSettings {
-        <StaticOutboundSettings as ::protobuf::Message>::default_instance()
+impl<'a> ::std::default::Default for &'a TuicOutboundSettings {
+    fn default() -> &'a TuicOutboundSettings {
+        <TuicOutboundSettings as ::protobuf::Message>::default_instance()
     }
 }
 
-impl StaticOutboundSettings {
-    pub fn new() -> StaticOutboundSettings {
+impl TuicOutboundSettings {
+    pub fn new() -> TuicOutboundSettings {
         ::std::default::Default::default()
     }
 }
 
-impl ::protobuf::Message for StaticOutboundSettings {
-    const NAME: &'static str = "StaticOutboundSettings";
+impl ::protobuf::Message for TuicOutboundSettings {
+    const NAME: &'static str = "TuicOutboundSettings";
 
     fn is_initialized(&self) -> bool {
         true
@@ -3196,10 +6595,28 @@ impl ::protobuf::Message for StaticOutboundSettings {
         while let Some(tag) = is.read_raw_tag_or_eof()? {
             match tag {
                 10 => {
-                    self.actors.push(is.read_string()?);
+                    self.address = is.read_string()?;
                 },
-                18 => {
-                    self.method = is.read_string()?;
+                16 => {
+                    self.port = is.read_uint32()?;
+                },
+                26 => {
+                    self.uuid = is.read_string()?;
+                },
+                34 => {
+                    self.password = is.read_string()?;
+                },
+                42 => {
+                    self.sni = is.read_string()?;
+                },
+                50 => {
+                    self.alpn.push(is.read_string()?);
+                },
+                56 => {
+                    self.insecure = is.read_bool()?;
+                },
+                66 => {
+                    self.udp_relay_mode = is.read_string()?;
                 },
                 tag => {
                     ::protobuf::rt::read_unknown_or_skip_group(tag, is, self.special_fields.mut_unknown_fields())?;
@@ -3213,11 +6630,29 @@ impl ::protobuf::Message for StaticOutboundSettings {
     #[allow(unused_variables)]
     fn compute_size(&self) -> u64 {
         let mut my_size = 0;
-        for value in &self.actors {
-            my_size += ::protobuf::rt::string_size(1, &value);
+        if !self.address.is_empty() {
+            my_size += ::protobuf::rt::string_size(1, &self.address);
+        }
+        if self.port != 0 {
+            my_size += ::protobuf::rt::uint32_size(2, self.port);
+        }
+        if !self.uuid.is_empty() {
+            my_size += ::protobuf::rt::string_size(3, &self.uuid);
+        }
+        if !self.password.is_empty() {
+            my_size += ::protobuf::rt::string_size(4, &self.password);
+        }
+        if !self.sni.is_empty() {
+            my_size += ::protobuf::rt::string_size(5, &self.sni);
+        }
+        for value in &self.alpn {
+            my_size += ::protobuf::rt::string_size(6, &value);
         };
-        if !self.method.is_empty() {
-            my_size += ::protobuf::rt::string_size(2, &self.method);
+        if self.insecure != false {
+            my_size += 1 + 1;
+        }
+        if !self.udp_relay_mode.is_empty() {
+            my_size += ::protobuf::rt::string_size(8, &self.udp_relay_mode);
         }
         my_size += ::protobuf::rt::unknown_fields_size(self.special_fields.unknown_fields());
         self.special_fields.cached_size().set(my_size as u32);
@@ -3225,11 +6660,29 @@ impl ::protobuf::Message for StaticOutboundSettings {
     }
 
     fn write_to_with_cached_sizes(&self, os: &mut ::protobuf::CodedOutputStream<'_>) -> ::protobuf::Result<()> {
-        for v in &self.actors {
-            os.write_string(1, &v)?;
+        if !self.address.is_empty() {
+            os.write_string(1, &self.address)?;
+        }
+        if self.port != 0 {
+            os.write_uint32(2, self.port)?;
+        }
+        if !self.uuid.is_empty() {
+            os.write_string(3, &self.uuid)?;
+        }
+        if !self.password.is_empty() {
+            os.write_string(4, &self.password)?;
+        }
+        if !self.sni.is_empty() {
+            os.write_string(5, &self.sni)?;
+        }
+        for v in &self.alpn {
+            os.write_string(6, &v)?;
         };
-        if !self.method.is_empty() {
-            os.write_string(2, &self.method)?;
+        if self.insecure != false {
+            os.write_bool(7, self.insecure)?;
+        }
+        if !self.udp_relay_mode.is_empty() {
+            os.write_string(8, &self.udp_relay_mode)?;
         }
         os.write_unknown_fields(self.special_fields.unknown_fields())?;
         ::std::result::Result::Ok(())
@@ -3243,63 +6696,75 @@ impl ::protobuf::Message for StaticOutboundSettings {
         &mut self.special_fields
     }
 
-    fn new() -> StaticOutboundSettings {
-        StaticOutboundSettings::new()
+    fn new() -> TuicOutboundSettings {
+        TuicOutboundSettings::new()
     }
 
     fn clear(&mut self) {
-        self.actors.clear();
-        self.method.clear();
+        self.address.clear();
+        self.port = 0;
+        self.uuid.clear();
+        self.password.clear();
+        self.sni.clear();
+        self.alpn.clear();
+        self.insecure = false;
+        self.udp_relay_mode.clear();
         self.special_fields.clear();
     }
 
-    fn default_instance() -> &'static StaticOutboundSettings {
-        static instance: StaticOutboundSettings = StaticOutboundSettings {
-            actors: ::std::vec::Vec::new(),
-            method: ::std::string::String::new(),
+    fn default_instance() -> &'static TuicOutboundSettings {
+        static instance: TuicOutboundSettings = TuicOutboundSettings {
+            address: ::std::string::String::new(),
+            port: 0,
+            uuid: ::std::string::String::new(),
+            password: ::std::string::String::new(),
+            sni: ::std::string::String::new(),
+            alpn: ::std::vec::Vec::new(),
+            insecure: false,
+            udp_relay_mode: ::std::string::String::new(),
             special_fields: ::protobuf::SpecialFields::new(),
         };
         &instance
     }
 }
 
-// @@protoc_insertion_point(message:AMuxOutboundSettings)
+// @@protoc_insertion_point(message:WireGuardOutboundSettings)
 #[derive(PartialEq,Clone,Default,Debug)]
-pub struct AMuxOutboundSettings {
+pub struct WireGuardOutboundSettings {
     // message fields
-    // @@protoc_insertion_point(field:AMuxOutboundSettings.address)
-    pub address: ::std::string::String,
-    // @@protoc_insertion_point(field:AMuxOutboundSettings.port)
-    pub port: u32,
-    // @@protoc_insertion_point(field:AMuxOutboundSettings.actors)
-    pub actors: ::std::vec::Vec<::std::string::String>,
-    // @@protoc_insertion_point(field:AMuxOutboundSettings.max_accepts)
-    pub max_accepts: u32,
-    // @@protoc_insertion_point(field:AMuxOutboundSettings.concurrency)
-    pub concurrency: u32,
-    // @@protoc_insertion_point(field:AMuxOutboundSettings.max_recv_bytes)
-    pub max_recv_bytes: u64,
-    // @@protoc_insertion_point(field:AMuxOutboundSettings.max_lifetime)
-    pub max_lifetime: u64,
+    // @@protoc_insertion_point(field:WireGuardOutboundSettings.private_key)
+    pub private_key: ::std::string::String,
+    // @@protoc_insertion_point(field:WireGuardOutboundSettings.peer_public_key)
+    pub peer_public_key: ::std::string::String,
+    // @@protoc_insertion_point(field:WireGuardOutboundSettings.preshared_key)
+    pub preshared_key: ::std::string::String,
+    // @@protoc_insertion_point(field:WireGuardOutboundSettings.endpoint)
+    pub endpoint: ::std::string::String,
+    // @@protoc_insertion_point(field:WireGuardOutboundSettings.allowed_ips)
+    pub allowed_ips: ::std::vec::Vec<::std::string::String>,
+    // @@protoc_insertion_point(field:WireGuardOutboundSettings.local_address)
+    pub local_address: ::std::vec::Vec<::std::string::String>,
+    // @@protoc_insertion_point(field:WireGuardOutboundSettings.mtu)
+    pub mtu: u32,
     // special fields
-    // @@protoc_insertion_point(special_field:AMuxOutboundSettings.special_fields)
+    // @@protoc_insertion_point(special_field:WireGuardOutboundSettings.special_fields)
     pub special_fields: ::protobuf::SpecialFields,
 }
 
-impl<'a> ::std::default::Default for &'a AMuxOutboundSettings {
-    fn default() -> &'a AMuxOutboundSettings {
-        <AMuxOutboundSettings as ::protobuf::Message>::default_instance()
+impl<'a> ::std::default::Default for &'a WireGuardOutboundSettings {
+    fn default() -> &'a WireGuardOutboundSettings {
+        <WireGuardOutboundSettings as ::protobuf::Message>::default_instance()
     }
 }
 
-impl AMuxOutboundSettings {
-    pub fn new() -> AMuxOutboundSettings {
+impl WireGuardOutboundSettings {
+    pub fn new() -> WireGuardOutboundSettings {
         ::std::default::Default::default()
     }
 }
 
-impl ::protobuf::Message for AMuxOutboundSettings {
-    const NAME: &'static str = "AMuxOutboundSettings";
+impl ::protobuf::Message for WireGuardOutboundSettings {
+    const NAME: &'static str = "WireGuardOutboundSettings";
 
     fn is_initialized(&self) -> bool {
         true
@@ -3309,25 +6774,25 @@ impl ::protobuf::Message for AMuxOutboundSettings {
         while let Some(tag) = is.read_raw_tag_or_eof()? {
             match tag {
                 10 => {
-                    self.address = is.read_string()?;
+                    self.private_key = is.read_string()?;
                 },
-                16 => {
-                    self.port = is.read_uint32()?;
+                18 => {
+                    self.peer_public_key = is.read_string()?;
                 },
                 26 => {
-                    self.actors.push(is.read_string()?);
+                    self.preshared_key = is.read_string()?;
                 },
-                32 => {
-                    self.max_accepts = is.read_uint32()?;
+                34 => {
+                    self.endpoint = is.read_string()?;
                 },
-                40 => {
-                    self.concurrency = is.read_uint32()?;
+                42 => {
+                    self.allowed_ips.push(is.read_string()?);
                 },
-                48 => {
-                    self.max_recv_bytes = is.read_uint64()?;
+                50 => {
+                    self.local_address.push(is.read_string()?);
                 },
                 56 => {
-                    self.max_lifetime = is.read_uint64()?;
+                    self.mtu = is.read_uint32()?;
                 },
                 tag => {
                     ::protobuf::rt::read_unknown_or_skip_group(tag, is, self.special_fields.mut_unknown_fields())?;
@@ -3341,26 +6806,26 @@ impl ::protobuf::Message for AMuxOutboundSettings {
     #[allow(unused_variables)]
     fn compute_size(&self) -> u64 {
         let mut my_size = 0;
-        if !self.address.is_empty() {
-            my_size += ::protobuf::rt::string_size(1, &self.address);
-        }
-        if self.port != 0 {
-            my_size += ::protobuf::rt::uint32_size(2, self.port);
+        if !self.private_key.is_empty() {
+            my_size += ::protobuf::rt::string_size(1, &self.private_key);
         }
-        for value in &self.actors {
-            my_size += ::protobuf::rt::string_size(3, &value);
-        };
-        if self.max_accepts != 0 {
-            my_size += ::protobuf::rt::uint32_size(4, self.max_accepts);
+        if !self.peer_public_key.is_empty() {
+            my_size += ::protobuf::rt::string_size(2, &self.peer_public_key);
         }
-        if self.concurrency != 0 {
-            my_size += ::protobuf::rt::uint32_size(5, self.concurrency);
+        if !self.preshared_key.is_empty() {
+            my_size += ::protobuf::rt::string_size(3, &self.preshared_key);
         }
-        if self.max_recv_bytes != 0 {
-            my_size += ::protobuf::rt::uint64_size(6, self.max_recv_bytes);
+        if !self.endpoint.is_empty() {
+            my_size += ::protobuf::rt::string_size(4, &self.endpoint);
         }
-        if self.max_lifetime != 0 {
-            my_size += ::protobuf::rt::uint64_size(7, self.max_lifetime);
+        for value in &self.allowed_ips {
+            my_size += ::protobuf::rt::string_size(5, &value);
+        };
+        for value in &self.local_address {
+            my_size += ::protobuf::rt::string_size(6, &value);
+        };
+        if self.mtu != 0 {
+            my_size += ::protobuf::rt::uint32_size(7, self.mtu);
         }
         my_size += ::protobuf::rt::unknown_fields_size(self.special_fields.unknown_fields());
         self.special_fields.cached_size().set(my_size as u32);
@@ -3368,26 +6833,26 @@ impl ::protobuf::Message for AMuxOutboundSettings {
     }
 
     fn write_to_with_cached_sizes(&self, os: &mut ::protobuf::CodedOutputStream<'_>) -> ::protobuf::Result<()> {
-        if !self.address.is_empty() {
-            os.write_string(1, &self.address)?;
-        }
-        if self.port != 0 {
-            os.write_uint32(2, self.port)?;
+        if !self.private_key.is_empty() {
+            os.write_string(1, &self.private_key)?;
         }
-        for v in &self.actors {
-            os.write_string(3, &v)?;
-        };
-        if self.max_accepts != 0 {
-            os.write_uint32(4, self.max_accepts)?;
+        if !self.peer_public_key.is_empty() {
+            os.write_string(2, &self.peer_public_key)?;
         }
-        if self.concurrency != 0 {
-            os.write_uint32(5, self.concurrency)?;
+        if !self.preshared_key.is_empty() {
+            os.write_string(3, &self.preshared_key)?;
         }
-        if self.max_recv_bytes != 0 {
-            os.write_uint64(6, self.max_recv_bytes)?;
+        if !self.endpoint.is_empty() {
+            os.write_string(4, &self.endpoint)?;
         }
-        if self.max_lifetime != 0 {
-            os.write_uint64(7, self.max_lifetime)?;
+        for v in &self.allowed_ips {
+            os.write_string(5, &v)?;
+        };
+        for v in &self.local_address {
+            os.write_string(6, &v)?;
+        };
+        if self.mtu != 0 {
+            os.write_uint32(7, self.mtu)?;
         }
         os.write_unknown_fields(self.special_fields.unknown_fields())?;
         ::std::result::Result::Ok(())
@@ -3401,71 +6866,77 @@ impl ::protobuf::Message for AMuxOutboundSettings {
         &mut self.special_fields
     }
 
-    fn new() -> AMuxOutboundSettings {
-        AMuxOutboundSettings::new()
+    fn new() -> WireGuardOutboundSettings {
+        WireGuardOutboundSettings::new()
     }
 
     fn clear(&mut self) {
-        self.address.clear();
-        self.port = 0;
-        self.actors.clear();
-        self.max_accepts = 0;
-        self.concurrency = 0;
-        self.max_recv_bytes = 0;
-        self.max_lifetime = 0;
+        self.private_key.clear();
+        self.peer_public_key.clear();
+        self.preshared_key.clear();
+        self.endpoint.clear();
+        self.allowed_ips.clear();
+        self.local_address.clear();
+        self.mtu = 0;
         self.special_fields.clear();
     }
 
-    fn default_instance() -> &'static AMuxOutboundSettings {
-        static instance: AMuxOutboundSettings = AMuxOutboundSettings {
-            address: ::std::string::String::new(),
-            port: 0,
-            actors: ::std::vec::Vec::new(),
-            max_accepts: 0,
-            concurrency: 0,
-            max_recv_bytes: 0,
-            max_lifetime: 0,
+    fn default_instance() -> &'static WireGuardOutboundSettings {
+        static instance: WireGuardOutboundSettings = WireGuardOutboundSettings {
+            private_key: ::std::string::String::new(),
+            peer_public_key: ::std::string::String::new(),
+            preshared_key: ::std::string::String::new(),
+            endpoint: ::std::string::String::new(),
+            allowed_ips: ::std::vec::Vec::new(),
+            local_address: ::std::vec::Vec::new(),
+            mtu: 0,
             special_fields: ::protobuf::SpecialFields::new(),
         };
         &instance
     }
 }
 
-// @@protoc_insertion_point(message:QuicOutboundSettings)
+// @@protoc_insertion_point(message:SshOutboundSettings)
 #[derive(PartialEq,Clone,Default,Debug)]
-pub struct QuicOutboundSettings {
+pub struct SshOutboundSettings {
     // message fields
-    // @@protoc_insertion_point(field:QuicOutboundSettings.address)
+    // @@protoc_insertion_point(field:SshOutboundSettings.address)
     pub address: ::std::string::String,
-    // @@protoc_insertion_point(field:QuicOutboundSettings.port)
+    // @@protoc_insertion_point(field:SshOutboundSettings.port)
     pub port: u32,
-    // @@protoc_insertion_point(field:QuicOutboundSettings.server_name)
-    pub server_name: ::std::string::String,
-    // @@protoc_insertion_point(field:QuicOutboundSettings.certificate)
-    pub certificate: ::std::string::String,
-    // @@protoc_insertion_point(field:QuicOutboundSettings.alpn)
-    pub alpn: ::std::vec::Vec<::std::string::String>,
-    // @@protoc_insertion_point(field:QuicOutboundSettings.certificate_key)
-    pub certificate_key: ::std::string::String,
+    // @@protoc_insertion_point(field:SshOutboundSettings.username)
+    pub username: ::std::string::String,
+    // @@protoc_insertion_point(field:SshOutboundSettings.password)
+    pub password: ::std::string::String,
+    // @@protoc_insertion_point(field:SshOutboundSettings.private_key)
+    pub private_key: ::std::string::String,
+    // @@protoc_insertion_point(field:SshOutboundSettings.private_key_passphrase)
+    pub private_key_passphrase: ::std::string::String,
+    // @@protoc_insertion_point(field:SshOutboundSettings.host_key_fingerprint)
+    pub host_key_fingerprint: ::std::string::String,
+    // @@protoc_insertion_point(field:SshOutboundSettings.known_hosts)
+    pub known_hosts: ::std::string::String,
+    // @@protoc_insertion_point(field:SshOutboundSettings.max_channels_per_conn)
+    pub max_channels_per_conn: u32,
     // special fields
-    // @@protoc_insertion_point(special_field:QuicOutboundSettings.special_fields)
+    // @@protoc_insertion_point(special_field:SshOutboundSettings.special_fields)
     pub special_fields: ::protobuf::SpecialFields,
 }
 
-impl<'a> ::std::default::Default for &'a QuicOutboundSettings {
-    fn default() -> &'a QuicOutboundSettings {
-        <QuicOutboundSettings as ::protobuf::Message>::default_instance()
+impl<'a> ::std::default::Default for &'a SshOutboundSettings {
+    fn default() -> &'a SshOutboundSettings {
+        <SshOutboundSettings as ::protobuf::Message>::default_instance()
     }
 }
 
-impl QuicOutboundSettings {
-    pub fn new() -> QuicOutboundSettings {
+impl SshOutboundSettings {
+    pub fn new() -> SshOutboundSettings {
         ::std::default::Default::default()
     }
 }
 
-impl ::protobuf::Message for QuicOutboundSettings {
-    const NAME: &'static str = "QuicOutboundSettings";
+impl ::protobuf::Message for SshOutboundSettings {
+    const NAME: &'static str = "SshOutboundSettings";
 
     fn is_initialized(&self) -> bool {
         true
@@ -3481,16 +6952,25 @@ impl ::protobuf::Message for QuicOutboundSettings {
                     self.port = is.read_uint32()?;
                 },
                 26 => {
-                    self.server_name = is.read_string()?;
+                    self.username = is.read_string()?;
                 },
                 34 => {
-                    self.certificate = is.read_string()?;
+                    self.password = is.read_string()?;
                 },
                 42 => {
-                    self.alpn.push(is.read_string()?);
+                    self.private_key = is.read_string()?;
                 },
                 50 => {
-                    self.certificate_key = is.read_string()?;
+                    self.private_key_passphrase = is.read_string()?;
+                },
+                58 => {
+                    self.host_key_fingerprint = is.read_string()?;
+                },
+                66 => {
+                    self.known_hosts = is.read_string()?;
+                },
+                72 => {
+                    self.max_channels_per_conn = is.read_uint32()?;
                 },
                 tag => {
                     ::protobuf::rt::read_unknown_or_skip_group(tag, is, self.special_fields.mut_unknown_fields())?;
@@ -3510,17 +6990,26 @@ impl ::protobuf::Message for QuicOutboundSettings {
         if self.port != 0 {
             my_size += ::protobuf::rt::uint32_size(2, self.port);
         }
-        if !self.server_name.is_empty() {
-            my_size += ::protobuf::rt::string_size(3, &self.server_name);
+        if !self.username.is_empty() {
+            my_size += ::protobuf::rt::string_size(3, &self.username);
         }
-        if !self.certificate.is_empty() {
-            my_size += ::protobuf::rt::string_size(4, &self.certificate);
+        if !self.password.is_empty() {
+            my_size += ::protobuf::rt::string_size(4, &self.password);
         }
-        for value in &self.alpn {
-            my_size += ::protobuf::rt::string_size(5, &value);
-        };
-        if !self.certificate_key.is_empty() {
-            my_size += ::protobuf::rt::string_size(6, &self.certificate_key);
+        if !self.private_key.is_empty() {
+            my_size += ::protobuf::rt::string_size(5, &self.private_key);
+        }
+        if !self.private_key_passphrase.is_empty() {
+            my_size += ::protobuf::rt::string_size(6, &self.private_key_passphrase);
+        }
+        if !self.host_key_fingerprint.is_empty() {
+            my_size += ::protobuf::rt::string_size(7, &self.host_key_fingerprint);
+        }
+        if !self.known_hosts.is_empty() {
+            my_size += ::protobuf::rt::string_size(8, &self.known_hosts);
+        }
+        if self.max_channels_per_conn != 0 {
+            my_size += ::protobuf::rt::uint32_size(9, self.max_channels_per_conn);
         }
         my_size += ::protobuf::rt::unknown_fields_size(self.special_fields.unknown_fields());
         self.special_fields.cached_size().set(my_size as u32);
@@ -3534,17 +7023,26 @@ impl ::protobuf::Message for QuicOutboundSettings {
         if self.port != 0 {
             os.write_uint32(2, self.port)?;
         }
-        if !self.server_name.is_empty() {
-            os.write_string(3, &self.server_name)?;
+        if !self.username.is_empty() {
+            os.write_string(3, &self.username)?;
         }
-        if !self.certificate.is_empty() {
-            os.write_string(4, &self.certificate)?;
+        if !self.password.is_empty() {
+            os.write_string(4, &self.password)?;
         }
-        for v in &self.alpn {
-            os.write_string(5, &v)?;
-        };
-        if !self.certificate_key.is_empty() {
-            os.write_string(6, &self.certificate_key)?;
+        if !self.private_key.is_empty() {
+            os.write_string(5, &self.private_key)?;
+        }
+        if !self.private_key_passphrase.is_empty() {
+            os.write_string(6, &self.private_key_passphrase)?;
+        }
+        if !self.host_key_fingerprint.is_empty() {
+            os.write_string(7, &self.host_key_fingerprint)?;
+        }
+        if !self.known_hosts.is_empty() {
+            os.write_string(8, &self.known_hosts)?;
+        }
+        if self.max_channels_per_conn != 0 {
+            os.write_uint32(9, self.max_channels_per_conn)?;
         }
         os.write_unknown_fields(self.special_fields.unknown_fields())?;
         ::std::result::Result::Ok(())
@@ -3558,65 +7056,75 @@ impl ::protobuf::Message for QuicOutboundSettings {
         &mut self.special_fields
     }
 
-    fn new() -> QuicOutboundSettings {
-        QuicOutboundSettings::new()
+    fn new() -> SshOutboundSettings {
+        SshOutboundSettings::new()
     }
 
     fn clear(&mut self) {
         self.address.clear();
         self.port = 0;
-        self.server_name.clear();
-        self.certificate.clear();
-        self.alpn.clear();
-        self.certificate_key.clear();
+        self.username.clear();
+        self.password.clear();
+        self.private_key.clear();
+        self.private_key_passphrase.clear();
+        self.host_key_fingerprint.clear();
+        self.known_hosts.clear();
+        self.max_channels_per_conn = 0;
         self.special_fields.clear();
     }
 
-    fn default_instance() -> &'static QuicOutboundSettings {
-        static instance: QuicOutboundSettings = QuicOutboundSettings {
+    fn default_instance() -> &'static SshOutboundSettings {
+        static instance: SshOutboundSettings = SshOutboundSettings {
             address: ::std::string::String::new(),
             port: 0,
-            server_name: ::std::string::String::new(),
-            certificate: ::std::string::String::new(),
-            alpn: ::std::vec::Vec::new(),
-            certificate_key: ::std::string::String::new(),
+            username: ::std::string::String::new(),
+            password: ::std::string::String::new(),
+            private_key: ::std::string::String::new(),
+            private_key_passphrase: ::std::string::String::new(),
+            host_key_fingerprint: ::std::string::String::new(),
+            known_hosts: ::std::string::String::new(),
+            max_channels_per_conn: 0,
             special_fields: ::protobuf::SpecialFields::new(),
         };
         &instance
     }
 }
 
-// @@protoc_insertion_point(message:VMessOutboundSettings)
+// @@protoc_insertion_point(message:H2OutboundSettings)
 #[derive(PartialEq,Clone,Default,Debug)]
-pub struct VMessOutboundSettings {
+pub struct H2OutboundSettings {
     // message fields
-    // @@protoc_insertion_point(field:VMessOutboundSettings.address)
+    // @@protoc_insertion_point(field:H2OutboundSettings.address)
     pub address: ::std::string::String,
-    // @@protoc_insertion_point(field:VMessOutboundSettings.port)
+    // @@protoc_insertion_point(field:H2OutboundSettings.port)
     pub port: u32,
-    // @@protoc_insertion_point(field:VMessOutboundSettings.uuid)
-    pub uuid: ::std::string::String,
-    // @@protoc_insertion_point(field:VMessOutboundSettings.security)
-    pub security: ::std::string::String,
+    // @@protoc_insertion_point(field:H2OutboundSettings.username)
+    pub username: ::std::string::String,
+    // @@protoc_insertion_point(field:H2OutboundSettings.password)
+    pub password: ::std::string::String,
+    // @@protoc_insertion_point(field:H2OutboundSettings.padding)
+    pub padding: bool,
+    // @@protoc_insertion_point(field:H2OutboundSettings.tls)
+    pub tls: ::protobuf::MessageField<TlsOutboundSettings>,
     // special fields
-    // @@protoc_insertion_point(special_field:VMessOutboundSettings.special_fields)
+    // @@protoc_insertion_point(special_field:H2OutboundSettings.special_fields)
     pub special_fields: ::protobuf::SpecialFields,
 }
 
-impl<'a> ::std::default::Default for &'a VMessOutboundSettings {
-    fn default() -> &'a VMessOutboundSettings {
-        <VMessOutboundSettings as ::protobuf::Message>::default_instance()
+impl<'a> ::std::default::Default for &'a H2OutboundSettings {
+    fn default() -> &'a H2OutboundSettings {
+        <H2OutboundSettings as ::protobuf::Message>::default_instance()
     }
 }
 
-impl VMessOutboundSettings {
-    pub fn new() -> VMessOutboundSettings {
+impl H2OutboundSettings {
+    pub fn new() -> H2OutboundSettings {
         ::std::default::Default::default()
     }
 }
 
-impl ::protobuf::Message for VMessOutboundSettings {
-    const NAME: &'static str = "VMessOutboundSettings";
+impl ::protobuf::Message for H2OutboundSettings {
+    const NAME: &'static str = "H2OutboundSettings";
 
     fn is_initialized(&self) -> bool {
         true
@@ -3632,10 +7140,16 @@ impl ::protobuf::Message for VMessOutboundSettings {
                     self.port = is.read_uint32()?;
                 },
                 26 => {
-                    self.uuid = is.read_string()?;
+                    self.username = is.read_string()?;
                 },
                 34 => {
-                    self.security = is.read_string()?;
+                    self.password = is.read_string()?;
+                },
+                40 => {
+                    self.padding = is.read_bool()?;
+                },
+                50 => {
+                    ::protobuf::rt::read_singular_message_into_field(is, &mut self.tls)?;
                 },
                 tag => {
                     ::protobuf::rt::read_unknown_or_skip_group(tag, is, self.special_fields.mut_unknown_fields())?;
@@ -3655,11 +7169,18 @@ impl ::protobuf::Message for VMessOutboundSettings {
         if self.port != 0 {
             my_size += ::protobuf::rt::uint32_size(2, self.port);
         }
-        if !self.uuid.is_empty() {
-            my_size += ::protobuf::rt::string_size(3, &self.uuid);
+        if !self.username.is_empty() {
+            my_size += ::protobuf::rt::string_size(3, &self.username);
         }
-        if !self.security.is_empty() {
-            my_size += ::protobuf::rt::string_size(4, &self.security);
+        if !self.password.is_empty() {
+            my_size += ::protobuf::rt::string_size(4, &self.password);
+        }
+        if self.padding != false {
+            my_size += 1 + 1;
+        }
+        if let Some(v) = self.tls.as_ref() {
+            let len = v.compute_size();
+            my_size += 1 + ::protobuf::rt::compute_raw_varint64_size(len) + len;
         }
         my_size += ::protobuf::rt::unknown_fields_size(self.special_fields.unknown_fields());
         self.special_fields.cached_size().set(my_size as u32);
@@ -3673,11 +7194,17 @@ impl ::protobuf::Message for VMessOutboundSettings {
         if self.port != 0 {
             os.write_uint32(2, self.port)?;
         }
-        if !self.uuid.is_empty() {
-            os.write_string(3, &self.uuid)?;
+        if !self.username.is_empty() {
+            os.write_string(3, &self.username)?;
         }
-        if !self.security.is_empty() {
-            os.write_string(4, &self.security)?;
+        if !self.password.is_empty() {
+            os.write_string(4, &self.password)?;
+        }
+        if self.padding != false {
+            os.write_bool(5, self.padding)?;
+        }
+        if let Some(v) = self.tls.as_ref() {
+            ::protobuf::rt::write_message_field_with_cached_size(6, v, os)?;
         }
         os.write_unknown_fields(self.special_fields.unknown_fields())?;
         ::std::result::Result::Ok(())
@@ -3691,59 +7218,65 @@ impl ::protobuf::Message for VMessOutboundSettings {
         &mut self.special_fields
     }
 
-    fn new() -> VMessOutboundSettings {
-        VMessOutboundSettings::new()
+    fn new() -> H2OutboundSettings {
+        H2OutboundSettings::new()
     }
 
     fn clear(&mut self) {
         self.address.clear();
         self.port = 0;
-        self.uuid.clear();
-        self.security.clear();
+        self.username.clear();
+        self.password.clear();
+        self.padding = false;
+        self.tls.clear();
         self.special_fields.clear();
     }
 
-    fn default_instance() -> &'static VMessOutboundSettings {
-        static instance: VMessOutboundSettings = VMessOutboundSettings {
+    fn default_instance() -> &'static H2OutboundSettings {
+        static instance: H2OutboundSettings = H2OutboundSettings {
             address: ::std::string::String::new(),
             port: 0,
-            uuid: ::std::string::String::new(),
-            security: ::std::string::String::new(),
+            username: ::std::string::String::new(),
+            password: ::std::string::String::new(),
+            padding: false,
+            tls: ::protobuf::MessageField::none(),
             special_fields: ::protobuf::SpecialFields::new(),
         };
         &instance
     }
 }
 
-// @@protoc_insertion_point(message:VlessOutboundSettings)
+// @@protoc_insertion_point(message:ShadowTlsOutboundSettings)
 #[derive(PartialEq,Clone,Default,Debug)]
-pub struct VlessOutboundSettings {
+pub struct ShadowTlsOutboundSettings {
     // message fields
-    // @@protoc_insertion_point(field:VlessOutboundSettings.address)
+    // @@protoc_insertion_point(field:ShadowTlsOutboundSettings.address)
     pub address: ::std::string::String,
-    // @@protoc_insertion_point(field:VlessOutboundSettings.port)
+    // @@protoc_insertion_point(field:ShadowTlsOutboundSettings.port)
     pub port: u32,
-    // @@protoc_insertion_point(field:VlessOutboundSettings.uuid)
-    pub uuid: ::std::string::String,
+    // @@protoc_insertion_point(field:ShadowTlsOutboundSettings.password)
+    pub password: ::std::string::String,
+    // @@protoc_insertion_point(field:ShadowTlsOutboundSettings.handshake_server)
+    pub handshake_server: ::std::string::String,
     // special fields
-    // @@protoc_insertion_point(special_field:VlessOutboundSettings.special_fields)
+    // @@protoc_insertion_point(special_field:ShadowTlsOutboundSettings.special_fields)
     pub special_fields: ::protobuf::SpecialFields,
 }
 
-impl<'a> ::std::default::Default for &'a VlessOutboundSettings {
-    fn default() -> &'a VlessOutboundSettings {
-        <VlessOutboundSettings as ::protobuf::Message>::default_instance()
+impl<'a> ::std::default::Default for &'a ShadowTlsOutboundSettings {
+    fn default() -> &'a ShadowTlsOutboundSettings {
+        <ShadowTlsOutboundSettings as ::protobuf::Message>::default_instance()
     }
 }
 
-impl VlessOutboundSettings {
-    pub fn new() -> VlessOutboundSettings {
+impl ShadowTlsOutboundSettings {
+    pub fn new() -> ShadowTlsOutboundSettings {
         ::std::default::Default::default()
     }
 }
 
-impl ::protobuf::Message for VlessOutboundSettings {
-    const NAME: &'static str = "VlessOutboundSettings";
+impl ::protobuf::Message for ShadowTlsOutboundSettings {
+    const NAME: &'static str = "ShadowTlsOutboundSettings";
 
     fn is_initialized(&self) -> bool {
         true
@@ -3759,7 +7292,10 @@ impl ::protobuf::Message for VlessOutboundSettings {
                     self.port = is.read_uint32()?;
                 },
                 26 => {
-                    self.uuid = is.read_string()?;
+                    self.password = is.read_string()?;
+                },
+                34 => {
+                    self.handshake_server = is.read_string()?;
                 },
                 tag => {
                     ::protobuf::rt::read_unknown_or_skip_group(tag, is, self.special_fields.mut_unknown_fields())?;
@@ -3779,8 +7315,11 @@ impl ::protobuf::Message for VlessOutboundSettings {
         if self.port != 0 {
             my_size += ::protobuf::rt::uint32_size(2, self.port);
         }
-        if !self.uuid.is_empty() {
-            my_size += ::protobuf::rt::string_size(3, &self.uuid);
+        if !self.password.is_empty() {
+            my_size += ::protobuf::rt::string_size(3, &self.password);
+        }
+        if !self.handshake_server.is_empty() {
+            my_size += ::protobuf::rt::string_size(4, &self.handshake_server);
         }
         my_size += ::protobuf::rt::unknown_fields_size(self.special_fields.unknown_fields());
         self.special_fields.cached_size().set(my_size as u32);
@@ -3794,8 +7333,11 @@ impl ::protobuf::Message for VlessOutboundSettings {
         if self.port != 0 {
             os.write_uint32(2, self.port)?;
         }
-        if !self.uuid.is_empty() {
-            os.write_string(3, &self.uuid)?;
+        if !self.password.is_empty() {
+            os.write_string(3, &self.password)?;
+        }
+        if !self.handshake_server.is_empty() {
+            os.write_string(4, &self.handshake_server)?;
         }
         os.write_unknown_fields(self.special_fields.unknown_fields())?;
         ::std::result::Result::Ok(())
@@ -3809,22 +7351,24 @@ impl ::protobuf::Message for VlessOutboundSettings {
         &mut self.special_fields
     }
 
-    fn new() -> VlessOutboundSettings {
-        VlessOutboundSettings::new()
+    fn new() -> ShadowTlsOutboundSettings {
+        ShadowTlsOutboundSettings::new()
     }
 
     fn clear(&mut self) {
         self.address.clear();
         self.port = 0;
-        self.uuid.clear();
+        self.password.clear();
+        self.handshake_server.clear();
         self.special_fields.clear();
     }
 
-    fn default_instance() -> &'static VlessOutboundSettings {
-        static instance: VlessOutboundSettings = VlessOutboundSettings {
+    fn default_instance() -> &'static ShadowTlsOutboundSettings {
+        static instance: ShadowTlsOutboundSettings = ShadowTlsOutboundSettings {
             address: ::std::string::String::new(),
             port: 0,
-            uuid: ::std::string::String::new(),
+            password: ::std::string::String::new(),
+            handshake_server: ::std::string::String::new(),
             special_fields: ::protobuf::SpecialFields::new(),
         };
         &instance
@@ -4191,6 +7735,12 @@ pub struct FailOverOutboundSettings {
     pub health_check_attempts: u32,
     // @@protoc_insertion_point(field:FailOverOutboundSettings.health_check_success_percentage)
     pub health_check_success_percentage: u32,
+    // @@protoc_insertion_point(field:FailOverOutboundSettings.health_check_url)
+    pub health_check_url: ::std::string::String,
+    // @@protoc_insertion_point(field:FailOverOutboundSettings.failures_before_down)
+    pub failures_before_down: u32,
+    // @@protoc_insertion_point(field:FailOverOutboundSettings.successes_before_up)
+    pub successes_before_up: u32,
     // special fields
     // @@protoc_insertion_point(special_field:FailOverOutboundSettings.special_fields)
     pub special_fields: ::protobuf::SpecialFields,
@@ -4269,6 +7819,15 @@ impl ::protobuf::Message for FailOverOutboundSettings {
                 136 => {
                     self.health_check_success_percentage = is.read_uint32()?;
                 },
+                146 => {
+                    self.health_check_url = is.read_string()?;
+                },
+                152 => {
+                    self.failures_before_down = is.read_uint32()?;
+                },
+                160 => {
+                    self.successes_before_up = is.read_uint32()?;
+                },
                 tag => {
                     ::protobuf::rt::read_unknown_or_skip_group(tag, is, self.special_fields.mut_unknown_fields())?;
                 },
@@ -4332,6 +7891,15 @@ impl ::protobuf::Message for FailOverOutboundSettings {
         if self.health_check_success_percentage != 0 {
             my_size += ::protobuf::rt::uint32_size(17, self.health_check_success_percentage);
         }
+        if !self.health_check_url.is_empty() {
+            my_size += ::protobuf::rt::string_size(18, &self.health_check_url);
+        }
+        if self.failures_before_down != 0 {
+            my_size += ::protobuf::rt::uint32_size(19, self.failures_before_down);
+        }
+        if self.successes_before_up != 0 {
+            my_size += ::protobuf::rt::uint32_size(20, self.successes_before_up);
+        }
         my_size += ::protobuf::rt::unknown_fields_size(self.special_fields.unknown_fields());
         self.special_fields.cached_size().set(my_size as u32);
         my_size
@@ -4389,6 +7957,15 @@ impl ::protobuf::Message for FailOverOutboundSettings {
         if self.health_check_success_percentage != 0 {
             os.write_uint32(17, self.health_check_success_percentage)?;
         }
+        if !self.health_check_url.is_empty() {
+            os.write_string(18, &self.health_check_url)?;
+        }
+        if self.failures_before_down != 0 {
+            os.write_uint32(19, self.failures_before_down)?;
+        }
+        if self.successes_before_up != 0 {
+            os.write_uint32(20, self.successes_before_up)?;
+        }
         os.write_unknown_fields(self.special_fields.unknown_fields())?;
         ::std::result::Result::Ok(())
     }
@@ -4423,6 +8000,9 @@ impl ::protobuf::Message for FailOverOutboundSettings {
         self.health_check_wait = false;
         self.health_check_attempts = 0;
         self.health_check_success_percentage = 0;
+        self.health_check_url.clear();
+        self.failures_before_down = 0;
+        self.successes_before_up = 0;
         self.special_fields.clear();
     }
 
@@ -4445,6 +8025,9 @@ impl ::protobuf::Message for FailOverOutboundSettings {
             health_check_wait: false,
             health_check_attempts: 0,
             health_check_success_percentage: 0,
+            health_check_url: ::std::string::String::new(),
+            failures_before_down: 0,
+            successes_before_up: 0,
             special_fields: ::protobuf::SpecialFields::new(),
         };
         &instance
@@ -4457,6 +8040,18 @@ pub struct SelectOutboundSettings {
     // message fields
     // @@protoc_insertion_point(field:SelectOutboundSettings.actors)
     pub actors: ::std::vec::Vec<::std::string::String>,
+    // @@protoc_insertion_point(field:SelectOutboundSettings.persist)
+    pub persist: bool,
+    // @@protoc_insertion_point(field:SelectOutboundSettings.cache_file)
+    pub cache_file: ::std::string::String,
+    // @@protoc_insertion_point(field:SelectOutboundSettings.fallback_on_failure)
+    pub fallback_on_failure: bool,
+    // @@protoc_insertion_point(field:SelectOutboundSettings.failures_before_down)
+    pub failures_before_down: u32,
+    // @@protoc_insertion_point(field:SelectOutboundSettings.health_check_url)
+    pub health_check_url: ::std::string::String,
+    // @@protoc_insertion_point(field:SelectOutboundSettings.check_interval)
+    pub check_interval: u32,
     // special fields
     // @@protoc_insertion_point(special_field:SelectOutboundSettings.special_fields)
     pub special_fields: ::protobuf::SpecialFields,
@@ -4487,6 +8082,177 @@ impl ::protobuf::Message for SelectOutboundSettings {
                 10 => {
                     self.actors.push(is.read_string()?);
                 },
+                16 => {
+                    self.persist = is.read_bool()?;
+                },
+                26 => {
+                    self.cache_file = is.read_string()?;
+                },
+                32 => {
+                    self.fallback_on_failure = is.read_bool()?;
+                },
+                40 => {
+                    self.failures_before_down = is.read_uint32()?;
+                },
+                50 => {
+                    self.health_check_url = is.read_string()?;
+                },
+                56 => {
+                    self.check_interval = is.read_uint32()?;
+                },
+                tag => {
+                    ::protobuf::rt::read_unknown_or_skip_group(tag, is, self.special_fields.mut_unknown_fields())?;
+                },
+            };
+        }
+        ::std::result::Result::Ok(())
+    }
+
+    // Compute sizes of nested messages
+    #[allow(unused_variables)]
+    fn compute_size(&self) -> u64 {
+        let mut my_size = 0;
+        for value in &self.actors {
+            my_size += ::protobuf::rt::string_size(1, &value);
+        };
+        if self.persist != false {
+            my_size += 1 + 1;
+        }
+        if !self.cache_file.is_empty() {
+            my_size += ::protobuf::rt::string_size(3, &self.cache_file);
+        }
+        if self.fallback_on_failure != false {
+            my_size += 1 + 1;
+        }
+        if self.failures_before_down != 0 {
+            my_size += ::protobuf::rt::uint32_size(5, self.failures_before_down);
+        }
+        if !self.health_check_url.is_empty() {
+            my_size += ::protobuf::rt::string_size(6, &self.health_check_url);
+        }
+        if self.check_interval != 0 {
+            my_size += ::protobuf::rt::uint32_size(7, self.check_interval);
+        }
+        my_size += ::protobuf::rt::unknown_fields_size(self.special_fields.unknown_fields());
+        self.special_fields.cached_size().set(my_size as u32);
+        my_size
+    }
+
+    fn write_to_with_cached_sizes(&self, os: &mut ::protobuf::CodedOutputStream<'_>) -> ::protobuf::Result<()> {
+        for v in &self.actors {
+            os.write_string(1, &v)?;
+        };
+        if self.persist != false {
+            os.write_bool(2, self.persist)?;
+        }
+        if !self.cache_file.is_empty() {
+            os.write_string(3, &self.cache_file)?;
+        }
+        if self.fallback_on_failure != false {
+            os.write_bool(4, self.fallback_on_failure)?;
+        }
+        if self.failures_before_down != 0 {
+            os.write_uint32(5, self.failures_before_down)?;
+        }
+        if !self.health_check_url.is_empty() {
+            os.write_string(6, &self.health_check_url)?;
+        }
+        if self.check_interval != 0 {
+            os.write_uint32(7, self.check_interval)?;
+        }
+        os.write_unknown_fields(self.special_fields.unknown_fields())?;
+        ::std::result::Result::Ok(())
+    }
+
+    fn special_fields(&self) -> &::protobuf::SpecialFields {
+        &self.special_fields
+    }
+
+    fn mut_special_fields(&mut self) -> &mut ::protobuf::SpecialFields {
+        &mut self.special_fields
+    }
+
+    fn new() -> SelectOutboundSettings {
+        SelectOutboundSettings::new()
+    }
+
+    fn clear(&mut self) {
+        self.actors.clear();
+        self.persist = false;
+        self.cache_file.clear();
+        self.fallback_on_failure = false;
+        self.failures_before_down = 0;
+        self.health_check_url.clear();
+        self.check_interval = 0;
+        self.special_fields.clear();
+    }
+
+    fn default_instance() -> &'static SelectOutboundSettings {
+        static instance: SelectOutboundSettings = SelectOutboundSettings {
+            actors: ::std::vec::Vec::new(),
+            persist: false,
+            cache_file: ::std::string::String::new(),
+            fallback_on_failure: false,
+            failures_before_down: 0,
+            health_check_url: ::std::string::String::new(),
+            check_interval: 0,
+            special_fields: ::protobuf::SpecialFields::new(),
+        };
+        &instance
+    }
+}
+
+// @@protoc_insertion_point(message:UrlTestOutboundSettings)
+#[derive(PartialEq,Clone,Default,Debug)]
+pub struct UrlTestOutboundSettings {
+    // message fields
+    // @@protoc_insertion_point(field:UrlTestOutboundSettings.actors)
+    pub actors: ::std::vec::Vec<::std::string::String>,
+    // @@protoc_insertion_point(field:UrlTestOutboundSettings.url)
+    pub url: ::std::string::String,
+    // @@protoc_insertion_point(field:UrlTestOutboundSettings.interval)
+    pub interval: u32,
+    // @@protoc_insertion_point(field:UrlTestOutboundSettings.tolerance)
+    pub tolerance: u32,
+    // special fields
+    // @@protoc_insertion_point(special_field:UrlTestOutboundSettings.special_fields)
+    pub special_fields: ::protobuf::SpecialFields,
+}
+
+impl<'a> ::std::default::Default for &'a UrlTestOutboundSettings {
+    fn default() -> &'a UrlTestOutboundSettings {
+        <UrlTestOutboundSettings as ::protobuf::Message>::default_instance()
+    }
+}
+
+impl UrlTestOutboundSettings {
+    pub fn new() -> UrlTestOutboundSettings {
+        ::std::default::Default::default()
+    }
+}
+
+impl ::protobuf::Message for UrlTestOutboundSettings {
+    const NAME: &'static str = "UrlTestOutboundSettings";
+
+    fn is_initialized(&self) -> bool {
+        true
+    }
+
+    fn merge_from(&mut self, is: &mut ::protobuf::CodedInputStream<'_>) -> ::protobuf::Result<()> {
+        while let Some(tag) = is.read_raw_tag_or_eof()? {
+            match tag {
+                10 => {
+                    self.actors.push(is.read_string()?);
+                },
+                18 => {
+                    self.url = is.read_string()?;
+                },
+                24 => {
+                    self.interval = is.read_uint32()?;
+                },
+                32 => {
+                    self.tolerance = is.read_uint32()?;
+                },
                 tag => {
                     ::protobuf::rt::read_unknown_or_skip_group(tag, is, self.special_fields.mut_unknown_fields())?;
                 },
@@ -4502,6 +8268,15 @@ impl ::protobuf::Message for SelectOutboundSettings {
         for value in &self.actors {
             my_size += ::protobuf::rt::string_size(1, &value);
         };
+        if !self.url.is_empty() {
+            my_size += ::protobuf::rt::string_size(2, &self.url);
+        }
+        if self.interval != 0 {
+            my_size += ::protobuf::rt::uint32_size(3, self.interval);
+        }
+        if self.tolerance != 0 {
+            my_size += ::protobuf::rt::uint32_size(4, self.tolerance);
+        }
         my_size += ::protobuf::rt::unknown_fields_size(self.special_fields.unknown_fields());
         self.special_fields.cached_size().set(my_size as u32);
         my_size
@@ -4511,6 +8286,15 @@ impl ::protobuf::Message for SelectOutboundSettings {
         for v in &self.actors {
             os.write_string(1, &v)?;
         };
+        if !self.url.is_empty() {
+            os.write_string(2, &self.url)?;
+        }
+        if self.interval != 0 {
+            os.write_uint32(3, self.interval)?;
+        }
+        if self.tolerance != 0 {
+            os.write_uint32(4, self.tolerance)?;
+        }
         os.write_unknown_fields(self.special_fields.unknown_fields())?;
         ::std::result::Result::Ok(())
     }
@@ -4523,18 +8307,24 @@ impl ::protobuf::Message for SelectOutboundSettings {
         &mut self.special_fields
     }
 
-    fn new() -> SelectOutboundSettings {
-        SelectOutboundSettings::new()
+    fn new() -> UrlTestOutboundSettings {
+        UrlTestOutboundSettings::new()
     }
 
     fn clear(&mut self) {
         self.actors.clear();
+        self.url.clear();
+        self.interval = 0;
+        self.tolerance = 0;
         self.special_fields.clear();
     }
 
-    fn default_instance() -> &'static SelectOutboundSettings {
-        static instance: SelectOutboundSettings = SelectOutboundSettings {
+    fn default_instance() -> &'static UrlTestOutboundSettings {
+        static instance: UrlTestOutboundSettings = UrlTestOutboundSettings {
             actors: ::std::vec::Vec::new(),
+            url: ::std::string::String::new(),
+            interval: 0,
+            tolerance: 0,
             special_fields: ::protobuf::SpecialFields::new(),
         };
         &instance
@@ -4768,6 +8558,14 @@ pub struct Router {
     pub rules: ::std::vec::Vec<router::Rule>,
     // @@protoc_insertion_point(field:Router.domain_resolve)
     pub domain_resolve: bool,
+    // @@protoc_insertion_point(field:Router.rule_providers)
+    pub rule_providers: ::std::vec::Vec<router::RuleProvider>,
+    // @@protoc_insertion_point(field:Router.geoip_url)
+    pub geoip_url: ::std::string::String,
+    // @@protoc_insertion_point(field:Router.geoip_update_interval)
+    pub geoip_update_interval: u32,
+    // @@protoc_insertion_point(field:Router.geoip_outbound)
+    pub geoip_outbound: ::std::string::String,
     // special fields
     // @@protoc_insertion_point(special_field:Router.special_fields)
     pub special_fields: ::protobuf::SpecialFields,
@@ -4801,6 +8599,18 @@ impl ::protobuf::Message for Router {
                 16 => {
                     self.domain_resolve = is.read_bool()?;
                 },
+                26 => {
+                    self.rule_providers.push(is.read_message()?);
+                },
+                34 => {
+                    self.geoip_url = is.read_string()?;
+                },
+                40 => {
+                    self.geoip_update_interval = is.read_uint32()?;
+                },
+                50 => {
+                    self.geoip_outbound = is.read_string()?;
+                },
                 tag => {
                     ::protobuf::rt::read_unknown_or_skip_group(tag, is, self.special_fields.mut_unknown_fields())?;
                 },
@@ -4820,6 +8630,19 @@ impl ::protobuf::Message for Router {
         if self.domain_resolve != false {
             my_size += 1 + 1;
         }
+        for value in &self.rule_providers {
+            let len = value.compute_size();
+            my_size += 1 + ::protobuf::rt::compute_raw_varint64_size(len) + len;
+        };
+        if !self.geoip_url.is_empty() {
+            my_size += ::protobuf::rt::string_size(4, &self.geoip_url);
+        }
+        if self.geoip_update_interval != 0 {
+            my_size += ::protobuf::rt::uint32_size(5, self.geoip_update_interval);
+        }
+        if !self.geoip_outbound.is_empty() {
+            my_size += ::protobuf::rt::string_size(6, &self.geoip_outbound);
+        }
         my_size += ::protobuf::rt::unknown_fields_size(self.special_fields.unknown_fields());
         self.special_fields.cached_size().set(my_size as u32);
         my_size
@@ -4832,6 +8655,18 @@ impl ::protobuf::Message for Router {
         if self.domain_resolve != false {
             os.write_bool(2, self.domain_resolve)?;
         }
+        for v in &self.rule_providers {
+            ::protobuf::rt::write_message_field_with_cached_size(3, v, os)?;
+        };
+        if !self.geoip_url.is_empty() {
+            os.write_string(4, &self.geoip_url)?;
+        }
+        if self.geoip_update_interval != 0 {
+            os.write_uint32(5, self.geoip_update_interval)?;
+        }
+        if !self.geoip_outbound.is_empty() {
+            os.write_string(6, &self.geoip_outbound)?;
+        }
         os.write_unknown_fields(self.special_fields.unknown_fields())?;
         ::std::result::Result::Ok(())
     }
@@ -4851,6 +8686,10 @@ impl ::protobuf::Message for Router {
     fn clear(&mut self) {
         self.rules.clear();
         self.domain_resolve = false;
+        self.rule_providers.clear();
+        self.geoip_url.clear();
+        self.geoip_update_interval = 0;
+        self.geoip_outbound.clear();
         self.special_fields.clear();
     }
 
@@ -4858,6 +8697,10 @@ impl ::protobuf::Message for Router {
         static instance: Router = Router {
             rules: ::std::vec::Vec::new(),
             domain_resolve: false,
+            rule_providers: ::std::vec::Vec::new(),
+            geoip_url: ::std::string::String::new(),
+            geoip_update_interval: 0,
+            geoip_outbound: ::std::string::String::new(),
             special_fields: ::protobuf::SpecialFields::new(),
         };
         &instance
@@ -4886,6 +8729,32 @@ pub mod router {
         pub inbound_tags: ::std::vec::Vec<::std::string::String>,
         // @@protoc_insertion_point(field:Router.Rule.process_names)
         pub process_names: ::std::vec::Vec<::std::string::String>,
+        // @@protoc_insertion_point(field:Router.Rule.alpns)
+        pub alpns: ::std::vec::Vec<::std::string::String>,
+        // @@protoc_insertion_point(field:Router.Rule.snis)
+        pub snis: ::std::vec::Vec<rule::Domain>,
+        // @@protoc_insertion_point(field:Router.Rule.users)
+        pub users: ::std::vec::Vec<::std::string::String>,
+        // @@protoc_insertion_point(field:Router.Rule.rule_providers)
+        pub rule_providers: ::std::vec::Vec<::std::string::String>,
+        // @@protoc_insertion_point(field:Router.Rule.processes)
+        pub processes: ::std::vec::Vec<::std::string::String>,
+        // @@protoc_insertion_point(field:Router.Rule.process_paths)
+        pub process_paths: ::std::vec::Vec<::std::string::String>,
+        // @@protoc_insertion_point(field:Router.Rule.source_ip_cidrs)
+        pub source_ip_cidrs: ::std::vec::Vec<::std::string::String>,
+        // @@protoc_insertion_point(field:Router.Rule.source_ports)
+        pub source_ports: ::std::vec::Vec<::std::string::String>,
+        // @@protoc_insertion_point(field:Router.Rule.asns)
+        pub asns: ::std::vec::Vec<rule::Asn>,
+        // @@protoc_insertion_point(field:Router.Rule.logic)
+        pub logic: ::std::string::String,
+        // @@protoc_insertion_point(field:Router.Rule.sub_rules)
+        pub sub_rules: ::std::vec::Vec<Rule>,
+        // @@protoc_insertion_point(field:Router.Rule.protocols)
+        pub protocols: ::std::vec::Vec<::std::string::String>,
+        // @@protoc_insertion_point(field:Router.Rule.set_domains)
+        pub set_domains: ::std::vec::Vec<rule::Domain>,
         // special fields
         // @@protoc_insertion_point(special_field:Router.Rule.special_fields)
         pub special_fields: ::protobuf::SpecialFields,
@@ -4937,6 +8806,45 @@ pub mod router {
                     66 => {
                         self.process_names.push(is.read_string()?);
                     },
+                    74 => {
+                        self.alpns.push(is.read_string()?);
+                    },
+                    82 => {
+                        self.snis.push(is.read_message()?);
+                    },
+                    90 => {
+                        self.users.push(is.read_string()?);
+                    },
+                    98 => {
+                        self.rule_providers.push(is.read_string()?);
+                    },
+                    106 => {
+                        self.processes.push(is.read_string()?);
+                    },
+                    114 => {
+                        self.process_paths.push(is.read_string()?);
+                    },
+                    122 => {
+                        self.source_ip_cidrs.push(is.read_string()?);
+                    },
+                    130 => {
+                        self.source_ports.push(is.read_string()?);
+                    },
+                    138 => {
+                        self.asns.push(is.read_message()?);
+                    },
+                    146 => {
+                        self.logic = is.read_string()?;
+                    },
+                    154 => {
+                        self.sub_rules.push(is.read_message()?);
+                    },
+                    162 => {
+                        self.protocols.push(is.read_string()?);
+                    },
+                    170 => {
+                        self.set_domains.push(is.read_message()?);
+                    },
                     tag => {
                         ::protobuf::rt::read_unknown_or_skip_group(tag, is, self.special_fields.mut_unknown_fields())?;
                     },
@@ -4975,6 +8883,49 @@ pub mod router {
             for value in &self.process_names {
                 my_size += ::protobuf::rt::string_size(8, &value);
             };
+            for value in &self.alpns {
+                my_size += ::protobuf::rt::string_size(9, &value);
+            };
+            for value in &self.snis {
+                let len = value.compute_size();
+                my_size += 1 + ::protobuf::rt::compute_raw_varint64_size(len) + len;
+            };
+            for value in &self.users {
+                my_size += ::protobuf::rt::string_size(11, &value);
+            };
+            for value in &self.rule_providers {
+                my_size += ::protobuf::rt::string_size(12, &value);
+            };
+            for value in &self.processes {
+                my_size += ::protobuf::rt::string_size(13, &value);
+            };
+            for value in &self.process_paths {
+                my_size += ::protobuf::rt::string_size(14, &value);
+            };
+            for value in &self.source_ip_cidrs {
+                my_size += ::protobuf::rt::string_size(15, &value);
+            };
+            for value in &self.source_ports {
+                my_size += ::protobuf::rt::string_size(16, &value);
+            };
+            for value in &self.asns {
+                let len = value.compute_size();
+                my_size += 2 + ::protobuf::rt::compute_raw_varint64_size(len) + len;
+            };
+            if !self.logic.is_empty() {
+                my_size += ::protobuf::rt::string_size(18, &self.logic);
+            }
+            for value in &self.sub_rules {
+                let len = value.compute_size();
+                my_size += 2 + ::protobuf::rt::compute_raw_varint64_size(len) + len;
+            };
+            for value in &self.protocols {
+                my_size += ::protobuf::rt::string_size(20, &value);
+            };
+            for value in &self.set_domains {
+                let len = value.compute_size();
+                my_size += 2 + ::protobuf::rt::compute_raw_varint64_size(len) + len;
+            };
             my_size += ::protobuf::rt::unknown_fields_size(self.special_fields.unknown_fields());
             self.special_fields.cached_size().set(my_size as u32);
             my_size
@@ -5005,6 +8956,45 @@ pub mod router {
             for v in &self.process_names {
                 os.write_string(8, &v)?;
             };
+            for v in &self.alpns {
+                os.write_string(9, &v)?;
+            };
+            for v in &self.snis {
+                ::protobuf::rt::write_message_field_with_cached_size(10, v, os)?;
+            };
+            for v in &self.users {
+                os.write_string(11, &v)?;
+            };
+            for v in &self.rule_providers {
+                os.write_string(12, &v)?;
+            };
+            for v in &self.processes {
+                os.write_string(13, &v)?;
+            };
+            for v in &self.process_paths {
+                os.write_string(14, &v)?;
+            };
+            for v in &self.source_ip_cidrs {
+                os.write_string(15, &v)?;
+            };
+            for v in &self.source_ports {
+                os.write_string(16, &v)?;
+            };
+            for v in &self.asns {
+                ::protobuf::rt::write_message_field_with_cached_size(17, v, os)?;
+            };
+            if !self.logic.is_empty() {
+                os.write_string(18, &self.logic)?;
+            }
+            for v in &self.sub_rules {
+                ::protobuf::rt::write_message_field_with_cached_size(19, v, os)?;
+            };
+            for v in &self.protocols {
+                os.write_string(20, &v)?;
+            };
+            for v in &self.set_domains {
+                ::protobuf::rt::write_message_field_with_cached_size(21, v, os)?;
+            };
             os.write_unknown_fields(self.special_fields.unknown_fields())?;
             ::std::result::Result::Ok(())
         }
@@ -5030,6 +9020,19 @@ pub mod router {
             self.networks.clear();
             self.inbound_tags.clear();
             self.process_names.clear();
+            self.alpns.clear();
+            self.snis.clear();
+            self.users.clear();
+            self.rule_providers.clear();
+            self.processes.clear();
+            self.process_paths.clear();
+            self.source_ip_cidrs.clear();
+            self.source_ports.clear();
+            self.asns.clear();
+            self.logic.clear();
+            self.sub_rules.clear();
+            self.protocols.clear();
+            self.set_domains.clear();
             self.special_fields.clear();
         }
 
@@ -5043,6 +9046,174 @@ pub mod router {
                 networks: ::std::vec::Vec::new(),
                 inbound_tags: ::std::vec::Vec::new(),
                 process_names: ::std::vec::Vec::new(),
+                alpns: ::std::vec::Vec::new(),
+                snis: ::std::vec::Vec::new(),
+                users: ::std::vec::Vec::new(),
+                rule_providers: ::std::vec::Vec::new(),
+                processes: ::std::vec::Vec::new(),
+                process_paths: ::std::vec::Vec::new(),
+                source_ip_cidrs: ::std::vec::Vec::new(),
+                source_ports: ::std::vec::Vec::new(),
+                asns: ::std::vec::Vec::new(),
+                logic: ::std::string::String::new(),
+                sub_rules: ::std::vec::Vec::new(),
+                protocols: ::std::vec::Vec::new(),
+                set_domains: ::std::vec::Vec::new(),
+                special_fields: ::protobuf::SpecialFields::new(),
+            };
+            &instance
+        }
+    }
+
+    // @@protoc_insertion_point(message:Router.RuleProvider)
+    #[derive(PartialEq,Clone,Default,Debug)]
+    pub struct RuleProvider {
+        // message fields
+        // @@protoc_insertion_point(field:Router.RuleProvider.name)
+        pub name: ::std::string::String,
+        // @@protoc_insertion_point(field:Router.RuleProvider.url)
+        pub url: ::std::string::String,
+        // @@protoc_insertion_point(field:Router.RuleProvider.interval)
+        pub interval: u32,
+        // @@protoc_insertion_point(field:Router.RuleProvider.format)
+        pub format: ::std::string::String,
+        // @@protoc_insertion_point(field:Router.RuleProvider.path)
+        pub path: ::std::string::String,
+        // @@protoc_insertion_point(field:Router.RuleProvider.outbound)
+        pub outbound: ::std::string::String,
+        // special fields
+        // @@protoc_insertion_point(special_field:Router.RuleProvider.special_fields)
+        pub special_fields: ::protobuf::SpecialFields,
+    }
+
+    impl<'a> ::std::default::Default for &'a RuleProvider {
+        fn default() -> &'a RuleProvider {
+            <RuleProvider as ::protobuf::Message>::default_instance()
+        }
+    }
+
+    impl RuleProvider {
+        pub fn new() -> RuleProvider {
+            ::std::default::Default::default()
+        }
+    }
+
+    impl ::protobuf::Message for RuleProvider {
+        const NAME: &'static str = "RuleProvider";
+
+        fn is_initialized(&self) -> bool {
+            true
+        }
+
+        fn merge_from(&mut self, is: &mut ::protobuf::CodedInputStream<'_>) -> ::protobuf::Result<()> {
+            while let Some(tag) = is.read_raw_tag_or_eof()? {
+                match tag {
+                    10 => {
+                        self.name = is.read_string()?;
+                    },
+                    18 => {
+                        self.url = is.read_string()?;
+                    },
+                    24 => {
+                        self.interval = is.read_uint32()?;
+                    },
+                    34 => {
+                        self.format = is.read_string()?;
+                    },
+                    42 => {
+                        self.path = is.read_string()?;
+                    },
+                    50 => {
+                        self.outbound = is.read_string()?;
+                    },
+                    tag => {
+                        ::protobuf::rt::read_unknown_or_skip_group(tag, is, self.special_fields.mut_unknown_fields())?;
+                    },
+                };
+            }
+            ::std::result::Result::Ok(())
+        }
+
+        // Compute sizes of nested messages
+        #[allow(unused_variables)]
+        fn compute_size(&self) -> u64 {
+            let mut my_size = 0;
+            if !self.name.is_empty() {
+                my_size += ::protobuf::rt::string_size(1, &self.name);
+            }
+            if !self.url.is_empty() {
+                my_size += ::protobuf::rt::string_size(2, &self.url);
+            }
+            if self.interval != 0 {
+                my_size += ::protobuf::rt::uint32_size(3, self.interval);
+            }
+            if !self.format.is_empty() {
+                my_size += ::protobuf::rt::string_size(4, &self.format);
+            }
+            if !self.path.is_empty() {
+                my_size += ::protobuf::rt::string_size(5, &self.path);
+            }
+            if !self.outbound.is_empty() {
+                my_size += ::protobuf::rt::string_size(6, &self.outbound);
+            }
+            my_size += ::protobuf::rt::unknown_fields_size(self.special_fields.unknown_fields());
+            self.special_fields.cached_size().set(my_size as u32);
+            my_size
+        }
+
+        fn write_to_with_cached_sizes(&self, os: &mut ::protobuf::CodedOutputStream<'_>) -> ::protobuf::Result<()> {
+            if !self.name.is_empty() {
+                os.write_string(1, &self.name)?;
+            }
+            if !self.url.is_empty() {
+                os.write_string(2, &self.url)?;
+            }
+            if self.interval != 0 {
+                os.write_uint32(3, self.interval)?;
+            }
+            if !self.format.is_empty() {
+                os.write_string(4, &self.format)?;
+            }
+            if !self.path.is_empty() {
+                os.write_string(5, &self.path)?;
+            }
+            if !self.outbound.is_empty() {
+                os.write_string(6, &self.outbound)?;
+            }
+            os.write_unknown_fields(self.special_fields.unknown_fields())?;
+            ::std::result::Result::Ok(())
+        }
+
+        fn special_fields(&self) -> &::protobuf::SpecialFields {
+            &self.special_fields
+        }
+
+        fn mut_special_fields(&mut self) -> &mut ::protobuf::SpecialFields {
+            &mut self.special_fields
+        }
+
+        fn new() -> RuleProvider {
+            RuleProvider::new()
+        }
+
+        fn clear(&mut self) {
+            self.name.clear();
+            self.url.clear();
+            self.interval = 0;
+            self.format.clear();
+            self.path.clear();
+            self.outbound.clear();
+            self.special_fields.clear();
+        }
+
+        fn default_instance() -> &'static RuleProvider {
+            static instance: RuleProvider = RuleProvider {
+                name: ::std::string::String::new(),
+                url: ::std::string::String::new(),
+                interval: 0,
+                format: ::std::string::String::new(),
+                path: ::std::string::String::new(),
+                outbound: ::std::string::String::new(),
                 special_fields: ::protobuf::SpecialFields::new(),
             };
             &instance
@@ -5165,6 +9336,8 @@ pub mod router {
                 DOMAIN = 1,
                 // @@protoc_insertion_point(enum_value:Router.Rule.Domain.Type.FULL)
                 FULL = 2,
+                // @@protoc_insertion_point(enum_value:Router.Rule.Domain.Type.REGEX)
+                REGEX = 3,
             }
 
             impl ::protobuf::Enum for Type {
@@ -5179,61 +9352,167 @@ pub mod router {
                         0 => ::std::option::Option::Some(Type::PLAIN),
                         1 => ::std::option::Option::Some(Type::DOMAIN),
                         2 => ::std::option::Option::Some(Type::FULL),
+                        3 => ::std::option::Option::Some(Type::REGEX),
                         _ => ::std::option::Option::None
                     }
                 }
 
-                fn from_str(str: &str) -> ::std::option::Option<Type> {
-                    match str {
-                        "PLAIN" => ::std::option::Option::Some(Type::PLAIN),
-                        "DOMAIN" => ::std::option::Option::Some(Type::DOMAIN),
-                        "FULL" => ::std::option::Option::Some(Type::FULL),
-                        _ => ::std::option::Option::None
-                    }
-                }
+                fn from_str(str: &str) -> ::std::option::Option<Type> {
+                    match str {
+                        "PLAIN" => ::std::option::Option::Some(Type::PLAIN),
+                        "DOMAIN" => ::std::option::Option::Some(Type::DOMAIN),
+                        "FULL" => ::std::option::Option::Some(Type::FULL),
+                        "REGEX" => ::std::option::Option::Some(Type::REGEX),
+                        _ => ::std::option::Option::None
+                    }
+                }
+
+                const VALUES: &'static [Type] = &[
+                    Type::PLAIN,
+                    Type::DOMAIN,
+                    Type::FULL,
+                    Type::REGEX,
+                ];
+            }
+
+            impl ::std::default::Default for Type {
+                fn default() -> Self {
+                    Type::PLAIN
+                }
+            }
+
+        }
+
+        // @@protoc_insertion_point(message:Router.Rule.Mmdb)
+        #[derive(PartialEq,Clone,Default,Debug)]
+        pub struct Mmdb {
+            // message fields
+            // @@protoc_insertion_point(field:Router.Rule.Mmdb.file)
+            pub file: ::std::string::String,
+            // @@protoc_insertion_point(field:Router.Rule.Mmdb.country_code)
+            pub country_code: ::std::string::String,
+            // special fields
+            // @@protoc_insertion_point(special_field:Router.Rule.Mmdb.special_fields)
+            pub special_fields: ::protobuf::SpecialFields,
+        }
+
+        impl<'a> ::std::default::Default for &'a Mmdb {
+            fn default() -> &'a Mmdb {
+                <Mmdb as ::protobuf::Message>::default_instance()
+            }
+        }
+
+        impl Mmdb {
+            pub fn new() -> Mmdb {
+                ::std::default::Default::default()
+            }
+        }
+
+        impl ::protobuf::Message for Mmdb {
+            const NAME: &'static str = "Mmdb";
+
+            fn is_initialized(&self) -> bool {
+                true
+            }
+
+            fn merge_from(&mut self, is: &mut ::protobuf::CodedInputStream<'_>) -> ::protobuf::Result<()> {
+                while let Some(tag) = is.read_raw_tag_or_eof()? {
+                    match tag {
+                        10 => {
+                            self.file = is.read_string()?;
+                        },
+                        18 => {
+                            self.country_code = is.read_string()?;
+                        },
+                        tag => {
+                            ::protobuf::rt::read_unknown_or_skip_group(tag, is, self.special_fields.mut_unknown_fields())?;
+                        },
+                    };
+                }
+                ::std::result::Result::Ok(())
+            }
+
+            // Compute sizes of nested messages
+            #[allow(unused_variables)]
+            fn compute_size(&self) -> u64 {
+                let mut my_size = 0;
+                if !self.file.is_empty() {
+                    my_size += ::protobuf::rt::string_size(1, &self.file);
+                }
+                if !self.country_code.is_empty() {
+                    my_size += ::protobuf::rt::string_size(2, &self.country_code);
+                }
+                my_size += ::protobuf::rt::unknown_fields_size(self.special_fields.unknown_fields());
+                self.special_fields.cached_size().set(my_size as u32);
+                my_size
+            }
+
+            fn write_to_with_cached_sizes(&self, os: &mut ::protobuf::CodedOutputStream<'_>) -> ::protobuf::Result<()> {
+                if !self.file.is_empty() {
+                    os.write_string(1, &self.file)?;
+                }
+                if !self.country_code.is_empty() {
+                    os.write_string(2, &self.country_code)?;
+                }
+                os.write_unknown_fields(self.special_fields.unknown_fields())?;
+                ::std::result::Result::Ok(())
+            }
+
+            fn special_fields(&self) -> &::protobuf::SpecialFields {
+                &self.special_fields
+            }
 
-                const VALUES: &'static [Type] = &[
-                    Type::PLAIN,
-                    Type::DOMAIN,
-                    Type::FULL,
-                ];
+            fn mut_special_fields(&mut self) -> &mut ::protobuf::SpecialFields {
+                &mut self.special_fields
             }
 
-            impl ::std::default::Default for Type {
-                fn default() -> Self {
-                    Type::PLAIN
-                }
+            fn new() -> Mmdb {
+                Mmdb::new()
+            }
+
+            fn clear(&mut self) {
+                self.file.clear();
+                self.country_code.clear();
+                self.special_fields.clear();
             }
 
+            fn default_instance() -> &'static Mmdb {
+                static instance: Mmdb = Mmdb {
+                    file: ::std::string::String::new(),
+                    country_code: ::std::string::String::new(),
+                    special_fields: ::protobuf::SpecialFields::new(),
+                };
+                &instance
+            }
         }
 
-        // @@protoc_insertion_point(message:Router.Rule.Mmdb)
+        // @@protoc_insertion_point(message:Router.Rule.Asn)
         #[derive(PartialEq,Clone,Default,Debug)]
-        pub struct Mmdb {
+        pub struct Asn {
             // message fields
-            // @@protoc_insertion_point(field:Router.Rule.Mmdb.file)
+            // @@protoc_insertion_point(field:Router.Rule.Asn.file)
             pub file: ::std::string::String,
-            // @@protoc_insertion_point(field:Router.Rule.Mmdb.country_code)
-            pub country_code: ::std::string::String,
+            // @@protoc_insertion_point(field:Router.Rule.Asn.number)
+            pub number: u32,
             // special fields
-            // @@protoc_insertion_point(special_field:Router.Rule.Mmdb.special_fields)
+            // @@protoc_insertion_point(special_field:Router.Rule.Asn.special_fields)
             pub special_fields: ::protobuf::SpecialFields,
         }
 
-        impl<'a> ::std::default::Default for &'a Mmdb {
-            fn default() -> &'a Mmdb {
-                <Mmdb as ::protobuf::Message>::default_instance()
+        impl<'a> ::std::default::Default for &'a Asn {
+            fn default() -> &'a Asn {
+                <Asn as ::protobuf::Message>::default_instance()
             }
         }
 
-        impl Mmdb {
-            pub fn new() -> Mmdb {
+        impl Asn {
+            pub fn new() -> Asn {
                 ::std::default::Default::default()
             }
         }
 
-        impl ::protobuf::Message for Mmdb {
-            const NAME: &'static str = "Mmdb";
+        impl ::protobuf::Message for Asn {
+            const NAME: &'static str = "Asn";
 
             fn is_initialized(&self) -> bool {
                 true
@@ -5245,8 +9524,8 @@ pub mod router {
                         10 => {
                             self.file = is.read_string()?;
                         },
-                        18 => {
-                            self.country_code = is.read_string()?;
+                        16 => {
+                            self.number = is.read_uint32()?;
                         },
                         tag => {
                             ::protobuf::rt::read_unknown_or_skip_group(tag, is, self.special_fields.mut_unknown_fields())?;
@@ -5263,8 +9542,8 @@ pub mod router {
                 if !self.file.is_empty() {
                     my_size += ::protobuf::rt::string_size(1, &self.file);
                 }
-                if !self.country_code.is_empty() {
-                    my_size += ::protobuf::rt::string_size(2, &self.country_code);
+                if self.number != 0 {
+                    my_size += ::protobuf::rt::uint32_size(2, self.number);
                 }
                 my_size += ::protobuf::rt::unknown_fields_size(self.special_fields.unknown_fields());
                 self.special_fields.cached_size().set(my_size as u32);
@@ -5275,8 +9554,8 @@ pub mod router {
                 if !self.file.is_empty() {
                     os.write_string(1, &self.file)?;
                 }
-                if !self.country_code.is_empty() {
-                    os.write_string(2, &self.country_code)?;
+                if self.number != 0 {
+                    os.write_uint32(2, self.number)?;
                 }
                 os.write_unknown_fields(self.special_fields.unknown_fields())?;
                 ::std::result::Result::Ok(())
@@ -5290,20 +9569,20 @@ pub mod router {
                 &mut self.special_fields
             }
 
-            fn new() -> Mmdb {
-                Mmdb::new()
+            fn new() -> Asn {
+                Asn::new()
             }
 
             fn clear(&mut self) {
                 self.file.clear();
-                self.country_code.clear();
+                self.number = 0;
                 self.special_fields.clear();
             }
 
-            fn default_instance() -> &'static Mmdb {
-                static instance: Mmdb = Mmdb {
+            fn default_instance() -> &'static Asn {
+                static instance: Asn = Asn {
                     file: ::std::string::String::new(),
-                    country_code: ::std::string::String::new(),
+                    number: 0,
                     special_fields: ::protobuf::SpecialFields::new(),
                 };
                 &instance
@@ -5312,6 +9591,225 @@ pub mod router {
     }
 }
 
+// @@protoc_insertion_point(message:Metrics)
+#[derive(PartialEq,Clone,Default,Debug)]
+pub struct Metrics {
+    // message fields
+    // @@protoc_insertion_point(field:Metrics.address)
+    pub address: ::std::string::String,
+    // @@protoc_insertion_point(field:Metrics.port)
+    pub port: u32,
+    // special fields
+    // @@protoc_insertion_point(special_field:Metrics.special_fields)
+    pub special_fields: ::protobuf::SpecialFields,
+}
+
+impl<'a> ::std::default::Default for &'a Metrics {
+    fn default() -> &'a Metrics {
+        <Metrics as ::protobuf::Message>::default_instance()
+    }
+}
+
+impl Metrics {
+    pub fn new() -> Metrics {
+        ::std::default::Default::default()
+    }
+}
+
+impl ::protobuf::Message for Metrics {
+    const NAME: &'static str = "Metrics";
+
+    fn is_initialized(&self) -> bool {
+        true
+    }
+
+    fn merge_from(&mut self, is: &mut ::protobuf::CodedInputStream<'_>) -> ::protobuf::Result<()> {
+        while let Some(tag) = is.read_raw_tag_or_eof()? {
+            match tag {
+                10 => {
+                    self.address = is.read_string()?;
+                },
+                16 => {
+                    self.port = is.read_uint32()?;
+                },
+                tag => {
+                    ::protobuf::rt::read_unknown_or_skip_group(tag, is, self.special_fields.mut_unknown_fields())?;
+                },
+            };
+        }
+        ::std::result::Result::Ok(())
+    }
+
+    // Compute sizes of nested messages
+    #[allow(unused_variables)]
+    fn compute_size(&self) -> u64 {
+        let mut my_size = 0;
+        if !self.address.is_empty() {
+            my_size += ::protobuf::rt::string_size(1, &self.address);
+        }
+        if self.port != 0 {
+            my_size += ::protobuf::rt::uint32_size(2, self.port);
+        }
+        my_size += ::protobuf::rt::unknown_fields_size(self.special_fields.unknown_fields());
+        self.special_fields.cached_size().set(my_size as u32);
+        my_size
+    }
+
+    fn write_to_with_cached_sizes(&self, os: &mut ::protobuf::CodedOutputStream<'_>) -> ::protobuf::Result<()> {
+        if !self.address.is_empty() {
+            os.write_string(1, &self.address)?;
+        }
+        if self.port != 0 {
+            os.write_uint32(2, self.port)?;
+        }
+        os.write_unknown_fields(self.special_fields.unknown_fields())?;
+        ::std::result::Result::Ok(())
+    }
+
+    fn special_fields(&self) -> &::protobuf::SpecialFields {
+        &self.special_fields
+    }
+
+    fn mut_special_fields(&mut self) -> &mut ::protobuf::SpecialFields {
+        &mut self.special_fields
+    }
+
+    fn new() -> Metrics {
+        Metrics::new()
+    }
+
+    fn clear(&mut self) {
+        self.address.clear();
+        self.port = 0;
+        self.special_fields.clear();
+    }
+
+    fn default_instance() -> &'static Metrics {
+        static instance: Metrics = Metrics {
+            address: ::std::string::String::new(),
+            port: 0,
+            special_fields: ::protobuf::SpecialFields::new(),
+        };
+        &instance
+    }
+}
+
+// @@protoc_insertion_point(message:Api)
+#[derive(PartialEq,Clone,Default,Debug)]
+pub struct Api {
+    // message fields
+    // @@protoc_insertion_point(field:Api.address)
+    pub address: ::std::string::String,
+    // @@protoc_insertion_point(field:Api.port)
+    pub port: u32,
+    // @@protoc_insertion_point(field:Api.secret)
+    pub secret: ::std::string::String,
+    // special fields
+    // @@protoc_insertion_point(special_field:Api.special_fields)
+    pub special_fields: ::protobuf::SpecialFields,
+}
+
+impl<'a> ::std::default::Default for &'a Api {
+    fn default() -> &'a Api {
+        <Api as ::protobuf::Message>::default_instance()
+    }
+}
+
+impl Api {
+    pub fn new() -> Api {
+        ::std::default::Default::default()
+    }
+}
+
+impl ::protobuf::Message for Api {
+    const NAME: &'static str = "Api";
+
+    fn is_initialized(&self) -> bool {
+        true
+    }
+
+    fn merge_from(&mut self, is: &mut ::protobuf::CodedInputStream<'_>) -> ::protobuf::Result<()> {
+        while let Some(tag) = is.read_raw_tag_or_eof()? {
+            match tag {
+                10 => {
+                    self.address = is.read_string()?;
+                },
+                16 => {
+                    self.port = is.read_uint32()?;
+                },
+                26 => {
+                    self.secret = is.read_string()?;
+                },
+                tag => {
+                    ::protobuf::rt::read_unknown_or_skip_group(tag, is, self.special_fields.mut_unknown_fields())?;
+                },
+            };
+        }
+        ::std::result::Result::Ok(())
+    }
+
+    // Compute sizes of nested messages
+    #[allow(unused_variables)]
+    fn compute_size(&self) -> u64 {
+        let mut my_size = 0;
+        if !self.address.is_empty() {
+            my_size += ::protobuf::rt::string_size(1, &self.address);
+        }
+        if self.port != 0 {
+            my_size += ::protobuf::rt::uint32_size(2, self.port);
+        }
+        if !self.secret.is_empty() {
+            my_size += ::protobuf::rt::string_size(3, &self.secret);
+        }
+        my_size += ::protobuf::rt::unknown_fields_size(self.special_fields.unknown_fields());
+        self.special_fields.cached_size().set(my_size as u32);
+        my_size
+    }
+
+    fn write_to_with_cached_sizes(&self, os: &mut ::protobuf::CodedOutputStream<'_>) -> ::protobuf::Result<()> {
+        if !self.address.is_empty() {
+            os.write_string(1, &self.address)?;
+        }
+        if self.port != 0 {
+            os.write_uint32(2, self.port)?;
+        }
+        if !self.secret.is_empty() {
+            os.write_string(3, &self.secret)?;
+        }
+        os.write_unknown_fields(self.special_fields.unknown_fields())?;
+        ::std::result::Result::Ok(())
+    }
+
+    fn special_fields(&self) -> &::protobuf::SpecialFields {
+        &self.special_fields
+    }
+
+    fn mut_special_fields(&mut self) -> &mut ::protobuf::SpecialFields {
+        &mut self.special_fields
+    }
+
+    fn new() -> Api {
+        Api::new()
+    }
+
+    fn clear(&mut self) {
+        self.address.clear();
+        self.port = 0;
+        self.secret.clear();
+        self.special_fields.clear();
+    }
+
+    fn default_instance() -> &'static Api {
+        static instance: Api = Api {
+            address: ::std::string::String::new(),
+            port: 0,
+            secret: ::std::string::String::new(),
+            special_fields: ::protobuf::SpecialFields::new(),
+        };
+        &instance
+    }
+}
+
 // @@protoc_insertion_point(message:Config)
 #[derive(PartialEq,Clone,Default,Debug)]
 pub struct Config {
@@ -5326,6 +9824,10 @@ pub struct Config {
     pub router: ::protobuf::MessageField<Router>,
     // @@protoc_insertion_point(field:Config.dns)
     pub dns: ::protobuf::MessageField<Dns>,
+    // @@protoc_insertion_point(field:Config.metrics)
+    pub metrics: ::protobuf::MessageField<Metrics>,
+    // @@protoc_insertion_point(field:Config.api)
+    pub api: ::protobuf::MessageField<Api>,
     // special fields
     // @@protoc_insertion_point(special_field:Config.special_fields)
     pub special_fields: ::protobuf::SpecialFields,
@@ -5368,6 +9870,12 @@ impl ::protobuf::Message for Config {
                 42 => {
                     ::protobuf::rt::read_singular_message_into_field(is, &mut self.dns)?;
                 },
+                50 => {
+                    ::protobuf::rt::read_singular_message_into_field(is, &mut self.metrics)?;
+                },
+                58 => {
+                    ::protobuf::rt::read_singular_message_into_field(is, &mut self.api)?;
+                },
                 tag => {
                     ::protobuf::rt::read_unknown_or_skip_group(tag, is, self.special_fields.mut_unknown_fields())?;
                 },
@@ -5400,6 +9908,14 @@ impl ::protobuf::Message for Config {
             let len = v.compute_size();
             my_size += 1 + ::protobuf::rt::compute_raw_varint64_size(len) + len;
         }
+        if let Some(v) = self.metrics.as_ref() {
+            let len = v.compute_size();
+            my_size += 1 + ::protobuf::rt::compute_raw_varint64_size(len) + len;
+        }
+        if let Some(v) = self.api.as_ref() {
+            let len = v.compute_size();
+            my_size += 1 + ::protobuf::rt::compute_raw_varint64_size(len) + len;
+        }
         my_size += ::protobuf::rt::unknown_fields_size(self.special_fields.unknown_fields());
         self.special_fields.cached_size().set(my_size as u32);
         my_size
@@ -5421,6 +9937,12 @@ impl ::protobuf::Message for Config {
         if let Some(v) = self.dns.as_ref() {
             ::protobuf::rt::write_message_field_with_cached_size(5, v, os)?;
         }
+        if let Some(v) = self.metrics.as_ref() {
+            ::protobuf::rt::write_message_field_with_cached_size(6, v, os)?;
+        }
+        if let Some(v) = self.api.as_ref() {
+            ::protobuf::rt::write_message_field_with_cached_size(7, v, os)?;
+        }
         os.write_unknown_fields(self.special_fields.unknown_fields())?;
         ::std::result::Result::Ok(())
     }
@@ -5443,6 +9965,8 @@ impl ::protobuf::Message for Config {
         self.outbounds.clear();
         self.router.clear();
         self.dns.clear();
+        self.metrics.clear();
+        self.api.clear();
         self.special_fields.clear();
     }
 
@@ -5453,6 +9977,8 @@ impl ::protobuf::Message for Config {
             outbounds: ::std::vec::Vec::new(),
             router: ::protobuf::MessageField::none(),
             dns: ::protobuf::MessageField::none(),
+            metrics: ::protobuf::MessageField::none(),
+            api: ::protobuf::MessageField::none(),
             special_fields: ::protobuf::SpecialFields::new(),
         };
         &instance
@@ -42,11 +42,17 @@ pub fn from_string(s: &str) -> Result<internal::Config> {
     common::to_internal(config)
 }
 
-pub fn from_file<P>(path: P) -> Result<internal::Config>
+pub fn common_from_file<P>(path: P) -> Result<common::Config>
 where
     P: AsRef<Path>,
 {
     let config = std::fs::read_to_string(path)?;
-    let config = json_from_string(&config)?;
-    common::to_internal(config)
+    json_from_string(&config)
+}
+
+pub fn from_file<P>(path: P) -> Result<internal::Config>
+where
+    P: AsRef<Path>,
+{
+    common::to_internal(common_from_file(path)?)
 }
@@ -32,3 +32,186 @@ fn test_dns_hosts() {
         &ips
     );
 }
+
+#[test]
+fn test_dns_hosts_wildcard_and_ttl() {
+    let json_str = r#"
+    {
+        "dns": {
+            "hosts": {
+                "*.lan": ["192.168.1.1", "fd00::1"]
+            },
+            "hostsTtl": 300
+        }
+    }
+    "#;
+    let config = crate::config::json::from_string(json_str).unwrap();
+    let dns = config.dns.unwrap();
+
+    assert_eq!(
+        dns.hosts.get("*.lan").unwrap().values,
+        vec!["192.168.1.1", "fd00::1"]
+    );
+    assert_eq!(dns.hosts_ttl, 300);
+}
+
+#[test]
+fn test_dns_rules() {
+    let json_str = r#"
+    {
+        "dns": {
+            "servers": ["https://dns.google/dns-query"],
+            "groups": {
+                "corp": ["10.0.0.53"]
+            },
+            "rules": [
+                {
+                    "domainSuffix": ["corp.example"],
+                    "domainKeyword": ["intranet"],
+                    "group": "corp"
+                }
+            ]
+        }
+    }
+    "#;
+    let config = crate::config::json::from_string(json_str).unwrap();
+    let dns = config.dns.unwrap();
+
+    assert_eq!(dns.groups.get("corp").unwrap().values, vec!["10.0.0.53"]);
+    assert_eq!(dns.rules.len(), 1);
+    let rule = &dns.rules[0];
+    assert_eq!(rule.group, "corp");
+    let domains: Vec<_> = rule
+        .domains
+        .iter()
+        .map(|x| (x.type_.unwrap(), x.value.as_str()))
+        .collect();
+    assert_eq!(
+        domains,
+        vec![
+            (crate::config::router::rule::domain::Type::PLAIN, "intranet"),
+            (
+                crate::config::router::rule::domain::Type::DOMAIN,
+                "corp.example"
+            ),
+        ]
+    );
+}
+
+#[test]
+fn test_dns_outbound() {
+    let json_str = r#"
+    {
+        "dns": {
+            "servers": ["https://1.1.1.1/dns-query"],
+            "outbound": "proxy",
+            "groups": {
+                "corp": ["10.0.0.53"],
+                "tunneled": {
+                    "servers": ["tls://8.8.8.8"],
+                    "outbound": "vpn"
+                }
+            }
+        }
+    }
+    "#;
+    let config = crate::config::json::from_string(json_str).unwrap();
+    let dns = config.dns.unwrap();
+
+    assert_eq!(dns.outbound, "proxy");
+    let corp = dns.groups.get("corp").unwrap();
+    assert_eq!(corp.values, vec!["10.0.0.53"]);
+    assert!(corp.outbound.is_empty());
+    let tunneled = dns.groups.get("tunneled").unwrap();
+    assert_eq!(tunneled.values, vec!["tls://8.8.8.8"]);
+    assert_eq!(tunneled.outbound, "vpn");
+}
+
+#[test]
+fn test_dns_strategy() {
+    let json_str = r#"
+    {
+        "dns": {
+            "strategy": "dual_stack"
+        }
+    }
+    "#;
+    let config = crate::config::json::from_string(json_str).unwrap();
+    assert_eq!(config.dns.unwrap().strategy, "dual_stack");
+}
+
+#[test]
+fn test_dns_cache() {
+    let json_str = r#"
+    {
+        "dns": {
+            "cacheMinTtl": 60,
+            "cacheMaxTtl": 3600,
+            "cache_negative_ttl": 10,
+            "cacheSize": 1024,
+            "cacheFile": "dns.cache"
+        }
+    }
+    "#;
+    let config = crate::config::json::from_string(json_str).unwrap();
+    let dns = config.dns.unwrap();
+    assert_eq!(dns.cache_min_ttl, 60);
+    assert_eq!(dns.cache_max_ttl, 3600);
+    assert_eq!(dns.cache_negative_ttl, 10);
+    assert_eq!(dns.cache_size, 1024);
+    assert_eq!(dns.cache_file, "dns.cache");
+}
+
+#[test]
+fn test_dns_fake_ip_filter() {
+    let json_str = r#"
+    {
+        "dns": {
+            "fakeIpFilter": {
+                "mode": "whitelist",
+                "domain": ["example.com"],
+                "domainSuffix": ["lan"],
+                "domainKeyword": ["stun"]
+            }
+        }
+    }
+    "#;
+    let config = crate::config::json::from_string(json_str).unwrap();
+    let dns = config.dns.unwrap();
+    assert_eq!(dns.fake_ip_filter_mode, "whitelist");
+    let values: Vec<&str> = dns
+        .fake_ip_filter
+        .iter()
+        .map(|x| x.value.as_str())
+        .collect();
+    assert_eq!(values, vec!["example.com", "stun", "lan"]);
+}
+
+#[test]
+fn test_dns_edns_client_subnet() {
+    let json_str = r#"
+    {
+        "dns": {
+            "servers": ["doh:dns.google", "noecs:doh:cloudflare-dns.com"],
+            "ednsClientSubnet": "1.2.3.0/24"
+        }
+    }
+    "#;
+    let config = crate::config::json::from_string(json_str).unwrap();
+    let dns = config.dns.unwrap();
+    assert_eq!(dns.edns_client_subnet, "1.2.3.0/24");
+    assert_eq!(dns.servers[1], "noecs:doh:cloudflare-dns.com");
+}
+
+#[test]
+fn test_dns_concurrent_queries() {
+    let json_str = r#"
+    {
+        "dns": {
+            "concurrentQueries": true
+        }
+    }
+    "#;
+    let config = crate::config::json::from_string(json_str).unwrap();
+    assert!(config.dns.unwrap().concurrent_queries);
+}
@@ -31,6 +31,23 @@ pub fn from_string(s: &str) -> Result<internal::Config> {
     Err(anyhow!("could not load config from:\n{:?}", s))
 }
 
+/// Loads a config file as the common config, which the internal one is
+/// converted from.
+pub fn common_from_file(path: &str) -> Result<common::Config> {
+    if let Some(ext) = Path::new(path).extension() {
+        if let Some(ext) = ext.to_str() {
+            match ext {
+                #[cfg(feature = "config-json")]
+                "json" => return json::common_from_file(path),
+                #[cfg(feature = "config-conf")]
+                "conf" => return conf::common_from_file(path),
+                _ => (),
+            }
+        }
+    }
+    Err(anyhow!("config files use extension .json or .conf"))
+}
+
 pub fn from_file(path: &str) -> Result<internal::Config> {
     if let Some(ext) = Path::new(path).extension() {
         if let Some(ext) = ext.to_str() {
@@ -18,8 +18,12 @@ use notify::{
 };
 
 use app::{
-    dispatcher::Dispatcher, dns::DnsClient, inbound::manager::InboundManager,
-    nat_manager::NatManager, outbound::manager::OutboundManager, router::Router,
+    dispatcher::Dispatcher,
+    dns::DnsClient,
+    inbound::manager::{is_network_inbound, InboundManager, NetworkListeners},
+    nat_manager::NatManager,
+    outbound::manager::OutboundManager,
+    router::Router,
 };
 
 use crate::app::{stat_manager::StatManager, SyncStatManager};
@@ -57,7 +61,10 @@ pub enum Error {
     Watcher(#[from] NotifyError),
     #[error(transparent)]
     AsyncChannelSend(
-        #[from] tokio::sync::mpsc::error::SendError<std::sync::mpsc::SyncSender<Result<(), Error>>>,
+        #[from]
+        tokio::sync::mpsc::error::SendError<
+            std::sync::mpsc::SyncSender<Result<ReloadKind, Error>>,
+        >,
     ),
     #[error(transparent)]
     SyncChannelRecv(#[from] std::sync::mpsc::RecvError),
@@ -67,20 +74,60 @@ pub enum Error {
 
 pub type Runner = futures::future::BoxFuture<'static, ()>;
 
+/// How a reload went.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReloadKind {
+    /// Only the parts which changed were rebuilt and all sessions survived.
+    Partial,
+    /// Sessions were closed, those through outbounds which were replaced or
+    /// removed, or the UDP ones of inbounds which were restarted.
+    Full,
+}
+
+/// The stages of a runtime shutting down.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShutdownState {
+    Running,
+    /// No new sessions are accepted, the active ones keep relaying until
+    /// they end or the shutdown timeout passes.
+    Draining,
+    /// The sessions left are being closed.
+    Stopped,
+}
+
 pub struct RuntimeManager {
     #[cfg(feature = "auto-reload")]
     rt_id: RuntimeId,
     config_path: Option<String>,
     #[cfg(feature = "auto-reload")]
     auto_reload: bool,
-    reload_tx: mpsc::Sender<std::sync::mpsc::SyncSender<Result<(), Error>>>,
+    reload_tx: mpsc::Sender<std::sync::mpsc::SyncSender<Result<ReloadKind, Error>>>,
     shutdown_tx: mpsc::Sender<()>,
     router: Arc<RwLock<Router>>,
     dns_client: Arc<RwLock<DnsClient>>,
     outbound_manager: Arc<RwLock<OutboundManager>>,
     stat_manager: SyncStatManager,
+    network_listeners: Mutex<NetworkListeners>,
+    nat_manager: Arc<NatManager>,
+    dispatcher: Arc<Dispatcher>,
+    shutdown_state: Mutex<ShutdownState>,
+    // Held by a reload, reloads triggered from different places don't
+    // interleave.
+    reload_lock: tokio::sync::Mutex<()>,
+    // The DNS config loaded, the DNS client is only reloaded when it changes.
+    dns_config: Mutex<protobuf::MessageField<config::Dns>>,
     #[cfg(feature = "auto-reload")]
     watcher: Mutex<Option<RecommendedWatcher>>,
+    // The rules replacing those of the config file until the next reload.
+    #[cfg(feature = "config-json")]
+    replaced_rules: Mutex<Option<ReplacedRules>>,
+}
+
+#[cfg(feature = "config-json")]
+struct ReplacedRules {
+    // The rules section of a JSON config.
+    rules: serde_json::Value,
+    persisted: bool,
 }
 
 impl RuntimeManager {
@@ -89,12 +136,16 @@ impl RuntimeManager {
         #[cfg(feature = "auto-reload")] rt_id: RuntimeId,
         config_path: Option<String>,
         #[cfg(feature = "auto-reload")] auto_reload: bool,
-        reload_tx: mpsc::Sender<std::sync::mpsc::SyncSender<Result<(), Error>>>,
+        reload_tx: mpsc::Sender<std::sync::mpsc::SyncSender<Result<ReloadKind, Error>>>,
         shutdown_tx: mpsc::Sender<()>,
         router: Arc<RwLock<Router>>,
         dns_client: Arc<RwLock<DnsClient>>,
         outbound_manager: Arc<RwLock<OutboundManager>>,
         stat_manager: SyncStatManager,
+        network_listeners: NetworkListeners,
+        nat_manager: Arc<NatManager>,
+        dispatcher: Arc<Dispatcher>,
+        dns_config: protobuf::MessageField<config::Dns>,
     ) -> Arc<Self> {
         Arc::new(Self {
             #[cfg(feature = "auto-reload")]
@@ -108,8 +159,16 @@ impl RuntimeManager {
             dns_client,
             outbound_manager,
             stat_manager,
+            network_listeners: Mutex::new(network_listeners),
+            nat_manager,
+            dispatcher,
+            shutdown_state: Mutex::new(ShutdownState::Running),
+            reload_lock: tokio::sync::Mutex::new(()),
+            dns_config: Mutex::new(dns_config),
             #[cfg(feature = "auto-reload")]
             watcher: Mutex::new(None),
+            #[cfg(feature = "config-json")]
+            replaced_rules: Mutex::new(None),
         })
     }
 
@@ -117,6 +176,152 @@ impl RuntimeManager {
         self.stat_manager.clone()
     }
 
+    pub async fn flush_dns_cache(&self) {
+        self.dns_client.read().await.flush_cache().await;
+    }
+
+    /// Downloads the rules of a provider, returns the number of rules in
+    /// it. Errors if the provider isn't found.
+    pub async fn refresh_rule_provider(&self, name: &str) -> Result<anyhow::Result<usize>, Error> {
+        let provider = self
+            .router
+            .read()
+            .await
+            .rule_provider(name)
+            .ok_or_else(|| Error::Config(anyhow!("rule provider {} not found", name)))?;
+        Ok(provider
+            .refresh(&self.outbound_manager, self.dns_client.clone())
+            .await)
+    }
+
+    /// The rules with the number of sessions each routed since they were
+    /// loaded.
+    pub async fn rule_hits(&self) -> Vec<app::router::RuleHits> {
+        self.router.read().await.rule_hits()
+    }
+
+    /// The rules of the router as the rules section of a JSON config, none
+    /// if they're from a config of another format, and whether they're the
+    /// rules of the config file.
+    #[cfg(feature = "config-json")]
+    pub fn router_rules(&self) -> (Option<serde_json::Value>, bool) {
+        if let Some(replaced) = self.replaced_rules.lock().unwrap().as_ref() {
+            return (Some(replaced.rules.clone()), replaced.persisted);
+        }
+        let rules = self
+            .config_path
+            .as_ref()
+            .filter(|x| x.ends_with(".json"))
+            .and_then(|x| std::fs::read_to_string(x).ok())
+            .and_then(|x| serde_json::from_str::<serde_json::Value>(&x).ok())
+            .and_then(|mut x| Some(x.get_mut("router")?.get_mut("rules")?.take()));
+        (rules, true)
+    }
+
+    /// Replaces the rules of the router with the rules section of a JSON
+    /// config, without a reload, and returns the number of rules. The next
+    /// reload brings back the rules of the config file, unless the rules
+    /// are persisted to it, which has to be a JSON config then.
+    #[cfg(feature = "config-json")]
+    pub async fn replace_rules(
+        &self,
+        rules: serde_json::Value,
+        persist: bool,
+    ) -> Result<usize, Error> {
+        let _guard = self.reload_lock.lock().await;
+        let mut ext_rules: Vec<config::common::Rule> = serde_json::from_value(rules.clone())
+            .map_err(|e| Error::Config(anyhow!("invalid rules: {}", e)))?;
+        // The rule providers and the ASN database the rules may use.
+        let ext_router = match self.config_path.as_ref() {
+            Some(path) => {
+                config::common_from_file(path)
+                    .map_err(Error::Config)?
+                    .router
+            }
+            None => None,
+        };
+        let mut int_rules = config::common::to_internal_rules(&mut ext_rules, ext_router.as_ref())
+            .map_err(Error::Config)?;
+        {
+            let outbound_manager = self.outbound_manager.read().await;
+            if let Some(rule) = int_rules
+                .iter()
+                .find(|x| outbound_manager.get(&x.target_tag).is_none())
+            {
+                return Err(Error::Config(anyhow!(
+                    "outbound {} not found",
+                    rule.target_tag
+                )));
+            }
+        }
+        if persist {
+            self.persist_rules(&rules)?;
+        }
+        self.router.read().await.replace_rules(&mut int_rules);
+        info!(
+            "replaced rules, {} rules, persisted {}",
+            int_rules.len(),
+            persist
+        );
+        *self.replaced_rules.lock().unwrap() = Some(ReplacedRules {
+            rules,
+            persisted: persist,
+        });
+        Ok(int_rules.len())
+    }
+
+    // Writes the rules to the router of the config file.
+    #[cfg(feature = "config-json")]
+    fn persist_rules(&self, rules: &serde_json::Value) -> Result<(), Error> {
+        let path = self.config_path.as_ref().ok_or(Error::NoConfigFile)?;
+        if !path.ends_with(".json") {
+            return Err(Error::Config(anyhow!(
+                "rules are only persisted to a JSON config"
+            )));
+        }
+        let mut config: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(path)?)
+                .map_err(|e| Error::Config(anyhow!("invalid config file: {}", e)))?;
+        if !config.is_object() {
+            return Err(Error::Config(anyhow!("invalid config file")));
+        }
+        config["router"]["rules"] = rules.clone();
+        let text = serde_json::to_string_pretty(&config).map_err(|e| Error::Config(e.into()))?;
+        // The watcher of the file doesn't see it half written.
+        let tmp = format!("{}.tmp", path);
+        std::fs::write(&tmp, text)?;
+        std::fs::rename(&tmp, path)?;
+        Ok(())
+    }
+
+    /// Routes a session the way the dispatcher does, without connecting
+    /// it. Returns the session as the rules saw it, the outbound and the
+    /// position of the rule picking it.
+    pub async fn explain_route(
+        &self,
+        sess: session::Session,
+    ) -> Result<(session::Session, String, Option<usize>), Error> {
+        Ok(self.dispatcher.explain(sess).await?)
+    }
+
+    /// Downloads the geoip database and has the rules match with it,
+    /// returns its size in bytes. Errors if no geoip URL is configured.
+    pub async fn update_geoip(&self) -> Result<anyhow::Result<usize>, Error> {
+        let updater = self
+            .router
+            .read()
+            .await
+            .geoip_updater()
+            .ok_or_else(|| Error::Config(anyhow!("no geoip url")))?;
+        Ok(updater
+            .update(
+                &self.router,
+                &self.outbound_manager,
+                self.dns_client.clone(),
+            )
+            .await)
+    }
+
     pub async fn health_check_outbound(
         &self,
         tag: &str,
@@ -173,6 +378,24 @@ impl RuntimeManager {
         Ok((tcp_res, udp_res))
     }
 
+    /// Measures a TCP health check of a target through an outbound, which
+    /// for a group goes through the actor in use. Returns the latency along
+    /// with the actor, errors if the outbound isn't found.
+    #[cfg(feature = "outbound-failover")]
+    pub async fn test_outbound_delay(
+        &self,
+        tag: &str,
+        target: &proxy::failover::HealthCheckTarget,
+    ) -> Result<anyhow::Result<(Duration, Option<String>)>, Error> {
+        let handler = self
+            .outbound_manager
+            .read()
+            .await
+            .get(tag)
+            .ok_or_else(|| Error::Config(anyhow!("outbound {} not found", tag)))?;
+        Ok(proxy::failover::tcp_check(&handler, target, self.dns_client.clone()).await)
+    }
+
     #[cfg(feature = "outbound-select")]
     pub async fn set_outbound_selected(&self, outbound: &str, select: &str) -> Result<(), Error> {
         if let Some(selector) = self.outbound_manager.read().await.get_selector(outbound) {
@@ -194,6 +417,16 @@ impl RuntimeManager {
         Err(Error::Config(anyhow!("selector {} not found", outbound)))
     }
 
+    /// Returns the handler in use by a select outbound, which differs from
+    /// the selected one while the outbound falls back from it.
+    #[cfg(feature = "outbound-select")]
+    pub async fn get_outbound_active(&self, outbound: &str) -> Result<String, Error> {
+        if let Some(selector) = self.outbound_manager.read().await.get_selector(outbound) {
+            return Ok(selector.read().await.get_active_tag());
+        }
+        Err(Error::Config(anyhow!("selector {} not found", outbound)))
+    }
+
     #[cfg(feature = "outbound-select")]
     pub async fn get_outbound_selects(&self, outbound: &str) -> Result<Vec<String>, Error> {
         if let Some(selector) = self.outbound_manager.read().await.get_selector(outbound) {
@@ -202,6 +435,25 @@ impl RuntimeManager {
         Err(Error::Config(anyhow!("selector {} not found", outbound)))
     }
 
+    /// Returns the selected member of a urltest group and the latencies last
+    /// measured through each member.
+    #[cfg(feature = "outbound-urltest")]
+    pub async fn get_outbound_url_test(
+        &self,
+        outbound: &str,
+    ) -> Result<(String, Vec<(String, Option<std::time::Duration>)>), Error> {
+        if let Some(status) = self.outbound_manager.read().await.get_url_test(outbound) {
+            return Ok((status.selected_tag(), status.latencies()));
+        }
+        Err(Error::Config(anyhow!("urltest {} not found", outbound)))
+    }
+
+    /// Returns the tag and protocol of each outbound, in the order of the
+    /// config.
+    pub async fn get_outbound_protocols(&self) -> Vec<(String, String)> {
+        self.outbound_manager.read().await.protocols()
+    }
+
     /// Get the last peer active time (in seconds) for an outbound
     pub async fn get_outbound_last_peer_active(
         &self,
@@ -216,29 +468,73 @@ impl RuntimeManager {
 
     // This function could block by an in-progress connection dialing.
     //
-    // TODO Reload FakeDns. And perhaps the inbounds as long as the listening
-    // addresses haven't changed.
-    pub async fn reload(&self) -> Result<(), Error> {
+    // Only the parts of the config which changed are reloaded, so that the
+    // sessions through the others survive.
+    //
+    // TODO Reload FakeDns, and the inbounds which aren't served by network
+    // listeners.
+    pub async fn reload(&self) -> Result<ReloadKind, Error> {
         let config_path = if let Some(p) = self.config_path.as_ref() {
             p
         } else {
             return Err(Error::NoConfigFile);
         };
+        let _guard = self.reload_lock.lock().await;
         info!("reloading from config file: {}", config_path);
         let mut config = config::from_file(config_path).map_err(Error::Config)?;
         app::logger::setup_logger(&config.log)?;
-        self.router.write().await.reload(&mut config.router)?;
-        self.dns_client.write().await.reload(&config.dns)?;
-        self.outbound_manager
+        #[cfg(feature = "rustls")]
+        common::key_log::setup(&config.log.key_log_file);
+        app::access_log::setup(&config.log);
+        // The router is always rebuilt, the site and IP databases it loads
+        // may have changed without the config, and it holds no sessions.
+        // The rules are loaded before the router is locked for the swap.
+        {
+            let reload = self.router.read().await.prepare_reload(&mut config.router);
+            let mut router = self.router.write().await;
+            router.apply_reload(reload);
+            router.load_inbound_finals(&config.inbounds);
+        }
+        #[cfg(feature = "config-json")]
+        {
+            *self.replaced_rules.lock().unwrap() = None;
+        }
+        let dns_changed = *self.dns_config.lock().unwrap() != config.dns;
+        if dns_changed {
+            self.dns_client.write().await.reload(&config.dns)?;
+            *self.dns_config.lock().unwrap() = config.dns.clone();
+        }
+        let replaced = self
+            .outbound_manager
             .write()
             .await
             .reload(&config.outbounds, self.dns_client.clone())
             .await?;
-        info!("reloaded from config file: {}", config_path);
-        Ok(())
+        let killed = {
+            let mut sm = self.stat_manager.write().await;
+            sm.retain_outbounds(|tag| config.outbounds.iter().any(|x| x.tag == tag));
+            sm.kill_outbounds(&replaced)
+        };
+        let stopped = self
+            .network_listeners
+            .lock()
+            .unwrap()
+            .reload(&config.inbounds)?;
+        let kind = if killed > 0 || stopped {
+            ReloadKind::Full
+        } else {
+            ReloadKind::Partial
+        };
+        info!(
+            "reloaded from config file: {}, {} outbounds replaced or removed, {} sessions closed",
+            config_path,
+            replaced.len(),
+            killed
+        );
+        Ok(kind)
     }
 
-    pub fn blocking_reload(&self) -> Result<(), Error> {
+    pub fn blocking_reload(&self) -> Result<ReloadKind, Error> {
         let tx = self.reload_tx.clone();
         let (res_tx, res_rx) = sync_channel(0);
         if let Err(e) = tx.blocking_send(res_tx) {
@@ -250,6 +546,54 @@ impl RuntimeManager {
         }
     }
 
+    pub fn shutdown_state(&self) -> ShutdownState {
+        *self.shutdown_state.lock().unwrap()
+    }
+
+    // Stops accepting sessions, then waits for the active ones to end, for up
+    // to the shutdown timeout. Returns the number of sessions left.
+    async fn drain(&self) -> usize {
+        *self.shutdown_state.lock().unwrap() = ShutdownState::Draining;
+        self.network_listeners.lock().unwrap().drain();
+        info!("draining sessions");
+        let deadline = tokio::time::Instant::now() + Duration::from_secs(*option::SHUTDOWN_TIMEOUT);
+        loop {
+            let active = self
+                .stat_manager
+                .read()
+                .await
+                .counters
+                .values()
+                .filter(|x| !x.send_completed() || !x.recv_completed())
+                .count()
+                + self.nat_manager.size().await;
+            if active == 0 || tokio::time::Instant::now() >= deadline {
+                return active;
+            }
+            tokio::time::sleep(Duration::from_millis(200)).await;
+        }
+    }
+
+    // A first shutdown signal drains the sessions, a second one closes them
+    // without waiting.
+    async fn shutdown_task(&self, mut shutdown_rx: mpsc::Receiver<()>) {
+        let _ = shutdown_rx.recv().await;
+        tokio::select! {
+            left = self.drain() => {
+                if left > 0 {
+                    warn!("closing {} sessions left after the shutdown timeout", left);
+                }
+            }
+            _ = shutdown_rx.recv() => {
+                warn!("closing sessions without waiting");
+            }
+        }
+        if let Err(e) = self.dns_client.read().await.save_cache().await {
+            warn!("saving dns cache failed: {}", e);
+        }
+        *self.shutdown_state.lock().unwrap() = ShutdownState::Stopped;
+    }
+
     pub async fn shutdown(&self) -> bool {
         let tx = self.shutdown_tx.clone();
         if let Err(e) = tx.send(()).await {
@@ -352,7 +696,7 @@ lazy_static! {
         Mutex::new(HashMap::new());
 }
 
-pub fn reload(key: RuntimeId) -> Result<(), Error> {
+pub fn reload(key: RuntimeId) -> Result<ReloadKind, Error> {
     if let Some(m) = RUNTIME_MANAGER
         .lock()
         .map_err(|_| Error::RuntimeManager)?
@@ -370,6 +714,12 @@ pub fn shutdown(key: RuntimeId) -> bool {
     false
 }
 
+/// Notifies all runtimes that the network has changed, outbounds holding
+/// long-lived UDP sockets then rebind them or reconnect.
+pub fn notify_network_change() {
+    proxy::notify_network_change();
+}
+
 pub fn is_running(key: RuntimeId) -> bool {
     RUNTIME_MANAGER.lock().unwrap().contains_key(&key)
 }
@@ -435,7 +785,7 @@ pub fn start(rt_id: RuntimeId, opts: StartOptions) -> Result<(), Error> {
     println!("start with options:\n{:#?}", opts);
 
     let (reload_tx, mut reload_rx) = mpsc::channel(1);
-    let (shutdown_tx, mut shutdown_rx) = mpsc::channel(1);
+    let (shutdown_tx, shutdown_rx) = mpsc::channel(1);
 
     let config_path = match opts.config {
         Config::File(ref p) => Some(p.to_owned()),
@@ -449,6 +799,9 @@ pub fn start(rt_id: RuntimeId, opts: StartOptions) -> Result<(), Error> {
     };
 
     app::logger::setup_logger(&config.log)?;
+    #[cfg(feature = "rustls")]
+    common::key_log::setup(&config.log.key_log_file);
+    app::access_log::setup(&config.log);
 
     let rt = new_runtime(&opts.runtime_opt)?;
     let _g = rt.enter();
@@ -462,10 +815,19 @@ pub fn start(rt_id: RuntimeId, opts: StartOptions) -> Result<(), Error> {
     let outbound_manager = Arc::new(RwLock::new(
         OutboundManager::new(&config.outbounds, dns_client.clone()).map_err(Error::Config)?,
     ));
-    let router = Arc::new(RwLock::new(Router::new(
-        &mut config.router,
+    let mut router = Router::new(&mut config.router, dns_client.clone());
+    router.load_inbound_finals(&config.inbounds);
+    let router = Arc::new(RwLock::new(router));
+    runners.push(app::rule_provider::refresh_task(
+        router.clone(),
+        outbound_manager.clone(),
+        dns_client.clone(),
+    ));
+    runners.push(app::geoip_update::update_task(
+        router.clone(),
+        outbound_manager.clone(),
         dns_client.clone(),
-    )));
+    ));
     let stat_manager = Arc::new(RwLock::new(StatManager::new()));
     runners.push(StatManager::cleanup_task(stat_manager.clone()));
     let dispatcher = Arc::new(Dispatcher::new(
@@ -485,8 +847,19 @@ pub fn start(rt_id: RuntimeId, opts: StartOptions) -> Result<(), Error> {
     });
 
     let nat_manager = Arc::new(NatManager::new(dispatcher.clone()));
+    let mut network_listeners = NetworkListeners::new(dispatcher.clone(), nat_manager.clone());
+    network_listeners
+        .reload(&config.inbounds)
+        .map_err(Error::Config)?;
+    let other_inbounds: Vec<config::Inbound> = config
+        .inbounds
+        .iter()
+        .filter(|x| !is_network_inbound(x))
+        .cloned()
+        .collect();
     let inbound_manager =
-        InboundManager::new(&config.inbounds, dispatcher, nat_manager).map_err(Error::Config)?;
+        InboundManager::new(&other_inbounds, dispatcher.clone(), nat_manager.clone())
+            .map_err(Error::Config)?;
     let mut inbound_net_runners = inbound_manager
         .get_network_runners()
         .map_err(Error::Config)?;
@@ -533,6 +906,21 @@ pub fn start(rt_id: RuntimeId, opts: StartOptions) -> Result<(), Error> {
     #[cfg(all(feature = "inbound-tun", any(target_os = "macos", target_os = "linux")))]
     sys::post_tun_creation_setup(&net_info);
 
+    #[cfg(feature = "metrics")]
+    if let Some(metrics) = config.metrics.as_ref() {
+        use std::net::{IpAddr, SocketAddr};
+        let ip = metrics
+            .address
+            .parse::<IpAddr>()
+            .map_err(|e| Error::Config(anyhow!("parse metrics address failed: {}", e)))?;
+        let metrics_server = app::metrics::MetricsServer::new(
+            stat_manager.clone(),
+            dns_client.clone(),
+            nat_manager.clone(),
+        );
+        runners.push(metrics_server.serve(SocketAddr::new(ip, metrics.port as u16)));
+    }
+
     let runtime_manager = RuntimeManager::new(
         #[cfg(feature = "auto-reload")]
         rt_id,
@@ -540,11 +928,15 @@ pub fn start(rt_id: RuntimeId, opts: StartOptions) -> Result<(), Error> {
         #[cfg(feature = "auto-reload")]
         opts.auto_reload,
         reload_tx,
-        shutdown_tx,
+        shutdown_tx.clone(),
         router,
         dns_client,
         outbound_manager,
         stat_manager,
+        network_listeners,
+        nat_manager,
+        dispatcher,
+        config.dns.clone(),
     );
 
     // Monitor config file changes.
@@ -557,18 +949,31 @@ pub fn start(rt_id: RuntimeId, opts: StartOptions) -> Result<(), Error> {
 
     #[cfg(feature = "api")]
     {
-        use std::net::SocketAddr;
+        use std::net::{IpAddr, SocketAddr};
         let listen_addr = if !option::API_LISTEN.is_empty() {
             Some(
                 option::API_LISTEN
                     .parse::<SocketAddr>()
                     .map_err(|e| Error::Config(anyhow!("parse SocketAddr failed: {}", e)))?,
             )
+        } else if let Some(api) = config.api.as_ref() {
+            let ip = api
+                .address
+                .parse::<IpAddr>()
+                .map_err(|e| Error::Config(anyhow!("parse api address failed: {}", e)))?;
+            Some(SocketAddr::new(ip, api.port as u16))
         } else {
             None
         };
+        // The secret of the config, or of the environment.
+        let secret = config
+            .api
+            .as_ref()
+            .map(|x| x.secret.clone())
+            .filter(|x| !x.is_empty())
+            .unwrap_or_else(|| option::API_SECRET.clone());
         if let Some(listen_addr) = listen_addr {
-            let api_server = ApiServer::new(runtime_manager.clone());
+            let api_server = ApiServer::new(runtime_manager.clone(), secret);
             runners.push(api_server.serve(listen_addr));
         }
     }
@@ -596,15 +1001,61 @@ pub fn start(rt_id: RuntimeId, opts: StartOptions) -> Result<(), Error> {
     }));
 
     // Monitor shutdown signal.
+    let rm = runtime_manager.clone();
     tasks.push(Box::pin(async move {
-        let _ = shutdown_rx.recv().await;
+        rm.shutdown_task(shutdown_rx).await;
     }));
 
-    // Monitor ctrl-c exit signal.
+    // Monitor ctrl-c exit signal, which shuts down as the shutdown signal
+    // does.
     #[cfg(feature = "ctrlc")]
-    tasks.push(Box::pin(async move {
-        let _ = tokio::signal::ctrl_c().await;
-    }));
+    {
+        let tx = shutdown_tx.clone();
+        tasks.push(Box::pin(async move {
+            while tokio::signal::ctrl_c().await.is_ok() {
+                if tx.send(()).await.is_err() {
+                    return;
+                }
+            }
+        }));
+    }
+
+    // Monitor SIGTERM.
+    #[cfg(all(feature = "ctrlc", unix))]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+        let mut sigterm = signal(SignalKind::terminate())?;
+        tasks.push(Box::pin(async move {
+            while sigterm.recv().await.is_some() {
+                if shutdown_tx.send(()).await.is_err() {
+                    return;
+                }
+            }
+        }));
+    }
+
+    // Reload the config file on SIGHUP, and reopen the access log, which
+    // may have been rotated. A reload failing leaves the running config
+    // in place. The SIGHUPs received during a reload are coalesced into
+    // a single one by the signal stream, so they cause at most one more
+    // reload.
+    #[cfg(all(feature = "ctrlc", unix))]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+        let mut sighup = signal(SignalKind::hangup())?;
+        let rm = runtime_manager.clone();
+        tasks.push(Box::pin(async move {
+            while sighup.recv().await.is_some() {
+                app::access_log::reopen();
+                if rm.config_path.is_none() {
+                    continue;
+                }
+                if let Err(e) = rm.reload().await {
+                    warn!("reloading on SIGHUP failed: {}", e);
+                }
+            }
+        }));
+    }
 
     RUNTIME_MANAGER
         .lock()
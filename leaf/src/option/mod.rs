@@ -177,6 +177,11 @@ lazy_static! {
         get_env_var_or("QUIC_ACCEPT_CHANNEL_SIZE", 1024)
     };
 
+    /// Number of TLS sessions the TLS outbounds keep for resumption.
+    pub static ref TLS_SESSION_CACHE_SIZE: usize = {
+        get_env_var_or("TLS_SESSION_CACHE_SIZE", 256)
+    };
+
     pub static ref INCOMING_ACCEPT_CONCURRENCY: usize = {
         get_env_var_or("INCOMING_ACCEPT_CONCURRENCY", 256)
     };
@@ -233,6 +238,12 @@ lazy_static! {
         get_env_var_or("OUTBOUND_DIAL_CONCURRENCY", 1)
     };
 
+    /// The head start of the connects to IPv6 addresses over those to IPv4
+    /// ones, with the dual_stack DNS strategy.
+    pub static ref HAPPY_EYEBALLS_DELAY_MS: u64 = {
+        get_env_var_or("HAPPY_EYEBALLS_DELAY_MS", 250)
+    };
+
     pub static ref ASSET_LOCATION: String = {
         get_env_var_or_else("ASSET_LOCATION", || {
             let mut file = std::env::current_exe().unwrap();
@@ -241,6 +252,12 @@ lazy_static! {
         })
     };
 
+    /// The geosite file the `site:` and `geosite:` rules read if they don't
+    /// name one, relative to the asset location.
+    pub static ref SITE_FILE: String = {
+        get_env_var_or("SITE_FILE", "site.dat".to_string())
+    };
+
     pub static ref CACHE_LOCATION: String = {
         get_env_var_or("CACHE_LOCATION", "".to_string())
     };
@@ -249,6 +266,11 @@ lazy_static! {
         get_env_var_or("API_LISTEN", "".to_string())
     };
 
+    /// The secret the API requires as a bearer token, none if empty.
+    pub static ref API_SECRET: String = {
+        get_env_var_or("API_SECRET", "".to_string())
+    };
+
     pub static ref ENABLE_IPV6: bool = {
         get_env_var_or("ENABLE_IPV6", false)
     };
@@ -307,6 +329,12 @@ lazy_static! {
         get_env_var_or("UDP_SESSION_TIMEOUT", 30)
     };
 
+    /// Seconds the active sessions have to end on shutdown before they're
+    /// closed. New sessions are refused meanwhile.
+    pub static ref SHUTDOWN_TIMEOUT: u64 = {
+        get_env_var_or("SHUTDOWN_TIMEOUT", 10)
+    };
+
     /// UDP session timeout check interval. The interval to check for UDP session
     /// timeouts.
     pub static ref UDP_SESSION_TIMEOUT_CHECK_INTERVAL: u64 = {
@@ -323,6 +351,12 @@ lazy_static! {
         get_env_var_or("DNS_TIMEOUT", 4)
     };
 
+    /// Timeout for a single request to a DoH, TCP or TLS server, which is
+    /// retried within the timeout of the query.
+    pub static ref DNS_REQUEST_TIMEOUT: u64 = {
+        get_env_var_or("DNS_REQUEST_TIMEOUT", 2)
+    };
+
     pub static ref DNS_SERVER_RESELECT_INTERVAL_SECS: u64 = {
         get_env_var_or("DNS_SERVER_RESELECT_INTERVAL_SECS", 30)
     };
@@ -339,10 +373,28 @@ lazy_static! {
         get_env_var_or("DNS_SERVER_FALLBACK_CONCURRENCY", 1)
     };
 
+    /// How many servers a lookup is sent to at once when the DNS client
+    /// races its queries, those with the best scores.
+    pub static ref DNS_CONCURRENT_QUERY_SERVERS: usize = {
+        get_env_var_or("DNS_CONCURRENT_QUERY_SERVERS", 3)
+    };
+
     pub static ref DNS_DUALSTACK_DELAY_MS: u64 = {
         get_env_var_or("DNS_DUALSTACK_DELAY_MS", 250)
     };
 
+    /// The public address of this host, the `auto` EDNS client subnet of
+    /// the DNS client is the /24 or /56 network of it.
+    pub static ref PUBLIC_IP: String = {
+        get_env_var_or("PUBLIC_IP", "".to_string())
+    };
+
+    /// The pool the fake DNS answers AAAA queries from. The domains take
+    /// the addresses at the same offsets as their IPv4 ones in 198.18.0.0/16.
+    pub static ref FAKE_IPV6_CIDR: cidr::Ipv6Cidr = {
+        get_env_var_or("FAKE_IPV6_CIDR", "fc00::/18".parse().unwrap())
+    };
+
     pub static ref DEFAULT_TUN_NAME: String = {
         get_env_var_or("DEFAULT_TUN_NAME", "utun233".to_string())
     };
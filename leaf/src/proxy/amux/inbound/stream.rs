@@ -18,10 +18,10 @@ pub struct Incoming {
 }
 
 impl Incoming {
-    pub fn new(sess: Session, conn: Box<dyn ProxyStream>) -> Self {
+    pub fn new(sess: Session, conn: Box<dyn ProxyStream>, max_streams: usize) -> Self {
         Incoming {
             sess,
-            acceptor: MuxSession::acceptor(conn),
+            acceptor: MuxSession::acceptor(conn, max_streams),
         }
     }
 }
@@ -42,6 +42,9 @@ impl Stream for Incoming {
 
 pub struct Handler {
     pub actors: Vec<AnyInboundHandler>,
+    // Streams over this number at a time on a connection are reset, 0 for
+    // no limit.
+    pub max_sessions_per_connection: usize,
 }
 
 #[async_trait]
@@ -64,7 +67,9 @@ impl InboundStreamHandler for Handler {
             }
         }
         Ok(InboundTransport::Incoming(Box::new(Incoming::new(
-            sess, stream,
+            sess,
+            stream,
+            self.max_sessions_per_connection,
         ))))
     }
 }
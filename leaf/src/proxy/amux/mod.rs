@@ -1,7 +1,7 @@
 use std::cmp::min;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::convert::TryInto;
-use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
 use std::sync::Arc;
 use std::time::Duration;
 use std::{io, pin::Pin};
@@ -22,7 +22,7 @@ use futures::{
 use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
 use tokio::sync::mpsc::{self, Receiver, Sender};
 use tokio::sync::Mutex;
-use tokio::time::{sleep, Instant};
+use tokio::time::{sleep, Instant, Sleep};
 use tracing::{debug, trace, Instrument};
 
 #[cfg(feature = "inbound-amux")]
@@ -32,8 +32,32 @@ pub mod outbound;
 
 pub const FRAME_STREAM: u8 = 0x01;
 pub const FRAME_STREAM_FIN: u8 = 0x02;
+pub const FRAME_PING: u8 = 0x03;
+pub const FRAME_PONG: u8 = 0x04;
+pub const FRAME_STREAM_RESET: u8 = 0x05;
+pub const FRAME_SESSION_OPEN: u8 = 0x06;
+pub const FRAME_PADDING: u8 = 0x07;
 pub const MAX_STREAM_FRAME_DATA_LEN: u16 = u16::MAX;
 
+/// The version of the protocol a peer speaks, carried by keep-alive frames.
+/// Peers without keep-alive are version 0, they don't know any frame after
+/// `StreamFin` and close the connection on one. Version 2 adds `StreamReset`,
+/// version 3 `SessionOpen` and `Padding`.
+pub const PROTOCOL_VERSION: u8 = 3;
+
+/// The flag of `SessionOpen` to pad the frames of the connection.
+pub const SESSION_FLAG_PADDING: u8 = 0x01;
+
+// The length of the header of a padding frame.
+const PADDING_HEADER_LEN: usize = 3;
+// Frames are padded up to one of these sizes, picked at random from the two
+// smallest that fit, larger ones up to a multiple of the largest.
+const PADDING_BUCKETS: [usize; 5] = [128, 256, 512, 1024, 1400];
+// The range of the time without a frame sent, in milliseconds, after which
+// a dummy frame goes out, and of its length.
+const PADDING_IDLE_MS: std::ops::Range<u64> = 1000..4000;
+const PADDING_DUMMY_LEN: std::ops::Range<usize> = 16..512;
+
 pub fn random_u16() -> u16 {
     use rand::{rngs::StdRng, RngCore, SeedableRng};
     let mut buf = [0u8; std::mem::size_of::<u16>()];
@@ -51,6 +75,21 @@ pub enum MuxFrame {
     Stream(StreamId, Vec<u8>), // |type(1,0x01)|id(2)|len(2)|data|
     /// A frame to close the send half of a stream.
     StreamFin(StreamId), // |type(1,0x02)|id(2)|
+    /// A keep-alive frame with the protocol version of the sender. Frames
+    /// from here on carry the length of their payload, so that peers skip
+    /// the types they don't know.
+    Ping(u8), // |type(1,0x03)|len(2)|version(1)|
+    /// The answer to a `Ping`, with the protocol version of the sender.
+    Pong(u8), // |type(1,0x04)|len(2)|version(1)|
+    /// A frame to refuse a stream the acceptor has no room for, the stream
+    /// fails on the connector.
+    StreamReset(StreamId), // |type(1,0x05)|len(2)|id(2)|
+    /// The first frame of a connector with the options it asks for, with the
+    /// protocol version and the flags. The acceptor answers with the flags
+    /// it agrees to, a peer of an earlier version doesn't answer.
+    SessionOpen(u8, u8, u16), // |type(1,0x06)|len(2)|version(1)|flags(1)|padding_overhead_pct(2)|
+    /// Random bytes of the given length, dropped by the receiver.
+    Padding(u16), // |type(1,0x07)|len(2)|random|
 }
 
 impl MuxFrame {
@@ -68,6 +107,36 @@ impl MuxFrame {
                 buf.put_u8(FRAME_STREAM_FIN);
                 buf.put_u16(*id);
             }
+            MuxFrame::Ping(version) => {
+                buf.put_u8(FRAME_PING);
+                buf.put_u16(1);
+                buf.put_u8(*version);
+            }
+            MuxFrame::Pong(version) => {
+                buf.put_u8(FRAME_PONG);
+                buf.put_u16(1);
+                buf.put_u8(*version);
+            }
+            MuxFrame::StreamReset(id) => {
+                buf.put_u8(FRAME_STREAM_RESET);
+                buf.put_u16(2);
+                buf.put_u16(*id);
+            }
+            MuxFrame::SessionOpen(version, flags, overhead_pct) => {
+                buf.put_u8(FRAME_SESSION_OPEN);
+                buf.put_u16(4);
+                buf.put_u8(*version);
+                buf.put_u8(*flags);
+                buf.put_u16(*overhead_pct);
+            }
+            MuxFrame::Padding(len) => {
+                use rand::RngCore;
+                buf.put_u8(FRAME_PADDING);
+                buf.put_u16(*len);
+                let mut padding = vec![0u8; *len as usize];
+                rand::thread_rng().fill_bytes(&mut padding);
+                buf.put_slice(&padding);
+            }
         }
         buf.freeze()
     }
@@ -82,12 +151,180 @@ impl std::fmt::Display for MuxFrame {
             MuxFrame::StreamFin(stream_id) => {
                 write!(f, "StreamFin({})", stream_id)
             }
+            MuxFrame::Ping(version) => write!(f, "Ping(version {})", version),
+            MuxFrame::Pong(version) => write!(f, "Pong(version {})", version),
+            MuxFrame::StreamReset(stream_id) => write!(f, "StreamReset({})", stream_id),
+            MuxFrame::SessionOpen(version, flags, _) => {
+                write!(f, "SessionOpen(version {}, flags {:#04x})", version, flags)
+            }
+            MuxFrame::Padding(len) => write!(f, "Padding({} bytes)", len),
         }
     }
 }
 
 pub type Streams = Arc<Mutex<HashMap<StreamId, Sender<Vec<u8>>>>>;
 
+/// Keep-alive of the connections of a connector, zero durations disable it.
+/// The peer must be of `PROTOCOL_VERSION` 1 or later to answer pings.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct KeepAlive {
+    /// Pings are sent on connections nothing was received on over the
+    /// interval.
+    pub interval: Duration,
+    /// A connection is closed after this number of pings without a frame
+    /// received.
+    pub max_missed: usize,
+    /// The time a new stream has to see the connection alive, by a frame of
+    /// any stream or a pong, after which it fails and the connection is
+    /// taken out of the pool. Bounds the dial of a new connection as well.
+    pub open_timeout: Duration,
+}
+
+// Whether the peer of a connection is still there, as far as the connector
+// can tell.
+pub struct Liveness {
+    // Number of frames received.
+    frames: AtomicU64,
+    dead: AtomicBool,
+}
+
+impl Liveness {
+    fn new() -> Self {
+        Liveness {
+            frames: AtomicU64::new(0),
+            dead: AtomicBool::new(false),
+        }
+    }
+
+    fn touch(&self) {
+        self.frames.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn frames(&self) -> u64 {
+        self.frames.load(Ordering::Relaxed)
+    }
+
+    fn set_dead(&self) {
+        self.dead.store(true, Ordering::Relaxed);
+    }
+
+    fn is_dead(&self) -> bool {
+        self.dead.load(Ordering::Relaxed)
+    }
+}
+
+// Fails a read of a new stream if the connection received nothing since the
+// stream was opened.
+pub struct OpenTimeout {
+    liveness: Arc<Liveness>,
+    // Frames received by the time the stream was opened.
+    frames: u64,
+    timer: Pin<Box<Sleep>>,
+}
+
+impl OpenTimeout {
+    fn new(liveness: Arc<Liveness>, timeout: Duration) -> Self {
+        OpenTimeout {
+            frames: liveness.frames(),
+            liveness,
+            timer: Box::pin(sleep(timeout)),
+        }
+    }
+}
+
+/// Padding of the frames on the connections of a connector, to hide their
+/// sizes and timing. The acceptor must be of `PROTOCOL_VERSION` 3 or later,
+/// an earlier one of version 1 or 2 leaves the connection unpadded.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Padding {
+    pub enabled: bool,
+    /// The padding sent on a connection, in percent of the other bytes sent.
+    pub max_overhead_pct: u16,
+}
+
+// The padding of a connection, off until both ends agreed on it.
+pub struct PaddingState {
+    enabled: AtomicBool,
+    max_overhead_pct: AtomicU64,
+    // Bytes of frames other than padding sent.
+    sent: AtomicU64,
+    // Bytes of padding sent.
+    padded: AtomicU64,
+}
+
+impl PaddingState {
+    fn new() -> Self {
+        PaddingState {
+            enabled: AtomicBool::new(false),
+            max_overhead_pct: AtomicU64::new(0),
+            sent: AtomicU64::new(0),
+            padded: AtomicU64::new(0),
+        }
+    }
+
+    fn enable(&self, max_overhead_pct: u16) {
+        self.max_overhead_pct
+            .store(max_overhead_pct as u64, Ordering::Relaxed);
+        self.enabled.store(true, Ordering::Relaxed);
+    }
+
+    fn is_enabled(&self) -> bool {
+        self.enabled.load(Ordering::Relaxed)
+    }
+
+    // Takes `n` bytes of padding out of the budget, if there's room.
+    fn take(&self, n: usize) -> bool {
+        let budget =
+            self.sent.load(Ordering::Relaxed) * self.max_overhead_pct.load(Ordering::Relaxed) / 100;
+        let padded = self.padded.load(Ordering::Relaxed) + n as u64;
+        if padded > budget {
+            return false;
+        }
+        self.padded.store(padded, Ordering::Relaxed);
+        true
+    }
+
+    // Returns the length of the padding frame to follow a frame of `len`
+    // bytes, if any.
+    fn pad_len(&self, len: usize) -> Option<u16> {
+        use rand::Rng;
+        if !self.is_enabled() {
+            return None;
+        }
+        self.sent.fetch_add(len as u64, Ordering::Relaxed);
+        let min_len = len + PADDING_HEADER_LEN;
+        let mut fits = PADDING_BUCKETS.iter().copied().filter(|x| *x >= min_len);
+        let target = match (fits.next(), fits.next()) {
+            (Some(a), Some(b)) => {
+                if rand::thread_rng().gen() {
+                    a
+                } else {
+                    b
+                }
+            }
+            (Some(a), None) => a,
+            _ => min_len.next_multiple_of(PADDING_BUCKETS[PADDING_BUCKETS.len() - 1]),
+        };
+        let n = min(target - min_len, u16::MAX as usize);
+        if self.take(n + PADDING_HEADER_LEN) {
+            Some(n as u16)
+        } else {
+            None
+        }
+    }
+
+    // Returns the length of a dummy frame for an idle connection, if any.
+    fn dummy_len(&self) -> Option<u16> {
+        use rand::Rng;
+        let n = rand::thread_rng().gen_range(PADDING_DUMMY_LEN);
+        if self.is_enabled() && self.take(n + PADDING_HEADER_LEN) {
+            Some(n as u16)
+        } else {
+            None
+        }
+    }
+}
+
 enum TaskState {
     Idle,
     Pending(Pin<Box<dyn Future<Output = io::Result<usize>> + 'static + Sync + Send>>),
@@ -102,6 +339,7 @@ pub struct MuxStream {
     write_state: TaskState,
     shutdown_state: TaskState,
     stream_end: Arc<AtomicBool>,
+    open_timeout: Option<OpenTimeout>,
 }
 
 impl MuxStream {
@@ -110,6 +348,7 @@ impl MuxStream {
         stream_id: StreamId,
         frame_write_tx: Sender<MuxFrame>,
         stream_end: Arc<AtomicBool>,
+        open_timeout: Option<OpenTimeout>,
     ) -> (Self, Sender<Vec<u8>>) {
         trace!("new mux stream {} (session {})", stream_id, session_id);
         let (stream_read_tx, stream_read_rx) =
@@ -124,6 +363,7 @@ impl MuxStream {
                 write_state: TaskState::Idle,
                 shutdown_state: TaskState::Idle,
                 stream_end,
+                open_timeout,
             },
             stream_read_tx,
         )
@@ -149,6 +389,31 @@ fn broken_pipe() -> io::Error {
     io::Error::new(io::ErrorKind::Interrupted, "broken pipe")
 }
 
+impl MuxStream {
+    fn poll_open_timeout(&mut self, cx: &mut Context) -> Poll<io::Result<()>> {
+        let Some(t) = self.open_timeout.as_mut() else {
+            return Poll::Pending;
+        };
+        ready!(t.timer.as_mut().poll(cx));
+        let alive = t.liveness.frames() != t.frames;
+        if !alive {
+            t.liveness.set_dead();
+        }
+        self.open_timeout = None;
+        if alive {
+            return Poll::Pending;
+        }
+        debug!(
+            "mux stream {} (session {}) open timed out",
+            self.stream_id, self.session_id
+        );
+        Poll::Ready(Err(io::Error::new(
+            io::ErrorKind::TimedOut,
+            "amux stream open timed out",
+        )))
+    }
+}
+
 impl AsyncRead for MuxStream {
     fn poll_read(
         mut self: Pin<&mut Self>,
@@ -161,20 +426,24 @@ impl AsyncRead for MuxStream {
             buf.put_slice(&for_read[..to_read]);
             return Poll::Ready(Ok(()));
         }
-        Poll::Ready(
-            ready!(self.stream_read_rx.poll_recv(cx)).map_or(Err(broken_pipe()), |data| {
-                if data.is_empty() {
-                    Ok(()) // EOF
-                } else {
-                    let to_read = min(buf.remaining(), data.len());
-                    buf.put_slice(&data[..to_read]);
-                    if data.len() > to_read {
-                        self.buf.extend_from_slice(&data[to_read..]);
-                    }
-                    Ok(())
+        let data = match self.stream_read_rx.poll_recv(cx) {
+            Poll::Ready(data) => data,
+            Poll::Pending => return self.poll_open_timeout(cx),
+        };
+        // The stream has heard from the connection.
+        self.open_timeout = None;
+        Poll::Ready(data.map_or(Err(broken_pipe()), |data| {
+            if data.is_empty() {
+                Ok(()) // EOF
+            } else {
+                let to_read = min(buf.remaining(), data.len());
+                buf.put_slice(&data[..to_read]);
+                if data.len() > to_read {
+                    self.buf.extend_from_slice(&data[to_read..]);
                 }
-            }),
-        )
+                Ok(())
+            }
+        }))
     }
 }
 
@@ -246,13 +515,7 @@ pub struct MuxConnection<S> {
     read_buf: BytesMut,
     write_buf: BytesMut,
     backpressure_boundary: usize,
-}
-
-fn unknown_frame(t: u8) -> io::Error {
-    io::Error::new(
-        io::ErrorKind::Interrupted,
-        format!("unknown frame type {}", t),
-    )
+    padding: Option<Arc<PaddingState>>,
 }
 
 impl<S> MuxConnection<S> {
@@ -262,68 +525,124 @@ impl<S> MuxConnection<S> {
             read_buf: BytesMut::with_capacity(2 * 1024),
             write_buf: BytesMut::new(),
             backpressure_boundary: 2 * 1024,
+            padding: None,
         }
     }
 
+    /// Pads the frames sent once the padding is enabled.
+    pub fn with_padding(mut self, padding: Arc<PaddingState>) -> Self {
+        self.padding = Some(padding);
+        self
+    }
+
     pub fn decode_frame(&mut self) -> io::Result<Option<MuxFrame>> {
-        let mut buf = &self.read_buf[..];
-        if buf.is_empty() {
-            return Ok(None);
-        }
-        match buf[0] {
-            FRAME_STREAM => {
-                buf = &buf[1..];
+        loop {
+            let mut buf = &self.read_buf[..];
+            if buf.is_empty() {
+                return Ok(None);
+            }
+            match buf[0] {
+                FRAME_STREAM => {
+                    buf = &buf[1..];
 
-                if buf.len() < 2 {
-                    self.read_buf.reserve(3);
-                    return Ok(None);
-                }
-                let stream_id = u16::from_be_bytes((&buf[..2]).try_into().unwrap());
-                buf = &buf[2..];
+                    if buf.len() < 2 {
+                        self.read_buf.reserve(3);
+                        return Ok(None);
+                    }
+                    let stream_id = u16::from_be_bytes((&buf[..2]).try_into().unwrap());
+                    buf = &buf[2..];
 
-                if buf.len() < 2 {
-                    self.read_buf.reserve(5);
-                    return Ok(None);
-                }
-                let len = u16::from_be_bytes((&buf[..2]).try_into().unwrap()) as usize;
-                buf = &buf[2..];
+                    if buf.len() < 2 {
+                        self.read_buf.reserve(5);
+                        return Ok(None);
+                    }
+                    let len = u16::from_be_bytes((&buf[..2]).try_into().unwrap()) as usize;
+                    buf = &buf[2..];
 
-                if buf.len() < len {
-                    self.read_buf.reserve(5 + len);
-                    return Ok(None);
+                    if buf.len() < len {
+                        self.read_buf.reserve(5 + len);
+                        return Ok(None);
+                    }
+                    let data = &buf[..len];
+
+                    // TODO freeze bytes
+                    let frame = MuxFrame::Stream(stream_id, data.to_vec());
+                    let _ = self.read_buf.split_to(5 + len);
+
+                    self.read_buf.reserve(3); // minimal frame size
+
+                    return Ok(Some(frame));
                 }
-                let data = &buf[..len];
+                FRAME_STREAM_FIN => {
+                    buf = &buf[1..];
 
-                // TODO freeze bytes
-                let frame = MuxFrame::Stream(stream_id, data.to_vec());
-                let _ = self.read_buf.split_to(5 + len);
+                    if buf.len() < 2 {
+                        self.read_buf.reserve(3);
+                        return Ok(None);
+                    }
+                    let stream_id = u16::from_be_bytes((&buf[..2]).try_into().unwrap());
 
-                self.read_buf.reserve(3); // minimal frame size
+                    let frame = MuxFrame::StreamFin(stream_id);
+                    let _ = self.read_buf.split_to(1 + 2);
 
-                Ok(Some(frame))
-            }
-            FRAME_STREAM_FIN => {
-                buf = &buf[1..];
+                    self.read_buf.reserve(3); // minimal frame size
 
-                if buf.len() < 2 {
-                    self.read_buf.reserve(3);
-                    return Ok(None);
+                    return Ok(Some(frame));
                 }
-                let stream_id = u16::from_be_bytes((&buf[..2]).try_into().unwrap());
+                // Frames of later versions, with the length of the payload.
+                frame_type => {
+                    buf = &buf[1..];
 
-                let frame = MuxFrame::StreamFin(stream_id);
-                let _ = self.read_buf.split_to(1 + 2);
+                    if buf.len() < 2 {
+                        self.read_buf.reserve(3);
+                        return Ok(None);
+                    }
+                    let len = u16::from_be_bytes((&buf[..2]).try_into().unwrap()) as usize;
+                    buf = &buf[2..];
+
+                    if buf.len() < len {
+                        self.read_buf.reserve(3 + len);
+                        return Ok(None);
+                    }
+                    let payload = &buf[..len];
+                    let frame = match frame_type {
+                        FRAME_PING => Some(MuxFrame::Ping(payload.first().copied().unwrap_or(0))),
+                        FRAME_PONG => Some(MuxFrame::Pong(payload.first().copied().unwrap_or(0))),
+                        FRAME_STREAM_RESET if len >= 2 => Some(MuxFrame::StreamReset(
+                            u16::from_be_bytes((&payload[..2]).try_into().unwrap()),
+                        )),
+                        FRAME_SESSION_OPEN if len >= 4 => Some(MuxFrame::SessionOpen(
+                            payload[0],
+                            payload[1],
+                            u16::from_be_bytes((&payload[2..4]).try_into().unwrap()),
+                        )),
+                        FRAME_PADDING => Some(MuxFrame::Padding(len as u16)),
+                        _ => None,
+                    };
+                    let _ = self.read_buf.split_to(3 + len);
 
-                self.read_buf.reserve(3); // minimal frame size
+                    self.read_buf.reserve(3); // minimal frame size
 
-                Ok(Some(frame))
+                    match frame {
+                        Some(frame) => return Ok(Some(frame)),
+                        None => trace!("skipped unknown frame type {}", frame_type),
+                    }
+                }
             }
-            _ => Err(unknown_frame(buf[0])),
         }
     }
 
     pub fn encode_frame(&mut self, frame: MuxFrame) -> io::Result<()> {
-        self.write_buf.extend_from_slice(&frame.to_bytes());
+        let bytes = frame.to_bytes();
+        self.write_buf.extend_from_slice(&bytes);
+        // Dummy frames have been accounted for already.
+        if matches!(frame, MuxFrame::Padding(_)) {
+            return Ok(());
+        }
+        if let Some(n) = self.padding.as_ref().and_then(|x| x.pad_len(bytes.len())) {
+            self.write_buf
+                .extend_from_slice(&MuxFrame::Padding(n).to_bytes());
+        }
         Ok(())
     }
 }
@@ -399,6 +718,8 @@ struct Accept {
     session_id: SessionId,
     stream_accept_tx: Sender<MuxStream>,
     frame_write_tx: Sender<MuxFrame>,
+    // Maximum number of streams at a time, 0 for no limit.
+    max_streams: usize,
 }
 
 pub struct MuxSession;
@@ -410,41 +731,76 @@ impl MuxSession {
         recv_end: Option<Arc<Mutex<bool>>>,
         mut accept: Option<Accept>,
         recv_bytes_counter: Option<Arc<AtomicUsize>>,
+        pong_tx: Sender<MuxFrame>,
+        liveness: Option<Arc<Liveness>>,
+        padding: Option<Arc<PaddingState>>,
     ) -> AbortHandle
     where
         S: 'static + AsyncRead + AsyncWrite + Unpin + Send,
     {
         let task = Box::pin(
             async move {
+                // Streams refused for the limit, their frames are dropped.
+                let mut rejected = HashSet::new();
                 while let Some(frame) = frame_stream.next().await {
                     match frame {
                         Ok(frame) => {
+                            if let Some(liveness) = liveness.as_ref() {
+                                liveness.touch();
+                            }
                             match frame {
                                 MuxFrame::Stream(stream_id, data) => {
+                                    if rejected.contains(&stream_id) {
+                                        continue;
+                                    }
+                                    let mut reject = false;
                                     // In accept mode.
                                     if let Some(Accept {
                                         session_id,
                                         stream_accept_tx,
                                         frame_write_tx,
+                                        max_streams,
                                     }) = accept.as_mut()
                                     {
+                                        let mut streams = streams.lock().await;
+                                        let n = streams.len();
                                         // Accepts new stream for an unseen stream ID.
                                         if let std::collections::hash_map::Entry::Vacant(e) =
-                                            streams.lock().await.entry(stream_id)
+                                            streams.entry(stream_id)
                                         {
-                                            let (mux_stream, stream_read_tx) = MuxStream::new(
-                                                *session_id,
-                                                stream_id,
-                                                frame_write_tx.clone(),
-                                                Arc::new(AtomicBool::new(false)),
-                                            );
-                                            e.insert(stream_read_tx);
-                                            if stream_accept_tx.send(mux_stream).await.is_err() {
-                                                // The `Incoming` transport has been dropped.
-                                                break;
+                                            if *max_streams > 0 && n >= *max_streams {
+                                                reject = true;
+                                            } else {
+                                                let (mux_stream, stream_read_tx) = MuxStream::new(
+                                                    *session_id,
+                                                    stream_id,
+                                                    frame_write_tx.clone(),
+                                                    Arc::new(AtomicBool::new(false)),
+                                                    None,
+                                                );
+                                                e.insert(stream_read_tx);
+                                                if stream_accept_tx.send(mux_stream).await.is_err()
+                                                {
+                                                    // The `Incoming` transport has been dropped.
+                                                    break;
+                                                }
                                             }
                                         }
                                     }
+                                    if reject {
+                                        debug!(
+                                            "refused mux stream {}, too many streams",
+                                            stream_id
+                                        );
+                                        rejected.insert(stream_id);
+                                        if let Some(accept) = accept.as_ref() {
+                                            let _ = accept
+                                                .frame_write_tx
+                                                .send(MuxFrame::StreamReset(stream_id))
+                                                .await;
+                                        }
+                                        continue;
+                                    }
                                     // Sends data to the stream.
                                     if let Some(stream_read_tx) =
                                         streams.lock().await.get(&stream_id).cloned()
@@ -457,6 +813,9 @@ impl MuxSession {
                                     }
                                 }
                                 MuxFrame::StreamFin(stream_id) => {
+                                    if rejected.remove(&stream_id) {
+                                        continue;
+                                    }
                                     // Send an empty buffer to indicate EOF.
                                     if let Some(stream_read_tx) =
                                         streams.lock().await.get(&stream_id).cloned()
@@ -470,6 +829,50 @@ impl MuxSession {
                                         streams2.lock().await.remove(&stream_id);
                                     });
                                 }
+                                MuxFrame::Ping(version) => {
+                                    trace!("received ping of version {}", version);
+                                    // Dropped if the send loop is busy, which
+                                    // keeps the peer hearing from us anyway.
+                                    let _ = pong_tx.try_send(MuxFrame::Pong(PROTOCOL_VERSION));
+                                }
+                                MuxFrame::Pong(version) => {
+                                    trace!("received pong of version {}", version);
+                                }
+                                MuxFrame::StreamReset(stream_id) => {
+                                    debug!("mux stream {} reset by peer", stream_id);
+                                    // The stream sees a broken pipe.
+                                    streams.lock().await.remove(&stream_id);
+                                }
+                                MuxFrame::SessionOpen(version, flags, overhead_pct) => {
+                                    trace!(
+                                        "received session open of version {}, flags {:#04x}",
+                                        version,
+                                        flags
+                                    );
+                                    let padding = padding
+                                        .as_ref()
+                                        .filter(|_| flags & SESSION_FLAG_PADDING != 0);
+                                    if let Some(padding) = padding {
+                                        padding.enable(overhead_pct);
+                                    }
+                                    // In accept mode, answers with the flags
+                                    // agreed to.
+                                    if accept.is_some() {
+                                        let flags = if padding.is_some() {
+                                            SESSION_FLAG_PADDING
+                                        } else {
+                                            0
+                                        };
+                                        let _ = pong_tx
+                                            .send(MuxFrame::SessionOpen(
+                                                PROTOCOL_VERSION,
+                                                flags,
+                                                overhead_pct,
+                                            ))
+                                            .await;
+                                    }
+                                }
+                                MuxFrame::Padding(_) => (),
                             }
                         }
                         // Borken pipe.
@@ -497,13 +900,35 @@ impl MuxSession {
         mut frame_sink: SplitSink<MuxConnection<S>, MuxFrame>,
         mut frame_write_rx: Receiver<MuxFrame>,
         send_end: Option<Arc<Mutex<bool>>>,
+        padding: Option<Arc<PaddingState>>,
     ) -> AbortHandle
     where
         S: 'static + AsyncRead + AsyncWrite + Unpin + Send,
     {
         let task = Box::pin(
             async move {
-                while let Some(frame) = frame_write_rx.recv().await {
+                loop {
+                    let frame = match padding.as_ref().filter(|x| x.is_enabled()) {
+                        // Sends a dummy frame if nothing was sent for a
+                        // while.
+                        Some(padding) => {
+                            use rand::Rng;
+                            let idle = rand::thread_rng().gen_range(PADDING_IDLE_MS);
+                            tokio::select! {
+                                frame = frame_write_rx.recv() => frame,
+                                _ = sleep(Duration::from_millis(idle)) => {
+                                    match padding.dummy_len() {
+                                        Some(n) => Some(MuxFrame::Padding(n)),
+                                        None => continue,
+                                    }
+                                }
+                            }
+                        }
+                        None => frame_write_rx.recv().await,
+                    };
+                    let Some(frame) = frame else {
+                        break;
+                    };
                     // Peek EOF.
                     if let MuxFrame::StreamFin(ref stream_id) = frame {
                         let streams2 = streams.clone();
@@ -533,35 +958,118 @@ impl MuxSession {
         handle
     }
 
+    // Pings the peer when the connection is idle, and closes the connection
+    // when too many pings went without anything received.
+    fn run_keep_alive_loop(
+        streams: Streams,
+        frame_write_tx: Sender<MuxFrame>,
+        liveness: Arc<Liveness>,
+        keep_alive: KeepAlive,
+        loop_handles: [AbortHandle; 2],
+    ) -> AbortHandle {
+        let task = Box::pin(
+            async move {
+                let mut frames = liveness.frames();
+                let mut missed = 0;
+                loop {
+                    sleep(keep_alive.interval).await;
+                    let n = liveness.frames();
+                    if n != frames {
+                        frames = n;
+                        missed = 0;
+                        continue;
+                    }
+                    if missed >= keep_alive.max_missed {
+                        debug!("mux connection missed {} pings, closing", missed);
+                        liveness.set_dead();
+                        for h in loop_handles.iter() {
+                            h.abort();
+                        }
+                        // Streams on the connection see a broken pipe.
+                        streams.lock().await.clear();
+                        break;
+                    }
+                    if frame_write_tx
+                        .send(MuxFrame::Ping(PROTOCOL_VERSION))
+                        .await
+                        .is_err()
+                    {
+                        break;
+                    }
+                    missed += 1;
+                }
+            }
+            .instrument(tracing::Span::current()),
+        );
+        let (task, handle) = abortable(task);
+        tokio::spawn(task);
+        handle
+    }
+
+    #[allow(clippy::too_many_arguments)]
     pub fn connector<S>(
         conn: S,
         max_accepts: usize,
         concurrency: usize,
         max_recv_bytes: usize,
         max_lifetime: u64,
+        idle_timeout: u64,
+        keep_alive: KeepAlive,
+        padding: Padding,
     ) -> MuxConnector
     where
         S: 'static + AsyncRead + AsyncWrite + Unpin + Send,
     {
-        let (frame_sink, frame_stream) = MuxConnection::new(conn).split();
+        let mut conn = MuxConnection::new(conn);
         let (frame_write_tx, frame_write_rx) =
             mpsc::channel::<MuxFrame>(*crate::option::AMUX_FRAME_CHANNEL_SIZE);
+        // Asks for the padding ahead of any stream, it's enabled on the
+        // answer of the acceptor.
+        let padding_state = if padding.enabled {
+            let state = Arc::new(PaddingState::new());
+            conn = conn.with_padding(state.clone());
+            let _ = frame_write_tx.try_send(MuxFrame::SessionOpen(
+                PROTOCOL_VERSION,
+                SESSION_FLAG_PADDING,
+                padding.max_overhead_pct,
+            ));
+            Some(state)
+        } else {
+            None
+        };
+        let (frame_sink, frame_stream) = conn.split();
         let (recv_end, send_end) = (Arc::new(Mutex::new(false)), Arc::new(Mutex::new(false)));
         let streams: Streams = Arc::new(Mutex::new(HashMap::new()));
         let recv_bytes_counter = Arc::new(AtomicUsize::new(0));
+        let liveness = Arc::new(Liveness::new());
         let recv_handle = Self::run_frame_receive_loop(
             streams.clone(),
             frame_stream,
             Some(recv_end.clone()),
             None,
             Some(recv_bytes_counter.clone()),
+            frame_write_tx.clone(),
+            Some(liveness.clone()),
+            padding_state.clone(),
         );
         let send_handle = Self::run_frame_send_loop(
             streams.clone(),
             frame_sink,
             frame_write_rx,
             Some(send_end.clone()),
+            padding_state,
         );
+        let keep_alive_handle = if keep_alive.interval.is_zero() {
+            None
+        } else {
+            Some(Self::run_keep_alive_loop(
+                streams.clone(),
+                frame_write_tx.clone(),
+                liveness.clone(),
+                keep_alive,
+                [recv_handle.clone(), send_handle.clone()],
+            ))
+        };
         let session_id = random_u16();
         let started_at = Instant::now();
         MuxConnector::new(
@@ -571,6 +1079,7 @@ impl MuxSession {
             recv_bytes_counter,
             max_lifetime,
             started_at,
+            idle_timeout,
             session_id,
             streams,
             frame_write_tx,
@@ -578,14 +1087,21 @@ impl MuxSession {
             send_end,
             recv_handle,
             send_handle,
+            keep_alive,
+            liveness,
+            keep_alive_handle,
         )
     }
 
-    pub fn acceptor<S>(conn: S) -> MuxAcceptor
+    pub fn acceptor<S>(conn: S, max_streams: usize) -> MuxAcceptor
     where
         S: 'static + AsyncRead + AsyncWrite + Unpin + Send,
     {
-        let (frame_sink, frame_stream) = MuxConnection::new(conn).split();
+        // The padding is enabled if the connector asks for it.
+        let padding = Arc::new(PaddingState::new());
+        let (frame_sink, frame_stream) = MuxConnection::new(conn)
+            .with_padding(padding.clone())
+            .split();
         let (frame_write_tx, frame_write_rx) =
             mpsc::channel::<MuxFrame>(*crate::option::AMUX_FRAME_CHANNEL_SIZE);
         let streams: Streams = Arc::new(Mutex::new(HashMap::new()));
@@ -599,11 +1115,16 @@ impl MuxSession {
             Some(Accept {
                 session_id,
                 stream_accept_tx,
-                frame_write_tx,
+                frame_write_tx: frame_write_tx.clone(),
+                max_streams,
             }),
             None,
+            frame_write_tx,
+            None,
+            Some(padding.clone()),
         );
-        let send_handle = Self::run_frame_send_loop(streams, frame_sink, frame_write_rx, None);
+        let send_handle =
+            Self::run_frame_send_loop(streams, frame_sink, frame_write_rx, None, Some(padding));
         MuxAcceptor::new(session_id, stream_accept_rx, recv_handle, send_handle)
     }
 }
@@ -623,6 +1144,11 @@ pub struct MuxConnector {
     max_lifetime: u64,
     // The time the connection is started.
     started_at: Instant,
+    // The connection is closed if it has no streams for this long, in
+    // seconds.
+    idle_timeout: u64,
+    // The time the connection was last seen without streams.
+    idle_since: Option<Instant>,
     // ID for debugging purposes.
     session_id: SessionId,
     // Counter for number of streams created.
@@ -641,6 +1167,11 @@ pub struct MuxConnector {
     recv_handle: AbortHandle,
     // Handle to abort the send loop.
     send_handle: AbortHandle,
+    keep_alive: KeepAlive,
+    // Whether the peer is still there.
+    liveness: Arc<Liveness>,
+    // Handle to abort the keep-alive loop, if any.
+    keep_alive_handle: Option<AbortHandle>,
     // Indicates the connector has no active streams and is no longer accept
     // new stream request.
     done: AtomicBool,
@@ -655,6 +1186,7 @@ impl MuxConnector {
         recv_bytes_counter: Arc<AtomicUsize>,
         max_lifetime: u64,
         started_at: Instant,
+        idle_timeout: u64,
         session_id: SessionId,
         streams: Streams,
         frame_write_tx: Sender<MuxFrame>,
@@ -662,6 +1194,9 @@ impl MuxConnector {
         send_end: Arc<Mutex<bool>>,
         recv_handle: AbortHandle,
         send_handle: AbortHandle,
+        keep_alive: KeepAlive,
+        liveness: Arc<Liveness>,
+        keep_alive_handle: Option<AbortHandle>,
     ) -> Self {
         trace!(
             "new mux connector {} (max_accepts: {}, concurrency: {})",
@@ -676,6 +1211,8 @@ impl MuxConnector {
             recv_bytes_counter,
             max_lifetime,
             started_at,
+            idle_timeout,
+            idle_since: None,
             session_id,
             total_accepted: 0,
             streams,
@@ -685,6 +1222,9 @@ impl MuxConnector {
             send_end,
             recv_handle,
             send_handle,
+            keep_alive,
+            liveness,
+            keep_alive_handle,
             done: AtomicBool::new(false),
         }
     }
@@ -694,7 +1234,8 @@ impl MuxConnector {
     }
 
     pub fn is_done(&self) -> bool {
-        if self.done.load(Ordering::SeqCst) {
+        // A dead connection goes at once, its streams can't make progress.
+        if self.done.load(Ordering::SeqCst) || self.liveness.is_dead() {
             true
         } else if self.total_accepted >= self.max_accepts
             || (self.max_recv_bytes > 0
@@ -713,10 +1254,31 @@ impl MuxConnector {
         }
     }
 
+    /// Marks the connector done if it had no streams for the idle timeout,
+    /// it's to be called periodically.
+    pub fn check_idle(&mut self) {
+        if self.idle_timeout == 0 {
+            return;
+        }
+        if self.stream_ends.iter().any(|x| !x.load(Ordering::Relaxed)) {
+            self.idle_since = None;
+            return;
+        }
+        let idle_since = *self.idle_since.get_or_insert_with(Instant::now);
+        if idle_since.elapsed().as_secs() >= self.idle_timeout {
+            trace!("mux connector {} idle timed out", self.session_id);
+            self.done.store(true, Ordering::Relaxed);
+        }
+    }
+
     pub async fn new_stream(&mut self) -> Option<MuxStream> {
         if self.is_done() {
             return None;
         }
+        if self.liveness.is_dead() {
+            self.done.store(true, Ordering::Relaxed);
+            return None;
+        }
         if *self.recv_end.lock().await {
             self.done.store(true, Ordering::Relaxed);
             return None;
@@ -747,11 +1309,25 @@ impl MuxConnector {
         let frame_write_tx = self.frame_write_tx.clone();
         let stream_id = random_u16();
         let stream_end = Arc::new(AtomicBool::new(false));
+        let open_timeout = if self.keep_alive.open_timeout.is_zero() {
+            None
+        } else {
+            // A peer with keep-alive answers at once, so that the stream
+            // doesn't depend on how long the destination takes.
+            if !self.keep_alive.interval.is_zero() {
+                let _ = frame_write_tx.try_send(MuxFrame::Ping(PROTOCOL_VERSION));
+            }
+            Some(OpenTimeout::new(
+                self.liveness.clone(),
+                self.keep_alive.open_timeout,
+            ))
+        };
         let (mux_stream, stream_read_tx) = MuxStream::new(
             self.session_id,
             stream_id,
             frame_write_tx,
             stream_end.clone(),
+            open_timeout,
         );
         self.stream_ends.push(stream_end);
         self.streams.lock().await.insert(stream_id, stream_read_tx);
@@ -764,6 +1340,9 @@ impl Drop for MuxConnector {
     fn drop(&mut self) {
         self.recv_handle.abort();
         self.send_handle.abort();
+        if let Some(h) = self.keep_alive_handle.as_ref() {
+            h.abort();
+        }
         trace!("drop mux connector {}", self.session_id);
     }
 }
@@ -811,3 +1390,294 @@ impl Stream for MuxAcceptor {
         self.stream_accept_rx.poll_recv(cx)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use tokio::io::{duplex, AsyncReadExt, AsyncWriteExt};
+    use tokio::time::timeout;
+
+    use super::*;
+
+    fn keep_alive(interval: u64, max_missed: usize, open_timeout: u64) -> KeepAlive {
+        KeepAlive {
+            interval: Duration::from_millis(interval),
+            max_missed,
+            open_timeout: Duration::from_millis(open_timeout),
+        }
+    }
+
+    #[test]
+    fn test_decode_keep_alive_frames() {
+        let mut conn = MuxConnection::new(());
+        conn.read_buf
+            .extend_from_slice(&MuxFrame::Ping(PROTOCOL_VERSION).to_bytes());
+        // A frame of a later version is skipped.
+        conn.read_buf
+            .extend_from_slice(&[0x7f, 0x00, 0x02, 0xaa, 0xbb]);
+        conn.read_buf
+            .extend_from_slice(&MuxFrame::Pong(7).to_bytes());
+        conn.read_buf.extend_from_slice(&[FRAME_PING, 0x00]);
+        assert!(matches!(
+            conn.decode_frame().unwrap(),
+            Some(MuxFrame::Ping(PROTOCOL_VERSION))
+        ));
+        assert!(matches!(
+            conn.decode_frame().unwrap(),
+            Some(MuxFrame::Pong(7))
+        ));
+        assert!(conn.decode_frame().unwrap().is_none());
+        conn.read_buf.extend_from_slice(&[0x01, 0x01]);
+        assert!(matches!(
+            conn.decode_frame().unwrap(),
+            Some(MuxFrame::Ping(1))
+        ));
+        conn.read_buf
+            .extend_from_slice(&MuxFrame::StreamReset(0x1234).to_bytes());
+        assert!(matches!(
+            conn.decode_frame().unwrap(),
+            Some(MuxFrame::StreamReset(0x1234))
+        ));
+    }
+
+    #[test]
+    fn test_decode_padding_frames() {
+        let mut conn = MuxConnection::new(());
+        conn.read_buf.extend_from_slice(
+            &MuxFrame::SessionOpen(PROTOCOL_VERSION, SESSION_FLAG_PADDING, 25).to_bytes(),
+        );
+        let padding = MuxFrame::Padding(100).to_bytes();
+        assert_eq!(padding.len(), 103);
+        conn.read_buf.extend_from_slice(&padding);
+        assert!(matches!(
+            conn.decode_frame().unwrap(),
+            Some(MuxFrame::SessionOpen(
+                PROTOCOL_VERSION,
+                SESSION_FLAG_PADDING,
+                25
+            ))
+        ));
+        assert!(matches!(
+            conn.decode_frame().unwrap(),
+            Some(MuxFrame::Padding(100))
+        ));
+        assert!(conn.decode_frame().unwrap().is_none());
+    }
+
+    #[test]
+    fn test_padding_buckets() {
+        let padding = PaddingState::new();
+        assert!(padding.pad_len(100).is_none());
+        padding.enable(50);
+        // Nothing sent yet, no room for padding.
+        assert!(padding.pad_len(10).is_none());
+        padding.sent.store(10_000, Ordering::Relaxed);
+        let n = padding.pad_len(100).unwrap() as usize;
+        assert!([128, 256].contains(&(100 + PADDING_HEADER_LEN + n)));
+        let n = padding.pad_len(2000).unwrap() as usize;
+        assert_eq!(2000 + PADDING_HEADER_LEN + n, 2800);
+        // The budget is spent.
+        padding.padded.store(6_000, Ordering::Relaxed);
+        assert!(padding.pad_len(100).is_none());
+        assert!(padding.dummy_len().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_padding_negotiated() {
+        let (conn, peer) = duplex(64 * 1024);
+        let padding = Padding {
+            enabled: true,
+            max_overhead_pct: 100,
+        };
+        let mut connector =
+            MuxSession::connector(conn, 8, 2, 0, 0, 0, KeepAlive::default(), padding);
+        let mut acceptor = MuxSession::acceptor(peer, 0);
+        let data: Vec<u8> = (0..64 * 1024).map(|x| x as u8).collect();
+        let mut stream = connector.new_stream().await.unwrap();
+        stream.write_all(&data).await.unwrap();
+        stream.shutdown().await.unwrap();
+        let mut accepted = acceptor.next().await.unwrap();
+        let mut buf = Vec::new();
+        accepted.read_to_end(&mut buf).await.unwrap();
+        assert!(buf == data, "received data differs");
+        accepted.write_all(&data).await.unwrap();
+        let mut buf = vec![0u8; data.len()];
+        stream.read_exact(&mut buf).await.unwrap();
+        assert!(buf == data, "received data differs");
+    }
+
+    #[tokio::test]
+    async fn test_padding_not_agreed() {
+        let (conn, mut peer) = duplex(64 * 1024);
+        let padding = Padding {
+            enabled: true,
+            max_overhead_pct: 100,
+        };
+        let mut connector =
+            MuxSession::connector(conn, 8, 2, 0, 0, 0, KeepAlive::default(), padding);
+        let mut stream = connector.new_stream().await.unwrap();
+        stream.write_all(b"hello").await.unwrap();
+        // A peer of an earlier version doesn't answer the session open, the
+        // frames go out unpadded.
+        let open = MuxFrame::SessionOpen(PROTOCOL_VERSION, SESSION_FLAG_PADDING, 100).to_bytes();
+        let data = MuxFrame::Stream(stream.id(), b"hello".to_vec()).to_bytes();
+        let mut buf = vec![0u8; open.len() + data.len()];
+        peer.read_exact(&mut buf).await.unwrap();
+        assert_eq!(buf, [&open[..], &data[..]].concat());
+        let mut extra = [0u8; 1];
+        assert!(timeout(Duration::from_millis(100), peer.read(&mut extra))
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_keep_alive_closes_dead_connection() {
+        let (conn, mut peer) = duplex(64 * 1024);
+        let mut connector = MuxSession::connector(
+            conn,
+            8,
+            2,
+            0,
+            0,
+            0,
+            keep_alive(50, 2, 0),
+            Padding::default(),
+        );
+        let mut stream = connector.new_stream().await.unwrap();
+        // The peer takes the frames but never answers.
+        sleep(Duration::from_millis(400)).await;
+        assert!(connector.is_done());
+        assert!(connector.new_stream().await.is_none());
+        let mut buf = [0u8; 1];
+        assert!(stream.read(&mut buf).await.is_err());
+        let mut sent = vec![0u8; 64];
+        let n = peer.read(&mut sent).await.unwrap();
+        let ping = MuxFrame::Ping(PROTOCOL_VERSION).to_bytes();
+        assert_eq!(&sent[..n], [&ping[..], &ping[..]].concat());
+    }
+
+    #[tokio::test]
+    async fn test_keep_alive_answered() {
+        let (conn, peer) = duplex(64 * 1024);
+        let mut connector = MuxSession::connector(
+            conn,
+            8,
+            2,
+            0,
+            0,
+            0,
+            keep_alive(50, 2, 0),
+            Padding::default(),
+        );
+        let _acceptor = MuxSession::acceptor(peer, 0);
+        sleep(Duration::from_millis(400)).await;
+        assert!(!connector.is_done());
+        assert!(connector.new_stream().await.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_open_timeout() {
+        let (conn, _peer) = duplex(64 * 1024);
+        let mut connector = MuxSession::connector(
+            conn,
+            8,
+            2,
+            0,
+            0,
+            0,
+            keep_alive(0, 3, 100),
+            Padding::default(),
+        );
+        let mut stream = connector.new_stream().await.unwrap();
+        stream.write_all(b"hello").await.unwrap();
+        let mut buf = [0u8; 5];
+        let e = stream.read(&mut buf).await.unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::TimedOut);
+        assert!(connector.is_done());
+    }
+
+    #[tokio::test]
+    async fn test_open_timeout_alive_connection() {
+        let (conn, peer) = duplex(64 * 1024);
+        let mut connector = MuxSession::connector(
+            conn,
+            8,
+            2,
+            0,
+            0,
+            0,
+            keep_alive(1000, 3, 100),
+            Padding::default(),
+        );
+        let mut acceptor = MuxSession::acceptor(peer, 0);
+        let mut stream = connector.new_stream().await.unwrap();
+        stream.write_all(b"ping").await.unwrap();
+        let mut accepted = acceptor.next().await.unwrap();
+        // The destination answers later than the open timeout, the pong the
+        // stream opened with shows the connection is alive.
+        sleep(Duration::from_millis(300)).await;
+        accepted.write_all(b"pong").await.unwrap();
+        let mut buf = [0u8; 4];
+        stream.read_exact(&mut buf).await.unwrap();
+        assert_eq!(&buf, b"pong");
+        assert!(!connector.is_done());
+    }
+
+    #[tokio::test]
+    async fn test_inbound_stream_limit() {
+        let (conn, peer) = duplex(64 * 1024);
+        let mut connector = MuxSession::connector(
+            conn,
+            8,
+            8,
+            0,
+            0,
+            0,
+            KeepAlive::default(),
+            Padding::default(),
+        );
+        let mut acceptor = MuxSession::acceptor(peer, 1);
+        let mut first = connector.new_stream().await.unwrap();
+        first.write_all(b"a").await.unwrap();
+        let mut accepted = acceptor.next().await.unwrap();
+
+        // The acceptor has no room for a second stream.
+        let mut second = connector.new_stream().await.unwrap();
+        second.write_all(b"b").await.unwrap();
+        let mut buf = [0u8; 1];
+        assert!(second.read(&mut buf).await.is_err());
+
+        // The first one goes on.
+        accepted.read_exact(&mut buf).await.unwrap();
+        assert_eq!(&buf, b"a");
+        accepted.write_all(b"x").await.unwrap();
+        first.read_exact(&mut buf).await.unwrap();
+        assert_eq!(&buf, b"x");
+    }
+
+    #[tokio::test]
+    async fn test_idle_timeout() {
+        let (conn, _peer) = duplex(64 * 1024);
+        let mut connector = MuxSession::connector(
+            conn,
+            8,
+            2,
+            0,
+            0,
+            1,
+            KeepAlive::default(),
+            Padding::default(),
+        );
+        let stream = connector.new_stream().await.unwrap();
+        connector.check_idle();
+        sleep(Duration::from_millis(1100)).await;
+        // Not idle while the stream is there.
+        connector.check_idle();
+        assert!(!connector.is_done());
+        drop(stream);
+        connector.check_idle();
+        assert!(!connector.is_done());
+        sleep(Duration::from_millis(1100)).await;
+        connector.check_idle();
+        assert!(connector.is_done());
+    }
+}
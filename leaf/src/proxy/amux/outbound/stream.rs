@@ -1,5 +1,6 @@
 use std::convert::TryFrom;
 use std::io;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
 
 use async_trait::async_trait;
@@ -10,6 +11,7 @@ use rand::prelude::SliceRandom;
 use rand::rngs::StdRng;
 use rand::SeedableRng;
 use tokio::sync::Mutex;
+use tokio::time::{timeout, Instant};
 use tracing::{debug, Instrument};
 
 use crate::{
@@ -18,9 +20,23 @@ use crate::{
     session::{Session, SocksAddr},
 };
 
+use super::KeepAlive;
 use super::MuxConnector;
 use super::MuxSession;
 use super::MuxStream;
+use super::Padding;
+
+// How often a session waiting for room in the pool checks again.
+const POOL_WAIT_INTERVAL: Duration = Duration::from_millis(100);
+
+// Counts a connection being dialed until it's in the pool or failed.
+struct Dialing<'a>(&'a AtomicUsize);
+
+impl Drop for Dialing<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
 
 pub struct MuxManager {
     pub address: String,
@@ -30,7 +46,15 @@ pub struct MuxManager {
     pub concurrency: usize,
     pub max_recv_bytes: usize,
     pub max_lifetime: u64,
+    // Maximum number of connections, 0 for no limit.
+    pub max_connections: usize,
+    // Connections without streams for this long are closed, in seconds.
+    pub idle_timeout: u64,
+    pub keep_alive: KeepAlive,
+    pub padding: Padding,
     pub dns_client: SyncDnsClient,
+    // Number of connections being dialed.
+    pub dialing: AtomicUsize,
     // TODO Verify whether the run loops in connectors are aborted after
     // a config reload.
     pub connectors: Arc<Mutex<Vec<MuxConnector>>>,
@@ -47,6 +71,10 @@ impl MuxManager {
         concurrency: usize,
         max_recv_bytes: usize,
         max_lifetime: u64,
+        max_connections: usize,
+        idle_timeout: u64,
+        keep_alive: KeepAlive,
+        padding: Padding,
         dns_client: SyncDnsClient,
     ) -> (Self, Vec<AbortHandle>) {
         let mut abort_handles = Vec::new();
@@ -54,10 +82,21 @@ impl MuxManager {
         let connectors2 = connectors.clone();
         // A task to monitor and remove completed connectors.
         // TODO passive detection
+        let interval = if idle_timeout > 0 {
+            Duration::from_secs(idle_timeout.min(5))
+        } else {
+            Duration::from_secs(5)
+        };
         let fut = async move {
             loop {
-                connectors2.lock().await.retain(|c| !c.is_done());
-                tokio::time::sleep(Duration::from_secs(5)).await;
+                {
+                    let mut conns = connectors2.lock().await;
+                    for c in conns.iter_mut() {
+                        c.check_idle();
+                    }
+                    conns.retain(|c| !c.is_done());
+                }
+                tokio::time::sleep(interval).await;
             }
         };
         let (abortable, abort_handle) = abortable(fut);
@@ -72,7 +111,12 @@ impl MuxManager {
                 concurrency,
                 max_recv_bytes,
                 max_lifetime,
+                max_connections,
+                idle_timeout,
+                keep_alive,
+                padding,
                 dns_client,
+                dialing: AtomicUsize::new(0),
                 connectors,
                 monitor_task: Mutex::new(Some(monitor_task)),
             },
@@ -88,43 +132,55 @@ impl MuxManager {
             }
         }
 
-        if !sess.new_conn_once {
-            // Try to create the stream from existing connections.
-            let mut conns = self.connectors.lock().await;
-            conns.shuffle(&mut StdRng::from_entropy());
-            for c in conns.iter_mut() {
-                if let Some(s) = c.new_stream().instrument(tracing::Span::current()).await {
-                    return Ok(s);
+        let queued_at = Instant::now();
+        loop {
+            {
+                let mut conns = self.connectors.lock().await;
+                if !sess.new_conn_once {
+                    // Try to create the stream from existing connections.
+                    conns.shuffle(&mut StdRng::from_entropy());
+                    for c in conns.iter_mut() {
+                        if let Some(s) = c.new_stream().instrument(tracing::Span::current()).await {
+                            return Ok(s);
+                        }
+                    }
                 }
+                // All connections are full, dial a new one if the pool has
+                // room for it.
+                conns.retain(|c| !c.is_done());
+                if self.max_connections == 0
+                    || conns.len() + self.dialing.load(Ordering::Relaxed) < self.max_connections
+                {
+                    self.dialing.fetch_add(1, Ordering::Relaxed);
+                    break;
+                }
+            }
+            // Wait for a stream to end or a connection to leave the pool.
+            if queued_at.elapsed().as_secs() >= *crate::option::OUTBOUND_DIAL_TIMEOUT {
+                return Err(io::Error::new(
+                    io::ErrorKind::TimedOut,
+                    "amux connection pool exhausted",
+                ));
             }
+            tokio::time::sleep(POOL_WAIT_INTERVAL).await;
         }
+        let dialing = Dialing(&self.dialing);
 
-        // Create a new connection.
-
-        // Create the underlying TCP stream.
-        let mut conn = self
-            .new_tcp_stream(self.dns_client.clone(), &self.address, &self.port)
-            .instrument(tracing::Span::current())
-            .await?;
-
-        // Pass the TCP stream through all sub-transports, e.g. TLS, WebSocket.
-        let mut sess = sess.clone();
-        sess.destination = SocksAddr::try_from((&self.address, self.port))?;
-        sess.dns_sniffed_domain = None;
-        sess.http_sniffed_domain = None;
-        sess.tls_sniffed_domain = None;
-        for a in self.actors.iter() {
-            conn = a
-                .stream()?
-                .handle(&sess, None, Some(conn))
-                .instrument(tracing::Span::current())
-                .await?;
-        }
+        // Create a new connection, within the open timeout if any.
+        let conn = if self.keep_alive.open_timeout.is_zero() {
+            self.dial(sess).await?
+        } else {
+            timeout(self.keep_alive.open_timeout, self.dial(sess))
+                .await
+                .map_err(|_| {
+                    io::Error::new(io::ErrorKind::TimedOut, "dial amux connection timed out")
+                })??
+        };
 
         // Create the stream over this new connection.
         let mut connector = {
             if sess.new_conn_once {
-                MuxSession::connector(conn, 1, 1, 0, 0)
+                MuxSession::connector(conn, 1, 1, 0, 0, 0, self.keep_alive, self.padding)
             } else {
                 MuxSession::connector(
                     conn,
@@ -132,6 +188,9 @@ impl MuxManager {
                     self.concurrency,
                     self.max_recv_bytes,
                     self.max_lifetime,
+                    self.idle_timeout,
+                    self.keep_alive,
+                    self.padding,
                 )
             }
         };
@@ -145,9 +204,33 @@ impl MuxManager {
         };
         let mut conns = self.connectors.lock().await;
         conns.push(connector);
+        drop(dialing);
         debug!("created new amux conn, total: {}", conns.len());
         Ok(s)
     }
+
+    async fn dial(&self, sess: &Session) -> io::Result<AnyStream> {
+        // Create the underlying TCP stream.
+        let mut conn = self
+            .new_tcp_stream(self.dns_client.clone(), &self.address, &self.port)
+            .instrument(tracing::Span::current())
+            .await?;
+
+        // Pass the TCP stream through all sub-transports, e.g. TLS, WebSocket.
+        let mut sess = sess.clone();
+        sess.destination = SocksAddr::try_from((&self.address, self.port))?;
+        sess.dns_sniffed_domain = None;
+        sess.http_sniffed_domain = None;
+        sess.tls_sniffed_domain = None;
+        for a in self.actors.iter() {
+            conn = a
+                .stream()?
+                .handle(&sess, None, Some(conn))
+                .instrument(tracing::Span::current())
+                .await?;
+        }
+        Ok(conn)
+    }
 }
 
 impl TcpConnector for MuxManager {}
@@ -166,6 +249,10 @@ impl Handler {
         concurrency: usize,
         max_recv_bytes: usize,
         max_lifetime: u64,
+        max_connections: usize,
+        idle_timeout: u64,
+        keep_alive: KeepAlive,
+        padding: Padding,
         dns_client: SyncDnsClient,
     ) -> (Self, Vec<AbortHandle>) {
         let (manager, abort_handles) = MuxManager::new(
@@ -176,6 +263,10 @@ impl Handler {
             concurrency,
             max_recv_bytes,
             max_lifetime,
+            max_connections,
+            idle_timeout,
+            keep_alive,
+            padding,
             dns_client,
         );
         (Handler { manager }, abort_handles)
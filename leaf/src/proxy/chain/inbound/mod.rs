@@ -57,6 +57,7 @@ async fn run_stream_actors(
             InboundTransport::Datagram(socket, sess) => {
                 return Ok(AnyBaseInboundTransport::Datagram(socket, sess));
             }
+            InboundTransport::Empty => return Ok(AnyBaseInboundTransport::Empty),
             _ => {
                 return Err(io::Error::other("invalid chain inbound incoming stream transport"));
             }
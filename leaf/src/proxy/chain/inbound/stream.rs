@@ -41,6 +41,9 @@ impl InboundStreamHandler for Handler {
                         self.actors[i + 1..].to_vec(), // FIXME oob check
                     ))));
                 }
+                // The actor took over the stream, e.g. to relay it to a
+                // fallback.
+                InboundTransport::Empty => return Ok(InboundTransport::Empty),
                 _ => {
                     return Err(io::Error::other("invalid transport"));
                 }
@@ -145,15 +145,19 @@ impl OutboundDatagramHandler for Handler {
         self.next_connect_addr(0)
     }
 
+    // The transport the chain takes depends on all actors, not only the
+    // first one. Any actor which needs a stream, such as the UDP of trojan or
+    // vmess, or a stream link before it, makes the chain take a stream. A
+    // chain nested in another one relies on this to be handed the right
+    // transport.
     fn transport_type(&self) -> DatagramTransportType {
-        self.actors
-            .first()
-            .map(|x| {
-                x.datagram()
-                    .map(|x| x.transport_type())
-                    .unwrap_or(DatagramTransportType::Unknown)
-            })
-            .unwrap_or(DatagramTransportType::Unknown)
+        if self.unreliable_chain(0) {
+            DatagramTransportType::Unreliable
+        } else if self.actors.iter().any(|a| a.datagram().is_ok()) {
+            DatagramTransportType::Reliable
+        } else {
+            DatagramTransportType::Unknown
+        }
     }
 
     async fn handle<'a>(
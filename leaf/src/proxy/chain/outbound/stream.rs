@@ -2,15 +2,19 @@ use std::convert::TryFrom;
 use std::io;
 
 use async_trait::async_trait;
-use tracing::Instrument;
+use tracing::{debug, Instrument};
 
 use crate::{
+    app::SyncDnsClient,
     proxy::*,
-    session::{Session, SocksAddr},
+    session::{Network, Session, SocksAddr},
 };
 
 pub struct Handler {
     pub actors: Vec<AnyOutboundHandler>,
+    // Dials the transport again for an actor which can only succeed on a
+    // new one.
+    pub dns_client: SyncDnsClient,
 }
 
 impl Handler {
@@ -49,21 +53,13 @@ impl Handler {
         }
         sess
     }
-}
-
-#[async_trait]
-impl OutboundStreamHandler for Handler {
-    fn connect_addr(&self) -> OutboundConnect {
-        self.next_connect_addr(0)
-    }
 
-    async fn handle<'a>(
-        &'a self,
-        sess: &'a Session,
+    async fn handle_actors(
+        &self,
+        sess: &Session,
         mut lhs: Option<&mut AnyStream>,
         mut stream: Option<AnyStream>,
     ) -> io::Result<AnyStream> {
-        tracing::trace!("handling outbound stream");
         for (i, a) in self.actors.iter().enumerate() {
             let new_sess = self.next_session(sess.clone(), i + 1);
             let s = stream.take();
@@ -81,4 +77,137 @@ impl OutboundStreamHandler for Handler {
         }
         Ok(stream.ok_or_else(|| io::Error::other("chain tcp invalid input"))?)
     }
+
+    // The transport the chain is given, as `connect_stream_outbound` dials
+    // it.
+    async fn dial(&self, sess: &Session) -> io::Result<Option<AnyStream>> {
+        match self.connect_addr() {
+            OutboundConnect::Proxy(Network::Tcp, addr, port) => Ok(Some(
+                new_tcp_stream(self.dns_client.clone(), &addr, &port).await?,
+            )),
+            OutboundConnect::Direct => {
+                let dest = &sess.destination;
+                Ok(Some(
+                    new_tcp_stream(self.dns_client.clone(), &dest.host(), &dest.port()).await?,
+                ))
+            }
+            _ => Ok(None),
+        }
+    }
+}
+
+#[async_trait]
+impl OutboundStreamHandler for Handler {
+    fn connect_addr(&self) -> OutboundConnect {
+        self.next_connect_addr(0)
+    }
+
+    async fn handle<'a>(
+        &'a self,
+        sess: &'a Session,
+        mut lhs: Option<&mut AnyStream>,
+        stream: Option<AnyStream>,
+    ) -> io::Result<AnyStream> {
+        tracing::trace!("handling outbound stream");
+        let dialed = stream.is_some();
+        match self.handle_actors(sess, lhs.as_deref_mut(), stream).await {
+            // Retried once, the transport given is gone with the failure.
+            Err(e) if dialed && is_retry_on_new_transport(&e) => {
+                debug!("retrying on a new transport: {}", e);
+                let Some(stream) = self.dial(sess).await? else {
+                    return Err(e);
+                };
+                self.handle_actors(sess, lhs, Some(stream)).await
+            }
+            res => res,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    use protobuf::MessageField;
+    use tokio::net::TcpListener;
+    use tokio::sync::RwLock;
+
+    use super::*;
+    use crate::app::dns::DnsClient;
+    use crate::proxy::outbound::HandlerBuilder;
+
+    // Asks for a new transport on the first `failures` handshakes.
+    struct Retrying {
+        port: u16,
+        failures: usize,
+        calls: AtomicUsize,
+    }
+
+    #[async_trait]
+    impl OutboundStreamHandler for Retrying {
+        fn connect_addr(&self) -> OutboundConnect {
+            OutboundConnect::Proxy(Network::Tcp, "127.0.0.1".to_string(), self.port)
+        }
+
+        async fn handle<'a>(
+            &'a self,
+            _sess: &'a Session,
+            _lhs: Option<&mut AnyStream>,
+            stream: Option<AnyStream>,
+        ) -> io::Result<AnyStream> {
+            if self.calls.fetch_add(1, Ordering::Relaxed) < self.failures {
+                return Err(io::Error::other(RetryOnNewTransport("retry".to_string())));
+            }
+            stream.ok_or_else(|| io::Error::other("no stream"))
+        }
+    }
+
+    async fn run(failures: usize) -> (io::Result<AnyStream>, usize, usize) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let accepted = Arc::new(AtomicUsize::new(0));
+        let counter = accepted.clone();
+        tokio::spawn(async move {
+            let mut conns = Vec::new();
+            while let Ok((conn, _)) = listener.accept().await {
+                counter.fetch_add(1, Ordering::Relaxed);
+                conns.push(conn);
+            }
+        });
+        let actor = Arc::new(Retrying {
+            port,
+            failures,
+            calls: AtomicUsize::new(0),
+        });
+        let mut dns = crate::config::Dns::new();
+        dns.servers.push("1.1.1.1".to_string());
+        let dns_client = Arc::new(RwLock::new(
+            DnsClient::new(&MessageField::some(dns)).unwrap(),
+        ));
+        let handler = Handler {
+            actors: vec![HandlerBuilder::new().stream_handler(actor.clone()).build()],
+            dns_client: dns_client.clone(),
+        };
+        let sess = Session::default();
+        let stream = new_tcp_stream(dns_client, &"127.0.0.1".to_string(), &port)
+            .await
+            .unwrap();
+        let res = handler.handle(&sess, None, Some(stream)).await;
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        let calls = actor.calls.load(Ordering::Relaxed);
+        (res, calls, accepted.load(Ordering::Relaxed))
+    }
+
+    #[tokio::test]
+    async fn test_retry_on_new_transport() {
+        let (res, calls, dials) = run(1).await;
+        assert!(res.is_ok());
+        assert_eq!((calls, dials), (2, 2));
+
+        // Retried only once.
+        let (res, calls, dials) = run(2).await;
+        assert!(is_retry_on_new_transport(&res.err().unwrap()));
+        assert_eq!((calls, dials), (2, 2));
+    }
 }
@@ -221,9 +221,6 @@ impl OutboundDatagramSendHalf for DomainAssociatedOutboundDatagramSendHalf {
                         .map_err(|e| io::Error::other(format!("lookup {} failed: {}", domain, e)))
                         .await?
                 };
-                // FIXME Since FakeDns returns IPv4 address only, it's always bound
-                // to IPv4 address if FakeDns is used.
-                //
                 // If the socket was bound to an IPv4 address, we need an IPv4
                 // address for sending, and vice versa for IPv6.
                 let needs_ipv4 = self.1.is_ipv4();
@@ -2,6 +2,7 @@ use std::str::FromStr;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::{sync::Arc, time::Duration};
 
+use anyhow::anyhow;
 use bytes::BytesMut;
 use hickory_proto::{
     op::{header::MessageType, op_code::OpCode, query::Query, Message},
@@ -11,9 +12,13 @@ use rand::{rngs::StdRng, Rng, SeedableRng};
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tokio::sync::{Mutex, Notify};
 use tokio::time::{timeout, Instant};
-use tracing::{debug, trace, warn};
+use tracing::{debug, info, trace, warn};
 
-use crate::{app::SyncDnsClient, proxy::*, session::*};
+use crate::{
+    app::{stat_manager, SyncDnsClient},
+    proxy::*,
+    session::*,
+};
 
 pub mod datagram;
 pub mod stream;
@@ -21,6 +26,187 @@ pub mod stream;
 pub use datagram::Handler as DatagramHandler;
 pub use stream::Handler as StreamHandler;
 
+/// The target of a TCP health check, reached through the outbound checked.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum HealthCheckTarget {
+    /// An HTTP request, over TLS if `tls`, any response is a success.
+    Http {
+        tls: bool,
+        host: String,
+        port: u16,
+        path: String,
+    },
+    /// A connection, with a TLS handshake if `tls`.
+    Connect { tls: bool, host: String, port: u16 },
+}
+
+impl Default for HealthCheckTarget {
+    fn default() -> Self {
+        HealthCheckTarget::Http {
+            tls: true,
+            host: "www.google.com".to_string(),
+            port: 443,
+            path: "/".to_string(),
+        }
+    }
+}
+
+// Splits an authority into the host and the port, an IPv6 host is in
+// brackets.
+fn split_host_port(authority: &str) -> Option<(&str, Option<u16>)> {
+    let (host, port) = if let Some(rest) = authority.strip_prefix('[') {
+        let (host, rest) = rest.split_once(']')?;
+        match rest {
+            "" => (host, None),
+            _ => (host, Some(rest.strip_prefix(':')?)),
+        }
+    } else {
+        match authority.rsplit_once(':') {
+            Some((host, port)) => (host, Some(port)),
+            None => (authority, None),
+        }
+    };
+    if host.is_empty() {
+        return None;
+    }
+    match port {
+        Some(port) => Some((host, Some(port.parse().ok()?))),
+        None => Some((host, None)),
+    }
+}
+
+impl HealthCheckTarget {
+    /// Parses an http:// or https:// URL, or a tcp://host:port or
+    /// tls://host:port, a bare host:port is taken as tcp. An empty string is
+    /// the default target.
+    pub fn parse(s: &str) -> Option<Self> {
+        let s = s.trim();
+        if s.is_empty() {
+            return Some(Self::default());
+        }
+        let (scheme, rest) = s.split_once("://").unwrap_or(("tcp", s));
+        let (authority, path) = match rest.find(['/', '?']) {
+            Some(i) => (&rest[..i], &rest[i..]),
+            None => (rest, ""),
+        };
+        let (host, port) = split_host_port(authority)?;
+        let host = host.to_string();
+        match scheme.to_ascii_lowercase().as_str() {
+            scheme @ ("http" | "https") => {
+                let tls = scheme == "https";
+                let path = if path.starts_with('/') {
+                    path.to_string()
+                } else {
+                    format!("/{}", path)
+                };
+                Some(HealthCheckTarget::Http {
+                    tls,
+                    host,
+                    port: port.unwrap_or(if tls { 443 } else { 80 }),
+                    path,
+                })
+            }
+            scheme @ ("tcp" | "tls") if path.is_empty() => Some(HealthCheckTarget::Connect {
+                tls: scheme == "tls",
+                host,
+                port: port?,
+            }),
+            _ => None,
+        }
+    }
+
+    fn destination(&self) -> Option<SocksAddr> {
+        let (host, port) = match self {
+            HealthCheckTarget::Http { host, port, .. } => (host, *port),
+            HealthCheckTarget::Connect { host, port, .. } => (host, *port),
+        };
+        SocksAddr::try_from((host, port)).ok()
+    }
+
+    fn tls(&self) -> bool {
+        match self {
+            HealthCheckTarget::Http { tls, .. } => *tls,
+            HealthCheckTarget::Connect { tls, .. } => *tls,
+        }
+    }
+
+    fn request(&self) -> Option<String> {
+        let HealthCheckTarget::Http {
+            tls,
+            host,
+            port,
+            path,
+        } = self
+        else {
+            return None;
+        };
+        let host = if host.contains(':') {
+            format!("[{}]", host)
+        } else {
+            host.clone()
+        };
+        let host = if *port == if *tls { 443 } else { 80 } {
+            host
+        } else {
+            format!("{}:{}", host, port)
+        };
+        Some(format!(
+            "GET {} HTTP/1.1\r\nHost: {}\r\nConnection: close\r\n\r\n",
+            path, host
+        ))
+    }
+}
+
+// Whether an outbound is up, across health checks. It takes a number of
+// failed checks in a row to go down and of successful ones to come back, so
+// that a single timeout doesn't flap the group.
+#[derive(Debug)]
+pub(crate) struct Health {
+    up: bool,
+    failures: u32,
+    successes: u32,
+}
+
+impl Health {
+    pub(crate) fn new() -> Self {
+        Health {
+            up: true,
+            failures: 0,
+            successes: 0,
+        }
+    }
+
+    pub(crate) fn is_up(&self) -> bool {
+        self.up
+    }
+
+    // Takes the result of a check, returns whether the outbound went up or
+    // down.
+    pub(crate) fn update(
+        &mut self,
+        success: bool,
+        failures_before_down: u32,
+        successes_before_up: u32,
+    ) -> bool {
+        if success {
+            self.failures = 0;
+            self.successes = self.successes.saturating_add(1);
+            if !self.up && self.successes >= successes_before_up.max(1) {
+                self.up = true;
+                return true;
+            }
+        } else {
+            self.successes = 0;
+            self.failures = self.failures.saturating_add(1);
+            if self.up && self.failures >= failures_before_down.max(1) {
+                self.up = false;
+                return true;
+            }
+        }
+        false
+    }
+}
+
 #[derive(Debug, Eq, Ord, PartialEq, PartialOrd)]
 struct Measure {
     idx: usize,
@@ -41,88 +227,34 @@ async fn single_health_check(
     h: AnyOutboundHandler,
     dns_client: SyncDnsClient,
     delay: u32,
+    target: HealthCheckTarget,
 ) -> Measure {
     tokio::time::sleep(Duration::from_millis(
         StdRng::from_entropy().gen_range(0..=delay) as u64,
     ))
     .await;
 
-    let dest = match network {
-        Network::Tcp => SocksAddr::Domain("www.google.com".to_string(), 443),
-        Network::Udp => SocksAddr::Ip(SocketAddr::new(IpAddr::V4(Ipv4Addr::new(1, 1, 1, 1)), 53)),
-    };
-
-    let sess = Session {
-        destination: dest,
-        new_conn_once: true,
-        ..Default::default()
-    };
-
-    let start = Instant::now();
-
     match network {
-        Network::Tcp => {
-            let stream =
-                match crate::proxy::connect_stream_outbound(&sess, dns_client.clone(), &h).await {
-                    Ok(s) => s,
-                    Err(_) => return Measure::new(idx, u128::MAX, tag),
-                };
-            let m: Measure;
-
-            let Ok(h) = h.stream() else {
-                return Measure::new(idx, u128::MAX, tag);
-            };
-
-            // TODO Mock an LHS stream with the given payload.
-            match h.handle(&sess, None, stream).await {
-                Ok(stream) => {
-                    let Ok(tls_handler) = crate::proxy::tls::outbound::StreamHandler::new(
-                        String::from(""),
-                        vec![],
-                        None,
-                        None,
-                        false,
-                        false,
-                        false,
-                        None,
-                        dns_client.clone(),
-                    ) else {
-                        return Measure::new(idx, u128::MAX, tag);
-                    };
-
-                    let Ok(mut stream) = tls_handler.handle(&sess, None, Some(stream)).await else {
-                        return Measure::new(idx, u128::MAX - 1, tag);
-                    };
-
-                    if stream.write_all(b"GET / HTTP/1.1\r\n\r\n").await.is_err() {
-                        return Measure::new(idx, u128::MAX - 2, tag);
-                    }
-                    let mut buf = BytesMut::with_capacity(2 * 1024);
-                    match stream.read_buf(&mut buf).await {
-                        Ok(n) => {
-                            let elapsed = Instant::now().duration_since(start);
-                            debug!(
-                                "received {} bytes tcp health check response from {} in {} ms: {}",
-                                n,
-                                &tag,
-                                elapsed.as_millis(),
-                                String::from_utf8_lossy(&buf[..n.min(12)]),
-                            );
-                            m = Measure::new(idx, elapsed.as_millis(), tag);
-                        }
-                        Err(_) => {
-                            m = Measure::new(idx, u128::MAX - 3, tag);
-                        }
-                    }
-                    let _ = stream.shutdown().await;
-                }
-                Err(_) => {
-                    m = Measure::new(idx, u128::MAX, tag);
-                }
+        Network::Tcp => match tcp_check(&h, &target, dns_client).await {
+            Ok((elapsed, _)) => {
+                stat_manager::observe_health_check(&tag, network, elapsed);
+                Measure::new(idx, elapsed.as_millis(), tag)
             }
-            m
-        }
+            Err(e) => {
+                debug!("tcp health check of {} failed: {}", &tag, e);
+                Measure::new(idx, u128::MAX, tag)
+            }
+        },
         Network::Udp => {
+            let sess = Session {
+                destination: SocksAddr::Ip(SocketAddr::new(
+                    IpAddr::V4(Ipv4Addr::new(1, 1, 1, 1)),
+                    53,
+                )),
+                new_conn_once: true,
+                ..Default::default()
+            };
+            let start = Instant::now();
             let transport =
                 match crate::proxy::connect_datagram_outbound(&sess, dns_client, &h).await {
                     Ok(t) => t,
@@ -176,6 +308,7 @@ async fn single_health_check(
                                 &tag,
                                 elapsed.as_millis()
                             );
+                            stat_manager::observe_health_check(&tag, network, elapsed);
                             Measure::new(idx, elapsed.as_millis(), tag)
                         }
                         Err(_) => Measure::new(idx, u128::MAX - 3, tag),
@@ -187,6 +320,104 @@ async fn single_health_check(
     }
 }
 
+/// Runs a TCP health check of `target` through the outbound, the way the
+/// sessions go through it. Returns the latency, along with the actor a group
+/// outbound handed the check to.
+pub(crate) async fn tcp_check(
+    h: &AnyOutboundHandler,
+    target: &HealthCheckTarget,
+    dns_client: SyncDnsClient,
+) -> anyhow::Result<(Duration, Option<String>)> {
+    let sess = Session {
+        destination: target
+            .destination()
+            .ok_or_else(|| anyhow!("invalid target"))?,
+        new_conn_once: true,
+        ..Default::default()
+    };
+    let start = Instant::now();
+    let stream = crate::proxy::connect_stream_outbound(&sess, dns_client.clone(), h)
+        .await
+        .map_err(|e| anyhow!("connect failed: {}", e))?;
+    // TODO Mock an LHS stream with the given payload.
+    let stream = h
+        .stream()?
+        .handle(&sess, None, stream)
+        .await
+        .map_err(|e| anyhow!("handshake failed: {}", e))?;
+    let mut stream = if target.tls() {
+        let tls_handler = crate::proxy::tls::outbound::StreamHandler::new(
+            String::from(""),
+            vec![],
+            None,
+            None,
+            None,
+            None,
+            false,
+            false,
+            None,
+            Vec::new(),
+            false,
+            false,
+            false,
+            false,
+            None,
+            dns_client,
+        )?;
+        tls_handler
+            .handle(&sess, None, Some(stream))
+            .await
+            .map_err(|e| anyhow!("tls handshake failed: {}", e))?
+    } else {
+        stream
+    };
+    if let Some(req) = target.request() {
+        stream
+            .write_all(req.as_bytes())
+            .await
+            .map_err(|e| anyhow!("request failed: {}", e))?;
+        let mut buf = BytesMut::with_capacity(2 * 1024);
+        let n = stream
+            .read_buf(&mut buf)
+            .await
+            .map_err(|e| anyhow!("response failed: {}", e))?;
+        debug!(
+            "received {} bytes tcp health check response from {}: {}",
+            n,
+            h.tag(),
+            String::from_utf8_lossy(&buf[..n.min(12)]),
+        );
+    }
+    let elapsed = Instant::now().duration_since(start);
+    debug!(
+        "tcp health check of {} to {} succeeded, latency {} ms",
+        h.tag(),
+        &sess.destination,
+        elapsed.as_millis()
+    );
+    let _ = stream.shutdown().await;
+    let actor = sess.group_actor.lock().unwrap().clone();
+    Ok((elapsed, actor))
+}
+
+/// Measures a TCP health check of `target` through the outbound, none if it
+/// failed or took longer than `max`.
+pub(crate) async fn tcp_latency(
+    h: AnyOutboundHandler,
+    target: HealthCheckTarget,
+    dns_client: SyncDnsClient,
+    max: Duration,
+) -> Option<Duration> {
+    let tag = h.tag().to_owned();
+    let check = single_health_check(Network::Tcp, 0, tag, h, dns_client, 0, target);
+    let m = timeout(max, check).await.ok()?;
+    if m.rtt < max.as_millis() {
+        Some(Duration::from_millis(m.rtt as u64))
+    } else {
+        None
+    }
+}
+
 #[allow(clippy::too_many_arguments)]
 async fn health_check(
     network: Network,
@@ -198,6 +429,7 @@ async fn health_check(
     health_check_timeout: u64,
     health_check_attempts: u32,
     health_check_success_percentage: u32,
+    target: HealthCheckTarget,
 ) -> Measure {
     debug!("health checking [{}] ({}) index ({})", &tag, &network, idx);
     let health_check_timeout = Duration::from_secs(health_check_timeout);
@@ -213,6 +445,7 @@ async fn health_check(
                 h.clone(),
                 dns_client.clone(),
                 delay,
+                target.clone(),
             ),
         ));
     }
@@ -266,7 +499,11 @@ async fn health_check_task(
     wait_for_health_check: Option<Arc<Notify>>,
     health_check_attempts: u32,
     health_check_success_percentage: u32,
+    health_check_target: HealthCheckTarget,
+    failures_before_down: u32,
+    successes_before_up: u32,
 ) {
+    let mut health: Vec<Health> = actors.iter().map(|_| Health::new()).collect();
     loop {
         let last_active = Instant::now()
             .duration_since(*last_active.lock().await)
@@ -286,10 +523,37 @@ async fn health_check_task(
                     health_check_timeout as u64,
                     health_check_attempts,
                     health_check_success_percentage,
+                    health_check_target.clone(),
                 )));
             }
             let mut measures = futures::future::join_all(checks).await;
 
+            // An outbound which is still up after a failed check goes after
+            // the ones which succeeded, one which is down after all.
+            let threshold = Duration::from_secs(health_check_timeout as u64).as_millis();
+            for m in measures.iter_mut() {
+                let success = m.rtt < threshold;
+                let h = &mut health[m.idx];
+                if h.update(success, failures_before_down, successes_before_up) {
+                    if h.up {
+                        info!(
+                            "[{}] [{}] is up after {} successful health checks, latency {} ms",
+                            network, &m.tag, h.successes, m.rtt
+                        );
+                    } else {
+                        warn!(
+                            "[{}] [{}] is down after {} failed health checks",
+                            network, &m.tag, h.failures
+                        );
+                    }
+                }
+                if !h.up {
+                    m.rtt = u128::MAX;
+                } else if !success {
+                    m.rtt = threshold.saturating_sub(1);
+                }
+            }
+
             measures.sort_by(|a, b| a.rtt.cmp(&b.rtt));
 
             debug!("[{}] sorted health check results: {:?}", network, measures);
@@ -316,7 +580,7 @@ async fn health_check_task(
                 // preferring unavailable outbounds.
                 for m in measures.iter_mut() {
                     if is_preferred_actor(&m.tag, &health_check_prefers) {
-                        m.rtt -= min_prefer_actor_rtt;
+                        m.rtt = m.rtt.saturating_sub(min_prefer_actor_rtt);
                     }
                 }
 
@@ -387,3 +651,75 @@ async fn health_check_task(
         tokio::time::sleep(Duration::from_secs(check_interval as u64)).await;
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_health_check_target() {
+        assert_eq!(
+            HealthCheckTarget::parse("").unwrap(),
+            HealthCheckTarget::default()
+        );
+        let target = HealthCheckTarget::parse("http://example.com:8080/generate_204").unwrap();
+        assert_eq!(
+            target,
+            HealthCheckTarget::Http {
+                tls: false,
+                host: "example.com".to_string(),
+                port: 8080,
+                path: "/generate_204".to_string(),
+            }
+        );
+        assert_eq!(
+            target.request().unwrap(),
+            "GET /generate_204 HTTP/1.1\r\nHost: example.com:8080\r\nConnection: close\r\n\r\n"
+        );
+        let target = HealthCheckTarget::parse("https://[::1]?a=b").unwrap();
+        assert_eq!(
+            target.request().unwrap(),
+            "GET /?a=b HTTP/1.1\r\nHost: [::1]\r\nConnection: close\r\n\r\n"
+        );
+        assert_eq!(
+            target.destination(),
+            Some(SocksAddr::try_from(("::1", 443)).unwrap())
+        );
+        assert_eq!(
+            HealthCheckTarget::parse("1.1.1.1:53").unwrap(),
+            HealthCheckTarget::Connect {
+                tls: false,
+                host: "1.1.1.1".to_string(),
+                port: 53,
+            }
+        );
+        assert!(HealthCheckTarget::parse("tls://example.com:853")
+            .unwrap()
+            .tls());
+        assert!(HealthCheckTarget::parse("tcp://example.com").is_none());
+        assert!(HealthCheckTarget::parse("tcp://example.com:80/path").is_none());
+        assert!(HealthCheckTarget::parse("ftp://example.com:21").is_none());
+        assert!(HealthCheckTarget::parse("http://:80/").is_none());
+    }
+
+    #[test]
+    fn test_health_hysteresis() {
+        let mut h = Health::new();
+        assert!(!h.update(false, 3, 2));
+        assert!(!h.update(false, 3, 2));
+        assert!(!h.update(true, 3, 2));
+        assert!(h.up);
+        assert!(!h.update(false, 3, 2));
+        assert!(!h.update(false, 3, 2));
+        assert!(h.update(false, 3, 2));
+        assert!(!h.up);
+        assert!(!h.update(true, 3, 2));
+        assert!(!h.update(false, 3, 2));
+        assert!(!h.update(true, 3, 2));
+        assert!(h.update(true, 3, 2));
+        assert!(h.up);
+        // Without thresholds, every check counts.
+        assert!(h.update(false, 0, 0));
+        assert!(h.update(true, 0, 0));
+    }
+}
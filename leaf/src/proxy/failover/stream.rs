@@ -46,6 +46,9 @@ impl Handler {
         health_check_wait: bool,
         health_check_attempts: u32,
         health_check_success_percentage: u32,
+        health_check_target: super::HealthCheckTarget,
+        failures_before_down: u32,
+        successes_before_up: u32,
         dns_client: SyncDnsClient,
     ) -> (Self, Vec<AbortHandle>) {
         let mut abort_handles = Vec::new();
@@ -76,6 +79,9 @@ impl Handler {
                 notify.as_ref().cloned(),
                 health_check_attempts,
                 health_check_success_percentage,
+                health_check_target,
+                failures_before_down,
+                successes_before_up,
             ));
             abort_handles.push(abort_handle);
             let task: BoxFuture<'static, ()> = Box::pin(abortable.map(|_| ()));
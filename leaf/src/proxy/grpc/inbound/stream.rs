@@ -0,0 +1,311 @@
+use std::io;
+use std::pin::Pin;
+
+// The glob import of proxy brings in the http module, which would clash
+// with the crate.
+use ::h2::server::{self, SendResponse};
+use ::h2::RecvStream;
+use ::http::{header, HeaderMap, Method, Request, Response, StatusCode};
+use async_trait::async_trait;
+use bytes::Bytes;
+use futures::stream::Stream;
+use futures::{
+    ready,
+    task::{Context, Poll},
+};
+use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
+use tracing::{debug, trace};
+
+use crate::{
+    proxy::*,
+    session::{Session, StreamId},
+};
+
+use super::super::stream::{
+    h2_to_io_error, GrpcStream, INITIAL_CONNECTION_WINDOW_SIZE, INITIAL_WINDOW_SIZE,
+};
+
+const GRPC_STATUS_UNIMPLEMENTED: &str = "12";
+
+// A call of the Tun method, with the source the proxy in front forwarded.
+type Call = (u32, Option<IpAddr>, GrpcStream);
+
+fn forwarded_source(headers: &HeaderMap) -> Option<IpAddr> {
+    let forwarded = headers
+        .get(&*crate::option::HTTP_FORWARDED_HEADER)?
+        .to_str()
+        .ok()?;
+    forwarded
+        .split(',')
+        .map(str::trim)
+        .map_while(|x| x.parse::<IpAddr>().ok())
+        .last()
+}
+
+fn is_grpc(headers: &HeaderMap) -> bool {
+    headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|x| x.to_str().ok())
+        .map(|x| x.starts_with("application/grpc"))
+        .unwrap_or(false)
+}
+
+// Answers calls of other methods the way a gRPC server does, so that probes
+// see nothing but a server without the method.
+fn unimplemented(path: &str, service_name: &str) -> Response<()> {
+    let message = match path.trim_start_matches('/').split_once('/') {
+        Some((service, method)) if service == service_name => {
+            format!("unknown method {} for service {}", method, service)
+        }
+        Some((service, _)) => format!("unknown service {}", service),
+        None => format!("malformed method name: {:?}", path),
+    };
+    let builder = Response::builder()
+        .status(StatusCode::OK)
+        .header(header::CONTENT_TYPE, "application/grpc")
+        .header("grpc-status", GRPC_STATUS_UNIMPLEMENTED);
+    // The path may hold bytes not allowed in a header value.
+    match builder.header("grpc-message", message).body(()) {
+        Ok(resp) => resp,
+        Err(_) => Response::builder()
+            .status(StatusCode::OK)
+            .header(header::CONTENT_TYPE, "application/grpc")
+            .header("grpc-status", GRPC_STATUS_UNIMPLEMENTED)
+            .body(())
+            .unwrap(),
+    }
+}
+
+fn accept_call(
+    req: Request<RecvStream>,
+    mut respond: SendResponse<Bytes>,
+    service_name: &str,
+) -> Result<Option<Call>, ::h2::Error> {
+    if !is_grpc(req.headers()) {
+        let resp = Response::builder()
+            .status(StatusCode::UNSUPPORTED_MEDIA_TYPE)
+            .body(())
+            .unwrap();
+        respond.send_response(resp, true)?;
+        return Ok(None);
+    }
+    let path = req.uri().path();
+    if req.method() != Method::POST || path != format!("/{}/Tun", service_name) {
+        trace!("unimplemented grpc method {} {}", req.method(), path);
+        respond.send_response(unimplemented(path, service_name), true)?;
+        return Ok(None);
+    }
+    let forwarded_source = forwarded_source(req.headers());
+    let resp = Response::builder()
+        .status(StatusCode::OK)
+        .header(header::CONTENT_TYPE, "application/grpc")
+        .body(())
+        .unwrap();
+    let send = respond.send_response(resp, false)?;
+    let id = respond.stream_id().as_u32();
+    let stream = GrpcStream::new_server(send, req.into_body());
+    Ok(Some((id, forwarded_source, stream)))
+}
+
+// Accepts the calls on the connection until it's closed. The connection
+// makes progress only while it's polled, it's driven here rather than by
+// the consumer of the calls, which may stop polling for new ones.
+async fn serve(
+    mut conn: server::Connection<AnyStream, Bytes>,
+    service_name: String,
+    calls: UnboundedSender<Call>,
+) {
+    while let Some(res) = conn.accept().await {
+        let (req, respond) = match res {
+            Ok(x) => x,
+            Err(e) => {
+                debug!("accept grpc call failed: {}", e);
+                return;
+            }
+        };
+        match accept_call(req, respond, &service_name) {
+            Ok(Some(call)) => {
+                if calls.send(call).is_err() {
+                    return;
+                }
+            }
+            Ok(None) => (),
+            Err(e) => debug!("respond grpc call failed: {}", e),
+        }
+    }
+}
+
+pub struct Incoming {
+    sess: Session,
+    calls: UnboundedReceiver<Call>,
+}
+
+impl Stream for Incoming {
+    type Item = AnyBaseInboundTransport;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        Poll::Ready(
+            ready!(self.calls.poll_recv(cx)).map(|(id, forwarded_source, stream)| {
+                let mut sess = self.sess.clone();
+                sess.stream_id = Some(StreamId::U64(id as u64));
+                if forwarded_source.is_some() {
+                    sess.forwarded_source = forwarded_source;
+                }
+                AnyBaseInboundTransport::Stream(Box::new(stream), sess)
+            }),
+        )
+    }
+}
+
+pub struct Handler {
+    service_name: String,
+}
+
+impl Handler {
+    pub fn new(service_name: String) -> Self {
+        Self {
+            service_name: if service_name.is_empty() {
+                "GunService".to_string()
+            } else {
+                service_name
+            },
+        }
+    }
+}
+
+#[async_trait]
+impl InboundStreamHandler for Handler {
+    async fn handle<'a>(
+        &'a self,
+        sess: Session,
+        stream: AnyStream,
+    ) -> io::Result<AnyInboundTransport> {
+        tracing::trace!("handling inbound stream");
+        // Works over TLS as well as in cleartext (h2c), with prior knowledge,
+        // which is what a reverse proxy in front speaks.
+        let conn = server::Builder::new()
+            .initial_window_size(INITIAL_WINDOW_SIZE)
+            .initial_connection_window_size(INITIAL_CONNECTION_WINDOW_SIZE)
+            .handshake(stream)
+            .await
+            .map_err(h2_to_io_error)?;
+        let (calls_tx, calls_rx) = mpsc::unbounded_channel();
+        tokio::spawn(serve(conn, self.service_name.clone(), calls_tx));
+        Ok(InboundTransport::Incoming(Box::new(Incoming {
+            sess,
+            calls: calls_rx,
+        })))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ::h2::client::{self, SendRequest};
+    use ::h2::SendStream;
+    use futures::StreamExt;
+    use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+    use tokio::time::{timeout, Duration};
+
+    use super::*;
+
+    // Returns the client of a connection to a handler and the calls the
+    // handler accepts.
+    async fn connect(service_name: &str) -> (SendRequest<Bytes>, AnyInboundTransport) {
+        let (client_io, server_io) = tokio::io::duplex(64 * 1024);
+        let handler = Handler::new(service_name.to_string());
+        let server = tokio::spawn(async move {
+            handler
+                .handle(Session::default(), Box::new(server_io))
+                .await
+                .unwrap()
+        });
+        let (sender, conn) = client::handshake(client_io).await.unwrap();
+        tokio::spawn(async move {
+            let _ = conn.await;
+        });
+        (sender, server.await.unwrap())
+    }
+
+    async fn call(
+        sender: SendRequest<Bytes>,
+        path: &str,
+    ) -> (Response<RecvStream>, SendStream<Bytes>) {
+        let mut sender = sender.ready().await.unwrap();
+        let req = Request::builder()
+            .method(Method::POST)
+            .uri(format!("http://localhost{}", path))
+            .header(header::CONTENT_TYPE, "application/grpc")
+            .header(header::TE, "trailers")
+            .body(())
+            .unwrap();
+        let (response, send) = sender.send_request(req, false).unwrap();
+        (response.await.unwrap(), send)
+    }
+
+    fn data(len: usize, seed: u8) -> Vec<u8> {
+        (0..len).map(|i| (i as u8).wrapping_mul(seed)).collect()
+    }
+
+    // Writes `send` and reads until the peer shuts down, at the same time.
+    async fn transfer<S>(stream: S, send: Vec<u8>, expected: Vec<u8>)
+    where
+        S: AsyncRead + AsyncWrite,
+    {
+        let (mut r, mut w) = tokio::io::split(stream);
+        let write = async move {
+            w.write_all(&send).await.unwrap();
+            w.shutdown().await.unwrap();
+        };
+        let read = async move {
+            let mut buf = Vec::new();
+            r.read_to_end(&mut buf).await.unwrap();
+            assert!(buf == expected, "received data differs");
+        };
+        tokio::join!(write, read);
+    }
+
+    #[tokio::test]
+    async fn test_bidirectional_bulk_transfer() {
+        // Well beyond the flow control windows of both ends.
+        const LEN: usize = 12 * 1024 * 1024;
+
+        let (sender, transport) = connect("Test").await;
+        let InboundTransport::Incoming(mut incoming) = transport else {
+            panic!("not an incoming transport");
+        };
+        let server = tokio::spawn(async move {
+            let Some(AnyBaseInboundTransport::Stream(stream, sess)) = incoming.next().await else {
+                panic!("not a stream");
+            };
+            assert!(sess.stream_id.is_some());
+            transfer(stream, data(LEN, 7), data(LEN, 3)).await;
+        });
+
+        let (response, send) = call(sender, "/Test/Tun").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(response.headers().get("grpc-status").is_none());
+        let client = GrpcStream::new(send, response.into_body());
+
+        timeout(Duration::from_secs(60), async move {
+            transfer(client, data(LEN, 3), data(LEN, 7)).await;
+            server.await.unwrap();
+        })
+        .await
+        .expect("transfer stalled");
+    }
+
+    #[tokio::test]
+    async fn test_unimplemented_method() {
+        let (sender, _transport) = connect("Test").await;
+        for path in ["/Test/Other", "/Other/Tun"] {
+            let (response, _send) = call(sender.clone(), path).await;
+            assert_eq!(response.status(), StatusCode::OK);
+            assert_eq!(
+                response.headers().get("grpc-status").unwrap(),
+                GRPC_STATUS_UNIMPLEMENTED
+            );
+            assert!(response.headers().contains_key("grpc-message"));
+            assert!(response.body().is_end_stream());
+        }
+    }
+}
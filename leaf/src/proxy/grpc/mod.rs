@@ -0,0 +1,10 @@
+//! The gRPC transport of V2Ray and Xray ("gun"), a session is a call of the
+//! bidirectional streaming method `/{service_name}/Tun`, the data goes in
+//! `Hunk { bytes data = 1; }` messages.
+
+mod stream;
+
+#[cfg(feature = "inbound-grpc")]
+pub mod inbound;
+#[cfg(feature = "outbound-grpc")]
+pub mod outbound;
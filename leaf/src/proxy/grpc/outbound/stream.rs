@@ -0,0 +1,276 @@
+use std::convert::TryFrom;
+use std::io;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+// The glob import of proxy brings in the http module, which would clash
+// with the crate.
+use ::h2::client::{self, SendRequest};
+use ::h2::{RecvStream, SendStream};
+use ::http::{header, Method, Request, Response, StatusCode};
+use async_trait::async_trait;
+use bytes::Bytes;
+use tokio::sync::Mutex;
+use tokio::time::{timeout, Duration};
+use tracing::{debug, trace, Instrument};
+
+use crate::{
+    app::SyncDnsClient,
+    proxy::*,
+    session::{Session, SocksAddr},
+};
+
+use super::super::stream::{
+    h2_to_io_error, GrpcStream, INITIAL_CONNECTION_WINDOW_SIZE, INITIAL_WINDOW_SIZE,
+};
+
+// As grpc-go, which is what the servers expect to see.
+const DEFAULT_USER_AGENT: &str = "grpc-go/1.48.0";
+
+struct Connection {
+    sender: SendRequest<Bytes>,
+    // Set once the connection is gone or the server sent GOAWAY, the next
+    // session dials again.
+    closed: Arc<AtomicBool>,
+}
+
+// A stream fails for the connection rather than itself, it's worth a retry
+// on a new one.
+fn is_connection_error(e: &::h2::Error) -> bool {
+    e.is_go_away() || e.is_io()
+}
+
+// Starts an HTTP/2 connection over `stream`, the connection is driven by a
+// task until it's closed.
+async fn handshake(stream: AnyStream, peer: String) -> io::Result<Connection> {
+    let (sender, conn) = client::Builder::new()
+        .enable_push(false)
+        .initial_window_size(INITIAL_WINDOW_SIZE)
+        .initial_connection_window_size(INITIAL_CONNECTION_WINDOW_SIZE)
+        .handshake(stream)
+        .await
+        .map_err(h2_to_io_error)?;
+    let closed = Arc::new(AtomicBool::new(false));
+    let conn_closed = closed.clone();
+    tokio::spawn(async move {
+        if let Err(e) = conn.await {
+            debug!("grpc connection to {} failed: {}", peer, e);
+        }
+        conn_closed.store(true, Ordering::Relaxed);
+    });
+    Ok(Connection { sender, closed })
+}
+
+pub struct Handler {
+    service_name: String,
+    host: String,
+    // Without an address the handler runs over the stream of a chain, one
+    // connection for each session. With one, it dials the address through
+    // the actors and the sessions share the connection.
+    address: String,
+    port: u16,
+    actors: Vec<AnyOutboundHandler>,
+    dns_client: SyncDnsClient,
+    conn: Mutex<Option<Connection>>,
+}
+
+impl TcpConnector for Handler {}
+
+impl Handler {
+    pub fn new(
+        service_name: String,
+        host: String,
+        address: String,
+        port: u16,
+        actors: Vec<AnyOutboundHandler>,
+        dns_client: SyncDnsClient,
+    ) -> Self {
+        Self {
+            service_name: if service_name.is_empty() {
+                "GunService".to_string()
+            } else {
+                service_name
+            },
+            host,
+            address,
+            port,
+            actors,
+            dns_client,
+            conn: Mutex::new(None),
+        }
+    }
+
+    fn authority(&self, sess: &Session) -> String {
+        if !self.host.is_empty() {
+            self.host.clone()
+        } else if !self.address.is_empty() {
+            self.address.clone()
+        } else {
+            sess.destination.host()
+        }
+    }
+
+    async fn dial(&self, sess: &Session) -> io::Result<Connection> {
+        let mut stream = self
+            .new_tcp_stream(self.dns_client.clone(), &self.address, &self.port)
+            .await?;
+        // Pass the TCP stream through all sub-transports, e.g. TLS.
+        let mut sess = sess.clone();
+        sess.destination = SocksAddr::try_from((&self.address, self.port))?;
+        sess.dns_sniffed_domain = None;
+        sess.http_sniffed_domain = None;
+        sess.tls_sniffed_domain = None;
+        for a in self.actors.iter() {
+            stream = a.stream()?.handle(&sess, None, Some(stream)).await?;
+        }
+        let conn = handshake(stream, format!("{}:{}", &self.address, self.port)).await?;
+        trace!("grpc connected to {}:{}", &self.address, self.port);
+        Ok(conn)
+    }
+
+    async fn dial_with_timeout(&self, sess: &Session) -> io::Result<Connection> {
+        let dial_timeout = Duration::from_secs(*crate::option::OUTBOUND_DIAL_TIMEOUT);
+        match timeout(dial_timeout, self.dial(sess)).await {
+            Ok(res) => res,
+            Err(_) => Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                "dial grpc timed out",
+            )),
+        }
+    }
+
+    // Returns the sender of the shared connection, dialing it if there's
+    // none or it's closed. Only one task dials, the others wait for it.
+    async fn sender(&self, sess: &Session) -> io::Result<(SendRequest<Bytes>, Arc<AtomicBool>)> {
+        if sess.new_conn_once {
+            let c = self.dial_with_timeout(sess).await?;
+            return Ok((c.sender, c.closed));
+        }
+        let mut conn = self.conn.lock().await;
+        if let Some(c) = conn.as_ref() {
+            if !c.closed.load(Ordering::Relaxed) {
+                return Ok((c.sender.clone(), c.closed.clone()));
+            }
+        }
+        let c = self.dial_with_timeout(sess).await?;
+        let res = (c.sender.clone(), c.closed.clone());
+        conn.replace(c);
+        Ok(res)
+    }
+
+    fn request(&self, sess: &Session) -> io::Result<Request<()>> {
+        let user_agent = if crate::option::HTTP_USER_AGENT.is_empty() {
+            DEFAULT_USER_AGENT
+        } else {
+            crate::option::HTTP_USER_AGENT.as_str()
+        };
+        Request::builder()
+            .method(Method::POST)
+            .uri(format!(
+                "https://{}/{}/Tun",
+                self.authority(sess),
+                &self.service_name
+            ))
+            .header(header::CONTENT_TYPE, "application/grpc")
+            .header(header::TE, "trailers")
+            .header(header::USER_AGENT, user_agent)
+            .body(())
+            .map_err(|e| io::Error::other(format!("invalid grpc request: {}", e)))
+    }
+
+    async fn send_request(
+        sender: SendRequest<Bytes>,
+        req: Request<()>,
+    ) -> Result<(Response<RecvStream>, SendStream<Bytes>), ::h2::Error> {
+        let mut sender = sender.ready().await?;
+        let (response, send) = sender.send_request(req, false)?;
+        Ok((response.await?, send))
+    }
+
+    async fn call(
+        &self,
+        sender: SendRequest<Bytes>,
+        closed: Arc<AtomicBool>,
+        sess: &Session,
+    ) -> io::Result<GrpcStream> {
+        let req = self.request(sess)?;
+        let (response, send) = match Self::send_request(sender, req).await {
+            Ok(x) => x,
+            Err(e) => {
+                if is_connection_error(&e) {
+                    closed.store(true, Ordering::Relaxed);
+                }
+                return Err(h2_to_io_error(e));
+            }
+        };
+        if response.status() != StatusCode::OK {
+            return Err(io::Error::other(format!(
+                "grpc call failed with status {}",
+                response.status()
+            )));
+        }
+        // A response with the status in its headers ends the call at once.
+        if let Some(status) = response.headers().get("grpc-status") {
+            if status != "0" {
+                let message = response
+                    .headers()
+                    .get("grpc-message")
+                    .and_then(|v| v.to_str().ok())
+                    .unwrap_or_default();
+                return Err(io::Error::other(format!(
+                    "grpc call failed with grpc-status {}: {}",
+                    status.to_str().unwrap_or_default(),
+                    message
+                )));
+            }
+        }
+        Ok(GrpcStream::new(send, response.into_body()))
+    }
+
+    async fn connect(&self, sess: &Session) -> io::Result<GrpcStream> {
+        let mut retried = false;
+        loop {
+            let (sender, closed) = self.sender(sess).await?;
+            match self.call(sender, closed.clone(), sess).await {
+                // The connection went away, such as on a GOAWAY, the
+                // session goes on a new one.
+                Err(e) if closed.load(Ordering::Relaxed) && !retried => {
+                    debug!("grpc connection lost, reconnecting: {}", e);
+                    retried = true;
+                }
+                res => return res,
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl OutboundStreamHandler for Handler {
+    fn connect_addr(&self) -> OutboundConnect {
+        if self.address.is_empty() {
+            OutboundConnect::Next
+        } else {
+            OutboundConnect::Unknown
+        }
+    }
+
+    async fn handle<'a>(
+        &'a self,
+        sess: &'a Session,
+        _lhs: Option<&mut AnyStream>,
+        stream: Option<AnyStream>,
+    ) -> io::Result<AnyStream> {
+        tracing::trace!("handling outbound stream");
+        if self.address.is_empty() {
+            let stream = stream.ok_or_else(|| io::Error::other("invalid input"))?;
+            let conn = handshake(stream, sess.destination.to_string()).await?;
+            let stream = self.call(conn.sender, conn.closed, sess).await?;
+            return Ok(Box::new(stream));
+        }
+        let stream = self
+            .connect(sess)
+            .instrument(tracing::Span::current())
+            .await?;
+        Ok(Box::new(stream))
+    }
+}
@@ -0,0 +1,275 @@
+use std::io;
+use std::pin::Pin;
+
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use futures::ready;
+use futures::task::{Context, Poll};
+use h2::{Reason, RecvStream, SendStream};
+use http::{HeaderMap, HeaderValue};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+// The compression flag and the length of a gRPC message.
+const MESSAGE_HEADER_LEN: usize = 5;
+// The largest message accepted from the peer.
+const MAX_MESSAGE_LEN: usize = 1024 * 1024;
+// The largest payload of a message sent, a message carries it with the
+// message header, the tag and the length of the data field.
+const MAX_HUNK_LEN: usize = 16 * 1024;
+const MAX_HUNK_OVERHEAD: usize = MESSAGE_HEADER_LEN + 1 + 3;
+
+// Flow control windows of both ends, large enough to not hold back bulk
+// transfers on links with a high latency.
+pub const INITIAL_WINDOW_SIZE: u32 = 4 * 1024 * 1024;
+pub const INITIAL_CONNECTION_WINDOW_SIZE: u32 = 8 * 1024 * 1024;
+
+pub fn h2_to_io_error(e: h2::Error) -> io::Error {
+    if e.is_io() {
+        e.into_io().unwrap()
+    } else {
+        io::Error::other(e)
+    }
+}
+
+fn put_varint(buf: &mut BytesMut, mut v: u64) {
+    while v >= 0x80 {
+        buf.put_u8((v as u8) | 0x80);
+        v >>= 7;
+    }
+    buf.put_u8(v as u8);
+}
+
+fn get_varint(buf: &mut Bytes) -> io::Result<u64> {
+    let mut v = 0u64;
+    for shift in (0..64).step_by(7) {
+        if !buf.has_remaining() {
+            break;
+        }
+        let b = buf.get_u8();
+        v |= ((b & 0x7f) as u64) << shift;
+        if b & 0x80 == 0 {
+            return Ok(v);
+        }
+    }
+    Err(io::Error::other("invalid varint in grpc message"))
+}
+
+/// Wraps `data` in a gRPC message of a `Hunk`.
+pub fn encode_hunk(data: &[u8]) -> Bytes {
+    let mut field = BytesMut::with_capacity(data.len() + 4);
+    field.put_u8(0x0a); // field 1, length delimited
+    put_varint(&mut field, data.len() as u64);
+    field.put_slice(data);
+    let mut msg = BytesMut::with_capacity(MESSAGE_HEADER_LEN + field.len());
+    msg.put_u8(0); // not compressed
+    msg.put_u32(field.len() as u32);
+    msg.put_slice(&field);
+    msg.freeze()
+}
+
+// Returns the data of a `Hunk`. The data fields are joined, which takes the
+// `MultiHunk` of the multi mode as well.
+fn parse_hunk(mut msg: Bytes) -> io::Result<Bytes> {
+    let mut data = BytesMut::new();
+    while msg.has_remaining() {
+        let key = get_varint(&mut msg)?;
+        let n = match key & 0x7 {
+            0 => {
+                get_varint(&mut msg)?;
+                0
+            }
+            1 => 8,
+            2 => get_varint(&mut msg)? as usize,
+            5 => 4,
+            _ => return Err(io::Error::other("invalid wire type in grpc message")),
+        };
+        if n > msg.remaining() {
+            return Err(io::Error::other("truncated grpc message"));
+        }
+        let value = msg.split_to(n);
+        if key == 0x0a {
+            data.put_slice(&value);
+        }
+    }
+    Ok(data.freeze())
+}
+
+/// Takes the data of a complete message out of `buf`, none if the message
+/// is not complete yet.
+pub fn decode_hunk(buf: &mut BytesMut) -> io::Result<Option<Bytes>> {
+    if buf.len() < MESSAGE_HEADER_LEN {
+        return Ok(None);
+    }
+    if buf[0] != 0 {
+        return Err(io::Error::other("compressed grpc message not supported"));
+    }
+    let len = u32::from_be_bytes([buf[1], buf[2], buf[3], buf[4]]) as usize;
+    if len > MAX_MESSAGE_LEN {
+        return Err(io::Error::other(format!("grpc message too large: {}", len)));
+    }
+    if buf.len() < MESSAGE_HEADER_LEN + len {
+        buf.reserve(MESSAGE_HEADER_LEN + len - buf.len());
+        return Ok(None);
+    }
+    buf.advance(MESSAGE_HEADER_LEN);
+    parse_hunk(buf.split_to(len).freeze()).map(Some)
+}
+
+/// A call of the `Tun` method.
+pub struct GrpcStream {
+    send: SendStream<Bytes>,
+    recv: RecvStream,
+    // The received bytes not decoded yet.
+    recv_buf: BytesMut,
+    payload: Bytes,
+    // The server ends the call with the status in trailers, the client with
+    // an empty frame.
+    server: bool,
+    shutdown: bool,
+}
+
+impl GrpcStream {
+    pub fn new(send: SendStream<Bytes>, recv: RecvStream) -> Self {
+        Self {
+            send,
+            recv,
+            recv_buf: BytesMut::new(),
+            payload: Bytes::new(),
+            server: false,
+            shutdown: false,
+        }
+    }
+
+    #[cfg(feature = "inbound-grpc")]
+    pub fn new_server(send: SendStream<Bytes>, recv: RecvStream) -> Self {
+        Self {
+            server: true,
+            ..Self::new(send, recv)
+        }
+    }
+
+    fn reset_error(&mut self, cx: &mut Context) -> Poll<io::Error> {
+        Poll::Ready(match ready!(self.send.poll_reset(cx)) {
+            Ok(Reason::NO_ERROR) | Ok(Reason::CANCEL) | Ok(Reason::STREAM_CLOSED) => {
+                io::ErrorKind::BrokenPipe.into()
+            }
+            Ok(reason) => io::Error::other(format!("grpc stream reset: {}", reason)),
+            Err(e) => h2_to_io_error(e),
+        })
+    }
+}
+
+impl AsyncRead for GrpcStream {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context,
+        buf: &mut ReadBuf,
+    ) -> Poll<io::Result<()>> {
+        let me = self.get_mut();
+        loop {
+            if !me.payload.is_empty() {
+                let n = buf.remaining().min(me.payload.len());
+                buf.put_slice(&me.payload[..n]);
+                me.payload.advance(n);
+                return Poll::Ready(Ok(()));
+            }
+            if let Some(payload) = decode_hunk(&mut me.recv_buf)? {
+                me.payload = payload;
+                continue;
+            }
+            let data = match ready!(me.recv.poll_data(cx)) {
+                Some(Ok(data)) => data,
+                Some(Err(e)) if e.reason() == Some(Reason::NO_ERROR) => return Poll::Ready(Ok(())),
+                Some(Err(e)) => return Poll::Ready(Err(h2_to_io_error(e))),
+                None => return Poll::Ready(Ok(())),
+            };
+            let _ = me.recv.flow_control().release_capacity(data.len());
+            me.recv_buf.extend_from_slice(&data);
+        }
+    }
+}
+
+impl AsyncWrite for GrpcStream {
+    fn poll_write(self: Pin<&mut Self>, cx: &mut Context, buf: &[u8]) -> Poll<io::Result<usize>> {
+        let me = self.get_mut();
+        if buf.is_empty() {
+            return Poll::Ready(Ok(0));
+        }
+        let n = buf.len().min(MAX_HUNK_LEN);
+        me.send.reserve_capacity(n + MAX_HUNK_OVERHEAD);
+        let capacity = match ready!(me.send.poll_capacity(cx)) {
+            Some(Ok(capacity)) => capacity,
+            Some(Err(e)) => return Poll::Ready(Err(h2_to_io_error(e))),
+            None => 0,
+        };
+        if capacity == 0 {
+            let e = ready!(me.reset_error(cx));
+            return Poll::Ready(Err(e));
+        }
+        // The message may go a few bytes beyond the window, h2 holds them
+        // until it opens.
+        let n = n.min(capacity.saturating_sub(MAX_HUNK_OVERHEAD).max(1));
+        if let Err(e) = me.send.send_data(encode_hunk(&buf[..n]), false) {
+            return Poll::Ready(Err(h2_to_io_error(e)));
+        }
+        Poll::Ready(Ok(n))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, _cx: &mut Context) -> Poll<io::Result<()>> {
+        let me = self.get_mut();
+        if !me.shutdown {
+            me.shutdown = true;
+            let res = if me.server {
+                let mut trailers = HeaderMap::new();
+                trailers.insert("grpc-status", HeaderValue::from_static("0"));
+                me.send.send_trailers(trailers)
+            } else {
+                me.send.send_data(Bytes::new(), true)
+            };
+            if let Err(e) = res {
+                return Poll::Ready(Err(h2_to_io_error(e)));
+            }
+        }
+        Poll::Ready(Ok(()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hunk() {
+        let data = vec![7u8; 300];
+        let msg = encode_hunk(&data);
+        // The header, the tag and a 2 bytes length.
+        assert_eq!(&msg[..8], &[0, 0, 0, 0x01, 0x2f, 0x0a, 0xac, 0x02]);
+
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(&msg[..100]);
+        assert_eq!(decode_hunk(&mut buf).unwrap(), None);
+        buf.extend_from_slice(&msg[100..]);
+        buf.extend_from_slice(&encode_hunk(b"next"));
+        assert_eq!(decode_hunk(&mut buf).unwrap().unwrap(), data);
+        assert_eq!(decode_hunk(&mut buf).unwrap().unwrap(), &b"next"[..]);
+        assert!(buf.is_empty());
+    }
+
+    #[test]
+    fn test_parse_hunk_fields() {
+        // Unknown fields are skipped, repeated data fields are joined.
+        let msg = Bytes::from_static(&[0x10, 0x96, 0x01, 0x0a, 0x02, b'a', b'b', 0x0a, 0x01, b'c']);
+        assert_eq!(parse_hunk(msg).unwrap(), &b"abc"[..]);
+        let msg = Bytes::from_static(&[0x0a, 0x05, b'a']);
+        assert!(parse_hunk(msg).is_err());
+    }
+
+    #[test]
+    fn test_decode_hunk_compressed() {
+        let mut buf = BytesMut::from(&[1u8, 0, 0, 0, 0][..]);
+        assert!(decode_hunk(&mut buf).is_err());
+    }
+}
@@ -0,0 +1,7 @@
+//! HTTP/2 CONNECT, as spoken by naiveproxy and the forward proxy of Caddy,
+//! the sessions are streams of one HTTP/2 connection over TLS.
+
+mod padding;
+mod stream;
+
+pub mod outbound;
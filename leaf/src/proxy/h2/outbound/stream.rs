@@ -0,0 +1,206 @@
+use std::io;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+// The glob import of proxy brings in the h2 and http modules, which would
+// clash with the crates.
+use ::h2::client::{self, SendRequest};
+use ::h2::{RecvStream, SendStream};
+use ::http::{header, Method, Request, Response, StatusCode};
+use async_trait::async_trait;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use bytes::Bytes;
+use tokio::sync::Mutex;
+use tokio::time::{timeout, Duration};
+use tracing::{debug, trace, Instrument};
+
+use crate::{app::SyncDnsClient, proxy::*, session::Session};
+
+use super::super::padding;
+use super::super::stream::{h2_to_io_error, H2Stream};
+
+// As Chrome sets up its HTTP/2 connections.
+const HEADER_TABLE_SIZE: u32 = 64 * 1024;
+const INITIAL_WINDOW_SIZE: u32 = 6 * 1024 * 1024;
+const INITIAL_CONNECTION_WINDOW_SIZE: u32 = 15 * 1024 * 1024;
+const MAX_HEADER_LIST_SIZE: u32 = 256 * 1024;
+
+struct Connection {
+    sender: SendRequest<Bytes>,
+    // Set once the connection is gone or the server sent GOAWAY, the next
+    // session dials again.
+    closed: Arc<AtomicBool>,
+}
+
+// A stream fails for the connection rather than itself, it's worth a retry
+// on a new one.
+fn is_connection_error(e: &::h2::Error) -> bool {
+    e.is_go_away() || e.is_io()
+}
+
+pub struct Handler {
+    address: String,
+    port: u16,
+    authorization: Option<String>,
+    padding: bool,
+    tls: tls::outbound::StreamHandler,
+    dns_client: SyncDnsClient,
+    conn: Mutex<Option<Connection>>,
+}
+
+impl TcpConnector for Handler {}
+
+impl Handler {
+    pub fn new(
+        address: String,
+        port: u16,
+        username: Option<String>,
+        password: Option<String>,
+        padding: bool,
+        tls: tls::outbound::StreamHandler,
+        dns_client: SyncDnsClient,
+    ) -> Self {
+        let authorization = username.map(|username| {
+            format!(
+                "Basic {}",
+                STANDARD.encode(format!("{}:{}", username, password.unwrap_or_default()))
+            )
+        });
+        Self {
+            address,
+            port,
+            authorization,
+            padding,
+            tls,
+            dns_client,
+            conn: Mutex::new(None),
+        }
+    }
+
+    async fn dial(&self, sess: &Session) -> io::Result<Connection> {
+        let stream = self
+            .new_tcp_stream(self.dns_client.clone(), &self.address, &self.port)
+            .await?;
+        let stream = self.tls.handle(sess, None, Some(stream)).await?;
+        let (sender, conn) = client::Builder::new()
+            .header_table_size(HEADER_TABLE_SIZE)
+            .enable_push(false)
+            .initial_window_size(INITIAL_WINDOW_SIZE)
+            .initial_connection_window_size(INITIAL_CONNECTION_WINDOW_SIZE)
+            .max_header_list_size(MAX_HEADER_LIST_SIZE)
+            .handshake(stream)
+            .await
+            .map_err(h2_to_io_error)?;
+        let closed = Arc::new(AtomicBool::new(false));
+        let conn_closed = closed.clone();
+        let address = self.address.clone();
+        let port = self.port;
+        tokio::spawn(async move {
+            if let Err(e) = conn.await {
+                debug!("h2 connection to {}:{} failed: {}", address, port, e);
+            }
+            conn_closed.store(true, Ordering::Relaxed);
+        });
+        trace!("h2 connected to {}:{}", &self.address, self.port);
+        Ok(Connection { sender, closed })
+    }
+
+    // Returns the sender of the shared connection, dialing it if there's
+    // none or it's closed. Only one task dials, the others wait for it.
+    async fn sender(&self, sess: &Session) -> io::Result<(SendRequest<Bytes>, Arc<AtomicBool>)> {
+        let mut conn = self.conn.lock().await;
+        if let Some(c) = conn.as_ref() {
+            if !c.closed.load(Ordering::Relaxed) {
+                return Ok((c.sender.clone(), c.closed.clone()));
+            }
+        }
+        let dial_timeout = Duration::from_secs(*crate::option::OUTBOUND_DIAL_TIMEOUT);
+        let c = match timeout(dial_timeout, self.dial(sess)).await {
+            Ok(res) => res?,
+            Err(_) => return Err(io::Error::new(io::ErrorKind::TimedOut, "dial h2 timed out")),
+        };
+        let res = (c.sender.clone(), c.closed.clone());
+        conn.replace(c);
+        Ok(res)
+    }
+
+    async fn send_request(
+        &self,
+        sender: SendRequest<Bytes>,
+        sess: &Session,
+    ) -> Result<(Response<RecvStream>, SendStream<Bytes>), ::h2::Error> {
+        let mut builder = Request::builder()
+            .method(Method::CONNECT)
+            .uri(sess.destination.to_string());
+        if let Some(authorization) = &self.authorization {
+            builder = builder.header(header::PROXY_AUTHORIZATION, authorization);
+        }
+        if self.padding {
+            builder = builder.header("padding", padding::header_value());
+        }
+        let req = builder.body(()).expect("build connect request");
+        let mut sender = sender.ready().await?;
+        let (response, send) = sender.send_request(req, false)?;
+        Ok((response.await?, send))
+    }
+
+    async fn connect(&self, sess: &Session) -> io::Result<H2Stream> {
+        let mut retried = false;
+        let (response, send) = loop {
+            let (sender, closed) = self.sender(sess).await?;
+            match self.send_request(sender, sess).await {
+                Ok(x) => break x,
+                // The connection went away, such as on a GOAWAY, the
+                // session goes on a new one.
+                Err(e) if is_connection_error(&e) => {
+                    closed.store(true, Ordering::Relaxed);
+                    if retried {
+                        return Err(h2_to_io_error(e));
+                    }
+                    debug!("h2 connection lost, reconnecting: {}", e);
+                    retried = true;
+                }
+                Err(e) => return Err(h2_to_io_error(e)),
+            }
+        };
+        match response.status() {
+            StatusCode::OK => (),
+            StatusCode::PROXY_AUTHENTICATION_REQUIRED => {
+                return Err(io::Error::new(
+                    io::ErrorKind::PermissionDenied,
+                    "h2 proxy authentication failed",
+                ))
+            }
+            status => {
+                return Err(io::Error::other(format!(
+                    "h2 proxy failed to connect {}: {}",
+                    &sess.destination, status
+                )))
+            }
+        }
+        // Padding is on only if the server pads too.
+        let padding = self.padding && response.headers().contains_key("padding");
+        Ok(H2Stream::new(send, response.into_body(), padding))
+    }
+}
+
+#[async_trait]
+impl OutboundStreamHandler for Handler {
+    fn connect_addr(&self) -> OutboundConnect {
+        OutboundConnect::Unknown
+    }
+
+    async fn handle<'a>(
+        &'a self,
+        sess: &'a Session,
+        _lhs: Option<&mut AnyStream>,
+        _stream: Option<AnyStream>,
+    ) -> io::Result<AnyStream> {
+        tracing::trace!("handling outbound stream");
+        let stream = self
+            .connect(sess)
+            .instrument(tracing::Span::current())
+            .await?;
+        Ok(Box::new(stream))
+    }
+}
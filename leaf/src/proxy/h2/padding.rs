@@ -0,0 +1,143 @@
+//! The padding of naiveproxy, the first frames each way carry their length
+//! and some padding to blur the sizes of the TLS and HTTP handshakes tunneled
+//! in them.
+//!
+//! A frame is the payload length in 2 bytes, the padding length in 1 byte,
+//! the payload and then the padding.
+
+use rand::Rng;
+
+/// Both sides pad the first frames they send only.
+pub const PADDED_FRAMES: usize = 8;
+pub const MAX_PAYLOAD_LEN: usize = u16::MAX as usize;
+const HEADER_LEN: usize = 3;
+
+// Naiveproxy takes these, HPACK doesn't Huffman code them, so the length
+// of the header goes out as it is.
+const HEADER_VALUE_CHARS: &[u8] = b"!#$()+<>?@[]^`{}";
+
+/// A random value for the padding header, the header tells a naiveproxy
+/// server the client pads.
+pub fn header_value() -> String {
+    let mut rng = rand::thread_rng();
+    let len = rng.gen_range(16..32);
+    (0..len)
+        .map(|_| HEADER_VALUE_CHARS[rng.gen_range(0..HEADER_VALUE_CHARS.len())] as char)
+        .collect()
+}
+
+/// Wraps `payload`, no longer than `MAX_PAYLOAD_LEN`, in a padded frame.
+pub fn pad(payload: &[u8]) -> Vec<u8> {
+    let padding_len = rand::thread_rng().gen_range(0..=u8::MAX) as usize;
+    let mut frame = Vec::with_capacity(HEADER_LEN + payload.len() + padding_len);
+    frame.extend_from_slice(&(payload.len() as u16).to_be_bytes());
+    frame.push(padding_len as u8);
+    frame.extend_from_slice(payload);
+    frame.resize(frame.len() + padding_len, 0);
+    frame
+}
+
+/// Takes the payloads out of the padded frames the server sends first, the
+/// frames may be split across and share the data chunks.
+pub struct Unpadder {
+    frames_left: usize,
+    header: [u8; HEADER_LEN],
+    header_len: usize,
+    payload_left: usize,
+    padding_left: usize,
+}
+
+impl Default for Unpadder {
+    fn default() -> Self {
+        Self {
+            frames_left: PADDED_FRAMES,
+            header: [0; HEADER_LEN],
+            header_len: 0,
+            payload_left: 0,
+            padding_left: 0,
+        }
+    }
+}
+
+impl Unpadder {
+    pub fn is_done(&self) -> bool {
+        self.frames_left == 0
+    }
+
+    /// Appends the payload bytes of `data` to `out`.
+    pub fn unpad(&mut self, mut data: &[u8], out: &mut Vec<u8>) {
+        while !data.is_empty() && !self.is_done() {
+            if self.header_len < HEADER_LEN {
+                let n = (HEADER_LEN - self.header_len).min(data.len());
+                self.header[self.header_len..self.header_len + n].copy_from_slice(&data[..n]);
+                self.header_len += n;
+                data = &data[n..];
+                if self.header_len < HEADER_LEN {
+                    break;
+                }
+                self.payload_left = u16::from_be_bytes([self.header[0], self.header[1]]) as usize;
+                self.padding_left = self.header[2] as usize;
+            } else if self.payload_left > 0 {
+                let n = self.payload_left.min(data.len());
+                out.extend_from_slice(&data[..n]);
+                self.payload_left -= n;
+                data = &data[n..];
+            } else {
+                let n = self.padding_left.min(data.len());
+                self.padding_left -= n;
+                data = &data[n..];
+            }
+            if self.header_len == HEADER_LEN && self.payload_left == 0 && self.padding_left == 0 {
+                self.header_len = 0;
+                self.frames_left -= 1;
+            }
+        }
+        out.extend_from_slice(data);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_header_value() {
+        let value = header_value();
+        assert!((16..32).contains(&value.len()));
+        assert!(value.bytes().all(|c| HEADER_VALUE_CHARS.contains(&c)));
+    }
+
+    #[test]
+    fn test_unpad() {
+        let mut data = Vec::new();
+        for i in 0..PADDED_FRAMES {
+            data.extend(pad(&[i as u8; 10]));
+        }
+        data.extend_from_slice(b"plain");
+        let mut expected: Vec<u8> = (0..PADDED_FRAMES).flat_map(|i| [i as u8; 10]).collect();
+        expected.extend_from_slice(b"plain");
+
+        // At once and byte by byte.
+        let mut out = Vec::new();
+        Unpadder::default().unpad(&data, &mut out);
+        assert_eq!(out, expected);
+        let mut unpadder = Unpadder::default();
+        let mut out = Vec::new();
+        for b in &data {
+            unpadder.unpad(std::slice::from_ref(b), &mut out);
+        }
+        assert_eq!(out, expected);
+        assert!(unpadder.is_done());
+    }
+
+    #[test]
+    fn test_unpad_empty_frame() {
+        let mut data = vec![0, 0, 0];
+        data.extend(pad(b"data"));
+        let mut unpadder = Unpadder::default();
+        let mut out = Vec::new();
+        unpadder.unpad(&data, &mut out);
+        assert_eq!(out, b"data");
+        assert_eq!(unpadder.frames_left, PADDED_FRAMES - 2);
+    }
+}
@@ -0,0 +1,141 @@
+use std::io;
+use std::pin::Pin;
+
+use bytes::{Buf, Bytes};
+use futures::ready;
+use futures::task::{Context, Poll};
+use h2::{Reason, RecvStream, SendStream};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+use super::padding::{self, Unpadder};
+
+pub fn h2_to_io_error(e: h2::Error) -> io::Error {
+    if e.is_io() {
+        e.into_io().unwrap()
+    } else {
+        io::Error::other(e)
+    }
+}
+
+/// The tunnel of a CONNECT request.
+pub struct H2Stream {
+    send: SendStream<Bytes>,
+    recv: RecvStream,
+    recv_buf: Bytes,
+    // The frames left to pad, none unless the server pads too.
+    padded_writes: usize,
+    unpadder: Option<Unpadder>,
+    shutdown: bool,
+}
+
+impl H2Stream {
+    pub fn new(send: SendStream<Bytes>, recv: RecvStream, padding: bool) -> Self {
+        Self {
+            send,
+            recv,
+            recv_buf: Bytes::new(),
+            padded_writes: if padding { padding::PADDED_FRAMES } else { 0 },
+            unpadder: if padding {
+                Some(Unpadder::default())
+            } else {
+                None
+            },
+            shutdown: false,
+        }
+    }
+
+    fn reset_error(&mut self, cx: &mut Context) -> Poll<io::Error> {
+        Poll::Ready(match ready!(self.send.poll_reset(cx)) {
+            Ok(Reason::NO_ERROR) | Ok(Reason::CANCEL) | Ok(Reason::STREAM_CLOSED) => {
+                io::ErrorKind::BrokenPipe.into()
+            }
+            Ok(reason) => h2_to_io_error(reason.into()),
+            Err(e) => h2_to_io_error(e),
+        })
+    }
+}
+
+impl AsyncRead for H2Stream {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context,
+        buf: &mut ReadBuf,
+    ) -> Poll<io::Result<()>> {
+        let me = self.get_mut();
+        loop {
+            if !me.recv_buf.is_empty() {
+                let n = buf.remaining().min(me.recv_buf.len());
+                buf.put_slice(&me.recv_buf[..n]);
+                me.recv_buf.advance(n);
+                return Poll::Ready(Ok(()));
+            }
+            let data = match ready!(me.recv.poll_data(cx)) {
+                Some(Ok(data)) => data,
+                Some(Err(e)) if e.reason() == Some(Reason::NO_ERROR) => return Poll::Ready(Ok(())),
+                Some(Err(e)) => return Poll::Ready(Err(h2_to_io_error(e))),
+                None => return Poll::Ready(Ok(())),
+            };
+            // The data is consumed right away, the window opens as the
+            // reader goes.
+            let _ = me.recv.flow_control().release_capacity(data.len());
+            me.recv_buf = match me.unpadder.as_mut() {
+                Some(unpadder) => {
+                    let mut payload = Vec::with_capacity(data.len());
+                    unpadder.unpad(&data, &mut payload);
+                    if unpadder.is_done() {
+                        me.unpadder = None;
+                    }
+                    Bytes::from(payload)
+                }
+                None => data,
+            };
+        }
+    }
+}
+
+impl AsyncWrite for H2Stream {
+    fn poll_write(self: Pin<&mut Self>, cx: &mut Context, buf: &[u8]) -> Poll<io::Result<usize>> {
+        let me = self.get_mut();
+        if buf.is_empty() {
+            return Poll::Ready(Ok(0));
+        }
+        // Waits for the flow control window, so a slow server holds the
+        // writer back rather than the data piling up in the send buffer.
+        me.send.reserve_capacity(buf.len());
+        let mut n = match ready!(me.send.poll_capacity(cx)) {
+            Some(Ok(n)) => n.min(buf.len()),
+            Some(Err(e)) => return Poll::Ready(Err(h2_to_io_error(e))),
+            None => 0,
+        };
+        if n == 0 {
+            let e = ready!(me.reset_error(cx));
+            return Poll::Ready(Err(e));
+        }
+        let data = if me.padded_writes > 0 {
+            me.padded_writes -= 1;
+            n = n.min(padding::MAX_PAYLOAD_LEN);
+            Bytes::from(padding::pad(&buf[..n]))
+        } else {
+            Bytes::copy_from_slice(&buf[..n])
+        };
+        if let Err(e) = me.send.send_data(data, false) {
+            return Poll::Ready(Err(h2_to_io_error(e)));
+        }
+        Poll::Ready(Ok(n))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, _cx: &mut Context) -> Poll<io::Result<()>> {
+        let me = self.get_mut();
+        if !me.shutdown {
+            me.shutdown = true;
+            if let Err(e) = me.send.send_data(Bytes::new(), true) {
+                return Poll::Ready(Err(h2_to_io_error(e)));
+            }
+        }
+        Poll::Ready(Ok(()))
+    }
+}
@@ -1,4 +1,5 @@
 use std::cmp;
+use std::collections::HashMap;
 use std::convert::TryFrom;
 use std::io;
 use std::str;
@@ -7,6 +8,7 @@ use std::{net::IpAddr, pin::Pin, task::Context, task::Poll};
 use ::http::{Method, Uri};
 use anyhow::Result;
 use async_trait::async_trait;
+use base64::{engine::general_purpose::STANDARD, Engine};
 use bytes::BytesMut;
 use tokio::io::{AsyncReadExt, AsyncWriteExt, ReadBuf};
 
@@ -19,10 +21,21 @@ const BUFFER_SIZE: usize = 1024;
 const EOL: [u8; 2] = [13, 10];
 const EOH: [u8; 4] = [13, 10, 13, 10];
 
+const PROXY_AUTH_REQUIRED: &[u8] = b"HTTP/1.1 407 Proxy Authentication Required\r\nProxy-Authenticate: Basic realm=\"leaf\"\r\nContent-Length: 0\r\nConnection: close\r\n\r\n";
+
 fn bad_request() -> io::Error {
     io::Error::other("bad request")
 }
 
+// Compares without exiting at the first mismatch, so the time taken doesn't
+// tell how much of a guess is right.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
 fn split_slice_once(s: &[u8], sep: &[u8]) -> Option<(Vec<u8>, Vec<u8>)> {
     s.windows(sep.len())
         .position(|w| w == sep)
@@ -105,6 +118,30 @@ impl RequestHead {
         }
         self.headers.push((name, value));
     }
+
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(n, _v)| n.eq_ignore_ascii_case(name))
+            .map(|(_n, v)| v.as_str())
+    }
+
+    fn remove_header(&mut self, name: &str) {
+        self.headers.retain(|(n, _v)| !n.eq_ignore_ascii_case(name));
+    }
+
+    /// Returns the user name and password from a `Proxy-Authorization`
+    /// header with the Basic scheme.
+    fn basic_credentials(&self) -> Option<(String, String)> {
+        let (scheme, token) = self.header("proxy-authorization")?.split_once(' ')?;
+        if !scheme.eq_ignore_ascii_case("basic") {
+            return None;
+        }
+        let decoded = STANDARD.decode(token.trim()).ok()?;
+        let decoded = String::from_utf8(decoded).ok()?;
+        let (name, password) = decoded.split_once(':')?;
+        Some((name.to_string(), password.to_string()))
+    }
 }
 
 impl From<RequestHead> for Vec<u8> {
@@ -149,14 +186,19 @@ impl TryFrom<Vec<u8>> for RequestHead {
 struct HttpStream {
     cache: Vec<u8>,
     destination: Option<SocksAddr>,
+    user: Option<String>,
     origin: AnyStream,
 }
 
 impl HttpStream {
-    async fn sniff(&mut self) -> io::Result<()> {
+    async fn sniff(&mut self, users: &HashMap<String, String>) -> io::Result<()> {
         let (head_buf, mut rest_buf) = self.drain(&EOH).await?;
         let mut head = RequestHead::try_from(head_buf)?;
 
+        if !users.is_empty() {
+            self.authenticate(&head, users).await?;
+        }
+
         let addr = SocksAddr::try_from(&head.uri)?;
         self.destination = Some(addr.clone());
 
@@ -175,6 +217,13 @@ impl HttpStream {
                     .unwrap_or("/");
                 head.uri = path_and_query.parse().unwrap();
                 head.set_header("host".to_string(), addr.to_string());
+                head.remove_header("proxy-authorization");
+                if !users.is_empty() {
+                    // Later requests on this connection are relayed as they
+                    // are, have the client send them on a new connection so
+                    // that every request is authenticated.
+                    head.set_header("Connection".to_string(), "close".to_string());
+                }
                 self.cache.clear();
                 self.cache.append(&mut head.into());
                 self.cache.append(&mut rest_buf);
@@ -190,6 +239,28 @@ impl HttpStream {
         }
     }
 
+    // Checks the credentials of a request, the client is told to authenticate
+    // if they are missing or wrong.
+    async fn authenticate(
+        &mut self,
+        head: &RequestHead,
+        users: &HashMap<String, String>,
+    ) -> io::Result<()> {
+        if let Some((name, password)) = head.basic_credentials() {
+            if let Some(expected) = users.get(&name) {
+                if constant_time_eq(password.as_bytes(), expected.as_bytes()) {
+                    self.user = Some(name);
+                    return Ok(());
+                }
+            }
+        }
+        self.origin.write_all(PROXY_AUTH_REQUIRED).await?;
+        Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            "http proxy authentication failed",
+        ))
+    }
+
     async fn drain(&mut self, stop_sign: &[u8]) -> io::Result<(Vec<u8>, Vec<u8>)> {
         let mut data = Vec::new();
         let mut buf = BytesMut::with_capacity(BUFFER_SIZE);
@@ -239,7 +310,16 @@ impl AsyncWrite for HttpStream {
     }
 }
 
-pub struct Handler;
+pub struct Handler {
+    // Accounts by user name, authentication is required if not empty.
+    users: HashMap<String, String>,
+}
+
+impl Handler {
+    pub fn new(users: HashMap<String, String>) -> Self {
+        Self { users }
+    }
+}
 
 #[async_trait]
 impl InboundStreamHandler for Handler {
@@ -252,12 +332,100 @@ impl InboundStreamHandler for Handler {
         let mut http_stream = HttpStream {
             cache: Vec::new(),
             destination: None,
+            user: None,
             origin: stream,
         };
-        http_stream.sniff().await?;
+        http_stream.sniff(&self.users).await?;
 
         sess.destination = http_stream.destination.clone().ok_or(bad_request())?;
+        sess.user = http_stream.user.take();
 
         Ok(InboundTransport::Stream(Box::new(http_stream), sess))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn handler() -> Handler {
+        let mut users = HashMap::new();
+        users.insert("alice".to_string(), "secret".to_string());
+        Handler::new(users)
+    }
+
+    fn basic(credentials: &str) -> String {
+        format!(
+            "Proxy-Authorization: Basic {}\r\n",
+            STANDARD.encode(credentials)
+        )
+    }
+
+    #[tokio::test]
+    async fn test_http_inbound_connect_auth() {
+        let (mut c, s) = tokio::io::duplex(1024);
+        let req = format!(
+            "CONNECT example.com:443 HTTP/1.1\r\nHost: example.com:443\r\n{}\r\n",
+            basic("alice:secret")
+        );
+        c.write_all(req.as_bytes()).await.unwrap();
+        let transport = handler()
+            .handle(Session::default(), Box::new(s))
+            .await
+            .unwrap();
+        let mut reply = [0u8; 39];
+        c.read_exact(&mut reply).await.unwrap();
+        assert_eq!(&reply, b"HTTP/1.1 200 Connection established\r\n\r\n");
+        match transport {
+            InboundTransport::Stream(_, sess) => {
+                assert_eq!(sess.user.as_deref(), Some("alice"));
+                assert_eq!(sess.destination.to_string(), "example.com:443");
+            }
+            _ => panic!("unexpected transport"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_http_inbound_absolute_auth() {
+        let (mut c, s) = tokio::io::duplex(1024);
+        let req = format!(
+            "GET http://example.com/index.html HTTP/1.1\r\nHost: example.com\r\n{}\r\n",
+            basic("alice:secret")
+        );
+        c.write_all(req.as_bytes()).await.unwrap();
+        let transport = handler()
+            .handle(Session::default(), Box::new(s))
+            .await
+            .unwrap();
+        match transport {
+            InboundTransport::Stream(mut stream, sess) => {
+                assert_eq!(sess.user.as_deref(), Some("alice"));
+                let mut buf = vec![0u8; 1024];
+                let n = stream.read(&mut buf).await.unwrap();
+                let forwarded = str::from_utf8(&buf[..n]).unwrap();
+                assert!(forwarded.starts_with("GET /index.html HTTP/1.1\r\n"));
+                assert!(!forwarded.to_lowercase().contains("proxy-authorization"));
+                assert!(forwarded.contains("Connection: close\r\n"));
+            }
+            _ => panic!("unexpected transport"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_http_inbound_auth_rejected() {
+        for auth in ["".to_string(), basic("alice:wrong"), basic("bob:secret")] {
+            let (mut c, s) = tokio::io::duplex(1024);
+            let req = format!("CONNECT example.com:443 HTTP/1.1\r\n{}\r\n", auth);
+            c.write_all(req.as_bytes()).await.unwrap();
+            let err = handler()
+                .handle(Session::default(), Box::new(s))
+                .await
+                .err()
+                .unwrap();
+            assert_eq!(err.kind(), io::ErrorKind::PermissionDenied);
+            let mut reply = vec![0u8; PROXY_AUTH_REQUIRED.len()];
+            c.read_exact(&mut reply).await.unwrap();
+            assert_eq!(reply, PROXY_AUTH_REQUIRED);
+        }
+    }
+}
@@ -0,0 +1,187 @@
+use std::collections::HashMap;
+use std::io;
+
+use async_trait::async_trait;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWriteExt};
+
+use crate::{proxy::*, session::*};
+
+// Upper bound of the response head to the CONNECT request.
+const MAX_RESPONSE_HEAD: usize = 8192;
+
+pub struct Handler {
+    pub address: String,
+    pub port: u16,
+    pub username: String,
+    pub password: String,
+    // Additional headers sent with the CONNECT request.
+    pub headers: HashMap<String, String>,
+}
+
+impl Handler {
+    fn connect_request(&self, destination: &SocksAddr) -> String {
+        let mut req = format!("CONNECT {0} HTTP/1.1\r\nHost: {0}\r\n", destination);
+        if !self.username.is_empty() {
+            let credentials = format!("{}:{}", self.username, self.password);
+            req.push_str(&format!(
+                "Proxy-Authorization: Basic {}\r\n",
+                STANDARD.encode(credentials)
+            ));
+        }
+        for (name, value) in &self.headers {
+            req.push_str(&format!("{}: {}\r\n", name, value));
+        }
+        req.push_str("\r\n");
+        req
+    }
+}
+
+// Reads the response head byte by byte, anything after it belongs to the
+// tunnel and must be left on the stream.
+async fn read_response_head<R: AsyncRead + Unpin>(r: &mut R) -> io::Result<String> {
+    let mut head = Vec::new();
+    while !head.ends_with(b"\r\n\r\n") {
+        if head.len() >= MAX_RESPONSE_HEAD {
+            return Err(io::Error::other("http proxy response head too large"));
+        }
+        head.push(r.read_u8().await?);
+    }
+    String::from_utf8(head).map_err(|_| io::Error::other("invalid http proxy response"))
+}
+
+// Returns the status code of a response, the reason phrase is ignored.
+fn status_code(head: &str) -> io::Result<u16> {
+    let status_line = head.split("\r\n").next().unwrap_or("");
+    let mut parts = status_line.splitn(3, ' ');
+    let version = parts.next().unwrap_or("");
+    let code = parts.next().and_then(|code| code.parse().ok());
+    match code {
+        Some(code) if version.starts_with("HTTP/") => Ok(code),
+        _ => Err(io::Error::other(format!(
+            "invalid http proxy status line: {}",
+            status_line
+        ))),
+    }
+}
+
+#[async_trait]
+impl OutboundStreamHandler for Handler {
+    fn connect_addr(&self) -> OutboundConnect {
+        OutboundConnect::Proxy(Network::Tcp, self.address.clone(), self.port)
+    }
+
+    async fn handle<'a>(
+        &'a self,
+        sess: &'a Session,
+        _lhs: Option<&mut AnyStream>,
+        stream: Option<AnyStream>,
+    ) -> io::Result<AnyStream> {
+        tracing::trace!("handling outbound stream");
+        let mut stream = stream.ok_or_else(|| io::Error::other("invalid input"))?;
+        stream
+            .write_all(self.connect_request(&sess.destination).as_bytes())
+            .await?;
+        let head = read_response_head(&mut stream).await?;
+        match status_code(&head)? {
+            200..=299 => Ok(stream),
+            407 if self.username.is_empty() => Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                "http proxy requires authentication",
+            )),
+            407 => Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                "http proxy authentication failed",
+            )),
+            code => Err(io::Error::other(format!(
+                "http proxy CONNECT failed with status {}",
+                code
+            ))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn handler(username: &str, password: &str) -> Handler {
+        let mut headers = HashMap::new();
+        headers.insert("X-Secret".to_string(), "42".to_string());
+        Handler {
+            address: "127.0.0.1".to_string(),
+            port: 8080,
+            username: username.to_string(),
+            password: password.to_string(),
+            headers,
+        }
+    }
+
+    fn session() -> Session {
+        Session {
+            destination: SocksAddr::Domain("example.com".to_string(), 443),
+            ..Default::default()
+        }
+    }
+
+    // Reads a request head and replies with `response`.
+    async fn mock_proxy(mut s: tokio::io::DuplexStream, response: &'static [u8]) -> String {
+        let head = read_response_head(&mut s).await.unwrap();
+        s.write_all(response).await.unwrap();
+        head
+    }
+
+    #[tokio::test]
+    async fn test_http_outbound_connect() {
+        let (c, s) = tokio::io::duplex(1024);
+        let server = tokio::spawn(mock_proxy(
+            s,
+            b"HTTP/1.0 200 Tunnel ready to go\r\nVia: proxy\r\n\r\nhello",
+        ));
+        let mut stream = handler("alice", "secret")
+            .handle(&session(), None, Some(Box::new(c)))
+            .await
+            .unwrap();
+        let req = server.await.unwrap();
+        assert!(req.starts_with("CONNECT example.com:443 HTTP/1.1\r\nHost: example.com:443\r\n"));
+        assert!(req.contains("Proxy-Authorization: Basic YWxpY2U6c2VjcmV0\r\n"));
+        assert!(req.contains("X-Secret: 42\r\n"));
+        // Data following the response head is left for the tunnel.
+        let mut buf = [0u8; 5];
+        stream.read_exact(&mut buf).await.unwrap();
+        assert_eq!(&buf, b"hello");
+    }
+
+    #[tokio::test]
+    async fn test_http_outbound_auth_required() {
+        for (username, msg) in [
+            ("", "http proxy requires authentication"),
+            ("alice", "http proxy authentication failed"),
+        ] {
+            let (c, s) = tokio::io::duplex(1024);
+            tokio::spawn(mock_proxy(
+                s,
+                b"HTTP/1.1 407 Proxy Authentication Required\r\n\r\n",
+            ));
+            let err = handler(username, "wrong")
+                .handle(&session(), None, Some(Box::new(c)))
+                .await
+                .err()
+                .unwrap();
+            assert_eq!(err.kind(), io::ErrorKind::PermissionDenied);
+            assert_eq!(err.to_string(), msg);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_http_outbound_connect_failed() {
+        let (c, s) = tokio::io::duplex(1024);
+        tokio::spawn(mock_proxy(s, b"HTTP/1.1 502 Bad Gateway\r\n\r\n"));
+        let err = handler("", "")
+            .handle(&session(), None, Some(Box::new(c)))
+            .await
+            .err()
+            .unwrap();
+        assert_eq!(err.to_string(), "http proxy CONNECT failed with status 502");
+    }
+}
@@ -0,0 +1,300 @@
+use std::io;
+
+use async_trait::async_trait;
+use bytes::BytesMut;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::time::{timeout, Duration};
+use tracing::debug;
+
+use crate::{common::io::PrefixedStream, proxy::*, session::Session};
+
+// The largest request head accepted.
+const MAX_REQUEST_HEAD_LEN: usize = 8 * 1024;
+// The time a client has to send the request head, so that half-open
+// requests don't hold connections.
+const REQUEST_HEAD_TIMEOUT: u64 = 10;
+
+const SWITCHING_PROTOCOLS: &[u8] =
+    b"HTTP/1.1 101 Switching Protocols\r\nConnection: Upgrade\r\nUpgrade: websocket\r\n\r\n";
+const NOT_FOUND: &[u8] =
+    b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n";
+
+// Reads until the end of the request head, which may come in any number of
+// reads. Returns the head and the bytes read after it, which the client may
+// have sent without waiting for the response.
+async fn read_request_head(stream: &mut AnyStream) -> io::Result<(Vec<u8>, BytesMut)> {
+    let mut buf = BytesMut::with_capacity(1024);
+    loop {
+        // The terminator may span the previous read.
+        let start = buf.len().saturating_sub(3);
+        let n = stream.read_buf(&mut buf).await?;
+        if n == 0 {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        if let Some(pos) = buf[start..].windows(4).position(|x| x == b"\r\n\r\n") {
+            let end = start + pos + 4;
+            if end > MAX_REQUEST_HEAD_LEN {
+                break;
+            }
+            let head = buf.split_to(end).to_vec();
+            return Ok((head, buf));
+        }
+        if buf.len() >= MAX_REQUEST_HEAD_LEN {
+            break;
+        }
+    }
+    Err(io::Error::other("httpupgrade request head too large"))
+}
+
+struct Request<'a> {
+    path: &'a [u8],
+    upgrade: bool,
+    forwarded: Option<&'a [u8]>,
+}
+
+fn parse_request(head: &[u8]) -> Option<Request<'_>> {
+    let mut lines = head
+        .split(|x| *x == b'\n')
+        .map(|x| x.trim_ascii())
+        .take_while(|x| !x.is_empty());
+    let mut parts = lines.next()?.split(|x| *x == b' ');
+    if parts.next()? != b"GET" {
+        return None;
+    }
+    let target = parts.next()?;
+    if !parts.next()?.starts_with(b"HTTP/1.") {
+        return None;
+    }
+    let path = target.split(|x| *x == b'?').next().unwrap_or_default();
+    let mut upgrade = false;
+    let mut forwarded = None;
+    for line in lines {
+        let colon = line.iter().position(|x| *x == b':')?;
+        let name = &line[..colon];
+        let value = line[colon + 1..].trim_ascii();
+        if name.eq_ignore_ascii_case(b"upgrade") {
+            upgrade = value.eq_ignore_ascii_case(b"websocket");
+        } else if name.eq_ignore_ascii_case(crate::option::HTTP_FORWARDED_HEADER.as_bytes()) {
+            forwarded = Some(value);
+        }
+    }
+    Some(Request {
+        path,
+        upgrade,
+        forwarded,
+    })
+}
+
+fn forwarded_source(forwarded: &[u8]) -> Option<IpAddr> {
+    std::str::from_utf8(forwarded)
+        .ok()?
+        .split(',')
+        .map(str::trim)
+        .map_while(|x| x.parse::<IpAddr>().ok())
+        .last()
+}
+
+pub struct Handler {
+    path: String,
+}
+
+impl Handler {
+    pub fn new(path: String) -> Self {
+        Handler {
+            path: if path.starts_with('/') {
+                path
+            } else {
+                format!("/{}", path)
+            },
+        }
+    }
+}
+
+#[async_trait]
+impl InboundStreamHandler for Handler {
+    async fn handle<'a>(
+        &'a self,
+        mut sess: Session,
+        mut stream: AnyStream,
+    ) -> io::Result<AnyInboundTransport> {
+        tracing::trace!("handling inbound stream");
+        let (head, rest) = timeout(
+            Duration::from_secs(REQUEST_HEAD_TIMEOUT),
+            read_request_head(&mut stream),
+        )
+        .await
+        .map_err(|_| {
+            io::Error::new(
+                io::ErrorKind::TimedOut,
+                "read httpupgrade request timed out",
+            )
+        })??;
+        let req = match parse_request(&head) {
+            Some(req) if req.upgrade && req.path == self.path.as_bytes() => req,
+            _ => {
+                let _ = stream.write_all(NOT_FOUND).await;
+                return Err(io::Error::other("invalid httpupgrade request"));
+            }
+        };
+        if let Some(source) = req.forwarded.and_then(forwarded_source) {
+            sess.forwarded_source.replace(source);
+        }
+        stream.write_all(SWITCHING_PROTOCOLS).await?;
+        debug!("accepted httpupgrade stream");
+        Ok(InboundTransport::Stream(
+            Box::new(PrefixedStream::new(stream, rest)),
+            sess,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::io::{duplex, AsyncRead};
+
+    use super::*;
+
+    const UPGRADE: &[u8] = b"GET /up?ed=2048 HTTP/1.1\r\n\
+        Host: example.com\r\n\
+        Connection: Upgrade\r\n\
+        Upgrade: websocket\r\n\r\n";
+
+    async fn read_response_head<R: AsyncRead + Unpin>(r: &mut R) -> Vec<u8> {
+        let mut head = Vec::new();
+        while !head.ends_with(b"\r\n\r\n") {
+            head.push(r.read_u8().await.unwrap());
+        }
+        head
+    }
+
+    #[test]
+    fn test_parse_request() {
+        let req = parse_request(UPGRADE).unwrap();
+        assert_eq!(req.path, b"/up");
+        assert!(req.upgrade);
+        let req = parse_request(b"GET /up HTTP/1.1\r\nHost: example.com\r\n\r\n").unwrap();
+        assert!(!req.upgrade);
+        assert!(parse_request(b"POST /up HTTP/1.1\r\nUpgrade: websocket\r\n\r\n").is_none());
+        assert!(parse_request(b"GET /up\r\n\r\n").is_none());
+        assert!(parse_request(b"GET /up HTTP/1.1\r\nbroken\r\n\r\n").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_split_reads() {
+        let (client, server) = duplex(64 * 1024);
+        let (mut r, mut w) = tokio::io::split(client);
+        let writer = tokio::spawn(async move {
+            // The head, with the terminator split, and then the first bytes
+            // of the stream along with the end of the head.
+            for chunk in UPGRADE[..UPGRADE.len() - 2].chunks(3) {
+                w.write_all(chunk).await.unwrap();
+                tokio::task::yield_now().await;
+            }
+            w.write_all(b"\r\nhello").await.unwrap();
+            w
+        });
+        let handler = Handler::new("up".to_string());
+        let transport = handler
+            .handle(Session::default(), Box::new(server))
+            .await
+            .unwrap();
+        let InboundTransport::Stream(mut stream, _) = transport else {
+            panic!("not a stream");
+        };
+        let _w = writer.await.unwrap();
+        assert_eq!(read_response_head(&mut r).await, SWITCHING_PROTOCOLS);
+        let mut buf = [0u8; 5];
+        stream.read_exact(&mut buf).await.unwrap();
+        assert_eq!(&buf, b"hello");
+    }
+
+    #[tokio::test]
+    async fn test_oversized_head() {
+        let (mut client, server) = duplex(64 * 1024);
+        let handler = Handler::new("/up".to_string());
+        let res =
+            tokio::spawn(async move { handler.handle(Session::default(), Box::new(server)).await });
+        let mut req = b"GET /up HTTP/1.1\r\nUpgrade: websocket\r\n".to_vec();
+        for _ in 0..8 {
+            req.extend_from_slice(format!("X-Filler: {}\r\n", "a".repeat(1024)).as_bytes());
+        }
+        client.write_all(&req).await.unwrap();
+        assert!(res.await.unwrap().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_not_found() {
+        let (mut client, server) = duplex(64 * 1024);
+        client
+            .write_all(b"GET /other HTTP/1.1\r\nUpgrade: websocket\r\n\r\n")
+            .await
+            .unwrap();
+        let handler = Handler::new("/up".to_string());
+        assert!(handler
+            .handle(Session::default(), Box::new(server))
+            .await
+            .is_err());
+        assert!(read_response_head(&mut client)
+            .await
+            .starts_with(b"HTTP/1.1 404 "));
+    }
+
+    #[tokio::test]
+    async fn test_forwarded_source() {
+        let (mut client, server) = duplex(64 * 1024);
+        let header = &*crate::option::HTTP_FORWARDED_HEADER;
+        let req = format!(
+            "GET /up HTTP/1.1\r\nUpgrade: websocket\r\n{}: 10.0.0.1, 10.0.0.2\r\n\r\n",
+            header
+        );
+        client.write_all(req.as_bytes()).await.unwrap();
+        let handler = Handler::new("/up".to_string());
+        let transport = handler
+            .handle(Session::default(), Box::new(server))
+            .await
+            .unwrap();
+        let InboundTransport::Stream(_, sess) = transport else {
+            panic!("not a stream");
+        };
+        assert_eq!(sess.forwarded_source, Some("10.0.0.2".parse().unwrap()));
+    }
+
+    #[cfg(feature = "outbound-httpupgrade")]
+    #[tokio::test]
+    async fn test_round_trip() {
+        use std::collections::HashMap;
+
+        use super::super::super::outbound;
+
+        let (client, server) = duplex(64 * 1024);
+        let handler = Handler::new("/up".to_string());
+        let server = tokio::spawn(async move {
+            let transport = handler
+                .handle(Session::default(), Box::new(server))
+                .await
+                .unwrap();
+            let InboundTransport::Stream(mut stream, _) = transport else {
+                panic!("not a stream");
+            };
+            let mut buf = [0u8; 4];
+            stream.read_exact(&mut buf).await.unwrap();
+            assert_eq!(&buf, b"ping");
+            stream.write_all(b"pong").await.unwrap();
+            stream
+        });
+        let outbound = outbound::StreamHandler {
+            path: "/up".to_string(),
+            headers: HashMap::new(),
+        };
+        let sess = Session::default();
+        let mut stream = outbound
+            .handle(&sess, None, Some(Box::new(client)))
+            .await
+            .unwrap();
+        stream.write_all(b"ping").await.unwrap();
+        let mut buf = [0u8; 4];
+        stream.read_exact(&mut buf).await.unwrap();
+        assert_eq!(&buf, b"pong");
+        drop(server.await.unwrap());
+    }
+}
@@ -0,0 +1,8 @@
+//! The HTTPUpgrade transport, a WebSocket upgrade without the WebSocket
+//! framing. Once the server answers 101, the connection carries the raw
+//! bytes of the stream.
+
+#[cfg(feature = "inbound-httpupgrade")]
+pub mod inbound;
+#[cfg(feature = "outbound-httpupgrade")]
+pub mod outbound;
@@ -0,0 +1,213 @@
+use std::collections::HashMap;
+use std::io;
+
+use async_trait::async_trait;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWriteExt};
+
+use crate::{proxy::*, session::Session};
+
+// Upper bound of the response head to the upgrade request.
+const MAX_RESPONSE_HEAD: usize = 8192;
+
+pub struct Handler {
+    pub path: String,
+    // Additional headers sent with the upgrade request, a Host replaces the
+    // destination.
+    pub headers: HashMap<String, String>,
+}
+
+impl Handler {
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+
+    fn upgrade_request(&self, sess: &Session) -> io::Result<String> {
+        // A line break in the path or a header would end the head early.
+        let mut fields =
+            std::iter::once(&self.path).chain(self.headers.iter().flat_map(|(k, v)| [k, v]));
+        if fields.any(|x| x.contains(['\r', '\n'])) {
+            return Err(io::Error::other("invalid httpupgrade request"));
+        }
+        let path = if self.path.starts_with('/') {
+            self.path.clone()
+        } else {
+            format!("/{}", self.path)
+        };
+        let host = match self.header("host") {
+            Some(host) => host.to_string(),
+            None => sess.destination.host(),
+        };
+        let mut req = format!(
+            "GET {} HTTP/1.1\r\nHost: {}\r\nConnection: Upgrade\r\nUpgrade: websocket\r\n",
+            path, host
+        );
+        if self.header("user-agent").is_none() && !crate::option::HTTP_USER_AGENT.is_empty() {
+            req.push_str(&format!(
+                "User-Agent: {}\r\n",
+                &*crate::option::HTTP_USER_AGENT
+            ));
+        }
+        for (name, value) in &self.headers {
+            if name.eq_ignore_ascii_case("host") {
+                continue;
+            }
+            req.push_str(&format!("{}: {}\r\n", name, value));
+        }
+        req.push_str("\r\n");
+        Ok(req)
+    }
+}
+
+// Reads the response head byte by byte, anything after it belongs to the
+// stream and must be left on it.
+async fn read_response_head<R: AsyncRead + Unpin>(r: &mut R) -> io::Result<String> {
+    let mut head = Vec::new();
+    while !head.ends_with(b"\r\n\r\n") {
+        if head.len() >= MAX_RESPONSE_HEAD {
+            return Err(io::Error::other("httpupgrade response head too large"));
+        }
+        head.push(r.read_u8().await?);
+    }
+    String::from_utf8(head).map_err(|_| io::Error::other("invalid httpupgrade response"))
+}
+
+// Returns the status code of a response, the reason phrase is ignored.
+fn status_code(head: &str) -> io::Result<u16> {
+    let status_line = head.split("\r\n").next().unwrap_or("");
+    let mut parts = status_line.splitn(3, ' ');
+    let version = parts.next().unwrap_or("");
+    let code = parts.next().and_then(|code| code.parse().ok());
+    match code {
+        Some(code) if version.starts_with("HTTP/") => Ok(code),
+        _ => Err(io::Error::other(format!(
+            "invalid httpupgrade status line: {}",
+            status_line
+        ))),
+    }
+}
+
+#[async_trait]
+impl OutboundStreamHandler for Handler {
+    fn connect_addr(&self) -> OutboundConnect {
+        OutboundConnect::Next
+    }
+
+    async fn handle<'a>(
+        &'a self,
+        sess: &'a Session,
+        _lhs: Option<&mut AnyStream>,
+        stream: Option<AnyStream>,
+    ) -> io::Result<AnyStream> {
+        tracing::trace!("handling outbound stream");
+        let mut stream = stream.ok_or_else(|| io::Error::other("invalid input"))?;
+        stream
+            .write_all(self.upgrade_request(sess)?.as_bytes())
+            .await?;
+        let head = read_response_head(&mut stream).await?;
+        match status_code(&head)? {
+            101 => Ok(stream),
+            code => Err(io::Error::other(format!(
+                "httpupgrade failed with status {}",
+                code
+            ))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::session::SocksAddr;
+
+    use super::*;
+
+    fn session() -> Session {
+        Session {
+            destination: SocksAddr::try_from(("example.com", 443)).unwrap(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_upgrade_request() {
+        let mut handler = Handler {
+            path: "up".to_string(),
+            headers: HashMap::new(),
+        };
+        let req = handler.upgrade_request(&session()).unwrap();
+        assert!(req.starts_with(
+            "GET /up HTTP/1.1\r\nHost: example.com\r\nConnection: Upgrade\r\nUpgrade: websocket\r\n"
+        ));
+        assert!(req.ends_with("\r\n\r\n"));
+
+        handler
+            .headers
+            .insert("HOST".to_string(), "cdn.example.com".to_string());
+        handler
+            .headers
+            .insert("X-Key".to_string(), "s3cr3t".to_string());
+        let req = handler.upgrade_request(&session()).unwrap();
+        assert!(req.contains("\r\nHost: cdn.example.com\r\n"));
+        assert!(req.contains("\r\nX-Key: s3cr3t\r\n"));
+        assert_eq!(req.matches("ost: ").count(), 1);
+
+        handler
+            .headers
+            .insert("X-Key".to_string(), "a\r\nX-Injected: 1".to_string());
+        assert!(handler.upgrade_request(&session()).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_handle() {
+        let handler = Handler {
+            path: "/up".to_string(),
+            headers: HashMap::new(),
+        };
+        let (client, mut server) = tokio::io::duplex(1024);
+        let server = tokio::spawn(async move {
+            let mut head = Vec::new();
+            while !head.ends_with(b"\r\n\r\n") {
+                head.push(server.read_u8().await.unwrap());
+            }
+            // The first bytes of the stream come along with the response.
+            server
+                .write_all(b"HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\n\r\nhello")
+                .await
+                .unwrap();
+            server
+        });
+        let sess = session();
+        let mut stream = handler
+            .handle(&sess, None, Some(Box::new(client)))
+            .await
+            .unwrap();
+        let mut buf = [0u8; 5];
+        stream.read_exact(&mut buf).await.unwrap();
+        assert_eq!(&buf, b"hello");
+        drop(server.await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_handle_rejected() {
+        let handler = Handler {
+            path: "/up".to_string(),
+            headers: HashMap::new(),
+        };
+        let (client, mut server) = tokio::io::duplex(1024);
+        tokio::spawn(async move {
+            server
+                .write_all(b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\n\r\n")
+                .await
+                .unwrap();
+            let mut buf = Vec::new();
+            let _ = server.read_to_end(&mut buf).await;
+        });
+        let sess = session();
+        assert!(handler
+            .handle(&sess, None, Some(Box::new(client)))
+            .await
+            .is_err());
+    }
+}
@@ -0,0 +1,6 @@
+//! Hysteria 2, an HTTP/3 authenticated proxy protocol on QUIC.
+
+pub mod protocol;
+
+#[cfg(feature = "outbound-hysteria2")]
+pub mod outbound;
@@ -0,0 +1,343 @@
+//! The HTTP/3 request authenticating a Hysteria 2 connection.
+//!
+//! Only what the exchange needs is implemented: a control stream with empty
+//! settings, and QPACK field sections without the dynamic table or Huffman
+//! coding, which is what the reference server sends.
+
+use std::io;
+
+use anyhow::{anyhow, Result};
+use bytes::{BufMut, BytesMut};
+use tokio::io::AsyncReadExt;
+
+use super::super::protocol::{padding, put_varint, read_varint, AUTH_STATUS_OK};
+
+const STREAM_TYPE_CONTROL: u64 = 0x00;
+const FRAME_DATA: u64 = 0x00;
+const FRAME_HEADERS: u64 = 0x01;
+const FRAME_SETTINGS: u64 = 0x04;
+
+const MAX_HEADERS_LEN: u64 = 64 * 1024;
+
+// Indices of the QPACK static table.
+const STATIC_AUTHORITY: usize = 0;
+const STATIC_PATH: usize = 1;
+const STATIC_METHOD_POST: usize = 20;
+const STATIC_SCHEME_HTTPS: usize = 23;
+
+// The :status entries of the QPACK static table.
+const STATIC_STATUS: &[(usize, u16)] = &[
+    (24, 103),
+    (25, 200),
+    (26, 304),
+    (27, 404),
+    (28, 503),
+    (63, 100),
+    (64, 204),
+    (65, 206),
+    (66, 302),
+    (67, 400),
+    (68, 403),
+    (69, 421),
+    (70, 425),
+    (71, 500),
+];
+const STATIC_STATUS_NAME: usize = 24;
+
+/// What the server tells about itself in its response.
+#[derive(Debug, PartialEq, Eq)]
+pub struct AuthResponse {
+    pub udp: bool,
+    /// The server's receive rate in bytes per second, 0 if unlimited and
+    /// `None` if it asks the client to detect it.
+    pub rx: Option<u64>,
+}
+
+// Appends an integer with an N-bit prefix, the other bits of the first byte
+// being `flags`.
+fn put_prefixed_int(buf: &mut BytesMut, flags: u8, prefix_bits: u32, mut v: usize) {
+    let max = (1usize << prefix_bits) - 1;
+    if v < max {
+        buf.put_u8(flags | v as u8);
+        return;
+    }
+    buf.put_u8(flags | max as u8);
+    v -= max;
+    while v >= 0x80 {
+        buf.put_u8(0x80 | (v & 0x7f) as u8);
+        v >>= 7;
+    }
+    buf.put_u8(v as u8);
+}
+
+fn get_prefixed_int(buf: &mut &[u8], prefix_bits: u32) -> io::Result<usize> {
+    let truncated = || io::Error::other("truncated qpack integer");
+    let max = (1usize << prefix_bits) - 1;
+    let (first, rest) = buf.split_first().ok_or_else(truncated)?;
+    *buf = rest;
+    let mut v = (*first as usize) & max;
+    if v < max {
+        return Ok(v);
+    }
+    let mut shift = 0;
+    loop {
+        let (b, rest) = buf.split_first().ok_or_else(truncated)?;
+        *buf = rest;
+        if shift > 28 {
+            return Err(io::Error::other("qpack integer overflow"));
+        }
+        v += ((*b & 0x7f) as usize) << shift;
+        shift += 7;
+        if *b & 0x80 == 0 {
+            return Ok(v);
+        }
+    }
+}
+
+// Appends a string literal without Huffman coding, the length taking an
+// N-bit prefix.
+fn put_string(buf: &mut BytesMut, flags: u8, prefix_bits: u32, s: &[u8]) {
+    put_prefixed_int(buf, flags, prefix_bits, s.len());
+    buf.put_slice(s);
+}
+
+// Takes a string literal, returning `None` for Huffman coded ones.
+fn get_string<'a>(buf: &mut &'a [u8], prefix_bits: u32) -> io::Result<Option<&'a [u8]>> {
+    let huffman = buf.first().is_some_and(|b| b & (1 << prefix_bits) != 0);
+    let len = get_prefixed_int(buf, prefix_bits)?;
+    if buf.len() < len {
+        return Err(io::Error::other("truncated qpack string"));
+    }
+    let (s, rest) = buf.split_at(len);
+    *buf = rest;
+    Ok(if huffman { None } else { Some(s) })
+}
+
+fn encode_request(headers: &[(&str, &[u8])]) -> BytesMut {
+    // No dynamic table entries are referenced.
+    let mut section = BytesMut::from(&[0x00, 0x00][..]);
+    put_prefixed_int(&mut section, 0xc0, 6, STATIC_METHOD_POST);
+    put_prefixed_int(&mut section, 0xc0, 6, STATIC_SCHEME_HTTPS);
+    put_prefixed_int(&mut section, 0x50, 4, STATIC_AUTHORITY);
+    put_string(&mut section, 0x00, 7, b"hysteria");
+    put_prefixed_int(&mut section, 0x50, 4, STATIC_PATH);
+    put_string(&mut section, 0x00, 7, b"/auth");
+    for (name, value) in headers {
+        put_string(&mut section, 0x20, 3, name.as_bytes());
+        put_string(&mut section, 0x00, 7, value);
+    }
+    let mut frame = BytesMut::new();
+    put_varint(&mut frame, FRAME_HEADERS);
+    put_varint(&mut frame, section.len() as u64);
+    frame.put_slice(&section);
+    frame
+}
+
+/// Builds the HEADERS frame of the authentication request. `rx` is the
+/// client's receive rate in bytes per second, 0 for unknown.
+pub fn encode_auth_request(password: &str, rx: u64) -> BytesMut {
+    encode_request(&[
+        ("hysteria-auth", password.as_bytes()),
+        ("hysteria-cc-rx", rx.to_string().as_bytes()),
+        ("hysteria-padding", padding(64..512).as_slice()),
+    ])
+}
+
+/// Decodes the field section of the response, returning the status and the
+/// headers it could decode.
+fn decode_response(mut section: &[u8]) -> io::Result<(u16, Vec<(String, String)>)> {
+    let buf = &mut section;
+    let required_insert_count = get_prefixed_int(buf, 8)?;
+    let _delta_base = get_prefixed_int(buf, 7)?;
+    if required_insert_count != 0 {
+        return Err(io::Error::other(
+            "qpack dynamic table references are not supported",
+        ));
+    }
+    let mut status = None;
+    let mut headers = Vec::new();
+    while let Some(first) = buf.first().copied() {
+        if first & 0x80 != 0 {
+            // Indexed field line.
+            if first & 0x40 == 0 {
+                return Err(io::Error::other(
+                    "qpack dynamic table references are not supported",
+                ));
+            }
+            let index = get_prefixed_int(buf, 6)?;
+            if let Some((_, code)) = STATIC_STATUS.iter().find(|(i, _)| *i == index) {
+                status = Some(*code);
+            }
+        } else if first & 0x40 != 0 {
+            // Literal field line with a name reference.
+            if first & 0x10 == 0 {
+                return Err(io::Error::other(
+                    "qpack dynamic table references are not supported",
+                ));
+            }
+            let index = get_prefixed_int(buf, 4)?;
+            let value = get_string(buf, 7)?;
+            if index == STATIC_STATUS_NAME || STATIC_STATUS.iter().any(|(i, _)| *i == index) {
+                let value = value
+                    .and_then(|v| std::str::from_utf8(v).ok())
+                    .and_then(|v| v.parse().ok())
+                    .ok_or_else(|| io::Error::other("invalid :status"))?;
+                status = Some(value);
+            }
+        } else if first & 0x20 != 0 {
+            // Literal field line with a literal name.
+            let name = get_string(buf, 3)?;
+            let value = get_string(buf, 7)?;
+            if let (Some(name), Some(value)) = (name, value) {
+                headers.push((
+                    String::from_utf8_lossy(name).to_ascii_lowercase(),
+                    String::from_utf8_lossy(value).into_owned(),
+                ));
+            }
+        } else {
+            return Err(io::Error::other(
+                "qpack dynamic table references are not supported",
+            ));
+        }
+    }
+    let status = status.ok_or_else(|| io::Error::other("missing :status"))?;
+    Ok((status, headers))
+}
+
+fn parse_auth_response(section: &[u8]) -> Result<AuthResponse> {
+    let (status, headers) = decode_response(section)?;
+    if status != AUTH_STATUS_OK {
+        return Err(anyhow!("authentication failed, status {}", status));
+    }
+    let header = |name: &str| {
+        headers
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, v)| v.as_str())
+    };
+    let udp = header("hysteria-udp") == Some("true");
+    let rx = match header("hysteria-cc-rx") {
+        Some("auto") => None,
+        Some(rx) => Some(rx.parse().unwrap_or(0)),
+        None => Some(0),
+    };
+    Ok(AuthResponse { udp, rx })
+}
+
+/// Opens the HTTP/3 control stream, which has to stay open for the lifetime
+/// of the connection.
+pub async fn open_control_stream(conn: &quinn::Connection) -> Result<quinn::SendStream> {
+    let mut send = conn.open_uni().await?;
+    let mut buf = BytesMut::new();
+    put_varint(&mut buf, STREAM_TYPE_CONTROL);
+    put_varint(&mut buf, FRAME_SETTINGS);
+    put_varint(&mut buf, 0);
+    send.write_all(&buf).await?;
+    Ok(send)
+}
+
+/// Sends the authentication request and waits for the response.
+pub async fn authenticate(
+    conn: &quinn::Connection,
+    password: &str,
+    rx: u64,
+) -> Result<AuthResponse> {
+    let (mut send, mut recv) = conn.open_bi().await?;
+    send.write_all(&encode_auth_request(password, rx)).await?;
+    send.finish()?;
+    loop {
+        let frame_type = read_varint(&mut recv).await?;
+        let len = read_varint(&mut recv).await?;
+        if frame_type == FRAME_HEADERS {
+            if len > MAX_HEADERS_LEN {
+                return Err(anyhow!("auth response headers too large"));
+            }
+            let mut section = vec![0u8; len as usize];
+            recv.read_exact(&mut section).await?;
+            return parse_auth_response(&section);
+        }
+        if frame_type == FRAME_DATA {
+            return Err(anyhow!("unexpected data before auth response headers"));
+        }
+        // Skips unknown and reserved frames.
+        tokio::io::copy(&mut (&mut recv).take(len), &mut tokio::io::sink()).await?;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::super::protocol::get_varint;
+    use super::*;
+
+    #[test]
+    fn test_prefixed_int() {
+        // The examples of RFC 7541, C.1.
+        let mut buf = BytesMut::new();
+        put_prefixed_int(&mut buf, 0, 5, 10);
+        assert_eq!(&buf[..], &[0x0a]);
+        let mut buf = BytesMut::new();
+        put_prefixed_int(&mut buf, 0, 5, 1337);
+        assert_eq!(&buf[..], &[0x1f, 0x9a, 0x0a]);
+        assert_eq!(get_prefixed_int(&mut &buf[..], 5).unwrap(), 1337);
+        assert!(get_prefixed_int(&mut &[0x1f, 0x9a][..], 5).is_err());
+    }
+
+    #[test]
+    fn test_encode_auth_request() {
+        let frame = encode_auth_request("secret", 1_250_000);
+        let mut rest = &frame[..];
+        assert_eq!(get_varint(&mut rest).unwrap(), FRAME_HEADERS);
+        let len = get_varint(&mut rest).unwrap();
+        assert_eq!(len as usize, rest.len());
+        let section = rest;
+        assert_eq!(&section[..4], &[0x00, 0x00, 0xd4, 0xd7]);
+        assert_eq!(&section[4..14], b"\x50\x08hysteria");
+        assert_eq!(&section[14..21], b"\x51\x05/auth");
+        // hysteria-auth takes the 3-bit prefix and a continuation byte.
+        assert_eq!(&section[21..23], &[0x27, 13 - 7]);
+        assert_eq!(&section[23..36], b"hysteria-auth");
+        assert_eq!(&section[36..43], b"\x06secret");
+    }
+
+    fn response(status: &[u8], headers: &[(&str, &str)]) -> BytesMut {
+        let mut section = BytesMut::from(&[0x00, 0x00][..]);
+        section.put_slice(status);
+        for (name, value) in headers {
+            put_string(&mut section, 0x20, 3, name.as_bytes());
+            put_string(&mut section, 0x00, 7, value.as_bytes());
+        }
+        section
+    }
+
+    #[test]
+    fn test_parse_auth_response() {
+        let status = b"\x5f\x09\x03233";
+        let section = response(
+            status,
+            &[("Hysteria-UDP", "true"), ("Hysteria-CC-RX", "auto")],
+        );
+        assert_eq!(
+            parse_auth_response(&section).unwrap(),
+            AuthResponse {
+                udp: true,
+                rx: None
+            }
+        );
+        let section = response(status, &[("hysteria-cc-rx", "12500000")]);
+        assert_eq!(
+            parse_auth_response(&section).unwrap(),
+            AuthResponse {
+                udp: false,
+                rx: Some(12_500_000)
+            }
+        );
+        // 404 from the static table.
+        let section = response(&[0xdb], &[]);
+        assert!(parse_auth_response(&section)
+            .unwrap_err()
+            .to_string()
+            .contains("404"));
+        // A dynamic table reference.
+        assert!(decode_response(&[0x01, 0x00, 0x80]).is_err());
+    }
+}
@@ -0,0 +1,241 @@
+//! Congestion control of Hysteria 2 connections.
+//!
+//! A connection starts out on BBR. Once the server has told its receive rate
+//! during authentication, and a send rate is configured, it switches to
+//! Brutal. Brutal doesn't back off on loss, it keeps the window at what
+//! sending at the fixed rate takes, grown by the share of packets lost so
+//! that the rate holds after the losses.
+
+use std::any::Any;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use quinn::congestion::{BbrConfig, Controller, ControllerFactory};
+use quinn::RttEstimator;
+
+const SLOT_COUNT: usize = 5;
+const MIN_SAMPLE_PACKETS: u64 = 50;
+const MIN_ACK_RATE: f64 = 0.8;
+const WINDOW_MULTIPLIER: f64 = 2.0;
+const INITIAL_RTT: Duration = Duration::from_millis(333);
+
+#[derive(Clone, Copy, Default)]
+struct Slot {
+    second: u64,
+    acked: u64,
+    lost: u64,
+}
+
+#[derive(Clone)]
+struct Brutal {
+    epoch: Instant,
+    slots: [Slot; SLOT_COUNT],
+    ack_rate: f64,
+    rtt: Duration,
+    mtu: u16,
+}
+
+impl Brutal {
+    fn new(now: Instant, mtu: u16) -> Self {
+        Self {
+            epoch: now,
+            slots: [Slot::default(); SLOT_COUNT],
+            ack_rate: 1.0,
+            rtt: INITIAL_RTT,
+            mtu,
+        }
+    }
+
+    fn slot(&mut self, now: Instant) -> &mut Slot {
+        let second = now.saturating_duration_since(self.epoch).as_secs();
+        let slot = &mut self.slots[second as usize % SLOT_COUNT];
+        if slot.second != second {
+            *slot = Slot {
+                second,
+                ..Default::default()
+            };
+        }
+        slot
+    }
+
+    // The ack rate of the last few seconds, in packets.
+    fn update_ack_rate(&mut self, now: Instant) {
+        let second = now.saturating_duration_since(self.epoch).as_secs();
+        let (acked, lost) = self
+            .slots
+            .iter()
+            .filter(|slot| second.saturating_sub(slot.second) < SLOT_COUNT as u64)
+            .fold((0, 0), |(acked, lost), slot| {
+                (acked + slot.acked, lost + slot.lost)
+            });
+        self.ack_rate = if acked + lost < MIN_SAMPLE_PACKETS {
+            1.0
+        } else {
+            (acked as f64 / (acked + lost) as f64).max(MIN_ACK_RATE)
+        };
+    }
+
+    fn on_ack(&mut self, now: Instant, rtt: Duration) {
+        self.slot(now).acked += 1;
+        self.rtt = rtt;
+        self.update_ack_rate(now);
+    }
+
+    fn on_loss(&mut self, now: Instant, lost_bytes: u64) {
+        let lost = lost_bytes.div_ceil(self.mtu.max(1) as u64).max(1);
+        self.slot(now).lost += lost;
+        self.update_ack_rate(now);
+    }
+
+    fn window(&self, bps: u64) -> u64 {
+        let window = bps as f64 * self.rtt.as_secs_f64() * WINDOW_MULTIPLIER / self.ack_rate;
+        (window as u64).max(self.mtu as u64)
+    }
+}
+
+/// Builds the controllers of a connection, the send rate in bytes per second
+/// is shared with them and 0 keeps them on BBR.
+pub struct CongestionFactory {
+    bps: Arc<AtomicU64>,
+    bbr: Arc<BbrConfig>,
+}
+
+impl CongestionFactory {
+    pub fn new(bps: Arc<AtomicU64>) -> Self {
+        Self {
+            bps,
+            bbr: Arc::new(BbrConfig::default()),
+        }
+    }
+}
+
+impl ControllerFactory for CongestionFactory {
+    fn build(self: Arc<Self>, now: Instant, current_mtu: u16) -> Box<dyn Controller> {
+        Box::new(Congestion {
+            bps: self.bps.clone(),
+            bbr: self.bbr.clone().build(now, current_mtu),
+            brutal: Brutal::new(now, current_mtu),
+        })
+    }
+}
+
+// Both controllers see every event so that either is up to date when it's
+// picked.
+struct Congestion {
+    bps: Arc<AtomicU64>,
+    bbr: Box<dyn Controller>,
+    brutal: Brutal,
+}
+
+impl Controller for Congestion {
+    fn on_sent(&mut self, now: Instant, bytes: u64, last_packet_number: u64) {
+        self.bbr.on_sent(now, bytes, last_packet_number);
+    }
+
+    fn on_ack(
+        &mut self,
+        now: Instant,
+        sent: Instant,
+        bytes: u64,
+        app_limited: bool,
+        rtt: &RttEstimator,
+    ) {
+        self.bbr.on_ack(now, sent, bytes, app_limited, rtt);
+        self.brutal.on_ack(now, rtt.get());
+    }
+
+    fn on_end_acks(
+        &mut self,
+        now: Instant,
+        in_flight: u64,
+        app_limited: bool,
+        largest_packet_num_acked: Option<u64>,
+    ) {
+        self.bbr
+            .on_end_acks(now, in_flight, app_limited, largest_packet_num_acked);
+    }
+
+    fn on_congestion_event(
+        &mut self,
+        now: Instant,
+        sent: Instant,
+        is_persistent_congestion: bool,
+        lost_bytes: u64,
+    ) {
+        self.bbr
+            .on_congestion_event(now, sent, is_persistent_congestion, lost_bytes);
+        self.brutal.on_loss(now, lost_bytes);
+    }
+
+    fn on_mtu_update(&mut self, new_mtu: u16) {
+        self.bbr.on_mtu_update(new_mtu);
+        self.brutal.mtu = new_mtu;
+    }
+
+    fn window(&self) -> u64 {
+        match self.bps.load(Ordering::Relaxed) {
+            0 => self.bbr.window(),
+            bps => self.brutal.window(bps),
+        }
+    }
+
+    fn clone_box(&self) -> Box<dyn Controller> {
+        Box::new(Congestion {
+            bps: self.bps.clone(),
+            bbr: self.bbr.clone_box(),
+            brutal: self.brutal.clone(),
+        })
+    }
+
+    fn initial_window(&self) -> u64 {
+        self.bbr.initial_window()
+    }
+
+    fn into_any(self: Box<Self>) -> Box<dyn Any> {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_brutal_window() {
+        let now = Instant::now();
+        let mut brutal = Brutal::new(now, 1200);
+        for _ in 0..100 {
+            brutal.on_ack(now, Duration::from_millis(100));
+        }
+        // 1 MB/s over a 100 ms round trip, doubled.
+        assert_eq!(brutal.window(1_000_000), 200_000);
+
+        // A tenth of the packets lost grows the window by as much.
+        brutal.on_loss(now, 12_000 - 600);
+        assert!((brutal.ack_rate - 100.0 / 110.0).abs() < 1e-9);
+        assert_eq!(brutal.window(1_000_000), 220_000);
+
+        // Heavy loss is only made up for up to the minimum ack rate.
+        brutal.on_loss(now, 1_200_000);
+        assert_eq!(brutal.ack_rate, MIN_ACK_RATE);
+
+        // Old samples fall out of the window.
+        let later = now + Duration::from_secs(SLOT_COUNT as u64 + 1);
+        brutal.on_ack(later, Duration::from_millis(100));
+        assert_eq!(brutal.ack_rate, 1.0);
+        assert_eq!(brutal.window(1), 1200);
+    }
+
+    #[test]
+    fn test_switch_to_brutal() {
+        let bps = Arc::new(AtomicU64::new(0));
+        let factory = Arc::new(CongestionFactory::new(bps.clone()));
+        let controller = factory.build(Instant::now(), 1200);
+        let bbr_window = controller.window();
+        bps.store(10_000_000, Ordering::Relaxed);
+        // 10 MB/s over the initial round trip estimate, doubled.
+        assert_eq!(controller.window(), 6_660_000);
+        assert_ne!(controller.window(), bbr_window);
+    }
+}
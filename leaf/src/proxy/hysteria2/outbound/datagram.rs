@@ -0,0 +1,180 @@
+use std::collections::HashMap;
+use std::io;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use tokio::sync::mpsc::{channel, Receiver, Sender};
+use tracing::{debug, trace};
+
+use crate::common::udp_session::{Defragger, SessionMap};
+use crate::{proxy::*, session::SocksAddr};
+
+use super::super::protocol::{parse_address, UdpMessage};
+
+type Packet = (SocksAddr, Bytes);
+
+// UDP sessions relayed over a connection, keyed by session id.
+pub(super) type Sessions = SessionMap<u32, Sender<Packet>>;
+
+// Reassembles the UDP messages received on the connection and dispatches them
+// to their sessions.
+pub(super) fn spawn_demux(conn: quinn::Connection, sessions: Arc<Sessions>) {
+    tokio::spawn(async move {
+        let mut defraggers: HashMap<u32, Defragger<UdpMessage>> = HashMap::new();
+        loop {
+            let data = match conn.read_datagram().await {
+                Ok(data) => data,
+                Err(e) => {
+                    debug!("hysteria2 connection closed: {}", e);
+                    break;
+                }
+            };
+            let msg = match UdpMessage::decode(data) {
+                Ok(msg) => msg,
+                Err(e) => {
+                    debug!("invalid hysteria2 udp message: {}", e);
+                    continue;
+                }
+            };
+            let session_id = msg.session_id;
+            let packet_tx = sessions.lock().get(&session_id).cloned();
+            let Some(packet_tx) = packet_tx else {
+                trace!("unknown hysteria2 udp session {}", session_id);
+                defraggers.remove(&session_id);
+                continue;
+            };
+            let Some(msg) = defraggers.entry(session_id).or_default().feed(msg) else {
+                continue;
+            };
+            let addr = match parse_address(&msg.address) {
+                Ok(addr) => addr,
+                Err(e) => {
+                    debug!("invalid hysteria2 udp message: {}", e);
+                    continue;
+                }
+            };
+            if packet_tx.try_send((addr, msg.payload)).is_err() {
+                trace!(
+                    "hysteria2 udp session {} is full, dropping packet",
+                    session_id
+                );
+            }
+        }
+    });
+}
+
+// Unregisters the session once both halves are dropped.
+struct SessionGuard {
+    session_id: u32,
+    sessions: Arc<Sessions>,
+}
+
+impl Drop for SessionGuard {
+    fn drop(&mut self) {
+        self.sessions.remove(&self.session_id);
+    }
+}
+
+pub struct Datagram {
+    conn: quinn::Connection,
+    packet_rx: Receiver<Packet>,
+    guard: Arc<SessionGuard>,
+}
+
+impl Datagram {
+    pub(super) fn new(conn: quinn::Connection, sessions: Arc<Sessions>) -> io::Result<Self> {
+        let (packet_tx, packet_rx) = channel(*crate::option::UDP_DOWNLINK_CHANNEL_SIZE);
+        let session_id = sessions.register(packet_tx)?;
+        Ok(Self {
+            conn,
+            packet_rx,
+            guard: Arc::new(SessionGuard {
+                session_id,
+                sessions,
+            }),
+        })
+    }
+}
+
+impl OutboundDatagram for Datagram {
+    fn split(
+        self: Box<Self>,
+    ) -> (
+        Box<dyn OutboundDatagramRecvHalf>,
+        Box<dyn OutboundDatagramSendHalf>,
+    ) {
+        (
+            Box::new(DatagramRecvHalf {
+                packet_rx: self.packet_rx,
+                _guard: self.guard.clone(),
+            }),
+            Box::new(DatagramSendHalf {
+                conn: self.conn,
+                next_packet_id: 1,
+                guard: self.guard,
+            }),
+        )
+    }
+}
+
+struct DatagramRecvHalf {
+    packet_rx: Receiver<Packet>,
+    _guard: Arc<SessionGuard>,
+}
+
+#[async_trait]
+impl OutboundDatagramRecvHalf for DatagramRecvHalf {
+    async fn recv_from(&mut self, buf: &mut [u8]) -> io::Result<(usize, SocksAddr)> {
+        let (src_addr, payload) = self
+            .packet_rx
+            .recv()
+            .await
+            .ok_or_else(|| io::Error::other("hysteria2 connection closed"))?;
+        if buf.len() < payload.len() {
+            return Err(io::Error::other("Small buffer"));
+        }
+        buf[..payload.len()].copy_from_slice(&payload);
+        Ok((payload.len(), src_addr))
+    }
+}
+
+struct DatagramSendHalf {
+    conn: quinn::Connection,
+    next_packet_id: u16,
+    guard: Arc<SessionGuard>,
+}
+
+#[async_trait]
+impl OutboundDatagramSendHalf for DatagramSendHalf {
+    async fn send_to(&mut self, buf: &[u8], dst_addr: &SocksAddr) -> io::Result<usize> {
+        let max_size = self
+            .conn
+            .max_datagram_size()
+            .ok_or_else(|| io::Error::other("hysteria2 server doesn't accept datagrams"))?;
+        let msg = UdpMessage {
+            session_id: self.guard.session_id,
+            packet_id: 0,
+            fragment_id: 0,
+            fragment_count: 1,
+            address: dst_addr.to_string(),
+            payload: Bytes::copy_from_slice(buf),
+        };
+        let fragments = msg
+            .fragment(self.next_packet_id, max_size)
+            .ok_or_else(|| io::Error::other("udp packet too large"))?;
+        if fragments.len() > 1 {
+            self.next_packet_id = self.next_packet_id.wrapping_add(1).max(1);
+        }
+        for fragment in fragments {
+            self.conn
+                .send_datagram(fragment.encode())
+                .map_err(io::Error::other)?;
+        }
+        Ok(buf.len())
+    }
+
+    async fn close(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
@@ -0,0 +1,6 @@
+mod auth;
+mod congestion;
+mod datagram;
+mod stream;
+
+pub use stream::Handler;
@@ -0,0 +1,356 @@
+use std::io;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use futures::task::{Context, Poll};
+use rustls::crypto::CryptoProvider;
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::sync::Mutex;
+use tokio::time::{timeout, Duration};
+use tracing::{debug, trace, Instrument};
+
+use crate::{app::SyncDnsClient, common::cert::InsecureVerifier, proxy::*, session::Session};
+
+use super::super::protocol::{encode_tcp_request, read_tcp_response};
+use super::auth::{authenticate, open_control_stream};
+use super::congestion::CongestionFactory;
+use super::datagram::{self, Datagram, Sessions};
+
+// A TCP proxy stream.
+struct Hysteria2Stream {
+    send: quinn::SendStream,
+    recv: quinn::RecvStream,
+}
+
+impl AsyncRead for Hysteria2Stream {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context,
+        buf: &mut ReadBuf,
+    ) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.recv).poll_read(cx, buf)
+    }
+}
+
+impl AsyncWrite for Hysteria2Stream {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.send).poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.send).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.send).poll_shutdown(cx)
+    }
+}
+
+// The send rate once the server has told its receive rate, 0 for BBR. A
+// server detecting the rate itself gets BBR too.
+fn send_rate(up_bps: u64, server_rx: Option<u64>) -> u64 {
+    match server_rx {
+        None => 0,
+        Some(rx) if rx == 0 || rx > up_bps => up_bps,
+        Some(rx) => rx,
+    }
+}
+
+// An authenticated connection, all streams and UDP sessions share it.
+struct Connection {
+    conn: quinn::Connection,
+    udp: bool,
+    sessions: Arc<Sessions>,
+    // Closing the HTTP/3 control stream would be a connection error.
+    _control: quinn::SendStream,
+}
+
+struct Manager {
+    address: String,
+    port: u16,
+    server_name: Option<String>,
+    password: String,
+    up_bps: u64,
+    down_bps: u64,
+    crypto: Arc<quinn::crypto::rustls::QuicClientConfig>,
+    dns_client: SyncDnsClient,
+    connection: Mutex<Option<Arc<Connection>>>,
+}
+
+impl Manager {
+    #[allow(clippy::too_many_arguments)]
+    fn new(
+        address: String,
+        port: u16,
+        password: String,
+        up_mbps: u32,
+        down_mbps: u32,
+        server_name: Option<String>,
+        insecure: bool,
+        dns_client: SyncDnsClient,
+    ) -> Result<Self> {
+        #[cfg(feature = "rustls-tls-aws-lc")]
+        let provider: Arc<CryptoProvider> = rustls::crypto::aws_lc_rs::default_provider().into();
+        #[cfg(not(feature = "rustls-tls-aws-lc"))]
+        let provider: Arc<CryptoProvider> = rustls::crypto::ring::default_provider().into();
+
+        let builder = rustls::ClientConfig::builder_with_provider(provider.clone())
+            .with_protocol_versions(&[&rustls::version::TLS13])?;
+        let mut crypto = if insecure {
+            builder
+                .dangerous()
+                .with_custom_certificate_verifier(Arc::new(InsecureVerifier(provider)))
+                .with_no_client_auth()
+        } else {
+            let mut roots = rustls::RootCertStore::empty();
+            roots.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned());
+            builder.with_root_certificates(roots).with_no_client_auth()
+        };
+        crypto.alpn_protocols = vec![b"h3".to_vec()];
+        if let Some(key_log) = crate::common::key_log::key_log() {
+            crypto.key_log = key_log;
+        }
+
+        Ok(Manager {
+            address,
+            port,
+            server_name,
+            password,
+            up_bps: up_mbps as u64 * 125_000,
+            down_bps: down_mbps as u64 * 125_000,
+            crypto: Arc::new(quinn::crypto::rustls::QuicClientConfig::try_from(crypto)?),
+            dns_client,
+            connection: Mutex::new(None),
+        })
+    }
+
+    fn client_config(&self, bps: Arc<AtomicU64>) -> quinn::ClientConfig {
+        let mut client_config = quinn::ClientConfig::new(self.crypto.clone());
+        let mut transport_config = quinn::TransportConfig::default();
+        transport_config.stream_receive_window(quinn::VarInt::from_u32(8 * 1024 * 1024));
+        transport_config.receive_window(quinn::VarInt::from_u32(20 * 1024 * 1024));
+        transport_config.max_idle_timeout(Some(quinn::IdleTimeout::from(quinn::VarInt::from_u32(
+            30_000,
+        ))));
+        transport_config.keep_alive_interval(Some(Duration::from_secs(10)));
+        transport_config.congestion_controller_factory(Arc::new(CongestionFactory::new(bps)));
+        client_config.transport_config(Arc::new(transport_config));
+        client_config
+    }
+
+    async fn connect(&self, dial_timeout: Duration) -> Result<Connection> {
+        let socket = self
+            .new_udp_socket(&crate::option::UNSPECIFIED_BIND_ADDR)
+            .instrument(tracing::Span::current())
+            .await?;
+        let mut endpoint = quinn::Endpoint::new(
+            quinn::EndpointConfig::default(),
+            None,
+            socket.into_std()?,
+            Arc::new(quinn::TokioRuntime),
+        )?;
+        let bps = Arc::new(AtomicU64::new(0));
+        endpoint.set_default_client_config(self.client_config(bps.clone()));
+        let ips = {
+            self.dns_client
+                .read()
+                .await
+                .direct_lookup(&self.address)
+                .map_err(|e| io::Error::other(format!("lookup {} failed: {}", &self.address, e)))
+                .instrument(tracing::Span::current())
+                .await?
+        };
+        if ips.is_empty() {
+            return Err(anyhow!("could not resolve to any address",));
+        }
+        let server_name = self.server_name.as_ref().unwrap_or(&self.address);
+        let mut last_err: Option<anyhow::Error> = None;
+        for ip in ips {
+            let connect_addr = SocketAddr::new(ip, self.port);
+            let connecting = match endpoint.connect(connect_addr, server_name) {
+                Ok(c) => c,
+                Err(e) => {
+                    last_err = Some(e.into());
+                    continue;
+                }
+            };
+            match timeout(dial_timeout, connecting).await {
+                Ok(Ok(conn)) => {
+                    trace!("hysteria2 connected to {}", &connect_addr);
+                    let control = open_control_stream(&conn).await?;
+                    // The controllers of the connection read the rate from
+                    // here, so it's set once it's known.
+                    let auth = timeout(
+                        dial_timeout,
+                        authenticate(&conn, &self.password, self.down_bps),
+                    )
+                    .await
+                    .map_err(|_| anyhow!("hysteria2 authentication timed out"))??;
+                    let rate = send_rate(self.up_bps, auth.rx);
+                    bps.store(rate, Ordering::Relaxed);
+                    debug!(
+                        "hysteria2 authenticated to {}, udp {}, send rate {}",
+                        &connect_addr,
+                        auth.udp,
+                        if rate == 0 {
+                            "bbr".to_string()
+                        } else {
+                            format!("{} bytes/s", rate)
+                        }
+                    );
+                    let sessions = Arc::new(Sessions::default());
+                    if auth.udp {
+                        datagram::spawn_demux(conn.clone(), sessions.clone());
+                    }
+                    return Ok(Connection {
+                        conn,
+                        udp: auth.udp,
+                        sessions,
+                        _control: control,
+                    });
+                }
+                Ok(Err(e)) => last_err = Some(e.into()),
+                Err(_) => last_err = Some(anyhow!("connect hysteria2 timed out")),
+            }
+        }
+        Err(last_err.unwrap_or_else(|| anyhow!("connect hysteria2 failed")))
+    }
+
+    // Returns the shared connection, dialing a new one if there's none or it
+    // has been closed.
+    async fn connection(&self) -> Result<Arc<Connection>> {
+        let mut connection = self.connection.lock().await;
+        if let Some(c) = connection.as_ref() {
+            if c.conn.close_reason().is_none() {
+                return Ok(c.clone());
+            }
+            debug!("hysteria2 connection closed, reconnecting");
+        }
+        let dial_timeout = Duration::from_secs(*crate::option::OUTBOUND_DIAL_TIMEOUT);
+        let c = Arc::new(self.connect(dial_timeout).await?);
+        *connection = Some(c.clone());
+        Ok(c)
+    }
+
+    async fn new_stream(&self, sess: &Session) -> Result<AnyStream> {
+        let c = self.connection().await?;
+        let (mut send, mut recv) = c.conn.open_bi().await?;
+        send.write_all(&encode_tcp_request(&sess.destination))
+            .await?;
+        read_tcp_response(&mut recv).await?;
+        Ok(Box::new(Hysteria2Stream { send, recv }))
+    }
+
+    async fn new_datagram(&self) -> Result<Datagram> {
+        let c = self.connection().await?;
+        if !c.udp {
+            return Err(anyhow!("hysteria2 server doesn't support udp"));
+        }
+        Ok(Datagram::new(c.conn.clone(), c.sessions.clone())?)
+    }
+}
+
+impl UdpConnector for Manager {}
+
+pub struct Handler {
+    manager: Manager,
+}
+
+impl Handler {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        address: String,
+        port: u16,
+        password: String,
+        up_mbps: u32,
+        down_mbps: u32,
+        server_name: Option<String>,
+        insecure: bool,
+        dns_client: SyncDnsClient,
+    ) -> Result<Self> {
+        Ok(Self {
+            manager: Manager::new(
+                address,
+                port,
+                password,
+                up_mbps,
+                down_mbps,
+                server_name,
+                insecure,
+                dns_client,
+            )?,
+        })
+    }
+}
+
+impl UdpConnector for Handler {}
+
+#[async_trait]
+impl OutboundStreamHandler for Handler {
+    fn connect_addr(&self) -> OutboundConnect {
+        OutboundConnect::Unknown
+    }
+
+    async fn handle<'a>(
+        &'a self,
+        sess: &'a Session,
+        _lhs: Option<&mut AnyStream>,
+        _stream: Option<AnyStream>,
+    ) -> io::Result<AnyStream> {
+        tracing::trace!("handling outbound stream");
+        self.manager
+            .new_stream(sess)
+            .instrument(tracing::Span::current())
+            .await
+            .map_err(|e| io::Error::other(format!("new hysteria2 stream failed: {}", e)))
+    }
+}
+
+#[async_trait]
+impl OutboundDatagramHandler for Handler {
+    fn connect_addr(&self) -> OutboundConnect {
+        OutboundConnect::Unknown
+    }
+
+    fn transport_type(&self) -> DatagramTransportType {
+        DatagramTransportType::Unreliable
+    }
+
+    async fn handle<'a>(
+        &'a self,
+        _sess: &'a Session,
+        _transport: Option<AnyOutboundTransport>,
+    ) -> io::Result<AnyOutboundDatagram> {
+        tracing::trace!("handling outbound datagram");
+        let dgram = self
+            .manager
+            .new_datagram()
+            .instrument(tracing::Span::current())
+            .await
+            .map_err(|e| io::Error::other(format!("new hysteria2 udp session failed: {}", e)))?;
+        Ok(Box::new(dgram))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_send_rate() {
+        let up_bps = 12_500_000;
+        assert_eq!(send_rate(up_bps, None), 0);
+        assert_eq!(send_rate(up_bps, Some(0)), up_bps);
+        assert_eq!(send_rate(up_bps, Some(1_000_000)), 1_000_000);
+        assert_eq!(send_rate(up_bps, Some(20_000_000)), up_bps);
+        assert_eq!(send_rate(0, Some(1_000_000)), 0);
+    }
+}
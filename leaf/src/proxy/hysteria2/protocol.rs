@@ -0,0 +1,360 @@
+use std::io;
+use std::net::SocketAddr;
+
+use bytes::{BufMut, Bytes, BytesMut};
+use rand::Rng;
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+use crate::common::udp_session::Fragment;
+use crate::session::SocksAddr;
+
+/// The status the server answers a successful authentication with.
+pub const AUTH_STATUS_OK: u16 = 233;
+
+const TCP_REQUEST_ID: u64 = 0x401;
+const TCP_STATUS_OK: u8 = 0x00;
+
+const MAX_ADDRESS_LEN: u64 = 2048;
+const MAX_MESSAGE_LEN: u64 = 2048;
+const MAX_PADDING_LEN: u64 = 4096;
+
+// Session id, packet id, fragment id and fragment count.
+const UDP_HEADER_LEN: usize = 8;
+
+/// Appends a QUIC variable-length integer.
+pub fn put_varint(buf: &mut BytesMut, v: u64) {
+    if v < 1 << 6 {
+        buf.put_u8(v as u8);
+    } else if v < 1 << 14 {
+        buf.put_u16(0x4000 | v as u16);
+    } else if v < 1 << 30 {
+        buf.put_u32(0x8000_0000 | v as u32);
+    } else {
+        buf.put_u64(0xc000_0000_0000_0000 | v);
+    }
+}
+
+fn varint_len(v: u64) -> usize {
+    match v {
+        v if v < 1 << 6 => 1,
+        v if v < 1 << 14 => 2,
+        v if v < 1 << 30 => 4,
+        _ => 8,
+    }
+}
+
+/// Reads a QUIC variable-length integer from a stream.
+pub async fn read_varint<R: AsyncRead + Unpin>(r: &mut R) -> io::Result<u64> {
+    let first = r.read_u8().await?;
+    let len = 1usize << (first >> 6);
+    let mut v = (first & 0x3f) as u64;
+    for _ in 1..len {
+        v = (v << 8) | r.read_u8().await? as u64;
+    }
+    Ok(v)
+}
+
+/// Takes a QUIC variable-length integer off the front of `buf`.
+pub fn get_varint(buf: &mut &[u8]) -> io::Result<u64> {
+    let first = *buf
+        .first()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::UnexpectedEof, "truncated varint"))?;
+    let len = 1usize << (first >> 6);
+    if buf.len() < len {
+        return Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            "truncated varint",
+        ));
+    }
+    let mut v = (first & 0x3f) as u64;
+    for b in &buf[1..len] {
+        v = (v << 8) | *b as u64;
+    }
+    *buf = &buf[len..];
+    Ok(v)
+}
+
+/// Returns random padding of a length in `range`, made of characters which
+/// are also valid in header values.
+pub fn padding(range: std::ops::Range<usize>) -> Vec<u8> {
+    const CHARS: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789";
+    let mut rng = rand::thread_rng();
+    let len = rng.gen_range(range);
+    (0..len)
+        .map(|_| CHARS[rng.gen_range(0..CHARS.len())])
+        .collect()
+}
+
+/// Builds the request opening a TCP proxy stream.
+pub fn encode_tcp_request(destination: &SocksAddr) -> Bytes {
+    let addr = destination.to_string();
+    let padding = padding(64..512);
+    let mut buf = BytesMut::new();
+    put_varint(&mut buf, TCP_REQUEST_ID);
+    put_varint(&mut buf, addr.len() as u64);
+    buf.put_slice(addr.as_bytes());
+    put_varint(&mut buf, padding.len() as u64);
+    buf.put_slice(&padding);
+    buf.freeze()
+}
+
+async fn skip<R: AsyncRead + Unpin>(r: &mut R, len: u64) -> io::Result<()> {
+    let n = tokio::io::copy(&mut r.take(len), &mut tokio::io::sink()).await?;
+    if n < len {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+    Ok(())
+}
+
+/// Reads the server's response to a TCP request, failing if the server
+/// couldn't connect to the destination.
+pub async fn read_tcp_response<R: AsyncRead + Unpin>(r: &mut R) -> io::Result<()> {
+    let status = r.read_u8().await?;
+    let msg_len = read_varint(r).await?;
+    if msg_len > MAX_MESSAGE_LEN {
+        return Err(io::Error::other("invalid hysteria2 tcp response"));
+    }
+    let mut msg = vec![0u8; msg_len as usize];
+    r.read_exact(&mut msg).await?;
+    let padding_len = read_varint(r).await?;
+    if padding_len > MAX_PADDING_LEN {
+        return Err(io::Error::other("invalid hysteria2 tcp response"));
+    }
+    skip(r, padding_len).await?;
+    if status != TCP_STATUS_OK {
+        return Err(io::Error::other(format!(
+            "hysteria2 server refused the connection: {}",
+            String::from_utf8_lossy(&msg)
+        )));
+    }
+    Ok(())
+}
+
+/// Parses the `host:port` addresses of UDP messages.
+pub fn parse_address(addr: &str) -> io::Result<SocksAddr> {
+    if let Ok(addr) = addr.parse::<SocketAddr>() {
+        return Ok(SocksAddr::from(addr));
+    }
+    let (host, port) = addr
+        .rsplit_once(':')
+        .ok_or_else(|| io::Error::other(format!("invalid address {}", addr)))?;
+    let port = port
+        .parse::<u16>()
+        .map_err(|_| io::Error::other(format!("invalid address {}", addr)))?;
+    SocksAddr::try_from((host, port))
+}
+
+/// A UDP message, or a fragment of one, carried in a QUIC datagram.
+#[derive(Debug, PartialEq, Eq)]
+pub struct UdpMessage {
+    pub session_id: u32,
+    pub packet_id: u16,
+    pub fragment_id: u8,
+    pub fragment_count: u8,
+    pub address: String,
+    pub payload: Bytes,
+}
+
+impl UdpMessage {
+    pub fn encode(&self) -> Bytes {
+        let mut buf = BytesMut::with_capacity(self.header_len() + self.payload.len());
+        buf.put_u32(self.session_id);
+        buf.put_u16(self.packet_id);
+        buf.put_u8(self.fragment_id);
+        buf.put_u8(self.fragment_count);
+        put_varint(&mut buf, self.address.len() as u64);
+        buf.put_slice(self.address.as_bytes());
+        buf.put_slice(&self.payload);
+        buf.freeze()
+    }
+
+    pub fn decode(data: Bytes) -> io::Result<Self> {
+        if data.len() < UDP_HEADER_LEN {
+            return Err(io::Error::other("short hysteria2 udp message"));
+        }
+        let session_id = u32::from_be_bytes(data[..4].try_into().unwrap());
+        let packet_id = u16::from_be_bytes(data[4..6].try_into().unwrap());
+        let fragment_id = data[6];
+        let fragment_count = data[7];
+        let mut rest = &data[UDP_HEADER_LEN..];
+        let addr_len = get_varint(&mut rest)?;
+        if addr_len > MAX_ADDRESS_LEN || rest.len() < addr_len as usize {
+            return Err(io::Error::other("invalid hysteria2 udp message address"));
+        }
+        let address = String::from_utf8(rest[..addr_len as usize].to_vec())
+            .map_err(|_| io::Error::other("invalid hysteria2 udp message address"))?;
+        let offset = data.len() - rest.len() + addr_len as usize;
+        if fragment_count == 0 || fragment_id >= fragment_count {
+            return Err(io::Error::other("invalid hysteria2 udp message fragment"));
+        }
+        Ok(Self {
+            session_id,
+            packet_id,
+            fragment_id,
+            fragment_count,
+            address,
+            payload: data.slice(offset..),
+        })
+    }
+
+    fn header_len(&self) -> usize {
+        UDP_HEADER_LEN + varint_len(self.address.len() as u64) + self.address.len()
+    }
+
+    /// Splits the message into fragments of at most `max_size` bytes, or
+    /// returns `None` if even the header doesn't fit.
+    pub fn fragment(self, packet_id: u16, max_size: usize) -> Option<Vec<UdpMessage>> {
+        if self.header_len() + self.payload.len() <= max_size {
+            return Some(vec![self]);
+        }
+        let chunk = max_size.checked_sub(self.header_len()).filter(|n| *n > 0)?;
+        let count = self.payload.len().div_ceil(chunk);
+        if count > u8::MAX as usize {
+            return None;
+        }
+        Some(
+            (0..count)
+                .map(|i| UdpMessage {
+                    session_id: self.session_id,
+                    packet_id,
+                    fragment_id: i as u8,
+                    fragment_count: count as u8,
+                    address: self.address.clone(),
+                    payload: self
+                        .payload
+                        .slice(i * chunk..((i + 1) * chunk).min(self.payload.len())),
+                })
+                .collect(),
+        )
+    }
+}
+
+impl Fragment for UdpMessage {
+    fn packet_id(&self) -> u16 {
+        self.packet_id
+    }
+
+    fn fragment_id(&self) -> u8 {
+        self.fragment_id
+    }
+
+    fn fragment_count(&self) -> u8 {
+        self.fragment_count
+    }
+
+    fn payload(&self) -> &Bytes {
+        &self.payload
+    }
+
+    fn into_packet(self, payload: Bytes) -> Self {
+        UdpMessage {
+            fragment_id: 0,
+            fragment_count: 1,
+            payload,
+            ..self
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::common::udp_session::Defragger;
+
+    #[test]
+    fn test_varint() {
+        for v in [0u64, 63, 64, 0x401, 16383, 16384, (1 << 30) - 1, 1 << 30] {
+            let mut buf = BytesMut::new();
+            put_varint(&mut buf, v);
+            assert_eq!(buf.len(), varint_len(v));
+            let mut rest = &buf[..];
+            assert_eq!(get_varint(&mut rest).unwrap(), v);
+            assert!(rest.is_empty());
+        }
+        let mut buf = BytesMut::new();
+        put_varint(&mut buf, 0x401);
+        assert_eq!(&buf[..], &[0x44, 0x01]);
+        assert!(get_varint(&mut &[0x44][..]).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_tcp_request() {
+        let destination = SocksAddr::try_from(("example.com", 443)).unwrap();
+        let data = encode_tcp_request(&destination);
+        let mut r = &data[..];
+        assert_eq!(read_varint(&mut r).await.unwrap(), TCP_REQUEST_ID);
+        assert_eq!(read_varint(&mut r).await.unwrap(), 15);
+        assert_eq!(&r[..15], b"example.com:443");
+
+        let mut ok = vec![0x00, 0x00, 0x03];
+        ok.extend_from_slice(b"abc");
+        ok.extend_from_slice(b"data");
+        let mut r = &ok[..];
+        read_tcp_response(&mut r).await.unwrap();
+        assert_eq!(r, b"data");
+        let refused = [0x01, 0x05, b'e', b'r', b'r', b'o', b'r', 0x00];
+        let e = read_tcp_response(&mut &refused[..]).await.unwrap_err();
+        assert!(e.to_string().contains("error"));
+    }
+
+    #[test]
+    fn test_udp_message() {
+        let msg = UdpMessage {
+            session_id: 7,
+            packet_id: 0,
+            fragment_id: 0,
+            fragment_count: 1,
+            address: "1.2.3.4:53".to_string(),
+            payload: Bytes::from_static(b"hello"),
+        };
+        let data = msg.encode();
+        assert_eq!(data.len(), 8 + 1 + 10 + 5);
+        assert_eq!(UdpMessage::decode(data).unwrap(), msg);
+        assert!(UdpMessage::decode(Bytes::from_static(&[0, 0, 0, 7, 0, 0, 1, 1, 0])).is_err());
+
+        assert_eq!(parse_address("[::1]:53").unwrap().to_string(), "[::1]:53");
+        assert_eq!(
+            parse_address("example.com:443").unwrap().to_string(),
+            "example.com:443"
+        );
+        assert!(parse_address("example.com").is_err());
+    }
+
+    #[test]
+    fn test_fragment() {
+        let payload: Vec<u8> = (0..100).collect();
+        let msg = UdpMessage {
+            session_id: 1,
+            packet_id: 0,
+            fragment_id: 0,
+            fragment_count: 1,
+            address: "1.2.3.4:53".to_string(),
+            payload: Bytes::from(payload.clone()),
+        };
+        // 19 header bytes leave 21 bytes of payload per fragment.
+        let fragments = msg.fragment(9, 40).unwrap();
+        assert_eq!(fragments.len(), 5);
+        assert!(fragments.iter().all(|f| f.encode().len() <= 40));
+
+        let mut defragger = Defragger::default();
+        let mut fragments = fragments.into_iter().rev();
+        let last = fragments.next().unwrap();
+        for fragment in fragments {
+            assert!(defragger.feed(fragment).is_none());
+        }
+        let msg = defragger.feed(last).unwrap();
+        assert_eq!(msg.fragment_count, 1);
+        assert_eq!(&msg.payload[..], &payload[..]);
+
+        let msg = UdpMessage {
+            session_id: 1,
+            packet_id: 0,
+            fragment_id: 0,
+            fragment_count: 1,
+            address: "1.2.3.4:53".to_string(),
+            payload: Bytes::from_static(b"x"),
+        };
+        assert!(msg.fragment(1, 19).is_none());
+    }
+}
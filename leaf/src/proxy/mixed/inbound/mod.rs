@@ -0,0 +1,3 @@
+mod stream;
+
+pub use stream::Handler as StreamHandler;
@@ -0,0 +1,128 @@
+use std::io;
+
+use async_trait::async_trait;
+use bytes::BytesMut;
+use tokio::io::AsyncReadExt;
+
+use crate::{common::io::PrefixedStream, proxy::*, session::Session};
+
+/// Serves SOCKS and HTTP proxy requests on the same port, each connection is
+/// passed to the handler of the protocol its first byte indicates.
+pub struct Handler {
+    socks: AnyInboundStreamHandler,
+    http: AnyInboundStreamHandler,
+}
+
+impl Handler {
+    pub fn new(socks: AnyInboundStreamHandler, http: AnyInboundStreamHandler) -> Self {
+        Self { socks, http }
+    }
+}
+
+#[async_trait]
+impl InboundStreamHandler for Handler {
+    async fn handle<'a>(
+        &'a self,
+        sess: Session,
+        mut stream: AnyStream,
+    ) -> std::io::Result<AnyInboundTransport> {
+        let first = stream.read_u8().await?;
+        // The byte is put back for the sub-handler to read the request as is.
+        let handler = match first {
+            0x04 | 0x05 => &self.socks,
+            // HTTP methods are made of uppercase letters.
+            b'A'..=b'Z' => &self.http,
+            _ => {
+                return Err(io::Error::other(format!(
+                    "unknown mixed inbound protocol, first byte {:#04x}",
+                    first
+                )))
+            }
+        };
+        let stream = PrefixedStream::new(stream, BytesMut::from(&[first][..]));
+        handler.handle(sess, Box::new(stream)).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::sync::Arc;
+
+    use tokio::io::AsyncWriteExt;
+
+    use super::*;
+    use crate::proxy::{http, socks};
+
+    fn handler() -> Handler {
+        let associations = Arc::new(socks::inbound::UdpAssociations::new(false));
+        Handler::new(
+            Arc::new(socks::inbound::StreamHandler::new(
+                HashMap::new(),
+                true,
+                associations,
+            )),
+            Arc::new(http::inbound::StreamHandler::new(HashMap::new())),
+        )
+    }
+
+    fn destination(transport: AnyInboundTransport) -> String {
+        match transport {
+            InboundTransport::Stream(_, sess) => sess.destination.to_string(),
+            _ => panic!("unexpected transport"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_mixed_inbound_socks5() {
+        let (mut c, s) = tokio::io::duplex(1024);
+        // No auth, then CONNECT 1.2.3.4:80.
+        c.write_all(&[0x05, 0x01, 0x00, 0x05, 0x01, 0x00, 0x01, 1, 2, 3, 4, 0, 80])
+            .await
+            .unwrap();
+        let transport = handler()
+            .handle(Session::default(), Box::new(s))
+            .await
+            .unwrap();
+        assert_eq!(destination(transport), "1.2.3.4:80");
+        let mut reply = [0u8; 2];
+        c.read_exact(&mut reply).await.unwrap();
+        assert_eq!(reply, [0x05, 0x00]);
+    }
+
+    #[tokio::test]
+    async fn test_mixed_inbound_socks4() {
+        let (mut c, s) = tokio::io::duplex(1024);
+        c.write_all(&[0x04, 0x01, 0, 80, 1, 2, 3, 4, 0])
+            .await
+            .unwrap();
+        let transport = handler()
+            .handle(Session::default(), Box::new(s))
+            .await
+            .unwrap();
+        assert_eq!(destination(transport), "1.2.3.4:80");
+    }
+
+    #[tokio::test]
+    async fn test_mixed_inbound_http() {
+        let (mut c, s) = tokio::io::duplex(1024);
+        c.write_all(b"CONNECT example.com:443 HTTP/1.1\r\n\r\n")
+            .await
+            .unwrap();
+        let transport = handler()
+            .handle(Session::default(), Box::new(s))
+            .await
+            .unwrap();
+        assert_eq!(destination(transport), "example.com:443");
+    }
+
+    #[tokio::test]
+    async fn test_mixed_inbound_unknown() {
+        let (mut c, s) = tokio::io::duplex(1024);
+        c.write_all(&[0x16, 0x03, 0x01]).await.unwrap();
+        assert!(handler()
+            .handle(Session::default(), Box::new(s))
+            .await
+            .is_err());
+    }
+}
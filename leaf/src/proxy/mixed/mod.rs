@@ -0,0 +1,2 @@
+#[cfg(feature = "inbound-mixed")]
+pub mod inbound;
@@ -13,6 +13,7 @@ use socket2::{Domain, SockRef, Socket, Type};
 use thiserror::Error;
 use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite};
 use tokio::net::{TcpSocket, TcpStream, UdpSocket};
+use tokio::sync::watch;
 use tokio::time::timeout;
 use tracing::{debug, trace};
 
@@ -30,7 +31,7 @@ use {
 };
 
 use crate::{
-    app::SyncDnsClient,
+    app::{dns::Strategy, SyncDnsClient},
     common::resolver::Resolver,
     option,
     session::{DatagramSource, Network, Session, SocksAddr},
@@ -50,10 +51,20 @@ pub mod direct;
 pub mod drop;
 #[cfg(feature = "outbound-failover")]
 pub mod failover;
+#[cfg(any(feature = "inbound-grpc", feature = "outbound-grpc"))]
+pub mod grpc;
+#[cfg(feature = "outbound-h2")]
+pub mod h2;
 #[cfg(feature = "inbound-hc")]
 pub mod hc;
-#[cfg(feature = "inbound-http")]
+#[cfg(any(feature = "inbound-http", feature = "outbound-http"))]
 pub mod http;
+#[cfg(any(feature = "inbound-httpupgrade", feature = "outbound-httpupgrade"))]
+pub mod httpupgrade;
+#[cfg(feature = "outbound-hysteria2")]
+pub mod hysteria2;
+#[cfg(feature = "inbound-mixed")]
+pub mod mixed;
 #[cfg(feature = "outbound-mptp")]
 pub mod mptp;
 #[cfg(all(feature = "inbound-nf", windows))]
@@ -70,8 +81,12 @@ pub mod redirect;
 pub mod select;
 #[cfg(any(feature = "inbound-shadowsocks", feature = "outbound-shadowsocks"))]
 pub mod shadowsocks;
+#[cfg(feature = "outbound-shadowtls")]
+pub mod shadowtls;
 #[cfg(any(feature = "inbound-socks", feature = "outbound-socks"))]
 pub mod socks;
+#[cfg(feature = "outbound-ssh")]
+pub mod ssh;
 #[cfg(feature = "outbound-static")]
 pub mod r#static;
 #[cfg(feature = "outbound-tls")]
@@ -80,12 +95,20 @@ pub mod tls;
 pub mod trojan;
 #[cfg(feature = "outbound-tryall")]
 pub mod tryall;
+#[cfg(feature = "outbound-tuic")]
+pub mod tuic;
 #[cfg(feature = "inbound-tun")]
 pub mod tun;
-#[cfg(feature = "outbound-vless")]
+#[cfg(feature = "inbound-tunnel")]
+pub mod tunnel;
+#[cfg(feature = "outbound-urltest")]
+pub mod urltest;
+#[cfg(any(feature = "inbound-vless", feature = "outbound-vless"))]
 pub mod vless;
 #[cfg(feature = "outbound-vmess")]
 pub mod vmess;
+#[cfg(feature = "outbound-wireguard")]
+pub mod wireguard;
 #[cfg(any(feature = "inbound-ws", feature = "outbound-ws"))]
 pub mod ws;
 
@@ -101,6 +124,17 @@ pub enum ProxyError {
 
 pub type ProxyResult<T> = std::result::Result<T, ProxyError>;
 
+/// The error of a stream handler which may succeed on a new transport, like
+/// a TLS handshake the server asked to redo with other parameters. The
+/// handler dialing the transport may dial it again and retry once.
+#[derive(Error, Debug)]
+#[error("{0}")]
+pub struct RetryOnNewTransport(pub String);
+
+pub fn is_retry_on_new_transport(err: &io::Error) -> bool {
+    err.get_ref().is_some_and(|x| x.is::<RetryOnNewTransport>())
+}
+
 #[derive(Clone, Copy, PartialEq, Debug)]
 pub enum DatagramTransportType {
     Reliable,
@@ -193,6 +227,14 @@ impl TcpListener {
         })
     }
 
+    /// Wraps a listener which has been bound and set up by the caller.
+    pub fn from_std(listener: std::net::TcpListener) -> io::Result<Self> {
+        listener.set_nonblocking(true)?;
+        Ok(Self {
+            inner: tokio::net::TcpListener::from_std(listener)?,
+        })
+    }
+
     pub fn io(&self) -> &tokio::net::TcpListener {
         &self.inner
     }
@@ -306,6 +348,21 @@ async fn bind_socket<T: BindSocket>(socket: &T, indicator: &SocketAddr) -> io::R
     }))
 }
 
+lazy_static::lazy_static! {
+    static ref NETWORK_CHANGE: watch::Sender<u64> = watch::channel(0).0;
+}
+
+/// Signals that the network of the device has changed, e.g. switched from
+/// Wi-Fi to cellular, so sockets bound before may no longer work.
+pub fn notify_network_change() {
+    NETWORK_CHANGE.send_modify(|generation| *generation += 1);
+}
+
+/// Returns a receiver which is notified on every network change.
+pub fn subscribe_network_change() -> watch::Receiver<u64> {
+    NETWORK_CHANGE.subscribe()
+}
+
 // New UDP socket.
 pub async fn new_udp_socket(indicator: &SocketAddr) -> io::Result<UdpSocket> {
     let socket = match indicator {
@@ -464,16 +521,82 @@ struct DialResult {
     addr: SocketAddr,
 }
 
+// Dials the addresses one after another.
+async fn tcp_dial_in_order(addrs: Vec<SocketAddr>) -> io::Result<DialResult> {
+    let mut last_err = None;
+    for addr in addrs {
+        match tcp_dial_task(addr).await {
+            Ok(v) => return Ok(v),
+            Err(e) => last_err = Some(e),
+        }
+    }
+    Err(last_err.unwrap_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "could not resolve to any address",
+        )
+    }))
+}
+
+// Happy Eyeballs, RFC 8305. The preferred addresses are dialed first, the
+// others join the race after a head start, or as soon as the preferred
+// ones failed.
+async fn tcp_dial_racing(
+    preferred: Vec<SocketAddr>,
+    fallback: Vec<SocketAddr>,
+) -> io::Result<DialResult> {
+    if preferred.is_empty() || fallback.is_empty() {
+        return tcp_dial_in_order([preferred, fallback].concat()).await;
+    }
+    let preferred = tcp_dial_in_order(preferred);
+    let fallback = tcp_dial_in_order(fallback);
+    tokio::pin!(preferred, fallback);
+    let head_start = tokio::time::sleep(Duration::from_millis(*option::HAPPY_EYEBALLS_DELAY_MS));
+    tokio::select! {
+        res = &mut preferred => {
+            return match res {
+                Ok(v) => Ok(v),
+                Err(_) => fallback.await,
+            };
+        }
+        _ = head_start => (),
+    }
+    tokio::select! {
+        res = &mut preferred => match res {
+            Ok(v) => Ok(v),
+            Err(_) => fallback.await,
+        },
+        res = &mut fallback => match res {
+            Ok(v) => Ok(v),
+            Err(_) => preferred.await,
+        },
+    }
+}
+
 // Dials a TCP stream.
 pub async fn new_tcp_stream(
     dns_client: SyncDnsClient,
     address: &String,
     port: &u16,
 ) -> io::Result<AnyStream> {
+    let strategy = dns_client.read().await.strategy();
     let mut resolver = Resolver::new(dns_client.clone(), address, port)
         .map_err(|e| io::Error::other(format!("resolve address failed: {}", e)))
         .await?;
 
+    if strategy == Strategy::DualStack {
+        let (ipv6, ipv4): (Vec<_>, Vec<_>) = resolver.partition(|x| x.is_ipv6());
+        let v = tcp_dial_racing(ipv6, ipv4)
+            .await
+            .map_err(|e| io::Error::other(format!("all attempts failed, last error: {}", e)))?;
+        dns_client
+            .read()
+            .await
+            .optimize_cache(address.to_owned(), v.addr.ip())
+            .await;
+        return Ok(v.stream);
+    }
+
     let mut last_err = None;
 
     let mut done = false;
@@ -558,6 +681,9 @@ pub trait OutboundHandler: BaseHandler {
     fn is_direct(&self) -> bool {
         false
     }
+    fn is_drop(&self) -> bool {
+        false
+    }
 }
 
 pub type AnyOutboundHandler = Arc<dyn OutboundHandler>;
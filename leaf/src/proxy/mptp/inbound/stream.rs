@@ -15,55 +15,11 @@ use tokio::sync::mpsc::{self, UnboundedSender};
 use uuid::Uuid;
 
 use crate::{
+    common::io::PrefixedStream,
     proxy::{AnyInboundTransport, AnyStream, InboundStreamHandler, InboundTransport},
     session::{DatagramSource, Session, SocksAddr, StreamId},
 };
 
-struct PrefixedStream<S> {
-    stream: S,
-    prefix: BytesMut,
-}
-
-impl<S> PrefixedStream<S> {
-    fn new(stream: S, prefix: BytesMut) -> Self {
-        Self { stream, prefix }
-    }
-}
-
-impl<S: AsyncRead + Unpin> AsyncRead for PrefixedStream<S> {
-    fn poll_read(
-        mut self: Pin<&mut Self>,
-        cx: &mut Context<'_>,
-        buf: &mut ReadBuf<'_>,
-    ) -> Poll<io::Result<()>> {
-        if !self.prefix.is_empty() {
-            let len = std::cmp::min(buf.remaining(), self.prefix.len());
-            buf.put_slice(&self.prefix[..len]);
-            self.prefix.advance(len);
-            return Poll::Ready(Ok(()));
-        }
-        Pin::new(&mut self.stream).poll_read(cx, buf)
-    }
-}
-
-impl<S: AsyncWrite + Unpin> AsyncWrite for PrefixedStream<S> {
-    fn poll_write(
-        mut self: Pin<&mut Self>,
-        cx: &mut Context<'_>,
-        buf: &[u8],
-    ) -> Poll<io::Result<usize>> {
-        Pin::new(&mut self.stream).poll_write(cx, buf)
-    }
-
-    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
-        Pin::new(&mut self.stream).poll_flush(cx)
-    }
-
-    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
-        Pin::new(&mut self.stream).poll_shutdown(cx)
-    }
-}
-
 struct TrackedMptpStream<S> {
     inner: MptpStream<S>,
     cid: Uuid,
@@ -205,7 +205,7 @@ impl InboundDatagramSendHalf for DatagramSendHalf {
         let src_addr = match src_addr {
             SocksAddr::Ip(a) => *a,
             SocksAddr::Domain(domain, port) => {
-                if let Some(ip) = self.1.query_fake_ip(&domain).await {
+                if let Some(ip) = self.1.query_fake_ip(&domain, dst_addr.is_ipv6()).await {
                     SocketAddr::new(ip, *port)
                 } else {
                     return Err(io::Error::other(format!(
@@ -1,6 +1,6 @@
 use std::fs;
-use std::path::Path;
 use std::sync::Arc;
+use std::time::SystemTime;
 use std::{io, pin::Pin};
 
 use anyhow::{anyhow, Result};
@@ -9,41 +9,78 @@ use futures::stream::Stream;
 use futures::task::{Context, Poll};
 use quinn::{RecvStream, SendStream};
 use rustls::pki_types::{CertificateDer, PrivateKeyDer};
-use rustls_pemfile::{certs, pkcs8_private_keys, rsa_private_keys};
 use tokio::sync::mpsc::{channel, Receiver, Sender};
 use tokio::time::{timeout, Duration};
-use tracing::{debug, trace, warn};
+use tracing::{debug, error, info, trace, warn};
+use x509_parser::prelude::{FromDer, GeneralName, X509Certificate};
 
-use crate::{proxy::*, session::Session, session::StreamId};
+use crate::{
+    common::cert::{load_certs, load_private_key},
+    proxy::*,
+    session::Session,
+    session::StreamId,
+};
 
+use super::udp;
 use super::QuicProxyStream;
 
+// Each connection is driven by its own accept task (see `handle_conn`) which
+// feeds accepted streams and UDP sessions into this channel, so polling
+// only touches transports that are ready instead of scanning every connection.
 struct Incoming {
-    stream_rx: Receiver<(SocketAddr, (SendStream, RecvStream))>,
+    transport_rx: Receiver<AnyBaseInboundTransport>,
 }
 
 impl Stream for Incoming {
     type Item = AnyBaseInboundTransport;
 
     fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
-        match self.stream_rx.poll_recv(cx) {
-            Poll::Ready(Some((source, (send, recv)))) => {
-                let mut sess = Session {
-                    source,
-                    ..Default::default()
-                };
-                sess.stream_id = Some(StreamId::U64(send.id().index()));
-                Poll::Ready(Some(AnyBaseInboundTransport::Stream(
-                    Box::new(QuicProxyStream { recv, send }),
-                    sess,
-                )))
-            }
-            Poll::Ready(None) => Poll::Ready(None),
-            Poll::Pending => Poll::Pending,
-        }
+        self.transport_rx.poll_recv(cx)
     }
 }
 
+fn stream_transport(
+    source: SocketAddr,
+    peer_identity: Option<String>,
+    alpn: Option<String>,
+    send: SendStream,
+    recv: RecvStream,
+) -> AnyBaseInboundTransport {
+    let mut sess = Session {
+        source,
+        peer_identity,
+        alpn,
+        ..Default::default()
+    };
+    sess.stream_id = Some(StreamId::U64(send.id().index()));
+    AnyBaseInboundTransport::Stream(Box::new(QuicProxyStream { recv, send }), sess)
+}
+
+// A unidirectional stream is relayed without its type byte and with
+// everything written to it discarded.
+fn uni_stream_transport(
+    source: SocketAddr,
+    peer_identity: Option<String>,
+    alpn: Option<String>,
+    recv: RecvStream,
+) -> AnyBaseInboundTransport {
+    let mut sess = Session {
+        source,
+        peer_identity,
+        alpn,
+        ..Default::default()
+    };
+    // The full stream id, so it doesn't collide with the index of a bidi one.
+    sess.stream_id = Some(StreamId::U64(quinn::VarInt::from(recv.id()).into_inner()));
+    AnyBaseInboundTransport::Stream(
+        Box::new(QuicProxyStream {
+            recv,
+            send: tokio::io::sink(),
+        }),
+        sess,
+    )
+}
+
 fn quic_err<E>(error: E) -> io::Error
 where
     E: Into<Box<dyn std::error::Error + Send + Sync>>,
@@ -51,106 +88,384 @@ where
     io::Error::other(error)
 }
 
+fn load_cert_and_key(
+    certificate: &str,
+    certificate_key: &str,
+) -> Result<(Vec<CertificateDer<'static>>, PrivateKeyDer<'static>)> {
+    let cert = load_certs(certificate)?;
+    let key = load_private_key(certificate_key)?;
+    Ok((cert, key))
+}
+
+fn new_server_config(
+    certificate: &str,
+    certificate_key: &str,
+    client_ca_certificate: Option<&str>,
+    alpns: &[String],
+    transport_config: Arc<quinn::TransportConfig>,
+) -> Result<quinn::ServerConfig> {
+    let (cert, key) = load_cert_and_key(certificate, certificate_key)?;
+
+    #[cfg(feature = "rustls-tls-aws-lc")]
+    let provider: Arc<rustls::crypto::CryptoProvider> =
+        rustls::crypto::aws_lc_rs::default_provider().into();
+    #[cfg(not(feature = "rustls-tls-aws-lc"))]
+    let provider: Arc<rustls::crypto::CryptoProvider> =
+        rustls::crypto::ring::default_provider().into();
+
+    let builder = rustls::ServerConfig::builder_with_provider(provider.clone())
+        .with_safe_default_protocol_versions()
+        .unwrap();
+    let builder = if let Some(client_ca_certificate) = client_ca_certificate {
+        let mut roots = rustls::RootCertStore::empty();
+        for cert in load_certs(client_ca_certificate)? {
+            roots.add(cert)?;
+        }
+        let verifier =
+            rustls::server::WebPkiClientVerifier::builder_with_provider(Arc::new(roots), provider)
+                .build()?;
+        builder.with_client_cert_verifier(verifier)
+    } else {
+        builder.with_no_client_auth()
+    };
+    let mut crypto = builder.with_single_cert(cert, key)?;
+    for alpn in alpns {
+        crypto.alpn_protocols.push(alpn.as_bytes().to_vec());
+    }
+    // Lets clients resuming a session send 0-RTT data, QUIC requires the
+    // limit to be either 0 or u32::MAX.
+    crypto.max_early_data_size = u32::MAX;
+    if let Some(key_log) = crate::common::key_log::key_log() {
+        crypto.key_log = key_log;
+    }
+
+    let mut server_config = quinn::ServerConfig::with_crypto(Arc::new(
+        quinn::crypto::rustls::QuicServerConfig::try_from(crypto)?,
+    ));
+    server_config.transport_config(transport_config);
+    Ok(server_config)
+}
+
+fn modified_time(path: &str) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+// Application error code used to close connections negotiating a fallback
+// ALPN when there's no fallback address.
+const ALPN_REJECTED: u32 = 0x102;
+
+// How accepted connections are handled, shared by all connections of an
+// endpoint.
+struct ConnConfig {
+    tag: String,
+    client_auth: bool,
+    udp_over_stream: bool,
+    accept_uni_streams: bool,
+    fallback_alpns: Vec<String>,
+    fallback: Option<String>,
+}
+
 pub struct Handler {
+    certificate: String,
+    certificate_key: String,
+    client_ca_certificate: Option<String>,
+    alpns: Vec<String>,
+    transport_config: Arc<quinn::TransportConfig>,
     server_config: quinn::ServerConfig,
+    certificate_reload_interval: Option<Duration>,
+    conn_config: Arc<ConnConfig>,
 }
 
 impl Handler {
-    pub fn new(certificate: String, certificate_key: String, alpns: Vec<String>) -> Result<Self> {
-        let cert = if certificate.contains("-----BEGIN") {
-            certificate.as_bytes().to_vec()
-        } else {
-            fs::read(&certificate)?
-        };
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        tag: String,
+        certificate: String,
+        certificate_key: String,
+        alpns: Vec<String>,
+        max_concurrent_bidi_streams: Option<u32>,
+        max_idle_timeout_ms: Option<u32>,
+        keep_alive_interval_ms: Option<u32>,
+        congestion_controller: Option<String>,
+        certificate_reload_interval: Option<u32>,
+        client_ca_certificate: Option<String>,
+        udp_over_stream: bool,
+        accept_uni_streams: bool,
+        fallback_alpns: Vec<String>,
+        fallback: Option<String>,
+    ) -> Result<Self> {
+        let max_concurrent_bidi_streams =
+            max_concurrent_bidi_streams.unwrap_or(*crate::option::QUIC_MAX_CONCURRENT_BIDI_STREAMS);
+        let max_idle_timeout_ms =
+            max_idle_timeout_ms.unwrap_or(*crate::option::QUIC_MAX_IDLE_TIMEOUT_MS);
+        if max_concurrent_bidi_streams == 0 {
+            return Err(anyhow!(
+                "max concurrent bidi streams must be greater than 0"
+            ));
+        }
+        if max_idle_timeout_ms == 0 {
+            return Err(anyhow!("max idle timeout must be greater than 0"));
+        }
+        let mut transport_config = quinn::TransportConfig::default();
+        transport_config
+            .max_concurrent_bidi_streams(quinn::VarInt::from_u32(max_concurrent_bidi_streams));
+        transport_config.max_idle_timeout(Some(quinn::IdleTimeout::from(quinn::VarInt::from_u32(
+            max_idle_timeout_ms,
+        ))));
+        if let Some(keep_alive_interval_ms) = keep_alive_interval_ms {
+            if keep_alive_interval_ms >= max_idle_timeout_ms {
+                return Err(anyhow!(
+                    "keep alive interval {}ms must be less than max idle timeout {}ms",
+                    keep_alive_interval_ms,
+                    max_idle_timeout_ms
+                ));
+            }
+            transport_config
+                .keep_alive_interval(Some(Duration::from_millis(keep_alive_interval_ms as u64)));
+        }
+        transport_config.congestion_controller_factory(
+            crate::proxy::quic::congestion_controller_factory(congestion_controller.as_deref())?,
+        );
+        if udp_over_stream {
+            // Tells the client not to send DATAGRAMs.
+            transport_config.datagram_receive_buffer_size(None);
+        }
+        let transport_config = Arc::new(transport_config);
 
-        let key = if certificate_key.contains("-----BEGIN") {
-            certificate_key.as_bytes().to_vec()
-        } else {
-            fs::read(&certificate_key)?
-        };
+        let mut alpns = alpns;
+        for alpn in &fallback_alpns {
+            if !alpns.contains(alpn) {
+                alpns.push(alpn.clone());
+            }
+        }
+        let server_config = new_server_config(
+            &certificate,
+            &certificate_key,
+            client_ca_certificate.as_deref(),
+            &alpns,
+            transport_config.clone(),
+        )?;
 
-        let cert = if !certificate.contains("-----BEGIN")
-            && Path::new(&certificate).extension().map(|ext| ext.to_str()) == Some(Some("der"))
-        {
-            vec![CertificateDer::from(cert)]
-        } else {
-            certs(&mut io::BufReader::new(&*cert)).collect::<Result<Vec<_>, _>>()?
-        };
+        // Inline certificates have nothing to watch.
+        let certificate_reload_interval = certificate_reload_interval
+            .filter(|_| {
+                !certificate.contains("-----BEGIN") && !certificate_key.contains("-----BEGIN")
+            })
+            .map(|secs| Duration::from_secs(secs as u64));
 
-        let key = if !certificate_key.contains("-----BEGIN")
-            && Path::new(&certificate_key)
-                .extension()
-                .map(|ext| ext.to_str())
-                == Some(Some("der"))
-        {
-            PrivateKeyDer::Pkcs8(key.into())
-        } else {
-            let pkcs8 = pkcs8_private_keys(&mut io::BufReader::new(&*key))
-                .collect::<Result<Vec<_>, _>>()?;
-            match pkcs8.into_iter().next() {
-                Some(x) => PrivateKeyDer::Pkcs8(x),
-                None => {
-                    let rsa = rsa_private_keys(&mut io::BufReader::new(&*key))
-                        .collect::<Result<Vec<_>, _>>()?;
-                    match rsa.into_iter().next() {
-                        Some(x) => PrivateKeyDer::Pkcs1(x),
-                        None => {
-                            return Err(anyhow!("no private keys found",));
-                        }
-                    }
-                }
-            }
-        };
+        let client_auth = client_ca_certificate.is_some();
+        Ok(Self {
+            certificate,
+            certificate_key,
+            client_ca_certificate,
+            alpns,
+            transport_config,
+            server_config,
+            certificate_reload_interval,
+            conn_config: Arc::new(ConnConfig {
+                tag,
+                client_auth,
+                udp_over_stream,
+                accept_uni_streams,
+                fallback_alpns,
+                fallback,
+            }),
+        })
+    }
+}
 
-        #[cfg(feature = "rustls-tls-aws-lc")]
-        let provider = rustls::crypto::aws_lc_rs::default_provider().into();
-        #[cfg(not(feature = "rustls-tls-aws-lc"))]
-        let provider = rustls::crypto::ring::default_provider().into();
+// Periodically checks the certificate and key files for changes and swaps a
+// rebuilt server config into the endpoint. Established connections keep the
+// config they were accepted with.
+async fn reload_certificate(
+    endpoint: quinn::Endpoint,
+    certificate: String,
+    certificate_key: String,
+    client_ca_certificate: Option<String>,
+    alpns: Vec<String>,
+    transport_config: Arc<quinn::TransportConfig>,
+    interval: Duration,
+) {
+    let mut last_modified = (modified_time(&certificate), modified_time(&certificate_key));
+    loop {
+        tokio::time::sleep(interval).await;
+        let modified = (modified_time(&certificate), modified_time(&certificate_key));
+        if modified == last_modified {
+            continue;
+        }
+        // A failed reload isn't retried until the files change again, so a bad
+        // certificate is reported once.
+        last_modified = modified;
+        match new_server_config(
+            &certificate,
+            &certificate_key,
+            client_ca_certificate.as_deref(),
+            &alpns,
+            transport_config.clone(),
+        ) {
+            Ok(server_config) => {
+                endpoint.set_server_config(Some(server_config));
+                info!("reloaded quic certificate {}", &certificate);
+            }
+            Err(e) => {
+                error!(
+                    "reload quic certificate {} failed, keeping the current one: {}",
+                    &certificate, e
+                );
+            }
+        }
+    }
+}
 
-        let mut crypto = rustls::ServerConfig::builder_with_provider(provider)
-            .with_safe_default_protocol_versions()
-            .unwrap()
-            .with_no_client_auth()
-            .with_single_cert(cert, key)?;
-        for alpn in alpns {
-            crypto.alpn_protocols.push(alpn.as_bytes().to_vec());
+// Returns the common name and the DNS/email subject alternative names of the
+// peer's end-entity certificate.
+fn peer_cert_names(conn: &quinn::Connection) -> (Option<String>, Vec<String>) {
+    let Some(certs) = conn
+        .peer_identity()
+        .and_then(|id| id.downcast::<Vec<CertificateDer<'static>>>().ok())
+    else {
+        return (None, Vec::new());
+    };
+    let Some(Ok((_, cert))) = certs.first().map(|c| X509Certificate::from_der(c.as_ref())) else {
+        return (None, Vec::new());
+    };
+    let cn = cert
+        .subject()
+        .iter_common_name()
+        .next()
+        .and_then(|cn| cn.as_str().ok())
+        .map(|cn| cn.to_string());
+    let mut sans = Vec::new();
+    if let Ok(Some(san)) = cert.subject_alternative_name() {
+        for name in &san.value.general_names {
+            match name {
+                GeneralName::DNSName(x) | GeneralName::RFC822Name(x) => sans.push(x.to_string()),
+                _ => (),
+            }
         }
+    }
+    (cn, sans)
+}
 
-        let mut server_config = quinn::ServerConfig::with_crypto(Arc::new(
-            quinn::crypto::rustls::QuicServerConfig::try_from(crypto).unwrap(),
-        ));
-        let mut transport_config = quinn::TransportConfig::default();
-        transport_config.max_concurrent_bidi_streams(quinn::VarInt::from_u32(
-            *crate::option::QUIC_MAX_CONCURRENT_BIDI_STREAMS,
-        ));
-        transport_config.max_idle_timeout(Some(quinn::IdleTimeout::from(
-            quinn::VarInt::from_u32(*crate::option::QUIC_MAX_IDLE_TIMEOUT_MS),
-        )));
-        transport_config
-            .congestion_controller_factory(Arc::new(quinn::congestion::BbrConfig::default()));
-        server_config.transport_config(Arc::new(transport_config));
+// Returns the ALPN protocol the client and the server agreed on.
+async fn negotiated_alpn(conn: &mut quinn::Connecting) -> Result<Option<String>> {
+    let data = conn.handshake_data().await?;
+    Ok(data
+        .downcast::<quinn::crypto::rustls::HandshakeData>()
+        .ok()
+        .and_then(|data| data.protocol)
+        .map(|alpn| String::from_utf8_lossy(&alpn).into_owned()))
+}
 
-        Ok(Self { server_config })
+// Proxies every bidi stream of the connection to a TCP connection to the
+// fallback address.
+async fn relay_fallback(
+    conn: quinn::Connection,
+    remote_addr: SocketAddr,
+    fallback: String,
+) -> Result<()> {
+    loop {
+        let (send, recv) = conn.accept_bi().await?;
+        trace!("quic accepted fallback stream from {}", remote_addr);
+        let fallback = fallback.clone();
+        tokio::spawn(async move {
+            let mut stream = QuicProxyStream { recv, send };
+            match tokio::net::TcpStream::connect(&fallback).await {
+                Ok(mut target) => {
+                    if let Err(e) = tokio::io::copy_bidirectional(&mut stream, &mut target).await {
+                        debug!("quic fallback stream to {} failed: {}", &fallback, e);
+                    }
+                }
+                Err(e) => {
+                    debug!("connect quic fallback {} failed: {}", &fallback, e);
+                }
+            }
+        });
     }
 }
 
 async fn handle_conn(
-    stream_tx: Sender<(SocketAddr, (SendStream, RecvStream))>,
+    config: Arc<ConnConfig>,
+    transport_tx: Sender<AnyBaseInboundTransport>,
     remote_addr: SocketAddr,
-    conn: quinn::Connecting,
+    mut conn: quinn::Connecting,
 ) -> Result<()> {
-    let (conn, _) = conn
-        .into_0rtt()
-        .map_err(|_| anyhow!("convert 0rtt failed"))?;
+    let alpn = negotiated_alpn(&mut conn).await?;
+    if let Some(alpn) = alpn
+        .as_ref()
+        .filter(|alpn| config.fallback_alpns.contains(alpn))
+    {
+        let conn = conn.await?;
+        let Some(fallback) = config.fallback.clone() else {
+            debug!(
+                "closing quic connection from {} negotiating alpn {}",
+                remote_addr, alpn
+            );
+            conn.close(quinn::VarInt::from_u32(ALPN_REJECTED), b"unsupported alpn");
+            return Ok(());
+        };
+        debug!(
+            "relaying quic connection from {} negotiating alpn {} to {}",
+            remote_addr, alpn, &fallback
+        );
+        return relay_fallback(conn, remote_addr, fallback).await;
+    }
+    // Streams accepted over 0-RTT would arrive before the client certificate
+    // is verified, so wait for the full handshake when client auth is on.
+    let (conn, peer_identity) = if config.client_auth {
+        let conn = conn.await?;
+        let (cn, sans) = peer_cert_names(&conn);
+        debug!(
+            "quic client {} authenticated, cn: {:?}, san: {:?}",
+            remote_addr, cn, sans
+        );
+        let peer_identity = cn.or_else(|| sans.into_iter().next());
+        (conn, peer_identity)
+    } else {
+        let (conn, _) = conn
+            .into_0rtt()
+            .map_err(|_| anyhow!("convert 0rtt failed"))?;
+        (conn, None)
+    };
     let send_timeout = Duration::from_secs(*crate::option::QUIC_ACCEPT_QUEUE_TIMEOUT);
     trace!("quic handling connection from {}", remote_addr);
+    super::super::spawn_path_stats(conn.clone(), config.tag.clone());
+    let uni_tx = if config.accept_uni_streams {
+        let (uni_tx, mut uni_rx) = channel(*crate::option::QUIC_ACCEPT_CHANNEL_SIZE);
+        let transport_tx = transport_tx.clone();
+        let peer_identity = peer_identity.clone();
+        let alpn = alpn.clone();
+        tokio::spawn(async move {
+            while let Some(recv) = uni_rx.recv().await {
+                trace!("quic accepted uni stream from {}", remote_addr);
+                let s =
+                    uni_stream_transport(remote_addr, peer_identity.clone(), alpn.clone(), recv);
+                if transport_tx.send(s).await.is_err() {
+                    break;
+                }
+            }
+        });
+        Some(uni_tx)
+    } else {
+        None
+    };
+    tokio::spawn(udp::relay(
+        conn.clone(),
+        remote_addr,
+        peer_identity.clone(),
+        alpn.clone(),
+        config.udp_over_stream,
+        transport_tx.clone(),
+        uni_tx,
+    ));
     loop {
-        let s = conn.accept_bi().await?;
+        let (send, recv) = conn.accept_bi().await?;
         trace!("quic accepted stream from {}", remote_addr);
-        if stream_tx.capacity() == 0 {
+        if transport_tx.capacity() == 0 {
             warn!("quic accept channel full");
         }
-        match timeout(send_timeout, stream_tx.send((remote_addr, s))).await {
+        let s = stream_transport(remote_addr, peer_identity.clone(), alpn.clone(), send, recv);
+        match timeout(send_timeout, transport_tx.send(s)).await {
             Ok(Ok(())) => {}
             Ok(Err(_)) => return Ok(()),
             Err(_) => {
@@ -167,7 +482,7 @@ async fn handle_conn(
 impl InboundDatagramHandler for Handler {
     async fn handle<'a>(&'a self, socket: AnyInboundDatagram) -> io::Result<AnyInboundTransport> {
         tracing::trace!("handling inbound datagram");
-        let (stream_tx, stream_rx) = channel(*crate::option::QUIC_ACCEPT_CHANNEL_SIZE);
+        let (transport_tx, transport_rx) = channel(*crate::option::QUIC_ACCEPT_CHANNEL_SIZE);
         let endpoint = quinn::Endpoint::new(
             quinn::EndpointConfig::default(),
             Some(self.server_config.clone()),
@@ -175,14 +490,30 @@ impl InboundDatagramHandler for Handler {
             Arc::new(quinn::TokioRuntime),
         )
         .map_err(quic_err)?;
+        let reload_task = self.certificate_reload_interval.map(|interval| {
+            tokio::spawn(reload_certificate(
+                endpoint.clone(),
+                self.certificate.clone(),
+                self.certificate_key.clone(),
+                self.client_ca_certificate.clone(),
+                self.alpns.clone(),
+                self.transport_config.clone(),
+                interval,
+            ))
+        });
+        let conn_config = self.conn_config.clone();
         tokio::spawn(async move {
             while let Some(incoming) = endpoint.accept().await {
-                let stream_tx_c = stream_tx.clone();
+                let transport_tx_c = transport_tx.clone();
+                let conn_config_c = conn_config.clone();
                 tokio::spawn(async move {
                     let remote_addr = incoming.remote_address();
                     match incoming.accept() {
                         Ok(connecting) => {
-                            if let Err(e) = handle_conn(stream_tx_c, remote_addr, connecting).await {
+                            if let Err(e) =
+                                handle_conn(conn_config_c, transport_tx_c, remote_addr, connecting)
+                                    .await
+                            {
                                 debug!(
                                     "handle quic connection from {} failed: {}",
                                     &remote_addr, e
@@ -195,7 +526,283 @@ impl InboundDatagramHandler for Handler {
                     }
                 });
             }
+            if let Some(reload_task) = reload_task {
+                reload_task.abort();
+            }
         });
-        Ok(InboundTransport::Incoming(Box::new(Incoming { stream_rx })))
+        Ok(InboundTransport::Incoming(Box::new(Incoming {
+            transport_rx,
+        })))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::StreamExt;
+    use tokio::io::AsyncReadExt;
+
+    use super::*;
+    use crate::proxy::datagram::SimpleInboundDatagram;
+    use crate::proxy::quic::udp;
+    use crate::session::SocksAddr;
+
+    // A certificate for localhost issued by a CA of its own, in PEM.
+    struct Issued {
+        ca: String,
+        cert: String,
+        key: String,
+    }
+
+    fn issue(name: &str) -> Issued {
+        let mut params = rcgen::CertificateParams::new(Vec::<String>::new()).unwrap();
+        params.is_ca = rcgen::IsCa::Ca(rcgen::BasicConstraints::Unconstrained);
+        params
+            .distinguished_name
+            .push(rcgen::DnType::CommonName, format!("{} ca", name));
+        let ca_key = rcgen::KeyPair::generate().unwrap();
+        let ca = params.self_signed(&ca_key).unwrap();
+        let mut params = rcgen::CertificateParams::new(vec!["localhost".to_string()]).unwrap();
+        params
+            .distinguished_name
+            .push(rcgen::DnType::CommonName, name);
+        let key = rcgen::KeyPair::generate().unwrap();
+        let cert = params.signed_by(&key, &ca, &ca_key).unwrap();
+        Issued {
+            ca: ca.pem(),
+            cert: cert.pem(),
+            key: key.serialize_pem(),
+        }
+    }
+
+    fn new_handler(
+        certificate: &str,
+        certificate_key: &str,
+        certificate_reload_interval: Option<u32>,
+        client_ca_certificate: Option<&str>,
+        accept_uni_streams: bool,
+    ) -> Handler {
+        Handler::new(
+            "quic".to_string(),
+            certificate.to_string(),
+            certificate_key.to_string(),
+            Vec::new(),
+            None,
+            None,
+            None,
+            None,
+            certificate_reload_interval,
+            client_ca_certificate.map(str::to_string),
+            false,
+            accept_uni_streams,
+            Vec::new(),
+            None,
+        )
+        .unwrap()
+    }
+
+    async fn serve(handler: &Handler) -> (SocketAddr, AnyIncomingTransport) {
+        let socket = tokio::net::UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let addr = socket.local_addr().unwrap();
+        match handler
+            .handle(Box::new(SimpleInboundDatagram(socket)))
+            .await
+            .unwrap()
+        {
+            InboundTransport::Incoming(incoming) => (addr, incoming),
+            _ => panic!("not an incoming transport"),
+        }
+    }
+
+    // A client endpoint trusting `ca`, authenticating with `client` if any.
+    fn new_client(ca: &str, client: Option<&Issued>) -> quinn::Endpoint {
+        #[cfg(feature = "rustls-tls-aws-lc")]
+        let provider: Arc<rustls::crypto::CryptoProvider> =
+            rustls::crypto::aws_lc_rs::default_provider().into();
+        #[cfg(not(feature = "rustls-tls-aws-lc"))]
+        let provider: Arc<rustls::crypto::CryptoProvider> =
+            rustls::crypto::ring::default_provider().into();
+        let mut roots = rustls::RootCertStore::empty();
+        for cert in load_certs(ca).unwrap() {
+            roots.add(cert).unwrap();
+        }
+        let builder = rustls::ClientConfig::builder_with_provider(provider)
+            .with_safe_default_protocol_versions()
+            .unwrap()
+            .with_root_certificates(roots);
+        let crypto = match client {
+            Some(client) => builder
+                .with_client_auth_cert(
+                    load_certs(&client.cert).unwrap(),
+                    load_private_key(&client.key).unwrap(),
+                )
+                .unwrap(),
+            None => builder.with_no_client_auth(),
+        };
+        let mut endpoint = quinn::Endpoint::client("127.0.0.1:0".parse().unwrap()).unwrap();
+        endpoint.set_default_client_config(quinn::ClientConfig::new(Arc::new(
+            quinn::crypto::rustls::QuicClientConfig::try_from(crypto).unwrap(),
+        )));
+        endpoint
+    }
+
+    async fn next_transport(incoming: &mut AnyIncomingTransport) -> AnyBaseInboundTransport {
+        timeout(Duration::from_secs(5), incoming.next())
+            .await
+            .expect("no transport accepted")
+            .unwrap()
+    }
+
+    // Sends a stream over the connection and returns the session the inbound
+    // accepted it with.
+    async fn ping(conn: &quinn::Connection, incoming: &mut AnyIncomingTransport) -> Session {
+        let (mut send, _recv) = conn.open_bi().await.unwrap();
+        send.write_all(b"ping").await.unwrap();
+        match next_transport(incoming).await {
+            BaseInboundTransport::Stream(mut stream, sess) => {
+                let mut buf = [0u8; 4];
+                stream.read_exact(&mut buf).await.unwrap();
+                assert_eq!(&buf, b"ping");
+                sess
+            }
+            _ => panic!("not a stream transport"),
+        }
+    }
+
+    fn peer_certificate(conn: &quinn::Connection) -> CertificateDer<'static> {
+        conn.peer_identity()
+            .unwrap()
+            .downcast::<Vec<CertificateDer<'static>>>()
+            .unwrap()
+            .remove(0)
+    }
+
+    #[tokio::test]
+    async fn test_uni_stream_types() {
+        let server = issue("server");
+        let handler = new_handler(&server.cert, &server.key, None, None, true);
+        let (addr, mut incoming) = serve(&handler).await;
+        let client = new_client(&server.ca, None);
+        let conn = client.connect(addr, "localhost").unwrap().await.unwrap();
+
+        // A payload starting with the UDP type byte is still a raw payload.
+        let mut send = conn.open_uni().await.unwrap();
+        send.write_all(&[udp::UNI_STREAM_RAW, udp::UNI_STREAM_UDP, b'r'])
+            .await
+            .unwrap();
+        send.finish().unwrap();
+        // A UDP frame on a stream of its own.
+        let dest = SocksAddr::Ip("127.0.0.1:53".parse().unwrap());
+        udp::send_frame(&conn, udp::encode_frame(7, &dest, b"udp"), true)
+            .await
+            .unwrap();
+
+        let (mut raw, mut udp) = (None, None);
+        while raw.is_none() || udp.is_none() {
+            match next_transport(&mut incoming).await {
+                BaseInboundTransport::Stream(stream, _) => raw = Some(stream),
+                BaseInboundTransport::Datagram(datagram, _) => udp = Some(datagram),
+                BaseInboundTransport::Empty => panic!("empty transport"),
+            }
+        }
+        let mut payload = Vec::new();
+        raw.unwrap().read_to_end(&mut payload).await.unwrap();
+        assert_eq!(payload, [udp::UNI_STREAM_UDP, b'r']);
+        let (mut recv, _) = udp.unwrap().split();
+        let mut buf = [0u8; 16];
+        let (n, _, dst) = recv.recv_from(&mut buf).await.unwrap();
+        assert_eq!(&buf[..n], b"udp");
+        assert_eq!(dst, dest);
+
+        // Streams of an unknown type are stopped.
+        let mut send = conn.open_uni().await.unwrap();
+        send.write_all(&[0x02, b'x']).await.unwrap();
+        send.finish().unwrap();
+        assert_eq!(
+            send.stopped().await.unwrap(),
+            Some(quinn::VarInt::from_u32(udp::UNI_STREAM_REJECTED))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_reload_certificate() {
+        let dir = std::env::temp_dir().join(format!("leaf-quic-reload-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let cert_path = dir.join("server.crt");
+        let key_path = dir.join("server.key");
+        let (old, new) = (issue("old"), issue("new"));
+        fs::write(&cert_path, &old.cert).unwrap();
+        fs::write(&key_path, &old.key).unwrap();
+
+        let handler = new_handler(
+            cert_path.to_str().unwrap(),
+            key_path.to_str().unwrap(),
+            Some(1),
+            None,
+            false,
+        );
+        let (addr, mut incoming) = serve(&handler).await;
+        let client = new_client(&format!("{}{}", old.ca, new.ca), None);
+        let old_conn = client.connect(addr, "localhost").unwrap().await.unwrap();
+        ping(&old_conn, &mut incoming).await;
+        assert_eq!(
+            peer_certificate(&old_conn),
+            load_certs(&old.cert).unwrap()[0]
+        );
+
+        // Past the modification time of the first files.
+        tokio::time::sleep(Duration::from_millis(1100)).await;
+        fs::write(&cert_path, &new.cert).unwrap();
+        fs::write(&key_path, &new.key).unwrap();
+        let new_cert = load_certs(&new.cert).unwrap().remove(0);
+        let mut reloaded = false;
+        for _ in 0..50 {
+            let conn = client.connect(addr, "localhost").unwrap().await.unwrap();
+            reloaded = peer_certificate(&conn) == new_cert;
+            conn.close(quinn::VarInt::from_u32(0), b"");
+            if reloaded {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        assert!(reloaded, "new handshakes kept the old certificate");
+
+        // The connection established before keeps working.
+        assert!(old_conn.close_reason().is_none());
+        ping(&old_conn, &mut incoming).await;
+        assert_eq!(
+            peer_certificate(&old_conn),
+            load_certs(&old.cert).unwrap()[0]
+        );
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    // Whether the connection is closed with a TLS alert. The client may
+    // finish its side of the handshake before the inbound verifies its
+    // certificate, the alert then closes the connection right after.
+    async fn rejected(client: &quinn::Endpoint, addr: SocketAddr) -> bool {
+        let err = match client.connect(addr, "localhost").unwrap().await {
+            Ok(conn) => match timeout(Duration::from_secs(5), conn.closed()).await {
+                Ok(err) => err,
+                Err(_) => return false,
+            },
+            Err(err) => err,
+        };
+        matches!(err, quinn::ConnectionError::ConnectionClosed(close)
+            if (0x100..0x200).contains(&u64::from(close.error_code)))
+    }
+
+    #[tokio::test]
+    async fn test_client_ca_certificate() {
+        let (server, trusted, other) = (issue("server"), issue("trusted"), issue("other"));
+        let handler = new_handler(&server.cert, &server.key, None, Some(&trusted.ca), false);
+        let (addr, mut incoming) = serve(&handler).await;
+
+        assert!(rejected(&new_client(&server.ca, None), addr).await);
+        assert!(rejected(&new_client(&server.ca, Some(&other)), addr).await);
+
+        let client = new_client(&server.ca, Some(&trusted));
+        let conn = client.connect(addr, "localhost").unwrap().await.unwrap();
+        let sess = ping(&conn, &mut incoming).await;
+        assert_eq!(sess.peer_identity.as_deref(), Some("trusted"));
     }
 }
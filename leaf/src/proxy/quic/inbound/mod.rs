@@ -1,4 +1,5 @@
 mod datagram;
+mod udp;
 
 pub use datagram::Handler as DatagramHandler;
 
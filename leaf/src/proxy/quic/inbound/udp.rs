@@ -0,0 +1,162 @@
+use std::collections::HashMap;
+use std::io;
+use std::net::SocketAddr;
+
+use anyhow::anyhow;
+use async_trait::async_trait;
+use bytes::Bytes;
+use tokio::sync::mpsc::error::TrySendError;
+use tokio::sync::mpsc::{channel, Receiver, Sender};
+use tracing::{debug, trace};
+
+use crate::{
+    proxy::*,
+    session::{DatagramSource, Session, SocksAddr, StreamId},
+};
+
+use super::super::udp;
+
+type Packet = (SocksAddr, Bytes);
+
+// A UDP session relayed over a QUIC connection, identified by the session id
+// the client put in the frames.
+struct Datagram {
+    conn: quinn::Connection,
+    session_id: u32,
+    source: DatagramSource,
+    over_stream: bool,
+    packet_rx: Receiver<Packet>,
+}
+
+impl InboundDatagram for Datagram {
+    fn split(
+        self: Box<Self>,
+    ) -> (
+        Box<dyn InboundDatagramRecvHalf>,
+        Box<dyn InboundDatagramSendHalf>,
+    ) {
+        (
+            Box::new(DatagramRecvHalf(self.packet_rx, self.source)),
+            Box::new(DatagramSendHalf {
+                conn: self.conn,
+                session_id: self.session_id,
+                over_stream: self.over_stream,
+            }),
+        )
+    }
+
+    fn into_std(self: Box<Self>) -> io::Result<std::net::UdpSocket> {
+        Err(io::Error::other("quic datagram transport"))
+    }
+}
+
+struct DatagramRecvHalf(Receiver<Packet>, DatagramSource);
+
+#[async_trait]
+impl InboundDatagramRecvHalf for DatagramRecvHalf {
+    async fn recv_from(
+        &mut self,
+        buf: &mut [u8],
+    ) -> ProxyResult<(usize, DatagramSource, SocksAddr)> {
+        let (dst_addr, payload) = self
+            .0
+            .recv()
+            .await
+            .ok_or_else(|| ProxyError::DatagramFatal(anyhow!("quic connection closed")))?;
+        if buf.len() < payload.len() {
+            return Err(ProxyError::DatagramWarn(anyhow!("Small buffer")));
+        }
+        buf[..payload.len()].copy_from_slice(&payload);
+        Ok((payload.len(), self.1.clone(), dst_addr))
+    }
+}
+
+struct DatagramSendHalf {
+    conn: quinn::Connection,
+    session_id: u32,
+    over_stream: bool,
+}
+
+#[async_trait]
+impl InboundDatagramSendHalf for DatagramSendHalf {
+    async fn send_to(
+        &mut self,
+        buf: &[u8],
+        src_addr: &SocksAddr,
+        _dst_addr: &SocketAddr,
+    ) -> io::Result<usize> {
+        let frame = udp::encode_frame(self.session_id, src_addr, buf);
+        udp::send_frame(&self.conn, frame, self.over_stream).await?;
+        Ok(buf.len())
+    }
+
+    async fn close(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+// Demultiplexes the UDP frames received on the connection by session id, the
+// first frame of every session yields a new datagram transport. One-way
+// payload streams go to `uni_tx`.
+pub(super) async fn relay(
+    conn: quinn::Connection,
+    source: SocketAddr,
+    peer_identity: Option<String>,
+    alpn: Option<String>,
+    over_stream: bool,
+    transport_tx: Sender<AnyBaseInboundTransport>,
+    uni_tx: Option<Sender<udp::UniStream>>,
+) {
+    let mut frame_rx = udp::recv_frames(conn.clone(), uni_tx);
+    let mut sessions: HashMap<u32, Sender<Packet>> = HashMap::new();
+    while let Some(frame) = frame_rx.recv().await {
+        let (session_id, addr, payload) = match udp::decode_frame(frame) {
+            Ok(x) => x,
+            Err(e) => {
+                debug!("invalid quic udp frame from {}: {}", &source, e);
+                continue;
+            }
+        };
+        let packet = match sessions.get(&session_id) {
+            Some(packet_tx) => match packet_tx.try_send((addr, payload)) {
+                Ok(()) => continue,
+                Err(TrySendError::Full(_)) => {
+                    trace!("quic udp session {} is full, dropping packet", session_id);
+                    continue;
+                }
+                Err(TrySendError::Closed(packet)) => packet,
+            },
+            None => (addr, payload),
+        };
+        sessions.retain(|_, packet_tx| !packet_tx.is_closed());
+        let (packet_tx, packet_rx) = channel(*crate::option::UDP_UPLINK_CHANNEL_SIZE);
+        let _ = packet_tx.try_send(packet);
+        sessions.insert(session_id, packet_tx);
+        trace!("quic udp session {} from {}", session_id, &source);
+        let stream_id = Some(StreamId::U64(session_id as u64));
+        let sess = Session {
+            source,
+            peer_identity: peer_identity.clone(),
+            alpn: alpn.clone(),
+            stream_id,
+            ..Default::default()
+        };
+        let dgram = Datagram {
+            conn: conn.clone(),
+            session_id,
+            source: DatagramSource::new(source, stream_id),
+            over_stream,
+            packet_rx,
+        };
+        if transport_tx
+            .send(AnyBaseInboundTransport::Datagram(
+                Box::new(dgram),
+                Some(sess),
+            ))
+            .await
+            .is_err()
+        {
+            return;
+        }
+    }
+}
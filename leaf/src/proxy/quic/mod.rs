@@ -1,13 +1,68 @@
+use std::sync::Arc;
+use std::time::Duration;
 use std::{io, pin::Pin};
 
+use anyhow::{anyhow, Result};
 use futures::task::{Context, Poll};
 use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tracing::debug;
+
+use crate::app::stat_manager::{self, QuicPathStat};
 
 #[cfg(feature = "inbound-quic")]
 pub mod inbound;
 #[cfg(feature = "outbound-quic")]
 pub mod outbound;
 
+mod udp;
+
+/// Returns the congestion controller factory for the given name, defaults to BBR.
+pub(crate) fn congestion_controller_factory(
+    name: Option<&str>,
+) -> Result<Arc<dyn quinn::congestion::ControllerFactory + Send + Sync>> {
+    match name.unwrap_or("bbr") {
+        "bbr" => Ok(Arc::new(quinn::congestion::BbrConfig::default())),
+        "cubic" => Ok(Arc::new(quinn::congestion::CubicConfig::default())),
+        "newreno" => Ok(Arc::new(quinn::congestion::NewRenoConfig::default())),
+        x => Err(anyhow!("unknown congestion controller: {}", x)),
+    }
+}
+
+const PATH_STATS_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Samples the path statistics of the connection every 30 seconds until it's
+/// closed, logging them and publishing them to the stat manager under `tag`.
+pub(crate) fn spawn_path_stats(conn: quinn::Connection, tag: String) {
+    tokio::spawn(async move {
+        let remote_addr = conn.remote_address();
+        while tokio::time::timeout(PATH_STATS_INTERVAL, conn.closed())
+            .await
+            .is_err()
+        {
+            let path = conn.stats().path;
+            debug!(
+                "quic path [{}] {}: rtt {} ms, lost packets {}, congestion events {}, cwnd {}",
+                &tag,
+                &remote_addr,
+                path.rtt.as_millis(),
+                path.lost_packets,
+                path.congestion_events,
+                path.cwnd,
+            );
+            stat_manager::update_quic_path_stat(QuicPathStat {
+                tag: tag.clone(),
+                remote_addr,
+                rtt_ms: path.rtt.as_millis() as u64,
+                lost_packets: path.lost_packets,
+                congestion_events: path.congestion_events,
+                cwnd: path.cwnd,
+                update_time: 0,
+            });
+        }
+        stat_manager::remove_quic_path_stat(&tag, &remote_addr);
+    });
+}
+
 pub struct QuicProxyStream<R, W> {
     recv: R,
     send: W,
@@ -0,0 +1,148 @@
+use std::io;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use tokio::sync::mpsc::{channel, Receiver, Sender};
+use tracing::{debug, trace};
+
+use crate::common::udp_session::SessionMap;
+use crate::{proxy::*, session::SocksAddr};
+
+use super::super::udp;
+use super::stream::ConnSlot;
+
+type Packet = (SocksAddr, Bytes);
+
+// UDP sessions relayed over a connection, keyed by session id.
+pub(super) type Sessions = SessionMap<u32, Sender<Packet>>;
+
+// Dispatches the UDP frames received on the connection to their sessions.
+pub(super) fn spawn_demux(conn: quinn::Connection, sessions: Arc<Sessions>) {
+    tokio::spawn(async move {
+        let mut frame_rx = udp::recv_frames(conn, None);
+        while let Some(frame) = frame_rx.recv().await {
+            let (session_id, addr, payload) = match udp::decode_frame(frame) {
+                Ok(x) => x,
+                Err(e) => {
+                    debug!("invalid quic udp frame: {}", e);
+                    continue;
+                }
+            };
+            let packet_tx = sessions.lock().get(&session_id).cloned();
+            match packet_tx {
+                Some(packet_tx) => {
+                    if packet_tx.try_send((addr, payload)).is_err() {
+                        trace!("quic udp session {} is full, dropping packet", session_id);
+                    }
+                }
+                None => {
+                    trace!("unknown quic udp session {}", session_id);
+                }
+            }
+        }
+    });
+}
+
+// Unregisters the session once both halves are dropped.
+struct SessionGuard {
+    session_id: u32,
+    sessions: Arc<Sessions>,
+    _slot: ConnSlot,
+}
+
+impl Drop for SessionGuard {
+    fn drop(&mut self) {
+        self.sessions.remove(&self.session_id);
+    }
+}
+
+pub struct Datagram {
+    conn: quinn::Connection,
+    over_stream: bool,
+    packet_rx: Receiver<Packet>,
+    guard: Arc<SessionGuard>,
+}
+
+impl Datagram {
+    pub(super) fn new(
+        conn: quinn::Connection,
+        sessions: Arc<Sessions>,
+        slot: ConnSlot,
+        over_stream: bool,
+    ) -> io::Result<Self> {
+        let (packet_tx, packet_rx) = channel(*crate::option::UDP_DOWNLINK_CHANNEL_SIZE);
+        let session_id = sessions.register(packet_tx)?;
+        Ok(Self {
+            conn,
+            over_stream,
+            packet_rx,
+            guard: Arc::new(SessionGuard {
+                session_id,
+                sessions,
+                _slot: slot,
+            }),
+        })
+    }
+}
+
+impl OutboundDatagram for Datagram {
+    fn split(
+        self: Box<Self>,
+    ) -> (
+        Box<dyn OutboundDatagramRecvHalf>,
+        Box<dyn OutboundDatagramSendHalf>,
+    ) {
+        (
+            Box::new(DatagramRecvHalf {
+                packet_rx: self.packet_rx,
+                _guard: self.guard.clone(),
+            }),
+            Box::new(DatagramSendHalf {
+                conn: self.conn,
+                over_stream: self.over_stream,
+                guard: self.guard,
+            }),
+        )
+    }
+}
+
+struct DatagramRecvHalf {
+    packet_rx: Receiver<Packet>,
+    _guard: Arc<SessionGuard>,
+}
+
+#[async_trait]
+impl OutboundDatagramRecvHalf for DatagramRecvHalf {
+    async fn recv_from(&mut self, buf: &mut [u8]) -> io::Result<(usize, SocksAddr)> {
+        let (src_addr, payload) = self
+            .packet_rx
+            .recv()
+            .await
+            .ok_or_else(|| io::Error::other("quic connection closed"))?;
+        if buf.len() < payload.len() {
+            return Err(io::Error::other("Small buffer"));
+        }
+        buf[..payload.len()].copy_from_slice(&payload);
+        Ok((payload.len(), src_addr))
+    }
+}
+
+struct DatagramSendHalf {
+    conn: quinn::Connection,
+    over_stream: bool,
+    guard: Arc<SessionGuard>,
+}
+
+#[async_trait]
+impl OutboundDatagramSendHalf for DatagramSendHalf {
+    async fn send_to(&mut self, buf: &[u8], dst_addr: &SocksAddr) -> io::Result<usize> {
+        let frame = udp::encode_frame(self.guard.session_id, dst_addr, buf);
+        udp::send_frame(&self.conn, frame, self.over_stream).await?;
+        Ok(buf.len())
+    }
+
+    async fn close(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
@@ -0,0 +1,171 @@
+use std::io;
+use std::pin::Pin;
+
+use futures::future::Shared;
+use futures::ready;
+use futures::task::{Context, Poll};
+use futures::Future;
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::sync::oneshot;
+use tracing::debug;
+
+use super::stream::PooledSendStream;
+
+/// Resolves to whether the server accepted the 0-RTT data of a connection.
+pub(super) type ZeroRtt = Shared<quinn::ZeroRttAccepted>;
+
+type Reopened = Option<(quinn::SendStream, quinn::RecvStream)>;
+
+// A stream opened before the server accepted or rejected the 0-RTT data of
+// its connection. It's used optimistically, and everything written is kept
+// until the outcome is known. If the early data is rejected the stream is
+// reopened on the then established connection and the data is replayed.
+pub struct EarlyStream {
+    recv: quinn::RecvStream,
+    send: PooledSendStream,
+    written: Vec<u8>,
+    replayed: usize,
+    resolved: Option<oneshot::Receiver<Reopened>>,
+}
+
+impl EarlyStream {
+    pub(super) fn new(
+        conn: quinn::Connection,
+        zero_rtt: ZeroRtt,
+        send: PooledSendStream,
+        recv: quinn::RecvStream,
+    ) -> Self {
+        let (resolved_tx, resolved_rx) = oneshot::channel();
+        tokio::spawn(async move {
+            if zero_rtt.await {
+                let _ = resolved_tx.send(None);
+                return;
+            }
+            match conn.open_bi().await {
+                Ok(streams) => {
+                    let _ = resolved_tx.send(Some(streams));
+                }
+                Err(e) => {
+                    debug!("reopen quic stream after 0-rtt rejection failed: {}", e);
+                }
+            }
+        });
+        Self {
+            recv,
+            send,
+            written: Vec::new(),
+            replayed: 0,
+            resolved: Some(resolved_rx),
+        }
+    }
+
+    fn is_resolved(&self) -> bool {
+        self.resolved.is_none() && self.replayed == self.written.len()
+    }
+
+    // Ready once the early data is accepted, or rejected and replayed on the
+    // new stream.
+    fn poll_resolved(&mut self, cx: &mut Context) -> Poll<io::Result<()>> {
+        if let Some(resolved) = self.resolved.as_mut() {
+            let reopened = ready!(Pin::new(resolved).poll(cx));
+            self.resolved = None;
+            match reopened {
+                Ok(None) => self.written = Vec::new(),
+                Ok(Some((send, recv))) => {
+                    debug!(
+                        "quic 0-rtt rejected, replaying {} bytes",
+                        self.written.len()
+                    );
+                    self.send.inner = send;
+                    self.recv = recv;
+                }
+                Err(_) => {
+                    self.written = Vec::new();
+                    return Poll::Ready(Err(io::Error::other(
+                        "quic 0-rtt rejected and reopening the stream failed",
+                    )));
+                }
+            }
+        }
+        while self.replayed < self.written.len() {
+            let n =
+                ready!(Pin::new(&mut self.send).poll_write(cx, &self.written[self.replayed..]))?;
+            self.replayed += n;
+        }
+        if !self.written.is_empty() {
+            self.written = Vec::new();
+            self.replayed = 0;
+        }
+        Poll::Ready(Ok(()))
+    }
+}
+
+// While unresolved, an error from the 0-RTT stream may just be the rejection,
+// so it's not reported and the caller waits for the outcome instead.
+impl AsyncRead for EarlyStream {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context,
+        buf: &mut ReadBuf,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        if !this.is_resolved() {
+            match this.poll_resolved(cx) {
+                Poll::Ready(res) => res?,
+                Poll::Pending if this.resolved.is_some() => {
+                    return match ready!(Pin::new(&mut this.recv).poll_read(cx, buf)) {
+                        Ok(()) => Poll::Ready(Ok(())),
+                        Err(_) => Poll::Pending,
+                    };
+                }
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+        Pin::new(&mut this.recv).poll_read(cx, buf)
+    }
+}
+
+impl AsyncWrite for EarlyStream {
+    fn poll_write(self: Pin<&mut Self>, cx: &mut Context, buf: &[u8]) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        if !this.is_resolved() {
+            match this.poll_resolved(cx) {
+                Poll::Ready(res) => res?,
+                Poll::Pending if this.resolved.is_some() => {
+                    let n = match ready!(Pin::new(&mut this.send).poll_write(cx, buf)) {
+                        Ok(n) => n,
+                        Err(_) => buf.len(),
+                    };
+                    this.written.extend_from_slice(&buf[..n]);
+                    return Poll::Ready(Ok(n));
+                }
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+        Pin::new(&mut this.send).poll_write(cx, buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        if !this.is_resolved() {
+            match this.poll_resolved(cx) {
+                Poll::Ready(res) => res?,
+                Poll::Pending if this.resolved.is_some() => {
+                    return match ready!(Pin::new(&mut this.send).poll_flush(cx)) {
+                        Ok(()) => Poll::Ready(Ok(())),
+                        Err(_) => Poll::Pending,
+                    };
+                }
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+        Pin::new(&mut this.send).poll_flush(cx)
+    }
+
+    // The stream is only finished once it's known which one carries the data.
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        ready!(this.poll_resolved(cx))?;
+        Pin::new(&mut this.send).poll_shutdown(cx)
+    }
+}
@@ -1,3 +1,5 @@
+mod datagram;
+mod early;
 mod stream;
 
 pub use stream::Handler as StreamHandler;
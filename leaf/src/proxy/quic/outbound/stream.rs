@@ -1,81 +1,255 @@
-use std::fs;
 use std::io;
 use std::net::SocketAddr;
-use std::path::Path;
-use std::sync::Arc;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex as StdMutex};
+use std::time::Instant;
 
 use anyhow::{anyhow, Result};
 use async_trait::async_trait;
-use futures::TryFutureExt;
-use rustls::pki_types::CertificateDer;
-use rustls_pemfile::certs;
-use tokio::sync::RwLock;
+use futures::future::Either;
+use futures::task::{Context, Poll};
+use futures::{FutureExt, TryFutureExt};
+use tokio::io::AsyncWrite;
+use tokio::sync::{Mutex, RwLock};
 use tokio::time::{timeout, Duration};
 use tracing::{debug, trace, Instrument};
 
-use crate::{app::SyncDnsClient, proxy::*, session::Session};
+use crate::{
+    app::SyncDnsClient,
+    common::cert::{load_certs, root_verifier, FingerprintVerifier, SpkiPinVerifier},
+    proxy::*,
+    session::Session,
+};
 
+use super::datagram::{self, Datagram, Sessions};
+use super::early::{EarlyStream, ZeroRtt};
 use super::QuicProxyStream;
 
+// Pooled connections without any open stream for this long are closed.
+const CONN_IDLE_TIMEOUT: Duration = Duration::from_secs(300);
+
+// The most connections kept in the pool, once they are all full new streams
+// go to the least loaded one.
+const MAX_POOLED_CONNS: usize = 4;
+
+struct ConnStats {
+    streams: AtomicUsize,
+    last_active: StdMutex<Instant>,
+}
+
+// A stream or UDP session slot of a pooled connection, released on drop.
+pub(super) struct ConnSlot(Arc<ConnStats>);
+
+impl Drop for ConnSlot {
+    fn drop(&mut self) {
+        self.0.streams.fetch_sub(1, Ordering::Relaxed);
+        *self.0.last_active.lock().unwrap() = Instant::now();
+    }
+}
+
+struct PooledConnection {
+    conn: quinn::Connection,
+    stats: Arc<ConnStats>,
+    sessions: Arc<Sessions>,
+    zero_rtt: Option<ZeroRtt>,
+}
+
+impl PooledConnection {
+    fn new(conn: quinn::Connection, zero_rtt: Option<ZeroRtt>) -> Self {
+        let sessions = Arc::new(Sessions::default());
+        datagram::spawn_demux(conn.clone(), sessions.clone());
+        if let Some(zero_rtt) = zero_rtt.clone() {
+            // Also resolves the outcome when no stream waits for it.
+            tokio::spawn(async move {
+                if !zero_rtt.await {
+                    debug!("quic 0-rtt rejected");
+                }
+            });
+        }
+        Self {
+            conn,
+            stats: Arc::new(ConnStats {
+                streams: AtomicUsize::new(0),
+                last_active: StdMutex::new(Instant::now()),
+            }),
+            sessions,
+            zero_rtt,
+        }
+    }
+
+    fn new_slot(&self) -> ConnSlot {
+        self.stats.streams.fetch_add(1, Ordering::Relaxed);
+        *self.stats.last_active.lock().unwrap() = Instant::now();
+        ConnSlot(self.stats.clone())
+    }
+
+    fn reserve_stream(&self) -> StreamSlot {
+        StreamSlot {
+            conn: self.conn.clone(),
+            zero_rtt: self.zero_rtt.clone(),
+            slot: self.new_slot(),
+        }
+    }
+
+    fn new_datagram(&self, over_stream: bool) -> io::Result<Datagram> {
+        Datagram::new(
+            self.conn.clone(),
+            self.sessions.clone(),
+            self.new_slot(),
+            over_stream,
+        )
+    }
+
+    fn is_full(&self, max_streams: usize) -> bool {
+        self.stats.streams.load(Ordering::Relaxed) >= max_streams
+    }
+
+    fn is_idle(&self) -> bool {
+        self.stats.streams.load(Ordering::Relaxed) == 0
+            && self.stats.last_active.lock().unwrap().elapsed() > CONN_IDLE_TIMEOUT
+    }
+}
+
+// A slot reserved on a pooled connection for a stream which is opened after
+// the pool lock is released.
+struct StreamSlot {
+    conn: quinn::Connection,
+    zero_rtt: Option<ZeroRtt>,
+    slot: ConnSlot,
+}
+
+impl StreamSlot {
+    fn into_stream(self, send: quinn::SendStream, recv: quinn::RecvStream) -> AnyStream {
+        let send = PooledSendStream {
+            inner: send,
+            _slot: self.slot,
+        };
+        match self.zero_rtt.filter(|z| z.peek().is_none()) {
+            Some(zero_rtt) => Box::new(EarlyStream::new(self.conn, zero_rtt, send, recv)),
+            None => Box::new(QuicProxyStream { recv, send }),
+        }
+    }
+}
+
+fn log_pool_stats(conns: &[PooledConnection]) {
+    let streams: usize = conns
+        .iter()
+        .map(|c| c.stats.streams.load(Ordering::Relaxed))
+        .sum();
+    debug!(
+        "quic pool: {} connections, {} streams",
+        conns.len(),
+        streams
+    );
+}
+
+// A send stream which releases its slot in the pooled connection on drop.
+pub struct PooledSendStream {
+    pub(super) inner: quinn::SendStream,
+    _slot: ConnSlot,
+}
+
+impl AsyncWrite for PooledSendStream {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.inner).poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+}
+
 struct Manager {
+    tag: String,
     address: String,
     port: u16,
     server_name: Option<String>,
     dns_client: SyncDnsClient,
     client_config: quinn::ClientConfig,
-    connections: RwLock<Vec<quinn::Connection>>,
+    max_sessions_per_connection: usize,
+    udp_over_stream: bool,
+    connections: RwLock<Vec<PooledConnection>>,
+    zero_rtt: bool,
+    reconnect_on_network_change: bool,
+    dial_lock: Mutex<()>,
 }
 
 impl Manager {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
+        tag: String,
         address: String,
         port: u16,
         server_name: Option<String>,
         alpns: Vec<String>,
         certificate: Option<String>,
         certificate_key: Option<String>,
+        congestion_controller: Option<String>,
+        max_sessions_per_connection: Option<u32>,
+        udp_over_stream: bool,
+        certificate_fingerprint: Option<String>,
+        certificate_pins: Vec<String>,
+        pin_only: bool,
+        allow_name_mismatch: bool,
+        zero_rtt: bool,
+        reconnect_on_network_change: bool,
         dns_client: SyncDnsClient,
-    ) -> Self {
+    ) -> Result<Self> {
         let mut roots = rustls::RootCertStore::empty();
-        if let Some(cert_path) = certificate.as_ref() {
-            if cert_path.contains("-----BEGIN") {
-                let mut reader = io::BufReader::new(cert_path.as_bytes());
-                for cert in certs(&mut reader) {
-                    roots.add(cert.unwrap()).unwrap();
-                }
-            } else {
-                match fs::read(cert_path) {
-                    Ok(cert) => {
-                        match Path::new(&cert_path).extension().map(|ext| ext.to_str()) {
-                            Some(Some("der")) => {
-                                roots.add(CertificateDer::from(cert)).unwrap(); // FIXME
-                            }
-                            _ => {
-                                let mut reader = io::BufReader::new(&*cert);
-                                for cert in certs(&mut reader) {
-                                    roots.add(cert.unwrap()).unwrap();
-                                }
-                            }
-                        }
-                    }
-                    Err(e) => {
-                        panic!("read certificate {} failed: {}", cert_path, e);
-                    }
-                }
+        if let Some(cert) = certificate.as_ref() {
+            for cert in load_certs(cert)? {
+                roots.add(cert)?;
             }
         } else {
             roots.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned());
         }
 
         #[cfg(feature = "rustls-tls-aws-lc")]
-        let provider = rustls::crypto::aws_lc_rs::default_provider().into();
+        let provider: Arc<rustls::crypto::CryptoProvider> =
+            rustls::crypto::aws_lc_rs::default_provider().into();
         #[cfg(not(feature = "rustls-tls-aws-lc"))]
-        let provider = rustls::crypto::ring::default_provider().into();
+        let provider: Arc<rustls::crypto::CryptoProvider> =
+            rustls::crypto::ring::default_provider().into();
 
-        let builder = rustls::ClientConfig::builder_with_provider(provider)
+        let builder = rustls::ClientConfig::builder_with_provider(provider.clone())
             .with_safe_default_protocol_versions()
-            .unwrap()
-            .with_root_certificates(roots);
+            .unwrap();
+        let builder = if let Some(fingerprint) = certificate_fingerprint.as_deref() {
+            builder
+                .dangerous()
+                .with_custom_certificate_verifier(Arc::new(FingerprintVerifier::new(
+                    fingerprint,
+                    provider,
+                )))
+        } else if !certificate_pins.is_empty() {
+            let inner = if pin_only {
+                None
+            } else {
+                Some(root_verifier(roots, provider.clone(), allow_name_mismatch)?)
+            };
+            builder
+                .dangerous()
+                .with_custom_certificate_verifier(Arc::new(SpkiPinVerifier::new(
+                    certificate_pins,
+                    inner,
+                    provider,
+                )))
+        } else if allow_name_mismatch {
+            builder
+                .dangerous()
+                .with_custom_certificate_verifier(root_verifier(roots, provider, true)?)
+        } else {
+            builder.with_root_certificates(roots)
+        };
 
         let mut client_crypto = if let Some(_certificate) = certificate {
             if let Some(_certificate_key) = certificate_key {
@@ -90,6 +264,12 @@ impl Manager {
         for alpn in alpns {
             client_crypto.alpn_protocols.push(alpn.as_bytes().to_vec());
         }
+        // Session tickets are kept in the default in-memory store of the
+        // client config, keyed by server name.
+        client_crypto.enable_early_data = zero_rtt;
+        if let Some(key_log) = crate::common::key_log::key_log() {
+            client_crypto.key_log = key_log;
+        }
 
         let mut client_config = quinn::ClientConfig::new(Arc::new(
             quinn::crypto::rustls::QuicClientConfig::try_from(client_crypto).unwrap(),
@@ -100,53 +280,206 @@ impl Manager {
             300_000,
         ))));
         transport_config.keep_alive_interval(Some(Duration::from_secs(10)));
-        transport_config
-            .congestion_controller_factory(Arc::new(quinn::congestion::BbrConfig::default()));
+        transport_config.congestion_controller_factory(
+            crate::proxy::quic::congestion_controller_factory(congestion_controller.as_deref())?,
+        );
+        if udp_over_stream {
+            // Tells the server not to send DATAGRAMs.
+            transport_config.datagram_receive_buffer_size(None);
+        }
         client_config.transport_config(Arc::new(transport_config));
 
-        Manager {
+        Ok(Manager {
+            tag,
             address,
             port,
             server_name,
             dns_client,
             client_config,
+            max_sessions_per_connection: max_sessions_per_connection.unwrap_or(32) as usize,
+            udp_over_stream,
             connections: RwLock::new(Vec::new()),
-        }
+            zero_rtt,
+            reconnect_on_network_change,
+            dial_lock: Mutex::new(()),
+        })
     }
 }
 
+// Moves the connection to a new socket on every network change so it migrates
+// instead of retransmitting on a dead path, or closes it with `reconnect` for
+// servers which disable migration.
+fn spawn_rebind(endpoint: quinn::Endpoint, conn: quinn::Connection, reconnect: bool) {
+    tokio::spawn(async move {
+        let mut network_change = crate::proxy::subscribe_network_change();
+        loop {
+            let changed = Box::pin(network_change.changed());
+            match futures::future::select(changed, Box::pin(conn.closed())).await {
+                Either::Left((Ok(()), _)) => {}
+                _ => break,
+            }
+            if reconnect {
+                debug!(
+                    "network changed, closing quic connection to {}",
+                    conn.remote_address()
+                );
+                conn.close(quinn::VarInt::from_u32(0), b"network changed");
+                break;
+            }
+            let res =
+                match crate::proxy::new_udp_socket(&crate::option::UNSPECIFIED_BIND_ADDR).await {
+                    Ok(socket) => socket.into_std().and_then(|socket| endpoint.rebind(socket)),
+                    Err(e) => Err(e),
+                };
+            match res {
+                Ok(()) => debug!(
+                    "network changed, rebound quic connection to {}",
+                    conn.remote_address()
+                ),
+                Err(e) => debug!("rebind quic endpoint failed: {}", e),
+            }
+        }
+    });
+}
+
+fn evict_connections(conns: &mut Vec<PooledConnection>) {
+    conns.retain(|c| {
+        if let Some(reason) = c.conn.close_reason() {
+            debug!("evict closed quic connection: {}", reason);
+            return false;
+        }
+        if c.is_idle() {
+            debug!("evict idle quic connection");
+            c.conn.close(quinn::VarInt::from_u32(0), b"idle");
+            return false;
+        }
+        true
+    });
+}
+
 impl Manager {
-    pub async fn new_stream(
-        &self,
-    ) -> Result<QuicProxyStream<quinn::RecvStream, quinn::SendStream>> {
-        let dial_timeout = Duration::from_secs(*crate::option::OUTBOUND_DIAL_TIMEOUT);
-        let start = std::time::Instant::now();
+    // Picks the connection for a new stream or UDP session, one below the
+    // session cap or, once the pool is full, the least loaded one. Returns
+    // `None` if a new connection should be dialed.
+    fn pick_connection<'a>(&self, conns: &'a [PooledConnection]) -> Option<&'a PooledConnection> {
+        if let Some(conn) = conns
+            .iter()
+            .find(|c| !c.is_full(self.max_sessions_per_connection))
         {
-            let mut conns = self.connections.write().await;
-            let idx = 0usize;
-            while idx < conns.len() {
-                let conn = &conns[idx];
-                match timeout(dial_timeout, conn.open_bi()).await {
-                    Ok(Ok((send, recv))) => {
-                        trace!(
-                            "opened stream on existing connection (rtt {} ms) in {} ms",
-                            conn.rtt().as_millis(),
-                            start.elapsed().as_millis(),
-                        );
-                        return Ok(QuicProxyStream { recv, send });
-                    }
-                    Ok(Err(e)) => {
-                        debug!("open stream failed: {}", e);
-                        conns.swap_remove(idx);
-                    }
-                    Err(_) => {
-                        debug!("open stream timed out");
-                        conns.swap_remove(idx);
-                    }
+            return Some(conn);
+        }
+        if conns.len() < MAX_POOLED_CONNS {
+            return None;
+        }
+        conns
+            .iter()
+            .min_by_key(|c| c.stats.streams.load(Ordering::Relaxed))
+    }
+
+    // Opens a stream on a pooled connection, evicting closed and idle
+    // connections along the way. The slot is reserved under the pool lock and
+    // the stream opened after releasing it, so callers don't wait on each
+    // other's handshakes.
+    async fn open_pooled_stream(&self, dial_timeout: Duration) -> Option<AnyStream> {
+        loop {
+            let reserved = {
+                let mut conns = self.connections.write().await;
+                evict_connections(&mut conns);
+                let reserved = self.pick_connection(&conns)?.reserve_stream();
+                log_pool_stats(&conns);
+                reserved
+            };
+            match timeout(dial_timeout, reserved.conn.open_bi()).await {
+                Ok(Ok((send, recv))) => {
+                    trace!(
+                        "opened stream on existing connection (rtt {} ms)",
+                        reserved.conn.rtt().as_millis(),
+                    );
+                    return Some(reserved.into_stream(send, recv));
                 }
+                Ok(Err(e)) => debug!("open stream failed: {}", e),
+                Err(_) => debug!("open stream timed out"),
             }
+            let id = reserved.conn.stable_id();
+            self.connections
+                .write()
+                .await
+                .retain(|c| c.conn.stable_id() != id);
+        }
+    }
+
+    pub async fn new_stream(&self) -> Result<AnyStream> {
+        let dial_timeout = Duration::from_secs(*crate::option::OUTBOUND_DIAL_TIMEOUT);
+        let start = Instant::now();
+        if let Some(stream) = self.open_pooled_stream(dial_timeout).await {
+            trace!("opened pooled stream in {} ms", start.elapsed().as_millis());
+            return Ok(stream);
         }
 
+        // Only one task dials at a time, the others wait and then reuse the
+        // new connection if it still has room.
+        let _dial_guard = self.dial_lock.lock().await;
+        if let Some(stream) = self.open_pooled_stream(dial_timeout).await {
+            trace!("opened pooled stream in {} ms", start.elapsed().as_millis());
+            return Ok(stream);
+        }
+
+        let (conn, zero_rtt) = self.dial(dial_timeout).await?;
+        let (send, recv) = match timeout(dial_timeout, conn.open_bi()).await {
+            Ok(Ok(x)) => x,
+            Ok(Err(e)) => return Err(e.into()),
+            Err(_) => return Err(anyhow!("open quic stream timed out")),
+        };
+
+        let pooled = PooledConnection::new(conn, zero_rtt);
+        let stream = pooled.reserve_stream().into_stream(send, recv);
+        let mut conns = self.connections.write().await;
+        conns.push(pooled);
+
+        trace!("opened quic stream on new connection",);
+        log_pool_stats(&conns);
+
+        Ok(stream)
+    }
+
+    // Opens a UDP session on a pooled connection.
+    async fn open_pooled_datagram(&self) -> io::Result<Option<Datagram>> {
+        let mut conns = self.connections.write().await;
+        evict_connections(&mut conns);
+        let Some(conn) = self.pick_connection(&conns) else {
+            return Ok(None);
+        };
+        let dgram = conn.new_datagram(self.udp_over_stream)?;
+        log_pool_stats(&conns);
+        Ok(Some(dgram))
+    }
+
+    pub async fn new_datagram(&self) -> Result<Datagram> {
+        if let Some(dgram) = self.open_pooled_datagram().await? {
+            return Ok(dgram);
+        }
+        let _dial_guard = self.dial_lock.lock().await;
+        if let Some(dgram) = self.open_pooled_datagram().await? {
+            return Ok(dgram);
+        }
+
+        let dial_timeout = Duration::from_secs(*crate::option::OUTBOUND_DIAL_TIMEOUT);
+        let (conn, zero_rtt) = self.dial(dial_timeout).await?;
+        let pooled = PooledConnection::new(conn, zero_rtt);
+        let dgram = pooled.new_datagram(self.udp_over_stream)?;
+        let mut conns = self.connections.write().await;
+        conns.push(pooled);
+
+        trace!("opened quic udp session on new connection");
+        log_pool_stats(&conns);
+
+        Ok(dgram)
+    }
+
+    // Connects to the server, in 0-RTT if enabled and there's a session ticket
+    // for it, the returned future then resolves to whether the server accepted
+    // the early data.
+    async fn dial(&self, dial_timeout: Duration) -> Result<(quinn::Connection, Option<ZeroRtt>)> {
         // FIXME A better indicator.
         let socket = self
             .new_udp_socket(&crate::option::UNSPECIFIED_BIND_ADDR)
@@ -182,6 +515,23 @@ impl Manager {
                     continue;
                 }
             };
+            let connecting = if self.zero_rtt {
+                match connecting.into_0rtt() {
+                    Ok((conn, accepted)) => {
+                        trace!("connecting quic {} in 0-rtt", &connect_addr);
+                        super::super::spawn_path_stats(conn.clone(), self.tag.clone());
+                        spawn_rebind(
+                            endpoint.clone(),
+                            conn.clone(),
+                            self.reconnect_on_network_change,
+                        );
+                        return Ok((conn, Some(accepted.shared())));
+                    }
+                    Err(connecting) => connecting,
+                }
+            } else {
+                connecting
+            };
             let conn = match timeout(dial_timeout, connecting).await {
                 Ok(Ok(c)) => c,
                 Ok(Err(e)) => {
@@ -193,25 +543,13 @@ impl Manager {
                     continue;
                 }
             };
-            let (send, recv) = match timeout(dial_timeout, conn.open_bi()).await {
-                Ok(Ok(x)) => x,
-                Ok(Err(e)) => {
-                    last_err = Some(e.into());
-                    continue;
-                }
-                Err(_) => {
-                    last_err = Some(anyhow!("open quic stream timed out"));
-                    continue;
-                }
-            };
-
-            let mut conns = self.connections.write().await;
-            conns.push(conn);
-            conns.truncate(4);
-
-            trace!("opened quic stream on new connection",);
-
-            return Ok(QuicProxyStream { recv, send });
+            super::super::spawn_path_stats(conn.clone(), self.tag.clone());
+            spawn_rebind(
+                endpoint.clone(),
+                conn.clone(),
+                self.reconnect_on_network_change,
+            );
+            return Ok((conn, None));
         }
 
         Err(last_err.unwrap_or_else(|| anyhow!("connect quic failed")))
@@ -225,31 +563,50 @@ pub struct Handler {
 }
 
 impl Handler {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
+        tag: String,
         address: String,
         port: u16,
         server_name: Option<String>,
         alpns: Vec<String>,
         certificate: Option<String>,
         certificate_key: Option<String>,
+        congestion_controller: Option<String>,
+        max_sessions_per_connection: Option<u32>,
+        udp_over_stream: bool,
+        certificate_fingerprint: Option<String>,
+        certificate_pins: Vec<String>,
+        pin_only: bool,
+        allow_name_mismatch: bool,
+        zero_rtt: bool,
+        reconnect_on_network_change: bool,
         dns_client: SyncDnsClient,
-    ) -> Self {
-        Self {
+    ) -> Result<Self> {
+        Ok(Self {
             manager: Manager::new(
+                tag,
                 address,
                 port,
                 server_name,
                 alpns,
                 certificate,
                 certificate_key,
+                congestion_controller,
+                max_sessions_per_connection,
+                udp_over_stream,
+                certificate_fingerprint,
+                certificate_pins,
+                pin_only,
+                allow_name_mismatch,
+                zero_rtt,
+                reconnect_on_network_change,
                 dns_client,
-            ),
-        }
+            )?,
+        })
     }
 
-    pub async fn new_stream(
-        &self,
-    ) -> io::Result<QuicProxyStream<quinn::RecvStream, quinn::SendStream>> {
+    pub async fn new_stream(&self) -> io::Result<AnyStream> {
         self.manager
             .new_stream()
             .await
@@ -272,10 +629,212 @@ impl OutboundStreamHandler for Handler {
         _stream: Option<AnyStream>,
     ) -> io::Result<AnyStream> {
         tracing::trace!("handling outbound stream");
-        Ok(Box::new(
-            self.new_stream()
-                .instrument(tracing::Span::current())
-                .await?,
-        ))
+        self.new_stream().instrument(tracing::Span::current()).await
+    }
+}
+
+#[async_trait]
+impl OutboundDatagramHandler for Handler {
+    fn connect_addr(&self) -> OutboundConnect {
+        OutboundConnect::Unknown
+    }
+
+    fn transport_type(&self) -> DatagramTransportType {
+        if self.manager.udp_over_stream {
+            DatagramTransportType::Reliable
+        } else {
+            DatagramTransportType::Unreliable
+        }
+    }
+
+    async fn handle<'a>(
+        &'a self,
+        _sess: &'a Session,
+        _transport: Option<AnyOutboundTransport>,
+    ) -> io::Result<AnyOutboundDatagram> {
+        tracing::trace!("handling outbound datagram");
+        let dgram = self
+            .manager
+            .new_datagram()
+            .instrument(tracing::Span::current())
+            .await
+            .map_err(|e| io::Error::other(format!("new quic udp session failed: {}", e)))?;
+        Ok(Box::new(dgram))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use protobuf::MessageField;
+
+    use super::*;
+    use crate::app::dns::DnsClient;
+
+    // A QUIC server keeping the connections it accepts, returns its port, its
+    // certificate and the number of connections accepted.
+    async fn serve() -> (u16, String, Arc<AtomicUsize>) {
+        let rcgen::CertifiedKey { cert, key_pair } =
+            rcgen::generate_simple_self_signed(vec!["localhost".to_string()]).unwrap();
+        #[cfg(feature = "rustls-tls-aws-lc")]
+        let provider: Arc<rustls::crypto::CryptoProvider> =
+            rustls::crypto::aws_lc_rs::default_provider().into();
+        #[cfg(not(feature = "rustls-tls-aws-lc"))]
+        let provider: Arc<rustls::crypto::CryptoProvider> =
+            rustls::crypto::ring::default_provider().into();
+        let crypto = rustls::ServerConfig::builder_with_provider(provider)
+            .with_safe_default_protocol_versions()
+            .unwrap()
+            .with_no_client_auth()
+            .with_single_cert(
+                vec![cert.der().clone()],
+                rustls::pki_types::PrivateKeyDer::Pkcs8(key_pair.serialize_der().into()),
+            )
+            .unwrap();
+        let server_config = quinn::ServerConfig::with_crypto(Arc::new(
+            quinn::crypto::rustls::QuicServerConfig::try_from(crypto).unwrap(),
+        ));
+        let endpoint =
+            quinn::Endpoint::server(server_config, "127.0.0.1:0".parse().unwrap()).unwrap();
+        let port = endpoint.local_addr().unwrap().port();
+        let accepted = Arc::new(AtomicUsize::new(0));
+        let accepted_c = accepted.clone();
+        tokio::spawn(async move {
+            let mut conns = Vec::new();
+            while let Some(incoming) = endpoint.accept().await {
+                if let Ok(conn) = incoming.await {
+                    accepted_c.fetch_add(1, Ordering::Relaxed);
+                    conns.push(conn);
+                }
+            }
+        });
+        (port, cert.pem(), accepted)
+    }
+
+    fn new_manager(
+        port: u16,
+        certificate: String,
+        max_sessions_per_connection: Option<u32>,
+    ) -> Manager {
+        let mut dns = crate::config::Dns::new();
+        dns.servers.push("1.1.1.1".to_string());
+        let dns_client = Arc::new(RwLock::new(
+            DnsClient::new(&MessageField::some(dns)).unwrap(),
+        ));
+        Manager::new(
+            "quic".to_string(),
+            "127.0.0.1".to_string(),
+            port,
+            Some("localhost".to_string()),
+            Vec::new(),
+            Some(certificate),
+            None,
+            None,
+            max_sessions_per_connection,
+            false,
+            None,
+            Vec::new(),
+            false,
+            false,
+            false,
+            false,
+            dns_client,
+        )
+        .unwrap()
+    }
+
+    async fn conn_streams(manager: &Manager) -> Vec<usize> {
+        manager
+            .connections
+            .read()
+            .await
+            .iter()
+            .map(|c| c.stats.streams.load(Ordering::Relaxed))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_streams_per_conn() {
+        let (port, certificate, accepted) = serve().await;
+        let manager = new_manager(port, certificate, None);
+        assert_eq!(manager.max_sessions_per_connection, 32);
+
+        let mut streams = Vec::new();
+        for _ in 0..32 {
+            streams.push(manager.new_stream().await.unwrap());
+        }
+        assert_eq!(conn_streams(&manager).await, vec![32]);
+        // A full connection makes a new one.
+        streams.push(manager.new_stream().await.unwrap());
+        assert_eq!(conn_streams(&manager).await, vec![32, 1]);
+
+        // A closed stream frees its slot for the next one.
+        drop(streams.remove(0));
+        assert_eq!(conn_streams(&manager).await, vec![31, 1]);
+        streams.push(manager.new_stream().await.unwrap());
+        assert_eq!(conn_streams(&manager).await, vec![32, 1]);
+        let _dgram = manager.new_datagram().await.unwrap();
+        assert_eq!(conn_streams(&manager).await, vec![32, 2]);
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(accepted.load(Ordering::Relaxed), 2);
+    }
+
+    #[tokio::test]
+    async fn test_evict_idle_connections() {
+        let (port, certificate, accepted) = serve().await;
+        let manager = new_manager(port, certificate, Some(1));
+        let busy = manager.new_stream().await.unwrap();
+        drop(manager.new_stream().await.unwrap());
+        assert_eq!(conn_streams(&manager).await, vec![1, 0]);
+
+        // Both connections last active past the idle timeout, only the one
+        // without streams goes.
+        let Some(past) = Instant::now().checked_sub(CONN_IDLE_TIMEOUT + Duration::from_secs(1))
+        else {
+            return;
+        };
+        let idle_conn = {
+            let conns = manager.connections.read().await;
+            for c in conns.iter() {
+                *c.stats.last_active.lock().unwrap() = past;
+            }
+            conns[1].conn.clone()
+        };
+        let _stream = manager.new_stream().await.unwrap();
+        assert!(idle_conn.close_reason().is_some());
+        assert_eq!(conn_streams(&manager).await, vec![1, 1]);
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(accepted.load(Ordering::Relaxed), 3);
+        drop(busy);
+    }
+
+    #[tokio::test]
+    async fn test_pool_cap() {
+        let (port, certificate, accepted) = serve().await;
+        let manager = new_manager(port, certificate, Some(1));
+        let mut streams = Vec::new();
+        for _ in 0..MAX_POOLED_CONNS {
+            streams.push(manager.new_stream().await.unwrap());
+        }
+        assert_eq!(conn_streams(&manager).await, vec![1; MAX_POOLED_CONNS]);
+        // A full pool puts new streams on the least loaded connection.
+        streams.push(manager.new_stream().await.unwrap());
+        let _dgram = manager.new_datagram().await.unwrap();
+        let mut expected = vec![1; MAX_POOLED_CONNS];
+        expected[0] = 2;
+        expected[1] = 2;
+        assert_eq!(conn_streams(&manager).await, expected);
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(accepted.load(Ordering::Relaxed), MAX_POOLED_CONNS);
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_dials_share_conn() {
+        let (port, certificate, accepted) = serve().await;
+        let manager = new_manager(port, certificate, None);
+        let streams = futures::future::join_all((0..8).map(|_| manager.new_stream())).await;
+        assert!(streams.iter().all(|s| s.is_ok()));
+        assert_eq!(conn_streams(&manager).await, vec![8]);
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(accepted.load(Ordering::Relaxed), 1);
     }
 }
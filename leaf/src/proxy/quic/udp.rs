@@ -0,0 +1,146 @@
+//! UDP relay framing shared by the QUIC inbound and outbound.
+//!
+//! Every UDP packet is carried in one frame:
+//!
+//! ```text
+//! +------------+---------------------+---------+
+//! | session id | address (port last) | payload |
+//! +------------+---------------------+---------+
+//! |     4      |      variable       |   ...   |
+//! +------------+---------------------+---------+
+//! ```
+//!
+//! The address is the destination on the uplink and the source on the
+//! downlink. Frames are sent as QUIC DATAGRAMs, frames exceeding the maximum
+//! datagram size the peer accepts go on a unidirectional stream of their own.
+//!
+//! Every unidirectional stream starts with a type byte: `UNI_STREAM_UDP` for
+//! a UDP frame, `UNI_STREAM_RAW` for a one-way payload which is handed to the
+//! caller without the type byte. Streams of other types are rejected, the
+//! payloads themselves are never looked at to tell the types apart.
+
+use std::convert::TryFrom;
+use std::io;
+
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use tokio::sync::mpsc::{channel, Receiver, Sender};
+use tracing::debug;
+
+use crate::session::{SocksAddr, SocksAddrWireType};
+
+/// Type of a unidirectional stream carrying a one-way payload.
+pub(crate) const UNI_STREAM_RAW: u8 = 0x00;
+
+/// Type of a unidirectional stream carrying a single UDP frame.
+pub(crate) const UNI_STREAM_UDP: u8 = 0x01;
+
+/// Application error code used to stop unidirectional streams nobody accepts.
+pub(crate) const UNI_STREAM_REJECTED: u32 = 0x101;
+
+/// A unidirectional stream carrying a one-way payload, its type byte has
+/// already been read.
+pub(crate) type UniStream = quinn::RecvStream;
+
+// A UDP payload plus the largest possible frame header.
+const MAX_STREAM_FRAME_SIZE: usize = 1 + 4 + 1 + 1 + 255 + 2 + u16::MAX as usize;
+
+pub(crate) fn encode_frame(session_id: u32, addr: &SocksAddr, payload: &[u8]) -> Bytes {
+    let mut buf = BytesMut::with_capacity(4 + addr.size() + payload.len());
+    buf.put_u32(session_id);
+    addr.write_buf(&mut buf, SocksAddrWireType::PortLast);
+    buf.put_slice(payload);
+    buf.freeze()
+}
+
+pub(crate) fn decode_frame(mut frame: Bytes) -> io::Result<(u32, SocksAddr, Bytes)> {
+    if frame.len() < 4 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "invalid quic udp frame",
+        ));
+    }
+    let session_id = frame.get_u32();
+    let addr = SocksAddr::try_from((&frame[..], SocksAddrWireType::PortLast))?;
+    frame.advance(addr.size());
+    Ok((session_id, addr, frame))
+}
+
+/// Sends a frame as a DATAGRAM, or on a unidirectional stream if it doesn't
+/// fit or `over_stream` is set.
+pub(crate) async fn send_frame(
+    conn: &quinn::Connection,
+    frame: Bytes,
+    over_stream: bool,
+) -> io::Result<()> {
+    if !over_stream
+        && conn
+            .max_datagram_size()
+            .is_some_and(|max| frame.len() <= max)
+    {
+        return conn.send_datagram(frame).map_err(io::Error::other);
+    }
+    let mut send = conn.open_uni().await.map_err(io::Error::other)?;
+    send.write_all(&[UNI_STREAM_UDP])
+        .await
+        .map_err(io::Error::other)?;
+    send.write_all(&frame).await.map_err(io::Error::other)?;
+    send.finish().map_err(io::Error::other)?;
+    Ok(())
+}
+
+async fn recv_stream_frame(
+    mut recv: quinn::RecvStream,
+    frame_tx: Sender<Bytes>,
+    uni_tx: Option<Sender<UniStream>>,
+) {
+    let mut stream_type = [0u8; 1];
+    if let Err(e) = recv.read_exact(&mut stream_type).await {
+        debug!("read quic uni stream type failed: {}", e);
+        return;
+    }
+    match (stream_type[0], uni_tx) {
+        (UNI_STREAM_UDP, _) => (),
+        (UNI_STREAM_RAW, Some(uni_tx)) => {
+            let _ = uni_tx.send(recv).await;
+            return;
+        }
+        (ty, _) => {
+            debug!("rejecting quic uni stream {} of type {}", recv.id(), ty);
+            let _ = recv.stop(quinn::VarInt::from_u32(UNI_STREAM_REJECTED));
+            return;
+        }
+    }
+    match recv.read_to_end(MAX_STREAM_FRAME_SIZE).await {
+        Ok(frame) => {
+            let _ = frame_tx.send(Bytes::from(frame)).await;
+        }
+        Err(e) => {
+            debug!("read quic udp frame failed: {}", e);
+        }
+    }
+}
+
+/// Returns the frames received on the connection, both as DATAGRAMs and on
+/// unidirectional streams, until it's closed. One-way payload streams go to
+/// `uni_tx`, or are stopped with `UNI_STREAM_REJECTED` without one.
+pub(crate) fn recv_frames(
+    conn: quinn::Connection,
+    uni_tx: Option<Sender<UniStream>>,
+) -> Receiver<Bytes> {
+    let (frame_tx, frame_rx) = channel(*crate::option::UDP_UPLINK_CHANNEL_SIZE);
+    let conn_c = conn.clone();
+    let frame_tx_c = frame_tx.clone();
+    tokio::spawn(async move {
+        while let Ok(frame) = conn_c.read_datagram().await {
+            if frame_tx_c.send(frame).await.is_err() {
+                break;
+            }
+        }
+    });
+    tokio::spawn(async move {
+        while let Ok(recv) = conn.accept_uni().await {
+            tokio::spawn(recv_stream_frame(recv, frame_tx.clone(), uni_tx.clone()));
+        }
+    });
+    frame_rx
+}
@@ -4,16 +4,24 @@ use std::sync::Arc;
 
 use async_trait::async_trait;
 
-use crate::{proxy::*, session::Session};
+use crate::{app::SyncDnsClient, proxy::*, session::Session};
+
+use super::Fallback;
 
 pub struct Handler {
     pub actors: Vec<AnyOutboundHandler>,
     pub selected: Arc<AtomicUsize>,
+    pub fallback: Option<Arc<Fallback>>,
+    pub dns_client: SyncDnsClient,
 }
 
 #[async_trait]
 impl OutboundDatagramHandler for Handler {
     fn connect_addr(&self) -> OutboundConnect {
+        // The actor may change before the session is handled.
+        if self.fallback.is_some() {
+            return OutboundConnect::Unknown;
+        }
         let a = &self.actors[self.selected.load(Ordering::Relaxed)];
         match a.datagram() {
             Ok(h) => return h.connect_addr(),
@@ -26,6 +34,9 @@ impl OutboundDatagramHandler for Handler {
     }
 
     fn transport_type(&self) -> DatagramTransportType {
+        if self.fallback.is_some() {
+            return DatagramTransportType::Unknown;
+        }
         let a = &self.actors[self.selected.load(Ordering::Relaxed)];
         a.datagram()
             .map(|x| x.transport_type())
@@ -38,8 +49,19 @@ impl OutboundDatagramHandler for Handler {
         transport: Option<AnyOutboundTransport>,
     ) -> io::Result<AnyOutboundDatagram> {
         tracing::trace!("handling outbound datagram");
-        let a = &self.actors[self.selected.load(Ordering::Relaxed)];
+        let Some(fallback) = self.fallback.as_ref() else {
+            let a = &self.actors[self.selected.load(Ordering::Relaxed)];
+            tracing::debug!("select handles to [{}]", a.tag());
+            return a.datagram()?.handle(sess, transport).await;
+        };
+        let i = fallback.active();
+        let a = &self.actors[i];
         tracing::debug!("select handles to [{}]", a.tag());
-        a.datagram()?.handle(sess, transport).await
+        let res = match connect_datagram_outbound(sess, self.dns_client.clone(), a).await {
+            Ok(transport) => a.datagram()?.handle(sess, transport).await,
+            Err(e) => Err(e),
+        };
+        fallback.report(i, res.is_ok());
+        res
     }
 }
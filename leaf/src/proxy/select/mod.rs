@@ -1,5 +1,183 @@
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, Weak};
+use std::time::Duration;
+
+use tracing::{info, warn};
+
+use crate::{app::SyncDnsClient, proxy::*};
+
+use super::failover::{self, Health, HealthCheckTarget};
+
 pub mod datagram;
 pub mod stream;
 
 pub use datagram::Handler as DatagramHandler;
 pub use stream::Handler as StreamHandler;
+
+// The time a health check of an actor has to complete.
+const CHECK_TIMEOUT: Duration = Duration::from_secs(6);
+
+// Returns none if the selected actor is up, or else the next one which is,
+// none if all are down.
+fn fallback_actor(selected: usize, health: &[Health]) -> Option<usize> {
+    if health[selected].is_up() {
+        return None;
+    }
+    let n = health.len();
+    (1..n)
+        .map(|i| (selected + i) % n)
+        .find(|i| health[*i].is_up())
+}
+
+/// Routes the sessions of a group to another actor while the selected one is
+/// down. Failed sessions take an actor down, the way failed health checks do
+/// in a failover group, and health checks bring it back.
+pub struct Fallback {
+    actors: Vec<AnyOutboundHandler>,
+    selected: Arc<AtomicUsize>,
+    // The actor in use instead of the selected one.
+    active: Mutex<Option<usize>>,
+    health: Mutex<Vec<Health>>,
+    failures_before_down: u32,
+    target: HealthCheckTarget,
+    check_interval: Duration,
+    dns_client: SyncDnsClient,
+    checking: AtomicBool,
+}
+
+impl Fallback {
+    pub fn new(
+        actors: Vec<AnyOutboundHandler>,
+        selected: Arc<AtomicUsize>,
+        failures_before_down: u32,
+        target: HealthCheckTarget,
+        check_interval: Duration,
+        dns_client: SyncDnsClient,
+    ) -> Self {
+        Fallback {
+            health: Mutex::new(actors.iter().map(|_| Health::new()).collect()),
+            actors,
+            selected,
+            active: Mutex::new(None),
+            failures_before_down,
+            target,
+            check_interval,
+            dns_client,
+            checking: AtomicBool::new(false),
+        }
+    }
+
+    /// The actor the sessions go to.
+    pub fn active(&self) -> usize {
+        self.active
+            .lock()
+            .unwrap()
+            .unwrap_or_else(|| self.selected.load(Ordering::Relaxed))
+    }
+
+    /// Routes to the selected actor again, unless it's down as well. To be
+    /// called when the selection changes.
+    pub fn reset(&self) {
+        let health = self.health.lock().unwrap();
+        self.reroute(&health);
+    }
+
+    fn reroute(&self, health: &[Health]) {
+        let selected = self.selected.load(Ordering::Relaxed);
+        let active = fallback_actor(selected, health);
+        let mut current = self.active.lock().unwrap();
+        if *current != active {
+            match active {
+                Some(i) => warn!(
+                    "[{}] is down, using [{}] instead",
+                    self.actors[selected].tag(),
+                    self.actors[i].tag()
+                ),
+                None => info!("using [{}] again", self.actors[selected].tag()),
+            }
+            *current = active;
+        }
+    }
+
+    /// Takes the result of a session on an actor.
+    pub fn report(self: &Arc<Self>, idx: usize, success: bool) {
+        let mut health = self.health.lock().unwrap();
+        if !health[idx].update(success, self.failures_before_down, 1) {
+            return;
+        }
+        if !success {
+            warn!(
+                "[{}] down after {} failed sessions",
+                self.actors[idx].tag(),
+                self.failures_before_down.max(1)
+            );
+            if !self.checking.swap(true, Ordering::Relaxed) {
+                tokio::spawn(check_task(Arc::downgrade(self)));
+            }
+        }
+        self.reroute(&health);
+    }
+}
+
+// Checks the actors which are down until all of them are up again. It holds
+// the fallback only while checking, so it ends when the group goes away.
+async fn check_task(fallback: Weak<Fallback>) {
+    loop {
+        let interval = match fallback.upgrade() {
+            Some(f) => f.check_interval,
+            None => return,
+        };
+        tokio::time::sleep(interval).await;
+        let Some(f) = fallback.upgrade() else {
+            return;
+        };
+        let down: Vec<usize> = {
+            let health = f.health.lock().unwrap();
+            let down: Vec<usize> = (0..health.len()).filter(|i| !health[*i].is_up()).collect();
+            // Under the lock, so that an actor going down now starts another
+            // task.
+            if down.is_empty() {
+                f.checking.store(false, Ordering::Relaxed);
+                return;
+            }
+            down
+        };
+        let checks = down.iter().map(|i| {
+            failover::tcp_latency(
+                f.actors[*i].clone(),
+                f.target.clone(),
+                f.dns_client.clone(),
+                CHECK_TIMEOUT,
+            )
+        });
+        let results = futures::future::join_all(checks).await;
+        let mut health = f.health.lock().unwrap();
+        for (i, res) in down.into_iter().zip(results) {
+            if res.is_some() && health[i].update(true, f.failures_before_down, 1) {
+                info!("[{}] up", f.actors[i].tag());
+            }
+        }
+        f.reroute(&health);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fallback_actor() {
+        let mut health: Vec<Health> = (0..3).map(|_| Health::new()).collect();
+        assert_eq!(fallback_actor(1, &health), None);
+        health[1].update(false, 1, 1);
+        assert_eq!(fallback_actor(1, &health), Some(2));
+        health[2].update(false, 1, 1);
+        assert_eq!(fallback_actor(1, &health), Some(0));
+        health[0].update(false, 1, 1);
+        assert_eq!(fallback_actor(1, &health), None);
+        health[2].update(true, 1, 1);
+        assert_eq!(fallback_actor(1, &health), Some(2));
+        health[1].update(true, 1, 1);
+        assert_eq!(fallback_actor(1, &health), None);
+    }
+}
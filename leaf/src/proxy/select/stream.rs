@@ -3,16 +3,24 @@ use std::{io, sync::Arc};
 
 use async_trait::async_trait;
 
-use crate::{proxy::*, session::Session};
+use crate::{app::SyncDnsClient, proxy::*, session::Session};
+
+use super::Fallback;
 
 pub struct Handler {
     pub actors: Vec<AnyOutboundHandler>,
     pub selected: Arc<AtomicUsize>,
+    pub fallback: Option<Arc<Fallback>>,
+    pub dns_client: SyncDnsClient,
 }
 
 #[async_trait]
 impl OutboundStreamHandler for Handler {
     fn connect_addr(&self) -> OutboundConnect {
+        // The actor may change before the session is handled.
+        if self.fallback.is_some() {
+            return OutboundConnect::Unknown;
+        }
         let a = &self.actors[self.selected.load(Ordering::Relaxed)];
         match a.stream() {
             Ok(h) => return h.connect_addr(),
@@ -31,8 +39,19 @@ impl OutboundStreamHandler for Handler {
         stream: Option<AnyStream>,
     ) -> io::Result<AnyStream> {
         tracing::trace!("handling outbound stream");
-        let a = &self.actors[self.selected.load(Ordering::Relaxed)];
+        let Some(fallback) = self.fallback.as_ref() else {
+            let a = &self.actors[self.selected.load(Ordering::Relaxed)];
+            tracing::debug!("select handles to [{}]", a.tag());
+            return a.stream()?.handle(sess, lhs, stream).await;
+        };
+        let i = fallback.active();
+        let a = &self.actors[i];
         tracing::debug!("select handles to [{}]", a.tag());
-        a.stream()?.handle(sess, lhs, stream).await
+        let res = match connect_stream_outbound(sess, self.dns_client.clone(), a).await {
+            Ok(stream) => a.stream()?.handle(sess, lhs, stream).await,
+            Err(e) => Err(e),
+        };
+        fallback.report(i, res.is_ok());
+        res
     }
 }
@@ -5,7 +5,7 @@ use std::sync::Arc;
 
 use anyhow::anyhow;
 use async_trait::async_trait;
-use bytes::{BufMut, BytesMut};
+use bytes::{BufMut, Bytes, BytesMut};
 
 use crate::{
     proxy::*,
@@ -13,6 +13,7 @@ use crate::{
 };
 
 use super::shadow::{self, ShadowedDatagram};
+use super::ss2022::{self, ServerDatagram};
 
 pub struct Handler {
     pub cipher: String,
@@ -23,7 +24,12 @@ pub struct Handler {
 impl InboundDatagramHandler for Handler {
     async fn handle<'a>(&'a self, socket: AnyInboundDatagram) -> io::Result<AnyInboundTransport> {
         tracing::trace!("handling inbound datagram");
-        let dgram = ShadowedDatagram::new(&self.cipher, &self.password)?;
+        let dgram = if ss2022::is_2022(&self.cipher) {
+            let method = ss2022::Method::from_name(&self.cipher)?;
+            DatagramCipher::Ss2022(ServerDatagram::new(method, &self.password)?)
+        } else {
+            DatagramCipher::Legacy(ShadowedDatagram::new(&self.cipher, &self.password)?)
+        };
         Ok(InboundTransport::Datagram(
             Box::new(Datagram { dgram, socket }),
             None,
@@ -31,8 +37,50 @@ impl InboundDatagramHandler for Handler {
     }
 }
 
+enum DatagramCipher {
+    Legacy(ShadowedDatagram),
+    Ss2022(ServerDatagram),
+}
+
+impl DatagramCipher {
+    // Returns the target address and the payload.
+    fn decrypt(&self, buf: BytesMut, peer: SocketAddr) -> anyhow::Result<(SocksAddr, Bytes)> {
+        match self {
+            DatagramCipher::Legacy(dgram) => {
+                let plaintext = dgram
+                    .decrypt(buf)
+                    .map_err(|e| anyhow!("Decrypt payload failed: {}", e))?;
+                let dst_addr = SocksAddr::try_from((&plaintext[..], SocksAddrWireType::PortLast))
+                    .map_err(|e| anyhow!("Parse target address failed: {}", e))?;
+                let header_size = dst_addr.size();
+                Ok((dst_addr, plaintext.slice(header_size..)))
+            }
+            DatagramCipher::Ss2022(dgram) => dgram
+                .decrypt(buf, peer)
+                .map_err(|e| anyhow!("Decrypt packet failed: {}", e)),
+        }
+    }
+
+    fn encrypt(
+        &self,
+        src_addr: &SocksAddr,
+        payload: &[u8],
+        peer: &SocketAddr,
+    ) -> io::Result<Bytes> {
+        match self {
+            DatagramCipher::Legacy(dgram) => {
+                let mut send_buf = BytesMut::new();
+                src_addr.write_buf(&mut send_buf, SocksAddrWireType::PortLast);
+                send_buf.put_slice(payload);
+                dgram.encrypt(send_buf)
+            }
+            DatagramCipher::Ss2022(dgram) => dgram.encrypt(src_addr, payload, peer),
+        }
+    }
+}
+
 pub struct Datagram {
-    dgram: ShadowedDatagram,
+    dgram: DatagramCipher,
     socket: AnyInboundDatagram,
 }
 
@@ -56,7 +104,7 @@ impl InboundDatagram for Datagram {
     }
 }
 
-pub struct DatagramRecvHalf(Arc<ShadowedDatagram>, Box<dyn InboundDatagramRecvHalf>);
+pub struct DatagramRecvHalf(Arc<DatagramCipher>, Box<dyn InboundDatagramRecvHalf>);
 
 #[async_trait]
 impl InboundDatagramRecvHalf for DatagramRecvHalf {
@@ -68,21 +116,18 @@ impl InboundDatagramRecvHalf for DatagramRecvHalf {
         recv_buf.resize(buf.len(), 0);
         let (n, src_addr, _) = self.1.recv_from(&mut recv_buf).await?;
         recv_buf.resize(n, 0);
-        let plaintext = self
+        let (dst_addr, payload) = self
             .0
-            .decrypt(recv_buf)
-            .map_err(|e| ProxyError::DatagramWarn(anyhow!("Decrypt payload failed: {}", e)))?;
-        let dst_addr = SocksAddr::try_from((&plaintext[..], SocksAddrWireType::PortLast))
-            .map_err(|e| ProxyError::DatagramWarn(anyhow!("Parse target address failed: {}", e)))?;
-        let header_size = dst_addr.size();
-        let payload_size = plaintext.len() - header_size;
+            .decrypt(recv_buf, src_addr.address)
+            .map_err(ProxyError::DatagramWarn)?;
+        let payload_size = payload.len();
         assert!(buf.len() >= payload_size);
-        buf[..payload_size].copy_from_slice(&plaintext[header_size..header_size + payload_size]);
+        buf[..payload_size].copy_from_slice(&payload);
         Ok((payload_size, src_addr, dst_addr))
     }
 }
 
-pub struct DatagramSendHalf(Arc<ShadowedDatagram>, Box<dyn InboundDatagramSendHalf>);
+pub struct DatagramSendHalf(Arc<DatagramCipher>, Box<dyn InboundDatagramSendHalf>);
 
 #[async_trait]
 impl InboundDatagramSendHalf for DatagramSendHalf {
@@ -92,10 +137,10 @@ impl InboundDatagramSendHalf for DatagramSendHalf {
         src_addr: &SocksAddr,
         dst_addr: &SocketAddr,
     ) -> io::Result<usize> {
-        let mut send_buf = BytesMut::new();
-        src_addr.write_buf(&mut send_buf, SocksAddrWireType::PortLast);
-        send_buf.put_slice(buf);
-        let ciphertext = self.0.encrypt(send_buf).map_err(|_| shadow::crypto_err())?;
+        let ciphertext = self
+            .0
+            .encrypt(src_addr, buf, dst_addr)
+            .map_err(|_| shadow::crypto_err())?;
         self.1.send_to(&ciphertext[..], src_addr, dst_addr).await
     }
 
@@ -5,3 +5,4 @@ pub use datagram::Handler as DatagramHandler;
 pub use stream::Handler as StreamHandler;
 
 use super::shadow;
+use super::ss2022;
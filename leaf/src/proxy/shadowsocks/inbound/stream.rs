@@ -1,3 +1,6 @@
+use std::sync::Arc;
+
+use anyhow::Result;
 use async_trait::async_trait;
 
 use crate::{
@@ -6,10 +9,25 @@ use crate::{
 };
 
 use super::shadow::ShadowedStream;
+use super::ss2022::{self, ReplayFilter, Ss2022Stream};
 
 pub struct Handler {
     pub cipher: String,
     pub password: String,
+    replay_filter: Arc<ReplayFilter>,
+}
+
+impl Handler {
+    pub fn new(cipher: String, password: String) -> Result<Self> {
+        if ss2022::is_2022(&cipher) {
+            ss2022::parse_psk(ss2022::Method::from_name(&cipher)?, &password)?;
+        }
+        Ok(Self {
+            cipher,
+            password,
+            replay_filter: Arc::new(ReplayFilter::new()),
+        })
+    }
 }
 
 #[async_trait]
@@ -20,6 +38,17 @@ impl InboundStreamHandler for Handler {
         stream: AnyStream,
     ) -> std::io::Result<AnyInboundTransport> {
         tracing::trace!("handling inbound stream");
+        if ss2022::is_2022(&self.cipher) {
+            let method = ss2022::Method::from_name(&self.cipher)?;
+            let mut stream = Ss2022Stream::new_server(
+                stream,
+                method,
+                &self.password,
+                self.replay_filter.clone(),
+            )?;
+            sess.destination = stream.accept().await?;
+            return Ok(InboundTransport::Stream(Box::new(stream), sess));
+        }
         let mut stream = ShadowedStream::new(stream, &self.cipher, &self.password, None)?;
         let destination = SocksAddr::read_from(&mut stream, SocksAddrWireType::PortLast).await?;
         sess.destination = destination;
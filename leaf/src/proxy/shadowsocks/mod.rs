@@ -1,5 +1,6 @@
 mod crypto;
 pub mod shadow;
+pub mod ss2022;
 
 #[cfg(feature = "inbound-shadowsocks")]
 pub mod inbound;
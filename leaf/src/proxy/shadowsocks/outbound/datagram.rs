@@ -1,27 +1,72 @@
 use std::{convert::TryFrom, io, sync::Arc};
 
 use async_trait::async_trait;
-use bytes::{BufMut, BytesMut};
+use bytes::{BufMut, Bytes, BytesMut};
 
 use crate::{proxy::*, session::*};
 
-use super::shadow::{self, ShadowedDatagram};
+use super::shadow::{self, ShadowedDatagram, ShadowedStream};
+use super::ss2022::{self, ClientDatagram, Ss2022Stream};
+use super::uot;
 
 pub struct Handler {
     pub address: String,
     pub port: u16,
     pub cipher: String,
     pub password: String,
+    // Tunnels each UDP session through a TCP connection with UoT.
+    pub udp_over_tcp: bool,
+}
+
+impl Handler {
+    fn handle_udp_over_tcp(
+        &self,
+        sess: &Session,
+        transport: Option<AnyOutboundTransport>,
+    ) -> io::Result<AnyOutboundDatagram> {
+        let stream = if let Some(OutboundTransport::Stream(stream)) = transport {
+            stream
+        } else {
+            return Err(io::Error::other("invalid ss input"));
+        };
+        let magic_addr = SocksAddr::Domain(uot::MAGIC_ADDRESS.to_string(), 0);
+        if ss2022::is_2022(&self.cipher) {
+            let method = ss2022::Method::from_name(&self.cipher)?;
+            let stream = Ss2022Stream::new_client(stream, method, &self.password, magic_addr)?;
+            Ok(Box::new(uot::Datagram::new(
+                stream,
+                &sess.destination,
+                BytesMut::new(),
+            )))
+        } else {
+            let stream = ShadowedStream::new(stream, &self.cipher, &self.password, None)?;
+            let mut head = BytesMut::new();
+            magic_addr.write_buf(&mut head, SocksAddrWireType::PortLast);
+            Ok(Box::new(uot::Datagram::new(
+                stream,
+                &sess.destination,
+                head,
+            )))
+        }
+    }
 }
 
 #[async_trait]
 impl OutboundDatagramHandler for Handler {
     fn connect_addr(&self) -> OutboundConnect {
-        OutboundConnect::Proxy(Network::Udp, self.address.clone(), self.port)
+        if self.udp_over_tcp {
+            OutboundConnect::Proxy(Network::Tcp, self.address.clone(), self.port)
+        } else {
+            OutboundConnect::Proxy(Network::Udp, self.address.clone(), self.port)
+        }
     }
 
     fn transport_type(&self) -> DatagramTransportType {
-        DatagramTransportType::Unreliable
+        if self.udp_over_tcp {
+            DatagramTransportType::Reliable
+        } else {
+            DatagramTransportType::Unreliable
+        }
     }
 
     async fn handle<'a>(
@@ -30,6 +75,9 @@ impl OutboundDatagramHandler for Handler {
         transport: Option<AnyOutboundTransport>,
     ) -> io::Result<AnyOutboundDatagram> {
         tracing::trace!("handling outbound datagram");
+        if self.udp_over_tcp {
+            return self.handle_udp_over_tcp(sess, transport);
+        }
         let server_addr = SocksAddr::try_from((&self.address, self.port))?;
 
         let socket = if let Some(OutboundTransport::Datagram(socket)) = transport {
@@ -40,7 +88,12 @@ impl OutboundDatagramHandler for Handler {
             return Err(io::Error::other("invalid ss input"));
         };
 
-        let dgram = ShadowedDatagram::new(&self.cipher, &self.password)?;
+        let dgram = if ss2022::is_2022(&self.cipher) {
+            let method = ss2022::Method::from_name(&self.cipher)?;
+            DatagramCipher::Ss2022(ClientDatagram::new(method, &self.password)?)
+        } else {
+            DatagramCipher::Legacy(ShadowedDatagram::new(&self.cipher, &self.password)?)
+        };
 
         let destination = match &sess.destination {
             SocksAddr::Domain(domain, port) => Some(SocksAddr::Domain(domain.to_owned(), *port)),
@@ -56,8 +109,40 @@ impl OutboundDatagramHandler for Handler {
     }
 }
 
+pub enum DatagramCipher {
+    Legacy(ShadowedDatagram),
+    Ss2022(ClientDatagram),
+}
+
+impl DatagramCipher {
+    fn encrypt(&self, target: &SocksAddr, payload: &[u8]) -> io::Result<Bytes> {
+        match self {
+            DatagramCipher::Legacy(dgram) => {
+                let mut send_buf = BytesMut::new();
+                target.write_buf(&mut send_buf, SocksAddrWireType::PortLast);
+                send_buf.put_slice(payload);
+                dgram.encrypt(send_buf)
+            }
+            DatagramCipher::Ss2022(dgram) => dgram.encrypt(target, payload),
+        }
+    }
+
+    // Returns the source address and the payload.
+    fn decrypt(&self, buf: BytesMut) -> io::Result<(SocksAddr, Bytes)> {
+        match self {
+            DatagramCipher::Legacy(dgram) => {
+                let plaintext = dgram.decrypt(buf)?;
+                let src_addr = SocksAddr::try_from((&plaintext[..], SocksAddrWireType::PortLast))?;
+                let header_size = src_addr.size();
+                Ok((src_addr, plaintext.slice(header_size..)))
+            }
+            DatagramCipher::Ss2022(dgram) => dgram.decrypt(buf),
+        }
+    }
+}
+
 pub struct Datagram {
-    pub dgram: ShadowedDatagram,
+    pub dgram: DatagramCipher,
     pub socket: Box<dyn OutboundDatagram>,
     pub destination: Option<SocksAddr>,
     pub server_addr: SocksAddr,
@@ -84,7 +169,7 @@ impl OutboundDatagram for Datagram {
 }
 
 pub struct DatagramRecvHalf(
-    Arc<ShadowedDatagram>,
+    Arc<DatagramCipher>,
     Box<dyn OutboundDatagramRecvHalf>,
     Option<SocksAddr>,
 );
@@ -96,18 +181,16 @@ impl OutboundDatagramRecvHalf for DatagramRecvHalf {
         recv_buf.resize(buf.len(), 0);
         let (n, _) = self.1.recv_from(&mut recv_buf).await?;
         recv_buf.resize(n, 0);
-        let plaintext = self.0.decrypt(recv_buf).map_err(|_| shadow::crypto_err())?;
-        let src_addr = SocksAddr::try_from((&plaintext[..], SocksAddrWireType::PortLast))?;
-        let payload_len = plaintext.len() - src_addr.size();
+        let (src_addr, payload) = self.0.decrypt(recv_buf).map_err(|_| shadow::crypto_err())?;
+        let payload_len = payload.len();
         assert!(payload_len <= buf.len());
-        buf[..payload_len]
-            .copy_from_slice(&plaintext[src_addr.size()..src_addr.size() + payload_len]);
+        buf[..payload_len].copy_from_slice(&payload);
         Ok((payload_len, self.2.clone().unwrap_or(src_addr)))
     }
 }
 
 pub struct DatagramSendHalf {
-    dgram: Arc<ShadowedDatagram>,
+    dgram: Arc<DatagramCipher>,
     send_half: Box<dyn OutboundDatagramSendHalf>,
     server_addr: SocksAddr,
 }
@@ -115,12 +198,9 @@ pub struct DatagramSendHalf {
 #[async_trait]
 impl OutboundDatagramSendHalf for DatagramSendHalf {
     async fn send_to(&mut self, buf: &[u8], target: &SocksAddr) -> io::Result<usize> {
-        let mut send_buf = BytesMut::new();
-        target.write_buf(&mut send_buf, SocksAddrWireType::PortLast);
-        send_buf.put_slice(buf);
         let ciphertext = self
             .dgram
-            .encrypt(send_buf)
+            .encrypt(target, buf)
             .map_err(|_| shadow::crypto_err())?;
         self.send_half
             .send_to(&ciphertext, &self.server_addr)
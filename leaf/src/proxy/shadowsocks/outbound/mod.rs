@@ -1,7 +1,10 @@
 pub mod datagram;
+pub mod plugin;
 pub mod stream;
+pub mod uot;
 
 pub use datagram::Handler as DatagramHandler;
 pub use stream::Handler as StreamHandler;
 
 use super::shadow;
+use super::ss2022;
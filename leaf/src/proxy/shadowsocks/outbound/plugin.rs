@@ -0,0 +1,124 @@
+//! SIP003 plugins, run as child processes listening on a local port which
+//! the shadowsocks connection goes through.
+
+use std::cmp::min;
+use std::io;
+use std::net::{Ipv4Addr, TcpListener};
+use std::process::Stdio;
+use std::time::{Duration, Instant};
+
+use futures::future::{abortable, AbortHandle};
+use tokio::process::{Child, Command};
+use tracing::{debug, warn};
+
+const MIN_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(64);
+
+// A plugin which has been running this long is considered healthy, the
+// backoff starts over the next time it exits.
+const STABLE_RUNTIME: Duration = Duration::from_secs(60);
+
+/// Starts the plugin at `path` forwarding to `remote_host:remote_port`, and
+/// restarts it with backoff whenever it exits. Returns the local port the
+/// plugin listens on, and the handle to stop it, which also kills the process.
+pub fn start(
+    path: &str,
+    opts: Option<&str>,
+    remote_host: &str,
+    remote_port: u16,
+) -> io::Result<(u16, AbortHandle)> {
+    let local_port = TcpListener::bind((Ipv4Addr::LOCALHOST, 0))?
+        .local_addr()?
+        .port();
+    let mut cmd = Command::new(path);
+    cmd.env("SS_REMOTE_HOST", remote_host)
+        .env("SS_REMOTE_PORT", remote_port.to_string())
+        .env("SS_LOCAL_HOST", Ipv4Addr::LOCALHOST.to_string())
+        .env("SS_LOCAL_PORT", local_port.to_string())
+        .stdin(Stdio::null())
+        .kill_on_drop(true);
+    if let Some(opts) = opts {
+        cmd.env("SS_PLUGIN_OPTIONS", opts);
+    }
+    // Spawn the first one right away so a bad path fails the config.
+    let child = cmd.spawn()?;
+    debug!("started plugin {} on port {}", path, local_port);
+    let (task, abort_handle) = abortable(supervise(cmd, child, path.to_string()));
+    tokio::spawn(task);
+    Ok((local_port, abort_handle))
+}
+
+async fn supervise(mut cmd: Command, mut child: Child, path: String) {
+    let mut backoff = MIN_BACKOFF;
+    loop {
+        let started = Instant::now();
+        match child.wait().await {
+            Ok(status) => warn!("plugin {} exited: {}", path, status),
+            Err(e) => warn!("wait for plugin {} failed: {}", path, e),
+        }
+        if started.elapsed() >= STABLE_RUNTIME {
+            backoff = MIN_BACKOFF;
+        }
+        child = loop {
+            tokio::time::sleep(backoff).await;
+            backoff = min(backoff * 2, MAX_BACKOFF);
+            match cmd.spawn() {
+                Ok(child) => break child,
+                Err(e) => warn!("restart plugin {} failed: {}", path, e),
+            }
+        };
+        debug!("restarted plugin {}", path);
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use std::os::unix::fs::PermissionsExt;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_plugin_restart_and_stop() {
+        let dir = std::env::temp_dir().join(format!("leaf-sip003-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let out = dir.join("out");
+        let plugin = dir.join("plugin.sh");
+        std::fs::write(
+            &plugin,
+            format!(
+                "#!/bin/sh\necho \"$SS_REMOTE_HOST:$SS_REMOTE_PORT $SS_LOCAL_HOST:$SS_LOCAL_PORT $SS_PLUGIN_OPTIONS\" >> {}\n",
+                out.display()
+            ),
+        )
+        .unwrap();
+        std::fs::set_permissions(&plugin, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        let (port, abort_handle) = start(
+            plugin.to_str().unwrap(),
+            Some("mode=websocket"),
+            "example.com",
+            8388,
+        )
+        .unwrap();
+        // The plugin exits immediately, it's run again after a second.
+        tokio::time::sleep(Duration::from_millis(1500)).await;
+        abort_handle.abort();
+        let runs = std::fs::read_to_string(&out).unwrap();
+        let lines: Vec<&str> = runs.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(
+            lines[0],
+            format!("example.com:8388 127.0.0.1:{} mode=websocket", port)
+        );
+
+        // Nothing is run after stopping.
+        tokio::time::sleep(Duration::from_millis(2500)).await;
+        assert_eq!(std::fs::read_to_string(&out).unwrap(), runs);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_plugin_not_found() {
+        assert!(start("/nonexistent/plugin", None, "example.com", 8388).is_err());
+    }
+}
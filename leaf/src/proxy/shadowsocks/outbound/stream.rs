@@ -1,12 +1,13 @@
 use std::io;
 
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use async_trait::async_trait;
 use bytes::BufMut;
 use bytes::BytesMut;
 use tokio::io::AsyncWriteExt;
 
 use super::shadow::ShadowedStream;
+use super::ss2022::{self, Ss2022Stream};
 use crate::{proxy::*, session::*};
 
 pub struct Handler {
@@ -30,6 +31,12 @@ impl Handler {
             .map(|x| percent_encoding::percent_decode(x.as_bytes()).decode_utf8())
             .transpose()?
             .map(|x| x.to_string().into_bytes().into_boxed_slice());
+        if ss2022::is_2022(&cipher) {
+            ss2022::parse_psk(ss2022::Method::from_name(&cipher)?, &password)?;
+            if prefix.is_some() {
+                return Err(anyhow!("prefix is not supported by {}", &cipher));
+            }
+        }
         Ok(Self {
             address,
             port,
@@ -54,6 +61,17 @@ impl OutboundStreamHandler for Handler {
     ) -> io::Result<AnyStream> {
         tracing::trace!("handling outbound stream");
         let stream = stream.ok_or_else(|| io::Error::other("invalid input"))?;
+        if ss2022::is_2022(&self.cipher) {
+            let method = ss2022::Method::from_name(&self.cipher)?;
+            let mut stream =
+                Ss2022Stream::new_client(stream, method, &self.password, sess.destination.clone())?;
+            // The request header goes out with the first payload, or on its
+            // own on flush.
+            let payload = peek_tcp_one_off(lhs).await;
+            stream.write_all(&payload).await?;
+            stream.flush().await?;
+            return Ok(Box::new(stream));
+        }
         let mut stream = ShadowedStream::new(
             stream,
             &self.cipher,
@@ -0,0 +1,246 @@
+//! UDP-over-TCP version 2 as in sing-box, tunneling the packets of a UDP
+//! session through one shadowsocks TCP connection.
+
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+use async_trait::async_trait;
+use bytes::{BufMut, BytesMut};
+use futures::future::TryFutureExt;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, ReadHalf, WriteHalf};
+
+use crate::{proxy::*, session::*};
+
+/// The destination to request from the shadowsocks server for a UoT
+/// connection.
+pub const MAGIC_ADDRESS: &str = "sp.v2.udp-over-tcp.arpa";
+
+const ADDR_TYPE_IPV4: u8 = 0x00;
+const ADDR_TYPE_IPV6: u8 = 0x01;
+const ADDR_TYPE_DOMAIN: u8 = 0x02;
+
+// UoT uses its own address type values, otherwise the same as SOCKS5.
+fn write_addr(buf: &mut BytesMut, addr: &SocksAddr) {
+    match addr {
+        SocksAddr::Ip(addr) => {
+            match addr.ip() {
+                IpAddr::V4(ip) => {
+                    buf.put_u8(ADDR_TYPE_IPV4);
+                    buf.put_slice(&ip.octets());
+                }
+                IpAddr::V6(ip) => {
+                    buf.put_u8(ADDR_TYPE_IPV6);
+                    buf.put_slice(&ip.octets());
+                }
+            }
+            buf.put_u16(addr.port());
+        }
+        SocksAddr::Domain(domain, port) => {
+            buf.put_u8(ADDR_TYPE_DOMAIN);
+            buf.put_u8(domain.len() as u8);
+            buf.put_slice(domain.as_bytes());
+            buf.put_u16(*port);
+        }
+    }
+}
+
+async fn read_addr<R: AsyncRead + Unpin>(r: &mut R) -> io::Result<SocksAddr> {
+    match r.read_u8().await? {
+        ADDR_TYPE_IPV4 => {
+            let ip = Ipv4Addr::from(r.read_u32().await?);
+            let port = r.read_u16().await?;
+            Ok(SocksAddr::Ip((ip, port).into()))
+        }
+        ADDR_TYPE_IPV6 => {
+            let ip = Ipv6Addr::from(r.read_u128().await?);
+            let port = r.read_u16().await?;
+            Ok(SocksAddr::Ip((ip, port).into()))
+        }
+        ADDR_TYPE_DOMAIN => {
+            let domain_len = r.read_u8().await? as usize;
+            let mut buf = vec![0u8; domain_len];
+            r.read_exact(&mut buf).await?;
+            let domain = String::from_utf8(buf).map_err(|_| io::Error::other("invalid domain"))?;
+            let port = r.read_u16().await?;
+            Ok(SocksAddr::Domain(domain, port))
+        }
+        t => Err(io::Error::other(format!("invalid address type: {}", t))),
+    }
+}
+
+/// Encodes the request header, which is sent once at the beginning of the
+/// stream.
+pub fn request_header(destination: &SocksAddr) -> BytesMut {
+    let mut buf = BytesMut::new();
+    // Not in connect mode, every packet carries its own address.
+    buf.put_u8(0);
+    write_addr(&mut buf, destination);
+    buf
+}
+
+pub struct Datagram<S> {
+    stream: S,
+    destination: Option<SocksAddr>,
+    head: Option<BytesMut>,
+}
+
+impl<S> Datagram<S> {
+    /// Creates a UoT session for `destination` on `stream`, `head` is
+    /// written before the request header along with the first packet.
+    pub fn new(stream: S, destination: &SocksAddr, mut head: BytesMut) -> Self {
+        head.extend_from_slice(&request_header(destination));
+        let head = Some(head);
+        let destination = match destination {
+            SocksAddr::Domain(domain, port) => Some(SocksAddr::Domain(domain.to_owned(), *port)),
+            _ => None,
+        };
+        Datagram {
+            stream,
+            destination,
+            head,
+        }
+    }
+}
+
+impl<S> OutboundDatagram for Datagram<S>
+where
+    S: 'static + AsyncRead + AsyncWrite + Unpin + Send + Sync,
+{
+    fn split(
+        self: Box<Self>,
+    ) -> (
+        Box<dyn OutboundDatagramRecvHalf>,
+        Box<dyn OutboundDatagramSendHalf>,
+    ) {
+        let (r, w) = tokio::io::split(self.stream);
+        (
+            Box::new(DatagramRecvHalf(r, self.destination, Vec::new())),
+            Box::new(DatagramSendHalf(w, self.head)),
+        )
+    }
+}
+
+pub struct DatagramRecvHalf<T>(ReadHalf<T>, Option<SocksAddr>, Vec<u8>);
+
+#[async_trait]
+impl<T> OutboundDatagramRecvHalf for DatagramRecvHalf<T>
+where
+    T: AsyncRead + AsyncWrite + Send + Sync,
+{
+    async fn recv_from(&mut self, buf: &mut [u8]) -> io::Result<(usize, SocksAddr)> {
+        let addr = read_addr(&mut self.0).await?;
+        let payload_len = self.0.read_u16().await? as usize;
+        // Packets larger than `buf` are truncated like a UDP socket does,
+        // the rest is still read off to keep the stream in sync.
+        let n = if payload_len <= buf.len() {
+            self.0.read_exact(&mut buf[..payload_len]).await?;
+            payload_len
+        } else {
+            self.2.resize(payload_len, 0);
+            self.0.read_exact(&mut self.2).await?;
+            buf.copy_from_slice(&self.2[..buf.len()]);
+            buf.len()
+        };
+        // If the initial destination is of domain type, we return that
+        // domain address instead of the real source address.
+        Ok((n, self.1.clone().unwrap_or(addr)))
+    }
+}
+
+pub struct DatagramSendHalf<T>(WriteHalf<T>, Option<BytesMut>);
+
+#[async_trait]
+impl<T> OutboundDatagramSendHalf for DatagramSendHalf<T>
+where
+    T: AsyncRead + AsyncWrite + Send + Sync,
+{
+    async fn send_to(&mut self, buf: &[u8], target: &SocksAddr) -> io::Result<usize> {
+        if buf.len() > u16::MAX as usize {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("packet too large: {}", buf.len()),
+            ));
+        }
+        // Writes the header along with the first packet.
+        let mut data = self.1.take().unwrap_or_default();
+        write_addr(&mut data, target);
+        data.put_u16(buf.len() as u16);
+        data.put_slice(buf);
+        self.0.write_all(&data).map_ok(|_| buf.len()).await
+    }
+
+    async fn close(&mut self) -> io::Result<()> {
+        self.0.shutdown().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::SocketAddr;
+
+    use super::*;
+
+    // Echoes packets back the way a UoT server would.
+    async fn echo_server<S: AsyncRead + AsyncWrite + Unpin>(mut s: S) {
+        assert_eq!(s.read_u8().await.unwrap(), 0);
+        read_addr(&mut s).await.unwrap();
+        loop {
+            let Ok(addr) = read_addr(&mut s).await else {
+                return;
+            };
+            let len = s.read_u16().await.unwrap() as usize;
+            let mut payload = vec![0u8; len];
+            s.read_exact(&mut payload).await.unwrap();
+            let mut buf = BytesMut::new();
+            write_addr(&mut buf, &addr);
+            buf.put_u16(len as u16);
+            buf.put_slice(&payload);
+            s.write_all(&buf).await.unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn test_uot_order_and_size() {
+        let (c, s) = tokio::io::duplex(0x40000);
+        tokio::spawn(echo_server(s));
+        let dns_server = SocksAddr::from("8.8.8.8:53".parse::<SocketAddr>().unwrap());
+        let (mut r, mut w) = Box::new(Datagram::new(c, &dns_server, BytesMut::new())).split();
+
+        let v6 = SocksAddr::from("[2001:db8::1]:53".parse::<SocketAddr>().unwrap());
+        let domain = SocksAddr::try_from(("example.com", 443)).unwrap();
+        let packets: Vec<(Vec<u8>, &SocksAddr)> = vec![
+            (vec![1; 1], &dns_server),
+            (vec![2; 65535], &v6),
+            (vec![], &domain),
+            (vec![3; 512], &dns_server),
+        ];
+        for (p, addr) in &packets {
+            assert_eq!(w.send_to(p, addr).await.unwrap(), p.len());
+        }
+        let mut buf = vec![0u8; 65535];
+        for (p, addr) in &packets {
+            let (n, from) = r.recv_from(&mut buf).await.unwrap();
+            assert_eq!(&buf[..n], &p[..]);
+            assert_eq!(&from, *addr);
+        }
+
+        assert!(w.send_to(&[0; 65536], &dns_server).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_uot_truncate() {
+        let (c, s) = tokio::io::duplex(0x40000);
+        tokio::spawn(echo_server(s));
+        let dns_server = SocksAddr::from("8.8.8.8:53".parse::<SocketAddr>().unwrap());
+        let (mut r, mut w) = Box::new(Datagram::new(c, &dns_server, BytesMut::new())).split();
+
+        w.send_to(&[1; 4096], &dns_server).await.unwrap();
+        w.send_to(&[2; 16], &dns_server).await.unwrap();
+        let mut buf = [0u8; 2048];
+        let (n, _) = r.recv_from(&mut buf).await.unwrap();
+        assert_eq!(&buf[..n], &[1; 2048]);
+        // The next packet is intact.
+        let (n, _) = r.recv_from(&mut buf).await.unwrap();
+        assert_eq!(&buf[..n], &[2; 16]);
+    }
+}
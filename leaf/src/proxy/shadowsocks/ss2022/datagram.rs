@@ -0,0 +1,361 @@
+use std::net::SocketAddr;
+use std::num::NonZeroUsize;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use aes::cipher::{generic_array::GenericArray, BlockDecrypt, BlockEncrypt, KeyInit};
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use chacha20poly1305::{aead::AeadInPlace, XChaCha20Poly1305, XNonce};
+use lru::LruCache;
+use rand::{rngs::StdRng, Rng, RngCore, SeedableRng};
+
+use crate::common::crypto::{aead::AeadCipher, Cipher, Decryptor, Encryptor, NonceSequence};
+use crate::session::{SocksAddr, SocksAddrWireType};
+
+use super::super::shadow::crypto_err;
+use super::*;
+
+// Session ID and packet ID.
+const SEPARATE_HEADER_LEN: usize = 8 + 8;
+const TAG_LEN: usize = 16;
+const XNONCE_LEN: usize = 24;
+
+// Client sessions remembered by a server, the least recently used ones are
+// dropped first.
+const MAX_CLIENT_SESSIONS: usize = 4096;
+
+fn short_packet() -> io::Error {
+    io::Error::other("short packet")
+}
+
+// The AES body nonce is taken from the plaintext separate header, so there's
+// exactly one nonce per key.
+struct PacketNonce(Vec<u8>);
+
+impl NonceSequence for PacketNonce {
+    fn advance(&mut self) -> anyhow::Result<Vec<u8>> {
+        Ok(self.0.clone())
+    }
+}
+
+/// Seals and opens packets, which for the AES methods are an AES-ECB
+/// encrypted separate header followed by the AEAD body under a per-session
+/// subkey, and for ChaCha20-Poly1305 the whole packet sealed with
+/// XChaCha20-Poly1305 under the PSK.
+struct PacketCipher {
+    method: Method,
+    psk: Vec<u8>,
+    cipher: AeadCipher,
+}
+
+impl PacketCipher {
+    fn new(method: Method, password: &str) -> io::Result<Self> {
+        let cipher = AeadCipher::new(method.aead_name())
+            .map_err(|e| io::Error::other(format!("create AEAD cipher failed: {}", e)))?;
+        let psk = parse_psk(method, password)?;
+        Ok(PacketCipher {
+            method,
+            psk,
+            cipher,
+        })
+    }
+
+    fn encrypt_header(&self, header: &mut [u8]) -> io::Result<()> {
+        let block = GenericArray::from_mut_slice(header);
+        match self.method {
+            Method::Aes128Gcm => aes::Aes128::new_from_slice(&self.psk)
+                .map_err(|_| crypto_err())?
+                .encrypt_block(block),
+            Method::Aes256Gcm => aes::Aes256::new_from_slice(&self.psk)
+                .map_err(|_| crypto_err())?
+                .encrypt_block(block),
+            Method::ChaCha20Poly1305 => unreachable!(),
+        }
+        Ok(())
+    }
+
+    fn decrypt_header(&self, header: &mut [u8]) -> io::Result<()> {
+        let block = GenericArray::from_mut_slice(header);
+        match self.method {
+            Method::Aes128Gcm => aes::Aes128::new_from_slice(&self.psk)
+                .map_err(|_| crypto_err())?
+                .decrypt_block(block),
+            Method::Aes256Gcm => aes::Aes256::new_from_slice(&self.psk)
+                .map_err(|_| crypto_err())?
+                .decrypt_block(block),
+            Method::ChaCha20Poly1305 => unreachable!(),
+        }
+        Ok(())
+    }
+
+    fn seal(&self, session_id: u64, packet_id: u64, body: &[u8]) -> io::Result<Bytes> {
+        let mut packet = BytesMut::new();
+        if self.method == Method::ChaCha20Poly1305 {
+            let mut nonce = [0u8; XNONCE_LEN];
+            StdRng::from_entropy().fill_bytes(&mut nonce);
+            let mut buf = Vec::with_capacity(SEPARATE_HEADER_LEN + body.len() + TAG_LEN);
+            buf.put_u64(session_id);
+            buf.put_u64(packet_id);
+            buf.put_slice(body);
+            XChaCha20Poly1305::new_from_slice(&self.psk)
+                .map_err(|_| crypto_err())?
+                .encrypt_in_place(XNonce::from_slice(&nonce), &[], &mut buf)
+                .map_err(|_| crypto_err())?;
+            packet.put_slice(&nonce);
+            packet.put_slice(&buf);
+        } else {
+            let mut header = [0u8; SEPARATE_HEADER_LEN];
+            header[..8].copy_from_slice(&session_id.to_be_bytes());
+            header[8..].copy_from_slice(&packet_id.to_be_bytes());
+            let key = derive_subkey(&self.psk, &header[..8], self.method.key_len());
+            let mut enc = self
+                .cipher
+                .encryptor(&key, PacketNonce(header[4..].to_vec()))
+                .map_err(|_| crypto_err())?;
+            let mut buf = BytesMut::with_capacity(body.len() + TAG_LEN);
+            buf.put_slice(body);
+            enc.encrypt(&mut buf).map_err(|_| crypto_err())?;
+            self.encrypt_header(&mut header)?;
+            packet.put_slice(&header);
+            packet.put_slice(&buf);
+        }
+        Ok(packet.freeze())
+    }
+
+    /// Returns session ID, packet ID and the plaintext body.
+    fn open(&self, mut packet: BytesMut) -> io::Result<(u64, u64, BytesMut)> {
+        if self.method == Method::ChaCha20Poly1305 {
+            if packet.len() < XNONCE_LEN + SEPARATE_HEADER_LEN + TAG_LEN {
+                return Err(short_packet());
+            }
+            let nonce = packet.split_to(XNONCE_LEN);
+            let mut buf = packet.to_vec();
+            XChaCha20Poly1305::new_from_slice(&self.psk)
+                .map_err(|_| crypto_err())?
+                .decrypt_in_place(XNonce::from_slice(&nonce), &[], &mut buf)
+                .map_err(|_| crypto_err())?;
+            let mut body = BytesMut::from(&buf[..]);
+            let session_id = body.get_u64();
+            let packet_id = body.get_u64();
+            Ok((session_id, packet_id, body))
+        } else {
+            if packet.len() < SEPARATE_HEADER_LEN + TAG_LEN {
+                return Err(short_packet());
+            }
+            let mut header = packet.split_to(SEPARATE_HEADER_LEN);
+            self.decrypt_header(&mut header)?;
+            let key = derive_subkey(&self.psk, &header[..8], self.method.key_len());
+            let mut dec = self
+                .cipher
+                .decryptor(&key, PacketNonce(header[4..].to_vec()))
+                .map_err(|_| crypto_err())?;
+            dec.decrypt(&mut packet).map_err(|_| crypto_err())?;
+            packet.truncate(packet.len() - TAG_LEN);
+            let session_id = header.get_u64();
+            let packet_id = header.get_u64();
+            Ok((session_id, packet_id, packet))
+        }
+    }
+}
+
+fn read_header(body: &mut BytesMut, header_type: u8) -> io::Result<()> {
+    if body.len() < 1 + 8 {
+        return Err(short_packet());
+    }
+    let ty = body.get_u8();
+    if ty != header_type {
+        return Err(io::Error::other(format!("unexpected header type: {}", ty)));
+    }
+    check_timestamp(body.get_u64())
+}
+
+fn read_address_and_payload(mut body: BytesMut) -> io::Result<(SocksAddr, Bytes)> {
+    if body.len() < 2 {
+        return Err(short_packet());
+    }
+    let padding_len = body.get_u16() as usize;
+    if body.len() < padding_len {
+        return Err(short_packet());
+    }
+    body.advance(padding_len);
+    let addr = SocksAddr::try_from((&body[..], SocksAddrWireType::PortLast))?;
+    body.advance(addr.size());
+    Ok((addr, body.freeze()))
+}
+
+/// The client side of a UDP session.
+pub struct ClientDatagram {
+    cipher: PacketCipher,
+    session_id: u64,
+    packet_id: AtomicU64,
+    // The current server session and its packet window.
+    server_session: Mutex<Option<(u64, PacketWindow)>>,
+}
+
+impl ClientDatagram {
+    pub fn new(method: Method, password: &str) -> io::Result<Self> {
+        Ok(ClientDatagram {
+            cipher: PacketCipher::new(method, password)?,
+            session_id: StdRng::from_entropy().gen(),
+            packet_id: AtomicU64::new(0),
+            server_session: Mutex::new(None),
+        })
+    }
+
+    pub fn encrypt(&self, target: &SocksAddr, payload: &[u8]) -> io::Result<Bytes> {
+        let mut body = BytesMut::new();
+        body.put_u8(HEADER_TYPE_CLIENT);
+        body.put_u64(unix_timestamp());
+        body.put_u16(0);
+        target.write_buf(&mut body, SocksAddrWireType::PortLast);
+        body.put_slice(payload);
+        let packet_id = self.packet_id.fetch_add(1, Ordering::Relaxed);
+        self.cipher.seal(self.session_id, packet_id, &body)
+    }
+
+    /// Decrypts a packet from the server, returns the source address and the
+    /// payload.
+    pub fn decrypt(&self, packet: BytesMut) -> io::Result<(SocksAddr, Bytes)> {
+        let (session_id, packet_id, mut body) = self.cipher.open(packet)?;
+        read_header(&mut body, HEADER_TYPE_SERVER)?;
+        if body.len() < 8 {
+            return Err(short_packet());
+        }
+        if body.get_u64() != self.session_id {
+            return Err(io::Error::other("client session ID mismatch"));
+        }
+        {
+            let mut server_session = self.server_session.lock().unwrap();
+            let (id, window) =
+                server_session.get_or_insert_with(|| (session_id, PacketWindow::default()));
+            // The server may start a new session, e.g. after restarting.
+            if *id != session_id {
+                *id = session_id;
+                *window = PacketWindow::default();
+            }
+            if !window.check_and_update(packet_id) {
+                return Err(io::Error::other("replayed packet"));
+            }
+        }
+        read_address_and_payload(body)
+    }
+}
+
+struct ServerSession {
+    window: PacketWindow,
+    session_id: u64,
+    packet_id: u64,
+}
+
+struct ServerSessions {
+    by_client_session: LruCache<u64, ServerSession>,
+    by_peer: LruCache<SocketAddr, u64>,
+}
+
+/// The server side of UDP sessions from any number of clients.
+pub struct ServerDatagram {
+    cipher: PacketCipher,
+    sessions: Mutex<ServerSessions>,
+}
+
+impl ServerDatagram {
+    pub fn new(method: Method, password: &str) -> io::Result<Self> {
+        let cap = NonZeroUsize::new(MAX_CLIENT_SESSIONS).unwrap();
+        Ok(ServerDatagram {
+            cipher: PacketCipher::new(method, password)?,
+            sessions: Mutex::new(ServerSessions {
+                by_client_session: LruCache::new(cap),
+                by_peer: LruCache::new(cap),
+            }),
+        })
+    }
+
+    /// Decrypts a packet from `peer`, returns the target address and the
+    /// payload.
+    pub fn decrypt(&self, packet: BytesMut, peer: SocketAddr) -> io::Result<(SocksAddr, Bytes)> {
+        let (client_session_id, packet_id, mut body) = self.cipher.open(packet)?;
+        read_header(&mut body, HEADER_TYPE_CLIENT)?;
+        {
+            let mut sessions = self.sessions.lock().unwrap();
+            let session = sessions
+                .by_client_session
+                .get_or_insert_mut(client_session_id, || ServerSession {
+                    window: PacketWindow::default(),
+                    session_id: StdRng::from_entropy().gen(),
+                    packet_id: 0,
+                });
+            if !session.window.check_and_update(packet_id) {
+                return Err(io::Error::other("replayed packet"));
+            }
+            sessions.by_peer.put(peer, client_session_id);
+        }
+        read_address_and_payload(body)
+    }
+
+    /// Encrypts a packet from `src` to the client session at `peer`.
+    pub fn encrypt(&self, src: &SocksAddr, payload: &[u8], peer: &SocketAddr) -> io::Result<Bytes> {
+        let (client_session_id, session_id, packet_id) = {
+            let mut sessions = self.sessions.lock().unwrap();
+            let client_session_id = *sessions
+                .by_peer
+                .get(peer)
+                .ok_or_else(|| io::Error::other(format!("no session for {}", peer)))?;
+            let session = sessions
+                .by_client_session
+                .get_mut(&client_session_id)
+                .ok_or_else(|| io::Error::other(format!("no session for {}", peer)))?;
+            let packet_id = session.packet_id;
+            session.packet_id += 1;
+            (client_session_id, session.session_id, packet_id)
+        };
+        let mut body = BytesMut::new();
+        body.put_u8(HEADER_TYPE_SERVER);
+        body.put_u64(unix_timestamp());
+        body.put_u64(client_session_id);
+        body.put_u16(0);
+        src.write_buf(&mut body, SocksAddrWireType::PortLast);
+        body.put_slice(payload);
+        self.cipher.seal(session_id, packet_id, &body)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use base64::{engine::general_purpose::STANDARD, Engine};
+
+    use super::*;
+
+    #[test]
+    fn test_ss2022_datagram() {
+        let peer: SocketAddr = "127.0.0.1:1234".parse().unwrap();
+        let target = SocksAddr::try_from(("example.com", 53)).unwrap();
+        for method in [
+            Method::Aes128Gcm,
+            Method::Aes256Gcm,
+            Method::ChaCha20Poly1305,
+        ] {
+            let password = STANDARD.encode(vec![5u8; method.key_len()]);
+            let client = ClientDatagram::new(method, &password).unwrap();
+            let server = ServerDatagram::new(method, &password).unwrap();
+
+            let packet = client.encrypt(&target, b"ping").unwrap();
+            let (addr, payload) = server.decrypt(BytesMut::from(&packet[..]), peer).unwrap();
+            assert_eq!(addr, target);
+            assert_eq!(&payload[..], b"ping");
+            // The same packet again is a replay.
+            assert!(server.decrypt(BytesMut::from(&packet[..]), peer).is_err());
+
+            let packet = server.encrypt(&target, b"pong", &peer).unwrap();
+            let (addr, payload) = client.decrypt(BytesMut::from(&packet[..])).unwrap();
+            assert_eq!(addr, target);
+            assert_eq!(&payload[..], b"pong");
+            assert!(client.decrypt(BytesMut::from(&packet[..])).is_err());
+
+            // A packet from another client session is rejected.
+            let other = ClientDatagram::new(method, &password).unwrap();
+            let packet = other.encrypt(&target, b"ping").unwrap();
+            server.decrypt(BytesMut::from(&packet[..]), peer).unwrap();
+            let packet = server.encrypt(&target, b"pong", &peer).unwrap();
+            assert!(client.decrypt(BytesMut::from(&packet[..])).is_err());
+        }
+    }
+}
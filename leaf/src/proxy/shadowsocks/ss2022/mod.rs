@@ -0,0 +1,244 @@
+//! Shadowsocks 2022 edition (SIP022) ciphers.
+//!
+//! Unlike the legacy AEAD construction, the PSK is a base64-encoded random key
+//! of exactly the cipher's key length, session subkeys are derived with BLAKE3,
+//! and both directions carry a fixed-length header with a timestamp so that
+//! replayed requests can be rejected.
+
+use std::collections::HashSet;
+use std::io;
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+
+mod datagram;
+mod stream;
+
+pub use datagram::{ClientDatagram, ServerDatagram};
+pub use stream::Ss2022Stream;
+
+const SUBKEY_CONTEXT: &str = "shadowsocks 2022 session subkey";
+
+const HEADER_TYPE_CLIENT: u8 = 0;
+const HEADER_TYPE_SERVER: u8 = 1;
+
+// Maximum allowed difference between the header timestamp and local time.
+const MAX_TIME_DIFF: u64 = 30;
+
+// Salts must be remembered at least as long as a request with the same salt
+// could pass the timestamp check.
+const SALT_RETENTION: Duration = Duration::from_secs(2 * MAX_TIME_DIFF);
+
+/// Returns whether `method` names one of the 2022 edition ciphers.
+pub fn is_2022(method: &str) -> bool {
+    method.starts_with("2022-blake3-")
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Method {
+    Aes128Gcm,
+    Aes256Gcm,
+    ChaCha20Poly1305,
+}
+
+impl Method {
+    pub fn from_name(name: &str) -> io::Result<Self> {
+        match name {
+            "2022-blake3-aes-128-gcm" => Ok(Method::Aes128Gcm),
+            "2022-blake3-aes-256-gcm" => Ok(Method::Aes256Gcm),
+            "2022-blake3-chacha20-poly1305" => Ok(Method::ChaCha20Poly1305),
+            _ => Err(io::Error::other(format!("unsupported cipher: {}", name))),
+        }
+    }
+
+    /// Name of the underlying AEAD, as understood by `AeadCipher`.
+    fn aead_name(&self) -> &'static str {
+        match self {
+            Method::Aes128Gcm => "aes-128-gcm",
+            Method::Aes256Gcm => "aes-256-gcm",
+            Method::ChaCha20Poly1305 => "chacha20-ietf-poly1305",
+        }
+    }
+
+    pub fn key_len(&self) -> usize {
+        match self {
+            Method::Aes128Gcm => 16,
+            Method::Aes256Gcm | Method::ChaCha20Poly1305 => 32,
+        }
+    }
+}
+
+/// Decodes the base64 PSK and checks it matches the key length of `method`.
+pub fn parse_psk(method: Method, password: &str) -> io::Result<Vec<u8>> {
+    let psk = STANDARD
+        .decode(password)
+        .map_err(|e| io::Error::other(format!("invalid base64 PSK: {}", e)))?;
+    if psk.len() != method.key_len() {
+        return Err(io::Error::other(format!(
+            "invalid PSK length: {} != {}",
+            psk.len(),
+            method.key_len()
+        )));
+    }
+    Ok(psk)
+}
+
+fn derive_subkey(psk: &[u8], salt: &[u8], size: usize) -> Vec<u8> {
+    let mut hasher = blake3::Hasher::new_derive_key(SUBKEY_CONTEXT);
+    hasher.update(psk);
+    hasher.update(salt);
+    let mut key = vec![0u8; size];
+    hasher.finalize_xof().fill(&mut key);
+    key
+}
+
+fn unix_timestamp() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+fn check_timestamp(ts: u64) -> io::Result<()> {
+    if unix_timestamp().abs_diff(ts) > MAX_TIME_DIFF {
+        return Err(io::Error::other(format!("bad timestamp: {}", ts)));
+    }
+    Ok(())
+}
+
+/// Remembers request salts seen in the last `SALT_RETENTION`, rejecting any
+/// salt that shows up twice.
+///
+/// Salts are kept in two generations which are rotated every retention
+/// period, so a salt is remembered for at least one and at most two periods.
+pub struct ReplayFilter {
+    inner: Mutex<ReplayFilterState>,
+}
+
+struct ReplayFilterState {
+    current: HashSet<Box<[u8]>>,
+    previous: HashSet<Box<[u8]>>,
+    rotated_at: Instant,
+}
+
+impl ReplayFilter {
+    pub fn new() -> Self {
+        ReplayFilter {
+            inner: Mutex::new(ReplayFilterState {
+                current: HashSet::new(),
+                previous: HashSet::new(),
+                rotated_at: Instant::now(),
+            }),
+        }
+    }
+
+    /// Records `salt`, returns false if it has been seen before.
+    pub fn check_and_insert(&self, salt: &[u8]) -> bool {
+        let mut state = self.inner.lock().unwrap();
+        if state.rotated_at.elapsed() >= SALT_RETENTION {
+            state.previous = std::mem::take(&mut state.current);
+            state.rotated_at = Instant::now();
+        }
+        if state.previous.contains(salt) {
+            return false;
+        }
+        state.current.insert(salt.into())
+    }
+}
+
+impl Default for ReplayFilter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+const PACKET_WINDOW_SIZE: u64 = 128;
+
+/// Sliding window over the packet IDs of a UDP session, rejecting duplicates
+/// and packets too old to tell.
+#[derive(Default)]
+struct PacketWindow {
+    // Highest packet ID accepted so far, none before the first packet.
+    top: Option<u64>,
+    // Bit i is set if packet `top - i` has been accepted.
+    seen: u128,
+}
+
+impl PacketWindow {
+    fn check_and_update(&mut self, packet_id: u64) -> bool {
+        let Some(top) = self.top else {
+            self.top = Some(packet_id);
+            self.seen = 1;
+            return true;
+        };
+        if packet_id > top {
+            let shift = packet_id - top;
+            self.seen = if shift >= PACKET_WINDOW_SIZE {
+                0
+            } else {
+                self.seen << shift
+            };
+            self.seen |= 1;
+            self.top = Some(packet_id);
+            return true;
+        }
+        let offset = top - packet_id;
+        if offset >= PACKET_WINDOW_SIZE {
+            return false;
+        }
+        let mask = 1u128 << offset;
+        if self.seen & mask != 0 {
+            return false;
+        }
+        self.seen |= mask;
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_psk() {
+        let psk = STANDARD.encode([7u8; 16]);
+        assert_eq!(parse_psk(Method::Aes128Gcm, &psk).unwrap(), vec![7u8; 16]);
+        assert!(parse_psk(Method::Aes256Gcm, &psk).is_err());
+        assert!(parse_psk(Method::ChaCha20Poly1305, "not base64").is_err());
+    }
+
+    #[test]
+    fn test_derive_subkey() {
+        let psk = [1u8; 32];
+        let salt = [2u8; 32];
+        let key = derive_subkey(&psk, &salt, 32);
+        assert_eq!(
+            key,
+            blake3::derive_key(SUBKEY_CONTEXT, &[&psk[..], &salt[..]].concat())
+        );
+        // Shorter keys are prefixes of the extended output.
+        assert_eq!(derive_subkey(&psk, &salt, 16), key[..16]);
+    }
+
+    #[test]
+    fn test_replay_filter() {
+        let filter = ReplayFilter::new();
+        assert!(filter.check_and_insert(b"salt1"));
+        assert!(filter.check_and_insert(b"salt2"));
+        assert!(!filter.check_and_insert(b"salt1"));
+    }
+
+    #[test]
+    fn test_packet_window() {
+        let mut w = PacketWindow::default();
+        assert!(w.check_and_update(0));
+        assert!(!w.check_and_update(0));
+        assert!(w.check_and_update(2));
+        assert!(w.check_and_update(1));
+        assert!(!w.check_and_update(1));
+        assert!(w.check_and_update(200));
+        assert!(!w.check_and_update(2));
+        assert!(w.check_and_update(199));
+    }
+}
@@ -0,0 +1,545 @@
+use std::mem::MaybeUninit;
+use std::sync::Arc;
+use std::{cmp::min, io, pin::Pin};
+
+use bytes::{BufMut, BytesMut};
+use futures::{
+    ready,
+    task::{Context, Poll},
+};
+use rand::{rngs::StdRng, Rng, RngCore, SeedableRng};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, ReadBuf};
+
+use crate::common::crypto::{
+    aead::{AeadCipher, AeadDecryptor, AeadEncryptor},
+    Cipher, Decryptor, Encryptor, SizedCipher,
+};
+use crate::session::{SocksAddr, SocksAddrWireType};
+
+use super::super::crypto::ShadowsocksNonceSequence;
+use super::super::shadow::crypto_err;
+use super::*;
+
+// Type, timestamp and length of the variable-length header.
+const REQUEST_HEADER_LEN: usize = 1 + 8 + 2;
+
+const MAX_CHUNK_LEN: usize = 0xffff;
+const MAX_PADDING_LEN: usize = 900;
+
+enum ReadState {
+    WaitingSalt,
+    WaitingHeader,
+    WaitingLength,
+    WaitingData(usize),
+    PendingData(usize),
+}
+
+enum WriteState {
+    WaitingHeader,
+    WaitingChunk,
+    PendingChunk(usize),
+}
+
+/// A stream using one of the 2022 edition ciphers.
+///
+/// On the client side the request header, carrying `destination`, is sent
+/// along with the first write, or on flush if there's nothing to write. On
+/// the server side `accept` must be called before using the stream.
+pub struct Ss2022Stream<T> {
+    inner: T,
+    cipher: AeadCipher,
+    psk: Vec<u8>,
+    enc: Option<AeadEncryptor<ShadowsocksNonceSequence>>,
+    dec: Option<AeadDecryptor<ShadowsocksNonceSequence>>,
+    read_buf: BytesMut,
+    write_buf: BytesMut,
+    read_state: ReadState,
+    write_state: WriteState,
+    read_pos: usize,
+    // Client only, the destination to send in the request header.
+    destination: Option<SocksAddr>,
+    // Server only, rejects replayed requests.
+    replay_filter: Option<Arc<ReplayFilter>>,
+    // The salt of the request, which the response header must echo.
+    request_salt: Option<Box<[u8]>>,
+}
+
+impl<T> Ss2022Stream<T> {
+    fn new(
+        s: T,
+        method: Method,
+        password: &str,
+        destination: Option<SocksAddr>,
+        replay_filter: Option<Arc<ReplayFilter>>,
+    ) -> io::Result<Self> {
+        let cipher = AeadCipher::new(method.aead_name())
+            .map_err(|e| io::Error::other(format!("create AEAD cipher failed: {}", e)))?;
+        let psk = parse_psk(method, password)?;
+        Ok(Ss2022Stream {
+            inner: s,
+            cipher,
+            psk,
+            enc: None,
+            dec: None,
+            read_buf: BytesMut::new(),
+            write_buf: BytesMut::new(),
+            read_state: ReadState::WaitingSalt,
+            write_state: WriteState::WaitingHeader,
+            read_pos: 0,
+            destination,
+            replay_filter,
+            request_salt: None,
+        })
+    }
+
+    pub fn new_client(
+        s: T,
+        method: Method,
+        password: &str,
+        destination: SocksAddr,
+    ) -> io::Result<Self> {
+        Self::new(s, method, password, Some(destination), None)
+    }
+
+    pub fn new_server(
+        s: T,
+        method: Method,
+        password: &str,
+        replay_filter: Arc<ReplayFilter>,
+    ) -> io::Result<Self> {
+        Self::new(s, method, password, None, Some(replay_filter))
+    }
+
+    fn is_client(&self) -> bool {
+        self.replay_filter.is_none()
+    }
+
+    fn salt_len(&self) -> usize {
+        self.cipher.key_len()
+    }
+
+    fn seal(&mut self, plaintext: &[u8]) -> io::Result<()> {
+        let enc = self.enc.as_mut().expect("uninitialized cipher");
+        let mut chunk = BytesMut::with_capacity(plaintext.len() + self.cipher.tag_len());
+        chunk.put_slice(plaintext);
+        enc.encrypt(&mut chunk).map_err(|_| crypto_err())?;
+        self.write_buf.put_slice(&chunk);
+        Ok(())
+    }
+
+    // Generates the salt and writes it followed by the header, which on the
+    // client also carries the first `buf` bytes. Returns the number of bytes
+    // consumed from `buf`.
+    fn write_header(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let salt_len = self.salt_len();
+        let mut salt = vec![0u8; salt_len];
+        let mut rng = StdRng::from_entropy();
+        rng.fill_bytes(&mut salt);
+        let key = derive_subkey(&self.psk, &salt, self.cipher.key_len());
+        let nonce = ShadowsocksNonceSequence::new(self.cipher.nonce_len());
+        let enc = self
+            .cipher
+            .encryptor(&key, nonce)
+            .map_err(|_| crypto_err())?;
+        self.enc.replace(enc);
+        self.write_buf.put_slice(&salt);
+
+        if let Some(destination) = self.destination.take() {
+            // Pad the request if there's no payload to hide the header length.
+            let padding_len = if buf.is_empty() {
+                rng.gen_range(1..=MAX_PADDING_LEN)
+            } else {
+                0
+            };
+            let consume_len = min(
+                buf.len(),
+                MAX_CHUNK_LEN - destination.size() - 2 - padding_len,
+            );
+            let var_len = destination.size() + 2 + padding_len + consume_len;
+
+            let mut header = BytesMut::with_capacity(REQUEST_HEADER_LEN);
+            header.put_u8(HEADER_TYPE_CLIENT);
+            header.put_u64(unix_timestamp());
+            header.put_u16(var_len as u16);
+            self.seal(&header)?;
+
+            let mut var_header = BytesMut::with_capacity(var_len);
+            destination.write_buf(&mut var_header, SocksAddrWireType::PortLast);
+            var_header.put_u16(padding_len as u16);
+            var_header.put_bytes(0, padding_len);
+            var_header.put_slice(&buf[..consume_len]);
+            self.seal(&var_header)?;
+
+            self.request_salt.replace(salt.into_boxed_slice());
+            Ok(consume_len)
+        } else {
+            let request_salt = self
+                .request_salt
+                .clone()
+                .ok_or_else(|| io::Error::other("response before request"))?;
+            let consume_len = min(buf.len(), MAX_CHUNK_LEN);
+
+            let mut header = BytesMut::with_capacity(1 + 8 + salt_len + 2);
+            header.put_u8(HEADER_TYPE_SERVER);
+            header.put_u64(unix_timestamp());
+            header.put_slice(&request_salt);
+            header.put_u16(consume_len as u16);
+            self.seal(&header)?;
+            self.seal(&buf[..consume_len])?;
+            Ok(consume_len)
+        }
+    }
+
+    fn write_chunk(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let consume_len = min(buf.len(), MAX_CHUNK_LEN);
+        self.seal(&(consume_len as u16).to_be_bytes())?;
+        self.seal(&buf[..consume_len])?;
+        Ok(consume_len)
+    }
+}
+
+impl<T> Ss2022Stream<T>
+where
+    T: AsyncRead + Unpin,
+{
+    /// Reads and verifies the request header, returns the destination.
+    pub async fn accept(&mut self) -> io::Result<SocksAddr> {
+        let tag_len = self.cipher.tag_len();
+
+        let mut salt = vec![0u8; self.salt_len()];
+        self.inner.read_exact(&mut salt).await?;
+        let key = derive_subkey(&self.psk, &salt, self.cipher.key_len());
+        let nonce = ShadowsocksNonceSequence::new(self.cipher.nonce_len());
+        let mut dec = self
+            .cipher
+            .decryptor(&key, nonce)
+            .map_err(|_| crypto_err())?;
+
+        let mut header = vec![0u8; REQUEST_HEADER_LEN + tag_len];
+        self.inner.read_exact(&mut header).await?;
+        dec.decrypt(&mut header).map_err(|_| crypto_err())?;
+        if header[0] != HEADER_TYPE_CLIENT {
+            return Err(io::Error::other(format!(
+                "unexpected header type: {}",
+                header[0]
+            )));
+        }
+        check_timestamp(u64::from_be_bytes(header[1..9].try_into().unwrap()))?;
+        // Only authenticated salts go into the filter, so garbage from probes
+        // doesn't pile up.
+        let replay_filter = self.replay_filter.as_ref().expect("not a server");
+        if !replay_filter.check_and_insert(&salt) {
+            return Err(io::Error::other("replayed request"));
+        }
+        let var_len = u16::from_be_bytes(header[9..11].try_into().unwrap()) as usize;
+
+        let mut var_header = vec![0u8; var_len + tag_len];
+        self.inner.read_exact(&mut var_header).await?;
+        dec.decrypt(&mut var_header).map_err(|_| crypto_err())?;
+        let var_header = &var_header[..var_len];
+        let destination = SocksAddr::try_from((var_header, SocksAddrWireType::PortLast))?;
+        let mut pos = destination.size();
+        if var_len < pos + 2 {
+            return Err(io::Error::other("invalid request header"));
+        }
+        let padding_len = u16::from_be_bytes(var_header[pos..pos + 2].try_into().unwrap()) as usize;
+        pos += 2 + padding_len;
+        if var_len < pos {
+            return Err(io::Error::other("invalid request header"));
+        }
+
+        let payload = &var_header[pos..];
+        self.read_buf = BytesMut::from(payload);
+        self.read_state = if payload.is_empty() {
+            ReadState::WaitingLength
+        } else {
+            ReadState::PendingData(payload.len())
+        };
+        self.dec.replace(dec);
+        self.request_salt.replace(salt.into_boxed_slice());
+        Ok(destination)
+    }
+}
+
+trait ReadExt {
+    fn poll_read_exact(&mut self, cx: &mut Context, size: usize) -> Poll<io::Result<()>>;
+}
+
+fn early_eof() -> io::Error {
+    io::Error::new(io::ErrorKind::UnexpectedEof, "early eof")
+}
+
+impl<T> ReadExt for Ss2022Stream<T>
+where
+    T: AsyncRead + Unpin,
+{
+    // Read exactly `size` bytes into `read_buf`, starting from position 0.
+    fn poll_read_exact(&mut self, cx: &mut Context, size: usize) -> Poll<io::Result<()>> {
+        self.read_buf.reserve(size);
+        unsafe { self.read_buf.set_len(size) }
+        loop {
+            if self.read_pos < size {
+                let dst = unsafe {
+                    &mut *((&mut self.read_buf[self.read_pos..size]) as *mut _
+                        as *mut [MaybeUninit<u8>])
+                };
+                let mut buf = ReadBuf::uninit(dst);
+                let ptr = buf.filled().as_ptr();
+                ready!(Pin::new(&mut self.inner).poll_read(cx, &mut buf))?;
+                assert_eq!(ptr, buf.filled().as_ptr());
+                if buf.filled().is_empty() {
+                    return Poll::Ready(Err(early_eof()));
+                }
+                self.read_pos += buf.filled().len();
+            } else {
+                assert!(self.read_pos == size);
+                self.read_pos = 0;
+                return Poll::Ready(Ok(()));
+            }
+        }
+    }
+}
+
+impl<T> AsyncRead for Ss2022Stream<T>
+where
+    T: AsyncRead + Unpin,
+{
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context,
+        buf: &mut ReadBuf,
+    ) -> Poll<io::Result<()>> {
+        loop {
+            match self.read_state {
+                ReadState::WaitingSalt => {
+                    // Only the client gets here, the server reads the salt
+                    // in `accept`.
+                    let salt_len = self.salt_len();
+                    ready!(self.poll_read_exact(cx, salt_len))?;
+                    let key = derive_subkey(&self.psk, &self.read_buf[..salt_len], salt_len);
+                    let nonce = ShadowsocksNonceSequence::new(self.cipher.nonce_len());
+                    let dec = self
+                        .cipher
+                        .decryptor(&key, nonce)
+                        .map_err(|_| crypto_err())?;
+                    self.dec.replace(dec);
+                    self.read_buf.clear();
+                    self.read_state = ReadState::WaitingHeader;
+                }
+                ReadState::WaitingHeader => {
+                    // type, timestamp, request salt and first chunk length
+                    let me = &mut *self;
+                    let salt_len = me.salt_len();
+                    let read_size = 1 + 8 + salt_len + 2 + me.cipher.tag_len();
+                    ready!(me.poll_read_exact(cx, read_size))?;
+                    let dec = me.dec.as_mut().expect("uninitialized cipher");
+                    dec.decrypt(&mut me.read_buf).map_err(|_| crypto_err())?;
+                    if me.read_buf[0] != HEADER_TYPE_SERVER {
+                        return Poll::Ready(Err(io::Error::other(format!(
+                            "unexpected header type: {}",
+                            me.read_buf[0]
+                        ))));
+                    }
+                    check_timestamp(u64::from_be_bytes(me.read_buf[1..9].try_into().unwrap()))?;
+                    if me.request_salt.as_deref() != Some(&me.read_buf[9..9 + salt_len]) {
+                        return Poll::Ready(Err(io::Error::other("request salt mismatch")));
+                    }
+                    let payload_len = u16::from_be_bytes(
+                        me.read_buf[9 + salt_len..11 + salt_len].try_into().unwrap(),
+                    ) as usize;
+                    if payload_len == 0 {
+                        return Poll::Ready(Err(io::Error::other("empty chunk")));
+                    }
+                    me.read_state = ReadState::WaitingData(payload_len);
+                }
+                ReadState::WaitingLength => {
+                    // read and decipher payload length
+                    let me = &mut *self;
+                    let read_size = 2 + me.cipher.tag_len();
+                    if let Err(e) = ready!(me.poll_read_exact(cx, read_size)) {
+                        if e.kind() == io::ErrorKind::UnexpectedEof {
+                            return Poll::Ready(Ok(()));
+                        } else {
+                            return Poll::Ready(Err(e));
+                        }
+                    }
+                    let dec = me.dec.as_mut().expect("uninitialized cipher");
+                    dec.decrypt(&mut me.read_buf).map_err(|_| crypto_err())?;
+                    let payload_len =
+                        u16::from_be_bytes(me.read_buf[..2].try_into().unwrap()) as usize;
+                    if payload_len == 0 {
+                        return Poll::Ready(Err(io::Error::other("empty chunk")));
+                    }
+
+                    // ready to read payload
+                    me.read_state = ReadState::WaitingData(payload_len);
+                }
+                ReadState::WaitingData(n) => {
+                    // read and decipher payload
+                    let me = &mut *self;
+                    let read_size = n + me.cipher.tag_len();
+                    ready!(me.poll_read_exact(cx, read_size))?;
+                    let dec = me.dec.as_mut().expect("uninitialized cipher");
+                    dec.decrypt(&mut me.read_buf).map_err(|_| crypto_err())?;
+
+                    // ready to read plaintext payload into buf
+                    me.read_state = ReadState::PendingData(n);
+                }
+                ReadState::PendingData(n) => {
+                    let to_read = min(buf.remaining(), n);
+                    let payload = self.read_buf.split_to(to_read);
+                    buf.put_slice(&payload);
+                    if to_read < n {
+                        // there're unread data, continues in next poll
+                        self.read_state = ReadState::PendingData(n - to_read);
+                    } else {
+                        // all data consumed, ready to read next chunk
+                        self.read_state = ReadState::WaitingLength;
+                    }
+                    return Poll::Ready(Ok(()));
+                }
+            }
+        }
+    }
+}
+
+impl<T> Ss2022Stream<T>
+where
+    T: AsyncWrite + Unpin,
+{
+    fn poll_write_pending(&mut self, cx: &mut Context) -> Poll<io::Result<()>> {
+        use tokio_util::io::poll_write_buf;
+        while !self.write_buf.is_empty() {
+            let nw = ready!(poll_write_buf(
+                Pin::new(&mut self.inner),
+                cx,
+                &mut self.write_buf
+            ))?;
+            if nw == 0 {
+                return Err(early_eof()).into();
+            }
+        }
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<T> AsyncWrite for Ss2022Stream<T>
+where
+    T: AsyncWrite + Unpin,
+{
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        loop {
+            match self.write_state {
+                WriteState::WaitingHeader => {
+                    if buf.is_empty() {
+                        return Poll::Ready(Ok(0));
+                    }
+                    let consumed = self.write_header(buf)?;
+                    self.write_state = WriteState::PendingChunk(consumed);
+                }
+                WriteState::WaitingChunk => {
+                    if buf.is_empty() {
+                        return Poll::Ready(Ok(0));
+                    }
+                    let consumed = self.write_chunk(buf)?;
+                    self.write_state = WriteState::PendingChunk(consumed);
+                }
+                // There would be trouble if the caller change the buf upon
+                // pending, but I believe that's not a usual use case.
+                WriteState::PendingChunk(consumed) => {
+                    ready!(self.poll_write_pending(cx))?;
+                    self.write_state = WriteState::WaitingChunk;
+                    return Poll::Ready(Ok(consumed));
+                }
+            }
+        }
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<io::Result<()>> {
+        // Without any payload, the request header is sent on flush.
+        if matches!(self.write_state, WriteState::WaitingHeader) && self.is_client() {
+            self.write_header(&[])?;
+            self.write_state = WriteState::PendingChunk(0);
+        }
+        ready!(self.poll_write_pending(cx))?;
+        if matches!(self.write_state, WriteState::PendingChunk(0)) {
+            self.write_state = WriteState::WaitingChunk;
+        }
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<io::Result<()>> {
+        ready!(self.as_mut().poll_flush(cx))?;
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use base64::{engine::general_purpose::STANDARD, Engine};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    use super::*;
+
+    async fn echo(method: Method, payload: &[u8]) {
+        let password = STANDARD.encode(vec![9u8; method.key_len()]);
+        let (c, s) = tokio::io::duplex(0x20000);
+        let destination = SocksAddr::try_from(("example.com", 443)).unwrap();
+        let mut client =
+            Ss2022Stream::new_client(c, method, &password, destination.clone()).unwrap();
+        let filter = Arc::new(ReplayFilter::new());
+        let mut server = Ss2022Stream::new_server(s, method, &password, filter).unwrap();
+
+        client.write_all(payload).await.unwrap();
+        client.flush().await.unwrap();
+        assert_eq!(server.accept().await.unwrap(), destination);
+
+        let mut buf = vec![0u8; payload.len()];
+        server.read_exact(&mut buf).await.unwrap();
+        assert_eq!(buf, payload);
+
+        server.write_all(b"pong").await.unwrap();
+        let mut buf = [0u8; 4];
+        client.read_exact(&mut buf).await.unwrap();
+        assert_eq!(&buf, b"pong");
+
+        client.shutdown().await.unwrap();
+        assert_eq!(server.read(&mut buf).await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_ss2022_stream() {
+        for method in [
+            Method::Aes128Gcm,
+            Method::Aes256Gcm,
+            Method::ChaCha20Poly1305,
+        ] {
+            echo(method, b"").await;
+            echo(method, b"ping").await;
+            echo(method, &[1u8; 100000]).await;
+        }
+    }
+
+    #[tokio::test]
+    async fn test_ss2022_stream_replay() {
+        let method = Method::Aes256Gcm;
+        let password = STANDARD.encode([3u8; 32]);
+        let destination = SocksAddr::try_from(("example.com", 443)).unwrap();
+        let mut request = Vec::new();
+        let mut client =
+            Ss2022Stream::new_client(&mut request, method, &password, destination).unwrap();
+        client.write_all(b"ping").await.unwrap();
+        drop(client);
+
+        let filter = Arc::new(ReplayFilter::new());
+        let mut server =
+            Ss2022Stream::new_server(&request[..], method, &password, filter.clone()).unwrap();
+        assert!(server.accept().await.is_ok());
+        let mut server = Ss2022Stream::new_server(&request[..], method, &password, filter).unwrap();
+        assert!(server.accept().await.is_err());
+    }
+}
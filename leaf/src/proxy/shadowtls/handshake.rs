@@ -0,0 +1,390 @@
+use std::io;
+
+use rand::RngCore;
+use ring::{digest, hmac};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tracing::trace;
+
+use super::tls::{self, CipherSuite, HandshakeReader, RecordKey, ServerHello};
+
+pub const HMAC_LEN: usize = 4;
+
+/// A running HMAC-SHA1 keyed with the password, every tag covers all the
+/// data fed before it.
+#[derive(Clone)]
+pub struct Hmac(hmac::Context);
+
+impl Hmac {
+    pub fn new(password: &str, init: &[&[u8]]) -> Self {
+        let key = hmac::Key::new(hmac::HMAC_SHA1_FOR_LEGACY_USE_ONLY, password.as_bytes());
+        let mut ctx = hmac::Context::with_key(&key);
+        init.iter().for_each(|x| ctx.update(x));
+        Self(ctx)
+    }
+
+    fn tag_of(ctx: &hmac::Context) -> [u8; HMAC_LEN] {
+        let mut tag = [0u8; HMAC_LEN];
+        tag.copy_from_slice(&ctx.clone().sign().as_ref()[..HMAC_LEN]);
+        tag
+    }
+
+    /// Feeds `data` and returns the tag.
+    pub fn tag(&mut self, data: &[u8]) -> [u8; HMAC_LEN] {
+        self.0.update(data);
+        Self::tag_of(&self.0)
+    }
+
+    /// Feeds `data` only if it carries the right tag.
+    pub fn verify(&mut self, data: &[u8], tag: &[u8]) -> bool {
+        let mut ctx = self.0.clone();
+        ctx.update(data);
+        if Self::tag_of(&ctx) != tag {
+            return false;
+        }
+        self.0 = ctx;
+        true
+    }
+}
+
+/// The state the data relay goes on with.
+pub struct Established {
+    /// Tags the records of the handshake server, some may still come after
+    /// the handshake.
+    pub handshake_hmac: Hmac,
+    pub client_hmac: Hmac,
+    pub server_hmac: Hmac,
+    /// Encrypts the alert sent on a bad record, as the client would under
+    /// its application traffic key.
+    pub alert_key: RecordKey,
+}
+
+// The last 4 bytes of the session id are an HMAC tag over the ClientHello,
+// taken with the tag zeroed, the others are random.
+fn sign_session_id(client_hello: &mut [u8], password: &str) {
+    let tag_start = tls::SESSION_ID_OFFSET + tls::SESSION_ID_LEN - HMAC_LEN;
+    rand::thread_rng().fill_bytes(&mut client_hello[tls::SESSION_ID_OFFSET..tag_start]);
+    client_hello[tag_start..tag_start + HMAC_LEN].fill(0);
+    let tag = Hmac::new(password, &[]).tag(client_hello);
+    client_hello[tag_start..tag_start + HMAC_LEN].copy_from_slice(&tag);
+}
+
+fn xor(data: &mut [u8], key: &[u8]) {
+    data.iter_mut()
+        .zip(key.iter().cycle())
+        .for_each(|(b, k)| *b ^= k);
+}
+
+async fn read_record<S>(stream: &mut S) -> io::Result<(u8, Vec<u8>)>
+where
+    S: AsyncRead + Unpin,
+{
+    let mut header = [0u8; tls::RECORD_HEADER_LEN];
+    stream.read_exact(&mut header).await?;
+    let len = u16::from_be_bytes([header[3], header[4]]) as usize;
+    let mut payload = vec![0u8; len];
+    stream.read_exact(&mut payload).await?;
+    Ok((header[0], payload))
+}
+
+fn alert_error(alert: &[u8]) -> io::Error {
+    io::Error::other(format!(
+        "shadowtls handshake failed: handshake server sent alert {}",
+        alert.get(1).copied().unwrap_or_default()
+    ))
+}
+
+// Sends a fatal alert, encrypted once there are handshake keys, and closes
+// the connection, as a TLS client gives up on a peer.
+async fn abort<S>(
+    stream: &mut S,
+    key: Option<&mut RecordKey>,
+    description: u8,
+    reason: &str,
+) -> io::Error
+where
+    S: AsyncWrite + Unpin,
+{
+    let alert = tls::alert(true, description);
+    let record = match key {
+        Some(key) => key.seal(tls::CONTENT_ALERT, &alert),
+        None => tls::record(tls::CONTENT_ALERT, &alert),
+    };
+    let _ = stream.write_all(&record).await;
+    let _ = stream.shutdown().await;
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("shadowtls handshake failed: {}", reason),
+    )
+}
+
+/// Performs the ShadowTLS v3 handshake, a TLS 1.3 handshake with
+/// `server_name` relayed by the shadowtls server, which tags the records
+/// of the handshake server to prove itself.
+pub async fn handshake<S>(
+    stream: &mut S,
+    password: &str,
+    server_name: &str,
+) -> io::Result<Established>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let (mut client_hello, private_key) = tls::client_hello(server_name);
+    sign_session_id(&mut client_hello, password);
+    stream
+        .write_all(&tls::record(tls::CONTENT_HANDSHAKE, &client_hello))
+        .await?;
+    let mut transcript = client_hello;
+
+    let mut messages = HandshakeReader::default();
+    let server_hello = loop {
+        let (ty, payload) = read_record(stream).await?;
+        match ty {
+            tls::CONTENT_HANDSHAKE => {
+                messages.push(&payload);
+                if let Some(msg) = messages.next_message() {
+                    break msg;
+                }
+            }
+            tls::CONTENT_ALERT => return Err(alert_error(&payload)),
+            _ => {
+                return Err(abort(
+                    stream,
+                    None,
+                    tls::ALERT_UNEXPECTED_MESSAGE,
+                    "unexpected record",
+                )
+                .await)
+            }
+        }
+    };
+    if server_hello[0] != tls::HANDSHAKE_SERVER_HELLO {
+        return Err(abort(
+            stream,
+            None,
+            tls::ALERT_UNEXPECTED_MESSAGE,
+            "expected server hello",
+        )
+        .await);
+    }
+    let Some(sh) = ServerHello::parse(&server_hello[4..]) else {
+        return Err(abort(
+            stream,
+            None,
+            tls::ALERT_DECODE_ERROR,
+            "invalid server hello",
+        )
+        .await);
+    };
+    if sh.is_hello_retry_request() {
+        return Err(abort(
+            stream,
+            None,
+            tls::ALERT_HANDSHAKE_FAILURE,
+            "handshake server asked for a hello retry",
+        )
+        .await);
+    }
+    if sh.version != Some(tls::TLS13) {
+        return Err(abort(
+            stream,
+            None,
+            tls::ALERT_PROTOCOL_VERSION,
+            "handshake server doesn't support tls 1.3",
+        )
+        .await);
+    }
+    let Some(suite) = CipherSuite::from_id(sh.cipher_suite) else {
+        return Err(abort(
+            stream,
+            None,
+            tls::ALERT_ILLEGAL_PARAMETER,
+            "unsupported cipher suite",
+        )
+        .await);
+    };
+    let server_random = sh.random;
+    let Some(shared) = sh.shared_secret(private_key) else {
+        return Err(abort(
+            stream,
+            None,
+            tls::ALERT_ILLEGAL_PARAMETER,
+            "invalid key share",
+        )
+        .await);
+    };
+    transcript.extend_from_slice(&server_hello);
+    let [handshake_secret, client_secret, server_secret] =
+        suite.handshake_secrets(&shared, &transcript);
+    let mut client_key = suite.record_key(&client_secret);
+    let mut server_key = suite.record_key(&server_secret);
+
+    // The shadowtls server masks the encrypted records of the handshake
+    // server and prefixes them with a tag, records without a valid one are
+    // taken as they are and fail to decrypt if they've been tampered with.
+    let mut handshake_hmac = Hmac::new(password, &[&server_random]);
+    let mask = digest::digest(
+        &digest::SHA256,
+        &[password.as_bytes(), &server_random[..]].concat(),
+    );
+    let mut authenticated = false;
+    let mut certificate_request = None;
+    'records: loop {
+        let (ty, mut payload) = read_record(stream).await?;
+        match ty {
+            tls::CONTENT_CHANGE_CIPHER_SPEC => continue,
+            tls::CONTENT_ALERT => return Err(alert_error(&payload)),
+            tls::CONTENT_APPLICATION_DATA => (),
+            _ => {
+                return Err(abort(
+                    stream,
+                    Some(&mut client_key),
+                    tls::ALERT_UNEXPECTED_MESSAGE,
+                    "unexpected record",
+                )
+                .await)
+            }
+        }
+        if payload.len() > HMAC_LEN
+            && handshake_hmac.verify(&payload[HMAC_LEN..], &payload[..HMAC_LEN])
+        {
+            authenticated = true;
+            payload.drain(..HMAC_LEN);
+            xor(&mut payload, mask.as_ref());
+        }
+        if payload.len() > tls::MAX_RECORD_LEN {
+            return Err(abort(
+                stream,
+                Some(&mut client_key),
+                tls::ALERT_RECORD_OVERFLOW,
+                "record too large",
+            )
+            .await);
+        }
+        let (inner, plain) = match server_key.open(&mut payload) {
+            Some(x) => x,
+            None => {
+                return Err(abort(
+                    stream,
+                    Some(&mut client_key),
+                    tls::ALERT_BAD_RECORD_MAC,
+                    "decrypt record failed",
+                )
+                .await)
+            }
+        };
+        match inner {
+            tls::CONTENT_HANDSHAKE => (),
+            tls::CONTENT_ALERT => return Err(alert_error(&plain)),
+            _ => {
+                return Err(abort(
+                    stream,
+                    Some(&mut client_key),
+                    tls::ALERT_UNEXPECTED_MESSAGE,
+                    "unexpected record",
+                )
+                .await)
+            }
+        }
+        messages.push(&plain);
+        while let Some(msg) = messages.next_message() {
+            match msg[0] {
+                tls::HANDSHAKE_CERTIFICATE_REQUEST => {
+                    certificate_request = tls::certificate_request_context(&msg[4..]);
+                    if certificate_request.is_none() {
+                        return Err(abort(
+                            stream,
+                            Some(&mut client_key),
+                            tls::ALERT_DECODE_ERROR,
+                            "invalid certificate request",
+                        )
+                        .await);
+                    }
+                }
+                tls::HANDSHAKE_FINISHED => {
+                    if msg[4..] != suite.finished(&server_secret, &transcript)[..] {
+                        return Err(abort(
+                            stream,
+                            Some(&mut client_key),
+                            tls::ALERT_DECRYPT_ERROR,
+                            "invalid server finished",
+                        )
+                        .await);
+                    }
+                    transcript.extend_from_slice(&msg);
+                    break 'records;
+                }
+                _ => (),
+            }
+            transcript.extend_from_slice(&msg);
+        }
+    }
+    let application_secret = suite.client_application_secret(&handshake_secret, &transcript);
+
+    let mut flight = Vec::new();
+    if let Some(context) = certificate_request {
+        let certificate = tls::empty_certificate(&context);
+        transcript.extend_from_slice(&certificate);
+        flight.extend_from_slice(&certificate);
+    }
+    flight.extend_from_slice(&tls::handshake_message(
+        tls::HANDSHAKE_FINISHED,
+        &suite.finished(&client_secret, &transcript),
+    ));
+    let mut records = tls::record(tls::CONTENT_CHANGE_CIPHER_SPEC, &[1]);
+    records.extend_from_slice(&client_key.seal(tls::CONTENT_HANDSHAKE, &flight));
+    stream.write_all(&records).await?;
+
+    let mut alert_key = suite.record_key(&application_secret);
+    if !authenticated {
+        // A real TLS server at the address, whatever it is, the client
+        // just hangs up.
+        let alert = tls::alert(false, tls::ALERT_CLOSE_NOTIFY);
+        let _ = stream
+            .write_all(&alert_key.seal(tls::CONTENT_ALERT, &alert))
+            .await;
+        let _ = stream.shutdown().await;
+        return Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            "shadowtls handshake failed: server not authenticated",
+        ));
+    }
+    trace!("shadowtls handshake with {} done", server_name);
+
+    Ok(Established {
+        handshake_hmac,
+        client_hmac: Hmac::new(password, &[&server_random, b"C"]),
+        server_hmac: Hmac::new(password, &[&server_random, b"S"]),
+        alert_key,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sign_session_id() {
+        let (mut client_hello, _) = tls::client_hello("www.example.com");
+        sign_session_id(&mut client_hello, "password");
+
+        // As the server checks it.
+        let tag_start = tls::SESSION_ID_OFFSET + tls::SESSION_ID_LEN - HMAC_LEN;
+        let tag = client_hello[tag_start..tag_start + HMAC_LEN].to_vec();
+        let mut zeroed = client_hello.clone();
+        zeroed[tag_start..tag_start + HMAC_LEN].fill(0);
+        assert!(Hmac::new("password", &[]).verify(&zeroed, &tag));
+        assert!(!Hmac::new("passw0rd", &[]).verify(&zeroed, &tag));
+    }
+
+    #[test]
+    fn test_hmac() {
+        let mut client = Hmac::new("password", &[b"random", b"C"]);
+        let mut server = client.clone();
+        let tag1 = client.tag(b"hello");
+        let tag2 = client.tag(b"world");
+        // Each tag covers the previous data too.
+        assert!(!server.verify(b"world", &tag2));
+        assert!(server.verify(b"hello", &tag1));
+        assert!(server.verify(b"world", &tag2));
+    }
+}
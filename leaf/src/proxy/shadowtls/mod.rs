@@ -0,0 +1,9 @@
+//! ShadowTLS v3, the shadowtls server relays a TLS 1.3 handshake with a real
+//! site and then carries the data in records tagged with HMACs keyed with
+//! the password.
+
+mod handshake;
+mod stream;
+mod tls;
+
+pub mod outbound;
@@ -0,0 +1,2 @@
+pub mod stream;
+pub use stream::Handler as StreamHandler;
@@ -0,0 +1,36 @@
+use std::io;
+
+use async_trait::async_trait;
+
+use crate::{proxy::*, session::Session};
+
+use super::super::handshake::handshake;
+use super::super::stream::ShadowTlsStream;
+
+pub struct Handler {
+    pub address: String,
+    pub port: u16,
+    pub password: String,
+    /// The site whose handshake is mirrored, it's the shadowtls server that
+    /// connects to it.
+    pub handshake_server: String,
+}
+
+#[async_trait]
+impl OutboundStreamHandler for Handler {
+    fn connect_addr(&self) -> OutboundConnect {
+        OutboundConnect::Proxy(Network::Tcp, self.address.clone(), self.port)
+    }
+
+    async fn handle<'a>(
+        &'a self,
+        _sess: &'a Session,
+        _lhs: Option<&mut AnyStream>,
+        stream: Option<AnyStream>,
+    ) -> io::Result<AnyStream> {
+        tracing::trace!("handling outbound stream");
+        let mut stream = stream.ok_or_else(|| io::Error::other("invalid input"))?;
+        let established = handshake(&mut stream, &self.password, &self.handshake_server).await?;
+        Ok(Box::new(ShadowTlsStream::new(stream, established)))
+    }
+}
@@ -0,0 +1,275 @@
+use std::io;
+use std::pin::Pin;
+
+use futures::ready;
+use futures::task::{Context, Poll};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tracing::trace;
+
+use crate::proxy::AnyStream;
+
+use super::handshake::{Established, Hmac, HMAC_LEN};
+use super::tls::{self, RecordKey};
+
+// Keeps the records within the plaintext limit of TLS.
+const MAX_PAYLOAD_LEN: usize = 16384 - HMAC_LEN;
+const READ_CHUNK_LEN: usize = 16 * 1024;
+
+enum ReadState {
+    Open,
+    // A bad record came in, the alert is being sent.
+    Failing,
+    Failed,
+    Eof,
+}
+
+/// Relays the data in application data records, each tagged with the
+/// running HMAC of its direction.
+pub struct ShadowTlsStream {
+    inner: AnyStream,
+    handshake_hmac: Hmac,
+    client_hmac: Hmac,
+    server_hmac: Hmac,
+    alert_key: RecordKey,
+    records_sent: u64,
+    read_state: ReadState,
+    read_buf: Vec<u8>,
+    plain: Vec<u8>,
+    plain_pos: usize,
+    write_buf: Vec<u8>,
+    write_pos: usize,
+}
+
+impl ShadowTlsStream {
+    pub fn new(inner: AnyStream, established: Established) -> Self {
+        Self {
+            inner,
+            handshake_hmac: established.handshake_hmac,
+            client_hmac: established.client_hmac,
+            server_hmac: established.server_hmac,
+            alert_key: established.alert_key,
+            records_sent: 0,
+            read_state: ReadState::Open,
+            read_buf: Vec::new(),
+            plain: Vec::new(),
+            plain_pos: 0,
+            write_buf: Vec::new(),
+            write_pos: 0,
+        }
+    }
+
+    fn take_record(&mut self) -> Option<Vec<u8>> {
+        if self.read_buf.len() < tls::RECORD_HEADER_LEN {
+            return None;
+        }
+        let len = u16::from_be_bytes([self.read_buf[3], self.read_buf[4]]) as usize;
+        if self.read_buf.len() < tls::RECORD_HEADER_LEN + len {
+            return None;
+        }
+        Some(
+            self.read_buf
+                .drain(..tls::RECORD_HEADER_LEN + len)
+                .collect(),
+        )
+    }
+
+    fn process_record(&mut self, record: Vec<u8>) {
+        let payload = &record[tls::RECORD_HEADER_LEN..];
+        if record[0] == tls::CONTENT_APPLICATION_DATA && payload.len() >= HMAC_LEN {
+            let (tag, data) = payload.split_at(HMAC_LEN);
+            if self.server_hmac.verify(data, tag) {
+                self.plain.clear();
+                self.plain.extend_from_slice(data);
+                self.plain_pos = 0;
+                return;
+            }
+            // Such as the session tickets the handshake server sent before
+            // the shadowtls server switched to relaying data.
+            if self.handshake_hmac.verify(data, tag) {
+                trace!("skipping handshake server record");
+                return;
+            }
+        }
+        self.fail();
+    }
+
+    // A TLS client fails to decrypt a tampered record, then sends a
+    // bad_record_mac alert and closes the connection.
+    fn fail(&mut self) {
+        self.alert_key.skip(self.records_sent);
+        let alert = tls::alert(true, tls::ALERT_BAD_RECORD_MAC);
+        self.write_buf
+            .extend_from_slice(&self.alert_key.seal(tls::CONTENT_ALERT, &alert));
+        self.read_state = ReadState::Failing;
+    }
+
+    fn poll_drain(&mut self, cx: &mut Context) -> Poll<io::Result<()>> {
+        while self.write_pos < self.write_buf.len() {
+            let n = ready!(
+                Pin::new(&mut self.inner).poll_write(cx, &self.write_buf[self.write_pos..])
+            )?;
+            if n == 0 {
+                return Poll::Ready(Err(io::ErrorKind::WriteZero.into()));
+            }
+            self.write_pos += n;
+        }
+        self.write_buf.clear();
+        self.write_pos = 0;
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl AsyncRead for ShadowTlsStream {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context,
+        buf: &mut ReadBuf,
+    ) -> Poll<io::Result<()>> {
+        let me = self.get_mut();
+        loop {
+            match me.read_state {
+                ReadState::Open => (),
+                ReadState::Failing => {
+                    ready!(me.poll_drain(cx))?;
+                    ready!(Pin::new(&mut me.inner).poll_shutdown(cx))?;
+                    me.read_state = ReadState::Failed;
+                    continue;
+                }
+                ReadState::Failed => {
+                    return Poll::Ready(Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        "invalid shadowtls record",
+                    )))
+                }
+                ReadState::Eof => return Poll::Ready(Ok(())),
+            }
+            if me.plain_pos < me.plain.len() {
+                let n = buf.remaining().min(me.plain.len() - me.plain_pos);
+                buf.put_slice(&me.plain[me.plain_pos..me.plain_pos + n]);
+                me.plain_pos += n;
+                return Poll::Ready(Ok(()));
+            }
+            if let Some(record) = me.take_record() {
+                me.process_record(record);
+                continue;
+            }
+            let start = me.read_buf.len();
+            me.read_buf.resize(start + READ_CHUNK_LEN, 0);
+            let mut read_buf = ReadBuf::new(&mut me.read_buf[start..]);
+            let res = Pin::new(&mut me.inner).poll_read(cx, &mut read_buf);
+            let n = read_buf.filled().len();
+            me.read_buf.truncate(start + n);
+            ready!(res)?;
+            if n == 0 {
+                if !me.read_buf.is_empty() {
+                    return Poll::Ready(Err(io::ErrorKind::UnexpectedEof.into()));
+                }
+                me.read_state = ReadState::Eof;
+            }
+        }
+    }
+}
+
+impl AsyncWrite for ShadowTlsStream {
+    fn poll_write(self: Pin<&mut Self>, cx: &mut Context, buf: &[u8]) -> Poll<io::Result<usize>> {
+        let me = self.get_mut();
+        if matches!(me.read_state, ReadState::Failing | ReadState::Failed) {
+            return Poll::Ready(Err(io::ErrorKind::BrokenPipe.into()));
+        }
+        ready!(me.poll_drain(cx))?;
+        if buf.is_empty() {
+            return Poll::Ready(Ok(0));
+        }
+        let data = &buf[..buf.len().min(MAX_PAYLOAD_LEN)];
+        let tag = me.client_hmac.tag(data);
+        me.write_buf.push(tls::CONTENT_APPLICATION_DATA);
+        me.write_buf.extend_from_slice(&[3, 3]);
+        me.write_buf
+            .extend_from_slice(&((HMAC_LEN + data.len()) as u16).to_be_bytes());
+        me.write_buf.extend_from_slice(&tag);
+        me.write_buf.extend_from_slice(data);
+        me.records_sent += 1;
+        // The record is taken, what doesn't go out now is sent on the next
+        // write or flush.
+        if let Poll::Ready(Err(e)) = me.poll_drain(cx) {
+            return Poll::Ready(Err(e));
+        }
+        Poll::Ready(Ok(data.len()))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context) -> Poll<io::Result<()>> {
+        let me = self.get_mut();
+        ready!(me.poll_drain(cx))?;
+        Pin::new(&mut me.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context) -> Poll<io::Result<()>> {
+        let me = self.get_mut();
+        ready!(me.poll_drain(cx))?;
+        Pin::new(&mut me.inner).poll_shutdown(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    use super::super::tls::CipherSuite;
+    use super::*;
+
+    fn established() -> Established {
+        let suite = CipherSuite::from_id(0x1301).unwrap();
+        Established {
+            handshake_hmac: Hmac::new("password", &[b"random"]),
+            client_hmac: Hmac::new("password", &[b"random", b"C"]),
+            server_hmac: Hmac::new("password", &[b"random", b"S"]),
+            alert_key: suite.record_key(&suite.hash(b"secret")),
+        }
+    }
+
+    fn server_record(hmac: &mut Hmac, data: &[u8]) -> Vec<u8> {
+        let mut record = vec![tls::CONTENT_APPLICATION_DATA, 3, 3];
+        record.extend_from_slice(&((HMAC_LEN + data.len()) as u16).to_be_bytes());
+        record.extend_from_slice(&hmac.tag(data));
+        record.extend_from_slice(data);
+        record
+    }
+
+    #[tokio::test]
+    async fn test_relay() {
+        let (client, mut server) = tokio::io::duplex(1 << 16);
+        let mut stream = ShadowTlsStream::new(Box::new(client), established());
+
+        stream.write_all(b"hello").await.unwrap();
+        stream.flush().await.unwrap();
+        let mut record = [0u8; tls::RECORD_HEADER_LEN + HMAC_LEN + 5];
+        server.read_exact(&mut record).await.unwrap();
+        assert_eq!(&record[..5], &[23, 3, 3, 0, 9]);
+        let mut client_hmac = Hmac::new("password", &[b"random", b"C"]);
+        assert!(client_hmac.verify(&record[9..], &record[5..9]));
+        assert_eq!(&record[9..], b"hello");
+
+        // A leftover record of the handshake server is skipped.
+        let mut handshake_hmac = Hmac::new("password", &[b"random"]);
+        let mut server_hmac = Hmac::new("password", &[b"random", b"S"]);
+        let mut records = server_record(&mut handshake_hmac, b"ticket");
+        records.extend(server_record(&mut server_hmac, b"world"));
+        server.write_all(&records).await.unwrap();
+        let mut buf = [0u8; 5];
+        stream.read_exact(&mut buf).await.unwrap();
+        assert_eq!(&buf, b"world");
+
+        // A record with a bad tag gets an alert back.
+        let mut record = server_record(&mut server_hmac, b"again");
+        record[6] ^= 1;
+        server.write_all(&record).await.unwrap();
+        let err = stream.read(&mut buf).await.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        let mut alert = Vec::new();
+        server.read_to_end(&mut alert).await.unwrap();
+        assert_eq!(alert[0], tls::CONTENT_APPLICATION_DATA);
+        // The alert, its content type and the AEAD tag.
+        assert_eq!(alert.len(), tls::RECORD_HEADER_LEN + 2 + 1 + 16);
+        assert!(stream.write(b"x").await.is_err());
+    }
+}
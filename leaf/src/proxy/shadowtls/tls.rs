@@ -0,0 +1,563 @@
+//! The parts of a TLS 1.3 client the handshake with the mirrored site needs:
+//! the ClientHello, the key schedule and the record protection.
+//!
+//! ShadowTLS tags the session id of the ClientHello with an HMAC over the
+//! ClientHello itself, which a TLS library can't be asked to do without
+//! breaking its transcript, hence the handshake is driven by hand. The
+//! certificate of the site isn't checked, the shadowtls server is
+//! authenticated by the HMAC tags on the records it relays instead.
+
+use rand::seq::SliceRandom;
+use rand::{Rng, RngCore};
+use ring::{aead, agreement, digest, hkdf, hmac};
+
+pub const RECORD_HEADER_LEN: usize = 5;
+// The largest ciphertext record a TLS 1.3 peer may send.
+pub const MAX_RECORD_LEN: usize = 16384 + 256;
+
+pub const CONTENT_CHANGE_CIPHER_SPEC: u8 = 20;
+pub const CONTENT_ALERT: u8 = 21;
+pub const CONTENT_HANDSHAKE: u8 = 22;
+pub const CONTENT_APPLICATION_DATA: u8 = 23;
+
+pub const HANDSHAKE_CLIENT_HELLO: u8 = 1;
+pub const HANDSHAKE_SERVER_HELLO: u8 = 2;
+pub const HANDSHAKE_CERTIFICATE: u8 = 11;
+pub const HANDSHAKE_CERTIFICATE_REQUEST: u8 = 13;
+pub const HANDSHAKE_FINISHED: u8 = 20;
+
+pub const ALERT_CLOSE_NOTIFY: u8 = 0;
+pub const ALERT_UNEXPECTED_MESSAGE: u8 = 10;
+pub const ALERT_BAD_RECORD_MAC: u8 = 20;
+pub const ALERT_RECORD_OVERFLOW: u8 = 22;
+pub const ALERT_HANDSHAKE_FAILURE: u8 = 40;
+pub const ALERT_ILLEGAL_PARAMETER: u8 = 47;
+pub const ALERT_DECODE_ERROR: u8 = 50;
+pub const ALERT_DECRYPT_ERROR: u8 = 51;
+pub const ALERT_PROTOCOL_VERSION: u8 = 70;
+
+const ALERT_LEVEL_WARNING: u8 = 1;
+const ALERT_LEVEL_FATAL: u8 = 2;
+
+pub const TLS13: u16 = 0x0304;
+const X25519: u16 = 0x001d;
+
+/// Where the session id starts in the ClientHello handshake message.
+pub const SESSION_ID_OFFSET: usize = 4 + 2 + 32 + 1;
+pub const SESSION_ID_LEN: usize = 32;
+
+// The random of a ServerHello which is a HelloRetryRequest.
+const HELLO_RETRY_RANDOM: [u8; 32] = [
+    0xcf, 0x21, 0xad, 0x74, 0xe5, 0x9a, 0x61, 0x11, 0xbe, 0x1d, 0x8c, 0x02, 0x1e, 0x65, 0xb8, 0x91,
+    0xc2, 0xa2, 0x11, 0x16, 0x7a, 0xbb, 0x8c, 0x5e, 0x07, 0x9e, 0x09, 0xe2, 0xc8, 0xa8, 0x33, 0x9c,
+];
+
+// As a browser offers them, though only the TLS 1.3 suites are supported.
+const CIPHER_SUITES: &[u16] = &[
+    0x1301, 0x1302, 0x1303, 0xc02b, 0xc02f, 0xc02c, 0xc030, 0xcca9, 0xcca8, 0xc013, 0xc014, 0x009c,
+    0x009d, 0x002f, 0x0035,
+];
+const SUPPORTED_GROUPS: &[u16] = &[X25519, 0x0017, 0x0018];
+const SIGNATURE_ALGORITHMS: &[u16] = &[
+    0x0403, 0x0804, 0x0401, 0x0503, 0x0805, 0x0501, 0x0806, 0x0601,
+];
+const ALPN_PROTOCOLS: &[&[u8]] = &[b"h2", b"http/1.1"];
+
+fn grease() -> u16 {
+    0x0a0a + 0x1010 * rand::thread_rng().gen_range(0..16u16)
+}
+
+fn put_u16(buf: &mut Vec<u8>, v: u16) {
+    buf.extend_from_slice(&v.to_be_bytes());
+}
+
+fn put_u24(buf: &mut Vec<u8>, v: usize) {
+    buf.extend_from_slice(&(v as u32).to_be_bytes()[1..]);
+}
+
+fn put_extension(buf: &mut Vec<u8>, ty: u16, body: &[u8]) {
+    put_u16(buf, ty);
+    put_u16(buf, body.len() as u16);
+    buf.extend_from_slice(body);
+}
+
+/// Wraps a handshake message body with its header.
+pub fn handshake_message(ty: u8, body: &[u8]) -> Vec<u8> {
+    let mut msg = Vec::with_capacity(4 + body.len());
+    msg.push(ty);
+    put_u24(&mut msg, body.len());
+    msg.extend_from_slice(body);
+    msg
+}
+
+/// A plaintext record.
+pub fn record(content_type: u8, payload: &[u8]) -> Vec<u8> {
+    // The ClientHello goes out with the legacy version of TLS 1.0, as
+    // browsers send it.
+    let version: u16 = if content_type == CONTENT_HANDSHAKE {
+        0x0301
+    } else {
+        0x0303
+    };
+    let mut buf = Vec::with_capacity(RECORD_HEADER_LEN + payload.len());
+    buf.push(content_type);
+    put_u16(&mut buf, version);
+    put_u16(&mut buf, payload.len() as u16);
+    buf.extend_from_slice(payload);
+    buf
+}
+
+pub fn alert(level_fatal: bool, description: u8) -> [u8; 2] {
+    let level = if level_fatal {
+        ALERT_LEVEL_FATAL
+    } else {
+        ALERT_LEVEL_WARNING
+    };
+    [level, description]
+}
+
+/// Builds a browser like ClientHello for `server_name` with an X25519 key
+/// share, returning the handshake message and the private key.
+pub fn client_hello(server_name: &str) -> (Vec<u8>, agreement::EphemeralPrivateKey) {
+    let rng = ring::rand::SystemRandom::new();
+    let private_key = agreement::EphemeralPrivateKey::generate(&agreement::X25519, &rng)
+        .expect("generate x25519 key");
+    let public_key = private_key
+        .compute_public_key()
+        .expect("compute x25519 public key");
+
+    let mut body = Vec::with_capacity(512);
+    put_u16(&mut body, 0x0303);
+    let mut random = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut random);
+    body.extend_from_slice(&random);
+    let mut session_id = [0u8; SESSION_ID_LEN];
+    rand::thread_rng().fill_bytes(&mut session_id);
+    body.push(SESSION_ID_LEN as u8);
+    body.extend_from_slice(&session_id);
+
+    put_u16(&mut body, (CIPHER_SUITES.len() as u16 + 1) * 2);
+    put_u16(&mut body, grease());
+    CIPHER_SUITES.iter().for_each(|s| put_u16(&mut body, *s));
+    // Only the null compression.
+    body.extend_from_slice(&[1, 0]);
+
+    let mut extensions: Vec<(u16, Vec<u8>)> = Vec::new();
+    let mut sni = Vec::new();
+    put_u16(&mut sni, server_name.len() as u16 + 3);
+    sni.push(0);
+    put_u16(&mut sni, server_name.len() as u16);
+    sni.extend_from_slice(server_name.as_bytes());
+    extensions.push((0x0000, sni));
+    // extended_master_secret, session_ticket, signed_certificate_timestamp
+    extensions.push((0x0017, Vec::new()));
+    extensions.push((0x0023, Vec::new()));
+    extensions.push((0x0012, Vec::new()));
+    // renegotiation_info, ec_point_formats, status_request
+    extensions.push((0xff01, vec![0]));
+    extensions.push((0x000b, vec![1, 0]));
+    extensions.push((0x0005, vec![1, 0, 0, 0, 0]));
+    let mut groups = Vec::new();
+    put_u16(&mut groups, (SUPPORTED_GROUPS.len() as u16 + 1) * 2);
+    put_u16(&mut groups, grease());
+    SUPPORTED_GROUPS
+        .iter()
+        .for_each(|g| put_u16(&mut groups, *g));
+    extensions.push((0x000a, groups));
+    let mut sig_algs = Vec::new();
+    put_u16(&mut sig_algs, SIGNATURE_ALGORITHMS.len() as u16 * 2);
+    SIGNATURE_ALGORITHMS
+        .iter()
+        .for_each(|s| put_u16(&mut sig_algs, *s));
+    extensions.push((0x000d, sig_algs));
+    let mut alpn = Vec::new();
+    let alpn_len: usize = ALPN_PROTOCOLS.iter().map(|p| p.len() + 1).sum();
+    put_u16(&mut alpn, alpn_len as u16);
+    for p in ALPN_PROTOCOLS {
+        alpn.push(p.len() as u8);
+        alpn.extend_from_slice(p);
+    }
+    extensions.push((0x0010, alpn));
+    let mut key_share = Vec::new();
+    let pub_key = public_key.as_ref();
+    put_u16(&mut key_share, (4 + 1 + 4 + pub_key.len()) as u16);
+    put_u16(&mut key_share, grease());
+    put_u16(&mut key_share, 1);
+    key_share.push(0);
+    put_u16(&mut key_share, X25519);
+    put_u16(&mut key_share, pub_key.len() as u16);
+    key_share.extend_from_slice(pub_key);
+    extensions.push((0x0033, key_share));
+    // psk_key_exchange_modes: psk_dhe_ke
+    extensions.push((0x002d, vec![1, 1]));
+    let mut versions = vec![6];
+    put_u16(&mut versions, grease());
+    put_u16(&mut versions, TLS13);
+    put_u16(&mut versions, 0x0303);
+    extensions.push((0x002b, versions));
+    // compress_certificate: brotli
+    extensions.push((0x001b, vec![2, 0, 2]));
+    // Browsers shuffle the extensions between the GREASE ones too.
+    extensions.shuffle(&mut rand::thread_rng());
+
+    // The two GREASE extensions mustn't be taken for a duplicate.
+    let first_grease = grease();
+    let last_grease = loop {
+        let x = grease();
+        if x != first_grease {
+            break x;
+        }
+    };
+    let mut ext_buf = Vec::new();
+    put_extension(&mut ext_buf, first_grease, &[]);
+    for (ty, ext) in &extensions {
+        put_extension(&mut ext_buf, *ty, ext);
+    }
+    put_extension(&mut ext_buf, last_grease, &[0]);
+    put_u16(&mut body, ext_buf.len() as u16);
+    body.extend_from_slice(&ext_buf);
+
+    (
+        handshake_message(HANDSHAKE_CLIENT_HELLO, &body),
+        private_key,
+    )
+}
+
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Option<&'a [u8]> {
+        if self.0.len() < n {
+            return None;
+        }
+        let (x, rest) = self.0.split_at(n);
+        self.0 = rest;
+        Some(x)
+    }
+
+    fn u8(&mut self) -> Option<u8> {
+        self.take(1).map(|x| x[0])
+    }
+
+    fn u16(&mut self) -> Option<u16> {
+        self.take(2).map(|x| u16::from_be_bytes([x[0], x[1]]))
+    }
+
+    fn vec_u8(&mut self) -> Option<&'a [u8]> {
+        let n = self.u8()? as usize;
+        self.take(n)
+    }
+
+    fn vec_u16(&mut self) -> Option<&'a [u8]> {
+        let n = self.u16()? as usize;
+        self.take(n)
+    }
+}
+
+pub struct ServerHello {
+    pub random: [u8; 32],
+    pub cipher_suite: u16,
+    pub version: Option<u16>,
+    pub key_share: Option<(u16, Vec<u8>)>,
+}
+
+impl ServerHello {
+    /// Parses the body of a ServerHello message.
+    pub fn parse(body: &[u8]) -> Option<Self> {
+        let mut r = Reader(body);
+        r.u16()?;
+        let random: [u8; 32] = r.take(32)?.try_into().ok()?;
+        r.vec_u8()?;
+        let cipher_suite = r.u16()?;
+        r.u8()?;
+        let mut version = None;
+        let mut key_share = None;
+        if !r.0.is_empty() {
+            let mut exts = Reader(r.vec_u16()?);
+            while !exts.0.is_empty() {
+                let ty = exts.u16()?;
+                let mut ext = Reader(exts.vec_u16()?);
+                match ty {
+                    0x002b => version = Some(ext.u16()?),
+                    0x0033 => {
+                        let group = ext.u16()?;
+                        key_share = Some((group, ext.vec_u16()?.to_vec()));
+                    }
+                    _ => (),
+                }
+            }
+        }
+        Some(Self {
+            random,
+            cipher_suite,
+            version,
+            key_share,
+        })
+    }
+
+    pub fn is_hello_retry_request(&self) -> bool {
+        self.random == HELLO_RETRY_RANDOM
+    }
+
+    pub fn shared_secret(&self, private_key: agreement::EphemeralPrivateKey) -> Option<Vec<u8>> {
+        let (group, key) = self.key_share.as_ref()?;
+        if *group != X25519 {
+            return None;
+        }
+        agreement::agree_ephemeral(
+            private_key,
+            &agreement::UnparsedPublicKey::new(&agreement::X25519, key),
+            |secret| secret.to_vec(),
+        )
+        .ok()
+    }
+}
+
+/// Reassembles the handshake messages carried by records.
+#[derive(Default)]
+pub struct HandshakeReader(Vec<u8>);
+
+impl HandshakeReader {
+    pub fn push(&mut self, data: &[u8]) {
+        self.0.extend_from_slice(data);
+    }
+
+    /// Returns the next complete message, header included.
+    pub fn next_message(&mut self) -> Option<Vec<u8>> {
+        if self.0.len() < 4 {
+            return None;
+        }
+        let len = u32::from_be_bytes([0, self.0[1], self.0[2], self.0[3]]) as usize;
+        if self.0.len() < 4 + len {
+            return None;
+        }
+        Some(self.0.drain(..4 + len).collect())
+    }
+}
+
+/// The certificate_request_context of a CertificateRequest body.
+pub fn certificate_request_context(body: &[u8]) -> Option<Vec<u8>> {
+    Reader(body).vec_u8().map(|x| x.to_vec())
+}
+
+/// An empty Certificate message answering a CertificateRequest.
+pub fn empty_certificate(context: &[u8]) -> Vec<u8> {
+    let mut body = vec![context.len() as u8];
+    body.extend_from_slice(context);
+    put_u24(&mut body, 0);
+    handshake_message(HANDSHAKE_CERTIFICATE, &body)
+}
+
+struct Len(usize);
+
+impl hkdf::KeyType for Len {
+    fn len(&self) -> usize {
+        self.0
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct CipherSuite {
+    aead: &'static aead::Algorithm,
+    hash: &'static digest::Algorithm,
+    hkdf: hkdf::Algorithm,
+    hmac: hmac::Algorithm,
+}
+
+impl CipherSuite {
+    pub fn from_id(id: u16) -> Option<Self> {
+        match id {
+            0x1301 => Some(Self {
+                aead: &aead::AES_128_GCM,
+                hash: &digest::SHA256,
+                hkdf: hkdf::HKDF_SHA256,
+                hmac: hmac::HMAC_SHA256,
+            }),
+            0x1302 => Some(Self {
+                aead: &aead::AES_256_GCM,
+                hash: &digest::SHA384,
+                hkdf: hkdf::HKDF_SHA384,
+                hmac: hmac::HMAC_SHA384,
+            }),
+            0x1303 => Some(Self {
+                aead: &aead::CHACHA20_POLY1305,
+                hash: &digest::SHA256,
+                hkdf: hkdf::HKDF_SHA256,
+                hmac: hmac::HMAC_SHA256,
+            }),
+            _ => None,
+        }
+    }
+
+    fn hash_len(&self) -> usize {
+        self.hash.output_len()
+    }
+
+    pub fn hash(&self, data: &[u8]) -> Vec<u8> {
+        digest::digest(self.hash, data).as_ref().to_vec()
+    }
+
+    fn extract(&self, salt: &[u8], ikm: &[u8]) -> Vec<u8> {
+        let salt = if salt.is_empty() {
+            vec![0u8; self.hash_len()]
+        } else {
+            salt.to_vec()
+        };
+        hmac::sign(&hmac::Key::new(self.hmac, &salt), ikm)
+            .as_ref()
+            .to_vec()
+    }
+
+    fn expand_label(&self, secret: &[u8], label: &[u8], context: &[u8], len: usize) -> Vec<u8> {
+        let out_len = (len as u16).to_be_bytes();
+        let label_len = [(6 + label.len()) as u8];
+        let context_len = [context.len() as u8];
+        let info = [
+            &out_len[..],
+            &label_len[..],
+            &b"tls13 "[..],
+            label,
+            &context_len[..],
+            context,
+        ];
+        let mut out = vec![0u8; len];
+        hkdf::Prk::new_less_safe(self.hkdf, secret)
+            .expand(&info, Len(len))
+            .and_then(|okm| okm.fill(&mut out))
+            .expect("hkdf expand");
+        out
+    }
+
+    fn derive_secret(&self, secret: &[u8], label: &[u8], transcript: &[u8]) -> Vec<u8> {
+        self.expand_label(secret, label, &self.hash(transcript), self.hash_len())
+    }
+
+    /// Returns the handshake secret and the client and server handshake
+    /// traffic secrets for the transcript up to the ServerHello.
+    pub fn handshake_secrets(&self, shared: &[u8], transcript: &[u8]) -> [Vec<u8>; 3] {
+        let early = self.extract(&[], &vec![0u8; self.hash_len()]);
+        let derived = self.derive_secret(&early, b"derived", &[]);
+        let handshake = self.extract(&derived, shared);
+        let client = self.derive_secret(&handshake, b"c hs traffic", transcript);
+        let server = self.derive_secret(&handshake, b"s hs traffic", transcript);
+        [handshake, client, server]
+    }
+
+    /// The client application traffic secret for the transcript up to the
+    /// server Finished.
+    pub fn client_application_secret(&self, handshake: &[u8], transcript: &[u8]) -> Vec<u8> {
+        let derived = self.derive_secret(handshake, b"derived", &[]);
+        let master = self.extract(&derived, &vec![0u8; self.hash_len()]);
+        self.derive_secret(&master, b"c ap traffic", transcript)
+    }
+
+    /// The verify data of a Finished message.
+    pub fn finished(&self, traffic_secret: &[u8], transcript: &[u8]) -> Vec<u8> {
+        let key = self.expand_label(traffic_secret, b"finished", &[], self.hash_len());
+        hmac::sign(&hmac::Key::new(self.hmac, &key), &self.hash(transcript))
+            .as_ref()
+            .to_vec()
+    }
+
+    pub fn record_key(&self, traffic_secret: &[u8]) -> RecordKey {
+        let key = self.expand_label(traffic_secret, b"key", &[], self.aead.key_len());
+        let iv = self.expand_label(traffic_secret, b"iv", &[], aead::NONCE_LEN);
+        RecordKey {
+            key: aead::LessSafeKey::new(
+                aead::UnboundKey::new(self.aead, &key).expect("aead key length"),
+            ),
+            iv: iv.try_into().expect("aead iv length"),
+            seq: 0,
+        }
+    }
+}
+
+/// Protects the records of one direction.
+pub struct RecordKey {
+    key: aead::LessSafeKey,
+    iv: [u8; aead::NONCE_LEN],
+    seq: u64,
+}
+
+impl RecordKey {
+    fn nonce(&mut self) -> aead::Nonce {
+        let mut nonce = self.iv;
+        for (n, s) in nonce[4..].iter_mut().zip(self.seq.to_be_bytes()) {
+            *n ^= s;
+        }
+        self.seq += 1;
+        aead::Nonce::assume_unique_for_key(nonce)
+    }
+
+    /// Skips the sequence numbers of records sent without this key, such as
+    /// the data records the shadowtls server doesn't decrypt.
+    pub fn skip(&mut self, n: u64) {
+        self.seq += n;
+    }
+
+    /// Encrypts `payload` of `content_type` into a whole record.
+    pub fn seal(&mut self, content_type: u8, payload: &[u8]) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(payload.len() + 1 + self.key.algorithm().tag_len());
+        buf.extend_from_slice(payload);
+        buf.push(content_type);
+        let len = buf.len() + self.key.algorithm().tag_len();
+        let mut header = [CONTENT_APPLICATION_DATA, 3, 3, 0, 0];
+        header[3..].copy_from_slice(&(len as u16).to_be_bytes());
+        let nonce = self.nonce();
+        self.key
+            .seal_in_place_append_tag(nonce, aead::Aad::from(header), &mut buf)
+            .expect("seal record");
+        let mut record = header.to_vec();
+        record.extend_from_slice(&buf);
+        record
+    }
+
+    /// Decrypts the payload of an application data record, returning the
+    /// inner content type and the plaintext.
+    pub fn open(&mut self, payload: &mut [u8]) -> Option<(u8, Vec<u8>)> {
+        let mut header = [CONTENT_APPLICATION_DATA, 3, 3, 0, 0];
+        header[3..].copy_from_slice(&(payload.len() as u16).to_be_bytes());
+        let nonce = self.nonce();
+        let plain = self
+            .key
+            .open_in_place(nonce, aead::Aad::from(header), payload)
+            .ok()?;
+        let end = plain.iter().rposition(|b| *b != 0)?;
+        Some((plain[end], plain[..end].to_vec()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_client_hello() {
+        let (msg, _) = client_hello("www.example.com");
+        assert_eq!(msg[0], HANDSHAKE_CLIENT_HELLO);
+        let len = u32::from_be_bytes([0, msg[1], msg[2], msg[3]]) as usize;
+        assert_eq!(len + 4, msg.len());
+        assert_eq!(msg[SESSION_ID_OFFSET - 1] as usize, SESSION_ID_LEN);
+        let name = b"www.example.com";
+        assert!(msg.windows(name.len()).any(|w| w == name));
+    }
+
+    #[test]
+    fn test_record_key() {
+        let suite = CipherSuite::from_id(0x1301).unwrap();
+        let secret = suite.hash(b"secret");
+        let mut sealer = suite.record_key(&secret);
+        let mut opener = suite.record_key(&secret);
+        for i in 0..3u8 {
+            let record = sealer.seal(CONTENT_HANDSHAKE, &[i; 10]);
+            assert_eq!(record[0], CONTENT_APPLICATION_DATA);
+            let mut payload = record[RECORD_HEADER_LEN..].to_vec();
+            let (ty, plain) = opener.open(&mut payload).unwrap();
+            assert_eq!(ty, CONTENT_HANDSHAKE);
+            assert_eq!(plain, vec![i; 10]);
+        }
+        let mut record = sealer.seal(CONTENT_ALERT, &[2, 20]);
+        record[RECORD_HEADER_LEN] ^= 1;
+        assert!(opener.open(&mut record[RECORD_HEADER_LEN..]).is_none());
+    }
+}
@@ -1,6 +1,7 @@
 use std::convert::TryFrom;
 use std::io;
 use std::net::SocketAddr;
+use std::sync::Arc;
 
 use anyhow::anyhow;
 use async_trait::async_trait;
@@ -11,14 +12,27 @@ use crate::{
     session::{DatagramSource, SocksAddr, SocksAddrWireType},
 };
 
-pub struct Handler;
+use super::UdpAssociations;
+
+pub struct Handler {
+    associations: Arc<UdpAssociations>,
+}
+
+impl Handler {
+    pub fn new(associations: Arc<UdpAssociations>) -> Self {
+        Self { associations }
+    }
+}
 
 #[async_trait]
 impl InboundDatagramHandler for Handler {
     async fn handle<'a>(&'a self, socket: AnyInboundDatagram) -> io::Result<AnyInboundTransport> {
         tracing::trace!("handling inbound datagram");
         Ok(InboundTransport::Datagram(
-            Box::new(Datagram { socket }),
+            Box::new(Datagram {
+                socket,
+                associations: self.associations.clone(),
+            }),
             None,
         ))
     }
@@ -26,6 +40,7 @@ impl InboundDatagramHandler for Handler {
 
 pub struct Datagram {
     socket: Box<dyn InboundDatagram>,
+    associations: Arc<UdpAssociations>,
 }
 
 impl InboundDatagram for Datagram {
@@ -37,7 +52,7 @@ impl InboundDatagram for Datagram {
     ) {
         let (rh, sh) = self.socket.split();
         (
-            Box::new(DatagramRecvHalf(rh)),
+            Box::new(DatagramRecvHalf(rh, self.associations)),
             Box::new(DatagramSendHalf(sh)),
         )
     }
@@ -47,7 +62,7 @@ impl InboundDatagram for Datagram {
     }
 }
 
-pub struct DatagramRecvHalf(Box<dyn InboundDatagramRecvHalf>);
+pub struct DatagramRecvHalf(Box<dyn InboundDatagramRecvHalf>, Arc<UdpAssociations>);
 
 #[async_trait]
 impl InboundDatagramRecvHalf for DatagramRecvHalf {
@@ -56,7 +71,14 @@ impl InboundDatagramRecvHalf for DatagramRecvHalf {
         buf: &mut [u8],
     ) -> ProxyResult<(usize, DatagramSource, SocksAddr)> {
         let mut recv_buf = vec![0u8; buf.len() + 512];
-        let (n, src_addr, _) = self.0.recv_from(&mut recv_buf).await?;
+        let (n, mut src_addr, _) = self.0.recv_from(&mut recv_buf).await?;
+        src_addr.user = self.1.user(src_addr.address.ip());
+        if self.1.required && src_addr.user.is_none() {
+            return Err(ProxyError::DatagramWarn(anyhow!(
+                "No authenticated association for {}",
+                src_addr.address
+            )));
+        }
         if n < 3 {
             return Err(ProxyError::DatagramWarn(anyhow!("Short message")));
         }
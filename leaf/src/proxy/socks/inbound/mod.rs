@@ -1,5 +1,86 @@
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex};
+
 mod datagram;
 mod stream;
 
 pub use datagram::Handler as DatagramHandler;
 pub use stream::Handler as StreamHandler;
+
+/// Users authenticated on UDP ASSOCIATE control connections, by client IP.
+/// Datagrams from a client are attributed to the user of its control
+/// connection, and dropped if there is none while authentication is required.
+pub struct UdpAssociations {
+    required: bool,
+    users: Mutex<HashMap<IpAddr, (String, usize)>>,
+}
+
+impl UdpAssociations {
+    pub fn new(required: bool) -> Self {
+        Self {
+            required,
+            users: Mutex::new(HashMap::new()),
+        }
+    }
+
+    // Registers `user` for datagrams from `ip` until the returned value is
+    // dropped. A later association from the same IP takes over the user.
+    fn insert(self: &Arc<Self>, ip: IpAddr, user: String) -> UdpAssociation {
+        let ip = ip.to_canonical();
+        let mut users = self.users.lock().unwrap();
+        let entry = users.entry(ip).or_insert_with(|| (String::new(), 0));
+        entry.0 = user;
+        entry.1 += 1;
+        UdpAssociation {
+            associations: self.clone(),
+            ip,
+        }
+    }
+
+    fn user(&self, ip: IpAddr) -> Option<String> {
+        let users = self.users.lock().unwrap();
+        users.get(&ip.to_canonical()).map(|(user, _)| user.clone())
+    }
+}
+
+struct UdpAssociation {
+    associations: Arc<UdpAssociations>,
+    ip: IpAddr,
+}
+
+impl Drop for UdpAssociation {
+    fn drop(&mut self) {
+        let mut users = self.associations.users.lock().unwrap();
+        if let Some(entry) = users.get_mut(&self.ip) {
+            entry.1 -= 1;
+            if entry.1 == 0 {
+                users.remove(&self.ip);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_udp_associations() {
+        let associations = Arc::new(UdpAssociations::new(true));
+        let ip: IpAddr = "192.168.1.2".parse().unwrap();
+        assert_eq!(associations.user(ip), None);
+
+        let a = associations.insert(ip, "alice".to_string());
+        // IPv4-mapped addresses from a dual-stack socket are the same client.
+        let mapped: IpAddr = "::ffff:192.168.1.2".parse().unwrap();
+        assert_eq!(associations.user(mapped).as_deref(), Some("alice"));
+
+        let b = associations.insert(ip, "bob".to_string());
+        assert_eq!(associations.user(ip).as_deref(), Some("bob"));
+        drop(b);
+        assert_eq!(associations.user(ip).as_deref(), Some("bob"));
+        drop(a);
+        assert_eq!(associations.user(ip), None);
+    }
+}
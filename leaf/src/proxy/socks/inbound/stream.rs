@@ -1,4 +1,6 @@
+use std::collections::HashMap;
 use std::io;
+use std::sync::Arc;
 
 use async_trait::async_trait;
 use bytes::{BufMut, BytesMut};
@@ -10,12 +12,33 @@ use crate::{
     session::{Session, SocksAddr, SocksAddrWireType},
 };
 
+use super::UdpAssociations;
+
+// SOCKS4 reply codes.
+const SOCKS4_GRANTED: u8 = 0x5a;
+const SOCKS4_REJECTED: u8 = 0x5b;
+
 pub struct Handler {
-    pub username: Option<String>,
-    pub password: Option<String>,
+    // Accounts by user name, authentication is required if not empty.
+    users: HashMap<String, String>,
+    // Whether SOCKS4 and SOCKS4a requests are served.
+    socks4: bool,
+    associations: Arc<UdpAssociations>,
 }
 
 impl Handler {
+    pub fn new(
+        users: HashMap<String, String>,
+        socks4: bool,
+        associations: Arc<UdpAssociations>,
+    ) -> Self {
+        Self {
+            users,
+            socks4,
+            associations,
+        }
+    }
+
     async fn handle_socks4(
         &self,
         mut sess: Session,
@@ -26,7 +49,9 @@ impl Handler {
         buf.resize(1 + 2 + 4, 0);
         stream.read_exact(&mut buf[..]).await?;
 
+        // Only CONNECT, BIND is rejected.
         if buf[0] != 0x01 {
+            reject_socks4(&mut stream).await?;
             return Err(io::Error::other(format!(
                 "unsupported socks4 cmd {}",
                 buf[0]
@@ -73,7 +98,7 @@ impl Handler {
         // Reply: VN=0, CD=90(Granted), DSTPORT, DSTIP
         let mut reply = BytesMut::new();
         reply.put_u8(0);
-        reply.put_u8(90);
+        reply.put_u8(SOCKS4_GRANTED);
         reply.put_u16(port);
         reply.put_slice(&ip_bytes);
         stream.write_all(&reply).await?;
@@ -103,7 +128,7 @@ impl Handler {
         // methods
         stream.read_exact(&mut buf[..]).await?;
         let mut method_accepted = false;
-        let supported_method: u8 = if self.username.is_some() { 0x02 } else { 0x00 };
+        let supported_method: u8 = if self.users.is_empty() { 0x00 } else { 0x02 };
 
         for method in buf[..].iter() {
             if method == &supported_method {
@@ -122,6 +147,7 @@ impl Handler {
 
         stream.write_all(&[0x05, supported_method]).await?;
 
+        let mut user = None;
         if supported_method == 0x02 {
             buf.resize(2, 0);
             // ver, ulen
@@ -136,7 +162,7 @@ impl Handler {
             buf.resize(ulen, 0);
             // uname
             stream.read_exact(&mut buf[..]).await?;
-            let username = String::from_utf8_lossy(&buf).to_string();
+            let username = String::from_utf8(buf.to_vec()).ok();
 
             buf.resize(1, 0);
             // plen
@@ -145,17 +171,27 @@ impl Handler {
             buf.resize(plen, 0);
             // passwd
             stream.read_exact(&mut buf[..]).await?;
-            let password = String::from_utf8_lossy(&buf).to_string();
-
-            if self.username.as_ref().unwrap() == &username
-                && self.password.as_ref().unwrap() == &password
-            {
-                stream.write_all(&[0x01, 0x00]).await?;
-            } else {
-                stream.write_all(&[0x01, 0x01]).await?;
-                return Err(io::Error::other("socks5 authentication failed"));
+
+            match username {
+                Some(username)
+                    if self
+                        .users
+                        .get(&username)
+                        .is_some_and(|password| password.as_bytes() == &buf[..]) =>
+                {
+                    stream.write_all(&[0x01, 0x00]).await?;
+                    user = Some(username);
+                }
+                username => {
+                    stream.write_all(&[0x01, 0x01]).await?;
+                    return Err(io::Error::other(format!(
+                        "socks5 authentication failed for user {}",
+                        username.as_deref().unwrap_or("<invalid>")
+                    )));
+                }
             }
         }
+        sess.user = user;
 
         // handle request
         buf.resize(3, 0);
@@ -202,8 +238,15 @@ impl Handler {
                 let relay_addr = SocksAddr::from(sess.local_addr);
                 relay_addr.write_buf(&mut buf, SocksAddrWireType::PortLast);
                 stream.write_all(&buf[..]).await?;
+                // The datagrams of the client are authenticated as long as
+                // the control connection stays open.
+                let association = sess
+                    .user
+                    .clone()
+                    .map(|user| self.associations.insert(sess.source.ip(), user));
                 tokio::spawn(
                     async move {
+                        let _association = association;
                         let mut buf = [0u8; 1];
                         // TODO explicitly drop resources allocated above before waiting?
                         // if stream.read_exact(&mut buf).await.is_err() {
@@ -224,6 +267,13 @@ impl Handler {
     }
 }
 
+async fn reject_socks4(stream: &mut AnyStream) -> io::Result<()> {
+    // VN=0, CD=91(Rejected), DSTPORT and DSTIP are ignored.
+    stream
+        .write_all(&[0, SOCKS4_REJECTED, 0, 0, 0, 0, 0, 0])
+        .await
+}
+
 #[async_trait]
 impl InboundStreamHandler for Handler {
     async fn handle<'a>(
@@ -236,9 +286,157 @@ impl InboundStreamHandler for Handler {
         stream.read_exact(&mut buf).await?;
         let span = sess.span();
         match buf[0] {
+            0x04 if !self.socks4 => {
+                reject_socks4(&mut stream).await?;
+                Err(io::Error::other("socks4 request rejected, socks4 disabled"))
+            }
+            // SOCKS4 has no password authentication.
+            0x04 if !self.users.is_empty() => {
+                reject_socks4(&mut stream).await?;
+                Err(io::Error::other(
+                    "socks4 request rejected, authentication required",
+                ))
+            }
             0x04 => self.handle_socks4(sess, stream).instrument(span).await,
             0x05 => self.handle_socks5(sess, stream).instrument(span).await,
             v => Err(io::Error::other(format!("unknown socks version {}", v))),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn handler() -> Handler {
+        let mut users = HashMap::new();
+        users.insert("alice".to_string(), "pässwörd".to_string());
+        Handler::new(users, true, Arc::new(UdpAssociations::new(true)))
+    }
+
+    fn auth_request(username: &str, password: &str) -> Vec<u8> {
+        let mut req = vec![0x05, 0x01, 0x02, 0x01, username.len() as u8];
+        req.extend_from_slice(username.as_bytes());
+        req.push(password.len() as u8);
+        req.extend_from_slice(password.as_bytes());
+        req
+    }
+
+    #[tokio::test]
+    async fn test_socks5_inbound_auth() {
+        let (mut c, s) = tokio::io::duplex(1024);
+        let mut req = auth_request("alice", "pässwörd");
+        // CONNECT 1.2.3.4:80
+        req.extend_from_slice(&[0x05, 0x01, 0x00, 0x01, 1, 2, 3, 4, 0, 80]);
+        c.write_all(&req).await.unwrap();
+        let transport = handler()
+            .handle(Session::default(), Box::new(s))
+            .await
+            .unwrap();
+        let mut reply = [0u8; 4];
+        c.read_exact(&mut reply).await.unwrap();
+        assert_eq!(reply, [0x05, 0x02, 0x01, 0x00]);
+        match transport {
+            InboundTransport::Stream(_, sess) => {
+                assert_eq!(sess.user.as_deref(), Some("alice"));
+                assert_eq!(sess.destination.to_string(), "1.2.3.4:80");
+            }
+            _ => panic!("unexpected transport"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_socks5_inbound_auth_rejected() {
+        let (mut c, s) = tokio::io::duplex(1024);
+        c.write_all(&auth_request("alice", "wrong")).await.unwrap();
+        assert!(handler()
+            .handle(Session::default(), Box::new(s))
+            .await
+            .is_err());
+        let mut reply = [0u8; 4];
+        c.read_exact(&mut reply).await.unwrap();
+        assert_eq!(reply, [0x05, 0x02, 0x01, 0x01]);
+    }
+
+    #[tokio::test]
+    async fn test_socks5_inbound_no_auth_rejected() {
+        let (mut c, s) = tokio::io::duplex(1024);
+        c.write_all(&[0x05, 0x01, 0x00]).await.unwrap();
+        assert!(handler()
+            .handle(Session::default(), Box::new(s))
+            .await
+            .is_err());
+        let mut reply = [0u8; 2];
+        c.read_exact(&mut reply).await.unwrap();
+        assert_eq!(reply, [0x05, 0xff]);
+
+        // SOCKS4 can't authenticate.
+        let (mut c, s) = tokio::io::duplex(1024);
+        c.write_all(&[0x04, 0x01, 0, 80, 1, 2, 3, 4, 0])
+            .await
+            .unwrap();
+        assert!(handler()
+            .handle(Session::default(), Box::new(s))
+            .await
+            .is_err());
+        let mut reply = [0u8; 8];
+        c.read_exact(&mut reply).await.unwrap();
+        assert_eq!(reply[1], SOCKS4_REJECTED);
+    }
+
+    fn no_auth_handler(socks4: bool) -> Handler {
+        Handler::new(
+            HashMap::new(),
+            socks4,
+            Arc::new(UdpAssociations::new(false)),
+        )
+    }
+
+    // Sends a SOCKS4 request and returns the reply and the destination of
+    // the session if granted.
+    async fn socks4_request(handler: &Handler, req: &[u8]) -> ([u8; 8], Option<SocksAddr>) {
+        let (mut c, s) = tokio::io::duplex(1024);
+        c.write_all(req).await.unwrap();
+        let destination = match handler.handle(Session::default(), Box::new(s)).await {
+            Ok(InboundTransport::Stream(_, sess)) => Some(sess.destination),
+            _ => None,
+        };
+        let mut reply = [0u8; 8];
+        c.read_exact(&mut reply).await.unwrap();
+        (reply, destination)
+    }
+
+    #[tokio::test]
+    async fn test_socks4_inbound() {
+        let handler = no_auth_handler(true);
+
+        let (reply, destination) =
+            socks4_request(&handler, &[0x04, 0x01, 0, 80, 1, 2, 3, 4, b'u', 0]).await;
+        assert_eq!(reply, [0, SOCKS4_GRANTED, 0, 80, 1, 2, 3, 4]);
+        assert_eq!(destination.unwrap().to_string(), "1.2.3.4:80");
+
+        // SOCKS4a with the host name after the user ID.
+        let mut req = vec![0x04, 0x01, 0x01, 0xbb, 0, 0, 0, 1, 0];
+        req.extend_from_slice(b"example.com\0");
+        let (reply, destination) = socks4_request(&handler, &req).await;
+        assert_eq!(reply[1], SOCKS4_GRANTED);
+        assert_eq!(
+            destination.unwrap(),
+            SocksAddr::Domain("example.com".to_string(), 443)
+        );
+
+        // BIND
+        let (reply, destination) =
+            socks4_request(&handler, &[0x04, 0x02, 0, 80, 1, 2, 3, 4, 0]).await;
+        assert_eq!(reply[1], SOCKS4_REJECTED);
+        assert!(destination.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_socks4_inbound_disabled() {
+        let (reply, destination) =
+            socks4_request(&no_auth_handler(false), &[0x04, 0x01, 0, 80, 1, 2, 3, 4, 0]).await;
+        assert_eq!(reply[1], SOCKS4_REJECTED);
+        assert!(destination.is_none());
+    }
+}
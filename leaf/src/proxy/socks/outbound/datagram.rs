@@ -1,14 +1,14 @@
-use std::{
-    io::{Error, Result},
-    sync::Arc,
-};
+use std::{convert::TryFrom, io};
 
-use async_socks5::{AddrKind, Auth, SocksDatagram};
 use async_trait::async_trait;
+use bytes::{BufMut, BytesMut};
 use futures::future::TryFutureExt;
-use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite};
+use tokio::sync::watch;
 
-use crate::{app::SyncDnsClient, common::resolver::Resolver, proxy::*, session::*};
+use crate::{app::SyncDnsClient, proxy::*, session::*};
+
+use super::{credentials, negotiate, request, CMD_UDP_ASSOCIATE};
 
 pub struct Handler {
     pub address: String,
@@ -19,7 +19,6 @@ pub struct Handler {
 }
 
 impl TcpConnector for Handler {}
-impl UdpConnector for Handler {}
 
 #[async_trait]
 impl OutboundDatagramHandler for Handler {
@@ -34,111 +33,311 @@ impl OutboundDatagramHandler for Handler {
     async fn handle<'a>(
         &'a self,
         sess: &'a Session,
-        _transport: Option<AnyOutboundTransport>,
+        transport: Option<AnyOutboundTransport>,
     ) -> io::Result<AnyOutboundDatagram> {
         tracing::trace!("handling outbound datagram");
-        // TODO support chaining, this requires implementing our own socks5 client
-        let stream = self
+        let socket = if let Some(OutboundTransport::Datagram(socket)) = transport {
+            socket
+        } else {
+            return Err(io::Error::other("invalid socks input"));
+        };
+        // The control connection is always dialed directly, only the
+        // datagrams go through the transport.
+        let mut control = self
             .new_tcp_stream(self.dns_client.clone(), &self.address, &self.port)
             .await?;
-        let mut indicator = sess.source;
-        if let Ok(ip) = self.address.parse::<IpAddr>() {
-            if ip.is_loopback() {
-                indicator = SocketAddr::new(ip, 0);
-            }
-        }
-        let socket = self.new_udp_socket(&indicator).await?;
+        let auth = credentials(&self.username, &self.password);
+        let relay = associate(&mut control, auth).await?;
+        let relay = relay_addr(relay, &self.address)?;
+        Ok(Box::new(Datagram::new(
+            control,
+            socket,
+            relay,
+            &sess.destination,
+        )))
+    }
+}
 
-        // Resolve the SOCKS server address to IP (handles both IP and domain names)
-        let mut resolver = Resolver::new(self.dns_client.clone(), &self.address, &self.port)
-            .await
-            .map_err(|e| Error::other(format!("resolve SOCKS server address failed: {}", e)))?;
-        let server_addr = resolver
-            .next()
-            .ok_or_else(|| Error::other("no resolved address for SOCKS server"))?;
-
-        let auth = match (&self.username, &self.password) {
-            (auth_username, _) if auth_username.is_empty() => None,
-            (auth_username, auth_password) => Some(Auth {
-                username: auth_username.to_owned(),
-                password: auth_password.to_owned(),
-            }),
-        };
+/// Sends a UDP ASSOCIATE request on the control connection, returns the relay
+/// address from the server reply.
+async fn associate<S>(stream: &mut S, auth: Option<(&str, &str)>) -> io::Result<SocksAddr>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    negotiate(stream, auth).await?;
+    // The address the client sends from isn't known before the first
+    // datagram, all zeros let the server accept any.
+    request(stream, CMD_UDP_ASSOCIATE, &SocksAddr::any_ipv4()).await
+}
 
-        let socket = SocksDatagram::associate(stream, socket, auth, server_addr.into())
-            .map_err(Error::other)
-            .await?;
-        Ok(Box::new(Datagram { socket }))
+// An unspecified relay address means the relay is on the same host as the
+// socks server.
+fn relay_addr(relay: SocksAddr, server_address: &str) -> io::Result<SocksAddr> {
+    match relay {
+        SocksAddr::Ip(addr) if addr.ip().is_unspecified() => {
+            SocksAddr::try_from((server_address, addr.port()))
+        }
+        relay => Ok(relay),
     }
 }
 
-pub struct Datagram<S> {
-    pub socket: SocksDatagram<S>,
+fn control_closed() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::ConnectionAborted,
+        "socks5 control connection closed",
+    )
 }
 
-impl<S> OutboundDatagram for Datagram<S>
+// Keeps the control connection open while the datagram is in use, the
+// association ends as soon as the server closes it.
+async fn hold_control<S>(mut control: S, closed: watch::Sender<bool>)
 where
-    S: 'static + AsyncRead + AsyncWrite + Unpin + Send + Sync,
+    S: AsyncRead + Unpin,
 {
+    let mut buf = [0u8; 64];
+    tokio::select! {
+        _ = async {
+            // Nothing is expected on the control connection after the reply.
+            while let Ok(n) = control.read(&mut buf).await {
+                if n == 0 {
+                    break;
+                }
+            }
+        } => {
+            let _ = closed.send(true);
+        }
+        _ = closed.closed() => {}
+    }
+}
+
+pub struct Datagram {
+    socket: Box<dyn OutboundDatagram>,
+    relay: SocksAddr,
+    destination: Option<SocksAddr>,
+    closed: watch::Receiver<bool>,
+}
+
+impl Datagram {
+    pub fn new<S>(
+        control: S,
+        socket: Box<dyn OutboundDatagram>,
+        relay: SocksAddr,
+        destination: &SocksAddr,
+    ) -> Self
+    where
+        S: 'static + AsyncRead + AsyncWrite + Unpin + Send,
+    {
+        let (tx, closed) = watch::channel(false);
+        tokio::spawn(hold_control(control, tx));
+        let destination = match destination {
+            SocksAddr::Domain(domain, port) => Some(SocksAddr::Domain(domain.to_owned(), *port)),
+            _ => None,
+        };
+        Datagram {
+            socket,
+            relay,
+            destination,
+            closed,
+        }
+    }
+}
+
+impl OutboundDatagram for Datagram {
     fn split(
         self: Box<Self>,
     ) -> (
         Box<dyn OutboundDatagramRecvHalf>,
         Box<dyn OutboundDatagramSendHalf>,
     ) {
-        let rh = Arc::new(self.socket);
-        let sh = rh.clone();
+        let (r, s) = self.socket.split();
         (
-            Box::new(DatagramRecvHalf(rh)),
-            Box::new(DatagramSendHalf(sh)),
+            Box::new(DatagramRecvHalf(r, self.destination, self.closed.clone())),
+            Box::new(DatagramSendHalf {
+                send_half: s,
+                relay: self.relay,
+                closed: Some(self.closed),
+            }),
         )
     }
 }
 
-pub struct DatagramRecvHalf<S>(Arc<SocksDatagram<S>>);
+pub struct DatagramRecvHalf(
+    Box<dyn OutboundDatagramRecvHalf>,
+    Option<SocksAddr>,
+    watch::Receiver<bool>,
+);
 
 #[async_trait]
-impl<S> OutboundDatagramRecvHalf for DatagramRecvHalf<S>
-where
-    S: 'static + AsyncRead + AsyncWrite + Send + Unpin + Sync,
-{
-    async fn recv_from(&mut self, buf: &mut [u8]) -> Result<(usize, SocksAddr)> {
-        let (n, addr) = self.0.recv_from(buf).map_err(Error::other).await?;
-        match addr {
-            AddrKind::Ip(addr) => Ok((n, SocksAddr::Ip(addr))),
-            AddrKind::Domain(domain, port) => Ok((n, SocksAddr::Domain(domain, port))),
+impl OutboundDatagramRecvHalf for DatagramRecvHalf {
+    async fn recv_from(&mut self, buf: &mut [u8]) -> io::Result<(usize, SocksAddr)> {
+        // RSV, FRAG, and the longest possible address.
+        let mut recv_buf = vec![0u8; buf.len() + 3 + 1 + 1 + 255 + 2];
+        loop {
+            let n = tokio::select! {
+                res = self.0.recv_from(&mut recv_buf) => res?.0,
+                _ = self.2.wait_for(|closed| *closed) => return Err(control_closed()),
+            };
+            if n < 3 {
+                tracing::debug!("dropped short socks5 datagram");
+                continue;
+            }
+            if recv_buf[2] != 0 {
+                tracing::debug!("dropped fragmented socks5 datagram");
+                continue;
+            }
+            let header = &recv_buf[3..n];
+            let src_addr = match SocksAddr::try_from((header, SocksAddrWireType::PortLast)) {
+                Ok(addr) => addr,
+                Err(e) => {
+                    tracing::debug!("dropped socks5 datagram: {}", e);
+                    continue;
+                }
+            };
+            let header_size = 3 + src_addr.size();
+            let payload_size = std::cmp::min(n - header_size, buf.len());
+            buf[..payload_size].copy_from_slice(&recv_buf[header_size..header_size + payload_size]);
+            // If the initial destination is of domain type, we return that
+            // domain address instead of the real source address.
+            return Ok((payload_size, self.1.clone().unwrap_or(src_addr)));
         }
     }
 }
 
-pub struct DatagramSendHalf<S>(Arc<SocksDatagram<S>>);
+pub struct DatagramSendHalf {
+    send_half: Box<dyn OutboundDatagramSendHalf>,
+    relay: SocksAddr,
+    closed: Option<watch::Receiver<bool>>,
+}
 
 #[async_trait]
-impl<S> OutboundDatagramSendHalf for DatagramSendHalf<S>
-where
-    S: 'static + AsyncRead + AsyncWrite + Send + Unpin + Sync,
-{
-    async fn send_to(&mut self, buf: &[u8], target: &SocksAddr) -> Result<usize> {
-        match target {
-            SocksAddr::Ip(a) => {
-                self.0
-                    .send_to(buf, a.to_owned())
-                    .map_ok(|_| buf.len())
-                    .map_err(Error::other)
-                    .await
+impl OutboundDatagramSendHalf for DatagramSendHalf {
+    async fn send_to(&mut self, buf: &[u8], target: &SocksAddr) -> io::Result<usize> {
+        if self.closed.as_ref().is_none_or(|closed| *closed.borrow()) {
+            return Err(control_closed());
+        }
+        let mut send_buf = BytesMut::new();
+        send_buf.put_u16(0);
+        send_buf.put_u8(0);
+        target.write_buf(&mut send_buf, SocksAddrWireType::PortLast);
+        send_buf.put_slice(buf);
+        self.send_half
+            .send_to(&send_buf, &self.relay)
+            .map_ok(|_| buf.len())
+            .await
+    }
+
+    async fn close(&mut self) -> io::Result<()> {
+        // The control connection is closed once the receive half is also
+        // gone.
+        self.closed.take();
+        self.send_half.close().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::SocketAddr;
+
+    use tokio::io::AsyncWriteExt;
+    use tokio::net::{TcpListener, TcpStream, UdpSocket};
+
+    use super::*;
+
+    // Accepts one association and relays datagrams back to the client.
+    async fn mock_server(auth: Option<(&'static str, &'static str)>) -> SocketAddr {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (mut control, _) = listener.accept().await.unwrap();
+            let relay = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+
+            let mut buf = [0u8; 4];
+            if let Some((username, password)) = auth {
+                control.read_exact(&mut buf).await.unwrap();
+                assert_eq!(buf, [0x05, 0x02, 0x00, 0x02]);
+                control.write_all(&[0x05, 0x02]).await.unwrap();
+                let mut req = vec![0u8; 3 + username.len() + password.len()];
+                control.read_exact(&mut req).await.unwrap();
+                assert_eq!(&req[2..2 + username.len()], username.as_bytes());
+                assert_eq!(&req[3 + username.len()..], password.as_bytes());
+                control.write_all(&[0x01, 0x00]).await.unwrap();
+            } else {
+                control.read_exact(&mut buf[..3]).await.unwrap();
+                assert_eq!(buf[..3], [0x05, 0x01, 0x00]);
+                control.write_all(&[0x05, 0x00]).await.unwrap();
             }
-            SocksAddr::Domain(domain, port) => {
-                self.0
-                    .send_to(buf, (domain.to_owned(), *port))
-                    .map_ok(|_| buf.len())
-                    .map_err(Error::other)
-                    .await
+            let mut req = [0u8; 10];
+            control.read_exact(&mut req).await.unwrap();
+            assert_eq!(req[1], 0x03);
+            let mut reply = BytesMut::new();
+            reply.put_slice(&[0x05, 0x00, 0x00, 0x01, 0, 0, 0, 0]);
+            reply.put_u16(relay.local_addr().unwrap().port());
+            control.write_all(&reply).await.unwrap();
+
+            // Relays two datagrams, then tears down the association.
+            let mut buf = [0u8; 2048];
+            for _ in 0..2 {
+                let (n, peer) = relay.recv_from(&mut buf).await.unwrap();
+                relay.send_to(&buf[..n], peer).await.unwrap();
             }
+            drop(control);
+        });
+        addr
+    }
+
+    async fn connect(server: SocketAddr, auth: Option<(&str, &str)>) -> Box<Datagram> {
+        let mut control = TcpStream::connect(server).await.unwrap();
+        let relay = associate(&mut control, auth).await.unwrap();
+        assert!(matches!(&relay, SocksAddr::Ip(a) if a.ip().is_unspecified()));
+        let relay = relay_addr(relay, "127.0.0.1").unwrap();
+        let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let socket = Box::new(StdOutboundDatagram::new(socket));
+        let destination = SocksAddr::from("1.2.3.4:53".parse::<SocketAddr>().unwrap());
+        Box::new(Datagram::new(control, socket, relay, &destination))
+    }
+
+    #[tokio::test]
+    async fn test_socks5_udp_associate() {
+        let server = mock_server(None).await;
+        let (mut r, mut w) = connect(server, None).await.split();
+
+        let dns_server = SocksAddr::from("1.2.3.4:53".parse::<SocketAddr>().unwrap());
+        let domain = SocksAddr::try_from(("example.com", 443)).unwrap();
+        let mut buf = [0u8; 1500];
+        for (payload, target) in [(&b"ping"[..], &dns_server), (&[7u8; 1024][..], &domain)] {
+            assert_eq!(w.send_to(payload, target).await.unwrap(), payload.len());
+            let (n, src) = r.recv_from(&mut buf).await.unwrap();
+            assert_eq!(&buf[..n], payload);
+            assert_eq!(&src, target);
         }
+
+        // The association is gone with the control connection.
+        let err = r.recv_from(&mut buf).await.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::ConnectionAborted);
+        let err = w.send_to(b"ping", &dns_server).await.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::ConnectionAborted);
     }
 
-    async fn close(&mut self) -> io::Result<()> {
-        // FIXME implement our own socks5 outbound to propagate this.
-        Ok(())
+    #[tokio::test]
+    async fn test_socks5_udp_associate_auth() {
+        let server = mock_server(Some(("user", "pass"))).await;
+        let (mut r, mut w) = connect(server, Some(("user", "pass"))).await.split();
+
+        let dns_server = SocksAddr::from("1.2.3.4:53".parse::<SocketAddr>().unwrap());
+        w.send_to(b"ping", &dns_server).await.unwrap();
+        let mut buf = [0u8; 1500];
+        let (n, _) = r.recv_from(&mut buf).await.unwrap();
+        assert_eq!(&buf[..n], b"ping");
+    }
+
+    #[test]
+    fn test_relay_addr() {
+        let relay = SocksAddr::from("0.0.0.0:1080".parse::<SocketAddr>().unwrap());
+        assert_eq!(
+            relay_addr(relay, "example.com").unwrap(),
+            SocksAddr::Domain("example.com".to_string(), 1080)
+        );
+        let relay = SocksAddr::from("10.0.0.1:1080".parse::<SocketAddr>().unwrap());
+        assert_eq!(relay_addr(relay.clone(), "example.com").unwrap(), relay);
     }
 }
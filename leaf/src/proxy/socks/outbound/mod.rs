@@ -1,5 +1,120 @@
+use std::io;
+
+use bytes::{BufMut, BytesMut};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+use crate::session::{SocksAddr, SocksAddrWireType};
+
 mod datagram;
 mod stream;
 
 pub use datagram::Handler as DatagramHandler;
 pub use stream::Handler as StreamHandler;
+
+const CMD_CONNECT: u8 = 0x01;
+const CMD_UDP_ASSOCIATE: u8 = 0x03;
+
+const METHOD_NO_AUTH: u8 = 0x00;
+const METHOD_USERNAME_PASSWORD: u8 = 0x02;
+const METHOD_NO_ACCEPTABLE: u8 = 0xff;
+
+// Returns the credentials to authenticate with, none if no username is set.
+fn credentials<'a>(username: &'a str, password: &'a str) -> Option<(&'a str, &'a str)> {
+    if username.is_empty() {
+        None
+    } else {
+        Some((username, password))
+    }
+}
+
+/// Negotiates the authentication method. With credentials, both no auth and
+/// RFC 1929 username/password are offered and the server picks one.
+async fn negotiate<S>(stream: &mut S, auth: Option<(&str, &str)>) -> io::Result<()>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    if let Some((username, password)) = auth {
+        if username.len() > 255 || password.len() > 255 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "socks5 username or password longer than 255 bytes",
+            ));
+        }
+        stream
+            .write_all(&[0x05, 0x02, METHOD_NO_AUTH, METHOD_USERNAME_PASSWORD])
+            .await?;
+    } else {
+        stream.write_all(&[0x05, 0x01, METHOD_NO_AUTH]).await?;
+    }
+    let mut buf = [0u8; 2];
+    stream.read_exact(&mut buf).await?;
+    if buf[0] != 0x05 {
+        return Err(io::Error::other(format!(
+            "unknown socks version {}",
+            buf[0]
+        )));
+    }
+    match (buf[1], auth) {
+        (METHOD_NO_AUTH, _) => Ok(()),
+        (METHOD_USERNAME_PASSWORD, Some((username, password))) => {
+            // The credentials are arbitrary octets, sent as they are.
+            let mut req = BytesMut::new();
+            req.put_u8(0x01);
+            req.put_u8(username.len() as u8);
+            req.put_slice(username.as_bytes());
+            req.put_u8(password.len() as u8);
+            req.put_slice(password.as_bytes());
+            stream.write_all(&req).await?;
+            stream.read_exact(&mut buf).await?;
+            if buf[1] != 0x00 {
+                return Err(io::Error::new(
+                    io::ErrorKind::PermissionDenied,
+                    format!(
+                        "socks5 server rejected the credentials with status {}",
+                        buf[1]
+                    ),
+                ));
+            }
+            Ok(())
+        }
+        (METHOD_NO_ACCEPTABLE, None) => Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            "socks5 server requires authentication",
+        )),
+        (METHOD_NO_ACCEPTABLE, Some(_)) => Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            "socks5 server accepts neither no auth nor username/password",
+        )),
+        (method, _) => Err(io::Error::other(format!(
+            "unexpected socks5 authentication method {}",
+            method
+        ))),
+    }
+}
+
+/// Sends a request with `cmd` for `addr`, returns the bound address from
+/// the reply.
+async fn request<S>(stream: &mut S, cmd: u8, addr: &SocksAddr) -> io::Result<SocksAddr>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let mut req = BytesMut::new();
+    req.put_slice(&[0x05, cmd, 0x00]);
+    addr.write_buf(&mut req, SocksAddrWireType::PortLast);
+    stream.write_all(&req).await?;
+    let mut buf = [0u8; 3];
+    stream.read_exact(&mut buf).await?;
+    if buf[0] != 0x05 {
+        return Err(io::Error::other(format!(
+            "unknown socks version {}",
+            buf[0]
+        )));
+    }
+    if buf[1] != 0x00 {
+        return Err(io::Error::other(format!(
+            "socks5 request failed with reply {}",
+            buf[1]
+        )));
+    }
+    SocksAddr::read_from(stream, SocksAddrWireType::PortLast).await
+}
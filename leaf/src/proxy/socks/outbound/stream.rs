@@ -1,11 +1,11 @@
-use async_socks5::Auth;
 use std::io;
 
 use async_trait::async_trait;
-use futures::future::TryFutureExt;
 
 use crate::{proxy::*, session::*};
 
+use super::{credentials, negotiate, request, CMD_CONNECT};
+
 pub struct Handler {
     pub address: String,
     pub port: u16,
@@ -27,25 +27,8 @@ impl OutboundStreamHandler for Handler {
     ) -> io::Result<AnyStream> {
         tracing::trace!("handling outbound stream");
         let mut stream = stream.ok_or_else(|| io::Error::other("invalid input"))?;
-        let auth = match (&self.username, &self.password) {
-            (auth_username, _) if auth_username.is_empty() => None,
-            (auth_username, auth_password) => Some(Auth {
-                username: auth_username.to_owned(),
-                password: auth_password.to_owned(),
-            }),
-        };
-        match &sess.destination {
-            SocksAddr::Ip(a) => {
-                let _ = async_socks5::connect(&mut stream, a.to_owned(), auth)
-                    .map_err(io::Error::other)
-                    .await?;
-            }
-            SocksAddr::Domain(domain, port) => {
-                let _ = async_socks5::connect(&mut stream, (domain.to_owned(), *port), auth)
-                    .map_err(io::Error::other)
-                    .await?;
-            }
-        }
+        negotiate(&mut stream, credentials(&self.username, &self.password)).await?;
+        request(&mut stream, CMD_CONNECT, &sess.destination).await?;
         Ok(stream)
     }
 }
@@ -107,4 +90,108 @@ mod tests {
             .await;
         assert!(result.is_ok());
     }
+
+    // Answers the greeting with `method` and the subnegotiation with
+    // `status`, returns the greeting and the credentials received.
+    async fn mock_auth_server(
+        mut s: tokio::io::DuplexStream,
+        method: u8,
+        status: u8,
+    ) -> (Vec<u8>, Vec<u8>) {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let mut greeting = vec![0u8; 2];
+        s.read_exact(&mut greeting).await.unwrap();
+        let mut methods = vec![0u8; greeting[1] as usize];
+        s.read_exact(&mut methods).await.unwrap();
+        greeting.extend_from_slice(&methods);
+        s.write_all(&[0x05, method]).await.unwrap();
+
+        let mut credentials = Vec::new();
+        if method == 0x02 {
+            let mut buf = [0u8; 2];
+            s.read_exact(&mut buf).await.unwrap();
+            let mut username = vec![0u8; buf[1] as usize];
+            s.read_exact(&mut username).await.unwrap();
+            let plen = s.read_u8().await.unwrap();
+            let mut password = vec![0u8; plen as usize];
+            s.read_exact(&mut password).await.unwrap();
+            credentials.extend_from_slice(&username);
+            credentials.push(b':');
+            credentials.extend_from_slice(&password);
+            s.write_all(&[0x01, status]).await.unwrap();
+            if status != 0x00 {
+                return (greeting, credentials);
+            }
+        }
+
+        // CONNECT to example.com:80.
+        let mut req = vec![0u8; 3 + 1 + 1 + 11 + 2];
+        s.read_exact(&mut req).await.unwrap();
+        assert_eq!(&req[..5], &[0x05, 0x01, 0x00, 0x03, 11]);
+        s.write_all(&[0x05, 0x00, 0x00, 0x01, 0, 0, 0, 0, 0, 0])
+            .await
+            .unwrap();
+        (greeting, credentials)
+    }
+
+    fn auth_handler(username: &str, password: &str) -> Handler {
+        Handler {
+            address: "127.0.0.1".to_string(),
+            port: 1080,
+            username: username.to_string(),
+            password: password.to_string(),
+        }
+    }
+
+    fn example_session() -> Session {
+        Session {
+            destination: SocksAddr::Domain("example.com".to_string(), 80),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_socks5_outbound_auth() {
+        let (c, s) = tokio::io::duplex(1024);
+        let server = tokio::spawn(mock_auth_server(s, 0x02, 0x00));
+        let handler = auth_handler("usér", "pässwörd");
+        handler
+            .handle(&example_session(), None, Some(Box::new(c)))
+            .await
+            .unwrap();
+        let (greeting, credentials) = server.await.unwrap();
+        assert_eq!(greeting, [0x05, 0x02, 0x00, 0x02]);
+        // Non-ASCII credentials are sent as they are.
+        assert_eq!(credentials, "usér:pässwörd".as_bytes());
+    }
+
+    #[tokio::test]
+    async fn test_socks5_outbound_auth_rejected() {
+        let (c, s) = tokio::io::duplex(1024);
+        tokio::spawn(mock_auth_server(s, 0x02, 0x01));
+        let handler = auth_handler("user", "wrong");
+        let err = match handler
+            .handle(&example_session(), None, Some(Box::new(c)))
+            .await
+        {
+            Ok(_) => panic!("credentials should be rejected"),
+            Err(e) => e,
+        };
+        assert_eq!(err.kind(), io::ErrorKind::PermissionDenied);
+        assert!(err.to_string().contains("status 1"));
+    }
+
+    #[tokio::test]
+    async fn test_socks5_outbound_no_auth_with_credentials() {
+        let (c, s) = tokio::io::duplex(1024);
+        let server = tokio::spawn(mock_auth_server(s, 0x00, 0x00));
+        let handler = auth_handler("user", "pass");
+        handler
+            .handle(&example_session(), None, Some(Box::new(c)))
+            .await
+            .unwrap();
+        let (_, credentials) = server.await.unwrap();
+        assert!(credentials.is_empty());
+    }
 }
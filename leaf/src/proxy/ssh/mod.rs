@@ -0,0 +1,7 @@
+//! An SSH server used as a jump host, each session is forwarded through a
+//! `direct-tcpip` channel of a shared SSH connection.
+
+mod stream;
+mod verify;
+
+pub use stream::Handler;
@@ -0,0 +1,501 @@
+use std::io;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex as StdMutex};
+use std::time::Instant;
+
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use russh::client::{self, Msg};
+use russh::{Channel, ChannelOpenFailure, Disconnect};
+use russh_keys::key::{KeyPair, PublicKey};
+use tokio::sync::{Mutex, RwLock};
+use tokio::time::{timeout, Duration};
+use tracing::{debug, trace, Instrument};
+
+use crate::{app::SyncDnsClient, proxy::*, session::Session};
+
+use super::verify::HostKeyCheck;
+
+// OpenSSH serves up to 10 channels per connection by default.
+const DEFAULT_MAX_CHANNELS_PER_CONN: usize = 10;
+// Pooled connections without any open channel for this long are closed.
+const CONN_IDLE_TIMEOUT: Duration = Duration::from_secs(300);
+const KEEPALIVE_INTERVAL: Duration = Duration::from_secs(15);
+const KEEPALIVE_MAX: usize = 3;
+const MIN_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+const RELAY_BUFFER_SIZE: usize = 64 * 1024;
+
+struct ClientHandler {
+    host: String,
+    port: u16,
+    host_key_check: Arc<HostKeyCheck>,
+}
+
+#[async_trait]
+impl client::Handler for ClientHandler {
+    type Error = russh::Error;
+
+    async fn check_server_key(
+        &mut self,
+        server_public_key: &PublicKey,
+    ) -> Result<bool, Self::Error> {
+        Ok(self
+            .host_key_check
+            .verify(&self.host, self.port, server_public_key))
+    }
+}
+
+enum Auth {
+    Password(String),
+    PrivateKey(Arc<KeyPair>),
+}
+
+impl Auth {
+    // The key is either a path to a key file or the key itself.
+    fn new(
+        password: Option<String>,
+        private_key: Option<&str>,
+        passphrase: Option<&str>,
+    ) -> Result<Self> {
+        if let Some(key) = private_key {
+            let key = if key.trim_start().starts_with("-----BEGIN") {
+                russh_keys::decode_secret_key(key, passphrase)
+            } else {
+                russh_keys::load_secret_key(key, passphrase)
+            }
+            .map_err(|e| anyhow!("load ssh private key failed: {}", e))?;
+            return Ok(Self::PrivateKey(Arc::new(key)));
+        }
+        password
+            .map(Self::Password)
+            .ok_or_else(|| anyhow!("ssh password or private key required"))
+    }
+}
+
+struct ConnStats {
+    channels: AtomicUsize,
+    last_active: StdMutex<Instant>,
+}
+
+// A channel slot of a pooled connection, released on drop.
+struct ConnSlot(Arc<ConnStats>);
+
+impl Drop for ConnSlot {
+    fn drop(&mut self) {
+        self.0.channels.fetch_sub(1, Ordering::Relaxed);
+        *self.0.last_active.lock().unwrap() = Instant::now();
+    }
+}
+
+struct PooledConnection {
+    handle: client::Handle<ClientHandler>,
+    stats: Arc<ConnStats>,
+}
+
+impl PooledConnection {
+    fn new(handle: client::Handle<ClientHandler>) -> Self {
+        Self {
+            handle,
+            stats: Arc::new(ConnStats {
+                channels: AtomicUsize::new(0),
+                last_active: StdMutex::new(Instant::now()),
+            }),
+        }
+    }
+
+    fn new_slot(&self) -> ConnSlot {
+        self.stats.channels.fetch_add(1, Ordering::Relaxed);
+        *self.stats.last_active.lock().unwrap() = Instant::now();
+        ConnSlot(self.stats.clone())
+    }
+
+    fn is_full(&self, max_channels: usize) -> bool {
+        self.stats.channels.load(Ordering::Relaxed) >= max_channels
+    }
+
+    fn is_idle(&self) -> bool {
+        self.stats.channels.load(Ordering::Relaxed) == 0
+            && self.stats.last_active.lock().unwrap().elapsed() > CONN_IDLE_TIMEOUT
+    }
+}
+
+fn evict_connections(conns: &mut Vec<PooledConnection>) {
+    let mut idx = 0usize;
+    while idx < conns.len() {
+        if conns[idx].handle.is_closed() {
+            debug!("evict closed ssh connection");
+            conns.swap_remove(idx);
+        } else if conns[idx].is_idle() {
+            debug!("evict idle ssh connection");
+            let conn = conns.swap_remove(idx);
+            tokio::spawn(async move {
+                let _ = conn
+                    .handle
+                    .disconnect(Disconnect::ByApplication, "idle", "")
+                    .await;
+            });
+        } else {
+            idx += 1;
+        }
+    }
+}
+
+fn log_pool_stats(conns: &[PooledConnection]) {
+    let channels: usize = conns
+        .iter()
+        .map(|c| c.stats.channels.load(Ordering::Relaxed))
+        .sum();
+    debug!(
+        "ssh pool: {} connections, {} channels",
+        conns.len(),
+        channels
+    );
+}
+
+// Failed dials are retried no sooner than after an exponentially growing
+// delay, so that an unreachable server isn't hammered by every session.
+#[derive(Default)]
+struct Backoff {
+    failures: u32,
+    retry_at: Option<Instant>,
+}
+
+impl Backoff {
+    fn delay(failures: u32) -> Duration {
+        MIN_BACKOFF
+            .saturating_mul(1u32 << failures.saturating_sub(1).min(16))
+            .min(MAX_BACKOFF)
+    }
+
+    fn remaining(&self) -> Option<Duration> {
+        self.retry_at
+            .and_then(|at| at.checked_duration_since(Instant::now()))
+    }
+
+    fn failed(&mut self) {
+        self.failures = self.failures.saturating_add(1);
+        self.retry_at = Some(Instant::now() + Self::delay(self.failures));
+    }
+
+    fn succeeded(&mut self) {
+        *self = Self::default();
+    }
+}
+
+fn channel_open_error(e: russh::Error, host: &str, port: u16) -> anyhow::Error {
+    match e {
+        russh::Error::ChannelOpenFailure(ChannelOpenFailure::AdministrativelyProhibited) => {
+            anyhow!("ssh server doesn't allow tcp forwarding (AllowTcpForwarding)")
+        }
+        russh::Error::ChannelOpenFailure(ChannelOpenFailure::ConnectFailed) => {
+            anyhow!("ssh server failed to connect {}:{}", host, port)
+        }
+        russh::Error::ChannelOpenFailure(reason) => {
+            anyhow!("open ssh channel to {}:{} failed: {:?}", host, port, reason)
+        }
+        e => anyhow!("open ssh channel to {}:{} failed: {}", host, port, e),
+    }
+}
+
+// The channel stream isn't Sync, so it's relayed through a pipe which is.
+fn relay(channel: Channel<Msg>, slot: ConnSlot) -> AnyStream {
+    let (stream, mut pipe) = tokio::io::duplex(RELAY_BUFFER_SIZE);
+    tokio::spawn(async move {
+        let _slot = slot;
+        let mut channel = channel.into_stream();
+        if let Err(e) = tokio::io::copy_bidirectional(&mut channel, &mut pipe).await {
+            trace!("ssh channel relay ended: {}", e);
+        }
+    });
+    Box::new(stream)
+}
+
+struct Manager {
+    address: String,
+    port: u16,
+    username: String,
+    auth: Auth,
+    host_key_check: Arc<HostKeyCheck>,
+    config: Arc<client::Config>,
+    max_channels_per_conn: usize,
+    dns_client: SyncDnsClient,
+    connections: RwLock<Vec<PooledConnection>>,
+    backoff: StdMutex<Backoff>,
+    dial_lock: Mutex<()>,
+}
+
+impl TcpConnector for Manager {}
+
+impl Manager {
+    async fn dial(&self) -> Result<client::Handle<ClientHandler>> {
+        let stream = self
+            .new_tcp_stream(self.dns_client.clone(), &self.address, &self.port)
+            .await?;
+        let handler = ClientHandler {
+            host: self.address.clone(),
+            port: self.port,
+            host_key_check: self.host_key_check.clone(),
+        };
+        let mut handle = client::connect_stream(self.config.clone(), stream, handler)
+            .await
+            .map_err(|e| match e {
+                russh::Error::UnknownKey => anyhow!("ssh host key verification failed"),
+                e => anyhow!("ssh handshake failed: {}", e),
+            })?;
+        let authenticated = match &self.auth {
+            Auth::Password(password) => {
+                handle
+                    .authenticate_password(&self.username, password)
+                    .await?
+            }
+            Auth::PrivateKey(key) => {
+                handle
+                    .authenticate_publickey(&self.username, key.clone())
+                    .await?
+            }
+        };
+        if !authenticated {
+            return Err(anyhow!("ssh authentication failed for {}", &self.username));
+        }
+        trace!("ssh connected to {}:{}", &self.address, self.port);
+        Ok(handle)
+    }
+
+    // Dials a new connection unless the previous attempts failed recently.
+    async fn reconnect(&self, dial_timeout: Duration) -> Result<client::Handle<ClientHandler>> {
+        let remaining = self.backoff.lock().unwrap().remaining();
+        if let Some(remaining) = remaining {
+            return Err(anyhow!(
+                "ssh server {}:{} unreachable, retrying in {} ms",
+                &self.address,
+                self.port,
+                remaining.as_millis()
+            ));
+        }
+        let res = match timeout(dial_timeout, self.dial()).await {
+            Ok(res) => res,
+            Err(_) => Err(anyhow!("connect ssh timed out")),
+        };
+        let mut backoff = self.backoff.lock().unwrap();
+        match res {
+            Ok(handle) => {
+                backoff.succeeded();
+                Ok(handle)
+            }
+            Err(e) => {
+                backoff.failed();
+                Err(e)
+            }
+        }
+    }
+
+    async fn open_channel(
+        &self,
+        handle: &client::Handle<ClientHandler>,
+        sess: &Session,
+        dial_timeout: Duration,
+    ) -> Result<Channel<Msg>> {
+        let host = sess.destination.host();
+        let port = sess.destination.port();
+        match timeout(
+            dial_timeout,
+            handle.channel_open_direct_tcpip(host.clone(), port as u32, "127.0.0.1", 0),
+        )
+        .await
+        {
+            Ok(Ok(channel)) => Ok(channel),
+            Ok(Err(e)) => Err(channel_open_error(e, &host, port)),
+            Err(_) => Err(anyhow!("open ssh channel timed out")),
+        }
+    }
+
+    // Opens a channel on an existing connection which is below the channel
+    // cap, evicting closed and idle connections along the way.
+    async fn open_pooled_channel(
+        &self,
+        sess: &Session,
+        dial_timeout: Duration,
+    ) -> Result<Option<AnyStream>> {
+        let mut conns = self.connections.write().await;
+        evict_connections(&mut conns);
+        let mut idx = 0usize;
+        while idx < conns.len() {
+            if conns[idx].is_full(self.max_channels_per_conn) {
+                idx += 1;
+                continue;
+            }
+            let slot = conns[idx].new_slot();
+            match self
+                .open_channel(&conns[idx].handle, sess, dial_timeout)
+                .await
+            {
+                Ok(channel) => {
+                    trace!("opened channel on existing connection");
+                    log_pool_stats(&conns);
+                    return Ok(Some(relay(channel, slot)));
+                }
+                // The connection is fine, the server refused the channel.
+                Err(e) if !conns[idx].handle.is_closed() => return Err(e),
+                Err(e) => {
+                    debug!("{}", e);
+                    drop(slot);
+                    conns.swap_remove(idx);
+                }
+            }
+        }
+        Ok(None)
+    }
+
+    async fn new_stream(&self, sess: &Session) -> Result<AnyStream> {
+        let dial_timeout = Duration::from_secs(*crate::option::OUTBOUND_DIAL_TIMEOUT);
+        if let Some(stream) = self.open_pooled_channel(sess, dial_timeout).await? {
+            return Ok(stream);
+        }
+
+        // Only one task dials at a time, the others wait and then reuse the
+        // new connection if it still has room.
+        let _dial_guard = self.dial_lock.lock().await;
+        if let Some(stream) = self.open_pooled_channel(sess, dial_timeout).await? {
+            return Ok(stream);
+        }
+
+        let pooled = PooledConnection::new(self.reconnect(dial_timeout).await?);
+        let slot = pooled.new_slot();
+        let res = self.open_channel(&pooled.handle, sess, dial_timeout).await;
+        let mut conns = self.connections.write().await;
+        conns.push(pooled);
+        let channel = res?;
+
+        trace!("opened ssh channel on new connection");
+        log_pool_stats(&conns);
+
+        Ok(relay(channel, slot))
+    }
+}
+
+pub struct Handler {
+    manager: Manager,
+}
+
+impl Handler {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        address: String,
+        port: u16,
+        username: String,
+        password: Option<String>,
+        private_key: Option<&str>,
+        private_key_passphrase: Option<&str>,
+        host_key_fingerprint: Option<&str>,
+        known_hosts: Option<&str>,
+        max_channels_per_conn: Option<u32>,
+        dns_client: SyncDnsClient,
+    ) -> Result<Self> {
+        let auth = Auth::new(password, private_key, private_key_passphrase)?;
+        // Keepalives detect a dead transport so that the connection is
+        // dropped from the pool and dialed again.
+        let config = client::Config {
+            keepalive_interval: Some(KEEPALIVE_INTERVAL),
+            keepalive_max: KEEPALIVE_MAX,
+            ..Default::default()
+        };
+        Ok(Self {
+            manager: Manager {
+                address,
+                port,
+                username,
+                auth,
+                host_key_check: Arc::new(HostKeyCheck::new(host_key_fingerprint, known_hosts)),
+                config: Arc::new(config),
+                max_channels_per_conn: max_channels_per_conn
+                    .map(|x| x as usize)
+                    .unwrap_or(DEFAULT_MAX_CHANNELS_PER_CONN),
+                dns_client,
+                connections: RwLock::new(Vec::new()),
+                backoff: StdMutex::new(Backoff::default()),
+                dial_lock: Mutex::new(()),
+            },
+        })
+    }
+}
+
+#[async_trait]
+impl OutboundStreamHandler for Handler {
+    fn connect_addr(&self) -> OutboundConnect {
+        OutboundConnect::Unknown
+    }
+
+    async fn handle<'a>(
+        &'a self,
+        sess: &'a Session,
+        _lhs: Option<&mut AnyStream>,
+        _stream: Option<AnyStream>,
+    ) -> io::Result<AnyStream> {
+        tracing::trace!("handling outbound stream");
+        self.manager
+            .new_stream(sess)
+            .instrument(tracing::Span::current())
+            .await
+            .map_err(|e| io::Error::other(format!("new ssh stream failed: {}", e)))
+    }
+}
+
+#[async_trait]
+impl OutboundDatagramHandler for Handler {
+    fn connect_addr(&self) -> OutboundConnect {
+        OutboundConnect::Unknown
+    }
+
+    fn transport_type(&self) -> DatagramTransportType {
+        DatagramTransportType::Unknown
+    }
+
+    async fn handle<'a>(
+        &'a self,
+        _sess: &'a Session,
+        _transport: Option<AnyOutboundTransport>,
+    ) -> io::Result<AnyOutboundDatagram> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "ssh outbound doesn't support udp",
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backoff() {
+        assert_eq!(Backoff::delay(1), Duration::from_secs(1));
+        assert_eq!(Backoff::delay(2), Duration::from_secs(2));
+        assert_eq!(Backoff::delay(5), Duration::from_secs(16));
+        assert_eq!(Backoff::delay(6), MAX_BACKOFF);
+        assert_eq!(Backoff::delay(100), MAX_BACKOFF);
+
+        let mut backoff = Backoff::default();
+        assert!(backoff.remaining().is_none());
+        backoff.failed();
+        assert!(backoff.remaining().is_some());
+        backoff.succeeded();
+        assert!(backoff.remaining().is_none());
+    }
+
+    #[test]
+    fn test_channel_open_error() {
+        let e = channel_open_error(
+            russh::Error::ChannelOpenFailure(ChannelOpenFailure::AdministrativelyProhibited),
+            "example.com",
+            443,
+        );
+        assert!(e.to_string().contains("doesn't allow tcp forwarding"));
+        let e = channel_open_error(
+            russh::Error::ChannelOpenFailure(ChannelOpenFailure::ConnectFailed),
+            "example.com",
+            443,
+        );
+        assert!(e.to_string().contains("example.com:443"));
+    }
+}
@@ -0,0 +1,102 @@
+use std::path::PathBuf;
+
+use russh_keys::key::PublicKey;
+use tracing::{debug, warn};
+
+// Accepts the fingerprints as `ssh-keygen -l` prints them, with or without
+// the hash name and the base64 padding.
+fn normalize_fingerprint(fingerprint: &str) -> &str {
+    let fingerprint = fingerprint.trim();
+    fingerprint
+        .strip_prefix("SHA256:")
+        .unwrap_or(fingerprint)
+        .trim_end_matches('=')
+}
+
+/// How the host key of the server is checked.
+pub enum HostKeyCheck {
+    /// The SHA-256 fingerprint of the key.
+    Fingerprint(String),
+    /// An entry for the server in a known_hosts file.
+    KnownHosts(PathBuf),
+    /// Any key is accepted, its fingerprint is logged.
+    None,
+}
+
+impl HostKeyCheck {
+    /// The fingerprint takes precedence over the known_hosts file when both
+    /// are given.
+    pub fn new(fingerprint: Option<&str>, known_hosts: Option<&str>) -> Self {
+        if let Some(fingerprint) = fingerprint {
+            Self::Fingerprint(normalize_fingerprint(fingerprint).to_string())
+        } else if let Some(known_hosts) = known_hosts {
+            Self::KnownHosts(PathBuf::from(known_hosts))
+        } else {
+            Self::None
+        }
+    }
+
+    pub fn verify(&self, host: &str, port: u16, key: &PublicKey) -> bool {
+        let fingerprint = key.fingerprint();
+        match self {
+            Self::Fingerprint(expected) => {
+                let ok = normalize_fingerprint(&fingerprint) == expected;
+                if !ok {
+                    warn!(
+                        "ssh host key of {}:{} doesn't match, got SHA256:{}",
+                        host, port, fingerprint
+                    );
+                }
+                ok
+            }
+            Self::KnownHosts(path) => {
+                match russh_keys::check_known_hosts_path(host, port, key, path) {
+                    Ok(true) => true,
+                    Ok(false) => {
+                        warn!(
+                            "ssh host {}:{} not found in {}, its key is SHA256:{}",
+                            host,
+                            port,
+                            path.display(),
+                            fingerprint
+                        );
+                        false
+                    }
+                    Err(e) => {
+                        warn!("check ssh host key of {}:{} failed: {}", host, port, e);
+                        false
+                    }
+                }
+            }
+            Self::None => {
+                debug!(
+                    "accepting ssh host key SHA256:{} of {}:{} unverified",
+                    fingerprint, host, port
+                );
+                true
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_fingerprint() {
+        let fp = "nThbg6kXUpJWGl7E1IGOCspRomTxdCARLviKw6E5SY8";
+        assert_eq!(normalize_fingerprint(fp), fp);
+        assert_eq!(normalize_fingerprint(&format!("SHA256:{}", fp)), fp);
+        assert_eq!(normalize_fingerprint(&format!(" SHA256:{}= ", fp)), fp);
+        assert!(matches!(
+            HostKeyCheck::new(Some(&format!("SHA256:{}", fp)), Some("known_hosts")),
+            HostKeyCheck::Fingerprint(x) if x == fp
+        ));
+        assert!(matches!(
+            HostKeyCheck::new(None, Some("known_hosts")),
+            HostKeyCheck::KnownHosts(_)
+        ));
+        assert!(matches!(HostKeyCheck::new(None, None), HostKeyCheck::None));
+    }
+}
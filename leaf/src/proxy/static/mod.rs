@@ -1,3 +1,15 @@
+use std::io;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use futures::task::{Context, Poll};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+use crate::{proxy::*, session::SocksAddr};
+
 pub mod datagram;
 pub mod stream;
 
@@ -8,4 +20,217 @@ enum Method {
     Random,
     RandomOnce,
     RoundRobin,
+    // The actor with the fewest active sessions.
+    LeastLoad,
+    // The same actor for the same destination host.
+    ConsistentHash,
+}
+
+impl Method {
+    fn parse(method: &str) -> Result<Self> {
+        match method {
+            "random" => Ok(Method::Random),
+            "random-once" => Ok(Method::RandomOnce),
+            "rr" | "round-robin" => Ok(Method::RoundRobin),
+            "least-load" => Ok(Method::LeastLoad),
+            "consistent-hash" => Ok(Method::ConsistentHash),
+            _ => Err(anyhow!("unknown method")),
+        }
+    }
+
+    // The actor of a session is only known when the session is handled.
+    fn per_session(&self) -> bool {
+        matches!(self, Method::LeastLoad | Method::ConsistentHash)
+    }
+}
+
+/// Active sessions of each actor of a group, shared by the stream and
+/// datagram handlers of the group.
+pub struct Sessions(Vec<AtomicUsize>);
+
+impl Sessions {
+    pub fn new(n: usize) -> Self {
+        Sessions((0..n).map(|_| AtomicUsize::new(0)).collect())
+    }
+
+    // Returns the actor with the fewest active sessions, the first one of
+    // them on a tie.
+    fn least_loaded(&self) -> usize {
+        self.0
+            .iter()
+            .enumerate()
+            .min_by_key(|(_, x)| x.load(Ordering::Relaxed))
+            .map(|(i, _)| i)
+            .unwrap_or(0)
+    }
+
+    fn count(self: &Arc<Self>, i: usize) -> SessionGuard {
+        self.0[i].fetch_add(1, Ordering::Relaxed);
+        SessionGuard {
+            sessions: self.clone(),
+            index: i,
+        }
+    }
+}
+
+// Counts a session on an actor for as long as the session lasts.
+struct SessionGuard {
+    sessions: Arc<Sessions>,
+    index: usize,
+}
+
+impl Drop for SessionGuard {
+    fn drop(&mut self) {
+        self.sessions.0[self.index].fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+// FNV-1a, a hash that doesn't change between builds or runs, unlike the
+// hasher of the standard library.
+fn fnv1a(data: &[u8], mut hash: u64) -> u64 {
+    for b in data {
+        hash ^= *b as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
+}
+
+// Picks the actor of a destination host by rendezvous hashing, so that only
+// the hosts of an actor move when the actor is removed from the list.
+fn hash_actor(tags: &[&str], destination: &SocksAddr) -> usize {
+    let host = destination.host();
+    tags.iter()
+        .enumerate()
+        .max_by_key(|(_, tag)| {
+            let hash = fnv1a(tag.as_bytes(), 0xcbf29ce484222325);
+            let mut hash = fnv1a(host.as_bytes(), fnv1a(&[0], hash));
+            // Spreads the low entropy of similar inputs.
+            hash ^= hash >> 33;
+            hash = hash.wrapping_mul(0xff51afd7ed558ccd);
+            hash ^ (hash >> 33)
+        })
+        .map(|(i, _)| i)
+        .unwrap_or(0)
+}
+
+struct CountedStream {
+    inner: AnyStream,
+    _guard: SessionGuard,
+}
+
+impl AsyncRead for CountedStream {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context,
+        buf: &mut ReadBuf,
+    ) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_read(cx, buf)
+    }
+}
+
+impl AsyncWrite for CountedStream {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.inner).poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+}
+
+// The session ends when both halves are dropped.
+struct CountedDatagram {
+    inner: AnyOutboundDatagram,
+    guard: SessionGuard,
+}
+
+impl OutboundDatagram for CountedDatagram {
+    fn split(
+        self: Box<Self>,
+    ) -> (
+        Box<dyn OutboundDatagramRecvHalf>,
+        Box<dyn OutboundDatagramSendHalf>,
+    ) {
+        let (r, s) = self.inner.split();
+        let guard = Arc::new(self.guard);
+        (
+            Box::new(CountedRecvHalf(r, guard.clone())),
+            Box::new(CountedSendHalf(s, guard)),
+        )
+    }
+}
+
+struct CountedRecvHalf(Box<dyn OutboundDatagramRecvHalf>, Arc<SessionGuard>);
+
+#[async_trait]
+impl OutboundDatagramRecvHalf for CountedRecvHalf {
+    async fn recv_from(&mut self, buf: &mut [u8]) -> io::Result<(usize, SocksAddr)> {
+        self.0.recv_from(buf).await
+    }
+}
+
+struct CountedSendHalf(Box<dyn OutboundDatagramSendHalf>, Arc<SessionGuard>);
+
+#[async_trait]
+impl OutboundDatagramSendHalf for CountedSendHalf {
+    async fn send_to(&mut self, buf: &[u8], target: &SocksAddr) -> io::Result<usize> {
+        self.0.send_to(buf, target).await
+    }
+
+    async fn close(&mut self) -> io::Result<()> {
+        self.0.close().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_least_loaded() {
+        let sessions = Arc::new(Sessions::new(3));
+        let a = sessions.count(0);
+        let _b = sessions.count(1);
+        assert_eq!(sessions.least_loaded(), 2);
+        let _c = sessions.count(2);
+        let _d = sessions.count(2);
+        assert_eq!(sessions.least_loaded(), 0);
+        drop(a);
+        assert_eq!(sessions.0[0].load(Ordering::Relaxed), 0);
+        assert_eq!(sessions.least_loaded(), 0);
+    }
+
+    #[test]
+    fn test_hash_actor() {
+        // The hash must not change, or hosts would move between actors
+        // after an upgrade.
+        assert_eq!(fnv1a(b"a", 0xcbf29ce484222325), 0xaf63dc4c8601ec8c);
+        let tags = ["a", "b", "c", "d"];
+        let hosts: Vec<SocksAddr> = (0..64)
+            .map(|i| SocksAddr::try_from((format!("host{}.example.com", i), 443)).unwrap())
+            .collect();
+        let picks: Vec<usize> = hosts.iter().map(|x| hash_actor(&tags, x)).collect();
+        // The same host on another port goes to the same actor.
+        let other_port = SocksAddr::try_from(("host0.example.com", 80)).unwrap();
+        assert_eq!(hash_actor(&tags, &other_port), picks[0]);
+        // All actors are used.
+        for i in 0..tags.len() {
+            assert!(picks.contains(&i));
+        }
+        // Removing an actor only moves the hosts of that actor.
+        let fewer = ["a", "b", "d"];
+        for (host, pick) in hosts.iter().zip(picks.iter()) {
+            if *pick != 2 {
+                assert_eq!(fewer[hash_actor(&fewer, host)], tags[*pick]);
+            }
+        }
+    }
 }
@@ -1,47 +1,66 @@
 use std::io;
 use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
 
-use anyhow::{anyhow, Result};
+use anyhow::Result;
 use async_trait::async_trait;
 use rand::{rngs::StdRng, Rng, SeedableRng};
 
-use crate::{proxy::*, session::Session};
+use crate::{app::SyncDnsClient, proxy::*, session::Session};
 
-use super::Method;
+use super::{hash_actor, CountedStream, Method, Sessions};
 
 pub struct Handler {
     actors: Vec<AnyOutboundHandler>,
     method: Method,
     next: AtomicUsize,
+    sessions: Arc<Sessions>,
+    dns_client: SyncDnsClient,
 }
 
 impl Handler {
-    pub fn new(actors: Vec<AnyOutboundHandler>, method: &str) -> Result<Self> {
-        let (method, next) = match method {
-            "random" => {
+    pub fn new(
+        actors: Vec<AnyOutboundHandler>,
+        method: &str,
+        sessions: Arc<Sessions>,
+        dns_client: SyncDnsClient,
+    ) -> Result<Self> {
+        let method = Method::parse(method)?;
+        let next = match method {
+            Method::Random | Method::RandomOnce => {
                 let mut rng = StdRng::from_entropy();
                 let i: usize = rng.gen_range(0..actors.len());
-                (Method::Random, AtomicUsize::new(i))
+                AtomicUsize::new(i)
             }
-            "random-once" => {
-                let mut rng = StdRng::from_entropy();
-                let i: usize = rng.gen_range(0..actors.len());
-                (Method::RandomOnce, AtomicUsize::new(i))
-            }
-            "rr" => (Method::RoundRobin, AtomicUsize::new(0)),
-            _ => return Err(anyhow!("unknown method")),
+            _ => AtomicUsize::new(0),
         };
         Ok(Handler {
             actors,
             method,
             next,
+            sessions,
+            dns_client,
         })
     }
+
+    // Picks the actor of a session for the methods which depend on it.
+    fn session_actor(&self, sess: &Session) -> usize {
+        match self.method {
+            Method::ConsistentHash => {
+                let tags: Vec<&str> = self.actors.iter().map(|a| a.tag().as_str()).collect();
+                hash_actor(&tags, &sess.destination)
+            }
+            _ => self.sessions.least_loaded(),
+        }
+    }
 }
 
 #[async_trait]
 impl OutboundStreamHandler for Handler {
     fn connect_addr(&self) -> OutboundConnect {
+        if self.method.per_session() {
+            return OutboundConnect::Unknown;
+        }
         let a = &self.actors[self.next.load(Ordering::Relaxed)];
         match a.stream() {
             Ok(h) => return h.connect_addr(),
@@ -92,6 +111,20 @@ impl OutboundStreamHandler for Handler {
                     .handle(sess, lhs, stream)
                     .await
             }
+            Method::LeastLoad | Method::ConsistentHash => {
+                let current = self.session_actor(sess);
+                // Counted from the start, so that concurrent sessions don't
+                // all go to the same actor while connecting.
+                let guard = self.sessions.count(current);
+                let a = &self.actors[current];
+                tracing::debug!("static handles tcp [{}] to [{}]", sess.destination, a.tag());
+                let stream = connect_stream_outbound(sess, self.dns_client.clone(), a).await?;
+                let stream = a.stream()?.handle(sess, lhs, stream).await?;
+                Ok(Box::new(CountedStream {
+                    inner: stream,
+                    _guard: guard,
+                }))
+            }
         }
     }
 }
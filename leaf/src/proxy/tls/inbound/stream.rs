@@ -1,5 +1,5 @@
 #[cfg(feature = "rustls-tls")]
-use {std::fs::File, std::io, std::io::BufReader, std::path::Path};
+use {std::fs::File, std::io, std::io::BufReader, std::path::Path, std::sync::Arc};
 
 use anyhow::Result;
 
@@ -8,16 +8,19 @@ use {
     rustls_pemfile::{certs, ec_private_keys, pkcs8_private_keys, rsa_private_keys},
     tokio_rustls::rustls::{
         pki_types::{CertificateDer, PrivateKeyDer},
+        server::Acceptor,
         ServerConfig,
     },
-    tokio_rustls::TlsAcceptor,
+    tokio_rustls::LazyConfigAcceptor,
 };
 
 use crate::{proxy::*, session::Session};
 
 pub struct Handler {
     #[cfg(feature = "rustls-tls")]
-    acceptor: TlsAcceptor,
+    config: Arc<ServerConfig>,
+    #[cfg(feature = "rustls-tls")]
+    default_sni: Option<String>,
 }
 
 #[cfg(feature = "rustls-tls")]
@@ -71,6 +74,7 @@ impl Handler {
         certificate_key: String,
         ech_config: Option<String>,
         ech_key: Option<String>,
+        default_sni: Option<String>,
     ) -> Result<Self> {
         #[cfg(feature = "rustls-tls")]
         {
@@ -93,23 +97,40 @@ impl Handler {
             #[cfg(not(feature = "rustls-tls-aws-lc"))]
             let provider = rustls::crypto::ring::default_provider().into();
 
-            let config = ServerConfig::builder_with_provider(provider)
+            let mut config = ServerConfig::builder_with_provider(provider)
                 .with_safe_default_protocol_versions()
                 .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?
                 .with_no_client_auth()
                 .with_single_cert(certs, keys.remove(0))
                 .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
-            let acceptor = TlsAcceptor::from(Arc::new(config));
-            Ok(Self { acceptor })
+            if let Some(key_log) = crate::common::key_log::key_log() {
+                config.key_log = key_log;
+            }
+            Ok(Self {
+                config: Arc::new(config),
+                default_sni,
+            })
         }
         #[cfg(all(not(feature = "rustls-tls"), feature = "openssl-tls"))]
         {
-            let _ = (certificate, certificate_key, ech_config, ech_key);
+            let _ = (
+                certificate,
+                certificate_key,
+                ech_config,
+                ech_key,
+                default_sni,
+            );
             unimplemented!();
         }
         #[cfg(all(not(feature = "rustls-tls"), not(feature = "openssl-tls")))]
         {
-            let _ = (certificate, certificate_key, ech_config, ech_key);
+            let _ = (
+                certificate,
+                certificate_key,
+                ech_config,
+                ech_key,
+                default_sni,
+            );
             Err(anyhow::anyhow!("no tls feature enabled"))
         }
     }
@@ -266,8 +287,17 @@ impl InboundStreamHandler for Handler {
         tracing::trace!("handling inbound stream");
         #[cfg(feature = "rustls-tls")]
         {
+            // The ClientHello is parsed once, its server name goes to the
+            // session for routing and the config is picked after it.
+            let start = LazyConfigAcceptor::new(Acceptor::default(), stream).await?;
+            let mut sess = sess;
+            sess.sni = start
+                .client_hello()
+                .server_name()
+                .map(str::to_string)
+                .or_else(|| self.default_sni.clone());
             Ok(InboundTransport::Stream(
-                Box::new(self.acceptor.accept(stream).await?),
+                Box::new(start.into_stream(self.config.clone()).await?),
                 sess,
             ))
         }
@@ -332,6 +362,7 @@ mod tests {
             key_pem,
             Some("AQID".to_string()),
             Some("BAUG".to_string()),
+            None,
         );
         assert!(result.is_err());
     }
@@ -7,10 +7,17 @@ use tracing::trace;
 
 #[cfg(feature = "rustls-tls")]
 use {
+    crate::common::cert::{
+        load_certs, load_private_key, root_verifier, FingerprintVerifier, SpkiPinVerifier,
+    },
     std::sync::Arc,
-    std::{fs::File, io::BufReader, io::Cursor},
     tokio_rustls::{
-        rustls::{pki_types::ServerName, ClientConfig, RootCertStore},
+        rustls::{
+            client::{ClientSessionMemoryCache, Resumption, WantsClientCert},
+            pki_types::{pem::PemObject, EchConfigListBytes, ServerName},
+            sign::CertifiedKey,
+            ClientConfig, ConfigBuilder, RootCertStore,
+        },
         TlsConnector,
     },
 };
@@ -18,7 +25,7 @@ use {
 #[cfg(all(feature = "rustls-tls", feature = "rustls-tls-aws-lc"))]
 use tokio_rustls::rustls::client::{EchConfig, EchMode};
 #[cfg(all(feature = "rustls-tls", feature = "rustls-tls-aws-lc"))]
-use tokio_rustls::rustls::pki_types::{pem::PemObject, EchConfigListBytes};
+use {std::collections::HashMap, std::sync::Mutex, tracing::debug};
 
 #[cfg(feature = "openssl-tls")]
 use {
@@ -30,6 +37,22 @@ use {
 
 use crate::{app::SyncDnsClient, proxy::*, session::Session};
 
+#[cfg(feature = "rustls-tls")]
+lazy_static::lazy_static! {
+    // Shared by all TLS outbounds and kept across config rebuilds, so that
+    // reconnects to a server can resume its session.
+    static ref SESSION_CACHE: Arc<ClientSessionMemoryCache> =
+        Arc::new(ClientSessionMemoryCache::new(*crate::option::TLS_SESSION_CACHE_SIZE));
+}
+
+#[cfg(all(feature = "rustls-tls", feature = "rustls-tls-aws-lc"))]
+lazy_static::lazy_static! {
+    // The retry configs servers sent on rejecting ECH, by server name. They
+    // take precedence over the configured and DNS sources until rejected.
+    static ref ECH_RETRY_CONFIGS: Mutex<HashMap<String, EchConfigListBytes<'static>>> =
+        Mutex::new(HashMap::new());
+}
+
 #[cfg(feature = "rustls-tls")]
 mod dangerous {
     use tokio_rustls::rustls::{
@@ -103,12 +126,26 @@ pub struct Handler {
     #[cfg(feature = "rustls-tls")]
     certificate_key: Option<String>,
     #[cfg(feature = "rustls-tls")]
+    client_certificate: Option<String>,
+    #[cfg(feature = "rustls-tls")]
+    client_certificate_key: Option<String>,
+    #[cfg(feature = "rustls-tls")]
     insecure: bool,
     #[cfg(feature = "rustls-tls")]
+    allow_name_mismatch: bool,
+    #[cfg(feature = "rustls-tls")]
+    certificate_fingerprint: Option<String>,
+    #[cfg(feature = "rustls-tls")]
+    certificate_pins: Vec<String>,
+    #[cfg(feature = "rustls-tls")]
+    pin_only: bool,
+    #[cfg(feature = "rustls-tls")]
     fixed_ech_config_list: Option<String>,
     #[cfg(feature = "rustls-tls")]
     ech_disable_dns_lookup: bool,
     #[cfg(feature = "rustls-tls")]
+    ech_required: bool,
+    #[cfg(feature = "rustls-tls")]
     dns_client: SyncDnsClient,
     ech_enabled: bool,
     #[cfg(feature = "rustls-tls")]
@@ -119,37 +156,36 @@ pub struct Handler {
 
 impl Handler {
     #[cfg(feature = "rustls-tls")]
+    #[allow(clippy::too_many_arguments)]
     fn build_rustls_config(
         alpns: &[String],
         certificate: Option<&String>,
         certificate_key: Option<&String>,
+        client_certificate: Option<&String>,
+        client_certificate_key: Option<&String>,
         insecure: bool,
-        ech_config_list: Option<&str>,
+        allow_name_mismatch: bool,
+        certificate_fingerprint: Option<&str>,
+        certificate_pins: &[String],
+        pin_only: bool,
+        ech_config_list: Option<EchConfigListBytes<'static>>,
     ) -> Result<Arc<ClientConfig>> {
         let mut roots = RootCertStore::empty();
         if let Some(cert) = certificate {
-            if cert.contains("-----BEGIN") {
-                let mut pem = BufReader::new(Cursor::new(cert.as_bytes()));
-                for cert in rustls_pemfile::certs(&mut pem) {
-                    roots.add(cert?)?;
-                }
-            } else {
-                let mut pem = BufReader::new(File::open(cert).map_err(|e| {
-                    anyhow::anyhow!("load certificates from {} failed: {}", cert, e)
-                })?);
-                for cert in rustls_pemfile::certs(&mut pem) {
-                    roots.add(cert?)?;
-                }
+            for cert in load_certs(cert)? {
+                roots.add(cert)?;
             }
         } else {
             roots.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned());
         }
         #[cfg(feature = "rustls-tls-aws-lc")]
-        let provider = rustls::crypto::aws_lc_rs::default_provider().into();
+        let provider: Arc<rustls::crypto::CryptoProvider> =
+            rustls::crypto::aws_lc_rs::default_provider().into();
         #[cfg(not(feature = "rustls-tls-aws-lc"))]
-        let provider = rustls::crypto::ring::default_provider().into();
+        let provider: Arc<rustls::crypto::CryptoProvider> =
+            rustls::crypto::ring::default_provider().into();
 
-        let builder = ClientConfig::builder_with_provider(provider);
+        let builder = ClientConfig::builder_with_provider(provider.clone());
         #[cfg(not(feature = "rustls-tls-aws-lc"))]
         if ech_config_list.is_some() {
             return Err(anyhow::anyhow!(
@@ -160,7 +196,6 @@ impl Handler {
         let builder = if let Some(ech_config_list) = ech_config_list {
             #[cfg(feature = "rustls-tls-aws-lc")]
             {
-                let ech_config_list = decode_ech_config_list(ech_config_list)?;
                 let suites = rustls::crypto::aws_lc_rs::hpke::ALL_SUPPORTED_SUITES;
                 let ech_config = EchConfig::new(ech_config_list, suites)
                     .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
@@ -181,25 +216,84 @@ impl Handler {
                 .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?
         };
 
-        let mut config = if insecure {
-            let builder = builder
-                .dangerous()
-                .with_custom_certificate_verifier(Arc::new(dangerous::NotVerified));
-            if certificate.is_some() {
-                if certificate_key.is_some() {
-                    builder.with_no_client_auth()
+        let builder: ConfigBuilder<ClientConfig, WantsClientCert> =
+            if let Some(fingerprint) = certificate_fingerprint {
+                builder
+                    .dangerous()
+                    .with_custom_certificate_verifier(Arc::new(FingerprintVerifier::new(
+                        fingerprint,
+                        provider.clone(),
+                    )))
+            } else if !certificate_pins.is_empty() {
+                let inner = if pin_only {
+                    None
                 } else {
-                    builder.with_no_client_auth()
-                }
+                    Some(root_verifier(roots, provider.clone(), allow_name_mismatch)?)
+                };
+                builder
+                    .dangerous()
+                    .with_custom_certificate_verifier(Arc::new(SpkiPinVerifier::new(
+                        certificate_pins.to_vec(),
+                        inner,
+                        provider.clone(),
+                    )))
+            } else if insecure {
+                builder
+                    .dangerous()
+                    .with_custom_certificate_verifier(Arc::new(dangerous::NotVerified))
+            } else if allow_name_mismatch {
+                builder
+                    .dangerous()
+                    .with_custom_certificate_verifier(root_verifier(roots, provider.clone(), true)?)
             } else {
-                builder.with_no_client_auth()
+                builder.with_root_certificates(roots)
+            };
+        // Client auth is configured with client_certificate and
+        // client_certificate_key.
+        let _ = certificate_key;
+
+        let mut config = match (client_certificate, client_certificate_key) {
+            (Some(cert_source), Some(key_source)) => {
+                let certs = load_certs(cert_source)?;
+                let key = load_private_key(key_source)?;
+                let signing_key = provider.key_provider.load_private_key(key.clone_key())?;
+                let describe = |source: &'_ String| {
+                    if source.contains("-----BEGIN") {
+                        "inline pem".to_string()
+                    } else {
+                        source.clone()
+                    }
+                };
+                CertifiedKey::new(certs.clone(), signing_key)
+                    .keys_match()
+                    .map_err(|e| {
+                        anyhow::anyhow!(
+                            "client certificate {} doesn't match key {}: {}",
+                            describe(cert_source),
+                            describe(key_source),
+                            e
+                        )
+                    })?;
+                builder.with_client_auth_cert(certs, key)?
+            }
+            (None, None) => builder.with_no_client_auth(),
+            _ => {
+                return Err(anyhow::anyhow!(
+                    "tls outbound client certificate and key must be set together"
+                ))
             }
-        } else {
-            builder.with_root_certificates(roots).with_no_client_auth()
         };
         for alpn in alpns {
             config.alpn_protocols.push(alpn.as_bytes().to_vec());
         }
+        // Sessions of unverified servers aren't shared with outbounds that
+        // verify them.
+        if !insecure {
+            config.resumption = Resumption::store(SESSION_CACHE.clone());
+        }
+        if let Some(key_log) = crate::common::key_log::key_log() {
+            config.key_log = key_log;
+        }
         Ok(Arc::new(config))
     }
 
@@ -255,15 +349,20 @@ impl Handler {
         &self,
         name: &str,
         allow_dns_lookup: bool,
-    ) -> io::Result<Option<String>> {
+    ) -> io::Result<Option<EchConfigListBytes<'static>>> {
         if !self.ech_enabled {
             trace!("ech source for {}: none", name);
             return Ok(None);
         }
+        #[cfg(feature = "rustls-tls-aws-lc")]
+        if let Some(retry) = ECH_RETRY_CONFIGS.lock().unwrap().get(name) {
+            trace!("ech source for {}: retry configs of the server", name);
+            return Ok(Some(retry.clone()));
+        }
         if self.ech_disable_dns_lookup {
             if let Some(fixed) = self.fixed_ech_config_list.as_deref() {
                 trace!("ech source for {}: fixed ech config", name);
-                return Ok(Some(fixed.to_string()));
+                return decode_ech_config_list(fixed).map(Some);
             }
             trace!("ech source for {}: none", name);
             return Ok(None);
@@ -282,17 +381,28 @@ impl Handler {
             name,
             self.fixed_ech_config_list.as_deref(),
             auto_result,
-        )
+        )?
+        .as_deref()
+        .map(decode_ech_config_list)
+        .transpose()
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         server_name: String,
         alpns: Vec<String>,
         certificate: Option<String>,
         certificate_key: Option<String>,
+        client_certificate: Option<String>,
+        client_certificate_key: Option<String>,
         insecure: bool,
+        allow_name_mismatch: bool,
+        certificate_fingerprint: Option<String>,
+        certificate_pins: Vec<String>,
+        pin_only: bool,
         ech: bool,
         ech_disable_dns_lookup: bool,
+        ech_required: bool,
         ech_config_list: Option<String>,
         dns_client: SyncDnsClient,
     ) -> Result<Self> {
@@ -305,12 +415,26 @@ impl Handler {
             #[cfg(feature = "rustls-tls")]
             certificate_key: certificate_key.clone(),
             #[cfg(feature = "rustls-tls")]
+            client_certificate: client_certificate.clone(),
+            #[cfg(feature = "rustls-tls")]
+            client_certificate_key: client_certificate_key.clone(),
+            #[cfg(feature = "rustls-tls")]
             insecure,
             #[cfg(feature = "rustls-tls")]
+            allow_name_mismatch,
+            #[cfg(feature = "rustls-tls")]
+            certificate_fingerprint: certificate_fingerprint.clone(),
+            #[cfg(feature = "rustls-tls")]
+            certificate_pins: certificate_pins.clone(),
+            #[cfg(feature = "rustls-tls")]
+            pin_only,
+            #[cfg(feature = "rustls-tls")]
             fixed_ech_config_list: ech_config_list.clone(),
             #[cfg(feature = "rustls-tls")]
             ech_disable_dns_lookup,
             #[cfg(feature = "rustls-tls")]
+            ech_required,
+            #[cfg(feature = "rustls-tls")]
             dns_client,
             ech_enabled: ech,
             #[cfg(feature = "rustls-tls")]
@@ -322,7 +446,14 @@ impl Handler {
         let _ = (
             &certificate,
             &certificate_key,
+            &client_certificate,
+            &client_certificate_key,
+            allow_name_mismatch,
+            &certificate_fingerprint,
+            &certificate_pins,
+            pin_only,
             ech_disable_dns_lookup,
+            ech_required,
             &ech_config_list,
             &dns_client,
         );
@@ -338,11 +469,20 @@ impl Handler {
                 &alpns,
                 certificate.as_ref(),
                 certificate_key.as_ref(),
+                client_certificate.as_ref(),
+                client_certificate_key.as_ref(),
                 insecure,
+                allow_name_mismatch,
+                certificate_fingerprint.as_deref(),
+                &certificate_pins,
+                pin_only,
                 if handler.ech_enabled {
                     #[cfg(feature = "rustls-tls-aws-lc")]
                     {
-                        ech_config_list.as_deref()
+                        ech_config_list
+                            .as_deref()
+                            .map(decode_ech_config_list)
+                            .transpose()?
                     }
                     #[cfg(not(feature = "rustls-tls-aws-lc"))]
                     {
@@ -370,6 +510,26 @@ impl Handler {
                     .concat();
                 builder.set_alpn_protos(&wire).expect("set alpn failed");
             }
+            if certificate_fingerprint.is_some() {
+                return Err(anyhow::anyhow!(
+                    "tls outbound certificate fingerprint requires rustls-tls"
+                ));
+            }
+            if !certificate_pins.is_empty() {
+                return Err(anyhow::anyhow!(
+                    "tls outbound certificate pins require rustls-tls"
+                ));
+            }
+            if allow_name_mismatch {
+                return Err(anyhow::anyhow!(
+                    "tls outbound allowNameMismatch requires rustls-tls"
+                ));
+            }
+            if client_certificate.is_some() || client_certificate_key.is_some() {
+                return Err(anyhow::anyhow!(
+                    "tls outbound client certificate requires rustls-tls"
+                ));
+            }
             if insecure {
                 builder.set_verify(openssl::ssl::SslVerifyMode::NONE);
             }
@@ -379,7 +539,34 @@ impl Handler {
     }
 }
 
+// Keeps the retry configs of a server which rejected ECH, which the
+// connections to it use from then on, returns whether there are any. The
+// rejected connection is retried with them on a new transport, the chain
+// the handler is in dials it.
 #[cfg(all(feature = "rustls-tls", feature = "rustls-tls-aws-lc"))]
+fn update_ech_retry_configs(name: &str, err: &io::Error) -> bool {
+    use tokio_rustls::rustls::Error;
+
+    let Some(Error::RejectedEch(rejected)) = err.get_ref().and_then(|e| e.downcast_ref::<Error>())
+    else {
+        return false;
+    };
+    let mut cache = ECH_RETRY_CONFIGS.lock().unwrap();
+    match rejected.retry_configs() {
+        Some(configs) => {
+            debug!("ech rejected by {}, retrying with its retry configs", name);
+            cache.insert(name.to_string(), configs);
+            true
+        }
+        None => {
+            debug!("ech rejected by {} without retry configs", name);
+            cache.remove(name);
+            false
+        }
+    }
+}
+
+#[cfg(feature = "rustls-tls")]
 fn decode_ech_config_list(ech_config_list: &str) -> io::Result<EchConfigListBytes<'static>> {
     let ech_config_list = ech_config_list.trim();
     if ech_config_list.starts_with("-----BEGIN") {
@@ -392,7 +579,7 @@ fn decode_ech_config_list(ech_config_list: &str) -> io::Result<EchConfigListByte
     Ok(EchConfigListBytes::from(decoded))
 }
 
-#[cfg(all(feature = "rustls-tls", any(feature = "rustls-tls-aws-lc", test)))]
+#[cfg(feature = "rustls-tls")]
 fn ensure_ech_config_list_bytes(mut decoded: Vec<u8>) -> (Vec<u8>, bool) {
     if decoded.len() >= 2 {
         let declared = u16::from_be_bytes([decoded[0], decoded[1]]) as usize;
@@ -414,7 +601,7 @@ fn ensure_ech_config_list_bytes(mut decoded: Vec<u8>) -> (Vec<u8>, bool) {
     (decoded, false)
 }
 
-#[cfg(all(feature = "rustls-tls", any(feature = "rustls-tls-aws-lc", test)))]
+#[cfg(feature = "rustls-tls")]
 fn decode_base64(data: &str) -> io::Result<Vec<u8>> {
     fn value(byte: u8) -> Option<u8> {
         match byte {
@@ -556,12 +743,24 @@ impl OutboundStreamHandler for Handler {
                             .select_ech_config_list(&name, !ech_dns_lookup_skipped)
                             .await?;
                         ech_config_selected = selected_ech.is_some();
+                        if !ech_config_selected && self.ech_required {
+                            return Err(io::Error::other(format!(
+                                "no ech config for {}, not connecting with a plaintext sni",
+                                &name
+                            )));
+                        }
                         Self::build_rustls_config(
                             &self.alpns,
                             self.certificate.as_ref(),
                             self.certificate_key.as_ref(),
+                            self.client_certificate.as_ref(),
+                            self.client_certificate_key.as_ref(),
                             self.insecure,
-                            selected_ech.as_deref(),
+                            self.allow_name_mismatch,
+                            self.certificate_fingerprint.as_deref(),
+                            &self.certificate_pins,
+                            self.pin_only,
+                            selected_ech,
                         )
                         .map_err(|e| io::Error::other(format!("build tls config failed: {}", e)))?
                     } else {
@@ -588,6 +787,13 @@ impl OutboundStreamHandler for Handler {
                 let tls_stream = connector
                     .connect(domain.to_owned(), stream)
                     .map_err(|e| {
+                        #[cfg(feature = "rustls-tls-aws-lc")]
+                        if ech_config_selected && update_ech_retry_configs(&name, &e) {
+                            return io::Error::other(RetryOnNewTransport(format!(
+                                "connect tls failed: ech rejected by {}, retry configs received",
+                                &name
+                            )));
+                        }
                         io::Error::new(
                             io::ErrorKind::InvalidInput,
                             format!("connect tls failed: {}", e),
@@ -652,6 +858,7 @@ mod tests {
 
     use protobuf::MessageField;
     use tokio::sync::RwLock;
+    use tokio_rustls::rustls::pki_types::EchConfigListBytes;
 
     use crate::app::{dns::DnsClient, SyncDnsClient};
     #[cfg(feature = "rustls-tls-aws-lc")]
@@ -751,9 +958,16 @@ mod tests {
             vec![],
             None,
             None,
+            None,
+            None,
+            false,
+            false,
+            None,
+            Vec::new(),
             false,
             true,
             false,
+            false,
             Some("$$$".to_string()),
             new_test_dns_client(),
         );
@@ -768,9 +982,16 @@ mod tests {
             vec![],
             None,
             None,
+            None,
+            None,
+            false,
+            false,
+            None,
+            Vec::new(),
             false,
             true,
             false,
+            false,
             None,
             new_test_dns_client(),
         );
@@ -780,7 +1001,226 @@ mod tests {
     #[cfg(not(feature = "rustls-tls-aws-lc"))]
     #[test]
     fn test_build_rustls_config_with_ech_on_ring_returns_connection_error() {
-        let err = Handler::build_rustls_config(&[], None, None, false, Some("AQID")).unwrap_err();
+        let err = Handler::build_rustls_config(
+            &[],
+            None,
+            None,
+            None,
+            None,
+            false,
+            false,
+            None,
+            &[],
+            false,
+            Some(EchConfigListBytes::from(vec![1, 2, 3])),
+        )
+        .unwrap_err();
         assert!(err.to_string().contains("requires rustls-tls-aws-lc"));
     }
+
+    #[cfg(feature = "rustls-tls-aws-lc")]
+    #[tokio::test]
+    async fn test_ech_required_without_config_fails() {
+        use crate::proxy::OutboundStreamHandler;
+
+        let handler = Handler::new(
+            "localhost".to_string(),
+            vec![],
+            None,
+            None,
+            None,
+            None,
+            false,
+            false,
+            None,
+            Vec::new(),
+            false,
+            true,
+            true,
+            true,
+            None,
+            new_test_dns_client(),
+        )
+        .unwrap();
+        let (stream, _peer) = tokio::io::duplex(1024);
+        let err = match handler
+            .handle(&Session::default(), None, Some(Box::new(stream)))
+            .await
+        {
+            Ok(_) => panic!("connected without ech"),
+            Err(e) => e,
+        };
+        assert!(err.to_string().contains("no ech config for localhost"));
+    }
+
+    #[cfg(feature = "rustls-tls-aws-lc")]
+    #[test]
+    fn test_update_ech_retry_configs() {
+        use tokio_rustls::rustls::Error;
+
+        // Errors other than a rejection of ECH keep the retry configs and
+        // aren't retried.
+        let name = "ech-retry.test";
+        super::ECH_RETRY_CONFIGS
+            .lock()
+            .unwrap()
+            .insert(name.to_string(), EchConfigListBytes::from(vec![0, 0]));
+        let other = std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            Error::General("other".to_string()),
+        );
+        assert!(!super::update_ech_retry_configs(name, &other));
+        assert!(super::ECH_RETRY_CONFIGS.lock().unwrap().contains_key(name));
+    }
+
+    #[tokio::test]
+    async fn test_session_resumption() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+        use tokio::net::{TcpListener, TcpStream};
+        use tokio_rustls::rustls::{
+            pki_types::{PrivateKeyDer, PrivatePkcs8KeyDer, ServerName},
+            HandshakeKind, ServerConfig,
+        };
+        use tokio_rustls::{TlsAcceptor, TlsConnector};
+
+        let cert = rcgen::generate_simple_self_signed(vec!["resumption.test".to_string()]).unwrap();
+        let key = PrivateKeyDer::Pkcs8(PrivatePkcs8KeyDer::from(cert.key_pair.serialize_der()));
+        let server_config = ServerConfig::builder_with_provider(Arc::new(default_provider()))
+            .with_safe_default_protocol_versions()
+            .unwrap()
+            .with_no_client_auth()
+            .with_single_cert(vec![cert.cert.der().clone()], key)
+            .unwrap();
+        let acceptor = TlsAcceptor::from(Arc::new(server_config));
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            loop {
+                let (stream, _) = listener.accept().await.unwrap();
+                let mut stream = acceptor.accept(stream).await.unwrap();
+                stream.write_all(b"x").await.unwrap();
+                stream.flush().await.unwrap();
+            }
+        });
+
+        let certificate = cert.cert.pem();
+        let connector = TlsConnector::from(
+            Handler::build_rustls_config(
+                &[],
+                Some(&certificate),
+                None,
+                None,
+                None,
+                false,
+                false,
+                None,
+                &[],
+                false,
+                None,
+            )
+            .unwrap(),
+        );
+        let mut kinds = Vec::new();
+        for _ in 0..2 {
+            let stream = TcpStream::connect(addr).await.unwrap();
+            let name = ServerName::try_from("resumption.test").unwrap();
+            let mut stream = connector.connect(name, stream).await.unwrap();
+            // Reading past the handshake processes the session tickets.
+            let mut buf = [0u8; 1];
+            stream.read_exact(&mut buf).await.unwrap();
+            kinds.push(stream.get_ref().1.handshake_kind());
+        }
+        assert_eq!(
+            kinds,
+            vec![Some(HandshakeKind::Full), Some(HandshakeKind::Resumed)]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_client_certificate() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+        use tokio::net::{TcpListener, TcpStream};
+        use tokio_rustls::rustls::{
+            pki_types::{PrivateKeyDer, PrivatePkcs8KeyDer, ServerName},
+            server::WebPkiClientVerifier,
+            RootCertStore, ServerConfig,
+        };
+        use tokio_rustls::{TlsAcceptor, TlsConnector};
+
+        let server = rcgen::generate_simple_self_signed(vec!["mtls.test".to_string()]).unwrap();
+        let client = rcgen::generate_simple_self_signed(vec!["client".to_string()]).unwrap();
+        let provider = Arc::new(default_provider());
+        let mut client_roots = RootCertStore::empty();
+        client_roots.add(client.cert.der().clone()).unwrap();
+        let client_verifier =
+            WebPkiClientVerifier::builder_with_provider(Arc::new(client_roots), provider.clone())
+                .build()
+                .unwrap();
+        let key = PrivateKeyDer::Pkcs8(PrivatePkcs8KeyDer::from(server.key_pair.serialize_der()));
+        let server_config = ServerConfig::builder_with_provider(provider)
+            .with_safe_default_protocol_versions()
+            .unwrap()
+            .with_client_cert_verifier(client_verifier)
+            .with_single_cert(vec![server.cert.der().clone()], key)
+            .unwrap();
+        let acceptor = TlsAcceptor::from(Arc::new(server_config));
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            loop {
+                let (stream, _) = listener.accept().await.unwrap();
+                if let Ok(mut stream) = acceptor.accept(stream).await {
+                    let _ = stream.write_all(b"x").await;
+                }
+            }
+        });
+
+        let build = |client_certificate: Option<&String>,
+                     client_certificate_key: Option<&String>| {
+            Handler::build_rustls_config(
+                &[],
+                Some(&server.cert.pem()),
+                None,
+                client_certificate,
+                client_certificate_key,
+                false,
+                false,
+                None,
+                &[],
+                false,
+                None,
+            )
+        };
+        let connect = |config: Arc<tokio_rustls::rustls::ClientConfig>| async move {
+            let stream = TcpStream::connect(addr).await?;
+            let name = ServerName::try_from("mtls.test").unwrap();
+            let mut stream = TlsConnector::from(config).connect(name, stream).await?;
+            let mut buf = [0u8; 1];
+            stream.read_exact(&mut buf).await.map(|_| ())
+        };
+
+        let client_cert = client.cert.pem();
+        let client_key = client.key_pair.serialize_pem();
+        let config = build(Some(&client_cert), Some(&client_key)).unwrap();
+        assert!(connect(config).await.is_ok());
+
+        let config = build(None, None).unwrap();
+        assert!(connect(config).await.is_err());
+
+        let other_key = server.key_pair.serialize_pem();
+        let err = build(Some(&client_cert), Some(&other_key)).unwrap_err();
+        assert!(err.to_string().contains("doesn't match key"));
+        assert!(build(Some(&client_cert), None).is_err());
+    }
+
+    fn default_provider() -> tokio_rustls::rustls::crypto::CryptoProvider {
+        #[cfg(feature = "rustls-tls-aws-lc")]
+        {
+            tokio_rustls::rustls::crypto::aws_lc_rs::default_provider()
+        }
+        #[cfg(not(feature = "rustls-tls-aws-lc"))]
+        {
+            tokio_rustls::rustls::crypto::ring::default_provider()
+        }
+    }
 }
@@ -1,4 +1,4 @@
-use std::collections::HashSet;
+use std::collections::HashMap;
 use std::io;
 use std::net::SocketAddr;
 
@@ -114,16 +114,26 @@ where
 }
 
 pub struct Handler {
-    keys: HashSet<Vec<u8>>,
+    // The users by the keys of their passwords, none for the passwords
+    // without a user.
+    keys: HashMap<Vec<u8>, Option<String>>,
+}
+
+fn password_key(password: &str) -> Vec<u8> {
+    let key = Sha224::digest(password.as_bytes());
+    hex::encode(&key[..]).into_bytes()
 }
 
 impl Handler {
-    pub fn new(passwords: Vec<String>) -> Self {
-        let mut keys = HashSet::new();
+    /// The sessions of `users`, pairs of a name and a password, carry the
+    /// name of the user.
+    pub fn new(passwords: Vec<String>, users: Vec<(String, String)>) -> Self {
+        let mut keys = HashMap::new();
         for pass in passwords {
-            let key = Sha224::digest(pass.as_bytes());
-            let key = hex::encode(&key[..]);
-            keys.insert(key.as_bytes().to_vec());
+            keys.insert(password_key(&pass), None);
+        }
+        for (name, pass) in users {
+            keys.insert(password_key(&pass), Some(name));
         }
         Handler { keys }
     }
@@ -140,9 +150,10 @@ impl InboundStreamHandler for Handler {
         let mut buf = [0; 56];
         // read key
         stream.read_exact(&mut buf[..56]).await?;
-        if !self.keys.contains(&buf[..]) {
+        let Some(user) = self.keys.get(&buf[..]) else {
             return Err(io::Error::other("invalid key"));
-        }
+        };
+        sess.user = user.clone();
         // read crlf and cmd
         stream.read_exact(&mut buf[..3]).await?;
         // TODO Check CRLF?
@@ -158,11 +169,10 @@ impl InboundStreamHandler for Handler {
             // udp
             0x03 => {
                 sess.network = Network::Udp;
+                let mut source = DatagramSource::new(sess.source, sess.stream_id);
+                source.user = sess.user.clone();
                 Ok(InboundTransport::Datagram(
-                    Box::new(Datagram::new(
-                        stream,
-                        DatagramSource::new(sess.source, sess.stream_id),
-                    )),
+                    Box::new(Datagram::new(stream, source)),
                     Some(sess),
                 ))
             }
@@ -170,3 +180,63 @@ impl InboundStreamHandler for Handler {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn handler() -> Handler {
+        Handler::new(
+            vec!["shared".to_string()],
+            vec![("alice".to_string(), "secret".to_string())],
+        )
+    }
+
+    fn request(password: &str, cmd: u8) -> BytesMut {
+        let mut req = BytesMut::new();
+        req.put_slice(&password_key(password));
+        req.put_slice(b"\r\n");
+        req.put_u8(cmd);
+        SocksAddr::Domain("example.com".to_string(), 443)
+            .write_buf(&mut req, SocksAddrWireType::PortLast);
+        req.put_slice(b"\r\n");
+        req
+    }
+
+    #[tokio::test]
+    async fn test_trojan_inbound_user() {
+        let (mut c, s) = tokio::io::duplex(1024);
+        c.write_all(&request("secret", 0x01)).await.unwrap();
+        match handler().handle(Session::default(), Box::new(s)).await {
+            Ok(InboundTransport::Stream(_, sess)) => {
+                assert_eq!(sess.user.as_deref(), Some("alice"));
+                assert_eq!(sess.destination.to_string(), "example.com:443");
+            }
+            _ => panic!("unexpected transport"),
+        }
+
+        let (mut c, s) = tokio::io::duplex(1024);
+        c.write_all(&request("secret", 0x03)).await.unwrap();
+        match handler().handle(Session::default(), Box::new(s)).await {
+            Ok(InboundTransport::Datagram(_, Some(sess))) => {
+                assert_eq!(sess.user.as_deref(), Some("alice"));
+            }
+            _ => panic!("unexpected transport"),
+        }
+
+        // The passwords without a user leave the session without one.
+        let (mut c, s) = tokio::io::duplex(1024);
+        c.write_all(&request("shared", 0x01)).await.unwrap();
+        match handler().handle(Session::default(), Box::new(s)).await {
+            Ok(InboundTransport::Stream(_, sess)) => assert!(sess.user.is_none()),
+            _ => panic!("unexpected transport"),
+        }
+
+        let (mut c, s) = tokio::io::duplex(1024);
+        c.write_all(&request("wrong", 0x01)).await.unwrap();
+        assert!(handler()
+            .handle(Session::default(), Box::new(s))
+            .await
+            .is_err());
+    }
+}
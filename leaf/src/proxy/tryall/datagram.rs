@@ -1,19 +1,16 @@
 use std::io;
+use std::time::Duration;
 
 use async_trait::async_trait;
-use futures::future::select_ok;
 use tracing::debug;
 
 use crate::{app::SyncDnsClient, proxy::*, session::Session};
 
-struct HandleResult {
-    idx: usize,
-    dgram: AnyOutboundDatagram,
-}
-
 pub struct Handler {
     pub actors: Vec<AnyOutboundHandler>,
     pub delay_base: u32,
+    // No limit if 0.
+    pub max_concurrent_attempts: u32,
     pub dns_client: SyncDnsClient,
 }
 
@@ -33,39 +30,33 @@ impl OutboundDatagramHandler for Handler {
         _transport: Option<AnyOutboundTransport>,
     ) -> io::Result<AnyOutboundDatagram> {
         tracing::trace!("handling outbound datagram");
-        let mut tasks = Vec::new();
-        for (i, a) in self.actors.iter().enumerate() {
-            let t = async move {
-                if self.delay_base > 0 {
-                    tokio::time::sleep(std::time::Duration::from_millis(
-                        (self.delay_base * i as u32) as u64,
-                    ))
-                    .await;
-                }
-                let transport =
-                    crate::proxy::connect_datagram_outbound(sess, self.dns_client.clone(), a)
-                        .await?;
-                a.datagram()?
-                    .handle(sess, transport)
-                    .await
-                    .map(|dgram| HandleResult { idx: i, dgram })
-            };
-            tasks.push(Box::pin(t));
-        }
-        match select_ok(tasks.into_iter()).await {
-            Ok(v) => {
+        let attempt = |i: usize| async move {
+            let a = &self.actors[i];
+            let transport =
+                crate::proxy::connect_datagram_outbound(sess, self.dns_client.clone(), a).await?;
+            a.datagram()?.handle(sess, transport).await
+        };
+        match super::race(
+            self.actors.len(),
+            Duration::from_millis(self.delay_base as u64),
+            self.max_concurrent_attempts as usize,
+            attempt,
+        )
+        .await
+        {
+            Ok((i, dgram, elapsed)) => {
+                let tag = self.actors[i].tag();
                 debug!(
-                    "tryall handles [{}:{}] to [{}]",
+                    "tryall handles [{}:{}] to [{}] in {}ms",
                     sess.network,
                     sess.destination,
-                    self.actors[v.0.idx].tag()
+                    tag,
+                    elapsed.as_millis()
                 );
-                Ok(v.0.dgram)
+                sess.group_actor.lock().unwrap().replace(tag.clone());
+                Ok(dgram)
             }
-            Err(e) => Err(io::Error::other(format!(
-                "all outbound attempts failed, last error: {}",
-                e
-            ))),
+            Err(errors) => Err(super::all_failed(&self.actors, errors)),
         }
     }
 }
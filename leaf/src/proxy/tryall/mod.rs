@@ -1,5 +1,150 @@
+use std::future::Future;
+use std::io;
+use std::time::Duration;
+
+use futures::stream::{FuturesUnordered, StreamExt};
+use tokio::time::Instant;
+
 pub mod datagram;
 pub mod stream;
 
 pub use datagram::Handler as DatagramHandler;
 pub use stream::Handler as StreamHandler;
+
+// Races an attempt on each of `n` actors, attempt `i` starting `delay_base`
+// × `i` after the first one, and no more than `max_concurrent` of them at
+// once if it's not 0. Returns the first one which succeeds with the time it
+// took, the other attempts are dropped right away, which closes their
+// connections. Or else the error of each attempt.
+async fn race<T, F, Fut>(
+    n: usize,
+    delay_base: Duration,
+    max_concurrent: usize,
+    attempt: F,
+) -> Result<(usize, T, Duration), Vec<(usize, io::Error)>>
+where
+    F: Fn(usize) -> Fut,
+    Fut: Future<Output = io::Result<T>>,
+{
+    let start = Instant::now();
+    let max_concurrent = if max_concurrent == 0 {
+        n
+    } else {
+        max_concurrent
+    };
+    let mut next = 0;
+    let mut running = FuturesUnordered::new();
+    let mut errors = Vec::new();
+    loop {
+        while next < n && running.len() < max_concurrent {
+            let i = next;
+            // Not delayed any further if it had to wait for a free slot.
+            let at = start + delay_base * i as u32;
+            let fut = attempt(i);
+            running.push(async move {
+                tokio::time::sleep_until(at).await;
+                (i, fut.await)
+            });
+            next += 1;
+        }
+        match running.next().await {
+            Some((i, Ok(v))) => return Ok((i, v, start.elapsed())),
+            Some((i, Err(e))) => errors.push((i, e)),
+            None => return Err(errors),
+        }
+    }
+}
+
+// The error of a session which all actors failed, with the reason of each.
+fn all_failed(
+    actors: &[crate::proxy::AnyOutboundHandler],
+    errors: Vec<(usize, io::Error)>,
+) -> io::Error {
+    let reasons: Vec<String> = errors
+        .into_iter()
+        .map(|(i, e)| format!("[{}] {}", actors[i].tag(), e))
+        .collect();
+    io::Error::other(format!(
+        "all outbound attempts failed: {}",
+        reasons.join("; ")
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    use super::*;
+
+    // An attempt which succeeds or fails after a time, and tells whether it
+    // was dropped before completing.
+    struct Attempt {
+        ok: bool,
+        after: Duration,
+    }
+
+    #[tokio::test]
+    async fn test_race() {
+        let attempts = [
+            Attempt {
+                ok: false,
+                after: Duration::from_millis(10),
+            },
+            Attempt {
+                ok: true,
+                after: Duration::from_millis(300),
+            },
+            Attempt {
+                ok: true,
+                after: Duration::from_millis(50),
+            },
+        ];
+        let dropped = Arc::new(AtomicUsize::new(0));
+        let run = |i: usize| {
+            let a = &attempts[i];
+            let dropped = dropped.clone();
+            async move {
+                struct Guard(Arc<AtomicUsize>);
+                impl Drop for Guard {
+                    fn drop(&mut self) {
+                        self.0.fetch_add(1, Ordering::Relaxed);
+                    }
+                }
+                let g = Guard(dropped);
+                tokio::time::sleep(a.after).await;
+                std::mem::forget(g);
+                if a.ok {
+                    Ok(i)
+                } else {
+                    Err(io::Error::other("refused"))
+                }
+            }
+        };
+        // The third one wins at 100 + 50 ms, the second one is dropped.
+        let (i, v, elapsed) = race(3, Duration::from_millis(50), 0, run).await.unwrap();
+        assert_eq!((i, v), (2, 2));
+        assert!(elapsed < Duration::from_millis(300));
+        assert_eq!(dropped.load(Ordering::Relaxed), 1);
+
+        // With one attempt at a time the second one wins, the third one
+        // never starts.
+        dropped.store(0, Ordering::Relaxed);
+        let (i, _, _) = race(3, Duration::from_millis(50), 1, run).await.unwrap();
+        assert_eq!(i, 1);
+        assert_eq!(dropped.load(Ordering::Relaxed), 0);
+    }
+
+    #[tokio::test]
+    async fn test_race_all_failed() {
+        let res: Result<(usize, (), Duration), _> =
+            race(3, Duration::from_millis(10), 2, |i| async move {
+                Err(io::Error::other(format!("error {}", i)))
+            })
+            .await;
+        let errors = res.unwrap_err();
+        let mut idx: Vec<usize> = errors.iter().map(|(i, _)| *i).collect();
+        idx.sort();
+        assert_eq!(idx, vec![0, 1, 2]);
+    }
+}
@@ -0,0 +1,7 @@
+//! TUIC v5, a proxy protocol relaying TCP and UDP over a shared QUIC
+//! connection.
+
+pub mod protocol;
+
+#[cfg(feature = "outbound-tuic")]
+pub mod outbound;
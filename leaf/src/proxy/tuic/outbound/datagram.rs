@@ -0,0 +1,240 @@
+use std::io;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use tokio::sync::mpsc::{channel, Receiver, Sender};
+use tracing::{debug, trace};
+
+use crate::common::udp_session::{Defragger, SessionMap};
+use crate::{proxy::*, session::SocksAddr};
+
+use super::super::protocol::{encode_dissociate, Packet};
+use super::stream::TaskGuard;
+
+type Payload = (SocksAddr, Bytes);
+
+// The largest packet a server may send on a unidirectional stream.
+const MAX_STREAM_PACKET_LEN: usize = 64 * 1024 + 512;
+
+/// How UDP packets are carried.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum UdpRelayMode {
+    /// As QUIC datagrams, fragmented to fit.
+    Native,
+    /// Each on its own unidirectional stream.
+    Quic,
+}
+
+struct Session {
+    packet_tx: Sender<Payload>,
+    defragger: Defragger<Packet>,
+}
+
+// UDP sessions relayed over a connection, keyed by association id.
+#[derive(Default)]
+pub(super) struct Sessions(SessionMap<u16, Session>);
+
+impl Sessions {
+    fn dispatch(&self, data: Bytes) {
+        let packet = match Packet::decode(data) {
+            Ok(Some(packet)) => packet,
+            Ok(None) => return,
+            Err(e) => {
+                debug!("invalid tuic packet: {}", e);
+                return;
+            }
+        };
+        let assoc_id = packet.assoc_id;
+        let mut sessions = self.0.lock();
+        let Some(session) = sessions.get_mut(&assoc_id) else {
+            trace!("unknown tuic udp session {}", assoc_id);
+            return;
+        };
+        let Some(packet) = session.defragger.feed(packet) else {
+            return;
+        };
+        let Some(addr) = packet.address else {
+            debug!("tuic packet without address");
+            return;
+        };
+        if session.packet_tx.try_send((addr, packet.payload)).is_err() {
+            trace!("tuic udp session {} is full, dropping packet", assoc_id);
+        }
+    }
+}
+
+// Dispatches the packets the server sends back, as datagrams or on
+// unidirectional streams, to their sessions.
+pub(super) fn spawn_receivers(conn: quinn::Connection, sessions: Arc<Sessions>) {
+    let datagram_conn = conn.clone();
+    let datagram_sessions = sessions.clone();
+    tokio::spawn(async move {
+        loop {
+            match datagram_conn.read_datagram().await {
+                Ok(data) => datagram_sessions.dispatch(data),
+                Err(e) => {
+                    debug!("tuic connection closed: {}", e);
+                    break;
+                }
+            }
+        }
+    });
+    tokio::spawn(async move {
+        while let Ok(mut recv) = conn.accept_uni().await {
+            let sessions = sessions.clone();
+            tokio::spawn(async move {
+                match recv.read_to_end(MAX_STREAM_PACKET_LEN).await {
+                    Ok(data) => sessions.dispatch(Bytes::from(data)),
+                    Err(e) => debug!("read tuic packet stream failed: {}", e),
+                }
+            });
+        }
+    });
+}
+
+// Unregisters the session and dissociates it once both halves are dropped.
+struct SessionGuard {
+    assoc_id: u16,
+    conn: quinn::Connection,
+    sessions: Arc<Sessions>,
+    _task: TaskGuard,
+}
+
+impl Drop for SessionGuard {
+    fn drop(&mut self) {
+        self.sessions.0.remove(&self.assoc_id);
+        let Ok(rt) = tokio::runtime::Handle::try_current() else {
+            return;
+        };
+        let conn = self.conn.clone();
+        let assoc_id = self.assoc_id;
+        rt.spawn(async move {
+            let Ok(mut send) = conn.open_uni().await else {
+                return;
+            };
+            if send.write_all(&encode_dissociate(assoc_id)).await.is_ok() {
+                let _ = send.finish();
+            }
+        });
+    }
+}
+
+pub struct Datagram {
+    mode: UdpRelayMode,
+    packet_rx: Receiver<Payload>,
+    guard: Arc<SessionGuard>,
+}
+
+impl Datagram {
+    pub(super) fn new(
+        conn: quinn::Connection,
+        sessions: Arc<Sessions>,
+        mode: UdpRelayMode,
+        task: TaskGuard,
+    ) -> io::Result<Self> {
+        let (packet_tx, packet_rx) = channel(*crate::option::UDP_DOWNLINK_CHANNEL_SIZE);
+        let assoc_id = sessions.0.register(Session {
+            packet_tx,
+            defragger: Defragger::default(),
+        })?;
+        Ok(Self {
+            mode,
+            packet_rx,
+            guard: Arc::new(SessionGuard {
+                assoc_id,
+                conn,
+                sessions,
+                _task: task,
+            }),
+        })
+    }
+}
+
+impl OutboundDatagram for Datagram {
+    fn split(
+        self: Box<Self>,
+    ) -> (
+        Box<dyn OutboundDatagramRecvHalf>,
+        Box<dyn OutboundDatagramSendHalf>,
+    ) {
+        (
+            Box::new(DatagramRecvHalf {
+                packet_rx: self.packet_rx,
+                _guard: self.guard.clone(),
+            }),
+            Box::new(DatagramSendHalf {
+                mode: self.mode,
+                next_packet_id: 0,
+                guard: self.guard,
+            }),
+        )
+    }
+}
+
+struct DatagramRecvHalf {
+    packet_rx: Receiver<Payload>,
+    _guard: Arc<SessionGuard>,
+}
+
+#[async_trait]
+impl OutboundDatagramRecvHalf for DatagramRecvHalf {
+    async fn recv_from(&mut self, buf: &mut [u8]) -> io::Result<(usize, SocksAddr)> {
+        let (src_addr, payload) = self
+            .packet_rx
+            .recv()
+            .await
+            .ok_or_else(|| io::Error::other("tuic connection closed"))?;
+        if buf.len() < payload.len() {
+            return Err(io::Error::other("Small buffer"));
+        }
+        buf[..payload.len()].copy_from_slice(&payload);
+        Ok((payload.len(), src_addr))
+    }
+}
+
+struct DatagramSendHalf {
+    mode: UdpRelayMode,
+    next_packet_id: u16,
+    guard: Arc<SessionGuard>,
+}
+
+#[async_trait]
+impl OutboundDatagramSendHalf for DatagramSendHalf {
+    async fn send_to(&mut self, buf: &[u8], dst_addr: &SocksAddr) -> io::Result<usize> {
+        let packet = Packet {
+            assoc_id: self.guard.assoc_id,
+            packet_id: self.next_packet_id,
+            fragment_count: 1,
+            fragment_id: 0,
+            address: Some(dst_addr.clone()),
+            payload: Bytes::copy_from_slice(buf),
+        };
+        self.next_packet_id = self.next_packet_id.wrapping_add(1);
+        let conn = &self.guard.conn;
+        match self.mode {
+            UdpRelayMode::Native => {
+                let max_size = conn
+                    .max_datagram_size()
+                    .ok_or_else(|| io::Error::other("tuic server doesn't accept datagrams"))?;
+                let fragments = packet
+                    .fragment(max_size)
+                    .ok_or_else(|| io::Error::other("udp packet too large"))?;
+                for fragment in fragments {
+                    conn.send_datagram(fragment.encode())
+                        .map_err(io::Error::other)?;
+                }
+            }
+            UdpRelayMode::Quic => {
+                let mut send = conn.open_uni().await.map_err(io::Error::other)?;
+                send.write_all(&packet.encode()).await?;
+                send.finish().map_err(io::Error::other)?;
+            }
+        }
+        Ok(buf.len())
+    }
+
+    async fn close(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
@@ -0,0 +1,4 @@
+mod datagram;
+mod stream;
+
+pub use stream::Handler;
@@ -0,0 +1,356 @@
+use std::io;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use futures::task::{Context, Poll};
+use rustls::crypto::CryptoProvider;
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::sync::Mutex;
+use tokio::time::{timeout, Duration};
+use tracing::{debug, trace, Instrument};
+
+use crate::{app::SyncDnsClient, common::cert::InsecureVerifier, proxy::*, session::Session};
+
+use super::super::protocol::{encode_authenticate, encode_connect, encode_heartbeat, TOKEN_LEN};
+use super::datagram::{self, Datagram, Sessions, UdpRelayMode};
+
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(3);
+
+// Counts a relay of the connection while it's alive.
+pub(super) struct TaskGuard(Arc<AtomicUsize>);
+
+impl TaskGuard {
+    fn new(tasks: &Arc<AtomicUsize>) -> Self {
+        tasks.fetch_add(1, Ordering::Relaxed);
+        Self(tasks.clone())
+    }
+}
+
+impl Drop for TaskGuard {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+// A TCP relay stream.
+struct TuicStream {
+    send: quinn::SendStream,
+    recv: quinn::RecvStream,
+    _task: TaskGuard,
+}
+
+impl AsyncRead for TuicStream {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context,
+        buf: &mut ReadBuf,
+    ) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.recv).poll_read(cx, buf)
+    }
+}
+
+impl AsyncWrite for TuicStream {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.send).poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.send).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.send).poll_shutdown(cx)
+    }
+}
+
+// Sends heartbeats until the connection is closed, skipping them while
+// nothing is relayed so that an idle connection times out.
+fn spawn_heartbeat(conn: quinn::Connection, tasks: Arc<AtomicUsize>) {
+    tokio::spawn(async move {
+        while timeout(HEARTBEAT_INTERVAL, conn.closed()).await.is_err() {
+            if tasks.load(Ordering::Relaxed) == 0 {
+                continue;
+            }
+            if let Err(e) = conn.send_datagram(encode_heartbeat()) {
+                debug!("send tuic heartbeat failed: {}", e);
+                break;
+            }
+        }
+    });
+}
+
+// An authenticated connection, all streams and UDP sessions share it.
+struct Connection {
+    conn: quinn::Connection,
+    sessions: Arc<Sessions>,
+    tasks: Arc<AtomicUsize>,
+}
+
+struct Manager {
+    address: String,
+    port: u16,
+    server_name: Option<String>,
+    uuid: uuid::Uuid,
+    password: String,
+    udp_relay_mode: UdpRelayMode,
+    crypto: Arc<quinn::crypto::rustls::QuicClientConfig>,
+    dns_client: SyncDnsClient,
+    connection: Mutex<Option<Arc<Connection>>>,
+}
+
+impl Manager {
+    #[allow(clippy::too_many_arguments)]
+    fn new(
+        address: String,
+        port: u16,
+        uuid: uuid::Uuid,
+        password: String,
+        server_name: Option<String>,
+        alpns: Vec<String>,
+        insecure: bool,
+        udp_relay_mode: UdpRelayMode,
+        dns_client: SyncDnsClient,
+    ) -> Result<Self> {
+        #[cfg(feature = "rustls-tls-aws-lc")]
+        let provider: Arc<CryptoProvider> = rustls::crypto::aws_lc_rs::default_provider().into();
+        #[cfg(not(feature = "rustls-tls-aws-lc"))]
+        let provider: Arc<CryptoProvider> = rustls::crypto::ring::default_provider().into();
+
+        let builder = rustls::ClientConfig::builder_with_provider(provider.clone())
+            .with_protocol_versions(&[&rustls::version::TLS13])?;
+        let mut crypto = if insecure {
+            builder
+                .dangerous()
+                .with_custom_certificate_verifier(Arc::new(InsecureVerifier(provider)))
+                .with_no_client_auth()
+        } else {
+            let mut roots = rustls::RootCertStore::empty();
+            roots.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned());
+            builder.with_root_certificates(roots).with_no_client_auth()
+        };
+        crypto.alpn_protocols = alpns.iter().map(|x| x.as_bytes().to_vec()).collect();
+        if let Some(key_log) = crate::common::key_log::key_log() {
+            crypto.key_log = key_log;
+        }
+
+        Ok(Manager {
+            address,
+            port,
+            server_name,
+            uuid,
+            password,
+            udp_relay_mode,
+            crypto: Arc::new(quinn::crypto::rustls::QuicClientConfig::try_from(crypto)?),
+            dns_client,
+            connection: Mutex::new(None),
+        })
+    }
+
+    // The server checks the token before serving any of the relays, so they
+    // can be opened at once.
+    async fn authenticate(&self, conn: &quinn::Connection) -> Result<()> {
+        let mut token = [0u8; TOKEN_LEN];
+        conn.export_keying_material(&mut token, self.uuid.as_bytes(), self.password.as_bytes())
+            .map_err(|e| anyhow!("export keying material failed: {:?}", e))?;
+        let mut send = conn.open_uni().await?;
+        send.write_all(&encode_authenticate(self.uuid.as_bytes(), &token))
+            .await?;
+        send.finish()?;
+        Ok(())
+    }
+
+    async fn connect(&self, dial_timeout: Duration) -> Result<Connection> {
+        let socket = self
+            .new_udp_socket(&crate::option::UNSPECIFIED_BIND_ADDR)
+            .instrument(tracing::Span::current())
+            .await?;
+        let mut endpoint = quinn::Endpoint::new(
+            quinn::EndpointConfig::default(),
+            None,
+            socket.into_std()?,
+            Arc::new(quinn::TokioRuntime),
+        )?;
+        endpoint.set_default_client_config(quinn::ClientConfig::new(self.crypto.clone()));
+        let ips = {
+            self.dns_client
+                .read()
+                .await
+                .direct_lookup(&self.address)
+                .map_err(|e| io::Error::other(format!("lookup {} failed: {}", &self.address, e)))
+                .instrument(tracing::Span::current())
+                .await?
+        };
+        if ips.is_empty() {
+            return Err(anyhow!("could not resolve to any address",));
+        }
+        let server_name = self.server_name.as_ref().unwrap_or(&self.address);
+        let mut last_err: Option<anyhow::Error> = None;
+        for ip in ips {
+            let connect_addr = SocketAddr::new(ip, self.port);
+            let connecting = match endpoint.connect(connect_addr, server_name) {
+                Ok(c) => c,
+                Err(e) => {
+                    last_err = Some(e.into());
+                    continue;
+                }
+            };
+            match timeout(dial_timeout, connecting).await {
+                Ok(Ok(conn)) => {
+                    trace!("tuic connected to {}", &connect_addr);
+                    self.authenticate(&conn).await?;
+                    let sessions = Arc::new(Sessions::default());
+                    let tasks = Arc::new(AtomicUsize::new(0));
+                    datagram::spawn_receivers(conn.clone(), sessions.clone());
+                    spawn_heartbeat(conn.clone(), tasks.clone());
+                    return Ok(Connection {
+                        conn,
+                        sessions,
+                        tasks,
+                    });
+                }
+                Ok(Err(e)) => last_err = Some(e.into()),
+                Err(_) => last_err = Some(anyhow!("connect tuic timed out")),
+            }
+        }
+        Err(last_err.unwrap_or_else(|| anyhow!("connect tuic failed")))
+    }
+
+    // Returns the shared connection, dialing a new one if there's none or it
+    // has been closed.
+    async fn connection(&self) -> Result<Arc<Connection>> {
+        let mut connection = self.connection.lock().await;
+        if let Some(c) = connection.as_ref() {
+            if c.conn.close_reason().is_none() {
+                return Ok(c.clone());
+            }
+            debug!("tuic connection closed, reconnecting");
+        }
+        let dial_timeout = Duration::from_secs(*crate::option::OUTBOUND_DIAL_TIMEOUT);
+        let c = Arc::new(self.connect(dial_timeout).await?);
+        *connection = Some(c.clone());
+        Ok(c)
+    }
+
+    async fn new_stream(&self, sess: &Session) -> Result<AnyStream> {
+        let c = self.connection().await?;
+        let task = TaskGuard::new(&c.tasks);
+        let (mut send, recv) = c.conn.open_bi().await?;
+        send.write_all(&encode_connect(&sess.destination)).await?;
+        Ok(Box::new(TuicStream {
+            send,
+            recv,
+            _task: task,
+        }))
+    }
+
+    async fn new_datagram(&self) -> Result<Datagram> {
+        let c = self.connection().await?;
+        Ok(Datagram::new(
+            c.conn.clone(),
+            c.sessions.clone(),
+            self.udp_relay_mode,
+            TaskGuard::new(&c.tasks),
+        )?)
+    }
+}
+
+impl UdpConnector for Manager {}
+
+pub struct Handler {
+    manager: Manager,
+}
+
+impl Handler {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        address: String,
+        port: u16,
+        uuid: &str,
+        password: String,
+        server_name: Option<String>,
+        alpns: Vec<String>,
+        insecure: bool,
+        udp_relay_mode: Option<&str>,
+        dns_client: SyncDnsClient,
+    ) -> Result<Self> {
+        let uuid = uuid::Uuid::parse_str(uuid).map_err(|e| anyhow!("invalid uuid: {}", e))?;
+        let udp_relay_mode = match udp_relay_mode.unwrap_or("native") {
+            "native" => UdpRelayMode::Native,
+            "quic" => UdpRelayMode::Quic,
+            x => return Err(anyhow!("unknown udp relay mode: {}", x)),
+        };
+        Ok(Self {
+            manager: Manager::new(
+                address,
+                port,
+                uuid,
+                password,
+                server_name,
+                alpns,
+                insecure,
+                udp_relay_mode,
+                dns_client,
+            )?,
+        })
+    }
+}
+
+impl UdpConnector for Handler {}
+
+#[async_trait]
+impl OutboundStreamHandler for Handler {
+    fn connect_addr(&self) -> OutboundConnect {
+        OutboundConnect::Unknown
+    }
+
+    async fn handle<'a>(
+        &'a self,
+        sess: &'a Session,
+        _lhs: Option<&mut AnyStream>,
+        _stream: Option<AnyStream>,
+    ) -> io::Result<AnyStream> {
+        tracing::trace!("handling outbound stream");
+        self.manager
+            .new_stream(sess)
+            .instrument(tracing::Span::current())
+            .await
+            .map_err(|e| io::Error::other(format!("new tuic stream failed: {}", e)))
+    }
+}
+
+#[async_trait]
+impl OutboundDatagramHandler for Handler {
+    fn connect_addr(&self) -> OutboundConnect {
+        OutboundConnect::Unknown
+    }
+
+    fn transport_type(&self) -> DatagramTransportType {
+        match self.manager.udp_relay_mode {
+            UdpRelayMode::Native => DatagramTransportType::Unreliable,
+            UdpRelayMode::Quic => DatagramTransportType::Reliable,
+        }
+    }
+
+    async fn handle<'a>(
+        &'a self,
+        _sess: &'a Session,
+        _transport: Option<AnyOutboundTransport>,
+    ) -> io::Result<AnyOutboundDatagram> {
+        tracing::trace!("handling outbound datagram");
+        let dgram = self
+            .manager
+            .new_datagram()
+            .instrument(tracing::Span::current())
+            .await
+            .map_err(|e| io::Error::other(format!("new tuic udp session failed: {}", e)))?;
+        Ok(Box::new(dgram))
+    }
+}
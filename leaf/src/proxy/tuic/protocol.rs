@@ -0,0 +1,346 @@
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+
+use crate::common::udp_session::Fragment;
+use crate::session::SocksAddr;
+
+pub const VERSION: u8 = 0x05;
+
+const COMMAND_AUTHENTICATE: u8 = 0x00;
+const COMMAND_CONNECT: u8 = 0x01;
+const COMMAND_PACKET: u8 = 0x02;
+const COMMAND_DISSOCIATE: u8 = 0x03;
+const COMMAND_HEARTBEAT: u8 = 0x04;
+
+const ADDR_TYPE_NONE: u8 = 0xff;
+const ADDR_TYPE_DOMAIN: u8 = 0x00;
+const ADDR_TYPE_IPV4: u8 = 0x01;
+const ADDR_TYPE_IPV6: u8 = 0x02;
+
+// Version, command, association id, packet id, fragment count, fragment id
+// and size.
+const PACKET_HEADER_LEN: usize = 10;
+
+/// The length of the token which authenticates a connection.
+pub const TOKEN_LEN: usize = 32;
+
+fn address_len(addr: Option<&SocksAddr>) -> usize {
+    match addr {
+        None => 1,
+        Some(SocksAddr::Ip(SocketAddr::V4(_))) => 1 + 4 + 2,
+        Some(SocksAddr::Ip(SocketAddr::V6(_))) => 1 + 16 + 2,
+        Some(SocksAddr::Domain(domain, _)) => 1 + 1 + domain.len() + 2,
+    }
+}
+
+fn put_address(buf: &mut BytesMut, addr: Option<&SocksAddr>) {
+    match addr {
+        None => buf.put_u8(ADDR_TYPE_NONE),
+        Some(SocksAddr::Ip(addr)) => {
+            match addr.ip() {
+                IpAddr::V4(ip) => {
+                    buf.put_u8(ADDR_TYPE_IPV4);
+                    buf.put_slice(&ip.octets());
+                }
+                IpAddr::V6(ip) => {
+                    buf.put_u8(ADDR_TYPE_IPV6);
+                    buf.put_slice(&ip.octets());
+                }
+            }
+            buf.put_u16(addr.port());
+        }
+        Some(SocksAddr::Domain(domain, port)) => {
+            buf.put_u8(ADDR_TYPE_DOMAIN);
+            buf.put_u8(domain.len() as u8);
+            buf.put_slice(domain.as_bytes());
+            buf.put_u16(*port);
+        }
+    }
+}
+
+fn truncated() -> io::Error {
+    io::Error::new(io::ErrorKind::UnexpectedEof, "truncated tuic packet")
+}
+
+fn get_address(buf: &mut &[u8]) -> io::Result<Option<SocksAddr>> {
+    if !buf.has_remaining() {
+        return Err(truncated());
+    }
+    let addr = match buf.get_u8() {
+        ADDR_TYPE_NONE => return Ok(None),
+        ADDR_TYPE_IPV4 => {
+            if buf.remaining() < 4 + 2 {
+                return Err(truncated());
+            }
+            let ip = Ipv4Addr::from(buf.get_u32());
+            SocksAddr::from((ip, buf.get_u16()))
+        }
+        ADDR_TYPE_IPV6 => {
+            if buf.remaining() < 16 + 2 {
+                return Err(truncated());
+            }
+            let ip = Ipv6Addr::from(buf.get_u128());
+            SocksAddr::from((ip, buf.get_u16()))
+        }
+        ADDR_TYPE_DOMAIN => {
+            if !buf.has_remaining() {
+                return Err(truncated());
+            }
+            let len = buf.get_u8() as usize;
+            if buf.remaining() < len + 2 {
+                return Err(truncated());
+            }
+            let domain = String::from_utf8(buf[..len].to_vec())
+                .map_err(|_| io::Error::other("invalid tuic address"))?;
+            buf.advance(len);
+            SocksAddr::try_from((domain, buf.get_u16()))?
+        }
+        t => return Err(io::Error::other(format!("unknown tuic address type {}", t))),
+    };
+    Ok(Some(addr))
+}
+
+/// Builds the command authenticating a connection, sent on a
+/// unidirectional stream.
+pub fn encode_authenticate(uuid: &[u8; 16], token: &[u8; TOKEN_LEN]) -> Bytes {
+    let mut buf = BytesMut::with_capacity(2 + 16 + TOKEN_LEN);
+    buf.put_u8(VERSION);
+    buf.put_u8(COMMAND_AUTHENTICATE);
+    buf.put_slice(uuid);
+    buf.put_slice(token);
+    buf.freeze()
+}
+
+/// Builds the command opening a TCP relay, which is followed by the relayed
+/// data on the same bidirectional stream.
+pub fn encode_connect(destination: &SocksAddr) -> Bytes {
+    let mut buf = BytesMut::with_capacity(2 + address_len(Some(destination)));
+    buf.put_u8(VERSION);
+    buf.put_u8(COMMAND_CONNECT);
+    put_address(&mut buf, Some(destination));
+    buf.freeze()
+}
+
+/// Builds the command telling the server a UDP session is over.
+pub fn encode_dissociate(assoc_id: u16) -> Bytes {
+    let mut buf = BytesMut::with_capacity(4);
+    buf.put_u8(VERSION);
+    buf.put_u8(COMMAND_DISSOCIATE);
+    buf.put_u16(assoc_id);
+    buf.freeze()
+}
+
+/// Builds the heartbeat keeping a connection alive, sent as a datagram.
+pub fn encode_heartbeat() -> Bytes {
+    Bytes::from_static(&[VERSION, COMMAND_HEARTBEAT])
+}
+
+/// A UDP packet, or a fragment of one. Only the first fragment carries the
+/// address.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Packet {
+    pub assoc_id: u16,
+    pub packet_id: u16,
+    pub fragment_count: u8,
+    pub fragment_id: u8,
+    pub address: Option<SocksAddr>,
+    pub payload: Bytes,
+}
+
+impl Packet {
+    pub fn encode(&self) -> Bytes {
+        let mut buf = BytesMut::with_capacity(self.header_len() + self.payload.len());
+        buf.put_u8(VERSION);
+        buf.put_u8(COMMAND_PACKET);
+        buf.put_u16(self.assoc_id);
+        buf.put_u16(self.packet_id);
+        buf.put_u8(self.fragment_count);
+        buf.put_u8(self.fragment_id);
+        buf.put_u16(self.payload.len() as u16);
+        put_address(&mut buf, self.address.as_ref());
+        buf.put_slice(&self.payload);
+        buf.freeze()
+    }
+
+    /// Decodes a packet received as a datagram or on a unidirectional
+    /// stream, returns `None` for other commands.
+    pub fn decode(data: Bytes) -> io::Result<Option<Self>> {
+        if data.len() < 2 {
+            return Err(truncated());
+        }
+        if data[0] != VERSION {
+            return Err(io::Error::other(format!(
+                "unsupported tuic version {}",
+                data[0]
+            )));
+        }
+        if data[1] != COMMAND_PACKET {
+            return Ok(None);
+        }
+        if data.len() < PACKET_HEADER_LEN {
+            return Err(truncated());
+        }
+        let mut rest = &data[2..];
+        let assoc_id = rest.get_u16();
+        let packet_id = rest.get_u16();
+        let fragment_count = rest.get_u8();
+        let fragment_id = rest.get_u8();
+        let size = rest.get_u16() as usize;
+        let address = get_address(&mut rest)?;
+        if rest.len() < size {
+            return Err(truncated());
+        }
+        if fragment_count == 0 || fragment_id >= fragment_count {
+            return Err(io::Error::other("invalid tuic packet fragment"));
+        }
+        let offset = data.len() - rest.len();
+        Ok(Some(Self {
+            assoc_id,
+            packet_id,
+            fragment_count,
+            fragment_id,
+            address,
+            payload: data.slice(offset..offset + size),
+        }))
+    }
+
+    fn header_len(&self) -> usize {
+        PACKET_HEADER_LEN + address_len(self.address.as_ref())
+    }
+
+    /// Splits the packet into fragments of at most `max_size` bytes, or
+    /// returns `None` if even the header doesn't fit.
+    pub fn fragment(self, max_size: usize) -> Option<Vec<Packet>> {
+        if self.header_len() + self.payload.len() <= max_size {
+            return Some(vec![self]);
+        }
+        // The first fragment has the longest header, which bounds the size of
+        // all of them.
+        let chunk = max_size.checked_sub(self.header_len()).filter(|n| *n > 0)?;
+        let count = self.payload.len().div_ceil(chunk);
+        if count > u8::MAX as usize {
+            return None;
+        }
+        Some(
+            (0..count)
+                .map(|i| Packet {
+                    assoc_id: self.assoc_id,
+                    packet_id: self.packet_id,
+                    fragment_count: count as u8,
+                    fragment_id: i as u8,
+                    address: if i == 0 { self.address.clone() } else { None },
+                    payload: self
+                        .payload
+                        .slice(i * chunk..((i + 1) * chunk).min(self.payload.len())),
+                })
+                .collect(),
+        )
+    }
+}
+
+impl Fragment for Packet {
+    fn packet_id(&self) -> u16 {
+        self.packet_id
+    }
+
+    fn fragment_id(&self) -> u8 {
+        self.fragment_id
+    }
+
+    fn fragment_count(&self) -> u8 {
+        self.fragment_count
+    }
+
+    fn payload(&self) -> &Bytes {
+        &self.payload
+    }
+
+    fn into_packet(self, payload: Bytes) -> Self {
+        Packet {
+            fragment_count: 1,
+            fragment_id: 0,
+            payload,
+            ..self
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::common::udp_session::Defragger;
+
+    #[test]
+    fn test_encode_connect() {
+        let dst = SocksAddr::try_from(("example.com", 443)).unwrap();
+        let mut expected = vec![VERSION, COMMAND_CONNECT, ADDR_TYPE_DOMAIN, 11];
+        expected.extend_from_slice(b"example.com");
+        expected.extend_from_slice(&443u16.to_be_bytes());
+        assert_eq!(encode_connect(&dst)[..], expected[..]);
+
+        let dst = SocksAddr::from(("1.2.3.4".parse::<Ipv4Addr>().unwrap(), 53));
+        assert_eq!(
+            encode_connect(&dst)[..],
+            [VERSION, COMMAND_CONNECT, ADDR_TYPE_IPV4, 1, 2, 3, 4, 0, 53]
+        );
+    }
+
+    #[test]
+    fn test_packet() {
+        let packet = Packet {
+            assoc_id: 7,
+            packet_id: 1,
+            fragment_count: 1,
+            fragment_id: 0,
+            address: Some(SocksAddr::from(("::1".parse::<Ipv6Addr>().unwrap(), 53))),
+            payload: Bytes::from_static(b"hello"),
+        };
+        let data = packet.encode();
+        assert_eq!(data.len(), PACKET_HEADER_LEN + 19 + 5);
+        assert_eq!(&data[..4], &[VERSION, COMMAND_PACKET, 0, 7]);
+        assert_eq!(Packet::decode(data).unwrap(), Some(packet));
+
+        assert_eq!(Packet::decode(encode_heartbeat()).unwrap(), None);
+        assert!(Packet::decode(Bytes::from_static(&[4, COMMAND_PACKET])).is_err());
+    }
+
+    #[test]
+    fn test_fragment() {
+        let packet = Packet {
+            assoc_id: 7,
+            packet_id: 3,
+            fragment_count: 1,
+            fragment_id: 0,
+            address: Some(SocksAddr::try_from(("example.com", 53)).unwrap()),
+            payload: Bytes::from((0..100).collect::<Vec<u8>>()),
+        };
+        let fragments = Packet {
+            payload: packet.payload.clone(),
+            address: packet.address.clone(),
+            ..packet
+        }
+        .fragment(60)
+        .unwrap();
+        assert_eq!(fragments.len(), 3);
+        assert!(fragments.iter().all(|f| f.encode().len() <= 60));
+        assert!(fragments[1..].iter().all(|f| f.address.is_none()));
+
+        let mut defragger = Defragger::default();
+        let mut fragments = fragments
+            .into_iter()
+            .map(|f| Packet::decode(f.encode()).unwrap().unwrap())
+            .rev();
+        for fragment in fragments.by_ref().take(2) {
+            assert!(defragger.feed(fragment).is_none());
+        }
+        let reassembled = defragger.feed(fragments.next().unwrap()).unwrap();
+        assert_eq!(reassembled.address, packet.address);
+        assert_eq!(reassembled.payload, packet.payload);
+
+        let header_len = packet.header_len();
+        assert!(packet.fragment(header_len).is_none());
+    }
+}
@@ -132,7 +132,8 @@ async fn handle_inbound_datagram_lwip(
                 SocksAddr::Ip(a) => a,
                 SocksAddr::Domain(domain, port) => {
                     if let Some(fakedns) = &fakedns_cloned {
-                        if let Some(ip) = fakedns.query_fake_ip(&domain).await {
+                        let ipv6 = pkt.dst_addr.must_ip().is_ipv6();
+                        if let Some(ip) = fakedns.query_fake_ip(&domain, ipv6).await {
                             SocketAddr::new(ip, port)
                         } else {
                             warn!(
@@ -244,7 +245,8 @@ async fn handle_inbound_datagram_smoltcp(
                 SocksAddr::Ip(a) => a,
                 SocksAddr::Domain(domain, port) => {
                     if let Some(fakedns) = &fakedns_cloned {
-                        if let Some(ip) = fakedns.query_fake_ip(&domain).await {
+                        let ipv6 = pkt.dst_addr.must_ip().is_ipv6();
+                        if let Some(ip) = fakedns.query_fake_ip(&domain, ipv6).await {
                             SocketAddr::new(ip, port)
                         } else {
                             warn!(
@@ -576,11 +578,15 @@ pub fn new(
         Some(Arc::new(FakeDns::new(
             FakeDnsMode::Include,
             fake_dns_include,
+            dispatcher.dns_client.clone(),
+            crate::app::fake_dns::cache_file(&inbound.tag),
         )))
     } else if !fake_dns_exclude.is_empty() {
         Some(Arc::new(FakeDns::new(
             FakeDnsMode::Exclude,
             fake_dns_exclude,
+            dispatcher.dns_client.clone(),
+            crate::app::fake_dns::cache_file(&inbound.tag),
         )))
     } else {
         None
@@ -0,0 +1,60 @@
+use std::io;
+use std::net::IpAddr;
+
+use crate::session::SocksAddr;
+
+mod datagram;
+mod stream;
+
+pub use datagram::Handler as DatagramHandler;
+pub use stream::Handler as StreamHandler;
+
+/// Parses the configured `host:port` destination, an IPv6 host is enclosed
+/// in brackets.
+pub fn parse_destination(destination: &str) -> io::Result<SocksAddr> {
+    let invalid = || {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("invalid tunnel destination {}", destination),
+        )
+    };
+    let (host, port) = destination.rsplit_once(':').ok_or_else(invalid)?;
+    let port = port.parse::<u16>().map_err(|_| invalid())?;
+    let host = host
+        .strip_prefix('[')
+        .and_then(|h| h.strip_suffix(']'))
+        .unwrap_or(host);
+    if let Ok(ip) = host.parse::<IpAddr>() {
+        return Ok(SocksAddr::from((ip, port)));
+    }
+    if host.is_empty() || host.contains(':') {
+        return Err(invalid());
+    }
+    SocksAddr::try_from((host, port)).map_err(|_| invalid())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_destination() {
+        assert_eq!(
+            parse_destination("1.1.1.1:53").unwrap().to_string(),
+            "1.1.1.1:53"
+        );
+        assert_eq!(
+            parse_destination("[2606:4700:4700::1111]:53")
+                .unwrap()
+                .to_string(),
+            "[2606:4700:4700::1111]:53"
+        );
+        assert_eq!(
+            parse_destination("dns.google:853").unwrap(),
+            SocksAddr::Domain("dns.google".to_string(), 853)
+        );
+        assert!(parse_destination("1.1.1.1").is_err());
+        assert!(parse_destination(":53").is_err());
+        assert!(parse_destination("2606:4700::1111:53").is_err());
+    }
+}
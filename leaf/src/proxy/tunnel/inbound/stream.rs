@@ -0,0 +1,28 @@
+use std::io;
+
+use async_trait::async_trait;
+
+use crate::{proxy::*, session::*};
+
+/// Forwards every connection to the configured destination.
+pub struct Handler {
+    destination: SocksAddr,
+}
+
+impl Handler {
+    pub fn new(destination: SocksAddr) -> Self {
+        Self { destination }
+    }
+}
+
+#[async_trait]
+impl InboundStreamHandler for Handler {
+    async fn handle<'a>(
+        &'a self,
+        mut sess: Session,
+        stream: AnyStream,
+    ) -> io::Result<AnyInboundTransport> {
+        sess.destination = self.destination.clone();
+        Ok(InboundTransport::Stream(stream, sess))
+    }
+}
@@ -0,0 +1,2 @@
+#[cfg(feature = "inbound-tunnel")]
+pub mod inbound;
@@ -0,0 +1,45 @@
+use std::{io, sync::Arc};
+
+use async_trait::async_trait;
+
+use crate::{app::SyncDnsClient, proxy::*, session::Session};
+
+use super::Status;
+
+pub struct Handler {
+    pub actors: Vec<AnyOutboundHandler>,
+    pub status: Arc<Status>,
+    pub dns_client: SyncDnsClient,
+}
+
+#[async_trait]
+impl OutboundDatagramHandler for Handler {
+    fn connect_addr(&self) -> OutboundConnect {
+        // The member may change before the session is handled.
+        OutboundConnect::Unknown
+    }
+
+    fn transport_type(&self) -> DatagramTransportType {
+        DatagramTransportType::Unknown
+    }
+
+    async fn handle<'a>(
+        &'a self,
+        sess: &'a Session,
+        _transport: Option<AnyOutboundTransport>,
+    ) -> io::Result<AnyOutboundDatagram> {
+        tracing::trace!("handling outbound datagram");
+        let a = &self.actors[self.status.selected()];
+        tracing::debug!(
+            "url test handles udp [{}] to [{}]",
+            sess.destination,
+            a.tag()
+        );
+        a.datagram()?
+            .handle(
+                sess,
+                connect_datagram_outbound(sess, self.dns_client.clone(), a).await?,
+            )
+            .await
+    }
+}
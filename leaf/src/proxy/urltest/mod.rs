@@ -0,0 +1,158 @@
+//! A group which routes new sessions to the member with the lowest latency,
+//! measured periodically by health checks of the failover group through each
+//! member.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use futures::future::{abortable, AbortHandle};
+use tracing::{debug, info};
+
+use crate::{app::SyncDnsClient, proxy::*};
+
+use super::failover::{self, HealthCheckTarget};
+
+pub mod datagram;
+pub mod stream;
+
+pub use datagram::Handler as DatagramHandler;
+pub use stream::Handler as StreamHandler;
+
+// The time a check of a member has to complete.
+const CHECK_TIMEOUT: Duration = Duration::from_secs(6);
+
+/// The selected member of a group and the latencies last measured.
+pub struct Status {
+    tags: Vec<String>,
+    selected: AtomicUsize,
+    latencies: Mutex<Vec<Option<Duration>>>,
+}
+
+impl Status {
+    fn new(tags: Vec<String>) -> Self {
+        Status {
+            latencies: Mutex::new(vec![None; tags.len()]),
+            tags,
+            selected: AtomicUsize::new(0),
+        }
+    }
+
+    pub fn selected(&self) -> usize {
+        self.selected.load(Ordering::Relaxed)
+    }
+
+    pub fn selected_tag(&self) -> String {
+        self.tags[self.selected()].clone()
+    }
+
+    /// The tag of each member with the latency last measured, none if the
+    /// check failed or hasn't run yet.
+    pub fn latencies(&self) -> Vec<(String, Option<Duration>)> {
+        let latencies = self.latencies.lock().unwrap();
+        self.tags
+            .iter()
+            .cloned()
+            .zip(latencies.iter().copied())
+            .collect()
+    }
+}
+
+// Returns the member to select. The current one is kept unless another one
+// is faster by more than the tolerance, or it failed.
+fn select(current: usize, latencies: &[Option<Duration>], tolerance: Duration) -> usize {
+    let Some((fastest, min)) = latencies
+        .iter()
+        .enumerate()
+        .filter_map(|(i, x)| x.map(|x| (i, x)))
+        .min_by_key(|(_, x)| *x)
+    else {
+        return current;
+    };
+    match latencies.get(current).copied().flatten() {
+        Some(latency) if latency <= min + tolerance => current,
+        _ => fastest,
+    }
+}
+
+async fn check_task(
+    actors: Vec<AnyOutboundHandler>,
+    status: Arc<Status>,
+    target: HealthCheckTarget,
+    interval: Duration,
+    tolerance: Duration,
+    dns_client: SyncDnsClient,
+) {
+    loop {
+        let checks = actors.iter().map(|a| {
+            failover::tcp_latency(a.clone(), target.clone(), dns_client.clone(), CHECK_TIMEOUT)
+        });
+        let latencies = futures::future::join_all(checks).await;
+        for (a, latency) in actors.iter().zip(latencies.iter()) {
+            match latency {
+                Some(latency) => debug!("[{}] latency {} ms", a.tag(), latency.as_millis()),
+                None => debug!("[{}] latency check failed", a.tag()),
+            }
+        }
+        let current = status.selected();
+        let selected = select(current, &latencies, tolerance);
+        if selected != current {
+            info!(
+                "url test selected [{}] ({} ms) over [{}]",
+                actors[selected].tag(),
+                latencies[selected].unwrap_or_default().as_millis(),
+                actors[current].tag(),
+            );
+            status.selected.store(selected, Ordering::Relaxed);
+        }
+        *status.latencies.lock().unwrap() = latencies;
+        tokio::time::sleep(interval).await;
+    }
+}
+
+/// Starts measuring the members, the first one is selected until the
+/// first checks are done. Returns the status of the group and the handle to
+/// stop the checks.
+pub fn start(
+    actors: Vec<AnyOutboundHandler>,
+    target: HealthCheckTarget,
+    interval: Duration,
+    tolerance: Duration,
+    dns_client: SyncDnsClient,
+) -> (Arc<Status>, AbortHandle) {
+    let status = Arc::new(Status::new(
+        actors.iter().map(|x| x.tag().to_owned()).collect(),
+    ));
+    let (task, abort_handle) = abortable(check_task(
+        actors,
+        status.clone(),
+        target,
+        interval,
+        tolerance,
+        dns_client,
+    ));
+    tokio::spawn(task);
+    (status, abort_handle)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(x: u64) -> Option<Duration> {
+        Some(Duration::from_millis(x))
+    }
+
+    #[test]
+    fn test_select() {
+        let tolerance = Duration::from_millis(50);
+        assert_eq!(select(0, &[ms(100), ms(60)], tolerance), 0);
+        assert_eq!(select(0, &[ms(100), ms(40)], tolerance), 1);
+        assert_eq!(select(1, &[ms(100), ms(140)], tolerance), 1);
+        // A failed member is left for any other.
+        assert_eq!(select(0, &[None, ms(500)], tolerance), 1);
+        // Nothing to go to.
+        assert_eq!(select(1, &[None, None], tolerance), 1);
+        assert_eq!(select(0, &[ms(100), ms(97)], Duration::ZERO), 1);
+    }
+}
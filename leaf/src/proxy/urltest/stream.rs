@@ -0,0 +1,43 @@
+use std::{io, sync::Arc};
+
+use async_trait::async_trait;
+
+use crate::{app::SyncDnsClient, proxy::*, session::Session};
+
+use super::Status;
+
+pub struct Handler {
+    pub actors: Vec<AnyOutboundHandler>,
+    pub status: Arc<Status>,
+    pub dns_client: SyncDnsClient,
+}
+
+#[async_trait]
+impl OutboundStreamHandler for Handler {
+    fn connect_addr(&self) -> OutboundConnect {
+        // The member may change before the session is handled.
+        OutboundConnect::Unknown
+    }
+
+    async fn handle<'a>(
+        &'a self,
+        sess: &'a Session,
+        lhs: Option<&mut AnyStream>,
+        _stream: Option<AnyStream>,
+    ) -> io::Result<AnyStream> {
+        tracing::trace!("handling outbound stream");
+        let a = &self.actors[self.status.selected()];
+        tracing::debug!(
+            "url test handles tcp [{}] to [{}]",
+            sess.destination,
+            a.tag()
+        );
+        a.stream()?
+            .handle(
+                sess,
+                lhs,
+                connect_stream_outbound(sess, self.dns_client.clone(), a).await?,
+            )
+            .await
+    }
+}
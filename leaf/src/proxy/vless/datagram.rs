@@ -1,37 +1,10 @@
-pub fn build_vless_udp_header(
-    uuid_bytes: &[u8; 16],
-    dst_addr: &str,
-    dst_port: u16,
-    addr_type: u8,
-) -> Vec<u8> {
-    let mut header = vec![];
-    header.push(0x00); // VLESS version
-    header.extend_from_slice(uuid_bytes);
-    header.push(0x00); // Addons length = 0 (no flow for UDP)
-    header.push(0x02); // Command: UDP
-    header.push((dst_port >> 8) as u8); // Port high byte
-    header.push((dst_port & 0xFF) as u8); // Port low byte
-    header.push(addr_type);
-    match addr_type {
-        1 => {
-            let parts: Vec<u8> = dst_addr
-                .split('.')
-                .map(|s| s.parse().unwrap_or(0))
-                .collect();
-            header.extend_from_slice(&parts);
-        }
-        2 => {
-            header.push(dst_addr.len() as u8);
-            header.extend_from_slice(dst_addr.as_bytes());
-        }
-        3 => {
-            if let Ok(addr6) = dst_addr.parse::<std::net::Ipv6Addr>() {
-                header.extend_from_slice(&addr6.octets());
-            }
-        }
-        _ => {}
-    }
-    header
+use crate::session::SocksAddr;
+
+use super::protocol::{build_request_header, COMMAND_UDP};
+
+pub fn build_vless_udp_header(uuid_bytes: &[u8; 16], destination: &SocksAddr) -> Vec<u8> {
+    // UDP goes without flow.
+    build_request_header(uuid_bytes, COMMAND_UDP, destination, None)
 }
 
 pub struct VlessUdpParser {
@@ -0,0 +1,364 @@
+use std::collections::HashSet;
+use std::io;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use futures::{ready, TryFutureExt};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, ReadBuf};
+use tracing::{debug, trace};
+use uuid::Uuid;
+
+use crate::{
+    proxy::*,
+    session::{DatagramSource, Network, Session, SocksAddr},
+};
+
+use super::super::protocol::{read_request_header, COMMAND_TCP, COMMAND_UDP, VERSION};
+
+// Version and empty addons.
+const RESPONSE_HEADER: [u8; 2] = [VERSION, 0x00];
+
+// Prefixes the first data written with the response header.
+struct VlessInboundStream {
+    inner: AnyStream,
+    header: Vec<u8>,
+}
+
+impl VlessInboundStream {
+    fn new(inner: AnyStream) -> Self {
+        Self {
+            inner,
+            header: RESPONSE_HEADER.to_vec(),
+        }
+    }
+
+    fn poll_write_header(&mut self, cx: &mut Context) -> Poll<io::Result<()>> {
+        while !self.header.is_empty() {
+            let n = ready!(Pin::new(&mut self.inner).poll_write(cx, &self.header))?;
+            if n == 0 {
+                return Poll::Ready(Err(io::ErrorKind::WriteZero.into()));
+            }
+            self.header.drain(..n);
+        }
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl AsyncRead for VlessInboundStream {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context,
+        buf: &mut ReadBuf,
+    ) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_read(cx, buf)
+    }
+}
+
+impl AsyncWrite for VlessInboundStream {
+    fn poll_write(self: Pin<&mut Self>, cx: &mut Context, buf: &[u8]) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        if !this.header.is_empty() {
+            // Sent together with the data so it doesn't stand out as a
+            // record of its own.
+            let mut data = this.header.clone();
+            data.extend_from_slice(buf);
+            let n = ready!(Pin::new(&mut this.inner).poll_write(cx, &data))?;
+            if n < this.header.len() {
+                this.header.drain(..n);
+                ready!(this.poll_write_header(cx))?;
+                return Pin::new(&mut this.inner).poll_write(cx, buf);
+            }
+            let n = n - this.header.len();
+            this.header.clear();
+            if n > 0 || buf.is_empty() {
+                return Poll::Ready(Ok(n));
+            }
+        }
+        Pin::new(&mut this.inner).poll_write(cx, buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        ready!(this.poll_write_header(cx))?;
+        Pin::new(&mut this.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        ready!(this.poll_write_header(cx))?;
+        Pin::new(&mut this.inner).poll_shutdown(cx)
+    }
+}
+
+// UDP packets are framed with a 2-byte length and all go to the destination
+// of the request.
+struct Datagram {
+    stream: AnyStream,
+    source: DatagramSource,
+    destination: SocksAddr,
+}
+
+impl InboundDatagram for Datagram {
+    fn split(
+        self: Box<Self>,
+    ) -> (
+        Box<dyn InboundDatagramRecvHalf>,
+        Box<dyn InboundDatagramSendHalf>,
+    ) {
+        let (r, s) = tokio::io::split(self.stream);
+        (
+            Box::new(DatagramRecvHalf(r, self.source, self.destination)),
+            Box::new(DatagramSendHalf(s, RESPONSE_HEADER.to_vec())),
+        )
+    }
+
+    fn into_std(self: Box<Self>) -> io::Result<std::net::UdpSocket> {
+        Err(io::Error::other("stream transport"))
+    }
+}
+
+struct DatagramRecvHalf<T>(T, DatagramSource, SocksAddr);
+
+#[async_trait]
+impl<T> InboundDatagramRecvHalf for DatagramRecvHalf<T>
+where
+    T: AsyncRead + Send + Sync + Unpin,
+{
+    async fn recv_from(
+        &mut self,
+        buf: &mut [u8],
+    ) -> ProxyResult<(usize, DatagramSource, SocksAddr)> {
+        let payload_len = self
+            .0
+            .read_u16()
+            .map_err(|e| ProxyError::DatagramFatal(e.into()))
+            .await? as usize;
+        if buf.len() < payload_len {
+            return Err(ProxyError::DatagramFatal(anyhow!("Small buffer")));
+        }
+        self.0
+            .read_exact(&mut buf[..payload_len])
+            .map_err(|e| ProxyError::DatagramFatal(e.into()))
+            .await?;
+        trace!(
+            "vless inbound received UDP {} bytes for {}",
+            payload_len,
+            &self.2
+        );
+        Ok((payload_len, self.1.clone(), self.2.clone()))
+    }
+}
+
+// The second field holds the response header until the first packet is sent.
+struct DatagramSendHalf<T>(T, Vec<u8>);
+
+#[async_trait]
+impl<T> InboundDatagramSendHalf for DatagramSendHalf<T>
+where
+    T: AsyncWrite + Send + Sync + Unpin,
+{
+    async fn send_to(
+        &mut self,
+        buf: &[u8],
+        src_addr: &SocksAddr,
+        _dst_addr: &SocketAddr,
+    ) -> io::Result<usize> {
+        trace!(
+            "vless inbound send UDP {} bytes for {}",
+            buf.len(),
+            &src_addr
+        );
+        let mut data = std::mem::take(&mut self.1);
+        data.extend_from_slice(&(buf.len() as u16).to_be_bytes());
+        data.extend_from_slice(buf);
+        self.0.write_all(&data).map_ok(|_| buf.len()).await
+    }
+
+    async fn close(&mut self) -> io::Result<()> {
+        self.0.shutdown().await
+    }
+}
+
+pub struct Handler {
+    uuids: HashSet<[u8; 16]>,
+    fallback: Option<String>,
+}
+
+impl Handler {
+    pub fn new(uuids: Vec<String>, fallback: Option<String>) -> Result<Self> {
+        let mut set = HashSet::new();
+        for uuid in uuids {
+            let u = Uuid::parse_str(&uuid).map_err(|e| anyhow!("invalid uuid {}: {}", uuid, e))?;
+            set.insert(*u.as_bytes());
+        }
+        Ok(Handler {
+            uuids: set,
+            fallback,
+        })
+    }
+
+    // Relays a rejected connection, the bytes already read included, to the
+    // fallback address so that the server looks like whatever runs there.
+    fn relay_fallback(
+        &self,
+        mut stream: AnyStream,
+        consumed: Vec<u8>,
+        reason: io::Error,
+    ) -> io::Result<AnyInboundTransport> {
+        let Some(fallback) = self.fallback.clone() else {
+            return Err(reason);
+        };
+        debug!("vless inbound falls back to {}: {}", &fallback, reason);
+        tokio::spawn(async move {
+            match tokio::net::TcpStream::connect(&fallback).await {
+                Ok(mut target) => {
+                    let res = async {
+                        target.write_all(&consumed).await?;
+                        tokio::io::copy_bidirectional(&mut stream, &mut target).await
+                    };
+                    if let Err(e) = res.await {
+                        debug!("vless fallback to {} failed: {}", &fallback, e);
+                    }
+                }
+                Err(e) => {
+                    debug!("connect vless fallback {} failed: {}", &fallback, e);
+                }
+            }
+        });
+        Ok(InboundTransport::Empty)
+    }
+}
+
+#[async_trait]
+impl InboundStreamHandler for Handler {
+    async fn handle<'a>(
+        &'a self,
+        mut sess: Session,
+        mut stream: AnyStream,
+    ) -> std::io::Result<AnyInboundTransport> {
+        tracing::trace!("handling inbound stream");
+        let mut consumed = Vec::new();
+        let header = match read_request_header(&mut stream, &mut consumed).await {
+            Ok(header) => header,
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Err(e),
+            Err(e) => return self.relay_fallback(stream, consumed, e),
+        };
+        if !self.uuids.contains(&header.uuid) {
+            return self.relay_fallback(stream, consumed, io::Error::other("invalid user"));
+        }
+        if let Some(flow) = header.flow.filter(|flow| !flow.is_empty()) {
+            return self.relay_fallback(
+                stream,
+                consumed,
+                io::Error::other(format!("unsupported flow {}", flow)),
+            );
+        }
+        sess.destination = header.destination;
+        match header.command {
+            COMMAND_TCP => Ok(InboundTransport::Stream(
+                Box::new(VlessInboundStream::new(stream)),
+                sess,
+            )),
+            COMMAND_UDP => {
+                sess.network = Network::Udp;
+                Ok(InboundTransport::Datagram(
+                    Box::new(Datagram {
+                        stream,
+                        source: DatagramSource::new(sess.source, sess.stream_id),
+                        destination: sess.destination.clone(),
+                    }),
+                    Some(sess),
+                ))
+            }
+            command => self.relay_fallback(
+                stream,
+                consumed,
+                io::Error::other(format!("invalid command {}", command)),
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::io::duplex;
+    use tokio::net::TcpListener;
+
+    use super::super::super::protocol::build_request_header;
+    use super::*;
+
+    const UUID: &str = "b831381d-6324-4d53-ad4f-8cda48b30811";
+
+    fn request(uuid: &str, command: u8) -> Vec<u8> {
+        let uuid = *Uuid::parse_str(uuid).unwrap().as_bytes();
+        let destination = SocksAddr::try_from(("example.com", 80)).unwrap();
+        build_request_header(&uuid, command, &destination, None)
+    }
+
+    #[tokio::test]
+    async fn test_handle_tcp() {
+        let handler = Handler::new(vec![UUID.to_string()], None).unwrap();
+        let (mut client, server) = duplex(1024);
+        client.write_all(&request(UUID, COMMAND_TCP)).await.unwrap();
+        client.write_all(b"ping").await.unwrap();
+        let transport = handler
+            .handle(Session::default(), Box::new(server))
+            .await
+            .unwrap();
+        let InboundTransport::Stream(mut stream, sess) = transport else {
+            panic!("expected a stream transport");
+        };
+        assert_eq!(sess.destination.to_string(), "example.com:80");
+        let mut buf = [0u8; 4];
+        stream.read_exact(&mut buf).await.unwrap();
+        assert_eq!(&buf, b"ping");
+        stream.write_all(b"pong").await.unwrap();
+        let mut buf = [0u8; 6];
+        client.read_exact(&mut buf).await.unwrap();
+        assert_eq!(&buf, b"\x00\x00pong");
+    }
+
+    #[tokio::test]
+    async fn test_handle_invalid_user() {
+        let handler = Handler::new(vec![UUID.to_string()], None).unwrap();
+        let (mut client, server) = duplex(1024);
+        let other = "0a7f1ec5-5f87-4b5e-9a6f-2a3c0f4d1e11";
+        client
+            .write_all(&request(other, COMMAND_TCP))
+            .await
+            .unwrap();
+        assert!(handler
+            .handle(Session::default(), Box::new(server))
+            .await
+            .is_err());
+
+        assert!(Handler::new(vec!["not-a-uuid".to_string()], None).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_fallback() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let fallback = listener.local_addr().unwrap().to_string();
+        let handler = Handler::new(vec![UUID.to_string()], Some(fallback)).unwrap();
+        let (mut client, server) = duplex(1024);
+        let probe = b"GET / HTTP/1.1\r\nHost: example.com\r\n\r\n";
+        client.write_all(probe).await.unwrap();
+        let transport = handler
+            .handle(Session::default(), Box::new(server))
+            .await
+            .unwrap();
+        assert!(matches!(transport, InboundTransport::Empty));
+
+        let (mut target, _) = listener.accept().await.unwrap();
+        let mut buf = vec![0u8; probe.len()];
+        target.read_exact(&mut buf).await.unwrap();
+        assert_eq!(&buf, probe);
+        target.write_all(b"HTTP/1.1 200 OK\r\n").await.unwrap();
+        let mut buf = [0u8; 17];
+        client.read_exact(&mut buf).await.unwrap();
+        assert_eq!(&buf, b"HTTP/1.1 200 OK\r\n");
+    }
+}
@@ -1,8 +1,11 @@
 pub mod datagram;
+pub mod protocol;
 pub mod stream;
 
 pub use datagram::{build_vless_udp_header, VlessDatagram};
 pub use stream::{build_vless_tcp_header, VlessStream};
 
+#[cfg(feature = "inbound-vless")]
+pub mod inbound;
 #[cfg(feature = "outbound-vless")]
 pub mod outbound;
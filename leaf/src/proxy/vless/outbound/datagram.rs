@@ -34,20 +34,7 @@ impl OutboundDatagramHandler for Handler {
             .map_err(|e| io::Error::other(format!("parse uuid failed: {}", e)))?;
         let uuid_bytes = *u.as_bytes();
 
-        let addr_type = match sess.destination.ip() {
-            Some(ip) => {
-                if ip.is_ipv4() {
-                    1
-                } else {
-                    3
-                }
-            }
-            None => 2,
-        };
-        let host = sess.destination.host();
-        let port = sess.destination.port();
-
-        let header = build_vless_udp_header(&uuid_bytes, &host, port, addr_type);
+        let header = build_vless_udp_header(&uuid_bytes, &sess.destination);
 
         let stream = if let Some(OutboundTransport::Stream(stream)) = transport {
             stream
@@ -4,6 +4,7 @@ use async_trait::async_trait;
 use tokio::io::AsyncWriteExt;
 use uuid::Uuid;
 
+use super::super::protocol::FLOW_VISION;
 use super::super::stream::{build_vless_tcp_header, VlessStream};
 use crate::{proxy::*, session::*};
 
@@ -11,6 +12,7 @@ pub struct Handler {
     pub address: String,
     pub port: u16,
     pub uuid: String,
+    pub flow: Option<String>,
 }
 
 #[async_trait]
@@ -30,28 +32,19 @@ impl OutboundStreamHandler for Handler {
             .map_err(|e| io::Error::other(format!("parse uuid failed: {}", e)))?;
         let uuid_bytes = *u.as_bytes();
 
-        let addr_type = match sess.destination.ip() {
-            Some(ip) => {
-                if ip.is_ipv4() {
-                    1
-                } else {
-                    3
-                }
-            }
-            None => 2,
-        };
-        let host = sess.destination.host();
-        let port = sess.destination.port();
-
-        let header = build_vless_tcp_header(&uuid_bytes, &host, port, addr_type);
+        let header = build_vless_tcp_header(&uuid_bytes, &sess.destination, self.flow.as_deref());
 
         let mut stream = stream.ok_or_else(|| io::Error::other("invalid input"))?;
         stream.write_all(&header).await?;
 
-        Ok(Box::new(VlessStream::new(
-            stream,
-            uuid_bytes,
-            Some(sess.vision_read_raw.clone()),
-        )))
+        if self.flow.as_deref() == Some(FLOW_VISION) {
+            Ok(Box::new(VlessStream::new(
+                stream,
+                uuid_bytes,
+                Some(sess.vision_read_raw.clone()),
+            )))
+        } else {
+            Ok(Box::new(VlessStream::plain(stream)))
+        }
     }
 }
@@ -0,0 +1,250 @@
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+use crate::session::SocksAddr;
+
+pub const VERSION: u8 = 0x00;
+pub const COMMAND_TCP: u8 = 0x01;
+pub const COMMAND_UDP: u8 = 0x02;
+
+pub const FLOW_VISION: &str = "xtls-rprx-vision";
+
+const ADDR_TYPE_IPV4: u8 = 0x01;
+const ADDR_TYPE_DOMAIN: u8 = 0x02;
+const ADDR_TYPE_IPV6: u8 = 0x03;
+
+// The addons are a protobuf message, of which only the flow, field 1, is
+// sent.
+fn encode_addons(flow: Option<&str>) -> Vec<u8> {
+    match flow {
+        Some(flow) if !flow.is_empty() => {
+            let mut addons = vec![0x0a, flow.len() as u8];
+            addons.extend_from_slice(flow.as_bytes());
+            addons
+        }
+        _ => Vec::new(),
+    }
+}
+
+/// Builds a request header: version, UUID, addons, command and destination,
+/// the port coming before the address.
+pub fn build_request_header(
+    uuid_bytes: &[u8; 16],
+    command: u8,
+    destination: &SocksAddr,
+    flow: Option<&str>,
+) -> Vec<u8> {
+    let mut header = vec![VERSION];
+    header.extend_from_slice(uuid_bytes);
+    let addons = encode_addons(flow);
+    header.push(addons.len() as u8);
+    header.extend_from_slice(&addons);
+    header.push(command);
+    header.extend_from_slice(&destination.port().to_be_bytes());
+    match destination {
+        SocksAddr::Ip(addr) => match addr.ip() {
+            std::net::IpAddr::V4(ip) => {
+                header.push(ADDR_TYPE_IPV4);
+                header.extend_from_slice(&ip.octets());
+            }
+            std::net::IpAddr::V6(ip) => {
+                header.push(ADDR_TYPE_IPV6);
+                header.extend_from_slice(&ip.octets());
+            }
+        },
+        SocksAddr::Domain(domain, _) => {
+            header.push(ADDR_TYPE_DOMAIN);
+            header.push(domain.len() as u8);
+            header.extend_from_slice(domain.as_bytes());
+        }
+    }
+    header
+}
+
+/// A request header as read by the server.
+pub struct RequestHeader {
+    pub uuid: [u8; 16],
+    pub flow: Option<String>,
+    pub command: u8,
+    pub destination: SocksAddr,
+}
+
+fn decode_flow(addons: &[u8]) -> Option<String> {
+    match addons {
+        [0x0a, len, flow @ ..] if flow.len() >= *len as usize => {
+            Some(String::from_utf8_lossy(&flow[..*len as usize]).into_owned())
+        }
+        _ => None,
+    }
+}
+
+async fn read_exact<'a, R: AsyncRead + Unpin>(
+    r: &mut R,
+    consumed: &'a mut Vec<u8>,
+    n: usize,
+) -> io::Result<&'a [u8]> {
+    let start = consumed.len();
+    consumed.resize(start + n, 0);
+    if let Err(e) = r.read_exact(&mut consumed[start..]).await {
+        consumed.truncate(start);
+        return Err(e);
+    }
+    Ok(&consumed[start..])
+}
+
+/// Reads a request header, appending the bytes read to `consumed` so that
+/// they can be replayed if the request is rejected.
+pub async fn read_request_header<R: AsyncRead + Unpin>(
+    r: &mut R,
+    consumed: &mut Vec<u8>,
+) -> io::Result<RequestHeader> {
+    let buf = read_exact(r, consumed, 18).await?;
+    if buf[0] != VERSION {
+        return Err(io::Error::other(format!(
+            "unexpected vless request version {}",
+            buf[0]
+        )));
+    }
+    let uuid: [u8; 16] = buf[1..17].try_into().unwrap();
+    let addons_len = buf[17] as usize;
+    let flow = decode_flow(read_exact(r, consumed, addons_len).await?);
+    let buf = read_exact(r, consumed, 4).await?;
+    let command = buf[0];
+    let port = u16::from_be_bytes([buf[1], buf[2]]);
+    let destination = match buf[3] {
+        ADDR_TYPE_IPV4 => {
+            let buf: [u8; 4] = read_exact(r, consumed, 4).await?.try_into().unwrap();
+            SocksAddr::from((IpAddr::V4(Ipv4Addr::from(buf)), port))
+        }
+        ADDR_TYPE_DOMAIN => {
+            let len = read_exact(r, consumed, 1).await?[0] as usize;
+            let domain = std::str::from_utf8(read_exact(r, consumed, len).await?)
+                .map_err(|_| io::Error::other("invalid vless request domain"))?;
+            SocksAddr::try_from((domain, port))?
+        }
+        ADDR_TYPE_IPV6 => {
+            let buf: [u8; 16] = read_exact(r, consumed, 16).await?.try_into().unwrap();
+            SocksAddr::from((IpAddr::V6(Ipv6Addr::from(buf)), port))
+        }
+        atyp => {
+            return Err(io::Error::other(format!(
+                "unknown vless request address type {}",
+                atyp
+            )));
+        }
+    };
+    Ok(RequestHeader {
+        uuid,
+        flow,
+        command,
+        destination,
+    })
+}
+
+/// Strips the response header, the version and the addons, off the start of
+/// the data received from the server.
+#[derive(Default)]
+pub struct ResponseHeader {
+    buffer: Vec<u8>,
+    done: bool,
+}
+
+impl ResponseHeader {
+    /// Returns the payload following the header, empty while the header is
+    /// incomplete.
+    pub fn strip(&mut self, data: &[u8]) -> io::Result<Vec<u8>> {
+        if self.done {
+            return Ok(data.to_vec());
+        }
+        self.buffer.extend_from_slice(data);
+        if self.buffer.len() < 2 {
+            return Ok(Vec::new());
+        }
+        if self.buffer[0] != VERSION {
+            return Err(io::Error::other(format!(
+                "unexpected vless response version {}",
+                self.buffer[0]
+            )));
+        }
+        let header_len = 2 + self.buffer[1] as usize;
+        if self.buffer.len() < header_len {
+            return Ok(Vec::new());
+        }
+        self.done = true;
+        Ok(self.buffer.split_off(header_len))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build_request_header() {
+        let uuid = [7u8; 16];
+        let destination = SocksAddr::try_from(("example.com", 443)).unwrap();
+        let header = build_request_header(&uuid, COMMAND_TCP, &destination, None);
+        let mut expected = vec![0x00];
+        expected.extend_from_slice(&uuid);
+        expected.extend_from_slice(&[0x00, 0x01, 0x01, 0xbb, 0x02, 11]);
+        expected.extend_from_slice(b"example.com");
+        assert_eq!(header, expected);
+
+        let destination = SocksAddr::from(("::1".parse::<std::net::IpAddr>().unwrap(), 53));
+        let header = build_request_header(&uuid, COMMAND_UDP, &destination, Some(FLOW_VISION));
+        assert_eq!(header[17], 18);
+        assert_eq!(&header[18..20], &[0x0a, 16]);
+        assert_eq!(&header[20..36], FLOW_VISION.as_bytes());
+        assert_eq!(&header[36..40], &[0x02, 0x00, 0x35, 0x03]);
+        assert_eq!(header[40..].len(), 16);
+    }
+
+    #[test]
+    fn test_response_header() {
+        let mut response = ResponseHeader::default();
+        assert!(response.strip(&[0x00]).unwrap().is_empty());
+        assert!(response.strip(&[0x02, 0xaa]).unwrap().is_empty());
+        assert_eq!(response.strip(&[0xbb, b'h', b'i']).unwrap(), b"hi");
+        assert_eq!(response.strip(b"!").unwrap(), b"!");
+
+        assert!(ResponseHeader::default().strip(&[0x01, 0x00]).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_read_request_header() {
+        let uuid = [7u8; 16];
+        let destination = SocksAddr::try_from(("example.com", 443)).unwrap();
+        let mut data = build_request_header(&uuid, COMMAND_TCP, &destination, Some(FLOW_VISION));
+        let header_len = data.len();
+        data.extend_from_slice(b"payload");
+
+        let mut r = &data[..];
+        let mut consumed = Vec::new();
+        let header = read_request_header(&mut r, &mut consumed).await.unwrap();
+        assert_eq!(header.uuid, uuid);
+        assert_eq!(header.flow.as_deref(), Some(FLOW_VISION));
+        assert_eq!(header.command, COMMAND_TCP);
+        assert_eq!(header.destination, destination);
+        assert_eq!(consumed, &data[..header_len]);
+        assert_eq!(r, b"payload");
+
+        let destination = SocksAddr::from(("::1".parse::<IpAddr>().unwrap(), 53));
+        let data = build_request_header(&uuid, COMMAND_UDP, &destination, None);
+        let header = read_request_header(&mut &data[..], &mut Vec::new())
+            .await
+            .unwrap();
+        assert!(header.flow.is_none());
+        assert_eq!(header.command, COMMAND_UDP);
+        assert_eq!(header.destination, destination);
+
+        // Everything read before the failure is kept for a fallback.
+        let data = b"GET / HTTP/1.1\r\n\r\n";
+        let mut consumed = Vec::new();
+        assert!(read_request_header(&mut &data[..], &mut consumed)
+            .await
+            .is_err());
+        assert_eq!(consumed, &data[..18]);
+    }
+}
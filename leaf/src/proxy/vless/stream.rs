@@ -1,41 +1,13 @@
+use crate::session::SocksAddr;
+
+use super::protocol::{build_request_header, ResponseHeader, COMMAND_TCP};
+
 pub fn build_vless_tcp_header(
     uuid_bytes: &[u8; 16],
-    dst_addr: &str,
-    dst_port: u16,
-    addr_type: u8,
+    destination: &SocksAddr,
+    flow: Option<&str>,
 ) -> Vec<u8> {
-    let mut vless_header = vec![];
-    vless_header.push(0x00); // Version
-    vless_header.extend_from_slice(uuid_bytes);
-
-    // TCP - Use Vision Flow
-    let flow_str = b"xtls-rprx-vision";
-    vless_header.push(18); // Extensions length
-    vless_header.push(0x0a); // Protobuf Field 1, Type Length-Delimited
-    vless_header.push(16); // String length
-    vless_header.extend_from_slice(flow_str);
-    vless_header.push(0x01); // Command: TCP
-
-    vless_header.push((dst_port >> 8) as u8);
-    vless_header.push((dst_port & 0xFF) as u8);
-    vless_header.push(addr_type);
-
-    match addr_type {
-        1 => {
-            let parts: Vec<u8> = dst_addr.split('.').map(|s| s.parse().unwrap()).collect();
-            vless_header.extend_from_slice(&parts);
-        }
-        2 => {
-            vless_header.push(dst_addr.len() as u8);
-            vless_header.extend_from_slice(dst_addr.as_bytes());
-        }
-        3 => {
-            let addr: std::net::Ipv6Addr = dst_addr.parse().unwrap();
-            vless_header.extend_from_slice(&addr.octets());
-        }
-        _ => unreachable!(),
-    }
-    vless_header
+    build_request_header(uuid_bytes, COMMAND_TCP, destination, flow)
 }
 
 pub struct VisionParser {
@@ -196,7 +168,10 @@ use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
 
 pub struct VlessStream<S> {
     stream: S,
-    vision_parser: VisionParser,
+    // None without the vision flow, the response header is then stripped
+    // with response_header.
+    vision_parser: Option<VisionParser>,
+    response_header: ResponseHeader,
     plaintext_buffer: Vec<u8>,
     is_direct_copy: bool,
     shared_read_raw: Option<Arc<AtomicBool>>,
@@ -206,13 +181,26 @@ impl<S: AsyncRead + AsyncWrite + Unpin> VlessStream<S> {
     pub fn new(stream: S, uuid_bytes: [u8; 16], shared_read_raw: Option<Arc<AtomicBool>>) -> Self {
         Self {
             stream,
-            vision_parser: VisionParser::new(uuid_bytes),
+            vision_parser: Some(VisionParser::new(uuid_bytes)),
+            response_header: ResponseHeader::default(),
             plaintext_buffer: Vec::new(),
             is_direct_copy: false,
             shared_read_raw,
         }
     }
 
+    /// A stream without flow, the payload is relayed as is.
+    pub fn plain(stream: S) -> Self {
+        Self {
+            stream,
+            vision_parser: None,
+            response_header: ResponseHeader::default(),
+            plaintext_buffer: Vec::new(),
+            is_direct_copy: false,
+            shared_read_raw: None,
+        }
+    }
+
     pub fn get_stream_mut(&mut self) -> &mut S {
         &mut self.stream
     }
@@ -247,14 +235,19 @@ impl<S: AsyncRead + AsyncWrite + Unpin> AsyncRead for VlessStream<S> {
                         return Poll::Ready(Ok(()));
                     }
 
-                    let decrypted = this.vision_parser.parse(&temp_buf[..bytes_read]);
-
-                    if this.vision_parser.v_direct_copy_rx && !this.is_direct_copy {
-                        this.is_direct_copy = true;
-                        if let Some(shared) = &this.shared_read_raw {
-                            shared.store(true, Ordering::Relaxed);
+                    let decrypted = match this.vision_parser.as_mut() {
+                        Some(vision_parser) => {
+                            let decrypted = vision_parser.parse(&temp_buf[..bytes_read]);
+                            if vision_parser.v_direct_copy_rx && !this.is_direct_copy {
+                                this.is_direct_copy = true;
+                                if let Some(shared) = &this.shared_read_raw {
+                                    shared.store(true, Ordering::Relaxed);
+                                }
+                            }
+                            decrypted
                         }
-                    }
+                        None => this.response_header.strip(&temp_buf[..bytes_read])?,
+                    };
 
                     if decrypted.is_empty() {
                         // Data consumed but no plaintext yielded. Loop around and poll inner again!
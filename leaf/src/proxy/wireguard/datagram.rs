@@ -0,0 +1,122 @@
+use std::io;
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use cidr::IpCidr;
+use futures::future::poll_fn;
+use futures::task::Poll;
+use smoltcp::iface::SocketHandle;
+use smoltcp::wire::IpEndpoint;
+
+use crate::{app::SyncDnsClient, proxy::*, session::SocksAddr};
+
+use super::stream::resolve;
+use super::tunnel::Shared;
+
+// Removes the socket once both halves are dropped.
+struct SocketGuard {
+    shared: Arc<Shared>,
+    handle: SocketHandle,
+}
+
+impl Drop for SocketGuard {
+    fn drop(&mut self) {
+        self.shared.stack.lock().unwrap().udp_close(self.handle);
+    }
+}
+
+pub struct Datagram {
+    guard: Arc<SocketGuard>,
+    dns_client: SyncDnsClient,
+    allowed_ips: Vec<IpCidr>,
+}
+
+impl Datagram {
+    pub(super) fn new(
+        shared: Arc<Shared>,
+        dns_client: SyncDnsClient,
+        allowed_ips: Vec<IpCidr>,
+    ) -> Result<Self> {
+        let handle = shared.stack.lock().unwrap().udp_bind()?;
+        Ok(Self {
+            guard: Arc::new(SocketGuard { shared, handle }),
+            dns_client,
+            allowed_ips,
+        })
+    }
+}
+
+impl OutboundDatagram for Datagram {
+    fn split(
+        self: Box<Self>,
+    ) -> (
+        Box<dyn OutboundDatagramRecvHalf>,
+        Box<dyn OutboundDatagramSendHalf>,
+    ) {
+        (
+            Box::new(DatagramRecvHalf(self.guard.clone())),
+            Box::new(DatagramSendHalf {
+                guard: self.guard,
+                dns_client: self.dns_client,
+                allowed_ips: self.allowed_ips,
+            }),
+        )
+    }
+}
+
+struct DatagramRecvHalf(Arc<SocketGuard>);
+
+#[async_trait]
+impl OutboundDatagramRecvHalf for DatagramRecvHalf {
+    async fn recv_from(&mut self, buf: &mut [u8]) -> io::Result<(usize, SocksAddr)> {
+        let guard = &self.0;
+        poll_fn(|cx| {
+            let mut stack = guard.shared.stack.lock().unwrap();
+            let socket = stack.udp(guard.handle);
+            if socket.can_recv() {
+                let (n, meta) = socket
+                    .recv_slice(buf)
+                    .map_err(|e| io::Error::other(e.to_string()))?;
+                return Poll::Ready(Ok((n, SocksAddr::from(SocketAddr::from(meta.endpoint)))));
+            }
+            socket.register_recv_waker(cx.waker());
+            Poll::Pending
+        })
+        .await
+    }
+}
+
+struct DatagramSendHalf {
+    guard: Arc<SocketGuard>,
+    dns_client: SyncDnsClient,
+    allowed_ips: Vec<IpCidr>,
+}
+
+#[async_trait]
+impl OutboundDatagramSendHalf for DatagramSendHalf {
+    async fn send_to(&mut self, buf: &[u8], dst_addr: &SocksAddr) -> io::Result<usize> {
+        let guard = &self.guard;
+        let remote = resolve(&self.dns_client, &self.allowed_ips, &guard.shared, dst_addr).await?;
+        poll_fn(|cx| {
+            let mut stack = guard.shared.stack.lock().unwrap();
+            let socket = stack.udp(guard.handle);
+            if socket.can_send() {
+                socket
+                    .send_slice(buf, IpEndpoint::from(remote))
+                    .map_err(|e| io::Error::other(e.to_string()))?;
+                return Poll::Ready(Ok(()));
+            }
+            socket.register_send_waker(cx.waker());
+            Poll::Pending
+        })
+        .await?;
+        guard.shared.notify.notify_one();
+        Ok(buf.len())
+    }
+
+    async fn close(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
@@ -0,0 +1,9 @@
+//! A WireGuard peer reached through a userspace tunnel, sessions are
+//! originated from the tunnel addresses by a TCP/IP stack of our own.
+
+mod datagram;
+mod stack;
+mod stream;
+mod tunnel;
+
+pub use stream::Handler;
@@ -0,0 +1,252 @@
+use std::collections::VecDeque;
+use std::io;
+use std::net::{IpAddr, SocketAddr};
+use std::time::Duration;
+
+use smoltcp::iface::{Config, Interface, SocketHandle, SocketSet};
+use smoltcp::phy::{Device, DeviceCapabilities, Medium, RxToken, TxToken};
+use smoltcp::socket::{tcp, udp};
+use smoltcp::time::Instant;
+use smoltcp::wire::{HardwareAddress, IpAddress, IpCidr, IpEndpoint};
+
+const TCP_BUFFER_SIZE: usize = 256 * 1024;
+const UDP_BUFFER_SIZE: usize = 128 * 1024;
+const UDP_PACKET_COUNT: usize = 64;
+
+const EPHEMERAL_PORT_START: u16 = 49152;
+// How long a closed socket may linger, e.g. when the remote end never
+// finishes the close.
+const CLOSE_TIMEOUT: Duration = Duration::from_secs(60);
+
+// Hands IP packets between the stack and the tunnel.
+struct TunnelDevice {
+    rx: VecDeque<Vec<u8>>,
+    tx: VecDeque<Vec<u8>>,
+    mtu: usize,
+}
+
+struct PacketRxToken(Vec<u8>);
+
+impl RxToken for PacketRxToken {
+    fn consume<R, F>(self, f: F) -> R
+    where
+        F: FnOnce(&[u8]) -> R,
+    {
+        f(&self.0)
+    }
+}
+
+struct PacketTxToken<'a>(&'a mut VecDeque<Vec<u8>>);
+
+impl TxToken for PacketTxToken<'_> {
+    fn consume<R, F>(self, len: usize, f: F) -> R
+    where
+        F: FnOnce(&mut [u8]) -> R,
+    {
+        let mut packet = vec![0u8; len];
+        let r = f(&mut packet);
+        self.0.push_back(packet);
+        r
+    }
+}
+
+impl Device for TunnelDevice {
+    type RxToken<'a> = PacketRxToken;
+    type TxToken<'a> = PacketTxToken<'a>;
+
+    fn receive(&mut self, _timestamp: Instant) -> Option<(Self::RxToken<'_>, Self::TxToken<'_>)> {
+        let packet = self.rx.pop_front()?;
+        Some((PacketRxToken(packet), PacketTxToken(&mut self.tx)))
+    }
+
+    fn transmit(&mut self, _timestamp: Instant) -> Option<Self::TxToken<'_>> {
+        Some(PacketTxToken(&mut self.tx))
+    }
+
+    fn capabilities(&self) -> DeviceCapabilities {
+        let mut caps = DeviceCapabilities::default();
+        caps.medium = Medium::Ip;
+        caps.max_transmission_unit = self.mtu;
+        caps
+    }
+}
+
+/// A TCP/IP stack originating connections from the tunnel addresses.
+pub struct Stack {
+    iface: Interface,
+    device: TunnelDevice,
+    sockets: SocketSet<'static>,
+    addrs: Vec<IpAddr>,
+    next_port: u16,
+    // Sockets closed by their owners, removed once they're done or have
+    // lingered for too long.
+    closing: Vec<(SocketHandle, std::time::Instant)>,
+}
+
+impl Stack {
+    pub fn new(addrs: &[(IpAddr, u8)], mtu: usize) -> Self {
+        let mut device = TunnelDevice {
+            rx: VecDeque::new(),
+            tx: VecDeque::new(),
+            mtu,
+        };
+        let mut iface = Interface::new(
+            Config::new(HardwareAddress::Ip),
+            &mut device,
+            Instant::now(),
+        );
+        iface.update_ip_addrs(|ip_addrs| {
+            for (addr, prefix) in addrs {
+                let _ = ip_addrs.push(IpCidr::new(IpAddress::from(*addr), *prefix));
+            }
+        });
+        // Everything goes out through the tunnel, the gateway is only there
+        // to make destinations routable.
+        for (addr, _) in addrs {
+            let _ = match addr {
+                IpAddr::V4(addr) => iface.routes_mut().add_default_ipv4_route((*addr).into()),
+                IpAddr::V6(addr) => iface.routes_mut().add_default_ipv6_route((*addr).into()),
+            };
+        }
+        Self {
+            iface,
+            device,
+            sockets: SocketSet::new(Vec::new()),
+            addrs: addrs.iter().map(|(addr, _)| *addr).collect(),
+            next_port: EPHEMERAL_PORT_START,
+            closing: Vec::new(),
+        }
+    }
+
+    /// Whether there's a tunnel address to reach `ip` from.
+    pub fn can_reach(&self, ip: &IpAddr) -> bool {
+        self.addrs.iter().any(|addr| addr.is_ipv4() == ip.is_ipv4())
+    }
+
+    fn next_port(&mut self) -> u16 {
+        let port = self.next_port;
+        self.next_port = self
+            .next_port
+            .checked_add(1)
+            .unwrap_or(EPHEMERAL_PORT_START);
+        port
+    }
+
+    /// Queues a packet received from the tunnel.
+    pub fn receive(&mut self, packet: Vec<u8>) {
+        self.device.rx.push_back(packet);
+    }
+
+    /// Processes the received packets and the sockets, returning the packets
+    /// to send through the tunnel and when to poll again at the latest.
+    pub fn poll(&mut self) -> (Vec<Vec<u8>>, Option<Duration>) {
+        let now = Instant::now();
+        self.iface.poll(now, &mut self.device, &mut self.sockets);
+        let sockets = &mut self.sockets;
+        self.closing.retain(|(handle, deadline)| {
+            let done = sockets.get::<tcp::Socket>(*handle).state() == tcp::State::Closed
+                || *deadline <= std::time::Instant::now();
+            if done {
+                sockets.remove(*handle);
+            }
+            !done
+        });
+        let delay = self
+            .iface
+            .poll_delay(now, &self.sockets)
+            .map(|d| Duration::from_micros(d.total_micros()));
+        (self.device.tx.drain(..).collect(), delay)
+    }
+
+    /// Starts connecting a TCP socket to `remote`.
+    pub fn tcp_connect(&mut self, remote: SocketAddr) -> io::Result<SocketHandle> {
+        let mut socket = tcp::Socket::new(
+            tcp::SocketBuffer::new(vec![0u8; TCP_BUFFER_SIZE]),
+            tcp::SocketBuffer::new(vec![0u8; TCP_BUFFER_SIZE]),
+        );
+        let port = self.next_port();
+        socket
+            .connect(self.iface.context(), IpEndpoint::from(remote), port)
+            .map_err(|e| io::Error::other(format!("connect {} failed: {}", remote, e)))?;
+        Ok(self.sockets.add(socket))
+    }
+
+    pub fn tcp(&mut self, handle: SocketHandle) -> &mut tcp::Socket<'static> {
+        self.sockets.get_mut::<tcp::Socket>(handle)
+    }
+
+    /// Closes a TCP socket gracefully, it's removed once the close completes.
+    pub fn tcp_close(&mut self, handle: SocketHandle) {
+        self.tcp(handle).close();
+        self.closing
+            .push((handle, std::time::Instant::now() + CLOSE_TIMEOUT));
+    }
+
+    /// Binds a UDP socket to an ephemeral port.
+    pub fn udp_bind(&mut self) -> io::Result<SocketHandle> {
+        let mut socket = udp::Socket::new(
+            udp::PacketBuffer::new(
+                vec![udp::PacketMetadata::EMPTY; UDP_PACKET_COUNT],
+                vec![0u8; UDP_BUFFER_SIZE],
+            ),
+            udp::PacketBuffer::new(
+                vec![udp::PacketMetadata::EMPTY; UDP_PACKET_COUNT],
+                vec![0u8; UDP_BUFFER_SIZE],
+            ),
+        );
+        let port = self.next_port();
+        socket
+            .bind(port)
+            .map_err(|e| io::Error::other(format!("bind udp socket failed: {}", e)))?;
+        Ok(self.sockets.add(socket))
+    }
+
+    pub fn udp(&mut self, handle: SocketHandle) -> &mut udp::Socket<'static> {
+        self.sockets.get_mut::<udp::Socket>(handle)
+    }
+
+    pub fn udp_close(&mut self, handle: SocketHandle) {
+        self.sockets.remove(handle);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Two stacks wired back to back, one listening.
+    #[test]
+    fn test_tcp_connect() {
+        let client_addr: IpAddr = "10.0.0.2".parse().unwrap();
+        let server_addr: IpAddr = "10.0.0.1".parse().unwrap();
+        let mut client = Stack::new(&[(client_addr, 32)], 1420);
+        let mut server = Stack::new(&[(server_addr, 32)], 1420);
+
+        let mut listener = tcp::Socket::new(
+            tcp::SocketBuffer::new(vec![0u8; 4096]),
+            tcp::SocketBuffer::new(vec![0u8; 4096]),
+        );
+        listener.listen(80).unwrap();
+        let listener = server.sockets.add(listener);
+
+        let handle = client
+            .tcp_connect(SocketAddr::new(server_addr, 80))
+            .unwrap();
+        for _ in 0..10 {
+            let (packets, _) = client.poll();
+            packets.into_iter().for_each(|p| server.receive(p));
+            let (packets, _) = server.poll();
+            packets.into_iter().for_each(|p| client.receive(p));
+        }
+        assert_eq!(client.tcp(handle).state(), tcp::State::Established);
+        let socket = server.tcp(listener);
+        assert_eq!(socket.state(), tcp::State::Established);
+        assert_eq!(
+            socket.remote_endpoint().map(|e| SocketAddr::from(e).ip()),
+            Some(client_addr)
+        );
+
+        assert!(client.can_reach(&server_addr));
+        assert!(!client.can_reach(&"::1".parse().unwrap()));
+    }
+}
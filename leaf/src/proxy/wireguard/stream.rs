@@ -0,0 +1,391 @@
+use std::io;
+use std::net::{IpAddr, SocketAddr};
+use std::pin::Pin;
+use std::sync::Arc;
+
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use cidr::IpCidr;
+use futures::future::poll_fn;
+use futures::task::{Context, Poll};
+use smoltcp::iface::SocketHandle;
+use smoltcp::socket::tcp;
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::sync::OnceCell;
+use tokio::task::JoinHandle;
+use tokio::time::{timeout, Duration};
+use tracing::{debug, Instrument};
+
+use crate::{
+    app::SyncDnsClient,
+    proxy::*,
+    session::{Session, SocksAddr},
+};
+
+use super::datagram::Datagram;
+use super::stack::Stack;
+use super::tunnel::{Peer, Shared, Tunnel};
+
+const DEFAULT_MTU: usize = 1420;
+
+fn decode_key(key: &str, name: &str) -> Result<[u8; 32]> {
+    STANDARD
+        .decode(key)
+        .ok()
+        .and_then(|key| key.try_into().ok())
+        .ok_or_else(|| anyhow!("invalid wireguard {}", name))
+}
+
+fn parse_endpoint(endpoint: &str) -> Result<(String, u16)> {
+    if let Ok(addr) = endpoint.parse::<SocketAddr>() {
+        return Ok((addr.ip().to_string(), addr.port()));
+    }
+    endpoint
+        .rsplit_once(':')
+        .and_then(|(host, port)| Some((host.to_string(), port.parse::<u16>().ok()?)))
+        .ok_or_else(|| anyhow!("invalid wireguard endpoint {}", endpoint))
+}
+
+// An address with an optional prefix length, a host address without one.
+fn parse_local_address(addr: &str) -> Result<(IpAddr, u8)> {
+    let (ip, prefix) = match addr.split_once('/') {
+        Some((ip, prefix)) => (ip, Some(prefix)),
+        None => (addr, None),
+    };
+    let ip = ip
+        .parse::<IpAddr>()
+        .map_err(|_| anyhow!("invalid wireguard local address {}", addr))?;
+    let max_prefix = if ip.is_ipv4() { 32 } else { 128 };
+    let prefix = match prefix {
+        Some(prefix) => prefix
+            .parse::<u8>()
+            .ok()
+            .filter(|prefix| *prefix <= max_prefix)
+            .ok_or_else(|| anyhow!("invalid wireguard local address {}", addr))?,
+        None => max_prefix,
+    };
+    Ok((ip, prefix))
+}
+
+/// Resolves the destination to an address the tunnel can carry.
+pub(super) async fn resolve(
+    dns_client: &SyncDnsClient,
+    allowed_ips: &[IpCidr],
+    shared: &Shared,
+    destination: &SocksAddr,
+) -> io::Result<SocketAddr> {
+    let ips = match destination {
+        SocksAddr::Ip(addr) => vec![addr.ip()],
+        SocksAddr::Domain(domain, _) => dns_client
+            .read()
+            .await
+            .lookup(domain)
+            .await
+            .map_err(|e| io::Error::other(format!("lookup {} failed: {}", domain, e)))?,
+    };
+    let stack = shared.stack.lock().unwrap();
+    let ip = ips
+        .into_iter()
+        .find(|ip| stack.can_reach(ip) && allowed_ips.iter().any(|cidr| cidr.contains(ip)))
+        .ok_or_else(|| {
+            io::Error::other(format!(
+                "{} is not reachable through the tunnel",
+                destination
+            ))
+        })?;
+    Ok(SocketAddr::new(ip, destination.port()))
+}
+
+// A running tunnel, stopped once the handler is dropped.
+struct Device {
+    shared: Arc<Shared>,
+    task: JoinHandle<()>,
+}
+
+impl Drop for Device {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+// A TCP connection through the tunnel.
+struct WireGuardStream {
+    shared: Arc<Shared>,
+    handle: SocketHandle,
+}
+
+impl WireGuardStream {
+    async fn connect(shared: Arc<Shared>, remote: SocketAddr) -> io::Result<Self> {
+        let handle = shared.stack.lock().unwrap().tcp_connect(remote)?;
+        // Owns the socket from here so that it's closed on failure.
+        let stream = Self { shared, handle };
+        stream.shared.notify.notify_one();
+        poll_fn(|cx| {
+            let mut stack = stream.shared.stack.lock().unwrap();
+            let socket = stack.tcp(stream.handle);
+            match socket.state() {
+                tcp::State::Established => Poll::Ready(Ok(())),
+                tcp::State::Closed => Poll::Ready(Err(io::Error::new(
+                    io::ErrorKind::ConnectionRefused,
+                    format!("connect {} failed", remote),
+                ))),
+                _ => {
+                    socket.register_send_waker(cx.waker());
+                    Poll::Pending
+                }
+            }
+        })
+        .await?;
+        Ok(stream)
+    }
+}
+
+impl AsyncRead for WireGuardStream {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context,
+        buf: &mut ReadBuf,
+    ) -> Poll<io::Result<()>> {
+        let mut stack = self.shared.stack.lock().unwrap();
+        let socket = stack.tcp(self.handle);
+        if socket.can_recv() {
+            let n = socket
+                .recv_slice(buf.initialize_unfilled())
+                .map_err(|e| io::Error::other(e.to_string()))?;
+            buf.advance(n);
+            drop(stack);
+            // The window may have opened.
+            self.shared.notify.notify_one();
+            return Poll::Ready(Ok(()));
+        }
+        if !socket.may_recv() {
+            return Poll::Ready(Ok(()));
+        }
+        socket.register_recv_waker(cx.waker());
+        Poll::Pending
+    }
+}
+
+impl AsyncWrite for WireGuardStream {
+    fn poll_write(self: Pin<&mut Self>, cx: &mut Context, buf: &[u8]) -> Poll<io::Result<usize>> {
+        let mut stack = self.shared.stack.lock().unwrap();
+        let socket = stack.tcp(self.handle);
+        if !socket.may_send() {
+            return Poll::Ready(Err(io::ErrorKind::BrokenPipe.into()));
+        }
+        if socket.can_send() {
+            let n = socket
+                .send_slice(buf)
+                .map_err(|e| io::Error::other(e.to_string()))?;
+            drop(stack);
+            self.shared.notify.notify_one();
+            return Poll::Ready(Ok(n));
+        }
+        socket.register_send_waker(cx.waker());
+        Poll::Pending
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, _cx: &mut Context) -> Poll<io::Result<()>> {
+        self.shared.stack.lock().unwrap().tcp(self.handle).close();
+        self.shared.notify.notify_one();
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl Drop for WireGuardStream {
+    fn drop(&mut self) {
+        self.shared.stack.lock().unwrap().tcp_close(self.handle);
+        self.shared.notify.notify_one();
+    }
+}
+
+pub struct Handler {
+    private_key: [u8; 32],
+    public_key: [u8; 32],
+    preshared_key: Option<[u8; 32]>,
+    endpoint: (String, u16),
+    allowed_ips: Vec<IpCidr>,
+    local_addrs: Vec<(IpAddr, u8)>,
+    mtu: usize,
+    dns_client: SyncDnsClient,
+    device: OnceCell<Device>,
+}
+
+impl Handler {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        private_key: &str,
+        peer_public_key: &str,
+        preshared_key: Option<&str>,
+        endpoint: &str,
+        allowed_ips: &[String],
+        local_addresses: &[String],
+        mtu: Option<u32>,
+        dns_client: SyncDnsClient,
+    ) -> Result<Self> {
+        let allowed_ips = if allowed_ips.is_empty() {
+            vec!["0.0.0.0/0".parse()?, "::/0".parse()?]
+        } else {
+            allowed_ips
+                .iter()
+                .map(|ip| {
+                    ip.parse::<IpCidr>()
+                        .map_err(|e| anyhow!("invalid wireguard allowed ip {}: {}", ip, e))
+                })
+                .collect::<Result<Vec<_>>>()?
+        };
+        let local_addrs = local_addresses
+            .iter()
+            .map(|addr| parse_local_address(addr))
+            .collect::<Result<Vec<_>>>()?;
+        if local_addrs.is_empty() {
+            return Err(anyhow!("wireguard local address is required"));
+        }
+        Ok(Self {
+            private_key: decode_key(private_key, "private key")?,
+            public_key: decode_key(peer_public_key, "peer public key")?,
+            preshared_key: preshared_key
+                .map(|key| decode_key(key, "preshared key"))
+                .transpose()?,
+            endpoint: parse_endpoint(endpoint)?,
+            allowed_ips,
+            local_addrs,
+            mtu: mtu.map(|mtu| mtu as usize).unwrap_or(DEFAULT_MTU),
+            dns_client,
+            device: OnceCell::new(),
+        })
+    }
+
+    // Starts the tunnel on first use.
+    async fn device(&self) -> Result<&Device> {
+        self.device
+            .get_or_try_init(|| async {
+                let shared = Arc::new(Shared::new(Stack::new(&self.local_addrs, self.mtu)));
+                let peer = Peer {
+                    private_key: self.private_key,
+                    public_key: self.public_key,
+                    preshared_key: self.preshared_key,
+                    endpoint: self.endpoint.clone(),
+                    allowed_ips: self.allowed_ips.clone(),
+                };
+                let tunnel = Tunnel::new(shared.clone(), peer, self.dns_client.clone()).await?;
+                debug!(
+                    "wireguard tunnel to {}:{} started",
+                    &self.endpoint.0, self.endpoint.1
+                );
+                let task = tokio::spawn(tunnel.run());
+                Ok(Device { shared, task })
+            })
+            .await
+    }
+
+    async fn new_stream(&self, sess: &Session) -> Result<AnyStream> {
+        let device = self.device().await?;
+        let remote = resolve(
+            &self.dns_client,
+            &self.allowed_ips,
+            &device.shared,
+            &sess.destination,
+        )
+        .await?;
+        let dial_timeout = Duration::from_secs(*crate::option::OUTBOUND_DIAL_TIMEOUT);
+        let stream = timeout(
+            dial_timeout,
+            WireGuardStream::connect(device.shared.clone(), remote),
+        )
+        .await
+        .map_err(|_| anyhow!("connect {} timed out", remote))??;
+        Ok(Box::new(stream))
+    }
+
+    async fn new_datagram(&self) -> Result<Datagram> {
+        let device = self.device().await?;
+        Datagram::new(
+            device.shared.clone(),
+            self.dns_client.clone(),
+            self.allowed_ips.clone(),
+        )
+    }
+}
+
+#[async_trait]
+impl OutboundStreamHandler for Handler {
+    fn connect_addr(&self) -> OutboundConnect {
+        OutboundConnect::Unknown
+    }
+
+    async fn handle<'a>(
+        &'a self,
+        sess: &'a Session,
+        _lhs: Option<&mut AnyStream>,
+        _stream: Option<AnyStream>,
+    ) -> io::Result<AnyStream> {
+        tracing::trace!("handling outbound stream");
+        self.new_stream(sess)
+            .instrument(tracing::Span::current())
+            .await
+            .map_err(|e| io::Error::other(format!("new wireguard stream failed: {}", e)))
+    }
+}
+
+#[async_trait]
+impl OutboundDatagramHandler for Handler {
+    fn connect_addr(&self) -> OutboundConnect {
+        OutboundConnect::Unknown
+    }
+
+    fn transport_type(&self) -> DatagramTransportType {
+        DatagramTransportType::Unreliable
+    }
+
+    async fn handle<'a>(
+        &'a self,
+        _sess: &'a Session,
+        _transport: Option<AnyOutboundTransport>,
+    ) -> io::Result<AnyOutboundDatagram> {
+        tracing::trace!("handling outbound datagram");
+        let dgram = self
+            .new_datagram()
+            .instrument(tracing::Span::current())
+            .await
+            .map_err(|e| io::Error::other(format!("new wireguard udp session failed: {}", e)))?;
+        Ok(Box::new(dgram))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_settings() {
+        assert_eq!(
+            parse_endpoint("example.com:51820").unwrap(),
+            ("example.com".to_string(), 51820)
+        );
+        assert_eq!(
+            parse_endpoint("[::1]:51820").unwrap(),
+            ("::1".to_string(), 51820)
+        );
+        assert!(parse_endpoint("example.com").is_err());
+
+        assert_eq!(
+            parse_local_address("10.0.0.2").unwrap(),
+            ("10.0.0.2".parse().unwrap(), 32)
+        );
+        assert_eq!(
+            parse_local_address("fd00::2/64").unwrap(),
+            ("fd00::2".parse().unwrap(), 64)
+        );
+        assert!(parse_local_address("10.0.0.2/33").is_err());
+
+        let key = STANDARD.encode([7u8; 32]);
+        assert_eq!(decode_key(&key, "private key").unwrap(), [7u8; 32]);
+        assert!(decode_key(&STANDARD.encode([7u8; 16]), "private key").is_err());
+    }
+}
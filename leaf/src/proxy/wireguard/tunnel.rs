@@ -0,0 +1,222 @@
+use std::io;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+use boringtun::noise::errors::WireGuardError;
+use boringtun::noise::{Tunn, TunnResult};
+use boringtun::x25519::{PublicKey, StaticSecret};
+use cidr::IpCidr;
+use tokio::net::UdpSocket;
+use tokio::sync::Notify;
+use tracing::{debug, trace};
+
+use crate::app::SyncDnsClient;
+
+use super::stack::Stack;
+
+// How often the WireGuard timers are updated, as the reference
+// implementations do.
+const TIMER_TICK: Duration = Duration::from_millis(250);
+// The longest the stack goes without being polled.
+const MAX_POLL_DELAY: Duration = Duration::from_secs(1);
+const PERSISTENT_KEEPALIVE: u16 = 25;
+const MAX_PACKET_LEN: usize = 65536;
+
+/// The peer and the tunnel addresses.
+pub struct Peer {
+    pub private_key: [u8; 32],
+    pub public_key: [u8; 32],
+    pub preshared_key: Option<[u8; 32]>,
+    pub endpoint: (String, u16),
+    pub allowed_ips: Vec<IpCidr>,
+}
+
+impl Peer {
+    pub fn allows(&self, ip: &IpAddr) -> bool {
+        self.allowed_ips.iter().any(|cidr| cidr.contains(ip))
+    }
+}
+
+/// The stack and the notification waking the tunnel to poll it, shared with
+/// the streams and datagrams.
+pub struct Shared {
+    pub stack: Mutex<Stack>,
+    pub notify: Notify,
+}
+
+impl Shared {
+    pub fn new(stack: Stack) -> Self {
+        Self {
+            stack: Mutex::new(stack),
+            notify: Notify::new(),
+        }
+    }
+}
+
+async fn resolve(dns_client: &SyncDnsClient, endpoint: &(String, u16)) -> Result<SocketAddr> {
+    let ips = dns_client
+        .read()
+        .await
+        .direct_lookup(&endpoint.0)
+        .await
+        .map_err(|e| anyhow!("lookup {} failed: {}", &endpoint.0, e))?;
+    let ip = ips
+        .first()
+        .ok_or_else(|| anyhow!("could not resolve {} to any address", &endpoint.0))?;
+    Ok(SocketAddr::new(*ip, endpoint.1))
+}
+
+async fn bind() -> io::Result<UdpSocket> {
+    crate::proxy::new_udp_socket(&crate::option::UNSPECIFIED_BIND_ADDR).await
+}
+
+/// Carries the packets of the stack through the tunnel to the peer.
+pub struct Tunnel {
+    shared: Arc<Shared>,
+    peer: Peer,
+    tunn: Tunn,
+    socket: UdpSocket,
+    peer_addr: SocketAddr,
+    dns_client: SyncDnsClient,
+    buf: Vec<u8>,
+}
+
+impl Tunnel {
+    pub async fn new(shared: Arc<Shared>, peer: Peer, dns_client: SyncDnsClient) -> Result<Self> {
+        let tunn = Tunn::new(
+            StaticSecret::from(peer.private_key),
+            PublicKey::from(peer.public_key),
+            peer.preshared_key,
+            Some(PERSISTENT_KEEPALIVE),
+            rand::random::<u32>() >> 8,
+            None,
+        );
+        let peer_addr = resolve(&dns_client, &peer.endpoint).await?;
+        Ok(Self {
+            shared,
+            peer,
+            tunn,
+            socket: bind().await?,
+            peer_addr,
+            dns_client,
+            buf: vec![0u8; MAX_PACKET_LEN],
+        })
+    }
+
+    async fn send(&self, packet: &[u8]) {
+        if let Err(e) = self.socket.send_to(packet, self.peer_addr).await {
+            debug!("send wireguard packet to {} failed: {}", &self.peer_addr, e);
+        }
+    }
+
+    async fn initiate_handshake(&mut self) {
+        let mut buf = std::mem::take(&mut self.buf);
+        if let TunnResult::WriteToNetwork(packet) =
+            self.tunn.format_handshake_initiation(&mut buf, false)
+        {
+            self.send(packet).await;
+        }
+        self.buf = buf;
+    }
+
+    // The handshake has timed out or the session expired, the peer may have
+    // moved or the NAT mapping gone, so the endpoint is resolved again and
+    // the handshake sent from a new socket.
+    async fn reconnect(&mut self) {
+        debug!("wireguard connection to {} expired", &self.peer_addr);
+        match resolve(&self.dns_client, &self.peer.endpoint).await {
+            Ok(addr) => self.peer_addr = addr,
+            Err(e) => debug!("{}", e),
+        }
+        match bind().await {
+            Ok(socket) => self.socket = socket,
+            Err(e) => debug!("bind wireguard socket failed: {}", e),
+        }
+        self.initiate_handshake().await;
+    }
+
+    async fn encapsulate(&mut self, packets: Vec<Vec<u8>>) {
+        let mut buf = std::mem::take(&mut self.buf);
+        for packet in packets {
+            match self.tunn.encapsulate(&packet, &mut buf) {
+                TunnResult::WriteToNetwork(packet) => self.send(packet).await,
+                TunnResult::Err(e) => debug!("wireguard encapsulate failed: {:?}", e),
+                _ => (),
+            }
+        }
+        self.buf = buf;
+    }
+
+    async fn decapsulate(&mut self, datagram: &[u8], from: SocketAddr) {
+        let mut buf = std::mem::take(&mut self.buf);
+        match self.tunn.decapsulate(Some(from.ip()), datagram, &mut buf) {
+            TunnResult::WriteToNetwork(packet) => {
+                self.send(packet).await;
+                // Packets queued while there was no session go out now.
+                while let TunnResult::WriteToNetwork(packet) =
+                    self.tunn.decapsulate(None, &[], &mut buf)
+                {
+                    self.send(packet).await;
+                }
+            }
+            TunnResult::WriteToTunnelV4(packet, src) => self.deliver(packet, src.into()),
+            TunnResult::WriteToTunnelV6(packet, src) => self.deliver(packet, src.into()),
+            TunnResult::Err(e) => debug!("wireguard decapsulate failed: {:?}", e),
+            TunnResult::Done => (),
+        }
+        self.buf = buf;
+    }
+
+    fn deliver(&self, packet: &[u8], src: IpAddr) {
+        if !self.peer.allows(&src) {
+            trace!("dropping wireguard packet from disallowed {}", src);
+            return;
+        }
+        self.shared.stack.lock().unwrap().receive(packet.to_vec());
+    }
+
+    async fn update_timers(&mut self) {
+        let mut buf = std::mem::take(&mut self.buf);
+        let result = match self.tunn.update_timers(&mut buf) {
+            TunnResult::WriteToNetwork(packet) => {
+                self.send(packet).await;
+                Ok(())
+            }
+            TunnResult::Err(e) => Err(e),
+            _ => Ok(()),
+        };
+        self.buf = buf;
+        match result {
+            Err(WireGuardError::ConnectionExpired) => self.reconnect().await,
+            Err(e) => debug!("wireguard timers failed: {:?}", e),
+            Ok(()) => (),
+        }
+    }
+
+    /// Runs the tunnel, it's meant to be aborted when no longer needed.
+    pub async fn run(mut self) {
+        // Handshake right away rather than on the first packet.
+        self.initiate_handshake().await;
+        let mut timers = tokio::time::interval(TIMER_TICK);
+        let mut recv_buf = vec![0u8; MAX_PACKET_LEN];
+        loop {
+            let (packets, delay) = self.shared.stack.lock().unwrap().poll();
+            self.encapsulate(packets).await;
+            let delay = delay.unwrap_or(MAX_POLL_DELAY).min(MAX_POLL_DELAY);
+            tokio::select! {
+                _ = self.shared.notify.notified() => (),
+                _ = tokio::time::sleep(delay) => (),
+                _ = timers.tick() => self.update_timers().await,
+                r = self.socket.recv_from(&mut recv_buf) => match r {
+                    Ok((n, from)) => self.decapsulate(&recv_buf[..n], from).await,
+                    Err(e) => {
+                        debug!("receive wireguard packet failed: {}", e);
+                        self.reconnect().await;
+                    }
+                },
+            }
+        }
+    }
+}
@@ -1,5 +1,5 @@
 mod stream;
 
-pub use stream::Handler as StreamHandler;
+pub use stream::{Fallback, Handler as StreamHandler};
 
 use super::stream as ws_stream;
@@ -1,31 +1,73 @@
 use std::io::{self};
+use std::path::Path;
 
 use async_trait::async_trait;
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use bytes::BytesMut;
 use futures::TryFutureExt;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::time::{timeout, Duration};
 use tokio_tungstenite::accept_hdr_async;
 use tracing::debug;
 use tungstenite::handshake::server::{Callback, ErrorResponse, Request, Response};
 
-use crate::{proxy::*, session::Session};
+use crate::{common::io::PrefixedStream, proxy::*, session::Session};
+
+// The largest request head read to decide between the upgrade and the
+// fallback.
+const MAX_REQUEST_HEAD_LEN: usize = 8 * 1024;
+// The time a client has to send the request head, so that half-open
+// requests don't hold connections.
+const REQUEST_HEAD_TIMEOUT: u64 = 10;
 
 struct SimpleCallback<'a> {
     sess: &'a mut Session,
     path: &'a str,
+    header: Option<&'a (String, String)>,
+    early_data: &'a mut Vec<u8>,
 }
 
 impl<'a> SimpleCallback<'a> {
-    pub fn new(sess: &'a mut Session, path: &'a str) -> Self {
-        Self { sess, path }
+    pub fn new(
+        sess: &'a mut Session,
+        path: &'a str,
+        header: Option<&'a (String, String)>,
+        early_data: &'a mut Vec<u8>,
+    ) -> Self {
+        Self {
+            sess,
+            path,
+            header,
+            early_data,
+        }
     }
 }
 
+fn not_found() -> ErrorResponse {
+    ::http::response::Response::builder()
+        .status(::http::StatusCode::NOT_FOUND)
+        .body(None)
+        .unwrap()
+}
+
 impl<'a> Callback for SimpleCallback<'a> {
-    fn on_request(self, request: &Request, response: Response) -> Result<Response, ErrorResponse> {
+    fn on_request(
+        self,
+        request: &Request,
+        mut response: Response,
+    ) -> Result<Response, ErrorResponse> {
         if request.uri().path() != self.path {
-            return Err(::http::response::Response::builder()
-                .status(::http::StatusCode::NOT_FOUND)
-                .body(None)
-                .unwrap());
+            return Err(not_found());
+        }
+        if let Some((name, value)) = self.header {
+            let matched = request
+                .headers()
+                .get(name.as_str())
+                .map(|x| x.as_bytes() == value.as_bytes())
+                .unwrap_or(false);
+            if !matched {
+                return Err(not_found());
+            }
         }
         if let Some(Ok(forwarded)) = request
             .headers()
@@ -43,17 +85,204 @@ impl<'a> Callback for SimpleCallback<'a> {
                 self.sess.forwarded_source.replace(f);
             }
         }
+        // Early data of V2Ray and Xray clients, the header is echoed as the
+        // client checks the subprotocol it asked for.
+        if let Some(protocol) = request
+            .headers()
+            .get(::http::header::SEC_WEBSOCKET_PROTOCOL)
+        {
+            if let Ok(data) = URL_SAFE_NO_PAD.decode(protocol.as_bytes()) {
+                *self.early_data = data;
+                response
+                    .headers_mut()
+                    .insert(::http::header::SEC_WEBSOCKET_PROTOCOL, protocol.clone());
+            }
+        }
         Ok(response)
     }
 }
 
+/// What requests that don't upgrade get.
+pub enum Fallback {
+    /// Relays the connection, the request included, to the address.
+    Address(String),
+    /// A complete HTTP response.
+    Response(Vec<u8>),
+}
+
+impl Fallback {
+    /// A response with `status` and the content of `file` as the body.
+    pub fn response(status: u32, file: Option<&str>) -> io::Result<Self> {
+        let status = u16::try_from(status)
+            .ok()
+            .and_then(|x| ::http::StatusCode::from_u16(x).ok())
+            .ok_or_else(|| io::Error::other(format!("invalid status {}", status)))?;
+        let mut resp = format!(
+            "HTTP/1.1 {} {}\r\n",
+            status.as_u16(),
+            status.canonical_reason().unwrap_or_default()
+        );
+        let body = if let Some(file) = file {
+            let content_type = match Path::new(file).extension().and_then(|x| x.to_str()) {
+                Some("html") | Some("htm") => "text/html; charset=utf-8",
+                Some("txt") => "text/plain; charset=utf-8",
+                Some("json") => "application/json",
+                _ => "application/octet-stream",
+            };
+            resp.push_str(&format!("Content-Type: {}\r\n", content_type));
+            std::fs::read(file)
+                .map_err(|e| io::Error::new(e.kind(), format!("read {} failed: {}", file, e)))?
+        } else {
+            Vec::new()
+        };
+        resp.push_str(&format!(
+            "Content-Length: {}\r\nConnection: close\r\n\r\n",
+            body.len()
+        ));
+        let mut resp = resp.into_bytes();
+        resp.extend_from_slice(&body);
+        Ok(Fallback::Response(resp))
+    }
+}
+
+// Reads until the end of the request head. What's read goes back to the
+// stream for the handshake, or to the fallback.
+async fn read_request_head(stream: &mut AnyStream) -> io::Result<Vec<u8>> {
+    let mut head = Vec::new();
+    let mut buf = [0u8; 1024];
+    while head.len() < MAX_REQUEST_HEAD_LEN {
+        let n = stream.read(&mut buf).await?;
+        if n == 0 {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        head.extend_from_slice(&buf[..n]);
+        if head.windows(4).any(|x| x == b"\r\n\r\n") {
+            break;
+        }
+    }
+    Ok(head)
+}
+
+// Returns whether `head` is a complete request upgrading to WebSocket on
+// `path`, with the required header if any.
+fn is_upgrade(head: &[u8], path: &str, header: Option<&(String, String)>) -> bool {
+    let Some(end) = head.windows(4).position(|x| x == b"\r\n\r\n") else {
+        return false;
+    };
+    let mut lines = head[..end].split(|x| *x == b'\n').map(|x| x.trim_ascii());
+    let Some(request_line) = lines.next() else {
+        return false;
+    };
+    let mut parts = request_line.split(|x| *x == b' ');
+    if parts.next() != Some(b"GET") {
+        return false;
+    }
+    let target = parts.next().unwrap_or_default();
+    let target_path = target.split(|x| *x == b'?').next().unwrap_or_default();
+    if target_path != path.as_bytes() {
+        return false;
+    }
+    let mut upgrade = false;
+    let mut header_matched = header.is_none();
+    for line in lines {
+        let Some(colon) = line.iter().position(|x| *x == b':') else {
+            continue;
+        };
+        let name = &line[..colon];
+        let value = line[colon + 1..].trim_ascii();
+        if name.eq_ignore_ascii_case(b"upgrade") {
+            upgrade = value.eq_ignore_ascii_case(b"websocket");
+        }
+        if let Some((required_name, required_value)) = header {
+            if name.eq_ignore_ascii_case(required_name.as_bytes()) {
+                header_matched = value == required_value.as_bytes();
+            }
+        }
+    }
+    upgrade && header_matched
+}
+
 pub struct Handler {
     path: String,
+    // The name and value of a header requests must have.
+    header: Option<(String, String)>,
+    fallback: Option<Fallback>,
 }
 
 impl Handler {
-    pub fn new(path: String) -> Self {
-        Handler { path }
+    pub fn new(path: String, header: Option<(String, String)>, fallback: Option<Fallback>) -> Self {
+        Handler {
+            path,
+            header,
+            fallback,
+        }
+    }
+
+    async fn accept(
+        &self,
+        mut sess: Session,
+        stream: AnyStream,
+    ) -> io::Result<AnyInboundTransport> {
+        let mut early_data = Vec::new();
+        let s = accept_hdr_async(
+            stream,
+            SimpleCallback::new(&mut sess, &self.path, self.header.as_ref(), &mut early_data),
+        )
+        .map_err(|e| io::Error::other(format!("accept ws failed: {}", e)));
+        let s = timeout(Duration::from_secs(REQUEST_HEAD_TIMEOUT), s)
+            .await
+            .map_err(|_| io::Error::new(io::ErrorKind::TimedOut, "accept ws timed out"))??;
+        debug!("accepted WS stream");
+        Ok(InboundTransport::Stream(
+            Box::new(super::ws_stream::WebSocketToStream::with_early_data(
+                s,
+                &early_data,
+            )),
+            sess,
+        ))
+    }
+
+    fn fall_back(
+        fallback: &Fallback,
+        mut stream: AnyStream,
+        head: Vec<u8>,
+    ) -> io::Result<AnyInboundTransport> {
+        match fallback {
+            Fallback::Address(address) => {
+                debug!("ws inbound falls back to {}", address);
+                let address = address.clone();
+                tokio::spawn(async move {
+                    match tokio::net::TcpStream::connect(&address).await {
+                        Ok(mut target) => {
+                            let res = async {
+                                target.write_all(&head).await?;
+                                tokio::io::copy_bidirectional(&mut stream, &mut target).await
+                            };
+                            if let Err(e) = res.await {
+                                debug!("ws fallback to {} failed: {}", &address, e);
+                            }
+                        }
+                        Err(e) => {
+                            debug!("connect ws fallback {} failed: {}", &address, e);
+                        }
+                    }
+                });
+            }
+            Fallback::Response(resp) => {
+                debug!("ws inbound falls back to the static response");
+                let resp = resp.clone();
+                tokio::spawn(async move {
+                    let res = async {
+                        stream.write_all(&resp).await?;
+                        stream.shutdown().await
+                    };
+                    if let Err(e) = res.await {
+                        debug!("send ws fallback response failed: {}", e);
+                    }
+                });
+            }
+        }
+        Ok(InboundTransport::Empty)
     }
 }
 
@@ -61,17 +290,114 @@ impl Handler {
 impl InboundStreamHandler for Handler {
     async fn handle<'a>(
         &'a self,
-        mut sess: Session,
-        stream: AnyStream,
+        sess: Session,
+        mut stream: AnyStream,
     ) -> std::io::Result<AnyInboundTransport> {
         tracing::trace!("handling inbound stream");
-        let s = accept_hdr_async(stream, SimpleCallback::new(&mut sess, &self.path))
-            .map_err(|e| io::Error::other(format!("accept ws failed: {}", e)))
-            .await?;
-        debug!("accepted WS stream");
-        Ok(InboundTransport::Stream(
-            Box::new(super::ws_stream::WebSocketToStream::new(s)),
-            sess,
-        ))
+        let Some(fallback) = &self.fallback else {
+            return self.accept(sess, stream).await;
+        };
+        let head = timeout(
+            Duration::from_secs(REQUEST_HEAD_TIMEOUT),
+            read_request_head(&mut stream),
+        )
+        .await
+        .map_err(|_| io::Error::new(io::ErrorKind::TimedOut, "read ws request timed out"))??;
+        if is_upgrade(&head, &self.path, self.header.as_ref()) {
+            let stream = PrefixedStream::new(stream, BytesMut::from(&head[..]));
+            return self.accept(sess, Box::new(stream)).await;
+        }
+        Self::fall_back(fallback, stream, head)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::io::duplex;
+    use tokio::net::TcpListener;
+
+    use super::*;
+
+    const UPGRADE: &[u8] = b"GET /ws?ed=2048 HTTP/1.1\r\n\
+        Host: example.com\r\n\
+        Upgrade: websocket\r\n\
+        Connection: Upgrade\r\n\
+        X-Key: s3cr3t\r\n\
+        Sec-WebSocket-Version: 13\r\n\
+        Sec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\n\r\n";
+    const PROBE: &[u8] = b"GET / HTTP/1.1\r\nHost: example.com\r\n\r\n";
+
+    #[test]
+    fn test_is_upgrade() {
+        let header = ("x-key".to_string(), "s3cr3t".to_string());
+        assert!(is_upgrade(UPGRADE, "/ws", None));
+        assert!(is_upgrade(UPGRADE, "/ws", Some(&header)));
+        assert!(!is_upgrade(UPGRADE, "/other", None));
+        assert!(!is_upgrade(PROBE, "/", None));
+        let header = ("x-key".to_string(), "other".to_string());
+        assert!(!is_upgrade(UPGRADE, "/ws", Some(&header)));
+        // An incomplete head.
+        assert!(!is_upgrade(&UPGRADE[..40], "/ws", None));
+    }
+
+    #[tokio::test]
+    async fn test_fallback_response() {
+        let fallback = Fallback::response(404, None).unwrap();
+        let handler = Handler::new("/ws".to_string(), None, Some(fallback));
+        let (mut client, server) = duplex(1024);
+        client.write_all(PROBE).await.unwrap();
+        let transport = handler
+            .handle(Session::default(), Box::new(server))
+            .await
+            .unwrap();
+        assert!(matches!(transport, InboundTransport::Empty));
+        let mut buf = Vec::new();
+        client.read_to_end(&mut buf).await.unwrap();
+        assert_eq!(
+            &buf,
+            b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+        );
+
+        assert!(Fallback::response(42, None).is_err());
+        assert!(Fallback::response(200, Some("/nonexistent/index.html")).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_fallback_address() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let fallback = Fallback::Address(listener.local_addr().unwrap().to_string());
+        let handler = Handler::new("/ws".to_string(), None, Some(fallback));
+        let (mut client, server) = duplex(1024);
+        client.write_all(PROBE).await.unwrap();
+        let transport = handler
+            .handle(Session::default(), Box::new(server))
+            .await
+            .unwrap();
+        assert!(matches!(transport, InboundTransport::Empty));
+
+        let (mut target, _) = listener.accept().await.unwrap();
+        let mut buf = vec![0u8; PROBE.len()];
+        target.read_exact(&mut buf).await.unwrap();
+        assert_eq!(&buf, PROBE);
+        target.write_all(b"HTTP/1.1 200 OK\r\n").await.unwrap();
+        let mut buf = [0u8; 17];
+        client.read_exact(&mut buf).await.unwrap();
+        assert_eq!(&buf, b"HTTP/1.1 200 OK\r\n");
+    }
+
+    #[tokio::test]
+    async fn test_upgrade_with_fallback() {
+        let fallback = Fallback::response(404, None).unwrap();
+        let handler = Handler::new("/ws".to_string(), None, Some(fallback));
+        let (mut client, server) = duplex(1024);
+        client.write_all(UPGRADE).await.unwrap();
+        let transport = handler
+            .handle(Session::default(), Box::new(server))
+            .await
+            .unwrap();
+        assert!(matches!(transport, InboundTransport::Stream(..)));
+        let mut buf = [0u8; 12];
+        client.read_exact(&mut buf).await.unwrap();
+        assert_eq!(&buf, b"HTTP/1.1 101");
     }
 }
@@ -1,19 +1,33 @@
 use std::collections::HashMap;
+use std::future::Future;
 use std::io;
+use std::pin::Pin;
 
 use ::http::{HeaderName, HeaderValue};
 use async_trait::async_trait;
-use futures::TryFutureExt;
-use tokio_tungstenite::client_async_with_config;
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use futures::task::{Context, Poll, Waker};
+use futures::{ready, TryFutureExt};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::task::JoinHandle;
+use tokio_tungstenite::{client_async_with_config, WebSocketStream};
 use tungstenite::client::IntoClientRequest;
+use tungstenite::handshake::client::Request as ClientRequest;
 use tungstenite::protocol::WebSocketConfig;
 use url::Url;
 
 use crate::{proxy::*, session::Session};
 
+use super::ws_stream::WebSocketToStream;
+
+const DEFAULT_EARLY_DATA_HEADER_NAME: &str = "Sec-WebSocket-Protocol";
+
 pub struct Handler {
     pub path: String,
     pub headers: HashMap<String, String>,
+    // Zero disables early data.
+    pub early_data_size: usize,
+    pub early_data_header_name: String,
 }
 
 struct Request<'a> {
@@ -26,22 +40,197 @@ impl<'a> IntoClientRequest for Request<'a> {
         self,
     ) -> tungstenite::error::Result<tungstenite::handshake::client::Request> {
         let mut req = self.uri.into_client_request()?;
-        for (k, v) in self.headers.iter() {
-            if k.to_uppercase() != "HOST" {
-                req.headers_mut()
-                    .insert(HeaderName::try_from(k)?, HeaderValue::from_str(v)?);
-            }
-        }
         if !crate::option::HTTP_USER_AGENT.is_empty() {
             req.headers_mut().insert(
                 ::http::header::USER_AGENT,
                 HeaderValue::from_static(&crate::option::HTTP_USER_AGENT),
             );
         }
+        // The values go as they are, a Host replaces the one derived from
+        // the URL, which is normalized.
+        for (k, v) in self.headers.iter() {
+            req.headers_mut().insert(
+                HeaderName::try_from(k)?,
+                HeaderValue::from_bytes(v.as_bytes())?,
+            );
+        }
         Ok(req)
     }
 }
 
+fn ws_config() -> WebSocketConfig {
+    WebSocketConfig {
+        write_buffer_size: 0,
+        ..Default::default()
+    }
+}
+
+async fn connect(req: ClientRequest, stream: AnyStream) -> io::Result<WebSocketStream<AnyStream>> {
+    let uri = req.uri().to_string();
+    let (socket, _) = client_async_with_config(req, stream, Some(ws_config()))
+        .map_err(|e| io::Error::other(format!("connect ws {} failed: {}", &uri, e)))
+        .await?;
+    Ok(socket)
+}
+
+enum State {
+    // Waits for the first write.
+    Idle(AnyStream, ClientRequest),
+    // The handshake carrying the first bytes of the write, with their number.
+    Connecting(JoinHandle<io::Result<WebSocketStream<AnyStream>>>, usize),
+    Connected(WebSocketToStream<WebSocketStream<AnyStream>>),
+    Failed,
+}
+
+/// Defers the handshake to the first write, up to `max_size` bytes of it
+/// are sent in a header of the upgrade request, so that the server has them
+/// without waiting for a round trip.
+struct EarlyDataStream {
+    state: State,
+    header_name: HeaderName,
+    max_size: usize,
+    // Reads wait for the handshake, which is driven by writes.
+    read_waker: Option<Waker>,
+}
+
+impl EarlyDataStream {
+    fn new(
+        stream: AnyStream,
+        req: ClientRequest,
+        header_name: HeaderName,
+        max_size: usize,
+    ) -> Self {
+        Self {
+            state: State::Idle(stream, req),
+            header_name,
+            max_size,
+            read_waker: None,
+        }
+    }
+
+    fn start(&mut self, data: &[u8]) {
+        let State::Idle(stream, mut req) = std::mem::replace(&mut self.state, State::Failed) else {
+            return;
+        };
+        let n = data.len().min(self.max_size);
+        if n > 0 {
+            // The base64url alphabet is valid in a header value.
+            let value = HeaderValue::from_str(&URL_SAFE_NO_PAD.encode(&data[..n])).unwrap();
+            req.headers_mut().insert(self.header_name.clone(), value);
+        }
+        self.state = State::Connecting(tokio::spawn(connect(req, stream)), n);
+    }
+
+    // Returns the number of bytes sent with the handshake once it's done.
+    fn poll_connect(&mut self, cx: &mut Context) -> Poll<io::Result<usize>> {
+        let State::Connecting(handle, n) = &mut self.state else {
+            return Poll::Ready(Ok(0));
+        };
+        let n = *n;
+        let res = ready!(Pin::new(handle).poll(cx))
+            .map_err(io::Error::other)
+            .and_then(|x| x);
+        let res = match res {
+            Ok(socket) => {
+                self.state = State::Connected(WebSocketToStream::new(socket));
+                Ok(n)
+            }
+            Err(e) => {
+                self.state = State::Failed;
+                Err(e)
+            }
+        };
+        if let Some(waker) = self.read_waker.take() {
+            waker.wake();
+        }
+        Poll::Ready(res)
+    }
+}
+
+impl Drop for EarlyDataStream {
+    fn drop(&mut self) {
+        if let State::Connecting(handle, _) = &self.state {
+            handle.abort();
+        }
+    }
+}
+
+fn broken_pipe() -> io::Error {
+    io::Error::new(io::ErrorKind::BrokenPipe, "ws handshake failed")
+}
+
+impl AsyncRead for EarlyDataStream {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context,
+        buf: &mut ReadBuf,
+    ) -> Poll<io::Result<()>> {
+        let me = self.get_mut();
+        match &mut me.state {
+            State::Connected(s) => Pin::new(s).poll_read(cx, buf),
+            State::Failed => Poll::Ready(Err(broken_pipe())),
+            _ => {
+                me.read_waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+impl AsyncWrite for EarlyDataStream {
+    fn poll_write(self: Pin<&mut Self>, cx: &mut Context, buf: &[u8]) -> Poll<io::Result<usize>> {
+        let me = self.get_mut();
+        loop {
+            match &mut me.state {
+                State::Idle(..) => {
+                    if buf.is_empty() {
+                        return Poll::Ready(Ok(0));
+                    }
+                    me.start(buf);
+                }
+                State::Connecting(..) => {
+                    // The bytes went with the handshake. The rest of the
+                    // buffer is sent in frames, by the next writes.
+                    let n = ready!(me.poll_connect(cx))?;
+                    if n > 0 {
+                        return Poll::Ready(Ok(n));
+                    }
+                }
+                State::Connected(s) => return Pin::new(s).poll_write(cx, buf),
+                State::Failed => return Poll::Ready(Err(broken_pipe())),
+            }
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context) -> Poll<io::Result<()>> {
+        let me = self.get_mut();
+        if let State::Connecting(..) = me.state {
+            ready!(me.poll_connect(cx))?;
+        }
+        match &mut me.state {
+            State::Connected(s) => Pin::new(s).poll_flush(cx),
+            State::Failed => Poll::Ready(Err(broken_pipe())),
+            _ => Poll::Ready(Ok(())),
+        }
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context) -> Poll<io::Result<()>> {
+        let me = self.get_mut();
+        // Nothing was written, the handshake goes without early data so that
+        // reads don't wait forever.
+        if let State::Idle(..) = me.state {
+            me.start(&[]);
+        }
+        if let State::Connecting(..) = me.state {
+            ready!(me.poll_connect(cx))?;
+        }
+        match &mut me.state {
+            State::Connected(s) => Pin::new(s).poll_shutdown(cx),
+            _ => Poll::Ready(Err(broken_pipe())),
+        }
+    }
+}
+
 #[async_trait]
 impl OutboundStreamHandler for Handler {
     fn connect_addr(&self) -> OutboundConnect {
@@ -56,28 +245,200 @@ impl OutboundStreamHandler for Handler {
     ) -> io::Result<AnyStream> {
         tracing::trace!("handling outbound stream");
         if let Some(stream) = stream {
-            let host = if let Some(host) = self.headers.get("Host") {
-                host.to_owned()
-            } else {
-                sess.destination.host()
-            };
-            let mut url = Url::parse(&format!("ws://{}", host)).unwrap();
-            url = url.join(self.path.as_str()).unwrap();
+            let host = self
+                .headers
+                .iter()
+                .find(|(k, _)| k.eq_ignore_ascii_case("host"))
+                .map(|(_, v)| v.to_owned())
+                .unwrap_or_else(|| sess.destination.host());
+            let url = Url::parse(&format!("ws://{}", host))
+                .and_then(|url| url.join(self.path.as_str()))
+                .map_err(|e| io::Error::other(format!("invalid ws url: {}", e)))?;
             let req = Request {
                 uri: url.as_ref(),
                 headers: &self.headers,
             };
-            let ws_config = WebSocketConfig {
-                write_buffer_size: 0,
-                ..Default::default()
-            };
-            let (socket, _) = client_async_with_config(req, stream, Some(ws_config))
+            if self.early_data_size > 0 {
+                let req = req
+                    .into_client_request()
+                    .map_err(|e| io::Error::other(format!("invalid ws request: {}", e)))?;
+                let header_name = if self.early_data_header_name.is_empty() {
+                    DEFAULT_EARLY_DATA_HEADER_NAME
+                } else {
+                    &self.early_data_header_name
+                };
+                let header_name = HeaderName::try_from(header_name)
+                    .map_err(|e| io::Error::other(format!("invalid early data header: {}", e)))?;
+                return Ok(Box::new(EarlyDataStream::new(
+                    stream,
+                    req,
+                    header_name,
+                    self.early_data_size,
+                )));
+            }
+            let (socket, _) = client_async_with_config(req, stream, Some(ws_config()))
                 .map_err(|e| io::Error::other(format!("connect ws {} failed: {}", &url, e)))
                 .await?;
-            let ws_stream = super::ws_stream::WebSocketToStream::new(socket);
+            let ws_stream = WebSocketToStream::new(socket);
             Ok(Box::new(ws_stream))
         } else {
             Err(io::Error::other("invalid input"))
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use ::http::header::SEC_WEBSOCKET_PROTOCOL;
+    use futures::{SinkExt, StreamExt};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tungstenite::handshake::server::{Request as ServerRequest, Response as ServerResponse};
+    use tungstenite::Message;
+
+    use super::*;
+
+    fn handler() -> Handler {
+        Handler {
+            path: "/ws".to_string(),
+            headers: HashMap::new(),
+            early_data_size: 8,
+            early_data_header_name: "".to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_headers() {
+        let (client_io, server_io) = tokio::io::duplex(64 * 1024);
+        let handler = Handler {
+            path: "/ws".to_string(),
+            headers: [
+                ("host".to_string(), "CDN.Example.com:8080".to_string()),
+                ("X-Key".to_string(), "s3cr3t =".to_string()),
+            ]
+            .into(),
+            early_data_size: 0,
+            early_data_header_name: "".to_string(),
+        };
+        let client = async move {
+            handler
+                .handle(&Session::default(), None, Some(Box::new(client_io)))
+                .await
+                .unwrap();
+        };
+        let server = async move {
+            let mut headers = None;
+            tokio_tungstenite::accept_hdr_async(
+                server_io,
+                |req: &ServerRequest, resp: ServerResponse| {
+                    headers = Some(req.headers().clone());
+                    Ok(resp)
+                },
+            )
+            .await
+            .unwrap();
+            let headers = headers.unwrap();
+            assert_eq!(headers.get_all("host").iter().count(), 1);
+            assert_eq!(headers.get("host").unwrap(), "CDN.Example.com:8080");
+            assert_eq!(headers.get("x-key").unwrap(), "s3cr3t =");
+        };
+        tokio::join!(client, server);
+    }
+
+    #[tokio::test]
+    async fn test_early_data() {
+        let (client_io, server_io) = tokio::io::duplex(64 * 1024);
+        let client = async move {
+            let mut stream = handler()
+                .handle(&Session::default(), None, Some(Box::new(client_io)))
+                .await
+                .unwrap();
+            stream.write_all(b"early and late").await.unwrap();
+            let mut buf = [0u8; 5];
+            stream.read_exact(&mut buf).await.unwrap();
+            assert_eq!(&buf, b"hello");
+        };
+        let server = async move {
+            let mut early_data = Vec::new();
+            let mut socket = tokio_tungstenite::accept_hdr_async(
+                server_io,
+                |req: &ServerRequest, mut resp: ServerResponse| {
+                    let protocol = req.headers().get(SEC_WEBSOCKET_PROTOCOL).unwrap().clone();
+                    early_data = URL_SAFE_NO_PAD.decode(protocol.as_bytes()).unwrap();
+                    resp.headers_mut().insert(SEC_WEBSOCKET_PROTOCOL, protocol);
+                    Ok(resp)
+                },
+            )
+            .await
+            .unwrap();
+            assert_eq!(early_data, b"early an");
+            let msg = socket.next().await.unwrap().unwrap();
+            assert_eq!(msg.into_data(), b"d late");
+            socket
+                .send(Message::Binary(b"hello".to_vec()))
+                .await
+                .unwrap();
+        };
+        tokio::join!(client, server);
+    }
+
+    #[cfg(feature = "inbound-ws")]
+    #[tokio::test]
+    async fn test_inbound_header() {
+        for (value, accepted) in [("s3cr3t", true), ("S3CR3T", false), ("", false)] {
+            let (client_io, server_io) = tokio::io::duplex(64 * 1024);
+            let mut headers = HashMap::new();
+            if !value.is_empty() {
+                headers.insert("X-Key".to_string(), value.to_string());
+            }
+            let handler = Handler {
+                path: "/ws".to_string(),
+                headers,
+                early_data_size: 0,
+                early_data_header_name: "".to_string(),
+            };
+            let sess = Session::default();
+            let client = handler.handle(&sess, None, Some(Box::new(client_io)));
+            let inbound = crate::proxy::ws::inbound::StreamHandler::new(
+                "/ws".to_string(),
+                Some(("x-key".to_string(), "s3cr3t".to_string())),
+                None,
+            );
+            let server = inbound.handle(Session::default(), Box::new(server_io));
+            let (client, server) = tokio::join!(client, server);
+            assert_eq!(client.is_ok(), accepted);
+            assert_eq!(server.is_ok(), accepted);
+        }
+    }
+
+    #[cfg(feature = "inbound-ws")]
+    #[tokio::test]
+    async fn test_early_data_inbound() {
+        let (client_io, server_io) = tokio::io::duplex(64 * 1024);
+        let client = async move {
+            let mut stream = handler()
+                .handle(&Session::default(), None, Some(Box::new(client_io)))
+                .await
+                .unwrap();
+            stream.write_all(b"early and late").await.unwrap();
+            let mut buf = [0u8; 5];
+            stream.read_exact(&mut buf).await.unwrap();
+            assert_eq!(&buf, b"hello");
+        };
+        let server = async move {
+            let inbound =
+                crate::proxy::ws::inbound::StreamHandler::new("/ws".to_string(), None, None);
+            let transport = inbound
+                .handle(Session::default(), Box::new(server_io))
+                .await
+                .unwrap();
+            let InboundTransport::Stream(mut stream, _) = transport else {
+                panic!("not a stream transport");
+            };
+            let mut buf = [0u8; 14];
+            stream.read_exact(&mut buf).await.unwrap();
+            assert_eq!(&buf, b"early and late");
+            stream.write_all(b"hello").await.unwrap();
+        };
+        tokio::join!(client, server);
+    }
+}
@@ -26,6 +26,15 @@ impl<S> WebSocketToStream<S> {
             inner: stream,
         }
     }
+
+    /// Reads return `early_data` before the data of the stream.
+    #[cfg(feature = "inbound-ws")]
+    pub fn with_early_data(stream: S, early_data: &[u8]) -> Self {
+        WebSocketToStream {
+            buf: BytesMut::from(early_data),
+            inner: stream,
+        }
+    }
 }
 
 fn broken_pipe() -> io::Error {
@@ -44,6 +44,10 @@ pub struct DatagramSource {
     pub address: SocketAddr,
     pub stream_id: Option<StreamId>,
     pub process_name: Option<String>,
+    pub user: Option<String>,
+    // The destination of a transparently proxied datagram, sessions are kept
+    // per destination and replies are sent from it.
+    pub original_dst: Option<SocketAddr>,
 }
 
 impl DatagramSource {
@@ -52,6 +56,8 @@ impl DatagramSource {
             address,
             stream_id,
             process_name: None,
+            user: None,
+            original_dst: None,
         }
     }
 
@@ -64,6 +70,8 @@ impl DatagramSource {
             address,
             stream_id,
             process_name,
+            user: None,
+            original_dst: None,
         }
     }
 }
@@ -97,8 +105,19 @@ pub struct Session {
     pub stream_id: Option<StreamId>,
     /// Optional source address which is forwarded via HTTP reverse proxy.
     pub forwarded_source: Option<IpAddr>,
-    /// Optional process name that initiated this connection.
+    /// Optional process name that initiated this connection, the path of
+    /// its executable where it's known.
     pub process_name: Option<String>,
+    /// The identity (CN or SAN) of the client certificate if the inbound
+    /// requires client authentication.
+    pub peer_identity: Option<String>,
+    /// The application protocol negotiated via ALPN by the inbound transport.
+    pub alpn: Option<String>,
+    /// The server name the client sent to a TLS inbound, or its configured
+    /// default if there was none.
+    pub sni: Option<String>,
+    /// The user authenticated by the inbound, if it requires authentication.
+    pub user: Option<String>,
     /// Instructs a multiplexed transport should creates a new underlying
     /// connection for this session, and it will be used only once.
     pub new_conn_once: bool,
@@ -108,10 +127,15 @@ pub struct Session {
     pub http_sniffed_domain: Option<String>,
     /// The sniffed domain name if the destination is an IP address.
     pub dns_sniffed_domain: Option<String>,
+    /// The protocol told by the first bytes of the session, if sniffed.
+    pub sniffed_protocol: Option<&'static str>,
     /// Shared state to coordinate XTLS vision read raw mode.
     pub vision_read_raw: std::sync::Arc<std::sync::atomic::AtomicBool>,
     /// Skip domain resolution during routing.
     pub skip_resolve: bool,
+    /// The tag of the actor a group outbound handed the session to, if the
+    /// group picks it while handling the session.
+    pub group_actor: std::sync::Arc<std::sync::Mutex<Option<String>>>,
 }
 
 impl Clone for Session {
@@ -127,12 +151,18 @@ impl Clone for Session {
             stream_id: self.stream_id,
             forwarded_source: self.forwarded_source,
             process_name: self.process_name.clone(),
+            peer_identity: self.peer_identity.clone(),
+            alpn: self.alpn.clone(),
+            sni: self.sni.clone(),
+            user: self.user.clone(),
             new_conn_once: self.new_conn_once,
             tls_sniffed_domain: self.tls_sniffed_domain.clone(),
             http_sniffed_domain: self.http_sniffed_domain.clone(),
             dns_sniffed_domain: self.dns_sniffed_domain.clone(),
+            sniffed_protocol: self.sniffed_protocol,
             vision_read_raw: self.vision_read_raw.clone(),
             skip_resolve: self.skip_resolve,
+            group_actor: self.group_actor.clone(),
         }
     }
 }
@@ -150,12 +180,18 @@ impl Default for Session {
             stream_id: None,
             forwarded_source: None,
             process_name: None,
+            peer_identity: None,
+            alpn: None,
+            sni: None,
+            user: None,
             new_conn_once: false,
             tls_sniffed_domain: None,
             http_sniffed_domain: None,
             dns_sniffed_domain: None,
+            sniffed_protocol: None,
             vision_read_raw: std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false)),
             skip_resolve: false,
+            group_actor: Default::default(),
         }
     }
 }
@@ -132,6 +132,8 @@ fn new_socks_outbound(
         outbounds: Some(outbounds),
         router: None,
         dns: None,
+        metrics: None,
+        api: None,
     };
     let config = leaf::config::json::to_internal(config).map_err(|e| anyhow::anyhow!(e))?;
     let dns_client = Arc::new(RwLock::new(
@@ -0,0 +1,95 @@
+mod common;
+
+// The error replies of the outbound delay API. A bare host and port is tested
+// with a TLS handshake, which a server never replying doesn't complete.
+#[cfg(all(
+    feature = "api",
+    feature = "outbound-failover",
+    feature = "outbound-direct"
+))]
+#[test]
+fn test_api_delay_errors() -> anyhow::Result<()> {
+    use std::time::Duration;
+
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::{TcpListener, TcpStream};
+
+    // Sends a GET request to the API, returns the status code and the body.
+    async fn get(api_port: u16, path: &str) -> anyhow::Result<(u16, String)> {
+        let mut stream = TcpStream::connect(("127.0.0.1", api_port)).await?;
+        stream
+            .write_all(
+                format!(
+                    "GET {} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n",
+                    path
+                )
+                .as_bytes(),
+            )
+            .await?;
+        let mut resp = String::new();
+        stream.read_to_string(&mut resp).await?;
+        let (head, body) = resp
+            .split_once("\r\n\r\n")
+            .ok_or_else(|| anyhow::anyhow!("invalid response {}", resp))?;
+        let status = head
+            .split(' ')
+            .nth(1)
+            .and_then(|x| x.parse().ok())
+            .ok_or_else(|| anyhow::anyhow!("invalid status line {}", head))?;
+        Ok((status, body.to_string()))
+    }
+
+    let config = r#"
+    {
+        "outbounds": [
+            {
+                "protocol": "direct",
+                "tag": "direct"
+            }
+        ],
+        "api": {
+            "address": "127.0.0.1",
+            "port": 3101
+        }
+    }
+    "#;
+
+    let rt = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()?;
+    let leaf_rt_ids = common::run_leaf_instances(&rt, vec![config.to_string()])?;
+    let res = rt.block_on(async {
+        let silent = TcpListener::bind("127.0.0.1:0").await?;
+        let silent_port = silent.local_addr()?.port();
+        tokio::spawn(async move {
+            let mut conns = Vec::new();
+            while let Ok((conn, _)) = silent.accept().await {
+                conns.push(conn);
+            }
+        });
+        tokio::time::sleep(Duration::from_millis(200)).await;
+
+        let (status, body) = get(3101, "/api/v1/runtime/outbound/nope/delay").await?;
+        assert_eq!(status, 404);
+        assert!(body.contains(r#""tag":"nope""#), "{}", body);
+        assert!(body.contains(r#""error":"not_found""#), "{}", body);
+
+        let (status, body) = get(3101, "/api/v1/runtime/outbound/direct/delay?url=ftp://x").await?;
+        assert_eq!(status, 400);
+        assert!(body.contains(r#""error":"invalid_url""#), "{}", body);
+
+        let path = format!(
+            "/api/v1/runtime/outbound/direct/delay?url=127.0.0.1:{}&timeout=300",
+            silent_port
+        );
+        let (status, body) = get(3101, &path).await?;
+        assert_eq!(status, 504);
+        assert!(body.contains(r#""error":"timeout""#), "{}", body);
+        assert!(body.contains("no reply in 300 ms"), "{}", body);
+        Ok::<(), anyhow::Error>(())
+    });
+    for id in leaf_rt_ids.into_iter() {
+        assert!(leaf::shutdown(id));
+    }
+    res
+}
@@ -0,0 +1,151 @@
+mod common;
+
+// app(socks) -> (socks)client(chain(shadowsocks+chain(shadowsocks+trojan))) -> (shadowsocks)server1(direct) -> (shadowsocks)server2(direct) -> (trojan)server3(direct) -> echo
+#[cfg(all(
+    feature = "outbound-socks",
+    feature = "inbound-socks",
+    feature = "outbound-shadowsocks",
+    feature = "inbound-shadowsocks",
+    feature = "outbound-trojan",
+    feature = "inbound-trojan",
+    feature = "outbound-direct",
+    feature = "outbound-chain",
+))]
+#[test]
+fn test_out_chain_11() -> anyhow::Result<()> {
+    let config1 = r#"
+    {
+        "inbounds": [
+            {
+                "protocol": "socks",
+                "address": "127.0.0.1",
+                "port": 1086
+            }
+        ],
+        "outbounds": [
+            {
+                "protocol": "chain",
+                "settings": {
+                    "actors": [
+                        "server1",
+                        "server2-server3"
+                    ]
+                }
+            },
+            {
+                "protocol": "chain",
+                "tag": "server2-server3",
+                "settings": {
+                    "actors": [
+                        "server2",
+                        "server3"
+                    ]
+                }
+            },
+            {
+                "protocol": "shadowsocks",
+                "tag": "server1",
+                "settings": {
+                    "address": "127.0.0.1",
+                    "port": 3001,
+                    "method": "chacha20-ietf-poly1305",
+                    "password": "password"
+                }
+            },
+            {
+                "protocol": "shadowsocks",
+                "tag": "server2",
+                "settings": {
+                    "address": "127.0.0.1",
+                    "port": 3002,
+                    "method": "aes-128-gcm",
+                    "password": "password"
+                }
+            },
+            {
+                "protocol": "trojan",
+                "tag": "server3",
+                "settings": {
+                    "address": "127.0.0.1",
+                    "port": 3003,
+                    "password": "password"
+                }
+            }
+        ]
+    }
+    "#;
+
+    let config2 = r#"
+    {
+        "inbounds": [
+            {
+                "protocol": "shadowsocks",
+                "address": "127.0.0.1",
+                "port": 3001,
+                "settings": {
+                    "method": "chacha20-ietf-poly1305",
+                    "password": "password"
+                }
+            }
+        ],
+        "outbounds": [
+            {
+                "protocol": "direct"
+            }
+        ]
+    }
+    "#;
+
+    let config3 = r#"
+    {
+        "inbounds": [
+            {
+                "protocol": "shadowsocks",
+                "address": "127.0.0.1",
+                "port": 3002,
+                "settings": {
+                    "method": "aes-128-gcm",
+                    "password": "password"
+                }
+            }
+        ],
+        "outbounds": [
+            {
+                "protocol": "direct"
+            }
+        ]
+    }
+    "#;
+
+    let config4 = r#"
+    {
+        "inbounds": [
+            {
+                "protocol": "trojan",
+                "address": "127.0.0.1",
+                "port": 3003,
+                "settings": {
+                    "passwords": [
+                        "password"
+                    ]
+                }
+            }
+        ],
+        "outbounds": [
+            {
+                "protocol": "direct"
+            }
+        ]
+    }
+    "#;
+
+    // The UDP of trojan needs a stream, so the inner chain must take one
+    // from server1 rather than its UDP.
+    let configs = vec![
+        config1.to_string(),
+        config2.to_string(),
+        config3.to_string(),
+        config4.to_string(),
+    ];
+    common::test_configs(configs, "127.0.0.1", 1086)
+}
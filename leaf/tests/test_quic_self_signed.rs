@@ -0,0 +1,124 @@
+mod common;
+
+// app(socks) -> (socks)client(chain(quic+trojan)) -> (chain(quic+trojan))server(direct) -> echo
+//
+// The server has no certificate configured and uses a persisted self-signed
+// one, the client pins its fingerprint.
+#[cfg(all(
+    feature = "outbound-socks",
+    feature = "inbound-socks",
+    feature = "outbound-quic",
+    feature = "outbound-trojan",
+    feature = "inbound-quic",
+    feature = "inbound-trojan",
+    feature = "outbound-direct",
+    feature = "inbound-chain",
+    feature = "outbound-chain",
+))]
+#[test]
+fn test_quic_self_signed() -> anyhow::Result<()> {
+    let mut path =
+        std::env::current_exe().map_err(|e| anyhow::anyhow!("current exe failed: {}", e))?;
+    path.pop();
+    let _ = std::fs::remove_file(path.join("quic.self-signed.crt"));
+    let _ = std::fs::remove_file(path.join("quic.self-signed.key"));
+    let (cert, _) =
+        leaf::common::cert::self_signed_certificate("localhost", Some("quic.self-signed"))?;
+    let der = {
+        use rustls::pki_types::{pem::PemObject, CertificateDer};
+        CertificateDer::from_pem_slice(cert.as_bytes())
+            .map_err(|e| anyhow::anyhow!("parse cert failed: {}", e))?
+    };
+    let fingerprint = leaf::common::cert::sha256_fingerprint(&der);
+
+    let config1 = format!(
+        r#"
+    {{
+        "inbounds": [
+            {{
+                "protocol": "socks",
+                "address": "127.0.0.1",
+                "port": 1086
+            }}
+        ],
+        "outbounds": [
+            {{
+                "protocol": "chain",
+                "settings": {{
+                    "actors": [
+                        "quic",
+                        "trojan"
+                    ]
+                }}
+            }},
+            {{
+                "protocol": "quic",
+                "tag": "quic",
+                "settings": {{
+                    "address": "127.0.0.1",
+                    "port": 3001,
+                    "serverName": "localhost",
+                    "certificateFingerprint": "{fingerprint}"
+                }}
+            }},
+            {{
+                "protocol": "trojan",
+                "tag": "trojan",
+                "settings": {{
+                    "password": "password"
+                }}
+            }}
+        ]
+    }}
+    "#,
+        fingerprint = fingerprint
+    );
+
+    let config2 = r#"
+    {
+        "inbounds": [
+            {
+                "tag": "quic-in",
+                "protocol": "chain",
+                "address": "127.0.0.1",
+                "port": 3001,
+                "settings": {
+                    "actors": [
+                        "quic",
+                        "trojan"
+                    ]
+                }
+            },
+            {
+                "protocol": "quic",
+                "tag": "quic",
+                "settings": {
+                    "selfSignedSan": "localhost",
+                    "persistSelfSigned": true
+                }
+            },
+            {
+                "protocol": "trojan",
+                "tag": "trojan",
+                "settings": {
+                    "passwords": [
+                        "password"
+                    ]
+                }
+            }
+        ],
+        "outbounds": [
+            {
+                "protocol": "direct"
+            }
+        ]
+    }
+    "#;
+
+    std::env::set_var("TCP_DOWNLINK_TIMEOUT", "3");
+    std::env::set_var("TCP_UPLINK_TIMEOUT", "3");
+
+    let configs = vec![config1, config2.to_string()];
+    common::test_configs(configs, "127.0.0.1", 1086)?;
+    Ok(())
+}
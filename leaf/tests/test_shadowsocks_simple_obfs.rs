@@ -0,0 +1,102 @@
+#![cfg(all(
+    feature = "test-simple-obfs",
+    feature = "outbound-socks",
+    feature = "inbound-socks",
+    feature = "outbound-shadowsocks",
+    feature = "outbound-obfs",
+    feature = "outbound-chain",
+))]
+
+mod common;
+
+use std::process::{Child, Command, Stdio};
+
+// Kills the ss-server when the test ends, its plugin goes with it.
+struct SsServer(Child);
+
+impl Drop for SsServer {
+    fn drop(&mut self) {
+        let _ = self.0.kill();
+        let _ = self.0.wait();
+    }
+}
+
+// Starts a shadowsocks-libev server behind an obfs-server plugin, the
+// binaries are taken from SS_SERVER_BIN and OBFS_SERVER_BIN or looked up in
+// PATH.
+fn run_ss_server(port: u16, mode: &str) -> anyhow::Result<SsServer> {
+    let ss_server = std::env::var("SS_SERVER_BIN").unwrap_or_else(|_| "ss-server".to_string());
+    let obfs_server =
+        std::env::var("OBFS_SERVER_BIN").unwrap_or_else(|_| "obfs-server".to_string());
+    let child = Command::new(&ss_server)
+        .args(["-s", "127.0.0.1", "-p", &port.to_string()])
+        .args(["-k", "password", "-m", "chacha20-ietf-poly1305"])
+        .args(["--plugin", &obfs_server])
+        .args(["--plugin-opts", &format!("obfs={}", mode)])
+        .stdout(Stdio::null())
+        .spawn()
+        .map_err(|e| anyhow::anyhow!("start {} failed: {}", ss_server, e))?;
+    std::thread::sleep(std::time::Duration::from_millis(500));
+    Ok(SsServer(child))
+}
+
+fn client_config(socks_port: u16, server_port: u16, mode: &str) -> String {
+    format!(
+        r#"
+    {{
+        "inbounds": [
+            {{
+                "protocol": "socks",
+                "address": "127.0.0.1",
+                "port": {}
+            }}
+        ],
+        "outbounds": [
+            {{
+                "protocol": "chain",
+                "settings": {{
+                    "actors": ["obfs", "shadowsocks"]
+                }}
+            }},
+            {{
+                "protocol": "obfs",
+                "tag": "obfs",
+                "settings": {{
+                    "mode": "{}",
+                    "host": "www.bing.com"
+                }}
+            }},
+            {{
+                "protocol": "shadowsocks",
+                "tag": "shadowsocks",
+                "settings": {{
+                    "address": "127.0.0.1",
+                    "port": {},
+                    "method": "chacha20-ietf-poly1305",
+                    "password": "password"
+                }}
+            }}
+        ]
+    }}
+    "#,
+        socks_port, mode, server_port
+    )
+}
+
+// app(socks) -> (socks)client(chain(obfs, shadowsocks)) -> obfs-server -> ss-server -> echo
+//
+// Only TCP, simple-obfs leaves UDP alone. Bulk data in both directions
+// runs through many obfs frames.
+#[test]
+fn test_shadowsocks_simple_obfs_http() -> anyhow::Result<()> {
+    let _ss_server = run_ss_server(3201, "http")?;
+    let configs = vec![client_config(1196, 3201, "http")];
+    common::test_data_transfering_reliability_on_configs(configs, "127.0.0.1", 1196)
+}
+
+#[test]
+fn test_shadowsocks_simple_obfs_tls() -> anyhow::Result<()> {
+    let _ss_server = run_ss_server(3202, "tls")?;
+    let configs = vec![client_config(1197, 3202, "tls")];
+    common::test_data_transfering_reliability_on_configs(configs, "127.0.0.1", 1197)
+}
@@ -0,0 +1,61 @@
+mod common;
+
+// app(socks) -> (socks)client(vless) -> (vless)server(direct) -> echo
+#[cfg(all(
+    feature = "outbound-socks",
+    feature = "inbound-socks",
+    feature = "outbound-vless",
+    feature = "inbound-vless",
+    feature = "outbound-direct",
+))]
+#[test]
+fn test_vless() -> anyhow::Result<()> {
+    let config1 = r#"
+    {
+        "inbounds": [
+            {
+                "protocol": "socks",
+                "address": "127.0.0.1",
+                "port": 1086
+            }
+        ],
+        "outbounds": [
+            {
+                "protocol": "vless",
+                "settings": {
+                    "address": "127.0.0.1",
+                    "port": 3001,
+                    "uuid": "b831381d-6324-4d53-ad4f-8cda48b30811",
+                    "flow": "none"
+                }
+            }
+        ]
+    }
+    "#;
+
+    let config2 = r#"
+    {
+        "inbounds": [
+            {
+                "protocol": "vless",
+                "address": "127.0.0.1",
+                "port": 3001,
+                "settings": {
+                    "uuids": [
+                        "0a7f1ec5-5f87-4b5e-9a6f-2a3c0f4d1e11",
+                        "b831381d-6324-4d53-ad4f-8cda48b30811"
+                    ]
+                }
+            }
+        ],
+        "outbounds": [
+            {
+                "protocol": "direct"
+            }
+        ]
+    }
+    "#;
+
+    let configs = vec![config1.to_string(), config2.to_string()];
+    common::test_configs(configs, "127.0.0.1", 1086)
+}
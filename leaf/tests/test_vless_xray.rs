@@ -0,0 +1,92 @@
+#![cfg(all(
+    feature = "test-xray",
+    feature = "outbound-socks",
+    feature = "inbound-socks",
+    feature = "outbound-vless",
+))]
+
+mod common;
+
+use std::io::Write;
+use std::process::{Child, Command, Stdio};
+
+// Kills the xray server when the test ends.
+struct XrayServer(Child);
+
+impl Drop for XrayServer {
+    fn drop(&mut self) {
+        let _ = self.0.kill();
+        let _ = self.0.wait();
+    }
+}
+
+// Starts an xray-core VLESS server, the binary is taken from XRAY_BIN or
+// looked up in PATH.
+fn run_xray_server(port: u16, uuid: &str) -> anyhow::Result<XrayServer> {
+    let config = format!(
+        r#"
+    {{
+        "log": {{ "loglevel": "warning" }},
+        "inbounds": [
+            {{
+                "listen": "127.0.0.1",
+                "port": {},
+                "protocol": "vless",
+                "settings": {{
+                    "clients": [{{ "id": "{}" }}],
+                    "decryption": "none"
+                }}
+            }}
+        ],
+        "outbounds": [{{ "protocol": "freedom" }}]
+    }}
+    "#,
+        port, uuid
+    );
+    let path = std::env::temp_dir().join(format!("leaf-test-xray-{}.json", port));
+    std::fs::File::create(&path)?.write_all(config.as_bytes())?;
+    let xray = std::env::var("XRAY_BIN").unwrap_or_else(|_| "xray".to_string());
+    let child = Command::new(&xray)
+        .arg("run")
+        .arg("-c")
+        .arg(&path)
+        .stdout(Stdio::null())
+        .spawn()
+        .map_err(|e| anyhow::anyhow!("start {} failed: {}", xray, e))?;
+    std::thread::sleep(std::time::Duration::from_millis(500));
+    Ok(XrayServer(child))
+}
+
+// app(socks) -> (socks)client(vless) -> (vless)xray(freedom) -> echo
+#[test]
+fn test_vless_xray() -> anyhow::Result<()> {
+    let uuid = "b831381d-6324-4d53-ad4f-8cda48b30811";
+    let _xray = run_xray_server(3101, uuid)?;
+
+    let config = format!(
+        r#"
+    {{
+        "inbounds": [
+            {{
+                "protocol": "socks",
+                "address": "127.0.0.1",
+                "port": 1186
+            }}
+        ],
+        "outbounds": [
+            {{
+                "protocol": "vless",
+                "settings": {{
+                    "address": "127.0.0.1",
+                    "port": 3101,
+                    "uuid": "{}",
+                    "flow": "none"
+                }}
+            }}
+        ]
+    }}
+    "#,
+        uuid
+    );
+    common::test_configs(vec![config], "127.0.0.1", 1186)
+}